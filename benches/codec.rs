@@ -0,0 +1,108 @@
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use elfo::{message, AnyMessage, Message};
+
+#[message]
+struct Small(u64);
+
+#[message]
+struct Order {
+    id: u64,
+    account: String,
+    price: f64,
+    quantity: u32,
+    tags: Vec<String>,
+}
+
+#[message]
+struct Snapshot {
+    levels: Vec<(f64, f64)>,
+    meta: HashMap<String, String>,
+}
+
+fn messages() -> Vec<(&'static str, AnyMessage)> {
+    let order = Order {
+        id: 42,
+        account: "trader-007".into(),
+        price: 1234.5,
+        quantity: 10,
+        tags: vec!["gtc".into(), "post-only".into()],
+    };
+
+    let snapshot = Snapshot {
+        levels: (0..100).map(|i| (i as f64, 1. / (i + 1) as f64)).collect(),
+        meta: (0..10)
+            .map(|i| (format!("key{i}"), format!("value{i}")))
+            .collect(),
+    };
+
+    vec![
+        ("small", AnyMessage::new(Small(42))),
+        ("order", AnyMessage::new(order)),
+        ("snapshot", AnyMessage::new(snapshot)),
+    ]
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+
+    for (name, message) in messages() {
+        let mut buffer = Vec::with_capacity(64 * 1024);
+
+        buffer.clear();
+        message.write_msgpack(&mut buffer, usize::MAX).unwrap();
+        group.throughput(Throughput::Bytes(buffer.len() as u64));
+        group.bench_with_input(BenchmarkId::new("msgpack", name), &message, |b, message| {
+            b.iter(|| {
+                buffer.clear();
+                message.write_msgpack(&mut buffer, usize::MAX).unwrap();
+            })
+        });
+
+        buffer.clear();
+        message.write_postcard(&mut buffer, usize::MAX).unwrap();
+        group.throughput(Throughput::Bytes(buffer.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("postcard", name),
+            &message,
+            |b, message| {
+                b.iter(|| {
+                    buffer.clear();
+                    message.write_postcard(&mut buffer, usize::MAX).unwrap();
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+
+    for (name, message) in messages() {
+        let (protocol, name_) = (message.protocol(), message.name());
+
+        let mut msgpack = Vec::new();
+        message.write_msgpack(&mut msgpack, usize::MAX).unwrap();
+        group.throughput(Throughput::Bytes(msgpack.len() as u64));
+        group.bench_with_input(BenchmarkId::new("msgpack", name), &msgpack, |b, bytes| {
+            b.iter(|| AnyMessage::read_msgpack(black_box(bytes), protocol, name_).unwrap())
+        });
+
+        let id = message.network_id();
+        let mut postcard = Vec::new();
+        message.write_postcard(&mut postcard, usize::MAX).unwrap();
+        group.throughput(Throughput::Bytes(postcard.len() as u64));
+        group.bench_with_input(BenchmarkId::new("postcard", name), &postcard, |b, bytes| {
+            b.iter(|| AnyMessage::read_postcard(black_box(bytes), id).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(cases, encode, decode);
+criterion_main!(cases);
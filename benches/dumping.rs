@@ -0,0 +1,80 @@
+//! Measures the cost of producing dumps on the send path.
+//!
+//! Run it twice to compare the default build with the one where dumping is
+//! compiled out:
+//! ```sh
+//! cargo bench --bench dumping
+//! cargo bench --bench dumping --no-default-features
+//! ```
+//! The difference between `send_recv` and `send_recv_no_dumping` is the cost
+//! of the producer side of dumping without any installed dumper. `baseline`
+//! is the same loop over a bare tokio channel for reference.
+
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use elfo::{prelude::*, Context};
+
+mod common;
+
+#[message]
+#[derive(Clone)]
+struct Sample {
+    value: u64,
+    payload: [u64; 4],
+}
+
+const SAMPLE: Sample = Sample {
+    value: 42,
+    payload: [1, 2, 3, 4],
+};
+
+fn baseline(c: &mut Criterion) {
+    async fn testee(iter_count: u64) -> Duration {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+
+        let start = Instant::now();
+        for _ in 0..iter_count {
+            tx.try_send(black_box(SAMPLE)).unwrap();
+            black_box(rx.recv().await.unwrap());
+        }
+        start.elapsed()
+    }
+
+    c.bench_function("baseline", |b| {
+        b.iter_custom(|iter_count| {
+            common::make_st_runtime().block_on(async {
+                tokio::task::spawn(async move { testee(iter_count).await })
+                    .await
+                    .unwrap()
+            })
+        })
+    });
+}
+
+async fn testee(mut ctx: Context, iter_count: u64) -> Duration {
+    let addr = ctx.addr();
+
+    let start = Instant::now();
+    for _ in 0..iter_count {
+        ctx.try_send_to(addr, black_box(SAMPLE)).unwrap();
+        black_box(ctx.try_recv().await.unwrap());
+    }
+    start.elapsed()
+}
+
+fn send_recv(c: &mut Criterion) {
+    let name = if elfo::dumping::ENABLED {
+        "send_recv"
+    } else {
+        "send_recv_no_dumping"
+    };
+
+    c.bench_function(name, |b| {
+        b.iter_custom(|iter_count| common::bench_singleton(iter_count, testee))
+    });
+}
+
+criterion_group!(cases, baseline, send_recv);
+criterion_main!(cases);
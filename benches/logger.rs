@@ -0,0 +1,73 @@
+//! Measures the cost of logging on the producer side, i.e. in actors.
+//!
+//! Events are formatted and written (to `/dev/null`) by the logger running on
+//! other threads, so only capturing and sending are measured. `dropped` is
+//! the cost of an event rejected by the full channel before the logger is
+//! started.
+
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{config::AnyConfig, Topology};
+
+mod common;
+
+const CAPACITY: u32 = 128 * 1024;
+
+fn logging(c: &mut Criterion) {
+    let logger = elfo::batteries::logger::init();
+
+    // Fill the channel, the logger isn't started yet.
+    for _ in 0..CAPACITY {
+        tracing::info!("filling");
+    }
+
+    c.bench_function("dropped", |b| {
+        b.iter(|| tracing::info!(order_id = black_box(42), "dropped"))
+    });
+
+    let config = AnyConfig::deserialize(toml! {
+        [system.loggers]
+        sink = "File"
+        path = "/dev/null"
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    loggers.mount(logger);
+
+    let rt = common::make_mt_runtime(2);
+    rt.spawn(elfo::init::start(topology));
+    // Wait for the logger to drain the channel.
+    std::thread::sleep(Duration::from_secs(1));
+
+    let mut group = c.benchmark_group("accepted");
+
+    group.bench_function("message", |b| b.iter(|| tracing::info!("message")));
+    group.bench_function("fields", |b| {
+        b.iter(|| {
+            tracing::info!(
+                order_id = black_box(42),
+                side = "buy",
+                filled = true,
+                "order is placed"
+            )
+        })
+    });
+    group.bench_function("debug", |b| {
+        let prices = [1.5, 2.25, 3.125];
+        b.iter(|| tracing::info!(prices = ?black_box(&prices), "order is placed"))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, logging);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use elfo::{message, msg, Context};
+
+mod common;
+
+#[message]
+struct Step;
+
+async fn via_mailbox(mut ctx: Context, iter_count: u64) -> Duration {
+    let addr = ctx.addr();
+    let start = Instant::now();
+    for _ in 0..iter_count {
+        ctx.try_send_to(addr, Step).unwrap();
+        msg!(match ctx.recv().await.unwrap() {
+            Step => {}
+            _ => unreachable!(),
+        });
+    }
+    start.elapsed()
+}
+
+async fn via_self_queue(mut ctx: Context, iter_count: u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iter_count {
+        ctx.send_to_self(Step).unwrap();
+        msg!(match ctx.recv().await.unwrap() {
+            Step => {}
+            _ => unreachable!(),
+        });
+    }
+    start.elapsed()
+}
+
+fn mailbox(c: &mut Criterion) {
+    c.bench_function("mailbox", |b| {
+        b.iter_custom(|iter_count| common::bench_singleton(iter_count, via_mailbox))
+    });
+}
+
+fn self_queue(c: &mut Criterion) {
+    c.bench_function("self_queue", |b| {
+        b.iter_custom(|iter_count| common::bench_singleton(iter_count, via_self_queue))
+    });
+}
+
+criterion_group!(cases, mailbox, self_queue);
+criterion_main!(cases);
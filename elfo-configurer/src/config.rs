@@ -0,0 +1,64 @@
+//! Configuration for the configurer.
+//!
+//! Note: all types here are exported only for documentation purposes
+//! and are not subject to stable guarantees. However, the config
+//! structure (usually encoded in TOML) follows stable guarantees.
+
+use std::time::Duration as StdDuration;
+
+use serde::Deserialize;
+
+use elfo_core::config::Duration;
+
+/// Configurer configuration, the section of the configurer's group.
+///
+/// Unlike other groups, it's read on every reload before sending configs,
+/// so it takes effect at startup and by the same reload.
+///
+/// # Example
+/// ```toml
+/// [system.configurers]
+/// sync_updates = true
+/// update_order = ["storage", "gateways"]
+/// update_timeout = "30s"
+/// ```
+///
+/// It's exported only for documentation purposes and cannot be created or
+/// received outside the configurer.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Updates groups one wave after another: a group gets `UpdateConfig`
+    /// (and, thus, `ConfigUpdated`) only after all groups it routes to by
+    /// [`Local::route_to()`] have applied their configs, so it can rely on
+    /// their new configs. Groups in a cycle are updated together.
+    ///
+    /// Otherwise, configs are sent to all groups at once. `false` by default.
+    ///
+    /// [`Local::route_to()`]: elfo_core::topology::Local::route_to
+    #[serde(default)]
+    pub sync_updates: bool,
+    /// Replaces the topology order used by `sync_updates`: listed groups are
+    /// updated one by one in this order, the rest are updated together after
+    /// them. Empty by default.
+    #[serde(default)]
+    pub update_order: Vec<String>,
+    /// How long to wait for a group to apply its config in `sync_updates`.
+    /// On timeout or failure, the error is logged and dependent groups are
+    /// updated anyway. `30s` by default.
+    #[serde(default = "default_update_timeout")]
+    pub update_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sync_updates: false,
+            update_order: Vec::new(),
+            update_timeout: default_update_timeout(),
+        }
+    }
+}
+
+fn default_update_timeout() -> Duration {
+    StdDuration::from_secs(30).into()
+}
@@ -1,5 +1,7 @@
 use serde_value::Value;
 
+use elfo_core::messages::{ConfigOrigin, ConfigProvenance};
+
 pub(crate) fn lookup_value<'a>(mut value: &'a Value, path: &str) -> Option<&'a Value> {
     for part in path.split('.') {
         match value {
@@ -30,6 +32,134 @@ pub(crate) fn add_defaults(config: Option<Value>, default: &Value) -> Value {
     }
 }
 
+/// Returns where leaf values of `add_defaults(config, default)` come from.
+pub(crate) fn provenance(config: Option<&Value>, default: &Value) -> Vec<ConfigProvenance> {
+    let mut provenance = Vec::new();
+    collect_provenance(config, Some(default), &mut String::new(), &mut provenance);
+    provenance
+}
+
+fn collect_provenance(
+    config: Option<&Value>,
+    default: Option<&Value>,
+    path: &mut String,
+    out: &mut Vec<ConfigProvenance>,
+) {
+    use Value::*;
+
+    let push = |out: &mut Vec<_>, path: &str, origin| {
+        out.push(ConfigProvenance::new(path.into(), origin));
+    };
+
+    match (config, default) {
+        (None, None) => {}
+        (Some(Newtype(t)), d) => collect_provenance(Some(t), d, path, out),
+        (c, Some(Newtype(d))) => collect_provenance(c, Some(d), path, out),
+        (Some(Option(t)), d) => collect_provenance(t.as_deref(), d, path, out),
+        (Some(Map(config)), Some(Map(default))) => {
+            let keys = config
+                .keys()
+                .chain(default.keys().filter(|k| !config.contains_key(k)));
+            let mut keys = keys.collect::<Vec<_>>();
+            keys.sort();
+
+            for key in keys {
+                with_key(path, key, |path| {
+                    collect_provenance(config.get(key), default.get(key), path, out)
+                });
+            }
+        }
+        // The group's value wins entirely if at least one of them isn't a table.
+        (Some(Map(config)), _) => {
+            for (key, value) in config {
+                with_key(path, key, |path| {
+                    collect_provenance(Some(value), None, path, out)
+                });
+            }
+        }
+        (None, Some(Map(default))) => {
+            for (key, value) in default {
+                with_key(path, key, |path| {
+                    collect_provenance(None, Some(value), path, out)
+                });
+            }
+        }
+        (Some(_), _) => push(out, path, ConfigOrigin::Group),
+        (None, Some(_)) => push(out, path, ConfigOrigin::Common),
+    }
+}
+
+/// Returns descriptions of top-level sections that don't match any group or
+/// the special sections, with suggestions of the closest known ones.
+pub(crate) fn unknown_sections<'a>(
+    config: &Value,
+    groups: &[&'a str],
+    special: &[&'a str],
+) -> Vec<String> {
+    let mut unknown = Vec::new();
+    collect_unknown_sections(config, "", groups, special, &mut unknown);
+    unknown
+}
+
+fn collect_unknown_sections<'a>(
+    config: &Value,
+    prefix: &str,
+    groups: &[&'a str],
+    special: &[&'a str],
+    out: &mut Vec<String>,
+) {
+    let Value::Map(map) = config else {
+        return;
+    };
+
+    // Next parts of group names with the prefix, e.g. `loggers` of `system.loggers`.
+    let known = groups
+        .iter()
+        .filter_map(|group| group.strip_prefix(prefix))
+        .map(|rest| rest.split('.').next().unwrap_or(rest))
+        .chain(special.iter().copied())
+        .collect::<Vec<_>>();
+
+    for (key, value) in map {
+        let Value::String(key) = key else {
+            continue;
+        };
+
+        let path = format!("{prefix}{key}");
+        let nested = format!("{path}.");
+
+        if groups.contains(&path.as_str()) || special.contains(&key.as_str()) {
+            continue;
+        }
+
+        if groups.iter().any(|group| group.starts_with(&nested)) {
+            collect_unknown_sections(value, &nested, groups, &[], out);
+            continue;
+        }
+
+        let mut message = format!("unknown section `[{path}]`");
+        if let Some(suggestion) = elfo_utils::closest(key, known.iter().copied()) {
+            message.push_str(&format!(", did you mean `[{prefix}{suggestion}]`?"));
+        }
+        out.push(message);
+    }
+}
+
+fn with_key(path: &mut String, key: &Value, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    if !path.is_empty() {
+        path.push('.');
+    }
+
+    match key {
+        Value::String(key) => path.push_str(key),
+        key => path.push_str(&format!("{key:?}")),
+    }
+
+    f(path);
+    path.truncate(len);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,6 +296,77 @@ mod tests {
         );
     }
 
+    #[test]
+    fn provenance_of_common_section() {
+        let config: Value = toml::from_str(
+            r#"
+            [common]
+            a = 1
+            b = { c = 2, d = 3 }
+            e = [1, 2]
+            f = { g = 4 }
+            [foo]
+            b.c = 20
+            e = [3]
+            f = 5
+            h.i = 6
+            "#,
+        )
+        .unwrap();
+
+        let common = lookup_value(&config, "common").unwrap();
+        let foo_config = lookup_value(&config, "foo");
+        let provenance = provenance(foo_config, common)
+            .into_iter()
+            .map(|p| (p.path, p.origin))
+            .collect::<Vec<_>>();
+
+        let expected = [
+            ("a", ConfigOrigin::Common),
+            ("b.c", ConfigOrigin::Group),
+            ("b.d", ConfigOrigin::Common),
+            // Arrays are replaced.
+            ("e", ConfigOrigin::Group),
+            // Tables are replaced by non-tables.
+            ("f", ConfigOrigin::Group),
+            ("h.i", ConfigOrigin::Group),
+        ]
+        .map(|(path, origin)| (path.to_owned(), origin));
+        assert_eq!(provenance, expected);
+
+        // Groups without a section.
+        let provenance = super::provenance(None, common);
+        assert!(provenance.iter().all(|p| p.origin == ConfigOrigin::Common));
+        assert_eq!(provenance.len(), 5);
+    }
+
+    #[test]
+    fn unknown_sections() {
+        let config: Value = toml::from_str(
+            r#"
+            [common]
+            [routes]
+            [producers]
+            [producrs]
+            [system.loggers]
+            [system.logers]
+            [metrics]
+            "#,
+        )
+        .unwrap();
+
+        let groups = ["producers", "system.loggers", "system.telemeters"];
+        let unknown = super::unknown_sections(&config, &groups, &["common", "routes"]);
+        assert_eq!(
+            unknown,
+            [
+                "unknown section `[metrics]`",
+                "unknown section `[producrs]`, did you mean `[producers]`?",
+                "unknown section `[system.logers]`, did you mean `[system.loggers]`?",
+            ]
+        );
+    }
+
     /// The `toml` crate prior to v0.6 merges sections incorrectly,
     /// now it should work fine. Added to prevent regression.
     /// See #30 for details.
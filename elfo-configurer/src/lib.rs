@@ -1,5 +1,34 @@
 //! Loads and validates configs from a file or a fixture.
 //! Usually, it's used as an entrypoint in the topology.
+//!
+//! Every group gets its own section, e.g. `[producers]` for the `producers`
+//! group, with keys of the `[common]` section merged as defaults:
+//! * tables are merged recursively, so a leaf key of the group's section wins;
+//! * other values, including arrays, are replaced, not concatenated.
+//!
+//! ```toml
+//! [common]
+//! system.mailbox.capacity = 1000
+//! telemetry = { interval = "1s", labels = ["a", "b"] }
+//!
+//! [producers]
+//! telemetry.labels = ["c"]
+//! # The effective config of `producers` is
+//! # system.mailbox.capacity = 1000
+//! # telemetry = { interval = "1s", labels = ["c"] }
+//! ```
+//!
+//! If `system.strict_config` is set in the `[common]` section, top-level
+//! sections that don't match any group are rejected, e.g. a misspelled one.
+//!
+//! The merge is done on every reload, so groups also get changes of `[common]`
+//! and conditionally mounted groups see the effective config. `GetConfig`
+//! returns the effective config, use `GetConfig::with_provenance()` to get
+//! which keys come from `[common]`.
+//!
+//! By default, configs are sent to all groups at once. If a group relies on
+//! new configs of groups it sends to, set `sync_updates` in the configurer's
+//! own section, see [`config::Config`].
 
 use std::{
     future::Future,
@@ -8,7 +37,7 @@ use std::{
 };
 
 use futures::future;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use serde::{de::Deserializer, Deserialize};
 use serde_value::Value;
 use tokio::{fs, select, time};
@@ -16,22 +45,36 @@ use tracing::{error, info, warn};
 
 use elfo_core::{
     config::AnyConfig,
+    errors::ErrorKind,
     messages::{
-        EntrypointError, StartEntrypoint, StartEntrypointRejected, UpdateConfig, ValidateConfig,
+        EntrypointError, GetConfig, GetMessageCatalog, GetRecentDumps, GetTopologyGraph,
+        MessageCatalog, StartEntrypoint, StartEntrypointRejected, UpdateConfig, ValidateConfig,
     },
     msg, scope,
     signal::{Signal, SignalKind},
-    ActorGroup, ActorStatus, Addr, Blueprint, Context, RestartParams, RestartPolicy, Topology,
+    topology::{ConnectionTo, RoutesConfig},
+    ActorGroup, ActorStatus, Addr, Blueprint, Context, ResponseToken, RestartParams, RestartPolicy,
+    Topology,
 };
 
 pub use self::protocol::*;
 
+use self::config::Config;
+
+pub mod config;
+
 mod helpers;
 mod protocol;
 
 // How often warn if a group is updating a config too long.
 const WARN_INTERVAL: Duration = Duration::from_secs(5);
 
+// The section of weighted routes, see `Topology::weighted()`.
+const ROUTES_SECTION: &str = "routes";
+
+// The section merged into all groups' sections, see the crate docs.
+const COMMON_SECTION: &str = "common";
+
 /// Creates a blueprint for a configurer that uses the provided fixture.
 ///
 /// # Example
@@ -75,6 +118,7 @@ pub fn from_path(topology: &Topology, path_to_config: impl AsRef<Path>) -> Bluep
 fn blueprint(topology: &Topology, source: ConfigSource) -> Blueprint {
     let topology = topology.clone();
     ActorGroup::new()
+        .config::<Config>()
         .stop_order(100)
         .restart_policy(RestartPolicy::on_failure(RestartParams::new(
             Duration::from_secs(5),
@@ -84,11 +128,13 @@ fn blueprint(topology: &Topology, source: ConfigSource) -> Blueprint {
 }
 
 struct Configurer {
-    ctx: Context,
+    ctx: Context<Config>,
     topology: Topology,
     source: ConfigSource,
     /// Stores hashes of configs per group.
     versions: FxHashMap<String, u64>,
+    /// The last applied config with all sections, used to find provenance.
+    applied: Option<Value>,
 }
 
 #[derive(Clone)]
@@ -106,12 +152,13 @@ struct ConfigWithMeta {
 }
 
 impl Configurer {
-    fn new(ctx: Context, topology: Topology, source: ConfigSource) -> Self {
+    fn new(ctx: Context<Config>, topology: Topology, source: ConfigSource) -> Self {
         Self {
             ctx,
             topology,
             source,
             versions: FxHashMap::default(),
+            applied: None,
         }
     }
 
@@ -191,10 +238,76 @@ impl Configurer {
 
                     self.ctx.respond(token, response);
                 }
+                (GetTopologyGraph, token) => {
+                    self.ctx.respond(token, self.topology.graph());
+                }
+                (GetRecentDumps { group, key, .. }, token) => {
+                    // Unknown groups are ignored by dropping the token.
+                    let addr = self
+                        .topology
+                        .locals()
+                        .find(|g| g.name == group)
+                        .map(|g| g.addr);
+                    if let Some(addr) = addr {
+                        let request = GetRecentDumps::new(group, key);
+                        let _ = self.ctx.forward_request_to(token, addr, request).await;
+                    }
+                }
+                (
+                    GetMessageCatalog {
+                        protocol_prefix, ..
+                    },
+                    token,
+                ) => {
+                    self.ctx
+                        .respond(token, MessageCatalog::collect(&protocol_prefix));
+                }
+                (
+                    GetConfig {
+                        group,
+                        with_provenance,
+                        ..
+                    },
+                    token,
+                ) => {
+                    // Unknown groups are ignored by dropping the token.
+                    let addr = self
+                        .topology
+                        .locals()
+                        .find(|g| g.name == group)
+                        .map(|g| g.addr);
+                    if let Some(addr) = addr {
+                        if with_provenance {
+                            self.get_config_with_provenance(token, addr, group).await;
+                        } else {
+                            let request = GetConfig::new(group);
+                            let _ = self.ctx.forward_request_to(token, addr, request).await;
+                        }
+                    }
+                }
             })
         }
     }
 
+    async fn get_config_with_provenance(
+        &self,
+        token: ResponseToken<GetConfig>,
+        addr: Addr,
+        group: String,
+    ) {
+        let request = GetConfig::new(group.clone());
+        // On errors, the token is dropped, so the request is ignored as usual.
+        let Ok(mut applied) = self.ctx.request_to(addr, request).resolve().await else {
+            return;
+        };
+
+        let empty = Value::Map(Default::default());
+        let config = self.applied.as_ref().unwrap_or(&empty);
+        let common = helpers::lookup_value(config, COMMON_SECTION).unwrap_or(&empty);
+        applied.provenance = helpers::provenance(helpers::lookup_value(config, &group), common);
+        self.ctx.respond(token, applied);
+    }
+
     async fn load_configs(&self) -> Result<Value, Vec<ReloadConfigsError>> {
         let config = match &self.source {
             ConfigSource::File(path) => {
@@ -229,6 +342,8 @@ impl Configurer {
 
     async fn load_and_check_configs(&self) -> Result<(), Vec<ReloadConfigsError>> {
         let configs = self.load_configs().await?;
+        check_sections(&self.topology, &configs)?;
+        match_routes(&self.topology, &configs)?;
 
         // Here we rely on the fact that the first `ValidateConfig` message is consumed
         // by the supervisor and no actors are actually started.
@@ -241,19 +356,25 @@ impl Configurer {
         force: bool,
     ) -> Result<(), Vec<ReloadConfigsError>> {
         let configs = self.load_configs().await?;
-
-        let mut configs = match_configs(&self.topology, &configs);
-
-        // Filter out up-to-date configs if needed.
-        if !force {
-            configs.retain(|c| {
-                self.versions
+        check_sections(&self.topology, &configs)?;
+
+        let routes = match_routes(&self.topology, &configs)?;
+        let raw = configs;
+        let configs = match_configs(&self.topology, &raw);
+
+        // Filter out up-to-date configs if needed. Hashes are calculated after
+        // merging with the common section, so changes in it are also detected.
+        let (configs, unchanged): (Vec<_>, Vec<_>) = configs.into_iter().partition(|c| {
+            force
+                || self
+                    .versions
                     .get(&c.group_name)
                     .map_or(true, |v| c.hash != *v)
-            });
-        }
+        });
 
         if configs.is_empty() {
+            self.update_routes(&routes);
+            self.applied = Some(raw);
             info!("all groups' configs are up-to-date, nothing to update");
             return Ok(());
         }
@@ -275,7 +396,16 @@ impl Configurer {
         // Updating.
         let status = ActorStatus::NORMAL.with_details("updating");
         self.ctx.set_status(status);
-        self.update_all(&configs).await;
+        let own = configs.iter().find(|c| c.addr == self.ctx.group());
+        let own = own.map(|c| Config::deserialize(c.config.clone()));
+        let settings = own.and_then(Result::ok).unwrap_or_default();
+        if settings.sync_updates {
+            self.update_in_waves(&configs, force, &settings).await;
+        } else {
+            self.update_all(&configs, force).await;
+        }
+        self.update_routes(&routes);
+        self.applied = Some(raw);
 
         self.ctx.set_status(ActorStatus::NORMAL);
 
@@ -288,9 +418,12 @@ impl Configurer {
             .map(|config| config.group_name)
             .collect();
 
+        let unchanged_groups: Vec<String> = unchanged.into_iter().map(|c| c.group_name).collect();
+
         info!(
             message = "groups' configs are updated",
             groups = ?updated_groups,
+            unchanged = ?unchanged_groups,
         );
 
         Ok(())
@@ -341,9 +474,9 @@ impl Configurer {
         }
     }
 
-    async fn update_all(&self, configs: &[ConfigWithMeta]) {
+    async fn update_all(&self, configs: &[ConfigWithMeta], force: bool) {
         for item in configs {
-            let message = UpdateConfig::new(item.config.clone()); // cheap due to `Arc`s.
+            let message = make_update(item, force);
 
             // While `UpdateConfig` is defined as a request to cover more use cases, default
             // configurer simply sends out new configs instead of waiting for all groups to
@@ -353,6 +486,83 @@ impl Configurer {
             let _ = self.ctx.unbounded_send_to(item.addr, message);
         }
     }
+
+    /// Updates groups wave by wave, see `Config::sync_updates`.
+    async fn update_in_waves(&self, configs: &[ConfigWithMeta], force: bool, settings: &Config) {
+        let deps = self
+            .topology
+            .connections()
+            .filter_map(|c| match c.to {
+                ConnectionTo::Local(to) => Some((c.from, to)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let waves = if settings.update_order.is_empty() {
+            waves_by_deps(configs, &deps)
+        } else {
+            waves_by_order(configs, &settings.update_order)
+        };
+
+        for wave in waves {
+            let futures = wave
+                .into_iter()
+                .map(|item| self.update_and_wait(item, force, *settings.update_timeout));
+            future::join_all(futures).await;
+        }
+    }
+
+    async fn update_and_wait(&self, item: &ConfigWithMeta, force: bool, timeout: Duration) {
+        let group = &item.group_name;
+        let message = make_update(item, force);
+
+        // The configurer cannot wait for itself.
+        if item.addr == self.ctx.group() {
+            let _ = self.ctx.unbounded_send_to(item.addr, message);
+            return;
+        }
+
+        let fut = self
+            .ctx
+            .request_to(item.addr, message)
+            .all()
+            .resolve_detailed();
+        let fut = wrap_long_running_future(
+            fut,
+            group.clone(),
+            "group is applying the config suspiciously long",
+        );
+
+        let Ok((_, results)) = time::timeout(timeout, fut).await else {
+            error!(
+                %group,
+                ?timeout,
+                "group hasn't applied the config in time, dependent groups are updated anyway"
+            );
+            return;
+        };
+
+        use ErrorKind::{GroupDisabled, Ignored};
+
+        for result in results {
+            match result {
+                Ok(Ok(())) => {}
+                // There are no actors to apply the config.
+                Err(err) if matches!(err.kind(), Ignored | GroupDisabled) => {}
+                Ok(Err(reject)) => {
+                    error!(%group, reason = %reject.reason, "group has rejected the config");
+                }
+                Err(error) => error!(%group, %error, "group hasn't applied the config"),
+            }
+        }
+    }
+
+    fn update_routes(&self, routes: &RoutesConfig) {
+        // Routes are already checked by `match_routes()`.
+        if let Err(err) = self.topology.set_routes(routes) {
+            error!(reason = %err.reason, "cannot update routes");
+        }
+    }
 }
 
 async fn wrap_long_running_future<F: Future>(
@@ -378,12 +588,145 @@ async fn load_raw_config(path: impl AsRef<Path>) -> Result<Value, String> {
     toml::from_str(&content).map_err(|err| err.to_string())
 }
 
+fn check_sections(topology: &Topology, config: &Value) -> Result<(), Vec<ReloadConfigsError>> {
+    let strict = helpers::lookup_value(config, "common.system.strict_config");
+    if strict != Some(&Value::Bool(true)) {
+        return Ok(());
+    }
+
+    let groups = topology
+        .locals()
+        .map(|group| group.name)
+        .collect::<Vec<_>>();
+    let groups = groups.iter().map(String::as_str).collect::<Vec<_>>();
+    let special = [COMMON_SECTION, ROUTES_SECTION];
+
+    let errors = helpers::unknown_sections(config, &groups, &special)
+        .into_iter()
+        .inspect(|reason| error!(%reason, "invalid config"))
+        .map(|reason| ReloadConfigsError {
+            group: scope::meta().group.clone(),
+            reason,
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn match_routes(
+    topology: &Topology,
+    config: &Value,
+) -> Result<RoutesConfig, Vec<ReloadConfigsError>> {
+    let reject = |reason: String| {
+        error!(%reason, "invalid routes");
+        vec![ReloadConfigsError {
+            group: ROUTES_SECTION.into(),
+            reason,
+        }]
+    };
+
+    let routes = match helpers::lookup_value(config, ROUTES_SECTION) {
+        Some(value) => value
+            .clone()
+            .deserialize_into::<RoutesConfig>()
+            .map_err(|err| reject(err.to_string()))?,
+        None => RoutesConfig::default(),
+    };
+
+    topology
+        .check_routes(&routes)
+        .map_err(|rejected| reject(rejected.reason))?;
+
+    Ok(routes)
+}
+
+fn make_update(item: &ConfigWithMeta, force: bool) -> UpdateConfig {
+    let message = UpdateConfig::new(item.config.clone()); // cheap due to `Arc`s.
+
+    // Otherwise, groups skip configs equal to applied ones.
+    if force {
+        message.forcing()
+    } else {
+        message
+    }
+}
+
+/// Splits configs into waves, so every group is updated after groups it
+/// depends on. The order of configs is kept inside waves.
+fn waves_by_deps<'a>(
+    configs: &'a [ConfigWithMeta],
+    deps: &[(Addr, Addr)],
+) -> Vec<Vec<&'a ConfigWithMeta>> {
+    let pending_deps = |item: &ConfigWithMeta, pending: &FxHashSet<Addr>| {
+        deps.iter()
+            .any(|(from, to)| *from == item.addr && *to != item.addr && pending.contains(to))
+    };
+
+    let mut pending = configs.iter().map(|c| c.addr).collect::<FxHashSet<_>>();
+    let mut waves = Vec::new();
+
+    while !pending.is_empty() {
+        let mut wave = configs
+            .iter()
+            .filter(|c| pending.contains(&c.addr) && !pending_deps(c, &pending))
+            .collect::<Vec<_>>();
+
+        if wave.is_empty() {
+            // Only cycles are left, update them together.
+            let groups = configs.iter().filter(|c| pending.contains(&c.addr));
+            let groups = groups.map(|c| &c.group_name[..]).collect::<Vec<_>>();
+            warn!(
+                ?groups,
+                "cyclic dependencies between groups, updated together"
+            );
+            wave = configs
+                .iter()
+                .filter(|c| pending.contains(&c.addr))
+                .collect();
+        }
+
+        for item in &wave {
+            pending.remove(&item.addr);
+        }
+        waves.push(wave);
+    }
+
+    waves
+}
+
+/// Splits configs into waves following `Config::update_order`.
+fn waves_by_order<'a>(
+    configs: &'a [ConfigWithMeta],
+    order: &[String],
+) -> Vec<Vec<&'a ConfigWithMeta>> {
+    let mut waves = order
+        .iter()
+        .filter_map(|group| configs.iter().find(|c| c.group_name == *group))
+        .map(|item| vec![item])
+        .collect::<Vec<_>>();
+
+    let rest = configs
+        .iter()
+        .filter(|c| !order.contains(&c.group_name))
+        .collect::<Vec<_>>();
+
+    if !rest.is_empty() {
+        waves.push(rest);
+    }
+
+    waves
+}
+
 fn match_configs(topology: &Topology, config: &Value) -> Vec<ConfigWithMeta> {
     let mut configs: Vec<ConfigWithMeta> = topology
         .locals()
         .map(|group| {
             let empty = Value::Map(Default::default());
-            let common = helpers::lookup_value(config, "common").unwrap_or(&empty);
+            let common = helpers::lookup_value(config, COMMON_SECTION).unwrap_or(&empty);
             let group_config = helpers::lookup_value(config, &group.name).cloned();
             let group_config = helpers::add_defaults(group_config, common);
 
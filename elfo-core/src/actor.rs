@@ -1,5 +1,8 @@
 use std::{
+    any::Any,
+    collections::BTreeMap,
     fmt, mem,
+    num::NonZeroU32,
     sync::{atomic, Arc},
 };
 
@@ -11,15 +14,25 @@ use tracing::{error, info, warn};
 
 use crate::{
     actor_status::{ActorStatus, ActorStatusKind, AtomicActorStatusKind},
+    admission::AdmissionPolicy,
+    deferred::DeferredTable,
+    dumping::{config::RecentDumpsConfig, RecentDumps},
     envelope::Envelope,
     errors::{SendError, TrySendError},
     group::TerminationPolicy,
-    mailbox::{config::MailboxConfig, Mailbox, RecvResult},
+    mailbox::{
+        config::{MailboxConfig, MailboxQuota, OnTerminate},
+        Mailbox, RecvResult,
+    },
     messages::{ActorStatusReport, Terminate},
     msg,
+    poisoning::Poisoning,
+    pool::Pool,
     request_table::RequestTable,
     restarting::RestartPolicy,
+    runtime::RuntimeHandle,
     scope,
+    spawn_throttle::SpawnPermit,
     subscription::SubscriptionManager,
     Addr,
 };
@@ -69,6 +82,11 @@ pub enum ActorStartCause {
     OnMessage,
     /// The actor started due to the restart policy.
     Restarted,
+    /// The actor started on another runtime, because its placement changed,
+    /// see [`ActorGroup::placement()`].
+    ///
+    /// [`ActorGroup::placement()`]: crate::ActorGroup::placement
+    Migrated,
 }
 
 impl ActorStartInfo {
@@ -89,6 +107,12 @@ impl ActorStartInfo {
             cause: ActorStartCause::Restarted,
         }
     }
+
+    pub(crate) fn on_migration() -> Self {
+        Self {
+            cause: ActorStartCause::Migrated,
+        }
+    }
 }
 
 impl ActorStartCause {
@@ -103,15 +127,28 @@ impl ActorStartCause {
     pub fn is_on_message(&self) -> bool {
         matches!(self, ActorStartCause::OnMessage)
     }
+
+    pub fn is_migrated(&self) -> bool {
+        matches!(self, ActorStartCause::Migrated)
+    }
 }
 
 // === Actor ===
 
 pub(crate) struct Actor {
     meta: Arc<ActorMeta>,
+    /// Set if chosen by `ActorGroup::placement()`.
+    placement: Option<RuntimeHandle>,
     termination_policy: TerminationPolicy,
     mailbox: Mailbox,
+    poisoning: Poisoning,
+    recent_dumps: RecentDumps,
+    /// Set for workers of pooled groups, see `ActorGroup::pool()`.
+    pool: Option<Arc<Pool>>,
+    /// The number of the worker, i.e. its key, if `pool` is set.
+    worker_no: usize,
     request_table: RequestTable,
+    deferred_table: Arc<DeferredTable>,
     status_kind: AtomicActorStatusKind,
     control: RwLock<Control>,
     finished: ManualResetEvent, // TODO: remove in favor of `status_subscription`?
@@ -126,33 +163,68 @@ struct Control {
     mailbox_capacity_config: usize,
     /// Explicitly set mailbox capacity via `Context::set_mailbox_capacity()`.
     mailbox_capacity_override: Option<usize>,
+    /// Set by `Context::drain*()`, contains `DrainTarget<R::Key>`.
+    drain_target: Option<Box<dyn Any + Send + Sync>>,
+    /// Held while initializing, see `system.spawn_concurrency`.
+    spawn_permit: Option<SpawnPermit>,
+    /// Set once the supervisor replaces the actor on another runtime.
+    is_migrating: bool,
+}
+
+/// Where to hand off messages left in the mailbox once the actor finishes.
+pub(crate) enum DrainTarget<K> {
+    /// Re-route through the group's router.
+    Router,
+    /// Send to the actor with the specified key in the same group.
+    Key(K),
 }
 
 impl Actor {
     pub(crate) fn new(
         meta: Arc<ActorMeta>,
+        placement: Option<RuntimeHandle>,
         addr: Addr,
         mailbox_config: &MailboxConfig,
+        recent_dumps_config: &RecentDumpsConfig,
         termination_policy: TerminationPolicy,
         status_subscription: Arc<SubscriptionManager>,
     ) -> Self {
         Actor {
             status_kind: AtomicActorStatusKind::from(ActorStatusKind::Initializing),
+            deferred_table: DeferredTable::new(meta.clone()),
             meta,
+            placement,
             termination_policy,
             mailbox: Mailbox::new(mailbox_config),
+            poisoning: Poisoning::new(mailbox_config.poison_threshold),
+            recent_dumps: RecentDumps::new(recent_dumps_config),
+            pool: None,
+            worker_no: 0,
             request_table: RequestTable::new(addr),
             control: RwLock::new(Control {
                 status: ActorStatus::INITIALIZING,
                 restart_policy: None,
                 mailbox_capacity_config: mailbox_config.capacity,
                 mailbox_capacity_override: None,
+                drain_target: None,
+                spawn_permit: None,
+                is_migrating: false,
             }),
             finished: ManualResetEvent::new(false),
             status_subscription,
         }
     }
 
+    pub(crate) fn with_pool(mut self, pool: Option<Arc<Pool>>) -> Self {
+        if pool.is_some() {
+            // Workers are keyed by numbers, see `PoolRouter`.
+            self.worker_no = self.meta.key.parse().expect("invalid worker key");
+        }
+
+        self.pool = pool;
+        self
+    }
+
     pub(crate) fn on_start(&self) {
         increment_gauge!("elfo_active_actors", 1.,
             "status" => ActorStatusKind::Initializing.as_str());
@@ -187,7 +259,9 @@ impl Actor {
     fn handle_system(&self, envelope: Envelope) -> Option<Envelope> {
         msg!(match &envelope {
             Terminate { closing } => {
-                if (*closing || self.termination_policy.close_mailbox) && self.close() {
+                if (*closing || self.termination_policy.close_mailbox)
+                    && self.mailbox.terminate(scope::trace_id())
+                {
                     // First closing `Terminate` is considered successful.
                     return None;
                 }
@@ -199,22 +273,86 @@ impl Actor {
     }
 
     pub(crate) async fn recv(&self) -> RecvResult {
-        self.mailbox.recv().await
+        let Some(pool) = &self.pool else {
+            return self.mailbox.recv().await;
+        };
+
+        // The own mailbox goes first, it's used for system messages.
+        // Once the pool is closed, only the own mailbox is polled.
+        tokio::select! {
+            biased;
+            result = self.mailbox.recv() => result,
+            Some(envelope) = pool.recv(self.worker_no) => RecvResult::Data(envelope),
+        }
     }
 
     pub(crate) fn try_recv(&self) -> Option<RecvResult> {
-        self.mailbox.try_recv()
+        let result = self.mailbox.try_recv();
+        match &self.pool {
+            Some(pool) if result.is_none() => pool.try_recv(self.worker_no).map(RecvResult::Data),
+            _ => result,
+        }
+    }
+
+    /// Peeks the own mailbox only, because envelopes of the pool's shared
+    /// queue can be taken by other workers at any moment.
+    pub(crate) fn peek<R>(&self, f: impl FnOnce(&Envelope) -> R) -> Option<R> {
+        self.mailbox.peek(f)
+    }
+
+    pub(crate) fn mailbox(&self) -> &Mailbox {
+        &self.mailbox
+    }
+
+    pub(crate) fn meta(&self) -> &Arc<ActorMeta> {
+        &self.meta
+    }
+
+    pub(crate) fn placement(&self) -> Option<&RuntimeHandle> {
+        self.placement.as_ref()
     }
 
     pub(crate) fn request_table(&self) -> &RequestTable {
         &self.request_table
     }
 
+    pub(crate) fn deferred_table(&self) -> &Arc<DeferredTable> {
+        &self.deferred_table
+    }
+
+    pub(crate) fn mailbox_len(&self) -> usize {
+        self.mailbox.len()
+    }
+
     pub(crate) fn set_mailbox_capacity_config(&self, capacity: usize) {
         self.control.write().mailbox_capacity_config = capacity;
         self.update_mailbox_capacity();
     }
 
+    pub(crate) fn set_mailbox_quotas(&self, quotas: &BTreeMap<String, MailboxQuota>) {
+        self.mailbox.set_quotas(quotas);
+    }
+
+    pub(crate) fn set_mailbox_admission(&self, policy: Option<Arc<AdmissionPolicy>>) {
+        self.mailbox.set_admission(policy);
+    }
+
+    pub(crate) fn set_mailbox_on_terminate(&self, on_terminate: OnTerminate) {
+        self.mailbox.set_on_terminate(on_terminate);
+    }
+
+    pub(crate) fn set_poison_threshold(&self, threshold: Option<NonZeroU32>) {
+        self.poisoning.configure(threshold);
+    }
+
+    pub(crate) fn poisoning(&self) -> &Poisoning {
+        &self.poisoning
+    }
+
+    pub(crate) fn recent_dumps(&self) -> &RecentDumps {
+        &self.recent_dumps
+    }
+
     pub(crate) fn set_mailbox_capacity_override(&self, capacity: Option<usize>) {
         self.control.write().mailbox_capacity_override = capacity;
         self.update_mailbox_capacity();
@@ -238,6 +376,60 @@ impl Actor {
         self.control.write().restart_policy = policy;
     }
 
+    pub(crate) fn set_drain_target<K: Send + Sync + 'static>(&self, target: DrainTarget<K>) {
+        self.control.write().drain_target = Some(Box::new(target));
+        self.close();
+    }
+
+    /// Takes messages left in the mailbox if draining has been requested.
+    pub(crate) fn take_drained<K: 'static>(&self) -> Option<(DrainTarget<K>, Vec<Envelope>)> {
+        let target = self.control.write().drain_target.take()?;
+
+        let Ok(target) = target.downcast::<DrainTarget<K>>() else {
+            error!("drain target has an invalid key type, messages are dropped");
+            return None;
+        };
+
+        Some((*target, self.mailbox.drain()))
+    }
+
+    /// Closes the mailbox and takes messages left in it.
+    pub(crate) fn take_left(&self) -> Vec<Envelope> {
+        self.close();
+        self.mailbox.drain()
+    }
+
+    /// Marks the actor as migrating, so left messages are handed off to the
+    /// actor with the same key once this one finishes, see `take_drained()`.
+    /// The mailbox should be closed once the successor is ready to receive.
+    ///
+    /// Returns `false` if the actor is already finishing or migrating.
+    pub(crate) fn start_migration<K: Send + Sync + 'static>(&self, key: K) -> bool {
+        let mut control = self.control.write();
+        if control.status.kind().is_finished()
+            || control.drain_target.is_some()
+            || control.is_migrating
+        {
+            return false;
+        }
+
+        control.is_migrating = true;
+        control.drain_target = Some(Box::new(DrainTarget::Key(key)));
+        true
+    }
+
+    pub(crate) fn is_migrating(&self) -> bool {
+        self.control.read().is_migrating
+    }
+
+    /// Holds the permit until the actor leaves the `Initializing` status.
+    pub(crate) fn set_spawn_permit(&self, permit: SpawnPermit) {
+        let mut control = self.control.write();
+        if control.status.kind().is_initializing() {
+            control.spawn_permit = Some(permit);
+        }
+    }
+
     pub(crate) fn status_kind(&self) -> ActorStatusKind {
         self.status_kind.load(atomic::Ordering::Acquire)
     }
@@ -250,6 +442,13 @@ impl Actor {
         let mut control = self.control.write();
         let prev_status = mem::replace(&mut control.status, status.clone());
 
+        // The next actor can be spawned once this one is initialized or finished.
+        let _spawn_permit = if !status.kind().is_initializing() {
+            control.spawn_permit.take()
+        } else {
+            None
+        };
+
         if status == prev_status {
             return;
         }
@@ -261,6 +460,7 @@ impl Actor {
             self.close();
             // Drop all messages to release requests immediately.
             self.mailbox.drop_all();
+            self.deferred_table.drop_all();
             self.finished.set();
         }
 
@@ -297,6 +497,7 @@ impl Actor {
         f(ActorStatusReport {
             meta: self.meta.clone(),
             status: control.status.clone(),
+            runtime: self.runtime_name(),
         })
     }
 
@@ -304,12 +505,23 @@ impl Actor {
         self.status_subscription.send(ActorStatusReport {
             meta: self.meta.clone(),
             status: control.status.clone(),
+            runtime: self.runtime_name(),
         });
     }
+
+    fn runtime_name(&self) -> Option<String> {
+        self.placement.as_ref().map(|rt| rt.name().to_owned())
+    }
 }
 
 fn log_status(status: &ActorStatus) {
-    if let Some(details) = status.details.as_deref() {
+    if let Some(panic) = status.panic() {
+        let payload = panic.payload.as_ref().map(tracing::field::display);
+        error!(status = ?status.kind, details = %panic, payload, "status changed");
+        if let Some(backtrace) = &panic.backtrace {
+            backtrace.log();
+        }
+    } else if let Some(details) = status.details.as_deref() {
         match status.kind {
             ActorStatusKind::Failed => error!(status = ?status.kind, %details, "status changed"),
             ActorStatusKind::Alarming => warn!(status = ?status.kind, %details, "status changed"),
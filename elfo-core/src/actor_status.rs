@@ -1,10 +1,15 @@
 use std::{
     fmt, mem,
-    sync::atomic::{self, AtomicU8},
+    sync::{
+        atomic::{self, AtomicU8},
+        Arc,
+    },
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::panics::Panic;
+
 // === ActorStatus ===
 
 /// Represents the current status of an actor.
@@ -13,6 +18,9 @@ use serde::{Deserialize, Serialize};
 pub struct ActorStatus {
     pub(crate) kind: ActorStatusKind,
     pub(crate) details: Option<String>,
+    // Isn't transferred over the network, only `details` are.
+    #[serde(skip)]
+    pub(crate) panic: Option<Arc<Panic>>,
 }
 
 impl ActorStatus {
@@ -27,6 +35,7 @@ impl ActorStatus {
         Self {
             kind,
             details: None,
+            panic: None,
         }
     }
 
@@ -35,6 +44,17 @@ impl ActorStatus {
         ActorStatus {
             kind: self.kind,
             details: Some(details.to_string()),
+            panic: None,
+        }
+    }
+
+    /// Creates a new status caused by the panic, its description is used as
+    /// details.
+    pub(crate) fn with_panic(&self, panic: Panic) -> Self {
+        ActorStatus {
+            kind: self.kind,
+            details: Some(panic.to_string()),
+            panic: Some(Arc::new(panic)),
         }
     }
 
@@ -47,6 +67,14 @@ impl ActorStatus {
     pub fn details(&self) -> Option<&str> {
         self.details.as_deref()
     }
+
+    /// Returns the panic that caused the failure, if any.
+    ///
+    /// It's available only on the same node, remote subscribers get only
+    /// [`ActorStatus::details()`].
+    pub fn panic(&self) -> Option<&Panic> {
+        self.panic.as_deref()
+    }
 }
 
 impl fmt::Display for ActorStatus {
@@ -1,10 +1,19 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use idr_ebr::{EbrGuard, Idr};
 
+#[cfg(feature = "test-util")]
+use crate::dumping::capture::DumpCapture;
 use crate::{
     addr::{Addr, GroupNo, IdrConfig, NodeLaunchId, NodeNo},
+    broker::Broker,
+    group_ref::GroupDirectory,
     object::{BorrowedObject, Object, OwnedObject},
+    shutdown::Shutdown,
+    topology::{EdgeRecorder, Standbys},
 };
 
 // Reexported in `_priv`.
@@ -14,23 +23,84 @@ pub struct AddressBook {
     local: Arc<Idr<Object, IdrConfig>>,
     #[cfg(feature = "network")]
     remote: Arc<RemoteToHandleMap>, // TODO: use `arc_swap::cache::Cache` in TLS?
+    edge_recorder: Arc<EdgeRecorder>,
+    standbys: Arc<Standbys>,
+    groups: Arc<GroupDirectory>,
+    broker: Arc<Broker>,
+    shutdown: Arc<Shutdown>,
+    topology_generation: Arc<AtomicU64>,
+    #[cfg(feature = "test-util")]
+    dump_capture: Arc<DumpCapture>,
 }
 
 assert_impl_all!(AddressBook: Sync);
 
+/// Stats of local actors, see [`AddressBook::stats()`].
+// Reexported in `_priv`.
+#[derive(Debug, Default, Clone, Copy)]
+#[non_exhaustive]
+pub struct AddressBookStats {
+    /// The number of alive actors.
+    pub actors: usize,
+    /// The approximate number of messages in all mailboxes.
+    pub mailbox_len: usize,
+}
+
 impl AddressBook {
     pub(crate) fn new(launch_id: NodeLaunchId) -> Self {
-        let local = Arc::new(Idr::new());
-
-        #[cfg(feature = "network")]
-        return Self {
+        Self {
             launch_id,
-            local,
+            local: Arc::new(Idr::new()),
+            #[cfg(feature = "network")]
             remote: Default::default(),
-        };
+            edge_recorder: Default::default(),
+            standbys: Default::default(),
+            groups: Default::default(),
+            broker: Default::default(),
+            shutdown: Default::default(),
+            topology_generation: Default::default(),
+            #[cfg(feature = "test-util")]
+            dump_capture: Default::default(),
+        }
+    }
+
+    /// Returns the generation of local groups, which is changed every time a
+    /// group is added, mounted, enabled or disabled. Used to invalidate caches
+    /// built on top of the topology, see [`Topology::generation()`].
+    ///
+    /// [`Topology::generation()`]: crate::Topology::generation
+    #[inline]
+    pub fn topology_generation(&self) -> u64 {
+        self.topology_generation.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn bump_topology_generation(&self) {
+        self.topology_generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub(crate) fn edge_recorder(&self) -> &EdgeRecorder {
+        &self.edge_recorder
+    }
+
+    pub(crate) fn standbys(&self) -> &Standbys {
+        &self.standbys
+    }
+
+    pub(crate) fn groups(&self) -> &GroupDirectory {
+        &self.groups
+    }
+
+    pub(crate) fn broker(&self) -> &Broker {
+        &self.broker
+    }
 
-        #[cfg(not(feature = "network"))]
-        Self { launch_id, local }
+    pub(crate) fn shutdown(&self) -> &Arc<Shutdown> {
+        &self.shutdown
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn dump_capture(&self) -> &Arc<DumpCapture> {
+        &self.dump_capture
     }
 
     #[cfg(feature = "network")]
@@ -87,6 +157,24 @@ impl AddressBook {
             .filter(|object| object.addr() == addr)
     }
 
+    /// Collects stats of local actors.
+    ///
+    /// Traverses all objects, so it's intended to be called rarely.
+    #[stability::unstable]
+    pub fn stats(&self) -> AddressBookStats {
+        let guard = EbrGuard::new();
+        let mut stats = AddressBookStats::default();
+
+        for (_, object) in self.local.iter(&guard) {
+            if let Some(actor) = object.as_actor() {
+                stats.actors += 1;
+                stats.mailbox_len += actor.mailbox_len();
+            }
+        }
+
+        stats
+    }
+
     pub(crate) fn vacant_entry(&self, group_no: GroupNo) -> VacantEntry<'_> {
         self.local
             .vacant_entry()
@@ -0,0 +1,172 @@
+//! Admission control on the send path, see [`ActorGroup::admission()`].
+//!
+//! [`ActorGroup::admission()`]: crate::ActorGroup::admission
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{
+    actor::ActorMeta,
+    addr::Addr,
+    envelope::{Envelope, MessageKind},
+    message::{AnyMessageRef, Message},
+    scope,
+};
+
+/// A decision of an admission policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Enqueue the envelope as usual.
+    Admit,
+    /// Don't enqueue the envelope, the sender gets an error of the
+    /// [`Rejected`] kind with the provided reason.
+    ///
+    /// [`Rejected`]: crate::errors::ErrorKind::Rejected
+    Reject(&'static str),
+    /// Enqueue the envelope, but mark it as degraded, so the receiver can
+    /// handle it in a cheaper way, see [`Envelope::is_degraded()`].
+    Degrade,
+}
+
+/// The envelope being sent, passed to admission policies.
+pub struct EnvelopeMeta<'a> {
+    envelope: &'a Envelope,
+}
+
+impl<'a> EnvelopeMeta<'a> {
+    pub(crate) fn new(envelope: &'a Envelope) -> Self {
+        Self { envelope }
+    }
+
+    /// Returns the name of the message.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.envelope.message().name()
+    }
+
+    /// Returns the protocol of the message.
+    #[inline]
+    pub fn protocol(&self) -> &'static str {
+        self.envelope.message().protocol()
+    }
+
+    /// Returns the message itself, e.g. to downcast it.
+    #[inline]
+    pub fn message(&self) -> AnyMessageRef<'a> {
+        self.envelope.message()
+    }
+
+    /// Returns whether the envelope is a request.
+    #[inline]
+    pub fn is_request(&self) -> bool {
+        matches!(
+            self.envelope.message_kind(),
+            MessageKind::RequestAny(_) | MessageKind::RequestAll(_)
+        )
+    }
+
+    /// Returns the address of the sender.
+    #[inline]
+    pub fn sender(&self) -> Addr {
+        self.envelope.sender()
+    }
+
+    /// Returns the meta of the sending actor, `None` if the message is sent
+    /// outside actors. Messages from other nodes are sent by network actors.
+    #[inline]
+    pub fn sender_meta(&self) -> Option<Arc<ActorMeta>> {
+        scope::try_meta()
+    }
+}
+
+/// Cheap statistics of the receiver's mailbox, passed to admission policies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MailboxStats {
+    /// The approximate number of messages in the mailbox.
+    pub len: usize,
+    /// The capacity of the mailbox.
+    pub capacity: usize,
+    /// A lower bound of the time the oldest message spends in the mailbox,
+    /// measured as the time since the receiver took the last message or
+    /// since the mailbox became non-empty. Zero if the mailbox is empty.
+    pub oldest_age: Duration,
+}
+
+// === AdmissionPolicy ===
+
+type CheckFn = dyn Fn(&EnvelopeMeta<'_>, &MailboxStats) -> Admission + Send + Sync;
+
+/// A named admission policy registered by [`ActorGroup::admission()`].
+///
+/// [`ActorGroup::admission()`]: crate::ActorGroup::admission
+pub(crate) struct AdmissionPolicy {
+    name: &'static str,
+    check: Box<CheckFn>,
+}
+
+impl AdmissionPolicy {
+    pub(crate) fn new(
+        name: &'static str,
+        check: impl Fn(&EnvelopeMeta<'_>, &MailboxStats) -> Admission + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            check: Box::new(check),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub(crate) fn check(&self, envelope: &Envelope, stats: &MailboxStats) -> Admission {
+        (self.check)(&EnvelopeMeta::new(envelope), stats)
+    }
+}
+
+impl fmt::Debug for AdmissionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AdmissionPolicy").field(&self.name).finish()
+    }
+}
+
+/// Policies of one group, the first one is used unless
+/// `system.mailbox.admission` specifies another.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct AdmissionPolicies(Vec<Arc<AdmissionPolicy>>);
+
+impl AdmissionPolicies {
+    pub(crate) fn register(&mut self, policy: AdmissionPolicy) {
+        assert_ne!(policy.name, NONE, "`{NONE}` is reserved");
+        assert!(
+            self.0.iter().all(|p| p.name != policy.name),
+            "admission policy `{}` is registered twice",
+            policy.name
+        );
+
+        self.0.push(Arc::new(policy));
+    }
+
+    pub(crate) fn check_config(&self, name: Option<&str>) -> Result<(), String> {
+        match name {
+            Some(name) if name != NONE && self.get(Some(name)).is_none() => {
+                Err(format!("unknown admission policy `{name}`"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the policy with the specified name or the default one.
+    /// `"none"` disables admission control at all.
+    pub(crate) fn get(&self, name: Option<&str>) -> Option<Arc<AdmissionPolicy>> {
+        match name {
+            None => self.0.first().cloned(),
+            Some(NONE) => None,
+            Some(name) => self.0.iter().find(|p| p.name == name).cloned(),
+        }
+    }
+}
+
+const NONE: &str = "none";
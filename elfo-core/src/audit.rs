@@ -0,0 +1,282 @@
+//! Audit logs of received messages, see [`ActorGroup::audit()`].
+//!
+//! Every line of the log is a JSON record of one received envelope:
+//! ```json
+//! {"ts":1700000000000000000,"t":1234567,"from":{"addr":"1/2/3","group":"api","key":"_"},"mp":"risk","mn":"PlaceOrder","m":{"qty":1},"outcome":"ok"}
+//! ```
+//! * `ts` is the receiving time in nanoseconds since the unix epoch.
+//! * `t` is the trace id of the envelope.
+//! * `from` is the sender, `group` and `key` are set for local actors only.
+//! * `mp`, `mn` and `m` are the protocol, name and payload of the message.
+//! * `outcome` is one of the following:
+//!   * `"ok"` - the envelope is handled, i.e. the next one is received or the
+//!     actor is terminated.
+//!   * `"responded"` - the request is responded.
+//!   * `{"response":{"mp":..,"mn":..,"m":..}}` - the same, but with the
+//!     response if `include_response` is set.
+//!   * `{"panic":"reason"}` - the actor panicked while handling the envelope.
+//!   * `{"failed":"reason"}` - the actor returned an error after receiving the
+//!     envelope.
+//!
+//! Responses to requests received before the current envelope, e.g. deferred
+//! ones, are written as separate records if `include_response` is set:
+//! ```json
+//! {"ts":1700000000000000000,"t":1234567,"response":{"mp":"risk","mn":"OrderPlaced","m":{}}}
+//! ```
+//!
+//! [`ActorGroup::audit()`]: crate::ActorGroup::audit
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use tracing::error;
+
+use elfo_utils::time::SystemTime;
+
+use crate::{
+    actor::ActorMeta, actor_status::ActorStatus, addr::Addr, envelope::Envelope, message::Message,
+    request_table::RequestId, tracing::TraceId,
+};
+
+/// Settings of the audit log, see [`ActorGroup::audit()`].
+///
+/// [`ActorGroup::audit()`]: crate::ActorGroup::audit
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    /// A path to the log. Records are appended to the existing file,
+    /// directories are created if needed.
+    pub path: PathBuf,
+    /// Whether to sync the file (`fdatasync`) after every record.
+    pub sync: bool,
+    /// Whether to write responses to requests.
+    pub include_response: bool,
+}
+
+// === AuditLog ===
+
+/// The log shared by all actors of the group.
+pub(crate) struct AuditLog {
+    file: Mutex<File>,
+    sync: bool,
+    include_response: bool,
+}
+
+impl AuditLog {
+    pub(crate) fn open(config: &AuditConfig) -> io::Result<Self> {
+        if let Some(dir) = config.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            sync: config.sync,
+            include_response: config.include_response,
+        })
+    }
+
+    fn append(&self, record: &impl Serialize) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        // One `write` per record, so records of actors aren't interleaved.
+        let mut file = self.file.lock();
+        file.write_all(&line)?;
+
+        if self.sync {
+            file.sync_data()?;
+        }
+
+        Ok(())
+    }
+}
+
+// === ActorAudit ===
+
+/// Records envelopes received by one actor.
+///
+/// The record of the currently handled envelope is pending until its outcome
+/// is known. Errors of writing are fatal for the handler: the actor panics
+/// instead of responding without the record.
+#[derive(Clone)]
+pub(crate) struct ActorAudit {
+    log: Arc<AuditLog>,
+    pending: Arc<Mutex<Option<Pending>>>,
+}
+
+struct Pending {
+    request_id: Option<RequestId>,
+    record: Record,
+}
+
+impl ActorAudit {
+    pub(crate) fn new(log: Arc<AuditLog>) -> Self {
+        Self {
+            log,
+            pending: Default::default(),
+        }
+    }
+
+    /// Completes the previous envelope as handled and starts a new record.
+    pub(crate) fn on_received(&self, envelope: &Envelope, sender: Option<&ActorMeta>) {
+        let record = Record {
+            ts: SystemTime::now().to_unix_time_nanos(),
+            t: envelope.trace_id(),
+            from: Sender::new(envelope.sender(), sender),
+            payload: Payload::new(&*envelope.message()),
+            outcome: None,
+        };
+
+        let mut pending = self.pending.lock();
+
+        if let Some(prev) = pending.take() {
+            self.complete(prev, Outcome::Ok);
+        }
+
+        *pending = Some(Pending {
+            request_id: envelope.request_id(),
+            record,
+        });
+    }
+
+    /// Writes the record of the request. Must be called before responding.
+    pub(crate) fn on_response<M: Message>(
+        &self,
+        request_id: RequestId,
+        trace_id: TraceId,
+        response: &M,
+    ) {
+        let response = self.log.include_response.then(|| Payload::new(response));
+
+        let mut pending = self.pending.lock();
+
+        if pending.as_ref().and_then(|p| p.request_id) == Some(request_id) {
+            let outcome = response.map_or(Outcome::Responded, Outcome::Response);
+            self.complete(pending.take().unwrap(), outcome);
+        } else if let Some(response) = response {
+            let record = ResponseRecord {
+                ts: SystemTime::now().to_unix_time_nanos(),
+                t: trace_id,
+                response,
+            };
+
+            let result = self.log.append(&record);
+            must_be_written(result);
+        }
+    }
+
+    /// Completes the last envelope once the actor is finished.
+    pub(crate) fn on_finished(&self, status: &ActorStatus) {
+        let pending = ward!(self.pending.lock().take());
+
+        let outcome = match (status.panic(), status.details()) {
+            (Some(panic), _) => Outcome::Panic(panic.message.clone()),
+            (None, Some(details)) if status.kind().is_failed() => Outcome::Failed(details.into()),
+            _ => Outcome::Ok,
+        };
+
+        let mut record = pending.record;
+        record.outcome = Some(outcome);
+
+        if let Err(err) = self.log.append(&record) {
+            error!(error = %err, "cannot write the audit record");
+        }
+    }
+
+    fn complete(&self, pending: Pending, outcome: Outcome) {
+        let mut record = pending.record;
+        record.outcome = Some(outcome);
+        must_be_written(self.log.append(&record));
+    }
+}
+
+fn must_be_written(result: io::Result<()>) {
+    if let Err(err) = result {
+        panic!("cannot write the audit record: {err}");
+    }
+}
+
+// === Records ===
+
+#[derive(Serialize)]
+struct Record {
+    ts: u64,
+    t: TraceId,
+    from: Sender,
+    #[serde(flatten)]
+    payload: Payload,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<Outcome>,
+}
+
+#[derive(Serialize)]
+struct ResponseRecord {
+    ts: u64,
+    t: TraceId,
+    response: Payload,
+}
+
+#[derive(Serialize)]
+struct Sender {
+    addr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+}
+
+impl Sender {
+    fn new(addr: Addr, meta: Option<&ActorMeta>) -> Self {
+        Self {
+            addr: addr.to_string(),
+            group: meta.map(|meta| meta.group.clone()),
+            key: meta.map(|meta| meta.key.clone()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload {
+    mp: &'static str,
+    mn: &'static str,
+    m: Box<RawValue>,
+}
+
+impl Payload {
+    fn new<M: Message>(message: &M) -> Self {
+        // Unserializable messages are recorded anyway, with the error instead.
+        let m = serde_json::value::to_raw_value(&*message._erase()).unwrap_or_else(|err| {
+            let err = format!("cannot serialize: {err}");
+            serde_json::value::to_raw_value(&err).expect("string is always serializable")
+        });
+
+        Self {
+            mp: message.protocol(),
+            mn: message.name(),
+            m,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Ok,
+    Responded,
+    Response(Payload),
+    Panic(String),
+    Failed(String),
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use fxhash::{FxHashMap, FxHashSet};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use smallvec::SmallVec;
+
+use crate::{
+    addr::Addr,
+    dumping::Dumper,
+    message::{Message, MessageTypeId},
+};
+
+/// An identifier of a topic, see [`Context::publish()`].
+///
+/// Topics are identified by names only, so the same topic can be created in
+/// different places, e.g. as constants or from the config. Also, any type can
+/// be used as a topic by [`Topic::of()`].
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// use elfo::Topic;
+///
+/// const SESSIONS: Topic = Topic::new("sessions");
+///
+/// struct Orders;
+/// let orders = Topic::of::<Orders>();
+///
+/// // Dynamic names are interned.
+/// let market = Topic::from(format!("market.{}", "btc"));
+/// assert_eq!(market, Topic::new("market.btc"));
+/// ```
+///
+/// [`Context::publish()`]: crate::Context::publish
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Topic(&'static str);
+
+impl Topic {
+    /// Creates a topic with the specified name.
+    #[inline]
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    /// Creates a topic identified by the type.
+    #[inline]
+    pub fn of<T: ?Sized + 'static>() -> Self {
+        Self(std::any::type_name::<T>())
+    }
+
+    /// Returns the name of the topic.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl From<&'static str> for Topic {
+    #[inline]
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Topic {
+    fn from(name: String) -> Self {
+        Self(intern(name))
+    }
+}
+
+impl fmt::Debug for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Topic").field(&self.0).finish()
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+// Topics are expected to be bounded, so leaking is fine.
+fn intern(name: String) -> &'static str {
+    static NAMES: Lazy<Mutex<FxHashSet<&'static str>>> = Lazy::new(Default::default);
+
+    let mut names = NAMES.lock();
+    if let Some(name) = names.get(name.as_str()) {
+        return name;
+    }
+
+    let name = Box::leak(name.into_boxed_str());
+    names.insert(name);
+    name
+}
+
+// === Broker ===
+
+/// Topic => subscribers, shared by all actors of the node.
+///
+/// Subscribers are stored as addresses, delivery is done by the publisher.
+#[derive(Default)]
+pub(crate) struct Broker {
+    topics: RwLock<FxHashMap<Topic, TopicState>>,
+}
+
+struct TopicState {
+    dumper: Dumper,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    addr: Addr,
+    type_id: MessageTypeId,
+    accepts: fn(MessageTypeId) -> bool,
+}
+
+/// Subscribers of a topic accepting a specific message type.
+pub(crate) struct Subscribers {
+    pub(crate) dumper: Dumper,
+    pub(crate) addrs: SmallVec<[Addr; 4]>,
+}
+
+impl Broker {
+    /// Returns `false` if the actor is already subscribed.
+    pub(crate) fn subscribe<M: Message>(&self, topic: Topic, addr: Addr) -> bool {
+        let mut topics = self.topics.write();
+        let state = topics.entry(topic).or_insert_with(|| TopicState {
+            dumper: Dumper::new(intern(format!("topic:{topic}"))),
+            subscribers: Vec::new(),
+        });
+
+        let type_id = M::_type_id();
+        if state
+            .subscribers
+            .iter()
+            .any(|s| s.addr == addr && s.type_id == type_id)
+        {
+            return false;
+        }
+
+        state.subscribers.push(Subscriber {
+            addr,
+            type_id,
+            accepts: M::_is_supertype_of,
+        });
+        true
+    }
+
+    /// Returns `false` if the actor isn't subscribed.
+    pub(crate) fn unsubscribe<M: Message>(&self, topic: Topic, addr: Addr) -> bool {
+        let type_id = M::_type_id();
+        self.remove(topic, |s| s.addr == addr && s.type_id == type_id)
+    }
+
+    /// Removes all subscriptions of the actor, called on termination.
+    pub(crate) fn unsubscribe_all(&self, addr: Addr) {
+        self.topics.write().retain(|_, state| {
+            state.subscribers.retain(|s| s.addr != addr);
+            !state.subscribers.is_empty()
+        });
+    }
+
+    /// Removes the actor from the topic, called if it's closed.
+    pub(crate) fn remove_closed(&self, topic: Topic, addr: Addr) {
+        self.remove(topic, |s| s.addr == addr);
+    }
+
+    /// Returns subscribers accepting the message type, `None` if there are no
+    /// such ones.
+    pub(crate) fn subscribers(&self, topic: Topic, type_id: MessageTypeId) -> Option<Subscribers> {
+        let topics = self.topics.read();
+        let state = topics.get(&topic)?;
+
+        let mut addrs = SmallVec::new();
+        for subscriber in &state.subscribers {
+            // An actor can be subscribed to both `M` and `AnyMessage`.
+            if (subscriber.accepts)(type_id) && !addrs.contains(&subscriber.addr) {
+                addrs.push(subscriber.addr);
+            }
+        }
+
+        (!addrs.is_empty()).then(|| Subscribers {
+            dumper: state.dumper.clone(),
+            addrs,
+        })
+    }
+
+    fn remove(&self, topic: Topic, f: impl Fn(&Subscriber) -> bool) -> bool {
+        let mut topics = self.topics.write();
+        let state = ward!(topics.get_mut(&topic), return false);
+
+        let len = state.subscribers.len();
+        state.subscribers.retain(|s| !f(s));
+        let removed = state.subscribers.len() != len;
+
+        if state.subscribers.is_empty() {
+            topics.remove(&topic);
+        }
+
+        removed
+    }
+}
@@ -0,0 +1,473 @@
+//! Bidirectional channels between two actors, see [`Context::open_channel()`].
+//!
+//! A channel is a long-lived conversation: both sides send messages of their
+//! own type, each direction is backpressured by a credit-based window.
+//! Messages flow through ordinary mailboxes, so they are handled by `msg!`
+//! as usual, but carry the channel id ([`Envelope::channel_id()`]), which is
+//! also written to dumps to group them.
+//!
+//! Channels work inside the current node only.
+//!
+//! # Example
+//! ```
+//! # use elfo_core as elfo;
+//! # async fn exec(mut ctx: elfo::Context, venue: elfo::Addr) {
+//! use elfo::{channel::ChannelOpened, message, msg};
+//!
+//! #[message]
+//! struct PlaceOrder(u32);
+//!
+//! #[message]
+//! struct OrderFilled(u32);
+//!
+//! // The client side.
+//! let orders = ctx
+//!     .open_channel::<PlaceOrder, OrderFilled>(venue)
+//!     .window(32)
+//!     .await
+//!     .unwrap();
+//!
+//! // Waits if the venue has 32 unhandled orders.
+//! orders.send(PlaceOrder(42)).await.unwrap();
+//!
+//! // The venue side.
+//! while let Some(envelope) = ctx.recv().await {
+//!     msg!(match envelope {
+//!         (opened @ ChannelOpened { .. }, token) => {
+//!             let fills = opened.take::<OrderFilled, PlaceOrder>().unwrap();
+//!             ctx.respond(token, ());
+//!             // Store `fills` in the actor's state.
+//!         }
+//!         PlaceOrder(no) => { /* ... */ }
+//!     });
+//! }
+//! # }
+//! ```
+//!
+//! [`Context::open_channel()`]: crate::Context::open_channel()
+
+use std::{
+    fmt,
+    future::IntoFuture,
+    marker::PhantomData,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::future::BoxFuture;
+use idr_ebr::EbrGuard;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, TryAcquireError};
+use tracing::trace;
+
+use crate::{
+    address_book::AddressBook,
+    context::dumper,
+    dumping::Dump,
+    envelope::{Envelope, MessageKind},
+    errors::{RequestError, SendError, TrySendError},
+    message,
+    message::Message,
+    object::Object,
+    scope,
+    tracing::TraceId,
+    Addr, Context, MoveOwnership,
+};
+
+const DEFAULT_WINDOW: u32 = 64;
+
+const OPENER: usize = 0;
+const ACCEPTOR: usize = 1;
+
+// === ChannelId ===
+
+/// The identifier of a channel, unique inside the current node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ChannelId(NonZeroU64);
+
+impl ChannelId {
+    fn generate() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        Self(NonZeroU64::new(id).expect("channel ids are exhausted"))
+    }
+
+    #[inline]
+    pub fn to_u64(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// === Messages ===
+
+/// Delivered to the peer passed to [`Context::open_channel()`].
+///
+/// Call [`ChannelOpened::take()`] to get the handle for the reverse direction
+/// and respond to accept the channel. If the request is ignored, the opener
+/// gets an error and the channel is closed.
+///
+/// [`Context::open_channel()`]: crate::Context::open_channel()
+#[message(ret = ())]
+pub struct ChannelOpened {
+    pub id: ChannelId,
+    end: MoveOwnership<ChannelEnd>,
+}
+
+impl ChannelOpened {
+    /// Takes the handle, `S` and `R` are swapped relative to the opener.
+    /// Next calls return `None`.
+    ///
+    /// # Panics
+    ///
+    /// If types don't match ones passed to [`Context::open_channel()`].
+    ///
+    /// [`Context::open_channel()`]: crate::Context::open_channel()
+    pub fn take<S: Message, R: Message>(&self) -> Option<Channel<S, R>> {
+        let mut end = self.end.take()?;
+        end.addr = scope::with(|scope| scope.actor());
+        assert!(
+            end.shared.types == [R::_type_id(), S::_type_id()],
+            "types of the channel {} don't match ones of the opener",
+            self.id,
+        );
+        Some(Channel::new(end))
+    }
+}
+
+/// Delivered to the other side once the channel is closed.
+/// After that, sending to the channel fails.
+#[message]
+pub struct ChannelClosed {
+    pub id: ChannelId,
+    pub reason: CloseReason,
+}
+
+/// The reason of [`ChannelClosed`].
+#[message(part)]
+#[derive(PartialEq, Eq)]
+pub enum CloseReason {
+    /// Closed by [`Channel::close()`].
+    Closed(String),
+    /// The handle is dropped, usually because the actor has terminated.
+    Dropped,
+}
+
+// === ChannelBuilder ===
+
+/// Returned by [`Context::open_channel()`].
+///
+/// Awaiting the builder opens the channel, see [`ChannelOpened`].
+///
+/// [`Context::open_channel()`]: crate::Context::open_channel()
+#[must_use = "the channel isn't opened until awaited"]
+pub struct ChannelBuilder<'c, C, K, S, R> {
+    context: &'c Context<C, K>,
+    recipient: Addr,
+    window: u32,
+    marker: PhantomData<fn(S) -> R>,
+}
+
+impl<'c, C, K, S, R> ChannelBuilder<'c, C, K, S, R> {
+    pub(crate) fn new(context: &'c Context<C, K>, recipient: Addr) -> Self {
+        Self {
+            context,
+            recipient,
+            window: DEFAULT_WINDOW,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets how many messages can be sent in each direction before the other
+    /// side handles them. `64` by default.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn window(mut self, window: u32) -> Self {
+        assert!(window > 0, "the window must be positive");
+        self.window = window;
+        self
+    }
+}
+
+impl<'c, C, K, S, R> IntoFuture for ChannelBuilder<'c, C, K, S, R>
+where
+    C: Send + Sync + 'static,
+    K: Send + Sync,
+    S: Message,
+    R: Message,
+{
+    type IntoFuture = BoxFuture<'c, Self::Output>;
+    type Output = Result<Channel<S, R>, RequestError>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let window = self.window as usize;
+        let shared = Arc::new(Shared {
+            id: ChannelId::generate(),
+            types: [S::_type_id(), R::_type_id()],
+            credits: [Semaphore::new(window), Semaphore::new(window)],
+            is_closed: AtomicBool::new(false),
+        });
+
+        let book = self.context.book();
+        let mut opener = ChannelEnd {
+            shared: shared.clone(),
+            side: OPENER,
+            addr: self.context.addr(),
+            // Replaced with the responder if sent to a group.
+            peer: self.recipient,
+            book: book.clone(),
+        };
+        let acceptor = ChannelEnd {
+            shared,
+            side: ACCEPTOR,
+            // Set once the handle is taken.
+            addr: Addr::NULL,
+            peer: opener.addr,
+            book: book.clone(),
+        };
+
+        Box::pin(async move {
+            let opened = ChannelOpened {
+                id: opener.shared.id,
+                end: acceptor.into(),
+            };
+
+            let request = self.context.request_to(self.recipient, opened);
+            match request.resolve_with_responder().await {
+                Ok(((), responder)) => {
+                    opener.peer = responder;
+                    Ok(Channel::new(opener))
+                }
+                Err(err) => {
+                    // The peer isn't notified, it has rejected the channel.
+                    opener.shutdown();
+                    Err(err)
+                }
+            }
+        })
+    }
+}
+
+// === Channel ===
+
+/// A handle of one side of the channel, sends `S` and receives `R`.
+///
+/// Dropping the handle closes the channel with [`CloseReason::Dropped`].
+pub struct Channel<S, R> {
+    end: ChannelEnd,
+    marker: PhantomData<fn(S) -> R>,
+}
+
+impl<S: Message, R: Message> Channel<S, R> {
+    fn new(end: ChannelEnd) -> Self {
+        Self {
+            end,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> ChannelId {
+        self.end.shared.id
+    }
+
+    /// Returns the address of the other side.
+    #[inline]
+    pub fn peer(&self) -> Addr {
+        self.end.peer
+    }
+
+    /// Returns `true` if the channel is closed by any side.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.end.shared.is_closed.load(Ordering::Acquire)
+    }
+
+    /// Sends the message to the other side.
+    /// Waits if the window is exhausted or the peer's mailbox is full.
+    ///
+    /// Returns `Err` if the channel is closed or the peer is dead.
+    ///
+    /// # Cancel safety
+    ///
+    /// If cancelled, the message isn't sent.
+    pub async fn send(&self, message: S) -> Result<(), SendError<S>> {
+        let credits = &self.end.shared.credits[self.end.peer_side()];
+        let Ok(permit) = credits.acquire().await else {
+            return Err(SendError(message));
+        };
+        // Returned by the envelope once the peer handles it.
+        permit.forget();
+
+        let envelope = self.end.pack(message);
+        let res = {
+            let guard = EbrGuard::new();
+            let entry = self.end.book.get(self.end.peer, &guard);
+            let object = ward!(entry, return Err(SendError(unpack(envelope))));
+            Object::send(object, Addr::NULL, envelope)
+        }
+        .await;
+
+        res.map_err(|err| SendError(unpack(err.0)))
+    }
+
+    /// Tries to send the message to the other side.
+    /// Returns [`TrySendError::Full`] if the window is exhausted or the peer's
+    /// mailbox is full, [`TrySendError::Closed`] if the channel is closed.
+    pub fn try_send(&self, message: S) -> Result<(), TrySendError<S>> {
+        let credits = &self.end.shared.credits[self.end.peer_side()];
+        match credits.try_acquire() {
+            Ok(permit) => permit.forget(),
+            Err(TryAcquireError::NoPermits) => return Err(TrySendError::Full(message)),
+            Err(TryAcquireError::Closed) => return Err(TrySendError::Closed(message)),
+        }
+
+        let envelope = self.end.pack(message);
+        let guard = EbrGuard::new();
+        let entry = self.end.book.get(self.end.peer, &guard);
+        let object = ward!(entry, return Err(TrySendError::Closed(unpack(envelope))));
+        object
+            .try_send(Addr::NULL, envelope)
+            .map_err(|err| err.map(unpack))
+    }
+
+    /// Closes the channel, the other side receives [`ChannelClosed`] with
+    /// [`CloseReason::Closed`]. Messages sent before are still delivered.
+    pub fn close(self, reason: impl Into<String>) {
+        self.end.close(CloseReason::Closed(reason.into()));
+    }
+}
+
+impl<S, R> fmt::Debug for Channel<S, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("id", &self.end.shared.id)
+            .finish()
+    }
+}
+
+#[cold]
+fn unpack<M: Message>(envelope: Envelope) -> M {
+    envelope.unpack().expect("invalid message").0
+}
+
+// === ChannelEnd ===
+
+struct Shared {
+    id: ChannelId,
+    /// Types of messages sent by the opener and the acceptor.
+    types: [crate::message::MessageTypeId; 2],
+    /// Credits for sending to the opener and to the acceptor.
+    credits: [Semaphore; 2],
+    is_closed: AtomicBool,
+}
+
+/// An untyped side of the channel.
+struct ChannelEnd {
+    shared: Arc<Shared>,
+    side: usize,
+    addr: Addr,
+    peer: Addr,
+    book: AddressBook,
+}
+
+impl ChannelEnd {
+    fn peer_side(&self) -> usize {
+        1 - self.side
+    }
+
+    fn pack<M: Message>(&self, message: M) -> Envelope {
+        let recipient = self.peer;
+        let kind = MessageKind::regular(self.addr);
+
+        trace!(to = %recipient, channel = %self.shared.id, "> {:?}", message);
+        if let Some(permit) = dumper().acquire_m(&message) {
+            let mut dump = Dump::message_to(&message, &kind, recipient);
+            dump.channel_id = Some(self.shared.id);
+            permit.record(dump);
+        }
+
+        let mut envelope = Envelope::new(message, kind);
+        envelope.set_channel(ChannelMark {
+            shared: self.shared.clone(),
+            side: self.peer_side(),
+        });
+        envelope
+    }
+
+    /// Closes the channel, returns `false` if it's already closed.
+    fn shutdown(&self) -> bool {
+        if self.shared.is_closed.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+
+        // Wakes up senders waiting for credits.
+        for credits in &self.shared.credits {
+            credits.close();
+        }
+
+        true
+    }
+
+    fn close(&self, reason: CloseReason) {
+        if !self.shutdown() {
+            return;
+        }
+
+        let recipient = self.peer;
+        let message = ChannelClosed {
+            id: self.shared.id,
+            reason,
+        };
+
+        trace!(to = %recipient, "> {:?}", message);
+
+        // Handles can be dropped outside the actor system.
+        let trace_id = scope::try_trace_id().unwrap_or_else(TraceId::generate);
+        let kind = MessageKind::regular(self.addr);
+        let envelope = Envelope::with_trace_id(message, kind, trace_id);
+
+        let guard = EbrGuard::new();
+        let object = ward!(self.book.get(recipient, &guard));
+        // The notification must not be lost because of a full mailbox.
+        let _ = object.unbounded_send(Addr::NULL, envelope);
+    }
+}
+
+impl Drop for ChannelEnd {
+    fn drop(&mut self) {
+        self.close(CloseReason::Dropped);
+    }
+}
+
+// === ChannelMark ===
+
+/// Attached to envelopes sent over the channel.
+/// Returns the credit to the sender once the envelope is unpacked or dropped.
+pub(crate) struct ChannelMark {
+    shared: Arc<Shared>,
+    side: usize,
+}
+
+impl ChannelMark {
+    pub(crate) fn id(&self) -> ChannelId {
+        self.shared.id
+    }
+}
+
+impl Drop for ChannelMark {
+    fn drop(&mut self) {
+        self.shared.credits[self.side].add_permits(1);
+    }
+}
@@ -0,0 +1,60 @@
+//! [Config].
+//!
+//! [Config]: CircuitBreakerConfig
+
+use fxhash::FxHashMap;
+use serde::Deserialize;
+
+use crate::config::Duration;
+
+/// Circuit breakers for requests sent by actors of the group.
+/// Every breaker protects an edge between the group and a destination group.
+///
+/// Requests to destinations without a configured breaker aren't affected.
+/// Regular messages (and requests sent by `send()`) aren't affected at all.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.circuit_breaker.destinations.another_group.failure_ratio = 0.5
+/// system.circuit_breaker.destinations.another_group.min_requests = 20
+/// system.circuit_breaker.destinations.another_group.open_duration = "10s"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Breakers by names of destination groups.
+    pub destinations: FxHashMap<String, EdgeConfig>,
+}
+
+/// Thresholds of a circuit breaker.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EdgeConfig {
+    /// The ratio of failed requests to open the circuit.
+    ///
+    /// A request is considered failed if it hasn't reached the destination
+    /// or has been ignored by it (e.g. the responder is closed or failed).
+    ///
+    /// `0.5` by default.
+    pub failure_ratio: f64,
+    /// The number of the last requests the ratio is calculated over.
+    /// The circuit isn't opened until this number of requests is made.
+    ///
+    /// `20` by default.
+    pub min_requests: usize,
+    /// How long the circuit stays open before a probe request is allowed.
+    ///
+    /// `10s` by default.
+    pub open_duration: Duration,
+}
+
+impl Default for EdgeConfig {
+    fn default() -> Self {
+        Self {
+            failure_ratio: 0.5,
+            min_requests: 20,
+            open_duration: Duration::from_secs(10),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use fxhash::FxHashMap;
+use metrics::increment_counter;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use self::config::{CircuitBreakerConfig, EdgeConfig};
+use crate::messages::CircuitState;
+
+pub mod config;
+
+/// Circuit breakers of requests sent by actors of one group.
+#[derive(Default)]
+pub(crate) struct CircuitBreakers {
+    // Allows to avoid locking if nothing is configured or forced.
+    is_enabled: AtomicBool,
+    edges: Mutex<FxHashMap<String, Edge>>,
+}
+
+/// A permission to send a request to the destination.
+/// Must be passed to `complete()` or `cancel()`.
+pub(crate) struct Ticket {
+    destination: String,
+    is_probe: bool,
+}
+
+impl CircuitBreakers {
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn configure(&self, config: &CircuitBreakerConfig) {
+        let mut edges = self.edges.lock();
+
+        edges.retain(|destination, edge| {
+            let new_config = config.destinations.get(destination);
+            if edge.config.as_ref() != new_config {
+                // The state is reset on any changes of thresholds.
+                edge.config = new_config.cloned();
+                edge.reset();
+            }
+            edge.config.is_some() || edge.forced.is_some()
+        });
+
+        for (destination, config) in &config.destinations {
+            edges.entry(destination.clone()).or_insert_with(|| Edge {
+                config: Some(config.clone()),
+                ..Edge::default()
+            });
+        }
+
+        self.is_enabled.store(!edges.is_empty(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn force(&self, destination: &str, state: Option<CircuitState>) {
+        let mut edges = self.edges.lock();
+
+        let edge = edges.entry(destination.into()).or_default();
+        edge.forced = state;
+        edge.reset();
+
+        let state = match state {
+            Some(CircuitState::Open) => "open",
+            Some(CircuitState::Closed) => "closed",
+            None => "auto",
+        };
+        info!(destination, state, "circuit is forced");
+        increment_counter!("elfo_circuit_transitions_total",
+            "destination" => destination.to_string(),
+            "state" => state,
+        );
+
+        if edge.config.is_none() && edge.forced.is_none() {
+            edges.remove(destination);
+        }
+
+        self.is_enabled.store(!edges.is_empty(), Ordering::Relaxed);
+    }
+
+    /// Returns destinations with states forced by `SetCircuit`.
+    pub(crate) fn forced(&self) -> Vec<(String, CircuitState)> {
+        let edges = self.edges.lock();
+        let mut forced = edges
+            .iter()
+            .filter_map(|(destination, edge)| Some((destination.clone(), edge.forced?)))
+            .collect::<Vec<_>>();
+
+        forced.sort_by(|a, b| a.0.cmp(&b.0));
+        forced
+    }
+
+    /// Returns `None` if requests to the destination aren't tracked,
+    /// `Some(Err(()))` if the circuit is open.
+    pub(crate) fn admit(&self, destination: &str) -> Option<Result<Ticket, ()>> {
+        let mut edges = self.edges.lock();
+        let edge = edges.get_mut(destination)?;
+
+        let result = edge.admit(destination).map(|is_probe| {
+            is_probe.map(|is_probe| Ticket {
+                destination: destination.into(),
+                is_probe,
+            })
+        });
+
+        if result.is_err() {
+            increment_counter!("elfo_circuit_rejected_requests_total",
+                "destination" => destination.to_string(),
+            );
+        }
+
+        result.transpose()
+    }
+
+    /// Records the outcome of the request.
+    pub(crate) fn complete(&self, ticket: Ticket, is_success: bool) {
+        let mut edges = self.edges.lock();
+        let edge = ward!(edges.get_mut(&ticket.destination));
+        edge.complete(&ticket, is_success);
+    }
+
+    /// Releases the ticket if the request hasn't been sent.
+    pub(crate) fn cancel(&self, ticket: Ticket) {
+        let mut edges = self.edges.lock();
+        let edge = ward!(edges.get_mut(&ticket.destination));
+
+        if let State::HalfOpen { is_probing } = &mut edge.state {
+            if ticket.is_probe {
+                *is_probing = false;
+            }
+        }
+    }
+}
+
+// === Edge ===
+
+#[derive(Default)]
+struct Edge {
+    // `None` if only forced by `SetCircuit`.
+    config: Option<EdgeConfig>,
+    forced: Option<CircuitState>,
+    state: State,
+    // `true` for failed requests.
+    outcomes: VecDeque<bool>,
+    failures: usize,
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Closed,
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        is_probing: bool,
+    },
+}
+
+impl Edge {
+    fn reset(&mut self) {
+        self.state = State::Closed;
+        self.outcomes.clear();
+        self.failures = 0;
+    }
+
+    /// Returns whether the request is a probe or `None` if it isn't tracked.
+    fn admit(&mut self, destination: &str) -> Result<Option<bool>, ()> {
+        match self.forced {
+            Some(CircuitState::Open) => return Err(()),
+            Some(CircuitState::Closed) => return Ok(None),
+            None if self.config.is_none() => return Ok(None),
+            None => {}
+        }
+
+        match &mut self.state {
+            State::Closed => Ok(Some(false)),
+            State::Open { until } if Instant::now() < *until => Err(()),
+            State::Open { .. } => {
+                self.transit(destination, State::HalfOpen { is_probing: true });
+                Ok(Some(true))
+            }
+            State::HalfOpen { is_probing: true } => Err(()),
+            State::HalfOpen { is_probing } => {
+                *is_probing = true;
+                Ok(Some(true))
+            }
+        }
+    }
+
+    fn complete(&mut self, ticket: &Ticket, is_success: bool) {
+        if self.forced.is_some() {
+            return;
+        }
+
+        let config = ward!(&self.config);
+        let window = config.min_requests.max(1);
+        let failure_ratio = config.failure_ratio;
+        let open_duration = *config.open_duration;
+        let destination = &ticket.destination;
+
+        if ticket.is_probe {
+            if let State::HalfOpen { .. } = self.state {
+                if is_success {
+                    self.reset();
+                    self.transit(destination, State::Closed);
+                } else {
+                    let until = Instant::now() + open_duration;
+                    self.transit(destination, State::Open { until });
+                }
+            }
+            return;
+        }
+
+        // Outcomes of requests sent before opening the circuit are ignored.
+        if !matches!(self.state, State::Closed) {
+            return;
+        }
+
+        self.outcomes.push_back(!is_success);
+        self.failures += usize::from(!is_success);
+
+        while self.outcomes.len() > window {
+            let failed = self.outcomes.pop_front().unwrap_or_default();
+            self.failures -= usize::from(failed);
+        }
+
+        let ratio = self.failures as f64 / self.outcomes.len() as f64;
+        if self.outcomes.len() == window && ratio >= failure_ratio {
+            let until = Instant::now() + open_duration;
+            self.transit(destination, State::Open { until });
+        }
+    }
+
+    fn transit(&mut self, destination: &str, state: State) {
+        let name = match state {
+            State::Closed => {
+                info!(destination, "circuit is closed");
+                "closed"
+            }
+            State::Open { .. } => {
+                warn!(
+                    destination,
+                    failures = self.failures,
+                    requests = self.outcomes.len(),
+                    "circuit is open, requests are rejected"
+                );
+                "open"
+            }
+            State::HalfOpen { .. } => {
+                info!(destination, "circuit is half-open, probing");
+                "half_open"
+            }
+        };
+
+        increment_counter!("elfo_circuit_transitions_total",
+            "destination" => destination.to_string(),
+            "state" => name,
+        );
+
+        self.state = state;
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    cell::Cell,
+    fmt,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use metrics::Key;
+use once_cell::sync::OnceCell;
+use serde::{
+    de::{self, DeserializeOwned, Deserializer, SeqAccess, Visitor},
+    ser::{self, SerializeTuple, Serializer},
+    Deserialize, Serialize,
+};
+
+use super::{Algorithm, MESSAGE_LABELS};
+use crate::{
+    errors::DecompressError,
+    scope::{self, SerdeMode},
+};
+
+// === Compressed ===
+
+/// A field of a message, which is compressed when the message is serialized,
+/// i.e. sent over network or dumped. Useful for huge fields, which compress
+/// well, to avoid compressing whole messages with tiny metadata.
+///
+/// * Local sends don't serialize messages, so the value is passed as is.
+/// * Serialization encodes the value by `postcard` and compresses it. The
+///   algorithm is taken from [`CompressionConfig`] of the serializing actor's
+///   group, unless overridden by [`Compressed::with_algorithm()`], and
+///   stored along with compressed bytes.
+/// * Deserialization keeps compressed bytes, the value is decompressed on the
+///   first access by [`Compressed::get()`] and cached.
+///
+/// Clones share both forms, so the value is compressed once per [serde mode]
+/// even if the message is sent to several nodes and dumped. Received values
+/// are serialized as received until decompressed, so they can be forwarded
+/// and dumped without decompression. Thus, fields of `T` hidden in dumps
+/// (e.g. by [`dumping::hide()`]) are dumped as is for such values.
+///
+/// Metrics, labeled by the message's protocol and name:
+/// * `elfo_compression_input_bytes_total`
+/// * `elfo_compression_output_bytes_total`
+/// * `elfo_compression_ratio` (histogram of output/input)
+///
+/// [`CompressionConfig`]: super::config::CompressionConfig
+/// [serde mode]: crate::scope::with_serde_mode
+/// [`dumping::hide()`]: crate::dumping::hide
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// use elfo::{compression::Algorithm, Compressed};
+///
+/// #[elfo::message]
+/// struct Snapshot {
+///     version: u64,
+///     data: Compressed<Vec<u8>>,
+/// }
+///
+/// let snapshot = Snapshot {
+///     version: 1,
+///     data: Compressed::new(vec![0; 1024]).with_algorithm(Algorithm::Deflate, 9),
+/// };
+/// assert_eq!(snapshot.data.get().len(), 1024);
+/// ```
+pub struct Compressed<T> {
+    /// Overrides the config, see `with_algorithm()`.
+    algorithm: Option<(Algorithm, u32)>,
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    value: OnceCell<T>,
+    /// Compressed forms by serde modes (see `slot()`), because `T` can be
+    /// serialized differently in them, e.g. hide fields in dumps.
+    packed: [OnceCell<Packed>; 3],
+}
+
+impl<T> Compressed<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            algorithm: None,
+            inner: Arc::new(Inner {
+                value: OnceCell::with_value(value),
+                packed: Default::default(),
+            }),
+        }
+    }
+
+    /// Overrides the algorithm and the level set by [`CompressionConfig`].
+    ///
+    /// [`CompressionConfig`]: super::config::CompressionConfig
+    pub fn with_algorithm(mut self, algorithm: Algorithm, level: u32) -> Self {
+        self.algorithm = Some((algorithm, level));
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Compressed<T> {
+    /// Returns the value, decompressing it on the first call if the value
+    /// has been received.
+    ///
+    /// # Panics
+    /// If the value cannot be decompressed, see [`Compressed::try_get()`].
+    #[inline]
+    pub fn get(&self) -> &T {
+        match self.try_get() {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Returns the value, decompressing it on the first call if the value
+    /// has been received.
+    pub fn try_get(&self) -> Result<&T, DecompressError> {
+        self.inner.value.get_or_try_init(|| {
+            // Either the value or any packed form is always present.
+            let packed = self.inner.packed.iter().find_map(OnceCell::get);
+            packed.expect("no value").unpack()
+        })
+    }
+
+    /// Returns the value, decompressing it if the value has been received.
+    ///
+    /// # Panics
+    /// If the value cannot be decompressed, see [`Compressed::try_get()`].
+    pub fn into_inner(self) -> T
+    where
+        T: Clone,
+    {
+        self.get();
+
+        match Arc::try_unwrap(self.inner) {
+            Ok(inner) => inner.value.into_inner().expect("no value"),
+            Err(inner) => inner.value.get().expect("no value").clone(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Compressed<T> {
+    fn pack(&self) -> Result<Packed, String> {
+        let value = self.try_get().map_err(|err| err.to_string())?;
+        let raw = postcard::to_stdvec(value).map_err(|err| err.to_string())?;
+
+        let (algorithm, level) = self.algorithm.unwrap_or_else(|| {
+            let config = scope::try_with(|scope| scope.compression()).unwrap_or_default();
+            (config.algorithm, config.level)
+        });
+
+        let bytes = compress(algorithm, level, &raw).map_err(|err| err.to_string())?;
+        report(raw.len(), bytes.len());
+        Ok(Packed { algorithm, bytes })
+    }
+}
+
+impl<T> From<T> for Compressed<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> Clone for Compressed<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            algorithm: self.algorithm,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq + DeserializeOwned> PartialEq for Compressed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        matches!((self.try_get(), other.try_get()), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Compressed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(value) = self.inner.value.get() {
+            return value.fmt(f);
+        }
+
+        let packed = self.inner.packed.iter().find_map(OnceCell::get);
+        let packed = packed.expect("no value");
+        write!(
+            f,
+            "<compressed by {:?}, {} bytes>",
+            packed.algorithm,
+            packed.bytes.len()
+        )
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Serialize for Compressed<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let cell = &self.inner.packed[slot(scope::serde_mode())];
+
+        // Received values are serialized as received until decompressed.
+        let packed = match cell.get() {
+            Some(packed) => packed,
+            None if self.inner.value.get().is_none() => {
+                let packed = self.inner.packed.iter().find_map(OnceCell::get);
+                packed.expect("no value")
+            }
+            None => cell
+                .get_or_try_init(|| self.pack())
+                .map_err(ser::Error::custom)?,
+        };
+
+        packed.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Compressed<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let packed = Packed::deserialize(deserializer)?;
+        let inner = Inner {
+            value: OnceCell::new(),
+            packed: Default::default(),
+        };
+
+        // Reused if the message is serialized in the same mode, e.g. forwarded.
+        let _ = inner.packed[slot(scope::serde_mode())].set(packed);
+
+        Ok(Self {
+            algorithm: None,
+            inner: Arc::new(inner),
+        })
+    }
+}
+
+fn slot(mode: SerdeMode) -> usize {
+    match mode {
+        SerdeMode::Normal => 0,
+        SerdeMode::Dumping => 1,
+        SerdeMode::Network => 2,
+    }
+}
+
+// === Packed ===
+
+struct Packed {
+    algorithm: Algorithm,
+    bytes: Vec<u8>,
+}
+
+impl Packed {
+    fn unpack<T: DeserializeOwned>(&self) -> Result<T, DecompressError> {
+        let error = |reason: String| DecompressError { reason };
+
+        let raw = decompress(self.algorithm, &self.bytes).map_err(error)?;
+        postcard::from_bytes(&raw).map_err(|err| error(err.to_string()))
+    }
+}
+
+// Serialized as `(algorithm, bytes)`.
+impl Serialize for Packed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Bytes<'a>(&'a [u8]);
+
+        impl Serialize for Bytes<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.algorithm)?;
+        tuple.serialize_element(&Bytes(&self.bytes))?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Packed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, PackedVisitor)
+    }
+}
+
+struct PackedVisitor;
+
+impl<'de> Visitor<'de> for PackedVisitor {
+    type Value = Packed;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a tuple of an algorithm and compressed bytes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let algorithm = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let bytes = seq
+            .next_element::<ByteBuf>()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Packed {
+            algorithm,
+            bytes: bytes.0,
+        })
+    }
+}
+
+/// Accepts both bytes and sequences (e.g. arrays in JSON).
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(ByteBuf(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(ByteBuf(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+fn compress(algorithm: Algorithm, level: u32, raw: &[u8]) -> std::io::Result<Vec<u8>> {
+    match algorithm {
+        Algorithm::Lz4 => Ok(lz4_flex::compress_prepend_size(raw)),
+        Algorithm::Deflate => {
+            let level = flate2::Compression::new(level.min(9));
+            let mut encoder = DeflateEncoder::new(Vec::new(), level);
+            encoder.write_all(raw)?;
+            encoder.finish()
+        }
+    }
+}
+
+fn decompress(algorithm: Algorithm, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match algorithm {
+        Algorithm::Lz4 => lz4_flex::decompress_size_prepended(bytes).map_err(|err| err.to_string()),
+        Algorithm::Deflate => {
+            let mut raw = Vec::new();
+            DeflateDecoder::new(bytes)
+                .read_to_end(&mut raw)
+                .map_err(|err| err.to_string())?;
+            Ok(raw)
+        }
+    }
+}
+
+// === Metrics ===
+
+fn report(input: usize, output: usize) {
+    let recorder = ward!(metrics::try_recorder());
+    let labels = MESSAGE_LABELS.with(Cell::get);
+
+    let key = Key::from_static_parts("elfo_compression_input_bytes_total", labels);
+    recorder.increment_counter(&key, input as u64);
+    let key = Key::from_static_parts("elfo_compression_output_bytes_total", labels);
+    recorder.increment_counter(&key, output as u64);
+
+    if input > 0 {
+        let key = Key::from_static_parts("elfo_compression_ratio", labels);
+        recorder.record_histogram(&key, output as f64 / input as f64);
+    }
+}
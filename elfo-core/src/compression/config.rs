@@ -0,0 +1,45 @@
+//! [Config].
+//!
+//! [Config]: CompressionConfig
+
+use serde::Deserialize;
+
+use super::Algorithm;
+
+/// How [`Compressed`] fields are compressed when serialized, unless
+/// overridden by the field itself.
+///
+/// Fields are compressed by actors serializing messages (e.g. network
+/// workers and dumpers), so it's node-wide in practice and usually set in
+/// the `[common]` section.
+///
+/// # Example
+/// ```toml
+/// [common]
+/// system.compression.algorithm = "Deflate"
+/// system.compression.level = 9
+/// ```
+///
+/// [`Compressed`]: super::Compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// The compression algorithm.
+    ///
+    /// `Lz4` by default.
+    pub algorithm: Algorithm,
+    /// The compression level in `[0, 9]`, higher values are slower but
+    /// compress better. Ignored by `Lz4`.
+    ///
+    /// `6` by default.
+    pub level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Lz4,
+            level: 6,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Compression of individual fields of messages, see [`Compressed`].
+
+use std::cell::Cell;
+
+use metrics::Label;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize, Serializer};
+
+use self::config::CompressionConfig;
+use crate::message::Message;
+
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub use self::compressed::Compressed;
+
+pub mod config;
+
+#[cfg(feature = "compression")]
+mod compressed;
+
+// === Algorithm ===
+
+/// An algorithm used to compress [`Compressed`] fields.
+// Stored along with compressed bytes, so new variants must be appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Algorithm {
+    /// LZ4, fast with a moderate ratio. Has no levels.
+    Lz4,
+    /// DEFLATE, slower with a better ratio.
+    Deflate,
+}
+
+// === Metrics ===
+
+static UNKNOWN_LABELS: &[Label] = &[Label::from_static_parts("message", "<Unknown>")];
+
+thread_local! {
+    static MESSAGE_LABELS: Cell<&'static [Label]> = const { Cell::new(UNKNOWN_LABELS) };
+}
+
+/// Labels metrics of fields compressed while running the function.
+pub(crate) fn with_message_labels<R>(labels: &'static [Label], f: impl FnOnce() -> R) -> R {
+    // We use a guard here to restore the previous labels even on panics.
+    struct Guard(&'static [Label]);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            MESSAGE_LABELS.with(|cell| cell.set(self.0));
+        }
+    }
+
+    let prev = MESSAGE_LABELS.with(|cell| cell.replace(labels));
+    let _guard = Guard(prev);
+    f()
+}
+
+/// Serializes the message, labeling metrics of its compressed fields.
+pub(crate) struct Labeled<M>(pub(crate) M);
+
+impl<M: Message> Serialize for Labeled<M> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        with_message_labels(self.0.labels(), || self.0.serialize(serializer))
+    }
+}
+
+// === CompressionControl ===
+
+/// The config of the group, see `Scope::compression()`.
+#[derive(Default)]
+pub(crate) struct CompressionControl(Mutex<CompressionConfig>);
+
+impl CompressionControl {
+    pub(crate) fn configure(&self, config: &CompressionConfig) {
+        *self.0.lock() = *config;
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn get(&self) -> CompressionConfig {
+        *self.0.lock()
+    }
+}
@@ -0,0 +1,195 @@
+//! Concurrent handling of envelopes, see [`ActorGroup::concurrency()`].
+//!
+//! [`ActorGroup::concurrency()`]: crate::ActorGroup::concurrency
+
+use std::{
+    any::Any,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{future::CatchUnwind, stream::FuturesUnordered, FutureExt, StreamExt};
+use metrics::{decrement_gauge, increment_gauge, Key, Label};
+use pin_project::pin_project;
+use tracing::error;
+
+use elfo_utils::time::Instant;
+
+use crate::{envelope::Envelope, message::Message, messages, panics, scope, tracing::TraceId};
+
+/// Limits the number of envelopes handled concurrently by
+/// [`Context::recv_concurrent()`] in every actor of the group.
+///
+/// [`Context::recv_concurrent()`]: crate::Context::recv_concurrent
+#[derive(Debug, Clone, Copy)]
+pub struct Concurrency {
+    limit: usize,
+    isolate_panics: bool,
+}
+
+impl Concurrency {
+    /// Allows at most `limit` envelopes to be handled at the same time.
+    ///
+    /// # Panics
+    ///
+    /// If `limit` is zero.
+    pub fn new(limit: usize) -> Self {
+        assert!(limit > 0, "the limit must be positive");
+        Self {
+            limit,
+            isolate_panics: true,
+        }
+    }
+
+    /// Fails the actor if any handler panics.
+    ///
+    /// By default, a panic is logged and only the panicked handler is dropped,
+    /// other handlers continue to run.
+    pub fn propagate_panics(mut self) -> Self {
+        self.isolate_panics = false;
+        self
+    }
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl From<usize> for Concurrency {
+    fn from(limit: usize) -> Self {
+        Self::new(limit)
+    }
+}
+
+/// Returns `true` for messages that must be handled by the actor itself after
+/// all in-flight handlers are finished, e.g. `ConfigUpdated` or `Terminate`.
+pub(crate) fn is_barrier(envelope: &Envelope) -> bool {
+    envelope.message().protocol() == messages::Ping.protocol()
+}
+
+/// Handlers being executed by [`Context::recv_concurrent()`].
+///
+/// [`Context::recv_concurrent()`]: crate::Context::recv_concurrent
+pub(crate) struct InFlight<F> {
+    config: Concurrency,
+    handlers: FuturesUnordered<Handler<F>>,
+}
+
+impl<F: Future<Output = ()>> InFlight<F> {
+    pub(crate) fn new(config: Concurrency) -> Self {
+        Self {
+            config,
+            handlers: FuturesUnordered::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.handlers.len() >= self.config.limit
+    }
+
+    pub(crate) fn push(&mut self, envelope: Envelope, handler: impl FnOnce(Envelope) -> F) {
+        let trace_id = envelope.trace_id();
+        let (name, labels) = {
+            let message = envelope.message();
+            (message.name(), message.labels())
+        };
+
+        self.handlers.push(Handler {
+            fut: AssertUnwindSafe(handler(envelope)).catch_unwind(),
+            trace_id,
+            name,
+            labels,
+            start_time: Instant::now(),
+        });
+
+        increment_gauge!("elfo_in_flight_handlers", 1.);
+    }
+
+    /// Waits for any handler to finish.
+    /// Must be called only if there are in-flight handlers.
+    pub(crate) async fn next(&mut self) {
+        debug_assert!(!self.is_empty());
+
+        let Some(result) = self.handlers.next().await else {
+            return;
+        };
+
+        decrement_gauge!("elfo_in_flight_handlers", 1.);
+
+        if let Err((name, payload)) = result {
+            // Propagated panics are captured by the supervisor.
+            if !self.config.isolate_panics {
+                error!(name, "handler panicked");
+                panic::resume_unwind(payload);
+            }
+
+            let panic = panics::capture(&*payload);
+            error!(
+                message = "handler panicked",
+                name,
+                error = %panic,
+                payload = panic.payload.as_ref().map(tracing::field::display),
+            );
+            if let Some(backtrace) = &panic.backtrace {
+                backtrace.log();
+            }
+            panic.dump();
+        }
+    }
+
+    /// Waits for all handlers to finish.
+    pub(crate) async fn drain(&mut self) {
+        while !self.is_empty() {
+            self.next().await;
+        }
+    }
+}
+
+impl<F> Drop for InFlight<F> {
+    fn drop(&mut self) {
+        // Handlers are dropped if the actor is cancelled or fails.
+        if !self.handlers.is_empty() {
+            decrement_gauge!("elfo_in_flight_handlers", self.handlers.len() as f64);
+        }
+    }
+}
+
+type HandlerResult = Result<(), (&'static str, Box<dyn Any + Send>)>;
+
+#[pin_project]
+struct Handler<F> {
+    #[pin]
+    fut: CatchUnwind<AssertUnwindSafe<F>>,
+    trace_id: TraceId,
+    name: &'static str,
+    labels: &'static [Label],
+    start_time: Instant,
+}
+
+impl<F: Future<Output = ()>> Future for Handler<F> {
+    type Output = HandlerResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        // Attribute logs and sent messages to the triggering envelope.
+        scope::set_trace_id(*this.trace_id);
+        let result = ready!(this.fut.poll(cx));
+
+        if let Some(recorder) = metrics::try_recorder() {
+            let key = Key::from_static_parts("elfo_concurrent_handling_time_seconds", this.labels);
+            let value = Instant::now().secs_f64_since(*this.start_time);
+            recorder.record_histogram(&key, value);
+        }
+
+        Poll::Ready(result.map_err(|payload| (*this.name, payload)))
+    }
+}
@@ -4,17 +4,21 @@
 
 use std::{
     any::{Any, TypeId},
+    cell::RefCell,
     fmt, mem,
     ops::Deref,
     str::FromStr,
     sync::Arc,
 };
 
-use derive_more::From;
+use derive_more::{From, Into};
 use serde::{de, de::value::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
 use serde_value::{Value, ValueDeserializer};
 
-use crate::{local::Local, panic};
+use crate::{local::Local, panics};
+
+mod rejection;
+mod strict;
 
 /// Represents any user-defined config.
 ///
@@ -55,6 +59,8 @@ struct Decoded {
     system: Arc<SystemConfig>,
     // Actually, we store `Arc<Arc<C>>` here.
     user: Arc<dyn Any + Send + Sync>,
+    // Values deserialized into `Secret<_>`, used to mask them.
+    secrets: Arc<Vec<Value>>,
 }
 
 impl AnyConfig {
@@ -82,8 +88,38 @@ impl AnyConfig {
         &self.decoded.as_ref().expect("must be decoded").system
     }
 
+    /// Checks whether the provided path is specified explicitly in the config.
+    pub(crate) fn contains(&self, path: &[&str]) -> bool {
+        self.get(path.iter().copied()).is_some()
+    }
+
+    /// Returns the boolean located at the dot-separated path,
+    /// e.g. `exporter.enabled`.
+    ///
+    /// Returns `false` if the path isn't specified or isn't a boolean.
+    /// Useful for conditions of [`Local::mount_if()`].
+    ///
+    /// [`Local::mount_if()`]: crate::topology::Local::mount_if
+    pub fn get_bool(&self, path: &str) -> bool {
+        matches!(self.get(path.split('.')), Some(Value::Bool(true)))
+    }
+
+    fn get<'a>(&self, path: impl IntoIterator<Item = &'a str>) -> Option<&Value> {
+        let mut value = &*self.raw;
+
+        for key in path {
+            let Value::Map(map) = value else {
+                return None;
+            };
+
+            value = map.get(&Value::String(key.into()))?;
+        }
+
+        Some(value)
+    }
+
     pub(crate) fn decode<C: Config>(&self) -> Result<AnyConfig, String> {
-        match panic::sync_catch(|| self.do_decode::<C>()) {
+        match panics::sync_catch(|| self.do_decode::<C>()) {
             Ok(Ok(config)) => Ok(config),
             Ok(Err(err)) => Err(err),
             Err(panic) => Err(panic),
@@ -95,9 +131,7 @@ impl AnyConfig {
 
         let system_decoded = if let Value::Map(map) = &mut raw {
             if let Some(system_raw) = map.remove(&Value::String("system".into())) {
-                let de = ValueDeserializer::<DeError>::new(system_raw);
-                let config = SystemConfig::deserialize(de).map_err(|err| err.to_string())?;
-                Arc::new(config)
+                Arc::new(strict::decode_system(system_raw)?)
             } else {
                 Default::default()
             }
@@ -106,12 +140,15 @@ impl AnyConfig {
         };
 
         // Handle the special case of default config.
-        let user_decoded = if TypeId::of::<C>() == TypeId::of::<()>() {
-            Arc::new(Arc::new(())) as Arc<_>
+        let (user_decoded, secrets) = if TypeId::of::<C>() == TypeId::of::<()>() {
+            (Arc::new(Arc::new(())) as Arc<_>, Vec::new())
         } else {
-            let de = ValueDeserializer::<DeError>::new(raw);
-            let config = C::deserialize(de).map_err(|err| err.to_string())?;
-            Arc::new(Arc::new(config)) as Arc<_>
+            let de = ValueDeserializer::<DeError>::new(raw.clone());
+            let (config, secrets) = collect_secrets(|| C::deserialize(de));
+            let config = config.map_err(|err| {
+                rejection::describe::<C>(raw, err, &system_decoded.config_rejection)
+            })?;
+            (Arc::new(Arc::new(config)) as Arc<_>, secrets)
         };
 
         Ok(AnyConfig {
@@ -119,10 +156,30 @@ impl AnyConfig {
             decoded: Some(Local::from(Decoded {
                 system: system_decoded,
                 user: user_decoded,
+                secrets: Arc::new(secrets),
             })),
         })
     }
 
+    /// Returns the raw config with values of `Secret` fields replaced with
+    /// `"<secret>"`. Other values equal to secrets are masked too.
+    pub(crate) fn masked(&self) -> AnyConfig {
+        let mut raw = (*self.raw).clone();
+
+        if let Some(decoded) = &self.decoded {
+            if !decoded.secrets.is_empty() {
+                mask(&mut raw, &decoded.secrets);
+            }
+        }
+
+        Self::from_value(raw)
+    }
+
+    /// Checks whether raw configs are equal, regardless of decoding.
+    pub(crate) fn raw_eq(&self, other: &AnyConfig) -> bool {
+        Arc::ptr_eq(&self.raw, &other.raw) || self.raw == other.raw
+    }
+
     pub(crate) fn into_value(mut self) -> Value {
         mem::replace(Arc::make_mut(&mut self.raw), Value::Unit)
     }
@@ -188,6 +245,43 @@ impl<'de> Deserializer<'de> for AnyConfig {
     }
 }
 
+thread_local! {
+    // `Some` while decoding a config, see `Secret::deserialize()`.
+    static SECRETS: RefCell<Option<Vec<Value>>> = const { RefCell::new(None) };
+}
+
+fn collect_secrets<R>(f: impl FnOnce() -> R) -> (R, Vec<Value>) {
+    // Resets the collector even if `f` panics.
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            SECRETS.with(|secrets| secrets.borrow_mut().take());
+        }
+    }
+
+    SECRETS.with(|secrets| *secrets.borrow_mut() = Some(Vec::new()));
+    let guard = Guard;
+    let result = f();
+    let secrets = SECRETS.with(|secrets| secrets.borrow_mut().take().unwrap_or_default());
+    drop(guard);
+    (result, secrets)
+}
+
+fn mask(value: &mut Value, secrets: &[Value]) {
+    if secrets.contains(value) {
+        *value = Value::String("<secret>".into());
+        return;
+    }
+
+    match value {
+        Value::Map(map) => map.values_mut().for_each(|value| mask(value, secrets)),
+        Value::Seq(seq) => seq.iter_mut().for_each(|value| mask(value, secrets)),
+        Value::Option(Some(value)) | Value::Newtype(value) => mask(value, secrets),
+        _ => {}
+    }
+}
+
 // === SystemConfig ===
 
 pub mod system {
@@ -202,10 +296,15 @@ pub mod system {
     use super::*;
 
     pub use crate::{
-        dumping::config as dumping, logging::config as logging, mailbox::config as mailbox,
+        circuit_breaking::config as circuit_breaker, compression::config as compression,
+        deprecation::config as deprecation, dumping::config as dumping, logging::config as logging,
+        mailbox::config as mailbox, rate_limiting::config as rate_limiter,
         restarting::config as restart_policy, telemetry::config as telemetry,
+        tracing::config as tracing, ttl::config as ttl,
     };
 
+    pub use super::rejection::RejectionConfig;
+
     /// The `system.*` section in configs.
     ///
     /// # Example
@@ -215,9 +314,22 @@ pub mod system {
     /// system.logging.max_level = "Warn"
     /// system.dumping.max_rate = 10_000
     /// system.telemetry.per_actor_key = true
+    /// system.tracing.detailed_budget = "5/m"
     /// system.restart_policy.when = "Never"
+    /// system.circuit_breaker.destinations.another_group.min_requests = 20
+    /// system.rate_limiter.destinations.another_group = "50/s"
+    /// system.compression.algorithm = "Lz4"
+    /// system.ttl."*" = "5s"
+    /// system.deprecation.strict = true
+    /// system.config_rejection.max_size = "512B"
+    /// system.allow_duplicate_messages = false
+    /// system.spawn_concurrency = 32
+    /// system.spawn_requests_first = true
+    /// system.strict_config = true
+    /// system.max_exec_future_size = "64KiB"
+    /// system.strict_exec_future_size = true
     /// ```
-    #[derive(Debug, Default, Deserialize)]
+    #[derive(Debug, Deserialize)]
     #[serde(default)]
     pub struct SystemConfig {
         /// Mailbox configuration.
@@ -228,13 +340,136 @@ pub mod system {
         pub dumping: dumping::DumpingConfig,
         /// Telemetry configuration.
         pub telemetry: telemetry::TelemetryConfig,
+        /// Tracing configuration.
+        pub tracing: tracing::TracingConfig,
         /// Restarting configuration.
         pub restart_policy: restart_policy::RestartPolicyConfig,
+        /// Circuit breakers configuration.
+        pub circuit_breaker: circuit_breaker::CircuitBreakerConfig,
+        /// Outbound rate limiters configuration.
+        pub rate_limiter: rate_limiter::RateLimiterConfig,
+        /// Compression of `Compressed` fields configuration.
+        pub compression: compression::CompressionConfig,
+        /// Default TTLs of messages and requests configuration.
+        pub ttl: ttl::TtlConfig,
+        /// Reporting of deprecated messages configuration.
+        pub deprecation: deprecation::DeprecationConfig,
+        /// Describing of rejected configs configuration.
+        pub config_rejection: RejectionConfig,
+        /// Allows messages with the same protocol and name to be defined
+        /// several times in the binary, otherwise the config is rejected.
+        /// Intended only for transitional builds, `false` by default.
+        ///
+        /// It's node-wide, so usually set in the `[common]` section.
+        pub allow_duplicate_messages: bool,
+        /// Limits the number of actors of the group in the `Initializing`
+        /// status. Further spawns are queued, their messages are held in
+        /// (bounded) mailboxes until actors are started. Unlimited by default.
+        pub spawn_concurrency: Option<usize>,
+        /// Actors spawned to handle requests jump the queue of spawns
+        /// limited by `spawn_concurrency`. `false` by default.
+        pub spawn_requests_first: bool,
+        /// Rejects the config if the `system` section contains unknown keys,
+        /// e.g. misspelled ones. `false` by default.
+        ///
+        /// If it's set in the `[common]` section, the configurer also rejects
+        /// unknown top-level sections, which don't match any group.
+        pub strict_config: bool,
+        /// Warns if the future returned by the group's exec function is
+        /// larger, such futures are stored inline for every actor and moved
+        /// around at spawn. Consider [`ActorGroup::boxed_exec()`] for them.
+        ///
+        /// `64KiB` by default.
+        ///
+        /// [`ActorGroup::boxed_exec()`]: crate::ActorGroup::boxed_exec
+        pub max_exec_future_size: ByteSize,
+        /// Rejects the config instead of warning if the exec future is larger
+        /// than `max_exec_future_size`, so it fails startup. `false` by
+        /// default.
+        pub strict_exec_future_size: bool,
+    }
+
+    impl Default for SystemConfig {
+        fn default() -> Self {
+            Self {
+                mailbox: Default::default(),
+                logging: Default::default(),
+                dumping: Default::default(),
+                telemetry: Default::default(),
+                tracing: Default::default(),
+                restart_policy: Default::default(),
+                circuit_breaker: Default::default(),
+                rate_limiter: Default::default(),
+                compression: Default::default(),
+                ttl: Default::default(),
+                deprecation: Default::default(),
+                config_rejection: Default::default(),
+                allow_duplicate_messages: false,
+                spawn_concurrency: None,
+                spawn_requests_first: false,
+                strict_config: false,
+                max_exec_future_size: ByteSize::new(64 * 1024),
+                strict_exec_future_size: false,
+            }
+        }
     }
 }
 
 pub(crate) use system::SystemConfig;
 
+// === Extended ===
+
+/// A group's config extended by extra fields, which are decoded from the same
+/// section. Useful for presets adding their own settings to groups, whose
+/// config types are defined by authors of the groups.
+///
+/// Dereferences to the original config, the extra part is available by
+/// [`Extended::extra()`]. Note that `#[serde(deny_unknown_fields)]` cannot be
+/// used on both parts, because they share keys of the section.
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// # use elfo_core as elfo;
+/// use elfo::{config::{Config, Extended}, ActorGroup};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Limits {
+///     max_batch: usize,
+/// }
+///
+/// fn with_limits<R, C: Config>(group: ActorGroup<R, C>) -> ActorGroup<R, Extended<C, Limits>> {
+///     group.config()
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Extended<C, E> {
+    #[serde(flatten)]
+    user: C,
+    #[serde(flatten)]
+    extra: E,
+}
+
+impl<C, E> Extended<C, E> {
+    /// Returns the extra part of the config.
+    pub fn extra(&self) -> &E {
+        &self.extra
+    }
+
+    pub fn into_inner(self) -> (C, E) {
+        (self.user, self.extra)
+    }
+}
+
+impl<C, E> Deref for Extended<C, E> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
 // === Secret ===
 
 /// A secret value that is not printed in logs or debug output.
@@ -297,7 +532,19 @@ impl<T: FromStr> FromStr for Secret<T> {
 
 impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        T::deserialize(deserializer).map(Self)
+        let is_collecting = SECRETS.with(|secrets| secrets.borrow().is_some());
+        if !is_collecting {
+            return T::deserialize(deserializer).map(Self);
+        }
+
+        // Remember the value to mask it in `AnyConfig::masked()`.
+        let value = Value::deserialize(deserializer)?;
+        SECRETS.with(|secrets| {
+            if let Some(secrets) = &mut *secrets.borrow_mut() {
+                secrets.push(value.clone());
+            }
+        });
+        T::deserialize(ValueDeserializer::<D::Error>::new(value)).map(Self)
     }
 }
 
@@ -310,3 +557,709 @@ impl<T: Serialize> Serialize for Secret<T> {
         }
     }
 }
+
+// === Duration ===
+
+const DURATION_HINT: &str = r#"expected e.g. "100ms" or "2h 30m" (units: ns, us, ms, s, m, h, d)"#;
+
+/// A duration in a human-readable form, e.g. `"100ms"` or `"2h 30m"`.
+///
+/// * `Deserialize` expects a string of numbers with units: `ns`, `us`, `ms`,
+///   `s`, `m`, `h`, `d` (long forms like `sec` or `hours` are also allowed).
+///   Numbers can be fractional, e.g. `"1.5s"`.
+/// * `Serialize`, `Debug` and `Display` produce the same form, e.g. `"2h 30m"`.
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// # use elfo_core::config;
+/// #[derive(Deserialize)]
+/// struct MyConfig {
+///     timeout: config::Duration,
+/// }
+///
+/// # let config: MyConfig = toml::from_str(r#"timeout = "1m 30s""#).unwrap();
+/// let timeout: std::time::Duration = config.timeout.into();
+/// # assert_eq!(timeout.as_secs(), 90);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, From, Into)]
+pub struct Duration(std::time::Duration);
+
+impl Duration {
+    pub const fn new(duration: std::time::Duration) -> Self {
+        Self(duration)
+    }
+
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(std::time::Duration::from_secs(secs))
+    }
+
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(std::time::Duration::from_millis(millis))
+    }
+
+    pub const fn into_inner(self) -> std::time::Duration {
+        self.0
+    }
+}
+
+impl Deref for Duration {
+    type Target = std::time::Duration;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut nanos = self.0.as_nanos();
+        if nanos == 0 {
+            return f.write_str("0s");
+        }
+
+        let mut is_first = true;
+        for &(unit, unit_nanos) in DURATION_UNITS {
+            let value = nanos / unit_nanos;
+            if value == 0 {
+                continue;
+            }
+
+            if !mem::take(&mut is_first) {
+                f.write_str(" ")?;
+            }
+            write!(f, "{value}{unit}")?;
+            nanos %= unit_nanos;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Duration {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_duration(s)
+            .map(|nanos| Self(nanos_to_duration(nanos)))
+            .map_err(|reason| ParseUnitError::new("duration", s, reason, DURATION_HINT))
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer, DURATION_HINT)
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Units used for formatting, in descending order.
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+fn duration_unit(unit: &str) -> Option<u128> {
+    let unit = match unit {
+        "ns" | "nsec" | "nanos" => "ns",
+        "us" | "µs" | "usec" | "micros" => "us",
+        "ms" | "msec" | "millis" => "ms",
+        "s" | "sec" | "secs" | "second" | "seconds" => "s",
+        "m" | "min" | "mins" | "minute" | "minutes" => "m",
+        "h" | "hr" | "hrs" | "hour" | "hours" => "h",
+        "d" | "day" | "days" => "d",
+        _ => return None,
+    };
+
+    DURATION_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, nanos)| *nanos)
+}
+
+/// Parses a sequence of `<number><unit>`, returns nanoseconds.
+fn parse_duration(s: &str) -> Result<u128, String> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("empty string".into());
+    }
+
+    let mut total = 0u128;
+    while !rest.is_empty() {
+        let (number, tail) = split_number(rest);
+        if !has_digits(number) {
+            return Err(format!("expected a number at {rest:?}"));
+        }
+
+        let tail = tail.trim_start();
+        let unit_len = tail.find(|c: char| c.is_whitespace() || c.is_ascii_digit());
+        let (unit, tail) = tail.split_at(unit_len.unwrap_or(tail.len()));
+        if unit.is_empty() {
+            return Err(format!("no unit after {number:?}"));
+        }
+
+        let unit_nanos = duration_unit(unit).ok_or_else(|| format!("unknown unit {unit:?}"))?;
+        total = scale(number, unit_nanos)
+            .and_then(|nanos| total.checked_add(nanos))
+            .filter(|total| *total <= MAX_DURATION_NANOS)
+            .ok_or("too large")?;
+
+        rest = tail.trim_start();
+    }
+
+    Ok(total)
+}
+
+const MAX_DURATION_NANOS: u128 = u64::MAX as u128 * 1_000_000_000 + 999_999_999;
+
+fn nanos_to_duration(nanos: u128) -> std::time::Duration {
+    debug_assert!(nanos <= MAX_DURATION_NANOS);
+    let secs = (nanos / 1_000_000_000) as u64;
+    let subsec_nanos = (nanos % 1_000_000_000) as u32;
+    std::time::Duration::new(secs, subsec_nanos)
+}
+
+// === ByteSize ===
+
+const BYTE_SIZE_HINT: &str =
+    r#"expected e.g. "512KiB" or "1.5GB" (units: B, KB, MB, GB, TB, PB, KiB, MiB, GiB, TiB, PiB)"#;
+
+/// A size in bytes in a human-readable form, e.g. `"512KiB"` or `"1.5GB"`.
+///
+/// * `Deserialize` expects a number (in bytes) or a string of a number with
+///   a unit: `B`, `KB`, `MB`, `GB`, `TB`, `PB` (powers of 1000) or `KiB`,
+///   `MiB`, `GiB`, `TiB`, `PiB` (powers of 1024). Units are case-insensitive.
+///   Numbers can be fractional, e.g. `"1.5GB"`.
+/// * `Serialize`, `Debug` and `Display` produce a string with the largest
+///   unit representing the size exactly, e.g. `"512KiB"` or `"1500MB"`.
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// # use elfo_core::config::ByteSize;
+/// #[derive(Deserialize)]
+/// struct MyConfig {
+///     max_size: ByteSize,
+/// }
+///
+/// # let config: MyConfig = toml::from_str(r#"max_size = "1.5KiB""#).unwrap();
+/// let max_size: u64 = config.max_size.as_u64();
+/// # assert_eq!(max_size, 1536);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, From, Into)]
+pub struct ByteSize(u64);
+
+impl ByteSize {
+    pub const fn new(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the size as `usize`, saturating on 32-bit platforms.
+    pub fn as_usize(self) -> usize {
+        usize::try_from(self.0).unwrap_or(usize::MAX)
+    }
+}
+
+impl fmt::Debug for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = u128::from(self.0);
+        let (unit, unit_bytes) = BYTE_SIZE_UNITS
+            .iter()
+            .find(|(_, unit_bytes)| bytes % unit_bytes == 0 && bytes >= *unit_bytes)
+            .copied()
+            .unwrap_or(("B", 1));
+
+        write!(f, "{}{unit}", bytes / unit_bytes)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_byte_size(s)
+            .map(Self)
+            .map_err(|reason| ParseUnitError::new("byte size", s, reason, BYTE_SIZE_HINT))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a number of bytes or a string, {BYTE_SIZE_HINT}")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(ByteSize(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                u64::try_from(v)
+                    .map(ByteSize)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Units used for formatting, in descending order.
+const BYTE_SIZE_UNITS: &[(&str, u128)] = &[
+    ("PiB", 1 << 50),
+    ("PB", 1_000_000_000_000_000),
+    ("TiB", 1 << 40),
+    ("TB", 1_000_000_000_000),
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("KB", 1_000),
+];
+
+fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let (number, unit) = split_number(s.trim());
+    if !has_digits(number) {
+        return Err("expected a number".into());
+    }
+
+    let unit = unit.trim_start();
+    let unit_bytes = if unit.is_empty() || unit.eq_ignore_ascii_case("b") {
+        1
+    } else {
+        let prefix = unit.strip_suffix(['b', 'B']).unwrap_or(unit);
+        BYTE_SIZE_UNITS
+            .iter()
+            .find(|(name, _)| name.trim_end_matches('B').eq_ignore_ascii_case(prefix))
+            .map(|(_, bytes)| *bytes)
+            .ok_or_else(|| format!("unknown unit {unit:?}"))?
+    };
+
+    scale(number, unit_bytes)
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(|| "too large".into())
+}
+
+// === Rate ===
+
+const RATE_HINT: &str = r#"expected e.g. "100/s", "5/m" or "10/100ms""#;
+
+/// A rate in a human-readable form, e.g. `"100/s"` or `"10/100ms"`.
+///
+/// * `Deserialize` expects a string `<count>/<period>`, where `count` is
+///   an integer and `period` is a [`Duration`], `1` can be omitted, e.g.
+///   `"5/m"` is the same as `"5/1m"`. The count can have the `k` (thousands)
+///   or `M` (millions) suffix, e.g. `"50k/s"`.
+/// * `Serialize`, `Debug` and `Display` produce the same form.
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// # use elfo_core::config::Rate;
+/// #[derive(Deserialize)]
+/// struct MyConfig {
+///     max_rate: Rate,
+/// }
+///
+/// # let config: MyConfig = toml::from_str(r#"max_rate = "30/m""#).unwrap();
+/// let max_rate: f64 = config.max_rate.per_second();
+/// # assert_eq!(max_rate, 0.5);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rate {
+    count: u64,
+    period: std::time::Duration,
+}
+
+impl Rate {
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    pub fn new(count: u64, period: std::time::Duration) -> Self {
+        assert!(!period.is_zero(), "the period must be non-zero");
+        Self { count, period }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn period(&self) -> std::time::Duration {
+        self.period
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.count as f64 / self.period.as_secs_f64()
+    }
+}
+
+impl fmt::Debug for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let period = Duration(self.period).to_string();
+        // Omit `1` in single units, e.g. `100/s` instead of `100/1s`.
+        let period = match period.strip_prefix('1') {
+            Some(unit) if unit.chars().all(|c| c.is_ascii_alphabetic()) => unit,
+            _ => &period,
+        };
+
+        write!(f, "{}/{period}", self.count)
+    }
+}
+
+impl FromStr for Rate {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_rate(s).map_err(|reason| ParseUnitError::new("rate", s, reason, RATE_HINT))
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer, RATE_HINT)
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn parse_rate(s: &str) -> Result<Rate, String> {
+    let (count, period) = s.split_once('/').ok_or("no `/`")?;
+
+    let count = count.trim();
+    let (digits, multiplier) = if let Some(digits) = count.strip_suffix('k') {
+        (digits, 1_000)
+    } else if let Some(digits) = count.strip_suffix('M') {
+        (digits, 1_000_000)
+    } else {
+        (count, 1)
+    };
+    let count = digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|digits| digits.checked_mul(multiplier))
+        .ok_or_else(|| {
+            if split_number(digits).0.contains('.') {
+                "the count must be an integer, use a longer period instead".into()
+            } else {
+                format!("invalid count {count:?}")
+            }
+        })?;
+
+    let period = period.trim();
+    let period = if period.starts_with(|c: char| c.is_ascii_digit()) {
+        parse_duration(period)?
+    } else {
+        parse_duration(&format!("1{period}"))?
+    };
+
+    if period == 0 {
+        return Err("the period must be non-zero".into());
+    }
+
+    Ok(Rate::new(count, nanos_to_duration(period)))
+}
+
+// === Parsing utils ===
+
+/// An error of parsing [`Duration`], [`ByteSize`] or [`Rate`].
+#[derive(Debug, Clone)]
+pub struct ParseUnitError(String);
+
+impl ParseUnitError {
+    fn new(what: &str, input: &str, reason: String, hint: &str) -> Self {
+        Self(format!("invalid {what} {input:?}: {reason}, {hint}"))
+    }
+}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+fn deserialize_from_str<'de, D, T>(deserializer: D, hint: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = ParseUnitError>,
+{
+    struct Visitor<T>(&'static str, std::marker::PhantomData<T>);
+
+    impl<T: FromStr<Err = ParseUnitError>> de::Visitor<'_> for Visitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a string, {}", self.0)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.parse().map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_str(Visitor(hint, std::marker::PhantomData))
+}
+
+/// Splits a leading decimal number (possibly fractional) from the rest.
+fn split_number(s: &str) -> (&str, &str) {
+    let mut has_dot = false;
+    let len = s
+        .find(|c: char| {
+            if c == '.' && !has_dot {
+                has_dot = true;
+                return false;
+            }
+            !c.is_ascii_digit()
+        })
+        .unwrap_or(s.len());
+
+    s.split_at(len)
+}
+
+fn has_digits(number: &str) -> bool {
+    number.bytes().any(|b| b.is_ascii_digit())
+}
+
+/// Multiplies a decimal number by the unit exactly, truncating the remainder.
+fn scale(number: &str, unit: u128) -> Option<u128> {
+    let (int, frac) = number.split_once('.').unwrap_or((number, ""));
+    let int = if int.is_empty() {
+        0
+    } else {
+        int.parse::<u128>().ok()?
+    };
+    let mut value = int.checked_mul(unit)?;
+
+    // Only significant digits of the fraction matter.
+    let mut denominator = 1u128;
+    let mut numerator = 0u128;
+    for digit in frac.bytes().take(18) {
+        numerator = numerator * 10 + u128::from(digit - b'0');
+        denominator *= 10;
+    }
+    value = value.checked_add(numerator.checked_mul(unit)? / denominator)?;
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn duration() {
+        let parse = |s: &str| s.parse::<Duration>().map(Duration::into_inner);
+        assert_eq!(parse("100ms").unwrap(), StdDuration::from_millis(100));
+        assert_eq!(parse("2h 30m").unwrap(), StdDuration::from_secs(9000));
+        assert_eq!(parse("2h30m").unwrap(), StdDuration::from_secs(9000));
+        assert_eq!(parse("1.5s").unwrap(), StdDuration::from_millis(1500));
+        assert_eq!(parse(".5s").unwrap(), StdDuration::from_millis(500));
+        assert_eq!(parse("3 sec").unwrap(), StdDuration::from_secs(3));
+        assert_eq!(parse("0s").unwrap(), StdDuration::ZERO);
+        assert_eq!(parse("1.0000000001s").unwrap(), StdDuration::from_secs(1));
+        assert_eq!(
+            parse(&format!("{}s", u64::MAX)).unwrap().as_secs(),
+            u64::MAX
+        );
+
+        for invalid in ["", "10", "s", "5 parsecs", ".s", &format!("{}1s", u64::MAX)] {
+            let err = parse(invalid).unwrap_err().to_string();
+            assert!(err.contains(&format!("{invalid:?}")), "{err}");
+            assert!(err.contains(r#"e.g. "100ms""#), "{err}");
+        }
+
+        let format = |d: StdDuration| Duration::new(d).to_string();
+        assert_eq!(format(StdDuration::ZERO), "0s");
+        assert_eq!(format(StdDuration::from_secs(9000)), "2h 30m");
+        assert_eq!(
+            format(StdDuration::from_nanos(86_400_000_000_001)),
+            "1d 1ns"
+        );
+    }
+
+    #[test]
+    fn byte_size() {
+        let parse = |s: &str| s.parse::<ByteSize>().map(ByteSize::as_u64);
+        assert_eq!(parse("512KiB").unwrap(), 512 * 1024);
+        assert_eq!(parse("512 kib").unwrap(), 512 * 1024);
+        assert_eq!(parse("1.5GB").unwrap(), 1_500_000_000);
+        assert_eq!(parse("1.5k").unwrap(), 1500);
+        assert_eq!(parse("42").unwrap(), 42);
+        assert_eq!(parse("42B").unwrap(), 42);
+        assert_eq!(parse("0").unwrap(), 0);
+        assert_eq!(parse("1.0001B").unwrap(), 1);
+        assert_eq!(parse(&u64::MAX.to_string()).unwrap(), u64::MAX);
+
+        for invalid in ["", "KiB", "5 XB", "-1", &format!("{}1", u64::MAX), "16EiB"] {
+            let err = parse(invalid).unwrap_err().to_string();
+            assert!(err.contains(&format!("{invalid:?}")), "{err}");
+            assert!(err.contains(r#"e.g. "512KiB""#), "{err}");
+        }
+
+        let format = |bytes| ByteSize::new(bytes).to_string();
+        assert_eq!(format(0), "0B");
+        assert_eq!(format(1023), "1023B");
+        assert_eq!(format(512 * 1024), "512KiB");
+        assert_eq!(format(1_500_000_000), "1500MB");
+        assert_eq!(format(u64::MAX), format!("{}B", u64::MAX));
+
+        // Numbers are also accepted.
+        let size: ByteSize = serde_json::from_str("1024").unwrap();
+        assert_eq!(size.as_u64(), 1024);
+        let size: ByteSize = serde_json::from_str(r#""1KiB""#).unwrap();
+        assert_eq!(size.as_u64(), 1024);
+    }
+
+    #[test]
+    fn rate() {
+        let parse = |s: &str| s.parse::<Rate>().map(|r| (r.count(), r.period()));
+        assert_eq!(parse("100/s").unwrap(), (100, StdDuration::from_secs(1)));
+        assert_eq!(parse("5 / m").unwrap(), (5, StdDuration::from_secs(60)));
+        assert_eq!(
+            parse("10/100ms").unwrap(),
+            (10, StdDuration::from_millis(100))
+        );
+        assert_eq!(parse("0/h").unwrap(), (0, StdDuration::from_secs(3600)));
+        assert_eq!("30/m".parse::<Rate>().unwrap().per_second(), 0.5);
+        assert_eq!(parse("50k/s").unwrap(), (50_000, StdDuration::from_secs(1)));
+        assert_eq!(
+            parse("2M/m").unwrap(),
+            (2_000_000, StdDuration::from_secs(60))
+        );
+
+        for invalid in [
+            "", "100", "/s", "0.5/s", "1/0s", "1/parsec", "-1/s", "k/s", "1.5k/s", "1K/s",
+        ] {
+            let err = parse(invalid).unwrap_err().to_string();
+            assert!(err.contains(&format!("{invalid:?}")), "{err}");
+            assert!(err.contains(r#"e.g. "100/s""#), "{err}");
+        }
+
+        let format = |count, period| Rate::new(count, period).to_string();
+        assert_eq!(format(100, StdDuration::from_secs(1)), "100/s");
+        assert_eq!(format(100, StdDuration::from_secs(10)), "100/10s");
+        assert_eq!(format(5, StdDuration::from_millis(1)), "5/ms");
+        assert_eq!(format(5, StdDuration::from_secs(90)), "5/1m 30s");
+    }
+
+    #[test]
+    fn serde() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Sample {
+            duration: Duration,
+            size: ByteSize,
+            rate: Rate,
+        }
+
+        let sample: Sample = toml::from_str(
+            r#"
+            duration = "1m 30s"
+            size = "64KiB"
+            rate = "100/s"
+            "#,
+        )
+        .unwrap();
+
+        let serialized = toml::to_string(&sample).unwrap();
+        assert!(
+            serialized.contains(r#"duration = "1m 30s""#),
+            "{serialized}"
+        );
+        assert!(serialized.contains(r#"size = "64KiB""#), "{serialized}");
+        assert!(serialized.contains(r#"rate = "100/s""#), "{serialized}");
+        assert_eq!(toml::from_str::<Sample>(&serialized).unwrap(), sample);
+
+        let err = toml::from_str::<Sample>(r#"duration = "5 parsecs""#).unwrap_err();
+        let err = err.to_string();
+        assert!(
+            err.contains(r#"invalid duration "5 parsecs": unknown unit "parsecs""#),
+            "{err}"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn duration_roundtrip(secs in prop::num::u64::ANY, nanos in 0..1_000_000_000u32) {
+            let duration = Duration::new(StdDuration::new(secs, nanos));
+            prop_assert_eq!(duration.to_string().parse::<Duration>().unwrap(), duration);
+        }
+
+        #[test]
+        fn byte_size_roundtrip(bytes in prop::num::u64::ANY, shift in 0..60u32) {
+            for bytes in [bytes, bytes >> shift, (bytes >> shift) << shift] {
+                let size = ByteSize::new(bytes);
+                prop_assert_eq!(size.to_string().parse::<ByteSize>().unwrap(), size);
+            }
+        }
+
+        #[test]
+        fn byte_size_fraction(int in 0..1_000_000u64, frac in 0..1000u64) {
+            let size = format!("{int}.{frac:03}KB").parse::<ByteSize>().unwrap();
+            prop_assert_eq!(size.as_u64(), int * 1000 + frac);
+        }
+
+        #[test]
+        fn rate_roundtrip(count in prop::num::u64::ANY, millis in 1..u64::MAX) {
+            let rate = Rate::new(count, StdDuration::from_millis(millis));
+            prop_assert_eq!(rate.to_string().parse::<Rate>().unwrap(), rate);
+        }
+    }
+}
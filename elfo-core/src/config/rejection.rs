@@ -0,0 +1,586 @@
+//! Describes why the group's config is rejected, see [`RejectionConfig`].
+//!
+//! The reason contains the serde message, the location of the offending value
+//! and the value itself, bounded by depth and size and with secrets replaced.
+//! The location is found by decoding the config again, tracking the path.
+
+use std::{cell::RefCell, collections::btree_map, fmt::Write, iter::Enumerate, vec};
+
+use serde::{
+    de::{self, value::Error as DeError, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_value::{Value, ValueDeserializer};
+
+use super::{collect_secrets, ByteSize, Config};
+
+const ELIDED: &str = "\"…\"";
+const SECRET: &str = "\"<secret>\"";
+
+/// How rejected configs are described in [`ConfigRejected`] and logs.
+///
+/// Only the offending value is embedded, e.g. the field of the wrong type or
+/// the section missing a required field. Nested values deeper than
+/// `max_depth` and ones after `max_size` are elided as `"…"`. Values of
+/// [`Secret`] fields and fields, which keys contain any of `redacted_keys`
+/// (case-insensitive), are replaced with `"<secret>"`.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.config_rejection.max_depth = 2
+/// system.config_rejection.max_size = "256B"
+/// system.config_rejection.redacted_keys = ["password", "dsn"]
+/// ```
+///
+/// [`ConfigRejected`]: crate::messages::ConfigRejected
+/// [`Secret`]: super::Secret
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RejectionConfig {
+    /// The depth of the offending value, after which nested values are
+    /// elided.
+    ///
+    /// `3` by default.
+    pub max_depth: usize,
+    /// The approximate size of the rendered value and the serde message.
+    ///
+    /// `1KiB` by default.
+    pub max_size: ByteSize,
+    /// Substrings of keys, which values are never embedded.
+    ///
+    /// `["password", "secret", "token", "credential"]` by default.
+    pub redacted_keys: Vec<String>,
+}
+
+impl Default for RejectionConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_size: ByteSize::new(1024),
+            redacted_keys: ["password", "secret", "token", "credential"]
+                .map(Into::into)
+                .into(),
+        }
+    }
+}
+
+/// Describes the error of decoding `raw` into `C`.
+#[cold]
+pub(super) fn describe<C: Config>(raw: Value, error: DeError, config: &RejectionConfig) -> String {
+    let location = RefCell::new(None);
+    let (_, secrets) = collect_secrets(|| {
+        C::deserialize(Tracked {
+            value: raw.clone(),
+            path: Vec::new(),
+            location: &location,
+        })
+    });
+
+    // The path isn't found if decoding depends on something else, e.g. time.
+    let path = location.into_inner().unwrap_or_default();
+    let renderer = Renderer::new(config, &secrets);
+
+    let mut reason = renderer.scrub(error.to_string());
+    truncate(&mut reason, config.max_size.as_usize());
+
+    let Some(value) = lookup(&raw, &path) else {
+        return reason;
+    };
+
+    if !path.is_empty() {
+        let _ = write!(reason, " at `{}`", path.join("."));
+    }
+
+    let is_redacted = path.last().is_some_and(|key| renderer.is_redacted_key(key));
+    let value = if is_redacted {
+        SECRET.into()
+    } else {
+        renderer.render(value)
+    };
+
+    let _ = write!(reason, ", got {value}");
+    reason
+}
+
+fn lookup<'a>(mut value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    for segment in path {
+        value = match unwrap(value) {
+            Value::Map(map) => map.get(&Value::String(segment.clone()))?,
+            Value::Seq(seq) => seq.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+fn unwrap(value: &Value) -> &Value {
+    match value {
+        Value::Option(Some(value)) | Value::Newtype(value) => unwrap(value),
+        value => value,
+    }
+}
+
+fn truncate(s: &mut String, max_size: usize) {
+    if s.len() <= max_size {
+        return;
+    }
+
+    let mut end = max_size;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    s.truncate(end);
+    s.push('…');
+}
+
+// === Renderer ===
+
+/// Renders values as JSON, bounded by `max_depth` and `max_size`.
+struct Renderer<'a> {
+    config: &'a RejectionConfig,
+    secrets: &'a [Value],
+}
+
+impl<'a> Renderer<'a> {
+    fn new(config: &'a RejectionConfig, secrets: &'a [Value]) -> Self {
+        Self { config, secrets }
+    }
+
+    fn is_redacted_key(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.config
+            .redacted_keys
+            .iter()
+            .any(|redacted| key.contains(&redacted.to_lowercase()))
+    }
+
+    /// Removes values of secrets from the message, e.g. `invalid type: string
+    /// "hunter2", expected u32`.
+    fn scrub(&self, mut message: String) -> String {
+        for secret in self.secrets {
+            let secret = match unwrap(secret) {
+                Value::String(s) => s.clone(),
+                Value::Char(c) => c.to_string(),
+                _ => continue,
+            };
+
+            if !secret.is_empty() {
+                message = message.replace(&secret, "<secret>");
+            }
+        }
+
+        message
+    }
+
+    fn render(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.value(&mut out, value, 0);
+        out
+    }
+
+    fn is_full(&self, out: &str) -> bool {
+        out.len() >= self.config.max_size.as_usize()
+    }
+
+    fn value(&self, out: &mut String, value: &Value, depth: usize) {
+        if self.secrets.contains(value) {
+            out.push_str(SECRET);
+            return;
+        }
+
+        match value {
+            Value::Bool(v) => out.push_str(if *v { "true" } else { "false" }),
+            Value::U8(v) => push(out, v),
+            Value::U16(v) => push(out, v),
+            Value::U32(v) => push(out, v),
+            Value::U64(v) => push(out, v),
+            Value::I8(v) => push(out, v),
+            Value::I16(v) => push(out, v),
+            Value::I32(v) => push(out, v),
+            Value::I64(v) => push(out, v),
+            Value::F32(v) => push(out, v),
+            Value::F64(v) => push(out, v),
+            Value::Char(v) => self.string(out, &v.to_string()),
+            Value::String(v) => self.string(out, v),
+            Value::Unit | Value::Option(None) => out.push_str("null"),
+            Value::Option(Some(v)) | Value::Newtype(v) => self.value(out, v, depth),
+            Value::Bytes(v) => push(out, format_args!("\"<{} bytes>\"", v.len())),
+            Value::Seq(_) | Value::Map(_) if depth >= self.config.max_depth => out.push_str(ELIDED),
+            Value::Seq(seq) => {
+                out.push('[');
+                for (index, item) in seq.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    if self.is_full(out) {
+                        out.push_str(ELIDED);
+                        break;
+                    }
+                    self.value(out, item, depth + 1);
+                }
+                out.push(']');
+            }
+            Value::Map(map) => {
+                out.push('{');
+                for (index, (key, item)) in map.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    if self.is_full(out) {
+                        out.push_str(ELIDED);
+                        break;
+                    }
+
+                    match key {
+                        Value::String(key) => {
+                            self.string(out, key);
+                            out.push_str(": ");
+                            if self.is_redacted_key(key) {
+                                out.push_str(SECRET);
+                                continue;
+                            }
+                        }
+                        key => {
+                            self.value(out, key, self.config.max_depth);
+                            out.push_str(": ");
+                        }
+                    }
+
+                    self.value(out, item, depth + 1);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    fn string(&self, out: &mut String, s: &str) {
+        let mut s = s.to_string();
+        let limit = self.config.max_size.as_usize().saturating_sub(out.len());
+        truncate(&mut s, limit.max(1));
+        out.push_str(&serde_json::to_string(&s).expect("strings are serializable"));
+    }
+}
+
+fn push(out: &mut String, value: impl std::fmt::Display) {
+    let _ = write!(out, "{value}");
+}
+
+// === Tracked ===
+
+/// Deserializes `Value` like `ValueDeserializer`, but remembers the path to
+/// the innermost value, which decoding fails at.
+struct Tracked<'a> {
+    value: Value,
+    path: Vec<String>,
+    location: &'a RefCell<Option<Vec<String>>>,
+}
+
+impl<'a> Tracked<'a> {
+    fn nested(&self, value: Value) -> Self {
+        Self {
+            value,
+            path: self.path.clone(),
+            location: self.location,
+        }
+    }
+
+    fn track<T>(
+        path: Vec<String>,
+        location: &RefCell<Option<Vec<String>>>,
+        result: Result<T, DeError>,
+    ) -> Result<T, DeError> {
+        if result.is_err() {
+            // Inner values fail first, so the innermost path is kept.
+            location.borrow_mut().get_or_insert(path);
+        }
+        result
+    }
+}
+
+impl<'de> Deserializer<'de> for Tracked<'_> {
+    type Error = DeError;
+
+    serde::forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit
+        seq bytes byte_buf map unit_struct
+        tuple_struct struct tuple ignored_any identifier
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let (path, location) = (self.path.clone(), self.location);
+
+        let result = match self.value {
+            Value::Option(Some(ref value)) => visitor.visit_some(self.nested((**value).clone())),
+            Value::Newtype(ref value) => {
+                visitor.visit_newtype_struct(self.nested((**value).clone()))
+            }
+            Value::Seq(seq) => visitor.visit_seq(TrackedSeq {
+                iter: seq.into_iter().enumerate(),
+                path: self.path,
+                location,
+            }),
+            Value::Map(map) => visitor.visit_map(TrackedMap {
+                iter: map.into_iter(),
+                value: None,
+                path: self.path,
+                location,
+            }),
+            value => ValueDeserializer::<DeError>::new(value).deserialize_any(visitor),
+        };
+
+        Self::track(path, location, result)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let (path, location) = (self.path.clone(), self.location);
+
+        let result = match self.value {
+            Value::Option(_) => return self.deserialize_any(visitor),
+            Value::Unit => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        };
+
+        Self::track(path, location, result)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        // Variants aren't tracked, the path points to the enum itself.
+        let result =
+            ValueDeserializer::<DeError>::new(self.value).deserialize_enum(name, variants, visitor);
+        Self::track(self.path, self.location, result)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (path, location) = (self.path.clone(), self.location);
+
+        let result = match self.value {
+            Value::Newtype(ref value) => {
+                visitor.visit_newtype_struct(self.nested((**value).clone()))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        };
+
+        Self::track(path, location, result)
+    }
+}
+
+struct TrackedSeq<'a> {
+    iter: Enumerate<vec::IntoIter<Value>>,
+    path: Vec<String>,
+    location: &'a RefCell<Option<Vec<String>>>,
+}
+
+impl<'de> SeqAccess<'de> for TrackedSeq<'_> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let Some((index, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        let mut path = self.path.clone();
+        path.push(index.to_string());
+        let result = seed.deserialize(Tracked {
+            value,
+            path: path.clone(),
+            location: self.location,
+        });
+        Tracked::track(path, self.location, result).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct TrackedMap<'a> {
+    iter: btree_map::IntoIter<Value, Value>,
+    value: Option<(String, Value)>,
+    path: Vec<String>,
+    location: &'a RefCell<Option<Vec<String>>>,
+}
+
+impl<'de> MapAccess<'de> for TrackedMap<'_> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        let segment = match unwrap(&key) {
+            Value::String(key) => key.clone(),
+            key => Renderer::new(&RejectionConfig::default(), &[]).render(key),
+        };
+
+        self.value = Some((segment, value));
+        seed.deserialize(ValueDeserializer::<DeError>::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (segment, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+
+        let mut path = self.path.clone();
+        path.push(segment);
+        // Values can be decoded without the deserializer, e.g. by `Secret`.
+        let result = seed.deserialize(Tracked {
+            value,
+            path: path.clone(),
+            location: self.location,
+        });
+        Tracked::track(path, self.location, result)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Limits {
+        max_size: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Sample {
+        password: super::super::Secret<String>,
+        items: Vec<u32>,
+        limits: Option<Limits>,
+        nested: Option<Value>,
+    }
+
+    fn describe(config: toml::Value, rejection: &RejectionConfig) -> String {
+        let raw = Value::deserialize(config).unwrap();
+        let err = Sample::deserialize(ValueDeserializer::<DeError>::new(raw.clone())).unwrap_err();
+        super::describe::<Sample>(raw, err, rejection)
+    }
+
+    #[test]
+    fn innermost_path() {
+        let config = toml::toml! {
+            password = "hunter2"
+            items = [1, 2, 3]
+            limits.max_size = "large"
+        };
+
+        let reason = describe(config.into(), &RejectionConfig::default());
+        assert_eq!(
+            reason,
+            "invalid type: string \"large\", expected u32 at `limits.max_size`, got \"large\""
+        );
+
+        let config = toml::toml! {
+            password = "hunter2"
+            items = [1, 2, "three"]
+        };
+
+        let reason = describe(config.into(), &RejectionConfig::default());
+        assert_eq!(
+            reason,
+            "invalid type: string \"three\", expected u32 at `items.2`, got \"three\""
+        );
+    }
+
+    #[test]
+    fn bounded_and_redacted() {
+        let config = toml::toml! {
+            password = "hunter2"
+            items = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+            nested.a.b.c = 1
+            nested.api_token = "abc"
+            limits = { max_size = 1, extra = 2 }
+        };
+
+        let rejection = RejectionConfig {
+            max_size: ByteSize::new(32),
+            ..Default::default()
+        };
+
+        // Missing `items` is reported at the root.
+        let mut config = config;
+        let items = config.remove("items").unwrap();
+        let reason = describe(config.clone().into(), &rejection);
+        assert!(
+            reason.starts_with("missing field `items`, got {"),
+            "{reason}"
+        );
+        assert!(!reason.contains("hunter2"), "{reason}");
+        assert!(!reason.contains("abc"), "{reason}");
+
+        config.insert("items".into(), items);
+        config.insert("password".into(), 42.into());
+        let reason = describe(config.into(), &RejectionConfig::default());
+        assert_eq!(
+            reason,
+            "invalid type: integer `42`, expected a string at `password`, got \"<secret>\""
+        );
+    }
+
+    #[test]
+    fn render() {
+        let rejection = RejectionConfig {
+            max_depth: 2,
+            max_size: ByteSize::new(64),
+            ..Default::default()
+        };
+        let secrets = [Value::String("hunter2".into())];
+        let renderer = Renderer::new(&rejection, &secrets);
+
+        let value = toml::toml! {
+            a.b.c = 1
+            db = "hunter2"
+            session_token = 5
+        };
+        let value = Value::deserialize(toml::Value::from(value)).unwrap();
+        assert_eq!(
+            renderer.render(&value),
+            r#"{"a": {"b": "…"}, "db": "<secret>", "session_token": "<secret>"}"#
+        );
+
+        let value = Value::Seq((0..100).map(Value::U32).collect());
+        let rendered = renderer.render(&value);
+        assert!(rendered.starts_with("[0, 1, 2, 3"), "{rendered}");
+        assert!(rendered.ends_with(", \"…\"]"), "{rendered}");
+        assert!(rendered.len() < 80, "{rendered}");
+
+        let value = Value::String("x".repeat(100));
+        let rendered = renderer.render(&value);
+        assert_eq!(rendered, format!("\"{}…\"", "x".repeat(64)));
+
+        assert_eq!(
+            renderer.scrub("invalid value: string \"hunter2\"".into()),
+            "invalid value: string \"<secret>\""
+        );
+    }
+}
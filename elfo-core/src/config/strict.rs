@@ -0,0 +1,296 @@
+//! Decoding of the `system` section, rejecting unknown keys if
+//! `system.strict_config` is set.
+
+use serde::{
+    de::{
+        self, value::Error as DeError, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess,
+        Visitor,
+    },
+    Deserialize, Deserializer,
+};
+use serde_value::{Value, ValueDeserializer};
+
+use super::SystemConfig;
+
+/// Errors in the `system` section are prefixed to distinguish them from errors
+/// in the group's own config.
+const PREFIX: &str = "system section: ";
+
+pub(super) fn decode_system(raw: Value) -> Result<SystemConfig, String> {
+    // Collected before decoding, because it consumes the raw value.
+    let unknown = unknown_keys(&raw);
+    let de = ValueDeserializer::<DeError>::new(raw);
+    let config = SystemConfig::deserialize(de).map_err(|err| format!("{PREFIX}{err}"))?;
+
+    if !config.strict_config || unknown.is_empty() {
+        return Ok(config);
+    }
+
+    let errors = unknown
+        .iter()
+        .map(|path| describe(path))
+        .collect::<Vec<_>>();
+    Err(format!("{PREFIX}{}", errors.join("; ")))
+}
+
+/// Returns paths of keys, which aren't fields of structs containing them.
+fn unknown_keys(raw: &Value) -> Vec<Vec<String>> {
+    let mut unknown = Vec::new();
+    collect_unknown_keys(raw, &mut Vec::new(), &mut unknown);
+    unknown
+}
+
+fn collect_unknown_keys(value: &Value, path: &mut Vec<String>, unknown: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Map(map) => {
+            // Empty for maps, because their keys are arbitrary.
+            let fields = expected_fields::<SystemConfig>(path);
+
+            for (key, value) in map {
+                let Value::String(key) = key else { continue };
+
+                path.push(key.clone());
+                if fields.is_empty() || fields.contains(&key.as_str()) {
+                    collect_unknown_keys(value, path, unknown);
+                } else {
+                    unknown.push(path.clone());
+                }
+                path.pop();
+            }
+        }
+        Value::Seq(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(index.to_string());
+                collect_unknown_keys(item, path, unknown);
+                path.pop();
+            }
+        }
+        Value::Option(Some(value)) | Value::Newtype(value) => {
+            collect_unknown_keys(value, path, unknown);
+        }
+        _ => {}
+    }
+}
+
+fn describe(path: &[String]) -> String {
+    let mut message = format!("unknown key `system.{}`", path.join("."));
+
+    if let Some((key, parent)) = path.split_last() {
+        let fields = expected_fields::<SystemConfig>(parent);
+
+        if let Some(suggestion) = elfo_utils::closest(key, fields.iter().copied()) {
+            let parent = parent.iter().map(|s| format!("{s}.")).collect::<String>();
+            message.push_str(&format!(", did you mean `system.{parent}{suggestion}`?"));
+        }
+    }
+
+    message
+}
+
+// === Probe ===
+
+/// Returns fields of the struct located at the path inside `T`.
+fn expected_fields<T: for<'de> Deserialize<'de>>(path: &[String]) -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    // Always fails, because the probe doesn't provide any values.
+    let _ = T::deserialize(Probe {
+        path,
+        fields: &mut fields,
+    });
+    fields
+}
+
+/// A deserializer that follows the path and records fields of the last struct.
+/// Indices of sequences in the path aren't checked, the first item is probed.
+struct Probe<'a> {
+    path: &'a [String],
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de> Deserializer<'de> for Probe<'_> {
+    type Error = DeError;
+
+    serde::forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit
+        bytes byte_buf unit_struct tuple_struct tuple enum identifier ignored_any
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("probe"))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.path.split_first() {
+            Some((_index, path)) => visitor.visit_seq(ProbeSeq {
+                path: Some(path),
+                fields: self.fields,
+            }),
+            None => Err(de::Error::custom("probe")),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.path.split_first() {
+            Some((key, path)) => visitor.visit_map(ProbeMap {
+                key: Some(key),
+                path,
+                fields: self.fields,
+            }),
+            None => Err(de::Error::custom("probe")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.path.is_empty() {
+            *self.fields = fields;
+        }
+
+        self.deserialize_map(visitor)
+    }
+}
+
+struct ProbeMap<'a> {
+    key: Option<&'a str>,
+    path: &'a [String],
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de> MapAccess<'de> for ProbeMap<'_> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.key.take() {
+            Some(key) => seed.deserialize(key.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(Probe {
+            path: self.path,
+            fields: self.fields,
+        })
+    }
+}
+
+struct ProbeSeq<'a> {
+    path: Option<&'a [String]>,
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de> SeqAccess<'de> for ProbeSeq<'_> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.path.take() {
+            Some(path) => seed
+                .deserialize(Probe {
+                    path,
+                    fields: self.fields,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(config: toml::Value) -> Result<SystemConfig, String> {
+        decode_system(Value::deserialize(config).unwrap())
+    }
+
+    #[test]
+    fn fields() {
+        let fields = expected_fields::<SystemConfig>(&[]);
+        assert!(fields.contains(&"logging"));
+        assert!(fields.contains(&"strict_config"));
+
+        let fields = expected_fields::<SystemConfig>(&["logging".into()]);
+        assert!(fields.contains(&"max_level"));
+
+        // Keys of maps are arbitrary.
+        let path = ["circuit_breaker".into(), "destinations".into()];
+        assert!(expected_fields::<SystemConfig>(&path).is_empty());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let config = toml::toml! {
+            loging.max_level = "Warn"
+            logging.max_levle = "Warn"
+            whatever = 1
+        };
+
+        // Ignored by default.
+        assert!(decode(config.clone().into()).is_ok());
+
+        let mut config = config;
+        config.insert("strict_config".into(), true.into());
+        let err = decode(config.into()).unwrap_err();
+        assert!(err.starts_with("system section: "), "{err}");
+        assert!(
+            err.contains("unknown key `system.loging`, did you mean `system.logging`?"),
+            "{err}"
+        );
+        assert!(
+            err.contains(
+                "unknown key `system.logging.max_levle`, did you mean `system.logging.max_level`?"
+            ),
+            "{err}"
+        );
+        assert!(err.contains("unknown key `system.whatever`"), "{err}");
+        assert!(!err.contains("`system.whatever`, did you mean"), "{err}");
+    }
+
+    #[test]
+    fn unknown_keys_inside_maps() {
+        let config = toml::toml! {
+            strict_config = true
+            circuit_breaker.destinations.payments.open_duraton = "5s"
+            circuit_breaker.destinations.payments.min_requests = 10
+        };
+
+        let err = decode(config.into()).unwrap_err();
+        assert_eq!(
+            err,
+            "system section: unknown key \
+             `system.circuit_breaker.destinations.payments.open_duraton`, did you mean \
+             `system.circuit_breaker.destinations.payments.open_duration`?"
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let err = decode(toml::toml! { mailbox.capacity = "many" }.into()).unwrap_err();
+        assert!(err.starts_with("system section: "), "{err}");
+    }
+}
@@ -1,40 +1,95 @@
-use std::{future::poll_fn, marker::PhantomData, pin::Pin, sync::Arc, task::Poll};
+use std::{
+    future::{poll_fn, Future},
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+};
 
-use futures::{pin_mut, Stream};
+use futures::{future, pin_mut, FutureExt, Stream};
 use idr_ebr::EbrGuard;
+use metrics::increment_counter;
+#[cfg(feature = "dumping")]
 use once_cell::sync::Lazy;
-use tracing::{info, trace};
+use smallvec::SmallVec;
+use tokio::{sync::oneshot, time::Instant as TokioInstant};
+use tracing::{debug, info, trace, warn};
 
 use elfo_utils::unlikely;
 
 use crate::{
-    actor::{Actor, ActorStartInfo},
+    actor::{Actor, ActorStartInfo, DrainTarget},
     actor_status::ActorStatus,
     addr::Addr,
     address_book::AddressBook,
-    config::AnyConfig,
+    audit::ActorAudit,
+    broker::Topic,
+    channel::ChannelBuilder,
+    circuit_breaking::Ticket,
+    concurrency::{self, Concurrency, InFlight},
+    config::{AnyConfig, Rate},
     coop,
-    demux::Demux,
-    dumping::{Direction, Dump, Dumper, INTERNAL_CLASS},
+    dedup::Dedup,
+    deferred::{DeferredStats, DeferredToken},
+    demux::{Addrs, Demux},
+    deprecation::Usage,
+    dumping::{Direction, Dump, DumpClassifier, Dumper, SequenceNo, INTERNAL_CLASS},
     envelope::{Envelope, MessageKind},
-    errors::{RequestError, SendError, TryRecvError, TrySendError},
+    errors::{
+        AckError, DeliveryError, DeriveConfigError, ErrorContext, ErrorKind, RequestError,
+        SendError, TryRecvError, TrySendError, UnknownGroupError,
+    },
+    group_ref::GroupRef,
+    idempotency::{IdempotencyCache, IdempotencyKey},
+    intercept::Interceptors,
     mailbox::RecvResult,
-    message::{Message, Request},
+    message::{AnyMessage, Message, MessageTypeId, Request},
     messages, msg,
     object::{BorrowedObject, Object, OwnedObject},
-    request_table::ResponseToken,
+    pipeline::Pipeline,
+    rate_limiting::RateLimited,
+    request_table::{PendingRequest, RequestId, RequestLimits, ResponseToken, Responses},
     restarting::RestartPolicy,
     routers::Singleton,
     scope,
+    self_queue::{SelfEnvelopes, SelfQueue},
+    shutdown::ShutdownReason,
     source::{SourceHandle, Sources, UnattachedSource},
     ActorStatusKind,
 };
 
-use self::stats::Stats;
+pub use self::batch::Batch;
+
+#[cfg(feature = "network")]
+use crate::remote::AckToken;
 
+use self::{derived::DerivedConfigs, stats::Stats};
+
+mod batch;
+mod derived;
 mod stats;
 
+#[cfg(feature = "dumping")]
 static DUMPER: Lazy<Dumper> = Lazy::new(|| Dumper::new(INTERNAL_CLASS));
+#[cfg(feature = "dumping")]
+static DUP_DUMPER: Lazy<Dumper> = Lazy::new(|| Dumper::new("dup"));
+// Plain statics to avoid even checking initialization of `Lazy`.
+#[cfg(not(feature = "dumping"))]
+static DUMPER: Dumper = Dumper::disabled(INTERNAL_CLASS);
+#[cfg(not(feature = "dumping"))]
+static DUP_DUMPER: Dumper = Dumper::disabled("dup");
+
+/// Returns the dumper of regular messages, taking into account the class
+/// overridden in the current scope, see `scope::with_dump_class()`.
+#[inline]
+pub(crate) fn dumper() -> &'static Dumper {
+    #[cfg(feature = "dumping")]
+    if let Some(dumper) = scope::try_with(|scope| scope.dumper()).flatten() {
+        return dumper;
+    }
+
+    &DUMPER
+}
 
 /// An actor execution context.
 pub struct Context<C = (), K = Singleton> {
@@ -45,16 +100,35 @@ pub struct Context<C = (), K = Singleton> {
     group_addr: Addr,
     demux: Demux,
     config: Arc<C>,
+    config_generation: u64,
+    derived_configs: DerivedConfigs,
+    dedup: Dedup,
+    interceptors: Interceptors,
+    idempotency: Option<Arc<IdempotencyCache>>,
+    concurrency: Concurrency,
+    dump_classifier: Option<DumpClassifier>,
+    audit: Option<ActorAudit>,
     key: K,
     sources: Sources,
+    self_queue: SelfEnvelopes,
     stage: Stage,
     stats: Stats,
+    /// The deadline of the currently handled request, see `RequestLimits`.
+    deadline: Option<Deadline>,
+}
+
+#[derive(Clone, Copy)]
+struct Deadline {
+    at: TokioInstant,
+    request: &'static str,
 }
 
 #[derive(Clone, Copy, PartialEq)]
 enum Stage {
     PreRecv,
     Working,
+    /// The input is closed, but `None` isn't returned yet.
+    Closing,
     Closed,
 }
 
@@ -80,6 +154,57 @@ impl<C, K> Context<C, K> {
         &self.config
     }
 
+    /// Returns the generation of the actual config.
+    ///
+    /// The generation starts from zero when the actor is spawned and is
+    /// incremented every time an updated config is applied, i.e. on
+    /// each [`ConfigUpdated`]. Useful for manual invalidation of values
+    /// derived from the config.
+    ///
+    /// [`ConfigUpdated`]: crate::messages::ConfigUpdated
+    #[inline]
+    pub fn config_generation(&self) -> u64 {
+        self.config_generation
+    }
+
+    /// Returns a value derived from the actual config.
+    ///
+    /// The value is built by the provided closure lazily, at most once per
+    /// [config generation](Context::config_generation), and cached by its
+    /// type. Thus, different call sites in the actor share the same value
+    /// if they derive the same type.
+    ///
+    /// Panics in the closure are caught and returned as errors, which are
+    /// cached until the next config update as well.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let patterns = ctx.derived_config(|config| Patterns::build(&config.patterns))?;
+    /// ```
+    pub fn derived_config<T>(
+        &mut self,
+        build: impl FnOnce(&C) -> T,
+    ) -> Result<Arc<T>, DeriveConfigError>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.derived_configs
+            .get_or_build(self.config_generation, &*self.config, build)
+    }
+
+    /// Marks the current trace as detailed: always sampled for dumping and
+    /// logging, regardless of `trace_sample_rate` settings. Also, messages
+    /// are dumped bypassing rate limits and events are logged with at least
+    /// `Debug` level.
+    ///
+    /// The mark follows messages sent in this trace, including ones sent to
+    /// other nodes, so the whole trace is captured. Some traces are marked
+    /// automatically according to `system.tracing.detailed_budget`.
+    #[inline]
+    pub fn force_sampling(&self) {
+        scope::force_sampling();
+    }
+
     /// Returns the actor's key.
     #[inline]
     pub fn key(&self) -> &K {
@@ -153,6 +278,62 @@ impl<C, K> Context<C, K> {
             .set_mailbox_capacity_override(capacity.into());
     }
 
+    /// Returns the approximate number of messages stored in the mailbox.
+    /// Messages sent to self (see [`Context::send_to_self()`]) and ones sent
+    /// by [`Context::unbounded_send()`] above the capacity aren't counted.
+    pub fn mailbox_len(&self) -> usize {
+        ward!(self.actor.as_ref().and_then(|o| o.as_actor()), return 0).mailbox_len()
+    }
+
+    /// Returns the name of the message that is received next by
+    /// [`Context::recv()`] from the mailbox or messages sent to self, without
+    /// receiving it. Useful to decide whether to wait for more messages before
+    /// handling ones received so far.
+    ///
+    /// Sources aren't peeked, so a source's message can be received before.
+    /// In pooled groups, only messages sent to the actor itself are peeked.
+    /// Envelopes are peeked as is, before the context handles them, e.g.
+    /// skips expired requests or replaces `UpdateConfig` with `ConfigUpdated`.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg};
+    /// # #[message]
+    /// # struct WriteRow;
+    /// # fn flush() {}
+    /// while let Some(envelope) = ctx.recv().await {
+    ///     msg!(match envelope {
+    ///         WriteRow => {
+    ///             // Flush only if no more rows are queued.
+    ///             if !ctx.has_queued::<WriteRow>() {
+    ///                 flush();
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    pub fn peek_next_message_name(&mut self) -> Option<&'static str> {
+        self.peek_next(|envelope| envelope.message().name())
+    }
+
+    /// Returns `true` if the message that is received next by
+    /// [`Context::recv()`] is of the `M` type. Only the next message is
+    /// checked, see [`Context::peek_next_message_name()`] for details.
+    pub fn has_queued<M: Message>(&mut self) -> bool {
+        self.peek_next(|envelope| envelope.is::<M>())
+            .unwrap_or(false)
+    }
+
+    fn peek_next<R>(&mut self, f: impl Fn(&Envelope) -> R) -> Option<R> {
+        let actor = self.actor.as_ref()?.as_actor()?;
+        let peek_mailbox =
+            || actor.peek(|envelope| (concurrency::is_barrier(envelope), f(envelope)));
+        self.self_queue.peek(peek_mailbox, &f)
+    }
+
     /// Overrides the group's default restart policy, which set in the config.
     ///
     /// Note: after restart the actor will be created from scratch, so this
@@ -181,12 +362,77 @@ impl<C, K> Context<C, K> {
         ward!(self.actor.as_ref().and_then(|o| o.as_actor()), return false).close()
     }
 
+    /// Closes the mailbox and hands off messages left in it to the actor with
+    /// the provided key in the same group once the current actor finishes.
+    ///
+    /// It's useful for actors handing off their work, e.g. when a shard moves.
+    /// Messages are delivered in order, preserving trace ids, and requests
+    /// can be responded to by the new owner. At most
+    /// `system.mailbox.drain_limit` messages are handed off, the rest is
+    /// dead-lettered. Messages received after this call are not handed off,
+    /// so it should be called right before returning from the actor.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context<(), u32>) {
+    /// if *ctx.key() == 1 {
+    ///     // Actor `1` is retiring, actor `2` takes over its messages.
+    ///     ctx.drain_to(2);
+    ///     return;
+    /// }
+    /// # }
+    /// ```
+    pub fn drain_to(&self, key: K)
+    where
+        K: Send + Sync + 'static,
+    {
+        ward!(self.actor.as_ref().and_then(|o| o.as_actor()))
+            .set_drain_target(DrainTarget::Key(key));
+    }
+
+    /// Like [`Context::drain_to()`], but messages are re-routed through the
+    /// group's router, so the current routing decides where they go.
+    /// Messages routed to the current actor again are dropped.
+    pub fn drain(&self)
+    where
+        K: Send + Sync + 'static,
+    {
+        ward!(self.actor.as_ref().and_then(|o| o.as_actor()))
+            .set_drain_target(DrainTarget::<K>::Router);
+    }
+
+    /// Initiates graceful shutdown of the whole node, just like `SIGTERM` does:
+    /// groups are terminated according to the shutdown order, then the
+    /// top-level [`init::try_run()`] returns the reason.
+    ///
+    /// Only the first reason is kept, subsequent calls (and signals) don't
+    /// affect the ongoing shutdown. Returns `false` in this case.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context) {
+    /// // The external system is gone, there is nothing to do anymore.
+    /// ctx.initiate_shutdown("upstream is decommissioned");
+    /// # }
+    /// ```
+    ///
+    /// [`init::try_run()`]: crate::init::try_run
+    pub fn initiate_shutdown(&self, reason: impl Into<String>) -> bool {
+        let reason = ShutdownReason::Requested(reason.into());
+        self.book.shutdown().initiate(reason)
+    }
+
     /// Sends a message using the [inter-group routing] system.
     ///
     /// It's possible to send requests if the response is not needed.
     ///
     /// Returns `Err` if the message hasn't reached any mailboxes.
     ///
+    /// Use [`Context::send_acknowledged()`] to wait until remote recipients
+    /// enqueue the message.
+    ///
     /// # Cancel safety
     ///
     /// If cancelled, recipients with full mailboxes wont't receive the message.
@@ -211,8 +457,46 @@ impl<C, K> Context<C, K> {
     ///
     /// [inter-group routing]: https://actoromicon.rs/ch04-01-routing.html
     pub async fn send<M: Message>(&self, message: M) -> Result<(), SendError<M>> {
+        self.send_detailed(message)
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    /// Sends a message using the [inter-group routing] system and waits until
+    /// any recipient enqueues it.
+    ///
+    /// Local recipients are acknowledged once the message is added to their
+    /// mailboxes. Remote ones are acknowledged by the receiving node once
+    /// the message is added to the mailbox there, which costs one lightweight
+    /// frame per message, much cheaper than a request with a response.
+    ///
+    /// Returns `Err` if no recipient has acknowledged the message, see
+    /// [`AckError`] for possible reasons.
+    ///
+    /// # Cancel safety
+    ///
+    /// If cancelled, recipients with full mailboxes wont't receive the message.
+    /// Already sent messages aren't recalled.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg};
+    /// #[message]
+    /// struct SomethingHappened;
+    ///
+    /// // Fire and wait for the acknowledgement.
+    /// if let Err(error) = ctx.send_acknowledged(SomethingHappened).await {
+    ///     tracing::warn!(%error, "...");
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [inter-group routing]: https://actoromicon.rs/ch04-01-routing.html
+    pub async fn send_acknowledged<M: Message>(&self, message: M) -> Result<(), AckError> {
         let kind = MessageKind::regular(self.actor_addr);
-        self.do_send_async(message, kind).await
+        self.do_send_acknowledged(message, kind).await
     }
 
     /// Tries to send a message using the [inter-group routing] system.
@@ -244,37 +528,63 @@ impl<C, K> Context<C, K> {
     ///
     /// [inter-group routing]: https://actoromicon.rs/ch04-01-routing.html
     pub fn try_send<M: Message>(&self, message: M) -> Result<(), TrySendError<M>> {
+        self.try_send_detailed(message)
+            .map_err(DeliveryError::into_error)
+    }
+
+    fn try_send_detailed<M: Message>(
+        &self,
+        message: M,
+    ) -> Result<(), DeliveryError<TrySendError<M>>> {
         // XXX: avoid duplication with `unbounded_send()` and `send()`.
 
         let kind = MessageKind::regular(self.actor_addr);
+        let name = (message.protocol(), message.name());
+
+        if !self.use_deprecated(&message) {
+            let err = TrySendError::Closed(message);
+            return Err(self.deprecated_error(err, name, &[]));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            let err = TrySendError::Closed(message);
+            return Err(self.trace_budget_error(err, name, &[]));
+        }
 
         self.stats.on_sent_message(&message); // TODO: only if successful?
 
         trace!("> {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&message) {
+        if let Some(permit) = dumper().acquire_m(&message) {
             permit.record(Dump::message(&message, &kind, Direction::Out));
         }
 
-        let envelope = Envelope::new(message, kind);
-        let addrs = self.demux.filter(&envelope);
+        let mut envelope = Envelope::new(message, kind);
+        apply_default_ttl(&mut envelope);
+        let addrs = self.route(&envelope);
 
         if addrs.is_empty() {
-            return Err(TrySendError::Closed(e2m(envelope)));
+            let err = TrySendError::Closed(e2m(envelope));
+            return Err(self.try_send_error(err, name, &addrs));
         }
 
         let guard = EbrGuard::new();
 
         if addrs.len() == 1 {
             return match self.book.get(addrs[0], &guard) {
-                Some(object) => object
-                    .try_send(Addr::NULL, envelope)
-                    .map_err(|err| err.map(e2m)),
-                None => Err(TrySendError::Closed(e2m(envelope))),
+                Some(object) => object.try_send(Addr::NULL, envelope).map_err(|err| {
+                    let rejection = rejection(&err);
+                    with_rejection(self.try_send_error(err.map(e2m), name, &addrs), rejection)
+                }),
+                None => {
+                    let err = TrySendError::Closed(e2m(envelope));
+                    Err(self.try_send_error(err, name, &addrs))
+                }
             };
         }
 
         let mut unused = None;
         let mut has_full = false;
+        let mut rejection = None;
         let mut success = false;
 
         for (addr, envelope) in addrs_with_envelope(envelope, &addrs) {
@@ -283,6 +593,7 @@ impl<C, K> Context<C, K> {
                     Ok(()) => success = true,
                     Err(err) => {
                         has_full |= err.is_full();
+                        rejection = self::rejection(&err).or(rejection);
                         unused = Some(err.into_inner());
                     }
                 },
@@ -293,9 +604,14 @@ impl<C, K> Context<C, K> {
         if success {
             Ok(())
         } else if has_full {
-            Err(TrySendError::Full(e2m(unused.unwrap())))
+            let err = TrySendError::Full(e2m(unused.unwrap()));
+            Err(self.try_send_error(err, name, &addrs))
         } else {
-            Err(TrySendError::Closed(e2m(unused.unwrap())))
+            let err = TrySendError::Closed(e2m(unused.unwrap()));
+            Err(with_rejection(
+                self.try_send_error(err, name, &addrs),
+                rejection,
+            ))
         }
     }
 
@@ -331,20 +647,39 @@ impl<C, K> Context<C, K> {
     ///
     /// [inter-group routing]: https://actoromicon.rs/ch04-01-routing.html
     pub fn unbounded_send<M: Message>(&self, message: M) -> Result<(), SendError<M>> {
+        self.unbounded_send_detailed(message)
+            .map_err(DeliveryError::into_error)
+    }
+
+    fn unbounded_send_detailed<M: Message>(
+        &self,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        let name = (message.protocol(), message.name());
+
+        if !self.use_deprecated(&message) {
+            return Err(self.deprecated_error(SendError(message), name, &[]));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            return Err(self.trace_budget_error(SendError(message), name, &[]));
+        }
+
         let kind = MessageKind::regular(self.actor_addr);
 
         self.stats.on_sent_message(&message); // TODO: only if successful?
 
         trace!("> {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&message) {
+        if let Some(permit) = dumper().acquire_m(&message) {
             permit.record(Dump::message(&message, &kind, Direction::Out));
         }
 
-        let envelope = Envelope::new(message, kind);
-        let addrs = self.demux.filter(&envelope);
+        let mut envelope = Envelope::new(message, kind);
+        apply_default_ttl(&mut envelope);
+        let addrs = self.route(&envelope);
 
         if addrs.is_empty() {
-            return Err(SendError(e2m(envelope)));
+            return Err(self.send_error(SendError(e2m(envelope)), name, &addrs));
         }
 
         let guard = EbrGuard::new();
@@ -353,8 +688,8 @@ impl<C, K> Context<C, K> {
             return match self.book.get(addrs[0], &guard) {
                 Some(object) => object
                     .unbounded_send(Addr::NULL, envelope)
-                    .map_err(|err| err.map(e2m)),
-                None => Err(SendError(e2m(envelope))),
+                    .map_err(|err| self.send_error(err.map(e2m), name, &addrs)),
+                None => Err(self.send_error(SendError(e2m(envelope)), name, &addrs)),
             };
         }
 
@@ -374,10 +709,37 @@ impl<C, K> Context<C, K> {
         if success {
             Ok(())
         } else {
-            Err(SendError(e2m(unused.unwrap())))
+            let err = SendError(e2m(unused.unwrap()));
+            Err(self.send_error(err, name, &addrs))
         }
     }
 
+    /// Returns a wrapper to send messages, which returns errors with the
+    /// context of the failure: the kind, the message and the destination,
+    /// see [`DeliveryError`]. Useful for retrying and alerting logic.
+    ///
+    /// Requests provide the context by [`RequestBuilder::resolve_detailed()`].
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context, addr: elfo::Addr) {
+    /// # use elfo::{errors::ErrorKind, message};
+    /// #[message]
+    /// struct SomethingHappened;
+    ///
+    /// match ctx.detailed().send_to(addr, SomethingHappened).await {
+    ///     Ok(()) => {}
+    ///     Err(err) if err.kind() == ErrorKind::NoRoute => { /* ... */ }
+    ///     Err(err) => tracing::warn!(group = ?err.context().group, "{err}"),
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn detailed(&self) -> Detailed<'_, C, K> {
+        Detailed { context: self }
+    }
+
     /// Returns a request builder to send a request (on `resolve()`) using
     /// the [inter-group routing] system.
     ///
@@ -419,35 +781,131 @@ impl<C, K> Context<C, K> {
         RequestBuilder::new(self, request).to(recipient)
     }
 
+    /// Returns a pipeline of requests to the specified recipient, which
+    /// sends requests without waiting for responses to previous ones,
+    /// but at most [`Pipeline::max_in_flight()`] at the same time.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut pipeline = ctx.pipeline(addr).max_in_flight(64);
+    /// pipeline.push(SomeRequest).await;
+    /// let response = pipeline.next_response().await;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the context doesn't belong to an actor.
+    pub fn pipeline<R: Request>(&self, recipient: Addr) -> Pipeline<'_, C, K, R>
+    where
+        C: 'static,
+    {
+        Pipeline::new(self, recipient)
+    }
+
+    /// Returns requests sent by this actor and not resolved yet, e.g. made
+    /// concurrently by sub-futures. Every handle can be used to cancel the
+    /// request, see [`PendingRequest::cancel()`].
+    ///
+    /// Returns an empty list if called outside the actor.
+    pub fn pending_requests(&self) -> Vec<PendingRequest> {
+        let object = ward!(self.actor.as_ref(), return Vec::new());
+        let actor = ward!(object.as_actor(), return Vec::new());
+        actor.request_table().pending(object)
+    }
+
+    /// Cancels pending requests matching the predicate, e.g. requests made
+    /// obsolete by a config update. Returns the number of cancelled requests.
+    ///
+    /// See [`Context::pending_requests()`] and [`PendingRequest::cancel()`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # use elfo_core as elfo;
+    /// # fn exec(ctx: elfo::Context) {
+    /// ctx.cancel_requests_matching(|request| request.age() > Duration::from_secs(5));
+    /// # }
+    /// ```
+    pub fn cancel_requests_matching(&self, mut f: impl FnMut(&PendingRequest) -> bool) -> usize {
+        self.pending_requests()
+            .into_iter()
+            .filter(|request| f(request) && request.cancel())
+            .count()
+    }
+
+    /// Finds recipients of a routed message.
+    fn route(&self, envelope: &Envelope) -> Addrs {
+        let addrs = self.demux(envelope);
+
+        let recorder = self.book.edge_recorder();
+        if unlikely(recorder.is_enabled()) {
+            recorder.record(self.group_addr, &addrs, envelope.message().name());
+        }
+
+        addrs
+    }
+
+    /// Applies routes and switches of standby groups, see `Topology::standby()`.
+    fn demux(&self, envelope: &Envelope) -> Addrs {
+        let mut addrs = self.demux.filter(envelope);
+
+        let standbys = self.book.standbys();
+        if unlikely(!standbys.is_empty()) {
+            standbys.reroute(envelope, &mut addrs);
+        }
+
+        addrs
+    }
+
+    /// Returns recipients of the message.
     async fn do_send_async<M: Message>(
         &self,
         message: M,
         kind: MessageKind,
-    ) -> Result<(), SendError<M>> {
+    ) -> Result<Addrs, DeliveryError<SendError<M>>> {
+        let name = (message.protocol(), message.name());
         self.stats.on_sent_message(&message); // TODO: only if successful?
 
         trace!("> {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&message) {
+        if let Some(permit) = dumper().acquire_m(&message) {
             permit.record(Dump::message(&message, &kind, Direction::Out));
         }
 
-        let envelope = Envelope::new(message, kind);
-        let addrs = self.demux.filter(&envelope);
+        let mut envelope = Envelope::new(message, kind);
+        apply_default_ttl(&mut envelope);
+        self.do_send_envelope(envelope, name, e2m).await
+    }
+
+    /// Routes and sends the built envelope, returns recipients of the message.
+    async fn do_send_envelope<M>(
+        &self,
+        envelope: Envelope,
+        name: (&'static str, &'static str),
+        e2m: fn(Envelope) -> M,
+    ) -> Result<Addrs, DeliveryError<SendError<M>>> {
+        let addrs = self.route(&envelope);
 
         if addrs.is_empty() {
-            return Err(SendError(e2m(envelope)));
+            return Err(self.send_error(SendError(e2m(envelope)), name, &addrs));
         }
 
         if addrs.len() == 1 {
             let recipient = addrs[0];
-            return {
+            let res = {
                 let guard = EbrGuard::new();
                 let entry = self.book.get(recipient, &guard);
-                let object = ward!(entry, return Err(SendError(e2m(envelope))));
+                let object = ward!(
+                    entry,
+                    return Err(self.send_error(SendError(e2m(envelope)), name, &addrs))
+                );
                 Object::send(object, Addr::NULL, envelope)
             }
-            .await
-            .map_err(|err| err.map(e2m));
+            .await;
+
+            return match res {
+                Ok(()) => Ok(addrs),
+                Err(err) => Err(self.send_envelope_error(err, e2m, name, &addrs)),
+            };
         }
 
         let mut unused = None;
@@ -475,10 +933,76 @@ impl<C, K> Context<C, K> {
         }
 
         if success {
-            Ok(())
+            Ok(addrs)
         } else {
-            Err(SendError(e2m(unused.unwrap())))
+            let err = SendError(unused.unwrap());
+            Err(self.send_envelope_error(err, e2m, name, &addrs))
+        }
+    }
+
+    /// Like `do_send_async()`, but also waits for acknowledgements of remote
+    /// recipients. Local recipients are acknowledged once enqueued.
+    async fn do_send_acknowledged<M: Message>(
+        &self,
+        message: M,
+        kind: MessageKind,
+    ) -> Result<(), AckError> {
+        if !self.use_deprecated(&message) {
+            return Err(AckError::Deprecated);
+        }
+
+        if !self.spend_trace_budget(message.protocol()) {
+            return Err(AckError::TraceBudgetExceeded);
         }
+
+        self.stats.on_sent_message(&message);
+
+        trace!("> {:?}", message);
+        if let Some(permit) = dumper().acquire_m(&message) {
+            permit.record(Dump::message(&message, &kind, Direction::Out));
+        }
+
+        let mut envelope = Envelope::new(message, kind);
+        apply_default_ttl(&mut envelope);
+        let addrs = self.route(&envelope);
+
+        if addrs.is_empty() || self.are_disabled_groups(&addrs) {
+            return Err(AckError::NoRoute);
+        }
+
+        let mut is_acked = false;
+        let mut pending = Vec::new();
+        let mut error = AckError::Closed;
+
+        // TODO: send concurrently.
+        for (recipient, mut envelope) in addrs_with_envelope(envelope, &addrs) {
+            let (fut, ack) = {
+                let guard = EbrGuard::new();
+                let object = ward!(self.book.get(recipient, &guard), continue);
+                let ack = object.is_remote().then(|| attach_ack(&mut envelope));
+                (Object::send(object, Addr::NULL, envelope), ack)
+            };
+
+            match fut.await {
+                Ok(()) => match ack {
+                    Some(ack) => pending.push(ack),
+                    None => is_acked = true,
+                },
+                Err(err) if err.0.rejection().is_some() => error = AckError::Rejected,
+                Err(_) => error = AckError::Closed,
+            }
+        }
+
+        if is_acked {
+            return Ok(());
+        }
+
+        if pending.is_empty() {
+            return Err(error);
+        }
+
+        // Wait for the first successful acknowledgement or the last error.
+        future::select_ok(pending).await.map(drop)
     }
 
     /// Sends a message to the specified recipient.
@@ -513,12 +1037,34 @@ impl<C, K> Context<C, K> {
         recipient: Addr,
         message: M,
     ) -> Result<(), SendError<M>> {
+        self.send_to_detailed(recipient, message)
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    async fn send_to_detailed<M: Message>(
+        &self,
+        recipient: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
         let kind = MessageKind::regular(self.actor_addr);
+        let name = (message.protocol(), message.name());
+        let recipients = [recipient];
+
+        if !self.use_deprecated(&message) {
+            return Err(self.deprecated_error(SendError(message), name, &recipients));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            return Err(self.trace_budget_error(SendError(message), name, &recipients));
+        }
+
         self.do_send_to(recipient, message, kind, |object, envelope| {
             Object::send(object, recipient, envelope)
-        })?
+        })
+        .map_err(|err| self.send_error(err, name, &recipients))?
         .await
-        .map_err(|err| err.map(e2m))
+        .map_err(|err| self.send_envelope_error(err, e2m, name, &recipients))
     }
 
     /// Tries to send a message to the specified recipient.
@@ -553,12 +1099,41 @@ impl<C, K> Context<C, K> {
         recipient: Addr,
         message: M,
     ) -> Result<(), TrySendError<M>> {
+        self.do_try_send_to(recipient, recipient, message)
+            .map_err(DeliveryError::into_error)
+    }
+
+    // `target` is passed to the object, see `try_send_to_group()`.
+    fn do_try_send_to<M: Message>(
+        &self,
+        recipient: Addr,
+        target: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<TrySendError<M>>> {
         let kind = MessageKind::regular(self.actor_addr);
+        let name = (message.protocol(), message.name());
+
+        if !self.use_deprecated(&message) {
+            let err = TrySendError::Closed(message);
+            return Err(self.deprecated_error(err, name, &[recipient]));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            let err = TrySendError::Closed(message);
+            return Err(self.trace_budget_error(err, name, &[recipient]));
+        }
+
+        let mut rejection = None;
+
         self.do_send_to(recipient, message, kind, |object, envelope| {
-            object
-                .try_send(recipient, envelope)
-                .map_err(|err| err.map(e2m))
-        })?
+            object.try_send(target, envelope).map_err(|err| {
+                rejection = self::rejection(&err);
+                err.map(e2m)
+            })
+        })
+        .map_err(TrySendError::from)
+        .and_then(|res| res)
+        .map_err(|err| with_rejection(self.try_send_error(err, name, &[recipient]), rejection))
     }
 
     /// Sends a message to the specified recipient.
@@ -595,15 +1170,278 @@ impl<C, K> Context<C, K> {
         recipient: Addr,
         message: M,
     ) -> Result<(), SendError<M>> {
+        self.unbounded_send_to_detailed(recipient, message)
+            .map_err(DeliveryError::into_error)
+    }
+
+    fn unbounded_send_to_detailed<M: Message>(
+        &self,
+        recipient: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        let name = (message.protocol(), message.name());
+        let recipients = [recipient];
+
+        if !self.use_deprecated(&message) {
+            return Err(self.deprecated_error(SendError(message), name, &recipients));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            return Err(self.trace_budget_error(SendError(message), name, &recipients));
+        }
+
         let kind = MessageKind::regular(self.actor_addr);
         self.do_send_to(recipient, message, kind, |object, envelope| {
             object
                 .unbounded_send(recipient, envelope)
                 .map_err(|err| err.map(e2m))
-        })?
+        })
+        .and_then(|res| res)
+        .map_err(|err| self.send_error(err, name, &recipients))
     }
 
-    #[inline(always)]
+    /// Sends a message to the actor itself, bypassing the address book,
+    /// routing and the mailbox. Useful to split work into smaller pieces.
+    ///
+    /// The message is placed into a small queue owned by this context and
+    /// received by [`Context::recv()`] and [`Context::try_recv()`] according
+    /// to [`SelfQueuePriority`]. If the queue is full or disabled, e.g. for
+    /// pruned contexts, the message is sent to the own mailbox by
+    /// [`Context::try_send_to()`] instead. See [`ActorGroup::self_queue()`].
+    ///
+    /// Outgoing and incoming dumps are recorded as usual with the same trace
+    /// id. Messages queued before closing the mailbox are still received, like
+    /// ones left in the mailbox, and dropped along with the context. Once the
+    /// mailbox is closed, new messages are rejected.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg};
+    /// #[message]
+    /// struct Process(std::ops::Range<u32>);
+    ///
+    /// while let Some(envelope) = ctx.recv().await {
+    ///     msg!(match envelope {
+    ///         Process(range) if range.len() > 100 => {
+    ///             let mid = range.start + 100;
+    ///             // Let other messages go between pieces.
+    ///             let _ = ctx.send_to_self(Process(mid..range.end));
+    ///             // ... process `range.start..mid`
+    ///         }
+    ///         Process(_range) => { /* ... */ }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`SelfQueuePriority`]: crate::SelfQueuePriority
+    /// [`ActorGroup::self_queue()`]: crate::ActorGroup::self_queue
+    pub fn send_to_self<M: Message>(&mut self, message: M) -> Result<(), TrySendError<M>> {
+        if self.self_queue.is_full() || self.stage == Stage::Closed {
+            return self.try_send_to(self.actor_addr, message);
+        }
+
+        if !self.use_deprecated(&message) || !self.spend_trace_budget(message.protocol()) {
+            return Err(TrySendError::Closed(message));
+        }
+
+        self.stats.on_sent_message(&message);
+
+        let kind = MessageKind::regular(self.actor_addr);
+        trace!(to = %self.actor_addr, "> {:?}", message);
+        if let Some(permit) = dumper().acquire_m(&message) {
+            permit.record(Dump::message_to(&message, &kind, self.actor_addr));
+        }
+
+        self.self_queue.push(Envelope::new(message, kind));
+        Ok(())
+    }
+
+    /// Returns a reference to the group with the specified name, which can be
+    /// used by [`Context::send_to_group()`]. Usually, it's called once at
+    /// startup to fail fast on unknown groups.
+    ///
+    /// Both local and remote (mounted by `elfo-network`) groups are located.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context) -> Result<(), elfo::errors::UnknownGroupError> {
+    /// let orders = ctx.locate_group("orders")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn locate_group(&self, name: &str) -> Result<GroupRef, UnknownGroupError> {
+        self.book
+            .groups()
+            .locate(name)
+            .ok_or_else(|| UnknownGroupError { name: name.into() })
+    }
+
+    /// Sends a message to the specified group, which routes it as usual.
+    /// Waits if the mailbox is full.
+    ///
+    /// Returns `Err` if the message hasn't reached any mailboxes, e.g. if the
+    /// group is disabled or terminated. Use [`Context::detailed()`] to tell
+    /// them apart by [`ErrorKind`]: unknown groups and remote groups without
+    /// connected nodes result in [`ErrorKind::NoRoute`].
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context, orders: elfo::GroupRef) {
+    /// # use elfo::message;
+    /// #[message]
+    /// struct OrderPlaced;
+    ///
+    /// // Fire or log.
+    /// if let Err(error) = ctx.send_to_group(&orders, OrderPlaced).await {
+    ///     tracing::warn!(%error, "...");
+    /// }
+    /// # }
+    /// ```
+    pub async fn send_to_group<M: Message>(
+        &self,
+        group: &GroupRef,
+        message: M,
+    ) -> Result<(), SendError<M>> {
+        self.send_to_group_detailed(group, message)
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    async fn send_to_group_detailed<M: Message>(
+        &self,
+        group: &GroupRef,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        let kind = MessageKind::regular(self.actor_addr);
+        let name = (message.protocol(), message.name());
+
+        let Some(recipient) = group.resolve(self.book.groups()) else {
+            let err = SendError(message);
+            return Err(self.group_error(ErrorKind::NoRoute, group, err, name));
+        };
+
+        let recipients = [recipient];
+
+        if !self.use_deprecated(&message) {
+            return Err(self.deprecated_error(SendError(message), name, &recipients));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            return Err(self.trace_budget_error(SendError(message), name, &recipients));
+        }
+
+        // Remote handles route messages if the recipient is `NULL`.
+        self.do_send_to(recipient, message, kind, |object, envelope| {
+            Object::send(object, Addr::NULL, envelope)
+        })
+        .map_err(|err| self.send_error(err, name, &recipients))?
+        .await
+        .map_err(|err| self.send_envelope_error(err, e2m, name, &recipients))
+    }
+
+    /// Like [`Context::send_to_group()`], but doesn't wait if the mailbox is
+    /// full. Used by [`RateLimited`].
+    pub(crate) fn try_send_to_group<M: Message>(
+        &self,
+        group: &GroupRef,
+        message: M,
+    ) -> Result<(), DeliveryError<TrySendError<M>>> {
+        let Some(recipient) = group.resolve(self.book.groups()) else {
+            let name = (message.protocol(), message.name());
+            let err = TrySendError::Closed(message);
+            return Err(self.group_error(ErrorKind::NoRoute, group, err, name));
+        };
+
+        // Remote handles route messages if the recipient is `NULL`.
+        self.do_try_send_to(recipient, Addr::NULL, message)
+    }
+
+    /// Sends the request to the specified group, which routes it as usual,
+    /// and waits for the response. Used by [`RateLimited`].
+    pub(crate) async fn request_to_group<R: Request>(
+        &self,
+        group: &GroupRef,
+        request: R,
+    ) -> Result<R::Response, DeliveryError<RequestError>>
+    where
+        C: 'static,
+    {
+        let Some(recipient) = group.resolve(self.book.groups()) else {
+            let name = (request.protocol(), request.name());
+            let err = RequestError::Failed;
+            return Err(self.group_error(ErrorKind::NoRoute, group, err, name));
+        };
+
+        let mut builder = self.request_to(recipient, request);
+        builder.is_routed = true;
+        builder.resolve_detailed().await
+    }
+
+    /// Returns a wrapper to send messages to the destination group, which
+    /// paces them according to the rate, e.g. `"50/s"`.
+    ///
+    /// The budget is shared by all call sites and all actors of the group:
+    /// messages take evenly spaced slots in the order of arrival, so actors
+    /// get shares proportional to their demands. The rate can be overridden
+    /// by the group's config without redeploying, see [`RateLimiterConfig`].
+    ///
+    /// The ratio of attempts to send within the last period to the budget is
+    /// exposed as the `elfo_rate_limiter_saturation` gauge.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context, gateway: elfo::GroupRef) {
+    /// # use elfo::message;
+    /// #[message]
+    /// struct PlaceOrder;
+    ///
+    /// let gateway = ctx.rate_limited(&gateway, "50/s".parse().unwrap());
+    ///
+    /// // Waits for the budget.
+    /// gateway.send(PlaceOrder).await.unwrap();
+    ///
+    /// // Fails fast if the budget is exhausted.
+    /// if let Err(error) = gateway.try_send(PlaceOrder) {
+    ///     tracing::warn!(%error, "...");
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the context doesn't belong to an actor.
+    ///
+    /// [`RateLimiterConfig`]: crate::config::system::rate_limiter::RateLimiterConfig
+    pub fn rate_limited(&self, destination: &GroupRef, rate: Rate) -> RateLimited<'_, C, K> {
+        let edge = scope::with(|scope| scope.rate_limiters().get(destination.name(), rate));
+        RateLimited::new(self, destination.clone(), edge)
+    }
+
+    /// Opens a bidirectional channel with the specified recipient, which
+    /// sends messages of type `S` and receives ones of type `R`.
+    ///
+    /// The recipient receives [`ChannelOpened`] as a request and must respond
+    /// to accept the channel. If the recipient is a group, the responding
+    /// actor becomes the other side. Both sides get handles to send messages, each
+    /// direction is limited by the window, see [`ChannelBuilder::window()`].
+    ///
+    /// See the [`channel`] module for details.
+    ///
+    /// [`ChannelOpened`]: crate::channel::ChannelOpened
+    /// [`channel`]: crate::channel
+    pub fn open_channel<S: Message, R: Message>(
+        &self,
+        recipient: Addr,
+    ) -> ChannelBuilder<'_, C, K, S, R> {
+        ChannelBuilder::new(self, recipient)
+    }
+
+    #[inline(always)]
     fn do_send_to<M: Message, R>(
         &self,
         recipient: Addr,
@@ -614,18 +1452,127 @@ impl<C, K> Context<C, K> {
         self.stats.on_sent_message(&message); // TODO: only if successful?
 
         trace!(to = %recipient, "> {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&message) {
-            permit.record(Dump::message(&message, &kind, Direction::Out));
+        if let Some(permit) = dumper().acquire_m(&message) {
+            permit.record(Dump::message_to(&message, &kind, recipient));
         }
 
         let guard = EbrGuard::new();
         let entry = self.book.get(recipient, &guard);
         let object = ward!(entry, return Err(SendError(message)));
-        let envelope = Envelope::new(message, kind);
+        let mut envelope = Envelope::new(message, kind);
+        apply_default_ttl(&mut envelope);
 
         Ok(f(object, envelope))
     }
 
+    /// Subscribes the actor to messages of type `M` published to the topic,
+    /// see [`Context::publish()`]. Use [`AnyMessage`] to receive messages of
+    /// all types. Returns `false` if the actor is already subscribed.
+    ///
+    /// Subscriptions are removed when the actor terminates, so restarted
+    /// actors must subscribe again.
+    ///
+    /// [`AnyMessage`]: crate::AnyMessage
+    pub fn subscribe<M: Message>(&self, topic: impl Into<Topic>) -> bool {
+        let topic = topic.into();
+        debug!(%topic, "subscribed");
+        self.book.broker().subscribe::<M>(topic, self.actor_addr)
+    }
+
+    /// Removes the subscription made by [`Context::subscribe()`].
+    /// Returns `false` if the actor isn't subscribed.
+    pub fn unsubscribe<M: Message>(&self, topic: impl Into<Topic>) -> bool {
+        let topic = topic.into();
+        debug!(%topic, "unsubscribed");
+        self.book.broker().unsubscribe::<M>(topic, self.actor_addr)
+    }
+
+    /// Publishes a message to all actors of the node subscribed to the topic
+    /// by [`Context::subscribe()`]. Waits if some mailboxes are full.
+    ///
+    /// The message is delivered to mailboxes as usual, so admission policies
+    /// are applied. The outgoing dump is recorded in the `topic:<name>` class.
+    ///
+    /// Returns the number of subscribers the message has reached. Publishing
+    /// to a topic without subscribers is cheap and only counted by the
+    /// `elfo_published_without_subscribers_total` metric.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg, Topic};
+    /// #[message]
+    /// struct SessionOpened(u64);
+    ///
+    /// const SESSIONS: Topic = Topic::new("sessions");
+    ///
+    /// // A subscriber.
+    /// ctx.subscribe::<SessionOpened>(SESSIONS);
+    ///
+    /// // A publisher.
+    /// let delivered = ctx.publish(SESSIONS, SessionOpened(42)).await;
+    /// tracing::info!(delivered, "session is opened");
+    /// # }
+    /// ```
+    pub async fn publish<M: Message>(&self, topic: impl Into<Topic>, message: M) -> usize {
+        let topic = topic.into();
+        // The real type is used to support publishing of `AnyMessage`.
+        let type_id = MessageTypeId::new(message._vtable());
+        let subscribers = self.book.broker().subscribers(topic, type_id);
+        let subscribers = ward!(subscribers, else {
+            let topic = topic.name();
+            increment_counter!("elfo_published_without_subscribers_total", "topic" => topic);
+            return 0;
+        });
+
+        if !self.use_deprecated(&message) {
+            debug!(%topic, "message isn't published, it's deprecated");
+            return 0;
+        }
+
+        if !self.spend_trace_budget(message.protocol()) {
+            debug!(%topic, "message isn't published, the trace budget is exceeded");
+            return 0;
+        }
+
+        self.stats.on_sent_message(&message); // TODO: only if successful?
+
+        trace!(%topic, "> {:?}", message);
+        if let Some(permit) = subscribers.dumper.acquire_m(&message) {
+            let kind = MessageKind::regular(self.actor_addr);
+            permit.record(Dump::message(&message, &kind, Direction::Out));
+        }
+
+        let mut delivered = 0;
+
+        for recipient in subscribers.addrs {
+            let fut = {
+                let guard = EbrGuard::new();
+                let object = ward!(self.book.get(recipient, &guard), else {
+                    self.book.broker().remove_closed(topic, recipient);
+                    continue;
+                });
+
+                let kind = MessageKind::regular(self.actor_addr);
+                let mut envelope = Envelope::new(message.clone(), kind);
+                apply_default_ttl(&mut envelope);
+                Object::send(object, recipient, envelope)
+            };
+
+            match fut.await {
+                Ok(()) => delivered += 1,
+                Err(SendError(envelope)) if envelope.rejection().is_some() => {
+                    debug!(%topic, to = %recipient, "published message is rejected");
+                }
+                // The actor is terminated, but the supervisor hasn't noticed it yet.
+                Err(_) => self.book.broker().remove_closed(topic, recipient),
+            }
+        }
+
+        delivered
+    }
+
     /// Responds to the requester with the provided response.
     ///
     /// The token can be used only once.
@@ -642,9 +1589,25 @@ impl<C, K> Context<C, K> {
             return;
         }
 
-        let token = token.into_untyped();
-        let recipient = token.sender();
         let message = R::Wrapper::from(message);
+        self.do_respond(token.into_untyped(), message, false);
+    }
+
+    fn do_respond<M: Message>(&self, token: ResponseToken, message: M, is_replayed: bool) {
+        let recipient = token.sender();
+
+        #[cfg(feature = "network")]
+        if let Some(limit) = token.max_response_size() {
+            if unlikely(crate::request_table::exceeds_response_size(&message, limit)) {
+                increment_counter!("elfo_oversized_responses_total");
+                warn!(
+                    response = message.name(),
+                    limit, "response is rejected, size limit exceeded"
+                );
+                token.fail(ErrorKind::LimitExceeded);
+                return;
+            }
+        }
         self.stats.on_sent_message(&message); // TODO: only if successful?
 
         let kind = MessageKind::Response {
@@ -653,8 +1616,21 @@ impl<C, K> Context<C, K> {
         };
 
         trace!(to = %recipient, "> {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&message) {
-            permit.record(Dump::message(&message, &kind, Direction::Out));
+        if let Some(permit) = dumper().acquire_m(&message) {
+            permit.record(Dump::message_to(&message, &kind, recipient));
+        }
+
+        // Write-ahead: the record must be written before responding.
+        if let Some(audit) = &self.audit {
+            audit.on_response(token.request_id(), token.trace_id(), &message);
+        }
+
+        // Recorded before responding, because the requester can retry
+        // immediately after receiving the response.
+        if let (Some(cache), Some(key), false) =
+            (&self.idempotency, token.idempotency_key(), is_replayed)
+        {
+            cache.record(recipient, key, AnyMessage::new(message.clone()));
         }
 
         let envelope = Envelope::new(message, kind);
@@ -663,6 +1639,166 @@ impl<C, K> Context<C, K> {
         object.respond(token, Ok(envelope));
     }
 
+    /// Responds to the request deferred by [`ResponseToken::defer()`].
+    ///
+    /// Does nothing if the deadline is already reached or the deferring actor
+    /// has terminated.
+    pub fn respond_deferred<R: Request>(&self, deferred: DeferredToken<R>, message: R::Response) {
+        let name = deferred.name();
+        let Some(token) = deferred.take() else {
+            debug!(
+                name,
+                "deferred token is expired or dropped, the response is discarded"
+            );
+            return;
+        };
+
+        self.respond(token.into_received::<R>(), message);
+    }
+
+    /// Returns stats of unresolved deferred responses of this actor,
+    /// see [`ResponseToken::defer()`].
+    ///
+    /// Returns empty stats if called outside the actor.
+    pub fn deferred_stats(&self) -> DeferredStats {
+        let object = ward!(self.actor.as_ref(), return DeferredStats::default());
+        let actor = ward!(object.as_actor(), return DeferredStats::default());
+        actor.deferred_table().stats()
+    }
+
+    /// Returns the deadline of the currently handled request, i.e. the last
+    /// one received by [`Context::recv()`] or [`Context::try_recv()`], if it
+    /// has the handling time limit, see [`RequestLimits`].
+    ///
+    /// # Stability
+    ///
+    /// This method is unstable, because it returns [`tokio::time::Instant`],
+    /// which will be replaced in the future to support other runtimes.
+    #[stability::unstable]
+    pub fn deadline(&self) -> Option<TokioInstant> {
+        self.deadline.map(|deadline| deadline.at)
+    }
+
+    /// Runs the future until the deadline of the currently handled request,
+    /// see [`Context::deadline()`]. If the deadline is exceeded, the future is
+    /// dropped, the cancellation is logged and `None` is returned. Dropping
+    /// the token after that fails the request with [`ErrorKind::LimitExceeded`].
+    ///
+    /// The future is run as is if the request has no deadline.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # use elfo::message;
+    /// # #[message(ret = Vec<u32>)] struct Select;
+    /// # async fn select_all() -> Vec<u32> { Vec::new() }
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// use elfo::msg;
+    ///
+    /// while let Some(envelope) = ctx.recv().await {
+    ///     msg!(match envelope {
+    ///         (Select, token) => {
+    ///             if let Some(rows) = ctx.within_deadline(select_all()).await {
+    ///                 ctx.respond(token, rows);
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    pub async fn within_deadline<F: Future>(&self, future: F) -> Option<F::Output> {
+        let Some(deadline) = self.deadline else {
+            return Some(future.await);
+        };
+
+        match tokio::time::timeout_at(deadline.at, future).await {
+            Ok(output) => Some(output),
+            Err(_) => {
+                on_expired_request(deadline.request);
+                None
+            }
+        }
+    }
+
+    /// Delegates the request to another actor using the [inter-group routing]
+    /// system. The recipient receives the token and responds directly to the
+    /// original requester with the same correlation and trace ids, so the
+    /// current actor doesn't wait for the response.
+    ///
+    /// The request can be modified before forwarding, but must be of the same
+    /// type. Works for remote recipients too. If the recipient drops the token,
+    /// the requester gets [`RequestError::Ignored`]. If the request isn't
+    /// delivered, the token is dropped and the requester gets
+    /// [`RequestError::Failed`].
+    ///
+    /// Forwarded requests are dumped with the `Forward` message kind on both
+    /// sides of the local delegation.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg};
+    /// #[message(ret = u64)]
+    /// struct GetBalance { account: String }
+    ///
+    /// while let Some(envelope) = ctx.recv().await {
+    ///     msg!(match envelope {
+    ///         (GetBalance { account }, token) => {
+    ///             let account = account.to_lowercase();
+    ///             let _ = ctx.forward_request(token, GetBalance { account }).await;
+    ///         }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [inter-group routing]: https://actoromicon.rs/ch04-01-routing.html
+    pub async fn forward_request<R: Request>(
+        &self,
+        token: ResponseToken<R>,
+        request: R,
+    ) -> Result<(), SendError<R>> {
+        if !self.use_deprecated(&request) || !self.spend_trace_budget(request.protocol()) {
+            return Err(SendError(request));
+        }
+
+        let kind = self.forwarded_kind(token);
+        self.do_send_async(request, kind)
+            .await
+            .map(drop)
+            .map_err(DeliveryError::into_error)
+    }
+
+    /// Delegates the request to the specified recipient.
+    /// See [`Context::forward_request()`] for details.
+    pub async fn forward_request_to<R: Request>(
+        &self,
+        token: ResponseToken<R>,
+        recipient: Addr,
+        request: R,
+    ) -> Result<(), SendError<R>> {
+        if !self.use_deprecated(&request) || !self.spend_trace_budget(request.protocol()) {
+            return Err(SendError(request));
+        }
+
+        let kind = self.forwarded_kind(token);
+        self.do_send_to(recipient, request, kind, |object, envelope| {
+            Object::send(object, recipient, envelope)
+        })?
+        .await
+        .map_err(|err| err.map(e2m))
+    }
+
+    fn forwarded_kind<R>(&self, token: ResponseToken<R>) -> MessageKind {
+        // If the response isn't expected, forward as a regular message.
+        if token.is_forgotten() {
+            return MessageKind::regular(self.actor_addr);
+        }
+
+        MessageKind::RequestAny(token.into_forwarded())
+    }
+
     /// Receives the next envelope from the mailbox or sources.
     /// If the envelope isn't available, the method waits for the next one.
     /// If the mailbox is closed, `None` is returned.
@@ -704,7 +1840,12 @@ impl<C, K> Context<C, K> {
             self.pre_recv().await;
 
             let envelope = 'received: {
-                let mailbox_fut = self.actor.as_ref()?.as_actor()?.recv();
+                let actor = self.actor.as_ref()?.as_actor()?;
+                if let Some(envelope) = self.self_queue.pop(|| actor.try_recv()) {
+                    break 'received envelope;
+                }
+
+                let mailbox_fut = actor.recv();
                 pin_mut!(mailbox_fut);
 
                 tokio::select! {
@@ -799,6 +1940,10 @@ impl<C, K> Context<C, K> {
                     return Err(TryRecvError::Closed)
                 );
 
+                if let Some(envelope) = self.self_queue.pop(|| actor.try_recv()) {
+                    break 'received envelope;
+                }
+
                 // TODO: poll mailbox and sources fairly.
                 match actor.try_recv() {
                     Some(RecvResult::Data(envelope)) => {
@@ -834,17 +1979,161 @@ impl<C, K> Context<C, K> {
         }
     }
 
-    /// Retrieves information related to the start of the actor.
+    /// Receives up to `limit` envelopes from the mailbox or sources.
+    /// If no envelopes are available, the method waits for the first one,
+    /// and then takes only already available ones without waiting.
+    /// If the mailbox is closed, `None` is returned.
+    ///
+    /// System messages (from [`messages`], e.g. `ConfigUpdated` or
+    /// `Terminate`) interrupt the batch: such an envelope is returned in
+    /// [`Batch::interrupted_by`] right after received items, so it's never
+    /// handled late. `Terminate` is received ahead of other messages in the
+    /// mailbox if `system.mailbox.on_terminate` is `"prioritize"` or `"stop"`.
+    ///
+    /// Once the mailbox is closed by `Terminate`, left messages are either
+    /// received or dropped according to `system.mailbox.on_terminate`,
+    /// then `None` is returned.
     ///
     /// # Panics
     ///
-    /// This method will panic if the context is pruned, indicating that the
-    /// required information is no longer available.
+    /// If `limit` is zero or the method is called again after `None` is
+    /// returned.
     ///
     /// # Example
     /// ```
     /// # use elfo_core as elfo;
-    /// # use elfo_core::{ActorStartCause, ActorStartInfo};
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// # use elfo::{message, msg, messages::Terminate};
+    /// # #[message]
+    /// # struct SomethingHappened;
+    /// # fn handle_batch(_batch: Vec<elfo::Envelope>) {}
+    /// while let Some(batch) = ctx.recv_many(100).await {
+    ///     handle_batch(batch.items);
+    ///
+    ///     if let Some(envelope) = batch.interrupted_by {
+    ///         msg!(match envelope {
+    ///             Terminate => break,
+    ///             _ => {}
+    ///         });
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`TerminationPolicy::manually()`]: crate::TerminationPolicy::manually
+    pub async fn recv_many(&mut self, limit: usize) -> Option<Batch>
+    where
+        C: 'static,
+    {
+        assert!(limit > 0, "`limit` must be positive");
+
+        // The input has been closed while the previous batch was received.
+        if self.stage == Stage::Closing {
+            self.stage = Stage::Closed;
+            return None;
+        }
+
+        let mut batch = Batch::new(limit.min(64));
+        let first = self.recv().await?;
+
+        if concurrency::is_barrier(&first) {
+            batch.interrupted_by = Some(first);
+            return Some(batch);
+        }
+
+        batch.items.push(first);
+
+        while batch.items.len() < limit {
+            match self.try_recv().await {
+                Ok(envelope) if concurrency::is_barrier(&envelope) => {
+                    batch.interrupted_by = Some(envelope);
+                    break;
+                }
+                Ok(envelope) => batch.items.push(envelope),
+                Err(err) => {
+                    if err.is_closed() {
+                        self.stage = Stage::Closing;
+                    }
+                    break;
+                }
+            }
+        }
+
+        Some(batch)
+    }
+
+    /// Receives envelopes and handles them concurrently by futures produced by
+    /// `handler`, at most [`ActorGroup::concurrency()`] at the same time.
+    /// Handlers are completed in any order.
+    ///
+    /// System messages (from [`messages`], e.g. `ConfigUpdated` or
+    /// `Terminate`) are barriers: once such an envelope is received, the
+    /// method waits for all in-flight handlers and returns the envelope.
+    /// If the mailbox is closed, `None` is returned after waiting for
+    /// in-flight handlers as well.
+    ///
+    /// Handlers cannot borrow the context, use [`Context::pruned()`] to send
+    /// messages and respond to requests inside them.
+    ///
+    /// # Panics
+    ///
+    /// A panic inside a handler is logged along with the name of the handled
+    /// message, other handlers continue to run. Use
+    /// [`Concurrency::propagate_panics()`] to fail the actor instead.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method isn't cancel safe: in-flight handlers are dropped.
+    ///
+    /// [`ActorGroup::concurrency()`]: crate::ActorGroup::concurrency
+    pub async fn recv_concurrent<F, Fut>(&mut self, mut handler: F) -> Option<Envelope>
+    where
+        C: 'static,
+        F: FnMut(Envelope) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut in_flight = InFlight::new(self.concurrency);
+
+        loop {
+            let envelope = if in_flight.is_full() {
+                in_flight.next().await;
+                continue;
+            } else if in_flight.is_empty() {
+                self.recv().await
+            } else {
+                tokio::select! {
+                    biased;
+                    _ = in_flight.next() => continue,
+                    envelope = self.recv() => envelope,
+                }
+            };
+
+            let Some(envelope) = envelope else {
+                in_flight.drain().await;
+                return None;
+            };
+
+            if concurrency::is_barrier(&envelope) {
+                in_flight.drain().await;
+                scope::set_trace_id(envelope.trace_id());
+                return Some(envelope);
+            }
+
+            in_flight.push(envelope, &mut handler);
+        }
+    }
+
+    /// Retrieves information related to the start of the actor.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the context is pruned, indicating that the
+    /// required information is no longer available.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # use elfo_core::{ActorStartCause, ActorStartInfo};
     /// # async fn exec(mut ctx: elfo::Context) {
     /// match ctx.start_info().cause {
     ///     ActorStartCause::GroupMounted => {
@@ -869,6 +2158,10 @@ impl<C, K> Context<C, K> {
     async fn pre_recv(&mut self) {
         self.stats.on_recv();
 
+        if let Some(actor) = self.actor.as_ref().and_then(|o| o.as_actor()) {
+            actor.poisoning().on_handled();
+        }
+
         coop::consume_budget().await;
 
         if unlikely(self.stage == Stage::Closed) {
@@ -888,11 +2181,27 @@ impl<C, K> Context<C, K> {
     where
         C: 'static,
     {
-        scope::set_trace_id(envelope.trace_id());
+        let sequence_no = scope::with(|scope| {
+            scope.set_trace_id(envelope.trace_id());
+            if envelope.is_force_sampled() {
+                scope.force_sampling();
+            }
+
+            // The class is kept for the whole handling, including sent messages.
+            if let Some(classifier) = &self.dump_classifier {
+                scope.replace_dumper(classifier.classify(&envelope));
+            }
+
+            // Reuse the dumping source to join logs and dumps of the handling.
+            let sequence_no = scope.dumping().next_sequence_no();
+            scope.set_sequence_no(sequence_no);
+            sequence_no
+        });
 
         let envelope = msg!(match envelope {
-            (messages::UpdateConfig { config }, token) => {
+            (messages::UpdateConfig { config, .. }, token) => {
                 self.config = config.get_user::<C>().clone();
+                self.config_generation += 1;
                 info!("config updated");
                 let message = messages::ConfigUpdated {};
                 let kind = MessageKind::regular(self.actor_addr);
@@ -903,11 +2212,56 @@ impl<C, K> Context<C, K> {
             envelope => envelope,
         });
 
+        let envelope = ward!(self.interceptors.apply(envelope), return None);
+
+        if unlikely(self.dedup.is_duplicate(&envelope)) {
+            on_duplicate(&envelope, sequence_no);
+            return None;
+        }
+
+        if unlikely(envelope.is_expired()) {
+            on_expired_message(&envelope);
+            return None;
+        }
+
+        // Expired requests are also cancelled by requesters, but logged.
+        if unlikely(is_expired_request(&envelope)) {
+            on_expired_request(envelope.message().name());
+            return None;
+        }
+
+        if unlikely(is_cancelled_request(&envelope)) {
+            on_cancelled_request(&envelope);
+            return None;
+        }
+
+        // Retried requests are responded without handling them again.
+        if let Some(response) = self.recorded_response(&envelope) {
+            self.replay_response(envelope, response);
+            return None;
+        }
+
+        self.deadline = request_deadline(&envelope);
+
         let message = envelope.message();
         trace!("< {:?}", message);
-        if let Some(permit) = DUMPER.acquire_m(&*message) {
+        if let Some(permit) = dumper().acquire_m(&*message) {
             let kind = envelope.message_kind();
-            permit.record(Dump::message(&*message, kind, Direction::In));
+            let mut dump = Dump::handled_message(&*message, kind, sequence_no);
+            dump.channel_id = envelope.channel_id();
+            permit.record(dump);
+        }
+
+        if let Some(audit) = &self.audit {
+            let guard = EbrGuard::new();
+            let sender = self.book.get(envelope.sender(), &guard);
+            let meta = sender.as_ref().and_then(|o| o.as_actor()).map(|a| a.meta());
+            audit.on_received(&envelope, meta.map(|meta| &**meta));
+        }
+
+        if let Some(actor) = self.actor.as_ref().and_then(|o| o.as_actor()) {
+            actor.poisoning().on_handling(&envelope);
+            actor.recent_dumps().on_handling(&envelope);
         }
 
         // We should change the status after dumping the original message
@@ -918,6 +2272,11 @@ impl<C, K> Context<C, K> {
 
         self.stats.on_received_envelope(&envelope);
 
+        let message = envelope.message();
+        if let Some(note) = message.deprecation() {
+            scope::try_with(|scope| scope.use_deprecated(Usage::Handled, &*message, note));
+        }
+
         msg!(match envelope {
             (messages::Ping, token) => {
                 self.respond(token, ());
@@ -927,6 +2286,28 @@ impl<C, K> Context<C, K> {
         })
     }
 
+    fn recorded_response(&self, envelope: &Envelope) -> Option<AnyMessage> {
+        let cache = self.idempotency.as_ref()?;
+        let key = envelope.idempotency_key()?;
+        cache.lookup(envelope.sender(), key)
+    }
+
+    #[cold]
+    fn replay_response(&self, envelope: Envelope, response: AnyMessage) {
+        let name = envelope.message().name();
+        let (_, kind) = envelope.unpack::<AnyMessage>().expect("impossible");
+        let token = match kind {
+            MessageKind::RequestAny(token) | MessageKind::RequestAll(token) => token,
+            _ => unreachable!("only requests have idempotency keys"),
+        };
+
+        debug!(
+            request = name,
+            "request is retried, the response is replayed"
+        );
+        self.do_respond(token, response, true);
+    }
+
     /// This is a part of private API for now.
     /// We should provide a way to handle it asynchronous.
     #[doc(hidden)]
@@ -962,10 +2343,20 @@ impl<C, K> Context<C, K> {
             group_addr: self.group_addr,
             demux: self.demux.clone(),
             config: Arc::new(()),
+            config_generation: 0,
+            derived_configs: DerivedConfigs::default(),
+            dedup: Dedup::default(),
+            interceptors: Interceptors::default(),
+            idempotency: self.idempotency.clone(),
+            concurrency: Concurrency::default(),
+            dump_classifier: None,
+            audit: self.audit.clone(),
             key: Singleton,
             sources: Sources::new(),
+            self_queue: SelfEnvelopes::default(),
             stage: self.stage,
             stats: Stats::empty(),
+            deadline: None,
         }
     }
 
@@ -984,10 +2375,20 @@ impl<C, K> Context<C, K> {
             group_addr: self.group_addr,
             demux: self.demux,
             config,
+            config_generation: 0,
+            derived_configs: DerivedConfigs::default(),
+            dedup: self.dedup,
+            interceptors: self.interceptors,
+            idempotency: self.idempotency,
+            concurrency: self.concurrency,
+            dump_classifier: self.dump_classifier,
+            audit: self.audit,
             key: self.key,
             sources: self.sources,
+            self_queue: self.self_queue,
             stage: self.stage,
             stats: self.stats,
+            deadline: self.deadline,
         }
     }
 
@@ -999,6 +2400,41 @@ impl<C, K> Context<C, K> {
         self
     }
 
+    pub(crate) fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub(crate) fn with_interceptors(mut self, interceptors: Interceptors) -> Self {
+        self.interceptors = interceptors;
+        self
+    }
+
+    pub(crate) fn with_idempotency(mut self, cache: Option<Arc<IdempotencyCache>>) -> Self {
+        self.idempotency = cache;
+        self
+    }
+
+    pub(crate) fn with_concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub(crate) fn with_dump_classifier(mut self, classifier: Option<DumpClassifier>) -> Self {
+        self.dump_classifier = classifier;
+        self
+    }
+
+    pub(crate) fn with_audit(mut self, audit: Option<ActorAudit>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub(crate) fn with_self_queue(mut self, config: SelfQueue) -> Self {
+        self.self_queue = SelfEnvelopes::new(config);
+        self
+    }
+
     pub(crate) fn with_group(mut self, group: Addr) -> Self {
         self.group_addr = group;
         self
@@ -1018,19 +2454,186 @@ impl<C, K> Context<C, K> {
             group_addr: self.group_addr,
             demux: self.demux,
             config: self.config,
+            config_generation: self.config_generation,
+            derived_configs: self.derived_configs,
+            dedup: self.dedup,
+            interceptors: self.interceptors,
+            idempotency: self.idempotency,
+            concurrency: self.concurrency,
+            dump_classifier: self.dump_classifier,
+            audit: self.audit,
             key,
             sources: self.sources,
+            self_queue: self.self_queue,
             stage: self.stage,
             stats: self.stats,
+            deadline: self.deadline,
         }
     }
 }
 
+// === Private API for `elfo::test::envelope()` ===
+
+#[cfg(feature = "test-util")]
+impl<C, K> Context<C, K> {
+    #[doc(hidden)]
+    pub fn __new_request(
+        &self,
+        name: &'static str,
+        trace_id: crate::tracing::TraceId,
+    ) -> ResponseToken {
+        // TODO: use `self.actor` after removing pruned contexts.
+        let object = self.book.get_owned(self.actor_addr).expect("invalid addr");
+        let actor = object.as_actor().expect("can be called only on actors");
+        actor.request_table().new_request(
+            self.book.clone(),
+            trace_id,
+            false,
+            name,
+            None,
+            RequestLimits::default(),
+            false,
+            None,
+        )
+    }
+
+    #[doc(hidden)]
+    pub async fn __wait_response<R: Request>(
+        &self,
+        request_id: crate::RequestId,
+    ) -> Result<R::Response, RequestError> {
+        let object = self.book.get_owned(self.actor_addr).expect("invalid addr");
+        let actor = object.as_actor().expect("can be called only on actors");
+        let mut responses = actor.request_table().wait(request_id, None).await;
+        debug_assert_eq!(responses.len(), 1);
+        let response = responses.pop().expect("missing response");
+        prepare_response::<R>(response)
+            .map(|(response, _responder)| response)
+            .map_err(RequestError::from_kind)
+    }
+
+    #[doc(hidden)]
+    pub fn __cancel_request(&self, request_id: crate::RequestId) {
+        let object = ward!(self.book.get_owned(self.actor_addr));
+        let actor = ward!(object.as_actor());
+        actor.request_table().cancel_request(request_id);
+    }
+
+    #[doc(hidden)]
+    pub async fn __send_envelope(
+        &self,
+        envelope: Envelope,
+    ) -> Result<(), DeliveryError<SendError<Envelope>>> {
+        let message = envelope.message();
+        let name = (message.protocol(), message.name());
+        self.stats.on_sent_message(&*message);
+
+        trace!("> {:?}", message);
+        if let Some(permit) = dumper().acquire_m(&*message) {
+            let kind = envelope.message_kind();
+            permit.record(Dump::message(&*message, kind, Direction::Out));
+        }
+
+        self.do_send_envelope(envelope, name, std::convert::identity)
+            .await
+            .map(drop)
+    }
+}
+
 #[cold]
 fn e2m<M: Message>(envelope: Envelope) -> M {
     envelope.unpack().expect("invalid message").0
 }
 
+/// Returns the reason kept in the envelope rejected by the admission policy.
+fn rejection(err: &TrySendError<Envelope>) -> Option<&'static str> {
+    match err {
+        TrySendError::Closed(envelope) => envelope.rejection(),
+        TrySendError::Full(_) => None,
+    }
+}
+
+fn with_rejection<E>(err: DeliveryError<E>, rejection: Option<&'static str>) -> DeliveryError<E> {
+    match rejection {
+        Some(reason) => err.rejected(reason),
+        None => err,
+    }
+}
+
+#[cold]
+fn on_duplicate(envelope: &Envelope, sequence_no: SequenceNo) {
+    increment_counter!("elfo_dedup_dropped_total");
+
+    let message = envelope.message();
+    trace!("< {:?} (duplicate)", message);
+    if let Some(permit) = DUP_DUMPER.acquire_m(&*message) {
+        let kind = envelope.message_kind();
+        permit.record(Dump::handled_message(&*message, kind, sequence_no));
+    }
+}
+
+fn is_cancelled_request(envelope: &Envelope) -> bool {
+    match envelope.message_kind() {
+        MessageKind::RequestAny(token) | MessageKind::RequestAll(token) => {
+            token.is_dropped_by_requester()
+        }
+        _ => false,
+    }
+}
+
+fn is_expired_request(envelope: &Envelope) -> bool {
+    match envelope.message_kind() {
+        MessageKind::RequestAny(token) | MessageKind::RequestAll(token) => token.is_expired(),
+        _ => false,
+    }
+}
+
+fn request_deadline(envelope: &Envelope) -> Option<Deadline> {
+    match envelope.message_kind() {
+        MessageKind::RequestAny(token) | MessageKind::RequestAll(token) => {
+            token.deadline().map(|at| Deadline {
+                at,
+                request: envelope.message().name(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Applies the default TTL of the sending group to regular messages,
+/// see `system.ttl`. Requests get it as the handling time limit instead.
+fn apply_default_ttl(envelope: &mut Envelope) {
+    if !matches!(envelope.message_kind(), MessageKind::Regular { .. }) {
+        return;
+    }
+
+    let type_id = envelope.type_id();
+    if let Some(ttl) = scope::try_with(|scope| scope.default_ttl(type_id)).flatten() {
+        envelope.set_ttl(ttl);
+    }
+}
+
+#[cold]
+fn on_expired_message(envelope: &Envelope) {
+    increment_counter!("elfo_message_ttl_exceeded_total");
+    trace!("< {:?} (expired)", envelope.message());
+}
+
+#[cold]
+fn on_expired_request(request: &'static str) {
+    increment_counter!("elfo_expired_requests_total");
+    warn!(
+        request,
+        "request is cancelled, handling time limit exceeded"
+    );
+}
+
+#[cold]
+fn on_cancelled_request(envelope: &Envelope) {
+    increment_counter!("elfo_cancelled_requests_dropped_total");
+    trace!("< {:?} (cancelled)", envelope.message());
+}
+
 #[cold]
 fn on_input_closed(stage: &mut Stage, actor: &Actor) {
     if !actor.status_kind().is_terminating() {
@@ -1040,6 +2643,35 @@ fn on_input_closed(stage: &mut Stage, actor: &Actor) {
     trace!("input closed");
 }
 
+type AckReceiver = future::Map<
+    oneshot::Receiver<Result<(), AckError>>,
+    fn(Result<Result<(), AckError>, oneshot::error::RecvError>) -> Result<(), AckError>,
+>;
+
+/// Attaches a token to the envelope, which is resolved by the network
+/// once the remote node enqueues the envelope.
+#[cfg(feature = "network")]
+fn attach_ack(envelope: &mut Envelope) -> AckReceiver {
+    let (tx, rx) = oneshot::channel();
+    envelope.set_ack(Some(AckToken::new(AckError::ConnectionLost, move |res| {
+        let _ = tx.send(res);
+    })));
+    rx.map(flatten_ack as _)
+}
+
+#[cfg(feature = "network")]
+fn flatten_ack(
+    res: Result<Result<(), AckError>, oneshot::error::RecvError>,
+) -> Result<(), AckError> {
+    // The token always resolves the channel, even if dropped.
+    res.unwrap_or(Err(AckError::ConnectionLost))
+}
+
+#[cfg(not(feature = "network"))]
+fn attach_ack(_envelope: &mut Envelope) -> AckReceiver {
+    unreachable!("remote objects require the `network` feature")
+}
+
 fn addrs_with_envelope(
     envelope: Envelope,
     addrs: &[Addr],
@@ -1070,10 +2702,20 @@ impl Context {
             actor_start_info: None,
             demux,
             config: Arc::new(()),
+            config_generation: 0,
+            derived_configs: DerivedConfigs::default(),
+            dedup: Dedup::default(),
+            interceptors: Interceptors::default(),
+            idempotency: None,
+            concurrency: Concurrency::default(),
+            dump_classifier: None,
+            audit: None,
             key: Singleton,
             sources: Sources::new(),
+            self_queue: SelfEnvelopes::default(),
             stage: Stage::PreRecv,
             stats: Stats::empty(),
+            deadline: None,
         }
     }
 }
@@ -1089,19 +2731,119 @@ impl<C, K: Clone> Clone for Context<C, K> {
             group_addr: self.group_addr,
             demux: self.demux.clone(),
             config: self.config.clone(),
+            config_generation: self.config_generation,
+            derived_configs: DerivedConfigs::default(),
+            dedup: Dedup::default(),
+            interceptors: Interceptors::default(),
+            idempotency: None,
+            concurrency: self.concurrency,
+            dump_classifier: self.dump_classifier.clone(),
+            audit: self.audit.clone(),
             key: self.key.clone(),
             sources: Sources::new(),
+            // Only the original context receives, so clones use the mailbox.
+            self_queue: SelfEnvelopes::default(),
             stage: self.stage,
             stats: Stats::empty(),
+            deadline: None,
         }
     }
 }
 
+impl<C, K> Context<C, K> {
+    async fn send_detailed<M: Message>(
+        &self,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        let kind = MessageKind::regular(self.actor_addr);
+        let name = (message.protocol(), message.name());
+
+        if !self.use_deprecated(&message) {
+            return Err(self.deprecated_error(SendError(message), name, &[]));
+        }
+
+        if !self.spend_trace_budget(name.0) {
+            return Err(self.trace_budget_error(SendError(message), name, &[]));
+        }
+
+        self.do_send_async(message, kind).await.map(drop)
+    }
+}
+
+/// Returned by [`Context::detailed()`] to send messages and get errors with
+/// the context of the failure, see [`DeliveryError`].
+///
+/// Requests provide the context by [`RequestBuilder::resolve_detailed()`].
+#[must_use]
+pub struct Detailed<'c, C, K> {
+    context: &'c Context<C, K>,
+}
+
+impl<'c, C, K> Detailed<'c, C, K> {
+    /// See [`Context::send()`].
+    pub async fn send<M: Message>(&self, message: M) -> Result<(), DeliveryError<SendError<M>>> {
+        self.context.send_detailed(message).await
+    }
+
+    /// See [`Context::try_send()`].
+    pub fn try_send<M: Message>(&self, message: M) -> Result<(), DeliveryError<TrySendError<M>>> {
+        self.context.try_send_detailed(message)
+    }
+
+    /// See [`Context::unbounded_send()`].
+    pub fn unbounded_send<M: Message>(
+        &self,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        self.context.unbounded_send_detailed(message)
+    }
+
+    /// See [`Context::send_to()`].
+    pub async fn send_to<M: Message>(
+        &self,
+        recipient: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        self.context.send_to_detailed(recipient, message).await
+    }
+
+    /// See [`Context::try_send_to()`].
+    pub fn try_send_to<M: Message>(
+        &self,
+        recipient: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<TrySendError<M>>> {
+        self.context.do_try_send_to(recipient, recipient, message)
+    }
+
+    /// See [`Context::unbounded_send_to()`].
+    pub fn unbounded_send_to<M: Message>(
+        &self,
+        recipient: Addr,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        self.context.unbounded_send_to_detailed(recipient, message)
+    }
+
+    /// See [`Context::send_to_group()`].
+    pub async fn send_to_group<M: Message>(
+        &self,
+        group: &GroupRef,
+        message: M,
+    ) -> Result<(), DeliveryError<SendError<M>>> {
+        self.context.send_to_group_detailed(group, message).await
+    }
+}
+
 #[must_use]
 pub struct RequestBuilder<'c, C, K, R, M> {
     context: &'c Context<C, K>,
     request: R,
     to: Option<Addr>,
+    // Remote handles route requests only if the recipient is `NULL`.
+    is_routed: bool,
+    limits: RequestLimits,
+    idempotency_key: Option<IdempotencyKey>,
     marker: PhantomData<M>,
 }
 
@@ -1114,6 +2856,9 @@ impl<'c, C, K, R> RequestBuilder<'c, C, K, R, Any> {
             context,
             request,
             to: None,
+            is_routed: false,
+            limits: RequestLimits::default(),
+            idempotency_key: None,
             marker: PhantomData,
         }
     }
@@ -1124,6 +2869,9 @@ impl<'c, C, K, R> RequestBuilder<'c, C, K, R, Any> {
             context: self.context,
             request: self.request,
             to: self.to,
+            is_routed: self.is_routed,
+            limits: self.limits,
+            idempotency_key: self.idempotency_key,
             marker: PhantomData,
         }
     }
@@ -1137,90 +2885,526 @@ impl<'c, C, K, R: Request, M> RequestBuilder<'c, C, K, R, M> {
         self
     }
 
-    async fn do_send(self, kind: MessageKind) -> bool {
-        if let Some(recipient) = self.to {
+    /// Attaches limits to the request, see [`RequestLimits`].
+    ///
+    /// If the handling time isn't limited, the default TTL of the group
+    /// (`system.ttl`) is used, if any.
+    #[inline]
+    pub fn limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Attaches the idempotency key to the request.
+    ///
+    /// Use the same key for all retries of the same logical request.
+    /// If the recipient's group has the idempotency cache enabled, see
+    /// [`ActorGroup::idempotency_cache()`], and the response to the key is
+    /// still cached, it's replayed without handling the request again.
+    ///
+    /// [`ActorGroup::idempotency_cache()`]: crate::ActorGroup::idempotency_cache
+    #[inline]
+    pub fn idempotency_key(mut self, key: IdempotencyKey) -> Self {
+        self.idempotency_key = Some(key);
+        self
+    }
+
+    /// Returns limits with the default TTL applied, if the handling time
+    /// isn't limited explicitly, and whether it's applied.
+    fn limits_with_default_ttl(&self) -> (RequestLimits, bool) {
+        if self.limits.max_handling_time.is_some() {
+            return (self.limits, false);
+        }
+
+        match scope::try_with(|scope| scope.default_ttl(R::_type_id())).flatten() {
+            Some(ttl) => (self.limits.max_handling_time(ttl), true),
+            None => (self.limits, false),
+        }
+    }
+
+    /// Returns tickets of circuit breakers and recipients of the request.
+    async fn do_send(
+        self,
+        kind: MessageKind,
+    ) -> Result<(Tickets, Addrs), DeliveryError<RequestError>> {
+        let name = (self.request.protocol(), self.request.name());
+
+        if !self.context.use_deprecated(&self.request) {
+            let err = RequestError::Failed;
+            return Err(self.context.deprecated_error(err, name, self.to.as_slice()));
+        }
+
+        if !self.context.spend_trace_budget(name.0) {
+            let err = RequestError::Failed;
+            return Err(self
+                .context
+                .trace_budget_error(err, name, self.to.as_slice()));
+        }
+
+        let is_breaking = scope::try_with(|scope| scope.circuit_breakers().is_enabled());
+
+        let (request, kind, tickets) = if is_breaking == Some(true) {
+            let envelope = Envelope::new(self.request, kind);
+            let tickets = self
+                .context
+                .admit_request(self.to, &envelope)
+                .map_err(|err| self.context.request_error(err, name, self.to.as_slice()))?;
+            let (request, kind) = envelope.unpack::<R>().expect("impossible");
+            (request, kind, tickets)
+        } else {
+            (self.request, kind, Tickets::new())
+        };
+
+        let res = if let Some(recipient) = self.to {
+            let recipients = Addrs::from_slice(&[recipient]);
+            let target = if self.is_routed {
+                Addr::NULL
+            } else {
+                recipient
+            };
             let res = self
                 .context
-                .do_send_to(recipient, self.request, kind, |o, e| {
-                    Object::send(o, recipient, e)
-                });
+                .do_send_to(recipient, request, kind, |o, e| Object::send(o, target, e));
 
             match res {
-                Ok(fut) => fut.await.is_ok(),
-                Err(_) => false,
+                Ok(fut) => match fut.await {
+                    Ok(()) => Ok(recipients),
+                    Err(err) => Err(self
+                        .context
+                        .send_envelope_error(err, drop, name, &recipients)),
+                },
+                Err(_) => Err(self.context.send_error(SendError(()), name, &recipients)),
+            }
+        } else {
+            let res = self.context.do_send_async(request, kind).await;
+            res.map_err(|err| err.map(|_| SendError(())))
+        };
+
+        match res {
+            Ok(recipients) => Ok((tickets, recipients)),
+            Err(err) => {
+                complete_tickets(tickets, false);
+                Err(err.map(|_| RequestError::Failed))
+            }
+        }
+    }
+}
+
+type Tickets = SmallVec<[Ticket; 1]>;
+
+impl<C, K> Context<C, K> {
+    /// Counts the message sent within the current trace.
+    /// Returns `false` if the trace has exceeded its budget,
+    /// see `system.tracing.fan_out`.
+    #[inline]
+    fn spend_trace_budget(&self, protocol: &str) -> bool {
+        scope::try_with(|scope| scope.spend_trace_budget(protocol)).unwrap_or(true)
+    }
+
+    #[cold]
+    fn trace_budget_error<E>(
+        &self,
+        err: E,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<E> {
+        self.delivery_error(ErrorKind::TraceBudgetExceeded, err, name, recipients)
+    }
+
+    /// Counts the deprecated message sent by the group.
+    /// Returns `false` if sending is rejected,
+    /// see `system.deprecation.strict`.
+    #[inline]
+    fn use_deprecated(&self, message: &impl Message) -> bool {
+        let note = ward!(message.deprecation(), return true);
+        scope::try_with(|scope| scope.use_deprecated(Usage::Sent, message, note)).unwrap_or(true)
+    }
+
+    #[cold]
+    fn deprecated_error<E>(
+        &self,
+        err: E,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<E> {
+        self.delivery_error(ErrorKind::Deprecated, err, name, recipients)
+    }
+
+    /// Checks circuit breakers of all destinations of the request.
+    fn admit_request(&self, to: Option<Addr>, envelope: &Envelope) -> Result<Tickets, ErrorKind> {
+        let recipients = match to {
+            Some(recipient) => std::iter::once(recipient).collect(),
+            None => self.demux(envelope),
+        };
+
+        let guard = EbrGuard::new();
+        let mut tickets = Tickets::new();
+
+        scope::with(|scope| {
+            let breakers = scope.circuit_breakers();
+
+            for recipient in recipients {
+                let object = ward!(self.book.get(recipient, &guard), continue);
+                let group = ward!(object.group_name(), continue);
+
+                match breakers.admit(group) {
+                    Some(Ok(ticket)) => tickets.push(ticket),
+                    Some(Err(())) => {
+                        for ticket in tickets.drain(..) {
+                            breakers.cancel(ticket);
+                        }
+                        return Err(ErrorKind::CircuitOpen);
+                    }
+                    None => {}
+                }
             }
+
+            Ok(tickets)
+        })
+    }
+}
+
+impl<C, K> Context<C, K> {
+    /// Returns `true` if all recipients are groups disabled by their mount
+    /// conditions, see `Local::mount_if()`.
+    fn are_disabled_groups(&self, recipients: &[Addr]) -> bool {
+        let guard = EbrGuard::new();
+        self.are_disabled_groups_in(recipients, &guard)
+    }
+
+    fn are_disabled_groups_in(&self, recipients: &[Addr], guard: &EbrGuard) -> bool {
+        !recipients.is_empty()
+            && recipients.iter().all(|recipient| {
+                self.book
+                    .get(*recipient, guard)
+                    .is_some_and(|object| object.is_disabled_group())
+            })
+    }
+
+    #[cold]
+    fn send_error<M>(
+        &self,
+        err: SendError<M>,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<SendError<M>> {
+        let kind = if recipients.is_empty() {
+            ErrorKind::NoRoute
+        } else if self.are_disabled_groups(recipients) {
+            ErrorKind::GroupDisabled
         } else {
-            self.context.do_send_async(self.request, kind).await.is_ok()
+            ErrorKind::Closed
+        };
+
+        self.delivery_error(kind, err, name, recipients)
+    }
+
+    /// Like `send_error()`, but also detects rejections by admission policies,
+    /// whose reasons are kept in returned envelopes.
+    #[cold]
+    fn send_envelope_error<M>(
+        &self,
+        err: SendError<Envelope>,
+        f: impl FnOnce(Envelope) -> M,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<SendError<M>> {
+        let rejection = err.0.rejection();
+        with_rejection(self.send_error(err.map(f), name, recipients), rejection)
+    }
+
+    #[cold]
+    fn try_send_error<M>(
+        &self,
+        err: TrySendError<M>,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<TrySendError<M>> {
+        let kind = if recipients.is_empty() {
+            ErrorKind::NoRoute
+        } else if err.is_full() {
+            ErrorKind::Full
+        } else if self.are_disabled_groups(recipients) {
+            ErrorKind::GroupDisabled
+        } else {
+            ErrorKind::Closed
+        };
+
+        self.delivery_error(kind, err, name, recipients)
+    }
+
+    #[cold]
+    fn request_error(
+        &self,
+        kind: ErrorKind,
+        name: (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<RequestError> {
+        let err = RequestError::from_kind(kind);
+        self.delivery_error(kind, err, name, recipients)
+    }
+
+    /// Attaches the message and the destination to the error.
+    fn delivery_error<E>(
+        &self,
+        kind: ErrorKind,
+        err: E,
+        (protocol, message): (&'static str, &'static str),
+        recipients: &[Addr],
+    ) -> DeliveryError<E> {
+        let mut context = ErrorContext::new(protocol, message);
+
+        // The destination is ambiguous if the message is routed to several groups.
+        if let [recipient] = recipients {
+            context.node_no = recipient.node_no();
+
+            let guard = EbrGuard::new();
+            if let Some(object) = self.book.get(*recipient, &guard) {
+                object.describe_destination(&mut context);
+            }
         }
+
+        DeliveryError::new(kind, err, context)
+    }
+
+    #[cold]
+    pub(crate) fn group_error<E>(
+        &self,
+        kind: ErrorKind,
+        group: &GroupRef,
+        err: E,
+        (protocol, message): (&'static str, &'static str),
+    ) -> DeliveryError<E> {
+        let mut context = ErrorContext::new(protocol, message);
+        context.group = Some(group.name().into());
+        DeliveryError::new(kind, err, context)
+    }
+}
+
+fn complete_tickets(tickets: Tickets, is_success: bool) {
+    if tickets.is_empty() {
+        return;
     }
+
+    scope::with(|scope| {
+        for ticket in tickets {
+            scope.circuit_breakers().complete(ticket, is_success);
+        }
+    });
 }
 
 // TODO: add `pub async fn id() { ... }`
 impl<'c, C: 'static, K, R: Request> RequestBuilder<'c, C, K, R, Any> {
     /// Waits for the response.
     pub async fn resolve(self) -> Result<R::Response, RequestError> {
-        // TODO: use `context.actor` after removing pruned contexts.
-        let this = self.context.actor_addr;
-        let object = self.context.book.get_owned(this).expect("invalid addr");
+        self.resolve_detailed()
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    /// Like [`RequestBuilder::resolve()`], but returns the error with the
+    /// context of the failure, see [`DeliveryError`].
+    pub async fn resolve_detailed(self) -> Result<R::Response, DeliveryError<RequestError>> {
+        self.do_resolve_with_responder()
+            .await
+            .map(|(response, _responder)| response)
+    }
+
+    /// Waits for the response and returns it along with the address of the
+    /// actor that has responded.
+    ///
+    /// The address is concrete, i.e. it's remote if the request has been
+    /// handled on another node. It can be used to pin the conversation to the
+    /// responder by [`Context::send_to()`] and [`Context::request_to()`],
+    /// bypassing routing. Sends to remote actors use the existing connection.
+    ///
+    /// Addresses aren't reused: a restarted actor gets a new one, so sends to
+    /// the old address fail as closed. For remote actors, it
+    /// becomes known only after the remote node reports the actor as gone, so
+    /// a message sent before that may be lost.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # async fn exec(ctx: elfo::Context) {
+    /// # use elfo::message;
+    /// #[message(ret = u64)]
+    /// struct OpenSession;
+    ///
+    /// #[message]
+    /// struct Heartbeat(u64);
+    ///
+    /// let request = ctx.request(OpenSession);
+    /// let (session, responder) = request.resolve_with_responder().await.unwrap();
+    /// // Routing isn't involved, the same actor gets heartbeats.
+    /// ctx.send_to(responder, Heartbeat(session)).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn resolve_with_responder(self) -> Result<(R::Response, Addr), RequestError> {
+        self.do_resolve_with_responder()
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    async fn do_resolve_with_responder(
+        self,
+    ) -> Result<(R::Response, Addr), DeliveryError<RequestError>> {
+        let context = self.context;
+        let object = context.actor_object();
         let actor = object.as_actor().expect("can be called only on actors");
-        let token =
-            actor
-                .request_table()
-                .new_request(self.context.book.clone(), scope::trace_id(), false);
+
+        let sent = self.send(actor).await?;
+        let responses = actor
+            .request_table()
+            .wait(sent.request_id, sent.deadline)
+            .await;
+        context.take_response::<R>(sent, responses)
+    }
+
+    /// Sends the request without waiting for the response,
+    /// which must be taken by [`Context::take_response()`].
+    pub(crate) async fn send(
+        self,
+        actor: &Actor,
+    ) -> Result<SentRequest, DeliveryError<RequestError>> {
+        let name = (self.request.protocol(), self.request.name());
+        let (limits, is_default_ttl) = self.limits_with_default_ttl();
+        let token = actor.request_table().new_request(
+            self.context.book.clone(),
+            scope::trace_id(),
+            false,
+            self.request.name(),
+            self.to,
+            limits,
+            is_default_ttl,
+            self.idempotency_key,
+        );
         let request_id = token.request_id();
+        let deadline = token.deadline();
         let kind = MessageKind::RequestAny(token);
 
-        if !self.do_send(kind).await {
-            actor.request_table().cancel_request(request_id);
-            return Err(RequestError::Failed);
+        match self.do_send(kind).await {
+            Ok((tickets, recipients)) => Ok(SentRequest {
+                request_id,
+                deadline,
+                tickets,
+                recipients,
+                name,
+            }),
+            Err(err) => {
+                actor.request_table().cancel_request(request_id);
+                Err(err)
+            }
         }
+    }
+}
+
+/// A request sent by [`RequestBuilder::send()`], which response isn't taken.
+pub(crate) struct SentRequest {
+    pub(crate) request_id: RequestId,
+    pub(crate) deadline: Option<TokioInstant>,
+    tickets: Tickets,
+    recipients: Addrs,
+    name: (&'static str, &'static str),
+}
+
+impl<C, K> Context<C, K> {
+    /// Returns the object of the actor owning the context.
+    ///
+    /// # Panics
+    ///
+    /// If the context doesn't belong to an actor.
+    pub(crate) fn actor_object(&self) -> OwnedObject {
+        // TODO: use `self.actor` after removing pruned contexts.
+        self.book.get_owned(self.actor_addr).expect("invalid addr")
+    }
 
-        let mut responses = actor.request_table().wait(request_id).await;
+    /// Completes the request sent by [`RequestBuilder::send()`].
+    pub(crate) fn take_response<R: Request>(
+        &self,
+        sent: SentRequest,
+        mut responses: Responses,
+    ) -> Result<(R::Response, Addr), DeliveryError<RequestError>> {
         debug_assert_eq!(responses.len(), 1);
-        prepare_response::<R>(responses.pop().expect("missing response"))
+        let response = responses.pop().expect("missing response");
+        complete_tickets(sent.tickets, response.is_ok());
+        prepare_response::<R>(response)
+            .map_err(|err| self.request_error(err, sent.name, &sent.recipients))
     }
 }
 
 impl<'c, C: 'static, K, R: Request> RequestBuilder<'c, C, K, R, All> {
     /// Waits for the responses.
     pub async fn resolve(self) -> Vec<Result<R::Response, RequestError>> {
+        self.resolve_detailed()
+            .await
+            .into_iter()
+            .map(|res| res.map_err(DeliveryError::into_error))
+            .collect()
+    }
+
+    /// Like [`RequestBuilder::resolve()`], but returns errors with the
+    /// context of the failure, see [`DeliveryError`].
+    pub async fn resolve_detailed(self) -> Vec<Result<R::Response, DeliveryError<RequestError>>> {
+        let context = self.context;
+        let name = (self.request.protocol(), self.request.name());
+
         // TODO: use `context.actor` after removing pruned contexts.
         let this = self.context.actor_addr;
         let object = self.context.book.get_owned(this).expect("invalid addr");
         let actor = object.as_actor().expect("can be called only on actors");
-        let token =
-            actor
-                .request_table()
-                .new_request(self.context.book.clone(), scope::trace_id(), true);
+        let (limits, is_default_ttl) = self.limits_with_default_ttl();
+        let token = actor.request_table().new_request(
+            self.context.book.clone(),
+            scope::trace_id(),
+            true,
+            self.request.name(),
+            self.to,
+            limits,
+            is_default_ttl,
+            self.idempotency_key,
+        );
         let request_id = token.request_id();
+        let deadline = token.deadline();
         let kind = MessageKind::RequestAll(token);
 
-        if !self.do_send(kind).await {
-            actor.request_table().cancel_request(request_id);
-            return vec![Err(RequestError::Failed)];
-        }
+        let (tickets, recipients) = match self.do_send(kind).await {
+            Ok(sent) => sent,
+            Err(err) => {
+                actor.request_table().cancel_request(request_id);
+                return vec![Err(err)];
+            }
+        };
 
-        actor
-            .request_table()
-            .wait(request_id)
-            .await
+        let responses = actor.request_table().wait(request_id, deadline).await;
+        complete_tickets(tickets, responses.iter().all(|r| r.is_ok()));
+
+        // Responses aren't matched with responders, so the destination is
+        // known only if the request has been sent to a single one.
+        responses
             .into_iter()
             .map(prepare_response::<R>)
+            .map(|res| res.map(|(response, _responder)| response))
+            .map(|res| res.map_err(|err| context.request_error(err, name, &recipients)))
             .collect()
     }
 }
 
+// Returns the response along with the responder.
 fn prepare_response<R: Request>(
-    response: Result<Envelope, RequestError>,
-) -> Result<R::Response, RequestError> {
+    response: Result<Envelope, ErrorKind>,
+) -> Result<(R::Response, Addr), ErrorKind> {
     let envelope = response?;
     let (message, kind) = envelope.unpack::<R::Wrapper>().expect("invalid response");
+    let MessageKind::Response { sender, .. } = kind else {
+        unreachable!("invalid response kind");
+    };
 
     // TODO: increase a counter.
     trace!("< {:?}", message);
-    if let Some(permit) = DUMPER.acquire_m(&message) {
+    if let Some(permit) = dumper().acquire_m(&message) {
         permit.record(Dump::message(&message, &kind, Direction::In));
     }
 
-    Ok(message.into())
+    Ok((message.into(), sender))
 }
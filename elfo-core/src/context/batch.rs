@@ -0,0 +1,27 @@
+use crate::envelope::Envelope;
+
+/// Envelopes received by [`Context::recv_many()`].
+///
+/// [`Context::recv_many()`]: crate::Context::recv_many
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Batch {
+    /// Received envelopes in order, can be empty if the batch is interrupted
+    /// by the first envelope.
+    pub items: Vec<Envelope>,
+    /// A system message (from [`messages`], e.g. `Terminate` or
+    /// `ConfigUpdated`) that interrupted the batch. It's received after all
+    /// `items` and should be handled after them.
+    ///
+    /// [`messages`]: crate::messages
+    pub interrupted_by: Option<Envelope>,
+}
+
+impl Batch {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            interrupted_by: None,
+        }
+    }
+}
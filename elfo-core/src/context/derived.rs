@@ -0,0 +1,55 @@
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
+
+use fxhash::FxHashMap;
+use tracing::error;
+
+use crate::{errors::DeriveConfigError, panics};
+
+/// Values derived from the config, one per type.
+/// Every value is rebuilt at most once per config generation.
+#[derive(Default)]
+pub(super) struct DerivedConfigs {
+    map: FxHashMap<TypeId, Entry>,
+}
+
+struct Entry {
+    generation: u64,
+    value: Result<Arc<dyn Any + Send + Sync>, DeriveConfigError>,
+}
+
+impl DerivedConfigs {
+    pub(super) fn get_or_build<C, T>(
+        &mut self,
+        generation: u64,
+        config: &C,
+        build: impl FnOnce(&C) -> T,
+    ) -> Result<Arc<T>, DeriveConfigError>
+    where
+        T: Send + Sync + 'static,
+    {
+        let entry = self.map.entry(TypeId::of::<T>()).or_insert_with(|| Entry {
+            // Never matches an actual generation.
+            generation: u64::MAX,
+            value: Err(DeriveConfigError::new(String::new())),
+        });
+
+        if entry.generation != generation {
+            entry.generation = generation;
+            entry.value = match panics::sync_catch(|| build(config)) {
+                Ok(value) => Ok(Arc::new(value)),
+                Err(reason) => {
+                    error!(%reason, "cannot derive a value from the config");
+                    Err(DeriveConfigError::new(reason))
+                }
+            };
+        }
+
+        match &entry.value {
+            Ok(value) => Ok(value.clone().downcast().expect("invariant")),
+            Err(err) => Err(err.clone()),
+        }
+    }
+}
@@ -4,9 +4,14 @@ use metrics::{self, Key, Label};
 use elfo_utils::time::Instant;
 
 use crate::{envelope::Envelope, message::Message};
+#[cfg(feature = "test-util")]
+use crate::{probe, tracing::TraceId, Addr};
 
 pub(super) struct Stats {
     in_handling: Option<InHandling>,
+    /// The sender and the trace id of the handled message, see [`probe`].
+    #[cfg(feature = "test-util")]
+    probed: Option<(Addr, TraceId)>,
 }
 
 #[derive(Constructor)]
@@ -20,12 +25,18 @@ static EMPTY_MAILBOX_LABELS: &[Label] = &[Label::from_static_parts("message", "<
 
 impl Stats {
     pub(super) fn empty() -> Self {
-        Self { in_handling: None }
+        Self {
+            in_handling: None,
+            #[cfg(feature = "test-util")]
+            probed: None,
+        }
     }
 
     pub(super) fn startup() -> Self {
         Self {
             in_handling: Some(InHandling::new(STARTUP_LABELS, Instant::now())),
+            #[cfg(feature = "test-util")]
+            probed: None,
         }
     }
 
@@ -37,6 +48,11 @@ impl Stats {
     pub(super) fn on_received_envelope(&mut self, envelope: &Envelope) {
         debug_assert!(self.in_handling.is_none());
 
+        #[cfg(feature = "test-util")]
+        if probe::is_active() {
+            self.probed = Some((envelope.sender(), envelope.trace_id()));
+        }
+
         let recorder = ward!(metrics::try_recorder());
         let key = Key::from_static_name("elfo_message_waiting_time_seconds");
         let now = Instant::now();
@@ -61,6 +77,11 @@ impl Stats {
     }
 
     fn emit_handling_time(&mut self) {
+        #[cfg(feature = "test-util")]
+        if let Some((sender, trace_id)) = self.probed.take() {
+            probe::on_handled(sender, trace_id);
+        }
+
         let in_handling = ward!(self.in_handling.take());
         let recorder = ward!(metrics::try_recorder());
         let key = Key::from_static_parts("elfo_message_handling_time_seconds", in_handling.labels);
@@ -0,0 +1,193 @@
+//! Dropping of duplicated envelopes, see [`ActorGroup::dedup_by()`].
+//!
+//! [`ActorGroup::dedup_by()`]: crate::ActorGroup::dedup_by
+
+use std::{hash::Hash, marker::PhantomData, mem, sync::Arc, time::Duration};
+
+use fxhash::FxHashSet;
+use tokio::time::Instant;
+
+use crate::{envelope::Envelope, Message};
+
+/// A window, within which keys are remembered by [`ActorGroup::dedup_by()`].
+///
+/// Keys are stored in a rotating pair of sets: new keys are inserted into
+/// the current set, which replaces the previous one once the window is over.
+/// Thus, a key is remembered at least for the window and at most for
+/// two windows.
+///
+/// [`ActorGroup::dedup_by()`]: crate::ActorGroup::dedup_by
+#[derive(Debug, Clone, Copy)]
+pub struct DedupWindow(WindowKind);
+
+#[derive(Debug, Clone, Copy)]
+enum WindowKind {
+    Count(usize),
+    Time(Duration),
+}
+
+impl DedupWindow {
+    /// Remembers at least `count` last keys, but at most twice as many.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is zero.
+    pub fn count(count: usize) -> Self {
+        assert!(count > 0, "the window must be non-empty");
+        Self(WindowKind::Count(count))
+    }
+
+    /// Remembers keys seen during at least `period`, but at most twice as long.
+    ///
+    /// Memory usage depends on the rate of messages, prefer [`Self::count()`]
+    /// if the rate is unpredictable.
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero.
+    pub fn time(period: Duration) -> Self {
+        assert!(!period.is_zero(), "the window must be non-empty");
+        Self(WindowKind::Time(period))
+    }
+}
+
+/// Creates a filter for every started actor.
+pub(crate) type FilterFactory = Arc<dyn Fn() -> Box<dyn Filter> + Send + Sync>;
+
+pub(crate) fn factory<M, K>(
+    extract: impl Fn(&M) -> K + Send + Sync + 'static,
+    window: DedupWindow,
+) -> FilterFactory
+where
+    M: Message,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let extract = Arc::new(extract);
+    Arc::new(move || {
+        Box::new(TypedFilter {
+            extract: extract.clone(),
+            keys: RotatingSet::new(window.0),
+            marker: PhantomData,
+        })
+    })
+}
+
+/// Filters of one actor, the state is lost on restarts.
+#[derive(Default)]
+pub(crate) struct Dedup {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl Dedup {
+    pub(crate) fn new(factories: &[FilterFactory]) -> Self {
+        Self {
+            filters: factories.iter().map(|factory| factory()).collect(),
+        }
+    }
+
+    /// Returns `true` if the envelope's key has been seen within the window.
+    #[inline]
+    pub(crate) fn is_duplicate(&mut self, envelope: &Envelope) -> bool {
+        !self.filters.is_empty() && self.filters.iter_mut().any(|f| f.is_duplicate(envelope))
+    }
+}
+
+pub(crate) trait Filter: Send + Sync {
+    fn is_duplicate(&mut self, envelope: &Envelope) -> bool;
+}
+
+struct TypedFilter<M, K, F> {
+    extract: Arc<F>,
+    keys: RotatingSet<K>,
+    marker: PhantomData<fn(&M)>,
+}
+
+impl<M, K, F> Filter for TypedFilter<M, K, F>
+where
+    M: Message,
+    K: Hash + Eq + Send + Sync,
+    F: Fn(&M) -> K + Send + Sync,
+{
+    fn is_duplicate(&mut self, envelope: &Envelope) -> bool {
+        // Other types are skipped without touching their content.
+        let message = envelope.message();
+        let message = ward!(message.downcast_ref::<M>(), return false);
+        !self.keys.insert((self.extract)(message))
+    }
+}
+
+struct RotatingSet<K> {
+    window: WindowKind,
+    current: FxHashSet<K>,
+    previous: FxHashSet<K>,
+    rotated_at: Instant,
+}
+
+impl<K: Hash + Eq> RotatingSet<K> {
+    fn new(window: WindowKind) -> Self {
+        Self {
+            window,
+            current: FxHashSet::default(),
+            previous: FxHashSet::default(),
+            rotated_at: Instant::now(),
+        }
+    }
+
+    /// Returns `false` if the key is already present.
+    fn insert(&mut self, key: K) -> bool {
+        self.rotate_if_needed();
+        !self.previous.contains(&key) && self.current.insert(key)
+    }
+
+    fn rotate_if_needed(&mut self) {
+        match self.window {
+            WindowKind::Count(count) => {
+                if self.current.len() >= count {
+                    self.rotate();
+                }
+            }
+            WindowKind::Time(period) => {
+                let elapsed = self.rotated_at.elapsed();
+                if elapsed >= period {
+                    self.rotate();
+                    self.rotated_at = Instant::now();
+
+                    // Both windows are over.
+                    if elapsed >= 2 * period {
+                        self.previous.clear();
+                    }
+                }
+            }
+        }
+    }
+
+    fn rotate(&mut self) {
+        // Reuse allocations.
+        mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_window() {
+        let mut set = RotatingSet::new(WindowKind::Count(2));
+        assert!(set.insert(1));
+        assert!(set.insert(2));
+        assert!(!set.insert(1));
+
+        // Rotated, but still remembered.
+        assert!(set.insert(3));
+        assert!(!set.insert(1));
+        assert!(!set.insert(2));
+        assert!(set.insert(4));
+
+        // Rotated again, `1` and `2` are forgotten.
+        assert!(set.insert(5));
+        assert!(set.insert(1));
+        assert!(!set.insert(5));
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use idr_ebr::EbrGuard;
+use metrics::increment_counter;
+use parking_lot::Mutex;
+use serde::{Serialize, Serializer};
+use slotmap::{new_key_type, Key, SlotMap};
+use tracing::{debug, warn};
+
+use elfo_utils::time::Instant;
+
+use crate::{
+    actor::ActorMeta, dumping::extract_name_by_type, errors::ErrorKind,
+    request_table::ResponseToken, scope,
+};
+
+new_key_type! {
+    struct DeferredId;
+}
+
+// === DeferredTable ===
+
+/// Stores deferred tokens of the actor, see [`ResponseToken::defer()`].
+pub(crate) struct DeferredTable {
+    meta: Arc<ActorMeta>,
+    entries: Mutex<SlotMap<DeferredId, Entry>>,
+}
+
+struct Entry {
+    token: ResponseToken,
+    name: &'static str,
+    message_name: String,
+    created_time: Instant,
+}
+
+impl DeferredTable {
+    pub(crate) fn new(meta: Arc<ActorMeta>) -> Arc<Self> {
+        Arc::new(Self {
+            meta,
+            entries: Mutex::new(SlotMap::default()),
+        })
+    }
+
+    fn insert(&self, token: ResponseToken, name: &'static str, message_name: String) -> DeferredId {
+        self.entries.lock().insert(Entry {
+            token,
+            name,
+            message_name,
+            created_time: Instant::now(),
+        })
+    }
+
+    fn take(&self, id: DeferredId) -> Option<ResponseToken> {
+        self.entries.lock().remove(id).map(|entry| entry.token)
+    }
+
+    /// Fails with `ErrorKind::Timeout` if the token isn't resolved yet.
+    fn expire(&self, id: DeferredId) {
+        let entry = ward!(self.entries.lock().remove(id));
+
+        // The actor is most likely forgotten about the token.
+        warn!(
+            message = "deferred response is expired",
+            group = %self.meta.group,
+            key = %self.meta.key,
+            name = entry.name,
+            request = %entry.message_name,
+            age = ?entry.created_time.elapsed(),
+        );
+        increment_counter!("elfo_expired_deferred_responses_total");
+
+        entry.token.fail(ErrorKind::Timeout);
+    }
+
+    pub(crate) fn stats(&self) -> DeferredStats {
+        let entries = self.entries.lock();
+
+        DeferredStats {
+            count: entries.len(),
+            oldest_age: entries
+                .values()
+                .map(|entry| entry.created_time)
+                .min()
+                .map(|created_time| created_time.elapsed()),
+        }
+    }
+
+    /// Drops all tokens, so requesters get `RequestError::Ignored` immediately
+    /// instead of waiting for the reclamation of the actor.
+    pub(crate) fn drop_all(&self) {
+        let entries = std::mem::take(&mut *self.entries.lock());
+        if !entries.is_empty() {
+            debug!(
+                count = entries.len(),
+                "unresolved deferred responses are dropped"
+            );
+        }
+    }
+}
+
+// === DeferredStats ===
+
+/// Stats of unresolved deferred responses of the actor,
+/// see [`Context::deferred_stats()`].
+///
+/// [`Context::deferred_stats()`]: crate::Context::deferred_stats
+#[derive(Debug, Default, Clone, Copy)]
+#[non_exhaustive]
+pub struct DeferredStats {
+    /// The number of unresolved deferred tokens.
+    pub count: usize,
+    /// The age of the oldest one, `None` if there are no tokens.
+    pub oldest_age: Option<Duration>,
+}
+
+// === DeferredToken ===
+
+/// A response token stored by the actor to respond later, e.g. once the
+/// required data is received by another message.
+///
+/// Unlike [`ResponseToken`], it's owned by the actor rather than its state,
+/// named and serializable, so it can be a part of dumped messages. It's
+/// resolved by [`Context::respond_deferred()`]. Tokens left unresolved are
+/// dropped once the actor terminates or expire after the deadline, see
+/// [`DeferredToken::with_deadline()`].
+///
+/// [`Context::respond_deferred()`]: crate::Context::respond_deferred
+#[must_use]
+pub struct DeferredToken<R> {
+    id: DeferredId,
+    name: &'static str,
+    table: Weak<DeferredTable>,
+    marker: PhantomData<fn(R)>,
+}
+
+impl<R> DeferredToken<R> {
+    /// Returns the name provided to [`ResponseToken::defer()`].
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Sets the deadline, after which the request fails with
+    /// [`ErrorKind::Timeout`] and the leak is logged.
+    ///
+    /// The deadline is driven by `tokio` timers, so it respects virtual time.
+    pub fn with_deadline(self, timeout: Duration) -> Self {
+        let table = self.table.clone();
+        let id = self.id;
+
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+
+            if let Some(table) = table.upgrade() {
+                table.expire(id);
+            }
+        });
+
+        self
+    }
+
+    pub(crate) fn take(self) -> Option<ResponseToken> {
+        self.table.upgrade()?.take(self.id)
+    }
+}
+
+impl<R> fmt::Debug for DeferredToken<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredToken")
+            .field("name", &self.name)
+            .field("id", &self.id.data().as_ffi())
+            .finish()
+    }
+}
+
+impl<R> Serialize for DeferredToken<R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<R> ResponseToken<R> {
+    /// Defers the response, so it can be provided later by
+    /// [`Context::respond_deferred()`], e.g. from another message handler.
+    ///
+    /// The name is used only for debugging purposes.
+    ///
+    /// # Panics
+    /// If called outside the actor.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # use elfo_core as elfo;
+    /// # use elfo::message;
+    /// # #[message(ret = u32)] struct GetPrice;
+    /// # #[message] struct PriceUpdated(u32);
+    /// # async fn exec(mut ctx: elfo::Context) {
+    /// use elfo::{msg, DeferredToken};
+    ///
+    /// let mut waiting: Vec<DeferredToken<GetPrice>> = Vec::new();
+    ///
+    /// while let Some(envelope) = ctx.recv().await {
+    ///     msg!(match envelope {
+    ///         (GetPrice, token) => {
+    ///             let deferred = token.defer("price").with_deadline(Duration::from_secs(5));
+    ///             waiting.push(deferred);
+    ///         }
+    ///         PriceUpdated(price) => {
+    ///             for deferred in waiting.drain(..) {
+    ///                 ctx.respond_deferred(deferred, price);
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`Context::respond_deferred()`]: crate::Context::respond_deferred
+    pub fn defer(self, name: &'static str) -> DeferredToken<R> {
+        let addr = scope::with(|scope| scope.actor());
+
+        let Some(book) = self.book().cloned() else {
+            // Forgotten tokens are never resolved.
+            return DeferredToken {
+                id: DeferredId::null(),
+                name,
+                table: Weak::new(),
+                marker: PhantomData,
+            };
+        };
+
+        let table = {
+            let guard = EbrGuard::new();
+            book.get(addr, &guard)
+                .and_then(|object| object.as_actor().map(|a| a.deferred_table().clone()))
+                .expect("`defer()` must be called inside an actor")
+        };
+
+        let message_name = extract_name_by_type::<R>().to_string();
+        let id = table.insert(self.into_untyped(), name, message_name);
+
+        DeferredToken {
+            id,
+            name,
+            table: Arc::downgrade(&table),
+            marker: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! [Config].
+//!
+//! [Config]: DeprecationConfig
+
+use serde::Deserialize;
+
+use crate::config::Duration;
+
+/// How uses of messages marked by `#[message(deprecated = "..")]` are
+/// reported by actors of the group.
+///
+/// Every use is counted by `elfo_deprecated_sent_messages_total` and
+/// `elfo_deprecated_handled_messages_total` metrics, but warnings are logged
+/// at most once per `warn_interval` for every message.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.deprecation.warn_interval = "10m"
+/// system.deprecation.strict = true
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DeprecationConfig {
+    /// How often a warning about the same deprecated message is logged,
+    /// separately for sent and handled ones.
+    ///
+    /// `1h` by default.
+    pub warn_interval: Duration,
+    /// Rejects sending of deprecated messages with `Deprecated` errors
+    /// instead of warnings. Handling of them isn't affected.
+    ///
+    /// `false` by default.
+    pub strict: bool,
+}
+
+impl Default for DeprecationConfig {
+    fn default() -> Self {
+        Self {
+            warn_interval: Duration::from_secs(3600),
+            strict: false,
+        }
+    }
+}
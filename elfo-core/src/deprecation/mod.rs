@@ -0,0 +1,90 @@
+//! Warnings about deprecated messages, see [`DeprecationConfig`].
+
+use fxhash::FxHashMap;
+use metrics::Key;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use elfo_utils::time::Instant;
+
+use self::config::DeprecationConfig;
+use crate::message::{Message, MessageTypeId};
+
+pub mod config;
+
+/// How a deprecated message is used by the group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Usage {
+    Sent,
+    Handled,
+}
+
+/// Tracks uses of deprecated messages by actors of one group.
+#[derive(Default)]
+pub(crate) struct Deprecations(Mutex<State>);
+
+#[derive(Default)]
+struct State {
+    config: DeprecationConfig,
+    /// When the last warning about the message has been logged.
+    warned: FxHashMap<(MessageTypeId, Usage), Instant>,
+}
+
+impl Deprecations {
+    pub(crate) fn configure(&self, config: &DeprecationConfig) {
+        self.0.lock().config = config.clone();
+    }
+
+    /// Counts the use of the deprecated message and warns about it, if it
+    /// hasn't been done recently. Returns `false` if sending is rejected,
+    /// see `system.deprecation.strict`.
+    #[cold]
+    pub(crate) fn on_used(&self, usage: Usage, message: &impl Message, note: &'static str) -> bool {
+        let name = match usage {
+            Usage::Sent => "elfo_deprecated_sent_messages_total",
+            Usage::Handled => "elfo_deprecated_handled_messages_total",
+        };
+
+        if let Some(recorder) = metrics::try_recorder() {
+            let key = Key::from_static_parts(name, message.labels());
+            recorder.increment_counter(&key, 1);
+        }
+
+        let (is_rejected, should_warn) = {
+            let mut state = self.0.lock();
+            let is_rejected = usage == Usage::Sent && state.config.strict;
+            let warn_interval = *state.config.warn_interval;
+            let now = Instant::now();
+
+            let type_id = MessageTypeId::new(message._vtable());
+            let should_warn = match state.warned.get_mut(&(type_id, usage)) {
+                Some(warned) if now.duration_since(*warned) < warn_interval => false,
+                Some(warned) => {
+                    *warned = now;
+                    true
+                }
+                None => {
+                    state.warned.insert((type_id, usage), now);
+                    true
+                }
+            };
+
+            (is_rejected, should_warn)
+        };
+
+        if should_warn {
+            let protocol = message.protocol();
+            let name = message.name();
+
+            match usage {
+                Usage::Sent if is_rejected => {
+                    warn!(protocol, name, note, "deprecated message is rejected")
+                }
+                Usage::Sent => warn!(protocol, name, note, "deprecated message is sent"),
+                Usage::Handled => warn!(protocol, name, note, "deprecated message is handled"),
+            }
+        }
+
+        !is_rejected
+    }
+}
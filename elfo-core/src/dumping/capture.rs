@@ -0,0 +1,131 @@
+//! In-memory capture of dumps, used by `elfo-test` to assert dumped messages.
+//! Every topology has its own capture, which is bounded in size.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use parking_lot::Mutex;
+
+#[cfg(feature = "dumping")]
+use super::control::CheckResult;
+use super::{
+    dump::{Direction, MessageKind},
+    Dump, SequenceNo,
+};
+use crate::{
+    actor::ActorMeta,
+    addr::Addr,
+    message::{MessageTypeId, MessageVTable},
+    scope::{self, SerdeMode},
+    tracing::TraceId,
+    Message,
+};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+#[doc(hidden)]
+pub struct DumpCapture {
+    capacity: usize,
+    dumps: Mutex<VecDeque<CapturedDump>>,
+}
+
+impl Default for DumpCapture {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl DumpCapture {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            dumps: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns all captured dumps in the order of recording.
+    pub fn snapshot(&self) -> Vec<CapturedDump> {
+        self.dumps.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.dumps.lock().clear();
+    }
+
+    pub(crate) fn push(&self, class: &'static str, dump: &Dump) {
+        let message = scope::with_serde_mode(SerdeMode::Dumping, || {
+            serde_value::to_value(&*dump.message).map_err(|err| err.to_string())
+        });
+
+        let message_name = dump.message_name.to_string();
+        let message_type =
+            MessageVTable::lookup(dump.message_protocol, &message_name).map(MessageTypeId::new);
+
+        let captured = CapturedDump {
+            meta: dump.meta.clone(),
+            class,
+            sequence_no: dump.sequence_no,
+            trace_id: dump.trace_id,
+            is_incoming: dump.direction == Direction::In,
+            recipient: dump.recipient,
+            message_name,
+            message_protocol: dump.message_protocol,
+            message_kind: dump.message_kind,
+            message,
+            message_type,
+        };
+
+        let mut dumps = self.dumps.lock();
+        // The oldest dumps are lost.
+        if dumps.len() >= self.capacity {
+            dumps.pop_front();
+        }
+        dumps.push_back(captured);
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct CapturedDump {
+    pub meta: Arc<ActorMeta>,
+    pub class: &'static str,
+    pub sequence_no: SequenceNo,
+    pub trace_id: TraceId,
+    pub is_incoming: bool,
+    /// The address the message is sent to, `NULL` if it's routed or incoming.
+    pub recipient: Addr,
+    pub message_name: String,
+    pub message_protocol: &'static str,
+    pub message_kind: MessageKind,
+    /// The serialized message or a serialization error.
+    pub message: Result<serde_value::Value, String>,
+    message_type: Option<MessageTypeId>,
+}
+
+impl CapturedDump {
+    /// Deserializes the message if it has the specified type.
+    /// Returns `None` if the type differs or some fields are hidden.
+    pub fn message<M: Message>(&self) -> Option<M> {
+        if !self.message_type.is_some_and(M::_is_supertype_of) {
+            return None;
+        }
+
+        let value = self.message.as_ref().ok()?.clone();
+        value.deserialize_into().ok()
+    }
+}
+
+/// Returns a capture of the current scope if a dump should be captured.
+/// `is_checked` is `true` if limits have already been checked by a recorder.
+#[cfg(feature = "dumping")]
+pub(crate) fn acquire(class: &'static str, is_checked: bool) -> Option<Arc<DumpCapture>> {
+    scope::try_with(|scope| {
+        let capture = scope.dumping().capture()?;
+
+        if !is_checked && !matches!(scope.check_dumping(class), CheckResult::Passed) {
+            return None;
+        }
+
+        Some(capture.clone())
+    })
+    .flatten()
+}
@@ -0,0 +1,53 @@
+//! Overrides of the dumping class, see [`scope::with_dump_class()`] and
+//! [`ActorGroup::dump_class_by()`].
+//!
+//! [`scope::with_dump_class()`]: crate::scope::with_dump_class
+//! [`ActorGroup::dump_class_by()`]: crate::ActorGroup::dump_class_by
+
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+use super::Dumper;
+use crate::envelope::Envelope;
+
+/// Returns the dumper of the class.
+///
+/// Dumpers are created once per class and never freed, so overrides don't
+/// allocate on the hot path. The number of classes is expected to be small.
+pub(crate) fn dumper_of(class: &'static str) -> &'static Dumper {
+    static DUMPERS: Lazy<RwLock<FxHashMap<&'static str, &'static Dumper>>> =
+        Lazy::new(Default::default);
+
+    if let Some(dumper) = DUMPERS.read().get(class) {
+        return dumper;
+    }
+
+    DUMPERS
+        .write()
+        .entry(class)
+        .or_insert_with(|| Box::leak(Box::new(Dumper::new(class))))
+}
+
+/// Chooses the class of incoming messages, see
+/// [`ActorGroup::dump_class_by()`].
+///
+/// [`ActorGroup::dump_class_by()`]: crate::ActorGroup::dump_class_by
+#[derive(Clone)]
+pub(crate) struct DumpClassifier(Arc<ClassifyFn>);
+
+type ClassifyFn = dyn Fn(&Envelope) -> Option<&'static str> + Send + Sync;
+
+impl DumpClassifier {
+    pub(crate) fn new(
+        f: impl Fn(&Envelope) -> Option<&'static str> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn classify(&self, envelope: &Envelope) -> Option<&'static Dumper> {
+        (self.0)(envelope).map(dumper_of)
+    }
+}
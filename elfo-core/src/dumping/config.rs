@@ -2,8 +2,12 @@
 //!
 //! [Config]: DumpingConfig
 
+use std::collections::HashMap;
+
 use serde::Deserialize;
 
+use crate::config::ByteSize;
+
 /// Dumping configuration.
 ///
 /// # Example
@@ -11,6 +15,7 @@ use serde::Deserialize;
 /// [some_group]
 /// system.dumping.disabled = false
 /// system.dumping.max_rate = 1_000
+/// system.dumping.trace_sample_rate = 0.01
 /// ```
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
@@ -24,7 +29,24 @@ pub struct DumpingConfig {
     ///
     /// `100_000` by default.
     pub max_rate: u64,
-    // TODO: per class overrides.
+    /// A share of traces, which messages are dumped, in `[0, 1]`.
+    ///
+    /// The decision is made by the trace id only, so every trace is either
+    /// dumped completely by all actors and nodes with the same rate, or isn't
+    /// dumped at all. Traces marked by [`Context::force_sampling()`] are
+    /// always dumped.
+    ///
+    /// `1.0` by default.
+    ///
+    /// [`Context::force_sampling()`]: crate::Context::force_sampling
+    pub trace_sample_rate: f64,
+    /// Overrides `trace_sample_rate` for specific dump classes.
+    ///
+    /// Empty by default.
+    pub class_trace_sample_rates: HashMap<String, f64>,
+    /// Messages recently handled by every actor of the group, which are kept
+    /// in memory, see [`RecentDumpsConfig`].
+    pub recent: RecentDumpsConfig,
 }
 
 impl Default for DumpingConfig {
@@ -32,6 +54,65 @@ impl Default for DumpingConfig {
         Self {
             disabled: false,
             max_rate: 100_000,
+            trace_sample_rate: 1.,
+            class_trace_sample_rates: HashMap::new(),
+            recent: RecentDumpsConfig::default(),
+        }
+    }
+}
+
+impl DumpingConfig {
+    pub(crate) fn trace_sample_rate(&self, class: &str) -> f64 {
+        self.class_trace_sample_rates
+            .get(class)
+            .copied()
+            .unwrap_or(self.trace_sample_rate)
+    }
+}
+
+/// Messages recently handled by the actor are kept in memory regardless of
+/// other dumping settings, attached to the panic report (see
+/// [`Panic::recent_dumps`]) and returned by [`GetRecentDumps`].
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.dumping.recent.count = 16
+/// system.dumping.recent.max_size = "64KiB"
+/// system.dumping.recent.payloads = true
+/// ```
+///
+/// [`Panic::recent_dumps`]: crate::panics::Panic::recent_dumps
+/// [`GetRecentDumps`]: crate::messages::GetRecentDumps
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RecentDumpsConfig {
+    /// How many messages are kept by every actor, `0` disables keeping.
+    ///
+    /// `0` by default.
+    pub count: usize,
+    /// The maximum total size of kept messages, including payloads.
+    /// The oldest messages are dropped to fit the size.
+    ///
+    /// `64KiB` by default.
+    pub max_size: ByteSize,
+    /// Whether to keep messages serialized to JSON in addition to names.
+    ///
+    /// `false` by default.
+    pub payloads: bool,
+    /// Longer payloads are truncated.
+    ///
+    /// `1KiB` by default.
+    pub max_payload: ByteSize,
+}
+
+impl Default for RecentDumpsConfig {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            max_size: ByteSize::new(64 * 1024),
+            payloads: false,
+            max_payload: ByteSize::new(1024),
         }
     }
 }
@@ -6,10 +6,13 @@ use smallvec::SmallVec;
 
 use elfo_utils::{CachePadded, RateLimit, RateLimiter};
 
+#[cfg(feature = "test-util")]
+use super::capture::DumpCapture;
 use super::{
     config::DumpingConfig,
     sequence_no::{SequenceNo, SequenceNoGenerator},
 };
+use crate::tracing::{TraceId, TraceSampler};
 
 #[stability::unstable]
 #[derive(Default)]
@@ -17,12 +20,15 @@ pub struct DumpingControl {
     config: Mutex<DumpingConfig>,
     sequence_no_gen: CachePadded<SequenceNoGenerator>,
     classes: ArcSwap<SmallVec<[PerClass; 1]>>, // TODO: use `SecondaryMap`?
+    #[cfg(feature = "test-util")]
+    capture: once_cell::sync::OnceCell<Arc<DumpCapture>>,
 }
 
 #[derive(Clone)]
 struct PerClass {
     class: &'static str,
     disabled: bool,
+    sampler: TraceSampler,
     limiter: Arc<CachePadded<RateLimiter>>,
 }
 
@@ -31,6 +37,7 @@ impl PerClass {
         Self {
             class,
             disabled: true,
+            sampler: TraceSampler::default(),
             limiter: Default::default(),
         }
     }
@@ -42,12 +49,14 @@ impl PerClass {
         Self {
             class: self.class,
             disabled: config.disabled,
+            sampler: TraceSampler::new(config.trace_sample_rate(self.class)),
             limiter,
         }
     }
 
-    fn check(&self) -> CheckResult {
-        if self.disabled {
+    fn check(&self, trace_id: Option<TraceId>) -> CheckResult {
+        // Check sampling first in order to not consume the rate limit.
+        if self.disabled || trace_id.is_some_and(|id| !self.sampler.is_sampled(id)) {
             CheckResult::NotInterested
         } else if self.limiter.acquire() {
             CheckResult::Passed
@@ -73,19 +82,56 @@ impl DumpingControl {
         self.classes.store(Arc::new(new_classes));
     }
 
+    #[cfg(feature = "test-util")]
+    pub(crate) fn set_capture(&self, capture: Arc<DumpCapture>) {
+        let _ = self.capture.set(capture);
+    }
+
+    #[cfg(all(feature = "test-util", feature = "dumping"))]
+    pub(crate) fn capture(&self) -> Option<&Arc<DumpCapture>> {
+        self.capture.get()
+    }
+
     pub(crate) fn next_sequence_no(&self) -> SequenceNo {
         self.sequence_no_gen.generate()
     }
 
+    /// Checks whether a message of the class can be dumped.
+    /// Prefer [`Scope::check_dumping()`], which also applies trace sampling.
+    ///
+    /// [`Scope::check_dumping()`]: crate::scope::Scope::check_dumping
     #[stability::unstable]
     pub fn check(&self, class: &'static str) -> CheckResult {
+        self.check_in_trace(class, None)
+    }
+
+    /// Checks whether a message of the class can be dumped in a detailed
+    /// trace: sampling and rate limiting are bypassed.
+    pub(crate) fn check_detailed(&self, class: &'static str) -> CheckResult {
+        let is_disabled = self.with_class(class, |per_class| per_class.disabled);
+
+        if is_disabled {
+            CheckResult::NotInterested
+        } else {
+            CheckResult::Passed
+        }
+    }
+
+    /// `trace_id` is `None` if the trace is sampled anyway.
+    pub(crate) fn check_in_trace(
+        &self,
+        class: &'static str,
+        trace_id: Option<TraceId>,
+    ) -> CheckResult {
+        self.with_class(class, |per_class| per_class.check(trace_id))
+    }
+
+    fn with_class<R>(&self, class: &'static str, f: impl FnOnce(&PerClass) -> R) -> R {
         if let Some(per_class) = find_class(&self.classes.load(), class) {
-            per_class.check()
+            f(per_class)
         } else {
             self.add_class(class);
-            find_class(&self.classes.load(), class)
-                .expect("absent class")
-                .check()
+            f(find_class(&self.classes.load(), class).expect("absent class"))
         }
     }
 
@@ -7,7 +7,10 @@ use smallbox::{smallbox, SmallBox};
 use elfo_utils::time::SystemTime;
 
 use super::{extract_name::extract_name, sequence_no::SequenceNo};
-use crate::{actor::ActorMeta, envelope, scope, thread::ThreadId, tracing::TraceId, Message};
+use crate::{
+    actor::ActorMeta, addr::Addr, channel::ChannelId, envelope, scope, thread::ThreadId,
+    tracing::TraceId, Message,
+};
 
 // === Dump ===
 
@@ -20,10 +23,16 @@ pub struct Dump {
     pub trace_id: TraceId,
     pub thread_id: ThreadId,
     pub direction: Direction,
+    /// The address the message is sent to, `NULL` if it's routed or incoming.
+    pub recipient: Addr,
     pub message_name: MessageName,
     pub message_protocol: &'static str,
     pub message_kind: MessageKind,
     pub message: ErasedMessage,
+    /// Made in a detailed trace, see `Scope::force_sampling()`.
+    pub is_detailed: bool,
+    /// The channel the message is sent over, see `Context::open_channel()`.
+    pub channel_id: Option<ChannelId>,
 }
 
 #[doc(hidden)]
@@ -31,7 +40,10 @@ pub struct Dump {
 pub type ErasedMessage = SmallBox<dyn ErasedSerialize + Send, [usize; 24]>;
 
 assert_impl_all!(Dump: Send);
-assert_eq_size!(Dump, [u8; 320]);
+#[cfg(not(feature = "trace-id-128"))]
+assert_eq_size!(Dump, [u8; 336]);
+#[cfg(feature = "trace-id-128")]
+assert_eq_size!(Dump, [u8; 352]);
 
 impl Dump {
     #[stability::unstable]
@@ -39,9 +51,11 @@ impl Dump {
         DumpBuilder {
             timestamp: None,
             direction: Direction::Out,
+            recipient: Addr::NULL,
             message_name: None,
             message_protocol: "",
             message_kind: MessageKind::Regular,
+            sequence_no: None,
         }
     }
 
@@ -57,6 +71,37 @@ impl Dump {
             .message_kind(MessageKind::from_message_kind(kind))
             .do_finish(message._erase())
     }
+
+    /// Dumps an outgoing message sent directly to the recipient.
+    pub(crate) fn message_to(
+        message: &impl Message,
+        kind: &envelope::MessageKind,
+        recipient: Addr,
+    ) -> Self {
+        Self::builder()
+            .recipient(recipient)
+            .message_name(message.name())
+            .message_protocol(message.protocol())
+            .message_kind(MessageKind::from_message_kind(kind))
+            .do_finish(message._erase())
+    }
+
+    /// Dumps an incoming message, handling of which has the provided sequence
+    /// number. It's used as a join key for logs written during the handling.
+    pub(crate) fn handled_message(
+        message: &impl Message,
+        kind: &envelope::MessageKind,
+        sequence_no: SequenceNo,
+    ) -> Self {
+        let mut builder = Self::builder();
+        builder.sequence_no = Some(sequence_no);
+        builder
+            .direction(Direction::In)
+            .message_name(message.name())
+            .message_protocol(message.protocol())
+            .message_kind(MessageKind::from_message_kind(kind))
+            .do_finish(message._erase())
+    }
 }
 
 // === DumpBuilder ===
@@ -65,9 +110,11 @@ impl Dump {
 pub struct DumpBuilder {
     timestamp: Option<SystemTime>,
     direction: Direction,
+    recipient: Addr,
     message_name: Option<MessageName>,
     message_protocol: &'static str,
     message_kind: MessageKind,
+    sequence_no: Option<SequenceNo>,
 }
 
 impl DumpBuilder {
@@ -83,6 +130,12 @@ impl DumpBuilder {
         self
     }
 
+    #[stability::unstable]
+    pub fn recipient(&mut self, recipient: Addr) -> &mut Self {
+        self.recipient = recipient;
+        self
+    }
+
     #[stability::unstable]
     pub fn message_name(&mut self, name: impl Into<MessageName>) -> &mut Self {
         self.message_name = Some(name.into());
@@ -115,11 +168,14 @@ impl DumpBuilder {
     }
 
     fn do_finish(&mut self, message: ErasedMessage) -> Dump {
-        let (meta, trace_id, sequence_no) = scope::with(|scope| {
+        let (meta, trace_id, sequence_no, is_detailed) = scope::with(|scope| {
             (
                 scope.meta().clone(),
                 scope.trace_id(),
-                scope.dumping().next_sequence_no(),
+                self.sequence_no
+                    .take()
+                    .unwrap_or_else(|| scope.dumping().next_sequence_no()),
+                scope.is_force_sampled(),
             )
         });
 
@@ -130,10 +186,13 @@ impl DumpBuilder {
             trace_id,
             thread_id: crate::thread::id(),
             direction: self.direction,
+            recipient: self.recipient,
             message_name: self.message_name.take().unwrap_or_default(),
             message_protocol: self.message_protocol,
             message_kind: self.message_kind,
             message,
+            is_detailed,
+            channel_id: None,
         }
     }
 }
@@ -157,7 +216,7 @@ impl<'a> PartialEq<&'a str> for MessageName {
     fn eq(&self, s: &&'a str) -> bool {
         if let Some(variant) = self.1 {
             s.split_once("::")
-                .map_or(false, |(n, v)| n == self.0 && v == variant)
+                .is_some_and(|(n, v)| n == self.0 && v == variant)
         } else {
             self.0 == *s
         }
@@ -245,6 +304,9 @@ pub enum MessageKind {
     Regular,
     Request(u64),
     Response(u64),
+    /// A request delegated to another actor, see `Context::forward_request()`.
+    /// Has the same correlation id as the original request.
+    Forward(u64),
 }
 
 impl MessageKind {
@@ -255,6 +317,9 @@ impl MessageKind {
 
         match kind {
             MK::Regular { .. } => Self::Regular,
+            MK::RequestAny(token) | MK::RequestAll(token) if token.is_forwarded() => {
+                Self::Forward(token.request_id().to_ffi())
+            }
             MK::RequestAny(token) | MK::RequestAll(token) => {
                 Self::Request(token.request_id().to_ffi())
             }
@@ -1,33 +1,84 @@
+#[cfg(any(feature = "dumping", feature = "test-util"))]
 use std::sync::Arc;
 
 use crate::Message;
 
-use super::{
-    dump::*,
-    recorder::{self, Recorder},
-};
+#[cfg(feature = "test-util")]
+use super::capture::DumpCapture;
+use super::{dump::*, recorder::Recorder, ENABLED};
 
 #[derive(Clone)]
 #[stability::unstable]
 pub struct Dumper {
+    class: &'static str,
+    #[cfg(feature = "dumping")]
     recorder: Option<Arc<dyn Recorder>>,
 }
 
 impl Dumper {
+    #[cfg(feature = "dumping")]
     pub fn new(class: &'static str) -> Self {
         Self {
-            recorder: recorder::make_recorder(class),
+            class,
+            recorder: super::recorder::make_recorder(class),
         }
     }
 
+    #[cfg(not(feature = "dumping"))]
+    pub fn new(class: &'static str) -> Self {
+        Self::disabled(class)
+    }
+
+    /// Returns a dumper that never dumps, even if a recorder is installed.
+    /// Used for statics if dumping is disabled at compile time.
+    #[cfg(not(feature = "dumping"))]
+    pub(crate) const fn disabled(class: &'static str) -> Self {
+        Self { class }
+    }
+
+    pub(crate) fn class(&self) -> &'static str {
+        self.class
+    }
+
+    // Dumping is compiled out, so callers fold `if let Some(permit)` away.
+    #[cfg(not(feature = "dumping"))]
+    #[inline(always)]
+    #[stability::unstable]
+    pub fn acquire(&self) -> Option<DumpingPermit<'_>> {
+        None
+    }
+
+    #[cfg(all(feature = "dumping", not(feature = "test-util")))]
+    #[inline]
+    #[stability::unstable]
+    pub fn acquire(&self) -> Option<DumpingPermit<'_>> {
+        let recorder = self.recorder.as_deref().filter(|r| r.enabled())?;
+        Some(DumpingPermit {
+            recorder: Some(recorder),
+        })
+    }
+
+    // Dumps are also captured in tests, even if no recorder is installed.
+    #[cfg(all(feature = "dumping", feature = "test-util"))]
     #[inline]
     #[stability::unstable]
     pub fn acquire(&self) -> Option<DumpingPermit<'_>> {
-        let r = self.recorder.as_deref().filter(|r| r.enabled())?;
-        Some(DumpingPermit { recorder: r })
+        let recorder = self.recorder.as_deref().filter(|r| r.enabled());
+        let capture = super::capture::acquire(self.class, recorder.is_some());
+
+        (recorder.is_some() || capture.is_some()).then_some(DumpingPermit {
+            recorder,
+            class: self.class,
+            capture,
+        })
     }
 
+    #[inline]
     pub(crate) fn acquire_m<M: Message>(&self, message: &M) -> Option<DumpingPermit<'_>> {
+        if !ENABLED {
+            return None;
+        }
+
         if !message.dumping_allowed() {
             return None;
         }
@@ -39,12 +90,23 @@ impl Dumper {
 #[must_use]
 #[stability::unstable]
 pub struct DumpingPermit<'a> {
-    recorder: &'a dyn Recorder,
+    recorder: Option<&'a dyn Recorder>,
+    #[cfg(feature = "test-util")]
+    class: &'static str,
+    #[cfg(feature = "test-util")]
+    capture: Option<Arc<DumpCapture>>,
 }
 
 impl DumpingPermit<'_> {
     #[stability::unstable]
     pub fn record(self, dump: Dump) {
-        self.recorder.record(dump);
+        #[cfg(feature = "test-util")]
+        if let Some(capture) = &self.capture {
+            capture.push(self.class, &dump);
+        }
+
+        if let Some(recorder) = self.recorder {
+            recorder.record(dump);
+        }
     }
 }
@@ -11,7 +11,6 @@ pub use self::{
     extract_name::{extract_name, extract_name_by_type},
     raw::Raw,
     recorder::{set_make_recorder, Recorder},
-    sequence_no::SequenceNo,
 };
 #[cfg(not(feature = "unstable"))] // TODO: patch `stability`, again.
 pub(crate) use self::{
@@ -21,20 +20,35 @@ pub(crate) use self::{
     extract_name::{extract_name, extract_name_by_type},
     raw::Raw,
     recorder::{set_make_recorder, Recorder},
-    sequence_no::SequenceNo,
 };
 
+pub(crate) use self::class::{dumper_of, DumpClassifier};
+pub(crate) use self::recent::RecentDumps;
+pub use self::sequence_no::SequenceNo;
+
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
 #[stability::unstable]
 pub const INTERNAL_CLASS: &str = "internal";
 
+/// Whether dumps are produced at all.
+///
+/// It's `false` if the `dumping` feature (enabled by default) is disabled.
+/// In this case the whole producer side is compiled out, but types are still
+/// present, so the code using them still compiles.
+pub const ENABLED: bool = cfg!(feature = "dumping");
+
+#[cfg(feature = "test-util")]
+#[doc(hidden)]
+pub mod capture;
 pub mod config;
 
+mod class;
 mod control;
 mod dump;
 mod dumper;
 mod extract_name;
 mod raw;
+mod recent;
 mod recorder;
 mod sequence_no;
 
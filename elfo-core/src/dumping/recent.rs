@@ -0,0 +1,215 @@
+//! Messages recently handled by the actor, see [`RecentDumpsConfig`].
+
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parking_lot::Mutex;
+
+use elfo_utils::time::SystemTime;
+
+use super::config::RecentDumpsConfig;
+use crate::{
+    compression,
+    envelope::Envelope,
+    message::{AnyMessage, Message},
+    messages::RecentDump,
+    scope::{self, SerdeMode},
+};
+
+/// A ring of recently handled messages, bounded by count and size.
+#[derive(Default)]
+pub(crate) struct RecentDumps {
+    is_enabled: AtomicBool,
+    ring: Mutex<Ring>,
+}
+
+#[derive(Default)]
+struct Ring {
+    config: RecentDumpsConfig,
+    dumps: VecDeque<RecentDump>,
+    /// The total size of `dumps`, see `size_of()`.
+    size: usize,
+}
+
+impl RecentDumps {
+    pub(crate) fn new(config: &RecentDumpsConfig) -> Self {
+        let this = Self::default();
+        this.configure(config);
+        this
+    }
+
+    pub(crate) fn configure(&self, config: &RecentDumpsConfig) {
+        let mut ring = self.ring.lock();
+        ring.config = config.clone();
+        ring.shrink(config.count, config.max_size.as_usize());
+        self.is_enabled.store(config.count > 0, Ordering::Relaxed);
+    }
+
+    /// Remembers the message being handled.
+    #[inline]
+    pub(crate) fn on_handling(&self, envelope: &Envelope) {
+        if self.is_enabled.load(Ordering::Relaxed) {
+            self.push(envelope);
+        }
+    }
+
+    fn push(&self, envelope: &Envelope) {
+        let message = envelope.message();
+        let mut ring = self.ring.lock();
+
+        let payload = ring
+            .config
+            .payloads
+            .then(|| serialize(&message, ring.config.max_payload.as_usize()));
+
+        ring.push(RecentDump {
+            timestamp: SystemTime::now().into(),
+            trace_id: envelope.trace_id(),
+            protocol: message.protocol().into(),
+            name: message.name().into(),
+            payload,
+        });
+    }
+
+    /// Returns kept messages, the oldest first.
+    pub(crate) fn snapshot(&self) -> Vec<RecentDump> {
+        self.ring.lock().dumps.iter().cloned().collect()
+    }
+}
+
+impl Ring {
+    fn push(&mut self, dump: RecentDump) {
+        let size = size_of(&dump);
+        let max_size = self.config.max_size.as_usize();
+
+        if self.config.count == 0 || size > max_size {
+            // Older messages are dropped too, otherwise kept ones aren't the last.
+            self.shrink(0, 0);
+            return;
+        }
+
+        self.shrink(self.config.count - 1, max_size - size);
+        self.dumps.push_back(dump);
+        self.size += size;
+    }
+
+    /// Drops the oldest dumps until both limits are met.
+    fn shrink(&mut self, count: usize, size: usize) {
+        while self.dumps.len() > count || self.size > size {
+            let dump = self.dumps.pop_front().expect("size is tracked");
+            self.size -= size_of(&dump);
+        }
+    }
+}
+
+/// Approximates the memory occupied by the dump.
+fn size_of(dump: &RecentDump) -> usize {
+    mem::size_of::<RecentDump>()
+        + dump.protocol.len()
+        + dump.name.len()
+        + dump.payload.as_ref().map_or(0, String::len)
+}
+
+fn serialize(message: &AnyMessage, limit: usize) -> String {
+    let json = scope::with_serde_mode(SerdeMode::Dumping, || {
+        compression::with_message_labels(message.labels(), || {
+            serde_json::to_string(message.as_serialize())
+        })
+    });
+
+    let mut json = json.unwrap_or_else(|err| format!("<cannot serialize: {err}>"));
+    if json.len() > limit {
+        let mut end = limit;
+        while !json.is_char_boundary(end) {
+            end -= 1;
+        }
+        json.truncate(end);
+        json.push('…');
+    }
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::ByteSize, tracing::TraceId};
+
+    use super::*;
+
+    fn dump(name: &str) -> RecentDump {
+        RecentDump {
+            timestamp: SystemTime::now().into(),
+            trace_id: TraceId::try_from(1).unwrap(),
+            protocol: "test".into(),
+            name: name.into(),
+            payload: None,
+        }
+    }
+
+    fn ring(count: usize, max_size: usize) -> Ring {
+        Ring {
+            config: RecentDumpsConfig {
+                count,
+                max_size: ByteSize::new(max_size as u64),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn names(ring: &Ring) -> Vec<&str> {
+        ring.dumps.iter().map(|d| &d.name[..]).collect()
+    }
+
+    #[test]
+    fn by_count() {
+        let mut ring = ring(3, 1 << 20);
+
+        for name in ["a", "b", "c", "d", "e"] {
+            ring.push(dump(name));
+        }
+
+        assert_eq!(names(&ring), ["c", "d", "e"]);
+        assert_eq!(ring.size, ring.dumps.iter().map(size_of).sum::<usize>());
+
+        ring.shrink(1, usize::MAX);
+        assert_eq!(names(&ring), ["e"]);
+    }
+
+    #[test]
+    fn by_size() {
+        let one = size_of(&dump("a"));
+        let mut ring = ring(10, 3 * one + 1);
+
+        for name in ["a", "b", "c", "d"] {
+            ring.push(dump(name));
+        }
+
+        assert_eq!(names(&ring), ["b", "c", "d"]);
+        assert!(ring.size <= 3 * one + 1);
+
+        // A larger dump displaces several smaller ones.
+        ring.push(dump("eeeeeeeeeeee"));
+        assert_eq!(names(&ring), ["d", "eeeeeeeeeeee"]);
+
+        // A dump larger than the limit drops all.
+        let mut huge = dump("f");
+        huge.payload = Some("x".repeat(3 * one));
+        ring.push(huge);
+        assert!(ring.dumps.is_empty());
+        assert_eq!(ring.size, 0);
+    }
+
+    #[test]
+    fn truncation() {
+        let message = AnyMessage::new(crate::messages::GetConfig::new("группа".into()));
+
+        let full = serialize(&message, usize::MAX);
+        assert_eq!(full, r#"{"group":"группа","with_provenance":false}"#);
+
+        // Not a char boundary.
+        assert_eq!(serialize(&message, 13), r#"{"group":"г…"#);
+    }
+}
@@ -19,6 +19,7 @@ pub fn set_make_recorder(make_recorder: MakeRecorder) -> bool {
     MAKE_RECORDER.set(make_recorder).is_ok()
 }
 
+#[cfg(feature = "dumping")]
 pub(crate) fn make_recorder(class: &'static str) -> Option<Arc<dyn Recorder>> {
     MAKE_RECORDER.get().map(|make| make(class))
 }
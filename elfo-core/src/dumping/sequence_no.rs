@@ -1,11 +1,16 @@
 use std::{
     convert::TryFrom,
+    fmt,
     num::{NonZeroU64, TryFromIntError},
     sync::atomic::{AtomicU64, Ordering},
 };
 
 use serde::Serialize;
 
+/// A sequence number of a dump, unique within a group.
+///
+/// Handling of every message has the sequence number of its incoming dump,
+/// see [`scope::sequence_no()`](crate::scope::sequence_no).
 // TODO: make it just type alias (or not?)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct SequenceNo(NonZeroU64);
@@ -19,6 +24,13 @@ impl TryFrom<u64> for SequenceNo {
     }
 }
 
+impl fmt::Display for SequenceNo {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl From<SequenceNo> for u64 {
     #[inline]
     fn from(sequence_no: SequenceNo) -> Self {
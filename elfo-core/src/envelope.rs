@@ -1,8 +1,13 @@
-use std::{alloc, fmt, mem, ptr, ptr::NonNull};
+use std::{alloc, fmt, mem, ptr, ptr::NonNull, time::Duration};
+
+use tokio::time::Instant as TokioInstant;
 
 use elfo_utils::time::Instant;
 
 use crate::{
+    admission::Admission,
+    channel::{ChannelId, ChannelMark},
+    idempotency::IdempotencyKey,
     mailbox,
     message::{AnyMessageRef, Message, MessageRepr, MessageTypeId, Request},
     request_table::{RequestId, ResponseToken},
@@ -10,6 +15,9 @@ use crate::{
     Addr,
 };
 
+#[cfg(feature = "network")]
+use crate::remote::AckToken;
+
 /// An envelope is a wrapper around message with additional metadata,
 /// involved in message passing between actors.
 ///
@@ -38,6 +46,19 @@ pub(crate) struct EnvelopeHeader {
     kind: MessageKind,
     /// Offset from the beginning of the envelope to the `MessageRepr`.
     message_offset: u32,
+    /// See `Scope::force_sampling()`.
+    is_force_sampled: bool,
+    /// The decision of the recipient's admission policy, if any.
+    admission: Admission,
+    /// Set for messages sent over a channel, see `Context::open_channel()`.
+    channel: Option<ChannelMark>,
+    /// Set for messages received by a passive standby, see `Topology::standby()`.
+    is_passive: bool,
+    /// Set for regular messages with the default TTL, see `system.ttl`.
+    expires_at: Option<TokioInstant>,
+    /// See `Context::send_acknowledged()`.
+    #[cfg(feature = "network")]
+    ack: Option<AckToken>,
 }
 
 assert_impl_all!(EnvelopeHeader: Send);
@@ -106,6 +127,14 @@ impl Envelope {
             trace_id,
             kind,
             message_offset,
+            is_force_sampled: crate::scope::try_with(|s| s.is_trace_force_sampled(trace_id))
+                .unwrap_or(false),
+            admission: Admission::Admit,
+            channel: None,
+            is_passive: false,
+            expires_at: None,
+            #[cfg(feature = "network")]
+            ack: None,
         };
 
         // SAFETY: `layout` is correct and non-zero.
@@ -145,6 +174,104 @@ impl Envelope {
         self.header().trace_id
     }
 
+    /// Part of private API. Do not use it.
+    #[doc(hidden)]
+    #[inline]
+    pub fn is_force_sampled(&self) -> bool {
+        self.header().is_force_sampled
+    }
+
+    /// Part of private API. Do not use it.
+    #[doc(hidden)]
+    pub fn set_force_sampled(&mut self) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.is_force_sampled = true;
+    }
+
+    /// Returns whether the envelope has been marked as degraded by the
+    /// admission policy of the recipient, so it can be handled in a cheaper
+    /// way, see [`ActorGroup::admission()`].
+    ///
+    /// [`ActorGroup::admission()`]: crate::ActorGroup::admission
+    #[inline]
+    pub fn is_degraded(&self) -> bool {
+        self.header().admission == Admission::Degrade
+    }
+
+    /// Returns the reason if the envelope has been rejected by the admission
+    /// policy of the recipient.
+    #[doc(hidden)]
+    pub fn rejection(&self) -> Option<&'static str> {
+        match self.header().admission {
+            Admission::Reject(reason) => Some(reason),
+            Admission::Admit | Admission::Degrade => None,
+        }
+    }
+
+    pub(crate) fn set_admission(&mut self, admission: Admission) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.admission = admission;
+    }
+
+    /// Returns the id of the channel the message is sent over, if any,
+    /// see [`Context::open_channel()`].
+    ///
+    /// [`Context::open_channel()`]: crate::Context::open_channel()
+    #[inline]
+    pub fn channel_id(&self) -> Option<ChannelId> {
+        self.header().channel.as_ref().map(ChannelMark::id)
+    }
+
+    pub(crate) fn set_channel(&mut self, mark: ChannelMark) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.channel = Some(mark);
+    }
+
+    /// Returns whether the envelope has been received by a passive standby
+    /// group, e.g. as a mirrored copy of the primary's traffic, so handlers
+    /// should skip side effects, see [`Topology::standby()`].
+    ///
+    /// [`Topology::standby()`]: crate::Topology::standby
+    #[inline]
+    pub fn is_passive(&self) -> bool {
+        self.header().is_passive
+    }
+
+    pub(crate) fn set_passive(&mut self) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.is_passive = true;
+    }
+
+    /// Returns whether the message hasn't been received within its TTL,
+    /// see `system.ttl`.
+    #[inline]
+    pub(crate) fn is_expired(&self) -> bool {
+        self.header()
+            .expires_at
+            .is_some_and(|at| at <= TokioInstant::now())
+    }
+
+    pub(crate) fn set_ttl(&mut self, ttl: Duration) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.expires_at = Some(TokioInstant::now() + ttl);
+    }
+
+    /// Part of private API. Do not use it.
+    #[doc(hidden)]
+    #[cfg(feature = "network")]
+    pub fn take_ack(&mut self) -> Option<AckToken> {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.ack.take()
+    }
+
+    /// Part of private API. Do not use it.
+    #[doc(hidden)]
+    #[cfg(feature = "network")]
+    pub fn set_ack(&mut self, ack: Option<AckToken>) {
+        // SAFETY: `self.0` is properly initialized and uniquely owned.
+        unsafe { self.0.as_mut() }.ack = ack;
+    }
+
     /// Returns a reference to the untyped message inside the envelope.
     #[inline]
     pub fn message(&self) -> AnyMessageRef<'_> {
@@ -184,6 +311,16 @@ impl Envelope {
         }
     }
 
+    #[inline]
+    pub fn idempotency_key(&self) -> Option<IdempotencyKey> {
+        match self.message_kind() {
+            MessageKind::RequestAny(token) | MessageKind::RequestAll(token) => {
+                token.idempotency_key()
+            }
+            _ => None,
+        }
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn type_id(&self) -> MessageTypeId {
@@ -217,6 +354,17 @@ impl Envelope {
                 },
             },
             message_offset,
+            is_force_sampled: header.is_force_sampled,
+            // Decided for every recipient separately.
+            admission: Admission::Admit,
+            // Credits are returned by one recipient only.
+            channel: None,
+            // Decided for every recipient separately.
+            is_passive: false,
+            expires_at: header.expires_at,
+            // Acknowledged by one recipient only.
+            #[cfg(feature = "network")]
+            ack: None,
         };
 
         // SAFETY: `layout` is correct and non-zero.
@@ -277,6 +425,13 @@ impl Envelope {
         let message = M::_read(self.message_repr_ptr());
         let kind = ptr::read(&self.0.as_ref().kind);
 
+        // Returns the credit of the channel.
+        drop(ptr::read(&self.0.as_ref().channel));
+
+        // Unresolved acknowledgements report the fallback error.
+        #[cfg(feature = "network")]
+        drop(ptr::read(&self.0.as_ref().ack));
+
         alloc::dealloc(self.0.as_ptr().cast(), layout);
         mem::forget(self);
         (message, kind)
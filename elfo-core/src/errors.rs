@@ -1,6 +1,14 @@
-use std::{collections::BTreeMap, fmt::Debug};
+use std::{
+    collections::BTreeMap,
+    error::Error as StdError,
+    fmt::{self, Debug},
+    ops::Deref,
+};
 
 use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::addr::NodeNo;
 
 // === StartError ===
 
@@ -81,6 +89,49 @@ pub struct StartGroupError {
     pub reason: String,
 }
 
+// === DeriveConfigError ===
+
+/// The closure passed to [`Context::derived_config()`] has panicked.
+///
+/// [`Context::derived_config()`]: crate::Context::derived_config
+#[derive(Clone, Debug, Display, Error)]
+#[non_exhaustive]
+#[display("cannot derive config: {reason}")]
+pub struct DeriveConfigError {
+    pub reason: String,
+}
+
+impl DeriveConfigError {
+    pub(crate) fn new(reason: String) -> Self {
+        Self { reason }
+    }
+}
+
+// === DecompressError ===
+
+/// Returned by [`Compressed::try_get()`] if the received value is corrupted
+/// or has been encoded by an incompatible version.
+///
+/// [`Compressed::try_get()`]: crate::Compressed::try_get
+#[derive(Clone, Debug, Display, Error)]
+#[non_exhaustive]
+#[display("cannot decompress value: {reason}")]
+pub struct DecompressError {
+    pub reason: String,
+}
+
+// === UnknownGroupError ===
+
+/// Returned by [`Context::locate_group()`] if there is no such group.
+///
+/// [`Context::locate_group()`]: crate::Context::locate_group
+#[derive(Clone, Debug, Display, Error)]
+#[non_exhaustive]
+#[display("unknown group `{name}`")]
+pub struct UnknownGroupError {
+    pub name: String,
+}
+
 // === SendError ===
 
 #[derive(Debug, Display, Error)]
@@ -175,6 +226,354 @@ impl RequestError {
     pub fn is_ignored(&self) -> bool {
         matches!(self, Self::Ignored)
     }
+
+    /// All kinds except `Ignored` are reported as `Failed`, the original kind
+    /// is available by [`DeliveryError::kind()`].
+    pub(crate) fn from_kind(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Ignored => Self::Ignored,
+            _ => Self::Failed,
+        }
+    }
+}
+
+// === AckError ===
+
+/// Returned by [`Context::send_acknowledged()`] if the message hasn't been
+/// enqueued into any mailbox.
+///
+/// [`Context::send_acknowledged()`]: crate::Context::send_acknowledged
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Display,
+    Error,
+    Serialize,
+    Deserialize
+)]
+#[non_exhaustive]
+pub enum AckError {
+    /// Nobody routes the message or the destination group isn't available
+    /// on the remote node: it's not mounted yet or disabled.
+    #[display("no route")]
+    NoRoute,
+    /// The connection isn't allowed to access the destination group.
+    #[display("forbidden")]
+    Forbidden,
+    /// The recipient's mailbox has been closed.
+    #[display("mailbox closed")]
+    Closed,
+    /// The message has been rejected by the admission policy of the
+    /// recipient, see [`ActorGroup::admission()`].
+    ///
+    /// [`ActorGroup::admission()`]: crate::ActorGroup::admission
+    #[display("rejected")]
+    Rejected,
+    /// The message cannot be encoded on this node or decoded on the remote
+    /// one, e.g. if it's unknown there.
+    #[display("decode failed")]
+    DecodeFailed,
+    /// The connection has been lost before the acknowledgement is received.
+    /// The message may or may not have been delivered.
+    #[display("connection lost")]
+    ConnectionLost,
+    /// The remote node doesn't support acknowledgements. The message is sent
+    /// as usual, but its delivery cannot be confirmed.
+    #[display("unsupported by remote")]
+    Unsupported,
+    /// Too many messages have been sent within the current trace,
+    /// see `system.tracing.fan_out`. The message hasn't been sent.
+    #[display("trace budget exceeded")]
+    TraceBudgetExceeded,
+    /// The message is deprecated and sending of such messages is rejected,
+    /// see `system.deprecation.strict`. The message hasn't been sent.
+    #[display("deprecated")]
+    Deprecated,
+}
+
+// === ErrorKind ===
+
+/// The kind of [`DeliveryError`], which is stable and suitable for retrying
+/// and alerting logic instead of matching error messages.
+///
+/// [`TrySendError`] and [`RequestError`] have only a few variants, so most
+/// kinds are reported by them as `Closed` and `Failed` respectively, except
+/// [`ErrorKind::RateLimited`], which is reported as `Full`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Nobody routes the message or the recipient isn't available.
+    #[display("no route")]
+    NoRoute,
+    /// The mailbox has been closed.
+    #[display("mailbox closed")]
+    Closed,
+    /// The mailbox is full.
+    #[display("mailbox full")]
+    Full,
+    /// The destination group is disabled by its mount condition,
+    /// see [`Local::mount_if()`](crate::topology::Local::mount_if).
+    #[display("group disabled")]
+    GroupDisabled,
+    /// The request hasn't been sent, because the circuit breaker is open.
+    #[display("circuit open")]
+    CircuitOpen,
+    /// The receiver hasn't got the request, see [`RequestError::Failed`].
+    #[display("request failed")]
+    Failed,
+    /// The receiver has got the request, but ignored it,
+    /// see [`RequestError::Ignored`].
+    #[display("request ignored")]
+    Ignored,
+    /// The request has been cancelled by the requester,
+    /// see [`PendingRequest::cancel()`].
+    ///
+    /// [`PendingRequest::cancel()`]: crate::PendingRequest::cancel
+    #[display("request cancelled")]
+    Cancelled,
+    /// The request has been rejected by the remote node, because the
+    /// connection isn't allowed to access the destination group.
+    #[display("request forbidden")]
+    Forbidden,
+    /// The responder has deferred the response, but hasn't provided it before
+    /// the deadline, see [`DeferredToken::with_deadline()`].
+    ///
+    /// [`DeferredToken::with_deadline()`]: crate::DeferredToken::with_deadline
+    #[display("request timed out")]
+    Timeout,
+    /// The request has been sent to the remote node, but all connections to
+    /// the node have been lost before the response is received.
+    #[display("network disconnected")]
+    NetworkDisconnected,
+    /// The request has been rejected by the remote node, because it cannot
+    /// be decoded there.
+    #[display("remote decode error")]
+    RemoteDecodeError,
+    /// The request has been rejected by the remote node, because the message
+    /// is unknown there, e.g. during rolling upgrades.
+    #[display("unsupported by remote")]
+    Unsupported,
+    /// The message has been rejected by the admission policy of the
+    /// recipient, the reason is in [`ErrorContext::rejection`],
+    /// see [`ActorGroup::admission()`](crate::ActorGroup::admission).
+    #[display("rejected")]
+    Rejected,
+    /// The request has exceeded its limits, see [`RequestLimits`]: either
+    /// it hasn't been handled in time or the response is too large.
+    ///
+    /// [`RequestLimits`]: crate::RequestLimits
+    #[display("limit exceeded")]
+    LimitExceeded,
+    /// Too many messages have been sent within the current trace,
+    /// see `system.tracing.fan_out`.
+    #[display("trace budget exceeded")]
+    TraceBudgetExceeded,
+    /// The budget of the outbound rate limiter is exhausted,
+    /// see [`Context::rate_limited()`](crate::Context::rate_limited).
+    #[display("rate limited")]
+    RateLimited,
+    /// The message is deprecated and sending of such messages is rejected,
+    /// see `system.deprecation.strict`.
+    #[display("deprecated")]
+    Deprecated,
+}
+
+// === ErrorContext ===
+
+/// Describes what has been sent and where when the delivery failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// The protocol of the message.
+    pub protocol: &'static str,
+    /// The name of the message.
+    pub message: &'static str,
+    /// The destination group. `None` if it's unknown, e.g. the message is
+    /// routed to several groups.
+    pub group: Option<String>,
+    /// The key of the destination actor, if it's sent directly to the actor.
+    pub key: Option<String>,
+    /// The node of the destination, `None` for local destinations.
+    pub node_no: Option<NodeNo>,
+    /// The reason provided by the admission policy, if the message has been
+    /// rejected by it.
+    pub rejection: Option<&'static str>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(protocol: &'static str, message: &'static str) -> Self {
+        Self {
+            protocol,
+            message,
+            group: None,
+            key: None,
+            node_no: None,
+            rejection: None,
+        }
+    }
+
+    /// Returns whether the destination is on another node.
+    #[inline]
+    pub fn is_remote(&self) -> bool {
+        self.node_no.is_some()
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}::{}`", self.protocol, self.message)?;
+
+        if let Some(group) = &self.group {
+            write!(f, " to `{group}`")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, " (key `{key}`)")?;
+        }
+        if let Some(node_no) = self.node_no {
+            write!(f, " on node {node_no}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// === DeliveryError ===
+
+/// An error of sending or requesting with the context of the failure,
+/// returned by [`Context::detailed()`] and [`RequestBuilder::resolve_detailed()`].
+///
+/// Wraps [`SendError`], [`TrySendError`] or [`RequestError`] and derefs to it,
+/// so their methods and variants are still available by [`Deref`] and
+/// [`DeliveryError::into_error()`].
+///
+/// [`Context::detailed()`]: crate::Context::detailed
+/// [`RequestBuilder::resolve_detailed()`]: crate::RequestBuilder::resolve_detailed
+pub struct DeliveryError<E> {
+    kind: ErrorKind,
+    error: E,
+    context: Box<ErrorContext>,
+}
+
+impl<E> DeliveryError<E> {
+    pub(crate) fn new(kind: ErrorKind, error: E, context: ErrorContext) -> Self {
+        Self {
+            kind,
+            error,
+            context: Box::new(context),
+        }
+    }
+
+    /// Marks the error as caused by the admission policy of the recipient.
+    pub(crate) fn rejected(mut self, reason: &'static str) -> Self {
+        self.kind = ErrorKind::Rejected;
+        self.context.rejection = Some(reason);
+        self
+    }
+
+    /// Returns the kind of the error.
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the context of the error: the message and the destination.
+    #[inline]
+    pub fn context(&self) -> &ErrorContext {
+        &self.context
+    }
+
+    /// Converts the error into the underlying one.
+    #[inline]
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Transforms the underlying error, keeping the kind and the context.
+    #[inline]
+    pub fn map<U>(self, f: impl FnOnce(E) -> U) -> DeliveryError<U> {
+        DeliveryError {
+            kind: self.kind,
+            error: f(self.error),
+            context: self.context,
+        }
+    }
+}
+
+impl<T> DeliveryError<SendError<T>> {
+    /// Converts the error into the unsent message.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.error.into_inner()
+    }
+}
+
+impl<T> DeliveryError<TrySendError<T>> {
+    /// Converts the error into the unsent message.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.error.into_inner()
+    }
+}
+
+impl<E> Deref for DeliveryError<E> {
+    type Target = E;
+
+    #[inline]
+    fn deref(&self) -> &E {
+        &self.error
+    }
+}
+
+impl<E: Debug> Debug for DeliveryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeliveryError")
+            .field("kind", &self.kind)
+            .field("error", &self.error)
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+impl<E> fmt::Display for DeliveryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot deliver {}: {}", self.context, self.kind)?;
+
+        if let Some(reason) = self.context.rejection {
+            write!(f, " ({reason})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: StdError + 'static> StdError for DeliveryError<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<T> From<DeliveryError<SendError<T>>> for SendError<T> {
+    #[inline]
+    fn from(err: DeliveryError<SendError<T>>) -> Self {
+        err.error
+    }
+}
+
+impl<T> From<DeliveryError<TrySendError<T>>> for TrySendError<T> {
+    #[inline]
+    fn from(err: DeliveryError<TrySendError<T>>) -> Self {
+        err.error
+    }
+}
+
+impl From<DeliveryError<RequestError>> for RequestError {
+    #[inline]
+    fn from(err: DeliveryError<RequestError>) -> Self {
+        err.error
+    }
 }
 
 // === TryRecvError ===
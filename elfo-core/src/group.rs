@@ -1,29 +1,70 @@
-use std::{fmt::Debug, future::Future, marker::PhantomData, sync::Arc};
+use std::{
+    any::{Any, TypeId},
+    fmt,
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
+};
 
 use futures::future::BoxFuture;
 
+#[cfg(feature = "dedicated-runtime")]
+use crate::runtime::{DedicatedRuntime, RuntimeOptions};
 use crate::{
     addr::NodeNo,
-    config::Config,
+    admission::{Admission, AdmissionPolicies, AdmissionPolicy, EnvelopeMeta, MailboxStats},
+    audit::{AuditConfig, AuditLog},
+    concurrency::Concurrency,
+    config::{AnyConfig, Config},
     context::Context,
+    dedup::{self, DedupWindow, FilterFactory},
+    dumping::DumpClassifier,
     envelope::Envelope,
     exec::{Exec, ExecResult},
+    idempotency::IdempotencyCache,
+    intercept::{Interceptor, Interceptors},
+    message::Message,
     object::{GroupHandle, GroupVisitor, Object},
+    pool::StickyKey,
     restarting::RestartPolicy,
-    routers::Router,
-    runtime::RuntimeManager,
+    routers::{PoolRouter, Router},
+    runtime::{Placement, RuntimeHandle, RuntimeManager},
+    self_queue::SelfQueue,
     supervisor::Supervisor,
+    topology::GroupDescription,
 };
 
-#[derive(Debug)]
 pub struct ActorGroup<R, C> {
     restart_policy: RestartPolicy,
     termination_policy: TerminationPolicy,
     stop_order: i8,
+    mailbox_capacity: Option<usize>,
+    concurrency: Concurrency,
+    self_queue: SelfQueue,
+    mount_hooks: Vec<MountHook>,
+    dedup: Vec<FilterFactory>,
+    interceptors: Vec<Interceptor>,
+    /// Set by `idempotency_cache()`, the capacity and the TTL.
+    idempotency: Option<(usize, Duration)>,
+    admission: AdmissionPolicies,
+    dump_classifier: Option<DumpClassifier>,
+    audit: Option<AuditConfig>,
+    #[cfg(feature = "dedicated-runtime")]
+    runtime: Option<RuntimeOptions>,
+    /// Contains `Placement<R::Key, C>`, erased to not bound the struct.
+    placement: Option<Box<dyn Any + Send + Sync>>,
+    /// Set by `pool()`, the router must stay `PoolRouter`.
+    is_pooled: bool,
+    /// Set by `sticky_by()`.
+    sticky: Option<StickyKey>,
     router: R,
     _config: PhantomData<C>,
 }
 
+type MountHook = Box<dyn FnOnce(&str) + Send>;
+
 impl ActorGroup<(), ()> {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
@@ -32,6 +73,21 @@ impl ActorGroup<(), ()> {
             termination_policy: TerminationPolicy::default(),
             router: (),
             stop_order: 0,
+            mailbox_capacity: None,
+            concurrency: Concurrency::default(),
+            self_queue: SelfQueue::default(),
+            mount_hooks: Vec::new(),
+            dedup: Vec::new(),
+            interceptors: Vec::new(),
+            idempotency: None,
+            admission: AdmissionPolicies::default(),
+            dump_classifier: None,
+            audit: None,
+            #[cfg(feature = "dedicated-runtime")]
+            runtime: None,
+            placement: None,
+            is_pooled: false,
+            sticky: None,
             _config: PhantomData,
         }
     }
@@ -44,10 +100,45 @@ impl<R, C> ActorGroup<R, C> {
             termination_policy: self.termination_policy,
             router: self.router,
             stop_order: self.stop_order,
+            mailbox_capacity: self.mailbox_capacity,
+            concurrency: self.concurrency,
+            self_queue: self.self_queue,
+            mount_hooks: self.mount_hooks,
+            dedup: self.dedup,
+            interceptors: self.interceptors,
+            idempotency: self.idempotency,
+            admission: self.admission,
+            dump_classifier: self.dump_classifier,
+            audit: self.audit,
+            #[cfg(feature = "dedicated-runtime")]
+            runtime: self.runtime,
+            placement: self.placement,
+            is_pooled: self.is_pooled,
+            sticky: self.sticky,
             _config: PhantomData,
         }
     }
 
+    /// Applies the provided preset to the group.
+    ///
+    /// Presets are useful to share the same settings between many groups.
+    /// They are applied in order, so later presets and builder calls
+    /// override earlier ones.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{presets, ActorGroup};
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .apply(presets::production())
+    ///     .apply(|group: ActorGroup<_, _>| group.mailbox_capacity(1000))
+    ///     .exec(|_ctx| async {});
+    /// ```
+    pub fn apply<P: Preset<R, C>>(self, preset: P) -> ActorGroup<P::Router, P::Config> {
+        preset.apply(self)
+    }
+
     /// The behaviour on actor termination.
     ///
     /// `RestartPolicy::never` is used by default.
@@ -66,15 +157,494 @@ impl<R, C> ActorGroup<R, C> {
 
     /// Installs a router.
     pub fn router<R1: Router<C>>(self, router: R1) -> ActorGroup<R1, C> {
+        self.map_router(|_| router)
+    }
+
+    /// Replaces the installed router with a new one based on it.
+    ///
+    /// Useful in presets to wrap a router provided by a group's author.
+    pub fn map_router<R1: Router<C>>(self, f: impl FnOnce(R) -> R1) -> ActorGroup<R1, C> {
         ActorGroup {
             restart_policy: self.restart_policy,
             termination_policy: self.termination_policy,
-            router,
+            router: f(self.router),
             stop_order: self.stop_order,
+            mailbox_capacity: self.mailbox_capacity,
+            concurrency: self.concurrency,
+            self_queue: self.self_queue,
+            mount_hooks: self.mount_hooks,
+            dedup: self.dedup,
+            interceptors: self.interceptors,
+            idempotency: self.idempotency,
+            admission: self.admission,
+            dump_classifier: self.dump_classifier,
+            audit: self.audit,
+            #[cfg(feature = "dedicated-runtime")]
+            runtime: self.runtime,
+            placement: self.placement,
+            is_pooled: self.is_pooled,
+            sticky: self.sticky,
             _config: self._config,
         }
     }
 
+    /// Turns the group into a pool of `workers` homogeneous actors sharing
+    /// one queue, so every message is handled by the first idle worker and a
+    /// slow message doesn't delay ones queued after it. Useful for stateless
+    /// workers, e.g. ones making HTTP calls.
+    ///
+    /// Workers are keyed by numbers `0..workers` and started with the group.
+    /// System messages (e.g. `UpdateConfig` and `Terminate`) are still sent to
+    /// every worker individually and received before queued messages. The
+    /// shared queue has the same settings as mailboxes, i.e.
+    /// `system.mailbox.*`, and is closed on `Terminate`.
+    ///
+    /// Messages are routed by [`PoolRouter`], which puts them into the queue
+    /// by [`Outcome::Pool`]. Other routers aren't supported, so installing one
+    /// after this call leads to a panic on mounting.
+    ///
+    /// Metrics:
+    /// * `elfo_pool_processed_total` counts messages taken from the queue,
+    ///   per worker if `system.telemetry.per_actor_key` is enabled.
+    /// * `elfo_pool_queue_depth` is the length of the queue, sampled once a
+    ///   message is taken.
+    ///
+    /// Messages of one trace can be handled by different workers concurrently,
+    /// use [`ActorGroup::sticky_by_trace()`] to serialize them.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::ActorGroup;
+    ///
+    /// let blueprint = ActorGroup::new().pool(8).exec(|mut ctx| async move {
+    ///     while let Some(_envelope) = ctx.recv().await {
+    ///         // `ctx.key()` is the number of the worker.
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// If `workers` is zero.
+    ///
+    /// [`Outcome::Pool`]: crate::routers::Outcome::Pool
+    pub fn pool(self, workers: usize) -> ActorGroup<PoolRouter, C> {
+        let mut group = self.router(PoolRouter::new(workers));
+        group.is_pooled = true;
+        group
+    }
+
+    /// The default capacity of actors' mailboxes.
+    ///
+    /// It's used only if `system.mailbox.capacity` isn't specified in the
+    /// config, so the config always has higher priority.
+    ///
+    /// `system.mailbox.capacity`'s default value is used by default.
+    pub fn mailbox_capacity(mut self, capacity: usize) -> Self {
+        self.mailbox_capacity = Some(capacity);
+        self
+    }
+
+    /// Limits the number of envelopes handled concurrently by
+    /// [`Context::recv_concurrent()`] in every actor of the group.
+    /// Accepts a number or [`Concurrency`] for more options.
+    ///
+    /// Useful for IO-bound handlers, e.g. ones making HTTP calls.
+    /// In-flight handlers are counted in the `elfo_in_flight_handlers` metric,
+    /// their handling time is in `elfo_concurrent_handling_time_seconds`.
+    ///
+    /// `1` by default, i.e. envelopes are handled one by one.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{messages::ConfigUpdated, msg, ActorGroup};
+    ///
+    /// let blueprint = ActorGroup::new().concurrency(16).exec(|mut ctx| async move {
+    ///     let pruned = ctx.pruned();
+    ///
+    ///     while let Some(envelope) = ctx
+    ///         .recv_concurrent(|envelope| {
+    ///             let ctx = pruned.clone();
+    ///             async move { /* handle `envelope` using `ctx` */ }
+    ///         })
+    ///         .await
+    ///     {
+    ///         // Only system messages are returned, all handlers are finished.
+    ///         msg!(match envelope {
+    ///             ConfigUpdated => { /* ... */ }
+    ///         });
+    ///     }
+    /// });
+    /// ```
+    pub fn concurrency(mut self, concurrency: impl Into<Concurrency>) -> Self {
+        self.concurrency = concurrency.into();
+        self
+    }
+
+    /// Configures the queue of messages sent by [`Context::send_to_self()`]
+    /// in every actor of the group. Accepts a capacity or [`SelfQueue`] for
+    /// more options, `0` disables the queue.
+    ///
+    /// `SelfQueue::default()` is used by default, i.e. at most 64 messages
+    /// received before ones in the mailbox.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{ActorGroup, SelfQueue, SelfQueuePriority};
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .self_queue(SelfQueue::new(256).priority(SelfQueuePriority::AfterMailbox))
+    ///     .exec(|_ctx| async {});
+    /// ```
+    pub fn self_queue(mut self, self_queue: impl Into<SelfQueue>) -> Self {
+        self.self_queue = self_queue.into();
+        self
+    }
+
+    /// Registers a function called once the group is mounted to the topology.
+    /// The function receives the group's name.
+    ///
+    /// Hooks are called in the registration order.
+    pub fn on_mount(mut self, hook: impl FnOnce(&str) + Send + 'static) -> Self {
+        self.mount_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Drops envelopes of type `M` whose key has already been seen within the
+    /// window, e.g. ones redelivered by at-least-once sources.
+    ///
+    /// Messages of other types are passed without touching their content.
+    /// Dropped requests are answered with [`RequestError::Ignored`].
+    /// Drops are counted in the `elfo_dedup_dropped_total` metric and dumped
+    /// with the `dup` class, set its rate to zero to disable dumping:
+    /// `system.dumping.class_trace_sample_rates.dup = 0.0`.
+    ///
+    /// Every actor has its own window, which is empty after every start,
+    /// so duplicates aren't detected across restarts of the actor.
+    ///
+    /// Can be called several times for different message types.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{message, ActorGroup, DedupWindow};
+    ///
+    /// #[message]
+    /// struct IngestMsg {
+    ///     event_id: u64,
+    /// }
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .dedup_by::<IngestMsg, _>(|m| m.event_id, DedupWindow::count(10_000))
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// [`RequestError::Ignored`]: crate::errors::RequestError::Ignored
+    pub fn dedup_by<M, K>(
+        mut self,
+        extract: impl Fn(&M) -> K + Send + Sync + 'static,
+        window: DedupWindow,
+    ) -> Self
+    where
+        M: Message,
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        self.dedup.push(dedup::factory(extract, window));
+        self
+    }
+
+    /// Registers a middleware called for every envelope received by actors
+    /// of the group before handling it by the actor's code. It can inspect
+    /// the envelope, replace it or drop it by returning `None`.
+    ///
+    /// Interceptors are called in the registration order, so it's useful in
+    /// presets to add common behaviour to groups written by other authors.
+    /// System messages (e.g. `Terminate`) bypass interceptors.
+    ///
+    /// Dropped requests are answered with [`RequestError::Ignored`].
+    /// Drops are counted in the `elfo_intercepted_dropped_total` metric.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{message, ActorGroup};
+    ///
+    /// #[message]
+    /// struct Noise;
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .intercept(|envelope| (!envelope.is::<Noise>()).then_some(envelope))
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// [`RequestError::Ignored`]: crate::errors::RequestError::Ignored
+    pub fn intercept(
+        mut self,
+        interceptor: impl Fn(Envelope) -> Option<Envelope> + Send + Sync + 'static,
+    ) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Records responses to requests with idempotency keys, see
+    /// [`RequestBuilder::idempotency_key()`], and replays them to retries of
+    /// the same requests without handling them again.
+    ///
+    /// Responses are keyed by the requester and the key, and kept at most
+    /// `ttl` since responding. If more than `capacity` responses are
+    /// recorded, the oldest ones are evicted, so their retries are handled as
+    /// new requests. Retries received before responding to the original
+    /// request are handled too.
+    ///
+    /// The cache is shared by all actors of the group and survives their
+    /// restarts. Replayed responses are counted in the
+    /// `elfo_idempotency_cache_hits_total` metric.
+    ///
+    /// # Panics
+    /// If `capacity` is zero.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use std::time::Duration;
+    ///
+    /// use elfo::ActorGroup;
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .idempotency_cache(10_000, Duration::from_secs(60))
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// [`RequestBuilder::idempotency_key()`]: crate::RequestBuilder::idempotency_key
+    pub fn idempotency_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        assert!(
+            capacity > 0,
+            "the capacity of the idempotency cache must be positive"
+        );
+        self.idempotency = Some((capacity, ttl));
+        self
+    }
+
+    /// Registers a named admission policy, which is called on every bounded
+    /// send (`send()`, `try_send()`, `request()` and their `*_to()` versions)
+    /// to an actor of the group before the envelope is enqueued.
+    ///
+    /// The policy gets the message, its sender and cheap stats of the
+    /// recipient's mailbox, and decides whether to admit the envelope, reject
+    /// it, so the sender gets an error of the [`Rejected`] kind, or admit it
+    /// marked as degraded, so the handler can check [`Envelope::is_degraded()`]
+    /// and do cheaper processing. It's called in the sender's context,
+    /// so it must be fast: no blocking and no allocations. It can be called
+    /// several times for the same envelope, e.g. if the mailbox is full.
+    /// Unbounded sends, responses and system messages bypass admission control.
+    ///
+    /// The first registered policy is used by default, the config can choose
+    /// another one by name or disable admission control at all:
+    /// `system.mailbox.admission = "none"`. Unknown names are rejected.
+    ///
+    /// Rejections are counted in `elfo_mailbox_admission_rejected_total`.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{admission::Admission, message, ActorGroup};
+    ///
+    /// #[message]
+    /// struct PlaceOrder;
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     // Cancels are always admitted, new orders only if there is no backlog.
+    ///     .admission("orders", |envelope, stats| {
+    ///         if !envelope.message().is::<PlaceOrder>() {
+    ///             Admission::Admit
+    ///         } else if stats.len >= 100 {
+    ///             Admission::Reject("too many pending orders")
+    ///         } else if stats.len >= 10 {
+    ///             Admission::Degrade
+    ///         } else {
+    ///             Admission::Admit
+    ///         }
+    ///     })
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// # Panics
+    /// If the name is `"none"` or already registered.
+    ///
+    /// [`Rejected`]: crate::errors::ErrorKind::Rejected
+    pub fn admission(
+        mut self,
+        name: &'static str,
+        policy: impl Fn(&EnvelopeMeta<'_>, &MailboxStats) -> Admission + Send + Sync + 'static,
+    ) -> Self {
+        self.admission.register(AdmissionPolicy::new(name, policy));
+        self
+    }
+
+    /// Chooses the dumping class of incoming messages by their content,
+    /// e.g. per client or per venue, instead of the default `"internal"`.
+    ///
+    /// The class is applied to the dump of the handled message and kept for
+    /// the whole handling, i.e. until the next `recv()`, so messages sent by
+    /// the handler are dumped with the same class. `None` means the default
+    /// class. [`scope::with_dump_class()`] overrides the class inside the
+    /// handler. Classes are used to filter, rate limit and write dumps to
+    /// separate files, see `elfo-dumper`.
+    ///
+    /// The function is called for every received message, so it must be
+    /// fast. The class must be a static string, the number of classes must
+    /// be bounded.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{message, ActorGroup};
+    ///
+    /// #[message]
+    /// struct Quote {
+    ///     is_binance: bool,
+    /// }
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .dump_class_by(|envelope| {
+    ///         let quote = envelope.message().downcast_ref::<Quote>()?;
+    ///         quote.is_binance.then_some("venue_binance")
+    ///     })
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// [`scope::with_dump_class()`]: crate::scope::with_dump_class
+    pub fn dump_class_by(
+        mut self,
+        classify: impl Fn(&Envelope) -> Option<&'static str> + Send + Sync + 'static,
+    ) -> Self {
+        self.dump_classifier = Some(DumpClassifier::new(classify));
+        self
+    }
+
+    /// Writes every envelope received by actors of the group to the audit log
+    /// along with the outcome of handling, see [`audit`] for the format.
+    ///
+    /// Unlike dumping, records are written synchronously by a dedicated
+    /// appender, which isn't affected by dumping settings, sampling or rate
+    /// limits. The record of a request is written before sending the response,
+    /// so nothing is responded without the record. If a record cannot be
+    /// written, the actor panics.
+    ///
+    /// The outcome of handling is tracked precisely only if envelopes are
+    /// handled one by one, i.e. by [`Context::recv()`] and
+    /// [`Context::try_recv()`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use elfo_core as elfo;
+    /// use elfo::{audit::AuditConfig, ActorGroup};
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .audit(AuditConfig {
+    ///         path: "/var/log/risk.audit".into(),
+    ///         sync: true,
+    ///         include_response: true,
+    ///     })
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// # Panics
+    /// On mounting if the log cannot be opened.
+    ///
+    /// [`audit`]: crate::audit
+    /// [`Context::recv()`]: crate::Context::recv
+    /// [`Context::try_recv()`]: crate::Context::try_recv
+    pub fn audit(mut self, config: AuditConfig) -> Self {
+        self.audit = Some(config);
+        self
+    }
+
+    /// Runs actors of the group on a runtime owned by the group, which is
+    /// started on mounting with the specified settings, e.g. pinned to
+    /// isolated cores for the lowest latency.
+    ///
+    /// The settings are applied only on mounting and cannot be changed by
+    /// config updates. The runtime's name is exposed in
+    /// [`ActorStatusReport`], the settings are logged on start.
+    ///
+    /// Takes precedence over [`Topology::add_dedicated_rt()`], but
+    /// [`ActorGroup::placement()`] falls back to it.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// use elfo::{ActorGroup, RuntimeOptions};
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .dedicated_runtime(RuntimeOptions {
+    ///         worker_threads: 2,
+    ///         thread_name_prefix: "md".into(),
+    ///         pin_to_cores: vec![2, 3],
+    ///         thread_priority: Some(50),
+    ///     })
+    ///     .exec(|_ctx| async {});
+    /// ```
+    ///
+    /// # Panics
+    /// On mounting if the runtime cannot be started.
+    ///
+    /// [`ActorStatusReport`]: crate::messages::ActorStatusReport
+    /// [`Topology::add_dedicated_rt()`]: crate::Topology::add_dedicated_rt
+    #[cfg(feature = "dedicated-runtime")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dedicated-runtime")))]
+    pub fn dedicated_runtime(mut self, options: RuntimeOptions) -> Self {
+        self.runtime = Some(options);
+        self
+    }
+
+    /// Chooses a runtime for every actor by its key and the group's config.
+    ///
+    /// The closure is called at spawn time and on every config update.
+    /// If the placement of a running actor changes, the actor is migrated:
+    /// its mailbox is closed, so it handles messages already received and
+    /// finishes, then a new actor is started on the new runtime with
+    /// [`ActorStartCause::Migrated`]. Messages sent meanwhile are queued in
+    /// the mailbox of the new actor, so no messages are lost and the order is
+    /// kept. Messages left unhandled by the old actor are handed off to the
+    /// new one, like [`Context::drain_to()`] does.
+    ///
+    /// Returning `None` falls back to the runtime chosen by
+    /// [`Topology::add_dedicated_rt()`] or the current one.
+    ///
+    /// Must be called after specifying the router and the config.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let hot = RuntimeHandle::new("hot", hot_rt.handle().clone());
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .config::<Config>()
+    ///     .router(router)
+    ///     .placement(move |symbol, config| {
+    ///         config.hot_symbols.contains(symbol).then(|| hot.clone())
+    ///     })
+    ///     .exec(exec);
+    /// ```
+    ///
+    /// [`ActorStartCause::Migrated`]: crate::ActorStartCause::Migrated
+    /// [`Context::drain_to()`]: crate::Context::drain_to
+    /// [`Topology::add_dedicated_rt()`]: crate::Topology::add_dedicated_rt
+    #[stability::unstable]
+    pub fn placement<P>(mut self, place: impl Fn(&R::Key, &C) -> P + Send + Sync + 'static) -> Self
+    where
+        R: Router<C>,
+        C: Config,
+        P: Into<Option<RuntimeHandle>>,
+    {
+        let placement: Placement<R::Key, C> =
+            Arc::new(move |key, config| place(key, config).into());
+        self.placement = Some(Box::new(placement));
+        self
+    }
+
     /// Specifies the order of stopping among other groups.
     ///
     /// Actors in groups with lower values are stopped first.
@@ -106,26 +676,224 @@ impl<R, C> ActorGroup<R, C> {
         ER: ExecResult,
         C: Config,
     {
-        let mount =
-            move |ctx: Context, node_no: NodeNo, name: String, rt_manager: RuntimeManager| {
-                let addr = ctx.group();
-                let sv = Arc::new(Supervisor::new(
-                    ctx,
-                    node_no,
-                    name,
-                    exec,
-                    self.router,
-                    self.restart_policy,
-                    self.termination_policy,
-                    rt_manager,
-                ));
-
-                Object::new(addr, Box::new(Handle(sv)) as Box<dyn GroupHandle>)
+        let description = GroupDescription::new::<C, R, O>(self.mailbox_capacity);
+        let mount = move |ctx: Context,
+                          node_no: NodeNo,
+                          name: String,
+                          rt_manager: RuntimeManager,
+                          mount_condition: Option<MountCondition>| {
+            assert!(
+                !self.is_pooled || TypeId::of::<R>() == TypeId::of::<PoolRouter>(),
+                "the pooled group `{name}` must be routed by `PoolRouter`, \
+                 only `Outcome::Pool` is supported for regular messages"
+            );
+
+            for hook in self.mount_hooks {
+                hook(&name);
+            }
+
+            let placement = self.placement.map(|placement| {
+                *placement
+                    .downcast::<Placement<R::Key, C>>()
+                    .expect("placement must be set after the router and the config")
+            });
+
+            // The runtime lives as long as the placement, i.e. the supervisor.
+            #[cfg(feature = "dedicated-runtime")]
+            let placement = match self.runtime {
+                Some(options) => {
+                    let runtime = DedicatedRuntime::start(&name, options);
+                    let placement: Placement<R::Key, C> = Arc::new(move |key, config| {
+                        let place = placement.as_ref().and_then(|place| place(key, config));
+                        place.or_else(|| Some(runtime.handle().clone()))
+                    });
+                    Some(placement)
+                }
+                None => placement,
             };
 
+            let audit = self.audit.map(|config| match AuditLog::open(&config) {
+                Ok(log) => Arc::new(log),
+                Err(err) => panic!(
+                    "cannot open the audit log {} of `{name}`: {err}",
+                    config.path.display()
+                ),
+            });
+
+            let idempotency = self
+                .idempotency
+                .map(|(capacity, ttl)| Arc::new(IdempotencyCache::new(capacity, ttl)));
+
+            let addr = ctx.group();
+            let sv = Arc::new(Supervisor::new(
+                ctx,
+                node_no,
+                name,
+                exec,
+                self.router,
+                self.restart_policy,
+                self.termination_policy,
+                self.mailbox_capacity,
+                rt_manager,
+                mount_condition,
+                self.dedup,
+                Interceptors::new(self.interceptors),
+                idempotency,
+                self.concurrency,
+                self.self_queue,
+                self.admission,
+                self.dump_classifier,
+                audit,
+                placement,
+                self.is_pooled,
+                self.sticky,
+            ));
+
+            Object::new(addr, Box::new(Handle(sv)) as Box<dyn GroupHandle>)
+        };
+
         Blueprint {
             mount: Box::new(mount),
             stop_order: self.stop_order,
+            description,
+        }
+    }
+
+    /// Builds the group like [`ActorGroup::exec()`], but boxes the future
+    /// returned by the executor function.
+    ///
+    /// Futures are stored inline for every actor, so groups with large
+    /// futures (e.g. holding big arrays across `.await`) and many actors take
+    /// a lot of memory and move it at spawn. Boxing costs an allocation per
+    /// spawn instead. See `system.max_exec_future_size`.
+    pub fn boxed_exec<X, O, ER>(self, exec: X) -> Blueprint
+    where
+        R: Router<C>,
+        X: Fn(Context<C, R::Key>) -> O + Send + Sync + 'static,
+        O: Future<Output = ER> + Send + 'static,
+        ER: ExecResult,
+        C: Config,
+    {
+        self.exec(move |ctx| Box::pin(exec(ctx)))
+    }
+}
+
+impl<C> ActorGroup<PoolRouter, C> {
+    /// Makes the pool sticky by trace ids, see [`ActorGroup::sticky_by()`].
+    pub fn sticky_by_trace(self) -> Self {
+        self.sticky_by(|envelope| Some(envelope.trace_id()))
+    }
+
+    /// Makes the pool sticky: messages with the same key are handled by the
+    /// same worker one by one in the FIFO order, while messages with different
+    /// keys are still handled in parallel. Useful for handlers keeping
+    /// short-lived state per key, e.g. per trace.
+    ///
+    /// The key is bound to the worker taken its first message and stays bound
+    /// until no its messages are queued or handled during
+    /// `system.mailbox.sticky_idle_window`. Messages without a key are handled
+    /// by any worker.
+    ///
+    /// Bindings are stored in a bounded map, `system.mailbox.sticky_capacity`.
+    /// If it's full of active keys, messages with new keys are handled by any
+    /// worker without ordering guarantees. Bindings of a finished worker are
+    /// released, its queued messages are handled by other workers.
+    ///
+    /// Metrics:
+    /// * `elfo_pool_affinity_hits_total` counts messages of bound keys.
+    /// * `elfo_pool_affinity_misses_total` counts new bindings.
+    /// * `elfo_pool_affinity_evictions_total` counts released idle bindings.
+    /// * `elfo_pool_affinity_overflows_total` counts messages with keys, which
+    ///   aren't bound because the map is full.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # #[elfo::message] struct Order { client: u32 }
+    /// use elfo::{msg, ActorGroup};
+    ///
+    /// let blueprint = ActorGroup::new()
+    ///     .pool(8)
+    ///     .sticky_by(|envelope| msg!(match envelope {
+    ///         Order { client } => Some(*client),
+    ///         _ => None,
+    ///     }))
+    ///     .exec(|mut ctx| async move {
+    ///         while let Some(_envelope) = ctx.recv().await {}
+    ///     });
+    /// ```
+    pub fn sticky_by<K: Hash>(
+        mut self,
+        f: impl Fn(&Envelope) -> Option<K> + Send + Sync + 'static,
+    ) -> Self {
+        self.sticky = Some(Arc::new(move |envelope| {
+            f(envelope).map(|key| fxhash::hash64(&key))
+        }));
+        self
+    }
+}
+
+impl<R: fmt::Debug, C> fmt::Debug for ActorGroup<R, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorGroup")
+            .field("restart_policy", &self.restart_policy)
+            .field("termination_policy", &self.termination_policy)
+            .field("stop_order", &self.stop_order)
+            .field("mailbox_capacity", &self.mailbox_capacity)
+            .field("concurrency", &self.concurrency)
+            .field("self_queue", &self.self_queue)
+            .field("router", &self.router)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reusable set of group settings, see [`ActorGroup::apply()`].
+///
+/// It's implemented for any `FnOnce(ActorGroup<R, C>) -> ActorGroup<R1, C1>`,
+/// but also can be implemented for custom types.
+pub trait Preset<R, C> {
+    /// A router of the group after applying the preset.
+    type Router;
+    /// A config of the group after applying the preset.
+    type Config;
+
+    /// Applies the preset to the group.
+    fn apply(self, group: ActorGroup<R, C>) -> ActorGroup<Self::Router, Self::Config>;
+}
+
+impl<R, C, R1, C1, F> Preset<R, C> for F
+where
+    F: FnOnce(ActorGroup<R, C>) -> ActorGroup<R1, C1>,
+{
+    type Router = R1;
+    type Config = C1;
+
+    #[inline]
+    fn apply(self, group: ActorGroup<R, C>) -> ActorGroup<R1, C1> {
+        self(group)
+    }
+}
+
+/// Built-in presets, see [`ActorGroup::apply()`].
+pub mod presets {
+    use std::time::Duration;
+
+    use super::ActorGroup;
+    use crate::restarting::{RestartParams, RestartPolicy};
+
+    /// Sensible defaults for production groups:
+    /// * Actors are restarted on failures with backoff from 5s to 30s.
+    /// * Mailboxes are limited by 1000 messages.
+    ///
+    /// All these settings can still be overridden by config.
+    pub fn production<R, C>() -> impl FnOnce(ActorGroup<R, C>) -> ActorGroup<R, C> {
+        |group| {
+            group
+                .restart_policy(RestartPolicy::on_failure(RestartParams::new(
+                    Duration::from_secs(5),
+                    Duration::from_secs(30),
+                )))
+                .mailbox_capacity(1000)
         }
     }
 }
@@ -139,6 +907,14 @@ where
     <X::Output as Future>::Output: ExecResult,
     C: Config,
 {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.0.is_disabled()
+    }
+
     fn handle(&self, envelope: Envelope, visitor: &mut dyn GroupVisitor) {
         self.0.handle(envelope, visitor)
     }
@@ -148,9 +924,17 @@ where
     }
 }
 
+/// A condition to enable a group, see [`Local::mount_if()`].
+///
+/// [`Local::mount_if()`]: crate::topology::Local::mount_if
+pub(crate) type MountCondition = Arc<dyn Fn(&AnyConfig) -> bool + Send + Sync>;
+
 pub struct Blueprint {
-    pub(crate) mount: Box<dyn FnOnce(Context, NodeNo, String, RuntimeManager) -> Object>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) mount:
+        Box<dyn FnOnce(Context, NodeNo, String, RuntimeManager, Option<MountCondition>) -> Object>,
     pub(crate) stop_order: i8,
+    pub(crate) description: GroupDescription,
 }
 
 /// The behaviour on the `Terminate` message.
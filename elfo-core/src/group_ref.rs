@@ -0,0 +1,167 @@
+use std::{fmt, sync::Arc};
+
+use fxhash::FxHashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Deserializer};
+
+use crate::addr::Addr;
+
+/// A typed reference to a local or remote group, used instead of group names
+/// to choose a destination dynamically, see [`Context::send_to_group()`].
+///
+/// It's obtained by [`Context::locate_group()`], which fails fast on unknown
+/// names, or by [`Local::group_ref()`]. Also, it can be a part of the config,
+/// deserialized from the group name. Such refs are resolved on every send,
+/// so unknown names fail only on sending.
+///
+/// It's cheap to clone and remains valid across restarts of the group's
+/// actors. Messages to a disabled or terminated group fail.
+///
+/// [`Context::send_to_group()`]: crate::Context::send_to_group
+/// [`Context::locate_group()`]: crate::Context::locate_group
+/// [`Local::group_ref()`]: crate::topology::Local::group_ref
+#[derive(Clone)]
+pub struct GroupRef {
+    name: Arc<str>,
+    target: Option<GroupTarget>,
+}
+
+impl GroupRef {
+    pub(crate) fn new(name: Arc<str>, target: GroupTarget) -> Self {
+        Self {
+            name,
+            target: Some(target),
+        }
+    }
+
+    /// Returns the name of the group.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the group is on another node.
+    /// Always `false` for unresolved refs (deserialized from the config).
+    #[inline]
+    pub fn is_remote(&self) -> bool {
+        match &self.target {
+            Some(GroupTarget::Local(_)) | None => false,
+            #[cfg(feature = "network")]
+            Some(GroupTarget::Remote(_)) => true,
+        }
+    }
+
+    /// Returns the address of the group or of the remote handle to send to,
+    /// `None` if the group is unknown or no nodes of the remote group are
+    /// connected.
+    pub(crate) fn resolve(&self, groups: &GroupDirectory) -> Option<Addr> {
+        match &self.target {
+            Some(target) => target.resolve(),
+            None => groups.locate(&self.name)?.target?.resolve(),
+        }
+    }
+}
+
+impl fmt::Debug for GroupRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupRef")
+            .field("name", &self.name)
+            .field("is_remote", &self.is_remote())
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for GroupRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self {
+            name: name.into(),
+            target: None,
+        })
+    }
+}
+
+// === GroupTarget ===
+
+#[derive(Clone)]
+pub(crate) enum GroupTarget {
+    Local(Addr),
+    /// Remote handles of connected nodes.
+    #[cfg(feature = "network")]
+    Remote(Arc<arc_swap::ArcSwap<FxHashMap<crate::addr::NodeNo, Addr>>>),
+}
+
+impl GroupTarget {
+    fn resolve(&self) -> Option<Addr> {
+        match self {
+            Self::Local(addr) => Some(*addr),
+            // The node with the lowest number is used to be deterministic.
+            #[cfg(feature = "network")]
+            Self::Remote(nodes) => nodes
+                .load()
+                .iter()
+                .min_by_key(|(node_no, _)| **node_no)
+                .map(|(_, addr)| *addr),
+        }
+    }
+}
+
+// === GroupDirectory ===
+
+/// Group name => group, filled by the topology.
+#[derive(Default)]
+pub(crate) struct GroupDirectory {
+    groups: RwLock<FxHashMap<Arc<str>, GroupTarget>>,
+}
+
+impl GroupDirectory {
+    pub(crate) fn locate(&self, name: &str) -> Option<GroupRef> {
+        let groups = self.groups.read();
+        let (name, target) = groups.get_key_value(name)?;
+        Some(GroupRef::new(name.clone(), target.clone()))
+    }
+
+    pub(crate) fn insert_local(&self, name: &str, addr: Addr) {
+        self.groups
+            .write()
+            .insert(name.into(), GroupTarget::Local(addr));
+    }
+}
+
+cfg_network!({
+    use crate::addr::NodeNo;
+
+    impl GroupDirectory {
+        pub(crate) fn insert_remote(&self, name: &str) {
+            self.groups
+                .write()
+                .insert(name.into(), GroupTarget::Remote(Default::default()));
+        }
+
+        pub(crate) fn add_remote_node(&self, name: &str, node_no: NodeNo, handle_addr: Addr) {
+            self.update_remote_nodes(name, |nodes| {
+                nodes.insert(node_no, handle_addr);
+            });
+        }
+
+        pub(crate) fn remove_remote_node(&self, name: &str, node_no: NodeNo, handle_addr: Addr) {
+            self.update_remote_nodes(name, |nodes| {
+                // The node can be re-registered by another handle.
+                if nodes.get(&node_no) == Some(&handle_addr) {
+                    nodes.remove(&node_no);
+                }
+            });
+        }
+
+        fn update_remote_nodes(&self, name: &str, f: impl Fn(&mut FxHashMap<NodeNo, Addr>)) {
+            let groups = self.groups.read();
+            if let Some(GroupTarget::Remote(nodes)) = groups.get(name) {
+                nodes.rcu(|nodes| {
+                    let mut nodes = (**nodes).clone();
+                    f(&mut nodes);
+                    nodes
+                });
+            }
+        }
+    }
+});
@@ -0,0 +1,184 @@
+//! Replaying of responses to retried requests, see
+//! [`ActorGroup::idempotency_cache()`].
+//!
+//! [`ActorGroup::idempotency_cache()`]: crate::ActorGroup::idempotency_cache
+
+use std::{collections::VecDeque, fmt, num::NonZeroU64, time::Duration};
+
+use fxhash::FxHashMap;
+use metrics::increment_counter;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::{addr::random_u64, message::AnyMessage, Addr};
+
+// === IdempotencyKey ===
+
+/// Identifies a logical request across its retries, see
+/// [`RequestBuilder::idempotency_key()`].
+///
+/// Generate a key once per logical request and attach it to every attempt.
+///
+/// [`RequestBuilder::idempotency_key()`]: crate::RequestBuilder::idempotency_key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey(NonZeroU64);
+
+impl IdempotencyKey {
+    /// Generates a new random key.
+    pub fn generate() -> Self {
+        loop {
+            if let Some(key) = NonZeroU64::new(random_u64()) {
+                return Self(key);
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        NonZeroU64::new(bits).map(Self)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn into_bits(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for IdempotencyKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+// === IdempotencyCache ===
+
+/// Responses of the group, shared by all its actors.
+pub(crate) struct IdempotencyCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    responses: FxHashMap<(Addr, IdempotencyKey), (AnyMessage, Instant)>,
+    /// Keys in the recording order, used to evict the oldest responses.
+    order: VecDeque<((Addr, IdempotencyKey), Instant)>,
+}
+
+impl IdempotencyCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Returns the response recorded for the request, if any.
+    pub(crate) fn lookup(&self, requester: Addr, key: IdempotencyKey) -> Option<AnyMessage> {
+        let mut inner = self.inner.lock();
+        inner.evict_expired(self.ttl);
+
+        let (response, _) = inner.responses.get(&(requester, key))?;
+        let response = response.clone();
+        drop(inner);
+
+        increment_counter!("elfo_idempotency_cache_hits_total");
+        Some(response)
+    }
+
+    pub(crate) fn record(&self, requester: Addr, key: IdempotencyKey, response: AnyMessage) {
+        let mut inner = self.inner.lock();
+        inner.evict_expired(self.ttl);
+
+        let now = Instant::now();
+        let entry = (requester, key);
+
+        if let Some(recorded) = inner.responses.get_mut(&entry) {
+            *recorded = (response, now);
+        } else {
+            while inner.responses.len() >= self.capacity {
+                inner.evict_oldest();
+            }
+            inner.responses.insert(entry, (response, now));
+        }
+
+        inner.order.push_back((entry, now));
+    }
+}
+
+impl Inner {
+    fn evict_expired(&mut self, ttl: Duration) {
+        let now = Instant::now();
+
+        while let Some(&(_, recorded_at)) = self.order.front() {
+            if now.duration_since(recorded_at) < ttl {
+                break;
+            }
+
+            self.evict_oldest();
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let (entry, recorded_at) = ward!(self.order.pop_front());
+
+        // Skip outdated records of responses recorded again.
+        if self
+            .responses
+            .get(&entry)
+            .is_some_and(|(_, at)| *at == recorded_at)
+        {
+            self.responses.remove(&entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::message;
+
+    #[message]
+    #[derive(PartialEq)]
+    struct Num(u32);
+
+    fn num(response: Option<AnyMessage>) -> Option<u32> {
+        response.map(|r| r.downcast::<Num>().unwrap().0)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn capacity_and_ttl() {
+        let cache = IdempotencyCache::new(2, Duration::from_secs(10));
+        let (a, b, c) = (
+            IdempotencyKey::generate(),
+            IdempotencyKey::generate(),
+            IdempotencyKey::generate(),
+        );
+
+        cache.record(Addr::NULL, a, AnyMessage::new(Num(1)));
+        cache.record(Addr::NULL, b, AnyMessage::new(Num(2)));
+        assert_eq!(num(cache.lookup(Addr::NULL, a)), Some(1));
+        assert_eq!(num(cache.lookup(Addr::NULL, b)), Some(2));
+
+        // The oldest response is evicted.
+        cache.record(Addr::NULL, c, AnyMessage::new(Num(3)));
+        assert_eq!(num(cache.lookup(Addr::NULL, a)), None);
+        assert_eq!(num(cache.lookup(Addr::NULL, c)), Some(3));
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        cache.record(Addr::NULL, b, AnyMessage::new(Num(4)));
+
+        // `c` is expired, but `b` is recorded again.
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(num(cache.lookup(Addr::NULL, c)), None);
+        assert_eq!(num(cache.lookup(Addr::NULL, b)), Some(4));
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(num(cache.lookup(Addr::NULL, b)), None);
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
-    future::Future,
-    sync::Arc,
+    future::{self, Future},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -26,17 +29,23 @@ use crate::{
     config::SystemConfig,
     context::Context,
     demux::Demux,
-    errors::{RequestError, StartError, StartGroupError},
+    errors::{StartError, StartGroupError},
     message,
     messages::{StartEntrypoint, Terminate, UpdateConfig},
     object::Object,
+    panics,
     scope::{Scope, ScopeGroupShared},
     signal::{Signal, SignalKind},
+    stream::Stream,
     subscription::SubscriptionManager,
-    topology::{Topology, SYSTEM_INIT_GROUP_NO},
+    topology::{LocalActorGroup, Topology, SYSTEM_INIT_GROUP_NO},
     tracing::TraceId,
 };
 
+#[cfg(feature = "unstable")] // TODO: patch `stability`, again.
+pub use crate::message::MessageCollision;
+pub use crate::shutdown::ShutdownReason;
+
 const INIT_GROUP_NAME: &str = "system.init";
 
 type Result<T, E = StartError> = std::result::Result<T, E>;
@@ -47,18 +56,13 @@ async fn start_entrypoints(ctx: &Context, topology: &Topology, is_check_only: bo
         .filter(|group| group.is_entrypoint)
         .map(|group| async move {
             let response = ctx
-                .request_to(
-                    group.addr,
-                    UpdateConfig {
-                        config: Default::default(),
-                    },
-                )
+                .request_to(group.addr, UpdateConfig::new(Default::default()))
                 .resolve()
                 .await;
             match response {
                 Ok(Ok(())) => Ok(()),
                 Ok(Err(e)) => Err(StartError::single(group.name.clone(), e.reason)),
-                Err(RequestError::Ignored) | Err(RequestError::Failed) => Err(StartError::single(
+                Err(_) => Err(StartError::single(
                     group.name.clone(),
                     "config cannot be delivered to the entrypoint".into(),
                 )),
@@ -81,7 +85,7 @@ async fn start_entrypoints(ctx: &Context, topology: &Topology, is_check_only: bo
                         .collect();
                     Err(StartError::multiple(group_errors))
                 }
-                Err(RequestError::Ignored) | Err(RequestError::Failed) => Err(StartError::single(
+                Err(_) => Err(StartError::single(
                     group.name,
                     "starting message cannot be delivered to the entrypoint".into(),
                 )),
@@ -114,7 +118,28 @@ pub async fn start(topology: Topology) {
 
 /// The same as `start()`, but returns an error rather than panics.
 pub async fn try_start(topology: Topology) -> Result<()> {
-    check_messages_uniqueness()?;
+    try_run(topology).await.map(drop)
+}
+
+/// The same as `try_start()`, but also returns why the system has been shut
+/// down, e.g. to choose the exit code of the process.
+///
+/// # Example
+/// ```no_run
+/// # use elfo_core as elfo;
+/// # async fn run(topology: elfo::Topology) {
+/// use elfo::init::ShutdownReason;
+///
+/// let code = match elfo::init::try_run(topology).await {
+///     Ok(ShutdownReason::Requested(_)) => 2,
+///     Ok(_) => 0,
+///     Err(_) => 1,
+/// };
+/// std::process::exit(code);
+/// # }
+/// ```
+pub async fn try_run(topology: Topology) -> Result<ShutdownReason> {
+    log_message_collisions();
 
     #[cfg(feature = "test-util")]
     warn!("elfo is compiled with `test-util` feature, it may affect performance");
@@ -132,7 +157,7 @@ pub async fn try_start(topology: Topology) -> Result<()> {
 /// Starts node in "check only" mode. Entrypoints are started, then the system
 /// is immediately gracefully terminated.
 pub async fn check_only(topology: Topology) -> Result<()> {
-    check_messages_uniqueness()?;
+    log_message_collisions();
 
     // The logger is not supposed to be initialized in this mode, so we do not wait
     // for it before exiting.
@@ -140,23 +165,45 @@ pub async fn check_only(topology: Topology) -> Result<()> {
 }
 
 /// Checks that all messages are unique by `(protocol, name)` pair.
-/// If there are duplicates, returns an error.
+/// If there are duplicates, returns an error naming colliding types.
 ///
-/// It's called automatically by `(try_)start()` and `check_only()`,
-/// but still provided in order to being called manually in service tests.
+/// Duplicates are detected automatically by `(try_)start()` and `check_only()`
+/// and refuse the start unless `system.allow_duplicate_messages = true`,
+/// but it's still provided in order to being called manually in service tests.
 #[stability::unstable]
 pub fn check_messages_uniqueness() -> Result<()> {
-    message::check_uniqueness().map_err(|duplicates| {
-        let errors = duplicates
-            .into_iter()
-            .map(|(protocol, name)| StartGroupError {
-                group: INIT_GROUP_NAME.into(),
-                reason: format!("message `{}/{}` is defined several times", protocol, name),
-            })
-            .collect();
-
-        StartError::multiple(errors)
-    })
+    let collisions = message::collisions();
+    if collisions.is_empty() {
+        return Ok(());
+    }
+
+    let errors = collisions
+        .iter()
+        .map(|collision| StartGroupError {
+            group: INIT_GROUP_NAME.into(),
+            reason: collision.to_string(),
+        })
+        .collect();
+
+    Err(StartError::multiple(errors))
+}
+
+/// Returns messages defined several times with the same protocol and name.
+/// See [`check_messages_uniqueness()`].
+#[stability::unstable]
+pub fn message_collisions() -> &'static [message::MessageCollision] {
+    message::collisions()
+}
+
+// Groups reject configs if there are collisions, see `SystemConfig`.
+fn log_message_collisions() {
+    for collision in message::collisions() {
+        error!(
+            protocol = collision.protocol,
+            name = collision.name,
+            "{collision}"
+        );
+    }
 }
 
 #[doc(hidden)]
@@ -166,6 +213,7 @@ pub async fn do_start<F: Future>(
     and_then: impl FnOnce(Context, Topology) -> F,
 ) -> Result<F::Output> {
     instant_clock_calibration();
+    panics::install_hook();
 
     let group_no = GroupNo::new(SYSTEM_INIT_GROUP_NO, topology.launch_id()).unwrap();
     let entry = topology.book.vacant_entry(group_no);
@@ -180,13 +228,17 @@ pub async fn do_start<F: Future>(
     // XXX: create a real group.
     let actor = Actor::new(
         meta.clone(),
+        None,
         addr,
         &<_>::default(),
+        &<_>::default(),
         <_>::default(),
         Arc::new(SubscriptionManager::new(ctx.clone())),
     );
 
     let scope_shared = ScopeGroupShared::new(topology.node_no(), addr);
+    #[cfg(feature = "test-util")]
+    scope_shared.set_dump_capture(topology.dump_capture().clone());
     let mut config = SystemConfig::default();
     config.logging.max_level = LevelFilter::INFO;
     scope_shared.configure(&config);
@@ -210,16 +262,31 @@ pub async fn do_start<F: Future>(
 #[message]
 struct TerminateSystem;
 
+// Also dumped to record the reason of shutdown.
+#[message]
+struct ShutdownInitiated {
+    reason: ShutdownReason,
+}
+
 #[message]
 struct CheckMemoryUsageTick;
 
 // TODO: make these values configurable.
 const SEND_CLOSING_TERMINATE_AFTER: Duration = Duration::from_secs(25);
-const STOP_GROUP_TERMINATION_AFTER: Duration = Duration::from_secs(35);
+pub(crate) const STOP_GROUP_TERMINATION_AFTER: Duration = Duration::from_secs(35);
 
-async fn exec(mut ctx: Context, topology: Topology) {
+async fn exec(mut ctx: Context, topology: Topology) -> ShutdownReason {
     emit_start_time();
 
+    let shutdown = topology.book.shutdown().clone();
+    ctx.attach(Stream::once({
+        let shutdown = shutdown.clone();
+        async move {
+            let reason = shutdown.initiated().await;
+            ShutdownInitiated { reason }
+        }
+    }));
+
     ctx.attach(Signal::new(SignalKind::UnixTerminate, TerminateSystem));
     ctx.attach(Signal::new(SignalKind::UnixInterrupt, TerminateSystem));
     ctx.attach(Signal::new(SignalKind::WindowsCtrlC, TerminateSystem));
@@ -242,13 +309,20 @@ async fn exec(mut ctx: Context, topology: Topology) {
         }
     };
 
-    let mut oom_prevented = false;
+    let mut reason = None;
 
     while let Some(envelope) = ctx.recv().await {
-        if envelope.is::<TerminateSystem>() {
+        if envelope.is::<ShutdownInitiated>() {
+            let (initiated, _) = envelope.unpack::<ShutdownInitiated>().unwrap();
+            reason = Some(initiated.reason);
             break;
         }
 
+        if envelope.is::<TerminateSystem>() {
+            shutdown.initiate(ShutdownReason::Signal);
+            continue;
+        }
+
         #[cfg(target_os = "linux")]
         if envelope.is::<CheckMemoryUsageTick>() {
             match memory_tracker.as_ref().map(|mt| mt.check()) {
@@ -265,8 +339,7 @@ async fn exec(mut ctx: Context, topology: Topology) {
                         "maximum memory usage is reached, forcibly terminating"
                     );
 
-                    let _ = ctx.try_send_to(ctx.addr(), TerminateSystem);
-                    oom_prevented = true;
+                    shutdown.initiate(ShutdownReason::OutOfMemory);
                 }
                 Some(Err(err)) => {
                     warn!(error = %err, "memory tracker cannot check memory usage");
@@ -275,61 +348,102 @@ async fn exec(mut ctx: Context, topology: Topology) {
         }
     }
 
+    let reason = reason.unwrap_or(ShutdownReason::Signal);
+    info!(message = "shutting down the system", %reason);
+
     ctx.set_status(ActorStatus::TERMINATING);
 
     let termination = terminate(ctx.pruned(), topology);
     pin!(termination);
 
+    // If shutdown is requested by the application, the first signal is
+    // treated as the first `Ctrl-C`, so only the second one is immediate.
+    let mut skip_signal = matches!(reason, ShutdownReason::Requested(_));
+
     loop {
         select! {
-            _ = &mut termination => return,
+            _ = &mut termination => return reason,
             Some(envelope) = ctx.recv() => {
                 if !envelope.is::<TerminateSystem>() {
                     continue;
                 }
 
-                if oom_prevented {
-                    // Skip the first signal after OOM prevented.
-                    oom_prevented = false;
+                if skip_signal {
+                    skip_signal = false;
                 } else {
                     // `Ctrl-C` has been pressed again. Terminate immediately.
                     // TODO: `Terminate::closing` on second `Ctrl-C`
-                    return;
+                    return reason;
                 }
             }
         }
     }
 }
 
-async fn terminate(ctx: Context, topology: Topology) {
-    let mut stop_order_list = topology
+#[doc(hidden)]
+pub async fn terminate(ctx: Context, topology: Topology) {
+    let mut waves = topology
         .locals()
-        .map(|group| group.stop_order)
+        .map(|group| group.shutdown_wave())
         .collect::<Vec<_>>();
 
-    stop_order_list.sort_unstable();
-    stop_order_list.dedup();
+    waves.sort_unstable();
+    waves.dedup();
+
+    let wave_timeout = topology.shutdown_wave_timeout();
 
-    for stop_order in stop_order_list {
-        info!(%stop_order, "terminating groups");
-        terminate_groups(&ctx, &topology, stop_order).await;
+    for wave in waves {
+        let groups = topology
+            .locals()
+            .filter(|group| group.shutdown_wave() == wave)
+            .collect::<Vec<_>>();
+
+        info!(
+            message = "terminating groups",
+            stop_order = wave.0,
+            groups = ?groups.iter().map(|g| &g.name).collect::<Vec<_>>(),
+        );
+        terminate_groups(&ctx, groups, wave_timeout).await;
     }
 }
 
-async fn terminate_groups(ctx: &Context, topology: &Topology, stop_order: i8) {
-    let futures = topology
-        .locals()
-        .filter(|group| group.stop_order == stop_order)
-        .map(|group| async move {
+async fn terminate_groups(ctx: &Context, groups: Vec<LocalActorGroup>, wave_timeout: Duration) {
+    let finished = groups
+        .iter()
+        .map(|_| AtomicBool::new(false))
+        .collect::<Vec<_>>();
+
+    let futures = groups
+        .iter()
+        .zip(&finished)
+        .map(|(group, finished)| async move {
             let started_at = Instant::now();
             select! {
                 _ = terminate_group(ctx, group.addr, group.name.clone(), started_at) => {},
-                _ = watch_group(ctx, group.addr, group.name, started_at) => {},
+                _ = watch_group(ctx, group.addr, group.name.clone(), started_at) => {
+                    finished.store(true, Ordering::Relaxed);
+                },
             }
         })
         .collect::<Vec<_>>();
 
-    join_all(futures).await;
+    if timeout(wave_timeout, join_all(futures)).await.is_ok() {
+        return;
+    }
+
+    // Proceed to the next wave, stragglers are forcibly closed.
+    for (group, finished) in groups.iter().zip(&finished) {
+        if finished.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        error!(
+            message = "failed to terminate an actor group, skipped",
+            group = %group.name,
+            elapsed = ?wave_timeout,
+        );
+        let _ = ctx.try_send_to(group.addr, Terminate::closing());
+    }
 }
 
 async fn terminate_group(ctx: &Context, addr: Addr, name: String, started_at: Instant) {
@@ -356,20 +470,12 @@ async fn terminate_group(ctx: &Context, addr: Addr, name: String, started_at: In
     );
     let fut = ctx.send_to(addr, Terminate::closing());
 
-    if timeout(STOP_GROUP_TERMINATION_AFTER, fut).await.is_ok() {
-        let elapsed = started_at.elapsed();
-        if let Some(delta) = STOP_GROUP_TERMINATION_AFTER.checked_sub(elapsed) {
-            sleep(delta).await;
-        }
-    } else {
+    if timeout(STOP_GROUP_TERMINATION_AFTER, fut).await.is_err() {
         warn!(group = %name, "failed to deliver closing Terminate");
     }
 
-    error!(
-        message = "failed to terminate an actor group, skipped",
-        group = %name,
-        elapsed = ?started_at.elapsed(),
-    );
+    // Wait for the wave timeout.
+    future::pending::<()>().await;
 }
 
 async fn watch_group(ctx: &Context, addr: Addr, name: String, started_at: Instant) {
@@ -0,0 +1,51 @@
+//! Interception of received envelopes, see [`ActorGroup::intercept()`].
+//!
+//! [`ActorGroup::intercept()`]: crate::ActorGroup::intercept
+
+use std::sync::Arc;
+
+use metrics::increment_counter;
+
+use crate::{envelope::Envelope, messages, Message};
+
+pub(crate) type Interceptor = Arc<dyn Fn(Envelope) -> Option<Envelope> + Send + Sync>;
+
+/// Interceptors of one group, shared by its actors.
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors(Arc<[Interceptor]>);
+
+impl Interceptors {
+    pub(crate) fn new(interceptors: Vec<Interceptor>) -> Self {
+        Self(interceptors.into())
+    }
+
+    /// Passes the envelope through all interceptors in order.
+    /// Returns `None` if any of them has dropped it.
+    #[inline]
+    pub(crate) fn apply(&self, envelope: Envelope) -> Option<Envelope> {
+        // Fast path, interceptors are rarely used.
+        if self.0.is_empty() {
+            return Some(envelope);
+        }
+
+        self.do_apply(envelope)
+    }
+
+    fn do_apply(&self, envelope: Envelope) -> Option<Envelope> {
+        // System messages are never intercepted to keep actors manageable.
+        if envelope.message().protocol() == messages::Ping.protocol() {
+            return Some(envelope);
+        }
+
+        let result = self
+            .0
+            .iter()
+            .try_fold(envelope, |envelope, interceptor| interceptor(envelope));
+
+        if result.is_none() {
+            increment_counter!("elfo_intercepted_dropped_total");
+        }
+
+        result
+    }
+}
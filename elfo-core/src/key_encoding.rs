@@ -0,0 +1,254 @@
+use std::{borrow::Cow, fmt::Write};
+
+use crate::actor::ActorMeta;
+
+/// Describes how an actor key is encoded to be used outside of routing.
+///
+/// Keys are produced by routers and can contain anything: slashes, spaces,
+/// newlines, unicode and so on. The raw key (`ActorMeta::key`) is still used
+/// for routing and dumps, but other places require sanitized keys. All
+/// encodings are pure functions of the key, so they are stable across
+/// restarts and metric series and files stay continuous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyEncoding {
+    /// Suitable for metric label values.
+    ///
+    /// All bytes except `[A-Za-z0-9_.:-]` are percent-encoded.
+    Label,
+    /// Suitable for file names.
+    ///
+    /// Keys consisting of `[A-Za-z0-9_.-]`, not starting with `.` and not
+    /// longer than [`KeyEncoding::MAX_PATH_LEN`] are used as is. Otherwise,
+    /// invalid chars are replaced with `_`, the result is truncated and
+    /// suffixed with a hash of the raw key.
+    Path,
+    /// Suitable for log prefixes.
+    ///
+    /// Control chars are escaped, the result is truncated to `max_width`
+    /// chars with the trailing ellipsis.
+    Prefix {
+        /// The maximum number of chars, including the ellipsis.
+        max_width: usize,
+    },
+}
+
+impl KeyEncoding {
+    /// The maximum length of keys encoded by [`KeyEncoding::Path`].
+    pub const MAX_PATH_LEN: usize = 64;
+
+    /// Encodes the provided key.
+    pub fn encode<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        match *self {
+            Self::Label => encode_label(key),
+            Self::Path => encode_path(key),
+            Self::Prefix { max_width } => encode_prefix(key, max_width),
+        }
+    }
+}
+
+impl ActorMeta {
+    /// Returns the key encoded by the provided encoding.
+    /// The raw key is available as the `key` field.
+    pub fn encoded_key(&self, encoding: KeyEncoding) -> Cow<'_, str> {
+        encoding.encode(&self.key)
+    }
+}
+
+fn encode_label(key: &str) -> Cow<'_, str> {
+    let is_allowed = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b':' | b'-');
+
+    if key.bytes().all(is_allowed) {
+        return key.into();
+    }
+
+    let mut encoded = String::with_capacity(key.len() * 3 / 2);
+    for b in key.bytes() {
+        if is_allowed(b) {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded.into()
+}
+
+fn encode_path(key: &str) -> Cow<'_, str> {
+    let is_allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-');
+
+    if !key.is_empty()
+        && key.len() <= KeyEncoding::MAX_PATH_LEN
+        && !key.starts_with('.')
+        && key.chars().all(is_allowed)
+    {
+        return key.into();
+    }
+
+    // `-` + 16 hex digits.
+    const SUFFIX_LEN: usize = 17;
+
+    let mut encoded = String::with_capacity(KeyEncoding::MAX_PATH_LEN);
+    for c in key.chars().take(KeyEncoding::MAX_PATH_LEN - SUFFIX_LEN) {
+        encoded.push(if is_allowed(c) { c } else { '_' });
+    }
+
+    // Avoid `.`, `..` and hidden files.
+    if encoded.starts_with('.') {
+        encoded.replace_range(..1, "_");
+    }
+
+    let _ = write!(encoded, "-{:016x}", fnv1a(key.as_bytes()));
+    encoded.into()
+}
+
+fn encode_prefix(key: &str, max_width: usize) -> Cow<'_, str> {
+    if key.chars().count() <= max_width && !key.chars().any(char::is_control) {
+        return key.into();
+    }
+
+    let mut encoded = String::with_capacity(max_width.min(key.len()) + 3);
+    let mut width = 0;
+    let mut escaped = String::new();
+
+    for c in key.chars() {
+        escaped.clear();
+        if c.is_control() {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+
+        let char_count = escaped.chars().count();
+        if width + char_count > max_width {
+            // Replace the last chars with the ellipsis.
+            while width + 1 > max_width && encoded.pop().is_some() {
+                width -= 1;
+            }
+            if max_width > 0 {
+                encoded.push('…');
+            }
+            break;
+        }
+
+        encoded.push_str(&escaped);
+        width += char_count;
+    }
+
+    encoded.into()
+}
+
+// The FNV-1a hash is used, because it's defined by the spec and never changes,
+// unlike `DefaultHasher` and `fxhash` depending on the platform.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    fn nasty_keys() -> Vec<String> {
+        vec![
+            String::new(),
+            "simple".into(),
+            "with/slash".into(),
+            "with space".into(),
+            "line\nbreak\r\n".into(),
+            "../../etc/passwd".into(),
+            "..".into(),
+            ".".into(),
+            ".hidden".into(),
+            "quote\"back\\slash".into(),
+            "emoji🦀🔥".into(),
+            "юникод".into(),
+            "x".repeat(10 * 1024),
+            "🦀".repeat(10 * 1024),
+            "\0\t\x1b[31m".into(),
+        ]
+    }
+
+    fn assert_label_safe(encoded: &str) {
+        assert!(encoded
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_.:-%".contains(&b)));
+    }
+
+    fn assert_path_safe(encoded: &str) {
+        assert!(!encoded.is_empty());
+        assert!(encoded.len() <= KeyEncoding::MAX_PATH_LEN);
+        assert!(!encoded.starts_with('.'));
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c)));
+    }
+
+    fn assert_prefix_safe(encoded: &str, max_width: usize) {
+        assert!(encoded.chars().count() <= max_width);
+        assert!(!encoded.chars().any(char::is_control));
+    }
+
+    #[test]
+    fn nasty() {
+        for key in nasty_keys() {
+            assert_label_safe(&KeyEncoding::Label.encode(&key));
+            assert_path_safe(&KeyEncoding::Path.encode(&key));
+            for max_width in [0, 1, 5, 32] {
+                let encoding = KeyEncoding::Prefix { max_width };
+                assert_prefix_safe(&encoding.encode(&key), max_width);
+            }
+        }
+    }
+
+    #[test]
+    fn stable() {
+        // Changing these values breaks continuity of metrics and files.
+        assert_eq!(KeyEncoding::Label.encode("a/b c"), "a%2Fb%20c");
+        assert_eq!(KeyEncoding::Label.encode("🦀"), "%F0%9F%A6%80");
+        assert_eq!(KeyEncoding::Path.encode("simple-key_1.0"), "simple-key_1.0");
+        assert_eq!(
+            KeyEncoding::Path.encode("../etc"),
+            "_._etc-54caf568cfae66bc"
+        );
+        assert_eq!(KeyEncoding::Path.encode(""), "-cbf29ce484222325");
+    }
+
+    #[test]
+    fn prefix() {
+        let encoding = KeyEncoding::Prefix { max_width: 5 };
+        assert_eq!(encoding.encode("abcde"), "abcde");
+        assert_eq!(encoding.encode("abcdef"), "abcd…");
+        assert_eq!(encoding.encode("a\nb"), "a\\nb");
+        assert_eq!(encoding.encode("🦀🦀🦀🦀🦀🦀"), "🦀🦀🦀🦀…");
+    }
+
+    proptest! {
+        #[test]
+        fn safe_and_stable(key in any::<String>(), max_width in 0..100usize) {
+            let label = KeyEncoding::Label.encode(&key);
+            assert_label_safe(&label);
+            prop_assert_eq!(&label, &KeyEncoding::Label.encode(&key));
+
+            let path = KeyEncoding::Path.encode(&key);
+            assert_path_safe(&path);
+            prop_assert_eq!(&path, &KeyEncoding::Path.encode(&key));
+
+            let encoding = KeyEncoding::Prefix { max_width };
+            let prefix = encoding.encode(&key);
+            assert_prefix_safe(&prefix, max_width);
+            prop_assert_eq!(&prefix, &encoding.encode(&key));
+        }
+
+        #[test]
+        fn label_is_injective(a in any::<String>(), b in any::<String>()) {
+            prop_assume!(a != b);
+            prop_assert_ne!(KeyEncoding::Label.encode(&a), KeyEncoding::Label.encode(&b));
+        }
+    }
+}
@@ -10,18 +10,33 @@ extern crate elfo_utils;
 extern crate self as elfo_core;
 
 // TODO: revise this list
+#[cfg(feature = "compression")]
+pub use crate::compression::Compressed;
+#[cfg(feature = "dedicated-runtime")]
+pub use crate::runtime::RuntimeOptions;
 pub use crate::{
     actor::{ActorMeta, ActorStartCause, ActorStartInfo},
     actor_status::{ActorStatus, ActorStatusKind},
     addr::Addr,
+    broker::Topic,
+    concurrency::Concurrency,
     config::Config,
-    context::{Context, RequestBuilder},
+    context::{Batch, Context, Detailed, RequestBuilder},
+    dedup::DedupWindow,
+    deferred::{DeferredStats, DeferredToken},
     envelope::Envelope,
-    group::{ActorGroup, Blueprint, TerminationPolicy},
+    group::{presets, ActorGroup, Blueprint, Preset, TerminationPolicy},
+    group_ref::GroupRef,
+    idempotency::IdempotencyKey,
+    key_encoding::KeyEncoding,
     local::{Local, MoveOwnership},
     message::{AnyMessage, AnyMessageRef, Message, Request},
-    request_table::{RequestId, ResponseToken},
+    pipeline::Pipeline,
+    rate_limiting::RateLimited,
+    request_table::{PendingRequest, RequestId, RequestLimits, ResponseToken},
     restarting::{RestartParams, RestartPolicy},
+    runtime::RuntimeHandle,
+    self_queue::{SelfQueue, SelfQueuePriority},
     source::{SourceHandle, UnattachedSource},
     topology::Topology,
 };
@@ -31,6 +46,10 @@ pub use elfo_macros::{message_core as message, msg_core as msg};
 mod macros;
 
 pub mod addr;
+pub mod admission;
+pub mod audit;
+pub mod channel;
+pub mod compression;
 pub mod config;
 pub mod coop;
 pub mod dumping;
@@ -38,9 +57,16 @@ pub mod errors;
 pub mod init;
 pub mod logging;
 pub mod messages;
+pub mod panics;
+#[cfg(feature = "test-util")]
+#[doc(hidden)]
+pub mod probe;
 pub mod routers;
 pub mod scope;
 pub mod signal;
+#[cfg(feature = "test-util")]
+#[doc(hidden)]
+pub mod simulation;
 pub mod stream;
 #[cfg(feature = "unstable-stuck-detection")]
 pub mod stuck_detection;
@@ -51,19 +77,32 @@ pub mod tracing;
 mod actor;
 mod actor_status;
 mod address_book;
+mod broker;
+mod circuit_breaking;
+mod concurrency;
 mod context;
+mod dedup;
+mod deferred;
 mod demux;
+mod deprecation;
 mod envelope;
 mod exec;
 mod group;
+mod group_ref;
+mod idempotency;
+mod intercept;
+mod key_encoding;
 mod local;
 mod mailbox;
 #[cfg(target_os = "linux")]
 mod memory_tracker;
 mod message;
 mod object;
-mod panic;
 mod permissions;
+mod pipeline;
+mod poisoning;
+mod pool;
+mod rate_limiting;
 #[cfg(all(feature = "network", feature = "unstable"))]
 pub mod remote;
 #[cfg(all(feature = "network", not(feature = "unstable")))]
@@ -71,18 +110,22 @@ mod remote;
 mod request_table;
 mod restarting;
 mod runtime;
+mod self_queue;
+mod shutdown;
 mod source;
+mod spawn_throttle;
 mod subscription;
 mod supervisor;
 mod telemetry;
 mod thread;
+mod ttl;
 
 #[doc(hidden)]
 pub mod _priv {
     pub use crate::{
-        address_book::AddressBook,
+        address_book::{AddressBook, AddressBookStats},
         envelope::{EnvelopeBorrowed, EnvelopeOwned, MessageKind},
-        init::do_start,
+        init::{do_start, terminate},
         message::*,
         object::{GroupVisitor, Object, OwnedObject},
         permissions::{AtomicPermissions, Permissions},
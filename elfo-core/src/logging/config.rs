@@ -2,6 +2,8 @@
 //!
 //! [Config]: LoggingConfig
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Deserializer};
 use tracing::level_filters::LevelFilter;
 
@@ -12,6 +14,8 @@ use tracing::level_filters::LevelFilter;
 /// [some_group]
 /// system.logging.max_level = "Warn"
 /// system.logging.max_rate_per_level = 1_000
+/// system.logging.trace_sample_rate = 0.01
+/// system.logging.fields = { subsystem = "billing", team = "payments" }
 /// ```
 #[derive(Debug, Deserialize)]
 #[serde(default)]
@@ -25,6 +29,21 @@ pub struct LoggingConfig {
     ///
     /// `1_000` by default.
     pub max_rate_per_level: u64,
+    /// A share of traces, which `Debug` and `Trace` logs are emitted, in
+    /// `[0, 1]`. Other levels aren't sampled.
+    ///
+    /// Decisions are made in the same way as for
+    /// `system.dumping.trace_sample_rate`, so with the same rate logs are
+    /// emitted exactly for dumped traces.
+    ///
+    /// `1.0` by default.
+    pub trace_sample_rate: f64,
+    /// Static fields appended to every event emitted in the group's scopes,
+    /// e.g. to route logs by subsystem. Fields of the event and its spans
+    /// take precedence on collisions.
+    ///
+    /// Empty by default.
+    pub fields: BTreeMap<String, String>,
 }
 
 impl Default for LoggingConfig {
@@ -32,6 +51,8 @@ impl Default for LoggingConfig {
         Self {
             max_level: LevelFilter::INFO,
             max_rate_per_level: 1000,
+            trace_sample_rate: 1.,
+            fields: BTreeMap::new(),
         }
     }
 }
@@ -1,13 +1,33 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use arc_swap::ArcSwapOption;
 use tracing::{Level, Metadata};
 
 use elfo_utils::{CachePadded, RateLimit, RateLimiter};
 
 use super::config::LoggingConfig;
+use crate::tracing::{TraceId, TraceSampler};
 
-#[derive(Default)]
 #[stability::unstable]
 pub struct LoggingControl {
     limiters: [CachePadded<RateLimiter>; 5],
+    /// `TraceSampler` for `Debug` and `Trace` levels.
+    sampler: AtomicU64,
+    /// `None` if there are no static fields.
+    fields: ArcSwapOption<StaticFields>,
+}
+
+impl Default for LoggingControl {
+    fn default() -> Self {
+        Self {
+            limiters: Default::default(),
+            sampler: AtomicU64::new(TraceSampler::default().into_bits()),
+            fields: ArcSwapOption::empty(),
+        }
+    }
 }
 
 impl LoggingControl {
@@ -15,9 +35,54 @@ impl LoggingControl {
         for limiter in &self.limiters {
             limiter.configure(RateLimit::Rps(config.max_rate_per_level));
         }
+
+        let sampler = TraceSampler::new(config.trace_sample_rate);
+        self.sampler.store(sampler.into_bits(), Ordering::Relaxed);
+
+        // Replaced only if changed, so loggers can cache rendered fields.
+        let fields: Option<StaticFields> = (!config.fields.is_empty()).then(|| {
+            config
+                .fields
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        });
+        if self.fields.load().as_deref() != fields.as_ref() {
+            self.fields.store(fields.map(Arc::new));
+        }
     }
 
+    /// Returns static fields of the group, see `LoggingConfig::fields`.
+    ///
+    /// The same instance is returned until the fields are changed by
+    /// a config update.
+    pub fn fields(&self) -> Option<Arc<StaticFields>> {
+        self.fields.load_full()
+    }
+
+    /// Checks whether an event can be logged.
+    /// Prefer [`Scope::check_logging()`], which also applies trace sampling.
+    ///
+    /// [`Scope::check_logging()`]: crate::scope::Scope::check_logging
     pub fn check(&self, meta: &Metadata<'_>) -> CheckResult {
+        self.check_in_trace(meta, None)
+    }
+
+    /// `trace_id` is `None` if the trace is sampled anyway.
+    pub(crate) fn check_in_trace(
+        &self,
+        meta: &Metadata<'_>,
+        trace_id: Option<TraceId>,
+    ) -> CheckResult {
+        if let Some(trace_id) = trace_id {
+            if *meta.level() >= Level::DEBUG {
+                let sampler = TraceSampler::from_bits(self.sampler.load(Ordering::Relaxed));
+                if !sampler.is_sampled(trace_id) {
+                    return CheckResult::NotInterested;
+                }
+            }
+        }
+
         let limiter = &self.limiters[log_level_to_value(*meta.level())];
         if limiter.acquire() {
             CheckResult::Passed
@@ -27,6 +92,26 @@ impl LoggingControl {
     }
 }
 
+/// Static fields of a group, see `LoggingConfig::fields`.
+#[derive(Debug, PartialEq)]
+pub struct StaticFields(Vec<(String, String)>);
+
+impl StaticFields {
+    /// Returns pairs of names and values ordered by names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl FromIterator<(String, String)> for StaticFields {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut fields = iter.into_iter().collect::<Vec<_>>();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        fields.dedup_by(|a, b| a.0 == b.0);
+        Self(fields)
+    }
+}
+
 pub enum CheckResult {
     Passed,
     NotInterested,
@@ -6,7 +6,7 @@ mod control;
 #[doc(hidden)]
 pub mod _priv {
     #[cfg(feature = "unstable")] // TODO: patch `stability`
-    pub use super::control::{CheckResult, LoggingControl};
+    pub use super::control::{CheckResult, LoggingControl, StaticFields};
     #[cfg(not(feature = "unstable"))]
-    pub(crate) use super::control::{CheckResult, LoggingControl};
+    pub(crate) use super::control::{CheckResult, LoggingControl, StaticFields};
 }
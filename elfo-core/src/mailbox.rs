@@ -6,6 +6,8 @@
 //! 2. Supports both bounded and unbounded usage.
 //! 3. The capacity is configurable on the fly.
 //! 4. Preallocates no additional memory.
+//! 5. `Terminate` can overtake envelopes stored in the mailbox.
+//! 6. The next envelope can be peeked without dequeuing it.
 //!
 //! A simplified structure can be pictured in the following way:
 //! ```text
@@ -25,20 +27,35 @@
 //!             └─────────────────────────────────────────────┘
 //! ```
 
-use std::ptr::{self, NonNull};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use arc_swap::{ArcSwap, ArcSwapOption};
 use cordyceps::{
     mpsc_queue::{Links, MpscQueue},
     Linked,
 };
+use fxhash::FxHashMap;
+use metrics::counter;
 use parking_lot::Mutex;
-use tokio::sync::{Notify, Semaphore, TryAcquireError};
+use tokio::sync::{Notify, Semaphore, SemaphorePermit, TryAcquireError};
 
-use elfo_utils::CachePadded;
+use elfo_utils::{time::Instant, unlikely, CachePadded};
 
+use self::config::{MailboxQuota, OnTerminate};
 use crate::{
+    admission::{Admission, AdmissionPolicy, MailboxStats},
     envelope::{Envelope, EnvelopeHeader},
     errors::{SendError, TrySendError},
+    message::{Message, MessageTypeId, MessageVTable},
+    messages,
     tracing::TraceId,
 };
 
@@ -49,14 +66,21 @@ pub mod config {
     //!
     //! [Config]: MailboxConfig
 
+    use std::{collections::BTreeMap, num::NonZeroU32};
+
+    use serde::{de::Error as _, Deserialize, Deserializer};
+
+    use crate::{config::Duration, message::MessageVTable};
+
     /// Mailbox configuration.
     ///
     /// # Example
     /// ```toml
     /// [some_group]
     /// system.mailbox.capacity = 1000
+    /// system.mailbox.quotas.DataUpdate = "80%"
     /// ```
-    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Deserialize)]
     #[serde(default)]
     pub struct MailboxConfig {
         /// The maximum number of messages that can be stored in the mailbox.
@@ -67,13 +91,173 @@ pub mod config {
         ///
         /// [`Context::set_mailbox_capacity()`]: crate::Context::set_mailbox_capacity
         pub capacity: usize,
+        /// The maximum number of messages handed off to another actor by
+        /// [`Context::drain_to()`] or [`Context::drain()`]. The excess is
+        /// sent as [`DeadLetter`]s to lifecycle subscribers.
+        ///
+        /// `10000` by default.
+        ///
+        /// [`Context::drain_to()`]: crate::Context::drain_to
+        /// [`Context::drain()`]: crate::Context::drain
+        /// [`DeadLetter`]: crate::messages::DeadLetter
+        pub drain_limit: usize,
+        /// Limits the number of messages of specific types in the mailbox,
+        /// so one type cannot crowd out others. Messages are specified by
+        /// `Name` (in any protocol) or `protocol/Name`.
+        ///
+        /// Over-quota messages are handled as if the mailbox is full:
+        /// `try_send()` fails with `Full` and `send()` waits.
+        ///
+        /// Empty by default.
+        #[serde(deserialize_with = "deserialize_quotas")]
+        pub quotas: BTreeMap<String, MailboxQuota>,
+        /// The name of the admission policy registered by
+        /// [`ActorGroup::admission()`], `"none"` disables admission control.
+        ///
+        /// The first registered policy is used by default.
+        ///
+        /// [`ActorGroup::admission()`]: crate::ActorGroup::admission
+        pub admission: Option<String>,
+        /// What to do with messages left in the mailbox once it's closed by
+        /// `Terminate`, see [`OnTerminate`].
+        ///
+        /// `"process"` by default.
+        pub on_terminate: OnTerminate,
+        /// The number of consecutive panics while handling the same message,
+        /// after which the message is considered poisoned.
+        ///
+        /// If set, messages left in the mailbox of the panicked actor are
+        /// handed off to the restarted one, starting with the message being
+        /// handled, unless it's poisoned. Poisoned messages are sent as
+        /// [`DeadLetter`] to subscribers of lifecycle events. Requests aren't
+        /// handled again, because their requesters are already responded with
+        /// an error.
+        ///
+        /// Messages are copied before handling, so it isn't free.
+        ///
+        /// Unset by default, left messages are dropped on panic.
+        ///
+        /// [`DeadLetter`]: crate::messages::DeadLetter
+        pub poison_threshold: Option<NonZeroU32>,
+        /// How long a key of a sticky pool stays bound to the worker after
+        /// its last message is handled, see [`ActorGroup::sticky_by()`].
+        ///
+        /// `1s` by default.
+        ///
+        /// [`ActorGroup::sticky_by()`]: crate::ActorGroup::sticky_by
+        pub sticky_idle_window: Duration,
+        /// The maximum number of keys bound to workers of a sticky pool.
+        /// Once reached, messages with new keys are handled by any worker.
+        ///
+        /// `10000` by default.
+        pub sticky_capacity: usize,
     }
 
     impl Default for MailboxConfig {
         fn default() -> Self {
-            Self { capacity: 100 }
+            Self {
+                capacity: 100,
+                drain_limit: 10_000,
+                quotas: BTreeMap::new(),
+                admission: None,
+                on_terminate: OnTerminate::default(),
+                poison_threshold: None,
+                sticky_idle_window: Duration::from_secs(1),
+                sticky_capacity: 10_000,
+            }
+        }
+    }
+
+    /// What to do with messages left in the mailbox once it's closed by
+    /// `Terminate` according to [`TerminationPolicy::closing()`].
+    ///
+    /// Actors with [`TerminationPolicy::manually()`] decide on their own,
+    /// but can receive `Terminate` before other messages with `Prioritize`.
+    ///
+    /// [`TerminationPolicy::closing()`]: crate::TerminationPolicy::closing
+    /// [`TerminationPolicy::manually()`]: crate::TerminationPolicy::manually
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum OnTerminate {
+        /// Left messages are received as usual, then the input is closed.
+        /// `Terminate` is received in order with other messages.
+        #[default]
+        Process,
+        /// Like `Process`, but `Terminate` overtakes messages stored in
+        /// the mailbox.
+        Prioritize,
+        /// Left messages are dropped, the input is closed immediately.
+        /// `Terminate` overtakes messages stored in the mailbox.
+        Stop,
+    }
+
+    /// A limit of messages of one type in the mailbox.
+    ///
+    /// Specified either as a share of the mailbox capacity (`"80%"`)
+    /// or as an absolute number of messages (`50`).
+    #[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+    #[serde(try_from = "RawQuota")]
+    pub enum MailboxQuota {
+        /// A share of the capacity, in `(0, 1]`.
+        Share(f64),
+        /// An absolute number of messages.
+        Count(usize),
+    }
+
+    impl MailboxQuota {
+        pub(crate) fn limit(&self, capacity: usize) -> usize {
+            match *self {
+                // At least one message must be allowed.
+                Self::Share(share) => ((capacity as f64 * share) as usize).max(1),
+                Self::Count(count) => count,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawQuota {
+        Count(usize),
+        Share(String),
+    }
+
+    impl TryFrom<RawQuota> for MailboxQuota {
+        type Error = String;
+
+        fn try_from(raw: RawQuota) -> Result<Self, Self::Error> {
+            let share = match raw {
+                RawQuota::Count(count) => return Ok(Self::Count(count)),
+                RawQuota::Share(share) => share,
+            };
+
+            let percents = share
+                .strip_suffix('%')
+                .and_then(|p| p.trim().parse::<f64>().ok())
+                .ok_or_else(|| format!("invalid quota `{share}`, expected `N%` or a number"))?;
+
+            if !(percents > 0. && percents <= 100.) {
+                return Err(format!("quota `{share}` must be in (0%, 100%]"));
+            }
+
+            Ok(Self::Share(percents / 100.))
         }
     }
+
+    fn deserialize_quotas<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, MailboxQuota>, D::Error> {
+        let quotas = BTreeMap::<String, MailboxQuota>::deserialize(deserializer)?;
+
+        for path in quotas.keys() {
+            if MessageVTable::lookup_by_path(path).is_empty() {
+                return Err(D::Error::custom(format!(
+                    "unknown message `{path}` in quotas"
+                )));
+            }
+        }
+
+        Ok(quotas)
+    }
 }
 
 // === Mailbox ===
@@ -117,6 +301,13 @@ pub(crate) struct Mailbox {
     /// A storage for envelopes based on an intrusive linked list.
     /// Note: `cordyceps` uses terms "head" and "tail" in the opposite way.
     queue: MpscQueue<EnvelopeHeader>,
+    /// `Terminate` envelopes, received before ones in `queue`.
+    /// Used only if `is_terminate_urgent` is set.
+    urgent: Mutex<VecDeque<Envelope>>,
+    has_urgent: AtomicBool,
+    /// The head of `queue` taken by `peek()`, received before other ones.
+    peeked: Mutex<Option<Envelope>>,
+    has_peeked: AtomicBool,
 
     /// A notifier of senders about the availability of new messages.
     // TODO: replace with a custom semaphore based on `async-event` (10-15% faster).
@@ -126,6 +317,28 @@ pub(crate) struct Mailbox {
     // TODO: replace with `diatomic-waker` (3-5% faster).
     rx_notify: CachePadded<Notify>,
 
+    /// Per-type quotas, empty in most cases.
+    /// Replaced on reconfiguration, but slots of the same type are reused.
+    quotas: ArcSwap<Quotas>,
+
+    /// An admission policy, `None` in most cases.
+    admission: ArcSwapOption<AdmissionPolicy>,
+    /// When the receiver took the last message or the mailbox became
+    /// non-empty, in nanoseconds since `created_at`.
+    /// Tracked only if the admission policy is set.
+    progressed_at: AtomicU64,
+    created_at: Instant,
+    /// Mirrors `Control::capacity` to calculate stats without locking.
+    capacity: AtomicUsize,
+
+    /// Set by `system.mailbox.on_terminate = "prioritize" | "stop"`.
+    is_terminate_urgent: AtomicBool,
+    /// Set by `system.mailbox.on_terminate = "stop"`.
+    stop_on_terminate: AtomicBool,
+    /// Set once the mailbox is closed by `Terminate` with the `Stop` policy,
+    /// the receiver drops left envelopes then.
+    is_stopped: AtomicBool,
+
     /// Use `Mutex` here for synchronization on close/configure.
     control: Mutex<Control>,
 }
@@ -135,20 +348,245 @@ struct Control {
     closed_trace_id: Option<TraceId>,
     /// A real capacity of the mailbox.
     capacity: usize,
+    /// Configured quotas, limits are recalculated on capacity changes.
+    quotas: BTreeMap<String, MailboxQuota>,
+}
+
+type Quotas = FxHashMap<MessageTypeId, Arc<Quota>>;
+
+/// Limits the number of messages of one type in the mailbox.
+struct Quota {
+    name: &'static str,
+    semaphore: Semaphore,
+    /// The current limit, changed only under the `Control` lock.
+    limit: AtomicUsize,
+}
+
+impl Quota {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            semaphore: Semaphore::new(0),
+            limit: AtomicUsize::new(0),
+        }
+    }
+
+    fn set_limit(&self, limit: usize) {
+        let limit = clamp_capacity(limit);
+        let current = self.limit.load(Ordering::Relaxed);
+
+        // Just like for the capacity, messages already stored in the queue can
+        // violate a reduced limit.
+        let real_limit = if limit < current {
+            current - self.semaphore.forget_permits(current - limit)
+        } else {
+            self.semaphore.add_permits(limit - current);
+            limit
+        };
+
+        self.limit.store(real_limit, Ordering::Relaxed);
+    }
+
+    async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        match self.semaphore.try_acquire() {
+            Ok(permit) => return Some(permit),
+            Err(TryAcquireError::Closed) => return None,
+            Err(TryAcquireError::NoPermits) => self.on_exceeded(),
+        }
+
+        self.semaphore.acquire().await.ok()
+    }
+
+    fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let result = self.semaphore.try_acquire();
+        if let Err(TryAcquireError::NoPermits) = result {
+            self.on_exceeded();
+        }
+        result
+    }
+
+    fn release(&self) {
+        // Messages sent by `unbounded_send()` above the limit or before the quota
+        // is configured have no permits, so they cannot increase the limit.
+        if self.semaphore.available_permits() < self.limit.load(Ordering::Relaxed) {
+            self.semaphore.add_permits(1);
+        }
+    }
+
+    #[cold]
+    fn on_exceeded(&self) {
+        counter!("elfo_mailbox_quota_exceeded_total", 1, "message" => self.name);
+    }
 }
 
 impl Mailbox {
     pub(crate) fn new(config: &config::MailboxConfig) -> Self {
         let capacity = clamp_capacity(config.capacity);
 
-        Self {
+        let mailbox = Self {
             queue: MpscQueue::new_with_stub(Envelope::stub()),
+            urgent: Mutex::new(VecDeque::new()),
+            has_urgent: AtomicBool::new(false),
+            peeked: Mutex::new(None),
+            has_peeked: AtomicBool::new(false),
             tx_semaphore: Semaphore::new(capacity),
             rx_notify: CachePadded::new(Notify::new()),
+            quotas: ArcSwap::default(),
+            admission: ArcSwapOption::empty(),
+            progressed_at: AtomicU64::new(0),
+            created_at: Instant::now(),
+            capacity: AtomicUsize::new(capacity),
+            is_terminate_urgent: AtomicBool::new(config.on_terminate != OnTerminate::Process),
+            stop_on_terminate: AtomicBool::new(config.on_terminate == OnTerminate::Stop),
+            is_stopped: AtomicBool::new(false),
             control: Mutex::new(Control {
                 closed_trace_id: None,
                 capacity,
+                quotas: config.quotas.clone(),
             }),
+        };
+
+        if !config.quotas.is_empty() {
+            mailbox.update_quotas(&mailbox.control.lock());
+        }
+
+        mailbox
+    }
+
+    pub(crate) fn set_quotas(&self, quotas: &BTreeMap<String, MailboxQuota>) {
+        let mut control = self.control.lock();
+
+        if &control.quotas == quotas {
+            return;
+        }
+
+        control.quotas = quotas.clone();
+        self.update_quotas(&control);
+    }
+
+    fn update_quotas(&self, control: &Control) {
+        let old = self.quotas.load();
+        let mut new = Quotas::default();
+
+        for (path, quota) in &control.quotas {
+            let limit = quota.limit(control.capacity);
+
+            for vtable in MessageVTable::lookup_by_path(path) {
+                let type_id = MessageTypeId::new(vtable);
+                let slot = old
+                    .get(&type_id)
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(Quota::new(vtable.name)));
+
+                slot.set_limit(limit);
+
+                if self.tx_semaphore.is_closed() {
+                    slot.semaphore.close();
+                }
+
+                new.insert(type_id, slot);
+            }
+        }
+
+        self.quotas.store(Arc::new(new));
+    }
+
+    #[inline]
+    fn quota(&self, envelope: &Envelope) -> Option<Arc<Quota>> {
+        let quotas = self.quotas.load();
+
+        // Fast path, quotas are rarely used.
+        if quotas.is_empty() {
+            return None;
+        }
+
+        quotas.get(&envelope.message().type_id()).cloned()
+    }
+
+    pub(crate) fn set_admission(&self, policy: Option<Arc<AdmissionPolicy>>) {
+        // Progress isn't tracked without a policy.
+        if self.admission.load().is_none() {
+            self.progressed_at
+                .store(self.nanos_since_created(), Ordering::Relaxed);
+        }
+
+        self.admission.store(policy);
+    }
+
+    /// Applies the admission policy, if any, and marks the envelope.
+    /// Returns the reason if the envelope is rejected.
+    #[inline]
+    fn admit(&self, envelope: &mut Envelope) -> Result<(), &'static str> {
+        // Fast path, admission control is rarely used.
+        match &*self.admission.load() {
+            Some(policy) => self.do_admit(policy, envelope),
+            None => Ok(()),
+        }
+    }
+
+    fn do_admit(
+        &self,
+        policy: &AdmissionPolicy,
+        envelope: &mut Envelope,
+    ) -> Result<(), &'static str> {
+        // System messages, e.g. `UpdateConfig` or `Terminate`, are always admitted.
+        if envelope.message().protocol() == messages::Ping.protocol() {
+            return Ok(());
+        }
+
+        let stats = self.stats();
+        if stats.len == 0 {
+            self.progressed_at
+                .store(self.nanos_since_created(), Ordering::Relaxed);
+        }
+
+        let admission = policy.check(envelope, &stats);
+        envelope.set_admission(admission);
+
+        match admission {
+            Admission::Reject(reason) => {
+                let name = envelope.message().name();
+                counter!("elfo_mailbox_admission_rejected_total", 1,
+                    "policy" => policy.name(), "message" => name);
+                Err(reason)
+            }
+            Admission::Admit | Admission::Degrade => Ok(()),
+        }
+    }
+
+    fn stats(&self) -> MailboxStats {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let len = capacity.saturating_sub(self.tx_semaphore.available_permits());
+
+        let oldest_age = if len > 0 {
+            let progressed_at = self.progressed_at.load(Ordering::Relaxed);
+            Duration::from_nanos(self.nanos_since_created().saturating_sub(progressed_at))
+        } else {
+            Duration::ZERO
+        };
+
+        MailboxStats {
+            len,
+            capacity,
+            oldest_age,
+        }
+    }
+
+    fn nanos_since_created(&self) -> u64 {
+        Instant::now().nanos_since(self.created_at)
+    }
+
+    #[inline]
+    fn on_dequeued(&self, envelope: &Envelope) {
+        self.tx_semaphore.add_permits(1);
+
+        if self.admission.load().is_some() {
+            self.progressed_at
+                .store(self.nanos_since_created(), Ordering::Relaxed);
+        }
+
+        if let Some(quota) = self.quota(envelope) {
+            quota.release();
         }
     }
 
@@ -159,6 +597,14 @@ impl Mailbox {
             return;
         }
 
+        self.do_set_capacity(&mut control, capacity);
+
+        if !control.quotas.is_empty() {
+            self.update_quotas(&control);
+        }
+    }
+
+    fn do_set_capacity(&self, control: &mut Control, capacity: usize) {
         if capacity < control.capacity {
             let delta = control.capacity - capacity;
             let real_delta = self.tx_semaphore.forget_permits(delta);
@@ -173,26 +619,134 @@ impl Mailbox {
             self.tx_semaphore.add_permits(real_delta);
             control.capacity += real_delta;
         }
+
+        self.capacity.store(control.capacity, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn enqueue(&self, envelope: Envelope) {
+        if unlikely(
+            self.is_terminate_urgent.load(Ordering::Relaxed)
+                && envelope.is::<messages::Terminate>(),
+        ) {
+            self.enqueue_urgent(envelope);
+        } else {
+            self.queue.enqueue(envelope);
+        }
+
+        self.rx_notify.notify_one();
+    }
+
+    #[cold]
+    fn enqueue_urgent(&self, envelope: Envelope) {
+        let mut urgent = self.urgent.lock();
+        urgent.push_back(envelope);
+        self.has_urgent.store(true, Ordering::Release);
     }
 
-    pub(crate) async fn send(&self, envelope: Envelope) -> Result<(), SendError<Envelope>> {
+    #[inline]
+    fn dequeue(&self) -> Option<Envelope> {
+        if unlikely(self.has_urgent.load(Ordering::Acquire)) {
+            if let Some(envelope) = self.dequeue_urgent() {
+                return Some(envelope);
+            }
+        }
+
+        if unlikely(self.has_peeked.load(Ordering::Acquire)) {
+            if let Some(envelope) = self.dequeue_peeked() {
+                return Some(envelope);
+            }
+        }
+
+        self.queue.dequeue()
+    }
+
+    #[cold]
+    fn dequeue_peeked(&self) -> Option<Envelope> {
+        let envelope = self.peeked.lock().take();
+        self.has_peeked.store(false, Ordering::Release);
+        envelope
+    }
+
+    #[cold]
+    fn dequeue_urgent(&self) -> Option<Envelope> {
+        let mut urgent = self.urgent.lock();
+        let envelope = urgent.pop_front();
+        if urgent.is_empty() {
+            self.has_urgent.store(false, Ordering::Release);
+        }
+        envelope
+    }
+
+    /// Calls `f` with the envelope that is dequeued next, if any.
+    /// The envelope is still counted as stored in the mailbox.
+    pub(crate) fn peek<R>(&self, f: impl FnOnce(&Envelope) -> R) -> Option<R> {
+        if unlikely(self.has_urgent.load(Ordering::Acquire)) {
+            if let Some(envelope) = self.urgent.lock().front() {
+                return Some(f(envelope));
+            }
+        }
+
+        // The queue has no way to peek, so its head is moved aside and
+        // returned by `dequeue()` before others.
+        let mut peeked = self.peeked.lock();
+        if peeked.is_none() {
+            *peeked = Some(self.queue.dequeue()?);
+            self.has_peeked.store(true, Ordering::Release);
+        }
+
+        peeked.as_ref().map(f)
+    }
+
+    pub(crate) async fn send(&self, mut envelope: Envelope) -> Result<(), SendError<Envelope>> {
+        // The rejection reason is kept in the envelope.
+        if self.admit(&mut envelope).is_err() {
+            return Err(SendError(envelope));
+        }
+
+        let quota = self.quota(&envelope);
+        let quota_permit = match &quota {
+            Some(quota) => match quota.acquire().await {
+                Some(permit) => Some(permit),
+                None => return Err(SendError(envelope)),
+            },
+            None => None,
+        };
+
         let permit = match self.tx_semaphore.acquire().await {
             Ok(permit) => permit,
             Err(_) => return Err(SendError(envelope)),
         };
 
         permit.forget();
-        self.queue.enqueue(envelope);
-        self.rx_notify.notify_one();
+        if let Some(permit) = quota_permit {
+            permit.forget();
+        }
+        self.enqueue(envelope);
         Ok(())
     }
 
-    pub(crate) fn try_send(&self, envelope: Envelope) -> Result<(), TrySendError<Envelope>> {
+    pub(crate) fn try_send(&self, mut envelope: Envelope) -> Result<(), TrySendError<Envelope>> {
+        // The rejection reason is kept in the envelope.
+        if self.admit(&mut envelope).is_err() {
+            return Err(TrySendError::Closed(envelope));
+        }
+
+        let quota = self.quota(&envelope);
+        let quota_permit = match quota.as_deref().map(Quota::try_acquire) {
+            Some(Ok(permit)) => Some(permit),
+            Some(Err(TryAcquireError::NoPermits)) => return Err(TrySendError::Full(envelope)),
+            Some(Err(TryAcquireError::Closed)) => return Err(TrySendError::Closed(envelope)),
+            None => None,
+        };
+
         match self.tx_semaphore.try_acquire() {
             Ok(permit) => {
                 permit.forget();
-                self.queue.enqueue(envelope);
-                self.rx_notify.notify_one();
+                if let Some(permit) = quota_permit {
+                    permit.forget();
+                }
+                self.enqueue(envelope);
                 Ok(())
             }
             Err(TryAcquireError::NoPermits) => Err(TrySendError::Full(envelope)),
@@ -202,8 +756,20 @@ impl Mailbox {
 
     pub(crate) fn unbounded_send(&self, envelope: Envelope) -> Result<(), SendError<Envelope>> {
         if !self.tx_semaphore.is_closed() {
-            self.queue.enqueue(envelope);
-            self.rx_notify.notify_one();
+            // Ignore the capacity, but take a permit if possible,
+            // because it's returned once the envelope is dequeued.
+            if let Ok(permit) = self.tx_semaphore.try_acquire() {
+                permit.forget();
+            }
+
+            if let Some(quota) = self.quota(&envelope) {
+                // Ignore the limit, but take a permit if possible.
+                if let Ok(permit) = quota.semaphore.try_acquire() {
+                    permit.forget();
+                }
+            }
+
+            self.enqueue(envelope);
             Ok(())
         } else {
             Err(SendError(envelope))
@@ -212,13 +778,21 @@ impl Mailbox {
 
     pub(crate) async fn recv(&self) -> RecvResult {
         loop {
+            // Let other actors go first in a seeded way.
+            #[cfg(feature = "test-util")]
+            crate::simulation::perturb().await;
+
             // TODO: it should be possible to use `dequeue_unchecked()` here.
             // Preliminarily, we should guarantee that it can be called only
             // by one consumer. However, it's not enough to create a dedicated
             // `MailboxConsumer` because users can steal `Context` to another
             // task/thread and create a race with the `drop_all()` method.
-            if let Some(envelope) = self.queue.dequeue() {
-                self.tx_semaphore.add_permits(1);
+            if unlikely(self.is_stopped.load(Ordering::Acquire)) {
+                return self.on_stopped();
+            }
+
+            if let Some(envelope) = self.dequeue() {
+                self.on_dequeued(&envelope);
                 return RecvResult::Data(envelope);
             }
 
@@ -230,10 +804,20 @@ impl Mailbox {
         }
     }
 
+    /// Waits until the mailbox is probably non-empty or closed.
+    /// Used by consumers checking it by `try_recv()` under their own lock.
+    pub(crate) async fn readable(&self) {
+        self.rx_notify.notified().await;
+    }
+
     pub(crate) fn try_recv(&self) -> Option<RecvResult> {
-        match self.queue.dequeue() {
+        if unlikely(self.is_stopped.load(Ordering::Acquire)) {
+            return Some(self.on_stopped());
+        }
+
+        match self.dequeue() {
             Some(envelope) => {
-                self.tx_semaphore.add_permits(1);
+                self.on_dequeued(&envelope);
                 Some(RecvResult::Data(envelope))
             }
             None if self.tx_semaphore.is_closed() => Some(self.on_close()),
@@ -256,19 +840,67 @@ impl Mailbox {
         control.closed_trace_id = Some(trace_id);
 
         self.tx_semaphore.close();
+        for quota in self.quotas.load().values() {
+            quota.semaphore.close();
+        }
         self.rx_notify.notify_one();
         true
     }
 
+    /// Closes the mailbox on `Terminate`, left envelopes are dropped by
+    /// the receiver if `on_terminate = "stop"` is configured.
+    #[cold]
+    pub(crate) fn terminate(&self, trace_id: TraceId) -> bool {
+        if !self.close(trace_id) {
+            return false;
+        }
+
+        // Set after closing, because `on_stopped()` requires `closed_trace_id`.
+        if self.stop_on_terminate.load(Ordering::Relaxed) {
+            self.is_stopped.store(true, Ordering::Release);
+            self.rx_notify.notify_one();
+        }
+
+        true
+    }
+
+    pub(crate) fn set_on_terminate(&self, on_terminate: OnTerminate) {
+        let is_urgent = on_terminate != OnTerminate::Process;
+        self.is_terminate_urgent.store(is_urgent, Ordering::Relaxed);
+        let is_stop = on_terminate == OnTerminate::Stop;
+        self.stop_on_terminate.store(is_stop, Ordering::Relaxed);
+    }
+
     #[cold]
     pub(crate) fn drop_all(&self) {
-        while self.queue.dequeue().is_some() {}
+        while self.dequeue().is_some() {}
+    }
+
+    /// Takes all messages stored in the mailbox in order.
+    /// Should be called only after closing the mailbox.
+    #[cold]
+    pub(crate) fn drain(&self) -> Vec<Envelope> {
+        debug_assert!(self.tx_semaphore.is_closed());
+        std::iter::from_fn(|| self.dequeue()).collect()
+    }
+
+    /// Returns the approximate number of stored messages.
+    /// Messages sent by `unbounded_send()` above the capacity aren't counted.
+    pub(crate) fn len(&self) -> usize {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        capacity.saturating_sub(self.tx_semaphore.available_permits())
+    }
+
+    #[cold]
+    fn on_stopped(&self) -> RecvResult {
+        self.drop_all();
+        self.on_close()
     }
 
     #[cold]
     fn on_close(&self) -> RecvResult {
         // Some messages may be in the queue after the channel is closed.
-        match self.queue.dequeue() {
+        match self.dequeue() {
             Some(envelope) => RecvResult::Data(envelope),
             None => {
                 let control = self.control.lock();
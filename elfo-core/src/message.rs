@@ -7,11 +7,12 @@ use metrics::Label;
 use serde::{Deserialize, Serialize};
 use smallbox::smallbox;
 
-use crate::dumping;
+use crate::{compression, dumping};
 
 pub use self::{any::*, lookup::*, protocol::*, repr::*};
 
 mod any;
+mod catalog;
 mod lookup;
 mod protocol;
 mod repr;
@@ -46,6 +47,12 @@ pub trait Message:
         self._vtable().dumping_allowed
     }
 
+    /// Returns the note of `#[message(deprecated = "..")]`, if any.
+    #[inline(always)]
+    fn deprecation(&self) -> Option<&'static str> {
+        self._vtable().deprecated
+    }
+
     // Private API.
 
     #[doc(hidden)]
@@ -95,7 +102,7 @@ pub trait Message:
     #[doc(hidden)]
     #[inline(always)]
     fn _erase(&self) -> dumping::ErasedMessage {
-        smallbox!(self.clone())
+        smallbox!(compression::Labeled(self.clone()))
     }
 
     /// # Safety
@@ -153,7 +153,7 @@ impl AnyMessage {
         (vtable.drop_data)(self.0);
     }
 
-    fn as_serialize(&self) -> &(impl Serialize + ?Sized) {
+    pub(crate) fn as_serialize(&self) -> &(impl Serialize + ?Sized) {
         let vtable = self._vtable();
 
         // SAFETY: the resulting reference is bound to the lifetime of `self`.
@@ -381,6 +381,19 @@ cfg_network!({
             protocol: &str,
             name: &str,
         ) -> Result<Option<Self>, decode::Error> {
+            Self::read_msgpack_lenient(buffer, protocol, name)
+                .map(|decoded| decoded.map(|(message, _)| message))
+        }
+
+        /// Like [`AnyMessage::read_msgpack()`], but also returns the number of
+        /// unknown fields that have been ignored while decoding.
+        #[doc(hidden)]
+        #[inline]
+        pub fn read_msgpack_lenient(
+            buffer: &[u8],
+            protocol: &str,
+            name: &str,
+        ) -> Result<Option<(Self, usize)>, decode::Error> {
             let Some(vtable) = MessageVTable::lookup(protocol, name) else {
                 return Ok(None);
             };
@@ -388,9 +401,9 @@ cfg_network!({
             let out_ptr = alloc_repr(vtable);
 
             // SAFETY: `out_ptr` belongs to the same object as the vtable.
-            unsafe { (vtable.read_msgpack)(buffer, out_ptr) }?;
+            let ignored = unsafe { (vtable.read_msgpack)(buffer, out_ptr) }?;
 
-            Ok(Some(Self(out_ptr)))
+            Ok(Some((Self(out_ptr), ignored)))
         }
 
         #[doc(hidden)]
@@ -400,6 +413,73 @@ cfg_network!({
             // SAFETY: the vtable belongs to `self`.
             unsafe { (vtable.write_msgpack)(self.0, out, limit) }
         }
+
+        /// Returns the hash of the protocol and name, see
+        /// [`AnyMessage::read_postcard()`].
+        #[doc(hidden)]
+        #[inline]
+        pub fn network_id(&self) -> u64 {
+            self._vtable().network_id
+        }
+
+        /// Returns the hash of the type's shape, which is used to detect
+        /// incompatible versions of the message on different nodes.
+        #[doc(hidden)]
+        #[inline]
+        pub fn schema_hash(&self) -> u64 {
+            self._vtable().schema_hash
+        }
+
+        /// Returns the protocol, name and schema hash of the message
+        /// registered with the provided network id.
+        #[doc(hidden)]
+        #[inline]
+        pub fn lookup_network_id(id: u64) -> Option<(&'static str, &'static str, u64)> {
+            MessageVTable::lookup_by_network_id(id)
+                .map(|vtable| (vtable.protocol, vtable.name, vtable.schema_hash))
+        }
+
+        /// Returns the protocol and name of the registered message as static
+        /// strings, e.g. to be used as labels of metrics.
+        #[doc(hidden)]
+        #[inline]
+        pub fn lookup_name(protocol: &str, name: &str) -> Option<(&'static str, &'static str)> {
+            MessageVTable::lookup(protocol, name).map(|vtable| (vtable.protocol, vtable.name))
+        }
+
+        /// Unlike msgpack, postcard isn't self-describing, so the schema hash
+        /// must be checked by the caller before decoding.
+        #[doc(hidden)]
+        #[inline]
+        pub fn read_postcard(buffer: &[u8], id: u64) -> Result<Option<Self>, postcard::Error> {
+            let Some(vtable) = MessageVTable::lookup_by_network_id(id) else {
+                return Ok(None);
+            };
+
+            let out_ptr = alloc_repr(vtable);
+
+            // SAFETY: `out_ptr` belongs to the same object as the vtable.
+            if let Err(err) = unsafe { (vtable.read_postcard)(buffer, out_ptr) } {
+                // SAFETY: `out_ptr` is allocated by `alloc_repr()`, but not
+                // initialized, so `dealloc_repr()` cannot be used.
+                unsafe { alloc::dealloc(out_ptr.as_ptr().cast(), vtable.repr_layout) };
+                return Err(err);
+            }
+
+            Ok(Some(Self(out_ptr)))
+        }
+
+        #[doc(hidden)]
+        #[inline]
+        pub fn write_postcard(
+            &self,
+            out: &mut Vec<u8>,
+            limit: usize,
+        ) -> Result<(), postcard::Error> {
+            let vtable = self._vtable();
+            // SAFETY: the vtable belongs to `self`.
+            unsafe { (vtable.write_postcard)(self.0, out, limit) }
+        }
     }
 });
 
@@ -663,4 +743,44 @@ mod tests_miri {
             assert!(format!("{:?}", err).contains("failed to write whole buffer"));
         }
     }
+
+    #[test]
+    fn postcard_roundtrip() {
+        let message = MyCoolMessage::example();
+        let any_message = AnyMessage::new(message.clone());
+
+        let mut buffer = Vec::new();
+        any_message.write_postcard(&mut buffer, 1024).unwrap();
+
+        let id = any_message.network_id();
+        let (protocol, name, schema_hash) = AnyMessage::lookup_network_id(id).unwrap();
+        assert_eq!((protocol, name), ("elfo-core", "MyCoolMessage"));
+        assert_eq!(schema_hash, any_message.schema_hash());
+
+        let deserialized_any_message = AnyMessage::read_postcard(&buffer, id).unwrap().unwrap();
+        let deserialized_message: MyCoolMessage = deserialized_any_message.downcast().unwrap();
+
+        assert_eq!(deserialized_message, message);
+    }
+
+    #[test]
+    fn postcard_nonexist() {
+        assert!(AnyMessage::lookup_network_id(42).is_none());
+        assert!(AnyMessage::read_postcard(&[], 42).unwrap().is_none());
+    }
+
+    #[test]
+    fn postcard_limited() {
+        let message = MyCoolMessage::example();
+        let any_message = AnyMessage::new(message.clone());
+
+        let mut buffer = Vec::new();
+        any_message.write_postcard(&mut buffer, 1024).unwrap();
+        let size = buffer.len();
+
+        for limit in 0..size {
+            buffer.clear();
+            assert!(any_message.write_postcard(&mut buffer, limit).is_err());
+        }
+    }
 }
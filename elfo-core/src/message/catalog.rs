@@ -0,0 +1,49 @@
+//! The catalog of registered messages, see [`GetMessageCatalog`].
+//!
+//! [`GetMessageCatalog`]: crate::messages::GetMessageCatalog
+
+use super::{MessageVTable, MESSAGE_VTABLES_LIST};
+use crate::messages::{CatalogMessage, MessageCatalog};
+
+impl MessageCatalog {
+    /// Collects messages, which protocols start with `protocol_prefix`.
+    ///
+    /// Wrappers of responses (`<Request>::Response`) are generated by
+    /// `#[message(ret = ...)]` and described by their requests, so they're
+    /// skipped.
+    pub fn collect(protocol_prefix: &str) -> Self {
+        let vtables = MESSAGE_VTABLES_LIST
+            .iter()
+            .filter(|vtable| vtable.protocol.starts_with(protocol_prefix))
+            .filter(|vtable| !is_response_wrapper(vtable));
+
+        let mut messages = vtables
+            .map(|vtable| CatalogMessage {
+                protocol: vtable.protocol.into(),
+                name: vtable.name.into(),
+                path: vtable.path.into(),
+                response: vtable.response.map(|type_name| type_name().into()),
+                aliases: vtable.aliases.iter().map(|&alias| alias.into()).collect(),
+                deprecated: vtable.deprecated.map(Into::into),
+                #[cfg(feature = "network")]
+                schema_hash: Some(vtable.schema_hash),
+                #[cfg(not(feature = "network"))]
+                schema_hash: None,
+            })
+            .collect::<Vec<_>>();
+
+        messages.sort_unstable_by(|a, b| {
+            (&a.protocol, &a.name, &a.path).cmp(&(&b.protocol, &b.name, &b.path))
+        });
+
+        Self { messages }
+    }
+}
+
+fn is_response_wrapper(vtable: &MessageVTable) -> bool {
+    let Some(request) = vtable.name.strip_suffix("::Response") else {
+        return false;
+    };
+
+    MessageVTable::lookup(vtable.protocol, request).is_some_and(|r| r.response.is_some())
+}
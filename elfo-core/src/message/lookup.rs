@@ -1,6 +1,8 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, fmt};
 
-use fxhash::{FxHashMap, FxHashSet};
+use fxhash::FxHashMap;
+use metrics::increment_counter;
+use once_cell::sync::Lazy;
 
 use super::{MessageTypeId, MessageVTable};
 
@@ -13,23 +15,90 @@ pub static MESSAGE_VTABLES_LIST: [&'static MessageVTable] = [..];
 
 static MESSAGE_VTABLES_MAP: vtables_map::VTablesMap = vtables_map::VTablesMap::new();
 
-/// Checks that all registered message have different protocol and name.
-/// Returns a list of duplicates if it's violated.
-pub(crate) fn check_uniqueness() -> Result<(), Vec<(String, String)>> {
-    if MESSAGE_VTABLES_MAP.len() == MESSAGE_VTABLES_LIST.len() {
-        return Ok(());
+/// Several message types registered with the same protocol and name.
+///
+/// It's a bug, because such messages are indistinguishable in configs and
+/// across nodes, but it can be allowed for transitional builds by
+/// `system.allow_duplicate_messages = true`.
+// Reexported in `elfo::init` with the `unstable` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MessageCollision {
+    /// The protocol of colliding messages.
+    pub protocol: &'static str,
+    /// The name of colliding messages.
+    pub name: &'static str,
+    /// Paths of colliding types, e.g. `my_crate::protocol::SomeMessage`.
+    pub paths: Vec<&'static str>,
+}
+
+impl fmt::Display for MessageCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "message `{}/{}` is defined several times: ",
+            self.protocol, self.name
+        )?;
+
+        for (i, path) in self.paths.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{path}`")?;
+        }
+
+        Ok(())
     }
+}
 
-    Err(MESSAGE_VTABLES_LIST
+/// Returns all messages registered several times with the same protocol and
+/// name. Collisions are found only once, subsequent calls are cheap.
+pub(crate) fn collisions() -> &'static [MessageCollision] {
+    static COLLISIONS: Lazy<Vec<MessageCollision>> = Lazy::new(find_collisions);
+    &COLLISIONS
+}
+
+fn find_collisions() -> Vec<MessageCollision> {
+    let signatures = MESSAGE_VTABLES_LIST
         .iter()
-        .filter(|vtable| {
-            let stored = MessageVTable::lookup(vtable.protocol, vtable.name).unwrap();
-            MessageTypeId::new(stored) != MessageTypeId::new(vtable)
-        })
-        .map(|vtable| (vtable.protocol.to_string(), vtable.name.to_string()))
-        .collect::<FxHashSet<_>>()
+        .map(|vtable| 1 + vtable.aliases.len())
+        .sum::<usize>();
+
+    if MESSAGE_VTABLES_MAP.len() == signatures {
+        return Vec::new();
+    }
+
+    // Aliases collide with actual names as well.
+    let mut groups = FxHashMap::<_, Vec<&'static MessageVTable>>::default();
+    for &vtable in MESSAGE_VTABLES_LIST.iter() {
+        for signature in vtable.aliases().chain([[vtable.protocol, vtable.name]]) {
+            let same = groups.entry(signature).or_default();
+            if !same
+                .iter()
+                .any(|&v| MessageTypeId::new(v) == MessageTypeId::new(vtable))
+            {
+                same.push(vtable);
+            }
+        }
+    }
+
+    let mut collisions = groups
         .into_iter()
-        .collect::<Vec<_>>())
+        .filter(|(_, same)| same.len() > 1)
+        .map(|([protocol, name], same)| {
+            let mut paths = same.iter().map(|vtable| vtable.path).collect::<Vec<_>>();
+            paths.sort_unstable();
+
+            MessageCollision {
+                protocol,
+                name,
+                paths,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    collisions.sort_unstable_by_key(|c| (c.protocol, c.name));
+    collisions
 }
 
 #[derive(PartialEq, Eq, Hash)]
@@ -42,10 +111,60 @@ impl<'a> Borrow<[&'a str; 2]> for Signature {
 }
 
 impl MessageVTable {
-    /// Finds a vtable by protocol and name.
+    /// Finds a vtable by protocol and name, also by aliases.
     /// Used for deserialization of `AnyMessage` and in networking.
     pub(crate) fn lookup(protocol: &str, name: &str) -> Option<&'static Self> {
-        MESSAGE_VTABLES_MAP.get(protocol, name)
+        let vtable = MESSAGE_VTABLES_MAP.get(protocol, name)?;
+        if vtable.name != name || vtable.protocol != protocol {
+            vtable.on_alias_used(|[p, n]| p == protocol && n == name);
+        }
+        Some(vtable)
+    }
+
+    /// Finds a vtable by the hash of protocol and name, also by aliases.
+    /// Used by the postcard codec in networking.
+    #[cfg(feature = "network")]
+    pub(crate) fn lookup_by_network_id(id: u64) -> Option<&'static Self> {
+        let vtable = MESSAGE_VTABLES_MAP.get_by_network_id(id)?;
+        if vtable.network_id != id {
+            vtable.on_alias_used(|[p, n]| super::repr::network_id(p, n) == id);
+        }
+        Some(vtable)
+    }
+
+    /// Returns `[protocol, name]` of aliases, see `#[message(alias = ..)]`.
+    pub(super) fn aliases(&self) -> impl Iterator<Item = [&'static str; 2]> + '_ {
+        self.aliases.iter().map(|alias| self.parse_alias(alias))
+    }
+
+    fn parse_alias(&self, alias: &'static str) -> [&'static str; 2] {
+        match alias.split_once('/') {
+            Some((protocol, name)) => [protocol, name],
+            None => [self.protocol, alias],
+        }
+    }
+
+    #[cold]
+    fn on_alias_used(&self, is_used: impl Fn([&str; 2]) -> bool) {
+        let Some(&alias) = self.aliases.iter().find(|a| is_used(self.parse_alias(a))) else {
+            return;
+        };
+
+        increment_counter!("elfo_message_aliases_used_total",
+            "protocol" => self.protocol, "message" => self.name, "alias" => alias);
+    }
+
+    /// Finds vtables by `protocol/name` or only by name (in any protocol).
+    /// Used to resolve messages specified in configs.
+    pub(crate) fn lookup_by_path(path: &str) -> Vec<&'static Self> {
+        match path.split_once('/') {
+            Some((protocol, name)) => Self::lookup(protocol, name).into_iter().collect(),
+            None => MESSAGE_VTABLES_LIST
+                .iter()
+                .filter(|vtable| vtable.name == path)
+                .copied()
+                .collect(),
+        }
     }
 
     #[cfg(miri)]
@@ -56,38 +175,65 @@ impl MessageVTable {
 
 #[cfg(not(miri))]
 mod vtables_map {
-    use once_cell::sync::Lazy;
-
+    #[cfg(feature = "network")]
+    use super::super::repr::network_id;
     use super::*;
 
-    pub(super) struct VTablesMap(Lazy<FxHashMap<Signature, &'static MessageVTable>>);
+    pub(super) struct VTablesMap {
+        by_signature: Lazy<FxHashMap<Signature, &'static MessageVTable>>,
+        #[cfg(feature = "network")]
+        by_network_id: Lazy<FxHashMap<u64, &'static MessageVTable>>,
+    }
 
     impl VTablesMap {
         pub(super) const fn new() -> Self {
-            let inner: Lazy<_> = Lazy::new(|| {
-                MESSAGE_VTABLES_LIST
-                    .iter()
-                    .map(|vtable| (Signature([vtable.protocol, vtable.name]), *vtable))
-                    .collect()
-            });
-
-            Self(inner)
+            Self {
+                by_signature: Lazy::new(|| {
+                    signatures()
+                        .map(|(signature, vtable)| (Signature(signature), vtable))
+                        .collect()
+                }),
+                #[cfg(feature = "network")]
+                by_network_id: Lazy::new(|| {
+                    signatures()
+                        .map(|([protocol, name], vtable)| (network_id(protocol, name), vtable))
+                        .collect()
+                }),
+            }
         }
 
         pub(super) fn get(&self, protocol: &str, name: &str) -> Option<&'static MessageVTable> {
-            self.0.get(&[protocol, name]).copied()
+            self.by_signature.get(&[protocol, name]).copied()
+        }
+
+        #[cfg(feature = "network")]
+        pub(super) fn get_by_network_id(&self, id: u64) -> Option<&'static MessageVTable> {
+            self.by_network_id.get(&id).copied()
         }
 
         pub(super) fn len(&self) -> usize {
-            self.0.len()
+            self.by_signature.len()
         }
     }
+
+    /// Aliases go first, so actual names win on collisions.
+    fn signatures() -> impl Iterator<Item = ([&'static str; 2], &'static MessageVTable)> {
+        let aliases = MESSAGE_VTABLES_LIST
+            .iter()
+            .flat_map(|&vtable| vtable.aliases().map(move |alias| (alias, vtable)));
+        let names = MESSAGE_VTABLES_LIST
+            .iter()
+            .map(|&vtable| ([vtable.protocol, vtable.name], vtable));
+        aliases.chain(names)
+    }
 }
 
 #[cfg(miri)]
 mod vtables_map {
     use std::sync::Mutex;
 
+    #[cfg(feature = "network")]
+    use super::super::repr::network_id;
     use super::*;
 
     // parking-lot doesn't compile with `-Zmiri-strict-provenance`,
@@ -104,14 +250,34 @@ mod vtables_map {
             guard.as_ref()?.get(&[protocol, name]).copied()
         }
 
+        #[cfg(feature = "network")]
+        pub(super) fn get_by_network_id(&self, id: u64) -> Option<&'static MessageVTable> {
+            let guard = self.0.lock().unwrap();
+            let map = guard.as_ref()?;
+            let ids = |v: &&MessageVTable| {
+                let aliases = v
+                    .aliases()
+                    .map(|[protocol, name]| network_id(protocol, name));
+                [v.network_id]
+                    .into_iter()
+                    .chain(aliases)
+                    .collect::<Vec<_>>()
+            };
+            map.values().find(|v| ids(v).contains(&id)).copied()
+        }
+
         pub(super) fn len(&self) -> usize {
             self.0.lock().unwrap().as_ref().map_or(0, |m| m.len())
         }
 
         pub(super) fn register(&self, vtable: &'static MessageVTable) {
-            let key = Signature([vtable.protocol, vtable.name]);
             let mut map = self.0.lock().unwrap();
-            map.get_or_insert_with(<_>::default).insert(key, vtable);
+            let map = map.get_or_insert_with(<_>::default);
+
+            for alias in vtable.aliases() {
+                map.entry(Signature(alias)).or_insert(vtable);
+            }
+            map.insert(Signature([vtable.protocol, vtable.name]), vtable);
         }
     }
 }
@@ -7,7 +7,7 @@ use metrics::Label;
 use smallbox::smallbox;
 
 use super::Message;
-use crate::dumping;
+use crate::{compression, dumping};
 
 #[cfg(feature = "network")]
 use rmp_serde::{decode, encode};
@@ -50,6 +50,15 @@ impl PartialEq for MessageTypeId {
     }
 }
 
+impl Eq for MessageTypeId {}
+
+impl std::hash::Hash for MessageTypeId {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state);
+    }
+}
+
 // === MessageRepr ===
 
 /// A message representation as a cpp-style object.
@@ -111,13 +120,21 @@ assert_not_impl_any!(MessageRepr: Clone);
 #[non_exhaustive] // must be created only via `MessageVTable::new()`
 pub struct MessageVTable {
     pub(super) repr_layout: alloc::Layout, // of `MessageRepr<M>`
-    pub(super) name: &'static str,
+    pub(crate) name: &'static str,
     pub(super) protocol: &'static str,
+    pub(super) path: &'static str,    // of the type, for diagnostics
     pub(super) labels: [Label; 2],    // protocol + name for `metrics`
     pub(super) dumping_allowed: bool, // TODO: introduce `DumpingMode`.
+    pub(super) response: Option<fn() -> &'static str>, // type name, for requests
+    pub(super) aliases: &'static [&'static str], // old names, `Name` or `protocol/Name`
+    pub(super) deprecated: Option<&'static str>, // the note of `#[message(deprecated = ..)]`
+    #[cfg(feature = "network")]
+    pub(super) network_id: u64, // hash of protocol + name
+    #[cfg(feature = "network")]
+    pub(super) schema_hash: u64, // hash of the type's shape, see the macro
     #[cfg(feature = "network")]
     pub(super) read_msgpack:
-        unsafe fn(buffer: &[u8], out_ptr: NonNull<MessageRepr>) -> Result<(), decode::Error>,
+        unsafe fn(buffer: &[u8], out_ptr: NonNull<MessageRepr>) -> Result<usize, decode::Error>,
     #[cfg(feature = "network")]
     #[allow(clippy::type_complexity)]
     pub(super) write_msgpack: unsafe fn(
@@ -125,6 +142,16 @@ pub struct MessageVTable {
         out: &mut Vec<u8>,
         limit: usize,
     ) -> Result<(), encode::Error>,
+    #[cfg(feature = "network")]
+    pub(super) read_postcard:
+        unsafe fn(buffer: &[u8], out_ptr: NonNull<MessageRepr>) -> Result<(), postcard::Error>,
+    #[cfg(feature = "network")]
+    #[allow(clippy::type_complexity)]
+    pub(super) write_postcard: unsafe fn(
+        ptr: NonNull<MessageRepr>,
+        out: &mut Vec<u8>,
+        limit: usize,
+    ) -> Result<(), postcard::Error>,
     pub(super) debug:
         unsafe fn(ptr: NonNull<MessageRepr>, f: &mut fmt::Formatter<'_>) -> fmt::Result,
     pub(super) clone: unsafe fn(ptr: NonNull<MessageRepr>, out_ptr: NonNull<MessageRepr>),
@@ -144,20 +171,37 @@ impl MessageVTable {
     /// This is the only way to create a vtable.
     // Reexported in `elfo::_priv`.
     #[doc(hidden)]
+    #[allow(clippy::too_many_arguments)] // called only by the macro
     pub const fn new<M: Message>(
         name: &'static str,
         protocol: &'static str,
+        path: &'static str,
         dumping_allowed: bool,
+        schema_hash: u64,
+        response: Option<fn() -> &'static str>,
+        aliases: &'static [&'static str],
+        deprecated: Option<&'static str>,
     ) -> Self {
+        #[cfg(not(feature = "network"))]
+        let _ = schema_hash;
+
         Self {
             repr_layout: alloc::Layout::new::<MessageRepr<M>>(),
             name,
             protocol,
+            path,
             labels: [
                 Label::from_static_parts("message", name),
                 Label::from_static_parts("protocol", protocol),
             ],
             dumping_allowed,
+            response,
+            aliases,
+            deprecated,
+            #[cfg(feature = "network")]
+            network_id: network_id(protocol, name),
+            #[cfg(feature = "network")]
+            schema_hash,
             debug: vtablefns::debug::<M>,
             clone: vtablefns::clone::<M>,
             erase: vtablefns::erase::<M>,
@@ -168,10 +212,33 @@ impl MessageVTable {
             read_msgpack: vtablefns::read_msgpack::<M>,
             #[cfg(feature = "network")]
             write_msgpack: vtablefns::write_msgpack::<M>,
+            #[cfg(feature = "network")]
+            read_postcard: vtablefns::read_postcard::<M>,
+            #[cfg(feature = "network")]
+            write_postcard: vtablefns::write_postcard::<M>,
         }
     }
 }
 
+/// Hashes `protocol/name` by FNV-1a, which is stable across builds, so the
+/// result can be sent over network instead of the protocol and name.
+#[cfg(feature = "network")]
+pub(super) const fn network_id(protocol: &str, name: &str) -> u64 {
+    const fn feed(mut hash: u64, bytes: &[u8]) -> u64 {
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x100_0000_01b3);
+            i += 1;
+        }
+        hash
+    }
+
+    let hash = feed(0xcbf2_9ce4_8422_2325, protocol.as_bytes());
+    let hash = feed(hash, b"/");
+    feed(hash, name.as_bytes())
+}
+
 /// Generic vtable's functions for monomorphization in [`MessageVTable::new()`].
 ///
 /// All functions are `unsafe` because they work with raw pointers.
@@ -214,7 +281,7 @@ mod vtablefns {
 
     pub(super) unsafe fn erase<M: Message>(ptr: NonNull<MessageRepr>) -> dumping::ErasedMessage {
         let data = ptr.cast::<MessageRepr<M>>().as_ref().data.clone();
-        smallbox!(data)
+        smallbox!(compression::Labeled(data))
     }
 
     /// # Safety
@@ -241,16 +308,27 @@ mod vtablefns {
     }
 
     cfg_network!({
+        /// Returns the number of ignored (unknown) fields.
         pub(super) unsafe fn read_msgpack<M: Message>(
             buffer: &[u8],
             out_ptr: NonNull<MessageRepr>,
-        ) -> Result<(), decode::Error> {
-            let data = decode::from_slice(buffer)?;
+        ) -> Result<usize, decode::Error> {
+            let mut ignored = 0;
+
+            // Tracking ignored fields is expensive, so it's done only if the
+            // message has more fields than the receiver knows about.
+            let data = if elfo_utils::unlikely(has_unknown_fields::<M>(buffer)) {
+                let mut deserializer = decode::Deserializer::from_read_ref(buffer);
+                serde_ignored::deserialize(&mut deserializer, |_| ignored += 1)?
+            } else {
+                decode::from_slice(buffer)?
+            };
+
             ptr::write(
                 out_ptr.cast::<MessageRepr<M>>().as_ptr(),
                 MessageRepr::new(data),
             );
-            Ok(())
+            Ok(ignored)
         }
 
         pub(super) unsafe fn write_msgpack<M: Message>(
@@ -260,11 +338,110 @@ mod vtablefns {
         ) -> Result<(), encode::Error> {
             let data = &ptr.cast::<MessageRepr<M>>().as_ref().data;
             let mut out = LimitedWrite(out, limit);
-            encode::write_named(&mut out, data)
+            compression::with_message_labels(data.labels(), || encode::write_named(&mut out, data))
+        }
+
+        pub(super) unsafe fn read_postcard<M: Message>(
+            buffer: &[u8],
+            out_ptr: NonNull<MessageRepr>,
+        ) -> Result<(), postcard::Error> {
+            let data = postcard::from_bytes(buffer)?;
+            ptr::write(
+                out_ptr.cast::<MessageRepr<M>>().as_ptr(),
+                MessageRepr::new(data),
+            );
+            Ok(())
+        }
+
+        pub(super) unsafe fn write_postcard<M: Message>(
+            ptr: NonNull<MessageRepr>,
+            out: &mut Vec<u8>,
+            limit: usize,
+        ) -> Result<(), postcard::Error> {
+            let data = &ptr.cast::<MessageRepr<M>>().as_ref().data;
+            let out = LimitedWrite(out, limit);
+            compression::with_message_labels(data.labels(), || postcard::to_io(data, out))
+                .map(|_| ())
         }
     });
 }
 
+// === Unknown fields detection ===
+
+cfg_network!({
+    use serde::de::{self, Deserializer, Visitor};
+
+    /// Checks whether the encoded message is a struct with more fields than
+    /// `M` declares. Only top-level fields are taken into account, it's enough
+    /// to detect messages sent by newer nodes without slowing down the common
+    /// path.
+    fn has_unknown_fields<M: Message>(buffer: &[u8]) -> bool {
+        let Some(received) = msgpack_map_len(buffer) else {
+            return false;
+        };
+
+        match M::deserialize(FieldsProbe) {
+            Err(FieldsProbeError::Struct(known)) => received > known,
+            _ => false,
+        }
+    }
+
+    fn msgpack_map_len(buffer: &[u8]) -> Option<usize> {
+        match *buffer {
+            [marker @ 0x80..=0x8f, ..] => Some(usize::from(marker & 0x0f)),
+            [0xde, a, b, ..] => Some(usize::from(u16::from_be_bytes([a, b]))),
+            [0xdf, a, b, c, d, ..] => usize::try_from(u32::from_be_bytes([a, b, c, d])).ok(),
+            _ => None,
+        }
+    }
+
+    /// A deserializer that only extracts the number of fields of a struct.
+    struct FieldsProbe;
+
+    #[derive(Debug)]
+    enum FieldsProbeError {
+        Struct(usize),
+        Other,
+    }
+
+    impl fmt::Display for FieldsProbeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("fields probe")
+        }
+    }
+
+    impl std::error::Error for FieldsProbeError {}
+
+    impl de::Error for FieldsProbeError {
+        fn custom<T: fmt::Display>(_msg: T) -> Self {
+            Self::Other
+        }
+    }
+
+    impl<'de> Deserializer<'de> for FieldsProbe {
+        type Error = FieldsProbeError;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(FieldsProbeError::Other)
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            Err(FieldsProbeError::Struct(fields.len()))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+});
+
 // === LimitedWrite ===
 
 cfg_network!({
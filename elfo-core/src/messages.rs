@@ -1,8 +1,15 @@
-use std::{fmt::Display, sync::Arc};
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use derive_more::Constructor;
 
-use crate::{actor::ActorMeta, actor_status::ActorStatus, config::AnyConfig, message};
+use crate::{
+    actor::ActorMeta, actor_status::ActorStatus, config::AnyConfig, message, message::AnyMessage,
+    topology::TopologyGraph, tracing::TraceId,
+};
 
 /// A helper type for using in generic code (e.g. as an associated type) to
 /// indicate a message that cannot be constructed.
@@ -27,11 +34,31 @@ pub struct ValidateConfig {
     pub config: AnyConfig,
 }
 
+/// Applies the config to the group.
+///
+/// The group skips the config if it's equal to the applied one, so neither
+/// decoding nor [`ConfigUpdated`] happens. Use [`UpdateConfig::forcing()`]
+/// to change this behavior.
 #[message(ret = Result<(), ConfigRejected>)]
-#[derive(Constructor)]
 #[non_exhaustive]
 pub struct UpdateConfig {
     pub config: AnyConfig,
+    pub force: bool,
+}
+
+impl UpdateConfig {
+    pub fn new(config: AnyConfig) -> Self {
+        Self {
+            config,
+            force: false,
+        }
+    }
+
+    /// The config will be applied even if it's equal to the applied one.
+    pub fn forcing(mut self) -> Self {
+        self.force = true;
+        self
+    }
 }
 
 #[message]
@@ -76,6 +103,77 @@ pub struct ConfigUpdated {
     // TODO: add `old_config`.
 }
 
+/// Returns the config currently applied to the group.
+/// Handled by `elfo-configurer`, which forwards it to the supervisor of the
+/// group. Supervisors also handle it directly if `group` is their name.
+///
+/// Unknown groups and groups without a config ignore the request, so
+/// [`RequestError::Ignored`] is returned.
+///
+/// [`RequestError::Ignored`]: crate::errors::RequestError::Ignored
+#[message(ret = AppliedConfig)]
+#[non_exhaustive]
+pub struct GetConfig {
+    pub group: String,
+    pub with_provenance: bool,
+}
+
+impl GetConfig {
+    pub fn new(group: String) -> Self {
+        Self {
+            group,
+            with_provenance: false,
+        }
+    }
+
+    /// Requests [`AppliedConfig::provenance`], which is filled only by
+    /// `elfo-configurer`, because supervisors don't know what sections
+    /// the config is merged from.
+    pub fn with_provenance(mut self) -> Self {
+        self.with_provenance = true;
+        self
+    }
+}
+
+/// The response to [`GetConfig`].
+#[message(part)]
+#[non_exhaustive]
+pub struct AppliedConfig {
+    /// The config as it was received by the group, including `system.*`.
+    /// Values of `Secret` fields are replaced with `"<secret>"`.
+    pub config: AnyConfig,
+    /// The number of configs applied since the group was mounted, from 1.
+    pub generation: u64,
+    pub applied_at: SystemTime,
+    /// Human-readable descriptions of overrides made at runtime, e.g. by
+    /// [`SetCircuit`]. Log levels overridden in `elfo-logger` aren't included.
+    pub overrides: Vec<String>,
+    /// Where leaf values of the config come from, ordered by paths.
+    /// Empty unless requested by [`GetConfig::with_provenance()`].
+    pub provenance: Vec<ConfigProvenance>,
+}
+
+/// The source of a leaf value of the config, see [`AppliedConfig`].
+#[message(part)]
+#[derive(Constructor, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigProvenance {
+    /// The dot-separated path, e.g. `telemetry.interval`.
+    /// Arrays are leaves, because they aren't merged.
+    pub path: String,
+    pub origin: ConfigOrigin,
+}
+
+/// See [`ConfigProvenance`].
+#[message(part)]
+#[derive(Copy, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    /// The `[common]` section shared by all groups.
+    Common,
+    /// The group's own section.
+    Group,
+}
+
 #[message]
 #[derive(Default)]
 #[non_exhaustive]
@@ -112,6 +210,11 @@ impl SubscribeToActorStatuses {
 pub struct ActorStatusReport {
     pub meta: Arc<ActorMeta>,
     pub status: ActorStatus,
+    /// The name of the runtime chosen by [`ActorGroup::placement()`].
+    ///
+    /// [`ActorGroup::placement()`]: crate::ActorGroup::placement
+    #[serde(default)]
+    pub runtime: Option<String>,
 }
 
 impl ActorStatusReport {
@@ -120,6 +223,284 @@ impl ActorStatusReport {
         Self {
             meta: meta.into(),
             status,
+            runtime: None,
+        }
+    }
+}
+
+// === Lifecycle ===
+
+/// Subscribes the sender to lifecycle events of actors in the target group:
+/// [`ActorSpawned`], [`ActorTerminated`], [`ActorRestarted`], [`DeadLetter`],
+/// [`GroupMounted`], [`GroupTerminated`] and [`StandbySwitched`].
+///
+/// Events related to the same actor are delivered in the order they happened.
+/// A subscriber is unsubscribed if its mailbox is full or closed.
+// TODO: should it be a request?
+#[message]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct SubscribeToLifecycleEvents {}
+
+/// An actor has been spawned, either for the first time or after restart.
+#[message]
+#[non_exhaustive]
+pub struct ActorSpawned {
+    pub meta: Arc<ActorMeta>,
+    pub timestamp: SystemTime,
+}
+
+/// An actor has finished its execution. `reason` is either `Terminated` or
+/// `Failed` (with details) status.
+#[message]
+#[non_exhaustive]
+pub struct ActorTerminated {
+    pub meta: Arc<ActorMeta>,
+    pub reason: ActorStatus,
+    pub timestamp: SystemTime,
+}
+
+/// An actor is going to be restarted after `backoff`.
+/// `attempt` is the number of restarts since the backoff was last reset.
+#[message]
+#[non_exhaustive]
+pub struct ActorRestarted {
+    pub meta: Arc<ActorMeta>,
+    pub attempt: u64,
+    pub backoff: Duration,
+    pub timestamp: SystemTime,
+}
+
+/// A message has been removed from the mailbox without being handled.
+#[message]
+#[non_exhaustive]
+pub struct DeadLetter {
+    pub meta: Arc<ActorMeta>,
+    pub trace_id: TraceId,
+    pub message: AnyMessage,
+    pub reason: DeadLetterReason,
+    pub timestamp: SystemTime,
+}
+
+/// Why the message has become a [`DeadLetter`].
+#[message(part)]
+#[non_exhaustive]
+pub enum DeadLetterReason {
+    /// The message has crashed the actor `system.mailbox.poison_threshold`
+    /// times in a row. Contains the last panic message.
+    Poisoned { panic: String },
+    /// The message has been left in the mailbox of a draining actor after
+    /// `system.mailbox.drain_limit` messages have been handed off.
+    DrainLimitExceeded,
+}
+
+/// A group has received its first config and started spawning actors.
+#[message]
+#[non_exhaustive]
+pub struct GroupMounted {
+    pub group: String,
+    pub timestamp: SystemTime,
+}
+
+/// A group has stopped spawning actors and all its actors are terminated.
+#[message]
+#[non_exhaustive]
+pub struct GroupTerminated {
+    pub group: String,
+    pub timestamp: SystemTime,
+}
+
+// === Dumping ===
+
+/// Returns messages recently handled by the actor with the specified key,
+/// see `system.dumping.recent`. Handled by the supervisor of the group and
+/// forwarded by `elfo-configurer`, so a struggling actor can be inspected
+/// before it crashes.
+///
+/// Unknown groups and actors ignore the request, so
+/// [`RequestError::Ignored`] is returned.
+///
+/// [`RequestError::Ignored`]: crate::errors::RequestError::Ignored
+#[message(ret = Vec<RecentDump>)]
+#[derive(Constructor)]
+#[non_exhaustive]
+pub struct GetRecentDumps {
+    pub group: String,
+    /// The key of the actor, as it's displayed in logs and dumps.
+    pub key: String,
+}
+
+/// A message handled by the actor, see [`GetRecentDumps`] and
+/// [`Panic::recent_dumps`].
+///
+/// [`Panic::recent_dumps`]: crate::panics::Panic::recent_dumps
+#[message(part)]
+#[derive(PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecentDump {
+    pub timestamp: SystemTime,
+    pub trace_id: TraceId,
+    pub protocol: String,
+    pub name: String,
+    /// The message in JSON, truncated to `system.dumping.recent.max_payload`.
+    /// `None` if payloads aren't kept.
+    pub payload: Option<String>,
+}
+
+// === Topology ===
+
+/// Returns the graph of the topology, see [`Topology::graph()`].
+/// Handled by `elfo-configurer`, so it can be served by an admin endpoint.
+///
+/// [`Topology::graph()`]: crate::Topology::graph
+#[message(ret = TopologyGraph)]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct GetTopologyGraph;
+
+/// Returns messages registered in the binary, see [`MessageCatalog`].
+/// Handled by `elfo-configurer`, so generic tools (e.g. CLI) can discover
+/// requests supported by the node.
+#[message(ret = MessageCatalog)]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct GetMessageCatalog {
+    /// Only messages, which protocols start with the prefix, are returned.
+    pub protocol_prefix: String,
+}
+
+impl GetMessageCatalog {
+    /// Returns only messages, which protocols start with the prefix.
+    pub fn with_protocol_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            protocol_prefix: prefix.into(),
         }
     }
 }
+
+/// Messages registered in the binary, sorted by protocol and name.
+#[message(part)]
+#[non_exhaustive]
+pub struct MessageCatalog {
+    pub messages: Vec<CatalogMessage>,
+}
+
+/// A message type in [`MessageCatalog`].
+#[message(part)]
+#[non_exhaustive]
+pub struct CatalogMessage {
+    pub protocol: String,
+    pub name: String,
+    /// The path of the type, e.g. `my_crate::protocol::SomeMessage`.
+    pub path: String,
+    /// The type name of the response, `None` for regular messages.
+    pub response: Option<String>,
+    /// Old names, which are still decoded into this message, as written in
+    /// `#[message(alias = ..)]`, i.e. `Name` or `protocol/Name`.
+    pub aliases: Vec<String>,
+    /// The note of deprecated messages, as written in
+    /// `#[message(deprecated = ..)]`, `None` for others.
+    pub deprecated: Option<String>,
+    /// The hash of the type's shape, which is used to detect incompatible
+    /// versions of the message on different nodes.
+    /// `None` if the `network` feature is disabled.
+    pub schema_hash: Option<u64>,
+}
+
+impl CatalogMessage {
+    /// Returns `true` if the message is a request.
+    pub fn is_request(&self) -> bool {
+        self.response.is_some()
+    }
+
+    /// Returns `true` if the message is deprecated.
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated.is_some()
+    }
+}
+
+// === Circuit breaking ===
+
+/// Forces the state of a circuit breaker, see [`CircuitBreakerConfig`].
+/// Handled by the supervisor of the `edge.source` group, ignored by others.
+///
+/// `None` returns the breaker to automatic mode, starting from the closed
+/// state. Can be used for edges without configured thresholds, e.g. to cut
+/// off a destination during an incident.
+///
+/// [`CircuitBreakerConfig`]: crate::config::system::circuit_breaker::CircuitBreakerConfig
+#[message]
+#[derive(Constructor)]
+#[non_exhaustive]
+pub struct SetCircuit {
+    pub edge: CircuitEdge,
+    pub state: Option<CircuitState>,
+}
+
+/// An edge between groups protected by a circuit breaker.
+#[message(part)]
+#[derive(Constructor, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct CircuitEdge {
+    /// The group sending requests.
+    pub source: String,
+    /// The group receiving requests.
+    pub destination: String,
+}
+
+/// The state of a circuit breaker.
+#[message(part)]
+#[derive(Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are passed.
+    Closed,
+    /// Requests are rejected with [`ErrorKind::CircuitOpen`].
+    ///
+    /// [`ErrorKind::CircuitOpen`]: crate::errors::ErrorKind::CircuitOpen
+    Open,
+}
+
+// === Standby ===
+
+/// Forces the active group of a standby relationship, see
+/// [`Topology::standby()`]. Handled by the supervisor of the `primary` group,
+/// the standby forwards it to the primary, others ignore it.
+///
+/// `None` returns the relationship to automatic mode, starting from the
+/// primary being active.
+///
+/// [`Topology::standby()`]: crate::Topology::standby
+#[message]
+#[derive(Constructor)]
+#[non_exhaustive]
+pub struct SetStandby {
+    /// The name of the primary group.
+    pub primary: String,
+    pub active: Option<StandbyRole>,
+}
+
+/// A group of a standby relationship.
+#[message(part)]
+#[derive(Copy, PartialEq, Eq)]
+pub enum StandbyRole {
+    /// The primary group receives traffic.
+    Primary,
+    /// The standby group receives traffic, the primary doesn't.
+    Standby,
+}
+
+/// Traffic routed to the primary group has been switched, see
+/// [`Topology::standby()`].
+///
+/// [`Topology::standby()`]: crate::Topology::standby
+#[message]
+#[non_exhaustive]
+pub struct StandbySwitched {
+    pub primary: String,
+    pub standby: String,
+    /// The group receiving traffic now.
+    pub active: StandbyRole,
+    /// Switched by [`SetStandby`], not by the health policy.
+    pub forced: bool,
+    pub timestamp: SystemTime,
+}
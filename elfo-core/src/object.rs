@@ -16,8 +16,9 @@ use crate::remote::{self, RemoteHandle};
 use crate::{
     actor::Actor,
     addr::Addr,
+    admission::Admission,
     envelope::Envelope,
-    errors::{RequestError, SendError, TrySendError},
+    errors::{ErrorContext, ErrorKind, SendError, TrySendError},
     request_table::ResponseToken,
 };
 
@@ -34,6 +35,7 @@ pub(crate) type BorrowedObject<'g> = BorrowedEntry<'g, Object>;
 pub type OwnedObject = OwnedEntry<Object>;
 
 #[derive(From)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum ObjectKind {
     Actor(Actor),
     Group(Box<dyn GroupHandle>),
@@ -68,6 +70,7 @@ impl Object {
         match &this.kind {
             ObjectKind::Actor(handle) => match handle.try_send(envelope) {
                 Ok(()) => SendFut::Ready(Ok(())),
+                // The rejection reason is kept in the envelope.
                 Err(TrySendError::Closed(envelope)) => SendFut::Ready(Err(SendError(envelope))),
                 Err(TrySendError::Full(envelope)) => {
                     let Some(this) = this.to_owned() else {
@@ -149,7 +152,7 @@ impl Object {
     }
 
     #[stability::unstable]
-    pub fn respond(&self, token: ResponseToken, response: Result<Envelope, RequestError>) {
+    pub fn respond(&self, token: ResponseToken, response: Result<Envelope, ErrorKind>) {
         match &self.kind {
             ObjectKind::Actor(handle) => handle.request_table().resolve(token, response),
             ObjectKind::Group(_handle) => unreachable!(),
@@ -167,6 +170,50 @@ impl Object {
         handle.handle(envelope, visitor);
     }
 
+    /// Returns the name of the local group the object belongs to.
+    pub(crate) fn group_name(&self) -> Option<&str> {
+        match &self.kind {
+            ObjectKind::Actor(handle) => Some(&handle.meta().group),
+            ObjectKind::Group(handle) => Some(handle.name()),
+            #[cfg(feature = "network")]
+            ObjectKind::Remote(_) => None,
+        }
+    }
+
+    /// Fills the destination of the failed delivery.
+    pub(crate) fn describe_destination(&self, context: &mut ErrorContext) {
+        match &self.kind {
+            ObjectKind::Actor(handle) => {
+                let meta = handle.meta();
+                context.group = Some(meta.group.clone());
+                context.key = Some(meta.key.clone());
+            }
+            ObjectKind::Group(handle) => context.group = Some(handle.name().into()),
+            #[cfg(feature = "network")]
+            ObjectKind::Remote(handle) => {
+                let (node_no, group) = handle.remote_group();
+                context.group = Some(group.into());
+                context.node_no = Some(node_no);
+            }
+        }
+    }
+
+    /// Returns `true` if it's a group disabled by its mount condition.
+    pub fn is_disabled_group(&self) -> bool {
+        match &self.kind {
+            ObjectKind::Group(handle) => handle.is_disabled(),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if it's a group on another node.
+    pub(crate) fn is_remote(&self) -> bool {
+        #[cfg(feature = "network")]
+        return matches!(self.kind, ObjectKind::Remote(_));
+        #[cfg(not(feature = "network"))]
+        false
+    }
+
     pub(crate) fn as_actor(&self) -> Option<&Actor> {
         match &self.kind {
             ObjectKind::Actor(handle) => Some(handle),
@@ -251,6 +298,8 @@ where
 }
 
 pub(crate) trait GroupHandle: Send + Sync + 'static {
+    fn name(&self) -> &str;
+    fn is_disabled(&self) -> bool;
     fn handle(&self, envelope: Envelope, visitor: &mut dyn GroupVisitor);
     fn finished(&self) -> BoxFuture<'static, ()>;
 }
@@ -376,6 +425,7 @@ struct TrySendGroupVisitor {
     extra: Option<Envelope>,
     has_ok: bool,
     has_full: bool,
+    rejection: Option<&'static str>,
 }
 
 impl TrySendGroupVisitor {
@@ -386,10 +436,10 @@ impl TrySendGroupVisitor {
         match actor.try_send(envelope) {
             Ok(()) => self.has_ok = true,
             Err(err) => {
-                if err.is_full() {
-                    self.has_full = true;
-                }
-                self.extra = Some(err.into_inner());
+                self.has_full |= err.is_full();
+                let envelope = err.into_inner();
+                self.rejection = envelope.rejection().or(self.rejection);
+                self.extra = Some(envelope);
             }
         }
     }
@@ -398,10 +448,14 @@ impl TrySendGroupVisitor {
         if self.has_ok {
             Ok(())
         } else {
-            let envelope = self.extra.take().expect("missing envelope");
+            let mut envelope = self.extra.take().expect("missing envelope");
             Err(if self.has_full {
                 TrySendError::Full(envelope)
             } else {
+                // The reason is kept in the envelope, see `Context::try_send()`.
+                if let Some(reason) = self.rejection {
+                    envelope.set_admission(Admission::Reject(reason));
+                }
                 TrySendError::Closed(envelope)
             })
         }
@@ -0,0 +1,352 @@
+//! Capturing of panics in actors and handlers.
+//!
+//! Payloads of type `&str` and `String` are captured as messages. Payloads of
+//! other types are opaque unless an extractor is registered for their type by
+//! [`register_extractor()`], which serializes them into JSON. Captured panics
+//! are used as details of the failure status (see [`ActorStatus::panic()`]),
+//! added to error logs and dumped to the `panic` class.
+//!
+//! Backtraces are captured if enabled by the `RUST_LIB_BACKTRACE` or
+//! `RUST_BACKTRACE` env variable, see [`std::backtrace`] for details. Only
+//! panics in actors are captured, and their symbols are resolved lazily,
+//! off the actor's thread, see [`PanicBacktrace`].
+//!
+//! [`ActorStatus::panic()`]: crate::ActorStatus::panic
+
+use std::{
+    any::{Any, TypeId},
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::{Cell, RefCell},
+    fmt,
+    future::{self, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
+    sync::{Arc, Once},
+};
+
+use futures::FutureExt;
+use fxhash::FxHashMap;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::RwLock;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use tracing::error;
+
+use crate::{
+    dumping::{extract_name_by_type, Dumper},
+    messages::RecentDump,
+    scope::{self, Scope},
+};
+
+/// Backtraces are truncated to this size in bytes.
+const MAX_BACKTRACE_SIZE: usize = 8 * 1024;
+
+#[cfg(feature = "dumping")]
+static DUMPER: Lazy<Dumper> = Lazy::new(|| Dumper::new("panic"));
+#[cfg(not(feature = "dumping"))]
+static DUMPER: Dumper = Dumper::disabled("panic");
+
+type Extractor = Box<dyn Fn(&(dyn Any + Send)) -> Result<Value, String> + Send + Sync>;
+
+struct Registered {
+    name: String,
+    extract: Extractor,
+}
+
+static EXTRACTORS: Lazy<RwLock<FxHashMap<TypeId, Registered>>> = Lazy::new(Default::default);
+
+/// Registers an extractor of panic payloads of the type `T`, which are passed
+/// to [`std::panic::panic_any()`]. Replaces a previously registered extractor
+/// for the same type.
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// #[derive(serde::Serialize)]
+/// struct OrderRejected {
+///     order_id: u64,
+///     reason: &'static str,
+/// }
+///
+/// elfo::panics::register_extractor::<OrderRejected>(|p| serde_json::to_value(p));
+/// ```
+pub fn register_extractor<T: Any + Send>(
+    extract: impl Fn(&T) -> serde_json::Result<Value> + Send + Sync + 'static,
+) {
+    let name = extract_name_by_type::<T>().to_string();
+    let name = if name.is_empty() {
+        std::any::type_name::<T>().to_string()
+    } else {
+        name
+    };
+
+    let extract: Extractor = Box::new(move |payload| {
+        let payload = payload.downcast_ref::<T>().expect("invalid extractor");
+        extract(payload).map_err(|err| err.to_string())
+    });
+
+    let registered = Registered { name, extract };
+    EXTRACTORS.write().insert(TypeId::of::<T>(), registered);
+}
+
+// === Panic ===
+
+/// A captured panic, see the [module-level documentation](self) for details.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Panic {
+    /// The message for string payloads, the type name for registered ones.
+    pub message: String,
+    /// The payload serialized by the registered extractor.
+    pub payload: Option<Value>,
+    /// The backtrace if enabled, see [`PanicBacktrace`].
+    pub backtrace: Option<PanicBacktrace>,
+    /// Messages handled by the actor before the panic, the oldest first.
+    /// Empty unless enabled by `system.dumping.recent`.
+    pub recent_dumps: Vec<RecentDump>,
+}
+
+impl Panic {
+    fn new(payload: &(dyn Any + Send)) -> Self {
+        let (message, payload) = if let Some(message) = payload.downcast_ref::<&str>() {
+            ((*message).to_string(), None)
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            (message.clone(), None)
+        } else if let Some(registered) = EXTRACTORS.read().get(&(*payload).type_id()) {
+            match (registered.extract)(payload) {
+                Ok(value) => (registered.name.clone(), Some(value)),
+                Err(err) => (format!("{} (cannot extract: {err})", registered.name), None),
+            }
+        } else {
+            ("<unsupported payload>".into(), None)
+        };
+
+        Self {
+            message,
+            payload,
+            backtrace: BACKTRACE
+                .with(|b| b.borrow_mut().take())
+                .map(PanicBacktrace::new),
+            recent_dumps: Vec::new(),
+        }
+    }
+
+    /// Dumps the panic to the `panic` class. Must be called inside the scope.
+    pub(crate) fn dump(&self) {
+        if let Some(permit) = DUMPER.acquire() {
+            permit.record(
+                crate::dumping::Dump::builder()
+                    .message_name("Panic")
+                    .finish(self.clone()),
+            );
+        }
+    }
+}
+
+impl fmt::Display for Panic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panic: {}", self.message)?;
+
+        if let Some(payload) = &self.payload {
+            write!(f, " {payload}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// === PanicBacktrace ===
+
+/// A backtrace of the panic. Resolving symbols is slow, so it's done only
+/// on the first access, e.g. once the panic is dumped, but never on the
+/// actor's thread by elfo itself.
+#[derive(Clone)]
+pub struct PanicBacktrace(Arc<LazyBacktrace>);
+
+struct LazyBacktrace {
+    raw: Backtrace,
+    resolved: OnceCell<String>,
+}
+
+impl PanicBacktrace {
+    fn new(raw: Backtrace) -> Self {
+        Self(Arc::new(LazyBacktrace {
+            raw,
+            resolved: OnceCell::new(),
+        }))
+    }
+
+    /// Returns the backtrace truncated to 8KiB, resolves symbols if not yet.
+    pub fn as_str(&self) -> &str {
+        self.0
+            .resolved
+            .get_or_init(|| truncate(self.0.raw.to_string()))
+    }
+
+    /// Resolves symbols in a blocking thread and logs the backtrace
+    /// within the current scope.
+    pub(crate) fn log(&self) {
+        let this = self.clone();
+        let scope = scope::try_with(Scope::clone);
+        let log = move || {
+            let log = || error!(backtrace = this.as_str(), "panic backtrace");
+            match scope {
+                Some(scope) => scope.sync_within(log),
+                None => log(),
+            }
+        };
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(log)),
+            Err(_) => log(),
+        }
+    }
+}
+
+impl fmt::Display for PanicBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for PanicBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't resolve symbols just to debug the panic.
+        match self.0.resolved.get() {
+            Some(resolved) => fmt::Debug::fmt(resolved, f),
+            None => f.write_str("<unresolved backtrace>"),
+        }
+    }
+}
+
+impl PartialEq for PanicBacktrace {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for PanicBacktrace {}
+
+impl Serialize for PanicBacktrace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// === Hook ===
+
+thread_local! {
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+    // Set while polling actors, other panics in the process aren't captured.
+    static IS_CAPTURING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs the hook capturing backtraces, the previous hook is called after.
+/// Called once the node starts, subsequent calls do nothing.
+pub(crate) fn install_hook() {
+    static INSTALLED: Once = Once::new();
+
+    INSTALLED.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if IS_CAPTURING.with(Cell::get) {
+                let backtrace = Backtrace::capture();
+                let backtrace =
+                    (backtrace.status() == BacktraceStatus::Captured).then_some(backtrace);
+                BACKTRACE.with(|b| *b.borrow_mut() = backtrace);
+            }
+
+            prev(info);
+        }));
+    });
+}
+
+fn capturing<R>(is_capturing: bool, f: impl FnOnce() -> R) -> R {
+    let prev = IS_CAPTURING.with(|c| c.replace(is_capturing));
+    let result = f();
+    IS_CAPTURING.with(|c| c.set(prev));
+    result
+}
+
+fn truncate(mut backtrace: String) -> String {
+    if backtrace.len() > MAX_BACKTRACE_SIZE {
+        let mut end = MAX_BACKTRACE_SIZE;
+        while !backtrace.is_char_boundary(end) {
+            end -= 1;
+        }
+        backtrace.truncate(end);
+        backtrace.push_str("\n<truncated>");
+    }
+    backtrace
+}
+
+// === Catching ===
+
+/// Catches a panic without the backtrace, only the message is returned.
+pub(crate) fn sync_catch<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    capturing(false, || panic::catch_unwind(AssertUnwindSafe(f)))
+        .map_err(|payload| Panic::new(&*payload).to_string())
+}
+
+pub(crate) async fn catch<R>(f: impl Future<Output = R>) -> Result<R, Panic> {
+    let mut f = pin!(AssertUnwindSafe(f).catch_unwind());
+    future::poll_fn(|cx| capturing(true, || f.as_mut().poll(cx)))
+        .await
+        .map_err(|payload| Panic::new(&*payload))
+}
+
+/// Captures a panic caught by other means, e.g. by `catch_unwind()`.
+pub(crate) fn capture(payload: &(dyn Any + Send)) -> Panic {
+    Panic::new(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Rejected {
+        id: u32,
+    }
+
+    struct Unsupported;
+
+    fn catch_sync(f: impl FnOnce()) -> Panic {
+        let payload = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        Panic::new(&*payload)
+    }
+
+    #[test]
+    fn payloads() {
+        let panic = catch_sync(|| panic!("oops"));
+        assert_eq!(panic.message, "oops");
+        assert_eq!(panic.payload, None);
+        assert_eq!(panic.to_string(), "panic: oops");
+
+        let panic = catch_sync(|| panic!("oops {}", 42));
+        assert_eq!(panic.to_string(), "panic: oops 42");
+
+        let panic = catch_sync(|| panic::panic_any(Unsupported));
+        assert_eq!(panic.to_string(), "panic: <unsupported payload>");
+
+        // Unregistered types keep opaque.
+        let panic = catch_sync(|| panic::panic_any(Rejected { id: 1 }));
+        assert_eq!(panic.to_string(), "panic: <unsupported payload>");
+
+        register_extractor::<Rejected>(|p| serde_json::to_value(p));
+        let panic = catch_sync(|| panic::panic_any(Rejected { id: 2 }));
+        assert_eq!(panic.message, "Rejected");
+        assert_eq!(panic.payload, Some(serde_json::json!({ "id": 2 })));
+        assert_eq!(panic.to_string(), r#"panic: Rejected {"id":2}"#);
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate("short".into()), "short");
+
+        let long = "ы".repeat(MAX_BACKTRACE_SIZE);
+        let truncated = truncate(long);
+        assert!(truncated.len() <= MAX_BACKTRACE_SIZE + "\n<truncated>".len());
+        assert!(truncated.ends_with("ы\n<truncated>"));
+    }
+}
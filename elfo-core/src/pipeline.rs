@@ -0,0 +1,285 @@
+//! Pipelining of requests to the same recipient, see [`Context::pipeline()`].
+
+use std::collections::VecDeque;
+
+use crate::{
+    addr::Addr,
+    context::{Context, SentRequest},
+    errors::{DeliveryError, RequestError},
+    message::Request,
+    object::OwnedObject,
+    request_table::{RequestLimits, Responses},
+};
+
+/// Requests sent to the same recipient without waiting for responses to
+/// previous ones, at most [`Pipeline::max_in_flight()`] at the same time.
+///
+/// Created by [`Context::pipeline()`]. Dropping the pipeline, e.g. when the
+/// actor terminates, cancels requests waiting for responses.
+///
+/// # Example
+/// ```ignore
+/// let mut pipeline = ctx.pipeline(backend).max_in_flight(64).ordered();
+///
+/// for request in requests {
+///     // Waits only if the window is full.
+///     pipeline.push(request).await;
+/// }
+///
+/// while let Some(result) = pipeline.next_response().await {
+///     // Responses and errors in the submission order.
+/// }
+/// ```
+///
+/// [`Context::pipeline()`]: crate::Context::pipeline
+#[must_use]
+pub struct Pipeline<'c, C, K, R: Request> {
+    context: &'c Context<C, K>,
+    object: OwnedObject,
+    recipient: Addr,
+    limits: RequestLimits,
+    max_in_flight: usize,
+    is_ordered: bool,
+    /// `None` means `max_in_flight`.
+    reorder_capacity: Option<usize>,
+    /// Pushed requests, which responses aren't yielded, in the submission order.
+    slots: VecDeque<Slot<R>>,
+    in_flight: usize,
+}
+
+struct Slot<R: Request> {
+    /// `None` once resolved.
+    sent: Option<SentRequest>,
+    /// `Some` once resolved.
+    result: Option<Result<R::Response, DeliveryError<RequestError>>>,
+}
+
+impl<'c, C: 'static, K, R: Request> Pipeline<'c, C, K, R> {
+    pub(crate) fn new(context: &'c Context<C, K>, recipient: Addr) -> Self {
+        Self {
+            context,
+            object: context.actor_object(),
+            recipient,
+            limits: RequestLimits::default(),
+            max_in_flight: 16,
+            is_ordered: false,
+            reorder_capacity: None,
+            slots: VecDeque::new(),
+            in_flight: 0,
+        }
+    }
+
+    /// Sets how many requests can wait for responses at the same time.
+    /// [`Pipeline::push()`] waits if the window is full. 16 by default.
+    ///
+    /// # Panics
+    ///
+    /// If `max` is zero.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        assert!(max > 0, "the window must be positive");
+        self.max_in_flight = max;
+        self
+    }
+
+    /// Attaches limits to every request, see [`RequestLimits`].
+    pub fn limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Yields responses in the submission order.
+    ///
+    /// Responses received before responses to earlier requests are kept in
+    /// the reorder buffer, at most [`Pipeline::max_in_flight()`] by default.
+    /// [`Pipeline::push()`] waits if the buffer is full.
+    pub fn ordered(mut self) -> Self {
+        self.is_ordered = true;
+        self
+    }
+
+    /// Yields responses in the submission order with the specified capacity
+    /// of the reorder buffer, see [`Pipeline::ordered()`].
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn reorder_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity must be positive");
+        self.is_ordered = true;
+        self.reorder_capacity = Some(capacity);
+        self
+    }
+
+    /// Returns the number of requests waiting for responses.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Returns `true` if all responses have been yielded.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Sends the request without waiting for the response, which is yielded
+    /// later by [`Pipeline::next_response()`].
+    ///
+    /// Waits until the window has room, see [`Pipeline::max_in_flight()`].
+    /// If the request cannot be sent, the error is yielded in place of
+    /// the response.
+    pub async fn push(&mut self, request: R) {
+        self.take_resolved();
+
+        while self.is_window_full() {
+            self.wait_any().await;
+        }
+
+        let actor = self
+            .object
+            .as_actor()
+            .expect("can be called only on actors");
+        let builder = self.context.request_to(self.recipient, request);
+        let slot = match builder.limits(self.limits).send(actor).await {
+            Ok(sent) => {
+                self.in_flight += 1;
+                Slot {
+                    sent: Some(sent),
+                    result: None,
+                }
+            }
+            Err(err) => Slot {
+                sent: None,
+                result: Some(Err(err)),
+            },
+        };
+
+        self.slots.push_back(slot);
+    }
+
+    /// Waits for the next response or the error, e.g. a timeout or closing of
+    /// the recipient, in place of it. Returns `None` if all responses have
+    /// been yielded.
+    ///
+    /// This method is cancel safe.
+    pub async fn next_response(&mut self) -> Option<Result<R::Response, RequestError>> {
+        let result = self.next_response_detailed().await?;
+        Some(result.map_err(DeliveryError::into_error))
+    }
+
+    /// Like [`Pipeline::next_response()`], but returns errors with the
+    /// context of the failure, see [`DeliveryError`].
+    ///
+    /// This method is cancel safe.
+    pub async fn next_response_detailed(
+        &mut self,
+    ) -> Option<Result<R::Response, DeliveryError<RequestError>>> {
+        loop {
+            let ready = if self.is_ordered {
+                self.slots
+                    .front()
+                    .filter(|slot| slot.result.is_some())
+                    .map(|_| 0)
+            } else {
+                self.slots.iter().position(|slot| slot.result.is_some())
+            };
+
+            if let Some(index) = ready {
+                return self.slots.remove(index).and_then(|slot| slot.result);
+            }
+
+            if self.in_flight == 0 {
+                return None;
+            }
+
+            self.wait_any().await;
+        }
+    }
+
+    fn is_window_full(&self) -> bool {
+        if self.in_flight >= self.max_in_flight {
+            return true;
+        }
+
+        if !self.is_ordered {
+            return false;
+        }
+
+        // Count responses received before responses to earlier requests.
+        let capacity = self.reorder_capacity.unwrap_or(self.max_in_flight);
+        let reordered = self
+            .slots
+            .iter()
+            .skip_while(|slot| slot.result.is_some())
+            .filter(|slot| slot.result.is_some())
+            .count();
+
+        reordered >= capacity
+    }
+
+    /// Stores results of already resolved requests without waiting.
+    fn take_resolved(&mut self) {
+        let actor = self
+            .object
+            .as_actor()
+            .expect("can be called only on actors");
+
+        for slot in self.slots.iter_mut() {
+            let Some(sent) = &slot.sent else { continue };
+            let Some(responses) = actor.request_table().try_take(sent.request_id) else {
+                continue;
+            };
+
+            let sent = slot.sent.take().expect("just checked");
+            slot.result = Some(take_response::<_, _, R>(self.context, sent, responses));
+            self.in_flight -= 1;
+        }
+    }
+
+    /// Waits for any of requests in flight, stores its result.
+    async fn wait_any(&mut self) {
+        let actor = self
+            .object
+            .as_actor()
+            .expect("can be called only on actors");
+        let pending = self
+            .slots
+            .iter()
+            .filter_map(|slot| slot.sent.as_ref())
+            .map(|sent| (sent.request_id, sent.deadline));
+
+        let (request_id, responses) = actor.request_table().wait_any(pending).await;
+
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| matches!(&slot.sent, Some(sent) if sent.request_id == request_id))
+            .expect("unknown request");
+
+        let sent = slot.sent.take().expect("just found");
+        slot.result = Some(take_response::<_, _, R>(self.context, sent, responses));
+        self.in_flight -= 1;
+    }
+}
+
+fn take_response<C, K, R: Request>(
+    context: &Context<C, K>,
+    sent: SentRequest,
+    responses: Responses,
+) -> Result<R::Response, DeliveryError<RequestError>> {
+    let result = context.take_response::<R>(sent, responses);
+    result.map(|(response, _responder)| response)
+}
+
+impl<C, K, R: Request> Drop for Pipeline<'_, C, K, R> {
+    fn drop(&mut self) {
+        let Some(actor) = self.object.as_actor() else {
+            return;
+        };
+
+        // Responders drop the requests and their late responses.
+        let table = actor.request_table();
+        for sent in self.slots.iter().filter_map(|slot| slot.sent.as_ref()) {
+            table.cancel(sent.request_id);
+            table.cancel_request(sent.request_id);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    envelope::{Envelope, MessageKind},
+    message::Message,
+    tracing::TraceId,
+};
+
+/// Detects messages repeatedly crashing the actor,
+/// see `system.mailbox.poison_threshold`.
+///
+/// While enabled, a copy of the handled message is kept until the next one
+/// is requested, so it can be handled again by the restarted actor.
+#[derive(Default)]
+pub(crate) struct Poisoning {
+    /// `0` means disabled.
+    threshold: AtomicU32,
+    handling: Mutex<Option<Envelope>>,
+    strikes: Mutex<Option<Strikes>>,
+}
+
+/// Consecutive panics caused by the same message.
+#[derive(Clone, Copy)]
+pub(crate) struct Strikes {
+    trace_id: TraceId,
+    name: &'static str,
+    count: u32,
+}
+
+/// What to do with the mailbox of the panicked actor.
+pub(crate) struct AfterPanic {
+    /// The message to handle again by the restarted actor.
+    pub(crate) retry: Option<Envelope>,
+    /// The message crashed the actor too many times in a row.
+    pub(crate) poisoned: Option<Envelope>,
+    /// Messages left in the mailbox, set by the supervisor.
+    pub(crate) left: Vec<Envelope>,
+    pub(crate) strikes: Option<Strikes>,
+}
+
+impl Poisoning {
+    pub(crate) fn new(threshold: Option<NonZeroU32>) -> Self {
+        let this = Self::default();
+        this.configure(threshold);
+        this
+    }
+
+    pub(crate) fn configure(&self, threshold: Option<NonZeroU32>) {
+        let threshold = threshold.map_or(0, NonZeroU32::get);
+        self.threshold.store(threshold, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_strikes(&self, strikes: Option<Strikes>) {
+        *self.strikes.lock() = strikes;
+    }
+
+    /// Remembers the message being handled.
+    ///
+    /// Requests aren't kept: their requesters are responded with an error
+    /// on panic, so handling them again makes no sense.
+    #[inline]
+    pub(crate) fn on_handling(&self, envelope: &Envelope) {
+        if self.threshold.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let copy = matches!(envelope.message_kind(), MessageKind::Regular { .. })
+            .then(|| envelope.duplicate());
+
+        *self.handling.lock() = copy;
+    }
+
+    /// Forgets the handled message once the next one is requested.
+    #[inline]
+    pub(crate) fn on_handled(&self) {
+        if self.threshold.load(Ordering::Relaxed) != 0 {
+            *self.handling.lock() = None;
+        }
+    }
+
+    /// Returns `None` if disabled.
+    pub(crate) fn on_panic(&self) -> Option<AfterPanic> {
+        let threshold = self.threshold.load(Ordering::Relaxed);
+        if threshold == 0 {
+            return None;
+        }
+
+        let Some(envelope) = self.handling.lock().take() else {
+            // The actor panicked outside handling messages.
+            return Some(AfterPanic {
+                retry: None,
+                poisoned: None,
+                left: Vec::new(),
+                strikes: None,
+            });
+        };
+
+        let trace_id = envelope.trace_id();
+        let name = envelope.message().name();
+
+        let count = match *self.strikes.lock() {
+            Some(prev) if prev.trace_id == trace_id && prev.name == name => prev.count + 1,
+            _ => 1,
+        };
+
+        Some(if count >= threshold {
+            AfterPanic {
+                retry: None,
+                poisoned: Some(envelope),
+                left: Vec::new(),
+                strikes: None,
+            }
+        } else {
+            AfterPanic {
+                retry: Some(envelope),
+                poisoned: None,
+                left: Vec::new(),
+                strikes: Some(Strikes {
+                    trace_id,
+                    name,
+                    count,
+                }),
+            }
+        })
+    }
+}
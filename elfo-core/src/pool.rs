@@ -0,0 +1,352 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use fxhash::FxHashMap;
+use metrics::{counter, gauge, increment_counter};
+use parking_lot::Mutex;
+use tokio::{sync::Notify, time::Instant};
+
+use crate::{
+    actor::{Actor, ActorMeta},
+    addr::Addr,
+    address_book::AddressBook,
+    config::system::mailbox::MailboxConfig,
+    envelope::Envelope,
+    group::TerminationPolicy,
+    mailbox::{Mailbox, RecvResult},
+    object::{Object, OwnedObject},
+    subscription::SubscriptionManager,
+};
+
+/// The queue shared by workers of a pooled group, see `ActorGroup::pool()`.
+///
+/// It's a mailbox without an actor: it's registered in the address book, so
+/// senders and visitors treat it as a regular actor, but nobody runs it.
+/// Instead, workers compete for its messages in `Actor::recv()`.
+pub(crate) struct Pool {
+    object: OwnedObject,
+    /// Set for sticky pools, see `ActorGroup::sticky_by()`.
+    affinity: Option<Affinity>,
+}
+
+/// Extracts a hashed key of a sticky pool, see `ActorGroup::sticky_by()`.
+pub(crate) type StickyKey = Arc<dyn Fn(&Envelope) -> Option<u64> + Send + Sync>;
+
+impl Pool {
+    pub(crate) fn new(
+        book: &AddressBook,
+        group_addr: Addr,
+        group: &str,
+        termination_policy: TerminationPolicy,
+        status_subscription: Arc<SubscriptionManager>,
+        sticky: Option<StickyKey>,
+    ) -> Self {
+        let group_no = group_addr.group_no().expect("invalid group addr");
+        let entry = book.vacant_entry(group_no);
+        let addr = entry.addr();
+
+        let meta = Arc::new(ActorMeta {
+            group: group.into(),
+            key: "pool".into(),
+        });
+
+        let queue = Actor::new(
+            meta,
+            None,
+            addr,
+            &<_>::default(),
+            &<_>::default(),
+            termination_policy,
+            status_subscription,
+        );
+
+        entry.insert(Object::new(addr, queue));
+
+        Self {
+            object: book.get_owned(addr).expect("just inserted"),
+            affinity: sticky.map(Affinity::new),
+        }
+    }
+
+    /// Returns the object to send messages to.
+    pub(crate) fn object(&self) -> &OwnedObject {
+        &self.object
+    }
+
+    pub(crate) fn configure(&self, config: &MailboxConfig) {
+        let queue = self.queue();
+        queue.set_mailbox_capacity_config(config.capacity);
+        queue.set_mailbox_quotas(&config.quotas);
+        queue.set_mailbox_on_terminate(config.on_terminate);
+
+        if let Some(affinity) = &self.affinity {
+            let mut state = affinity.state.lock();
+            state.idle_window = *config.sticky_idle_window;
+            state.capacity = config.sticky_capacity;
+        }
+    }
+
+    /// Rejects new messages, left ones are dropped along with the pool.
+    pub(crate) fn close(&self) -> bool {
+        self.queue().close()
+    }
+
+    /// Waits for the next message, returns `None` if the pool is closed.
+    pub(crate) async fn recv(&self, worker: usize) -> Option<Envelope> {
+        let mailbox = self.queue().mailbox();
+
+        let Some(affinity) = &self.affinity else {
+            return match mailbox.recv().await {
+                RecvResult::Data(envelope) => Some(self.on_taken(envelope)),
+                RecvResult::Closed(_) => None,
+            };
+        };
+
+        loop {
+            let notify = match affinity.take(mailbox, worker) {
+                Taken::Data(envelope) => return Some(self.on_taken(envelope)),
+                Taken::Closed => return None,
+                Taken::Empty(notify) => notify,
+            };
+
+            // Messages are either dispatched to this worker by others or put
+            // into the shared queue. Both are checked on the next iteration.
+            tokio::select! {
+                _ = notify.notified() => {}
+                _ = mailbox.readable() => {}
+            }
+        }
+    }
+
+    pub(crate) fn try_recv(&self, worker: usize) -> Option<Envelope> {
+        let mailbox = self.queue().mailbox();
+
+        let Some(affinity) = &self.affinity else {
+            return match mailbox.try_recv()? {
+                RecvResult::Data(envelope) => Some(self.on_taken(envelope)),
+                RecvResult::Closed(_) => None,
+            };
+        };
+
+        match affinity.take(mailbox, worker) {
+            Taken::Data(envelope) => Some(self.on_taken(envelope)),
+            Taken::Closed | Taken::Empty(_) => None,
+        }
+    }
+
+    /// Releases bindings of the finished worker, messages dispatched to it
+    /// are handled by other workers.
+    pub(crate) fn release(&self, worker: usize) {
+        if let Some(affinity) = &self.affinity {
+            affinity.release(worker);
+        }
+    }
+
+    // Called in the worker's scope, so metrics are labelled by the group
+    // and also by the worker if `system.telemetry.per_actor_key` is set.
+    fn on_taken(&self, envelope: Envelope) -> Envelope {
+        increment_counter!("elfo_pool_processed_total");
+        gauge!("elfo_pool_queue_depth", self.queue().mailbox().len() as f64);
+        envelope
+    }
+
+    fn queue(&self) -> &Actor {
+        self.object.as_actor().expect("the pool is an actor")
+    }
+}
+
+// === Affinity ===
+
+struct Affinity {
+    key_of: StickyKey,
+    state: Mutex<AffinityState>,
+}
+
+struct AffinityState {
+    idle_window: Duration,
+    capacity: usize,
+    bindings: FxHashMap<u64, Binding>,
+    workers: Vec<Worker>,
+    /// Messages left by finished workers, taken before the shared queue.
+    orphans: VecDeque<Envelope>,
+}
+
+struct Binding {
+    worker: usize,
+    /// The number of dispatched messages, which aren't handled yet.
+    pending: usize,
+    idle_since: Instant,
+}
+
+#[derive(Default)]
+struct Worker {
+    /// Messages of keys bound to the worker, taken before the shared queue.
+    queue: VecDeque<(u64, Envelope)>,
+    /// The key of the message being handled.
+    current: Option<u64>,
+    notify: Arc<Notify>,
+}
+
+enum Taken {
+    Data(Envelope),
+    Closed,
+    Empty(Arc<Notify>),
+}
+
+impl Affinity {
+    fn new(key_of: StickyKey) -> Self {
+        let config = MailboxConfig::default();
+
+        Self {
+            key_of,
+            state: Mutex::new(AffinityState {
+                idle_window: *config.sticky_idle_window,
+                capacity: config.sticky_capacity,
+                bindings: FxHashMap::default(),
+                workers: Vec::new(),
+                orphans: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn take(&self, mailbox: &Mailbox, worker: usize) -> Taken {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+
+        state.ensure_worker(worker);
+        state.finish(worker, now);
+
+        loop {
+            if let Some((key, envelope)) = state.workers[worker].queue.pop_front() {
+                state.workers[worker].current = Some(key);
+                return Taken::Data(envelope);
+            }
+
+            let envelope = match state.orphans.pop_front() {
+                Some(envelope) => envelope,
+                None => match mailbox.try_recv() {
+                    Some(RecvResult::Data(envelope)) => envelope,
+                    Some(RecvResult::Closed(_)) => return Taken::Closed,
+                    None => return Taken::Empty(state.workers[worker].notify.clone()),
+                },
+            };
+
+            if let Some(envelope) = self.dispatch(&mut state, worker, envelope, now) {
+                return Taken::Data(envelope);
+            }
+        }
+    }
+
+    // Returns the envelope if it must be handled by the worker.
+    fn dispatch(
+        &self,
+        state: &mut AffinityState,
+        worker: usize,
+        envelope: Envelope,
+        now: Instant,
+    ) -> Option<Envelope> {
+        let Some(key) = (self.key_of)(&envelope) else {
+            return Some(envelope);
+        };
+
+        let idle_window = state.idle_window;
+        if let Some(binding) = state.bindings.get_mut(&key) {
+            if binding.is_active(now, idle_window) {
+                increment_counter!("elfo_pool_affinity_hits_total");
+                binding.pending += 1;
+
+                // The own queue is empty here, so the order is kept.
+                if binding.worker == worker {
+                    state.workers[worker].current = Some(key);
+                    return Some(envelope);
+                }
+
+                let bound = &mut state.workers[binding.worker];
+                bound.queue.push_back((key, envelope));
+                bound.notify.notify_one();
+                return None;
+            }
+
+            increment_counter!("elfo_pool_affinity_evictions_total");
+            state.bindings.remove(&key);
+        }
+
+        if state.bindings.len() >= state.capacity {
+            let before = state.bindings.len();
+            state
+                .bindings
+                .retain(|_, binding| binding.is_active(now, idle_window));
+            let evicted = before - state.bindings.len();
+            counter!("elfo_pool_affinity_evictions_total", evicted as u64);
+
+            if state.bindings.len() >= state.capacity {
+                increment_counter!("elfo_pool_affinity_overflows_total");
+                return Some(envelope);
+            }
+        }
+
+        increment_counter!("elfo_pool_affinity_misses_total");
+        state.bindings.insert(
+            key,
+            Binding {
+                worker,
+                pending: 1,
+                idle_since: now,
+            },
+        );
+        state.workers[worker].current = Some(key);
+        Some(envelope)
+    }
+
+    fn release(&self, worker: usize) {
+        let mut state = self.state.lock();
+        if worker >= state.workers.len() {
+            return;
+        }
+
+        let left = std::mem::take(&mut state.workers[worker].queue);
+        state.workers[worker].current = None;
+        state.bindings.retain(|_, binding| binding.worker != worker);
+
+        if left.is_empty() {
+            return;
+        }
+
+        state
+            .orphans
+            .extend(left.into_iter().map(|(_, envelope)| envelope));
+
+        // Idle workers wait for the shared queue, so wake them up.
+        for worker in &state.workers {
+            worker.notify.notify_one();
+        }
+    }
+}
+
+impl AffinityState {
+    fn ensure_worker(&mut self, worker: usize) {
+        if worker >= self.workers.len() {
+            self.workers.resize_with(worker + 1, Worker::default);
+        }
+    }
+
+    // The previous message of the worker is handled.
+    fn finish(&mut self, worker: usize, now: Instant) {
+        let Some(key) = self.workers[worker].current.take() else {
+            return;
+        };
+
+        let binding = self.bindings.get_mut(&key);
+        if let Some(binding) = binding.filter(|binding| binding.worker == worker) {
+            binding.pending -= 1;
+            if binding.pending == 0 {
+                binding.idle_since = now;
+            }
+        }
+    }
+}
+
+impl Binding {
+    fn is_active(&self, now: Instant, idle_window: Duration) -> bool {
+        self.pending > 0 || now.duration_since(self.idle_since) < idle_window
+    }
+}
@@ -0,0 +1,54 @@
+//! Hooks of the load generator, used by `elfo::test::load()`.
+//!
+//! Registered probes are notified once any actor finishes handling a message,
+//! i.e. calls `recv()` again or terminates. Probes are global, so they must
+//! filter messages they're interested in, e.g. by senders and trace ids.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::RwLock;
+
+use crate::{tracing::TraceId, Addr};
+
+/// A hook called by actors, see the module docs.
+pub trait Probe: Send + Sync + 'static {
+    /// Called once the message sent by `sender` within `trace_id` is handled.
+    fn on_handled(&self, sender: Addr, trace_id: TraceId);
+}
+
+static IS_ACTIVE: AtomicBool = AtomicBool::new(false);
+static PROBES: RwLock<Vec<Arc<dyn Probe>>> = RwLock::new(Vec::new());
+
+/// Registers the probe until the guard is dropped.
+pub fn register(probe: Arc<dyn Probe>) -> ProbeGuard {
+    let mut probes = PROBES.write();
+    probes.push(probe.clone());
+    IS_ACTIVE.store(true, Ordering::Relaxed);
+    ProbeGuard(probe)
+}
+
+/// Unregisters the probe on drop, see [`register()`].
+#[must_use]
+pub struct ProbeGuard(Arc<dyn Probe>);
+
+impl Drop for ProbeGuard {
+    fn drop(&mut self) {
+        let mut probes = PROBES.write();
+        probes.retain(|probe| !Arc::ptr_eq(probe, &self.0));
+        IS_ACTIVE.store(!probes.is_empty(), Ordering::Relaxed);
+    }
+}
+
+#[inline]
+pub(crate) fn is_active() -> bool {
+    IS_ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn on_handled(sender: Addr, trace_id: TraceId) {
+    for probe in PROBES.read().iter() {
+        probe.on_handled(sender, trace_id);
+    }
+}
@@ -0,0 +1,30 @@
+//! [Config].
+//!
+//! [Config]: RateLimiterConfig
+
+use fxhash::FxHashMap;
+use serde::Deserialize;
+
+use crate::config::Rate;
+
+/// Overrides rates of outbound rate limiters of the group, see
+/// [`Context::rate_limited()`]. Every limiter paces messages sent by actors
+/// of the group to a destination group.
+///
+/// Rates set here take precedence over ones passed by the code, so they can
+/// be tuned without redeploying. Destinations not listed here use rates
+/// passed by the code.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.rate_limiter.destinations.another_group = "50/s"
+/// ```
+///
+/// [`Context::rate_limited()`]: crate::Context::rate_limited
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimiterConfig {
+    /// Rates by names of destination groups.
+    pub destinations: FxHashMap<String, Rate>,
+}
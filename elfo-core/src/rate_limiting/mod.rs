@@ -0,0 +1,259 @@
+//! Outbound rate limiters, see [`Context::rate_limited()`].
+
+use std::{sync::Arc, time::Duration};
+
+use fxhash::FxHashMap;
+use metrics::gauge;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use self::config::RateLimiterConfig;
+use crate::{
+    config::Rate,
+    context::Context,
+    errors::{DeliveryError, ErrorKind, RequestError, SendError, TrySendError},
+    group_ref::GroupRef,
+    message::{Message, Request},
+};
+
+pub mod config;
+
+/// Outbound rate limiters of one group by names of destination groups.
+#[derive(Default)]
+pub(crate) struct RateLimiters {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    config: RateLimiterConfig,
+    edges: FxHashMap<String, Arc<Edge>>,
+}
+
+impl RateLimiters {
+    pub(crate) fn configure(&self, config: &RateLimiterConfig) {
+        let mut inner = self.inner.lock();
+
+        for (destination, edge) in &inner.edges {
+            edge.state.lock().configured = config.destinations.get(destination).copied();
+        }
+
+        inner.config = config.clone();
+    }
+
+    /// Returns the limiter of the destination shared by all actors of the
+    /// group. The rate is used unless it's overridden by the config.
+    pub(crate) fn get(&self, destination: &str, rate: Rate) -> Arc<Edge> {
+        let mut inner = self.inner.lock();
+        let Inner { config, edges } = &mut *inner;
+
+        let edge = edges.entry(destination.into()).or_insert_with(|| {
+            let configured = config.destinations.get(destination).copied();
+            Arc::new(Edge::new(destination, rate, configured))
+        });
+
+        edge.state.lock().default = rate;
+        edge.clone()
+    }
+}
+
+// === Edge ===
+
+/// Paces messages to one destination: every message takes a slot, slots are
+/// spaced evenly according to the rate and taken in the order of arrival.
+/// Thus, actors of the group sharing the budget get shares proportional to
+/// their demands.
+pub(crate) struct Edge {
+    destination: String,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Passed by the code.
+    default: Rate,
+    /// Set in the config, takes precedence over `default`.
+    configured: Option<Rate>,
+    /// When the next slot is available.
+    next: Instant,
+    /// Attempts to send within the current period, used for the saturation.
+    window_start: Instant,
+    window_attempts: u64,
+}
+
+impl Edge {
+    fn new(destination: &str, default: Rate, configured: Option<Rate>) -> Self {
+        // Uses `tokio`'s time in order to be driven by the virtual time in tests.
+        let now = Instant::now();
+
+        Self {
+            destination: destination.into(),
+            state: Mutex::new(State {
+                default,
+                configured,
+                next: now,
+                window_start: now,
+                window_attempts: 0,
+            }),
+        }
+    }
+
+    /// Takes the next slot, returns when it starts.
+    /// If the rate is zero, returns when to try again.
+    fn reserve(&self) -> Result<Instant, Instant> {
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        self.on_attempt(&mut state, now);
+
+        let rate = state.rate();
+        let step = step(rate).ok_or(now + rate.period())?;
+        let slot = state.next.max(now);
+        state.next = slot + step;
+        Ok(slot)
+    }
+
+    /// Takes the next slot only if it's available right now.
+    fn try_take(&self) -> bool {
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        self.on_attempt(&mut state, now);
+
+        match step(state.rate()) {
+            Some(step) if state.next <= now => {
+                state.next = now + step;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Counts attempts within the period of the rate and reports the ratio
+    /// of the last period to the budget. Values above `1` mean that senders
+    /// wait or fail because of the limiter.
+    fn on_attempt(&self, state: &mut State, now: Instant) {
+        let rate = state.rate();
+        let window_end = state.window_start + rate.period();
+
+        if now >= window_end {
+            // The previous period has been idle if it's ended long ago.
+            let saturation = if now < window_end + rate.period() {
+                state.window_attempts as f64 / rate.count().max(1) as f64
+            } else {
+                0.
+            };
+
+            gauge!("elfo_rate_limiter_saturation", saturation,
+                "destination" => self.destination.clone(),
+            );
+
+            state.window_start = now;
+            state.window_attempts = 0;
+        }
+
+        state.window_attempts += 1;
+    }
+}
+
+impl State {
+    fn rate(&self) -> Rate {
+        self.configured.unwrap_or(self.default)
+    }
+}
+
+fn step(rate: Rate) -> Option<Duration> {
+    let count = u128::from(rate.count());
+    (count > 0).then(|| {
+        let step = rate.period().as_nanos() / count;
+        Duration::from_nanos(step.clamp(1, u128::from(u64::MAX)) as u64)
+    })
+}
+
+// === RateLimited ===
+
+/// Paces messages sent to the destination group according to the budget
+/// shared by all actors of the group.
+///
+/// Created by [`Context::rate_limited()`].
+///
+/// [`Context::rate_limited()`]: crate::Context::rate_limited
+#[must_use]
+pub struct RateLimited<'c, C, K> {
+    context: &'c Context<C, K>,
+    destination: GroupRef,
+    edge: Arc<Edge>,
+}
+
+impl<'c, C, K> RateLimited<'c, C, K> {
+    pub(crate) fn new(context: &'c Context<C, K>, destination: GroupRef, edge: Arc<Edge>) -> Self {
+        Self {
+            context,
+            destination,
+            edge,
+        }
+    }
+
+    /// Returns the destination group.
+    #[inline]
+    pub fn destination(&self) -> &GroupRef {
+        &self.destination
+    }
+
+    /// Waits until the budget allows, then sends the message to the
+    /// destination group, see [`Context::send_to_group()`].
+    ///
+    /// [`Context::send_to_group()`]: crate::Context::send_to_group
+    pub async fn send<M: Message>(&self, message: M) -> Result<(), SendError<M>> {
+        self.wait().await;
+        self.context.send_to_group(&self.destination, message).await
+    }
+
+    /// Sends the message to the destination group only if the budget allows
+    /// it right now. Otherwise, fails fast with [`TrySendError::Full`].
+    /// Also, doesn't wait if the mailbox is full.
+    pub fn try_send<M: Message>(&self, message: M) -> Result<(), TrySendError<M>> {
+        self.try_send_detailed(message)
+            .map_err(DeliveryError::into_error)
+    }
+
+    /// Like [`RateLimited::try_send()`], but returns the error with the
+    /// context of the failure, so an exhausted budget can be told apart from
+    /// a full mailbox by [`ErrorKind::RateLimited`].
+    pub fn try_send_detailed<M: Message>(
+        &self,
+        message: M,
+    ) -> Result<(), DeliveryError<TrySendError<M>>> {
+        if !self.edge.try_take() {
+            let name = (message.protocol(), message.name());
+            let err = TrySendError::Full(message);
+            let destination = &self.destination;
+            return Err(self
+                .context
+                .group_error(ErrorKind::RateLimited, destination, err, name));
+        }
+
+        self.context.try_send_to_group(&self.destination, message)
+    }
+
+    /// Waits until the budget allows, then sends the request to the
+    /// destination group and waits for the response.
+    pub async fn request<R: Request>(&self, request: R) -> Result<R::Response, RequestError>
+    where
+        C: 'static,
+    {
+        self.wait().await;
+        self.context
+            .request_to_group(&self.destination, request)
+            .await
+            .map_err(DeliveryError::into_error)
+    }
+
+    async fn wait(&self) {
+        loop {
+            match self.edge.reserve() {
+                Ok(slot) if slot <= Instant::now() => break,
+                Ok(slot) => break tokio::time::sleep_until(slot).await,
+                // Sends are paused until the rate is changed in the config.
+                Err(retry_at) => tokio::time::sleep_until(retry_at).await,
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
-    addr::Addr,
+    addr::{Addr, NodeNo},
     envelope::Envelope,
-    errors::{RequestError, SendError, TrySendError},
+    errors::{AckError, ErrorKind, SendError, TrySendError},
     request_table::ResponseToken,
 };
 
@@ -14,7 +14,9 @@ pub trait RemoteHandle: Send + Sync + 'static {
         recipient: Addr,
         envelope: Envelope,
     ) -> Result<(), SendError<Envelope>>;
-    fn respond(&self, token: ResponseToken, response: Result<Envelope, RequestError>);
+    fn respond(&self, token: ResponseToken, response: Result<Envelope, ErrorKind>);
+    /// Returns the node and the name of the remote group.
+    fn remote_group(&self) -> (NodeNo, &str);
 }
 
 #[stability::unstable]
@@ -24,6 +26,51 @@ pub enum SendResult {
     Wait(SendNotified, Envelope),
 }
 
+/// Confirms that the envelope has been enqueued into the recipient's mailbox,
+/// see [`Context::send_acknowledged()`].
+///
+/// Attached to envelopes sent to remote actors. If dropped without resolving,
+/// the fallback error is reported.
+///
+/// [`Context::send_acknowledged()`]: crate::Context::send_acknowledged
+#[stability::unstable]
+pub struct AckToken(Box<AckTokenInner>);
+
+type AckCallback = Box<dyn FnOnce(Result<(), AckError>) + Send>;
+
+struct AckTokenInner {
+    callback: Option<AckCallback>,
+    fallback: AckError,
+}
+
+impl AckToken {
+    #[stability::unstable]
+    pub fn new(
+        fallback: AckError,
+        callback: impl FnOnce(Result<(), AckError>) + Send + 'static,
+    ) -> Self {
+        Self(Box::new(AckTokenInner {
+            callback: Some(Box::new(callback)),
+            fallback,
+        }))
+    }
+
+    #[stability::unstable]
+    pub fn resolve(mut self, result: Result<(), AckError>) {
+        if let Some(callback) = self.0.callback.take() {
+            callback(result);
+        }
+    }
+}
+
+impl Drop for AckToken {
+    fn drop(&mut self) {
+        if let Some(callback) = self.0.callback.take() {
+            callback(Err(self.0.fallback));
+        }
+    }
+}
+
 pub use self::notifier::*;
 mod notifier {
     use std::{
@@ -1,14 +1,25 @@
-use std::{fmt, marker::PhantomData, sync::Arc};
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
 
 use idr_ebr::EbrGuard;
+use metrics::increment_counter;
 use parking_lot::Mutex;
 use slotmap::{new_key_type, Key, SlotMap};
 use smallvec::SmallVec;
-use tokio::sync::Notify;
+use tokio::{sync::Notify, time::Instant as TokioInstant};
+
+use elfo_utils::{time::Instant, unlikely};
 
 use crate::{
-    address_book::AddressBook, envelope::Envelope, errors::RequestError, message::AnyMessage,
-    tracing::TraceId, Addr,
+    address_book::AddressBook, envelope::Envelope, errors::ErrorKind, idempotency::IdempotencyKey,
+    message::AnyMessage, object::OwnedObject, tracing::TraceId, Addr,
 };
 
 // === RequestId ===
@@ -47,18 +58,24 @@ pub(crate) struct RequestTable {
 
 assert_impl_all!(RequestTable: Sync);
 
-type Responses = SmallVec<[Result<Envelope, RequestError>; 1]>;
+pub(crate) type Responses = SmallVec<[Result<Envelope, ErrorKind>; 1]>;
 
-#[derive(Default)]
 struct RequestData {
     remainder: usize,
     responses: Responses,
     collect_all: bool,
+    is_cancelled: bool,
+    token: Weak<ResponseTokenData>,
+    message_name: &'static str,
+    recipient: Option<Addr>,
+    created_time: Instant,
+    /// The deadline is set by `system.ttl`, not by the code.
+    is_default_ttl: bool,
 }
 
 impl RequestData {
     /// Returns `true` if the request is done.
-    fn push(&mut self, response: Result<Envelope, RequestError>) -> bool {
+    fn push(&mut self, response: Result<Envelope, ErrorKind>) -> bool {
         // Extra responses (in `any` case).
         if self.remainder == 0 {
             // TODO: move to `ResponseToken` to avoid sending extra responses over network.
@@ -85,7 +102,7 @@ impl RequestData {
         else if response.is_ok() {
             debug_assert!(self.responses[0].is_err());
             self.responses[0] = response;
-        } else if let Err(RequestError::Ignored) = response {
+        } else if let Err(ErrorKind::Ignored) = response {
             debug_assert!(self.responses[0].is_err());
             self.responses[0] = response;
         }
@@ -108,19 +125,36 @@ impl RequestTable {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_request(
         &self,
         book: AddressBook,
         trace_id: TraceId,
         collect_all: bool,
+        message_name: &'static str,
+        recipient: Option<Addr>,
+        limits: RequestLimits,
+        is_default_ttl: bool,
+        idempotency_key: Option<IdempotencyKey>,
     ) -> ResponseToken {
         let mut requests = self.requests.lock();
         let request_id = requests.insert(RequestData {
             remainder: 1,
             responses: Responses::new(),
             collect_all,
+            is_cancelled: false,
+            token: Weak::new(),
+            message_name,
+            recipient,
+            created_time: Instant::now(),
+            is_default_ttl,
         });
-        ResponseToken::new(self.owner, request_id, trace_id, book)
+        let token = ResponseToken::new(self.owner, request_id, trace_id, book)
+            .with_limits(limits)
+            .with_idempotency_key(idempotency_key);
+        let data = token.data.as_ref().expect("just created");
+        requests[request_id].token = Arc::downgrade(data);
+        token
     }
 
     pub(crate) fn cancel_request(&self, request_id: RequestId) {
@@ -128,7 +162,90 @@ impl RequestTable {
         requests.remove(request_id);
     }
 
-    pub(crate) async fn wait(&self, request_id: RequestId) -> Responses {
+    /// Resolves the request with `ErrorKind::Cancelled`.
+    /// Returns `false` if the request is already resolved or unknown.
+    pub(crate) fn cancel(&self, request_id: RequestId) -> bool {
+        let mut requests = self.requests.lock();
+        let request = ward!(requests.get_mut(request_id), return false);
+
+        if request.remainder == 0 {
+            return false;
+        }
+
+        request.remainder = 0;
+        request.is_cancelled = true;
+        request.responses.clear();
+        request.responses.push(Err(ErrorKind::Cancelled));
+
+        // Responders drop the request and its late responses.
+        if let Some(token) = request.token.upgrade() {
+            token.is_cancelled.store(true, Ordering::Relaxed);
+        }
+
+        self.notifier.notify_waiters();
+        true
+    }
+
+    /// Resolves the request with `ErrorKind::LimitExceeded` once the
+    /// deadline is reached. Responses received before are kept for `all`
+    /// requests.
+    fn expire(&self, request_id: RequestId) {
+        let mut requests = self.requests.lock();
+        let request = ward!(requests.get_mut(request_id));
+
+        if request.remainder == 0 {
+            return;
+        }
+
+        if !request.collect_all {
+            request.responses.clear();
+            request.remainder = 1;
+        }
+
+        for _ in 0..request.remainder {
+            request.responses.push(Err(ErrorKind::LimitExceeded));
+        }
+
+        request.remainder = 0;
+        request.is_cancelled = true;
+
+        let ttl = if request.is_default_ttl {
+            "default"
+        } else {
+            "explicit"
+        };
+        increment_counter!("elfo_request_ttl_exceeded_total", "ttl" => ttl);
+
+        // Responders are notified that nobody waits for the response.
+        if let Some(token) = request.token.upgrade() {
+            token.is_cancelled.store(true, Ordering::Relaxed);
+        }
+
+        self.notifier.notify_waiters();
+    }
+
+    /// Returns all requests, which responses haven't been taken yet.
+    pub(crate) fn pending(&self, owner: &OwnedObject) -> Vec<PendingRequest> {
+        let requests = self.requests.lock();
+        requests
+            .iter()
+            .map(|(request_id, request)| PendingRequest {
+                request_id,
+                message_name: request.message_name,
+                recipient: request.recipient,
+                trace_id: request.token.upgrade().map(|token| token.trace_id),
+                created_time: request.created_time,
+                owner: owner.clone(),
+            })
+            .collect()
+    }
+
+    /// Waits for responses until the deadline, if any, see `RequestLimits`.
+    pub(crate) async fn wait(
+        &self,
+        request_id: RequestId,
+        deadline: Option<TokioInstant>,
+    ) -> Responses {
         loop {
             let waiting = self.notifier.notified();
 
@@ -141,17 +258,83 @@ impl RequestTable {
                 }
             }
 
-            waiting.await;
+            if let Some(deadline) = deadline {
+                if tokio::time::timeout_at(deadline, waiting).await.is_err() {
+                    self.expire(request_id);
+                }
+            } else {
+                waiting.await;
+            }
+        }
+    }
+
+    /// Takes responses if the request is resolved.
+    pub(crate) fn try_take(&self, request_id: RequestId) -> Option<Responses> {
+        let mut requests = self.requests.lock();
+        let request = requests.get(request_id).expect("unknown request");
+
+        if request.remainder == 0 {
+            requests.remove(request_id).map(|request| request.responses)
+        } else {
+            None
         }
     }
 
-    pub(crate) fn resolve(
+    /// Waits until any of the requests is resolved, returns its id and
+    /// responses. Every request is expired once its deadline is reached.
+    ///
+    /// # Panics
+    ///
+    /// If `pending` is empty.
+    pub(crate) async fn wait_any(
         &self,
-        mut token: ResponseToken,
-        response: Result<Envelope, RequestError>,
-    ) {
+        pending: impl Iterator<Item = (RequestId, Option<TokioInstant>)> + Clone,
+    ) -> (RequestId, Responses) {
+        loop {
+            let waiting = self.notifier.notified();
+
+            {
+                let mut requests = self.requests.lock();
+                let mut pending = pending.clone().map(|(request_id, _)| request_id);
+                let resolved = pending.find(|request_id| {
+                    let request = requests.get(*request_id).expect("unknown request");
+                    request.remainder == 0
+                });
+
+                if let Some(request_id) = resolved {
+                    let request = requests.remove(request_id).expect("under lock");
+                    break (request_id, request.responses);
+                }
+            }
+
+            let deadline = pending.clone().filter_map(|(_, deadline)| deadline).min();
+
+            if let Some(deadline) = deadline {
+                if tokio::time::timeout_at(deadline, waiting).await.is_err() {
+                    pending
+                        .clone()
+                        .filter(|(_, d)| d.is_some_and(|d| d <= deadline))
+                        .for_each(|(request_id, _)| self.expire(request_id));
+                }
+            } else {
+                assert!(pending.clone().next().is_some(), "nothing to wait");
+                waiting.await;
+            }
+        }
+    }
+
+    pub(crate) fn resolve(&self, mut token: ResponseToken, response: Result<Envelope, ErrorKind>) {
         // Do nothing for forgotten tokens.
         let data = ward!(token.data.take());
+
+        if unlikely(data.is_cancelled.load(Ordering::Relaxed)) {
+            // Errors are produced by dropped tokens, e.g. of dropped requests.
+            if response.is_ok() {
+                increment_counter!("elfo_late_responses_total");
+            }
+            return;
+        }
+
         let mut requests = self.requests.lock();
 
         // `None` here means the request was with `collect_all = false` and
@@ -173,6 +356,8 @@ pub struct ResponseToken<T = AnyMessage> {
     /// `None` if forgotten.
     data: Option<Arc<ResponseTokenData>>,
     received: bool,
+    /// Set by `Context::forward_request()`, used only for dumping.
+    forwarded: bool,
     marker: PhantomData<T>,
 }
 
@@ -181,6 +366,10 @@ struct ResponseTokenData {
     request_id: RequestId,
     trace_id: TraceId,
     book: AddressBook,
+    is_cancelled: AtomicBool,
+    deadline: Option<TokioInstant>,
+    max_response_size: Option<usize>,
+    idempotency_key: Option<IdempotencyKey>,
 }
 
 impl ResponseToken {
@@ -196,8 +385,13 @@ impl ResponseToken {
                 request_id,
                 trace_id,
                 book,
+                is_cancelled: AtomicBool::new(false),
+                deadline: None,
+                max_response_size: None,
+                idempotency_key: None,
             })),
             received: false,
+            forwarded: false,
             marker: PhantomData,
         }
     }
@@ -234,12 +428,73 @@ impl ResponseToken {
         self.data.as_ref().map(Arc::strong_count).unwrap() <= 1
     }
 
+    /// Attaches limits to the just created token. The handling time is
+    /// counted from now.
+    ///
+    /// # Panics
+    /// If the token is forgotten or already duplicated.
+    #[doc(hidden)]
+    pub fn with_limits(mut self, limits: RequestLimits) -> Self {
+        if limits.is_unlimited() {
+            return self;
+        }
+
+        let data = self.data.as_mut().and_then(Arc::get_mut).unwrap();
+        data.deadline = limits
+            .max_handling_time
+            .map(|time| TokioInstant::now() + time);
+        data.max_response_size = limits.max_response_size;
+        self
+    }
+
+    /// Attaches the idempotency key to the just created token.
+    ///
+    /// # Panics
+    /// If the token is forgotten or already duplicated.
+    #[doc(hidden)]
+    pub fn with_idempotency_key(mut self, key: Option<IdempotencyKey>) -> Self {
+        if key.is_none() {
+            return self;
+        }
+
+        let data = self.data.as_mut().and_then(Arc::get_mut).unwrap();
+        data.idempotency_key = key;
+        self
+    }
+
+    /// Returns the key attached by [`RequestBuilder::idempotency_key()`].
+    /// Always `None` for forgotten tokens.
+    ///
+    /// [`RequestBuilder::idempotency_key()`]: crate::RequestBuilder::idempotency_key
+    #[doc(hidden)]
+    #[inline]
+    pub fn idempotency_key(&self) -> Option<IdempotencyKey> {
+        self.data.as_ref().and_then(|data| data.idempotency_key)
+    }
+
+    /// Returns limits left for the request, the handling time is counted
+    /// from now. Used to send requests over the network.
+    ///
+    /// # Panics
+    /// If the token is forgotten.
+    #[doc(hidden)]
+    pub fn limits(&self) -> RequestLimits {
+        let data = self.data.as_ref().unwrap();
+        RequestLimits {
+            max_handling_time: data
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(TokioInstant::now())),
+            max_response_size: data.max_response_size,
+        }
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn into_received<T>(mut self) -> ResponseToken<T> {
         ResponseToken {
             data: self.data.take(),
             received: true,
+            forwarded: false,
             marker: PhantomData,
         }
     }
@@ -250,6 +505,7 @@ impl ResponseToken {
         Self {
             data: self.do_duplicate(),
             received: self.received,
+            forwarded: self.forwarded,
             marker: PhantomData,
         }
     }
@@ -268,7 +524,12 @@ impl ResponseToken {
             let object = data.book.get(data.sender, &guard)?;
             let actor = object.as_actor()?;
             let mut requests = actor.request_table().requests.lock();
-            requests.get_mut(data.request_id)?.remainder += 1;
+            let request = requests.get_mut(data.request_id)?;
+
+            // Cancelled requests are resolved already.
+            if !request.is_cancelled {
+                request.remainder += 1;
+            }
         }
 
         Some(data.clone())
@@ -280,6 +541,7 @@ impl<R> ResponseToken<R> {
         Self {
             data: None,
             received: false,
+            forwarded: false,
             marker: PhantomData,
         }
     }
@@ -288,20 +550,47 @@ impl<R> ResponseToken<R> {
         ResponseToken {
             data: self.data.take(),
             received: self.received,
+            forwarded: self.forwarded,
             marker: PhantomData,
         }
     }
 
+    /// Prepares the received token to be sent to another actor, which becomes
+    /// responsible for responding, see `Context::forward_request()`.
+    pub(crate) fn into_forwarded(mut self) -> ResponseToken {
+        ResponseToken {
+            data: self.data.take(),
+            // Undelivered requests are failed, not ignored.
+            received: false,
+            forwarded: true,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_forwarded(&self) -> bool {
+        self.forwarded
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn is_forgotten(&self) -> bool {
         self.data.is_none()
     }
-}
 
-impl<T> Drop for ResponseToken<T> {
+    /// Returns `None` if forgotten.
+    pub(crate) fn book(&self) -> Option<&AddressBook> {
+        self.data.as_ref().map(|data| &data.book)
+    }
+
+    /// Fails the request with the provided kind of error.
+    #[doc(hidden)]
     #[inline]
-    fn drop(&mut self) {
+    pub fn fail(mut self, kind: ErrorKind) {
+        self.do_fail(kind);
+    }
+
+    fn do_fail(&mut self, kind: ErrorKind) {
         // Do nothing for forgotten tokens.
         let data = ward!(self.data.take());
         let book = data.book.clone();
@@ -310,15 +599,238 @@ impl<T> Drop for ResponseToken<T> {
         let this = ResponseToken {
             data: Some(data),
             received: self.received,
+            forwarded: false,
             marker: PhantomData,
         };
-        let err = if self.received {
-            RequestError::Ignored
+
+        object.respond(this, Err(kind));
+    }
+
+    /// Returns `true` if nobody waits for the response anymore: the request
+    /// is cancelled by the requester (see [`PendingRequest::cancel()`]), its
+    /// handling time is exceeded (see [`RequestLimits`]) or the requester has
+    /// terminated.
+    ///
+    /// It's advisory: responders can check it to stop long handling, because
+    /// the response is going to be dropped anyway. The termination of remote
+    /// requesters isn't tracked, but their deadlines are.
+    pub fn is_cancelled(&self) -> bool {
+        let data = ward!(self.data.as_ref(), return false);
+
+        if self.is_dropped_by_requester() || self.is_expired() {
+            return true;
+        }
+
+        let guard = EbrGuard::new();
+        data.sender.is_local() && data.book.get(data.sender, &guard).is_none()
+    }
+
+    /// Returns `true` if the request is cancelled or expired on the requester
+    /// side, so requests can be dropped from the mailbox.
+    #[inline]
+    pub(crate) fn is_dropped_by_requester(&self) -> bool {
+        let data = ward!(self.data.as_ref(), return false);
+        data.is_cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the handling time of the request is exceeded.
+    #[inline]
+    pub(crate) fn is_expired(&self) -> bool {
+        self.deadline()
+            .is_some_and(|deadline| deadline <= TokioInstant::now())
+    }
+
+    /// Returns the deadline of the request, see [`RequestLimits`].
+    #[inline]
+    pub(crate) fn deadline(&self) -> Option<TokioInstant> {
+        self.data.as_ref().and_then(|data| data.deadline)
+    }
+
+    /// Returns the maximum size of the response, see [`RequestLimits`].
+    #[cfg(feature = "network")]
+    #[inline]
+    pub(crate) fn max_response_size(&self) -> Option<usize> {
+        self.data.as_ref().and_then(|data| data.max_response_size)
+    }
+}
+
+// === RequestLimits ===
+
+/// Limits of the request, attached by [`RequestBuilder::limits()`].
+///
+/// Limits travel with the request, also to remote nodes. Once a limit is
+/// exceeded, the request fails with [`ErrorKind::LimitExceeded`]:
+/// * The handling time is counted since the request is sent. Expired requests
+///   are dropped from the mailbox of the responder, and handlers wrapped into
+///   [`Context::within_deadline()`] are cancelled.
+/// * The response size is measured as encoded by msgpack, oversized responses
+///   are rejected by the responder before transmission. It requires the
+///   `network` feature, otherwise the size isn't checked.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use elfo_core as elfo;
+/// # use elfo::message;
+/// # #[message(ret = Vec<u32>)] struct Select;
+/// # async fn exec(ctx: elfo::Context) {
+/// use elfo::RequestLimits;
+///
+/// let limits = RequestLimits::default()
+///     .max_handling_time(Duration::from_secs(5))
+///     .max_response_size(1 << 20);
+///
+/// let rows = ctx.request(Select).limits(limits).resolve().await;
+/// # }
+/// ```
+///
+/// [`RequestBuilder::limits()`]: crate::RequestBuilder::limits
+/// [`Context::within_deadline()`]: crate::Context::within_deadline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RequestLimits {
+    /// How long the request can be handled, including time in the mailbox.
+    pub max_handling_time: Option<Duration>,
+    /// The maximum size of the encoded response in bytes.
+    pub max_response_size: Option<usize>,
+}
+
+impl RequestLimits {
+    /// Sets [`RequestLimits::max_handling_time`].
+    pub fn max_handling_time(mut self, time: Duration) -> Self {
+        self.max_handling_time = Some(time);
+        self
+    }
+
+    /// Sets [`RequestLimits::max_response_size`].
+    pub fn max_response_size(mut self, size: usize) -> Self {
+        self.max_response_size = Some(size);
+        self
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_handling_time.is_none() && self.max_response_size.is_none()
+    }
+}
+
+cfg_network!({
+    use std::io;
+
+    use crate::{message::Message, scope};
+
+    /// Returns `true` if the message encoded by msgpack exceeds the limit.
+    pub(crate) fn exceeds_response_size<M: Message>(message: &M, limit: usize) -> bool {
+        // Stops encoding once the limit is exceeded.
+        struct Counter(usize, usize);
+
+        impl io::Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0 += buf.len();
+                if self.0 > self.1 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut counter = Counter(0, limit);
+        let _ = scope::with_serde_mode(scope::SerdeMode::Network, || {
+            rmp_serde::encode::write_named(&mut counter, message)
+        });
+        counter.0 > limit
+    }
+});
+
+// === PendingRequest ===
+
+/// A request sent by the actor and not resolved yet,
+/// see [`Context::pending_requests()`].
+///
+/// [`Context::pending_requests()`]: crate::Context::pending_requests
+pub struct PendingRequest {
+    request_id: RequestId,
+    message_name: &'static str,
+    recipient: Option<Addr>,
+    trace_id: Option<TraceId>,
+    created_time: Instant,
+    owner: OwnedObject,
+}
+
+impl PendingRequest {
+    /// Returns the correlation id of the request.
+    #[inline]
+    pub fn request_id(&self) -> RequestId {
+        self.request_id
+    }
+
+    /// Returns the name of the request message.
+    #[inline]
+    pub fn message_name(&self) -> &'static str {
+        self.message_name
+    }
+
+    /// Returns the recipient if the request is sent by `request_to()`,
+    /// `None` if it's routed.
+    #[inline]
+    pub fn recipient(&self) -> Option<Addr> {
+        self.recipient
+    }
+
+    /// Returns the trace id of the request, `None` if it's just resolved.
+    #[inline]
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.trace_id
+    }
+
+    /// Returns the time since the request has been sent.
+    #[inline]
+    pub fn age(&self) -> Duration {
+        self.created_time.elapsed()
+    }
+
+    /// Cancels the request, so it fails with [`ErrorKind::Cancelled`].
+    ///
+    /// If the responder hasn't received the request yet, it's dropped from its
+    /// mailbox. Responses received after cancellation are dropped and counted
+    /// by the `elfo_late_responses_total` metric.
+    ///
+    /// Returns `false` if the request is already resolved.
+    pub fn cancel(&self) -> bool {
+        let actor = self.owner.as_actor().expect("owned by an actor");
+        actor.request_table().cancel(self.request_id)
+    }
+}
+
+impl fmt::Debug for PendingRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingRequest")
+            .field("request_id", &self.request_id)
+            .field("message_name", &self.message_name)
+            .field("recipient", &self.recipient)
+            .field("trace_id", &self.trace_id)
+            .field("age", &self.age())
+            .finish()
+    }
+}
+
+impl<T> Drop for ResponseToken<T> {
+    #[inline]
+    fn drop(&mut self) {
+        let kind = if self.is_expired() {
+            ErrorKind::LimitExceeded
+        } else if self.received {
+            ErrorKind::Ignored
         } else {
-            RequestError::Failed
+            ErrorKind::Failed
         };
 
-        object.respond(this, Err(err));
+        self.do_fail(kind);
     }
 }
 
@@ -399,7 +911,7 @@ mod tests {
                     table1.resolve(request_id, Ok(envelope(addr, request_id, Num(i))));
                 } else {
                     // TODO: test a real `Drop`.
-                    table1.resolve(request_id, Err(RequestError::Ignored));
+                    table1.resolve(request_id, Err(ErrorKind::Ignored));
                 }
             });
         }
@@ -408,7 +920,7 @@ mod tests {
             table.resolve(request_id, Ok(envelope(addr, request_id, Num(0))));
         } else {
             // TODO: test a real `Drop`.
-            table.resolve(request_id, Err(RequestError::Ignored));
+            table.resolve(request_id, Err(ErrorKind::Ignored));
         }
 
         let mut data = table.wait(request_id).await;
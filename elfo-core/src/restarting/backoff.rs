@@ -24,6 +24,11 @@ impl RestartBackoff {
         self.start_time = Instant::now();
     }
 
+    /// The number of restarts since the backoff was last reset.
+    pub(crate) fn restart_count(&self) -> u64 {
+        self.restart_count
+    }
+
     pub(crate) fn next(&mut self, params: &RestartParams) -> Option<Duration> {
         // If an actor is alive enough time, reset the backoff.
         if self.start_time.elapsed() >= params.auto_reset {
@@ -2,11 +2,14 @@
 //!
 //! [Config]: RestartPolicyConfig
 
-use std::{num::NonZeroU64, time::Duration};
+use std::num::NonZeroU64;
 
 use serde::Deserialize;
 
-use crate::restarting::restart_policy::{RestartParams, RestartPolicy};
+use crate::{
+    config::Duration,
+    restarting::restart_policy::{RestartParams, RestartPolicy},
+};
 
 /// Restart policy configuration, `Never` by default.
 ///
@@ -45,16 +48,14 @@ pub enum WhenConfig {
 #[derive(Debug, Clone, Deserialize)]
 pub struct RestartParamsConfig {
     /// Minimal restart time limit.
-    #[serde(with = "humantime_serde")]
     min_backoff: Duration,
     /// Maximum restart time limit.
-    #[serde(with = "humantime_serde")]
     max_backoff: Duration,
     /// The duration of an actor's lifecycle sufficient to deem the actor
     /// healthy.
     ///
     /// The default value is `min_backoff`.
-    #[serde(with = "humantime_serde", default)]
+    #[serde(default)]
     auto_reset: Option<Duration>,
     /// The limit on retry attempts, after which the actor stops attempts to
     /// restart.
@@ -79,9 +80,9 @@ impl RestartPolicyConfig {
 
 impl RestartParamsConfig {
     fn make_params(&self) -> RestartParams {
-        RestartParams::new(self.min_backoff, self.max_backoff)
+        RestartParams::new(self.min_backoff.into(), self.max_backoff.into())
             .factor(self.factor)
-            .auto_reset(self.auto_reset)
+            .auto_reset(self.auto_reset.map(Into::into))
             .max_retries(self.max_retries)
     }
 }
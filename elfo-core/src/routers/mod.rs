@@ -5,9 +5,10 @@ use std::{
 
 use crate::{envelope::Envelope, msg};
 
-pub use self::map::MapRouter;
+pub use self::{map::MapRouter, pool::PoolRouter};
 
 mod map;
+mod pool;
 
 pub trait Router<C>: Send + Sync + 'static {
     type Key: Clone + Hash + Eq + Display + Send + Sync; // TODO: why is `Sync` required?
@@ -41,6 +42,13 @@ pub enum Outcome<T> {
     /// Discards a message.
     /// If a message is discarded by everyone, the sending side gets an error.
     Discard,
+    /// Routes a message to the queue shared by workers of a pooled group,
+    /// it's handled by the first idle worker. See [`ActorGroup::pool()`].
+    ///
+    /// Groups without a pool discard such messages and log an error.
+    ///
+    /// [`ActorGroup::pool()`]: crate::ActorGroup::pool
+    Pool,
     /// Route message using default behaviour.
     /// This behaviour depends on the message type:
     /// - `ValidateConfig` is routed as `Discard`
@@ -65,6 +73,7 @@ impl<T> Outcome<T> {
             }
             Outcome::Broadcast => Outcome::Broadcast,
             Outcome::Discard => Outcome::Discard,
+            Outcome::Pool => Outcome::Pool,
             Outcome::Default => Outcome::Default,
         }
     }
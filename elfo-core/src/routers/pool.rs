@@ -0,0 +1,41 @@
+use super::{Outcome, Router};
+use crate::{envelope::Envelope, msg};
+
+/// The router of pooled groups, see [`ActorGroup::pool()`].
+///
+/// Workers are keyed by their numbers, `0..workers`. All workers are started
+/// with the group, system messages are sent to every worker individually,
+/// all other messages are put into the shared queue.
+///
+/// [`ActorGroup::pool()`]: crate::ActorGroup::pool
+#[derive(Debug, Clone, Copy)]
+pub struct PoolRouter {
+    workers: usize,
+}
+
+impl PoolRouter {
+    pub(crate) fn new(workers: usize) -> Self {
+        assert!(workers > 0, "a pool must have workers");
+        Self { workers }
+    }
+
+    /// Returns the number of workers.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+}
+
+impl<C> Router<C> for PoolRouter {
+    type Key = usize;
+
+    #[inline]
+    fn route(&self, envelope: &Envelope) -> Outcome<Self::Key> {
+        use crate::messages::*;
+
+        msg!(match envelope {
+            UpdateConfig => Outcome::Multicast((0..self.workers).collect()),
+            Terminate | Ping | ValidateConfig => Outcome::Default,
+            _ => Outcome::Pool,
+        })
+    }
+}
@@ -1,6 +1,12 @@
-use std::sync::Arc;
+#[cfg(feature = "dedicated-runtime")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::{fmt, sync::Arc};
 
 use tokio::runtime::Handle;
+#[cfg(feature = "dedicated-runtime")]
+use tokio::runtime::{Builder, Runtime};
+#[cfg(feature = "dedicated-runtime")]
+use tracing::{info, warn};
 
 use crate::actor::ActorMeta;
 #[cfg(feature = "unstable-stuck-detection")]
@@ -9,6 +15,255 @@ use crate::stuck_detection::StuckDetector;
 pub(crate) trait RuntimeFilter: Fn(&ActorMeta) -> bool + Send + Sync + 'static {}
 impl<F: Fn(&ActorMeta) -> bool + Send + Sync + 'static> RuntimeFilter for F {}
 
+// === RuntimeHandle ===
+
+/// A named handle to a runtime, which actors can be placed on,
+/// see [`ActorGroup::placement()`].
+///
+/// Handles are compared by names, so the same name must not be used for
+/// different runtimes.
+///
+/// [`ActorGroup::placement()`]: crate::ActorGroup::placement
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    name: Arc<str>,
+    handle: Handle,
+}
+
+impl RuntimeHandle {
+    /// Creates a named handle. The name is used in logs and
+    /// [`ActorStatusReport`].
+    ///
+    /// [`ActorStatusReport`]: crate::messages::ActorStatusReport
+    pub fn new(name: impl Into<Arc<str>>, handle: Handle) -> Self {
+        Self {
+            name: name.into(),
+            handle,
+        }
+    }
+
+    /// Returns the name of the runtime.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the underlying tokio's handle.
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+}
+
+impl PartialEq for RuntimeHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for RuntimeHandle {}
+
+impl fmt::Debug for RuntimeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RuntimeHandle").field(&self.name).finish()
+    }
+}
+
+// === RuntimeOptions ===
+
+/// Settings of the runtime dedicated to a group,
+/// see [`ActorGroup::dedicated_runtime()`].
+///
+/// [`ActorGroup::dedicated_runtime()`]: crate::ActorGroup::dedicated_runtime
+#[cfg(feature = "dedicated-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dedicated-runtime")))]
+#[derive(Debug, Clone)]
+pub struct RuntimeOptions {
+    /// The number of worker threads.
+    ///
+    /// `1` by default.
+    pub worker_threads: usize,
+    /// The prefix of thread names, the thread number is appended to it.
+    /// Also used as the runtime's name in [`ActorStatusReport`].
+    ///
+    /// The group's name is used if empty.
+    ///
+    /// [`ActorStatusReport`]: crate::messages::ActorStatusReport
+    pub thread_name_prefix: String,
+    /// Pins all threads of the runtime to the specified cores.
+    /// Supported only on Linux, ignored with a warning elsewhere.
+    ///
+    /// Empty by default, threads aren't pinned.
+    pub pin_to_cores: Vec<usize>,
+    /// Sets the `SCHED_FIFO` policy with the specified priority (`1..=99`).
+    /// Requires `CAP_SYS_NICE`, otherwise the policy isn't changed and
+    /// a warning is logged. Supported only on Linux.
+    ///
+    /// `None` by default, the policy isn't changed.
+    pub thread_priority: Option<u8>,
+}
+
+#[cfg(feature = "dedicated-runtime")]
+impl Default for RuntimeOptions {
+    fn default() -> Self {
+        Self {
+            worker_threads: 1,
+            thread_name_prefix: String::new(),
+            pin_to_cores: Vec::new(),
+            thread_priority: None,
+        }
+    }
+}
+
+/// The runtime owned by a group, see [`RuntimeOptions`].
+#[cfg(feature = "dedicated-runtime")]
+pub(crate) struct DedicatedRuntime {
+    handle: RuntimeHandle,
+    runtime: Option<Runtime>,
+}
+
+#[cfg(feature = "dedicated-runtime")]
+impl DedicatedRuntime {
+    pub(crate) fn start(group: &str, mut options: RuntimeOptions) -> Self {
+        if options.thread_name_prefix.is_empty() {
+            options.thread_name_prefix = group.into();
+        }
+
+        info!(group, ?options, "starting a dedicated runtime");
+
+        let prefix = options.thread_name_prefix.clone();
+        let thread_no = AtomicUsize::new(0);
+        let is_warned = Arc::new(AtomicBool::new(false));
+        let options = Arc::new(options);
+
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(options.worker_threads)
+            .thread_name_fn(move || {
+                let no = thread_no.fetch_add(1, Ordering::Relaxed);
+                format!("{prefix}-{no}")
+            })
+            .on_thread_start({
+                let options = options.clone();
+                move || configure_thread(&options, &is_warned)
+            })
+            .enable_all()
+            .build()
+            .unwrap_or_else(|err| panic!("cannot start the runtime of `{group}`: {err}"));
+
+        Self {
+            handle: RuntimeHandle::new(
+                options.thread_name_prefix.clone(),
+                runtime.handle().clone(),
+            ),
+            runtime: Some(runtime),
+        }
+    }
+
+    pub(crate) fn handle(&self) -> &RuntimeHandle {
+        &self.handle
+    }
+}
+
+#[cfg(feature = "dedicated-runtime")]
+impl Drop for DedicatedRuntime {
+    fn drop(&mut self) {
+        // Can be dropped inside async context, where blocking is prohibited.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Called on every thread of the runtime, including blocking ones.
+/// Warnings are logged once per runtime.
+#[cfg(feature = "dedicated-runtime")]
+fn configure_thread(options: &RuntimeOptions, is_warned: &AtomicBool) {
+    let warn_once = |message: &str, error: &dyn fmt::Display| {
+        if !is_warned.swap(true, Ordering::Relaxed) {
+            let runtime = &options.thread_name_prefix;
+            warn!(runtime, error = %error, "{message}");
+        }
+    };
+
+    if !options.pin_to_cores.is_empty() {
+        if let Err(err) = sys::pin_to_cores(&options.pin_to_cores) {
+            warn_once("cannot pin threads to cores", &err);
+        }
+    }
+
+    if let Some(priority) = options.thread_priority {
+        if let Err(err) = sys::set_fifo_priority(priority) {
+            warn_once("cannot set SCHED_FIFO, is CAP_SYS_NICE granted?", &err);
+        }
+    }
+}
+
+#[cfg(all(feature = "dedicated-runtime", target_os = "linux"))]
+mod sys {
+    use std::{io, mem};
+
+    pub(super) fn pin_to_cores(cores: &[usize]) -> io::Result<()> {
+        // SAFETY: `cpu_set_t` is a plain bitmask, zeroed is an empty set.
+        let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+
+        for &core in cores {
+            if core >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("core {core} is out of range"),
+                ));
+            }
+
+            // SAFETY: the core is checked to be in the set's range.
+            unsafe { libc::CPU_SET(core, &mut set) };
+        }
+
+        // SAFETY: the set is initialized, `0` means the current thread.
+        let rc = unsafe { libc::sched_setaffinity(0, mem::size_of_val(&set), &set) };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) fn set_fifo_priority(priority: u8) -> io::Result<()> {
+        let param = libc::sched_param {
+            sched_priority: priority.into(),
+        };
+
+        // SAFETY: the param is initialized, the thread is the current one.
+        let rc =
+            unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(rc))
+        }
+    }
+}
+
+#[cfg(all(feature = "dedicated-runtime", not(target_os = "linux")))]
+mod sys {
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "supported only on Linux")
+    }
+
+    pub(super) fn pin_to_cores(_cores: &[usize]) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(super) fn set_fifo_priority(_priority: u8) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+/// Chooses a runtime for the actor by its key and the group's config,
+/// see [`ActorGroup::placement()`].
+///
+/// [`ActorGroup::placement()`]: crate::ActorGroup::placement
+pub(crate) type Placement<K, C> = Arc<dyn Fn(&K, &C) -> Option<RuntimeHandle> + Send + Sync>;
+
 // === RuntimeManager ===
 
 #[derive(Default, Clone)]
@@ -1,23 +1,33 @@
 #![allow(clippy::declare_interior_mutable_const)] // see tokio#4872
 
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     future::Future,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
+use tracing::{Level, Metadata};
+
 use crate::{
     actor::ActorMeta,
     addr::{Addr, NodeNo},
+    circuit_breaking::CircuitBreakers,
+    compression::CompressionControl,
     config::SystemConfig,
-    dumping::DumpingControl,
+    deprecation::{Deprecations, Usage},
+    dumping::{self, Dumper, DumpingControl, SequenceNo},
+    envelope::Envelope,
     logging::_priv::LoggingControl,
+    message::{Message, MessageTypeId},
     permissions::{AtomicPermissions, Permissions},
+    rate_limiting::RateLimiters,
     telemetry::config::TelemetryConfig,
-    tracing::TraceId,
+    tracing::{DetailedBudget, FanOutLimits, TraceId},
+    ttl::DefaultTtls,
 };
 
 tokio::task_local! {
@@ -27,6 +37,13 @@ tokio::task_local! {
 #[derive(Clone)]
 pub struct Scope {
     trace_id: Cell<TraceId>,
+    /// The trace marked by `force_sampling()`.
+    force_sampled: Cell<Option<TraceId>>,
+    sequence_no: Cell<Option<SequenceNo>>,
+    /// The dumper of the overridden class, see `with_dump_class()`.
+    dumper: Cell<Option<&'static Dumper>>,
+    /// Values attached to the current trace by `set_baggage()`.
+    baggage: RefCell<Baggage>,
     actor: Arc<ScopeActorShared>,
     group: Arc<ScopeGroupShared>,
 }
@@ -51,6 +68,10 @@ impl Scope {
     ) -> Self {
         Self {
             trace_id: Cell::new(trace_id),
+            force_sampled: Cell::new(None),
+            sequence_no: Cell::new(None),
+            dumper: Cell::new(None),
+            baggage: RefCell::default(),
             actor: Arc::new(ScopeActorShared::new(addr, meta)),
             group,
         }
@@ -102,6 +123,163 @@ impl Scope {
         self.trace_id.set(trace_id);
     }
 
+    /// Returns the sequence number of the message being handled.
+    ///
+    /// The number is the same as the one of the incoming dump of the message,
+    /// so it can be used to join logs and dumps. Returns `None` if no message
+    /// has been received yet.
+    #[inline]
+    pub fn sequence_no(&self) -> Option<SequenceNo> {
+        self.sequence_no.get()
+    }
+
+    #[inline]
+    pub(crate) fn set_sequence_no(&self, sequence_no: SequenceNo) {
+        self.sequence_no.set(Some(sequence_no));
+    }
+
+    /// Returns the dumping class overridden by [`with_dump_class()`]
+    /// or [`ActorGroup::dump_class_by()`], if any.
+    ///
+    /// [`ActorGroup::dump_class_by()`]: crate::ActorGroup::dump_class_by
+    #[inline]
+    pub fn dump_class(&self) -> Option<&'static str> {
+        self.dumper.get().map(Dumper::class)
+    }
+
+    #[cfg(feature = "dumping")]
+    #[inline]
+    pub(crate) fn dumper(&self) -> Option<&'static Dumper> {
+        self.dumper.get()
+    }
+
+    #[inline]
+    pub(crate) fn replace_dumper(
+        &self,
+        dumper: Option<&'static Dumper>,
+    ) -> Option<&'static Dumper> {
+        self.dumper.replace(dumper)
+    }
+
+    /// Marks the current trace as detailed: always sampled for dumping and
+    /// logging, dumped bypassing rate limits and logged with at least `Debug`
+    /// level. The mark is propagated with messages sent in this trace,
+    /// including ones sent to other nodes.
+    #[inline]
+    pub fn force_sampling(&self) {
+        self.force_sampled.set(Some(self.trace_id()));
+    }
+
+    /// Returns `true` if the current trace is marked as detailed.
+    #[inline]
+    pub fn is_force_sampled(&self) -> bool {
+        self.is_trace_force_sampled(self.trace_id())
+    }
+
+    #[inline]
+    pub(crate) fn is_trace_force_sampled(&self, trace_id: TraceId) -> bool {
+        self.force_sampled.get() == Some(trace_id)
+    }
+
+    /// Attaches the value to the current trace, replacing the previous value
+    /// of the same key. Baggage is local to the actor and is reset once the
+    /// trace id changes, e.g. when the next message is received.
+    ///
+    /// Useful to correlate logs with external ids, see the logger's meta
+    /// enricher.
+    pub fn set_baggage(&self, key: &'static str, value: impl Into<Arc<str>>) {
+        let trace_id = self.trace_id();
+        let mut baggage = self.baggage.borrow_mut();
+
+        if baggage.trace_id != Some(trace_id) {
+            baggage.trace_id = Some(trace_id);
+            baggage.items.clear();
+        }
+
+        let value = value.into();
+        match baggage.items.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => baggage.items.push((key, value)),
+        }
+    }
+
+    /// Returns the value attached to the current trace by
+    /// [`Scope::set_baggage()`].
+    pub fn baggage(&self, key: &str) -> Option<Arc<str>> {
+        let baggage = self.baggage.borrow();
+
+        if baggage.trace_id != Some(self.trace_id()) {
+            return None;
+        }
+
+        baggage
+            .items
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    /// Checks whether a message of the class can be dumped in the current
+    /// trace, taking into account both trace sampling and rate limiting.
+    #[inline]
+    #[stability::unstable]
+    #[doc(hidden)]
+    pub fn check_dumping(&self, class: &'static str) -> crate::dumping::CheckResult {
+        if self.is_force_sampled() {
+            self.group.dumping.check_detailed(class)
+        } else {
+            self.group
+                .dumping
+                .check_in_trace(class, Some(self.trace_id()))
+        }
+    }
+
+    /// Checks whether an event can be logged in the current trace, taking
+    /// into account both trace sampling and rate limiting.
+    ///
+    /// The maximum level isn't checked here, see [`Scope::is_logging_enabled()`].
+    #[inline]
+    #[stability::unstable]
+    #[doc(hidden)]
+    pub fn check_logging(&self, meta: &Metadata<'_>) -> crate::logging::_priv::CheckResult {
+        self.group
+            .logging
+            .check_in_trace(meta, self.sampled_trace_id())
+    }
+
+    /// Returns `None` if the current trace should be sampled anyway.
+    fn sampled_trace_id(&self) -> Option<TraceId> {
+        let trace_id = self.trace_id();
+        (!self.is_trace_force_sampled(trace_id)).then_some(trace_id)
+    }
+
+    /// Checks whether the level of logging is enabled by permissions, which
+    /// are lowered to `Debug` in detailed traces.
+    #[inline]
+    #[stability::unstable]
+    #[doc(hidden)]
+    pub fn is_logging_enabled(&self, level: Level) -> bool {
+        self.permissions().is_logging_enabled(level)
+            || (level <= Level::DEBUG && self.is_force_sampled())
+    }
+
+    /// Returns `true` if a trace started in the current group should be
+    /// detailed, see `system.tracing.detailed_budget`.
+    #[inline]
+    pub(crate) fn try_select_detailed(&self) -> bool {
+        self.group.detailed_budget.try_select()
+    }
+
+    /// Counts the message sent within the current trace.
+    /// Returns `false` if the trace has exceeded its budget,
+    /// see `system.tracing.fan_out`.
+    #[inline]
+    pub(crate) fn spend_trace_budget(&self, protocol: &str) -> bool {
+        self.group
+            .fan_out
+            .spend(self.trace_id(), self.meta(), protocol)
+    }
+
     /// Returns the current permissions (for logging, telemetry and so on).
     #[inline]
     pub fn permissions(&self) -> Permissions {
@@ -124,6 +302,41 @@ impl Scope {
         &self.group.dumping
     }
 
+    #[inline]
+    pub(crate) fn circuit_breakers(&self) -> &CircuitBreakers {
+        &self.group.circuit_breakers
+    }
+
+    #[inline]
+    pub(crate) fn rate_limiters(&self) -> &RateLimiters {
+        &self.group.rate_limiters
+    }
+
+    #[inline]
+    #[cfg(feature = "compression")]
+    pub(crate) fn compression(&self) -> crate::compression::config::CompressionConfig {
+        self.group.compression.get()
+    }
+
+    /// Returns the default TTL of messages of the type sent by the group,
+    /// see `system.ttl`.
+    #[inline]
+    pub(crate) fn default_ttl(&self, type_id: MessageTypeId) -> Option<Duration> {
+        self.group.default_ttls.get(type_id)
+    }
+
+    /// Counts the use of the deprecated message by the group and warns about
+    /// it. Returns `false` if sending is rejected, see `system.deprecation`.
+    #[inline]
+    pub(crate) fn use_deprecated(
+        &self,
+        usage: Usage,
+        message: &impl Message,
+        note: &'static str,
+    ) -> bool {
+        self.group.deprecations.on_used(usage, message, note)
+    }
+
     #[doc(hidden)]
     #[stability::unstable]
     pub fn increment_allocated_bytes(&self, by: usize) {
@@ -157,6 +370,12 @@ impl Scope {
     }
 }
 
+#[derive(Clone, Default)]
+struct Baggage {
+    trace_id: Option<TraceId>,
+    items: Vec<(&'static str, Arc<str>)>,
+}
+
 struct ScopeActorShared {
     addr: Addr,
     meta: Arc<ActorMeta>,
@@ -204,6 +423,13 @@ pub(crate) struct ScopeGroupShared {
     permissions: AtomicPermissions,
     logging: LoggingControl,
     dumping: DumpingControl,
+    circuit_breakers: CircuitBreakers,
+    rate_limiters: RateLimiters,
+    compression: CompressionControl,
+    default_ttls: DefaultTtls,
+    deprecations: Deprecations,
+    detailed_budget: DetailedBudget,
+    fan_out: FanOutLimits,
 }
 
 assert_impl_all!(ScopeGroupShared: Send, Sync);
@@ -216,9 +442,25 @@ impl ScopeGroupShared {
             permissions: Default::default(), // everything is disabled
             logging: Default::default(),
             dumping: Default::default(),
+            circuit_breakers: Default::default(),
+            rate_limiters: Default::default(),
+            compression: Default::default(),
+            default_ttls: Default::default(),
+            deprecations: Default::default(),
+            detailed_budget: Default::default(),
+            fan_out: Default::default(),
         }
     }
 
+    pub(crate) fn circuit_breakers(&self) -> &CircuitBreakers {
+        &self.circuit_breakers
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn set_dump_capture(&self, capture: Arc<crate::dumping::capture::DumpCapture>) {
+        self.dumping.set_capture(capture);
+    }
+
     pub(crate) fn configure(&self, config: &SystemConfig) {
         // Update the logging subsystem.
         self.logging.configure(&config.logging);
@@ -226,6 +468,26 @@ impl ScopeGroupShared {
         // Update the dumping subsystem.
         self.dumping.configure(&config.dumping);
 
+        // Update circuit breakers.
+        self.circuit_breakers.configure(&config.circuit_breaker);
+
+        // Update outbound rate limiters.
+        self.rate_limiters.configure(&config.rate_limiter);
+
+        // Update compression of `Compressed` fields.
+        self.compression.configure(&config.compression);
+
+        // Update default TTLs of messages and requests.
+        self.default_ttls.configure(&config.ttl);
+
+        // Update reporting of deprecated messages.
+        self.deprecations.configure(&config.deprecation);
+
+        // Update the tracing subsystem.
+        self.detailed_budget
+            .configure(config.tracing.detailed_budget);
+        self.fan_out.configure(&config.tracing.fan_out);
+
         // Update permissions.
         let mut perm = self.permissions.load();
         perm.set_logging_enabled(config.logging.max_level.into());
@@ -300,6 +562,26 @@ pub fn try_set_trace_id(trace_id: TraceId) -> bool {
     try_with(|scope| scope.set_trace_id(trace_id)).is_some()
 }
 
+/// Marks the current trace as always sampled for dumping and logging.
+/// See [`Scope::force_sampling()`] for details.
+///
+/// # Panics
+/// This function will panic if called ouside the actor system.
+#[inline]
+pub fn force_sampling() {
+    with(Scope::force_sampling);
+}
+
+/// Marks the envelope starting a new trace as detailed if it fits into the
+/// budget of the current group, see `system.tracing.detailed_budget`.
+/// Called by sources.
+#[inline]
+pub(crate) fn select_detailed(envelope: &mut Envelope) {
+    if try_with(Scope::try_select_detailed).unwrap_or(false) {
+        envelope.set_force_sampled();
+    }
+}
+
 /// Returns the current object's meta.
 ///
 /// # Panics
@@ -315,6 +597,15 @@ pub fn try_meta() -> Option<Arc<ActorMeta>> {
     try_with(|scope| scope.meta().clone())
 }
 
+/// Returns the sequence number of the message being handled.
+/// See [`Scope::sequence_no()`] for details.
+///
+/// Returns `None` if called outside the actor system.
+#[inline]
+pub fn sequence_no() -> Option<SequenceNo> {
+    try_with(|scope| scope.sequence_no()).flatten()
+}
+
 /// Returns the node number.
 ///
 /// # Panics
@@ -330,6 +621,88 @@ pub fn try_node_no() -> Option<NodeNo> {
     try_with(|scope| scope.node_no())
 }
 
+/// Attaches the value to the current trace, see [`Scope::set_baggage()`].
+/// Does nothing if called outside the actor system.
+#[inline]
+pub fn set_baggage(key: &'static str, value: impl Into<Arc<str>>) {
+    try_with(|scope| scope.set_baggage(key, value));
+}
+
+/// Returns the value attached to the current trace, see
+/// [`Scope::baggage()`]. Returns `None` if called outside the actor system.
+#[inline]
+pub fn baggage(key: &str) -> Option<Arc<str>> {
+    try_with(|scope| scope.baggage(key)).flatten()
+}
+
+/// Overrides the dumping class of messages sent and handled by the current
+/// actor while running the provided function. Nested overrides take
+/// precedence over outer ones. Classes are used to filter, rate limit and
+/// write dumps to separate files, see `elfo-dumper`.
+///
+/// The class must be a static string, e.g. a constant or an interned string.
+/// Dumpers are created once per class, so the number of classes must be
+/// bounded.
+///
+/// Use [`within_dump_class()`] to override the class for async code.
+/// Does nothing if called outside the actor system.
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// # #[elfo::message] struct Quote;
+/// # async fn exec(ctx: elfo::Context) {
+/// elfo::scope::with_dump_class("venue_binance", || {
+///     let _ = ctx.try_send(Quote);
+/// });
+/// # }
+/// ```
+#[inline]
+pub fn with_dump_class<R>(class: &'static str, f: impl FnOnce() -> R) -> R {
+    // We use a guard here to restore the previous class even on panics.
+    struct Guard(Option<&'static Dumper>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            try_with(|scope| scope.replace_dumper(self.0));
+        }
+    }
+
+    let dumper = dumping::dumper_of(class);
+    let Some(prev) = try_with(|scope| scope.replace_dumper(Some(dumper))) else {
+        return f();
+    };
+
+    let _guard = Guard(prev);
+    f()
+}
+
+/// Overrides the dumping class of messages sent and handled by the current
+/// actor while polling the provided future.
+/// See [`with_dump_class()`] for details.
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// # #[elfo::message] struct Quote;
+/// # async fn exec(ctx: elfo::Context) {
+/// elfo::scope::within_dump_class("venue_binance", async {
+///     let _ = ctx.send(Quote).await;
+/// })
+/// .await;
+/// # }
+/// ```
+pub async fn within_dump_class<F: Future>(class: &'static str, future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| with_dump_class(class, || future.as_mut().poll(cx))).await
+}
+
+/// Returns the overridden dumping class if inside the actor system.
+/// See [`Scope::dump_class()`] for details.
+#[inline]
+pub fn dump_class() -> Option<&'static str> {
+    try_with(|scope| scope.dump_class()).flatten()
+}
+
 thread_local! {
     static SERDE_MODE: Cell<SerdeMode> = const { Cell::new(SerdeMode::Normal) };
 }
@@ -0,0 +1,162 @@
+//! Local delivery of messages sent by an actor to itself, see
+//! [`Context::send_to_self()`].
+//!
+//! [`Context::send_to_self()`]: crate::Context::send_to_self
+
+use std::collections::VecDeque;
+
+use crate::{concurrency, envelope::Envelope, mailbox::RecvResult};
+
+/// Configures the queue of messages sent by [`Context::send_to_self()`] in
+/// every actor of the group, see [`ActorGroup::self_queue()`].
+///
+/// [`Context::send_to_self()`]: crate::Context::send_to_self
+/// [`ActorGroup::self_queue()`]: crate::ActorGroup::self_queue
+#[derive(Debug, Clone, Copy)]
+pub struct SelfQueue {
+    capacity: usize,
+    priority: SelfQueuePriority,
+}
+
+/// When messages of the self queue are received relative to the mailbox.
+/// System messages (e.g. `Terminate`) at the head of the mailbox are always
+/// received first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfQueuePriority {
+    /// Queued messages are received before ones in the mailbox and sources.
+    BeforeMailbox,
+    /// Queued messages are received only if the mailbox is empty, but still
+    /// before sources.
+    AfterMailbox,
+}
+
+impl SelfQueue {
+    /// Stores at most `capacity` messages, others go through the mailbox.
+    /// Zero disables the queue at all.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            priority: SelfQueuePriority::BeforeMailbox,
+        }
+    }
+
+    /// Sets the priority relative to the mailbox.
+    ///
+    /// [`SelfQueuePriority::BeforeMailbox`] by default.
+    pub fn priority(mut self, priority: SelfQueuePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Default for SelfQueue {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl From<usize> for SelfQueue {
+    fn from(capacity: usize) -> Self {
+        Self::new(capacity)
+    }
+}
+
+/// Envelopes sent to self, owned by the actor's context.
+/// Disabled by default, e.g. for pruned contexts.
+pub(crate) struct SelfEnvelopes {
+    config: SelfQueue,
+    queue: VecDeque<Envelope>,
+    /// A mailbox envelope received ahead of the queued ones, which must be
+    /// returned before any other mailbox envelope.
+    stashed: Option<Envelope>,
+    /// Set once the mailbox is closed, new envelopes are rejected then.
+    is_closed: bool,
+}
+
+// SAFETY: envelopes are accessed only through `&mut self`, so sharing
+// `&SelfEnvelopes` between threads never exposes them.
+unsafe impl Sync for SelfEnvelopes {}
+
+impl Default for SelfEnvelopes {
+    fn default() -> Self {
+        Self::new(SelfQueue::new(0))
+    }
+}
+
+impl SelfEnvelopes {
+    pub(crate) fn new(config: SelfQueue) -> Self {
+        Self {
+            config,
+            queue: VecDeque::new(),
+            stashed: None,
+            is_closed: false,
+        }
+    }
+
+    /// Always `true` for the disabled queue and after closing the mailbox.
+    pub(crate) fn is_full(&self) -> bool {
+        self.is_closed || self.queue.len() >= self.config.capacity
+    }
+
+    pub(crate) fn push(&mut self, envelope: Envelope) {
+        debug_assert!(!self.is_full());
+        self.queue.push_back(envelope);
+    }
+
+    /// Returns the next envelope if it shouldn't be received from the mailbox
+    /// as usual. `try_recv` polls the mailbox without waiting.
+    pub(crate) fn pop(
+        &mut self,
+        try_recv: impl FnOnce() -> Option<RecvResult>,
+    ) -> Option<Envelope> {
+        if self.queue.is_empty() {
+            return self.stashed.take();
+        }
+
+        if self.stashed.is_none() {
+            match try_recv() {
+                Some(RecvResult::Data(envelope)) => {
+                    if concurrency::is_barrier(&envelope)
+                        || self.config.priority == SelfQueuePriority::AfterMailbox
+                    {
+                        return Some(envelope);
+                    }
+
+                    self.stashed = Some(envelope);
+                }
+                // Queued envelopes are received like ones left in the mailbox,
+                // `Closed` is returned later by the mailbox again.
+                Some(RecvResult::Closed(_)) => self.is_closed = true,
+                None => {}
+            }
+        }
+
+        self.queue.pop_front()
+    }
+
+    /// Calls `f` with the envelope that `pop()` would return, or, if `None`,
+    /// with one of the mailbox. `peek_mailbox` returns whether its head is
+    /// a barrier along with `f` applied to it.
+    pub(crate) fn peek<R>(
+        &self,
+        peek_mailbox: impl FnOnce() -> Option<(bool, R)>,
+        f: impl FnOnce(&Envelope) -> R,
+    ) -> Option<R> {
+        if self.queue.is_empty() {
+            return match &self.stashed {
+                Some(envelope) => Some(f(envelope)),
+                None => peek_mailbox().map(|(_, result)| result),
+            };
+        }
+
+        if self.stashed.is_none() {
+            if let Some((is_barrier, result)) = peek_mailbox() {
+                if is_barrier || self.config.priority == SelfQueuePriority::AfterMailbox {
+                    return Some(result);
+                }
+            }
+        }
+
+        self.queue.front().map(f)
+    }
+}
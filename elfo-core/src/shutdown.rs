@@ -0,0 +1,58 @@
+use derive_more::Display;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// Why the system is shut down, see [`init::try_run()`].
+///
+/// [`init::try_run()`]: crate::init::try_run
+#[derive(Debug, Display, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ShutdownReason {
+    /// `SIGTERM`, `SIGINT` or `Ctrl-C` is received.
+    #[display("signal")]
+    Signal,
+    /// The maximum memory usage is reached.
+    #[display("out of memory")]
+    OutOfMemory,
+    /// Requested by [`Context::initiate_shutdown()`] or
+    /// [`Topology::initiate_shutdown()`].
+    ///
+    /// [`Context::initiate_shutdown()`]: crate::Context::initiate_shutdown
+    /// [`Topology::initiate_shutdown()`]: crate::Topology::initiate_shutdown
+    #[display("requested: {_0}")]
+    Requested(String),
+}
+
+/// The shutdown state of the node, shared via the address book.
+#[derive(Default)]
+pub(crate) struct Shutdown {
+    reason: OnceCell<ShutdownReason>,
+    notify: Notify,
+}
+
+impl Shutdown {
+    /// Initiates shutdown, only the first reason is kept.
+    /// Returns `false` if shutdown has already been initiated.
+    pub(crate) fn initiate(&self, reason: ShutdownReason) -> bool {
+        let is_first = self.reason.set(reason).is_ok();
+        if is_first {
+            self.notify.notify_waiters();
+        }
+        is_first
+    }
+
+    /// Waits until shutdown is initiated and returns its reason.
+    pub(crate) async fn initiated(&self) -> ShutdownReason {
+        loop {
+            // Register before checking in order not to miss the notification.
+            let notified = self.notify.notified();
+
+            if let Some(reason) = self.reason.get() {
+                return reason.clone();
+            }
+
+            notified.await;
+        }
+    }
+}
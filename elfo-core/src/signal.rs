@@ -18,6 +18,7 @@ use tokio::signal::windows;
 use crate::{
     envelope::{Envelope, MessageKind},
     message::Message,
+    scope,
     source::{SourceArc, SourceStream, UnattachedSource},
     tracing::TraceId,
     Addr,
@@ -204,7 +205,8 @@ impl<M: Message> SourceStream for SignalSource<M> {
         let message = this.message.clone();
         let kind = MessageKind::regular(Addr::NULL);
         let trace_id = TraceId::generate();
-        let envelope = Envelope::with_trace_id(message, kind, trace_id);
+        let mut envelope = Envelope::with_trace_id(message, kind, trace_id);
+        scope::select_detailed(&mut envelope);
         Poll::Ready(Some(envelope))
     }
 }
@@ -0,0 +1,84 @@
+//! Deterministic simulation of scheduling, used by `elfo::test::simulate()`.
+//!
+//! If enabled for the current thread, actors yield to the executor a seeded
+//! number of times before taking the next message and multicast deliveries
+//! are shuffled. Thus, a single-threaded runtime explores different
+//! interleavings of messages between groups, which are replayed exactly by
+//! reusing the same seed.
+//!
+//! Only actors polled by the thread entered the simulation are affected.
+
+use std::cell::Cell;
+
+thread_local! {
+    static STATE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The maximum number of yields before taking a message.
+const MAX_YIELDS: u64 = 3;
+
+/// Enables the simulation for the current thread until the guard is dropped.
+///
+/// # Panics
+/// If the simulation is already enabled for the current thread.
+pub fn enter(seed: u64) -> SimulationGuard {
+    STATE.with(|state| {
+        assert!(state.get().is_none(), "nested simulations are forbidden");
+        state.set(Some(seed));
+    });
+
+    SimulationGuard(())
+}
+
+/// Disables the simulation on drop, see [`enter()`].
+#[must_use]
+pub struct SimulationGuard(());
+
+impl Drop for SimulationGuard {
+    fn drop(&mut self) {
+        STATE.with(|state| state.set(None));
+    }
+}
+
+/// Returns whether the simulation is enabled for the current thread.
+#[inline]
+pub fn is_active() -> bool {
+    STATE.with(|state| state.get().is_some())
+}
+
+// SplitMix64, which is enough to choose interleavings.
+fn next() -> Option<u64> {
+    STATE.with(|state| {
+        let mut x = state.get()?.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state.set(Some(x));
+
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Some(x ^ (x >> 31))
+    })
+}
+
+/// Yields to the executor a seeded number of times, so other ready actors
+/// can be polled before the current one takes the next message.
+/// Does nothing if the simulation is disabled.
+pub(crate) async fn perturb() {
+    let yields = ward!(next()) % (MAX_YIELDS + 1);
+
+    for _ in 0..yields {
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Shuffles items in a seeded way, used for multicast deliveries.
+/// Does nothing if the simulation is disabled.
+pub(crate) fn shuffle<T>(items: &mut [T]) {
+    if items.len() < 2 || !is_active() {
+        return;
+    }
+
+    // Fisher-Yates.
+    for i in (1..items.len()).rev() {
+        let j = ward!(next()) % (i as u64 + 1);
+        items.swap(i, j as usize);
+    }
+}
@@ -0,0 +1,211 @@
+//! Limits the number of actors of a group being initialized simultaneously,
+//! see `system.spawn_concurrency`.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use metrics::{decrement_gauge, increment_gauge};
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use elfo_utils::time::Instant;
+
+use crate::config::SystemConfig;
+
+/// Why an actor is spawned, it affects the position in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpawnPriority {
+    /// Spawned by re-routing, on mounting and restarts.
+    Background,
+    /// Spawned by a request, can jump the queue if configured.
+    Request,
+}
+
+#[derive(Default)]
+pub(crate) struct SpawnThrottle {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// `None` means unlimited.
+    limit: Option<usize>,
+    requests_first: bool,
+    /// The number of granted permits.
+    active: usize,
+    requests: VecDeque<oneshot::Sender<SpawnPermit>>,
+    background: VecDeque<oneshot::Sender<SpawnPermit>>,
+}
+
+impl State {
+    fn has_free_slot(&self) -> bool {
+        self.limit.map_or(true, |limit| self.active < limit)
+    }
+}
+
+impl SpawnThrottle {
+    pub(crate) fn configure(self: &Arc<Self>, config: &SystemConfig) {
+        let mut state = self.state.lock();
+        state.limit = config.spawn_concurrency;
+        state.requests_first = config.spawn_requests_first;
+
+        // The limit can be increased or removed.
+        self.grant(&mut state);
+    }
+
+    /// Waits for a free slot. The returned permit must be held while the actor
+    /// is initializing.
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: SpawnPriority) -> SpawnPermit {
+        let rx = {
+            let mut state = self.state.lock();
+
+            if state.has_free_slot() {
+                state.active += 1;
+                return SpawnPermit::new(self.clone());
+            }
+
+            let (tx, rx) = oneshot::channel();
+            if priority == SpawnPriority::Request && state.requests_first {
+                state.requests.push_back(tx);
+            } else {
+                state.background.push_back(tx);
+            }
+            rx
+        };
+
+        let queued_at = Instant::now();
+        increment_gauge!("elfo_spawn_queued_actors", 1.);
+        // The sender is dropped only with the throttle, which outlives actors.
+        let permit = rx.await.expect("spawn throttle is dropped");
+        decrement_gauge!("elfo_spawn_queued_actors", 1.);
+
+        if let Some(recorder) = metrics::try_recorder() {
+            let key = metrics::Key::from_static_name("elfo_spawn_wait_time_seconds");
+            recorder.record_histogram(&key, Instant::now().secs_f64_since(queued_at));
+        }
+
+        permit
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.active -= 1;
+        self.grant(&mut state);
+    }
+
+    fn grant(self: &Arc<Self>, state: &mut State) {
+        while state.has_free_slot() {
+            let Some(tx) = state
+                .requests
+                .pop_front()
+                .or_else(|| state.background.pop_front())
+            else {
+                break;
+            };
+
+            state.active += 1;
+            if let Err(mut permit) = tx.send(SpawnPermit::new(self.clone())) {
+                // The waiting actor is gone, try the next one. The permit
+                // mustn't be released here, because the lock is held.
+                permit.throttle = None;
+                state.active -= 1;
+            }
+        }
+    }
+}
+
+/// Occupies a slot of the throttle until dropped.
+pub(crate) struct SpawnPermit {
+    throttle: Option<Arc<SpawnThrottle>>,
+}
+
+impl SpawnPermit {
+    fn new(throttle: Arc<SpawnThrottle>) -> Self {
+        Self {
+            throttle: Some(throttle),
+        }
+    }
+}
+
+impl Drop for SpawnPermit {
+    fn drop(&mut self) {
+        if let Some(throttle) = self.throttle.take() {
+            throttle.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn throttle(limit: usize, requests_first: bool) -> Arc<SpawnThrottle> {
+        let throttle = Arc::new(SpawnThrottle::default());
+        let config = SystemConfig {
+            spawn_concurrency: Some(limit),
+            spawn_requests_first: requests_first,
+            ..SystemConfig::default()
+        };
+        throttle.configure(&config);
+        throttle
+    }
+
+    #[tokio::test]
+    async fn fifo() {
+        let throttle = throttle(1, false);
+
+        let first = throttle.acquire(SpawnPriority::Background).await;
+        let mut second = Box::pin(throttle.acquire(SpawnPriority::Background));
+        let mut third = Box::pin(throttle.acquire(SpawnPriority::Request));
+        assert!((&mut second).now_or_never().is_none());
+        assert!((&mut third).now_or_never().is_none());
+
+        drop(first);
+        let second = second.await;
+        assert!((&mut third).now_or_never().is_none());
+        drop(second);
+        third.await;
+    }
+
+    #[tokio::test]
+    async fn requests_first() {
+        let throttle = throttle(1, true);
+
+        let first = throttle.acquire(SpawnPriority::Background).await;
+        let mut background = Box::pin(throttle.acquire(SpawnPriority::Background));
+        let mut request = Box::pin(throttle.acquire(SpawnPriority::Request));
+        assert!((&mut background).now_or_never().is_none());
+        assert!((&mut request).now_or_never().is_none());
+
+        drop(first);
+        let request = request.await;
+        assert!((&mut background).now_or_never().is_none());
+        drop(request);
+        background.await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gone_waiters_and_reconfiguration() {
+        let throttle = throttle(1, false);
+
+        let first = throttle.acquire(SpawnPriority::Background).await;
+        let gone = throttle.acquire(SpawnPriority::Background);
+        assert!(tokio::time::timeout(Duration::from_secs(1), gone)
+            .await
+            .is_err());
+        let mut waiting = Box::pin(throttle.acquire(SpawnPriority::Background));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        // The limit is removed, so waiters are granted.
+        throttle.configure(&SystemConfig::default());
+        let second = waiting.await;
+        assert_eq!(throttle.state.lock().active, 2);
+
+        drop(first);
+        drop(second);
+        assert_eq!(throttle.state.lock().active, 0);
+    }
+}
@@ -255,7 +255,14 @@ where
                     trace_id
                 });
 
-                Poll::Ready(Some(message.pack(trace_id)))
+                let mut envelope = message.pack(trace_id);
+
+                // The trace has been started by the stream.
+                if *this.rewrite_trace_id {
+                    scope::select_detailed(&mut envelope);
+                }
+
+                Poll::Ready(Some(envelope))
             }
             Poll::Ready(None) => Poll::Ready(None),
             Poll::Pending => {
@@ -1,4 +1,13 @@
-use std::{future::Future, mem, ops::Deref, sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    mem,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use dashmap::DashMap;
 use futures::future::BoxFuture;
@@ -7,27 +16,39 @@ use metrics::{decrement_gauge, increment_gauge};
 use parking_lot::RwLock;
 use tracing::{debug, error, error_span, info, warn, Instrument, Span};
 
-use elfo_utils::CachePadded;
+use elfo_utils::{time::SystemTime, unlikely, CachePadded};
 
 use self::{error_chain::ErrorChain, measure_poll::MeasurePoll};
 use crate::{
-    actor::{Actor, ActorMeta, ActorStartInfo},
+    actor::{Actor, ActorMeta, ActorStartInfo, DrainTarget},
     actor_status::ActorStatus,
     addr::{Addr, NodeNo},
-    config::{AnyConfig, Config, SystemConfig},
-    context::Context,
-    envelope::Envelope,
+    admission::{AdmissionPolicies, AdmissionPolicy},
+    audit::{ActorAudit, AuditLog},
+    concurrency::Concurrency,
+    config::{system::mailbox::MailboxConfig, AnyConfig, ByteSize, Config, SystemConfig},
+    context::{dumper, Context},
+    dedup::{Dedup, FilterFactory},
+    dumping::{Direction, Dump, DumpClassifier},
+    envelope::{Envelope, MessageKind},
     exec::{Exec, ExecResult},
-    group::TerminationPolicy,
-    message::Request,
+    group::{MountCondition, TerminationPolicy},
+    idempotency::IdempotencyCache,
+    intercept::Interceptors,
+    message::{self, AnyMessage, Message as _, Request},
     messages, msg,
     object::{GroupVisitor, Object, OwnedObject},
-    panic,
+    panics,
+    poisoning::AfterPanic,
+    pool::{Pool, StickyKey},
     restarting::{RestartBackoff, RestartPolicy},
     routers::{Outcome, Router},
-    runtime::RuntimeManager,
+    runtime::{Placement, RuntimeManager},
     scope::{self, Scope, ScopeGroupShared},
+    self_queue::SelfQueue,
+    spawn_throttle::{SpawnPriority, SpawnThrottle},
     subscription::SubscriptionManager,
+    topology::Member,
     tracing::TraceId,
     ResponseToken,
 };
@@ -39,6 +60,7 @@ pub(crate) struct Supervisor<R: Router<C>, C, X> {
     meta: Arc<ActorMeta>,
     restart_policy: RestartPolicy,
     termination_policy: TerminationPolicy,
+    mailbox_capacity: Option<usize>,
     span: Span,
     context: Context,
     objects: DashMap<R::Key, OwnedObject, FxBuildHasher>,
@@ -47,26 +69,57 @@ pub(crate) struct Supervisor<R: Router<C>, C, X> {
     control: CachePadded<RwLock<Control<C>>>,
     scope_shared: Arc<ScopeGroupShared>,
     status_subscription: Arc<SubscriptionManager>,
+    lifecycle_subscription: SubscriptionManager,
     rt_manager: RuntimeManager,
+    mount_condition: Option<MountCondition>,
+    /// Set if the mount condition isn't met, see `Local::mount_if()`.
+    is_disabled: AtomicBool,
+    dedup: Vec<FilterFactory>,
+    interceptors: Interceptors,
+    idempotency: Option<Arc<IdempotencyCache>>,
+    concurrency: Concurrency,
+    self_queue: SelfQueue,
+    admission: AdmissionPolicies,
+    dump_classifier: Option<DumpClassifier>,
+    audit: Option<Arc<AuditLog>>,
+    placement: Option<Placement<R::Key, C>>,
+    /// Set for pooled groups, see `ActorGroup::pool()`.
+    pool: Option<Arc<Pool>>,
+    spawn_throttle: Arc<SpawnThrottle>,
 }
 
 struct Control<C> {
     system_config: Arc<SystemConfig>,
+    /// The mailbox config with applied blueprint's defaults.
+    mailbox_config: MailboxConfig,
+    /// The admission policy chosen by `mailbox_config`.
+    admission: Option<Arc<AdmissionPolicy>>,
     user_config: Option<Arc<C>>,
+    /// The last applied config with the time of applying, see `GetConfig`.
+    applied_config: Option<(AnyConfig, std::time::SystemTime)>,
+    config_generation: u64,
     is_started: bool,
     stop_spawning: bool,
+    is_terminated: bool,
 }
 
 /// Returns `None` if cannot be spawned.
 macro_rules! get_or_spawn {
-    ($this:ident, $key:expr, $start_info:expr) => {{
+    ($this:ident, $key:expr, $start_info:expr) => {
+        get_or_spawn!($this, $key, $start_info, SpawnPriority::Background)
+    };
+    ($this:ident, $key:expr, $start_info:expr, $priority:expr) => {{
         let key = $key;
         match $this.objects.get(&key) {
             Some(object) => Some(object),
             None => $this
                 .objects
                 .entry(key.clone())
-                .or_try_insert_with(|| $this.spawn(key, $start_info, Default::default()).ok_or(()))
+                .or_try_insert_with(|| {
+                    $this
+                        .spawn(key, $start_info, $priority, Default::default(), None)
+                        .ok_or(())
+                })
                 .map(|o| o.downgrade()) // FIXME: take an exclusive lock here.
                 .ok(),
         }
@@ -89,16 +142,50 @@ where
         router: R,
         restart_policy: RestartPolicy,
         termination_policy: TerminationPolicy,
+        mailbox_capacity: Option<usize>,
         rt_manager: RuntimeManager,
+        mount_condition: Option<MountCondition>,
+        dedup: Vec<FilterFactory>,
+        interceptors: Interceptors,
+        idempotency: Option<Arc<IdempotencyCache>>,
+        concurrency: Concurrency,
+        self_queue: SelfQueue,
+        admission: AdmissionPolicies,
+        dump_classifier: Option<DumpClassifier>,
+        audit: Option<Arc<AuditLog>>,
+        placement: Option<Placement<R::Key, C>>,
+        is_pooled: bool,
+        sticky: Option<StickyKey>,
     ) -> Self {
         let control = Control {
             system_config: Default::default(),
+            mailbox_config: Default::default(),
+            admission: None,
             user_config: None,
+            applied_config: None,
+            config_generation: 0,
             is_started: false,
             stop_spawning: false,
+            is_terminated: false,
         };
 
-        let status_subscription = SubscriptionManager::new(ctx.clone());
+        let scope_shared = ScopeGroupShared::new(node_no, ctx.group());
+        #[cfg(feature = "test-util")]
+        scope_shared.set_dump_capture(ctx.book().dump_capture().clone());
+
+        let status_subscription = Arc::new(SubscriptionManager::new(ctx.clone()));
+        let lifecycle_subscription = SubscriptionManager::new(ctx.clone());
+
+        let pool = is_pooled.then(|| {
+            Arc::new(Pool::new(
+                ctx.book(),
+                ctx.group(),
+                &group,
+                termination_policy.clone(),
+                status_subscription.clone(),
+                sticky,
+            ))
+        });
 
         Self {
             span: error_span!(parent: Span::none(), "", actor_group = group.as_str()),
@@ -108,17 +195,40 @@ where
             }),
             restart_policy,
             termination_policy,
+            mailbox_capacity,
             objects: DashMap::default(),
             router,
             exec,
             control: CachePadded::new(RwLock::new(control)),
-            scope_shared: Arc::new(ScopeGroupShared::new(node_no, ctx.group())),
-            status_subscription: Arc::new(status_subscription),
+            scope_shared: Arc::new(scope_shared),
+            status_subscription,
+            lifecycle_subscription,
             context: ctx,
             rt_manager,
+            mount_condition,
+            is_disabled: AtomicBool::new(false),
+            dedup,
+            interceptors,
+            idempotency,
+            concurrency,
+            self_queue,
+            admission,
+            dump_classifier,
+            audit,
+            placement,
+            pool,
+            spawn_throttle: Default::default(),
         }
     }
 
+    pub(crate) fn name(&self) -> &str {
+        &self.meta.group
+    }
+
+    pub(crate) fn is_disabled(&self) -> bool {
+        self.is_disabled.load(Ordering::Relaxed)
+    }
+
     // This method shouldn't be called often.
     fn in_scope(&self, f: impl FnOnce()) {
         Scope::new(
@@ -132,8 +242,13 @@ where
     }
 
     pub(crate) fn handle(self: &Arc<Self>, mut envelope: Envelope, visitor: &mut dyn GroupVisitor) {
+        let standbys = self.context.book().standbys();
+        if unlikely(!standbys.is_empty()) && standbys.is_passive(self.context.group()) {
+            envelope.set_passive();
+        }
+
         let outcome = msg!(match &envelope {
-            messages::ValidateConfig { config } => match config.decode::<C>() {
+            messages::ValidateConfig { config } => match self.validate_config(config) {
                 Ok(config) => {
                     // Make all updates under lock, including telemetry/dumper ones.
                     let mut control = self.control.write();
@@ -143,6 +258,10 @@ where
                         // to avoid a race condition at startup.
                         // So, we update the config on `ValidateConfig` at the first time.
                         self.update_config(&mut control, &config);
+                        let is_disabled = !self.meets_condition(&config);
+                        if self.is_disabled.swap(is_disabled, Ordering::Relaxed) != is_disabled {
+                            self.context.book().bump_topology_generation();
+                        }
                         let token = extract_response_token::<messages::ValidateConfig>(envelope);
                         self.context.respond(token, Ok(()));
                         return visitor.done();
@@ -159,7 +278,13 @@ where
                     return visitor.done();
                 }
             },
-            messages::UpdateConfig { config } => match config.decode::<C>() {
+            messages::UpdateConfig { config, force } if !force && self.is_applied(config) => {
+                self.in_scope(|| debug!(group = %self.meta.group, "config is unchanged, skipped"));
+                let token = extract_response_token::<messages::UpdateConfig>(envelope);
+                self.context.respond(token, Ok(()));
+                return visitor.done();
+            }
+            messages::UpdateConfig { config, force } => match self.decode_config(config) {
                 Ok(config) => {
                     // Make all updates under lock, including telemetry/dumper ones.
                     let mut control = self.control.write();
@@ -171,18 +296,47 @@ where
                     }
 
                     control.is_started = true;
+
+                    let is_enabled = self.meets_condition(&config);
+                    let was_disabled = self.is_disabled.swap(!is_enabled, Ordering::Relaxed);
+                    if was_disabled == is_enabled {
+                        // Invalidate caches relying on the topology, e.g. in the network.
+                        self.context.book().bump_topology_generation();
+                    }
+                    let is_mounting =
+                        is_enabled && (only_spawn || was_disabled) && !control.stop_spawning;
+                    if is_mounting {
+                        // The group can be mounted again after being disabled.
+                        control.is_terminated = false;
+                    }
                     drop(control);
 
+                    if !is_enabled {
+                        if !only_spawn && !was_disabled {
+                            self.unmount();
+                        }
+                        let token = extract_response_token::<messages::UpdateConfig>(envelope);
+                        self.context.respond(token, Ok(()));
+                        return visitor.done();
+                    }
+
                     let outcome = self.router.route(&envelope);
 
-                    if only_spawn {
+                    if is_mounting {
+                        self.lifecycle_subscription.send(messages::GroupMounted {
+                            group: self.meta.group.clone(),
+                            timestamp: SystemTime::now().into(),
+                        });
                         self.spawn_on_group_mounted(outcome);
                         let token = extract_response_token::<messages::UpdateConfig>(envelope);
                         self.context.respond(token, Ok(()));
                         return visitor.done();
                     } else {
+                        self.migrate_misplaced();
+
                         // Send `UpdateConfig` across actors.
-                        envelope.set_message(messages::UpdateConfig { config });
+                        let force = *force;
+                        envelope.set_message(messages::UpdateConfig { config, force });
                         outcome.or(Outcome::Broadcast)
                     }
                 }
@@ -196,32 +350,107 @@ where
                     return visitor.done();
                 }
             },
+            messages::GetConfig { group, .. } => {
+                if *group != self.meta.group {
+                    // Handled by actors, e.g. forwarded by the configurer.
+                    if self.is_disabled() {
+                        return visitor.empty(envelope);
+                    }
+                    self.router.route(&envelope).or(Outcome::Discard)
+                } else {
+                    if let Some(applied) = self.applied_config() {
+                        let token = extract_response_token::<messages::GetConfig>(envelope);
+                        self.context.respond(token, applied);
+                    }
+                    return visitor.done();
+                }
+            }
+            messages::GetRecentDumps { group, key } => {
+                if *group == self.meta.group {
+                    let dumps = self.objects.iter().find_map(|object| {
+                        let actor = object.value().as_actor()?;
+                        (actor.meta().key == *key).then(|| actor.recent_dumps().snapshot())
+                    });
+
+                    // Unknown actors ignore the request.
+                    if let Some(dumps) = dumps {
+                        let token = extract_response_token::<messages::GetRecentDumps>(envelope);
+                        self.context.respond(token, dumps);
+                    }
+                }
+                return visitor.done();
+            }
             messages::SubscribeToActorStatuses { forcing } => {
                 let sender = envelope.sender();
                 self.in_scope(|| self.subscribe_to_statuses(sender, *forcing));
                 return visitor.done();
             }
-            messages::Terminate => {
+            messages::SetCircuit { edge, state } => {
+                if edge.source == self.meta.group {
+                    let breakers = self.scope_shared.circuit_breakers();
+                    self.in_scope(|| breakers.force(&edge.destination, *state));
+                }
+                return visitor.done();
+            }
+            messages::SetStandby { primary, active } => {
+                match standbys.member(self.context.group(), primary) {
+                    Some(Member::Primary) => {
+                        let group = self.context.group();
+                        if let Some(event) = standbys.force(group, *active) {
+                            self.on_standby_switched(event);
+                        }
+                    }
+                    // Routed messages can be delivered to the standby instead.
+                    Some(Member::Standby(primary_addr)) => {
+                        let message = messages::SetStandby::new(primary.clone(), *active);
+                        let _ = self.context.unbounded_send_to(primary_addr, message);
+                    }
+                    None => {}
+                }
+                return visitor.done();
+            }
+            messages::SubscribeToLifecycleEvents => {
+                self.lifecycle_subscription.add(envelope.sender());
+                return visitor.done();
+            }
+            messages::Terminate { closing } => {
+                if let Some(pool) = &self.pool {
+                    if *closing || self.termination_policy.close_mailbox {
+                        pool.close();
+                    }
+                }
+
                 if self.termination_policy.stop_spawning {
                     let is_newly = !mem::replace(&mut self.control.write().stop_spawning, true);
                     if is_newly {
                         self.in_scope(|| info!("stopped spawning new actors"));
+                        self.on_actor_removed();
                     }
                 }
 
                 self.router.route(&envelope).or(Outcome::Broadcast)
             }
             messages::Ping => {
+                if self.is_disabled() {
+                    return visitor.empty(envelope);
+                }
                 self.router.route(&envelope).or(Outcome::Broadcast)
             }
             _ => {
+                if self.is_disabled() {
+                    return visitor.empty(envelope);
+                }
                 self.router.route(&envelope).or(Outcome::Discard)
             }
         });
 
         let start_info = ActorStartInfo::on_message();
+        let priority = match envelope.message_kind() {
+            MessageKind::RequestAny(_) | MessageKind::RequestAll(_) => SpawnPriority::Request,
+            _ => SpawnPriority::Background,
+        };
         match outcome {
-            Outcome::Unicast(key) => match get_or_spawn!(self, key, start_info) {
+            Outcome::Unicast(key) => match get_or_spawn!(self, key, start_info, priority) {
                 Some(object) => visitor.visit_last(&object, envelope),
                 None => visitor.empty(envelope),
             },
@@ -232,7 +461,7 @@ where
             Outcome::Multicast(list) => {
                 for key in list.iter() {
                     if !self.objects.contains_key(key) {
-                        get_or_spawn!(self, key.clone(), start_info.clone());
+                        get_or_spawn!(self, key.clone(), start_info.clone(), priority);
                     }
                 }
                 let iter = list.into_iter().filter_map(|key| self.objects.get(&key));
@@ -244,6 +473,19 @@ where
             }
             Outcome::Broadcast => self.visit_multiple(envelope, visitor, self.objects.iter()),
             Outcome::Discard => visitor.empty(envelope),
+            Outcome::Pool => match &self.pool {
+                Some(pool) => visitor.visit_last(pool.object(), envelope),
+                None => {
+                    let name = envelope.message().name();
+                    self.in_scope(|| {
+                        error!(
+                            message = name,
+                            "`Outcome::Pool` is used by a group without a pool"
+                        )
+                    });
+                    visitor.empty(envelope)
+                }
+            },
             Outcome::Default => unreachable!("must be altered earlier"),
         }
     }
@@ -253,6 +495,22 @@ where
         envelope: Envelope,
         visitor: &mut dyn GroupVisitor,
         iter: impl Iterator<Item = impl Deref<Target = OwnedObject>>,
+    ) {
+        #[cfg(feature = "test-util")]
+        if crate::simulation::is_active() {
+            let mut objects = iter.collect::<smallvec::SmallVec<[_; 8]>>();
+            crate::simulation::shuffle(&mut objects);
+            return self.visit_ordered(envelope, visitor, objects.into_iter());
+        }
+
+        self.visit_ordered(envelope, visitor, iter)
+    }
+
+    fn visit_ordered(
+        &self,
+        envelope: Envelope,
+        visitor: &mut dyn GroupVisitor,
+        iter: impl Iterator<Item = impl Deref<Target = OwnedObject>>,
     ) {
         let mut iter = iter.peekable();
 
@@ -274,10 +532,12 @@ where
         self: &Arc<Self>,
         key: R::Key,
         start_info: ActorStartInfo,
+        priority: SpawnPriority,
         mut backoff: RestartBackoff,
+        predecessor: Option<OwnedObject>,
     ) -> Option<OwnedObject> {
         let control = self.control.read();
-        if control.stop_spawning {
+        if control.stop_spawning || self.is_disabled() {
             return None;
         }
 
@@ -301,11 +561,25 @@ where
             .cloned()
             .expect("config is unset");
 
+        let placement = self
+            .placement
+            .as_ref()
+            .and_then(|place| place(&key, &user_config));
+
+        let audit = self.audit.clone().map(ActorAudit::new);
+
         let ctx = self
             .context
             .clone()
             .with_key(key.clone())
-            .with_config(user_config);
+            .with_config(user_config)
+            .with_dedup(Dedup::new(&self.dedup))
+            .with_interceptors(self.interceptors.clone())
+            .with_idempotency(self.idempotency.clone())
+            .with_concurrency(self.concurrency)
+            .with_self_queue(self.self_queue)
+            .with_dump_classifier(self.dump_classifier.clone())
+            .with_audit(audit.clone());
 
         let meta = Arc::new(ActorMeta {
             group: self.meta.group.clone(),
@@ -313,43 +587,102 @@ where
         });
         let actor = Actor::new(
             meta.clone(),
+            placement.clone(),
             addr,
-            &system_config.mailbox,
+            &control.mailbox_config,
+            &control.system_config.dumping.recent,
             self.termination_policy.clone(),
             self.status_subscription.clone(),
-        );
+        )
+        .with_pool(self.pool.clone());
+        actor.set_mailbox_admission(control.admission.clone());
 
         drop(control);
 
         let sv = self.clone();
+        let actor_meta = meta.clone();
+
+        let runtime = placement.as_ref().map(|rt| rt.name().to_owned());
 
         // TODO: move to `harness.rs`.
         let fut = async move {
-            let thread = std::thread::current();
+            // The migrated actor starts once the previous one finishes to keep
+            // the order of messages, which are held in the mailbox meanwhile.
+            if let Some(predecessor) = predecessor {
+                let actor = predecessor
+                    .as_actor()
+                    .expect("a supervisor stores only actors");
+                actor.finished().await;
+            }
 
-            info!(%addr, thread = %thread.name().unwrap_or("?"), "started");
+            // Messages are held in the mailbox until the actor is started.
+            let spawn_permit = sv.spawn_throttle.acquire(priority).await;
 
-            sv.objects
-                .get(&key)
-                .expect("where is the current actor?")
-                .as_actor()
-                .expect("a supervisor stores only actors")
-                .on_start();
+            let thread = std::thread::current();
+
+            info!(%addr, thread = %thread.name().unwrap_or("?"), runtime, "started");
+
+            // The object is accessed by the address, because the key can already
+            // belong to a successor, see `migrate()`. It's kept until the address
+            // is removed from the book below.
+            let object = sv
+                .context
+                .book()
+                .get_owned(addr)
+                .expect("where is the current actor?");
+            let actor = object.as_actor().expect("a supervisor stores only actors");
+            actor.set_spawn_permit(spawn_permit);
+            actor.on_start();
+
+            sv.lifecycle_subscription.send(messages::ActorSpawned {
+                meta: actor_meta.clone(),
+                timestamp: SystemTime::now().into(),
+            });
 
             // It must be called after `entry.insert()`.
             let ctx = ctx.with_addr(addr).with_start_info(start_info);
             let fut = async { sv.exec.exec(ctx).await.unify() };
-            let new_status = match panic::catch(fut).await {
+            let new_status = match panics::catch(fut).await {
                 Ok(Ok(())) => ActorStatus::TERMINATED,
                 Ok(Err(err)) => ActorStatus::FAILED.with_details(ErrorChain(&*err)),
-                Err(panic) => ActorStatus::FAILED.with_details(panic),
+                Err(mut panic) => {
+                    // Attach the context of the failure, see `system.dumping.recent`.
+                    panic.recent_dumps = actor.recent_dumps().snapshot();
+
+                    panic.dump();
+                    ActorStatus::FAILED.with_panic(panic)
+                }
             };
 
-            let restart_after = {
-                let object = sv.objects.get(&key).expect("where is the current actor?");
+            if let Some(audit) = &audit {
+                audit.on_finished(&new_status);
+            }
 
-                let actor = object.as_actor().expect("a supervisor stores only actors");
+            // Check whether the handled message is poisoned, see `poison_threshold`.
+            let mut after_panic = new_status.panic().and_then(|panic| {
+                let mut after_panic = actor.poisoning().on_panic()?;
+
+                if let Some(poisoned) = after_panic.poisoned.take() {
+                    sv.on_poisoned(&actor_meta, poisoned, panic.message.clone());
+                }
+
+                Some(after_panic)
+            });
+
+            // Subscriptions don't survive restarts, new actors subscribe again.
+            sv.context.book().broker().unsubscribe_all(addr);
+
+            // Bindings of sticky pools don't survive restarts too.
+            if let Some(pool) = &sv.pool {
+                pool.release(actor_meta.key.parse().expect("invalid worker key"));
+            }
 
+            // Hand off messages left in the mailbox if requested by the actor.
+            if let Some((target, envelopes)) = actor.take_drained::<R::Key>() {
+                sv.hand_off(&actor_meta, target, envelopes);
+            }
+
+            let (restart_after, is_migrating) = {
                 // Select the restart policy with the following priority: actor override >
                 // config override > blueprint restart policy..
                 let default_restart_policy = sv
@@ -362,20 +695,52 @@ where
                 let restart_policy = actor.restart_policy().unwrap_or(default_restart_policy);
 
                 let restarting_allowed = restart_policy.restarting_allowed(&new_status)
-                    && !sv.control.read().stop_spawning;
+                    && !sv.control.read().stop_spawning
+                    && !sv.is_disabled();
+
+                sv.lifecycle_subscription.send(messages::ActorTerminated {
+                    meta: actor_meta.clone(),
+                    reason: new_status.clone(),
+                    timestamp: SystemTime::now().into(),
+                });
+
+                if new_status.kind().is_failed() {
+                    let group = sv.context.group();
+                    if let Some(event) = sv.context.book().standbys().on_failure(group) {
+                        sv.on_standby_switched(event);
+                    }
+                }
+
+                // The mailbox is cleared once the actor is finished.
+                if let Some(after_panic) = &mut after_panic {
+                    after_panic.left = actor.take_left();
+                }
 
                 actor.set_status(new_status);
 
-                restarting_allowed
+                let restart_after = restarting_allowed
                     .then(|| {
                         restart_policy
                             .restart_params()
                             .and_then(|p| backoff.next(&p))
                     })
-                    .flatten()
+                    .flatten();
+
+                // Checked after finishing, because `start_migration()` fails then.
+                (restart_after, actor.is_migrating())
             };
 
-            let _ = if let Some(after) = restart_after {
+            if is_migrating {
+                // The key is already owned by the successor.
+                debug!("actor has been migrated");
+            } else if let Some(after) = restart_after {
+                sv.lifecycle_subscription.send(messages::ActorRestarted {
+                    meta: actor_meta.clone(),
+                    attempt: backoff.restart_count(),
+                    backoff: after,
+                    timestamp: SystemTime::now().into(),
+                });
+
                 if after == Duration::ZERO {
                     debug!("actor will be restarted immediately");
                 } else {
@@ -390,22 +755,40 @@ where
                 scope::set_trace_id(TraceId::generate());
 
                 backoff.start();
-                if let Some(object) = sv.spawn(key.clone(), ActorStartInfo::on_restart(), backoff) {
+                let _ = if let Some(object) = sv.spawn(
+                    key.clone(),
+                    ActorStartInfo::on_restart(),
+                    SpawnPriority::Background,
+                    backoff,
+                    None,
+                ) {
+                    if let Some(after_panic) = after_panic {
+                        sv.retain_left(&object, after_panic);
+                    }
+
                     sv.objects.insert(key.clone(), object)
                 } else {
                     sv.objects.remove(&key).map(|(_, v)| v)
                 }
+                .expect("where is the current actor?");
             } else {
                 debug!("actor won't be restarted");
-                sv.objects.remove(&key).map(|(_, v)| v)
+                let _ = sv
+                    .objects
+                    .remove(&key)
+                    .expect("where is the current actor?");
             }
-            .expect("where is the current actor?");
 
             // TODO: should we unregister the address right after failure?
+            drop(object);
             sv.context.book().remove(addr);
+            sv.on_actor_removed();
         };
 
-        let rt = self.rt_manager.get(&meta);
+        let rt = match placement {
+            Some(rt) => rt.handle().clone(),
+            None => self.rt_manager.get(&meta),
+        };
 
         entry.insert(Object::new(addr, actor));
 
@@ -422,6 +805,70 @@ where
         Some(object)
     }
 
+    /// Migrates actors, whose placement is changed by the new config.
+    fn migrate_misplaced(self: &Arc<Self>) {
+        let Some(place) = &self.placement else {
+            return;
+        };
+
+        let config = ward!(self.control.read().user_config.clone());
+
+        let misplaced = self
+            .objects
+            .iter()
+            .filter(|object| {
+                let actor = object.as_actor().expect("a supervisor stores only actors");
+                actor.placement() != place(object.key(), &config).as_ref()
+            })
+            .map(|object| object.key().clone())
+            .collect::<Vec<_>>();
+
+        for key in misplaced {
+            self.migrate(key);
+        }
+    }
+
+    /// Replaces the actor with a new one, which is placed according to
+    /// the current config and started once the old one finishes.
+    fn migrate(self: &Arc<Self>, key: R::Key) {
+        // Hold the lock to avoid routing messages to the old actor meanwhile.
+        let mut entry = ward!(self.objects.get_mut(&key));
+        let old = entry.value().clone();
+        let old_actor = old.as_actor().expect("a supervisor stores only actors");
+
+        if !old_actor.start_migration(key.clone()) {
+            // Finishing, so will be restarted with the new placement if allowed.
+            return;
+        }
+
+        let start_info = ActorStartInfo::on_migration();
+        let priority = SpawnPriority::Background;
+        let spawned = self.spawn(
+            key.clone(),
+            start_info,
+            priority,
+            <_>::default(),
+            Some(old.clone()),
+        );
+
+        let is_spawned = spawned.is_some();
+        if let Some(successor) = spawned {
+            *entry.value_mut() = successor;
+        }
+        drop(entry);
+
+        // Now the old actor handles already received messages and finishes.
+        old_actor.close();
+
+        if is_spawned {
+            self.in_scope(|| info!(key = %key, "actor is being migrated"));
+        } else {
+            // The group is being terminated or disabled.
+            self.objects.remove_if(&key, |_, o| o.addr() == old.addr());
+            self.in_scope(|| warn!(key = %key, "actor cannot be migrated, terminating"));
+        }
+    }
+
     fn spawn_on_group_mounted(self: &Arc<Self>, outcome: Outcome<R::Key>) {
         let start_info = ActorStartInfo::on_group_mounted();
         match outcome {
@@ -437,31 +884,302 @@ where
             | Outcome::GentleMulticast(_)
             | Outcome::Broadcast
             | Outcome::Discard
+            | Outcome::Pool
             | Outcome::Default => {}
         }
     }
 
+    fn hand_off(
+        self: &Arc<Self>,
+        meta: &Arc<ActorMeta>,
+        target: DrainTarget<R::Key>,
+        envelopes: Vec<Envelope>,
+    ) {
+        // System messages are handled by the supervisor and must not be resent.
+        let mut envelopes = envelopes
+            .into_iter()
+            .filter(|envelope| {
+                !msg!(match envelope {
+                    messages::UpdateConfig | messages::ValidateConfig => true,
+                    messages::Ping | messages::Terminate => true,
+                    _ => false,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let limit = self.control.read().mailbox_config.drain_limit;
+        let excess = envelopes.split_off(limit.min(envelopes.len()));
+
+        if !excess.is_empty() {
+            warn!(
+                total = envelopes.len() + excess.len(),
+                limit, "too many messages to hand off, the excess is dead-lettered"
+            );
+
+            for envelope in excess {
+                self.send_dead_letter(
+                    meta,
+                    envelope,
+                    messages::DeadLetterReason::DrainLimitExceeded,
+                );
+            }
+        }
+
+        let total = envelopes.len();
+        let mut dropped = 0;
+
+        match target {
+            DrainTarget::Key(key) => {
+                if let Some(object) = get_or_spawn!(self, key, ActorStartInfo::on_message()) {
+                    let actor = object.as_actor().expect("a supervisor stores only actors");
+                    for envelope in envelopes {
+                        dropped += actor.unbounded_send(envelope).is_err() as usize;
+                    }
+                } else {
+                    dropped = total;
+                }
+            }
+            DrainTarget::Router => {
+                let group = self.context.group();
+                let object = self
+                    .context
+                    .book()
+                    .get_owned(group)
+                    .expect("group is missing");
+                for envelope in envelopes {
+                    dropped += object.unbounded_send(Addr::NULL, envelope).is_err() as usize;
+                }
+            }
+        }
+
+        if dropped > 0 {
+            warn!(
+                dropped,
+                total, "some messages cannot be handed off, dropped"
+            );
+        }
+    }
+
+    fn send_dead_letter(
+        &self,
+        meta: &Arc<ActorMeta>,
+        envelope: Envelope,
+        reason: messages::DeadLetterReason,
+    ) {
+        let trace_id = envelope.trace_id();
+        let message = ward!(envelope.unpack::<AnyMessage>()).0;
+
+        self.lifecycle_subscription.send(messages::DeadLetter {
+            meta: meta.clone(),
+            trace_id,
+            message,
+            reason,
+            timestamp: SystemTime::now().into(),
+        });
+    }
+
+    fn on_poisoned(&self, meta: &Arc<ActorMeta>, envelope: Envelope, panic: String) {
+        warn!(
+            message = envelope.message().name(),
+            trace_id = %envelope.trace_id(),
+            "message is poisoned, removed from the mailbox"
+        );
+
+        self.send_dead_letter(
+            meta,
+            envelope,
+            messages::DeadLetterReason::Poisoned { panic },
+        );
+    }
+
+    /// Hands off messages left by the panicked actor to the restarted one,
+    /// see `system.mailbox.poison_threshold`.
+    fn retain_left(&self, successor: &OwnedObject, after_panic: AfterPanic) {
+        let successor = successor
+            .as_actor()
+            .expect("a supervisor stores only actors");
+        successor.poisoning().set_strikes(after_panic.strikes);
+
+        let mut dropped = 0;
+        for envelope in after_panic.retry.into_iter().chain(after_panic.left) {
+            dropped += successor.unbounded_send(envelope).is_err() as usize;
+        }
+
+        if dropped > 0 {
+            warn!(dropped, "some messages cannot be handed off, dropped");
+        }
+    }
+
+    /// Logs, dumps and notifies subscribers that traffic is switched,
+    /// see `Topology::standby()`.
+    fn on_standby_switched(&self, event: messages::StandbySwitched) {
+        self.in_scope(|| {
+            let (active, forced) = (&event.active, event.forced);
+            match event.active {
+                messages::StandbyRole::Primary => {
+                    info!(?active, forced, standby = %event.standby, "traffic is switched")
+                }
+                messages::StandbyRole::Standby => {
+                    warn!(?active, forced, standby = %event.standby, "traffic is switched")
+                }
+            }
+
+            if let Some(permit) = dumper().acquire_m(&event) {
+                let kind = MessageKind::regular(self.context.group());
+                permit.record(Dump::message(&event, &kind, Direction::Out));
+            }
+        });
+
+        self.lifecycle_subscription.send(event);
+    }
+
+    /// Emits `GroupTerminated` once the group stops spawning and has no actors.
+    fn on_actor_removed(&self) {
+        // Don't hold the control lock while accessing objects to avoid deadlocks.
+        if !self.objects.is_empty() {
+            return;
+        }
+
+        let mut control = self.control.write();
+        if !(control.stop_spawning || self.is_disabled()) || control.is_terminated {
+            return;
+        }
+
+        control.is_terminated = true;
+        drop(control);
+
+        self.lifecycle_subscription.send(messages::GroupTerminated {
+            group: self.meta.group.clone(),
+            timestamp: SystemTime::now().into(),
+        });
+    }
+
+    fn meets_condition(&self, config: &AnyConfig) -> bool {
+        self.mount_condition
+            .as_ref()
+            .map_or(true, |condition| condition(config))
+    }
+
+    /// Gracefully terminates all actors once the group is disabled.
+    fn unmount(&self) {
+        self.in_scope(|| info!("group is disabled, terminating actors"));
+
+        for object in self.objects.iter() {
+            let addr = object.value().addr();
+            let _ = self
+                .context
+                .unbounded_send_to(addr, messages::Terminate::default());
+        }
+
+        self.on_actor_removed();
+    }
+
+    fn decode_config(&self, config: &AnyConfig) -> Result<AnyConfig, String> {
+        let config = config.decode::<C>()?;
+        let admission = config.get_system().mailbox.admission.as_deref();
+        self.admission.check_config(admission)?;
+        Ok(config)
+    }
+
+    /// Checks whether the config is already applied to the started group.
+    fn is_applied(&self, config: &AnyConfig) -> bool {
+        let control = self.control.read();
+        control.is_started
+            && control
+                .applied_config
+                .as_ref()
+                .is_some_and(|(applied, _)| applied.raw_eq(config))
+    }
+
+    fn validate_config(&self, config: &AnyConfig) -> Result<AnyConfig, String> {
+        let config = self.decode_config(config)?;
+        // Not checked on `UpdateConfig`, because entrypoints get an empty config
+        // at startup, which is validated later along with other groups.
+        check_message_collisions(config.get_system())?;
+        self.check_exec_future_size(config.get_system())?;
+        Ok(config)
+    }
+
+    fn check_exec_future_size(&self, system: &SystemConfig) -> Result<(), String> {
+        let size = mem::size_of::<X::Output>();
+        let limit = system.max_exec_future_size;
+        if !system.strict_exec_future_size || size as u64 <= limit.as_u64() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "the exec future is {size} bytes, more than `system.max_exec_future_size` ({limit}); \
+             reduce it or use `ActorGroup::boxed_exec()`"
+        ))
+    }
+
+    fn report_exec_future_size(&self, limit: ByteSize) {
+        let size = mem::size_of::<X::Output>();
+        self.in_scope(|| {
+            if size as u64 > limit.as_u64() {
+                warn!(
+                    size,
+                    %limit,
+                    "the exec future is too large, consider `ActorGroup::boxed_exec()`"
+                );
+            } else {
+                debug!(size, "the exec future size");
+            }
+        });
+    }
+
     fn update_config(&self, control: &mut Control<C>, config: &AnyConfig) {
         let system = config.get_system();
         self.scope_shared.configure(system);
 
-        let need_to_update_actors = control.system_config.mailbox != system.mailbox;
+        let mut mailbox_config = system.mailbox.clone();
+        if let Some(capacity) = self.mailbox_capacity {
+            if !config.contains(&["system", "mailbox", "capacity"]) {
+                mailbox_config.capacity = capacity;
+            }
+        }
+
+        let need_to_update_actors = control.mailbox_config != mailbox_config
+            || control.system_config.dumping.recent != system.dumping.recent;
+
+        self.spawn_throttle.configure(system);
+
+        if control.user_config.is_none()
+            || control.system_config.max_exec_future_size != system.max_exec_future_size
+        {
+            self.report_exec_future_size(system.max_exec_future_size);
+        }
 
         // Update user's config.
         control.system_config = system.clone();
+        control.admission = self.admission.get(mailbox_config.admission.as_deref());
+        control.mailbox_config = mailbox_config;
         control.user_config = Some(config.get_user::<C>().clone());
+        control.applied_config = Some((config.clone(), SystemTime::now().into()));
+        control.config_generation += 1;
 
         self.router
             .update(control.user_config.as_ref().expect("just saved"));
 
         if need_to_update_actors {
+            if let Some(pool) = &self.pool {
+                pool.configure(&control.mailbox_config);
+            }
+
             for object in self.objects.iter() {
                 let actor = object
                     .value()
                     .as_actor()
                     .expect("a supervisor stores only actors");
 
-                actor.set_mailbox_capacity_config(system.mailbox.capacity);
+                actor.set_mailbox_capacity_config(control.mailbox_config.capacity);
+                actor.set_mailbox_quotas(&control.mailbox_config.quotas);
+                actor.set_mailbox_admission(control.admission.clone());
+                actor.set_mailbox_on_terminate(control.mailbox_config.on_terminate);
+                actor.set_poison_threshold(control.mailbox_config.poison_threshold);
+                actor
+                    .recent_dumps()
+                    .configure(&control.system_config.dumping.recent);
             }
         }
 
@@ -474,6 +1192,33 @@ where
         });
     }
 
+    fn applied_config(&self) -> Option<messages::AppliedConfig> {
+        let control = self.control.read();
+        let (config, applied_at) = control.applied_config.as_ref()?;
+
+        let overrides = self
+            .scope_shared
+            .circuit_breakers()
+            .forced()
+            .into_iter()
+            .map(|(destination, state)| {
+                let state = match state {
+                    messages::CircuitState::Open => "open",
+                    messages::CircuitState::Closed => "closed",
+                };
+                format!("circuit to {destination} is forced {state}")
+            })
+            .collect();
+
+        Some(messages::AppliedConfig {
+            config: config.masked(),
+            generation: control.config_generation,
+            applied_at: *applied_at,
+            overrides,
+            provenance: Vec::new(),
+        })
+    }
+
     fn subscribe_to_statuses(&self, addr: Addr, forcing: bool) {
         // Firstly, add the subscriber to handle new objects right way.
         if !self.status_subscription.add(addr) && !forcing {
@@ -525,3 +1270,16 @@ fn extract_response_token<R: Request>(envelope: Envelope) -> ResponseToken<R> {
         _ => unreachable!(),
     })
 }
+
+fn check_message_collisions(system: &SystemConfig) -> Result<(), String> {
+    let collisions = message::collisions();
+    if collisions.is_empty() || system.allow_duplicate_messages {
+        return Ok(());
+    }
+
+    let collisions = collisions.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+    Err(format!(
+        "{}; set `system.allow_duplicate_messages = true` to allow it",
+        collisions.join("; ")
+    ))
+}
@@ -15,6 +15,7 @@ use crate::{
     message::Message,
     scope,
     source::{SourceArc, SourceStream, UnattachedSource},
+    time::ScheduledMark,
     tracing::TraceId,
 };
 
@@ -70,6 +71,7 @@ impl<M: Message> crate::source::SourceHandle for Delay<M> {
 struct DelaySource<M> {
     message: Option<M>,
     trace_id: Option<TraceId>,
+    scheduled: ScheduledMark,
     #[pin]
     sleep: Sleep,
 }
@@ -95,6 +97,7 @@ impl<M: Message> Delay<M> {
         let source = DelaySource {
             message: Some(message),
             trace_id: Some(scope::trace_id()),
+            scheduled: ScheduledMark::new(true),
             sleep: tokio::time::sleep_until(when),
         };
 
@@ -123,10 +126,17 @@ impl<M: Message> SourceStream for DelaySource<M> {
         }
 
         // Emit the message.
+        this.scheduled.set(false);
         let message = this.message.take().unwrap();
         let kind = MessageKind::regular(Addr::NULL);
-        let trace_id = this.trace_id.take().unwrap_or_else(TraceId::generate);
-        let envelope = Envelope::with_trace_id(message, kind, trace_id);
+        let trace_id = this.trace_id.take();
+        let is_new_trace = trace_id.is_none();
+        let trace_id = trace_id.unwrap_or_else(TraceId::generate);
+        let mut envelope = Envelope::with_trace_id(message, kind, trace_id);
+
+        if is_new_trace {
+            scope::select_detailed(&mut envelope);
+        }
 
         Poll::Ready(Some(envelope))
     }
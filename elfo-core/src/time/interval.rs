@@ -12,8 +12,9 @@ use tokio::time::{Duration, Instant, Sleep};
 use crate::{
     envelope::{Envelope, MessageKind},
     message::Message,
+    scope,
     source::{SourceArc, SourceStream, UnattachedSource},
-    time::far_future,
+    time::{far_future, ScheduledMark},
     tracing::TraceId,
     Addr,
 };
@@ -76,6 +77,7 @@ struct IntervalSource<M> {
     message: M,
     period: Duration,
     is_delayed: bool,
+    scheduled: ScheduledMark,
     #[pin]
     sleep: Sleep,
 }
@@ -87,6 +89,7 @@ impl<M: Message> Interval<M> {
             message,
             period: NEVER,
             is_delayed: false,
+            scheduled: ScheduledMark::new(false),
             sleep: tokio::time::sleep_until(far_future()),
         };
 
@@ -209,6 +212,7 @@ impl<M: Message> Interval<M> {
 
         *source.is_delayed = when.is_some();
         *source.period = period;
+        source.scheduled.set(period != NEVER);
 
         let new_deadline = when.unwrap_or_else(|| Instant::now() + period);
         source.sleep.reset(new_deadline);
@@ -248,7 +252,8 @@ impl<M: Message> SourceStream for IntervalSource<M> {
         let message = this.message.clone();
         let kind = MessageKind::regular(Addr::NULL);
         let trace_id = TraceId::generate();
-        let envelope = Envelope::with_trace_id(message, kind, trace_id);
+        let mut envelope = Envelope::with_trace_id(message, kind, trace_id);
+        scope::select_detailed(&mut envelope);
 
         Poll::Ready(Some(envelope))
     }
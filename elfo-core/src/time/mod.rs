@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use tokio::time::Instant;
 
@@ -15,3 +18,42 @@ fn far_future() -> Instant {
     // 1000 years overflows on macOS, 100 years overflows on FreeBSD.
     Instant::now() + Duration::from_secs(86400 * 365 * 30)
 }
+
+static SCHEDULED_TIMERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of pending [`Delay`]s and started [`Interval`]s.
+#[stability::unstable]
+pub fn scheduled_timers() -> usize {
+    SCHEDULED_TIMERS.load(Ordering::Relaxed)
+}
+
+/// Tracks whether the timer is counted in [`scheduled_timers()`].
+struct ScheduledMark(bool);
+
+impl ScheduledMark {
+    fn new(is_scheduled: bool) -> Self {
+        let mut mark = Self(false);
+        mark.set(is_scheduled);
+        mark
+    }
+
+    fn set(&mut self, is_scheduled: bool) {
+        if self.0 == is_scheduled {
+            return;
+        }
+
+        if is_scheduled {
+            SCHEDULED_TIMERS.fetch_add(1, Ordering::Relaxed);
+        } else {
+            SCHEDULED_TIMERS.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        self.0 = is_scheduled;
+    }
+}
+
+impl Drop for ScheduledMark {
+    fn drop(&mut self) {
+        self.set(false);
+    }
+}
@@ -1,5 +1,6 @@
-use std::{cell::RefCell, sync::Arc};
+use std::{cell::RefCell, sync::Arc, time::Duration};
 
+use fxhash::FxHashMap;
 use parking_lot::RwLock;
 use sealed::sealed;
 use tokio::runtime::Handle;
@@ -9,14 +10,32 @@ use crate::stuck_detection::StuckDetector;
 use crate::{
     addr::{Addr, GroupNo, NodeLaunchId, NodeNo},
     address_book::{AddressBook, VacantEntry},
+    config::AnyConfig,
     context::Context,
     demux::Demux,
     envelope::Envelope,
-    group::Blueprint,
+    group::{Blueprint, MountCondition},
+    group_ref::{GroupRef, GroupTarget},
+    init::STOP_GROUP_TERMINATION_AFTER,
     object::Object,
     runtime::RuntimeManager,
+    shutdown::ShutdownReason,
 };
 
+pub use self::{
+    graph::{EdgeMessage, GraphEdge, GraphGroup, TopologyGraph},
+    standby::{HealthPolicy, MessageFilter, StandbyConfig},
+    weighted::{RouteConfig, RouteTarget, RoutesConfig},
+};
+pub(crate) use self::{
+    graph::{EdgeRecorder, GroupDescription},
+    standby::{Member, Standbys},
+};
+
+mod graph;
+mod standby;
+mod weighted;
+
 pub(crate) const SYSTEM_INIT_GROUP_NO: u8 = 1;
 
 /// The topology defines local and remote groups, and routes between them.
@@ -34,7 +53,9 @@ struct Inner {
     #[cfg(feature = "network")]
     remotes: Vec<RemoteActorGroup>,
     connections: Vec<Connection>,
+    weighted: FxHashMap<String, weighted::Route>,
     rt_manager: RuntimeManager,
+    shutdown_wave_timeout: Duration,
 }
 
 impl Default for Inner {
@@ -45,7 +66,9 @@ impl Default for Inner {
             #[cfg(feature = "network")]
             remotes: Vec::new(),
             connections: Vec::new(),
+            weighted: FxHashMap::default(),
             rt_manager: RuntimeManager::default(),
+            shutdown_wave_timeout: STOP_GROUP_TERMINATION_AFTER,
         }
     }
 }
@@ -58,6 +81,17 @@ pub struct LocalActorGroup {
     pub name: String,
     pub is_entrypoint: bool,
     pub(crate) stop_order: i8,
+    /// A position in `Topology::shutdown_order()`, `usize::MAX` if unlisted.
+    pub(crate) shutdown_position: usize,
+    /// `None` until mounted.
+    pub(crate) description: Option<GroupDescription>,
+}
+
+impl LocalActorGroup {
+    /// Groups in the same wave are terminated simultaneously.
+    pub(crate) fn shutdown_wave(&self) -> (i8, usize) {
+        (self.stop_order, self.shutdown_position)
+    }
 }
 
 /// Represents a connection between two groups.
@@ -73,6 +107,8 @@ pub struct Connection {
 #[derive(Debug, Clone)]
 pub enum ConnectionTo {
     Local(Addr),
+    /// See [`Topology::weighted()`].
+    Weighted(String),
     #[cfg(feature = "network")]
     Remote(String),
 }
@@ -81,7 +117,7 @@ impl ConnectionTo {
     #[stability::unstable]
     pub fn into_remote(self) -> Option<String> {
         match self {
-            Self::Local(_) => None,
+            Self::Local(_) | Self::Weighted(_) => None,
             #[cfg(feature = "network")]
             Self::Remote(name) => Some(name),
         }
@@ -123,6 +159,23 @@ impl Topology {
         self.launch_id
     }
 
+    /// Returns the generation of local groups.
+    ///
+    /// It's changed every time a local group is added, mounted, enabled or
+    /// disabled by its mount condition, so caches built on top of the topology
+    /// (e.g. routing tables of the network layer) can be invalidated lazily.
+    #[stability::unstable]
+    pub fn generation(&self) -> u64 {
+        self.book.topology_generation()
+    }
+
+    /// Returns dumps captured in this topology.
+    #[cfg(feature = "test-util")]
+    #[doc(hidden)]
+    pub fn dump_capture(&self) -> &Arc<crate::dumping::capture::DumpCapture> {
+        self.book.dump_capture()
+    }
+
     #[stability::unstable]
     pub fn add_dedicated_rt<F: Fn(&crate::ActorMeta) -> bool + Send + Sync + 'static>(
         &self,
@@ -162,7 +215,11 @@ impl Topology {
             name: name.clone(),
             is_entrypoint: false,
             stop_order: 0,
+            shutdown_position: usize::MAX,
+            description: None,
         });
+        self.book.groups().insert_local(&name, entry.addr());
+        self.book.bump_topology_generation();
 
         Local {
             name,
@@ -172,6 +229,63 @@ impl Topology {
         }
     }
 
+    /// Defines the order of termination of the listed groups.
+    ///
+    /// On shutdown, groups are terminated in waves, every wave waits until all
+    /// its groups are finished or [the timeout] is reached. The listed groups
+    /// are terminated one by one, and unlisted ones are terminated after them
+    /// all together as a final wave.
+    ///
+    /// It refines the order defined by [`ActorGroup::stop_order()`], which
+    /// has precedence. Thus, system groups (e.g. `system.network`) are still
+    /// terminated after all user groups.
+    ///
+    /// Calling it again overrides positions of the listed groups.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// let topology = elfo::Topology::empty();
+    /// let ingest = topology.local("ingest");
+    /// let enrich = topology.local("enrich");
+    /// let store = topology.local("store");
+    ///
+    /// topology.shutdown_order([&ingest, &enrich, &store]);
+    /// ```
+    ///
+    /// [the timeout]: Topology::set_shutdown_wave_timeout
+    /// [`ActorGroup::stop_order()`]: crate::ActorGroup::stop_order
+    pub fn shutdown_order<'a, 't: 'a>(&self, groups: impl IntoIterator<Item = &'a Local<'t>>) {
+        for (position, local) in groups.into_iter().enumerate() {
+            local.with_group_mut(|group| group.shutdown_position = position);
+        }
+    }
+
+    /// Sets the maximum duration of one shutdown wave, see
+    /// [`Topology::shutdown_order()`]. After this timeout, the next wave is
+    /// started and groups of the current one are forcibly closed.
+    ///
+    /// 35s by default.
+    pub fn set_shutdown_wave_timeout(&self, timeout: Duration) {
+        self.inner.write().shutdown_wave_timeout = timeout;
+    }
+
+    pub(crate) fn shutdown_wave_timeout(&self) -> Duration {
+        self.inner.read().shutdown_wave_timeout
+    }
+
+    /// Initiates graceful shutdown of the node started with this topology,
+    /// see [`Context::initiate_shutdown()`]. Useful for code outside actors.
+    ///
+    /// Returns `false` if shutdown has already been initiated, the reason is
+    /// ignored then.
+    ///
+    /// [`Context::initiate_shutdown()`]: crate::Context::initiate_shutdown
+    pub fn initiate_shutdown(&self, reason: impl Into<String>) -> bool {
+        let reason = ShutdownReason::Requested(reason.into());
+        self.book.shutdown().initiate(reason)
+    }
+
     /// Returns an iterator over all local groups.
     pub fn locals(&self) -> impl Iterator<Item = LocalActorGroup> + '_ {
         let inner = self.inner.read();
@@ -200,6 +314,12 @@ impl Local<'_> {
         self.entry.addr()
     }
 
+    /// Returns a typed reference to this group, see [`GroupRef`].
+    pub fn group_ref(&self) -> GroupRef {
+        let target = GroupTarget::Local(self.entry.addr());
+        GroupRef::new(self.name.as_str().into(), target)
+    }
+
     /// Mark this group as an entrypoint.
     ///
     /// It means, that this group will be started automatically when the system
@@ -257,14 +377,60 @@ impl Local<'_> {
 
     /// Mounts a blueprint to this group.
     pub fn mount(self, blueprint: Blueprint) {
-        self.with_group_mut(|group| group.stop_order = blueprint.stop_order);
+        self.do_mount(blueprint, None);
+    }
+
+    /// Mounts a blueprint to this group, which is enabled only if the
+    /// condition is met by the group's config.
+    ///
+    /// The condition is evaluated on the initial config and on every config
+    /// update. Once enabled, the group is started as usual. Once disabled,
+    /// actors of the group are terminated gracefully, and new ones are not
+    /// spawned until the group is enabled again, with a fresh start.
+    ///
+    /// Messages aren't delivered to a disabled group, sends and requests fail
+    /// with [`ErrorKind::GroupDisabled`]. Transitions can be observed by
+    /// [`GroupMounted`] and [`GroupTerminated`] lifecycle events.
+    ///
+    /// If the group is enabled again before its actors are terminated,
+    /// messages can still reach the terminating ones and be lost.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # fn exporter() -> elfo::Blueprint { elfo::ActorGroup::new().exec(|_| async {}) }
+    /// let topology = elfo::Topology::empty();
+    /// let group = topology.local("exporter");
+    /// // [exporter]
+    /// // enabled = true
+    /// group.mount_if(exporter(), |config| config.get_bool("enabled"));
+    /// ```
+    ///
+    /// [`ErrorKind::GroupDisabled`]: crate::errors::ErrorKind::GroupDisabled
+    /// [`GroupMounted`]: crate::messages::GroupMounted
+    /// [`GroupTerminated`]: crate::messages::GroupTerminated
+    pub fn mount_if(
+        self,
+        blueprint: Blueprint,
+        condition: impl Fn(&AnyConfig) -> bool + Send + Sync + 'static,
+    ) {
+        self.do_mount(blueprint, Some(Arc::new(condition)));
+    }
+
+    fn do_mount(self, blueprint: Blueprint, condition: Option<MountCondition>) {
+        self.with_group_mut(|group| {
+            group.stop_order = blueprint.stop_order;
+            group.description = Some(blueprint.description.clone());
+        });
 
         let addr = self.entry.addr();
         let book = self.topology.book.clone();
         let ctx = Context::new(book, self.demux.into_inner()).with_group(addr);
         let rt_manager = self.topology.inner.read().rt_manager.clone();
-        let object = (blueprint.mount)(ctx, self.topology.node_no, self.name, rt_manager);
+        let node_no = self.topology.node_no;
+        let object = (blueprint.mount)(ctx, node_no, self.name, rt_manager, condition);
         self.entry.insert(object);
+        self.topology.book.bump_topology_generation();
     }
 
     fn with_group_mut(&self, f: impl FnOnce(&mut LocalActorGroup)) {
@@ -306,9 +472,35 @@ where
     }
 }
 
+/// Represents a weighted route's settings, see [`Topology::weighted()`].
+pub struct Weighted {
+    name: String,
+    route: weighted::Route,
+}
+
+#[sealed]
+impl<F> Destination<F> for Weighted
+where
+    F: Fn(&Envelope) -> bool + Send + Sync + 'static,
+{
+    fn extend_demux(&self, _: GroupNo, demux: &mut Demux, filter: F) {
+        let route = self.route.clone();
+        demux.append(move |envelope, addrs| {
+            if filter(envelope) {
+                if let Some(addr) = route.load().pick(envelope.trace_id()) {
+                    addrs.push(addr);
+                }
+            }
+        });
+    }
+
+    fn connection_endpoint(&self) -> ConnectionTo {
+        ConnectionTo::Weighted(self.name.clone())
+    }
+}
+
 cfg_network!({
     use arc_swap::ArcSwap;
-    use fxhash::FxHashMap;
 
     use crate::remote::RemoteHandle;
 
@@ -370,9 +562,15 @@ cfg_network!({
                 });
             }
 
+            // Make `GroupRef`s to this group work.
+            self.book
+                .groups()
+                .add_remote_node(remote_group_name, remote_group.0, handle_addr);
+
             RegisterRemoteGroupGuard {
                 book: &self.book,
                 handle_addr,
+                remote_group_name: remote_group_name.into(),
                 network_actor_addr,
                 local_group,
                 remote_group,
@@ -398,6 +596,7 @@ cfg_network!({
                 name: name.clone(),
                 nodes: Default::default(),
             });
+            self.book.groups().insert_remote(&name);
 
             Remote {
                 topology: self,
@@ -491,6 +690,7 @@ cfg_network!({
     pub struct RegisterRemoteGroupGuard<'a> {
         book: &'a AddressBook,
         handle_addr: Addr,
+        remote_group_name: String,
         network_actor_addr: Addr,
         local_group: GroupNo,
         remote_group: (NodeNo, GroupNo),
@@ -515,6 +715,11 @@ cfg_network!({
 
             // Disable direct messaging.
             self.book.remove(self.handle_addr);
+            self.book.groups().remove_remote_node(
+                &self.remote_group_name,
+                self.remote_group.0,
+                self.handle_addr,
+            );
 
             // Disable routing to this node if it was possible.
             if let Some(nodes) = &self.nodes {
@@ -0,0 +1,404 @@
+//! Export of the topology as a graph, see [`Topology::graph()`].
+
+use std::{
+    any,
+    fmt::Write,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use fxhash::FxHashMap;
+use parking_lot::Mutex;
+
+use super::{ConnectionTo, Topology};
+use crate::{addr::Addr, message};
+
+/// A graph of groups and routes between them.
+///
+/// Groups are sorted by name, edges by their endpoints and messages by name,
+/// so the output is stable between calls.
+#[message(part)]
+#[non_exhaustive]
+pub struct TopologyGraph {
+    pub groups: Vec<GraphGroup>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// A local or remote group in [`TopologyGraph`].
+#[message(part)]
+#[non_exhaustive]
+pub struct GraphGroup {
+    pub name: String,
+    pub is_remote: bool,
+    pub is_entrypoint: bool,
+    /// The type name of the group's config, `None` for `()`.
+    pub config: Option<String>,
+    /// The router's type name, `None` if the group isn't mounted.
+    pub router: Option<String>,
+    /// `None` if defined by the `system.mailbox.capacity` config parameter.
+    pub mailbox_capacity: Option<usize>,
+    /// The size of the future returned by the exec function in bytes,
+    /// `None` if the group isn't mounted. See `system.max_exec_future_size`.
+    pub exec_future_size: Option<usize>,
+    /// Nodes of a remote group, learned via the network.
+    pub node_nos: Vec<u16>,
+}
+
+/// A route between two groups in [`TopologyGraph`].
+#[message(part)]
+#[non_exhaustive]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    /// Whether the route is declared by [`Local::route_to()`].
+    ///
+    /// [`Local::route_to()`]: super::Local::route_to
+    pub is_declared: bool,
+    /// Observed messages, see [`Topology::set_record_edges()`].
+    pub messages: Vec<EdgeMessage>,
+}
+
+/// The number of messages of one type sent along [`GraphEdge`].
+#[message(part)]
+#[non_exhaustive]
+pub struct EdgeMessage {
+    pub name: String,
+    pub count: u64,
+}
+
+impl TopologyGraph {
+    /// Renders the graph in the GraphViz DOT format.
+    ///
+    /// Remote groups are dashed, entrypoints have a double border,
+    /// undeclared edges (observed only) are dotted.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph topology {\n");
+
+        for group in &self.groups {
+            let mut label = group.name.clone();
+            let mut attrs = String::from("shape=box");
+
+            if group.is_remote {
+                let node_nos = group.node_nos.iter().map(|n| n.to_string());
+                let node_nos = node_nos.collect::<Vec<_>>().join(", ");
+                let _ = write!(label, "\nnodes: {node_nos}");
+                attrs.push_str(", style=dashed");
+            } else {
+                if let Some(config) = &group.config {
+                    let _ = write!(label, "\nconfig: {config}");
+                }
+                if let Some(router) = &group.router {
+                    let _ = write!(label, "\nrouter: {router}");
+                }
+                match group.mailbox_capacity {
+                    Some(capacity) => {
+                        let _ = write!(label, "\nmailbox: {capacity}");
+                    }
+                    None if group.router.is_some() => label.push_str("\nmailbox: config"),
+                    None => {}
+                }
+                if group.is_entrypoint {
+                    attrs.push_str(", peripheries=2");
+                }
+            }
+
+            let _ = writeln!(
+                out,
+                "    {} [label={}, {attrs}];",
+                quote(&group.name),
+                quote(&label)
+            );
+        }
+
+        for edge in &self.edges {
+            let mut attrs = Vec::new();
+
+            if !edge.messages.is_empty() {
+                let label = edge
+                    .messages
+                    .iter()
+                    .map(|m| format!("{} ({})", m.name, m.count));
+                attrs.push(format!(
+                    "label={}",
+                    quote(&label.collect::<Vec<_>>().join("\n"))
+                ));
+            }
+            if !edge.is_declared {
+                attrs.push("style=dotted".into());
+            }
+
+            let attrs = if attrs.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attrs.join(", "))
+            };
+
+            let _ = writeln!(
+                out,
+                "    {} -> {}{attrs};",
+                quote(&edge.from),
+                quote(&edge.to)
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the graph as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the graph is always serializable")
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Topology {
+    /// Builds a graph of all groups and routes between them.
+    ///
+    /// Edges include declared routes and, if enabled by
+    /// [`Topology::set_record_edges()`], observed ones labeled with message
+    /// names and counts. Remote groups contain nodes learned via the network.
+    ///
+    /// See [`TopologyGraph::to_dot()`] and [`TopologyGraph::to_json()`].
+    /// Also, the graph can be requested live by [`GetTopologyGraph`].
+    ///
+    /// [`GetTopologyGraph`]: crate::messages::GetTopologyGraph
+    pub fn graph(&self) -> TopologyGraph {
+        let inner = self.inner.read();
+        let mut groups = Vec::new();
+        let mut names = FxHashMap::<Addr, &str>::default();
+
+        for local in &inner.locals {
+            names.insert(local.addr, &local.name);
+
+            let description = local.description.as_ref();
+            groups.push(GraphGroup {
+                name: local.name.clone(),
+                is_remote: false,
+                is_entrypoint: local.is_entrypoint,
+                config: description.and_then(|d| d.config).map(Into::into),
+                router: description.map(|d| d.router.into()),
+                mailbox_capacity: description.and_then(|d| d.mailbox_capacity),
+                exec_future_size: description.map(|d| d.exec_future_size),
+                node_nos: Vec::new(),
+            });
+        }
+
+        #[cfg(feature = "network")]
+        for remote in &inner.remotes {
+            let mut node_nos = Vec::new();
+
+            for nodes in remote.nodes.values() {
+                for (node_no, handle_addr) in nodes.load().iter() {
+                    names.insert(*handle_addr, &remote.name);
+                    node_nos.push(node_no.into_bits());
+                }
+            }
+
+            node_nos.sort_unstable();
+            node_nos.dedup();
+
+            groups.push(GraphGroup {
+                name: remote.name.clone(),
+                is_remote: true,
+                is_entrypoint: false,
+                config: None,
+                router: None,
+                mailbox_capacity: None,
+                exec_future_size: None,
+                node_nos,
+            });
+        }
+
+        // (from, to) => (is_declared, name => count)
+        let mut edges = FxHashMap::<(&str, &str), (bool, FxHashMap<&str, u64>)>::default();
+
+        for connection in &inner.connections {
+            let Some(&from) = names.get(&connection.from) else {
+                continue;
+            };
+            let to = match &connection.to {
+                ConnectionTo::Local(addr) => ward!(names.get(addr), continue),
+                // Edges lead to currently configured targets.
+                ConnectionTo::Weighted(name) => {
+                    let route = ward!(inner.weighted.get(name), continue);
+                    for addr in route.load().addrs() {
+                        if let Some(&to) = names.get(&addr) {
+                            edges.entry((from, to)).or_default().0 = true;
+                        }
+                    }
+                    continue;
+                }
+                #[cfg(feature = "network")]
+                ConnectionTo::Remote(name) => name.as_str(),
+            };
+            edges.entry((from, to)).or_default().0 = true;
+        }
+
+        for ((from, to, message), count) in self.book.edge_recorder().snapshot() {
+            let (Some(&from), Some(&to)) = (names.get(&from), names.get(&to)) else {
+                continue;
+            };
+            let messages = &mut edges.entry((from, to)).or_default().1;
+            *messages.entry(message).or_default() += count;
+        }
+
+        let mut edges = edges
+            .into_iter()
+            .map(|((from, to), (is_declared, messages))| {
+                let mut messages = messages
+                    .into_iter()
+                    .map(|(name, count)| EdgeMessage {
+                        name: name.into(),
+                        count,
+                    })
+                    .collect::<Vec<_>>();
+                messages.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+                GraphEdge {
+                    from: from.into(),
+                    to: to.into(),
+                    is_declared,
+                    messages,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        groups.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        edges.sort_unstable_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+
+        TopologyGraph { groups, edges }
+    }
+
+    /// Renders [`Topology::graph()`] in the GraphViz DOT format.
+    pub fn to_dot(&self) -> String {
+        self.graph().to_dot()
+    }
+
+    /// Renders [`Topology::graph()`] as JSON.
+    pub fn to_json(&self) -> String {
+        self.graph().to_json()
+    }
+
+    /// Enables or disables counting of messages sent between groups, which
+    /// are exposed as edges in [`Topology::graph()`]. Can be toggled at any
+    /// time, e.g. by an admin endpoint. Counters are kept when disabled.
+    ///
+    /// Only routed messages (`send()` and `try_send()`, but not `send_to()`)
+    /// are counted. It's disabled by default, because it takes a lock on
+    /// every sent message.
+    pub fn set_record_edges(&self, enabled: bool) {
+        self.book.edge_recorder().set_enabled(enabled);
+    }
+}
+
+// === GroupDescription ===
+
+/// Settings of a mounted group exposed in the graph.
+#[derive(Debug, Clone)]
+pub(crate) struct GroupDescription {
+    config: Option<&'static str>,
+    router: &'static str,
+    mailbox_capacity: Option<usize>,
+    exec_future_size: usize,
+}
+
+impl GroupDescription {
+    pub(crate) fn new<C: 'static, R: 'static, O>(mailbox_capacity: Option<usize>) -> Self {
+        let config = any::type_name::<C>();
+        let router = any::type_name::<R>();
+
+        Self {
+            config: (config != "()").then(|| strip_generics(config)),
+            router: if router == "()" {
+                "Singleton"
+            } else {
+                let router = strip_generics(router);
+                router.rsplit("::").next().unwrap_or(router)
+            },
+            mailbox_capacity,
+            exec_future_size: mem::size_of::<O>(),
+        }
+    }
+}
+
+/// `a::B<c::D>` => `a::B`
+fn strip_generics(type_name: &'static str) -> &'static str {
+    type_name.split('<').next().unwrap_or(type_name)
+}
+
+// === EdgeRecorder ===
+
+/// (from group, to group or remote handle, message name) => count
+type EdgeCounts = FxHashMap<(Addr, Addr, &'static str), u64>;
+
+#[derive(Default)]
+pub(crate) struct EdgeRecorder {
+    is_enabled: AtomicBool,
+    counts: Mutex<EdgeCounts>,
+}
+
+impl EdgeRecorder {
+    fn set_enabled(&self, enabled: bool) {
+        self.is_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record(&self, from: Addr, to: &[Addr], message: &'static str) {
+        let mut counts = self.counts.lock();
+        for to in to {
+            *counts.entry((from, *to, message)).or_default() += 1;
+        }
+    }
+
+    fn snapshot(&self) -> EdgeCounts {
+        self.counts.lock().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote() {
+        assert_eq!(super::quote(r#"a "b"\c"#), r#""a \"b\"\\c""#);
+        assert_eq!(super::quote("a\nb"), r#""a\nb""#);
+    }
+
+    #[test]
+    fn description() {
+        struct Config;
+        let d = GroupDescription::new::<Config, (), [u8; 100]>(None);
+        assert_eq!(
+            d.config,
+            Some("elfo_core::topology::graph::tests::description::Config")
+        );
+        assert_eq!(d.router, "Singleton");
+        assert_eq!(d.exec_future_size, 100);
+
+        let d =
+            GroupDescription::new::<(), crate::routers::MapRouter<(), (), fn(), u32>, ()>(Some(5));
+        assert_eq!(d.config, None);
+        assert_eq!(d.router, "MapRouter");
+        assert_eq!(d.mailbox_capacity, Some(5));
+    }
+}
@@ -0,0 +1,312 @@
+//! Warm standby groups, see [`Topology::standby()`].
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use arc_swap::ArcSwap;
+use parking_lot::Mutex;
+
+use super::{Local, Topology};
+use crate::{
+    addr::Addr,
+    demux::Addrs,
+    envelope::{Envelope, MessageKind},
+    messages::{StandbyRole, StandbySwitched},
+};
+
+/// Settings of a standby relationship, see [`Topology::standby()`].
+#[derive(Debug, Clone)]
+pub struct StandbyConfig {
+    /// Messages routed to the active primary, which are also delivered to the
+    /// standby to keep it warm. Only regular messages are mirrored, requests
+    /// are handled by the active group only.
+    pub mirror: MessageFilter,
+    /// When the primary is considered unhealthy and traffic is switched.
+    pub failover_on: HealthPolicy,
+}
+
+/// A predicate over envelopes, see [`StandbyConfig::mirror`].
+#[derive(Clone)]
+pub struct MessageFilter(FilterKind);
+
+#[derive(Clone)]
+enum FilterKind {
+    All,
+    None,
+    Custom(Arc<dyn Fn(&Envelope) -> bool + Send + Sync>),
+}
+
+impl MessageFilter {
+    /// Matches envelopes accepted by the function.
+    pub fn new(f: impl Fn(&Envelope) -> bool + Send + Sync + 'static) -> Self {
+        Self(FilterKind::Custom(Arc::new(f)))
+    }
+
+    /// Matches all envelopes.
+    pub fn all() -> Self {
+        Self(FilterKind::All)
+    }
+
+    /// Matches nothing.
+    pub fn none() -> Self {
+        Self(FilterKind::None)
+    }
+
+    fn matches(&self, envelope: &Envelope) -> bool {
+        match &self.0 {
+            FilterKind::All => true,
+            FilterKind::None => false,
+            FilterKind::Custom(f) => f(envelope),
+        }
+    }
+}
+
+impl fmt::Debug for MessageFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            FilterKind::All => f.write_str("MessageFilter::all()"),
+            FilterKind::None => f.write_str("MessageFilter::none()"),
+            FilterKind::Custom(_) => f.write_str("MessageFilter::new(..)"),
+        }
+    }
+}
+
+/// Defines when the primary is considered unhealthy, see
+/// [`StandbyConfig::failover_on`].
+#[derive(Debug, Clone)]
+pub struct HealthPolicy {
+    failures: Option<u32>,
+}
+
+impl HealthPolicy {
+    /// Traffic is switched only by [`SetStandby`].
+    ///
+    /// [`SetStandby`]: crate::messages::SetStandby
+    pub fn manual() -> Self {
+        Self { failures: None }
+    }
+
+    /// Traffic is switched once actors of the primary have failed (returned
+    /// an error or panicked) `threshold` times since it has become active.
+    ///
+    /// # Panics
+    /// If `threshold` is zero.
+    pub fn failures(threshold: u32) -> Self {
+        assert!(threshold > 0, "threshold must be positive");
+        Self {
+            failures: Some(threshold),
+        }
+    }
+}
+
+impl Topology {
+    /// Declares `standby` as a warm replica of `primary`.
+    ///
+    /// While the primary is active, messages routed to it and matched by
+    /// [`StandbyConfig::mirror`] are also delivered to the standby. All
+    /// messages received by the standby are flagged as
+    /// [`Envelope::is_passive()`] meanwhile, so handlers can warm up caches
+    /// skipping side effects.
+    ///
+    /// Once the primary is considered unhealthy by
+    /// [`StandbyConfig::failover_on`], routing is switched atomically: new
+    /// messages (including requests) routed to the primary are delivered to
+    /// the standby instead, which stops flagging them as passive. Messages
+    /// sent directly to the primary's actors aren't affected.
+    ///
+    /// The switch can be forced or reverted by [`SetStandby`]. Every switch
+    /// is logged, dumped and sent to lifecycle subscribers of the primary as
+    /// [`StandbySwitched`].
+    ///
+    /// # Panics
+    /// If the groups are the same or the primary already has a standby.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # #[elfo::message] struct Quote;
+    /// use elfo::topology::{HealthPolicy, MessageFilter, StandbyConfig};
+    ///
+    /// let topology = elfo::Topology::empty();
+    /// let cache = topology.local("cache");
+    /// let cache_standby = topology.local("cache.standby");
+    ///
+    /// topology.standby(&cache, &cache_standby, StandbyConfig {
+    ///     mirror: MessageFilter::new(|envelope| envelope.is::<Quote>()),
+    ///     failover_on: HealthPolicy::failures(3),
+    /// });
+    /// ```
+    ///
+    /// [`SetStandby`]: crate::messages::SetStandby
+    /// [`StandbySwitched`]: crate::messages::StandbySwitched
+    pub fn standby(&self, primary: &Local<'_>, standby: &Local<'_>, config: StandbyConfig) {
+        let pair = Pair {
+            primary: primary.addr(),
+            standby: standby.addr(),
+            primary_name: primary.name.clone(),
+            standby_name: standby.name.clone(),
+            config,
+            is_failed_over: AtomicBool::new(false),
+            control: Mutex::default(),
+        };
+
+        assert_ne!(
+            pair.primary, pair.standby,
+            "a group cannot be its own standby"
+        );
+        self.book.standbys().add(pair);
+    }
+}
+
+/// All standby relationships of the node, shared with contexts.
+#[derive(Default)]
+pub(crate) struct Standbys {
+    has_pairs: AtomicBool,
+    pairs: ArcSwap<Vec<Arc<Pair>>>,
+}
+
+struct Pair {
+    primary: Addr,
+    standby: Addr,
+    primary_name: String,
+    standby_name: String,
+    config: StandbyConfig,
+    /// Read on every routed message.
+    is_failed_over: AtomicBool,
+    control: Mutex<Control>,
+}
+
+#[derive(Default)]
+struct Control {
+    forced: Option<StandbyRole>,
+    failures: u32,
+}
+
+/// The role of a group in a standby relationship.
+pub(crate) enum Member {
+    Primary,
+    Standby(Addr),
+}
+
+impl Standbys {
+    fn add(&self, pair: Pair) {
+        let mut pairs = Vec::clone(&self.pairs.load());
+
+        if pairs.iter().any(|p| p.primary == pair.primary) {
+            panic!("`{}` already has a standby", pair.primary_name);
+        }
+
+        pairs.push(Arc::new(pair));
+        self.pairs.store(Arc::new(pairs));
+        self.has_pairs.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.has_pairs.load(Ordering::Relaxed)
+    }
+
+    /// Replaces failed over primaries with standbys and adds mirrored ones.
+    pub(crate) fn reroute(&self, envelope: &Envelope, addrs: &mut Addrs) {
+        for pair in self.pairs.load().iter() {
+            let Some(idx) = addrs.iter().position(|addr| *addr == pair.primary) else {
+                continue;
+            };
+
+            if pair.is_failed_over.load(Ordering::Acquire) {
+                if addrs.contains(&pair.standby) {
+                    addrs.remove(idx);
+                } else {
+                    addrs[idx] = pair.standby;
+                }
+            } else if matches!(envelope.message_kind(), MessageKind::Regular { .. })
+                && pair.config.mirror.matches(envelope)
+                && !addrs.contains(&pair.standby)
+            {
+                addrs.push(pair.standby);
+            }
+        }
+    }
+
+    /// Returns `true` if the group is a standby, which isn't active now.
+    pub(crate) fn is_passive(&self, group: Addr) -> bool {
+        self.pairs
+            .load()
+            .iter()
+            .any(|pair| pair.standby == group && !pair.is_failed_over.load(Ordering::Acquire))
+    }
+
+    /// Returns the role of the group in the relationship of `primary`.
+    pub(crate) fn member(&self, group: Addr, primary: &str) -> Option<Member> {
+        let pairs = self.pairs.load();
+        let pair = pairs.iter().find(|pair| pair.primary_name == primary)?;
+
+        if pair.primary == group {
+            Some(Member::Primary)
+        } else if pair.standby == group {
+            Some(Member::Standby(pair.primary))
+        } else {
+            None
+        }
+    }
+
+    /// Counts a failure of the primary's actor.
+    /// Returns the event if traffic is switched to the standby.
+    pub(crate) fn on_failure(&self, primary: Addr) -> Option<StandbySwitched> {
+        let pairs = self.pairs.load();
+        let pair = pairs.iter().find(|pair| pair.primary == primary)?;
+        let threshold = pair.config.failover_on.failures?;
+
+        let mut control = pair.control.lock();
+        if control.forced.is_some() || pair.is_failed_over.load(Ordering::Acquire) {
+            return None;
+        }
+
+        control.failures += 1;
+        if control.failures < threshold {
+            return None;
+        }
+
+        pair.switch(StandbyRole::Standby, false)
+    }
+
+    /// Forces the active group, `None` returns to automatic mode starting
+    /// from the primary. Returns the event if traffic is switched.
+    pub(crate) fn force(
+        &self,
+        primary: Addr,
+        role: Option<StandbyRole>,
+    ) -> Option<StandbySwitched> {
+        let pairs = self.pairs.load();
+        let pair = pairs.iter().find(|pair| pair.primary == primary)?;
+
+        let mut control = pair.control.lock();
+        control.forced = role;
+        control.failures = 0;
+
+        pair.switch(role.unwrap_or(StandbyRole::Primary), true)
+    }
+}
+
+impl Pair {
+    // Must be called under the control lock.
+    fn switch(&self, active: StandbyRole, forced: bool) -> Option<StandbySwitched> {
+        let is_failed_over = active == StandbyRole::Standby;
+        if self.is_failed_over.swap(is_failed_over, Ordering::AcqRel) == is_failed_over {
+            return None;
+        }
+
+        Some(StandbySwitched {
+            primary: self.primary_name.clone(),
+            standby: self.standby_name.clone(),
+            active,
+            forced,
+            timestamp: elfo_utils::time::SystemTime::now().into(),
+        })
+    }
+}
@@ -0,0 +1,187 @@
+//! Weighted routes between groups, see [`Topology::weighted()`].
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
+
+use super::{Topology, Weighted};
+use crate::{addr::Addr, messages::ConfigRejected, tracing::TraceId};
+
+/// The `routes` section of the config, logical name => route.
+///
+/// ```toml
+/// [routes.pricing]
+/// targets = [
+///     { group = "pricing-v1", weight = 90 },
+///     { group = "pricing-v2", weight = 10 },
+/// ]
+/// ```
+pub type RoutesConfig = FxHashMap<String, RouteConfig>;
+
+/// A config of one weighted route, see [`Topology::weighted()`].
+#[derive(Debug, Clone, Deserialize)]
+#[non_exhaustive]
+pub struct RouteConfig {
+    /// Concrete groups to split messages between.
+    pub targets: Vec<RouteTarget>,
+}
+
+/// A concrete group of [`RouteConfig`].
+#[derive(Debug, Clone, Deserialize)]
+#[non_exhaustive]
+pub struct RouteTarget {
+    /// The name of a local group.
+    pub group: String,
+    /// The share of traces routed to the group relative to other targets.
+    pub weight: u32,
+}
+
+/// Current targets of a weighted route, shared with demuxes.
+pub(crate) type Route = Arc<ArcSwap<Targets>>;
+
+/// Targets with cumulative weights, sorted by declaration order.
+#[derive(Debug, Default)]
+pub(crate) struct Targets {
+    bounds: Vec<(u64, Addr)>,
+}
+
+impl Targets {
+    pub(crate) fn addrs(&self) -> impl Iterator<Item = Addr> + '_ {
+        self.bounds.iter().map(|(_, addr)| *addr)
+    }
+
+    /// Picks a target for the trace, the same one for the same trace id
+    /// until weights are changed.
+    pub(super) fn pick(&self, trace_id: TraceId) -> Option<Addr> {
+        let total = self.bounds.last()?.0;
+        let point = mix(trace_id.to_u64_lossy()) % total;
+        let idx = self.bounds.partition_point(|(bound, _)| *bound <= point);
+        Some(self.bounds[idx].1)
+    }
+}
+
+// The finalizer of SplitMix64.
+// Bits of trace ids are far from uniform, so they must be mixed.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+impl Topology {
+    /// Declares a logical destination, which splits messages between
+    /// concrete local groups according to weights in the `routes` section of
+    /// the config, e.g. to shift traffic gradually between two implementations
+    /// of the same group.
+    ///
+    /// The target is chosen by the trace id, so all messages of one trace
+    /// are routed to the same group. Weights are reloaded by config updates,
+    /// unknown groups are rejected. Until configured, nothing is routed.
+    ///
+    /// The chosen group can be found in incoming dumps of the message.
+    ///
+    /// # Example
+    /// ```
+    /// # use elfo_core as elfo;
+    /// # use elfo::message;
+    /// # #[message(ret = u32)] struct GetPrice;
+    /// let topology = elfo::Topology::empty();
+    /// let client = topology.local("client");
+    /// let _pricing_v1 = topology.local("pricing-v1");
+    /// let _pricing_v2 = topology.local("pricing-v2");
+    ///
+    /// // [routes.pricing]
+    /// // targets = [
+    /// //     { group = "pricing-v1", weight = 90 },
+    /// //     { group = "pricing-v2", weight = 10 },
+    /// // ]
+    /// client.route_to(&topology.weighted("pricing"), |envelope| {
+    ///     elfo::msg!(match envelope {
+    ///         GetPrice => true,
+    ///         _ => false,
+    ///     })
+    /// });
+    /// ```
+    pub fn weighted(&self, name: impl Into<String>) -> Weighted {
+        let name = name.into();
+        let route = self
+            .inner
+            .write()
+            .weighted
+            .entry(name.clone())
+            .or_default()
+            .clone();
+
+        Weighted { name, route }
+    }
+
+    /// Checks that all routes are declared by [`Topology::weighted()`] and
+    /// targets are known local groups with positive total weights.
+    #[stability::unstable]
+    pub fn check_routes(&self, config: &RoutesConfig) -> Result<(), ConfigRejected> {
+        self.resolve_routes(config).map(drop)
+    }
+
+    /// Checks and applies the `routes` section of the config.
+    /// Routes missing in the config are reset, so nothing is routed by them.
+    #[stability::unstable]
+    pub fn set_routes(&self, config: &RoutesConfig) -> Result<(), ConfigRejected> {
+        let mut resolved = self.resolve_routes(config)?;
+
+        let inner = self.inner.read();
+        for (name, route) in &inner.weighted {
+            route.store(Arc::new(resolved.remove(name).unwrap_or_default()));
+        }
+
+        Ok(())
+    }
+
+    fn resolve_routes(
+        &self,
+        config: &RoutesConfig,
+    ) -> Result<FxHashMap<String, Targets>, ConfigRejected> {
+        let inner = self.inner.read();
+        let mut resolved = FxHashMap::default();
+
+        for (name, route) in config {
+            if !inner.weighted.contains_key(name) {
+                return Err(format!("unknown route `{name}`").into());
+            }
+
+            let mut seen = FxHashSet::default();
+            let mut bounds = Vec::with_capacity(route.targets.len());
+            let mut total = 0;
+
+            for target in &route.targets {
+                let group = inner
+                    .locals
+                    .iter()
+                    .find(|group| group.name == target.group)
+                    .ok_or_else(|| {
+                        format!("route `{name}`: unknown target group `{}`", target.group)
+                    })?;
+
+                if !seen.insert(group.addr) {
+                    let reason = format!("route `{name}`: duplicate target `{}`", target.group);
+                    return Err(reason.into());
+                }
+
+                // Zero weights are allowed to disable targets.
+                if target.weight > 0 {
+                    total += u64::from(target.weight);
+                    bounds.push((total, group.addr));
+                }
+            }
+
+            if total == 0 {
+                return Err(format!("route `{name}`: total weight must be positive").into());
+            }
+
+            resolved.insert(name.clone(), Targets { bounds });
+        }
+
+        Ok(resolved)
+    }
+}
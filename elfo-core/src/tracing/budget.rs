@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use once_cell::sync::OnceCell;
+use tokio::time::Instant;
+
+use crate::config::Rate;
+
+/// Selects new traces to be detailed, see `system.tracing.detailed_budget`.
+///
+/// Selections are spread over the period: at most one trace is selected per
+/// `period / count`, and the unused budget isn't accumulated.
+#[derive(Default)]
+pub(crate) struct DetailedBudget {
+    /// The minimal interval between selections in nanos, `0` if disabled.
+    step: AtomicU64,
+    /// Nanos since `origin`, when the next trace can be selected.
+    next: AtomicU64,
+    // Uses `tokio`'s time in order to be driven by the virtual time in tests.
+    origin: OnceCell<Instant>,
+}
+
+impl DetailedBudget {
+    pub(crate) fn configure(&self, budget: Option<Rate>) {
+        let step = budget
+            .filter(|budget| budget.count() > 0)
+            .map_or(0, |budget| {
+                let step = budget.period().as_nanos() / u128::from(budget.count());
+                step.clamp(1, u128::from(u64::MAX)) as u64
+            });
+
+        self.step.store(step, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if a new trace should be detailed.
+    #[inline]
+    pub(crate) fn try_select(&self) -> bool {
+        let step = self.step.load(Ordering::Relaxed);
+        if step == 0 {
+            return false;
+        }
+
+        self.do_try_select(step)
+    }
+
+    fn do_try_select(&self, step: u64) -> bool {
+        let origin = *self.origin.get_or_init(Instant::now);
+        let now = Instant::now().duration_since(origin).as_nanos() as u64;
+
+        self.next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                (next <= now).then_some(now.saturating_add(step))
+            })
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn budget(s: &str) -> DetailedBudget {
+        let budget = DetailedBudget::default();
+        budget.configure(Some(s.parse().unwrap()));
+        budget
+    }
+
+    /// Tries to select a trace every `tick` during `duration`, returns offsets
+    /// of selected ones.
+    async fn run(budget: &DetailedBudget, tick: Duration, duration: Duration) -> Vec<Duration> {
+        let start = Instant::now();
+        let mut selected = Vec::new();
+
+        while start.elapsed() < duration {
+            if budget.try_select() {
+                selected.push(start.elapsed());
+            }
+            tokio::time::sleep(tick).await;
+        }
+
+        selected
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spread() {
+        let budget = budget("5/m");
+        let tick = Duration::from_millis(100);
+        let selected = run(&budget, tick, Duration::from_secs(120)).await;
+
+        // The budget is honored and isn't front-loaded.
+        assert_eq!(selected.len(), 10);
+        for pair in selected.windows(2) {
+            assert_eq!(pair[1] - pair[0], Duration::from_secs(12));
+        }
+
+        // The unused budget isn't accumulated.
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(run(&budget, tick, Duration::from_secs(12)).await.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disabled() {
+        let budget = DetailedBudget::default();
+        let tick = Duration::from_secs(1);
+        assert!(run(&budget, tick, Duration::from_secs(10)).await.is_empty());
+
+        budget.configure(Some("0/m".parse().unwrap()));
+        assert!(run(&budget, tick, Duration::from_secs(10)).await.is_empty());
+
+        // Selection can be enabled later.
+        budget.configure(Some("1/s".parse().unwrap()));
+        assert_eq!(run(&budget, tick, Duration::from_secs(10)).await.len(), 10);
+
+        budget.configure(None);
+        assert!(run(&budget, tick, Duration::from_secs(10)).await.is_empty());
+    }
+}
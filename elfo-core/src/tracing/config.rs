@@ -0,0 +1,73 @@
+//! [Config].
+//!
+//! [Config]: TracingConfig
+
+use serde::Deserialize;
+
+use crate::config::{Duration, Rate};
+
+/// Tracing configuration.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.tracing.detailed_budget = "5/m"
+/// system.tracing.fan_out.soft_limit = 10000
+/// system.tracing.fan_out.hard_limit = 100000
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// How many traces started by the group (e.g. by ticks of timers,
+    /// signals and streams) are detailed.
+    ///
+    /// Detailed traces are handled as marked by [`Context::force_sampling()`]:
+    /// in all groups they're logged with at least `Debug` level and dumped
+    /// bypassing trace sampling and rate limits, but still bounded by
+    /// `max_detailed_trace_size` of dumpers. So, there is often already a
+    /// fully instrumented example, when something odd shows up.
+    ///
+    /// Traces are selected evenly over the period, e.g. one per 12s for
+    /// `"5/m"`, the unused budget isn't accumulated.
+    ///
+    /// Disabled by default.
+    ///
+    /// [`Context::force_sampling()`]: crate::Context::force_sampling
+    pub detailed_budget: Option<Rate>,
+    /// Protects against amplification storms, when one inbound message
+    /// recursively fans out into a huge number of messages within its trace.
+    pub fan_out: FanOutConfig,
+}
+
+/// Limits of messages sent within one trace, see [`TracingConfig::fan_out`].
+///
+/// Messages sent by actors of the group are counted per trace id on the node,
+/// so limits are usually set in the `[common]` section. System messages are
+/// neither counted nor limited.
+///
+/// Both limits are disabled by default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FanOutConfig {
+    /// Once the trace has sent more messages, a warning with the trace id
+    /// and the top sending groups is logged.
+    pub soft_limit: Option<u64>,
+    /// Once the trace has sent more messages, further sends within the trace
+    /// fail with [`ErrorKind::TraceBudgetExceeded`].
+    ///
+    /// [`ErrorKind::TraceBudgetExceeded`]: crate::errors::ErrorKind::TraceBudgetExceeded
+    pub hard_limit: Option<u64>,
+    /// How long the trace is counted after its last message.
+    /// Then the counter is reset. `1m` by default.
+    pub ttl: Duration,
+}
+
+impl Default for FanOutConfig {
+    fn default() -> Self {
+        Self {
+            soft_limit: None,
+            hard_limit: None,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
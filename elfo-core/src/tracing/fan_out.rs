@@ -0,0 +1,307 @@
+use std::{
+    cmp::Reverse,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use fxhash::FxHashMap;
+use metrics::increment_counter;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use tokio::time::Instant;
+use tracing::warn;
+
+use super::{config::FanOutConfig, TraceId};
+use crate::actor::ActorMeta;
+
+const SHARDS: usize = 16;
+/// Bounds the memory: at most `SHARDS * SHARD_CAPACITY` traces are counted.
+const SHARD_CAPACITY: usize = 1024;
+/// Only the top sending groups are kept per trace.
+const MAX_GROUPS: usize = 8;
+
+/// Counts messages sent within traces on the node, see
+/// `system.tracing.fan_out`.
+static TRACES: Lazy<Traces> = Lazy::new(Traces::default);
+
+/// Limits of the group, see `system.tracing.fan_out`.
+pub(crate) struct FanOutLimits {
+    /// `u64::MAX` if disabled.
+    soft: AtomicU64,
+    /// `u64::MAX` if disabled.
+    hard: AtomicU64,
+    ttl: AtomicU64,
+}
+
+impl Default for FanOutLimits {
+    fn default() -> Self {
+        let this = Self {
+            soft: AtomicU64::new(u64::MAX),
+            hard: AtomicU64::new(u64::MAX),
+            ttl: AtomicU64::new(0),
+        };
+        this.configure(&FanOutConfig::default());
+        this
+    }
+}
+
+impl FanOutLimits {
+    pub(crate) fn configure(&self, config: &FanOutConfig) {
+        let soft = config.soft_limit.unwrap_or(u64::MAX);
+        let hard = config.hard_limit.unwrap_or(u64::MAX);
+        let ttl = config.ttl.as_nanos().min(u128::from(u64::MAX)) as u64;
+
+        self.soft.store(soft, Ordering::Relaxed);
+        self.hard.store(hard, Ordering::Relaxed);
+        self.ttl.store(ttl, Ordering::Relaxed);
+    }
+
+    /// Counts the message sent within the trace.
+    /// Returns `false` if the hard limit is exceeded.
+    #[inline]
+    pub(crate) fn spend(&self, trace_id: TraceId, meta: &Arc<ActorMeta>, protocol: &str) -> bool {
+        let soft = self.soft.load(Ordering::Relaxed);
+        let hard = self.hard.load(Ordering::Relaxed);
+
+        // System messages are exempt, e.g. to terminate the storm.
+        if (soft == u64::MAX && hard == u64::MAX) || protocol == "elfo-core" {
+            return true;
+        }
+
+        let ttl = Duration::from_nanos(self.ttl.load(Ordering::Relaxed));
+        let count = TRACES.add(trace_id, meta, ttl, soft);
+
+        if count <= hard {
+            return true;
+        }
+
+        increment_counter!("elfo_trace_budget_exceeded_total");
+        false
+    }
+}
+
+#[derive(Default)]
+struct Traces {
+    shards: [Mutex<FxHashMap<TraceId, Counter>>; SHARDS],
+}
+
+struct Counter {
+    messages: u64,
+    started: Instant,
+    expires: Instant,
+    groups: SmallVec<[(Arc<ActorMeta>, u64); 2]>,
+}
+
+impl Traces {
+    /// Returns the number of messages sent within the trace.
+    fn add(&self, trace_id: TraceId, meta: &Arc<ActorMeta>, ttl: Duration, soft: u64) -> u64 {
+        let now = Instant::now();
+        let mut shard = self.shards[shard_index(trace_id)].lock();
+
+        if shard.len() >= SHARD_CAPACITY && !shard.contains_key(&trace_id) {
+            evict(&mut shard, now);
+        }
+
+        let counter = shard.entry(trace_id).or_insert_with(|| Counter {
+            messages: 0,
+            started: now,
+            expires: now,
+            groups: SmallVec::new(),
+        });
+
+        // The trace has aged out, count it from scratch.
+        if counter.expires < now {
+            counter.messages = 0;
+            counter.started = now;
+            counter.groups.clear();
+        }
+
+        counter.messages += 1;
+        counter.expires = now + ttl;
+
+        let groups = &mut counter.groups;
+        match groups.iter().position(|(m, _)| m.group == meta.group) {
+            Some(index) => groups[index].1 += 1,
+            None if groups.len() < MAX_GROUPS => groups.push((meta.clone(), 1)),
+            None => {}
+        }
+
+        if counter.messages == soft.saturating_add(1) {
+            let groups = top_groups(&counter.groups)
+                .iter()
+                .map(|(group, count)| format!("{group}={count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            warn!(
+                %trace_id,
+                messages = counter.messages,
+                top_groups = %groups,
+                "too many messages sent within the trace"
+            );
+        }
+
+        counter.messages
+    }
+
+    fn top(&self, limit: usize) -> Vec<TraceFanOut> {
+        let now = Instant::now();
+        let mut top = Vec::new();
+
+        for shard in &self.shards {
+            let shard = shard.lock();
+            top.extend(
+                shard
+                    .iter()
+                    .filter(|(_, counter)| counter.expires >= now)
+                    .map(|(trace_id, counter)| TraceFanOut {
+                        trace_id: *trace_id,
+                        messages: counter.messages,
+                        groups: top_groups(&counter.groups),
+                        age: now.duration_since(counter.started),
+                    }),
+            );
+        }
+
+        top.sort_unstable_by_key(|trace| Reverse(trace.messages));
+        top.truncate(limit);
+        top
+    }
+}
+
+fn top_groups(groups: &[(Arc<ActorMeta>, u64)]) -> Vec<(String, u64)> {
+    let mut groups = groups
+        .iter()
+        .map(|(meta, count)| (meta.group.clone(), *count))
+        .collect::<Vec<_>>();
+    groups.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    groups
+}
+
+fn shard_index(trace_id: TraceId) -> usize {
+    // Low bits are filled by the generator's counter, so they're well spread.
+    (u128::from(trace_id) % SHARDS as u128) as usize
+}
+
+/// Removes aged out traces or, if there are none, the least recently used one.
+#[cold]
+fn evict(shard: &mut FxHashMap<TraceId, Counter>, now: Instant) {
+    shard.retain(|_, counter| counter.expires >= now);
+
+    if shard.len() < SHARD_CAPACITY {
+        return;
+    }
+
+    let oldest = shard
+        .iter()
+        .min_by_key(|(_, counter)| counter.expires)
+        .map(|(trace_id, _)| *trace_id);
+
+    if let Some(trace_id) = oldest {
+        shard.remove(&trace_id);
+    }
+}
+
+/// Messages sent within the trace, see [`top_traces()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TraceFanOut {
+    /// The trace id.
+    pub trace_id: TraceId,
+    /// The number of messages sent within the trace on this node.
+    pub messages: u64,
+    /// The top sending groups with their numbers of messages, sorted by them.
+    pub groups: Vec<(String, u64)>,
+    /// The time since the first counted message.
+    pub age: Duration,
+}
+
+/// Returns up to `limit` traces with the most messages sent within them,
+/// see `system.tracing.fan_out`. Only traces sent by groups with configured
+/// limits are counted.
+pub fn top_traces(limit: usize) -> Vec<TraceFanOut> {
+    TRACES.top(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(group: &str) -> Arc<ActorMeta> {
+        Arc::new(ActorMeta {
+            group: group.into(),
+            key: String::new(),
+        })
+    }
+
+    fn limits(soft: u64, hard: u64) -> FanOutLimits {
+        let limits = FanOutLimits::default();
+        limits.configure(&FanOutConfig {
+            soft_limit: Some(soft),
+            hard_limit: Some(hard),
+            ttl: Duration::from_secs(1).into(),
+        });
+        limits
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hard_limit() {
+        let limits = limits(2, 3);
+        let trace_id = TraceId::try_from(0x1001).unwrap();
+        let (a, b) = (meta("a"), meta("b"));
+
+        assert!(limits.spend(trace_id, &a, "test"));
+        assert!(limits.spend(trace_id, &b, "test"));
+        assert!(limits.spend(trace_id, &b, "test"));
+        assert!(!limits.spend(trace_id, &b, "test"));
+
+        // System messages are exempt.
+        assert!(limits.spend(trace_id, &a, "elfo-core"));
+
+        // Other traces aren't affected.
+        let other = TraceId::try_from(0x1002).unwrap();
+        assert!(limits.spend(other, &a, "test"));
+
+        let top = top_traces(usize::MAX);
+        let top = top.iter().find(|t| t.trace_id == trace_id).unwrap();
+        assert_eq!(top.messages, 4);
+        assert_eq!(top.groups, [("b".into(), 3), ("a".into(), 1)]);
+
+        // The counter is reset once the trace ages out.
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(limits.spend(trace_id, &a, "test"));
+    }
+
+    #[test]
+    fn disabled() {
+        let limits = FanOutLimits::default();
+        let trace_id = TraceId::try_from(0x2001).unwrap();
+
+        for _ in 0..10 {
+            assert!(limits.spend(trace_id, &meta("a"), "test"));
+        }
+
+        assert!(!top_traces(usize::MAX)
+            .iter()
+            .any(|t| t.trace_id == trace_id));
+    }
+
+    #[test]
+    fn bounded() {
+        let traces = Traces::default();
+        let a = meta("a");
+
+        for i in 1..=(SHARDS * SHARD_CAPACITY * 2) as u64 {
+            let trace_id = TraceId::try_from(i).unwrap();
+            traces.add(trace_id, &a, Duration::from_secs(60), u64::MAX);
+        }
+
+        let len = traces.shards.iter().map(|s| s.lock().len()).sum::<usize>();
+        assert_eq!(len, SHARDS * SHARD_CAPACITY);
+    }
+}
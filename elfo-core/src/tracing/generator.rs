@@ -1,5 +1,8 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
+#[cfg(feature = "trace-id-128")]
+use once_cell::sync::Lazy;
+
 use elfo_utils::time::Instant;
 
 use super::trace_id::{TraceId, TraceIdLayout, TruncatedTime};
@@ -16,6 +19,10 @@ fn next_chunk(chunk_registry: &ChunkRegistry) -> u32 {
 
 // === Generator ===
 
+/// The upper half of 128-bit trace ids, random per process.
+#[cfg(feature = "trace-id-128")]
+static EPOCH: Lazy<u64> = Lazy::new(crate::addr::random_u64);
+
 pub(crate) struct Generator {
     node_no: Option<NodeNo>,
     timestamp: CachedTruncatedTime,
@@ -36,6 +43,7 @@ impl Default for Generator {
 
 impl Generator {
     /// Generates a new trace id according to the next layout:
+    /// * 64 bits per-process random epoch (only with `trace-id-128`)
     /// * 1  bit  0 (zero)
     /// * 25 bits timestamp in secs
     /// * 16 bits node_no
@@ -62,6 +70,8 @@ impl Generator {
         }
 
         TraceId::from_layout(TraceIdLayout {
+            #[cfg(feature = "trace-id-128")]
+            epoch: *EPOCH,
             timestamp: self.timestamp.get(),
             node_no: self.node_no,
             bottom: bottom.into(),
@@ -105,7 +115,7 @@ impl CachedTruncatedTime {
 
 #[cfg(test)]
 mod tests {
-    use std::{sync::Arc, time::Duration};
+    use std::{collections::HashSet, sync::Arc, time::Duration};
 
     use crate::{scope::Scope, ActorMeta, Addr};
 
@@ -122,17 +132,23 @@ mod tests {
             let mut generator = Generator::default();
 
             let sec = 1 << 38;
-            let st = u64::from(generator.generate(&chunk_registry));
+            let st = generator.generate(&chunk_registry).to_u64_lossy();
 
             mock.advance(Duration::from_millis(500));
-            assert_eq!(u64::from(generator.generate(&chunk_registry)), st + 1);
+            assert_eq!(generator.generate(&chunk_registry).to_u64_lossy(), st + 1);
             mock.advance(Duration::from_millis(500));
-            assert_eq!(u64::from(generator.generate(&chunk_registry)), st + sec + 2,);
+            assert_eq!(
+                generator.generate(&chunk_registry).to_u64_lossy(),
+                st + sec + 2,
+            );
             mock.advance(Duration::from_millis(500));
-            assert_eq!(u64::from(generator.generate(&chunk_registry)), st + sec + 3);
+            assert_eq!(
+                generator.generate(&chunk_registry).to_u64_lossy(),
+                st + sec + 3
+            );
             mock.advance(Duration::from_millis(500));
             assert_eq!(
-                u64::from(generator.generate(&chunk_registry)),
+                generator.generate(&chunk_registry).to_u64_lossy(),
                 st + 2 * sec + 4
             );
 
@@ -142,7 +158,7 @@ mod tests {
                     mock.advance(Duration::from_secs(2));
                     let mut generator = Generator::default();
                     is_divisible_by_chunk(
-                        u64::from(generator.generate(&chunk_registry1)) - (st + 2 * sec),
+                        generator.generate(&chunk_registry1).to_u64_lossy() - (st + 2 * sec),
                     );
                 });
             })
@@ -151,15 +167,30 @@ mod tests {
 
             for i in 5..1023 {
                 assert_eq!(
-                    u64::from(generator.generate(&chunk_registry)),
+                    generator.generate(&chunk_registry).to_u64_lossy(),
                     st + 2 * sec + i
                 );
             }
 
-            is_divisible_by_chunk(u64::from(generator.generate(&chunk_registry)) - (st + 2 * sec));
+            is_divisible_by_chunk(
+                generator.generate(&chunk_registry).to_u64_lossy() - (st + 2 * sec),
+            );
         });
     }
 
+    #[cfg(feature = "trace-id-128")]
+    #[test]
+    fn epoch() {
+        let chunk_registry = Arc::new(ChunkRegistry::default());
+        let mut generator = Generator::default();
+
+        for _ in 0..2000 {
+            let trace_id = generator.generate(&chunk_registry);
+            assert_eq!((u128::from(trace_id) >> 64) as u64, *EPOCH);
+            assert_eq!(trace_id.to_layout().epoch, *EPOCH);
+        }
+    }
+
     #[test]
     fn node_no() {
         let chunk_registry = Arc::new(ChunkRegistry::default());
@@ -185,4 +216,55 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn unique_across_threads_and_nodes() {
+        const NODES: u16 = 4;
+        const THREADS_PER_NODE: usize = 4;
+        const IDS_PER_THREAD: usize = 50_000;
+
+        let handles = (1..=NODES)
+            .flat_map(|node_no| {
+                // Every node is a separate process with its own random epoch.
+                let chunk_registry = Arc::new(ChunkRegistry::new(crate::addr::random_u64()));
+
+                (0..THREADS_PER_NODE).map(move |_| {
+                    let chunk_registry = chunk_registry.clone();
+                    std::thread::spawn(move || {
+                        let mut generator = Generator {
+                            node_no: NodeNo::from_bits(node_no),
+                            ..Generator::default()
+                        };
+
+                        let ids = (0..IDS_PER_THREAD)
+                            .map(|_| generator.generate(&chunk_registry))
+                            .collect::<Vec<_>>();
+
+                        for pair in ids.windows(2) {
+                            let (prev, next) = (pair[0].timestamp(), pair[1].timestamp());
+                            // The timestamp can wrap around, but only once per ~388 days.
+                            assert!(prev <= next || next == 0, "{prev} > {next}");
+                        }
+
+                        assert!(ids
+                            .iter()
+                            .all(|id| id.node_no() == NodeNo::from_bits(node_no)));
+                        ids
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(seen.insert(id), "duplicate trace id: {id}");
+            }
+        }
+
+        assert_eq!(
+            seen.len(),
+            usize::from(NODES) * THREADS_PER_NODE * IDS_PER_THREAD
+        );
+    }
 }
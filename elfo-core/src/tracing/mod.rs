@@ -3,9 +3,16 @@
 
 use std::cell::RefCell;
 
+use once_cell::sync::Lazy;
+
 use self::generator::{ChunkRegistry, Generator};
 
-pub use self::{trace_id::TraceId, validator::TraceIdValidator};
+pub(crate) use self::{budget::DetailedBudget, fan_out::FanOutLimits, sampling::TraceSampler};
+pub use self::{
+    fan_out::{top_traces, TraceFanOut},
+    trace_id::{ParseTraceIdError, TraceId},
+    validator::TraceIdValidator,
+};
 
 impl TraceId {
     /// Generates a new trace id according to [the schema](https://actoromicon.rs/ch05-04-tracing.html#traceid).
@@ -14,11 +21,19 @@ impl TraceId {
     }
 }
 
-static CHUNK_REGISTRY: ChunkRegistry = ChunkRegistry::new(0);
+// Chunks start from a per-process random epoch, so processes with the same
+// `node_no` started within the same second are unlikely to produce equal ids.
+static CHUNK_REGISTRY: Lazy<ChunkRegistry> =
+    Lazy::new(|| ChunkRegistry::new(crate::addr::random_u64()));
 thread_local! {
     static GENERATOR: RefCell<Generator> = RefCell::new(Generator::default());
 }
 
+pub mod config;
+
+mod budget;
+mod fan_out;
 mod generator;
+mod sampling;
 mod trace_id;
 mod validator;
@@ -0,0 +1,89 @@
+use super::TraceId;
+
+/// Decides whether a trace is sampled purely by its id, so all actors and
+/// nodes handling the same trace make the same decision without coordination.
+///
+/// Decisions are monotonic: a trace sampled with some rate is also sampled
+/// with any higher rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TraceSampler {
+    /// `u64::MAX` means that all traces are sampled.
+    threshold: u64,
+}
+
+impl Default for TraceSampler {
+    fn default() -> Self {
+        Self::new(1.)
+    }
+}
+
+impl TraceSampler {
+    pub(crate) fn new(rate: f64) -> Self {
+        let threshold = if rate >= 1. {
+            u64::MAX
+        } else {
+            // `as` saturates, so negative and NaN rates are handled as zero.
+            ((rate * u64::MAX as f64) as u64).min(u64::MAX - 1)
+        };
+
+        Self { threshold }
+    }
+
+    /// Used to store the sampler in an atomic.
+    pub(crate) fn into_bits(self) -> u64 {
+        self.threshold
+    }
+
+    pub(crate) fn from_bits(threshold: u64) -> Self {
+        Self { threshold }
+    }
+
+    #[inline]
+    pub(crate) fn is_sampled(&self, trace_id: TraceId) -> bool {
+        self.threshold == u64::MAX || mix(trace_id.to_u64_lossy()) < self.threshold
+    }
+}
+
+// The finalizer of splitmix64, must never be changed, because decisions
+// should be the same on all nodes.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(sampler: TraceSampler) -> f64 {
+        let count = 100_000;
+        let sampled = (1..=count)
+            .filter(|&raw| sampler.is_sampled(TraceId::try_from(raw).unwrap()))
+            .count();
+        sampled as f64 / count as f64
+    }
+
+    #[test]
+    fn it_works() {
+        assert_eq!(rate(TraceSampler::new(1.)), 1.);
+        assert_eq!(rate(TraceSampler::new(0.)), 0.);
+        assert_eq!(rate(TraceSampler::new(-1.)), 0.);
+
+        for expected in [0.01, 0.1, 0.5, 0.9] {
+            let actual = rate(TraceSampler::new(expected));
+            assert!((actual - expected).abs() < 0.005, "{actual} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn monotonic() {
+        let low = TraceSampler::new(0.1);
+        let high = TraceSampler::new(0.3);
+
+        for raw in 1..=10_000 {
+            let trace_id = TraceId::try_from(raw).unwrap();
+            assert!(!low.is_sampled(trace_id) || high.is_sampled(trace_id));
+        }
+    }
+}
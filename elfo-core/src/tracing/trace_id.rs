@@ -1,62 +1,365 @@
 use std::{
     convert::TryFrom,
-    num::{NonZeroU64, TryFromIntError},
+    fmt,
+    num::{NonZeroU128, NonZeroU64, TryFromIntError},
+    str::FromStr,
 };
 
-use derive_more::{Deref, Display, From, Into};
+use derive_more::Deref;
 use serde::{Deserialize, Serialize};
 
 use elfo_utils::time::SystemTime;
 
 use crate::addr::NodeNo;
 
+#[cfg(not(feature = "trace-id-128"))]
+type NonZeroRaw = NonZeroU64;
+#[cfg(feature = "trace-id-128")]
+type NonZeroRaw = NonZeroU128;
+
 /// The struct that represents the trace id.
-// TODO(v0.2): remove `derive(Deserialize)`.
+///
+/// Generated ids have the following layout (from the most significant bit):
+/// * 1  bit  0 (zero)
+/// * 25 bits timestamp in secs (truncated unix time)
+/// * 16 bits `node_no` (zero if generated outside the actor system)
+/// * 12 bits chunk number, starting from a per-process random epoch
+/// * 10 bits counter inside the chunk
+///
+/// Thus, ids are unique as long as nodes have distinct `node_no`s and a node
+/// generates less than 2^22 ids per second. Any nonzero `u64` is still
+/// accepted as a valid trace id, the layout is used only for generation and
+/// decomposition.
+///
+/// # 128-bit trace ids
+/// With the `trace-id-128` feature, trace ids are 128-bit. The lower half
+/// has the layout above, the upper one is random per process. Any nonzero
+/// `u128` is accepted, so ids of external tracing systems (e.g. W3C
+/// `traceparent`) are kept as is. `u64` ids are zero-extended, so the layout
+/// is still decomposed.
+///
+/// The feature changes the text form: [`Display`] and [`FromStr`] use 32 hex
+/// digits instead of a decimal number. Also, in human-readable formats (e.g.
+/// JSON in dumps) ids are serialized as such hex strings, and as `u128` in
+/// binary ones. Decimal numbers are still accepted for compatibility.
+///
+/// The network negotiates the width at handshake, so nodes built without the
+/// feature are supported: ids are zero-extended on receiving and truncated by
+/// [`TraceId::to_u64_lossy()`] on sending.
+///
+/// The feature doesn't change the set of methods and conversions, only their
+/// results: e.g. [`TryFrom<TraceId>`] for `u64` always succeeds without it.
+///
+/// [`Display`]: fmt::Display
+/// [`TryFrom<TraceId>`]: TryFrom
+// TODO(v0.2): remove `Deserialize`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[derive(Serialize, Deserialize, Into, From, Display)]
-#[display("{_0}")]
-pub struct TraceId(NonZeroU64);
+#[cfg_attr(not(feature = "trace-id-128"), derive(Serialize, Deserialize))]
+pub struct TraceId(NonZeroRaw);
 
 impl TraceId {
+    /// The size of the trace id in bits, 128 with the `trace-id-128` feature
+    /// and 64 otherwise.
+    pub const BITS: u32 = NonZeroRaw::BITS;
+
+    /// Returns the `node_no` part of the trace id.
+    ///
+    /// Returns `None` if the id was generated outside the actor system or
+    /// hasn't been produced by [`TraceId::generate()`] at all.
+    #[stability::unstable]
+    #[inline]
+    pub fn node_no(self) -> Option<NodeNo> {
+        self.to_layout().node_no
+    }
+
+    /// Returns the timestamp part of the trace id, a unix time in seconds
+    /// truncated to the lowest 25 bits (i.e. it wraps every ~388 days).
+    #[stability::unstable]
+    #[inline]
+    pub fn timestamp(self) -> u32 {
+        *self.to_layout().timestamp
+    }
+
+    /// Converts `u128` into the trace id. Fails on zero and, without the
+    /// `trace-id-128` feature, on values exceeding `u64`.
+    ///
+    /// It's not `TryFrom<u128>` to keep inference of `TraceId::try_from(1)`.
+    #[stability::unstable]
+    #[inline]
+    pub fn try_from_u128(raw: u128) -> Result<Self, TryFromIntError> {
+        #[cfg(not(feature = "trace-id-128"))]
+        let raw = u64::try_from(raw)?;
+
+        NonZeroRaw::try_from(raw).map(Self)
+    }
+
+    /// Returns the trace id as `u64`, losslessly without the `trace-id-128`
+    /// feature.
+    ///
+    /// With the feature, it's the lower half containing the layout, or the
+    /// upper one if the lower is zero. Used for peers supporting only 64-bit
+    /// trace ids.
+    #[stability::unstable]
+    #[inline]
+    pub fn to_u64_lossy(self) -> u64 {
+        narrow(u128::from(self))
+    }
+
+    /// Parses the trace id part of the W3C [`traceparent`] header, e.g.
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    ///
+    /// Lossless with the `trace-id-128` feature, otherwise the id is
+    /// truncated as by [`TraceId::to_u64_lossy()`].
+    ///
+    /// [`traceparent`]: https://www.w3.org/TR/trace-context/#traceparent-header
+    #[stability::unstable]
+    pub fn from_traceparent(traceparent: &str) -> Result<Self, ParseTraceIdError> {
+        let mut parts = traceparent.split('-');
+        let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ParseTraceIdError("traceparent must have four parts"));
+        };
+
+        // Future versions can append fields, but the first ones are the same.
+        if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return Err(ParseTraceIdError("unsupported traceparent version"));
+        }
+        if !is_hex(version) || !is_hex(parent_id) || parent_id.len() != 16 || !is_hex(flags) {
+            return Err(ParseTraceIdError("malformed traceparent"));
+        }
+
+        Self::from_hex(trace_id)
+    }
+
+    /// Makes the W3C [`traceparent`] header, the inverse of
+    /// [`TraceId::from_traceparent()`]. Without the `trace-id-128` feature,
+    /// the id is zero-extended.
+    ///
+    /// [`traceparent`]: https://www.w3.org/TR/trace-context/#traceparent-header
+    #[stability::unstable]
+    pub fn to_traceparent(self, parent_id: u64, is_sampled: bool) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            u128::from(self),
+            parent_id,
+            u8::from(is_sampled)
+        )
+    }
+
+    /// Parses 32 hex digits, truncating as by [`TraceId::to_u64_lossy()`]
+    /// without the `trace-id-128` feature.
+    fn from_hex(s: &str) -> Result<Self, ParseTraceIdError> {
+        if s.len() != 32 || !is_hex(s) {
+            return Err(ParseTraceIdError("must be 32 hex digits"));
+        }
+
+        let raw = u128::from_str_radix(s, 16).expect("checked above");
+
+        #[cfg(not(feature = "trace-id-128"))]
+        let raw = u128::from(narrow(raw));
+
+        Self::try_from_u128(raw).map_err(|_| ParseTraceIdError("cannot be zero"))
+    }
+
     pub(crate) fn from_layout(layout: TraceIdLayout) -> Self {
         let raw = (u64::from(*layout.timestamp)) << 38
             | u64::from(layout.node_no.map_or(0, |n| n.into_bits())) << 22
             | u64::from(*layout.bottom);
 
+        #[cfg(feature = "trace-id-128")]
+        return Self::try_from_u128(u128::from(layout.epoch) << 64 | u128::from(raw)).unwrap();
+
+        #[cfg(not(feature = "trace-id-128"))]
         Self::try_from(raw).unwrap()
     }
 
     pub(crate) fn to_layout(self) -> TraceIdLayout {
-        let raw = self.0.get();
+        let raw = u128::from(self);
+        let low = raw as u64;
 
         TraceIdLayout {
-            timestamp: TruncatedTime((raw >> 38) as u32 & 0x1ff_ffff),
-            node_no: NodeNo::from_bits((raw >> 22 & 0xffff) as u16),
-            bottom: Bottom((raw & 0x3f_ffff) as u32),
+            #[cfg(feature = "trace-id-128")]
+            epoch: (raw >> 64) as u64,
+            timestamp: TruncatedTime((low >> 38) as u32 & 0x1ff_ffff),
+            node_no: NodeNo::from_bits((low >> 22 & 0xffff) as u16),
+            bottom: Bottom((low & 0x3f_ffff) as u32),
         }
     }
 }
 
+fn narrow(raw: u128) -> u64 {
+    match raw as u64 {
+        0 => (raw >> 64) as u64,
+        low => low,
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
 impl TryFrom<u64> for TraceId {
     type Error = TryFromIntError;
 
     #[inline]
     fn try_from(raw: u64) -> Result<Self, Self::Error> {
-        NonZeroU64::try_from(raw).map(TraceId)
+        #[cfg(feature = "trace-id-128")]
+        let raw = u128::from(raw);
+
+        NonZeroRaw::try_from(raw).map(Self)
     }
 }
 
-impl From<TraceId> for u64 {
+impl From<NonZeroU64> for TraceId {
     #[inline]
+    #[allow(clippy::useless_conversion)]
+    fn from(raw: NonZeroU64) -> Self {
+        Self(raw.into())
+    }
+}
+
+/// Fails only with the `trace-id-128` feature on ids exceeding `u64`.
+/// Use [`TraceId::to_u64_lossy()`] for peers supporting only 64-bit ids.
+impl TryFrom<TraceId> for u64 {
+    type Error = TryFromIntError;
+
+    #[inline]
+    fn try_from(trace_id: TraceId) -> Result<Self, Self::Error> {
+        NonZeroU64::try_from(trace_id).map(NonZeroU64::get)
+    }
+}
+
+/// Fails only with the `trace-id-128` feature on ids exceeding `u64`.
+impl TryFrom<TraceId> for NonZeroU64 {
+    type Error = TryFromIntError;
+
+    #[inline]
+    fn try_from(trace_id: TraceId) -> Result<Self, Self::Error> {
+        #[cfg(not(feature = "trace-id-128"))]
+        return Ok(trace_id.0);
+
+        #[cfg(feature = "trace-id-128")]
+        NonZeroU64::try_from(trace_id.0)
+    }
+}
+
+impl From<TraceId> for NonZeroU128 {
+    #[inline]
+    #[allow(clippy::useless_conversion)]
     fn from(trace_id: TraceId) -> Self {
-        trace_id.0.get()
+        trace_id.0.into()
+    }
+}
+
+impl From<TraceId> for u128 {
+    #[inline]
+    #[allow(clippy::useless_conversion)]
+    fn from(trace_id: TraceId) -> Self {
+        trace_id.0.get().into()
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(not(feature = "trace-id-128"))]
+        return fmt::Display::fmt(&self.0, f);
+
+        #[cfg(feature = "trace-id-128")]
+        return write!(f, "{:032x}", self.0.get());
+    }
+}
+
+impl FromStr for TraceId {
+    type Err = ParseTraceIdError;
+
+    /// Parses the form produced by [`Display`]. With the `trace-id-128`
+    /// feature, decimal `u64` ids are also accepted.
+    ///
+    /// [`Display`]: fmt::Display
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if cfg!(feature = "trace-id-128") && s.len() == 32 {
+            return Self::from_hex(s);
+        }
+
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseTraceIdError("must be a decimal number"));
+        }
+
+        let raw = s
+            .parse::<u64>()
+            .map_err(|_| ParseTraceIdError("must fit into u64"))?;
+        Self::try_from(raw).map_err(|_| ParseTraceIdError("cannot be zero"))
+    }
+}
+
+#[cfg(feature = "trace-id-128")]
+impl Serialize for TraceId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u128(self.0.get())
+        }
     }
 }
 
+#[cfg(feature = "trace-id-128")]
+impl<'de> Deserialize<'de> for TraceId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de;
+
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = TraceId;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a nonzero number or a string of 32 hex digits")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                TraceId::try_from(v)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+                TraceId::try_from_u128(v)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Other("zero"), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(Visitor)
+        } else {
+            deserializer.deserialize_u128(Visitor)
+        }
+    }
+}
+
+/// An error of parsing [`TraceId`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTraceIdError(&'static str);
+
+impl fmt::Display for ParseTraceIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid trace id: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTraceIdError {}
+
 // === TraceIdLayout ===
 
 #[derive(Clone, Copy)]
 pub(crate) struct TraceIdLayout {
+    /// The upper half, random per process.
+    #[cfg(feature = "trace-id-128")]
+    pub(crate) epoch: u64,
     pub(crate) timestamp: TruncatedTime,
     pub(crate) node_no: Option<NodeNo>,
     pub(crate) bottom: Bottom,
@@ -126,3 +429,94 @@ fn layout_roundtrip() {
     check(5197794958151101819);
     check(8446744073709551614);
 }
+
+#[test]
+fn text_roundtrip() {
+    fn check(raw: u128, text: &str) {
+        let trace_id = TraceId::try_from_u128(raw).unwrap();
+        assert_eq!(trace_id.to_string(), text);
+        assert_eq!(text.parse::<TraceId>(), Ok(trace_id));
+
+        let json = serde_json::to_string(&trace_id).unwrap();
+        assert_eq!(serde_json::from_str::<TraceId>(&json).unwrap(), trace_id);
+    }
+
+    #[cfg(not(feature = "trace-id-128"))]
+    {
+        check(1, "1");
+        check(75997165362483795, "75997165362483795");
+        assert!(TraceId::try_from_u128(1 << 64).is_err());
+        assert!("0000000000000000000000000000002a"
+            .parse::<TraceId>()
+            .is_err());
+        assert_eq!(
+            serde_json::to_string(&TraceId::try_from(42).unwrap()).unwrap(),
+            "42"
+        );
+    }
+
+    #[cfg(feature = "trace-id-128")]
+    {
+        check(1, "00000000000000000000000000000001");
+        check(
+            0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736,
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+        );
+
+        // Legacy ids are zero-extended.
+        let trace_id = TraceId::try_from(42).unwrap();
+        assert_eq!("42".parse::<TraceId>(), Ok(trace_id));
+        assert_eq!(serde_json::from_str::<TraceId>("42").unwrap(), trace_id);
+        assert_eq!(
+            serde_json::to_string(&trace_id).unwrap(),
+            r#""0000000000000000000000000000002a""#
+        );
+    }
+
+    assert!("".parse::<TraceId>().is_err());
+    assert!("0".parse::<TraceId>().is_err());
+    assert!("+1".parse::<TraceId>().is_err());
+    assert!("00000000000000000000000000000000"
+        .parse::<TraceId>()
+        .is_err());
+}
+
+#[test]
+fn traceparent() {
+    let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let trace_id = TraceId::from_traceparent(header).unwrap();
+
+    #[cfg(not(feature = "trace-id-128"))]
+    assert_eq!(u64::try_from(trace_id), Ok(0xa3ce_929d_0e0e_4736));
+    #[cfg(feature = "trace-id-128")]
+    {
+        assert!(u64::try_from(trace_id).is_err());
+        assert_eq!(
+            u128::from(trace_id),
+            0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736
+        );
+        assert_eq!(trace_id.to_traceparent(0x00f0_67aa_0ba9_02b7, true), header);
+    }
+    assert_eq!(trace_id.to_u64_lossy(), 0xa3ce_929d_0e0e_4736);
+
+    // The upper half is used if the lower one is zero.
+    let trace_id =
+        TraceId::from_traceparent("00-4bf92f3577b34da60000000000000000-00f067aa0ba902b7-00");
+    assert_eq!(trace_id.unwrap().to_u64_lossy(), 0x4bf9_2f35_77b3_4da6);
+
+    // Future versions can have more fields.
+    assert!(
+        TraceId::from_traceparent(&format!("cc-{}-00f067aa0ba902b7-01-xx", "1".repeat(32))).is_ok()
+    );
+
+    for invalid in [
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e473z-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-xx",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+    ] {
+        assert!(TraceId::from_traceparent(invalid).is_err(), "{invalid}");
+    }
+}
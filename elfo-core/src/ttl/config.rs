@@ -0,0 +1,76 @@
+//! [Config].
+//!
+//! [Config]: TtlConfig
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{config, message::MessageVTable};
+
+/// Default TTLs of messages and requests sent by actors of the group.
+///
+/// For requests, it's the handling time limit applied if
+/// [`RequestLimits::max_handling_time`] isn't set by the code, limits set by
+/// the code always take precedence. Regular messages are dropped by local
+/// recipients if they haven't been received in time, messages sent to other
+/// nodes aren't expired.
+///
+/// Messages are specified by `Name` (in any protocol) or `protocol/Name`,
+/// `"*"` applies to other messages. Unknown messages are rejected, unless
+/// `allow_unknown` is set, e.g. for configs shared by several binaries.
+///
+/// # Example
+/// ```toml
+/// [some_group]
+/// system.ttl.GetQuote = "50ms"
+/// system.ttl.MarketDataUpdate = "50ms"
+/// system.ttl."*" = "5s"
+/// ```
+///
+/// [`RequestLimits::max_handling_time`]: crate::RequestLimits::max_handling_time
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "RawTtlConfig")]
+pub struct TtlConfig {
+    /// TTLs by messages, see above.
+    pub messages: BTreeMap<String, Duration>,
+    /// Allows messages unknown to the binary.
+    ///
+    /// `false` by default.
+    pub allow_unknown: bool,
+}
+
+#[derive(Deserialize)]
+struct RawTtlConfig {
+    #[serde(default)]
+    allow_unknown: bool,
+    #[serde(flatten)]
+    messages: BTreeMap<String, config::Duration>,
+}
+
+impl TryFrom<RawTtlConfig> for TtlConfig {
+    type Error = String;
+
+    fn try_from(raw: RawTtlConfig) -> Result<Self, Self::Error> {
+        let messages = raw
+            .messages
+            .into_iter()
+            .map(|(path, ttl)| (path, *ttl))
+            .collect::<BTreeMap<_, _>>();
+
+        if !raw.allow_unknown {
+            let unknown = messages
+                .keys()
+                .find(|path| *path != "*" && MessageVTable::lookup_by_path(path).is_empty());
+
+            if let Some(path) = unknown {
+                return Err(format!("unknown message `{path}` in ttl"));
+            }
+        }
+
+        Ok(Self {
+            messages,
+            allow_unknown: raw.allow_unknown,
+        })
+    }
+}
@@ -0,0 +1,54 @@
+//! Default TTLs of messages and requests, see [`TtlConfig`].
+
+use std::{sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use fxhash::FxHashMap;
+
+use self::config::TtlConfig;
+use crate::message::{MessageTypeId, MessageVTable};
+
+pub mod config;
+
+/// Default TTLs of messages and requests sent by actors of one group.
+#[derive(Default)]
+pub(crate) struct DefaultTtls(ArcSwap<Ttls>);
+
+#[derive(Default)]
+struct Ttls {
+    by_type: FxHashMap<MessageTypeId, Duration>,
+    /// Set by `"*"`.
+    fallback: Option<Duration>,
+}
+
+impl DefaultTtls {
+    pub(crate) fn configure(&self, config: &TtlConfig) {
+        let mut ttls = Ttls::default();
+
+        for (path, ttl) in &config.messages {
+            if path == "*" {
+                ttls.fallback = Some(*ttl);
+                continue;
+            }
+
+            for vtable in MessageVTable::lookup_by_path(path) {
+                ttls.by_type.insert(MessageTypeId::new(vtable), *ttl);
+            }
+        }
+
+        self.0.store(Arc::new(ttls));
+    }
+
+    /// Returns the default TTL of the message, if any.
+    #[inline]
+    pub(crate) fn get(&self, type_id: MessageTypeId) -> Option<Duration> {
+        let ttls = self.0.load();
+
+        // Fast path, defaults are rarely used.
+        if ttls.by_type.is_empty() {
+            return ttls.fallback;
+        }
+
+        ttls.by_type.get(&type_id).copied().or(ttls.fallback)
+    }
+}
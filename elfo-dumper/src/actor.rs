@@ -1,31 +1,41 @@
-use std::{iter, panic, sync::Arc, time::Duration};
+use std::{
+    iter, panic,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use eyre::{Result, WrapErr};
 use fxhash::FxHashSet;
+use metrics::{gauge, increment_counter};
 use parking_lot::Mutex;
 use tokio::task;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use elfo_core::{
-    dumping::INTERNAL_CLASS,
+    dumping::{self, INTERNAL_CLASS},
     message,
     messages::{ConfigUpdated, Terminate, UpdateConfig},
     msg,
     routers::{MapRouter, Outcome},
     scope::{self, SerdeMode},
     signal::{Signal, SignalKind},
+    stream::Stream,
     time::Interval,
-    ActorGroup, Blueprint, Context, RestartParams, RestartPolicy, TerminationPolicy,
+    ActorGroup, Blueprint, Context, KeyEncoding, RestartParams, RestartPolicy, TerminationPolicy,
 };
-use elfo_utils::ward;
+use elfo_utils::{ward, AdaptiveInterval, FlushReason};
 
 use crate::{
+    block_writer::BlockOptions,
     config::Config,
     dump_storage::{Drain, DumpRegistry, DumpStorage},
-    file_registry::{FileHandle, FileRegistry},
+    file_registry::{self, FileHandle, FileRegistry},
+    journal,
     reporter::{Report, Reporter},
     rule_set::RuleSet,
-    serializer::Serializer,
+    serializer::{Serializer, Trailer},
+    sinks::Sinks,
 };
 
 #[message]
@@ -34,19 +44,58 @@ struct StartDumperForClass(String);
 #[message]
 struct ReopenDumpFile;
 
+#[message]
+struct RotateDumpFile;
+
 #[message]
 struct DumpingTick;
 
+#[message]
+struct DumpingHighWater;
+
+#[message]
+struct JournalSyncTick;
+
+#[message]
+struct JournalSyncNeeded;
+
+/// Writes pending dumps of all classes immediately.
+///
+/// Responds once written, so dumps made before sending the request are
+/// guaranteed to be in files (but not synced to disk).
+#[message(ret = ())]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct FlushDumps {}
+
 struct Dumper {
     ctx: Context<Config, String>,
     dump_registry: Arc<DumpRegistry>,
     file_registry: Arc<FileRegistry>,
     interval: Interval<DumpingTick>,
+    write_interval: AdaptiveInterval,
+    journal_interval: Interval<JournalSyncTick>,
+    rotation_interval: Interval<RotateDumpFile>,
 
     // Used only by the manager actor.
     manager: Option<Manager>,
 }
 
+/// Active dump files, one per sink.
+struct DumpFile {
+    paths: Vec<String>,
+    slice: Option<i64>,
+    seq: u32,
+}
+
+struct Writer {
+    serializer: Serializer,
+    rule_set: RuleSet,
+    reporter: Reporter,
+    trailer: Trailer,
+    sinks: Sinks,
+}
+
 struct Manager {
     dump_storage: Arc<Mutex<DumpStorage>>,
     known_classes: FxHashSet<&'static str>,
@@ -71,124 +120,401 @@ impl Dumper {
             None
         };
 
+        let config = ctx.config();
+        dump_registry.set_high_water(config.write_high_water);
+        let write_interval = AdaptiveInterval::new(
+            *config.write_interval,
+            *config.max_write_interval,
+            config.write_high_water,
+        );
+
         Self {
             dump_registry,
             file_registry,
             interval: ctx.attach(Interval::new(DumpingTick)),
+            write_interval,
+            journal_interval: ctx.attach(Interval::new(JournalSyncTick)),
+            rotation_interval: ctx.attach(Interval::new(RotateDumpFile)),
             manager,
             ctx,
         }
     }
 
     async fn main(mut self) -> Result<()> {
-        let mut path = self.ctx.config().path(self.ctx.key());
-        self.file_registry
-            .open(&path, false)
-            .await
-            .wrap_err("cannot open the dump file")?;
-
-        let mut serializer = Serializer::new(self.dump_registry.class());
-        let mut rule_set = RuleSet::new(self.dump_registry.class());
-        let mut reporter = Reporter::new(self.ctx.config().log_cooldown);
-        let mut need_to_terminate = false;
+        let mut file = self.open_file(None, false).await?;
+
+        let mut writer = Writer {
+            serializer: Serializer::new(self.dump_registry.class()),
+            rule_set: RuleSet::new(self.dump_registry.class()),
+            reporter: Reporter::new(*self.ctx.config().log_cooldown),
+            trailer: Trailer::default(),
+            sinks: Sinks::new(self.ctx.config()),
+        };
 
-        rule_set.configure(&self.ctx.config().rules);
+        writer.rule_set.configure(&self.ctx.config().rules);
+        writer.serializer.configure(self.ctx.config());
 
         self.ctx
             .attach(Signal::new(SignalKind::UnixHangup, ReopenDumpFile));
 
+        let dump_registry = self.dump_registry.clone();
+        self.ctx.attach(Stream::generate(|mut e| async move {
+            loop {
+                dump_registry.high_water_reached().await;
+                e.emit(DumpingHighWater).await;
+            }
+        }));
+
+        let dump_registry = self.dump_registry.clone();
+        self.ctx.attach(Stream::generate(|mut e| async move {
+            loop {
+                dump_registry.journal().sync_needed().await;
+                e.emit(JournalSyncNeeded).await;
+            }
+        }));
+
+        self.configure_journal().await?;
+
         // TODO: use `interval.start_after` to set random time shift.
-        self.interval.start(self.ctx.config().write_interval);
+        self.interval.start(self.write_interval.current());
+
+        let mut terminated = None;
 
         while let Some(envelope) = self.ctx.recv().await {
             msg!(match envelope {
                 ConfigUpdated => {
                     let config = self.ctx.config();
-                    self.interval.set_period(config.write_interval);
-
-                    path = config.path(self.ctx.key());
-                    self.file_registry
-                        .open(&path, false)
-                        .await
-                        .wrap_err("cannot open the dump file")?;
-
-                    rule_set.configure(&config.rules);
-                    reporter.configure(config.log_cooldown);
+                    self.dump_registry.set_high_water(config.write_high_water);
+                    self.write_interval.configure(
+                        *config.write_interval,
+                        *config.max_write_interval,
+                        config.write_high_water,
+                    );
+                    self.interval.set_period(self.write_interval.current());
+
+                    writer.rule_set.configure(&config.rules);
+                    writer.serializer.configure(config);
+                    writer.reporter.configure(*config.log_cooldown);
+                    writer.sinks.configure(config);
 
                     if let Some(m) = &self.manager {
                         m.dump_storage.lock().configure(config.registry_capacity);
                     }
+
+                    self.configure_journal().await?;
+                    file = self.switch_file(file, false).await?;
                 }
                 ReopenDumpFile => {
-                    // TODO: reopen the dump file at most once.
-                    // It's possible to reopen the file multiple times,
-                    // if the same file is used for multiple classes.
-                    // It's ok for now, but should be fixed later.
-                    self.file_registry
-                        .open(&path, true)
-                        .await
-                        .wrap_err("cannot reopen the dump file")?;
-                }
-                DumpingTick => {
-                    let timeout = self.ctx.config().write_interval;
-                    let dump_registry = self.dump_registry.clone();
-                    let file = self.file_registry.acquire(&path).await;
-
-                    // A blocking background task that writes a lot of dumps in batch.
-                    // It's much faster than calling tokio's async functions.
-                    let background = move || -> Result<(Serializer, RuleSet, Reporter)> {
-                        let mut report = Report::default();
-
-                        let res = scope::with_serde_mode(SerdeMode::Dumping, || {
-                            write_dumps(
-                                dump_registry.drain(timeout),
-                                &mut serializer,
-                                &mut rule_set,
-                                file,
-                                &mut report,
-                            )
-                        });
-
-                        reporter.add(report);
-
-                        res?;
-                        Ok((serializer, rule_set, reporter))
-                    };
-
-                    // Run the background task and wait until it's completed.
-                    let scope = scope::expose();
-                    match task::spawn_blocking(|| scope.sync_within(background)).await {
-                        Ok(Ok(state)) => {
-                            serializer = state.0;
-                            rule_set = state.1;
-                            reporter = state.2;
+                    if self.ctx.config().has_seq() {
+                        file = self.switch_file(file, true).await?;
+                    } else {
+                        // TODO: reopen the dump file at most once.
+                        // It's possible to reopen the file multiple times,
+                        // if the same file is used for multiple classes.
+                        // It's ok for now, but should be fixed later.
+                        let options = BlockOptions::new(self.ctx.config());
+                        for path in &file.paths {
+                            self.file_registry
+                                .reopen(path, options)
+                                .await
+                                .wrap_err("cannot reopen the dump file")?;
                         }
-                        Ok(Err(err)) => return Err(err),
-                        Err(err) => panic::resume_unwind(err.into_panic()),
                     }
-
-                    if need_to_terminate {
-                        break;
+                }
+                RotateDumpFile => {
+                    if self.ctx.config().slice() == file.slice {
+                        // Woken up too early, e.g. the system clock is adjusted.
+                        self.schedule_rotation();
+                    } else {
+                        // Pending dumps are written to the previous file.
+                        let timeout = *self.ctx.config().write_interval;
+                        writer = self
+                            .write(&file.paths, writer, FlushReason::Timer, timeout)
+                            .await?;
+                        file = self.switch_file(file, false).await?;
                     }
-
+                }
+                DumpingTick => {
+                    let timeout = *self.ctx.config().write_interval;
+                    writer = self
+                        .write(&file.paths, writer, FlushReason::Timer, timeout)
+                        .await?;
                     self.spawn_dumpers_if_needed();
                 }
+                DumpingHighWater => {
+                    let timeout = *self.ctx.config().write_interval;
+                    writer = self
+                        .write(&file.paths, writer, FlushReason::HighWater, timeout)
+                        .await?;
+                    self.spawn_dumpers_if_needed();
+                }
+                JournalSyncTick | JournalSyncNeeded => {
+                    self.sync_journal().await?;
+                }
+                (FlushDumps, token) => {
+                    let timeout = *self.ctx.config().write_interval;
+                    writer = self
+                        .write(&file.paths, writer, FlushReason::Explicit, timeout)
+                        .await?;
+                    self.ctx.respond(token, ());
+                }
                 Terminate => {
-                    // Wait until the next tick to write the last dumps.
-                    need_to_terminate = true;
+                    let started_at = Instant::now();
+
+                    // Dumps made from now on are counted as lost.
+                    self.dump_registry.close();
+
+                    let timeout = *self.ctx.config().shutdown_timeout;
+                    let mut writer = self
+                        .write(&file.paths, writer, FlushReason::Shutdown, timeout)
+                        .await?;
+
+                    let discarded = self.dump_registry.discard();
+                    writer.trailer.lost = self.dump_registry.lost();
+                    writer.trailer.clean = discarded == 0;
+
+                    let trailer = self.write_trailer(&file.paths, writer).await?;
+
+                    // Everything is written, so the journal isn't needed anymore.
+                    if trailer.clean {
+                        self.close_journal().await?;
+                    }
+
+                    terminated = Some((trailer, started_at));
+                    break;
                 }
             });
         }
 
         info!("synchronizing the file");
-        self.file_registry
-            .sync(&path)
-            .await
-            .context("cannot sync the dump file")?;
+        for path in &file.paths {
+            self.file_registry
+                .sync(path)
+                .await
+                .context("cannot sync the dump file")?;
+        }
+
+        if let Some((trailer, started_at)) = terminated {
+            info!(
+                message = "dumper terminated",
+                written = trailer.written,
+                lost = trailer.lost,
+                clean = trailer.clean,
+                elapsed = ?started_at.elapsed(),
+            );
+        }
 
         Ok(())
     }
 
+    /// Opens the dump file of the current time slice. `{seq}` is continued
+    /// in the slice of `prev` and incremented if `bump` is set.
+    async fn open_file(&mut self, prev: Option<&DumpFile>, bump: bool) -> Result<DumpFile> {
+        let config = self.ctx.config();
+        let class = self.ctx.key();
+        let slice = config.slice();
+        let seq = match prev {
+            Some(prev) if prev.slice == slice => prev.seq + u32::from(bump),
+            _ => 0,
+        };
+
+        let options = BlockOptions::new(config);
+        let paths = config.paths(class, scope::node_no(), seq);
+        for (template, path) in config.templates().zip(&paths) {
+            self.file_registry
+                .open(path, options)
+                .await
+                .wrap_err("cannot open the dump file")?;
+
+            if let Some((link, target)) = template.current_link(class, path) {
+                let tag = KeyEncoding::Path.encode(class);
+                if let Err(err) = file_registry::update_link(&link, &target, &tag).await {
+                    warn!(link = %link.display(), error = %err, "cannot update the symlink");
+                }
+            }
+        }
+
+        self.schedule_rotation();
+        Ok(DumpFile { paths, slice, seq })
+    }
+
+    /// Opens the actual dump file and closes the previous one.
+    async fn switch_file(&mut self, prev: DumpFile, bump: bool) -> Result<DumpFile> {
+        let file = self.open_file(Some(&prev), bump).await?;
+
+        // Does nothing if the path is the same, because it's open twice.
+        for path in &prev.paths {
+            self.file_registry
+                .close(path)
+                .await
+                .wrap_err("cannot close the dump file")?;
+        }
+
+        Ok(file)
+    }
+
+    fn schedule_rotation(&self) {
+        match self.ctx.config().until_next_slice() {
+            Some(delay) => self.rotation_interval.start_after(delay, delay),
+            None => self.rotation_interval.stop(),
+        }
+    }
+
+    /// Writes pending dumps and schedules the next write.
+    async fn write(
+        &mut self,
+        paths: &[String],
+        mut writer: Writer,
+        reason: FlushReason,
+        timeout: Duration,
+    ) -> Result<Writer> {
+        let dump_registry = self.dump_registry.clone();
+        let files = self.acquire_files(paths).await;
+
+        // Partial blocks are written by the timer only once overdue.
+        let force = matches!(reason, FlushReason::Explicit | FlushReason::Shutdown);
+
+        // A blocking background task that writes a lot of dumps in batch.
+        // It's much faster than calling tokio's async functions.
+        let background = move || -> Result<(Writer, usize)> {
+            let mut report = Report::default();
+
+            let journal = dump_registry.journal();
+            let marker = journal.rotate().context("cannot rotate the journal")?;
+
+            let res: Result<_> = scope::with_serde_mode(SerdeMode::Dumping, || {
+                let mut dumps = dump_registry.drain(timeout);
+                let written = write_dumps(
+                    &mut dumps,
+                    &mut writer.serializer,
+                    &mut writer.rule_set,
+                    (&mut writer.sinks, &files),
+                    &mut report,
+                    &mut writer.trailer,
+                )?;
+                writer.sinks.flush(&files, force)?;
+
+                // Segments are released only if all their dumps are handled.
+                if !dumps.is_timed_out() {
+                    journal.release(marker);
+                }
+
+                Ok(written)
+            });
+
+            writer.reporter.add(report);
+
+            let written = res?;
+            Ok((writer, written))
+        };
+
+        // Run the background task and wait until it's completed.
+        let scope = scope::expose();
+        let (writer, written) = match task::spawn_blocking(|| scope.sync_within(background)).await {
+            Ok(res) => res?,
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        };
+
+        if written > 0 {
+            increment_counter!("elfo_flushes_total", "reason" => reason.as_str());
+        }
+
+        let interval = self.write_interval.on_flush(written);
+        self.interval.start(interval);
+        gauge!("elfo_flush_interval_seconds", interval.as_secs_f64());
+
+        Ok(writer)
+    }
+
+    /// Writes the trailer, which must be the last line of the class.
+    async fn write_trailer(&self, paths: &[String], mut writer: Writer) -> Result<Trailer> {
+        let files = self.acquire_files(paths).await;
+
+        let background = move || -> Result<Trailer> {
+            let chunk = writer.serializer.trailer(&writer.trailer);
+            writer.sinks.write(&files, chunk)?;
+            writer.sinks.flush(&files, true)?;
+            Ok(writer.trailer)
+        };
+
+        let scope = scope::expose();
+        match task::spawn_blocking(|| scope.sync_within(background)).await {
+            Ok(res) => res,
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        }
+    }
+
+    async fn acquire_files(&self, paths: &[String]) -> Vec<FileHandle> {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            files.push(self.file_registry.acquire(path).await);
+        }
+        files
+    }
+
+    /// Applies the journal's config. Once journaling is enabled, the manager
+    /// also recovers journals left by previous runs.
+    async fn configure_journal(&mut self) -> Result<()> {
+        let config = self.ctx.config();
+        let dir = config.journal_dir();
+        let sync_items = config.journal_sync_items;
+
+        if dir.is_some() {
+            self.journal_interval.start(*config.journal_sync_interval);
+        } else {
+            self.journal_interval.stop();
+        }
+
+        let recovery = self
+            .manager
+            .as_ref()
+            .filter(|m| dir.is_some() && m.dump_storage.lock().start_recovery())
+            .map(|m| m.dump_storage.clone());
+
+        let dump_registry = self.dump_registry.clone();
+        let background = move || -> Result<()> {
+            if let (Some(dir), Some(dump_storage)) = (&dir, recovery) {
+                recover_journals(dir, &dump_storage)?;
+            }
+
+            dump_registry
+                .journal()
+                .configure(dir.as_deref(), sync_items)
+                .context("cannot open the journal")
+        };
+
+        let scope = scope::expose();
+        match task::spawn_blocking(|| scope.sync_within(background)).await {
+            Ok(res) => res?,
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        }
+
+        // Recovered dumps can belong to new classes.
+        self.spawn_dumpers_if_needed();
+        Ok(())
+    }
+
+    /// Stops journaling and removes segments.
+    async fn close_journal(&self) -> Result<()> {
+        let dump_registry = self.dump_registry.clone();
+        let background = move || dump_registry.journal().configure(None, 0);
+
+        match task::spawn_blocking(background).await {
+            Ok(res) => res.context("cannot close the journal"),
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        }
+    }
+
+    async fn sync_journal(&self) -> Result<()> {
+        let dump_registry = self.dump_registry.clone();
+        let background = move || dump_registry.journal().sync();
+
+        match task::spawn_blocking(background).await {
+            Ok(res) => res.context("cannot sync the journal"),
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        }
+    }
+
     fn spawn_dumpers_if_needed(&mut self) {
         let m = ward!(self.manager.as_mut());
 
@@ -219,23 +545,55 @@ impl Dumper {
 }
 
 fn write_dumps(
-    dumps: Drain<'_>,
+    dumps: &mut Drain<'_>,
     serializer: &mut Serializer,
     rule_set: &mut RuleSet,
-    file: FileHandle,
+    (sinks, files): (&mut Sinks, &[FileHandle]),
     report: &mut Report,
-) -> Result<()> {
+    trailer: &mut Trailer,
+) -> Result<usize> {
+    let mut count = 0;
+
     for dump in dumps {
+        count += 1;
+        trailer.cover(dump.timestamp);
         let params = rule_set.get(dump.message_protocol, &dump.message_name);
         let chunk = ward!(serializer.append(&dump, params), continue);
-        file.write(chunk).context("cannot write to the dump file")?;
+        sinks.write(files, chunk)?;
     }
 
     let (chunk, new_report) = serializer.take();
+    trailer.written += new_report.appended;
     report.merge(new_report);
 
     if let Some(chunk) = chunk {
-        file.write(chunk).context("cannot write to the dump file")?;
+        sinks.write(files, chunk)?;
+    }
+
+    Ok(count)
+}
+
+/// Adds dumps from journals left by previous runs to registries.
+fn recover_journals(dir: &Path, dump_storage: &Mutex<DumpStorage>) -> Result<()> {
+    let (run_id, known) = {
+        let dump_storage = dump_storage.lock();
+        (dump_storage.run_id(), dump_storage.classes().clone())
+    };
+
+    let recovered = journal::recover(dir, run_id, &known).context("cannot recover the journal")?;
+
+    for recovered in recovered {
+        info!(
+            message = "dumps are recovered from the journal",
+            class = recovered.class,
+            dumps = recovered.dumps.len(),
+            segments = recovered.segments.len(),
+            skipped_bytes = recovered.skipped,
+        );
+
+        let registry = dump_storage.lock().registry(recovered.class);
+        registry.add_recovered(recovered.dumps);
+        registry.journal().adopt(recovered.segments);
     }
 
     Ok(())
@@ -256,12 +614,23 @@ pub(crate) fn new(dump_storage: Arc<Mutex<DumpStorage>>) -> Blueprint {
             Duration::from_secs(5),
             Duration::from_secs(30),
         )))
-        .stop_order(100)
+        // After other groups to capture their final dumps, but before the logger.
+        .stop_order(105)
+        .on_mount(|group| {
+            if !dumping::ENABLED {
+                panic!(
+                    "the dumper cannot be mounted as `{group}`, because dumping is disabled \
+                     (the `dumping` feature of `elfo-core` is off)"
+                );
+            }
+        })
         .router(MapRouter::new(move |envelope| {
             msg!(match envelope {
                 // TODO: there is a rare race condition here,
                 //       use `Broadcast & Unicast(INTERNAL_CLASS)` instead.
-                UpdateConfig => Outcome::Multicast(collect_classes(dump_storage.lock().classes())),
+                UpdateConfig | FlushDumps => {
+                    Outcome::Multicast(collect_classes(dump_storage.lock().classes()))
+                }
                 StartDumperForClass(class) => Outcome::Unicast(class.clone()),
                 _ => Outcome::Default,
             })
@@ -0,0 +1,520 @@
+//! Block-aligned writes of dump files, see `block_size` in the config.
+
+use std::{
+    fs::File,
+    io,
+    time::{Duration, Instant},
+};
+
+use metrics::{counter, histogram};
+use tracing::warn;
+
+use crate::config::{BlockPadding, Config};
+
+/// Offsets and sizes of direct writes must be aligned to this value,
+/// it's enough for all known filesystems and devices.
+pub(crate) const ALIGN: usize = 4096;
+
+/// A file written by [`BlockWriter`], abstracted for tests.
+pub(crate) trait RawFile {
+    /// Writes the whole buffer at the offset.
+    /// `direct` writes are aligned and must bypass the page cache.
+    fn write_at(&mut self, buf: &[u8], offset: u64, direct: bool) -> io::Result<()>;
+
+    /// Returns `true` if `direct` writes are supported, i.e. the file is open
+    /// with `O_DIRECT`.
+    fn is_direct(&self) -> bool;
+
+    /// Stops using `O_DIRECT`, e.g. if writes are refused by the filesystem.
+    fn disable_direct(&mut self);
+
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlockOptions {
+    pub(crate) block_size: usize,
+    pub(crate) direct_io: bool,
+    pub(crate) padding: BlockPadding,
+    pub(crate) max_delay: Duration,
+}
+
+impl BlockOptions {
+    pub(crate) fn new(config: &Config) -> Self {
+        // Rounded up to `ALIGN`, so full blocks can be written directly.
+        let block_size = config.block_size.as_usize();
+        let block_size = block_size.max(1).div_ceil(ALIGN) * ALIGN;
+
+        Self {
+            block_size,
+            direct_io: config.direct_io,
+            padding: config.block_padding,
+            max_delay: *config.block_flush_interval,
+        }
+    }
+}
+
+/// Accumulates chunks of dumps into blocks and writes them at offsets aligned
+/// to the block size. Partial blocks are written only by `flush()` and
+/// `close()`, and rewritten entirely once filled if `O_DIRECT` is used.
+pub(crate) struct BlockWriter<F> {
+    file: F,
+    options: BlockOptions,
+    /// The offset of the current block in the file.
+    block_start: u64,
+    /// Less than `block_size` only if the file has been opened unaligned.
+    capacity: usize,
+    block: AlignedBlock,
+    /// The length of the current block.
+    len: usize,
+    /// The number of bytes of the current block, which are already written.
+    written: usize,
+    /// When the oldest unwritten byte has been appended.
+    dirty_since: Option<Instant>,
+}
+
+impl<F: RawFile> BlockWriter<F> {
+    /// Continues the file of `len` bytes.
+    pub(crate) fn new(file: F, len: u64, options: BlockOptions) -> Self {
+        let unaligned = (len % options.block_size as u64) as usize;
+
+        Self {
+            file,
+            block_start: len,
+            capacity: options.block_size - unaligned,
+            block: AlignedBlock::new(options.block_size),
+            options,
+            len: 0,
+            written: 0,
+            dirty_since: None,
+        }
+    }
+
+    /// Appends the chunk, filled blocks are written immediately.
+    pub(crate) fn write(&mut self, mut chunk: &[u8]) -> io::Result<()> {
+        counter!("elfo_dump_payload_bytes_total", chunk.len() as u64);
+
+        while !chunk.is_empty() {
+            let size = chunk.len().min(self.capacity - self.len);
+            self.block.as_mut()[self.len..self.len + size].copy_from_slice(&chunk[..size]);
+            self.len += size;
+            chunk = &chunk[size..];
+
+            if self.dirty_since.is_none() {
+                self.dirty_since = Some(Instant::now());
+            }
+
+            if self.len == self.capacity {
+                self.write_block()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the partial block if `force` is set or it's been waiting for
+    /// longer than `block_flush_interval`.
+    pub(crate) fn flush(&mut self, force: bool, now: Instant) -> io::Result<()> {
+        let Some(dirty_since) = self.dirty_since else {
+            return Ok(());
+        };
+
+        if !force && now.saturating_duration_since(dirty_since) < self.options.max_delay {
+            return Ok(());
+        }
+
+        if self.options.padding == BlockPadding::OnFlush {
+            return self.pad();
+        }
+
+        // Written through the page cache, because it's unaligned.
+        let offset = self.block_start + self.written as u64;
+        self.write_raw(self.written, self.len, offset, false)?;
+        self.written = self.len;
+        self.dirty_since = None;
+        Ok(())
+    }
+
+    /// Writes the partial block before closing or rotating the file.
+    pub(crate) fn close(&mut self) -> io::Result<()> {
+        if self.options.padding != BlockPadding::None && self.len > 0 {
+            self.pad()
+        } else {
+            self.flush(true, Instant::now())
+        }
+    }
+
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        self.file.sync()
+    }
+
+    /// Fills the rest of the block with spaces ended by `\n`, so padding
+    /// looks like an empty line for readers.
+    fn pad(&mut self) -> io::Result<()> {
+        let block = &mut self.block.as_mut()[self.len..self.capacity];
+        block.fill(b' ');
+        block[block.len() - 1] = b'\n';
+        self.len = self.capacity;
+        self.write_block()
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        debug_assert_eq!(self.len, self.capacity);
+
+        // Only full aligned blocks can be written directly, so partially
+        // written blocks are rewritten entirely.
+        let mut is_written = false;
+        if self.file.is_direct() && self.capacity == self.block.size() {
+            match self.write_raw(0, self.len, self.block_start, true) {
+                Ok(()) => is_written = true,
+                Err(err) => {
+                    warn!(error = %err, "direct I/O is refused, falling back to buffered I/O");
+                    self.file.disable_direct();
+                }
+            }
+        }
+
+        if !is_written {
+            let offset = self.block_start + self.written as u64;
+            self.write_raw(self.written, self.len, offset, false)?;
+        }
+
+        self.block_start += self.capacity as u64;
+        self.capacity = self.block.size();
+        self.len = 0;
+        self.written = 0;
+        self.dirty_since = None;
+        Ok(())
+    }
+
+    fn write_raw(&mut self, from: usize, to: usize, offset: u64, direct: bool) -> io::Result<()> {
+        if from == to {
+            return Ok(());
+        }
+
+        let buf = &self.block.as_ref()[from..to];
+        self.file.write_at(buf, offset, direct)?;
+
+        counter!("elfo_dump_written_bytes_total", buf.len() as u64);
+        histogram!("elfo_dump_write_size_bytes", buf.len() as f64);
+        Ok(())
+    }
+}
+
+// === AlignedBlock ===
+
+/// A buffer aligned in memory to `ALIGN`, as required by `O_DIRECT`.
+struct AlignedBlock {
+    buffer: Vec<u8>,
+    start: usize,
+    size: usize,
+}
+
+impl AlignedBlock {
+    fn new(size: usize) -> Self {
+        let buffer = vec![0; size + ALIGN];
+        let start = buffer.as_ptr().align_offset(ALIGN);
+        assert!(start < ALIGN, "cannot align the buffer");
+        Self {
+            buffer,
+            start,
+            size,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer[self.start..self.start + self.size]
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[self.start..self.start + self.size]
+    }
+}
+
+// === DiskFile ===
+
+/// A real file, optionally with a second descriptor open with `O_DIRECT`.
+pub(crate) struct DiskFile {
+    file: File,
+    direct: Option<File>,
+}
+
+impl DiskFile {
+    pub(crate) fn new(file: File, direct: Option<File>) -> Self {
+        Self { file, direct }
+    }
+}
+
+impl RawFile for DiskFile {
+    fn write_at(&mut self, buf: &[u8], offset: u64, direct: bool) -> io::Result<()> {
+        let file = match &self.direct {
+            Some(file) if direct => file,
+            _ => &self.file,
+        };
+
+        write_all_at(file, buf, offset)
+    }
+
+    fn is_direct(&self) -> bool {
+        self.direct.is_some()
+    }
+
+    fn disable_direct(&mut self) {
+        self.direct = None;
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+#[cfg(unix)]
+fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(not(unix))]
+fn write_all_at(mut file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+/// Opens the file with `O_DIRECT` for aligned writes.
+/// Returns `None` if it's unsupported by the OS or the filesystem.
+pub(crate) async fn open_direct(path: &str) -> Option<File> {
+    #[cfg(target_os = "linux")]
+    {
+        let res = tokio::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)
+            .await;
+
+        match res {
+            Ok(file) => Some(file.into_std().await),
+            Err(err) => {
+                warn!(%path, error = %err, "direct I/O is unsupported, falling back to buffered I/O");
+                None
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        warn!(%path, "direct I/O is supported only on Linux, using buffered I/O");
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestFile {
+        content: Vec<u8>,
+        /// `(offset, size, direct)` of every write.
+        writes: Vec<(u64, usize, bool)>,
+        is_direct: bool,
+        refuse_direct: bool,
+    }
+
+    impl RawFile for TestFile {
+        fn write_at(&mut self, buf: &[u8], offset: u64, direct: bool) -> io::Result<()> {
+            if direct {
+                assert!(self.is_direct);
+                if self.refuse_direct {
+                    return Err(io::ErrorKind::InvalidInput.into());
+                }
+
+                assert_eq!(offset % ALIGN as u64, 0);
+                assert_eq!(buf.len() % ALIGN, 0);
+                assert_eq!(buf.as_ptr().align_offset(ALIGN), 0);
+            }
+
+            let offset = offset as usize;
+            if self.content.len() < offset + buf.len() {
+                self.content.resize(offset + buf.len(), 0);
+            }
+
+            self.content[offset..offset + buf.len()].copy_from_slice(buf);
+            self.writes.push((offset as u64, buf.len(), direct));
+            Ok(())
+        }
+
+        fn is_direct(&self) -> bool {
+            self.is_direct
+        }
+
+        fn disable_direct(&mut self) {
+            self.is_direct = false;
+        }
+
+        fn sync(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn writer(file: TestFile, padding: BlockPadding) -> BlockWriter<TestFile> {
+        let len = file.content.len() as u64;
+        let options = BlockOptions {
+            block_size: ALIGN,
+            direct_io: file.is_direct,
+            padding,
+            max_delay: Duration::from_secs(1),
+        };
+        BlockWriter::new(file, len, options)
+    }
+
+    fn direct() -> TestFile {
+        TestFile {
+            is_direct: true,
+            ..TestFile::default()
+        }
+    }
+
+    // Lines of 100 bytes.
+    fn items(from: usize, to: usize) -> Vec<u8> {
+        (from..to)
+            .flat_map(|no| format!("{no:099}\n").into_bytes())
+            .collect()
+    }
+
+    // Skips padding.
+    fn lines(content: &[u8]) -> Vec<u8> {
+        let content = std::str::from_utf8(content).unwrap();
+        content
+            .split_inclusive('\n')
+            .filter(|line| !line.trim().is_empty())
+            .flat_map(|line| line.bytes())
+            .collect()
+    }
+
+    #[test]
+    fn full_blocks() {
+        let mut w = writer(TestFile::default(), BlockPadding::None);
+
+        w.write(&items(0, 100)).unwrap();
+        assert_eq!(w.file.writes, [(0, 4096, false), (4096, 4096, false)]);
+        let now = Instant::now();
+
+        // Isn't overdue yet.
+        w.flush(false, now).unwrap();
+        assert_eq!(w.file.writes.len(), 2);
+
+        w.flush(false, now + Duration::from_secs(1)).unwrap();
+        assert_eq!(w.file.writes[2], (8192, 1808, false));
+        assert_eq!(w.file.content, items(0, 100));
+
+        // Nothing to write.
+        w.flush(true, now).unwrap();
+        w.close().unwrap();
+        assert_eq!(w.file.writes.len(), 3);
+    }
+
+    #[test]
+    fn partial_blocks_are_completed() {
+        let mut w = writer(TestFile::default(), BlockPadding::None);
+
+        w.write(&items(0, 10)).unwrap();
+        w.flush(true, Instant::now()).unwrap();
+        w.write(&items(10, 50)).unwrap();
+        w.close().unwrap();
+
+        assert_eq!(
+            w.file.writes,
+            [(0, 1000, false), (1000, 3096, false), (4096, 904, false)]
+        );
+        assert_eq!(w.file.content, items(0, 50));
+    }
+
+    #[test]
+    fn direct_rewrites_partial_blocks() {
+        let mut w = writer(direct(), BlockPadding::None);
+
+        w.write(&items(0, 10)).unwrap();
+        w.flush(true, Instant::now()).unwrap();
+        w.write(&items(10, 50)).unwrap();
+        w.close().unwrap();
+
+        assert_eq!(
+            w.file.writes,
+            [(0, 1000, false), (0, 4096, true), (4096, 904, false)]
+        );
+        assert_eq!(w.file.content, items(0, 50));
+    }
+
+    #[test]
+    fn direct_fallback() {
+        let file = TestFile {
+            refuse_direct: true,
+            ..direct()
+        };
+        let mut w = writer(file, BlockPadding::None);
+
+        w.write(&items(0, 100)).unwrap();
+        assert!(!w.file.is_direct);
+        assert_eq!(w.file.writes, [(0, 4096, false), (4096, 4096, false)]);
+
+        w.close().unwrap();
+        assert_eq!(w.file.content, items(0, 100));
+    }
+
+    #[test]
+    fn unaligned_file() {
+        let file = TestFile {
+            content: items(0, 10),
+            ..direct()
+        };
+        let mut w = writer(file, BlockPadding::None);
+
+        // The first block is shortened to reach the alignment.
+        w.write(&items(10, 100)).unwrap();
+        assert_eq!(w.file.writes, [(1000, 3096, false), (4096, 4096, true)]);
+
+        w.close().unwrap();
+        assert_eq!(w.file.content, items(0, 100));
+    }
+
+    #[test]
+    fn padding_on_close() {
+        let mut w = writer(direct(), BlockPadding::OnClose);
+
+        w.write(&items(0, 10)).unwrap();
+        w.flush(true, Instant::now()).unwrap();
+        w.write(&items(10, 20)).unwrap();
+        w.close().unwrap();
+
+        assert_eq!(w.file.writes, [(0, 1000, false), (0, 4096, true)]);
+        assert_eq!(w.file.content.len(), 4096);
+        assert!(w.file.content.ends_with(b"  \n"));
+
+        // The rotated file is continued from the aligned offset.
+        let mut file = w.file;
+        file.writes.clear();
+        let mut w = writer(file, BlockPadding::OnClose);
+        w.write(&items(20, 60)).unwrap();
+        w.close().unwrap();
+        assert_eq!(w.file.writes, [(4096, 4096, true)]);
+        assert_eq!(lines(&w.file.content), items(0, 60));
+    }
+
+    #[test]
+    fn padding_on_flush() {
+        let mut w = writer(direct(), BlockPadding::OnFlush);
+
+        w.write(&items(0, 10)).unwrap();
+        w.flush(true, Instant::now()).unwrap();
+        w.write(&items(10, 20)).unwrap();
+        w.flush(true, Instant::now()).unwrap();
+
+        assert_eq!(w.file.writes, [(0, 4096, true), (4096, 4096, true)]);
+        assert_eq!(lines(&w.file.content), items(0, 20));
+    }
+}
@@ -5,10 +5,20 @@
 //! structure (usually encoded in TOML) follows stable guarantees.
 //!
 //! The main structure here is [`Config`].
-use std::time::Duration;
+use std::{
+    fmt, iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use bytesize::ByteSize;
-use serde::Deserialize;
+use eyre::{bail, ensure, eyre};
+use serde::{de, Deserialize, Deserializer};
+
+use elfo_core::{
+    config::{ByteSize, Duration},
+    KeyEncoding,
+};
+use elfo_utils::time::SystemTime;
 
 /// The dumper's config.
 ///
@@ -34,26 +44,135 @@ use serde::Deserialize;
 ///     { class = "external", max_size = "1MiB" },
 /// ]
 /// ```
+///
+/// Files can be sliced by time, e.g. hourly files per class:
+/// ```toml
+/// [system.dumpers]
+/// path = "/path/{class}/{date}/{hour}-{seq}.dump"
+/// ```
+///
+/// Dumps can be written to another disk if the primary one fails:
+/// ```toml
+/// [system.dumpers]
+/// path = "/primary/{class}.dump"
+/// sinks = [{ kind = "file", path = "/fallback/{class}.dump" }]
+/// ```
 #[derive(Debug, Deserialize)]
 pub struct Config {
-    /// A path to a dump file or template:
+    /// A path to a dump file or template, see [`FileTemplate`]:
     /// * `path/all.dump` - one file.
     /// * `path/{class}.dump` - file per class.
-    pub path: String,
+    /// * `path/{class}/{date}/{hour}-{seq}.dump` - hourly files per class.
+    ///
+    /// Also accepted as `file_template`.
+    #[serde(alias = "file_template")]
+    pub path: FileTemplate,
+    /// Fallback sinks in order of priority, see [`Sink`].
+    ///
+    /// Dumps are written to the first healthy sink, `path` is the first one.
+    /// A sink becomes unhealthy after `sink_failure_threshold` consecutive
+    /// failed writes and is probed again every `sink_probe_interval`.
+    /// Chunks of dumps are never written to the same sink twice, so already
+    /// written dumps aren't replayed to a recovered sink.
+    ///
+    /// Empty by default.
+    #[serde(default)]
+    pub sinks: Vec<Sink>,
+    /// Whether to write dumps to all healthy sinks instead of the first one.
+    /// `false` by default.
+    #[serde(default)]
+    pub mirror: bool,
+    /// The number of consecutive failed writes, after which the sink is
+    /// considered unhealthy, see `sinks`.
+    /// `3` by default.
+    #[serde(default = "default_sink_failure_threshold")]
+    pub sink_failure_threshold: u32,
+    /// How often unhealthy sinks are probed, see `sinks`.
+    /// `10s` by default.
+    #[serde(default = "default_sink_probe_interval")]
+    pub sink_probe_interval: Duration,
+    /// A time zone used to render `{date}` and `{hour}` in `path`, see
+    /// [`TimeZone`].
+    /// `"UTC"` by default.
+    #[serde(default)]
+    pub timezone: TimeZone,
     /// How often dumpers should write dumps to files.
+    ///
+    /// The interval doubles after every write with no dumps up to
+    /// `max_write_interval` and is reset to `write_interval` once something
+    /// is written. Dumps are written immediately if more than
+    /// `write_high_water` of them are pending, on [`FlushDumps`] and on
+    /// termination.
+    ///
     /// `500ms` by default.
-    #[serde(with = "humantime_serde", default = "default_write_interval")]
+    ///
+    /// [`FlushDumps`]: crate::FlushDumps
+    #[serde(default = "default_write_interval")]
     pub write_interval: Duration,
+    /// The maximum interval between writes, see `write_interval`.
+    /// `5s` by default.
+    #[serde(default = "default_max_write_interval")]
+    pub max_write_interval: Duration,
+    /// The number of pending dumps of one class, at which they are written
+    /// without waiting for the interval. Note that the number is checked
+    /// with granularity of 8191 dumps.
+    /// `65536` by default.
+    #[serde(default = "default_write_high_water")]
+    pub write_high_water: usize,
+    /// Written dumps are accumulated into blocks of this size, which are
+    /// written at offsets aligned to the size, in order to avoid small writes.
+    /// Rounded up to a multiple of `4KiB`.
+    ///
+    /// A partial block is written on [`FlushDumps`], termination, closing
+    /// or rotation of the file and once it has been waiting for longer than
+    /// `block_flush_interval`. It's written through the page cache and
+    /// completed by the next write, see also `block_padding`.
+    ///
+    /// The ratio of `elfo_dump_written_bytes_total` to
+    /// `elfo_dump_payload_bytes_total` is write amplification, sizes of
+    /// writes are measured by the `elfo_dump_write_size_bytes` histogram.
+    ///
+    /// `64KiB` by default.
+    ///
+    /// [`FlushDumps`]: crate::FlushDumps
+    #[serde(default = "default_block_size")]
+    pub block_size: ByteSize,
+    /// How long a partial block can wait for being filled, see `block_size`.
+    /// It's checked on every write, so the latency is also bounded by
+    /// `max_write_interval`.
+    /// `1s` by default.
+    #[serde(default = "default_block_flush_interval")]
+    pub block_flush_interval: Duration,
+    /// Whether partial blocks are padded to the block size, see
+    /// [`BlockPadding`].
+    /// `"None"` by default.
+    #[serde(default)]
+    pub block_padding: BlockPadding,
+    /// Whether to write full blocks with `O_DIRECT` bypassing the page cache.
+    /// Partial blocks are written through the page cache and rewritten
+    /// directly once filled. Falls back to buffered I/O if the filesystem
+    /// refuses it. Supported only on Linux.
+    /// `false` by default.
+    #[serde(default)]
+    pub direct_io: bool,
     /// In order to avoid noisy logs about skipped, failed and truncated dumps,
     /// they are logged with this specified cooldown.
     /// `1m` by default.
-    #[serde(with = "humantime_serde", default = "default_log_cooldown")]
+    #[serde(default = "default_log_cooldown")]
     pub log_cooldown: Duration,
     /// The maximum number of dumps in memory per class. If exceeded, old
     /// dumps are dropped.
     /// `3_000_000` by default.
     #[serde(default = "default_registry_capacity")]
     pub registry_capacity: usize,
+    /// How long pending dumps are written on termination. Dumps that are
+    /// not written in time or made after termination are counted as lost.
+    ///
+    /// Then, the trailer line is written and the file is synced, see
+    /// [the crate's docs](crate).
+    /// `5s` by default.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: Duration,
     /// Rule set to override properties.
     /// All rules that match a message are merged. If several relevant rules
     /// define same property, the last one is applied.
@@ -64,6 +183,68 @@ pub struct Config {
     /// ```
     #[serde(default)]
     pub rules: Vec<Rule>,
+    /// Whether to compute a hash (xxh3) of the serialized message body and
+    /// include it as the `h` field in every dump. It can be used to
+    /// deduplicate dumps or verify their integrity.
+    ///
+    /// The hash is computed over the JSON-serialized message, so it's stable
+    /// across nodes only if the message's serialization is deterministic.
+    /// `false` by default.
+    #[serde(default)]
+    pub hash_payload: bool,
+    /// If non-zero, a dump whose message hash matches one of the last
+    /// `dedup_window` written dumps of the same class is serialized as
+    /// `"m":{"$dup":"<hash>"}` instead of the full body.
+    ///
+    /// Implies `hash_payload`.
+    /// `0` (disabled) by default.
+    #[serde(default)]
+    pub dedup_window: usize,
+    /// Names of fields in dumps, see [`FieldNames`].
+    /// Can be overridden per class by rules without `protocol` and `message`.
+    /// `"short"` by default.
+    #[serde(default)]
+    pub field_names: FieldNames,
+    /// The maximum size of written dumps of one detailed trace per class.
+    /// Detailed traces bypass rate limits of dumping, see
+    /// `system.tracing.detailed_budget`, so it bounds them instead.
+    /// Further dumps of the trace are skipped.
+    /// `1MiB` by default.
+    #[serde(default = "default_max_detailed_trace_size")]
+    pub max_detailed_trace_size: ByteSize,
+    /// Whether to append every dump to a write-ahead journal on arrival in
+    /// order to recover pending dumps after a crash, see
+    /// [the crate's docs](crate#journaling).
+    /// `false` by default.
+    #[serde(default)]
+    pub journal: bool,
+    /// A directory for journal files. The directory of `path` by default,
+    /// so it must be specified if the directory depends on placeholders.
+    #[serde(default)]
+    pub journal_dir: Option<String>,
+    /// How often the journal is synced to disk (`fdatasync`).
+    /// `100ms` by default.
+    #[serde(default = "default_journal_sync_interval")]
+    pub journal_sync_interval: Duration,
+    /// The number of journaled dumps of one class, at which the journal is
+    /// synced without waiting for the interval.
+    /// `1024` by default.
+    #[serde(default = "default_journal_sync_items")]
+    pub journal_sync_items: usize,
+}
+
+/// A fallback destination of dumps.
+///
+/// It's exported only for documentation purposes and cannot be created or
+/// received outside the dumper.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Sink {
+    /// A dump file or template, see [`FileTemplate`] and `path`.
+    File {
+        /// A path to a dump file or template.
+        path: FileTemplate,
+    },
 }
 
 /// Defines a rule to override some properties.
@@ -89,6 +270,9 @@ pub struct Rule {
     pub log_on_overflow: Option<LogLevel>,
     /// Specified the logging level if a message cannot be serialized.
     pub log_on_failure: Option<LogLevel>,
+    /// Specified names of fields. All dumps of one class are serialized
+    /// uniformly, so it's ignored in rules with `protocol` or `message`.
+    pub field_names: Option<FieldNames>,
 }
 
 /// What to do if a dump is too big.
@@ -104,16 +288,357 @@ pub enum OnOverflow {
     Truncate,
 }
 
+/// Names of fields in dumps.
+///
+/// | short | long               |
+/// |-------|--------------------|
+/// | `ts`  | `timestamp`        |
+/// | `g`   | `group`            |
+/// | `k`   | `key`              |
+/// | `n`   | `node`             |
+/// | `s`   | `sequence_no`      |
+/// | `t`   | `trace_id`         |
+/// | `th`  | `thread_id`        |
+/// | `d`   | `direction`        |
+/// | `to`  | `recipient`        |
+/// | `cl`  | `class`            |
+/// | `mn`  | `message_name`     |
+/// | `mp`  | `message_protocol` |
+/// | `mk`  | `message_kind`     |
+/// | `h`   | `hash`             |
+/// | `m`   | `message`          |
+/// | `c`   | `correlation_id`   |
+///
+/// It's exported only for documentation purposes and cannot be created or
+/// received outside the dumper.
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldNames {
+    /// Compact names, e.g. `mn`.
+    #[default]
+    Short,
+    /// Self-describing names, e.g. `message_name`.
+    Long,
+}
+
 impl Config {
-    pub(crate) fn path(&self, class: &str) -> String {
-        self.path.replace("{class}", class)
+    /// Returns templates of `path` and `sinks` in order of priority.
+    pub(crate) fn templates(&self) -> impl Iterator<Item = &FileTemplate> {
+        let sinks = self.sinks.iter().map(|sink| match sink {
+            Sink::File { path } => path,
+        });
+
+        iter::once(&self.path).chain(sinks)
+    }
+
+    /// Renders paths of all sinks, see [`Config::templates()`].
+    pub(crate) fn paths(&self, class: &str, node_no: impl fmt::Display, seq: u32) -> Vec<String> {
+        let now = self.timezone.local_secs(SystemTime::now());
+        let node_no = node_no.to_string();
+        self.templates()
+            .map(|template| template.render(class, &node_no, now, seq))
+            .collect()
+    }
+
+    pub(crate) fn has_seq(&self) -> bool {
+        self.templates().any(FileTemplate::has_seq)
+    }
+
+    /// Returns the current time slice of templates, if they depend on time.
+    pub(crate) fn slice(&self) -> Option<i64> {
+        let now = self.timezone.local_secs(SystemTime::now());
+        self.granularity().map(|g| now.div_euclid(g))
+    }
+
+    /// Returns the time left until the next time slice of templates.
+    pub(crate) fn until_next_slice(&self) -> Option<std::time::Duration> {
+        let now = self.timezone.local_secs(SystemTime::now());
+        let granularity = self.granularity()?;
+        let left = granularity - now.rem_euclid(granularity);
+        Some(std::time::Duration::from_secs(left as u64))
+    }
+
+    /// Hourly slices are finer than daily ones, so the finest one is used.
+    fn granularity(&self) -> Option<i64> {
+        self.templates().filter_map(FileTemplate::granularity).min()
+    }
+
+    /// Returns the journal's directory if journaling is enabled.
+    pub(crate) fn journal_dir(&self) -> Option<PathBuf> {
+        if !self.journal {
+            return None;
+        }
+
+        Some(match &self.journal_dir {
+            Some(dir) => dir.into(),
+            None => match Path::new(&self.path.template).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.into(),
+                _ => ".".into(),
+            },
+        })
+    }
+}
+
+/// A template of paths to dump files with the following placeholders:
+/// * `{class}` - a dumping class. Classes that are not valid file names are
+///   encoded by [`KeyEncoding::Path`].
+/// * `{date}` - a date in the `YYYY-MM-DD` format.
+/// * `{hour}` - an hour in the `HH` format.
+/// * `{node_no}` - the node number.
+/// * `{seq}` - a sequence number of a file in the current time slice.
+///
+/// If `{date}` or `{hour}` is used, files are switched once the rendered
+/// value changes, even if nothing is written. `{seq}` starts from zero in
+/// every time slice and is incremented on `SIGHUP`, otherwise the same file
+/// is reopened. Files are appended, so the existing file of the same slice
+/// is continued after restart.
+///
+/// Directories are created as needed. If the path depends on time or `{seq}`,
+/// the `current.dump` symlink to the active file is maintained in the deepest
+/// directory that doesn't depend on them (unix only). Thus, `{class}` should
+/// be a part of this directory if classes are written to different files.
+///
+/// Unknown placeholders and unpaired braces are rejected.
+///
+/// [`KeyEncoding::Path`]: elfo_core::KeyEncoding::Path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Class,
+    Date,
+    Hour,
+    NodeNo,
+    Seq,
+}
+
+impl Part {
+    fn is_dynamic(&self) -> bool {
+        matches!(self, Self::Date | Self::Hour | Self::Seq)
+    }
+}
+
+const SECS_PER_HOUR: i64 = 3600;
+const SECS_PER_DAY: i64 = 24 * SECS_PER_HOUR;
+
+impl FileTemplate {
+    /// Returns the length of time slices in seconds.
+    fn granularity(&self) -> Option<i64> {
+        if self.parts.contains(&Part::Hour) {
+            Some(SECS_PER_HOUR)
+        } else if self.parts.contains(&Part::Date) {
+            Some(SECS_PER_DAY)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    fn has_seq(&self) -> bool {
+        self.parts.contains(&Part::Seq)
+    }
+
+    /// Renders a path, `now` is local time in seconds since the unix epoch.
+    fn render(&self, class: &str, node_no: &str, now: i64, seq: u32) -> String {
+        let mut path = String::with_capacity(self.template.len());
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => path.push_str(text),
+                Part::Class => path.push_str(&KeyEncoding::Path.encode(class)),
+                Part::Date => {
+                    let (year, month, day) = civil_from_days(now.div_euclid(SECS_PER_DAY));
+                    path.push_str(&format!("{year:04}-{month:02}-{day:02}"));
+                }
+                Part::Hour => {
+                    let hour = now.rem_euclid(SECS_PER_DAY) / SECS_PER_HOUR;
+                    path.push_str(&format!("{hour:02}"));
+                }
+                Part::NodeNo => path.push_str(node_no),
+                Part::Seq => path.push_str(&seq.to_string()),
+            }
+        }
+
+        path
+    }
+
+    /// Returns `(link, target)` of the `current.dump` symlink to the rendered
+    /// `path`, if the path depends on time or `{seq}`.
+    pub(crate) fn current_link(&self, class: &str, path: &str) -> Option<(PathBuf, PathBuf)> {
+        let dynamic = self.parts.iter().position(Part::is_dynamic)?;
+
+        // The prefix is rendered the same way as the path itself.
+        let prefix = self.parts[..dynamic]
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Class => KeyEncoding::Path.encode(class).into_owned(),
+                _ => String::new(),
+            })
+            .collect::<String>();
+
+        let dir_len = prefix.rfind('/').map_or(0, |pos| pos + 1);
+        let (dir, target) = path.split_at(dir_len);
+        let dir = if dir.is_empty() { "." } else { dir };
+        Some((Path::new(dir).join("current.dump"), target.into()))
+    }
+}
+
+impl FromStr for FileTemplate {
+    type Err = eyre::Report;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        ensure!(!template.is_empty(), "path must be specified");
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            ensure!(rest.as_bytes()[start] == b'{', "unpaired `}}`");
+
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].into()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| eyre!("unpaired `{{`"))?;
+
+            parts.push(match &rest[start + 1..start + end] {
+                "class" => Part::Class,
+                "date" => Part::Date,
+                "hour" => Part::Hour,
+                "node_no" => Part::NodeNo,
+                "seq" => Part::Seq,
+                name => bail!(
+                    "unknown placeholder `{{{name}}}`, \
+                     expected one of `{{class}}`, `{{date}}`, `{{hour}}`, `{{node_no}}`, `{{seq}}`"
+                ),
+            });
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.into()));
+        }
+
+        Ok(Self {
+            template: template.into(),
+            parts,
+        })
     }
 }
 
+impl<'de> Deserialize<'de> for FileTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        s.parse()
+            .map_err(|err| de::Error::custom(format!(r#"invalid path template "{s}": {err}"#)))
+    }
+}
+
+/// A time zone used to render time in paths, either `"UTC"` or a fixed
+/// offset from UTC, e.g. `"+03:00"` or `"-05:30"`.
+///
+/// Named time zones are not supported intentionally: fixed offsets don't
+/// depend on the host's settings and daylight saving time, so time slices
+/// are never repeated or skipped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone {
+    offset_secs: i64,
+}
+
+impl TimeZone {
+    fn local_secs(self, time: SystemTime) -> i64 {
+        time.to_unix_time_secs() as i64 + self.offset_secs
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(Self::default());
+        }
+
+        let (sign, offset) = if let Some(offset) = s.strip_prefix('+') {
+            (1, offset)
+        } else if let Some(offset) = s.strip_prefix('-') {
+            (-1, offset)
+        } else {
+            bail!(r#"expected "UTC" or an offset like "+03:00""#);
+        };
+
+        let (hours, minutes) = offset
+            .split_once(':')
+            .filter(|(h, m)| h.len() == 2 && m.len() == 2)
+            .ok_or_else(|| eyre!("expected an offset in the `±HH:MM` format"))?;
+
+        let hours: i64 = hours.parse()?;
+        let minutes: i64 = minutes.parse()?;
+        ensure!(hours <= 14 && minutes < 60, "offset is out of range");
+
+        Ok(Self {
+            offset_secs: sign * (hours * SECS_PER_HOUR + minutes * 60),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeZone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        s.parse()
+            .map_err(|err| de::Error::custom(format!(r#"invalid timezone "{s}": {err}"#)))
+    }
+}
+
+/// Converts days since the unix epoch to `(year, month, day)`.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
 fn default_write_interval() -> Duration {
     Duration::from_millis(500)
 }
 
+fn default_max_write_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_write_high_water() -> usize {
+    65_536
+}
+
+fn default_block_size() -> ByteSize {
+    ByteSize::new(64 * 1024)
+}
+
+fn default_block_flush_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
 fn default_log_cooldown() -> Duration {
     Duration::from_secs(60)
 }
@@ -122,6 +647,48 @@ fn default_registry_capacity() -> usize {
     3_000_000
 }
 
+fn default_shutdown_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_max_detailed_trace_size() -> ByteSize {
+    ByteSize::new(1024 * 1024)
+}
+
+fn default_journal_sync_interval() -> Duration {
+    Duration::from_millis(100)
+}
+
+fn default_journal_sync_items() -> usize {
+    1024
+}
+
+fn default_sink_failure_threshold() -> u32 {
+    3
+}
+
+fn default_sink_probe_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// How partial blocks are padded, see [`Config::block_size`].
+///
+/// Padding is spaces ended by `\n`, i.e. a blank line for readers.
+///
+/// It's exported only for documentation purposes and cannot be created or
+/// received outside the dumper.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub enum BlockPadding {
+    /// Partial blocks are written as is.
+    #[default]
+    None,
+    /// The last partial block is padded once the file is closed or rotated.
+    OnClose,
+    /// Partial blocks are padded whenever written, so all writes are full
+    /// aligned blocks at the cost of the file's size.
+    OnFlush,
+}
+
 /// A logging level.
 ///
 /// It's exported only for documentation purposes and cannot be created or
@@ -136,3 +703,122 @@ pub enum LogLevel {
     Error,
     Off,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, now: i64, seq: u32) -> String {
+        let template: FileTemplate = template.parse().unwrap();
+        template.render("orders", "7", now, seq)
+    }
+
+    #[test]
+    fn template_rendering() {
+        // 2024-02-29 13:59:59 UTC
+        let now = 1_709_215_199;
+
+        assert_eq!(render("all.dump", now, 0), "all.dump");
+        assert_eq!(render("{class}.dump", now, 0), "orders.dump");
+        assert_eq!(
+            render("dumps/{class}/{date}/{hour}-{seq}.dump", now, 3),
+            "dumps/orders/2024-02-29/13-3.dump"
+        );
+        assert_eq!(
+            render("{node_no}-{date}-{hour}.dump", now + 1, 0),
+            "7-2024-02-29-14.dump"
+        );
+        assert_eq!(render("{date}.dump", 0, 0), "1970-01-01.dump");
+        assert_eq!(render("{date}.dump", -1, 0), "1969-12-31.dump");
+    }
+
+    #[test]
+    fn template_validation() {
+        let err = |t: &str| t.parse::<FileTemplate>().unwrap_err().to_string();
+
+        assert_eq!(err(""), "path must be specified");
+        assert_eq!(err("{class.dump"), "unpaired `{`");
+        assert_eq!(err("class}.dump"), "unpaired `}`");
+        assert!(err("{minute}.dump").starts_with("unknown placeholder `{minute}`"));
+
+        let config = toml::from_str::<Config>(r#"path = "{dat}.dump""#);
+        assert!(config
+            .unwrap_err()
+            .to_string()
+            .contains(r#"invalid path template "{dat}.dump": unknown placeholder"#));
+    }
+
+    #[test]
+    fn template_granularity() {
+        let granularity = |t: &str| t.parse::<FileTemplate>().unwrap().granularity();
+
+        assert_eq!(granularity("{class}-{seq}.dump"), None);
+        assert_eq!(granularity("{date}.dump"), Some(SECS_PER_DAY));
+        assert_eq!(granularity("{date}/{hour}.dump"), Some(SECS_PER_HOUR));
+    }
+
+    #[test]
+    fn current_link() {
+        let link = |t: &str, path: &str| {
+            let template = t.parse::<FileTemplate>().unwrap();
+            template.current_link("class", path)
+        };
+
+        assert_eq!(link("dumps/{class}.dump", "dumps/class.dump"), None);
+        assert_eq!(
+            link(
+                "dumps/{class}/{date}/{hour}.dump",
+                "dumps/class/2024-02-29/13.dump"
+            ),
+            Some((
+                "dumps/class/current.dump".into(),
+                "2024-02-29/13.dump".into()
+            ))
+        );
+        assert_eq!(
+            link("dumps/{class}-{seq}.dump", "dumps/class-0.dump"),
+            Some(("dumps/current.dump".into(), "class-0.dump".into()))
+        );
+        assert_eq!(
+            link("{date}.dump", "2024-02-29.dump"),
+            Some(("./current.dump".into(), "2024-02-29.dump".into()))
+        );
+    }
+
+    #[test]
+    fn sinks() {
+        let config = toml::from_str::<Config>(
+            r#"
+            path = "{date}/{class}.dump"
+            sinks = [{ kind = "file", path = "fallback/{date}/{hour}/{class}.dump" }]
+            "#,
+        )
+        .unwrap();
+
+        let templates = config.templates().map(FileTemplate::as_str);
+        assert_eq!(
+            templates.collect::<Vec<_>>(),
+            ["{date}/{class}.dump", "fallback/{date}/{hour}/{class}.dump"]
+        );
+        assert_eq!(config.granularity(), Some(SECS_PER_HOUR));
+        assert!(!config.has_seq());
+
+        let unknown = r#"
+            path = "all.dump"
+            sinks = [{ kind = "s3", path = "all.dump" }]
+        "#;
+        assert!(toml::from_str::<Config>(unknown).is_err());
+    }
+
+    #[test]
+    fn timezone_parsing() {
+        let offset = |s: &str| s.parse::<TimeZone>().map(|tz| tz.offset_secs);
+
+        assert_eq!(offset("UTC").unwrap(), 0);
+        assert_eq!(offset("+03:00").unwrap(), 3 * 3600);
+        assert_eq!(offset("-05:30").unwrap(), -(5 * 3600 + 30 * 60));
+        assert!(offset("Europe/Moscow").is_err());
+        assert!(offset("+3").is_err());
+        assert!(offset("+15:00").is_err());
+    }
+}
@@ -1,16 +1,22 @@
 use std::{
     collections::VecDeque,
     mem,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use fxhash::{FxHashMap, FxHashSet};
 use parking_lot::{Mutex, MutexGuard};
 use thread_local::ThreadLocal;
+use tokio::sync::Notify;
 
 use elfo_core::dumping::Dump;
-use elfo_utils::CachePadded;
+use elfo_utils::{time::SystemTime, unlikely, CachePadded};
+
+use crate::journal::Journal;
 
 type ShardNo = usize;
 
@@ -25,6 +31,10 @@ pub(crate) struct DumpStorage {
     registry_config: DumpRegistryConfig,
     registries: FxHashMap<&'static str, Arc<DumpRegistry>>,
     classes: FxHashSet<&'static str>,
+    /// Distinguishes journal segments of this storage from ones left by
+    /// previous runs.
+    run_id: u64,
+    is_recovered: bool,
 }
 
 impl DumpStorage {
@@ -37,6 +47,8 @@ impl DumpStorage {
             },
             registries: Default::default(),
             classes: Default::default(),
+            run_id: SystemTime::now().to_unix_time_nanos(),
+            is_recovered: false,
         }
     }
 
@@ -55,13 +67,23 @@ impl DumpStorage {
         self.classes.insert(class);
         self.registries
             .entry(class)
-            .or_insert_with(|| Arc::new(DumpRegistry::new(class, config)))
+            .or_insert_with(|| Arc::new(DumpRegistry::new(class, config, self.run_id)))
             .clone()
     }
 
     pub(crate) fn classes(&self) -> &FxHashSet<&'static str> {
         &self.classes
     }
+
+    pub(crate) fn run_id(&self) -> u64 {
+        self.run_id
+    }
+
+    /// Returns `true` only once, used to recover journals left by previous
+    /// runs only once.
+    pub(crate) fn start_recovery(&mut self) -> bool {
+        !mem::replace(&mut self.is_recovered, true)
+    }
 }
 
 // === DumpRegistry ===
@@ -70,6 +92,10 @@ pub(crate) struct DumpRegistry {
     class: &'static str,
     fund: Mutex<Fund>,
     shards: ThreadLocal<Shard>,
+    high_water: AtomicUsize,
+    high_water_reached: Notify,
+    is_closed: AtomicBool,
+    journal: Journal,
 }
 
 struct Shard {
@@ -78,11 +104,16 @@ struct Shard {
 }
 
 impl DumpRegistry {
-    fn new(class: &'static str, config: DumpRegistryConfig) -> Self {
+    fn new(class: &'static str, config: DumpRegistryConfig, run_id: u64) -> Self {
         Self {
             class,
             fund: Mutex::new(Fund::new(config)),
             shards: Default::default(),
+            // Disabled until the dumper is configured.
+            high_water: AtomicUsize::new(usize::MAX),
+            high_water_reached: Notify::new(),
+            is_closed: AtomicBool::new(false),
+            journal: Journal::new(class, run_id),
         }
     }
 
@@ -91,6 +122,30 @@ impl DumpRegistry {
     }
 
     pub(crate) fn add(&self, dump: Dump) {
+        if unlikely(self.is_closed.load(Ordering::Relaxed)) {
+            self.fund().lost += 1;
+            return;
+        }
+
+        if unlikely(self.journal.is_enabled()) {
+            return self.journal.append(dump, |dump| self.push(dump));
+        }
+
+        self.push(dump);
+    }
+
+    /// Adds dumps recovered from the journal, bypassing it.
+    pub(crate) fn add_recovered(&self, dumps: Vec<Dump>) {
+        for dump in dumps {
+            self.push(dump);
+        }
+    }
+
+    pub(crate) fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    fn push(&self, dump: Dump) {
         let shard = self.shards.get_or(|| self.make_shard());
         let need_to_renew = {
             let mut active_part = shard.active_part.lock();
@@ -100,13 +155,61 @@ impl DumpRegistry {
 
         if need_to_renew {
             self.renew_active_part(shard, Part::is_full);
+            self.notify_if_high_water();
         }
     }
 
+    /// Sets the number of pending dumps, at which
+    /// [`DumpRegistry::high_water_reached()`] resolves.
+    pub(crate) fn set_high_water(&self, high_water: usize) {
+        self.high_water.store(high_water, Ordering::Relaxed);
+    }
+
+    /// Resolves once the number of pending dumps reaches the high-water mark.
+    /// Only full parts are counted, so it's a lower bound.
+    pub(crate) async fn high_water_reached(&self) {
+        self.high_water_reached.notified().await;
+    }
+
     pub(crate) fn drain(&self, timeout: Duration) -> Drain<'_> {
         Drain::new(self, timeout)
     }
 
+    /// Stops accepting new dumps, they are counted as lost instead.
+    /// Already added dumps can still be drained.
+    pub(crate) fn close(&self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+    }
+
+    /// Drops all pending dumps, counting them as lost.
+    /// Returns the number of dropped dumps.
+    pub(crate) fn discard(&self) -> usize {
+        let mut fund = self.fund.lock();
+        let mut count = 0;
+
+        for shard in self.shards.iter() {
+            let mut active_part = shard.active_part.lock();
+            count += active_part.len();
+            active_part.clear();
+            drop(active_part);
+
+            while let Some(mut part) = fund.get_filled_part(shard.shard_no) {
+                count += part.len();
+                part.clear();
+                fund.add_empty_part(part);
+            }
+        }
+
+        fund.lost += count;
+        count
+    }
+
+    /// Returns the number of dumps dropped because of the capacity limit,
+    /// closing or discarding.
+    pub(crate) fn lost(&self) -> usize {
+        self.fund().lost
+    }
+
     fn configure(&self, config: DumpRegistryConfig) {
         self.fund.lock().configure(config);
     }
@@ -130,6 +233,16 @@ impl DumpRegistry {
         }
     }
 
+    #[cold]
+    #[inline(never)]
+    fn notify_if_high_water(&self) {
+        let pending = self.fund().filled_part_count * PART_CAPACITY;
+        if pending >= self.high_water.load(Ordering::Relaxed) {
+            // Stores a permit if the dumper is busy, so it's never lost.
+            self.high_water_reached.notify_one();
+        }
+    }
+
     #[cold]
     #[inline(never)]
     fn renew_active_part(&self, shard: &Shard, predicate: impl FnOnce(&Part) -> bool) -> bool {
@@ -152,8 +265,10 @@ struct Fund {
     config: DumpRegistryConfig,
     // Empty + filled + active + used by `Drain`.
     part_count: usize,
+    filled_part_count: usize,
     empty_parts: Vec<Part>,
     filled_parts: Vec<VecDeque<Part>>,
+    lost: usize,
 }
 
 impl Fund {
@@ -161,8 +276,10 @@ impl Fund {
         Self {
             config,
             part_count: 0,
+            filled_part_count: 0,
             empty_parts: Vec::with_capacity(128),
             filled_parts: Vec::with_capacity(64),
+            lost: 0,
         }
     }
 
@@ -187,10 +304,13 @@ impl Fund {
     fn add_filled_part(&mut self, shard_no: ShardNo, part: Part) {
         debug_assert!(!part.is_empty());
         self.filled_parts[shard_no].push_back(part);
+        self.filled_part_count += 1;
     }
 
     fn get_filled_part(&mut self, shard_no: ShardNo) -> Option<Part> {
-        self.filled_parts[shard_no].pop_front()
+        let part = self.filled_parts[shard_no].pop_front()?;
+        self.filled_part_count -= 1;
+        Some(part)
     }
 
     fn add_empty_part(&mut self, part: Part) {
@@ -222,7 +342,8 @@ impl Fund {
     fn clear_most_filled(&mut self) -> Option<Part> {
         let candidate = self.filled_parts.iter_mut().max_by_key(|q| q.len())?;
         let mut part = candidate.pop_front()?;
-        // TODO: count lost.
+        self.filled_part_count -= 1;
+        self.lost += part.len();
         part.clear();
         Some(part)
     }
@@ -248,6 +369,10 @@ impl Part {
         self.items.is_empty()
     }
 
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
     fn is_full(&self) -> bool {
         self.items.len() == PART_CAPACITY
     }
@@ -277,6 +402,7 @@ pub(crate) struct Drain<'a> {
     shard_iter: thread_local::Iter<'a, Shard>,
     current: Option<(&'a Shard, Part)>,
     until: Instant,
+    is_timed_out: bool,
 }
 
 impl<'a> Drain<'a> {
@@ -286,9 +412,16 @@ impl<'a> Drain<'a> {
             shard_iter: registry.shards(),
             current: None,
             until: Instant::now() + timeout,
+            is_timed_out: false,
         }
     }
 
+    /// Returns `true` if the iterator has ended because of the timeout,
+    /// i.e. some dumps can be still pending.
+    pub(crate) fn is_timed_out(&self) -> bool {
+        self.is_timed_out
+    }
+
     fn next_shard(&mut self) {
         debug_assert!(self.current.is_none());
 
@@ -332,8 +465,12 @@ impl Iterator for Drain<'_> {
     type Item = Dump;
 
     fn next(&mut self) -> Option<Dump> {
-        if self.current.is_none() && Instant::now() < self.until {
-            self.next_shard();
+        if self.current.is_none() {
+            if Instant::now() < self.until {
+                self.next_shard();
+            } else {
+                self.is_timed_out = true;
+            }
         }
 
         let (_, part) = self.current.as_mut()?;
@@ -1,55 +1,82 @@
-use std::{fs::File, io::Write, sync::Arc};
+use std::{path::Path, sync::Arc, time::Instant};
 
 use eyre::{eyre, Result};
 use fxhash::FxHashMap;
 use parking_lot::Mutex;
 use tokio::{
-    fs::{File as AsyncFile, OpenOptions as AsyncOpenOptions},
+    fs::{self as async_fs, OpenOptions as AsyncOpenOptions},
     sync::Mutex as AsyncMutex,
+    task,
 };
 use tracing::debug;
 
+use crate::block_writer::{self, BlockOptions, BlockWriter, DiskFile};
+
 // === FileRegistry ===
 
+/// Files shared by dumpers. The same file can be used by multiple classes,
+/// so it's closed once all its users close it.
 #[derive(Default)]
 pub(crate) struct FileRegistry {
-    files: Mutex<FxHashMap<String, FileHandle>>,
+    files: Mutex<FxHashMap<String, (FileHandle, usize /* users */)>>,
 }
 
 impl FileRegistry {
-    pub(crate) async fn open(&self, path: &str, force: bool) -> Result<()> {
+    /// Opens the file if it isn't open yet and registers a new user of it.
+    /// `options` are applied only if the file is actually opened.
+    pub(crate) async fn open(&self, path: &str, options: BlockOptions) -> Result<()> {
         let mut file = {
             let mut files = self.files.lock();
-
-            if !files.contains_key(path) {
-                files.insert(path.to_string(), FileHandle::default());
-            }
-
-            files.get(path).unwrap().clone()
+            let (file, users) = files.entry(path.to_string()).or_default();
+            *users += 1;
+            file.clone()
         };
 
-        if file.open(path, force).await? {
+        if file.open(path, false, options).await? {
             debug!(%path, "file opened");
         }
 
         Ok(())
     }
 
+    /// Reopens the already open file, e.g. after moving it by `logrotate`.
+    pub(crate) async fn reopen(&self, path: &str, options: BlockOptions) -> Result<()> {
+        let mut file = self.acquire(path).await;
+        file.open(path, true, options).await?;
+        debug!(%path, "file reopened");
+        Ok(())
+    }
+
+    /// Unregisters a user of the file. The last user syncs and closes it.
+    pub(crate) async fn close(&self, path: &str) -> Result<()> {
+        let file = {
+            let mut files = self.files.lock();
+            let (_, users) = files.get_mut(path).expect("file must be open already");
+            *users -= 1;
+
+            if *users > 0 {
+                return Ok(());
+            }
+
+            files.remove(path).unwrap().0
+        };
+
+        file.close().await?;
+        debug!(%path, "file closed");
+        Ok(())
+    }
+
     pub(crate) async fn acquire(&self, path: &str) -> FileHandle {
         self.files
             .lock()
             .get(path)
             .expect("file must be open already")
+            .0
             .clone()
     }
 
     pub(crate) async fn sync(&self, path: &str) -> Result<()> {
-        let file = self
-            .files
-            .lock()
-            .get(path)
-            .expect("file must be open already")
-            .clone();
+        let file = self.acquire(path).await;
 
         file.sync().await?;
         debug!(%path, "file synchronized");
@@ -59,51 +86,135 @@ impl FileRegistry {
 
 // === FileHandle ===
 
+type Writer = BlockWriter<DiskFile>;
+
 #[derive(Default, Clone)]
 pub(crate) struct FileHandle {
-    file: Arc<AsyncMutex<Option<File>>>,
+    file: Arc<AsyncMutex<Option<Writer>>>,
 }
 
 impl FileHandle {
-    async fn open(&mut self, path: &str, force: bool) -> Result<bool> {
+    async fn open(&mut self, path: &str, force: bool, options: BlockOptions) -> Result<bool> {
         let mut file_lock = self.file.lock().await;
 
         if file_lock.is_some() && !force {
             return Ok(false);
         }
 
+        if let Some(dir) = Path::new(path).parent() {
+            if !dir.as_os_str().is_empty() {
+                async_fs::create_dir_all(dir).await?;
+            }
+        }
+
+        // Before measuring the file, because it can be the same one.
+        if let Some(mut prev) = file_lock.take() {
+            blocking(move || prev.close()).await?;
+        }
+
+        // Not appended, because blocks are written by offsets.
         let file = AsyncOpenOptions::new()
             .create(true)
-            .append(true)
+            .truncate(false)
+            .write(true)
             .open(path)
-            .await?
-            .into_std()
-            .await;
+            .await?;
+        let len = file.metadata().await?.len();
+        let file = file.into_std().await;
+
+        let direct = if options.direct_io {
+            block_writer::open_direct(path).await
+        } else {
+            None
+        };
 
-        *file_lock = Some(file);
+        *file_lock = Some(BlockWriter::new(DiskFile::new(file, direct), len, options));
         Ok(true)
     }
 
     /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
     pub(crate) fn write(&self, buffer: &[u8]) -> Result<()> {
+        self.with_writer(|writer| writer.write(buffer))
+    }
+
+    /// Writes the partial block if `force` is set or it's overdue,
+    /// see `block_flush_interval`.
+    ///
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    pub(crate) fn flush(&self, force: bool) -> Result<()> {
+        self.with_writer(|writer| writer.flush(force, Instant::now()))
+    }
+
+    fn with_writer(&self, f: impl FnOnce(&mut Writer) -> std::io::Result<()>) -> Result<()> {
         let mut file_lock = self.file.blocking_lock();
-        let mut file = file_lock
+        let mut writer = file_lock
             .take()
             .ok_or_else(|| eyre!("file handle is poisoned"))?;
-        file.write_all(buffer)?;
-        file.flush()?; // on all (?) OS does nothing
-        *file_lock = Some(file);
+        f(&mut writer)?;
+        *file_lock = Some(writer);
         Ok(())
     }
 
+    /// Writes the partial block and syncs the file.
     pub(crate) async fn sync(&self) -> Result<()> {
         let mut file_lock = self.file.lock().await;
-        let file = file_lock
+        let mut writer = file_lock
             .take()
             .ok_or_else(|| eyre!("file handle is poisoned"))?;
-        let file = AsyncFile::from_std(file);
-        file.sync_all().await?;
-        *file_lock = Some(file.into_std().await);
+
+        let writer = blocking(move || {
+            writer.flush(true, Instant::now())?;
+            writer.sync()?;
+            Ok(writer)
+        })
+        .await?;
+
+        *file_lock = Some(writer);
         Ok(())
     }
+
+    /// Writes the partial block (padded if configured) and syncs the file.
+    async fn close(&self) -> Result<()> {
+        let mut writer = self
+            .file
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| eyre!("file handle is poisoned"))?;
+
+        blocking(move || {
+            writer.close()?;
+            writer.sync()
+        })
+        .await
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> std::io::Result<T> + Send + 'static,
+) -> Result<T> {
+    match task::spawn_blocking(f).await {
+        Ok(res) => Ok(res?),
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+// === Symlinks ===
+
+/// Atomically points the symlink to the target, `tag` makes the temporary
+/// name unique among dumpers sharing the same link's directory.
+#[cfg(unix)]
+pub(crate) async fn update_link(link: &Path, target: &Path, tag: &str) -> Result<()> {
+    let name = link.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = link.with_file_name(format!(".{name}.{tag}.tmp"));
+    let _ = async_fs::remove_file(&tmp).await;
+    async_fs::symlink(target, &tmp).await?;
+    async_fs::rename(&tmp, link).await?;
+    debug!(link = %link.display(), target = %target.display(), "symlink updated");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn update_link(_link: &Path, _target: &Path, _tag: &str) -> Result<()> {
+    Ok(())
 }
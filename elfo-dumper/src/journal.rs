@@ -0,0 +1,736 @@
+//! Write-ahead journaling of dumps, see `Config::journal`.
+//!
+//! A dump is appended to the journal of its class on arrival, before it's
+//! added to the registry. The journal consists of segments, files named
+//! `{class}.{no}.journal`. Every write of dumps starts a new segment and then
+//! removes segments, whose dumps have been written completely. Thus, normally,
+//! there are one or two segments per class. More of them remain only after a
+//! crash, in which case they are recovered on the next start of the dumper.
+//!
+//! Records are length-prefixed: `[len: u32][xxh3: u32][body: len bytes]`,
+//! little-endian. The body of the first record is a header, others are dumps
+//! encoded as JSON. Recovery of a segment stops at the first truncated or
+//! corrupted record, skipping the rest of the segment.
+
+use std::{
+    borrow::Cow,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use fxhash::{FxHashMap, FxHashSet};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::sync::Notify;
+use tracing::{error, warn};
+use xxhash_rust::xxh3::xxh3_64;
+
+use elfo_core::{
+    channel::ChannelId,
+    dumping::{Direction, Dump, MessageKind, MessageName},
+    scope::{self, SerdeMode},
+    tracing::TraceId,
+    ActorMeta, Addr, KeyEncoding,
+};
+use elfo_utils::{time::SystemTime, ward};
+
+const EXTENSION: &str = "journal";
+const HEADER_SIZE: usize = 8;
+
+// === Journal ===
+
+pub(crate) struct Journal {
+    class: &'static str,
+    /// Distinguishes segments of this process from ones left by previous runs.
+    run_id: u64,
+    is_enabled: AtomicBool,
+    sync_items: AtomicUsize,
+    sync_needed: Notify,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    segment: Option<Segment>,
+    /// Segments replaced by `rotate()` or adopted after recovery,
+    /// but not released yet.
+    rotated: Vec<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+struct Segment {
+    dir: PathBuf,
+    path: PathBuf,
+    no: u64,
+    file: Arc<File>,
+    records: usize,
+    unsynced: usize,
+}
+
+impl Journal {
+    pub(crate) fn new(class: &'static str, run_id: u64) -> Self {
+        Self {
+            class,
+            run_id,
+            is_enabled: AtomicBool::new(false),
+            sync_items: AtomicUsize::new(usize::MAX),
+            sync_needed: Notify::new(),
+            inner: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Starts journaling into the directory or stops it if `None`.
+    ///
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    pub(crate) fn configure(&self, dir: Option<&Path>, sync_items: usize) -> io::Result<()> {
+        self.sync_items.store(sync_items.max(1), Ordering::Relaxed);
+        let mut inner = self.inner.lock();
+
+        let Some(dir) = dir else {
+            self.is_enabled.store(false, Ordering::Relaxed);
+
+            // Nothing is guaranteed without journaling, so segments are useless.
+            if let Some(segment) = inner.segment.take() {
+                remove(&segment.path);
+            }
+            for path in inner.rotated.drain(..) {
+                remove(&path);
+            }
+            return Ok(());
+        };
+
+        if inner.segment.as_ref().is_some_and(|s| s.dir == dir) {
+            self.is_enabled.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        fs::create_dir_all(dir)?;
+        let prefix = KeyEncoding::Path.encode(self.class);
+        let no = list(dir)?
+            .into_iter()
+            .filter(|(_, p, _)| *p == prefix)
+            .map(|(_, _, no)| no + 1)
+            .max()
+            .unwrap_or(0);
+
+        let segment = self.create(dir, no)?;
+        if let Some(prev) = inner.segment.replace(segment) {
+            inner.rotated.push(prev.path);
+        }
+
+        self.is_enabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn create(&self, dir: &Path, no: u64) -> io::Result<Segment> {
+        let name = format!("{}.{no}.{EXTENSION}", KeyEncoding::Path.encode(self.class));
+        let path = dir.join(name);
+
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)?;
+
+        let header = Header {
+            class: self.class.into(),
+            run_id: self.run_id,
+        };
+        let mut buffer = vec![0; HEADER_SIZE];
+        serde_json::to_writer(&mut buffer, &header)?;
+        seal(&mut buffer);
+        file.write_all(&buffer)?;
+
+        Ok(Segment {
+            dir: dir.into(),
+            path,
+            no,
+            file: Arc::new(file),
+            records: 0,
+            unsynced: 0,
+        })
+    }
+
+    /// Appends the dump and calls `push` while the journal is still locked.
+    /// Thus, the dump is added to the registry before the segment containing
+    /// it can be rotated, see [`Journal::rotate()`].
+    pub(crate) fn append(&self, dump: Dump, push: impl FnOnce(Dump)) {
+        let mut inner = self.inner.lock();
+        let Inner {
+            segment,
+            rotated,
+            buffer,
+        } = &mut *inner;
+
+        if let Some(seg) = segment {
+            match seg.append(buffer, &dump) {
+                Ok(()) if seg.unsynced == self.sync_items.load(Ordering::Relaxed) => {
+                    self.sync_needed.notify_one();
+                }
+                Ok(()) => {}
+                Err(err) => {
+                    error!(class = self.class, error = %err, "cannot append to the journal, journaling is stopped");
+                    self.is_enabled.store(false, Ordering::Relaxed);
+                    let seg = segment.take().expect("checked above");
+                    rotated.push(seg.path);
+                }
+            }
+        }
+
+        push(dump);
+    }
+
+    /// Resolves once `sync_items` dumps are appended since the last sync.
+    pub(crate) async fn sync_needed(&self) {
+        self.sync_needed.notified().await;
+    }
+
+    /// Syncs appended dumps to disk.
+    ///
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    pub(crate) fn sync(&self) -> io::Result<()> {
+        let file = {
+            let mut inner = self.inner.lock();
+            let segment = ward!(
+                inner.segment.as_mut().filter(|s| s.unsynced > 0),
+                return Ok(())
+            );
+            segment.unsynced = 0;
+            segment.file.clone()
+        };
+
+        file.sync_data()
+    }
+
+    /// Starts a new segment if the current one isn't empty.
+    /// Returns a marker for [`Journal::release()`].
+    ///
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    pub(crate) fn rotate(&self) -> io::Result<usize> {
+        let mut inner = self.inner.lock();
+        let segment = ward!(
+            inner.segment.as_ref().filter(|s| s.records > 0),
+            return Ok(inner.rotated.len())
+        );
+
+        let next = self.create(&segment.dir, segment.no + 1)?;
+        let prev = mem::replace(inner.segment.as_mut().unwrap(), next);
+        inner.rotated.push(prev.path);
+        let marker = inner.rotated.len();
+        drop(inner);
+
+        // The segment is still needed until its dumps are written.
+        if prev.unsynced > 0 {
+            prev.file.sync_data()?;
+        }
+
+        Ok(marker)
+    }
+
+    /// Removes segments rotated before the marker is returned by
+    /// [`Journal::rotate()`]. Must be called only if all dumps added before
+    /// the rotation have been handled.
+    pub(crate) fn release(&self, marker: usize) {
+        let paths = {
+            let mut inner = self.inner.lock();
+            let marker = marker.min(inner.rotated.len());
+            inner.rotated.drain(..marker).collect::<Vec<_>>()
+        };
+
+        for path in paths {
+            remove(&path);
+        }
+    }
+
+    /// Takes ownership of recovered segments, they are removed once recovered
+    /// dumps are written.
+    pub(crate) fn adopt(&self, segments: Vec<PathBuf>) {
+        self.inner.lock().rotated.extend(segments);
+    }
+}
+
+impl Segment {
+    fn append(&mut self, buffer: &mut Vec<u8>, dump: &Dump) -> io::Result<()> {
+        buffer.clear();
+        buffer.resize(HEADER_SIZE, 0);
+
+        let record = Record {
+            timestamp: dump.timestamp.to_unix_time_nanos(),
+            group: Cow::Borrowed(&dump.meta.group),
+            key: Cow::Borrowed(&dump.meta.key),
+            sequence_no: dump.sequence_no.into(),
+            trace_id: dump.trace_id,
+            thread_id: dump.thread_id,
+            is_incoming: dump.direction == Direction::In,
+            recipient: dump.recipient.into_bits(),
+            message_name: Cow::from(&dump.message_name),
+            message_protocol: Cow::Borrowed(dump.message_protocol),
+            message_kind: encode_kind(dump.message_kind),
+            message: ErasedRef(dump),
+            is_detailed: dump.is_detailed,
+            channel_id: dump.channel_id,
+        };
+
+        let res = scope::with_serde_mode(SerdeMode::Dumping, || {
+            serde_json::to_writer(&mut *buffer, &record)
+        });
+
+        // Such dumps fail to be written too, the failure is reported there.
+        if res.is_err() {
+            return Ok(());
+        }
+
+        seal(buffer);
+        (&*self.file).write_all(buffer)?;
+        self.records += 1;
+        self.unsynced += 1;
+        Ok(())
+    }
+}
+
+fn seal(buffer: &mut [u8]) {
+    let body = &buffer[HEADER_SIZE..];
+    let len = u32::try_from(body.len()).unwrap_or(u32::MAX);
+    let checksum = xxh3_64(body) as u32;
+    buffer[..4].copy_from_slice(&len.to_le_bytes());
+    buffer[4..HEADER_SIZE].copy_from_slice(&checksum.to_le_bytes());
+}
+
+fn remove(path: &Path) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != io::ErrorKind::NotFound {
+            warn!(path = %path.display(), error = %err, "cannot remove a journal segment");
+        }
+    }
+}
+
+/// Returns `(path, prefix, no)` of all segments in the directory.
+fn list(dir: &Path) -> io::Result<Vec<(PathBuf, String, u64)>> {
+    let mut segments = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = ward!(path.file_name().and_then(|n| n.to_str()), continue);
+        let stem = ward!(
+            name.strip_suffix(EXTENSION)
+                .and_then(|s| s.strip_suffix('.')),
+            continue
+        );
+        let (prefix, no) = ward!(stem.rsplit_once('.'), continue);
+        let no = ward!(no.parse().ok(), continue);
+        let prefix = prefix.to_string();
+        segments.push((path, prefix, no));
+    }
+
+    Ok(segments)
+}
+
+// === Records ===
+
+#[derive(Serialize, Deserialize)]
+struct Header<'a> {
+    #[serde(borrow)]
+    class: Cow<'a, str>,
+    run_id: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record<'a, M> {
+    #[serde(rename = "ts")]
+    timestamp: u64,
+    #[serde(rename = "g", borrow)]
+    group: Cow<'a, str>,
+    #[serde(rename = "k", borrow)]
+    key: Cow<'a, str>,
+    #[serde(rename = "s")]
+    sequence_no: u64,
+    #[serde(rename = "t")]
+    trace_id: TraceId,
+    #[serde(rename = "th")]
+    thread_id: u64,
+    #[serde(rename = "i")]
+    is_incoming: bool,
+    #[serde(rename = "to")]
+    recipient: u64,
+    #[serde(rename = "mn", borrow)]
+    message_name: Cow<'a, str>,
+    #[serde(rename = "mp", borrow)]
+    message_protocol: Cow<'a, str>,
+    #[serde(rename = "mk")]
+    message_kind: (u8, u64),
+    #[serde(rename = "m")]
+    message: M,
+    #[serde(rename = "det")]
+    is_detailed: bool,
+    #[serde(rename = "ch", default, skip_serializing_if = "Option::is_none")]
+    channel_id: Option<ChannelId>,
+}
+
+impl Record<'_, Box<RawValue>> {
+    /// Must be called inside the actor's scope.
+    fn into_dump(self, recovery: &mut Recovery) -> Option<Dump> {
+        let message_name = recovery.intern(&self.message_name);
+        let message_name = match message_name.split_once("::") {
+            Some(pair) => MessageName::from(pair),
+            None => MessageName::from(message_name),
+        };
+
+        let mut builder = Dump::builder();
+        builder
+            .timestamp(SystemTime::from_unix_time_nanos(self.timestamp))
+            .direction(if self.is_incoming {
+                Direction::In
+            } else {
+                Direction::Out
+            })
+            .recipient(Addr::from_bits(self.recipient)?)
+            .message_name(message_name)
+            .message_protocol(recovery.intern(&self.message_protocol))
+            .message_kind(decode_kind(self.message_kind)?);
+
+        let mut dump = builder.finish(self.message);
+        dump.meta = recovery.meta(&self.group, &self.key);
+        dump.sequence_no = self.sequence_no.try_into().ok()?;
+        dump.trace_id = self.trace_id;
+        dump.thread_id = self.thread_id;
+        dump.is_detailed = self.is_detailed;
+        dump.channel_id = self.channel_id;
+        Some(dump)
+    }
+}
+
+/// Serializes the erased message of a dump.
+struct ErasedRef<'a>(&'a Dump);
+
+impl Serialize for ErasedRef<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Serialize::serialize(&*self.0.message, serializer)
+    }
+}
+
+fn encode_kind(kind: MessageKind) -> (u8, u64) {
+    match kind {
+        MessageKind::Regular => (0, 0),
+        MessageKind::Request(c) => (1, c),
+        MessageKind::Response(c) => (2, c),
+        MessageKind::Forward(c) => (3, c),
+    }
+}
+
+fn decode_kind((kind, c): (u8, u64)) -> Option<MessageKind> {
+    Some(match kind {
+        0 => MessageKind::Regular,
+        1 => MessageKind::Request(c),
+        2 => MessageKind::Response(c),
+        3 => MessageKind::Forward(c),
+        _ => return None,
+    })
+}
+
+// === Recovery ===
+
+/// Dumps of one class recovered from segments left by previous runs.
+pub(crate) struct Recovered {
+    pub(crate) class: &'static str,
+    pub(crate) dumps: Vec<Dump>,
+    pub(crate) segments: Vec<PathBuf>,
+    /// The number of bytes skipped because of truncated or corrupted records.
+    pub(crate) skipped: usize,
+}
+
+#[derive(Default)]
+struct Recovery {
+    strings: FxHashSet<&'static str>,
+    metas: FxHashMap<(String, String), Arc<ActorMeta>>,
+    classes: FxHashMap<&'static str, Recovered>,
+}
+
+impl Recovery {
+    // Strings are leaked, but the number of different ones is limited.
+    fn intern(&mut self, s: &str) -> &'static str {
+        if let Some(s) = self.strings.get(s) {
+            return s;
+        }
+
+        let s = Box::leak(s.to_string().into_boxed_str());
+        self.strings.insert(s);
+        s
+    }
+
+    fn meta(&mut self, group: &str, key: &str) -> Arc<ActorMeta> {
+        self.metas
+            .entry((group.into(), key.into()))
+            .or_insert_with(|| {
+                Arc::new(ActorMeta {
+                    group: group.into(),
+                    key: key.into(),
+                })
+            })
+            .clone()
+    }
+
+    fn read(&mut self, path: PathBuf, run_id: u64, known: &FxHashSet<&'static str>) {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "cannot read a journal segment");
+                return;
+            }
+        };
+
+        let mut records = Records(&data);
+        let header = records
+            .next()
+            .and_then(|body| serde_json::from_slice::<Header<'_>>(body).ok());
+
+        let Some(header) = header else {
+            // Probably, the process crashed right after creating the segment.
+            warn!(path = %path.display(), "skipping a journal segment without a header");
+            remove(&path);
+            return;
+        };
+
+        // Segments of this run are still in use.
+        if header.run_id == run_id {
+            return;
+        }
+
+        let class = match known.get(&*header.class) {
+            Some(class) => *class,
+            None => self.intern(&header.class),
+        };
+
+        let mut dumps = Vec::new();
+        let mut skipped = records.0.len();
+        while let Some(body) = records.next() {
+            let record = serde_json::from_slice::<Record<'_, Box<RawValue>>>(body).ok();
+            let dump = ward!(record.and_then(|r| r.into_dump(self)), break);
+            dumps.push(dump);
+            skipped = records.0.len();
+        }
+
+        let recovered = self.classes.entry(class).or_insert_with(|| Recovered {
+            class,
+            dumps: Vec::new(),
+            segments: Vec::new(),
+            skipped: 0,
+        });
+        recovered.dumps.append(&mut dumps);
+        recovered.segments.push(path);
+        recovered.skipped += skipped;
+    }
+}
+
+/// Reads segments left by previous runs in the directory.
+/// Returned dumps are ordered by segments, but not between classes.
+///
+/// Must be called in a blocking context inside the actor's scope.
+pub(crate) fn recover(
+    dir: &Path,
+    run_id: u64,
+    known: &FxHashSet<&'static str>,
+) -> io::Result<Vec<Recovered>> {
+    let mut segments = match list(dir) {
+        Ok(segments) => segments,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    segments.sort_by(|a, b| (&a.1, a.2).cmp(&(&b.1, b.2)));
+
+    let mut recovery = Recovery::default();
+    for (path, _, _) in segments {
+        recovery.read(path, run_id, known);
+    }
+
+    Ok(recovery.classes.into_values().collect())
+}
+
+/// An iterator over bodies of valid records.
+struct Records<'a>(&'a [u8]);
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let header = self.0.get(..HEADER_SIZE)?;
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let body = self.0.get(HEADER_SIZE..HEADER_SIZE + len)?;
+
+        if xxh3_64(body) as u32 != checksum {
+            return None;
+        }
+
+        self.0 = &self.0[HEADER_SIZE + len..];
+        Some(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Seek;
+
+    use elfo_core::scope::Scope;
+
+    use super::*;
+
+    fn scope() -> Scope {
+        let meta = ActorMeta {
+            group: "group".into(),
+            key: "key".into(),
+        };
+        Scope::test(Addr::NULL, meta.into())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("elfo-journal-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn dump(no: u64) -> Dump {
+        #[derive(Serialize)]
+        struct Item {
+            no: u64,
+        }
+
+        let mut dump = scope().sync_within(|| {
+            Dump::builder()
+                .message_protocol("test")
+                .message_kind(MessageKind::Request(no))
+                .finish(Item { no })
+        });
+        dump.sequence_no = no.try_into().unwrap();
+        dump
+    }
+
+    fn message(dump: &Dump) -> String {
+        serde_json::to_string(&*dump.message).unwrap()
+    }
+
+    fn recover_all(dir: &Path, run_id: u64) -> Vec<Recovered> {
+        scope().sync_within(|| recover(dir, run_id, &FxHashSet::default()).unwrap())
+    }
+
+    #[test]
+    fn recovery() {
+        let dir = temp_dir("recovery");
+        let journal = Journal::new("class", 1);
+        journal.configure(Some(&dir), 10).unwrap();
+
+        let mut pushed = Vec::new();
+        for no in 1..=5 {
+            journal.append(dump(no), |dump| pushed.push(dump));
+        }
+        assert_eq!(pushed.len(), 5);
+
+        // Segments of the current run are ignored.
+        assert!(recover_all(&dir, 1).is_empty());
+
+        let recovered = recover_all(&dir, 2);
+        assert_eq!(recovered.len(), 1);
+        let recovered = &recovered[0];
+        assert_eq!(recovered.class, "class");
+        assert_eq!(recovered.segments.len(), 1);
+        assert_eq!(recovered.skipped, 0);
+        assert_eq!(recovered.dumps.len(), 5);
+
+        for (actual, expected) in recovered.dumps.iter().zip(&pushed) {
+            assert_eq!(actual.meta, expected.meta);
+            assert_eq!(actual.sequence_no, expected.sequence_no);
+            assert_eq!(actual.timestamp, expected.timestamp);
+            assert_eq!(actual.trace_id, expected.trace_id);
+            assert_eq!(actual.thread_id, expected.thread_id);
+            assert_eq!(actual.direction, expected.direction);
+            assert_eq!(actual.recipient, expected.recipient);
+            assert_eq!(actual.message_name, expected.message_name);
+            assert_eq!(actual.message_protocol, expected.message_protocol);
+            assert_eq!(actual.message_kind, expected.message_kind);
+            assert_eq!(message(actual), message(expected));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_tail() {
+        let dir = temp_dir("corrupted");
+        let journal = Journal::new("class", 1);
+        journal.configure(Some(&dir), 10).unwrap();
+
+        for no in 1..=3 {
+            journal.append(dump(no), drop);
+        }
+
+        let path = list(&dir).unwrap().pop().unwrap().0;
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        let len = file.seek(io::SeekFrom::End(0)).unwrap();
+
+        // Corrupt the last record.
+        file.seek(io::SeekFrom::End(-2)).unwrap();
+        file.write_all(b"xx").unwrap();
+
+        let recovered = recover_all(&dir, 2);
+        assert_eq!(recovered[0].dumps.len(), 2);
+        let skipped = recovered[0].skipped;
+        assert!(skipped > HEADER_SIZE);
+
+        // Truncate the last record.
+        file.set_len(len - 1).unwrap();
+        let recovered = recover_all(&dir, 2);
+        assert_eq!(recovered[0].dumps.len(), 2);
+        assert_eq!(recovered[0].skipped, skipped - 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir("rotation");
+        let journal = Journal::new("class", 1);
+        journal.configure(Some(&dir), 10).unwrap();
+        let count = || list(&dir).unwrap().len();
+
+        // Empty segments aren't rotated.
+        let marker = journal.rotate().unwrap();
+        assert_eq!(count(), 1);
+
+        journal.append(dump(1), drop);
+        let marker2 = journal.rotate().unwrap();
+        assert_eq!(count(), 2);
+        journal.append(dump(2), drop);
+
+        journal.release(marker);
+        assert_eq!(count(), 2);
+        journal.release(marker2);
+        assert_eq!(count(), 1);
+
+        // Segments of previous runs are adopted and released after rotation.
+        let recovered = recover_all(&dir, 2).pop().unwrap();
+        assert_eq!(recovered.dumps.len(), 1);
+        let journal = Journal::new("class", 2);
+        journal.configure(Some(&dir), 10).unwrap();
+        journal.adopt(recovered.segments);
+        assert_eq!(count(), 2);
+        let marker = journal.rotate().unwrap();
+        journal.release(marker);
+        assert_eq!(count(), 1);
+
+        // Disabling removes all segments.
+        journal.configure(None, 10).unwrap();
+        assert_eq!(count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -7,7 +7,7 @@ use crate::dump_storage::DumpRegistry;
 
 impl Recorder for DumpRegistry {
     fn enabled(&self) -> bool {
-        scope::try_with(|scope| match scope.dumping().check(self.class()) {
+        scope::try_with(|scope| match scope.check_dumping(self.class()) {
             CheckResult::Passed => {
                 // TODO: `elfo_lost_dumps_total`
                 // TODO: `elfo_emitted_dumps_total`
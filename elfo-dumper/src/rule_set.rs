@@ -78,7 +78,7 @@ fn collect_params(rules: &[Rule], protocol: &'static str, message: &MessageName)
                 && r.message.as_ref().map_or(true, |m| &m.as_str() == message)
         })
         .for_each(|r| {
-            params.max_size = r.max_size.map(|s| s.0 as _).unwrap_or(params.max_size);
+            params.max_size = r.max_size.map_or(params.max_size, |s| s.as_usize());
             params.on_overflow = r.on_overflow.unwrap_or(params.on_overflow);
             params.log_on_overflow = r
                 .log_on_overflow
@@ -104,35 +104,35 @@ fn convert_level(level: LogLevel) -> LevelFilter {
 
 #[test]
 fn it_works() {
-    use bytesize::ByteSize;
+    use elfo_core::config::ByteSize;
 
     let mut rules = vec![
         Rule {
             class: Some("another".into()),
-            max_size: Some(ByteSize(0)),
+            max_size: Some(ByteSize::new(0)),
             ..Rule::default()
         },
         Rule {
             class: Some("some".into()),
             protocol: Some("proto_a".into()),
-            max_size: Some(ByteSize(1)),
+            max_size: Some(ByteSize::new(1)),
             ..Rule::default()
         },
         Rule {
             message: Some("A".into()),
-            max_size: Some(ByteSize(2)),
+            max_size: Some(ByteSize::new(2)),
             ..Rule::default()
         },
         Rule {
             protocol: Some("proto_b".into()),
             message: Some("B".into()),
-            max_size: Some(ByteSize(3)),
+            max_size: Some(ByteSize::new(3)),
             log_on_overflow: Some(LogLevel::Info),
             ..Rule::default()
         },
         Rule {
             message: Some("B".into()),
-            max_size: Some(ByteSize(4)),
+            max_size: Some(ByteSize::new(4)),
             log_on_failure: Some(LogLevel::Error),
             ..Rule::default()
         },
@@ -1,15 +1,22 @@
-use std::{borrow::Cow, io, mem};
+use std::{borrow::Cow, collections::VecDeque, fmt, io, mem};
 
+use fxhash::FxHashMap;
 use serde::ser::SerializeStruct;
+use xxhash_rust::xxh3::xxh3_64;
 
 use elfo_core::{
-    addr::NodeNo,
+    addr::{Addr, NodeNo},
     dumping::{Dump, MessageKind},
     scope,
+    tracing::TraceId,
 };
-use elfo_utils::unlikely;
+use elfo_utils::{time::SystemTime, unlikely, ward};
 
-use crate::{config::OnOverflow, reporter::Report, rule_set::DumpParams};
+use crate::{
+    config::{Config, FieldNames, OnOverflow},
+    reporter::Report,
+    rule_set::DumpParams,
+};
 
 // === Serializer ===
 
@@ -21,6 +28,12 @@ pub(crate) struct Serializer {
     name_buffer: String,
     /// A buffer for messages that serialized as strings.
     message_buffer: Vec<u8>,
+    /// A buffer for messages that serialized to be hashed.
+    hash_buffer: Vec<u8>,
+    hash_payload: bool,
+    dedup_window: DedupWindow,
+    detailed_traces: DetailedTraces,
+    keys: &'static Keys,
     output: Vec<u8>,
     need_to_clear: bool,
     report: Report,
@@ -43,15 +56,45 @@ impl Serializer {
             chunk_size,
             name_buffer: String::new(),
             message_buffer: Vec::new(),
+            hash_buffer: Vec::new(),
+            hash_payload: false,
+            dedup_window: DedupWindow::default(),
+            detailed_traces: DetailedTraces::default(),
+            keys: &Keys::SHORT,
             output: Vec::with_capacity(initial_chunk_capacity),
             need_to_clear: false,
             report: Report::default(),
         }
     }
 
+    pub(crate) fn configure(&mut self, config: &Config) {
+        self.hash_payload = config.hash_payload || config.dedup_window > 0;
+        self.dedup_window.configure(config.dedup_window);
+        self.detailed_traces.max_size = config.max_detailed_trace_size.as_usize();
+
+        // The last relevant rule wins.
+        let field_names = config
+            .rules
+            .iter()
+            .rev()
+            .filter(|r| r.class.as_ref().map_or(true, |c| c == self.class))
+            .filter(|r| r.protocol.is_none() && r.message.is_none())
+            .find_map(|r| r.field_names)
+            .unwrap_or(config.field_names);
+
+        self.keys = match field_names {
+            FieldNames::Short => &Keys::SHORT,
+            FieldNames::Long => &Keys::LONG,
+        };
+    }
+
     pub(crate) fn append(&mut self, dump: &Dump, params: &DumpParams) -> Option<&[u8]> {
         self.clear_if_needed();
 
+        if dump.is_detailed && !self.detailed_traces.has_room(dump.trace_id) {
+            return None;
+        }
+
         let prev_len = self.output.len();
 
         match self.do_append(dump, params) {
@@ -59,6 +102,12 @@ impl Serializer {
                 debug_assert_ne!(self.output.len(), prev_len);
                 self.report.appended += 1;
                 self.output.push(b'\n');
+
+                if dump.is_detailed {
+                    let size = self.output.len() - prev_len;
+                    self.detailed_traces.add(dump.trace_id, size);
+                }
+
                 self.take_if_limit_exceeded(self.chunk_size)
             }
             Ok(false) => {
@@ -77,12 +126,24 @@ impl Serializer {
     /// * `Ok(false)` — skipped.
     /// * `Err(err)` — failed.
     fn do_append(&mut self, dump: &Dump, params: &DumpParams) -> Result<bool, serde_json::Error> {
+        let hash = if self.hash_payload {
+            // The hash is computed over the same bytes that are written as `m`.
+            self.hash_buffer.clear();
+            serde_json::to_writer(&mut self.hash_buffer, &*dump.message)?;
+            Some(PayloadHash(xxh3_64(&self.hash_buffer)))
+        } else {
+            None
+        };
+
         let mut compact_dump = CompactDump {
             dump,
             class: self.class,
+            keys: self.keys,
             node_no: self.node_no,
             message_name: dump.message_name.to_str(&mut self.name_buffer),
             message: None,
+            hash,
+            is_dup: hash.is_some_and(|hash| self.dedup_window.contains(hash)),
         };
 
         let prev_len = self.output.len();
@@ -90,7 +151,12 @@ impl Serializer {
         // Try to serialize directly into the output buffer.
         let mut wr = LimitedWrite::new(&mut self.output, params.max_size);
         match serde_json::to_writer(&mut wr, &compact_dump) {
-            Ok(()) => return Ok(true),
+            Ok(()) => {
+                if let Some(hash) = hash {
+                    self.dedup_window.push(hash);
+                }
+                return Ok(true);
+            }
             Err(err) => {
                 let limit_reached = wr.limit_reached;
 
@@ -143,6 +209,10 @@ impl Serializer {
                 if limit_reached {
                     self.report.add_overflow(dump, true, params);
                 }
+                // Truncated messages cannot be referenced by duplicates.
+                if let Some(hash) = hash.filter(|_| !limit_reached) {
+                    self.dedup_window.push(hash);
+                }
                 true
             })
             .inspect_err(|_| self.output.truncate(prev_len))
@@ -154,6 +224,25 @@ impl Serializer {
         (self.take_if_limit_exceeded(0), report)
     }
 
+    /// Serializes the trailer as the only line of the returned chunk.
+    /// Pending dumps must be taken before.
+    pub(crate) fn trailer(&mut self, trailer: &Trailer) -> &[u8] {
+        self.clear_if_needed();
+        debug_assert!(self.output.is_empty());
+
+        let record = CompactTrailer {
+            trailer,
+            class: self.class,
+            keys: self.keys,
+            node_no: self.node_no,
+        };
+
+        serde_json::to_writer(&mut self.output, &record).expect("trailer is serializable");
+        self.output.push(b'\n');
+        self.need_to_clear = true;
+        &self.output
+    }
+
     fn clear_if_needed(&mut self) {
         if unlikely(self.need_to_clear) {
             self.output.clear();
@@ -176,58 +265,299 @@ impl Serializer {
 struct CompactDump<'a> {
     dump: &'a Dump,
     class: &'a str,
+    keys: &'static Keys,
     node_no: NodeNo,
     message_name: &'a str,
     message: Option<Cow<'a, str>>,
+    hash: Option<PayloadHash>,
+    is_dup: bool,
 }
 
 impl serde::Serialize for CompactDump<'_> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let field_count = 12
             + !self.dump.meta.key.is_empty() as usize // "k"
-            + !matches!(self.dump.message_kind, MessageKind::Regular) as usize; // "c"
+            + !self.dump.recipient.is_null() as usize // "to"
+            + self.hash.is_some() as usize // "h"
+            + !matches!(self.dump.message_kind, MessageKind::Regular) as usize // "c"
+            + self.dump.channel_id.is_some() as usize; // "ch"
 
+        let keys = self.keys;
         let mut s = serializer.serialize_struct("Dump", field_count)?;
 
         // Dump `ts` firstly to make it possible to use `sort`.
-        s.serialize_field("ts", &self.dump.timestamp.to_unix_time_nanos())?;
-        s.serialize_field("g", &self.dump.meta.group)?;
+        s.serialize_field(keys.timestamp, &self.dump.timestamp.to_unix_time_nanos())?;
+        s.serialize_field(keys.group, &self.dump.meta.group)?;
 
         if !self.dump.meta.key.is_empty() {
-            s.serialize_field("k", &self.dump.meta.key)?;
+            s.serialize_field(keys.key, &self.dump.meta.key)?;
+        }
+
+        s.serialize_field(keys.node, &self.node_no)?;
+        s.serialize_field(keys.sequence_no, &self.dump.sequence_no)?;
+        s.serialize_field(keys.trace_id, &self.dump.trace_id)?;
+        s.serialize_field(keys.thread_id, &self.dump.thread_id)?;
+        s.serialize_field(keys.direction, &self.dump.direction)?;
+
+        if !self.dump.recipient.is_null() {
+            s.serialize_field(keys.recipient, &DisplayAddr(self.dump.recipient))?;
         }
 
-        s.serialize_field("n", &self.node_no)?;
-        s.serialize_field("s", &self.dump.sequence_no)?;
-        s.serialize_field("t", &self.dump.trace_id)?;
-        s.serialize_field("th", &self.dump.thread_id)?;
-        s.serialize_field("d", &self.dump.direction)?;
-        s.serialize_field("cl", &self.class)?;
-        s.serialize_field("mn", &self.message_name)?;
-        s.serialize_field("mp", &self.dump.message_protocol)?;
+        s.serialize_field(keys.class, &self.class)?;
+        s.serialize_field(keys.message_name, &self.message_name)?;
+        s.serialize_field(keys.message_protocol, &self.dump.message_protocol)?;
 
         let (message_kind, correlation_id) = match self.dump.message_kind {
             MessageKind::Regular => ("Regular", None),
             MessageKind::Request(c) => ("Request", Some(c)),
             MessageKind::Response(c) => ("Response", Some(c)),
+            MessageKind::Forward(c) => ("Forward", Some(c)),
         };
 
-        s.serialize_field("mk", message_kind)?;
+        s.serialize_field(keys.message_kind, message_kind)?;
 
-        if let Some(message) = &self.message {
-            s.serialize_field("m", message)?;
+        if let Some(hash) = &self.hash {
+            s.serialize_field(keys.hash, hash)?;
+        }
+
+        if let Some(hash) = self.hash.filter(|_| self.is_dup) {
+            s.serialize_field(keys.message, &Duplicate { hash })?;
+        } else if let Some(message) = &self.message {
+            s.serialize_field(keys.message, message)?;
         } else {
-            s.serialize_field("m", &*self.dump.message)?;
+            s.serialize_field(keys.message, &*self.dump.message)?;
         }
 
         if let Some(correlation_id) = correlation_id {
-            s.serialize_field("c", &correlation_id)?;
+            s.serialize_field(keys.correlation_id, &correlation_id)?;
+        }
+
+        if let Some(channel_id) = self.dump.channel_id {
+            s.serialize_field(keys.channel_id, &channel_id)?;
         }
 
         s.end()
     }
 }
 
+// === Trailer ===
+
+/// The last line written by a dumper of the class on termination:
+/// ```json
+/// {"ts":..,"n":..,"cl":"internal","$trailer":{"written":10,"lost":0,"from":..,"to":..,"clean":true}}
+/// ```
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct Trailer {
+    /// The number of written dumps.
+    pub(crate) written: usize,
+    /// The number of dumps lost because of the capacity limit or termination.
+    pub(crate) lost: usize,
+    /// The timestamp of the earliest handled dump, `null` if there are none.
+    pub(crate) from: Option<u64>,
+    /// The timestamp of the latest handled dump, `null` if there are none.
+    pub(crate) to: Option<u64>,
+    /// Whether all pending dumps have been written before the deadline.
+    pub(crate) clean: bool,
+}
+
+impl Trailer {
+    pub(crate) fn cover(&mut self, timestamp: SystemTime) {
+        let timestamp = timestamp.to_unix_time_nanos();
+        self.from = Some(self.from.map_or(timestamp, |from| from.min(timestamp)));
+        self.to = Some(self.to.map_or(timestamp, |to| to.max(timestamp)));
+    }
+}
+
+struct CompactTrailer<'a> {
+    trailer: &'a Trailer,
+    class: &'a str,
+    keys: &'static Keys,
+    node_no: NodeNo,
+}
+
+impl serde::Serialize for CompactTrailer<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let keys = self.keys;
+        let mut s = serializer.serialize_struct("Trailer", 4)?;
+        s.serialize_field(keys.timestamp, &SystemTime::now().to_unix_time_nanos())?;
+        s.serialize_field(keys.node, &self.node_no)?;
+        s.serialize_field(keys.class, &self.class)?;
+        s.serialize_field("$trailer", self.trailer)?;
+        s.end()
+    }
+}
+
+// === Keys ===
+
+/// Names of fields, see `FieldNames`.
+struct Keys {
+    timestamp: &'static str,
+    group: &'static str,
+    key: &'static str,
+    node: &'static str,
+    sequence_no: &'static str,
+    trace_id: &'static str,
+    thread_id: &'static str,
+    direction: &'static str,
+    recipient: &'static str,
+    class: &'static str,
+    message_name: &'static str,
+    message_protocol: &'static str,
+    message_kind: &'static str,
+    hash: &'static str,
+    message: &'static str,
+    correlation_id: &'static str,
+    channel_id: &'static str,
+}
+
+impl Keys {
+    const LONG: Self = Self {
+        timestamp: "timestamp",
+        group: "group",
+        key: "key",
+        node: "node",
+        sequence_no: "sequence_no",
+        trace_id: "trace_id",
+        thread_id: "thread_id",
+        direction: "direction",
+        recipient: "recipient",
+        class: "class",
+        message_name: "message_name",
+        message_protocol: "message_protocol",
+        message_kind: "message_kind",
+        hash: "hash",
+        message: "message",
+        correlation_id: "correlation_id",
+        channel_id: "channel_id",
+    };
+    const SHORT: Self = Self {
+        timestamp: "ts",
+        group: "g",
+        key: "k",
+        node: "n",
+        sequence_no: "s",
+        trace_id: "t",
+        thread_id: "th",
+        direction: "d",
+        recipient: "to",
+        class: "cl",
+        message_name: "mn",
+        message_protocol: "mp",
+        message_kind: "mk",
+        hash: "h",
+        message: "m",
+        correlation_id: "c",
+        channel_id: "ch",
+    };
+}
+
+// === PayloadHash ===
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PayloadHash(u64);
+
+impl fmt::Display for PayloadHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl serde::Serialize for PayloadHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Addresses are written as `node/group/slot`, e.g. `"1/2/3"`.
+struct DisplayAddr(Addr);
+
+impl serde::Serialize for DisplayAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Duplicate {
+    #[serde(rename = "$dup")]
+    hash: PayloadHash,
+}
+
+// === DedupWindow ===
+
+/// Hashes of the last written dumps.
+#[derive(Default)]
+struct DedupWindow {
+    capacity: usize,
+    order: VecDeque<PayloadHash>,
+    counts: FxHashMap<PayloadHash, usize>,
+}
+
+impl DedupWindow {
+    fn configure(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.order.len() > capacity {
+            self.evict();
+        }
+    }
+
+    fn contains(&self, hash: PayloadHash) -> bool {
+        self.counts.contains_key(&hash)
+    }
+
+    fn push(&mut self, hash: PayloadHash) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.order.len() == self.capacity {
+            self.evict();
+        }
+
+        self.order.push_back(hash);
+        *self.counts.entry(hash).or_default() += 1;
+    }
+
+    fn evict(&mut self) {
+        let hash = ward!(self.order.pop_front());
+        let count = self.counts.get_mut(&hash).expect("invalid dedup window");
+        *count -= 1;
+
+        if *count == 0 {
+            self.counts.remove(&hash);
+        }
+    }
+}
+
+// === DetailedTraces ===
+
+/// Detailed traces are rare, so the map is just cleared if it's too big.
+const MAX_DETAILED_TRACES: usize = 1024;
+
+/// Sizes of written dumps of detailed traces.
+#[derive(Default)]
+struct DetailedTraces {
+    max_size: usize,
+    written: FxHashMap<TraceId, usize>,
+}
+
+impl DetailedTraces {
+    fn has_room(&self, trace_id: TraceId) -> bool {
+        self.written
+            .get(&trace_id)
+            .map_or(self.max_size > 0, |&written| written < self.max_size)
+    }
+
+    fn add(&mut self, trace_id: TraceId, size: usize) {
+        if self.written.len() >= MAX_DETAILED_TRACES && !self.written.contains_key(&trace_id) {
+            self.written.clear();
+        }
+
+        *self.written.entry(trace_id).or_default() += size;
+    }
+}
+
 // === LimitedWrite ===
 
 struct LimitedWrite<W> {
@@ -320,10 +650,16 @@ mod tests {
         dump
     }
 
+    /// The serialized trace id of `dump()`, depends on `trace-id-128`.
+    fn trace_id() -> String {
+        serde_json::to_string(&TraceId::try_from(1).unwrap()).unwrap()
+    }
+
     fn line(sequence_no: u64, length: usize) -> String {
-        let template = r#"{"ts":2,"g":"group","k":"key","n":65535,"s":SEQNO,"t":1,"th":0,"d":"Out","cl":"some","mn":"Some","mp":"some","mk":"Regular","m":{"body":"BODY"}}"#;
+        let template = r#"{"ts":2,"g":"group","k":"key","n":65535,"s":SEQNO,"t":TRACE_ID,"th":0,"d":"Out","cl":"some","mn":"Some","mp":"some","mk":"Regular","m":{"body":"BODY"}}"#;
         template
             .replace("SEQNO", &sequence_no.to_string())
+            .replace("TRACE_ID", &trace_id())
             .replace("BODY", &"X".repeat(length))
     }
 
@@ -413,7 +749,8 @@ mod tests {
         let mut serializer = serializer(chunk_size, "some");
 
         let sample = dump(42, 4, true);
-        let expected = r#"{"ts":2,"g":"group","k":"key","n":65535,"s":42,"t":1,"th":0,"d":"Out","cl":"some","mn":"Some","mp":"some","mk":"Regular","m":"{\"body\":\" TRUNCATED"}"#;
+        let expected = r#"{"ts":2,"g":"group","k":"key","n":65535,"s":42,"t":TRACE_ID,"th":0,"d":"Out","cl":"some","mn":"Some","mp":"some","mk":"Regular","m":"{\"body\":\" TRUNCATED"}"#
+            .replace("TRACE_ID", &trace_id());
         let mut expected_lines = chunk_size / (expected.len() + 1); // 1 for `\n`
         expected_lines += 1; // `append()` returns a chunk iff `chunk_size` is exceeded
 
@@ -474,4 +811,268 @@ mod tests {
             assert_eq!(chunk, format!("{expected}\n").repeat(expected_lines));
         }
     }
+
+    fn hashing_serializer(dedup_window: usize) -> Serializer {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "path": "unused",
+            "hash_payload": true,
+            "dedup_window": dedup_window,
+        }))
+        .unwrap();
+
+        let mut serializer = serializer(1024 * 1024, "some");
+        serializer.configure(&config);
+        serializer
+    }
+
+    fn append_all(serializer: &mut Serializer, dumps: &[Dump]) -> Vec<String> {
+        for dump in dumps {
+            assert!(serializer.append(dump, &DumpParams::default()).is_none());
+        }
+
+        let chunk = serializer.take().0.unwrap();
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        chunk.lines().map(String::from).collect()
+    }
+
+    fn hash_of(length: usize) -> String {
+        let body = format!(r#"{{"body":"{}"}}"#, "X".repeat(length));
+        format!("{:016x}", xxh3_64(body.as_bytes()))
+    }
+
+    #[test]
+    fn hashed() {
+        let mut serializer = hashing_serializer(0);
+        let lines = append_all(&mut serializer, &[dump(42, 4, true), dump(42, 4, true)]);
+
+        let expected = line(42, 4).replace(
+            r#""mk":"Regular","#,
+            &format!(r#""mk":"Regular","h":"{}","#, hash_of(4)),
+        );
+        assert_eq!(lines, [expected.clone(), expected]);
+    }
+
+    #[test]
+    fn deduplicated() {
+        let mut serializer = hashing_serializer(1000);
+        let dumps = (0..50).map(|_| dump(42, 100, true)).collect::<Vec<_>>();
+        let lines = append_all(&mut serializer, &dumps);
+
+        let hash = hash_of(100);
+        let full = line(42, 100).replace(
+            r#""mk":"Regular","#,
+            &format!(r#""mk":"Regular","h":"{hash}","#),
+        );
+        let dup = full.replace(
+            &format!(r#"{{"body":"{}"}}"#, "X".repeat(100)),
+            &format!(r#"{{"$dup":"{hash}"}}"#),
+        );
+
+        assert_eq!(lines.len(), 50);
+        assert_eq!(lines[0], full);
+        assert!(lines[1..].iter().all(|line| *line == dup));
+    }
+
+    #[test]
+    fn distinct_not_deduplicated() {
+        let mut serializer = hashing_serializer(1000);
+        let dumps = (1..=500).map(|len| dump(42, len, true)).collect::<Vec<_>>();
+        let lines = append_all(&mut serializer, &dumps);
+
+        assert_eq!(lines.len(), 500);
+        assert!(lines.iter().all(|line| !line.contains("$dup")));
+
+        let hashes = (1..=500)
+            .map(hash_of)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(hashes.len(), 500);
+    }
+
+    #[test]
+    fn dedup_window_is_limited() {
+        let mut serializer = hashing_serializer(1);
+        let dumps = [dump(1, 1, true), dump(2, 2, true), dump(3, 1, true)];
+        let lines = append_all(&mut serializer, &dumps);
+        assert!(lines.iter().all(|line| !line.contains("$dup")));
+
+        let mut serializer = hashing_serializer(2);
+        let lines = append_all(&mut serializer, &dumps);
+        assert!(lines[2].contains("$dup"));
+    }
+
+    fn configured_serializer(class: &'static str, config: serde_json::Value) -> Serializer {
+        let mut config = config;
+        config["path"] = "unused".into();
+        let config: Config = serde_json::from_value(config).unwrap();
+
+        let mut serializer = serializer(1024 * 1024, class);
+        serializer.configure(&config);
+        serializer
+    }
+
+    #[test]
+    fn detailed_traces_are_limited() {
+        let line_len = line(42, 100).len() + 1; // 1 for `\n`
+        let mut serializer = configured_serializer(
+            "some",
+            serde_json::json!({ "max_detailed_trace_size": 3 * line_len - 1 }),
+        );
+
+        let detailed = |trace_id: u64| {
+            let mut dump = dump(42, 100, true);
+            dump.trace_id = TraceId::try_from(trace_id).unwrap();
+            dump.is_detailed = true;
+            dump
+        };
+
+        // The limit is checked before appending, so it's exceeded by one dump.
+        let dumps = (0..5).map(|_| detailed(1)).collect::<Vec<_>>();
+        assert_eq!(append_all(&mut serializer, &dumps).len(), 3);
+
+        // Other traces have their own limits.
+        let dumps = (0..5).map(|_| detailed(2)).collect::<Vec<_>>();
+        assert_eq!(append_all(&mut serializer, &dumps).len(), 3);
+
+        // Ordinary dumps aren't limited.
+        let dumps = (0..5).map(|_| dump(42, 100, true)).collect::<Vec<_>>();
+        assert_eq!(append_all(&mut serializer, &dumps).len(), 5);
+    }
+
+    fn keys_of(line: &str) -> Vec<String> {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        value.as_object().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn field_names() {
+        let sample = || {
+            let mut sample = dump(42, 4, true);
+            sample.message_kind = MessageKind::Request(5);
+            sample.recipient = Addr::from_bits(1 << 48 | 2 << 40 | 3).unwrap();
+            sample
+        };
+
+        let mut short = configured_serializer("some", serde_json::json!({ "hash_payload": true }));
+        let short = append_all(&mut short, &[sample()]).remove(0);
+        assert_eq!(
+            short,
+            format!(
+                r#"{{"ts":2,"g":"group","k":"key","n":65535,"s":42,"t":{},"th":0,"d":"Out","to":"1/2/3","cl":"some","mn":"Some","mp":"some","mk":"Request","h":"{}","m":{{"body":"XXXX"}},"c":5}}"#,
+                trace_id(),
+                hash_of(4)
+            )
+        );
+
+        let mut long = configured_serializer(
+            "some",
+            serde_json::json!({ "hash_payload": true, "field_names": "long" }),
+        );
+        let long = append_all(&mut long, &[sample()]).remove(0);
+        assert_eq!(
+            long,
+            format!(
+                r#"{{"timestamp":2,"group":"group","key":"key","node":65535,"sequence_no":42,"trace_id":{},"thread_id":0,"direction":"Out","recipient":"1/2/3","class":"some","message_name":"Some","message_protocol":"some","message_kind":"Request","hash":"{}","message":{{"body":"XXXX"}},"correlation_id":5}}"#,
+                trace_id(),
+                hash_of(4)
+            )
+        );
+
+        let mut short_keys = keys_of(&short);
+        let mut long_keys = keys_of(&long);
+        short_keys.sort();
+        long_keys.sort();
+        assert_eq!(
+            short_keys,
+            [
+                "c", "cl", "d", "g", "h", "k", "m", "mk", "mn", "mp", "n", "s", "t", "th", "to",
+                "ts"
+            ]
+        );
+        assert_eq!(
+            long_keys,
+            [
+                "class",
+                "correlation_id",
+                "direction",
+                "group",
+                "hash",
+                "key",
+                "message",
+                "message_kind",
+                "message_name",
+                "message_protocol",
+                "node",
+                "recipient",
+                "sequence_no",
+                "thread_id",
+                "timestamp",
+                "trace_id",
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_id() {
+        let mut sample = dump(42, 4, true);
+        sample.channel_id = Some(serde_json::from_value(serde_json::json!(7)).unwrap());
+
+        let mut short = serializer(1024, "some");
+        let line = append_all(&mut short, &[sample]).remove(0);
+        assert!(line.ends_with(r#""m":{"body":"XXXX"},"ch":7}"#), "{line}");
+
+        // Omitted for ordinary messages.
+        let line = append_all(&mut short, &[dump(43, 4, true)]).remove(0);
+        assert!(!line.contains(r#""ch""#), "{line}");
+    }
+
+    #[test]
+    fn field_names_per_class() {
+        let config = serde_json::json!({
+            "field_names": "long",
+            "rules": [
+                { "class": "some", "field_names": "short" },
+                // Ignored, the property is per class.
+                { "class": "some", "message": "Some", "field_names": "long" },
+            ],
+        });
+
+        let mut some = configured_serializer("some", config.clone());
+        let lines = append_all(&mut some, &[dump(42, 4, true)]);
+        assert_eq!(lines, [line(42, 4)]);
+
+        let mut other = configured_serializer("other", config);
+        let lines = append_all(&mut other, &[dump(42, 4, true)]);
+        assert!(lines[0].starts_with(r#"{"timestamp":2,"group":"group","#));
+    }
+
+    #[test]
+    fn trailer() {
+        let mut serializer = serializer(1024, "some");
+        let mut trailer = Trailer::default();
+
+        let (chunk, _) = serializer.take();
+        assert!(chunk.is_none());
+        let chunk = std::str::from_utf8(serializer.trailer(&trailer)).unwrap();
+        let expected = r#","n":65535,"cl":"some","$trailer":{"written":0,"lost":0,"from":null,"to":null,"clean":false}}"#;
+        assert!(chunk.ends_with(&format!("{expected}\n")), "{chunk}");
+
+        assert!(serializer
+            .append(&dump(1, 4, true), &DumpParams::default())
+            .is_none());
+        let (chunk, report) = serializer.take();
+        assert_eq!(chunk.unwrap(), format!("{}\n", line(1, 4)).as_bytes());
+
+        trailer.written = report.appended;
+        trailer.lost = 3;
+        trailer.cover(SystemTime::from_unix_time_nanos(5));
+        trailer.cover(SystemTime::from_unix_time_nanos(2));
+        trailer.clean = true;
+
+        // Only the trailer is written, the previous chunk is cleared.
+        let chunk = std::str::from_utf8(serializer.trailer(&trailer)).unwrap();
+        assert!(chunk.starts_with(r#"{"ts":"#));
+        assert_eq!(chunk.lines().count(), 1);
+        let expected = r#","n":65535,"cl":"some","$trailer":{"written":1,"lost":3,"from":2,"to":5,"clean":true}}"#;
+        assert!(chunk.ends_with(&format!("{expected}\n")), "{chunk}");
+    }
 }
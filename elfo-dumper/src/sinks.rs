@@ -0,0 +1,380 @@
+//! Failover between sinks of dumps, see `sinks` and `mirror` in the config.
+
+use std::time::Duration;
+
+use eyre::{eyre, Result, WrapErr};
+use metrics::increment_counter;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{config::Config, file_registry::FileHandle};
+
+/// A destination of serialized dumps.
+pub(crate) trait Sink {
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    fn write(&self, chunk: &[u8]) -> Result<()>;
+
+    /// Writes buffered data, see `FileHandle::flush()`.
+    /// Must be called in a blocking context (e.g. inside `spawn_blocking`).
+    fn flush(&self, _force: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Sink for FileHandle {
+    fn write(&self, chunk: &[u8]) -> Result<()> {
+        FileHandle::write(self, chunk).context("cannot write to the dump file")
+    }
+
+    fn flush(&self, force: bool) -> Result<()> {
+        FileHandle::flush(self, force).context("cannot write to the dump file")
+    }
+}
+
+/// Health of sinks in order of priority.
+pub(crate) struct Sinks {
+    states: Vec<SinkState>,
+    /// The sequence number of the next chunk.
+    next_seq: u64,
+    mirror: bool,
+    failure_threshold: u32,
+    probe_interval: Duration,
+}
+
+struct SinkState {
+    name: String,
+    /// Consecutive failed writes.
+    failures: u32,
+    /// `Some` if the sink is unhealthy.
+    next_probe: Option<Instant>,
+    /// The sequence number of the last written chunk.
+    last_seq: u64,
+}
+
+impl Sinks {
+    pub(crate) fn new(config: &Config) -> Self {
+        let mut this = Self {
+            states: Vec::new(),
+            next_seq: 1,
+            mirror: false,
+            failure_threshold: 1,
+            probe_interval: Duration::ZERO,
+        };
+        this.configure(config);
+        this
+    }
+
+    pub(crate) fn configure(&mut self, config: &Config) {
+        self.mirror = config.mirror;
+        self.failure_threshold = config.sink_failure_threshold.max(1);
+        self.probe_interval = *config.sink_probe_interval;
+
+        // Sinks are matched by their templates, so changed ones start healthy.
+        let mut states = std::mem::take(&mut self.states);
+        self.states = config
+            .templates()
+            .map(|template| {
+                let name = template.as_str().to_owned();
+                match states.iter().position(|state| state.name == name) {
+                    Some(index) => states.swap_remove(index),
+                    None => SinkState::new(name),
+                }
+            })
+            .collect();
+    }
+
+    /// Writes the chunk to the first healthy sink or, if `mirror` is enabled,
+    /// to all healthy sinks. Unhealthy sinks are probed once in the interval.
+    ///
+    /// `sinks` correspond to `Config::templates()`.
+    /// Fails only if no sink has accepted the chunk.
+    pub(crate) fn write(&mut self, sinks: &[impl Sink], chunk: &[u8]) -> Result<()> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.write_seq(sinks, seq, chunk, Instant::now())
+    }
+
+    /// Flushes healthy sinks, unhealthy ones are flushed once recovered.
+    /// Fails only if all flushed sinks have failed.
+    pub(crate) fn flush(&mut self, sinks: &[impl Sink], force: bool) -> Result<()> {
+        debug_assert_eq!(sinks.len(), self.states.len());
+
+        let now = Instant::now();
+        let mut is_flushed = false;
+        let mut last_error = None;
+
+        for (sink, state) in sinks.iter().zip(&mut self.states) {
+            if state.next_probe.is_some() {
+                continue;
+            }
+
+            match sink.flush(force) {
+                Ok(()) => is_flushed = true,
+                Err(err) => {
+                    state.on_failure(&err, now, self.failure_threshold, self.probe_interval);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) if !is_flushed => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// `seq` must increase with every chunk, so a chunk is never written to
+    /// the same sink twice.
+    fn write_seq(
+        &mut self,
+        sinks: &[impl Sink],
+        seq: u64,
+        chunk: &[u8],
+        now: Instant,
+    ) -> Result<()> {
+        debug_assert_eq!(sinks.len(), self.states.len());
+
+        let mut is_written = false;
+        let mut last_error = None;
+
+        for (sink, state) in sinks.iter().zip(&mut self.states) {
+            if state.last_seq >= seq {
+                is_written = true;
+                continue;
+            }
+
+            if state.next_probe.is_some_and(|probe| probe > now) {
+                continue;
+            }
+
+            match sink.write(chunk) {
+                Ok(()) => {
+                    state.on_success(seq);
+                    is_written = true;
+
+                    if !self.mirror {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    state.on_failure(&err, now, self.failure_threshold, self.probe_interval);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        if is_written {
+            return Ok(());
+        }
+
+        Err(last_error.unwrap_or_else(|| eyre!("all dump sinks are unhealthy")))
+    }
+}
+
+impl SinkState {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            failures: 0,
+            next_probe: None,
+            last_seq: 0,
+        }
+    }
+
+    fn on_success(&mut self, seq: u64) {
+        self.last_seq = seq;
+        self.failures = 0;
+
+        if self.next_probe.take().is_some() {
+            info!(sink = %self.name, "dump sink is recovered");
+            increment_counter!("elfo_dump_sink_transitions_total", "to" => "healthy");
+        }
+    }
+
+    fn on_failure(
+        &mut self,
+        err: &eyre::Report,
+        now: Instant,
+        threshold: u32,
+        probe_interval: Duration,
+    ) {
+        self.failures = self.failures.saturating_add(1);
+
+        if self.next_probe.is_some() {
+            // The probe has failed, the sink is still unhealthy.
+            self.next_probe = Some(now + probe_interval);
+        } else if self.failures >= threshold {
+            warn!(
+                sink = %self.name,
+                failures = self.failures,
+                error = %err,
+                "dump sink is unhealthy, failing over",
+            );
+            increment_counter!("elfo_dump_sink_transitions_total", "to" => "unhealthy");
+            self.next_probe = Some(now + probe_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    use super::*;
+
+    #[derive(Default, Clone)]
+    struct TestSink {
+        is_failing: Rc<Cell<bool>>,
+        written: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl TestSink {
+        fn fail(&self, is_failing: bool) {
+            self.is_failing.set(is_failing);
+        }
+
+        fn written(&self) -> Vec<u8> {
+            self.written.borrow().clone()
+        }
+    }
+
+    impl Sink for TestSink {
+        fn write(&self, chunk: &[u8]) -> Result<()> {
+            if self.is_failing.get() {
+                return Err(eyre!("injected failure"));
+            }
+
+            self.written.borrow_mut().extend_from_slice(chunk);
+            Ok(())
+        }
+    }
+
+    fn config(mirror: bool) -> Config {
+        let config = format!(
+            r#"
+            path = "primary.dump"
+            sinks = [{{ kind = "file", path = "secondary.dump" }}]
+            mirror = {mirror}
+            sink_failure_threshold = 2
+            sink_probe_interval = "10s"
+            "#
+        );
+        toml::from_str(&config).unwrap()
+    }
+
+    struct Setup {
+        sinks: Sinks,
+        targets: [TestSink; 2],
+        seq: u64,
+        now: Instant,
+    }
+
+    impl Setup {
+        fn new(mirror: bool) -> Self {
+            Self {
+                sinks: Sinks::new(&config(mirror)),
+                targets: Default::default(),
+                seq: 0,
+                now: Instant::now(),
+            }
+        }
+
+        fn write(&mut self, chunk: u8) -> Result<()> {
+            self.seq += 1;
+            self.sinks
+                .write_seq(&self.targets, self.seq, &[chunk], self.now)
+        }
+
+        fn written(&self) -> [Vec<u8>; 2] {
+            self.targets.clone().map(|t| t.written())
+        }
+    }
+
+    #[test]
+    fn failover_and_recovery() {
+        let mut s = Setup::new(false);
+
+        s.write(1).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![]]);
+
+        // The failed chunk goes to the next sink, the primary is still healthy.
+        s.targets[0].fail(true);
+        s.write(2).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![2]]);
+        assert!(s.sinks.states[0].next_probe.is_none());
+
+        // The threshold is reached, the primary isn't tried until the probe.
+        s.write(3).unwrap();
+        s.targets[0].fail(false);
+        s.write(4).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![2, 3, 4]]);
+
+        // The failed probe postpones the next one.
+        s.now += Duration::from_secs(10);
+        s.targets[0].fail(true);
+        s.write(5).unwrap();
+        s.targets[0].fail(false);
+        s.now += Duration::from_secs(5);
+        s.write(6).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![2, 3, 4, 5, 6]]);
+
+        // Switched back once recovered.
+        s.now += Duration::from_secs(5);
+        s.write(7).unwrap();
+        s.write(8).unwrap();
+        assert_eq!(s.written(), [vec![1, 7, 8], vec![2, 3, 4, 5, 6]]);
+    }
+
+    #[test]
+    fn all_failed() {
+        let mut s = Setup::new(false);
+
+        s.targets[0].fail(true);
+        s.targets[1].fail(true);
+        assert!(s.write(1).is_err());
+        assert!(s.write(2).is_err());
+
+        // Both are unhealthy now, nothing is probed until the interval.
+        s.targets[1].fail(false);
+        assert!(s.write(3).is_err());
+
+        s.now += Duration::from_secs(10);
+        s.write(4).unwrap();
+        assert_eq!(s.written(), [vec![], vec![4]]);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut s = Setup::new(true);
+
+        s.write(1).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![1]]);
+
+        // Written to the rest of healthy sinks.
+        s.targets[0].fail(true);
+        s.write(2).unwrap();
+        s.write(3).unwrap();
+        s.targets[0].fail(false);
+        s.write(4).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![1, 2, 3, 4]]);
+
+        // The recovered sink gets only new chunks.
+        s.now += Duration::from_secs(10);
+        s.write(5).unwrap();
+        assert_eq!(s.written(), [vec![1, 5], vec![1, 2, 3, 4, 5]]);
+    }
+
+    #[test]
+    fn never_written_twice() {
+        let mut s = Setup::new(true);
+
+        s.write(1).unwrap();
+
+        // The same chunk again, e.g. retried after a failure of the batch.
+        s.sinks.write_seq(&s.targets, 1, &[1], s.now).unwrap();
+        assert_eq!(s.written(), [vec![1], vec![1]]);
+    }
+}
@@ -1,39 +1,63 @@
 use std::{
+    fmt::Write as _,
     io::{self, IsTerminal as _},
     sync::Arc,
     time::Duration,
 };
 
-use metrics::increment_counter;
+use metrics::{gauge, increment_counter};
 use tokio::{
     fs::{File, OpenOptions},
     io::AsyncWriteExt,
 };
-use tracing::Metadata;
+use tracing::{debug, error, info, Metadata};
 
 use elfo_core::{
     message,
     messages::{ConfigUpdated, Terminate},
     msg,
     signal::{Signal, SignalKind},
-    ActorGroup, Blueprint, Context, RestartParams, RestartPolicy, TerminationPolicy,
+    time::{Delay, Interval},
+    ActorGroup, ActorStatus, Blueprint, Context, KeyEncoding, RestartParams, RestartPolicy,
+    TerminationPolicy,
 };
+use elfo_utils::{AdaptiveInterval, FlushReason};
 
 use crate::{
-    config::{Config, Sink},
+    config::{Config, FormatKind, Sink},
+    enricher,
+    fields::{escape_json, write_fields, write_json_fields, Fields},
     filtering_layer::FilteringLayer,
-    formatters::Formatter,
+    formatters::{reduce_location, ActorPrefix, Formatter, Output as _},
+    framing::Framing,
     line_buffer::LineBuffer,
     line_transaction::{FailOnUnfit, Line as _, LineFactory, TruncateOnUnfit},
-    theme, PreparedEvent, Shared,
+    multiline::write_payload,
+    overrides::{LogLevelOverride, Overrides, RevertLogLevel, SetLogLevel},
+    rotation::{self, Rotator},
+    static_fields::StaticFieldsCache,
+    theme,
+    timestamp::TimestampFormatter,
+    PreparedEvent, Shared,
 };
 
 pub(crate) struct Logger {
     ctx: Context<Config>,
     shared: Arc<Shared>,
     filtering_layer: FilteringLayer,
+    overrides: Overrides,
+    last_override_id: u64,
 
     buffer: LineBuffer,
+    /// Fields of the current event and its spans, reused between events.
+    fields: Fields,
+    static_fields: StaticFieldsCache,
+    /// Used to escape values of fields.
+    scratch: String,
+    timestamp: TimestampFormatter,
+    flush_interval: AdaptiveInterval,
+    flush_tick: Interval<FlushTick>,
+    rotator: Rotator,
 }
 
 /// Reload a log file, usually after rotation.
@@ -42,6 +66,18 @@ pub(crate) struct Logger {
 #[non_exhaustive]
 pub struct ReopenLogFile {}
 
+/// Writes buffered logs immediately.
+///
+/// Responds once written, so logs emitted before sending the request are
+/// guaranteed to be in the file (but not synced to disk).
+#[message(ret = ())]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct FlushLogs {}
+
+#[message]
+struct FlushTick;
+
 impl Logger {
     // TODO: rename it?
     #[allow(clippy::new_ret_no_self)]
@@ -53,27 +89,48 @@ impl Logger {
                 Duration::from_secs(5),
                 Duration::from_secs(30),
             )))
-            .stop_order(105)
+            .stop_order(110)
             .exec(move |ctx| Logger::new(ctx, shared.clone(), filtering_layer.clone()).main())
     }
 
-    fn new(ctx: Context<Config>, shared: Arc<Shared>, filtering_layer: FilteringLayer) -> Self {
+    fn new(mut ctx: Context<Config>, shared: Arc<Shared>, filtering_layer: FilteringLayer) -> Self {
         filtering_layer.configure(&ctx.config().targets);
-        let buffer = LineBuffer::with_capacity(1024, {
+        shared.backlog.configure(&ctx.config().channel);
+        let mut buffer = LineBuffer::with_capacity(1024, {
             let cfg = ctx.config();
-            cfg.max_line_size.0 as _
+            cfg.max_line_size.as_usize()
         });
+        buffer.configure(
+            ctx.config().max_line_size.as_usize(),
+            Framing::from(ctx.config().format.kind),
+        );
+        let timestamp = TimestampFormatter::new(&ctx.config().timestamp);
+        let flush = &ctx.config().flush;
+        let flush_interval = AdaptiveInterval::new(
+            *flush.min_interval,
+            *flush.max_interval,
+            flush.high_water.as_usize(),
+        );
 
         Self {
-            ctx,
             shared,
             filtering_layer,
+            overrides: Overrides::default(),
+            last_override_id: 0,
             buffer,
+            fields: Fields::default(),
+            static_fields: StaticFieldsCache::default(),
+            scratch: String::new(),
+            timestamp,
+            flush_interval,
+            flush_tick: ctx.attach(Interval::new(FlushTick)),
+            rotator: Rotator::new(),
+            ctx,
         }
     }
 
     async fn main(mut self) {
-        let mut file = open_file(self.ctx.config()).await;
+        let mut file = self.open_file().await;
         let mut use_colors = can_use_colors(self.ctx.config());
 
         self.ctx.attach(Signal::new(
@@ -81,37 +138,69 @@ impl Logger {
             ReopenLogFile::default(),
         ));
 
+        self.flush_tick.start(self.flush_interval.current());
+
         // Note that we don't use `elfo::stream::Stream` here intentionally
         // to avoid cyclic dependences (`Context::recv()` logs all messages).
         loop {
             tokio::select! {
                 event = self.shared.channel.receive() => {
                     let event = ward!(event, break);
-                    self.buffer.clear();
-
                     self.format_event(use_colors, event);
+                    increment_counter!("elfo_written_events_total");
 
-                    if let Some(file) = file.as_mut() {
-                        // TODO: what about performance here?
-                        file.write_all(self.buffer.as_str().as_bytes()).await.expect("cannot write to the config file");
-                    } else {
-                        print!("{}", self.buffer.as_str());
+                    if self.flush_interval.is_high_water(self.buffer.as_str().len()) {
+                        self.flush(&mut file, FlushReason::HighWater).await;
                     }
-
-                    increment_counter!("elfo_written_events_total");
                 },
                 envelope = self.ctx.recv() => {
                     let envelope = ward!(envelope, break);
                     msg!(match envelope {
+                        FlushTick => self.flush(&mut file, FlushReason::Timer).await,
+                        (FlushLogs, token) => {
+                            self.drain_channel(use_colors);
+                            self.flush(&mut file, FlushReason::Explicit).await;
+                            self.ctx.respond(token, ());
+                        },
                         ReopenLogFile => {
-                            file = open_file(self.ctx.config()).await;
+                            self.flush(&mut file, FlushReason::Explicit).await;
+                            file = self.open_file().await;
                             use_colors = can_use_colors(self.ctx.config());
                         },
                         ConfigUpdated => {
-                            file = open_file(self.ctx.config()).await;
+                            self.flush(&mut file, FlushReason::Explicit).await;
+                            file = self.open_file().await;
                             use_colors = can_use_colors(self.ctx.config());
                             self.filtering_layer.configure(&self.ctx.config().targets);
-                            self.buffer.configure(self.ctx.config().max_line_size.0 as _);
+                            self.shared.backlog.configure(&self.ctx.config().channel);
+                            self.buffer.configure(
+                                self.ctx.config().max_line_size.as_usize(),
+                                Framing::from(self.ctx.config().format.kind),
+                            );
+                            self.timestamp = TimestampFormatter::new(&self.ctx.config().timestamp);
+
+                            let flush = &self.ctx.config().flush;
+                            self.flush_interval.configure(
+                                *flush.min_interval,
+                                *flush.max_interval,
+                                flush.high_water.as_usize(),
+                            );
+
+                            if self.overrides.iter().any(|o| !o.sticky) {
+                                info!("non-sticky log level overrides are cleared by the config update");
+                                self.overrides.clear_non_sticky();
+                                self.publish_overrides();
+                            }
+                        },
+                        (SetLogLevel { target, level, duration, sticky }, token) => {
+                            let prev = self.set_override(LogLevelOverride { target, level, duration, sticky });
+                            self.ctx.respond(token, prev);
+                        },
+                        RevertLogLevel { id } => {
+                            if let Some(reverted) = self.overrides.remove(id) {
+                                info!(%reverted, "log level override is expired");
+                                self.publish_overrides();
+                            }
                         },
                         Terminate => {
                             // Close the channel and wait for the rest of the events.
@@ -122,15 +211,117 @@ impl Logger {
             }
         }
 
+        self.flush(&mut file, FlushReason::Shutdown).await;
+
         if let Some(mut file) = file {
             file.flush().await.expect("cannot flush the log file");
             file.sync_all().await.expect("cannot sync the log file");
         }
     }
 
+    /// Writes buffered lines and schedules the next flush.
+    async fn flush(&mut self, file: &mut Option<File>, reason: FlushReason) {
+        let buffered = self.buffer.as_str().len();
+
+        if buffered > 0 {
+            if file.is_some() && self.rotator.is_due(&self.ctx.config().rotation, buffered) {
+                self.rotate_file(file).await;
+            }
+
+            if let Some(file) = file.as_mut() {
+                file.write_all(self.buffer.as_str().as_bytes())
+                    .await
+                    .expect("cannot write to the log file");
+                self.rotator.on_write(buffered);
+            } else {
+                print!("{}", self.buffer.as_str());
+            }
+
+            self.buffer.clear();
+            increment_counter!("elfo_flushes_total", "reason" => reason.as_str());
+        }
+
+        let interval = self.flush_interval.on_flush(buffered);
+        self.flush_tick.start(interval);
+        gauge!("elfo_flush_interval_seconds", interval.as_secs_f64());
+    }
+
+    async fn open_file(&mut self) -> Option<File> {
+        let file = open_file(self.ctx.config()).await?;
+        let size = file.metadata().await.map_or(0, |metadata| metadata.len());
+        self.rotator.on_open(size);
+        Some(file)
+    }
+
+    /// Renames the log file and opens a new one, see `config::Rotation`.
+    async fn rotate_file(&mut self, file: &mut Option<File>) {
+        if let Some(file) = file.as_mut() {
+            file.flush().await.expect("cannot flush the log file");
+        }
+
+        let config = self.ctx.config();
+        let path = ward!(config.path.as_ref());
+
+        match rotation::rotate(path, config.rotation.max_files).await {
+            Ok(()) => {
+                *file = self.open_file().await;
+                increment_counter!("elfo_log_rotations_total");
+            }
+            Err(err) => {
+                // Postpone the next attempt, the file is written as is.
+                self.rotator.reset();
+                error!(error = %err, "cannot rotate the log file");
+            }
+        }
+    }
+
+    /// Formats all received events without waiting for new ones.
+    fn drain_channel(&mut self, use_colors: bool) {
+        while let Ok(event) = self.shared.channel.try_receive() {
+            self.format_event(use_colors, event);
+            increment_counter!("elfo_written_events_total");
+        }
+    }
+
+    fn set_override(&mut self, new: LogLevelOverride) -> Option<LogLevelOverride> {
+        self.last_override_id += 1;
+        let id = self.last_override_id;
+
+        if let Some(duration) = new.duration {
+            self.ctx.attach(Delay::new(duration, RevertLogLevel { id }));
+        }
+
+        // Logged before applying, so it's visible even if logs are turned off.
+        // The same for expiration and clearing.
+        info!(%new, "log level is overridden");
+        let prev = self.overrides.set(id, new);
+        self.publish_overrides();
+        prev
+    }
+
+    fn publish_overrides(&self) {
+        self.filtering_layer.set_overrides(self.overrides.clone());
+
+        let status = if self.overrides.is_empty() {
+            ActorStatus::NORMAL
+        } else {
+            let list = self.overrides.iter().map(|o| o.to_string());
+            let list = list.collect::<Vec<_>>().join(", ");
+            ActorStatus::NORMAL.with_details(format_args!("log level overrides: {list}"))
+        };
+
+        self.ctx.set_status(status);
+    }
+
     fn format_event(&mut self, use_colors: bool, event: PreparedEvent) {
+        self.collect_fields(&event);
+
         // boolean operator || is short-circuit
-        let successful = if use_colors {
+        let successful = if self.ctx.config().format.kind == FormatKind::Json {
+            // JSON lines are written only by `TruncateOnUnfit`, which closes
+            // the object properly, see `LineBuffer::probe_size_limit()`.
+            self.do_format_json_event(&event)
+        } else if use_colors {
             self.do_format_event::<theme::ColoredTheme, FailOnUnfit>(&event)
                 || self.do_format_event::<theme::ColoredTheme, TruncateOnUnfit>(&event)
         } else {
@@ -139,69 +330,181 @@ impl Logger {
         };
 
         if successful {
-            self.shared.pool.clear(event.payload_id);
+            self.shared.pool.clear(event.message_id);
+            self.shared.fields.clear(event.fields_id);
+            if let Some(meta_id) = event.meta_id {
+                self.shared.pool.clear(meta_id);
+            }
+            self.shared.backlog.release();
         } else {
             unreachable!("truncation must succeed")
         }
     }
 
+    /// Collects fields of the event, its ancestors' spans and built-in ones,
+    /// then orders them according to the config.
+    fn collect_fields(&mut self, event: &PreparedEvent) {
+        let config = self.ctx.config();
+        sharded_slab::Clear::clear(&mut self.fields);
+
+        if let Some(fields) = self.shared.fields.get(event.fields_id) {
+            self.fields.extend(&fields);
+        }
+
+        // Add ancestors' fields.
+        let mut span_id = event.span_id.clone();
+        while let Some(data) = span_id
+            .as_ref()
+            .and_then(|span_id| self.shared.spans.get(span_id))
+        {
+            span_id.clone_from(&data.parent_id);
+
+            if let Some(fields) = self.shared.fields.get(data.fields_id) {
+                self.fields.extend(&fields);
+            }
+        }
+
+        if config.format.with_sequence_no {
+            if let Some(sequence_no) = event.sequence_no {
+                self.fields.push_u64("seq", u64::from(sequence_no));
+            }
+        }
+
+        if config.format.with_location {
+            if let Some((file, line)) = extract_location(event.metadata) {
+                let file = reduce_location(file);
+                self.fields
+                    .push_raw("_location", format_args!("{file}:{line}"));
+            }
+        }
+
+        if config.format.with_module {
+            if let Some(module) = event.metadata.module_path() {
+                self.fields.push_raw("_module", format_args!("{module}"));
+            }
+        }
+
+        self.fields.sort(&config.format);
+
+        for (name, _) in event.static_fields.iter().flat_map(|f| f.iter()) {
+            if self.fields.contains(name) {
+                debug!("static field `{name}` is overridden by the event's field");
+            }
+        }
+    }
+
     fn do_format_event<T: theme::Theme, F: LineFactory>(&mut self, event: &PreparedEvent) -> bool {
         let config = self.ctx.config();
         let mut line = F::create_line(&mut self.buffer);
 
-        let payload = self
+        let message = self
             .shared
             .pool
-            .get(event.payload_id)
+            .get(event.message_id)
             .expect("unknown string");
 
-        // <timestamp> <level> [<trace_id>] <object> - <message>\t<fields>
+        // <timestamp> <level> [<trace_id>] <extra meta> <object> - <message>\t<fields>
 
-        T::Timestamp::fmt(line.meta_mut(), &event.timestamp);
+        self.timestamp.write(&mut line.meta_mut(), event.timestamp);
         line.meta_mut().push(' ');
-        T::Level::fmt(line.meta_mut(), event.metadata.level());
+        T::Level::fmt(&mut line.meta_mut(), event.metadata.level());
         line.meta_mut().push_str(" [");
-        T::TraceId::fmt(line.meta_mut(), &event.trace_id);
+        T::TraceId::fmt(&mut line.meta_mut(), &event.trace_id);
         line.meta_mut().push_str("] ");
-        T::ActorMeta::fmt(line.payload_mut(), &event.object);
+        if let Some(meta) = event.meta_id.and_then(|id| self.shared.pool.get(id)) {
+            for (key, value) in enricher::entries(&meta) {
+                line.meta_mut().push_str(key);
+                line.meta_mut().push('=');
+                line.meta_mut().push_str(value);
+                line.meta_mut().push(' ');
+            }
+        }
+        let object = event.object.clone().map(|meta| ActorPrefix {
+            meta,
+            max_key_width: config.format.max_key_width,
+        });
+        T::ActorMeta::fmt(&mut line.payload_mut(), &object);
         line.payload_mut().push_str(" - ");
-        T::Payload::fmt(line.payload_mut(), &payload);
+        write_payload::<T>(
+            &mut line.payload_mut(),
+            &message,
+            config.multiline,
+            &event.trace_id,
+        );
+
+        write_fields::<T>(
+            &mut line,
+            &self.fields,
+            config.multiline,
+            &event.trace_id,
+            &mut self.scratch,
+        );
+
+        if let Some(static_fields) = &event.static_fields {
+            let fields = &self.fields;
+            let rendered = self.static_fields.get(static_fields);
+            rendered.write::<T>(&mut line, |name| fields.contains(name));
+        }
 
-        // Add ancestors' fields.
-        let mut span_id = event.span_id.clone();
+        line.try_commit()
+    }
 
-        {
-            let payload_buffer = line.payload_mut();
-            while let Some(data) = span_id
-                .as_ref()
-                .and_then(|span_id| self.shared.spans.get(span_id))
-            {
-                span_id.clone_from(&data.parent_id);
-
-                let payload = self
-                    .shared
-                    .pool
-                    .get(data.payload_id)
-                    .expect("unknown string");
-
-                T::Payload::fmt(payload_buffer, &payload);
+    fn do_format_json_event(&mut self, event: &PreparedEvent) -> bool {
+        let config = self.ctx.config();
+        let mut line = TruncateOnUnfit::create_line(&mut self.buffer);
+
+        let message = self
+            .shared
+            .pool
+            .get(event.message_id)
+            .expect("unknown string");
+
+        // {"timestamp":"..","ts_ns":..,"level":"..","trace_id":"..",<extra meta>,
+        //  "group":"..","key":"..","message":"..","fields":{..}}
+
+        self.scratch.clear();
+        self.timestamp.write(&mut self.scratch, event.timestamp);
+        line.meta_mut().push_str("{\"timestamp\":\"");
+        escape_json(&mut line.meta_mut(), &self.scratch);
+        let ts_ns = event.timestamp.to_unix_time_nanos();
+        let _ = write!(line.meta_mut(), "\",\"ts_ns\":{ts_ns},\"level\":\"");
+        line.meta_mut().push_str(event.metadata.level().as_str());
+        line.meta_mut().push('"');
+        if let Some(trace_id) = &event.trace_id {
+            let _ = write!(line.meta_mut(), ",\"trace_id\":\"{trace_id}\"");
+        }
+        if let Some(meta) = event.meta_id.and_then(|id| self.shared.pool.get(id)) {
+            for (key, value) in enricher::entries(&meta) {
+                line.meta_mut().push_str(",\"");
+                escape_json(&mut line.meta_mut(), key);
+                line.meta_mut().push_str("\":\"");
+                escape_json(&mut line.meta_mut(), value);
+                line.meta_mut().push('"');
             }
         }
-
-        if config.format.with_location {
-            if let Some(location) = extract_location(event.metadata) {
-                let fields_buffer = line.fields_mut();
-                fields_buffer.push('\t');
-                T::Location::fmt(line.fields_mut(), &location);
+        if let Some(object) = &event.object {
+            line.meta_mut().push_str(",\"group\":\"");
+            escape_json(&mut line.meta_mut(), &object.group);
+            line.meta_mut().push('"');
+
+            if !object.key.is_empty() {
+                let encoding = KeyEncoding::Prefix {
+                    max_width: config.format.max_key_width,
+                };
+                line.meta_mut().push_str(",\"key\":\"");
+                escape_json(&mut line.meta_mut(), &object.encoded_key(encoding));
+                line.meta_mut().push('"');
             }
         }
+        line.meta_mut().push_str(",\"message\":\"");
 
-        if config.format.with_module {
-            if let Some(module) = event.metadata.module_path() {
-                let fields_buffer = line.fields_mut();
-                fields_buffer.push('\t');
-                T::Module::fmt(fields_buffer, module);
-            }
+        escape_json(&mut line.payload_mut(), &message);
+        write_json_fields(&mut line, &self.fields);
+
+        if let Some(static_fields) = &event.static_fields {
+            let fields = &self.fields;
+            let rendered = self.static_fields.get(static_fields);
+            rendered.write_json(&mut line, |name| fields.contains(name));
         }
 
         line.try_commit()
@@ -0,0 +1,172 @@
+//! Limits the number of events waiting for the logger, see [`Channel`].
+//!
+//! [`Channel`]: crate::config::Channel
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tracing::Level;
+
+use crate::config::{Channel, Overflow};
+
+const BLOCKING_STEP: Duration = Duration::from_micros(50);
+
+pub(crate) struct Backlog {
+    len: AtomicUsize,
+    capacity: AtomicUsize,
+    /// In nanoseconds, zero if error-level events are dropped as others.
+    max_blocking: AtomicU64,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Reservation {
+    Reserved,
+    /// Reserved, but the caller has been blocked until the logger made room.
+    ReservedAfterBlocking,
+    Rejected,
+}
+
+impl Backlog {
+    pub(crate) fn new(config: &Channel) -> Self {
+        let this = Self {
+            len: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
+            max_blocking: AtomicU64::new(0),
+        };
+        this.configure(config);
+        this
+    }
+
+    pub(crate) fn configure(&self, config: &Channel) {
+        let max_blocking = match config.overflow {
+            Overflow::DropNewest => 0,
+            Overflow::BlockErrors => config.max_blocking.as_nanos().max(1) as u64,
+        };
+
+        self.capacity.store(config.capacity, Ordering::Relaxed);
+        self.max_blocking.store(max_blocking, Ordering::Relaxed);
+    }
+
+    /// Reserves a place for a new event, which must be released by the logger
+    /// once the event is received or right away if it's not sent.
+    pub(crate) fn reserve(&self, level: Level) -> Reservation {
+        if self.try_reserve() {
+            return Reservation::Reserved;
+        }
+
+        let max_blocking = self.max_blocking.load(Ordering::Relaxed);
+        if level != Level::ERROR || max_blocking == 0 {
+            return Reservation::Rejected;
+        }
+
+        // The logger is another actor, so we cannot wait for it asynchronously.
+        let deadline = Instant::now() + Duration::from_nanos(max_blocking);
+
+        loop {
+            thread::sleep(BLOCKING_STEP);
+
+            if self.try_reserve() {
+                return Reservation::ReservedAfterBlocking;
+            }
+
+            if Instant::now() >= deadline {
+                return Reservation::Rejected;
+            }
+        }
+    }
+
+    pub(crate) fn release(&self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn try_reserve(&self) -> bool {
+        // The capacity can be lowered by reconfiguration below the current
+        // length, so `fetch_add` isn't enough.
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        self.len
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |len| {
+                (len < capacity).then_some(len + 1)
+            })
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn channel(capacity: usize, overflow: Overflow) -> Channel {
+        Channel {
+            capacity,
+            overflow,
+            max_blocking: Duration::from_millis(50).into(),
+        }
+    }
+
+    #[test]
+    fn drop_newest() {
+        let backlog = Backlog::new(&channel(2, Overflow::DropNewest));
+
+        for level in [Level::INFO, Level::ERROR] {
+            assert_eq!(backlog.reserve(level), Reservation::Reserved);
+        }
+
+        for level in [Level::INFO, Level::ERROR] {
+            assert_eq!(backlog.reserve(level), Reservation::Rejected);
+        }
+
+        backlog.release();
+        assert_eq!(backlog.reserve(Level::INFO), Reservation::Reserved);
+        assert_eq!(backlog.reserve(Level::INFO), Reservation::Rejected);
+    }
+
+    #[test]
+    fn block_errors() {
+        let backlog = Arc::new(Backlog::new(&channel(1, Overflow::BlockErrors)));
+        assert_eq!(backlog.reserve(Level::INFO), Reservation::Reserved);
+
+        // Only errors are blocked.
+        assert_eq!(backlog.reserve(Level::WARN), Reservation::Rejected);
+
+        // Blocked until timeout.
+        let start = Instant::now();
+        assert_eq!(backlog.reserve(Level::ERROR), Reservation::Rejected);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Blocked until released.
+        let releaser = thread::spawn({
+            let backlog = backlog.clone();
+            move || {
+                thread::sleep(Duration::from_millis(5));
+                backlog.release();
+            }
+        });
+
+        assert_eq!(
+            backlog.reserve(Level::ERROR),
+            Reservation::ReservedAfterBlocking
+        );
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn reconfiguration() {
+        let backlog = Backlog::new(&channel(3, Overflow::DropNewest));
+
+        for _ in 0..3 {
+            assert_eq!(backlog.reserve(Level::INFO), Reservation::Reserved);
+        }
+
+        backlog.configure(&channel(1, Overflow::DropNewest));
+        backlog.release();
+        backlog.release();
+        assert_eq!(backlog.reserve(Level::INFO), Reservation::Rejected);
+        backlog.release();
+        assert_eq!(backlog.reserve(Level::INFO), Reservation::Reserved);
+    }
+}
@@ -10,7 +10,9 @@ use fxhash::FxHashMap;
 use serde::{Deserialize, Deserializer};
 use tracing::metadata::LevelFilter;
 
-use bytesize::ByteSize;
+use elfo_core::config::{ByteSize, Duration};
+
+use crate::timestamp;
 
 /// Logger configuration.
 ///
@@ -24,33 +26,111 @@ pub struct Config {
     pub sink: Sink,
     /// Path to the log file, applicable only for `Sink::File`.
     pub path: Option<PathBuf>,
-    /// Log format.
+    /// Rotation of the log file, applicable only for `Sink::File`.
+    /// By default the file isn't rotated.
     #[serde(default)]
+    pub rotation: Rotation,
+    /// Log format.
+    ///
+    /// `format = "json"` is a shorthand for `format.kind = "json"`.
+    #[serde(default, deserialize_with = "deserialize_format")]
     pub format: Format,
+    /// Flushing of buffered logs.
+    #[serde(default)]
+    pub flush: Flush,
+    /// Rendering of timestamps.
+    #[serde(default)]
+    pub timestamp: Timestamp,
+    /// The channel of events between logging code and the logger.
+    #[serde(default)]
+    pub channel: Channel,
 
     /// Size limit for each written log-line, in bytes.
     /// If size exceeds the limit, it will be truncated in the following order:
     ///
-    /// 1. Message with custom fields
-    /// 2. Meta-info (level, timestamp, ...)
-    /// 3. Meta-fields (location, module)
+    /// 1. Message
+    /// 2. Fields, whole trailing ones are dropped and counted by the
+    ///    `fields_dropped=<n>` marker
+    /// 3. Meta-info (level, timestamp, ...)
+    ///
+    /// JSON lines are truncated in the same order and marked by
+    /// `"truncated":true`, dropped fields are counted by `"fields_dropped"`
+    /// inside `"fields"`. Lines remain valid JSON unless the limit is too
+    /// small to fit the meta-info.
     #[serde(default = "default_max_line_size")]
     pub max_line_size: ByteSize,
+    /// Handling of newlines embedded into messages and fields.
+    #[serde(default)]
+    pub multiline: Multiline,
 
     /// Override log levels for specific targets.
     /// Useful to suppress noisy logs from dependencies.
+    ///
+    /// A target is either a group name or a `tracing` target (matched by
+    /// prefix). The level of the group replaces its `system.logging.max_level`
+    /// and has precedence over `tracing` targets, like [`SetLogLevel`] does.
+    /// Changes are applied on config updates.
+    ///
+    /// # Example
+    /// ```toml
+    /// [system.loggers]
+    /// targets.hyper = "Warn"
+    /// targets."my-group" = "Debug"
+    /// # The same, but in the full form.
+    /// targets."my-group" = { max_level = "Debug" }
+    /// ```
+    ///
+    /// [`SetLogLevel`]: crate::SetLogLevel
     #[serde(default)]
     pub targets: FxHashMap<String, LoggingTargetConfig>,
 }
 
 /// Configuration for a specific logging target.
-#[derive(Debug, Deserialize)]
+///
+/// A level can be used as a shorthand for the table with `max_level` only.
+#[derive(Debug)]
 pub struct LoggingTargetConfig {
     /// Maximum log level for the target.
-    #[serde(deserialize_with = "deserialize_level_filter")]
     pub max_level: LevelFilter,
 }
 
+impl<'de> Deserialize<'de> for LoggingTargetConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, value::MapAccessDeserializer, IntoDeserializer, MapAccess, Visitor};
+
+        #[derive(Deserialize)]
+        struct Full {
+            #[serde(deserialize_with = "deserialize_level_filter")]
+            max_level: LevelFilter,
+        }
+
+        struct TargetVisitor;
+
+        impl<'de> Visitor<'de> for TargetVisitor {
+            type Value = LoggingTargetConfig;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a log level or a table")
+            }
+
+            fn visit_str<E: de::Error>(self, level: &str) -> Result<Self::Value, E> {
+                Ok(LoggingTargetConfig {
+                    max_level: deserialize_level_filter(level.into_deserializer())?,
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let full = Full::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(LoggingTargetConfig {
+                    max_level: full.max_level,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(TargetVisitor)
+    }
+}
+
 /// Sink for the log output.
 /// By default logs are written to stdout.
 #[derive(Debug, Default, PartialEq, Deserialize)]
@@ -63,20 +143,430 @@ pub enum Sink {
     // TODO: stdout + stderr
 }
 
+/// Rotation of the log file.
+///
+/// Before writing buffered lines, the file is rotated if any limit is
+/// reached: it's renamed to `<path>.1`, older files are shifted (`<path>.1` to
+/// `<path>.2` and so on) and ones beyond `max_files` are removed. Then buffered
+/// lines are written to the new file, so they're never lost or split between
+/// files. [`ReopenLogFile`] is still supported for external rotation.
+///
+/// # Example
+/// ```toml
+/// [system.loggers]
+/// sink = "File"
+/// path = "app.log"
+/// rotation = { max_size = "100MiB", max_age = "1d", max_files = 7 }
+/// ```
+///
+/// [`ReopenLogFile`]: crate::ReopenLogFile
+#[derive(Debug, Deserialize)]
+pub struct Rotation {
+    /// Rotate the file if written lines would exceed the size.
+    /// The file can exceed it only if one flush of lines does.
+    ///
+    /// Not limited by default.
+    pub max_size: Option<ByteSize>,
+    /// Rotate the file once it's written longer than the age.
+    /// The age is counted since the logger started writing the file.
+    ///
+    /// Not limited by default.
+    pub max_age: Option<Duration>,
+    /// The number of rotated files to keep.
+    ///
+    /// `5` by default.
+    #[serde(default = "default_rotation_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            max_age: None,
+            max_files: default_rotation_max_files(),
+        }
+    }
+}
+
 /// Log format.
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct Format {
+    /// The layout of lines.
+    ///
+    /// `"text"` by default.
+    #[serde(default)]
+    pub kind: FormatKind,
     /// Include location info in the log output.
     #[serde(default)]
     pub with_location: bool,
     /// Include module info in the log output.
     #[serde(default)]
     pub with_module: bool,
+    /// Include the sequence number of the handled message as `seq=<n>`.
+    /// It's the same number as `s` in the incoming dump of the message.
+    ///
+    /// `true` by default.
+    #[serde(default = "default_with_sequence_no")]
+    pub with_sequence_no: bool,
+    /// The maximum width of actor keys in log prefixes, in chars.
+    /// Longer keys are truncated with an ellipsis, control chars are escaped.
+    ///
+    /// `64` by default.
+    #[serde(default = "default_max_key_width")]
+    pub max_key_width: usize,
+    /// The order of fields, including `seq`, `_location` and `_module`.
+    /// Fields listed in `priority_fields` always go first.
+    ///
+    /// `"registration"` by default.
+    #[serde(default)]
+    pub fields_order: FieldsOrder,
+    /// Fields written first in the listed order, so they survive truncation
+    /// by `max_line_size`, which drops trailing fields.
+    ///
+    /// Empty by default.
+    #[serde(default)]
+    pub priority_fields: Vec<String>,
     // TODO: colors
 }
 
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            kind: FormatKind::default(),
+            with_location: false,
+            with_module: false,
+            with_sequence_no: default_with_sequence_no(),
+            max_key_width: default_max_key_width(),
+            fields_order: FieldsOrder::default(),
+            priority_fields: Vec::new(),
+        }
+    }
+}
+
+/// The layout of lines.
+///
+/// # Example
+/// ```toml
+/// [system.loggers]
+/// format = "json"
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatKind {
+    /// Human-readable lines:
+    /// `<timestamp> <level> [<trace_id>] <group>.<key> - <message>\t<fields>`.
+    #[default]
+    Text,
+    /// One JSON object per line, e.g.
+    /// ```json
+    /// {"timestamp":"2023-11-14 22:13:20.123456789","level":"INFO","trace_id":"7f9a...","group":"users","key":"42","message":"joined","fields":{"seq":3}}
+    /// ```
+    ///
+    /// `trace_id`, `meta`, `group`, `key` and `fields` are omitted if absent.
+    /// Values of fields keep their types, other values are rendered by `Debug`
+    /// as strings. `multiline` and colors aren't applicable.
+    Json,
+}
+
+/// The order of fields in records.
+///
+/// Values are rendered according to their types: strings are quoted and
+/// escaped (`key="12"`), numbers and booleans aren't (`key=12`), other values
+/// are rendered by `Debug`.
+///
+/// # Example
+/// ```toml
+/// [system.loggers]
+/// format.fields_order = "alphabetical"
+/// format.priority_fields = ["request_id", "latency_ms"]
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldsOrder {
+    /// The order of recording: fields of the event, then fields of spans from
+    /// the innermost one, then `seq`, `_location` and `_module`.
+    #[default]
+    Registration,
+    /// Sorted by names.
+    Alphabetical,
+}
+
+/// Handling of newlines embedded into messages and fields, e.g. backtraces.
+///
+/// Every policy keeps each record starting on its own line with the timestamp,
+/// so the log remains machine-parseable. `max_line_size` limits the whole
+/// record after expansion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Multiline {
+    /// Replace newlines with `\n`, the default.
+    #[default]
+    Escape,
+    /// Write continuation lines prefixed with `  | [<trace_id>] `, so they can
+    /// be associated with the record.
+    Indent,
+    /// Write only the first line of every multi-line message or field value
+    /// followed by `…`.
+    TruncateFirstLine,
+}
+
+/// Rendering of timestamps.
+///
+/// By default, timestamps are rendered in UTC with nanoseconds, e.g.
+/// `2023-11-14 22:13:20.123456789`.
+///
+/// # Examples
+/// ```toml
+/// [system.loggers]
+/// timestamp = { format = "iso8601", timezone = "utc" }
+/// ```
+///
+/// ```toml
+/// [system.loggers]
+/// timestamp = { format = "custom", custom = "%H:%M:%S%.3f", timezone = "local" }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawTimestamp")]
+pub struct Timestamp {
+    /// `"custom"` by default.
+    pub format: TimestampFormat,
+    /// A `strftime`-like format, applicable only for `"custom"`.
+    ///
+    /// Supported specifiers: `%Y`, `%y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%F`
+    /// (`%Y-%m-%d`), `%T` (`%H:%M:%S`), `%f` (nanoseconds), `%3f`, `%6f`,
+    /// `%9f`, `%.3f`, `%.6f`, `%.9f`, `%s` (unix seconds), `%z` (`+hhmm`),
+    /// `%:z` (`+hh:mm`) and `%%`.
+    ///
+    /// `"%Y-%m-%d %H:%M:%S%.9f"` by default.
+    pub custom: String,
+    /// `"utc"` by default.
+    pub timezone: Timezone,
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self {
+            format: TimestampFormat::default(),
+            custom: default_timestamp_custom(),
+            timezone: Timezone::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawTimestamp {
+    #[serde(default)]
+    format: TimestampFormat,
+    #[serde(default = "default_timestamp_custom")]
+    custom: String,
+    #[serde(default)]
+    timezone: Timezone,
+}
+
+impl TryFrom<RawTimestamp> for Timestamp {
+    type Error = String;
+
+    fn try_from(raw: RawTimestamp) -> Result<Self, Self::Error> {
+        if raw.format == TimestampFormat::Custom {
+            timestamp::compile(&raw.custom)
+                .map_err(|err| format!("invalid custom timestamp format: {err}"))?;
+        }
+
+        Ok(Self {
+            format: raw.format,
+            custom: raw.custom,
+            timezone: raw.timezone,
+        })
+    }
+}
+
+/// A format of timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// ISO 8601 with microseconds, e.g. `2023-11-14T22:13:20.123456Z`
+    /// or `2023-11-14T22:13:20.123456+03:00` for the local timezone.
+    Iso8601,
+    /// Nanoseconds since the unix epoch, e.g. `1700000000123456789`.
+    UnixNanos,
+    /// Specified by `custom`.
+    #[default]
+    Custom,
+}
+
+/// A timezone of timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timezone {
+    /// UTC.
+    #[default]
+    Utc,
+    /// The local timezone of the system.
+    /// The offset is updated hourly to catch DST transitions.
+    Local,
+}
+
+/// The channel of events between logging code and the logger.
+///
+/// Logging code only captures events: fields are rendered into a string, the
+/// rest (metadata, the actor's meta, the trace id, the timestamp) is captured
+/// as is. Events are formatted into lines and written by the logger.
+///
+/// If the channel is full, new events are dropped and counted by
+/// `elfo_lost_events_total`.
+///
+/// # Example
+/// ```toml
+/// [system.loggers]
+/// channel = { capacity = 65536, overflow = "block_errors", max_blocking = "5ms" }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Channel {
+    /// The maximum number of events waiting for the logger.
+    ///
+    /// `131072` by default.
+    #[serde(default = "default_channel_capacity")]
+    pub capacity: usize,
+    /// What to do with new events if the channel is full.
+    ///
+    /// `"drop_newest"` by default.
+    #[serde(default)]
+    pub overflow: Overflow,
+    /// The maximum time to block logging code on one event, applicable only
+    /// for `"block_errors"`.
+    ///
+    /// `10ms` by default.
+    #[serde(default = "default_channel_max_blocking")]
+    pub max_blocking: Duration,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            capacity: default_channel_capacity(),
+            overflow: Overflow::default(),
+            max_blocking: default_channel_max_blocking(),
+        }
+    }
+}
+
+/// What to do with new events if the channel is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// Drop new events, the default.
+    #[default]
+    DropNewest,
+    /// Block the thread emitting an error-level event up to `max_blocking`
+    /// until the logger makes room, other events are dropped.
+    ///
+    /// The logger cannot make room while it shares the thread with the
+    /// blocked code (e.g. in the current-thread runtime), so the event is
+    /// dropped after `max_blocking` anyway in this case.
+    BlockErrors,
+}
+
+/// Flushing of buffered logs.
+///
+/// Lines are buffered and written once the interval is over. The interval
+/// starts at `min_interval` and doubles after every flush with nothing to
+/// write up to `max_interval`. Once something is written, it's reset to
+/// `min_interval`. The buffer is written immediately if its size reaches
+/// `high_water`, on [`FlushLogs`] and on termination.
+///
+/// [`FlushLogs`]: crate::FlushLogs
+#[derive(Debug, Deserialize)]
+pub struct Flush {
+    /// `10ms` by default.
+    #[serde(default = "default_flush_min_interval")]
+    pub min_interval: Duration,
+    /// `500ms` by default.
+    #[serde(default = "default_flush_max_interval")]
+    pub max_interval: Duration,
+    /// `64KiB` by default.
+    #[serde(default = "default_flush_high_water")]
+    pub high_water: ByteSize,
+}
+
+impl Default for Flush {
+    fn default() -> Self {
+        Self {
+            min_interval: default_flush_min_interval(),
+            max_interval: default_flush_max_interval(),
+            high_water: default_flush_high_water(),
+        }
+    }
+}
+
+fn default_rotation_max_files() -> usize {
+    5
+}
+
+fn default_flush_min_interval() -> Duration {
+    Duration::from_millis(10)
+}
+
+fn default_flush_max_interval() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_flush_high_water() -> ByteSize {
+    ByteSize::new(64 * 1024)
+}
+
+fn default_channel_capacity() -> usize {
+    128 * 1024
+}
+
+fn default_channel_max_blocking() -> Duration {
+    Duration::from_millis(10)
+}
+
+fn default_timestamp_custom() -> String {
+    "%Y-%m-%d %H:%M:%S%.9f".into()
+}
+
+fn default_with_sequence_no() -> bool {
+    true
+}
+
+fn default_max_key_width() -> usize {
+    64
+}
+
 fn default_max_line_size() -> ByteSize {
-    ByteSize(u64::MAX)
+    ByteSize::new(u64::MAX)
+}
+
+fn deserialize_format<'de, D>(deserializer: D) -> Result<Format, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::{self, value::MapAccessDeserializer, IntoDeserializer, MapAccess, Visitor};
+
+    struct FormatVisitor;
+
+    impl<'de> Visitor<'de> for FormatVisitor {
+        type Value = Format;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("a format kind or a table")
+        }
+
+        fn visit_str<E: de::Error>(self, kind: &str) -> Result<Format, E> {
+            Ok(Format {
+                kind: FormatKind::deserialize(kind.into_deserializer())?,
+                ..Format::default()
+            })
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Format, A::Error> {
+            Format::deserialize(MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(FormatVisitor)
 }
 
 // TODO: deduplicate with core
@@ -107,3 +597,36 @@ where
         Off => LevelFilter::OFF,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn shorthands() {
+        let config = Config::deserialize(json!({
+            "format": "json",
+            "targets": {
+                "hyper": "Warn",
+                "my-group": { "max_level": "Debug" },
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(config.format.kind, FormatKind::Json);
+        assert_eq!(config.targets["hyper"].max_level, LevelFilter::WARN);
+        assert_eq!(config.targets["my-group"].max_level, LevelFilter::DEBUG);
+
+        let config = Config::deserialize(json!({ "format": { "with_module": true } })).unwrap();
+        assert_eq!(config.format.kind, FormatKind::Text);
+        assert!(config.format.with_module);
+
+        let err = Config::deserialize(json!({ "targets": { "hyper": "Verbose" } })).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown variant `Verbose`"),
+            "{err}"
+        );
+    }
+}
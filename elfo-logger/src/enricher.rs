@@ -0,0 +1,123 @@
+use std::fmt::{self, Write};
+
+use arc_swap::ArcSwapOption;
+use metrics::increment_counter;
+use once_cell::sync::Lazy;
+
+use elfo_core::scope::Scope;
+
+use crate::{Shared, StringId};
+
+type EnrichFn = dyn Fn(&Scope, &mut Meta<'_>) -> fmt::Result + Send + Sync;
+
+struct Enricher(Box<EnrichFn>);
+
+static ENRICHER: Lazy<ArcSwapOption<Enricher>> = Lazy::new(ArcSwapOption::empty);
+
+/// Extra meta of a log record, filled by the function set by
+/// [`set_meta_enricher()`].
+///
+/// Entries are written as `key=value` pairs after the trace id:
+/// ```text
+/// <timestamp> <level> [<trace_id>] <key>=<value> <object> - <message>
+/// ```
+/// and as extra top-level string keys in the JSON format.
+pub struct Meta<'a> {
+    out: &'a mut String,
+}
+
+impl Meta<'_> {
+    /// Adds an entry. Keys must not clash with the standard ones
+    /// (`timestamp`, `level`, `message` and so on) to keep JSON lines valid.
+    pub fn add(&mut self, key: &str, value: impl fmt::Display) -> fmt::Result {
+        // Entries are stored as `key\0value\0`, so `\0` is stripped.
+        NoNul(self.out).write_str(key)?;
+        self.out.push('\0');
+        write!(NoNul(self.out), "{value}")?;
+        self.out.push('\0');
+        Ok(())
+    }
+}
+
+struct NoNul<'a>(&'a mut String);
+
+impl fmt::Write for NoNul<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend(s.chars().filter(|&c| c != '\0'));
+        Ok(())
+    }
+}
+
+/// Iterates over `(key, value)` entries written by [`Meta::add()`].
+pub(crate) fn entries(meta: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut parts = meta.split_terminator('\0');
+    std::iter::from_fn(move || Some((parts.next()?, parts.next()?)))
+}
+
+/// Sets the function adding extra meta to every log record emitted inside
+/// the actor system, e.g. external request ids stored in the scope's baggage.
+/// See [`Meta`] for how entries are rendered.
+///
+/// The function is called in the emitting thread, so it must be cheap.
+/// If it fails, the partial output is discarded and
+/// `elfo_meta_enricher_errors_total` is incremented.
+///
+/// Can be replaced at any time, affecting only subsequent records.
+///
+/// # Example
+/// ```
+/// elfo_logger::set_meta_enricher(|scope, meta| match scope.baggage("request_id") {
+///     Some(request_id) => meta.add("request_id", request_id),
+///     None => Ok(()),
+/// });
+/// ```
+pub fn set_meta_enricher(f: impl Fn(&Scope, &mut Meta<'_>) -> fmt::Result + Send + Sync + 'static) {
+    ENRICHER.store(Some(Enricher(Box::new(f)).into()));
+}
+
+/// Removes the function set by [`set_meta_enricher()`].
+pub fn unset_meta_enricher() {
+    ENRICHER.store(None);
+}
+
+/// Returns the id of a pooled string with the extra meta, if any.
+pub(crate) fn enrich(shared: &Shared, scope: &Scope) -> Option<StringId> {
+    let enricher = ENRICHER.load();
+    let enricher = enricher.as_ref()?;
+
+    let mut is_empty = true;
+    let meta_id = shared.pool.create_with(|meta| {
+        if (enricher.0)(scope, &mut Meta { out: meta }).is_err() {
+            meta.clear();
+            increment_counter!("elfo_meta_enricher_errors_total");
+        }
+
+        is_empty = meta.is_empty();
+    })?;
+
+    if is_empty {
+        shared.pool.clear(meta_id);
+        return None;
+    }
+
+    Some(meta_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_entries() {
+        let mut out = String::new();
+        let mut meta = Meta { out: &mut out };
+        meta.add("request_id", "5b0e\0-gw").unwrap();
+        meta.add("attempt", 2).unwrap();
+        meta.add("empty", "").unwrap();
+
+        assert_eq!(
+            entries(&out).collect::<Vec<_>>(),
+            [("request_id", "5b0e-gw"), ("attempt", "2"), ("empty", "")]
+        );
+    }
+}
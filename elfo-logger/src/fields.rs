@@ -0,0 +1,461 @@
+use std::fmt::{self, Write as _};
+
+use sharded_slab::Clear;
+
+use elfo_core::tracing::TraceId;
+
+use crate::{
+    config::{FieldsOrder, Format, Multiline},
+    formatters::{Formatter, Output, Payload},
+    line_transaction::Line,
+    multiline::write_multiline,
+    theme::Theme,
+};
+
+/// Ends the fields part if trailing fields are dropped by truncation.
+pub(crate) const FIELDS_DROPPED_MARKER: &str = "\tfields_dropped=";
+
+// Fields
+
+/// Typed fields of an event or a span, pooled to be reused.
+///
+/// Values of strings and fallbacks are stored in the same buffer,
+/// so collecting fields doesn't allocate once the pool is warmed up.
+#[derive(Debug, Default)]
+pub(crate) struct Fields {
+    entries: Vec<Entry>,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    name: &'static str,
+    /// The number of `.source` suffixes, used for sources of errors.
+    sources: u8,
+    value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    /// Rendered quoted, e.g. `key="value"`.
+    Str(Span),
+    I64(i64),
+    U64(u64),
+    /// Rendered with the fractional part, e.g. `key=12.0`.
+    F64(f64),
+    Bool(bool),
+    /// Rendered as is, e.g. `Debug` fallbacks.
+    Raw(Span),
+}
+
+/// A range of `Fields::text`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Fields {
+    pub(crate) fn push_str(&mut self, name: &'static str, value: &str) {
+        let span = self.push_text(format_args!("{value}")).expect("infallible");
+        self.push(name, 0, Value::Str(span));
+    }
+
+    pub(crate) fn push_i64(&mut self, name: &'static str, value: i64) {
+        self.push(name, 0, Value::I64(value));
+    }
+
+    pub(crate) fn push_u64(&mut self, name: &'static str, value: u64) {
+        self.push(name, 0, Value::U64(value));
+    }
+
+    pub(crate) fn push_f64(&mut self, name: &'static str, value: f64) {
+        self.push(name, 0, Value::F64(value));
+    }
+
+    pub(crate) fn push_bool(&mut self, name: &'static str, value: bool) {
+        self.push(name, 0, Value::Bool(value));
+    }
+
+    /// Pushes a source of an error (`sources > 0`) or the error itself.
+    /// The field is skipped if the value cannot be formatted.
+    pub(crate) fn push_error(
+        &mut self,
+        name: &'static str,
+        sources: u8,
+        value: fmt::Arguments<'_>,
+    ) {
+        if let Ok(span) = self.push_text(value) {
+            self.push(name, sources, Value::Str(span));
+        }
+    }
+
+    /// Pushes a value rendered as is, e.g. by `Debug`.
+    /// The field is skipped if the value cannot be formatted.
+    pub(crate) fn push_raw(&mut self, name: &'static str, value: fmt::Arguments<'_>) {
+        if let Ok(span) = self.push_text(value) {
+            self.push(name, 0, Value::Raw(span));
+        }
+    }
+
+    /// Appends fields of another event or span.
+    pub(crate) fn extend(&mut self, other: &Fields) {
+        let offset = self.text.len();
+        self.text.push_str(&other.text);
+
+        self.entries.extend(other.entries.iter().map(|entry| {
+            let shift = |span: Span| Span {
+                start: span.start + offset,
+                end: span.end + offset,
+            };
+
+            let value = match entry.value {
+                Value::Str(span) => Value::Str(shift(span)),
+                Value::Raw(span) => Value::Raw(shift(span)),
+                value => value,
+            };
+
+            Entry { value, ..*entry }
+        }));
+    }
+
+    /// Orders fields according to the config: fields listed in
+    /// `priority_fields` go first, the rest are ordered by `fields_order`.
+    /// Sources of errors always follow errors.
+    pub(crate) fn sort(&mut self, format: &Format) {
+        let priority = &format.priority_fields;
+        let rank = |entry: &Entry| {
+            priority
+                .iter()
+                .position(|name| name == entry.name)
+                .unwrap_or(priority.len())
+        };
+
+        // Sorting is stable, so the registration order is kept among equals.
+        match format.fields_order {
+            FieldsOrder::Registration if priority.is_empty() => {}
+            FieldsOrder::Registration => self.entries.sort_by_key(rank),
+            FieldsOrder::Alphabetical => self.entries.sort_by(|a, b| {
+                rank(a)
+                    .cmp(&rank(b))
+                    .then_with(|| a.name.cmp(b.name))
+                    .then_with(|| a.sources.cmp(&b.sources))
+            }),
+        }
+    }
+
+    /// Checks whether there is a field with the provided name.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+
+    fn push(&mut self, name: &'static str, sources: u8, value: Value) {
+        self.entries.push(Entry {
+            name,
+            sources,
+            value,
+        });
+    }
+
+    fn push_text(&mut self, value: fmt::Arguments<'_>) -> Result<Span, fmt::Error> {
+        let start = self.text.len();
+
+        if let Err(err) = self.text.write_fmt(value) {
+            self.text.truncate(start);
+            return Err(err);
+        }
+
+        Ok(Span {
+            start,
+            end: self.text.len(),
+        })
+    }
+
+    fn text(&self, span: Span) -> &str {
+        &self.text[span.start..span.end]
+    }
+}
+
+impl Clear for Fields {
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.text.clear();
+    }
+}
+
+// Rendering
+
+/// Writes fields (`\t<key>=<value>...`) into the fields part in one pass.
+///
+/// Every field is ended separately, so truncation drops whole trailing fields
+/// instead of cutting a value in the middle. Newlines in values are handled
+/// according to the policy, like in the message.
+pub(crate) fn write_fields<T: Theme>(
+    line: &mut impl Line,
+    fields: &Fields,
+    policy: Multiline,
+    trace_id: &Option<TraceId>,
+    scratch: &mut String,
+) {
+    for entry in &fields.entries {
+        write_field::<T>(
+            &mut line.fields_mut(),
+            fields,
+            entry,
+            policy,
+            trace_id,
+            scratch,
+        );
+        line.end_field();
+    }
+}
+
+fn write_field<T: Theme>(
+    out: &mut impl Output,
+    fields: &Fields,
+    entry: &Entry,
+    policy: Multiline,
+    trace_id: &Option<TraceId>,
+    scratch: &mut String,
+) {
+    out.push('\t');
+    T::FieldKey::fmt(out, entry.name);
+    for _ in 0..entry.sources {
+        out.push_str(".source");
+    }
+    out.push('=');
+
+    let _ = match entry.value {
+        Value::I64(value) => write!(out, "{value}"),
+        Value::U64(value) => write!(out, "{value}"),
+        Value::F64(value) => write!(out, "{value:?}"),
+        Value::Bool(value) => write!(out, "{value}"),
+        Value::Str(span) => {
+            scratch.clear();
+            scratch.push('"');
+            escape(scratch, fields.text(span), true);
+            scratch.push('"');
+            write_multiline::<T, Payload>(out, scratch, policy, trace_id);
+            Ok(())
+        }
+        Value::Raw(span) => {
+            scratch.clear();
+            escape(scratch, fields.text(span), false);
+            write_multiline::<T, Payload>(out, scratch, policy, trace_id);
+            Ok(())
+        }
+    };
+}
+
+/// Writes fields (`,"<key>":<value>...`) into the fields part of the JSON
+/// line, see `Framing::Json`.
+///
+/// Numbers and booleans are written as is, other values as strings.
+/// Every field is ended separately, like in the text format.
+pub(crate) fn write_json_fields(line: &mut impl Line, fields: &Fields) {
+    for entry in &fields.entries {
+        write_json_field(&mut line.fields_mut(), fields, entry);
+        line.end_field();
+    }
+}
+
+fn write_json_field(out: &mut impl Output, fields: &Fields, entry: &Entry) {
+    out.push_str(",\"");
+    escape_json(out, entry.name);
+    for _ in 0..entry.sources {
+        out.push_str(".source");
+    }
+    out.push_str("\":");
+
+    let _ = match entry.value {
+        Value::I64(value) => write!(out, "{value}"),
+        Value::U64(value) => write!(out, "{value}"),
+        Value::F64(value) if value.is_finite() => write!(out, "{value:?}"),
+        // JSON has no `NaN` and infinities.
+        Value::F64(value) => write!(out, "\"{value}\""),
+        Value::Bool(value) => write!(out, "{value}"),
+        Value::Str(span) | Value::Raw(span) => {
+            out.push('"');
+            escape_json(out, fields.text(span));
+            out.push('"');
+            Ok(())
+        }
+    };
+}
+
+/// Escapes the value to be written inside a JSON string.
+pub(crate) fn escape_json(out: &mut impl Output, value: &str) {
+    let mut start = 0;
+
+    for (idx, c) in value.char_indices() {
+        let escaped = match c {
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\r' => Some("\\r"),
+            '\t' => Some("\\t"),
+            c if c.is_ascii_control() => None,
+            _ => continue,
+        };
+
+        out.push_str(&value[start..idx]);
+        match escaped {
+            Some(escaped) => out.push_str(escaped),
+            None => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+        }
+        start = idx + c.len_utf8();
+    }
+
+    out.push_str(&value[start..]);
+}
+
+/// Escapes tabs, which separate fields, and, if `is_quoted`, also quotes
+/// and backslashes. Newlines are left for the multiline policy.
+pub(crate) fn escape(out: &mut String, value: &str, is_quoted: bool) {
+    for c in value.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\r' if is_quoted => out.push_str("\\r"),
+            '"' if is_quoted => out.push_str("\\\""),
+            '\\' if is_quoted => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        line_buffer::LineBuffer,
+        line_transaction::{FailOnUnfit, LineFactory, TruncateOnUnfit},
+        theme::PlainTheme,
+    };
+
+    fn fields() -> Fields {
+        let mut fields = Fields::default();
+        fields.push_u64("latency_ms", 12);
+        fields.push_str("user", "alice");
+        fields.push_error("error", 0, format_args!("failed"));
+        fields.push_error("error", 1, format_args!("timeout"));
+        fields.push_bool("cached", false);
+        fields
+    }
+
+    fn render(fields: &Fields, max_line_size: usize) -> String {
+        fn try_render<F: LineFactory>(buffer: &mut LineBuffer, fields: &Fields) -> bool {
+            let mut line = F::create_line(buffer);
+            line.meta_mut().push_str("meta -");
+            let mut scratch = String::new();
+            write_fields::<PlainTheme>(&mut line, fields, Multiline::Escape, &None, &mut scratch);
+            line.try_commit()
+        }
+
+        let mut buffer = LineBuffer::with_capacity(1024, max_line_size);
+        assert!(
+            try_render::<FailOnUnfit>(&mut buffer, fields)
+                || try_render::<TruncateOnUnfit>(&mut buffer, fields)
+        );
+        buffer.as_str().trim_end_matches('\n').to_owned()
+    }
+
+    fn format(fields_order: FieldsOrder, priority_fields: &[&str]) -> Format {
+        Format {
+            fields_order,
+            priority_fields: priority_fields.iter().map(|s| s.to_string()).collect(),
+            ..Format::default()
+        }
+    }
+
+    #[test]
+    fn rendering_by_type() {
+        let mut fields = Fields::default();
+        fields.push_str("str", "12");
+        fields.push_i64("i64", -12);
+        fields.push_u64("u64", 12);
+        fields.push_f64("f64", 12.);
+        fields.push_bool("bool", true);
+        fields.push_raw("debug", format_args!("{:?}", Some(12)));
+        fields.push_str("escaped", "say \"hi\"\tC:\\");
+        fields.push_str("multiline", "a\nb");
+
+        assert_eq!(
+            render(&fields, usize::MAX),
+            "meta -\tstr=\"12\"\ti64=-12\tu64=12\tf64=12.0\tbool=true\tdebug=Some(12)\
+             \tescaped=\"say \\\"hi\\\"\\tC:\\\\\"\tmultiline=\"a\\nb\""
+        );
+    }
+
+    #[test]
+    fn ordering() {
+        let cases = [
+            (
+                format(FieldsOrder::Registration, &[]),
+                "\tlatency_ms=12\tuser=\"alice\"\terror=\"failed\"\terror.source=\"timeout\"\tcached=false",
+            ),
+            (
+                format(FieldsOrder::Alphabetical, &[]),
+                "\tcached=false\terror=\"failed\"\terror.source=\"timeout\"\tlatency_ms=12\tuser=\"alice\"",
+            ),
+            (
+                format(FieldsOrder::Registration, &["user", "cached"]),
+                "\tuser=\"alice\"\tcached=false\tlatency_ms=12\terror=\"failed\"\terror.source=\"timeout\"",
+            ),
+            (
+                format(FieldsOrder::Alphabetical, &["user"]),
+                "\tuser=\"alice\"\tcached=false\terror=\"failed\"\terror.source=\"timeout\"\tlatency_ms=12",
+            ),
+        ];
+
+        for (format, expected) in cases {
+            let mut fields = fields();
+            fields.sort(&format);
+            assert_eq!(render(&fields, usize::MAX), format!("meta -{expected}"));
+        }
+    }
+
+    #[test]
+    fn extending() {
+        let mut fields = Fields::default();
+        fields.push_str("span", "outer");
+        fields.extend(&self::fields());
+        fields.push_raw("_module", format_args!("app"));
+
+        assert_eq!(
+            render(&fields, usize::MAX),
+            "meta -\tspan=\"outer\"\tlatency_ms=12\tuser=\"alice\"\terror=\"failed\"\
+             \terror.source=\"timeout\"\tcached=false\t_module=app"
+        );
+    }
+
+    #[test]
+    fn truncation_drops_whole_fields() {
+        let fields = fields();
+        let full = render(&fields, usize::MAX);
+
+        let cases = [
+            (full.len(), full.clone()),
+            (
+                full.len() - 1,
+                "meta -\tlatency_ms=12\tuser=\"alice\"\terror=\"failed\"\tfields_dropped=2 TRUNCATED"
+                    .into(),
+            ),
+            (
+                50,
+                "meta -\tlatency_ms=12\tfields_dropped=4 TRUNCATED".into(),
+            ),
+            (
+                35,
+                "meta -\tfields_dropped=5 TRUNCATED".into(),
+            ),
+        ];
+
+        for (max_line_size, expected) in cases {
+            let line = render(&fields, max_line_size);
+            assert_eq!(line, expected, "{max_line_size}");
+            assert!(line.len() <= max_line_size);
+        }
+    }
+}
@@ -12,23 +12,30 @@ use tracing_subscriber::{
 
 use elfo_core::{logging::_priv::CheckResult, scope};
 
-use crate::{config::LoggingTargetConfig, stats};
+use crate::{config::LoggingTargetConfig, overrides::Overrides, stats};
 
 #[derive(PartialEq)]
 struct FilteringConfig {
     targets: Targets,
+    /// Every target can be a group name, so all of them are here.
+    groups: FxHashMap<String, LevelFilter>,
+    /// The maximum level that can be enabled by any group.
+    max_group_level: LevelFilter,
 }
 
 impl Default for FilteringConfig {
     fn default() -> Self {
         Self {
             targets: Targets::new().with_default(LevelFilter::TRACE),
+            groups: FxHashMap::default(),
+            max_group_level: LevelFilter::OFF,
         }
     }
 }
 
 struct Inner {
     config: ArcSwap<FilteringConfig>,
+    overrides: ArcSwap<Overrides>,
     #[cfg(feature = "tracing-log")]
     log_metadata_name: OnceCell<&'static str>,
 }
@@ -43,6 +50,7 @@ impl FilteringLayer {
         Self {
             inner: Arc::new(Inner {
                 config: ArcSwap::new(Arc::new(FilteringConfig::default())),
+                overrides: ArcSwap::default(),
                 #[cfg(feature = "tracing-log")]
                 log_metadata_name: OnceCell::new(),
             }),
@@ -50,26 +58,40 @@ impl FilteringLayer {
     }
 
     pub(crate) fn configure(&self, targets: &FxHashMap<String, LoggingTargetConfig>) {
+        let groups = targets
+            .iter()
+            .map(|(target, target_config)| (target.clone(), target_config.max_level))
+            .collect::<FxHashMap<_, _>>();
+        let max_group_level = groups.values().copied().max().unwrap_or(LevelFilter::OFF);
         let targets = Targets::new()
             .with_default(LevelFilter::TRACE)
-            .with_targets(
-                targets
-                    .iter()
-                    .map(|(target, target_config)| (target, target_config.max_level)),
-            );
+            .with_targets(groups.iter().map(|(target, level)| (target, *level)));
 
-        let config = Arc::new(FilteringConfig { targets });
+        let config = Arc::new(FilteringConfig {
+            targets,
+            groups,
+            max_group_level,
+        });
         let old_config = self.inner.config.swap(Arc::clone(&config));
         if config != old_config {
             tracing::callsite::rebuild_interest_cache();
         }
     }
+
+    pub(crate) fn set_overrides(&self, overrides: Overrides) {
+        self.inner.overrides.store(Arc::new(overrides));
+        tracing::callsite::rebuild_interest_cache();
+    }
 }
 
 impl<S: Subscriber> Layer<S> for FilteringLayer {
     fn register_callsite(&self, meta: &'static Metadata<'static>) -> Interest {
         let config = self.inner.config.load();
-        if config.targets.would_enable(meta.target(), meta.level()) {
+        let overrides = self.inner.overrides.load();
+        if config.targets.would_enable(meta.target(), meta.level())
+            || *meta.level() <= config.max_group_level
+            || *meta.level() <= overrides.max_level()
+        {
             // Not `::always()`, because actor can impose its own limits.
             Interest::sometimes()
         } else {
@@ -79,8 +101,9 @@ impl<S: Subscriber> Layer<S> for FilteringLayer {
     }
 
     fn enabled(&self, meta: &Metadata<'_>, _cx: Context<'_, S>) -> bool {
-        // We don't need to recheck `.targets` here, because `.register_callsite()`
-        // would already eliminate logs that would be filtered by it.
+        // We don't need to recheck `.targets` here (unless there are overrides),
+        // because `.register_callsite()` would already eliminate logs that would be
+        // filtered by it.
         let level = *meta.level();
 
         #[cfg(feature = "tracing-log")]
@@ -111,12 +134,48 @@ impl<S: Subscriber> Layer<S> for FilteringLayer {
             }
         }
 
+        let overrides = self.inner.overrides.load();
+        let config = self.inner.config.load();
+        if !overrides.is_empty() || !config.groups.is_empty() {
+            // Callsites can be registered only because of overrides or groups,
+            // so `.targets` is rechecked if neither is matched.
+            // Overrides have precedence over the config.
+            let target = meta.target();
+            let max_level = scope::try_with(|scope| {
+                let group = &scope.meta().group;
+                overrides
+                    .level_for(Some(group), target)
+                    .or_else(|| config.groups.get(group).copied())
+            })
+            .unwrap_or_else(|| overrides.level_for(None, target));
+
+            match max_level {
+                Some(max_level) if level > max_level => return false,
+                Some(_) => return self.check_in_scope(meta, false),
+                None => {
+                    if !config.targets.would_enable(meta.target(), meta.level()) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        self.check_in_scope(meta, true)
+    }
+
+    // TODO: global max level and `max_level_hint()`.
+}
+
+impl FilteringLayer {
+    fn check_in_scope(&self, meta: &Metadata<'_>, use_permissions: bool) -> bool {
+        let level = *meta.level();
+
         scope::try_with(|scope| {
-            if !scope.permissions().is_logging_enabled(level) {
+            if use_permissions && !scope.is_logging_enabled(level) {
                 return false;
             }
 
-            match scope.logging().check(meta) {
+            match scope.check_logging(meta) {
                 CheckResult::Passed => true,
                 CheckResult::NotInterested => false,
                 CheckResult::Limited => {
@@ -126,8 +185,6 @@ impl<S: Subscriber> Layer<S> for FilteringLayer {
             }
         })
         // `INFO` is a global cap for non-actor logs.
-        .unwrap_or(level <= LevelFilter::INFO)
+        .unwrap_or(!use_permissions || level <= LevelFilter::INFO)
     }
-
-    // TODO: global max level and `max_level_hint()`.
 }
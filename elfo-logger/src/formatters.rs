@@ -1,12 +1,39 @@
-use std::{fmt::Write, hash::Hash, marker::PhantomData, sync::Arc};
+use std::{
+    fmt::Write,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::Arc,
+};
 
 use tracing::Level;
 
-use elfo_core::{tracing::TraceId, ActorMeta};
-use elfo_utils::time::SystemTime;
+use elfo_core::{tracing::TraceId, ActorMeta, KeyEncoding};
+
+// Output
+
+/// A destination of formatters: a `String` or a part of the line.
+pub(crate) trait Output: Write {
+    fn push_str(&mut self, s: &str) {
+        let _ = self.write_str(s);
+    }
+
+    fn push(&mut self, c: char) {
+        let _ = self.write_char(c);
+    }
+
+    /// Returns how many bytes will be retained, the rest is discarded.
+    fn remaining(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Accounts bytes that would be discarded without writing them.
+    fn discard(&mut self, _len: usize) {}
+}
+
+impl Output for String {}
 
 pub(crate) trait Formatter<T: ?Sized> {
-    fn fmt(dest: &mut String, v: &T);
+    fn fmt(dest: &mut impl Output, v: &T);
 }
 
 // DoNothing
@@ -14,7 +41,7 @@ pub(crate) trait Formatter<T: ?Sized> {
 pub(crate) struct DoNothing;
 
 impl<T> Formatter<T> for DoNothing {
-    fn fmt(_dest: &mut String, _v: &T) {
+    fn fmt(_dest: &mut impl Output, _v: &T) {
         // Apparently does nothing
     }
 }
@@ -24,30 +51,15 @@ impl<T> Formatter<T> for DoNothing {
 pub(crate) struct ResetStyle;
 
 impl Formatter<()> for ResetStyle {
-    fn fmt(dest: &mut String, _v: &()) {
+    fn fmt(dest: &mut impl Output, _v: &()) {
         dest.push_str("\x1b[0m");
     }
 }
 
-// Rfc3339Weak
-
-pub(crate) struct Rfc3339Weak;
-
-impl Formatter<SystemTime> for Rfc3339Weak {
-    fn fmt(out: &mut String, v: &SystemTime) {
-        let t_idx = out.len() + 10;
-        let _ = write!(out, "{}", humantime::format_rfc3339_nanos((*v).into()));
-        // Replace "T" with " ".
-        out.replace_range(t_idx..t_idx + 1, " ");
-        // Remove trailing "Z".
-        out.pop();
-    }
-}
-
 // Level
 
 impl Formatter<Level> for Level {
-    fn fmt(out: &mut String, v: &Level) {
+    fn fmt(out: &mut impl Output, v: &Level) {
         out.push_str(match *v {
             Level::TRACE => "TRACE",
             Level::DEBUG => "DEBUG",
@@ -63,7 +75,7 @@ impl Formatter<Level> for Level {
 pub(crate) struct ColoredLevel;
 
 impl Formatter<Level> for ColoredLevel {
-    fn fmt(out: &mut String, v: &Level) {
+    fn fmt(out: &mut impl Output, v: &Level) {
         out.push_str(match *v {
             Level::TRACE => "\x1b[37mTRACE\x1b[0m",
             Level::DEBUG => "DEBUG",
@@ -77,108 +89,77 @@ impl Formatter<Level> for ColoredLevel {
 // TraceId
 
 impl Formatter<TraceId> for TraceId {
-    fn fmt(out: &mut String, v: &TraceId) {
+    fn fmt(out: &mut impl Output, v: &TraceId) {
         let _ = write!(out, "{v}");
     }
 }
 
 // ActorMeta
 
-impl Formatter<Arc<ActorMeta>> for Arc<ActorMeta> {
-    fn fmt(out: &mut String, v: &Arc<ActorMeta>) {
-        let _ = write!(out, "{v}");
+/// The actor's meta with the key encoded by `KeyEncoding::Prefix`.
+pub(crate) struct ActorPrefix {
+    pub(crate) meta: Arc<ActorMeta>,
+    pub(crate) max_key_width: usize,
+}
+
+// Only the meta is hashed to keep colors independent of the config.
+impl Hash for ActorPrefix {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.meta.hash(state);
     }
 }
 
-// Payload
+impl Formatter<ActorPrefix> for ActorPrefix {
+    fn fmt(out: &mut impl Output, v: &ActorPrefix) {
+        out.push_str(&v.meta.group);
 
-pub(crate) struct Payload;
+        if !v.meta.key.is_empty() {
+            let encoding = KeyEncoding::Prefix {
+                max_width: v.max_key_width,
+            };
 
-impl Formatter<str> for Payload {
-    fn fmt(out: &mut String, v: &str) {
-        // TODO: escape \t.
-        for (idx, chunk) in v.split('\n').enumerate() {
-            if idx > 0 {
-                out.push_str("\\n");
-            }
-
-            out.push_str(chunk);
+            out.push('/');
+            out.push_str(&v.meta.encoded_key(encoding));
         }
     }
 }
 
-// ColoredPayload
+// Payload
 
-pub(crate) struct ColoredPayload;
+pub(crate) struct Payload;
 
-impl Formatter<str> for ColoredPayload {
-    fn fmt(out: &mut String, v: &str) {
+impl Formatter<str> for Payload {
+    fn fmt(out: &mut impl Output, v: &str) {
         // TODO: escape \t.
         for (idx, chunk) in v.split('\n').enumerate() {
             if idx > 0 {
                 out.push_str("\\n");
             }
 
-            // <message>\t<key>=<value>\t<key>=<value>
-            for section in chunk.split('\t') {
-                if let Some((key, value)) = section.split_once('=') {
-                    out.push_str("\t\x1b[1m");
-                    out.push_str(key);
-                    out.push_str("\x1b[22m=");
-                    out.push_str(value);
-                } else {
-                    // It's the message section.
-                    out.push_str(section);
-                }
-            }
+            out.push_str(chunk);
         }
     }
 }
 
-// Location
-
-pub(crate) struct Location;
-
-impl Formatter<(&'static str, u32)> for Location {
-    fn fmt(out: &mut String, v: &(&'static str, u32)) {
-        let _ = write!(out, "_location={}:{}", reduce_location(v.0), v.1);
-    }
-}
-
-// ColoredLocation
-
-pub(crate) struct ColoredLocation;
-
-impl Formatter<(&'static str, u32)> for ColoredLocation {
-    fn fmt(out: &mut String, v: &(&'static str, u32)) {
-        let _ = write!(
-            out,
-            "\x1b[1m_location\x1b[22m={}:{}",
-            reduce_location(v.0),
-            v.1
-        );
-    }
-}
-
-// Module
+// FieldKey
 
-pub(crate) struct Module;
+pub(crate) struct FieldKey;
 
-impl Formatter<str> for Module {
-    fn fmt(out: &mut String, v: &str) {
-        out.push_str("_module=");
+impl Formatter<str> for FieldKey {
+    fn fmt(out: &mut impl Output, v: &str) {
         out.push_str(v);
     }
 }
 
-// ColoredModule
+// ColoredFieldKey
 
-pub(crate) struct ColoredModule;
+pub(crate) struct ColoredFieldKey;
 
-impl Formatter<str> for ColoredModule {
-    fn fmt(out: &mut String, v: &str) {
-        out.push_str("\x1b[1m_module\x1b[22m=");
+impl Formatter<str> for ColoredFieldKey {
+    fn fmt(out: &mut impl Output, v: &str) {
+        out.push_str("\x1b[1m");
         out.push_str(v);
+        out.push_str("\x1b[22m");
     }
 }
 
@@ -187,7 +168,7 @@ impl Formatter<str> for ColoredModule {
 pub(crate) struct EmptyIfNone<I>(PhantomData<I>);
 
 impl<T, I: Formatter<T>> Formatter<Option<T>> for EmptyIfNone<I> {
-    fn fmt(out: &mut String, v: &Option<T>) {
+    fn fmt(out: &mut impl Output, v: &Option<T>) {
         if let Some(inner) = v {
             I::fmt(out, inner);
         }
@@ -202,7 +183,7 @@ pub(crate) struct ColoredByHash<I>(PhantomData<I>);
 
 impl<T: Hash, I: Formatter<T>> Formatter<T> for ColoredByHash<I> {
     #[allow(clippy::many_single_char_names)]
-    fn fmt(out: &mut String, v: &T) {
+    fn fmt(out: &mut impl Output, v: &T) {
         let hash = fxhash::hash64(v);
 
         let y = 128f64;
@@ -224,7 +205,7 @@ fn clamp(v: f64) -> u8 {
     v.clamp(0., 255.) as u8
 }
 
-fn reduce_location(s: &str) -> &str {
+pub(crate) fn reduce_location(s: &str) -> &str {
     // {cargo_home}/registry/src/{registry}-{hash}/{crate}-{version}/{path}
     //                                             ^------- useful -------^
     if let Some((_, s)) = s.split_once("/registry/src/") {
@@ -0,0 +1,207 @@
+use crate::{
+    config::FormatKind,
+    fields::FIELDS_DROPPED_MARKER,
+    line_buffer::{safe_truncate, TRUNCATED_MARKER},
+};
+
+const JSON_TRUNCATED_MARKER: &str = ",\"truncated\":true";
+const JSON_FIELDS_DROPPED_MARKER: &str = ",\"fields_dropped\":";
+const JSON_FIELDS_START: &str = ",\"fields\":{";
+
+/// How parts of a line (`<meta><payload><fields>`) are joined and marked
+/// as truncated. Parts are truncated by `TruncatingWrite` in the same order
+/// for all formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Framing {
+    /// `<meta><payload><fields>[ TRUNCATED]`.
+    #[default]
+    Text,
+    /// `<meta><payload>"[,"fields":{<fields>}][,"truncated":true]}`.
+    ///
+    /// The meta ends with `"message":"`, the payload is the escaped message
+    /// and every field starts with `,`, so whole fields can be dropped.
+    Json,
+}
+
+impl From<FormatKind> for Framing {
+    fn from(kind: FormatKind) -> Self {
+        match kind {
+            FormatKind::Text => Self::Text,
+            FormatKind::Json => Self::Json,
+        }
+    }
+}
+
+impl Framing {
+    pub(crate) fn truncated_marker(self) -> &'static str {
+        match self {
+            Self::Text => TRUNCATED_MARKER,
+            Self::Json => JSON_TRUNCATED_MARKER,
+        }
+    }
+
+    /// Ends the fields part if trailing fields are dropped by truncation,
+    /// followed by the number of dropped fields.
+    pub(crate) fn fields_dropped_marker(self) -> &'static str {
+        match self {
+            Self::Text => FIELDS_DROPPED_MARKER,
+            Self::Json => JSON_FIELDS_DROPPED_MARKER,
+        }
+    }
+
+    /// Returns how many bytes are added by joining parts.
+    pub(crate) fn glue_len(self, fields_len: usize) -> usize {
+        match self {
+            Self::Text => 0,
+            // The closing quote of the message and the closing brace.
+            Self::Json if fields_len == 0 => 2,
+            // Also the fields' object, which replaces the leading comma.
+            Self::Json => 2 + JSON_FIELDS_START.len(),
+        }
+    }
+
+    /// Appends the payload and the fields to the meta written to `out`.
+    pub(crate) fn join(self, out: &mut String, payload: &str, fields: &str, is_truncated: bool) {
+        out.push_str(payload);
+
+        match self {
+            Self::Text => {
+                out.push_str(fields);
+                if is_truncated {
+                    out.push_str(TRUNCATED_MARKER);
+                }
+            }
+            Self::Json => {
+                out.push('"');
+                if let Some(fields) = fields.strip_prefix(',') {
+                    out.push_str(JSON_FIELDS_START);
+                    out.push_str(fields);
+                    out.push('}');
+                }
+                if is_truncated {
+                    out.push_str(JSON_TRUNCATED_MARKER);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// Truncates the payload to `to` bytes, returns how many bytes more
+    /// are erased to keep chars and, for JSON, escape sequences whole.
+    pub(crate) fn truncate_payload(self, payload: &mut String, to: usize) -> usize {
+        let erased = safe_truncate(payload, to);
+
+        match self {
+            Self::Text => erased,
+            Self::Json => erased + trim_partial_escape(payload),
+        }
+    }
+}
+
+/// Removes an escape sequence cut by truncation, e.g. `\` or `\u00`.
+/// Returns the number of removed bytes.
+fn trim_partial_escape(escaped: &mut String) -> usize {
+    let bytes = escaped.as_bytes();
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx] != b'\\' {
+            idx += 1;
+            continue;
+        }
+
+        let len = if bytes.get(idx + 1) == Some(&b'u') {
+            6
+        } else {
+            2
+        };
+        if idx + len > bytes.len() {
+            let removed = bytes.len() - idx;
+            escaped.truncate(idx);
+            return removed;
+        }
+
+        idx += len;
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fields::{escape_json, write_json_fields, Fields},
+        formatters::Output as _,
+        line_buffer::LineBuffer,
+        line_transaction::{Line as _, LineFactory, TruncateOnUnfit},
+    };
+
+    const META: &str = "{\"level\":\"INFO\",\"message\":\"";
+
+    fn render(max_line_size: usize) -> String {
+        let mut fields = Fields::default();
+        fields.push_u64("latency_ms", 12);
+        fields.push_str("user", "\"alice\"\n");
+        fields.push_f64("ratio", f64::NAN);
+        fields.push_error("error", 1, format_args!("timeout"));
+
+        let mut buffer = LineBuffer::with_capacity(1024, usize::MAX);
+        buffer.configure(max_line_size, Framing::Json);
+
+        let mut line = TruncateOnUnfit::create_line(&mut buffer);
+        line.meta_mut().push_str(META);
+        escape_json(&mut line.payload_mut(), "ünïcode\t\"quoted\"\u{1}");
+        write_json_fields(&mut line, &fields);
+        assert!(line.try_commit());
+
+        buffer.as_str().trim_end_matches('\n').to_owned()
+    }
+
+    #[test]
+    fn json_lines() {
+        let line = render(usize::MAX);
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "level": "INFO",
+                "message": "ünïcode\t\"quoted\"\u{1}",
+                "fields": {
+                    "latency_ms": 12,
+                    "user": "\"alice\"\n",
+                    "ratio": "NaN",
+                    "error.source": "timeout",
+                },
+            })
+        );
+
+        // Truncated lines remain valid while the meta fits.
+        for max_line_size in META.len() + JSON_TRUNCATED_MARKER.len() + 2..=line.len() {
+            let truncated = render(max_line_size);
+            assert!(truncated.len() <= max_line_size, "{truncated}");
+
+            let json: serde_json::Value =
+                serde_json::from_str(&truncated).unwrap_or_else(|err| panic!("{err}: {truncated}"));
+            let is_truncated = max_line_size < line.len();
+            assert_eq!(json.get("truncated").is_some(), is_truncated, "{truncated}");
+        }
+    }
+
+    #[test]
+    fn partial_escapes_are_trimmed() {
+        for (escaped, expected) in [
+            ("plain", "plain"),
+            ("a\\", "a"),
+            ("a\\\\", "a\\\\"),
+            ("a\\\\\\", "a\\\\"),
+            ("a\\u00", "a"),
+            ("a\\u001f", "a\\u001f"),
+            ("a\\n\\", "a\\n"),
+        ] {
+            let mut escaped = escaped.to_owned();
+            trim_partial_escape(&mut escaped);
+            assert_eq!(escaped, expected);
+        }
+    }
+}
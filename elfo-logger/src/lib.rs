@@ -16,55 +16,83 @@ use sharded_slab::Pool;
 use tracing::{span::Id as SpanId, Metadata, Subscriber};
 use tracing_subscriber::{prelude::*, registry::Registry, EnvFilter};
 
-use elfo_core::{tracing::TraceId, ActorMeta, Blueprint};
+use elfo_core::{
+    dumping::SequenceNo, logging::_priv::StaticFields, tracing::TraceId, ActorMeta, Blueprint,
+};
 use elfo_utils::time::SystemTime;
 
-use crate::{actor::Logger, filtering_layer::FilteringLayer, printing_layer::PrintingLayer};
+use crate::{
+    actor::Logger, backlog::Backlog, config::Channel, fields::Fields,
+    filtering_layer::FilteringLayer, printing_layer::PrintingLayer,
+};
 
-pub use crate::actor::ReopenLogFile;
+pub use crate::{
+    actor::{FlushLogs, ReopenLogFile},
+    enricher::{set_meta_enricher, unset_meta_enricher, Meta},
+    overrides::{LogLevel, LogLevelOverride, SetLogLevel},
+};
 
 pub mod config;
 
 mod actor;
+mod backlog;
+mod enricher;
+mod fields;
 mod filtering_layer;
 mod formatters;
+mod framing;
+mod multiline;
+mod overrides;
 mod printing_layer;
+mod rotation;
+mod static_fields;
 mod stats;
 mod theme;
+mod timestamp;
 
 mod line_buffer;
 mod line_transaction;
 
-const CHANNEL_CAPACITY: usize = 128 * 1024;
-
 type StringId = usize;
+type FieldsId = usize;
 
 struct Shared {
+    // Bounded by `backlog`, because its capacity is configurable.
     channel: GenericChannel<RawMutex, PreparedEvent, GrowingHeapBuf<PreparedEvent>>,
     pool: Pool<String>,
+    fields: Pool<Fields>,
     spans: DashMap<SpanId, SpanData, FxBuildHasher>,
+    backlog: Backlog,
 }
 
 #[derive(Constructor)]
 struct SpanData {
     parent_id: Option<SpanId>,
-    payload_id: StringId,
+    fields_id: FieldsId,
 }
 
 struct PreparedEvent {
     timestamp: SystemTime,
     trace_id: Option<TraceId>,
+    sequence_no: Option<SequenceNo>,
     metadata: &'static Metadata<'static>,
     object: Option<Arc<ActorMeta>>,
     span_id: Option<SpanId>,
-    payload_id: StringId,
+    message_id: StringId,
+    fields_id: FieldsId,
+    /// Extra meta added by the enricher, see `set_meta_enricher()`.
+    meta_id: Option<StringId>,
+    /// Static fields of the group, see `system.logging.fields`.
+    static_fields: Option<Arc<StaticFields>>,
 }
 
 fn new() -> (PrintingLayer, FilteringLayer, Blueprint) {
     let shared = Shared {
-        channel: GenericChannel::with_capacity(CHANNEL_CAPACITY),
+        channel: GenericChannel::with_capacity(usize::MAX),
         pool: Pool::default(),
+        fields: Pool::default(),
         spans: DashMap::default(),
+        backlog: Backlog::new(&Channel::default()),
     };
 
     let shared = Arc::new(shared);
@@ -1,8 +1,11 @@
-use std::mem;
+use std::{
+    fmt::{self, Write as _},
+    mem,
+};
 
-use crate::line_transaction::Line;
+use crate::{formatters::Output, framing::Framing, line_transaction::Line};
 
-pub(super) const TRUNCATED_MARKER: &str = " TRUNCATED";
+pub(crate) const TRUNCATED_MARKER: &str = " TRUNCATED";
 
 // Repr
 
@@ -13,6 +16,89 @@ struct Repr<'a> {
     // We don't wanna write unfinished data, so there must be a way to
     // revert changes
     pre_start_buffer_size: usize,
+
+    // Bytes discarded by `LineWriter`s per part.
+    meta_discarded: usize,
+    payload_discarded: usize,
+    fields_discarded: usize,
+}
+
+impl Repr<'_> {
+    // Parts are truncated to `max_line_size` anyway, but the truncation logic
+    // shifts to char boundaries, so a bit more is retained.
+    fn part_limit(&self) -> usize {
+        let marker = self.buf.framing.truncated_marker();
+        self.buf.max_line_size.saturating_add(marker.len())
+    }
+}
+
+// LineWriter
+
+/// Writes a part of the line.
+///
+/// Stops retaining data beyond `limit` bytes of the part, so the peak memory
+/// is bounded by `max_line_size` regardless of the input. Discarded bytes are
+/// accounted, so the truncation logic works as if they were written.
+pub(crate) struct LineWriter<'a> {
+    buf: &'a mut String,
+    start: usize,
+    limit: usize,
+    discarded: &'a mut usize,
+}
+
+impl<'a> LineWriter<'a> {
+    pub(crate) fn new(buf: &'a mut String, limit: usize, discarded: &'a mut usize) -> Self {
+        Self::with_start(buf, 0, limit, discarded)
+    }
+
+    fn with_start(
+        buf: &'a mut String,
+        start: usize,
+        limit: usize,
+        discarded: &'a mut usize,
+    ) -> Self {
+        Self {
+            buf,
+            start,
+            limit,
+            discarded,
+        }
+    }
+}
+
+impl fmt::Write for LineWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = self.remaining();
+
+        if s.len() <= room {
+            self.buf.push_str(s);
+        } else {
+            let mut end = room;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            self.buf.push_str(&s[..end]);
+            *self.discarded += s.len() - end;
+        }
+
+        Ok(())
+    }
+}
+
+impl Output for LineWriter<'_> {
+    fn remaining(&self) -> usize {
+        // Nothing is retained after discarding to keep the retained data a prefix.
+        if *self.discarded > 0 {
+            0
+        } else {
+            self.limit - (self.buf.len() - self.start)
+        }
+    }
+
+    fn discard(&mut self, len: usize) {
+        *self.discarded += len;
+    }
 }
 
 // TruncatingWrite
@@ -25,74 +111,135 @@ impl Line for TruncatingWrite<'_> {
         let add_truncated_marker = self.probe_size_limit();
 
         {
-            let buffer = &mut self.0.buf.buffer;
-            buffer.push_str(&self.0.buf.payload);
-            buffer.push_str(&self.0.buf.fields);
-            if add_truncated_marker {
-                buffer.push_str(TRUNCATED_MARKER);
-            }
-            buffer.push('\n');
+            let buf = &mut *self.0.buf;
+            let framing = buf.framing;
+            framing.join(
+                &mut buf.buffer,
+                &buf.payload,
+                &buf.fields,
+                add_truncated_marker,
+            );
+            buf.buffer.push('\n');
         }
 
         mem::forget(self);
         true
     }
 
-    fn meta_mut(&mut self) -> &mut String {
-        &mut self.0.buf.buffer
+    fn meta_mut(&mut self) -> LineWriter<'_> {
+        let limit = self.0.part_limit();
+        let start = self.0.pre_start_buffer_size;
+        let discarded = &mut self.0.meta_discarded;
+        LineWriter::with_start(&mut self.0.buf.buffer, start, limit, discarded)
+    }
+
+    fn payload_mut(&mut self) -> LineWriter<'_> {
+        let limit = self.0.part_limit();
+        LineWriter::new(
+            &mut self.0.buf.payload,
+            limit,
+            &mut self.0.payload_discarded,
+        )
     }
 
-    fn payload_mut(&mut self) -> &mut String {
-        &mut self.0.buf.payload
+    fn fields_mut(&mut self) -> LineWriter<'_> {
+        let limit = self.0.part_limit();
+        LineWriter::new(&mut self.0.buf.fields, limit, &mut self.0.fields_discarded)
     }
 
-    fn fields_mut(&mut self) -> &mut String {
-        &mut self.0.buf.fields
+    fn end_field(&mut self) {
+        let end = self.fields_len();
+        self.0.buf.field_ends.push(end);
     }
 }
 
 impl TruncatingWrite<'_> {
     fn meta_len(&self) -> usize {
-        self.0.buf.buffer.len() - self.0.pre_start_buffer_size
+        self.0.buf.buffer.len() - self.0.pre_start_buffer_size + self.0.meta_discarded
+    }
+
+    fn payload_len(&self) -> usize {
+        self.0.buf.payload.len() + self.0.payload_discarded
+    }
+
+    fn fields_len(&self) -> usize {
+        self.0.buf.fields.len() + self.0.fields_discarded
     }
 
     fn len(&self) -> usize {
-        self.meta_len() + self.0.buf.payload.len() + self.0.buf.fields.len()
+        let fields_len = self.fields_len();
+        let glue_len = self.0.buf.framing.glue_len(fields_len);
+        self.meta_len() + self.payload_len() + fields_len + glue_len
     }
 }
 
 impl TruncatingWrite<'_> {
     fn probe_size_limit(&mut self) -> bool {
+        let framing = self.0.buf.framing;
+        let marker_len = framing.truncated_marker().len();
+
         let len = self.len();
-        let mut need_to_erase = if len > self.0.buf.max_line_size {
-            len - self.0.buf.max_line_size + TRUNCATED_MARKER.len()
-        } else {
-            return len + TRUNCATED_MARKER.len() <= self.0.buf.max_line_size;
-        };
+        // The line fits, e.g. JSON lines are written only by `TruncatingWrite`.
+        if len <= self.0.buf.max_line_size {
+            return false;
+        }
+        let mut need_to_erase = len - self.0.buf.max_line_size + marker_len;
 
-        let payload_len = self.0.buf.payload.len();
-        let fields_len = self.0.buf.fields.len();
-        let meta_len = self.meta_len();
+        let payload_len = self.payload_len();
+        let fields_len = self.fields_len();
+
+        // Kept parts never exceed retained ones, because the line is too long.
+        self.0.meta_discarded = 0;
+        self.0.payload_discarded = 0;
+        self.0.fields_discarded = 0;
 
         let payload_part = payload_len.min(need_to_erase);
         need_to_erase -= payload_part;
-        need_to_erase = need_to_erase.saturating_sub(safe_truncate(
-            &mut self.0.buf.payload,
-            payload_len - payload_part,
-        ));
+        need_to_erase = need_to_erase.saturating_sub(
+            framing.truncate_payload(&mut self.0.buf.payload, payload_len - payload_part),
+        );
 
         let fields_part = fields_len.min(need_to_erase);
-        need_to_erase -= fields_part;
-        need_to_erase = need_to_erase.saturating_sub(safe_truncate(
-            &mut self.0.buf.fields,
-            fields_len - fields_part,
-        ));
+        self.truncate_fields(fields_len - fields_part);
 
+        // Recalculated, since dropping all fields can also drop the glue.
+        let need_to_erase = (self.len() + marker_len).saturating_sub(self.0.buf.max_line_size);
+        let meta_len = self.meta_len();
         let meta_part = meta_len.min(need_to_erase);
-        let truncate_meta_to = self.0.buf.buffer.len() - meta_part;
+        let truncate_meta_to = self.0.pre_start_buffer_size + meta_len - meta_part;
 
         safe_truncate(&mut self.0.buf.buffer, truncate_meta_to);
-        self.len() + TRUNCATED_MARKER.len() <= self.0.buf.max_line_size
+        self.len() + marker_len <= self.0.buf.max_line_size
+    }
+
+    /// Truncates the fields part to at most `to` bytes. Whole trailing fields are dropped and replaced with the
+    /// `fields_dropped=<n>` marker, unmarked data after fields is cut as is.
+    fn truncate_fields(&mut self, to: usize) {
+        let dropped_marker = self.0.buf.framing.fields_dropped_marker();
+        let fields = &mut self.0.buf.fields;
+        let ends = &self.0.buf.field_ends;
+
+        if to >= ends.last().copied().unwrap_or(0) {
+            safe_truncate(fields, to);
+            return;
+        }
+
+        let mut marker = String::new();
+        for kept in (0..ends.len()).rev() {
+            let end = kept.checked_sub(1).map_or(0, |idx| ends[idx]);
+
+            marker.clear();
+            let _ = write!(marker, "{dropped_marker}{}", ends.len() - kept);
+
+            if end + marker.len() <= to {
+                fields.truncate(end);
+                fields.push_str(&marker);
+                return;
+            }
+        }
+
+        // Even the marker doesn't fit.
+        fields.clear();
     }
 }
 
@@ -109,7 +256,15 @@ pub(crate) struct DirectWrite<'a>(Repr<'a>);
 
 impl DirectWrite<'_> {
     fn len(&self) -> usize {
-        self.0.buf.buffer.len() - self.0.pre_start_buffer_size
+        self.0.buf.buffer.len() - self.0.pre_start_buffer_size + self.0.meta_discarded
+    }
+
+    // All parts are written to the same buffer, so they share one counter.
+    fn writer(&mut self) -> LineWriter<'_> {
+        let limit = self.0.part_limit();
+        let start = self.0.pre_start_buffer_size;
+        let discarded = &mut self.0.meta_discarded;
+        LineWriter::with_start(&mut self.0.buf.buffer, start, limit, discarded)
     }
 }
 
@@ -126,22 +281,20 @@ impl Line for DirectWrite<'_> {
         }
     }
 
-    // Nope, clippy, this is how it is supposed to work
-
-    #[allow(clippy::misnamed_getters)]
-    fn meta_mut(&mut self) -> &mut String {
-        &mut self.0.buf.buffer
+    fn meta_mut(&mut self) -> LineWriter<'_> {
+        self.writer()
     }
 
-    #[allow(clippy::misnamed_getters)]
-    fn payload_mut(&mut self) -> &mut String {
-        &mut self.0.buf.buffer
+    fn payload_mut(&mut self) -> LineWriter<'_> {
+        self.writer()
     }
 
-    #[allow(clippy::misnamed_getters)]
-    fn fields_mut(&mut self) -> &mut String {
-        &mut self.0.buf.buffer
+    fn fields_mut(&mut self) -> LineWriter<'_> {
+        self.writer()
     }
+
+    // The line isn't truncated, so boundaries aren't needed.
+    fn end_field(&mut self) {}
 }
 
 impl Drop for DirectWrite<'_> {
@@ -160,13 +313,17 @@ pub(crate) struct LineBuffer {
     buffer: String,
     payload: String,
     fields: String,
+    // Ends of fields written to `fields`, including discarded bytes.
+    field_ends: Vec<usize>,
 
     max_line_size: usize,
+    framing: Framing,
 }
 
 impl LineBuffer {
-    pub(crate) fn configure(&mut self, max_line_size: usize) {
+    pub(crate) fn configure(&mut self, max_line_size: usize, framing: Framing) {
         self.max_line_size = max_line_size;
+        self.framing = framing;
     }
 
     /// Discards current buffers
@@ -174,6 +331,7 @@ impl LineBuffer {
         self.buffer.clear();
         self.payload.clear();
         self.fields.clear();
+        self.field_ends.clear();
     }
 
     pub(crate) fn as_str(&self) -> &str {
@@ -181,10 +339,18 @@ impl LineBuffer {
     }
 
     fn create_repr(&mut self) -> Repr<'_> {
+        // The buffer can contain previous lines, but not their parts.
+        self.payload.clear();
+        self.fields.clear();
+        self.field_ends.clear();
+
         let size = self.buffer.len();
         Repr {
             buf: self,
             pre_start_buffer_size: size,
+            meta_discarded: 0,
+            payload_discarded: 0,
+            fields_discarded: 0,
         }
     }
 
@@ -201,12 +367,14 @@ impl LineBuffer {
             buffer: String::with_capacity(capacity),
             payload: String::new(),
             fields: String::new(),
+            field_ends: Vec::new(),
             max_line_size,
+            framing: Framing::Text,
         }
     }
 }
 
-fn safe_truncate(text: &mut String, to: usize) -> usize {
+pub(crate) fn safe_truncate(text: &mut String, to: usize) -> usize {
     let mut boundary = to;
     while !text.is_char_boundary(boundary) {
         boundary -= 1;
@@ -218,8 +386,8 @@ fn safe_truncate(text: &mut String, to: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{safe_truncate, LineBuffer, TruncatingWrite, TRUNCATED_MARKER};
-    use crate::line_transaction::Line as _;
+    use super::{safe_truncate, LineBuffer, LineWriter, TruncatingWrite, TRUNCATED_MARKER};
+    use crate::{formatters::Output as _, line_transaction::Line as _};
 
     fn put_msg(mut line: TruncatingWrite<'_>, meta: &str, payload: &str, fields: &str) {
         line.meta_mut().push_str(meta);
@@ -305,4 +473,60 @@ mod tests {
 
         assert_eq!(buffer.buffer, "\n\n\n");
     }
+
+    #[test]
+    fn test_writer_keeps_prefix() {
+        let mut buf = String::new();
+        let mut discarded = 0;
+        let mut writer = LineWriter::new(&mut buf, 4, &mut discarded);
+        writer.push_str("aя");
+        // Cut on the char boundary.
+        writer.push_str("яb");
+        // Nothing is retained after discarding.
+        writer.push('c');
+
+        assert_eq!(buf, "aя");
+        assert_eq!(discarded, 4);
+    }
+
+    #[test]
+    fn test_giant_parts_are_not_retained() {
+        const LIMIT: usize = 1000;
+        let giant = "x".repeat(10 * 1024 * 1024);
+        let kept = |other: &str| "x".repeat(LIMIT - TRUNCATED_MARKER.len() - other.len());
+
+        let mut buffer = LineBuffer::with_capacity(100, LIMIT);
+
+        // The logger tries to write directly firstly.
+        let mut line = buffer.direct_write();
+        line.meta_mut().push_str("meta ");
+        line.payload_mut().push_str(&giant);
+        line.fields_mut().push_str("\tk=v");
+        assert!(!line.try_commit());
+        assert_eq!(buffer.as_str(), "");
+
+        // The payload is truncated firstly.
+        put_msg(buffer.truncating_write(), "meta ", &giant, "\tk=v");
+        let expected = format!("meta {}\tk=v{TRUNCATED_MARKER}\n", kept("meta \tk=v"));
+        assert_eq!(buffer.as_str(), expected);
+        buffer.clear();
+
+        // If the payload is erased, fields are truncated.
+        let fields = format!("\tbody={giant}");
+        put_msg(buffer.truncating_write(), "meta ", "payload", &fields);
+        let expected = format!("meta \tbody={}{TRUNCATED_MARKER}\n", kept("meta \tbody="));
+        assert_eq!(buffer.as_str(), expected);
+
+        let capacities = [
+            buffer.buffer.capacity(),
+            buffer.payload.capacity(),
+            buffer.fields.capacity(),
+        ];
+        for capacity in capacities {
+            assert!(
+                capacity <= 2 * (LIMIT + TRUNCATED_MARKER.len()),
+                "{capacities:?}"
+            );
+        }
+    }
 }
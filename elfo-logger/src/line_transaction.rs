@@ -1,4 +1,4 @@
-use crate::line_buffer::{DirectWrite, TruncatingWrite};
+use crate::line_buffer::{DirectWrite, LineWriter, TruncatingWrite};
 
 use super::line_buffer::LineBuffer;
 
@@ -43,10 +43,18 @@ impl LineFactory for TruncateOnUnfit {
     }
 }
 
+/// A line being written by parts: `<meta><payload><fields>`.
+///
+/// Parts are written through `LineWriter`s, which discard data that cannot
+/// fit into `max_line_size` anyway.
 pub(crate) trait Line {
-    fn meta_mut(&mut self) -> &mut String;
-    fn payload_mut(&mut self) -> &mut String;
-    fn fields_mut(&mut self) -> &mut String;
+    fn meta_mut(&mut self) -> LineWriter<'_>;
+    fn payload_mut(&mut self) -> LineWriter<'_>;
+    fn fields_mut(&mut self) -> LineWriter<'_>;
+
+    /// Marks the end of a field written to the fields part,
+    /// so truncation can drop whole trailing fields.
+    fn end_field(&mut self);
 
     fn try_commit(self) -> bool;
 }
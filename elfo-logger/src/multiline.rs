@@ -0,0 +1,166 @@
+use elfo_core::tracing::TraceId;
+
+use crate::{
+    config::Multiline,
+    formatters::{Formatter, Output},
+    line_buffer::LineWriter,
+    theme::Theme,
+};
+
+/// Starts continuation lines written by `Multiline::Indent`.
+pub(crate) const CONTINUATION_MARKER: &str = "  | ";
+/// Ends values cut by `Multiline::TruncateFirstLine`.
+pub(crate) const CUT_MARKER: &str = "…";
+
+/// Writes the payload (`<message>\t<key>=<value>...`) according to the policy.
+///
+/// It's applied before committing the line, so the truncation logic sees
+/// the expanded payload and `max_line_size` is respected.
+pub(crate) fn write_payload<T: Theme>(
+    out: &mut impl Output,
+    payload: &str,
+    policy: Multiline,
+    trace_id: &Option<TraceId>,
+) {
+    write_multiline::<T, T::Payload>(out, payload, policy, trace_id);
+}
+
+/// Writes the text by the formatter `P` according to the policy.
+/// Continuation lines are prefixed by the trace id formatted by the theme.
+pub(crate) fn write_multiline<T: Theme, P: Formatter<str>>(
+    out: &mut impl Output,
+    payload: &str,
+    policy: Multiline,
+    trace_id: &Option<TraceId>,
+) {
+    if !payload.contains('\n') {
+        return P::fmt(out, payload);
+    }
+
+    match policy {
+        // Theme formatters escape newlines by themselves.
+        Multiline::Escape => P::fmt(out, payload),
+        Multiline::Indent => {
+            for (idx, chunk) in payload.split('\n').enumerate() {
+                if idx > 0 {
+                    out.push('\n');
+                    out.push_str(CONTINUATION_MARKER);
+                    out.push('[');
+                    T::TraceId::fmt(out, trace_id);
+                    out.push_str("] ");
+                }
+
+                P::fmt(out, chunk);
+            }
+        }
+        Multiline::TruncateFirstLine => {
+            // Only the part retained by `out` is built, the rest is accounted.
+            // It's exact for the plain theme, which writes the cut payload as is.
+            let mut buf = String::new();
+            let mut discarded = 0;
+            let mut cut = LineWriter::new(&mut buf, out.remaining(), &mut discarded);
+
+            for (idx, section) in payload.split('\t').enumerate() {
+                if idx > 0 {
+                    cut.push('\t');
+                }
+
+                if let Some((first_line, _)) = section.split_once('\n') {
+                    cut.push_str(first_line);
+                    cut.push_str(CUT_MARKER);
+                } else {
+                    cut.push_str(section);
+                }
+            }
+
+            P::fmt(out, &buf);
+            out.discard(discarded);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        line_buffer::{LineBuffer, TRUNCATED_MARKER},
+        line_transaction::Line,
+        theme::PlainTheme,
+    };
+
+    const PANIC: &str = "panicked at src/lib.rs:1:1:\nboom\nstack backtrace:\n   0: foo\tcode=42";
+    const META: &str = "2023-11-14 22:13:20 ERROR [42] ";
+
+    fn policies() -> [Multiline; 3] {
+        [
+            Multiline::Escape,
+            Multiline::Indent,
+            Multiline::TruncateFirstLine,
+        ]
+    }
+
+    // Mimics the logger: writes directly and truncates only if it doesn't fit.
+    fn write(policy: Multiline, max_line_size: usize) -> String {
+        fn fill(mut line: impl Line, policy: Multiline) -> bool {
+            let trace_id = TraceId::try_from(42).ok();
+            line.meta_mut().push_str(META);
+            write_payload::<PlainTheme>(&mut line.payload_mut(), PANIC, policy, &trace_id);
+            line.try_commit()
+        }
+
+        let mut buffer = LineBuffer::with_capacity(1024, max_line_size);
+        if !fill(buffer.direct_write(), policy) {
+            assert!(fill(buffer.truncating_write(), policy));
+        }
+
+        buffer.as_str().to_owned()
+    }
+
+    #[test]
+    fn exact() {
+        assert_eq!(
+            write(Multiline::Escape, usize::MAX),
+            "2023-11-14 22:13:20 ERROR [42] panicked at src/lib.rs:1:1:\\nboom\\nstack backtrace:\\n   0: foo\tcode=42\n"
+        );
+        // Continuation lines are prefixed with the trace id, which is hex with
+        // `trace-id-128`.
+        let trace_id = TraceId::try_from(42).unwrap();
+        assert_eq!(
+            write(Multiline::Indent, usize::MAX),
+            format!("2023-11-14 22:13:20 ERROR [42] panicked at src/lib.rs:1:1:\n  | [{trace_id}] boom\n  | [{trace_id}] stack backtrace:\n  | [{trace_id}]    0: foo\tcode=42\n")
+        );
+        assert_eq!(
+            write(Multiline::TruncateFirstLine, usize::MAX),
+            "2023-11-14 22:13:20 ERROR [42] panicked at src/lib.rs:1:1:…\tcode=42\n"
+        );
+    }
+
+    #[test]
+    fn single_line_is_untouched() {
+        let trace_id = TraceId::try_from(42).ok();
+
+        for policy in policies() {
+            let mut out = String::new();
+            write_payload::<PlainTheme>(&mut out, "hello\tkey=value", policy, &trace_id);
+            assert_eq!(out, "hello\tkey=value");
+        }
+    }
+
+    #[test]
+    fn max_line_size_is_respected() {
+        for policy in policies() {
+            let full_len = write(policy, usize::MAX).len() - 1;
+
+            for max_line_size in [0, 10, META.len(), 50, full_len - 1, full_len] {
+                let line = write(policy, max_line_size);
+                let record = line.strip_suffix('\n').unwrap();
+
+                assert!(record.len() <= max_line_size, "{policy:?}: {line:?}");
+                if max_line_size < full_len && max_line_size >= TRUNCATED_MARKER.len() {
+                    assert!(record.ends_with(TRUNCATED_MARKER), "{policy:?}: {line:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::{fmt, time::Duration};
+
+use tracing::metadata::LevelFilter;
+
+use elfo_core::message;
+
+/// Overrides the maximum log level without a config update.
+///
+/// Handled by the logger group. The level is applied immediately, either
+/// globally or for the specified target. The target is either a group name or
+/// a `tracing` target (matched by prefix, like the `targets` config
+/// parameter). The group override has precedence over the target one, which
+/// has precedence over the global one. Overrides replace levels defined by
+/// `system.logging.max_level` and `targets`, but not rate limits and sampling.
+///
+/// A later config update of the logger clears overrides, unless they're set
+/// [sticky](SetLogLevel::sticky). Responds with the replaced override, if any.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// use elfo_logger::{LogLevel, SetLogLevel};
+///
+/// // Turn on debug logs in the `producers` group for 10 minutes.
+/// let request = SetLogLevel::target("producers", LogLevel::Debug)
+///     .for_duration(Duration::from_secs(600));
+/// ```
+#[message(ret = Option<LogLevelOverride>)]
+#[non_exhaustive]
+pub struct SetLogLevel {
+    /// A group or a `tracing` target, `None` for the global level.
+    pub target: Option<String>,
+    /// The maximum level of logs.
+    pub level: LogLevel,
+    /// The override is reverted after this duration, if specified.
+    pub duration: Option<Duration>,
+    /// Whether the override survives config updates.
+    pub sticky: bool,
+}
+
+impl SetLogLevel {
+    /// Overrides the level of all logs.
+    pub fn global(level: LogLevel) -> Self {
+        Self {
+            target: None,
+            level,
+            duration: None,
+            sticky: false,
+        }
+    }
+
+    /// Overrides the level of the group or the `tracing` target.
+    pub fn target(target: impl Into<String>, level: LogLevel) -> Self {
+        Self {
+            target: Some(target.into()),
+            ..Self::global(level)
+        }
+    }
+
+    /// Reverts the override after the specified duration.
+    pub fn for_duration(self, duration: Duration) -> Self {
+        Self {
+            duration: Some(duration),
+            ..self
+        }
+    }
+
+    /// Keeps the override on config updates.
+    pub fn sticky(self) -> Self {
+        Self {
+            sticky: true,
+            ..self
+        }
+    }
+}
+
+/// A log level used by [`SetLogLevel`].
+#[message(part)]
+#[derive(Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    /// All logs.
+    Trace,
+    /// `Debug` and more severe logs.
+    Debug,
+    /// `Info` and more severe logs.
+    Info,
+    /// `Warn` and `Error` logs.
+    Warn,
+    /// Only `Error` logs.
+    Error,
+    /// No logs.
+    Off,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => LevelFilter::TRACE,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Off => LevelFilter::OFF,
+        }
+    }
+}
+
+/// An active override set by [`SetLogLevel`].
+#[message(part)]
+#[non_exhaustive]
+pub struct LogLevelOverride {
+    /// See [`SetLogLevel::target`].
+    pub target: Option<String>,
+    /// See [`SetLogLevel::level`].
+    pub level: LogLevel,
+    /// See [`SetLogLevel::duration`].
+    pub duration: Option<Duration>,
+    /// See [`SetLogLevel::sticky`].
+    pub sticky: bool,
+}
+
+impl fmt::Display for LogLevelOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={:?}",
+            self.target.as_deref().unwrap_or("*"),
+            self.level
+        )?;
+
+        if let Some(duration) = self.duration {
+            write!(f, " for {}", humantime::format_duration(duration))?;
+        }
+        if self.sticky {
+            f.write_str(" (sticky)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reverts the override if it hasn't been replaced since.
+#[message]
+pub(crate) struct RevertLogLevel {
+    pub(crate) id: u64,
+}
+
+// === Overrides ===
+
+#[derive(Default, Clone)]
+pub(crate) struct Overrides {
+    list: Vec<(u64, LogLevelOverride)>,
+}
+
+impl Overrides {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &LogLevelOverride> {
+        self.list.iter().map(|(_, o)| o)
+    }
+
+    /// Returns the replaced override.
+    pub(crate) fn set(&mut self, id: u64, new: LogLevelOverride) -> Option<LogLevelOverride> {
+        let pos = self.list.iter().position(|(_, o)| o.target == new.target);
+        match pos {
+            Some(pos) => Some(std::mem::replace(&mut self.list[pos], (id, new)).1),
+            None => {
+                self.list.push((id, new));
+                None
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: u64) -> Option<LogLevelOverride> {
+        let pos = self.list.iter().position(|(i, _)| *i == id)?;
+        Some(self.list.remove(pos).1)
+    }
+
+    pub(crate) fn clear_non_sticky(&mut self) {
+        self.list.retain(|(_, o)| o.sticky);
+    }
+
+    /// Returns the overridden level for the group (if inside an actor) and
+    /// the `tracing` target, or `None` if there is no matching override.
+    pub(crate) fn level_for(&self, group: Option<&str>, target: &str) -> Option<LevelFilter> {
+        let mut global = None;
+        let mut by_target = None::<(usize, LevelFilter)>;
+
+        for (_, o) in &self.list {
+            match o.target.as_deref() {
+                Some(t) if Some(t) == group => return Some(o.level.into()),
+                Some(t) if is_target_prefix(t, target) => {
+                    if !matches!(by_target, Some((len, _)) if len >= t.len()) {
+                        by_target = Some((t.len(), o.level.into()));
+                    }
+                }
+                Some(_) => {}
+                None => global = Some(o.level.into()),
+            }
+        }
+
+        by_target.map(|(_, level)| level).or(global)
+    }
+
+    /// The maximum level that can be enabled by any override.
+    pub(crate) fn max_level(&self) -> LevelFilter {
+        let levels = self.list.iter().map(|(_, o)| LevelFilter::from(o.level));
+        levels.max().unwrap_or(LevelFilter::OFF)
+    }
+}
+
+fn is_target_prefix(prefix: &str, target: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn o(target: Option<&str>, level: LogLevel, sticky: bool) -> LogLevelOverride {
+        LogLevelOverride {
+            target: target.map(Into::into),
+            level,
+            duration: None,
+            sticky,
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let mut overrides = Overrides::default();
+        assert_eq!(overrides.level_for(Some("group"), "a::b"), None);
+
+        overrides.set(1, o(None, LogLevel::Warn, false));
+        overrides.set(2, o(Some("a"), LogLevel::Info, false));
+        overrides.set(3, o(Some("a::b"), LogLevel::Debug, false));
+        overrides.set(4, o(Some("group"), LogLevel::Trace, false));
+
+        let level = |group, target| overrides.level_for(group, target);
+        assert_eq!(level(Some("group"), "a::b"), Some(LevelFilter::TRACE));
+        assert_eq!(level(Some("other"), "a::b::c"), Some(LevelFilter::DEBUG));
+        assert_eq!(level(Some("other"), "a::c"), Some(LevelFilter::INFO));
+        assert_eq!(level(None, "ab"), Some(LevelFilter::WARN));
+        assert_eq!(overrides.max_level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn replace_and_clear() {
+        let mut overrides = Overrides::default();
+        assert!(overrides.set(1, o(None, LogLevel::Warn, false)).is_none());
+        assert!(overrides
+            .set(2, o(Some("a"), LogLevel::Info, true))
+            .is_none());
+
+        let prev = overrides.set(3, o(None, LogLevel::Debug, false)).unwrap();
+        assert_eq!(prev.level, LogLevel::Warn);
+
+        // Already replaced.
+        assert!(overrides.remove(1).is_none());
+
+        overrides.clear_non_sticky();
+        assert_eq!(overrides.iter().count(), 1);
+        assert_eq!(overrides.remove(2).unwrap().to_string(), "a=Info (sticky)");
+        assert!(overrides.is_empty());
+    }
+}
@@ -7,7 +7,9 @@ use elfo_core::scope;
 use elfo_utils::time::SystemTime;
 
 use self::visitor::Visitor;
-use crate::{stats, PreparedEvent, Shared, SpanData, StringId};
+use crate::{
+    backlog::Reservation, enricher, stats, FieldsId, PreparedEvent, Shared, SpanData, StringId,
+};
 
 mod visitor;
 
@@ -20,16 +22,29 @@ impl PrintingLayer {
         Self { shared }
     }
 
-    fn prepare(
-        &self,
-        simplify_message: bool,
-        f: impl FnOnce(&mut Visitor<'_>),
-    ) -> Option<StringId> {
-        self.shared.pool.create_with(|payload| {
-            let mut visitor = Visitor::new(&self.shared, payload, simplify_message);
+    fn prepare(&self, f: impl FnOnce(&mut Visitor<'_>)) -> Option<FieldsId> {
+        self.shared.fields.create_with(|fields| {
+            let mut visitor = Visitor::new(None, fields);
             f(&mut visitor);
         })
     }
+
+    fn prepare_event(&self, event: &Event<'_>) -> Option<(StringId, FieldsId)> {
+        let mut fields_id = None;
+        let message_id = self.shared.pool.create_with(|message| {
+            fields_id = self.shared.fields.create_with(|fields| {
+                event.record(&mut Visitor::new(Some(message), fields));
+            });
+        })?;
+
+        match fields_id {
+            Some(fields_id) => Some((message_id, fields_id)),
+            None => {
+                self.shared.pool.clear(message_id);
+                None
+            }
+        }
+    }
 }
 
 impl<S: Subscriber> Layer<S> for PrintingLayer {
@@ -40,60 +55,177 @@ impl<S: Subscriber> Layer<S> for PrintingLayer {
             let current_span = ctx.current_span();
             attrs.parent().or_else(|| current_span.id()).cloned()
         };
-        let payload_id = ward!(self.prepare(false, |visitor| attrs.record(visitor)));
-        let span = SpanData::new(parent_id, payload_id);
+        let fields_id = ward!(self.prepare(|visitor| attrs.record(visitor)));
+        let span = SpanData::new(parent_id, fields_id);
         self.shared.spans.insert(id.clone(), span);
     }
 
     fn on_record(&self, id: &span::Id, record: &span::Record<'_>, _: Context<'_, S>) {
         let mut data = ward!(self.shared.spans.get_mut(id));
-        let old_payload_id = data.payload_id;
-        let old_payload = ward!(self.shared.pool.get(old_payload_id));
+        let old_fields_id = data.fields_id;
+        let old_fields = ward!(self.shared.fields.get(old_fields_id));
 
-        let payload_id = ward!(self.prepare(false, |visitor| {
-            visitor.push(&old_payload);
-            record.record(visitor);
+        let fields_id = ward!(self.shared.fields.create_with(|fields| {
+            fields.extend(&old_fields);
+            record.record(&mut Visitor::new(None, fields));
         }));
 
-        self.shared.pool.clear(old_payload_id);
-        data.payload_id = payload_id;
+        drop(old_fields);
+        self.shared.fields.clear(old_fields_id);
+        data.fields_id = fields_id;
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        let current_span = ctx.current_span();
         let level = *event.metadata().level();
-        let payload_id = ward!(self.prepare(true, |visitor| event.record(visitor)), {
+
+        // Reserve before capturing, so nothing is rendered for dropped events.
+        let is_blocked = match self.shared.backlog.reserve(level) {
+            Reservation::Reserved => false,
+            Reservation::ReservedAfterBlocking => true,
+            Reservation::Rejected => {
+                stats::counter_per_level("elfo_lost_events_total", level);
+                return;
+            }
+        };
+
+        let current_span = ctx.current_span();
+        let (message_id, fields_id) = ward!(self.prepare_event(event), {
+            self.shared.backlog.release();
             stats::counter_per_level("elfo_lost_events_total", level);
             return;
         });
 
-        let data = scope::try_with(|scope| (scope.meta().clone(), scope.trace_id()));
-        let (object, trace_id) = match data {
-            Some((meta, trace_id)) => (Some(meta), Some(trace_id)),
-            None => (None, None),
+        let data = scope::try_with(|scope| {
+            (
+                scope.meta().clone(),
+                scope.trace_id(),
+                scope.sequence_no(),
+                enricher::enrich(&self.shared, scope),
+                scope.logging().fields(),
+            )
+        });
+        let (object, trace_id, sequence_no, meta_id, static_fields) = match data {
+            Some((meta, trace_id, sequence_no, meta_id, static_fields)) => (
+                Some(meta),
+                Some(trace_id),
+                sequence_no,
+                meta_id,
+                static_fields,
+            ),
+            None => (None, None, None, None, None),
         };
 
         let event = PreparedEvent {
             timestamp: SystemTime::now(),
             trace_id,
+            sequence_no,
             metadata: event.metadata(),
             object,
             span_id: event.parent().or_else(|| current_span.id()).cloned(),
-            payload_id,
+            message_id,
+            fields_id,
+            meta_id,
+            static_fields,
         };
 
+        // Fails only if the logger is terminated.
         let is_lost = self.shared.channel.try_send(event).is_err();
         if is_lost {
-            self.shared.pool.clear(payload_id);
+            self.shared.backlog.release();
+            self.shared.pool.clear(message_id);
+            self.shared.fields.clear(fields_id);
+            if let Some(meta_id) = meta_id {
+                self.shared.pool.clear(meta_id);
+            }
             stats::counter_per_level("elfo_lost_events_total", level);
         } else {
             stats::counter_per_level("elfo_emitted_events_total", level);
+
+            if is_blocked {
+                stats::counter_per_level("elfo_blocked_events_total", level);
+            }
         }
     }
 
     fn on_close(&self, id: span::Id, _: Context<'_, S>) {
         if let Some((_, data)) = self.shared.spans.remove(&id) {
-            self.shared.pool.clear(data.payload_id);
+            self.shared.fields.clear(data.fields_id);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::{prelude::*, registry::Registry};
+
+    use super::*;
+    use crate::{
+        config::{Channel, Multiline, Overflow},
+        fields::write_fields,
+        formatters::Output as _,
+        line_buffer::LineBuffer,
+        line_transaction::Line as _,
+        theme::PlainTheme,
+    };
+
+    fn received(shared: &Shared) -> Vec<String> {
+        let mut received = Vec::new();
+        let mut scratch = String::new();
+
+        while let Ok(event) = shared.channel.try_receive() {
+            let message = shared.pool.get(event.message_id).unwrap();
+            let fields = shared.fields.get(event.fields_id).unwrap();
+
+            let mut buffer = LineBuffer::with_capacity(64, usize::MAX);
+            let mut line = buffer.direct_write();
+            line.payload_mut().push_str(&message);
+            write_fields::<PlainTheme>(&mut line, &fields, Multiline::Escape, &None, &mut scratch);
+            assert!(line.try_commit());
+            received.push(buffer.as_str().trim_end().to_owned());
+
+            drop((message, fields));
+            shared.pool.clear(event.message_id);
+            shared.fields.clear(event.fields_id);
+            shared.backlog.release();
+        }
+
+        received
+    }
+
+    #[test]
+    fn capacity() {
+        let shared = crate::new().0.shared;
+        shared.backlog.configure(&Channel {
+            capacity: 3,
+            overflow: Overflow::DropNewest,
+            max_blocking: Default::default(),
+        });
+
+        let emit = |range: std::ops::Range<u32>| {
+            let subscriber = Registry::default().with(PrintingLayer::new(shared.clone()));
+            tracing::subscriber::with_default(subscriber, || {
+                for no in range {
+                    tracing::info!(no, "event");
+                }
+            });
+        };
+
+        // Nothing is lost below the capacity.
+        emit(0..3);
+        assert_eq!(
+            received(&shared),
+            ["event\tno=0", "event\tno=1", "event\tno=2"]
+        );
+
+        // New events are dropped above it.
+        emit(3..8);
+        assert_eq!(
+            received(&shared),
+            ["event\tno=3", "event\tno=4", "event\tno=5"]
+        );
+
+        // Dropped events don't occupy the channel.
+        emit(8..9);
+        assert_eq!(received(&shared), ["event\tno=8"]);
+    }
+}
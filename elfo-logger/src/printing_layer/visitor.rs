@@ -3,44 +3,39 @@ use std::{
     fmt::{self, Write},
 };
 
-use sharded_slab::Pool;
 use tracing::field::{Field, Visit};
 
-use crate::Shared;
+use crate::fields::Fields;
 
 const MAX_ERROR_SOURCES: u8 = 5;
 
 pub(super) struct Visitor<'a> {
-    pool: &'a Pool<String>,
-    output: &'a mut String,
-    simplify_message: bool,
+    /// The first `message` field is written here if provided.
+    message: Option<&'a mut String>,
+    fields: &'a mut Fields,
 }
 
 impl<'a> Visitor<'a> {
-    pub(super) fn new(shared: &'a Shared, output: &'a mut String, simplify_message: bool) -> Self {
-        Self {
-            pool: &shared.pool,
-            output,
-            simplify_message,
-        }
-    }
-
-    pub(super) fn push(&mut self, str: &str) {
-        self.output.push_str(str);
+    pub(super) fn new(message: Option<&'a mut String>, fields: &'a mut Fields) -> Self {
+        Self { message, fields }
     }
 }
 
 impl Visit for Visitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.push_f64(field.name(), value);
+    }
+
     fn record_i64(&mut self, field: &Field, value: i64) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.fields.push_i64(field.name(), value);
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.fields.push_u64(field.name(), value);
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.fields.push_bool(field.name(), value);
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
@@ -51,22 +46,19 @@ impl Visit for Visitor<'_> {
             return;
         }
 
-        if name == "message" && self.simplify_message {
-            self.simplify_message = false;
-            self.output.insert_str(0, value);
-        } else {
-            let _ = write!(self.output, "\t{name}={value}");
+        match self.message.take() {
+            Some(message) if name == "message" => message.push_str(value),
+            message => {
+                self.message = message;
+                self.fields.push_str(name, value);
+            }
         }
     }
 
     fn record_error(&mut self, field: &Field, mut value: &(dyn Error + 'static)) {
-        for i in 0..=MAX_ERROR_SOURCES {
-            let prev_len = self.output.len();
-            let suffix = Repeat(".source", i);
-
-            if write!(self.output, "\t{}{}={}", field.name(), suffix, value).is_err() {
-                self.output.truncate(prev_len);
-            }
+        for sources in 0..=MAX_ERROR_SOURCES {
+            self.fields
+                .push_error(field.name(), sources, format_args!("{value}"));
 
             if let Some(source) = value.source() {
                 value = source;
@@ -77,43 +69,19 @@ impl Visit for Visitor<'_> {
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        let prev_len = self.output.len();
+        let name = field.name();
 
-        let result = match field.name() {
-            "message" if self.simplify_message && self.output.is_empty() => {
-                self.simplify_message = false;
-                write!(self.output, "{value:?}")
-            }
-            "message" if self.simplify_message => {
-                self.simplify_message = false;
-                let mut result = Ok(());
-
-                if let Some(id) = self.pool.create_with(|tmp| {
-                    result = write!(tmp, "{value:?}");
-                    self.output.insert_str(0, tmp);
-                }) {
-                    self.pool.clear(id);
+        match self.message.take() {
+            Some(message) if name == "message" => {
+                let prev_len = message.len();
+                if write!(message, "{value:?}").is_err() {
+                    message.truncate(prev_len);
                 }
-
-                result
             }
-            _ => write!(self.output, "\t{}={:?}", field.name(), value),
-        };
-
-        if result.is_err() {
-            self.output.truncate(prev_len);
-        }
-    }
-}
-
-struct Repeat<'a>(&'a str, u8);
-
-impl fmt::Display for Repeat<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for _ in 0..self.1 {
-            f.write_str(self.0)?;
+            message => {
+                self.message = message;
+                self.fields.push_raw(name, format_args!("{value:?}"));
+            }
         }
-
-        Ok(())
     }
 }
@@ -0,0 +1,135 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use elfo_utils::time::Instant;
+
+use crate::config::Rotation;
+
+/// Tracks the size and the age of the log file to rotate it.
+pub(crate) struct Rotator {
+    size: u64,
+    started_at: Instant,
+}
+
+impl Rotator {
+    pub(crate) fn new() -> Self {
+        Self {
+            size: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Called once the file is (re)opened. The age is kept unless the file
+    /// is new, e.g. reopened after external rotation.
+    pub(crate) fn on_open(&mut self, size: u64) {
+        if size == 0 {
+            self.reset();
+        } else {
+            self.size = size;
+        }
+    }
+
+    pub(crate) fn on_write(&mut self, len: usize) {
+        self.size += len as u64;
+    }
+
+    /// Starts counting the size and the age anew.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns whether the file should be rotated before writing `len` bytes.
+    pub(crate) fn is_due(&self, config: &Rotation, len: usize) -> bool {
+        is_due(config, self.size, len, self.started_at.elapsed())
+    }
+}
+
+fn is_due(config: &Rotation, size: u64, len: usize, age: Duration) -> bool {
+    // Empty files aren't rotated, so lines are never split between files.
+    if size == 0 {
+        return false;
+    }
+
+    config
+        .max_size
+        .is_some_and(|max_size| size + len as u64 > max_size.as_u64())
+        || config.max_age.is_some_and(|max_age| age >= *max_age)
+}
+
+/// Renames `<path>` to `<path>.1`, shifting older files, and removes files
+/// beyond `max_files`, including ones left by a larger limit.
+pub(crate) async fn rotate(path: &Path, max_files: usize) -> io::Result<()> {
+    let mut no = max_files.max(1);
+    while ignore_not_found(tokio::fs::remove_file(numbered(path, no)).await)? {
+        no += 1;
+    }
+
+    for no in (1..max_files).rev() {
+        ignore_not_found(tokio::fs::rename(numbered(path, no), numbered(path, no + 1)).await)?;
+    }
+
+    if max_files > 0 {
+        tokio::fs::rename(path, numbered(path, 1)).await
+    } else {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+fn numbered(path: &Path, no: usize) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(format!(".{no}"));
+    path.into()
+}
+
+/// Returns `false` if the file doesn't exist.
+fn ignore_not_found(result: io::Result<()>) -> io::Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elfo_core::config::ByteSize;
+
+    use super::*;
+
+    fn rotation(max_size: Option<u64>, max_age: Option<u64>) -> Rotation {
+        Rotation {
+            max_size: max_size.map(ByteSize::new),
+            max_age: max_age.map(|secs| Duration::from_secs(secs).into()),
+            ..Rotation::default()
+        }
+    }
+
+    #[test]
+    fn due() {
+        let secs = Duration::from_secs;
+
+        // Not limited.
+        let config = rotation(None, None);
+        assert!(!is_due(&config, 1 << 40, 1 << 20, secs(1 << 20)));
+
+        let config = rotation(Some(100), Some(60));
+        assert!(!is_due(&config, 50, 50, secs(59)));
+        assert!(is_due(&config, 50, 51, secs(59)));
+        assert!(is_due(&config, 1, 0, secs(60)));
+
+        // Empty files aren't rotated even if lines don't fit.
+        assert!(!is_due(&config, 0, 200, secs(60)));
+    }
+
+    #[test]
+    fn numbering() {
+        assert_eq!(
+            numbered(Path::new("/var/log/app.log"), 2),
+            Path::new("/var/log/app.log.2")
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+
+use elfo_core::logging::_priv::StaticFields;
+
+use crate::{
+    fields::{escape, escape_json},
+    formatters::{Formatter, Output as _},
+    line_transaction::Line,
+    theme::{ColoredTheme, PlainTheme, Theme},
+};
+
+/// Static fields of groups (`system.logging.fields`) rendered once per
+/// config update instead of per line.
+///
+/// Groups share the same `StaticFields` instance until their config is
+/// changed, so instances are identified by their address.
+#[derive(Default)]
+pub(crate) struct StaticFieldsCache {
+    rendered: FxHashMap<usize, Rendered>,
+}
+
+impl StaticFieldsCache {
+    pub(crate) fn get(&mut self, fields: &Arc<StaticFields>) -> &Rendered {
+        let key = Arc::as_ptr(fields) as usize;
+
+        if !self.rendered.contains_key(&key) {
+            // Forget instances replaced by config updates.
+            self.rendered
+                .retain(|_, rendered| Arc::strong_count(&rendered.source) > 1);
+            self.rendered.insert(key, Rendered::new(fields.clone()));
+        }
+
+        &self.rendered[&key]
+    }
+}
+
+pub(crate) struct Rendered {
+    /// Keeps the instance alive, so its address isn't reused.
+    source: Arc<StaticFields>,
+    plain: Segments,
+    colored: Segments,
+    json: Segments,
+}
+
+/// `\t<key>="<value>"` (or `,"<key>":"<value>"` for JSON) for every field,
+/// in one buffer.
+struct Segments {
+    text: String,
+    ends: Vec<usize>,
+}
+
+impl Rendered {
+    fn new(source: Arc<StaticFields>) -> Self {
+        Self {
+            plain: Segments::new::<PlainTheme>(&source),
+            colored: Segments::new::<ColoredTheme>(&source),
+            json: Segments::new_json(&source),
+            source,
+        }
+    }
+
+    /// Writes fields, except the ones overridden by fields of the event.
+    /// Every field is ended separately, like dynamic ones.
+    pub(crate) fn write<T: Theme>(
+        &self,
+        line: &mut impl Line,
+        is_overridden: impl Fn(&str) -> bool,
+    ) {
+        let segments = if T::IS_COLORED {
+            &self.colored
+        } else {
+            &self.plain
+        };
+
+        self.write_segments(segments, line, is_overridden);
+    }
+
+    /// Writes fields of the JSON line, see [`Rendered::write()`].
+    pub(crate) fn write_json(&self, line: &mut impl Line, is_overridden: impl Fn(&str) -> bool) {
+        self.write_segments(&self.json, line, is_overridden);
+    }
+
+    fn write_segments(
+        &self,
+        segments: &Segments,
+        line: &mut impl Line,
+        is_overridden: impl Fn(&str) -> bool,
+    ) {
+        let mut start = 0;
+        for ((name, _), &end) in self.source.iter().zip(&segments.ends) {
+            if !is_overridden(name) {
+                line.fields_mut().push_str(&segments.text[start..end]);
+                line.end_field();
+            }
+            start = end;
+        }
+    }
+}
+
+impl Segments {
+    fn new<T: Theme>(fields: &StaticFields) -> Self {
+        let mut text = String::new();
+        let mut ends = Vec::new();
+        let mut escaped = String::new();
+
+        for (name, value) in fields.iter() {
+            text.push('\t');
+            T::FieldKey::fmt(&mut text, name);
+            text.push_str("=\"");
+            // Newlines are always escaped, the multiline policy is per line.
+            escaped.clear();
+            escape(&mut escaped, value, true);
+            text.push_str(&escaped.replace('\n', "\\n"));
+            text.push('"');
+            ends.push(text.len());
+        }
+
+        Self { text, ends }
+    }
+
+    fn new_json(fields: &StaticFields) -> Self {
+        let mut text = String::new();
+        let mut ends = Vec::new();
+
+        for (name, value) in fields.iter() {
+            text.push_str(",\"");
+            escape_json(&mut text, name);
+            text.push_str("\":\"");
+            escape_json(&mut text, value);
+            text.push('"');
+            ends.push(text.len());
+        }
+
+        Self { text, ends }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        line_buffer::LineBuffer,
+        line_transaction::{FailOnUnfit, LineFactory},
+    };
+
+    fn static_fields(pairs: &[(&str, &str)]) -> Arc<StaticFields> {
+        let pairs = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        Arc::new(pairs.collect())
+    }
+
+    fn render(rendered: &Rendered, dynamic: &[&str]) -> String {
+        let mut buffer = LineBuffer::with_capacity(1024, usize::MAX);
+        let mut line = FailOnUnfit::create_line(&mut buffer);
+        line.meta_mut().push_str("meta -");
+        rendered.write::<PlainTheme>(&mut line, |name| dynamic.contains(&name));
+        assert!(line.try_commit());
+        buffer.as_str().trim_end_matches('\n').to_owned()
+    }
+
+    #[test]
+    fn presence_and_collisions() {
+        let mut cache = StaticFieldsCache::default();
+        let fields = static_fields(&[("team", "pay\"ments\n"), ("subsystem", "billing")]);
+
+        let rendered = cache.get(&fields);
+        assert_eq!(
+            render(rendered, &[]),
+            "meta -\tsubsystem=\"billing\"\tteam=\"pay\\\"ments\\n\""
+        );
+
+        // Dynamic values win.
+        assert_eq!(render(rendered, &["team"]), "meta -\tsubsystem=\"billing\"");
+        assert_eq!(render(rendered, &["team", "subsystem"]), "meta -");
+
+        let mut buffer = LineBuffer::with_capacity(1024, usize::MAX);
+        let mut line = FailOnUnfit::create_line(&mut buffer);
+        rendered.write_json(&mut line, |name| name == "subsystem");
+        assert!(line.try_commit());
+        assert_eq!(buffer.as_str(), ",\"team\":\"pay\\\"ments\\n\"\n");
+    }
+
+    #[test]
+    fn rendered_once_per_instance() {
+        let mut cache = StaticFieldsCache::default();
+        let fields = static_fields(&[("subsystem", "billing")]);
+
+        // Lines reuse the pre-rendered text, nothing is allocated per line.
+        let ptr = cache.get(&fields).plain.text.as_ptr();
+        for _ in 0..3 {
+            let rendered = cache.get(&fields);
+            assert_eq!(rendered.plain.text.as_ptr(), ptr);
+            assert_eq!(render(rendered, &[]), "meta -\tsubsystem=\"billing\"");
+        }
+
+        // A config update replaces the instance.
+        drop(fields);
+        let fields = static_fields(&[("subsystem", "risk")]);
+        assert_eq!(
+            render(cache.get(&fields), &[]),
+            "meta -\tsubsystem=\"risk\""
+        );
+        assert_eq!(cache.rendered.len(), 1);
+    }
+}
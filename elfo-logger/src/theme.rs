@@ -1,45 +1,43 @@
-use std::sync::Arc;
-
 use tracing::Level;
 
-use elfo_core::{tracing::TraceId, ActorMeta};
-use elfo_utils::time::SystemTime;
+use elfo_core::tracing::TraceId;
 
 use crate::formatters::*;
 
 pub(crate) trait Theme {
-    type Timestamp: Formatter<SystemTime>;
+    /// Whether ANSI styles are used, to select pre-rendered parts of lines.
+    const IS_COLORED: bool;
+
     type Level: Formatter<Level>;
     type TraceId: Formatter<Option<TraceId>>;
-    type ActorMeta: Formatter<Option<Arc<ActorMeta>>>;
+    type ActorMeta: Formatter<Option<ActorPrefix>>;
     type Payload: Formatter<str>;
-    type Location: Formatter<(&'static str, u32)>;
-    type Module: Formatter<str>;
+    type FieldKey: Formatter<str>;
     type ResetStyle: Formatter<()>;
 }
 
 pub(crate) struct PlainTheme;
 
 impl Theme for PlainTheme {
-    type ActorMeta = EmptyIfNone<Arc<ActorMeta>>;
+    const IS_COLORED: bool = false;
+
+    type ActorMeta = EmptyIfNone<ActorPrefix>;
+    type FieldKey = FieldKey;
     type Level = Level;
-    type Location = Location;
-    type Module = Module;
     type Payload = Payload;
     type ResetStyle = DoNothing;
-    type Timestamp = Rfc3339Weak;
     type TraceId = EmptyIfNone<TraceId>;
 }
 
 pub(crate) struct ColoredTheme;
 
 impl Theme for ColoredTheme {
-    type ActorMeta = EmptyIfNone<ColoredByHash<Arc<ActorMeta>>>;
+    const IS_COLORED: bool = true;
+
+    type ActorMeta = EmptyIfNone<ColoredByHash<ActorPrefix>>;
+    type FieldKey = ColoredFieldKey;
     type Level = ColoredLevel;
-    type Location = ColoredLocation;
-    type Module = ColoredModule;
-    type Payload = ColoredPayload;
+    type Payload = Payload;
     type ResetStyle = ResetStyle;
-    type Timestamp = Rfc3339Weak;
     type TraceId = EmptyIfNone<ColoredByHash<TraceId>>;
 }
@@ -0,0 +1,447 @@
+use std::fmt;
+
+use elfo_utils::time::SystemTime;
+
+use crate::{
+    config::{Timestamp, TimestampFormat, Timezone},
+    formatters::Output,
+};
+
+const ISO8601_UTC: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+const ISO8601_LOCAL: &str = "%Y-%m-%dT%H:%M:%S%.6f%:z";
+
+/// A timestamp format compiled once per config update.
+///
+/// Writes directly into the provided buffer without allocations.
+pub(crate) struct TimestampFormatter {
+    items: Vec<Item>,
+    timezone: Timezone,
+    offset: CachedOffset,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Item {
+    Literal(String),
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Fraction { digits: u8, dot: bool },
+    UnixSecs,
+    UnixNanos,
+    Offset { colon: bool },
+}
+
+/// An error in a custom timestamp format.
+#[derive(Debug, PartialEq)]
+pub(crate) struct FormatError {
+    /// A byte position of the invalid specifier.
+    pub(crate) position: usize,
+    pub(crate) reason: &'static str,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+impl TimestampFormatter {
+    pub(crate) fn new(config: &Timestamp) -> Self {
+        let items = match config.format {
+            TimestampFormat::Iso8601 => match config.timezone {
+                Timezone::Utc => compile(ISO8601_UTC),
+                Timezone::Local => compile(ISO8601_LOCAL),
+            },
+            TimestampFormat::UnixNanos => Ok(vec![Item::UnixNanos]),
+            TimestampFormat::Custom => compile(&config.custom),
+        };
+
+        Self {
+            // The format is validated while deserializing the config.
+            items: items.expect("invalid timestamp format"),
+            timezone: config.timezone,
+            offset: CachedOffset::default(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, out: &mut impl Output, time: SystemTime) {
+        let unix_nanos = time.to_unix_time_nanos();
+        let unix_secs = (unix_nanos / 1_000_000_000) as i64;
+        let nanos = (unix_nanos % 1_000_000_000) as u32;
+
+        let offset = match self.timezone {
+            Timezone::Utc => 0,
+            Timezone::Local => self.offset.get(unix_secs),
+        };
+
+        let local_secs = unix_secs + i64::from(offset);
+        let days = local_secs.div_euclid(86_400);
+        let secs_of_day = local_secs.rem_euclid(86_400) as u32;
+        let (year, month, day) = civil_from_days(days);
+
+        for item in &self.items {
+            match item {
+                Item::Literal(s) => out.push_str(s),
+                Item::Year => push_padded(out, year as u64, 4),
+                Item::ShortYear => push_padded(out, year.rem_euclid(100) as u64, 2),
+                Item::Month => push_padded(out, month.into(), 2),
+                Item::Day => push_padded(out, day.into(), 2),
+                Item::Hour => push_padded(out, (secs_of_day / 3600).into(), 2),
+                Item::Minute => push_padded(out, (secs_of_day / 60 % 60).into(), 2),
+                Item::Second => push_padded(out, (secs_of_day % 60).into(), 2),
+                Item::Fraction { digits, dot } => {
+                    if *dot {
+                        out.push('.');
+                    }
+                    let divisor = 10u32.pow(9 - u32::from(*digits));
+                    push_padded(out, (nanos / divisor).into(), (*digits).into());
+                }
+                Item::UnixSecs => push_padded(out, unix_secs as u64, 1),
+                Item::UnixNanos => push_padded(out, unix_nanos, 1),
+                Item::Offset { colon } => {
+                    out.push(if offset < 0 { '-' } else { '+' });
+                    let offset = offset.unsigned_abs() / 60;
+                    push_padded(out, (offset / 60).into(), 2);
+                    if *colon {
+                        out.push(':');
+                    }
+                    push_padded(out, (offset % 60).into(), 2);
+                }
+            }
+        }
+    }
+}
+
+/// Compiles a `strftime`-like format.
+///
+/// Supported specifiers: `%Y`, `%y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%F`
+/// (`%Y-%m-%d`), `%T` (`%H:%M:%S`), `%f` (nanoseconds), `%3f`, `%6f`, `%9f`,
+/// `%.3f`, `%.6f`, `%.9f`, `%s` (unix seconds), `%z` (`+hhmm`), `%:z`
+/// (`+hh:mm`) and `%%`.
+pub(crate) fn compile(format: &str) -> Result<Vec<Item>, FormatError> {
+    let mut items = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+
+    let push = |items: &mut Vec<Item>, literal: &mut String, item| {
+        if !literal.is_empty() {
+            items.push(Item::Literal(std::mem::take(literal)));
+        }
+        items.push(item);
+    };
+
+    while let Some(idx) = rest.find('%') {
+        literal.push_str(&rest[..idx]);
+        let position = format.len() - rest.len() + idx;
+        let spec = &rest[idx + 1..];
+
+        let fraction = |digits, dot| Some(Item::Fraction { digits, dot });
+
+        let (item, len) = match spec.as_bytes() {
+            [b'%', ..] => {
+                literal.push('%');
+                (None, 1)
+            }
+            [b'Y', ..] => (Some(Item::Year), 1),
+            [b'y', ..] => (Some(Item::ShortYear), 1),
+            [b'm', ..] => (Some(Item::Month), 1),
+            [b'd', ..] => (Some(Item::Day), 1),
+            [b'H', ..] => (Some(Item::Hour), 1),
+            [b'M', ..] => (Some(Item::Minute), 1),
+            [b'S', ..] => (Some(Item::Second), 1),
+            [b's', ..] => (Some(Item::UnixSecs), 1),
+            [b'z', ..] => (Some(Item::Offset { colon: false }), 1),
+            [b':', b'z', ..] => (Some(Item::Offset { colon: true }), 2),
+            [b'f', ..] => (fraction(9, false), 1),
+            [d @ (b'3' | b'6' | b'9'), b'f', ..] => (fraction(d - b'0', false), 2),
+            [b'.', d @ (b'3' | b'6' | b'9'), b'f', ..] => (fraction(d - b'0', true), 3),
+            [b'F', ..] => {
+                push(&mut items, &mut literal, Item::Year);
+                literal.push('-');
+                push(&mut items, &mut literal, Item::Month);
+                literal.push('-');
+                (Some(Item::Day), 1)
+            }
+            [b'T', ..] => {
+                push(&mut items, &mut literal, Item::Hour);
+                literal.push(':');
+                push(&mut items, &mut literal, Item::Minute);
+                literal.push(':');
+                (Some(Item::Second), 1)
+            }
+            [] => {
+                return Err(FormatError {
+                    position,
+                    reason: "incomplete specifier",
+                })
+            }
+            _ => {
+                return Err(FormatError {
+                    position,
+                    reason: "unknown specifier",
+                })
+            }
+        };
+
+        if let Some(item) = item {
+            push(&mut items, &mut literal, item);
+        }
+
+        rest = &spec[len..];
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        items.push(Item::Literal(literal));
+    }
+
+    Ok(items)
+}
+
+fn push_padded(out: &mut impl Output, mut value: u64, width: usize) {
+    let mut digits = [b'0'; 20];
+    let mut len = 0;
+
+    while value > 0 || len == 0 {
+        digits[len] = b'0' + (value % 10) as u8;
+        value /= 10;
+        len += 1;
+    }
+
+    for _ in len..width {
+        out.push('0');
+    }
+
+    for &digit in digits[..len].iter().rev() {
+        out.push(digit as char);
+    }
+}
+
+/// Converts days since the unix epoch to `(year, month, day)`.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The local UTC offset, recalculated once per hour to catch DST changes.
+#[derive(Default)]
+struct CachedOffset {
+    since: i64,
+    until: i64,
+    offset: i32,
+}
+
+impl CachedOffset {
+    fn get(&mut self, unix_secs: i64) -> i32 {
+        if !(self.since..self.until).contains(&unix_secs) {
+            self.since = unix_secs - unix_secs.rem_euclid(3600);
+            self.until = self.since + 3600;
+            self.offset = local_offset(unix_secs);
+        }
+
+        self.offset
+    }
+}
+
+#[cfg(unix)]
+fn local_offset(unix_secs: i64) -> i32 {
+    let time = unix_secs as libc::time_t;
+    // SAFETY: `tm` is a plain C struct, all zeros is a valid value.
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    // SAFETY: both pointers are valid during the call, `localtime_r` is
+    // the reentrant version of `localtime`.
+    let res = unsafe { libc::localtime_r(&time, &mut tm) };
+
+    if res.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i32
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_unix_secs: i64) -> i32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use super::*;
+
+    // 2023-11-14 22:13:20.123456789 UTC
+    const TIME: u64 = 1_700_000_000_123_456_789;
+
+    fn format(config: Timestamp, time: u64) -> String {
+        let mut out = String::new();
+        let mut formatter = TimestampFormatter::new(&config);
+        formatter.write(&mut out, SystemTime::from_unix_time_nanos(time));
+        out
+    }
+
+    fn custom(format: &str) -> Timestamp {
+        Timestamp {
+            format: TimestampFormat::Custom,
+            custom: format.into(),
+            timezone: Timezone::Utc,
+        }
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(
+            format(Timestamp::default(), TIME),
+            "2023-11-14 22:13:20.123456789"
+        );
+    }
+
+    #[test]
+    fn iso8601() {
+        let config = Timestamp {
+            format: TimestampFormat::Iso8601,
+            ..Timestamp::default()
+        };
+        assert_eq!(format(config, TIME), "2023-11-14T22:13:20.123456Z");
+
+        let config = Timestamp {
+            format: TimestampFormat::Iso8601,
+            timezone: Timezone::Local,
+            ..Timestamp::default()
+        };
+        let line = format(config, TIME);
+        let (datetime, offset) = line.split_at(line.len() - 6);
+        assert_eq!(datetime.len(), "2023-11-14T22:13:20.123456".len());
+        assert!(offset.starts_with(['+', '-']));
+        assert_eq!(&offset[3..4], ":");
+    }
+
+    #[test]
+    fn unix_nanos() {
+        assert_eq!(format(unix_nanos_config(), TIME), "1700000000123456789");
+        assert_eq!(format(unix_nanos_config(), 0), "0");
+    }
+
+    fn unix_nanos_config() -> Timestamp {
+        Timestamp {
+            format: TimestampFormat::UnixNanos,
+            ..Timestamp::default()
+        }
+    }
+
+    #[test]
+    fn custom_formats() {
+        let cases = [
+            ("%Y-%m-%d %H:%M:%S%.6f", "2023-11-14 22:13:20.123456"),
+            ("%F %T%.3f", "2023-11-14 22:13:20.123"),
+            ("%y%m%d-%H%M%S.%3f", "231114-221320.123"),
+            ("%s.%9f", "1700000000.123456789"),
+            ("%s %f", "1700000000 123456789"),
+            ("%T%z", "22:13:20+0000"),
+            ("%T%:z", "22:13:20+00:00"),
+            ("100%% at %H", "100% at 22"),
+            ("no specifiers", "no specifiers"),
+            ("", ""),
+        ];
+
+        for (fmt, expected) in cases {
+            assert_eq!(format(custom(fmt), TIME), expected, "{fmt}");
+        }
+
+        assert_eq!(format(custom("%F %T"), 0), "1970-01-01 00:00:00");
+        // 2000-02-29 (a leap day).
+        assert_eq!(
+            format(custom("%F %T"), 951_782_400 * 1_000_000_000),
+            "2000-02-29 00:00:00"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let error = |position, reason| Err(FormatError { position, reason });
+
+        assert_eq!(compile("%Y-%Q"), error(3, "unknown specifier"));
+        assert_eq!(compile("%Y %.4f"), error(3, "unknown specifier"));
+        assert_eq!(compile("%Y %"), error(3, "incomplete specifier"));
+        assert_eq!(compile("ük%x"), error(3, "unknown specifier"));
+    }
+
+    #[test]
+    fn literals_are_merged() {
+        assert_eq!(
+            compile("a%%b%Y").unwrap(),
+            vec![Item::Literal("a%b".into()), Item::Year]
+        );
+    }
+
+    // === No allocations ===
+
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // SAFETY: delegates to `System`.
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            // SAFETY: the same contract as the caller's one.
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // SAFETY: the same contract as the caller's one.
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn no_allocations() {
+        let configs = [
+            Timestamp::default(),
+            custom("%F %T%.3f %:z %s"),
+            unix_nanos_config(),
+            Timestamp {
+                format: TimestampFormat::Iso8601,
+                timezone: Timezone::Local,
+                ..Timestamp::default()
+            },
+        ];
+
+        for config in configs {
+            let mut formatter = TimestampFormatter::new(&config);
+            let mut out = String::with_capacity(1024);
+
+            // Warm up the cached offset.
+            formatter.write(&mut out, SystemTime::from_unix_time_nanos(TIME));
+
+            let before = ALLOCATIONS.with(Cell::get);
+            for i in 0..10_000 {
+                out.clear();
+                let time = SystemTime::from_unix_time_nanos(TIME + i * 1_000);
+                formatter.write(&mut out, time);
+            }
+            assert_eq!(ALLOCATIONS.with(Cell::get), before);
+        }
+    }
+}
@@ -5,7 +5,7 @@ use syn::{
     parse::{Error as ParseError, Parse, ParseStream},
     parse_macro_input,
     spanned::Spanned,
-    Data, DeriveInput, Ident, LitStr, Path, Token, Type,
+    Attribute, Data, DeriveInput, Fields, Ident, LitStr, Path, Token, Type,
 };
 
 use crate::errors::emit_error;
@@ -14,10 +14,13 @@ use crate::errors::emit_error;
 struct MessageArgs {
     name: Option<LitStr>,
     protocol: Option<LitStr>,
+    aliases: Vec<LitStr>,
     ret: Option<Type>,
     part: bool,
     transparent: bool,
+    strict: bool,
     dumping_allowed: Option<bool>,
+    deprecated: Option<LitStr>,
     crate_: Option<Path>,
     not: Vec<String>,
 }
@@ -28,9 +31,12 @@ impl Parse for MessageArgs {
             ret: None,
             name: None,
             protocol: None,
+            aliases: Vec::new(),
             part: false,
             transparent: false,
+            strict: false,
             dumping_allowed: None,
+            deprecated: None,
             crate_: None,
             not: Vec::new(),
         };
@@ -38,12 +44,15 @@ impl Parse for MessageArgs {
         // `#[message]`
         // `#[message(name = "N")]`
         // `#[message(protocol = "P")]`
+        // `#[message(alias = "A", alias = "P/A")]`
         // `#[message(ret = A)]`
         // `#[message(part)]`
         // `#[message(part, transparent)]`
+        // `#[message(strict)]`
         // `#[message(elfo = some)]`
         // `#[message(not(Debug))]`
         // `#[message(dumping = "disabled")]`
+        // `#[message(deprecated = "use OrderAcceptedV2")]`
         while !input.is_empty() {
             let ident: Ident = input.parse()?;
 
@@ -56,12 +65,17 @@ impl Parse for MessageArgs {
                     let _: Token![=] = input.parse()?;
                     args.protocol = Some(input.parse()?);
                 }
+                "alias" => {
+                    let _: Token![=] = input.parse()?;
+                    args.aliases.push(input.parse()?);
+                }
                 "ret" => {
                     let _: Token![=] = input.parse()?;
                     args.ret = Some(input.parse()?);
                 }
                 "part" => args.part = true,
                 "transparent" => args.transparent = true,
+                "strict" => args.strict = true,
                 "dumping" => {
                     // TODO: introduce `DumpingMode`.
                     let _: Token![=] = input.parse()?;
@@ -73,6 +87,10 @@ impl Parse for MessageArgs {
                         return Err(input.error("only `dumping = \"disabled\"` is supported"));
                     }
                 }
+                "deprecated" => {
+                    let _: Token![=] = input.parse()?;
+                    args.deprecated = Some(input.parse()?);
+                }
                 // TODO: call it `crate` like in linkme?
                 "elfo" => {
                     let _: Token![=] = input.parse()?;
@@ -112,6 +130,23 @@ impl MessageArgs {
             incompatible(&self.name, "name");
             incompatible(&self.protocol, "protocol");
             incompatible(&self.dumping_allowed, "dumping_allowed");
+            incompatible(&self.aliases.first(), "alias");
+            incompatible(&self.deprecated, "deprecated");
+        }
+
+        for alias in &self.aliases {
+            let value = alias.value();
+            let name = value.split_once('/').map_or(&value[..], |(_, name)| name);
+            if name.is_empty() || value.matches('/').count() > 1 {
+                emit_error!(alias.span(), "alias must be `Name` or `protocol/Name`");
+            }
+        }
+
+        if self.strict && self.transparent {
+            emit_error!(
+                proc_macro2::Span::call_site(),
+                "`strict` and `transparent` attributes are incompatible"
+            );
         }
     }
 }
@@ -155,6 +190,55 @@ fn gen_impl_debug(input: &DeriveInput) -> TokenStream {
     }
 }
 
+/// Hashes the shape of the type: names and types of fields and variants
+/// with their `serde` attributes. It's enough to detect most incompatible
+/// changes for non-self-describing formats (e.g. postcard), but changes of
+/// nested types aren't taken into account.
+fn gen_schema_hash(input: &DeriveInput) -> u64 {
+    fn add_attrs(schema: &mut String, attrs: &[Attribute]) {
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            schema.push_str(&attr.to_token_stream().to_string());
+        }
+    }
+
+    fn add_fields(schema: &mut String, fields: &Fields) {
+        schema.push('{');
+        for field in fields {
+            add_attrs(schema, &field.attrs);
+            if let Some(ident) = &field.ident {
+                schema.push_str(&ident.to_string());
+            }
+            schema.push(':');
+            schema.push_str(&field.ty.to_token_stream().to_string());
+            schema.push(',');
+        }
+        schema.push('}');
+    }
+
+    let mut schema = String::new();
+    add_attrs(&mut schema, &input.attrs);
+
+    match &input.data {
+        Data::Struct(data) => add_fields(&mut schema, &data.fields),
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                add_attrs(&mut schema, &variant.attrs);
+                schema.push_str(&variant.ident.to_string());
+                add_fields(&mut schema, &variant.fields);
+            }
+        }
+        Data::Union(_) => {}
+    }
+
+    // Formatting of tokens can differ between compilers.
+    schema.retain(|c| !c.is_whitespace());
+
+    // FNV-1a, which is stable across builds.
+    schema.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
 /// Implementation of the `#[message]` macro.
 pub fn message_impl(
     args: proc_macro::TokenStream,
@@ -193,6 +277,9 @@ pub fn message_impl(
         .then(|| quote! { #[serde(crate = #serde_crate)] });
 
     let serde_transparent_attr = args.transparent.then(|| quote! { #[serde(transparent)] });
+    let serde_strict_attr = args
+        .strict
+        .then(|| quote! { #[serde(deny_unknown_fields)] });
 
     // TODO: pass to `ElfoResponseWrapper`.
     let dumping_allowed = args.dumping_allowed.unwrap_or(true);
@@ -204,6 +291,19 @@ pub fn message_impl(
     };
 
     let impl_message = (!args.part).then(|| {
+        let schema_hash = gen_schema_hash(&input);
+        let response = match &args.ret {
+            Some(ret) => quote! {
+                ::std::option::Option::Some(::std::any::type_name::<#ret> as fn() -> &'static str)
+            },
+            None => quote! { ::std::option::Option::None },
+        };
+        let aliases = &args.aliases;
+        let deprecated = match &args.deprecated {
+            Some(note) => quote! { ::std::option::Option::Some(#note) },
+            None => quote! { ::std::option::Option::None },
+        };
+
         quote! {
             impl #crate_::Message for #name {
                 #[inline(always)]
@@ -222,7 +322,12 @@ pub fn message_impl(
             static VTABLE: &#internal::MessageVTable = &#internal::MessageVTable::new::<#name>(
                 #name_str,
                 #protocol,
-                #dumping_allowed
+                ::std::concat!(::std::module_path!(), "::", ::std::stringify!(#name)),
+                #dumping_allowed,
+                #schema_hash,
+                #response,
+                &[#(#aliases),*],
+                #deprecated
             );
         }
     });
@@ -230,6 +335,10 @@ pub fn message_impl(
     let impl_request = args.ret.as_ref().map(|ret| {
         let wrapper_name_str = format!("{name_str}::Response");
         let protocol = args.protocol.as_ref().map(|p| quote! { protocol = #p, });
+        let aliases = args.aliases.iter().map(|alias| {
+            let alias = format!("{}::Response", alias.value());
+            quote! { alias = #alias, }
+        });
 
         quote! {
             impl #crate_::Request for #name {
@@ -237,7 +346,7 @@ pub fn message_impl(
                 type Wrapper = ElfoResponseWrapper;
             }
 
-            #[message(not(Debug), #protocol name = #wrapper_name_str, elfo = #crate_)]
+            #[message(not(Debug), #protocol #(#aliases)* name = #wrapper_name_str, elfo = #crate_)]
             pub struct ElfoResponseWrapper(#ret);
 
             impl ::std::fmt::Debug for ElfoResponseWrapper {
@@ -274,6 +383,7 @@ pub fn message_impl(
         #derive_deserialize
         #serde_crate_attr
         #serde_transparent_attr
+        #serde_strict_attr
         #input
 
         #[doc(hidden)]
@@ -23,6 +23,9 @@ pub fn msg_core(input: TokenStream) -> TokenStream {
 /// * `part` — do not derive `Message`. Useful for parts of messages.
 /// * `ret = SomeType` — also derive `Request` with the provided response type.
 /// * `name = "SomeName"` — override a message name.
+/// * `strict` — reject unknown fields instead of ignoring them while decoding
+///   messages from other nodes. By default, unknown fields are silently
+///   dropped to support rolling upgrades.
 /// * `not(Debug)` — do not derive `Debug`. Useful for custom instances.
 /// * `not(Clone)` — the same for `Clone`.
 /// * `elfo = some::path` — override a path to elfo.
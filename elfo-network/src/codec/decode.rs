@@ -1,16 +1,27 @@
-use std::{convert::TryFrom, io::Cursor};
+use std::{convert::TryFrom, io::Cursor, time::Duration};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use eyre::{ensure, eyre, Error, WrapErr};
+use metrics::counter;
 use tracing::error;
 
-use elfo_core::{errors::RequestError, tracing::TraceId, AnyMessage, RequestId};
-use elfo_utils::likely;
+use elfo_core::{
+    errors::ErrorKind, scope, tracing::TraceId, AnyMessage, IdempotencyKey, Message, RequestId,
+    RequestLimits,
+};
+use elfo_utils::{likely, unlikely};
 
-use crate::codec::format::{
-    NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, FLAG_IS_LAST_RESPONSE, KIND_MASK,
-    KIND_REGULAR, KIND_REQUEST_ALL, KIND_REQUEST_ANY, KIND_RESPONSE_FAILED, KIND_RESPONSE_IGNORED,
-    KIND_RESPONSE_OK,
+use crate::{
+    codec::format::{
+        decode_ack_status, NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, TraceIdWidth,
+        FLAG_HAS_IDEMPOTENCY_KEY, FLAG_HAS_LIMITS, FLAG_HAS_NONCE, FLAG_IS_CANCELLED,
+        FLAG_IS_FIRST_CHUNK, FLAG_IS_FORCE_SAMPLED, FLAG_IS_LAST_CHUNK, FLAG_IS_LAST_RESPONSE,
+        KIND_ACK, KIND_CHUNK, KIND_MASK, KIND_REGULAR, KIND_REGULAR_ACKED, KIND_REQUEST_ALL,
+        KIND_REQUEST_ANY, KIND_RESPONSE_DECODE_ERROR, KIND_RESPONSE_FAILED,
+        KIND_RESPONSE_FORBIDDEN, KIND_RESPONSE_IGNORED, KIND_RESPONSE_LIMIT_EXCEEDED,
+        KIND_RESPONSE_NO_ROUTE, KIND_RESPONSE_OK, KIND_RESPONSE_TIMEOUT, KIND_RESPONSE_UNSUPPORTED,
+    },
+    config::Codec,
 };
 
 #[derive(Default)]
@@ -19,6 +30,8 @@ pub(crate) struct DecodeStats {
     pub(crate) total_messages_decoded: u64,
     /// How many messages were skipped because of non-fatal decoding errors.
     pub(crate) total_messages_decoding_skipped: u64,
+    /// How many unknown fields were ignored while decoding messages.
+    pub(crate) total_fields_ignored: u64,
 }
 
 #[derive(Debug)]
@@ -27,7 +40,11 @@ pub(crate) struct EnvelopeDetails {
     pub(crate) sender: NetworkAddr,
     pub(crate) recipient: NetworkAddr,
     pub(crate) request_id: Option<RequestId>,
+    /// The sequence number if the message should be acknowledged.
+    pub(crate) ack_seq: Option<u64>,
     pub(crate) trace_id: TraceId,
+    /// The message is skipped, because it's unknown to this node.
+    pub(crate) is_unknown_message: bool,
 }
 
 pub(crate) enum DecodeState {
@@ -48,7 +65,12 @@ pub(crate) enum DecodeState {
     },
 }
 
-pub(crate) fn decode(input: &[u8], stats: &mut DecodeStats) -> eyre::Result<DecodeState> {
+pub(crate) fn decode(
+    input: &[u8],
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
+    stats: &mut DecodeStats,
+) -> eyre::Result<DecodeState> {
     if input.len() < 4 {
         return Ok(DecodeState::NeedMoreData {
             total_length_estimate: 4,
@@ -64,25 +86,52 @@ pub(crate) fn decode(input: &[u8], stats: &mut DecodeStats) -> eyre::Result<Deco
         });
     }
 
-    let decode_result = do_decode(&mut src);
+    // Restrict the cursor to the current envelope.
+    let mut src = Cursor::new(&input[..size]);
+    src.set_position(4);
+
+    let decode_result = do_decode(&mut src, codec, trace_id_width, stats);
     if likely(decode_result.is_ok()) {
-        stats.total_messages_decoded += 1;
+        let decoded = decode_result.unwrap();
+
+        // Chunks are counted once the message is reassembled.
+        if !matches!(decoded.payload, NetworkEnvelopePayload::Chunk { .. }) {
+            stats.total_messages_decoded += 1;
+        }
+
         return Ok(DecodeState::Done {
             bytes_consumed: size,
-            decoded: decode_result.unwrap(),
+            decoded,
         });
     }
 
     stats.total_messages_decoding_skipped += 1;
 
-    // TODO: cooldown/metrics.
+    // TODO: cooldown.
     let DecodeError { message, details } = decode_result.unwrap_err();
+    let protocol = message.protocol.as_deref().unwrap_or("<unknown>");
+    let name = message.name.as_deref().unwrap_or("<unknown>");
+
+    // Names come from the remote node, so only registered ones are used as
+    // labels to keep the number of metrics bounded.
+    let (protocol_label, name_label) =
+        AnyMessage::lookup_name(protocol, name).unwrap_or(("<unknown>", "<unknown>"));
+
+    counter!(
+        "elfo_network_decoding_errors_total", 1,
+        "protocol" => protocol_label,
+        "message" => name_label,
+        "codec" => codec.to_string(),
+    );
+
     if let Some(details) = &details {
         error!(
             message = "cannot decode message, skipping",
             error = format!("{:#}", message.error),
-            protocol = message.protocol.as_deref().unwrap_or("<unknown>"),
-            name = message.name.as_deref().unwrap_or("<unknown>"),
+            protocol,
+            name,
+            %codec,
+            peer = ?details.sender.into_remote().node_no(),
             kind = ?details.kind,
             sender = %details.sender,
             recipient = %details.recipient,
@@ -93,21 +142,53 @@ pub(crate) fn decode(input: &[u8], stats: &mut DecodeStats) -> eyre::Result<Deco
         error!(
             message = "cannot decode message, skipping",
             error = format!("{:#}", message.error),
-            protocol = message.protocol.as_deref().unwrap_or("<unknown>"),
-            name = message.name.as_deref().unwrap_or("<unknown>"),
+            protocol,
+            name,
+            %codec,
         );
     }
 
     Ok(DecodeState::Skipped {
         bytes_consumed: size,
-        details,
+        details: details.map(|details| *details),
+    })
+}
+
+/// Decodes only the header of the envelope, which can be incomplete.
+/// Used to account large envelopes that cannot be reassembled.
+pub(crate) fn decode_details(
+    input: &[u8],
+    trace_id_width: TraceIdWidth,
+) -> Option<EnvelopeDetails> {
+    let mut src = Cursor::new(input);
+    let _size = src.read_u32::<LittleEndian>().ok()?;
+    let flags = src.read_u8().ok()?;
+    let kind = flags & KIND_MASK;
+    let sender = get_addr(&mut src).ok()?;
+    let recipient = get_addr(&mut src).ok()?;
+    let trace_id = get_trace_id(&mut src, trace_id_width).ok()?;
+    let (request_id, ack_seq) = match kind {
+        KIND_REGULAR => (None, None),
+        KIND_REGULAR_ACKED => (None, Some(src.read_u64::<LittleEndian>().ok()?)),
+        _ => (Some(get_request_id(&mut src).ok()?), None),
+    };
+
+    Some(EnvelopeDetails {
+        kind,
+        sender,
+        recipient,
+        request_id,
+        ack_seq,
+        trace_id,
+        is_unknown_message: false,
     })
 }
 
 #[derive(Debug)]
 struct DecodeError {
     message: MessageDecodeError,
-    details: Option<EnvelopeDetails>,
+    // Boxed to keep the error small, see `clippy::result_large_err`.
+    details: Option<Box<EnvelopeDetails>>,
 }
 
 impl<T> From<T> for DecodeError
@@ -120,6 +201,7 @@ where
                 protocol: None,
                 name: None,
                 error: value.into(),
+                is_unknown: false,
             },
             details: None,
         }
@@ -131,6 +213,7 @@ struct MessageDecodeError {
     protocol: Option<String>,
     name: Option<String>,
     error: eyre::Report,
+    is_unknown: bool,
 }
 
 impl<T> From<T> for MessageDecodeError
@@ -142,6 +225,7 @@ where
             protocol: None,
             name: None,
             error: value.into(),
+            is_unknown: false,
         }
     }
 }
@@ -155,7 +239,61 @@ fn get_request_id(frame: &mut Cursor<&[u8]>) -> eyre::Result<RequestId> {
     Ok(RequestId::from_ffi(frame.read_u64::<LittleEndian>()?))
 }
 
-fn get_message(frame: &mut Cursor<&[u8]>) -> Result<AnyMessage, MessageDecodeError> {
+fn get_nonce(frame: &mut Cursor<&[u8]>, flags: u8) -> eyre::Result<u64> {
+    if flags & FLAG_HAS_NONCE != 0 {
+        Ok(frame.read_u64::<LittleEndian>()?)
+    } else {
+        Ok(0)
+    }
+}
+
+fn get_limits(frame: &mut Cursor<&[u8]>, flags: u8) -> eyre::Result<RequestLimits> {
+    let mut limits = RequestLimits::default();
+
+    if flags & FLAG_HAS_LIMITS != 0 {
+        let max_handling_time = frame.read_u64::<LittleEndian>()?;
+        let max_response_size = frame.read_u64::<LittleEndian>()?;
+
+        if max_handling_time != 0 {
+            limits = limits.max_handling_time(Duration::from_micros(max_handling_time));
+        }
+        if max_response_size != 0 {
+            let size = usize::try_from(max_response_size).unwrap_or(usize::MAX);
+            limits = limits.max_response_size(size);
+        }
+    }
+
+    Ok(limits)
+}
+
+fn get_idempotency_key(
+    frame: &mut Cursor<&[u8]>,
+    flags: u8,
+) -> eyre::Result<Option<IdempotencyKey>> {
+    if flags & FLAG_HAS_IDEMPOTENCY_KEY != 0 {
+        let key = IdempotencyKey::from_bits(frame.read_u64::<LittleEndian>()?);
+        ensure!(key.is_some(), "zero idempotency key");
+        Ok(key)
+    } else {
+        Ok(None)
+    }
+}
+
+fn get_message(
+    frame: &mut Cursor<&[u8]>,
+    codec: Codec,
+    stats: &mut DecodeStats,
+) -> Result<AnyMessage, MessageDecodeError> {
+    match codec {
+        Codec::Msgpack => get_msgpack_message(frame, stats),
+        Codec::Postcard => get_postcard_message(frame),
+    }
+}
+
+fn get_msgpack_message(
+    frame: &mut Cursor<&[u8]>,
+    stats: &mut DecodeStats,
+) -> Result<AnyMessage, MessageDecodeError> {
     let protocol = get_str(frame).wrap_err("invalid message protocol")?;
     let name = get_str(frame)
         .wrap_err("invalid message name")
@@ -163,26 +301,89 @@ fn get_message(frame: &mut Cursor<&[u8]>) -> Result<AnyMessage, MessageDecodeErr
             protocol: Some(protocol.to_string()),
             name: None,
             error,
+            is_unknown: false,
         })?;
 
     // TODO: replace with `Cursor::remaining_slice` once it becomes stable.
     let position = frame.position() as usize;
     let remaining_slice = &frame.get_ref()[position..];
 
-    let result = AnyMessage::read_msgpack(remaining_slice, protocol, name).map_err(|error| {
-        MessageDecodeError {
-            protocol: Some(protocol.to_string()),
-            name: Some(name.to_string()),
-            error: error.into(),
-        }
+    let result = scope::with_serde_mode(scope::SerdeMode::Network, || {
+        AnyMessage::read_msgpack_lenient(remaining_slice, protocol, name)
+    })
+    .map_err(|error| MessageDecodeError {
+        protocol: Some(protocol.to_string()),
+        name: Some(name.to_string()),
+        error: error.into(),
+        is_unknown: false,
     })?;
     frame.set_position(frame.get_ref().len() as u64);
 
-    result.ok_or_else(|| MessageDecodeError {
+    let (message, ignored) = result.ok_or_else(|| MessageDecodeError {
         protocol: Some(protocol.to_string()),
         name: Some(name.to_string()),
         error: eyre!("unknown message"),
+        is_unknown: true,
+    })?;
+
+    // Unknown fields are expected during rolling upgrades, when a newer node
+    // sends a message with fields that this node doesn't know about yet.
+    // Messages marked as `#[message(strict)]` fail to decode instead.
+    if unlikely(ignored > 0) {
+        stats.total_fields_ignored += ignored as u64;
+        counter!(
+            "elfo_network_ignored_fields_total", ignored as u64,
+            "protocol" => message.protocol(),
+            "message" => message.name(),
+        );
+    }
+
+    Ok(message)
+}
+
+fn get_postcard_message(frame: &mut Cursor<&[u8]>) -> Result<AnyMessage, MessageDecodeError> {
+    let id = frame
+        .read_u64::<LittleEndian>()
+        .wrap_err("invalid message id")?;
+    let schema_hash = frame
+        .read_u64::<LittleEndian>()
+        .wrap_err("invalid schema hash")?;
+
+    let (protocol, name, local_schema_hash) =
+        AnyMessage::lookup_network_id(id).ok_or_else(|| MessageDecodeError {
+            protocol: None,
+            name: None,
+            error: eyre!("unknown message id {id:#018x}"),
+            is_unknown: true,
+        })?;
+
+    let make_error = |error| MessageDecodeError {
+        protocol: Some(protocol.to_string()),
+        name: Some(name.to_string()),
+        error,
+        is_unknown: false,
+    };
+
+    // Postcard isn't self-describing, so different versions of the message
+    // are decoded into garbage or fail at random places.
+    if unlikely(schema_hash != local_schema_hash) {
+        return Err(make_error(eyre!(
+            "schema mismatch: local {local_schema_hash:#018x}, remote {schema_hash:#018x}"
+        )));
+    }
+
+    // TODO: replace with `Cursor::remaining_slice` once it becomes stable.
+    let position = frame.position() as usize;
+    let remaining_slice = &frame.get_ref()[position..];
+
+    let message = scope::with_serde_mode(scope::SerdeMode::Network, || {
+        AnyMessage::read_postcard(remaining_slice, id)
     })
+    .map_err(|error| make_error(error.into()))?
+    .ok_or_else(|| make_error(eyre!("unknown message")))?;
+    frame.set_position(frame.get_ref().len() as u64);
+
+    Ok(message)
 }
 
 fn get_str<'a>(frame: &mut Cursor<&'a [u8]>) -> eyre::Result<&'a str> {
@@ -198,66 +399,151 @@ fn get_str<'a>(frame: &mut Cursor<&'a [u8]>) -> eyre::Result<&'a str> {
     Ok(decoded_string)
 }
 
-fn do_decode(frame: &mut Cursor<&[u8]>) -> Result<NetworkEnvelope, DecodeError> {
+fn get_trace_id(frame: &mut Cursor<&[u8]>, width: TraceIdWidth) -> eyre::Result<TraceId> {
+    Ok(match width {
+        TraceIdWidth::Bits64 => TraceId::try_from(frame.read_u64::<LittleEndian>()?)?,
+        TraceIdWidth::Bits128 => TraceId::try_from_u128(frame.read_u128::<LittleEndian>()?)?,
+    })
+}
+
+fn do_decode(
+    frame: &mut Cursor<&[u8]>,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
+    stats: &mut DecodeStats,
+) -> Result<NetworkEnvelope, DecodeError> {
     let flags = frame.read_u8()?;
     let kind = flags & KIND_MASK;
 
     let sender = get_addr(frame)?;
     let recipient = get_addr(frame)?;
-    let trace_id = TraceId::try_from(frame.read_u64::<LittleEndian>()?)?;
+    let trace_id = get_trace_id(frame, trace_id_width)?;
 
     let map_decode_error = |result: Result<AnyMessage, MessageDecodeError>,
                             request_id: Option<RequestId>|
      -> Result<AnyMessage, DecodeError> {
         result.map_err(|message| DecodeError {
-            message,
-            details: Some(EnvelopeDetails {
+            details: Some(Box::new(EnvelopeDetails {
                 kind,
                 sender,
                 recipient,
                 request_id,
+                ack_seq: None,
                 trace_id,
-            }),
+                is_unknown_message: message.is_unknown,
+            })),
+            message,
         })
     };
 
     use NetworkEnvelopePayload::*;
     let payload = match kind {
         KIND_REGULAR => Regular {
-            message: map_decode_error(get_message(frame), None)?,
+            message: map_decode_error(get_message(frame, codec, stats), None)?,
+        },
+        KIND_REGULAR_ACKED => {
+            let seq = frame.read_u64::<LittleEndian>()?;
+            RegularAcked {
+                seq,
+                message: map_decode_error(get_message(frame, codec, stats), None).map_err(
+                    |mut error| {
+                        if let Some(details) = &mut error.details {
+                            details.ack_seq = Some(seq);
+                        }
+                        error
+                    },
+                )?,
+            }
+        }
+        KIND_ACK => Ack {
+            seq: frame.read_u64::<LittleEndian>()?,
+            result: decode_ack_status(frame.read_u8()?),
         },
         KIND_REQUEST_ANY => {
             let request_id = get_request_id(frame)?;
             RequestAny {
                 request_id,
-                message: map_decode_error(get_message(frame), Some(request_id))?,
+                nonce: get_nonce(frame, flags)?,
+                limits: get_limits(frame, flags)?,
+                idempotency_key: get_idempotency_key(frame, flags)?,
+                message: map_decode_error(get_message(frame, codec, stats), Some(request_id))?,
             }
         }
         KIND_REQUEST_ALL => {
             let request_id = get_request_id(frame)?;
             RequestAll {
                 request_id,
-                message: map_decode_error(get_message(frame), Some(request_id))?,
+                nonce: get_nonce(frame, flags)?,
+                limits: get_limits(frame, flags)?,
+                idempotency_key: get_idempotency_key(frame, flags)?,
+                message: map_decode_error(get_message(frame, codec, stats), Some(request_id))?,
             }
         }
         KIND_RESPONSE_OK => {
             let request_id = get_request_id(frame)?;
             Response {
                 request_id,
-                message: Ok(map_decode_error(get_message(frame), Some(request_id))?),
+                message: Ok(map_decode_error(
+                    get_message(frame, codec, stats),
+                    Some(request_id),
+                )?),
                 is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
             }
         }
         KIND_RESPONSE_FAILED => Response {
             request_id: get_request_id(frame)?,
-            message: Err(RequestError::Failed),
+            message: Err(ErrorKind::Failed),
             is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
         },
         KIND_RESPONSE_IGNORED => Response {
             request_id: get_request_id(frame)?,
-            message: Err(RequestError::Ignored),
+            message: Err(ErrorKind::Ignored),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_RESPONSE_FORBIDDEN => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::Forbidden),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_RESPONSE_NO_ROUTE => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::NoRoute),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_RESPONSE_TIMEOUT => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::Timeout),
             is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
         },
+        KIND_RESPONSE_DECODE_ERROR => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::RemoteDecodeError),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_RESPONSE_UNSUPPORTED => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::Unsupported),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_RESPONSE_LIMIT_EXCEEDED => Response {
+            request_id: get_request_id(frame)?,
+            message: Err(ErrorKind::LimitExceeded),
+            is_last: flags & FLAG_IS_LAST_RESPONSE != 0,
+        },
+        KIND_CHUNK => {
+            let transfer_id = frame.read_u64::<LittleEndian>()?;
+            let position = frame.position() as usize;
+            let data = frame.get_ref()[position..].to_vec();
+            frame.set_position(frame.get_ref().len() as u64);
+
+            Chunk {
+                transfer_id,
+                is_first: flags & FLAG_IS_FIRST_CHUNK != 0,
+                is_last: flags & FLAG_IS_LAST_CHUNK != 0,
+                is_cancelled: flags & FLAG_IS_CANCELLED != 0,
+                data,
+            }
+        }
         n => return Err(eyre!("invalid message kind: {n}").into()),
     };
 
@@ -265,6 +551,7 @@ fn do_decode(frame: &mut Cursor<&[u8]>) -> Result<NetworkEnvelope, DecodeError>
         sender,
         recipient,
         trace_id,
+        is_force_sampled: kind != KIND_CHUNK && flags & FLAG_IS_FORCE_SAMPLED != 0,
         payload,
     })
 }
@@ -2,12 +2,20 @@ use byteorder::{LittleEndian, WriteBytesExt};
 use derive_more::{Display, From};
 use tracing::error;
 
-use elfo_core::{errors::RequestError, scope, Message};
+use elfo_core::{errors::ErrorKind, scope, tracing::TraceId, Message};
 use elfo_utils::likely;
 
-use crate::codec::format::{
-    NetworkEnvelope, NetworkEnvelopePayload, FLAG_IS_LAST_RESPONSE, KIND_REGULAR, KIND_REQUEST_ALL,
-    KIND_REQUEST_ANY, KIND_RESPONSE_FAILED, KIND_RESPONSE_IGNORED, KIND_RESPONSE_OK,
+use crate::{
+    codec::format::{
+        encode_ack_status, NetworkEnvelope, NetworkEnvelopePayload, TraceIdWidth,
+        FLAG_HAS_IDEMPOTENCY_KEY, FLAG_HAS_LIMITS, FLAG_HAS_NONCE, FLAG_IS_CANCELLED,
+        FLAG_IS_FIRST_CHUNK, FLAG_IS_FORCE_SAMPLED, FLAG_IS_LAST_CHUNK, FLAG_IS_LAST_RESPONSE,
+        KIND_ACK, KIND_CHUNK, KIND_REGULAR, KIND_REGULAR_ACKED, KIND_REQUEST_ALL, KIND_REQUEST_ANY,
+        KIND_RESPONSE_DECODE_ERROR, KIND_RESPONSE_FAILED, KIND_RESPONSE_FORBIDDEN,
+        KIND_RESPONSE_IGNORED, KIND_RESPONSE_LIMIT_EXCEEDED, KIND_RESPONSE_NO_ROUTE,
+        KIND_RESPONSE_OK, KIND_RESPONSE_TIMEOUT, KIND_RESPONSE_UNSUPPORTED,
+    },
+    config::Codec,
 };
 
 #[derive(Debug, Display, From)]
@@ -26,6 +34,8 @@ pub(crate) struct EncodeStats {
 
 pub(crate) fn encode(
     envelope: &NetworkEnvelope,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
     dst: &mut Vec<u8>,
     stats: &mut EncodeStats,
     limit: Option<usize>,
@@ -35,14 +45,17 @@ pub(crate) fn encode(
     // Reserve space for size, this will be rewritten below.
     dst.write_u32::<LittleEndian>(0)?;
 
-    let res = do_encode(envelope, dst, start_pos, limit);
+    let res = do_encode(envelope, codec, trace_id_width, dst, start_pos, limit);
 
     if likely(res.is_ok()) {
         // Rewrite the total frame size (message + length) if encoding was successfull.
         let size = dst.len() - start_pos;
         (&mut dst[start_pos..]).write_u32::<LittleEndian>(size as u32)?;
 
-        stats.total_messages_encoded += 1;
+        // Chunks are parts of an already counted message.
+        if !matches!(envelope.payload, NetworkEnvelopePayload::Chunk { .. }) {
+            stats.total_messages_encoded += 1;
+        }
 
         return Ok(());
     }
@@ -63,27 +76,93 @@ pub(crate) fn encode(
         error = format!("{:#}", error),
         %protocol,
         %name,
+        %codec,
     );
     Err(EncodeError::Skipped)
 }
 
+fn write_trace_id(
+    dst: &mut Vec<u8>,
+    trace_id: TraceId,
+    width: TraceIdWidth,
+) -> std::io::Result<()> {
+    match width {
+        TraceIdWidth::Bits64 => dst.write_u64::<LittleEndian>(trace_id.to_u64_lossy()),
+        TraceIdWidth::Bits128 => dst.write_u128::<LittleEndian>(trace_id.into()),
+    }
+}
+
 fn do_encode(
     envelope: &NetworkEnvelope,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
     dst: &mut Vec<u8>,
     start_pos: usize,
     limit: Option<usize>,
 ) -> eyre::Result<()> {
     use NetworkEnvelopePayload::*;
+
+    if let Chunk {
+        transfer_id,
+        is_first,
+        is_last,
+        is_cancelled,
+        data,
+    } = &envelope.payload
+    {
+        let mut flags = 0;
+        if *is_first {
+            flags |= FLAG_IS_FIRST_CHUNK;
+        }
+        if *is_last {
+            flags |= FLAG_IS_LAST_CHUNK;
+        }
+        if *is_cancelled {
+            flags |= FLAG_IS_CANCELLED;
+        }
+
+        dst.write_u8(flags | KIND_CHUNK)?;
+        dst.write_u64::<LittleEndian>(envelope.sender.into_bits())?;
+        dst.write_u64::<LittleEndian>(envelope.recipient.into_bits())?;
+        write_trace_id(dst, envelope.trace_id, trace_id_width)?;
+        dst.write_u64::<LittleEndian>(*transfer_id)?;
+        dst.extend_from_slice(data);
+        return Ok(());
+    }
+
+    if let Ack { seq, result } = &envelope.payload {
+        dst.write_u8(KIND_ACK)?;
+        dst.write_u64::<LittleEndian>(envelope.sender.into_bits())?;
+        dst.write_u64::<LittleEndian>(envelope.recipient.into_bits())?;
+        write_trace_id(dst, envelope.trace_id, trace_id_width)?;
+        dst.write_u64::<LittleEndian>(*seq)?;
+        dst.write_u8(encode_ack_status(*result))?;
+        return Ok(());
+    }
+
     let (is_last_response, kind, request_id, message) = match &envelope.payload {
         Regular { message } => (false, KIND_REGULAR, None, Some(message)),
+        RegularAcked { seq, message } => (false, KIND_REGULAR_ACKED, Some(*seq), Some(message)),
         RequestAny {
             request_id,
             message,
-        } => (false, KIND_REQUEST_ANY, Some(*request_id), Some(message)),
+            ..
+        } => (
+            false,
+            KIND_REQUEST_ANY,
+            Some(request_id.to_ffi()),
+            Some(message),
+        ),
         RequestAll {
             request_id,
             message,
-        } => (false, KIND_REQUEST_ALL, Some(*request_id), Some(message)),
+            ..
+        } => (
+            false,
+            KIND_REQUEST_ALL,
+            Some(request_id.to_ffi()),
+            Some(message),
+        ),
         Response {
             request_id,
             message,
@@ -92,12 +171,44 @@ fn do_encode(
             *is_last,
             match &message {
                 Ok(_) => KIND_RESPONSE_OK,
-                Err(RequestError::Failed) => KIND_RESPONSE_FAILED,
-                Err(RequestError::Ignored) => KIND_RESPONSE_IGNORED,
+                Err(ErrorKind::Ignored) => KIND_RESPONSE_IGNORED,
+                Err(ErrorKind::Forbidden) => KIND_RESPONSE_FORBIDDEN,
+                Err(ErrorKind::NoRoute) => KIND_RESPONSE_NO_ROUTE,
+                Err(ErrorKind::Timeout) => KIND_RESPONSE_TIMEOUT,
+                Err(ErrorKind::RemoteDecodeError) => KIND_RESPONSE_DECODE_ERROR,
+                Err(ErrorKind::Unsupported) => KIND_RESPONSE_UNSUPPORTED,
+                Err(ErrorKind::LimitExceeded) => KIND_RESPONSE_LIMIT_EXCEEDED,
+                // `Failed` and errors produced only on the sending side, e.g.
+                // `CircuitOpen`, `GroupDisabled`, `Cancelled`, `NetworkDisconnected`,
+                // `Rejected`, `TraceBudgetExceeded` and `Deprecated`.
+                Err(_) => KIND_RESPONSE_FAILED,
             },
-            Some(*request_id),
+            Some(request_id.to_ffi()),
             message.as_ref().ok(),
         ),
+        Chunk { .. } | Ack { .. } => unreachable!(),
+    };
+
+    let limits = match &envelope.payload {
+        RequestAny { limits, .. } | RequestAll { limits, .. } if !limits.is_unlimited() => {
+            Some(limits)
+        }
+        _ => None,
+    };
+
+    let nonce = match &envelope.payload {
+        RequestAny { nonce, .. } | RequestAll { nonce, .. } if *nonce != 0 => Some(*nonce),
+        _ => None,
+    };
+
+    let idempotency_key = match &envelope.payload {
+        RequestAny {
+            idempotency_key, ..
+        }
+        | RequestAll {
+            idempotency_key, ..
+        } => *idempotency_key,
+        _ => None,
     };
 
     // flags and kind
@@ -105,6 +216,18 @@ fn do_encode(
     if is_last_response {
         flags |= FLAG_IS_LAST_RESPONSE;
     }
+    if envelope.is_force_sampled {
+        flags |= FLAG_IS_FORCE_SAMPLED;
+    }
+    if limits.is_some() {
+        flags |= FLAG_HAS_LIMITS;
+    }
+    if nonce.is_some() {
+        flags |= FLAG_HAS_NONCE;
+    }
+    if idempotency_key.is_some() {
+        flags |= FLAG_HAS_IDEMPOTENCY_KEY;
+    }
     dst.write_u8(flags | kind)?;
 
     // sender
@@ -114,36 +237,74 @@ fn do_encode(
     dst.write_u64::<LittleEndian>(envelope.recipient.into_bits())?;
 
     // trace_id
-    dst.write_u64::<LittleEndian>(u64::from(envelope.trace_id))?;
+    write_trace_id(dst, envelope.trace_id, trace_id_width)?;
 
-    // request_id
+    // request_id or seq
     if let Some(request_id) = request_id {
-        dst.write_u64::<LittleEndian>(request_id.to_ffi())?;
+        dst.write_u64::<LittleEndian>(request_id)?;
     }
 
-    if let Some(message) = message {
-        let mut put_str = |s: &str| -> eyre::Result<()> {
-            let size = s.len();
-            assert!(size <= 255);
-            dst.write_u8(size as u8)?;
-            dst.extend_from_slice(s.as_bytes());
-            Ok(())
-        };
+    // nonce
+    if let Some(nonce) = nonce {
+        dst.write_u64::<LittleEndian>(nonce)?;
+    }
 
-        // protocol
-        put_str(message.protocol())?;
+    // limits
+    if let Some(limits) = limits {
+        let max_handling_time = limits.max_handling_time.map_or(0, |time| {
+            // Zero means no limit, so round up to keep expired requests.
+            u64::try_from(time.as_micros()).unwrap_or(u64::MAX).max(1)
+        });
+        let max_response_size = limits
+            .max_response_size
+            .map_or(0, |size| size.max(1) as u64);
+        dst.write_u64::<LittleEndian>(max_handling_time)?;
+        dst.write_u64::<LittleEndian>(max_response_size)?;
+    }
 
-        // name
-        put_str(message.name())?;
+    // idempotency key
+    if let Some(key) = idempotency_key {
+        dst.write_u64::<LittleEndian>(key.into_bits())?;
+    }
 
-        // message
-        let max_limit = u32::MAX as usize - (dst.len() - start_pos);
-        let limit = limit.map_or(max_limit, |limit| limit.min(max_limit));
+    let Some(message) = message else {
+        return Ok(());
+    };
 
-        scope::with_serde_mode(scope::SerdeMode::Network, || {
-            message.write_msgpack(dst, limit)
-        })?;
+    match codec {
+        Codec::Msgpack => {
+            let mut put_str = |s: &str| -> eyre::Result<()> {
+                let size = s.len();
+                assert!(size <= 255);
+                dst.write_u8(size as u8)?;
+                dst.extend_from_slice(s.as_bytes());
+                Ok(())
+            };
+
+            // protocol
+            put_str(message.protocol())?;
+
+            // name
+            put_str(message.name())?;
+        }
+        Codec::Postcard => {
+            // message id
+            dst.write_u64::<LittleEndian>(message.network_id())?;
+
+            // schema hash
+            dst.write_u64::<LittleEndian>(message.schema_hash())?;
+        }
     }
 
-    Ok(())
+    // message
+    let max_limit = u32::MAX as usize - (dst.len() - start_pos);
+    let limit = limit.map_or(max_limit, |limit| limit.min(max_limit));
+
+    scope::with_serde_mode(scope::SerdeMode::Network, || -> eyre::Result<()> {
+        match codec {
+            Codec::Msgpack => message.write_msgpack(dst, limit)?,
+            Codec::Postcard => message.write_postcard(dst, limit)?,
+        }
+        Ok(())
+    })
 }
@@ -5,45 +5,105 @@
 //! │ size of whole frame   │ 32 │                     │
 //! ├───────────────────────┼────┤                     │
 //! │ flags                 │  4 │                     │ flags:
-//! ├───────────────────────┼────┤                     │ - <reserved>       = 1
-//! │ kind                  │  4 │                     │ - <reserved>       = 2
-//! ├───────────────────────┼────┤       always        │ - <reserved>       = 4
-//! │ sender                │ 64 │                     │ - is last response = 8
+//! ├───────────────────────┼────┤                     │ - is first chunk   = 1 (Chunk)
+//! │ kind                  │  4 │                     │ - is force sampled = 1 (others)
+//! ├───────────────────────┼────┤       always        │ - is last chunk    = 2 (Chunk)
+//! │ sender                │ 64 │                     │ - has nonce        = 2 (Request*)
+//! ├───────────────────────┼────┤                     │ - is cancelled     = 4 (Chunk)
+//! │ recipient             │ 64 │                     │ - has limits       = 4 (Request*)
+//! ├───────────────────────┼────┤                     │ - is last response = 8 (Response*)
+//! │ trace id              │ 64*│                     │ - has idempotency  = 8 (Request*)
+//! ├───────────────────────┼────┼─────────────────────┤ kinds:
+//! │ request id            │ 64 │ if kind != Regular  │ - Regular           = 0
+//! ├───────────────────────┼────┼─────────────────────┤ - RequestAny        = 1
+//! │ nonce                 │ 64 │ if has nonce        │ - RequestAll        = 2
+//! ├───────────────────────┼────┼─────────────────────┤ - Response::Ok      = 3
+//! │ max handling time, µs │ 64 │ if has limits       │ - Response::Failed  = 4
+//! ├───────────────────────┼────┤                     │ - Response::Ignored = 5
+//! │ max response size     │ 64 │                     │ - Chunk             = 6
+//! ├───────────────────────┼────┼─────────────────────┤ - RegularAcked      = 13
+//! │ idempotency key       │ 64 │ if has idempotency  │ - Ack               = 14
+//! ├───────────────────────┼────┼─────────────────────┤
+//! │ protocol's length (P) │  8 │                     │
 //! ├───────────────────────┼────┤                     │
-//! │ recipient             │ 64 │                     │
-//! ├───────────────────────┼────┤                     │
-//! │ trace id              │ 64 │                     │ kinds:
-//! ├───────────────────────┼────┼─────────────────────┤ - Regular           = 0
-//! │ request id            │ 64 │ if kind != Regular  │ - RequestAny        = 1
-//! ├───────────────────────┼────┼─────────────────────┤ - RequestAll        = 2
-//! │ protocol's length (P) │  8 │                     │ - Response::Ok      = 3
-//! ├───────────────────────┼────┤                     │ - Response::Failed  = 4
-//! │ protocol              │ 8P │                     │ - Response::Ignored = 5
+//! │ protocol              │ 8P │                     │
 //! ├───────────────────────┼────┤ if kind !=          │
 //! │ msg name's length (N) │  8 │ - Response::Failed  │
 //! ├───────────────────────┼────┤ - Response::Ignored │
-//! │ msg name              │ 8N │                     │
-//! ├───────────────────────┼────┤                     │
+//! │ msg name              │ 8N │ - Chunk             │
+//! ├───────────────────────┼────┤ - Ack               │
 //! │ msg payload           │rest│                     │
 //! └───────────────────────┴────┴─────────────────────┘
 //! ```
 //!
+//! `RegularAcked` is a regular message, which should be acknowledged by
+//! the receiving node once enqueued, see `Context::send_acknowledged()`.
+//! It contains the sequence number in place of the request id. The receiving
+//! node replies with `Ack`, containing the same sequence number and a status
+//! byte as the payload (see `encode_ack_status()`). Acks aren't limited by
+//! flows. Both kinds are sent only if the peer supports them, see
+//! `Capabilities::ACKS`.
+//!
+//! Zero limits mean their absence. Limits are sent only if the peer supports
+//! them, see `Capabilities::REQUEST_LIMITS`.
+//!
+//! Idempotency keys are sent only if the peer supports them, see
+//! `Capabilities::IDEMPOTENCY_KEYS`.
+//!
+//! Responses with specific errors (kinds from 7 to 12, see `KIND_RESPONSE_*`)
+//! are sent only if the peer supports them, see `Capabilities::EXTENDED_RESPONSES`
+//! and `compat_response_error()`. Otherwise, `Response::Failed` is sent.
+//!
+//! Requests are stamped with increasing per-connection nonces if the peer
+//! checks them, see `Capabilities::NONCES` and `socket::replay`. Zero nonce
+//! means its absence.
+//!
 //! All fields are encoded using LE ordering.
-
-// TODO: send message ID instead of protocol/name.
+//!
+//! The layout above is used by the msgpack codec. If the postcard codec is
+//! negotiated, the protocol and name are replaced with the message id, which
+//! is a hash of them, and the hash of the message's shape:
+//! ```text
+//! ├───────────────────────┼────┼─────────────────────┤
+//! │ msg id                │ 64 │                     │
+//! ├───────────────────────┼────┤                     │
+//! │ msg schema hash       │ 64 │ same as above       │
+//! ├───────────────────────┼────┤                     │
+//! │ msg payload           │rest│                     │
+//! └───────────────────────┴────┴─────────────────────┘
+//! ```
+//!
+//! Large envelopes are transferred as a sequence of chunks in order to avoid
+//! blocking the connection. Chunks contain the transfer id in place of
+//! the request id and a part of the encoded envelope as the payload. Sender,
+//! recipient and trace id are copied from the transferred envelope.
+//!
+//! (*) Trace ids are 128-bit if both nodes are built with `trace-id-128`,
+//! see `Capabilities::TRACE_ID_128` and `TraceIdWidth`.
 
 use derive_more::Display;
 
 use elfo_core::{
     addr::{Addr, NodeNo},
-    errors::RequestError,
+    errors::{AckError, ErrorKind},
     tracing::TraceId,
-    AnyMessage, Message, RequestId,
+    AnyMessage, IdempotencyKey, Message, RequestId, RequestLimits,
 };
 use elfo_utils::likely;
 
 // Flags are shifted by 4 bits to the left because of the kind.
+pub(crate) const FLAG_IS_FIRST_CHUNK: u8 = 1 << 4;
+// Chunks never have this flag, the chunked envelope contains it instead.
+pub(crate) const FLAG_IS_FORCE_SAMPLED: u8 = 1 << 4;
+pub(crate) const FLAG_IS_LAST_CHUNK: u8 = 1 << 5;
+// Only requests have this flag.
+pub(crate) const FLAG_HAS_NONCE: u8 = 1 << 5;
+pub(crate) const FLAG_IS_CANCELLED: u8 = 1 << 6;
+// Only requests have this flag.
+pub(crate) const FLAG_HAS_LIMITS: u8 = 1 << 6;
 pub(crate) const FLAG_IS_LAST_RESPONSE: u8 = 1 << 7;
+// Only requests have this flag.
+pub(crate) const FLAG_HAS_IDEMPOTENCY_KEY: u8 = 1 << 7;
 
 pub(crate) const KIND_MASK: u8 = 0xF;
 pub(crate) const KIND_REGULAR: u8 = 0;
@@ -52,12 +112,33 @@ pub(crate) const KIND_REQUEST_ALL: u8 = 2;
 pub(crate) const KIND_RESPONSE_OK: u8 = 3;
 pub(crate) const KIND_RESPONSE_FAILED: u8 = 4;
 pub(crate) const KIND_RESPONSE_IGNORED: u8 = 5;
+pub(crate) const KIND_CHUNK: u8 = 6;
+pub(crate) const KIND_RESPONSE_FORBIDDEN: u8 = 7;
+pub(crate) const KIND_RESPONSE_NO_ROUTE: u8 = 8;
+pub(crate) const KIND_RESPONSE_TIMEOUT: u8 = 9;
+pub(crate) const KIND_RESPONSE_DECODE_ERROR: u8 = 10;
+pub(crate) const KIND_RESPONSE_UNSUPPORTED: u8 = 11;
+pub(crate) const KIND_RESPONSE_LIMIT_EXCEEDED: u8 = 12;
+pub(crate) const KIND_REGULAR_ACKED: u8 = 13;
+pub(crate) const KIND_ACK: u8 = 14;
+
+/// The width of trace ids in envelopes, negotiated by the handshake.
+///
+/// If only this node is built with `trace-id-128`, ids are truncated by
+/// `TraceId::to_u64_lossy()` on sending and zero-extended on receiving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TraceIdWidth {
+    Bits64,
+    Bits128,
+}
 
 #[derive(Debug)]
 pub(crate) struct NetworkEnvelope {
     pub(crate) sender: NetworkAddr,
     pub(crate) recipient: NetworkAddr,
     pub(crate) trace_id: TraceId,
+    /// See `Context::force_sampling()`.
+    pub(crate) is_force_sampled: bool,
     pub(crate) payload: NetworkEnvelopePayload,
 }
 
@@ -131,25 +212,53 @@ pub(crate) enum NetworkEnvelopePayload {
     Regular {
         message: AnyMessage,
     },
+    /// A regular message, which should be acknowledged by the recipient.
+    RegularAcked {
+        seq: u64,
+        message: AnyMessage,
+    },
+    /// An acknowledgement of `RegularAcked`.
+    Ack {
+        seq: u64,
+        result: Result<(), AckError>,
+    },
     RequestAny {
         request_id: RequestId,
+        /// See `WriteHalf::stamp()`, zero if absent.
+        nonce: u64,
+        limits: RequestLimits,
+        idempotency_key: Option<IdempotencyKey>,
         message: AnyMessage,
     },
     RequestAll {
         request_id: RequestId,
+        /// See `WriteHalf::stamp()`, zero if absent.
+        nonce: u64,
+        limits: RequestLimits,
+        idempotency_key: Option<IdempotencyKey>,
         message: AnyMessage,
     },
     Response {
         request_id: RequestId,
-        message: Result<AnyMessage, RequestError>,
+        message: Result<AnyMessage, ErrorKind>,
         is_last: bool,
     },
+    /// A part of a large envelope, see `socket::transfers`.
+    Chunk {
+        transfer_id: u64,
+        is_first: bool,
+        is_last: bool,
+        is_cancelled: bool,
+        data: Vec<u8>,
+    },
 }
 
 impl NetworkEnvelopePayload {
     pub(crate) fn protocol_and_name(&self) -> (&'static str, &'static str) {
         match self {
             Self::Regular { message } => (message.protocol(), message.name()),
+            Self::RegularAcked { message, .. } => (message.protocol(), message.name()),
+            Self::Ack { .. } => ("", "Ack"),
             Self::RequestAny { message, .. } => (message.protocol(), message.name()),
             Self::RequestAll { message, .. } => (message.protocol(), message.name()),
             Self::Response {
@@ -157,13 +266,105 @@ impl NetworkEnvelopePayload {
                 ..
             } => (message.protocol(), message.name()),
             Self::Response {
-                message: Err(RequestError::Failed),
+                message: Err(ErrorKind::Failed),
                 ..
             } => ("", "RequestError::Failed"),
             Self::Response {
-                message: Err(RequestError::Ignored),
+                message: Err(ErrorKind::Ignored),
                 ..
             } => ("", "RequestError::Ignored"),
+            Self::Response {
+                message: Err(ErrorKind::CircuitOpen),
+                ..
+            } => ("", "ErrorKind::CircuitOpen"),
+            Self::Response {
+                message: Err(ErrorKind::GroupDisabled),
+                ..
+            } => ("", "ErrorKind::GroupDisabled"),
+            Self::Response {
+                message: Err(ErrorKind::Cancelled),
+                ..
+            } => ("", "ErrorKind::Cancelled"),
+            Self::Response {
+                message: Err(ErrorKind::Forbidden),
+                ..
+            } => ("", "ErrorKind::Forbidden"),
+            Self::Response {
+                message: Err(ErrorKind::NoRoute),
+                ..
+            } => ("", "ErrorKind::NoRoute"),
+            Self::Response {
+                message: Err(ErrorKind::Timeout),
+                ..
+            } => ("", "ErrorKind::Timeout"),
+            Self::Response {
+                message: Err(ErrorKind::NetworkDisconnected),
+                ..
+            } => ("", "ErrorKind::NetworkDisconnected"),
+            Self::Response {
+                message: Err(ErrorKind::RemoteDecodeError),
+                ..
+            } => ("", "ErrorKind::RemoteDecodeError"),
+            Self::Response {
+                message: Err(ErrorKind::Unsupported),
+                ..
+            } => ("", "ErrorKind::Unsupported"),
+            Self::Response {
+                message: Err(ErrorKind::Rejected),
+                ..
+            } => ("", "ErrorKind::Rejected"),
+            Self::Response {
+                message: Err(ErrorKind::LimitExceeded),
+                ..
+            } => ("", "ErrorKind::LimitExceeded"),
+            Self::Response {
+                message: Err(ErrorKind::TraceBudgetExceeded),
+                ..
+            } => ("", "ErrorKind::TraceBudgetExceeded"),
+            Self::Response {
+                message: Err(ErrorKind::Deprecated),
+                ..
+            } => ("", "ErrorKind::Deprecated"),
+            Self::Response {
+                message: Err(_), ..
+            } => ("", "RequestError"),
+            Self::Chunk { .. } => ("", "Chunk"),
         }
     }
 }
+
+/// Replaces specific errors of responses with `ErrorKind::Failed` if
+/// the peer cannot decode them, because older nodes reject unknown kinds.
+pub(crate) fn compat_response_error(error: ErrorKind, has_extended_responses: bool) -> ErrorKind {
+    match error {
+        ErrorKind::Failed | ErrorKind::Ignored => error,
+        _ if has_extended_responses => error,
+        _ => ErrorKind::Failed,
+    }
+}
+
+pub(crate) fn encode_ack_status(result: Result<(), AckError>) -> u8 {
+    match result {
+        Ok(()) => 0,
+        Err(AckError::NoRoute) => 1,
+        Err(AckError::Forbidden) => 2,
+        Err(AckError::Closed) => 3,
+        Err(AckError::Rejected) => 4,
+        Err(AckError::DecodeFailed) => 5,
+        // Others are produced only on the sending side.
+        Err(_) => u8::MAX,
+    }
+}
+
+pub(crate) fn decode_ack_status(status: u8) -> Result<(), AckError> {
+    match status {
+        0 => Ok(()),
+        1 => Err(AckError::NoRoute),
+        2 => Err(AckError::Forbidden),
+        3 => Err(AckError::Closed),
+        4 => Err(AckError::Rejected),
+        5 => Err(AckError::DecodeFailed),
+        // Unknown statuses are possible only with newer peers.
+        _ => Err(AckError::Unsupported),
+    }
+}
@@ -4,14 +4,17 @@ pub(crate) mod format;
 
 #[cfg(test)]
 mod tests {
-    use elfo_core::{message, tracing::TraceId, Message, _priv::AnyMessage};
+    use elfo_core::{_priv::AnyMessage, message, tracing::TraceId, Message};
     use std::convert::TryFrom;
 
     use super::{
-        decode::{decode, DecodeState},
+        decode::{decode, DecodeState, DecodeStats},
         encode::{encode, EncodeError},
-        format::{NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload},
+        format::{NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, TraceIdWidth},
     };
+    use crate::config::Codec;
+
+    const CODECS: [Codec; 2] = [Codec::Msgpack, Codec::Postcard];
 
     #[message]
     #[derive(PartialEq)]
@@ -26,6 +29,7 @@ mod tests {
             sender: NetworkAddr::NULL,
             recipient: NetworkAddr::NULL,
             trace_id: TraceId::try_from(trace_index).unwrap(),
+            is_force_sampled: trace_index % 2 == 0,
             payload: NetworkEnvelopePayload::Regular {
                 message: AnyMessage::new(message),
             },
@@ -49,6 +53,14 @@ mod tests {
 
     #[test]
     fn smoke() {
+        for codec in CODECS {
+            for width in [TraceIdWidth::Bits64, TraceIdWidth::Bits128] {
+                smoke_with(codec, width);
+            }
+        }
+    }
+
+    fn smoke_with(codec: Codec, width: TraceIdWidth) {
         let mut bytes = Vec::new();
         let mut position = 0;
 
@@ -59,10 +71,26 @@ mod tests {
             // Small message must fit into 100 bytes, but big message must not.
             const LIMIT: Option<usize> = Some(100);
             let encode_start = bytes.len();
-            encode(&small_envelope, &mut bytes, &mut Default::default(), LIMIT).unwrap();
+            encode(
+                &small_envelope,
+                codec,
+                width,
+                &mut bytes,
+                &mut Default::default(),
+                LIMIT,
+            )
+            .unwrap();
             let encode_end = bytes.len();
             assert!(matches!(
-                encode(&big_envelope, &mut bytes, &mut Default::default(), LIMIT).unwrap_err(),
+                encode(
+                    &big_envelope,
+                    codec,
+                    width,
+                    &mut bytes,
+                    &mut Default::default(),
+                    LIMIT
+                )
+                .unwrap_err(),
                 EncodeError::Skipped
             ));
 
@@ -70,7 +98,8 @@ mod tests {
             // buffer.
             assert_eq!(encode_end, bytes.len());
 
-            let decode_state = decode(&bytes[position..], &mut Default::default()).unwrap();
+            let decode_state =
+                decode(&bytes[position..], codec, width, &mut Default::default()).unwrap();
             let decoded_small_envelope = match decode_state {
                 DecodeState::Skipped { .. } => {
                     panic!("there was a non-fatal error when decoding a message");
@@ -93,21 +122,244 @@ mod tests {
             assert_eq!(decoded_small_envelope.trace_id, small_envelope.trace_id);
             assert_eq!(decoded_small_envelope.sender, small_envelope.sender);
             assert_eq!(decoded_small_envelope.recipient, small_envelope.recipient);
+            assert_eq!(
+                decoded_small_envelope.is_force_sampled,
+                small_envelope.is_force_sampled
+            );
 
             assert_regular_eq::<SmallMessage>(&decoded_small_envelope, &small_envelope);
         }
     }
 
+    #[test]
+    fn trace_id_width() {
+        let trace_id =
+            TraceId::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+                .unwrap();
+        let envelope = NetworkEnvelope {
+            trace_id,
+            ..make_envelope(SmallMessage(0), 1)
+        };
+
+        let roundtrip = |width| {
+            let mut bytes = Vec::new();
+            encode(
+                &envelope,
+                Codec::Msgpack,
+                width,
+                &mut bytes,
+                &mut Default::default(),
+                None,
+            )
+            .unwrap();
+            match decode(&bytes, Codec::Msgpack, width, &mut Default::default()).unwrap() {
+                DecodeState::Done { decoded, .. } => decoded.trace_id,
+                _ => panic!("cannot decode"),
+            }
+        };
+
+        assert_eq!(roundtrip(TraceIdWidth::Bits128), trace_id);
+
+        // Truncated for peers supporting only 64-bit ids.
+        let truncated = roundtrip(TraceIdWidth::Bits64);
+        assert_eq!(truncated.to_u64_lossy(), trace_id.to_u64_lossy());
+        assert_eq!(truncated == trace_id, TraceId::BITS == 64);
+    }
+
+    #[test]
+    fn request_limits_nonce_and_idempotency_key() {
+        use std::time::Duration;
+
+        use elfo_core::{errors::ErrorKind, IdempotencyKey, RequestId, RequestLimits};
+
+        let roundtrip = |payload| {
+            let envelope = NetworkEnvelope {
+                payload,
+                ..make_envelope(SmallMessage(0), 1)
+            };
+
+            let mut bytes = Vec::new();
+            encode(
+                &envelope,
+                Codec::Msgpack,
+                TraceIdWidth::Bits64,
+                &mut bytes,
+                &mut Default::default(),
+                None,
+            )
+            .unwrap();
+
+            match decode(
+                &bytes,
+                Codec::Msgpack,
+                TraceIdWidth::Bits64,
+                &mut Default::default(),
+            )
+            .unwrap()
+            {
+                DecodeState::Done { decoded, .. } => decoded.payload,
+                _ => panic!("cannot decode"),
+            }
+        };
+
+        let all_limits = RequestLimits::default()
+            .max_handling_time(Duration::from_millis(1500))
+            .max_response_size(1024);
+        let time_limit = RequestLimits::default().max_handling_time(Duration::from_secs(1));
+
+        let cases = [
+            (all_limits, 0),
+            (time_limit, 7),
+            (RequestLimits::default(), 0),
+            (RequestLimits::default(), u64::MAX),
+        ];
+
+        let key = IdempotencyKey::generate();
+
+        for ((limits, nonce), idempotency_key) in cases.into_iter().zip([None, Some(key)].repeat(2))
+        {
+            let payload = roundtrip(NetworkEnvelopePayload::RequestAny {
+                request_id: RequestId::from_ffi(1),
+                nonce,
+                limits,
+                idempotency_key,
+                message: AnyMessage::new(SmallMessage(42)),
+            });
+
+            let NetworkEnvelopePayload::RequestAny {
+                nonce: decoded_nonce,
+                limits: decoded,
+                idempotency_key: decoded_key,
+                message,
+                ..
+            } = payload
+            else {
+                panic!("invalid payload");
+            };
+            assert_eq!(decoded, limits);
+            assert_eq!(decoded_nonce, nonce);
+            assert_eq!(decoded_key, idempotency_key);
+            assert_eq!(message.downcast_ref::<SmallMessage>().unwrap().0, 42);
+        }
+
+        let payload = roundtrip(NetworkEnvelopePayload::Response {
+            request_id: RequestId::from_ffi(1),
+            message: Err(ErrorKind::LimitExceeded),
+            is_last: true,
+        });
+        assert!(matches!(
+            payload,
+            NetworkEnvelopePayload::Response {
+                message: Err(ErrorKind::LimitExceeded),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn extended_responses_fall_back_for_old_peers() {
+        use elfo_core::{errors::ErrorKind, RequestId};
+
+        use super::format::{compat_response_error, KIND_MASK, KIND_RESPONSE_FAILED};
+
+        let roundtrip = |error, has_extended_responses| {
+            let envelope = NetworkEnvelope {
+                payload: NetworkEnvelopePayload::Response {
+                    request_id: RequestId::from_ffi(1),
+                    message: Err(compat_response_error(error, has_extended_responses)),
+                    is_last: true,
+                },
+                ..make_envelope(SmallMessage(0), 1)
+            };
+
+            let mut bytes = Vec::new();
+            encode(
+                &envelope,
+                Codec::Msgpack,
+                TraceIdWidth::Bits64,
+                &mut bytes,
+                &mut Default::default(),
+                None,
+            )
+            .unwrap();
+
+            // Older nodes reject all kinds unknown to them.
+            let kind = bytes[4] & KIND_MASK;
+
+            match decode(
+                &bytes,
+                Codec::Msgpack,
+                TraceIdWidth::Bits64,
+                &mut Default::default(),
+            )
+            .unwrap()
+            {
+                DecodeState::Done { decoded, .. } => match decoded.payload {
+                    NetworkEnvelopePayload::Response {
+                        message: Err(error),
+                        ..
+                    } => (kind, error),
+                    _ => panic!("invalid payload"),
+                },
+                _ => panic!("cannot decode"),
+            }
+        };
+
+        let errors = [
+            ErrorKind::Forbidden,
+            ErrorKind::NoRoute,
+            ErrorKind::Timeout,
+            ErrorKind::RemoteDecodeError,
+            ErrorKind::Unsupported,
+            ErrorKind::LimitExceeded,
+        ];
+
+        for error in errors {
+            let (kind, decoded) = roundtrip(error, true);
+            assert_ne!(kind, KIND_RESPONSE_FAILED);
+            assert_eq!(decoded, error);
+
+            let (kind, decoded) = roundtrip(decoded, false);
+            assert_eq!(kind, KIND_RESPONSE_FAILED);
+            assert_eq!(decoded, ErrorKind::Failed);
+        }
+
+        let (_, decoded) = roundtrip(ErrorKind::Ignored, false);
+        assert_eq!(decoded, ErrorKind::Ignored);
+    }
+
     #[test]
     fn test_decode_skip() {
+        for codec in CODECS {
+            decode_skip_with(codec);
+        }
+    }
+
+    fn decode_skip_with(codec: Codec) {
         let mut bytes = Vec::new();
 
         let envelope = make_envelope(BigMessage("a".repeat(100)), 1);
 
         // Encode two messages.
-        encode(&envelope, &mut bytes, &mut Default::default(), None).unwrap();
+        encode(
+            &envelope,
+            codec,
+            TraceIdWidth::Bits64,
+            &mut bytes,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
         let message_size = bytes.len();
-        encode(&envelope, &mut bytes, &mut Default::default(), None).unwrap();
+        encode(
+            &envelope,
+            codec,
+            TraceIdWidth::Bits64,
+            &mut bytes,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
 
         // Corrupt the second message.
         for byte in &mut bytes[message_size + 4..] {
@@ -115,9 +367,17 @@ mod tests {
         }
 
         // Encode the third message on top of the corrupted first one.
-        encode(&envelope, &mut bytes, &mut Default::default(), None).unwrap();
+        encode(
+            &envelope,
+            codec,
+            TraceIdWidth::Bits64,
+            &mut bytes,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
 
-        let state = decode(&bytes, &mut Default::default()).unwrap();
+        let state = decode(&bytes, codec, TraceIdWidth::Bits64, &mut Default::default()).unwrap();
         if let DecodeState::Done {
             bytes_consumed,
             decoded,
@@ -129,14 +389,26 @@ mod tests {
             panic!("expected the first message to be decoded successfully");
         }
 
-        let state = decode(&bytes[message_size..], &mut Default::default()).unwrap();
+        let state = decode(
+            &bytes[message_size..],
+            codec,
+            TraceIdWidth::Bits64,
+            &mut Default::default(),
+        )
+        .unwrap();
         if let DecodeState::Skipped { bytes_consumed, .. } = state {
             assert_eq!(bytes_consumed, message_size);
         } else {
             panic!("expected the second message to be skipped");
         }
 
-        let state = decode(&bytes[2 * message_size..], &mut Default::default()).unwrap();
+        let state = decode(
+            &bytes[2 * message_size..],
+            codec,
+            TraceIdWidth::Bits64,
+            &mut Default::default(),
+        )
+        .unwrap();
         if let DecodeState::Done {
             bytes_consumed,
             decoded,
@@ -149,5 +421,228 @@ mod tests {
         }
     }
 
+    // Messages with the same name, but in different protocols, emulate
+    // different versions of the same message compiled into different nodes.
+
+    #[message(protocol = "evolution-v1", name = "Evolving")]
+    #[derive(PartialEq)]
+    struct EvolvingV1 {
+        a: u32,
+    }
+
+    #[message(protocol = "evolution-v2", name = "Evolving")]
+    #[derive(PartialEq)]
+    struct EvolvingV2 {
+        a: u32,
+        #[serde(default)]
+        b: u32,
+    }
+
+    #[message(protocol = "evolution-v3", name = "Evolving")]
+    #[derive(PartialEq)]
+    struct EvolvingV3 {
+        a: u32,
+        c: u32,
+    }
+
+    #[message(protocol = "evolution-vs", name = "Evolving", strict)]
+    #[derive(PartialEq)]
+    struct EvolvingStrict {
+        a: u32,
+    }
+
+    /// Encodes the message and replaces its protocol to emulate a receiver
+    /// compiled with another version of the message.
+    fn encode_as(message: impl Message, protocol: &str) -> Vec<u8> {
+        let from = message.protocol().as_bytes().to_vec();
+        assert_eq!(from.len(), protocol.len());
+
+        let mut bytes = Vec::new();
+        encode(
+            &make_envelope(message, 1),
+            Codec::Msgpack,
+            TraceIdWidth::Bits64,
+            &mut bytes,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let pos = bytes.windows(from.len()).position(|w| w == from).unwrap();
+        bytes[pos..pos + from.len()].copy_from_slice(protocol.as_bytes());
+        bytes
+    }
+
+    fn decode_as<M: Message>(bytes: &[u8], stats: &mut DecodeStats) -> Option<M> {
+        decode_with_as(bytes, Codec::Msgpack, stats)
+    }
+
+    fn decode_with_as<M: Message>(
+        bytes: &[u8],
+        codec: Codec,
+        stats: &mut DecodeStats,
+    ) -> Option<M> {
+        match decode(bytes, codec, TraceIdWidth::Bits64, stats).unwrap() {
+            DecodeState::Done {
+                bytes_consumed,
+                decoded,
+            } => {
+                assert_eq!(bytes_consumed, bytes.len());
+                let NetworkEnvelopePayload::Regular { message } = decoded.payload else {
+                    panic!("expected a regular message");
+                };
+                Some(message.downcast::<M>().unwrap())
+            }
+            DecodeState::Skipped { bytes_consumed, .. } => {
+                assert_eq!(bytes_consumed, bytes.len());
+                None
+            }
+            DecodeState::NeedMoreData { .. } => panic!("unexpected end of data"),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let mut stats = DecodeStats::default();
+        let bytes = encode_as(EvolvingV2 { a: 1, b: 2 }, "evolution-v1");
+        let decoded = decode_as::<EvolvingV1>(&bytes, &mut stats);
+
+        assert_eq!(decoded, Some(EvolvingV1 { a: 1 }));
+        assert_eq!(stats.total_messages_decoded, 1);
+        assert_eq!(stats.total_fields_ignored, 1);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let mut stats = DecodeStats::default();
+        let bytes = encode_as(EvolvingV1 { a: 1 }, "evolution-v2");
+        let decoded = decode_as::<EvolvingV2>(&bytes, &mut stats);
+
+        assert_eq!(decoded, Some(EvolvingV2 { a: 1, b: 0 }));
+        assert_eq!(stats.total_fields_ignored, 0);
+    }
+
+    #[test]
+    fn missing_required_fields_are_skipped() {
+        let mut stats = DecodeStats::default();
+        let mut bytes = encode_as(EvolvingV1 { a: 1 }, "evolution-v3");
+
+        // The next message must be decoded successfully.
+        let skipped_len = bytes.len();
+        bytes.extend(encode_as(EvolvingV1 { a: 2 }, "evolution-v1"));
+
+        assert_eq!(
+            decode_as::<EvolvingV3>(&bytes[..skipped_len], &mut stats),
+            None
+        );
+        assert_eq!(
+            decode_as::<EvolvingV1>(&bytes[skipped_len..], &mut stats),
+            Some(EvolvingV1 { a: 2 })
+        );
+        assert_eq!(stats.total_messages_decoding_skipped, 1);
+        assert_eq!(stats.total_messages_decoded, 1);
+    }
+
+    #[test]
+    fn strict_messages_reject_unknown_fields() {
+        let mut stats = DecodeStats::default();
+        let bytes = encode_as(EvolvingV2 { a: 1, b: 2 }, "evolution-vs");
+        assert_eq!(decode_as::<EvolvingStrict>(&bytes, &mut stats), None);
+
+        let bytes = encode_as(EvolvingV1 { a: 1 }, "evolution-vs");
+        let decoded = decode_as::<EvolvingStrict>(&bytes, &mut stats);
+        assert_eq!(decoded, Some(EvolvingStrict { a: 1 }));
+    }
+
+    /// Encodes the message by postcard and replaces its id to emulate a
+    /// receiver compiled with another version of the message.
+    fn encode_postcard_as(message: impl Message, id: u64) -> Vec<u8> {
+        let from = AnyMessage::new(message.clone()).network_id().to_le_bytes();
+
+        let mut bytes = Vec::new();
+        encode(
+            &make_envelope(message, 1),
+            Codec::Postcard,
+            TraceIdWidth::Bits64,
+            &mut bytes,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let pos = bytes.windows(8).position(|w| w == from).unwrap();
+        bytes[pos..pos + 8].copy_from_slice(&id.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn postcard_is_compact() {
+        let envelope = make_envelope(EvolvingV2 { a: 1, b: 2 }, 1);
+
+        let mut msgpack = Vec::new();
+        encode(
+            &envelope,
+            Codec::Msgpack,
+            TraceIdWidth::Bits64,
+            &mut msgpack,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut postcard = Vec::new();
+        encode(
+            &envelope,
+            Codec::Postcard,
+            TraceIdWidth::Bits64,
+            &mut postcard,
+            &mut Default::default(),
+            None,
+        )
+        .unwrap();
+
+        assert!(postcard.len() < msgpack.len());
+    }
+
+    #[test]
+    fn postcard_rejects_schema_mismatch() {
+        let mut stats = DecodeStats::default();
+        let v3_id = AnyMessage::new(EvolvingV3 { a: 0, c: 0 }).network_id();
+        let mut bytes = encode_postcard_as(EvolvingV2 { a: 1, b: 2 }, v3_id);
+
+        // The next message must be decoded successfully.
+        let skipped_len = bytes.len();
+        bytes.extend(encode_postcard_as(EvolvingV3 { a: 1, c: 3 }, v3_id));
+
+        let skipped = &bytes[..skipped_len];
+        assert_eq!(
+            decode_with_as::<EvolvingV3>(skipped, Codec::Postcard, &mut stats),
+            None
+        );
+        assert_eq!(
+            decode_with_as::<EvolvingV3>(&bytes[skipped_len..], Codec::Postcard, &mut stats),
+            Some(EvolvingV3 { a: 1, c: 3 })
+        );
+        assert_eq!(stats.total_messages_decoding_skipped, 1);
+        assert_eq!(stats.total_messages_decoded, 1);
+    }
+
+    #[test]
+    fn postcard_skips_unknown_messages() {
+        let mut stats = DecodeStats::default();
+        let bytes = encode_postcard_as(EvolvingV1 { a: 1 }, 42);
+
+        match decode(&bytes, Codec::Postcard, TraceIdWidth::Bits64, &mut stats).unwrap() {
+            DecodeState::Skipped {
+                bytes_consumed,
+                details,
+            } => {
+                assert_eq!(bytes_consumed, bytes.len());
+                assert!(details.unwrap().is_unknown_message);
+            }
+            _ => panic!("expected the message to be skipped"),
+        }
+    }
+
     // TODO: test errors (including mismatch node_no).
 }
@@ -4,9 +4,7 @@
 //! and are not subject to stable guarantees. However, the config
 //! structure (usually encoded in TOML) follows stable guarantees.
 
-#[cfg(unix)]
-use std::path::PathBuf;
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, str::FromStr};
 
 use derive_more::Display;
 use eyre::{bail, Result};
@@ -15,6 +13,8 @@ use serde::{
     Deserialize, Serialize,
 };
 
+use elfo_core::config::{ByteSize, Duration};
+
 /// The network actors' config.
 ///
 /// # Examples
@@ -37,13 +37,20 @@ pub struct Config {
     /// Compression settings.
     #[serde(default)]
     pub compression: CompressionConfig,
+    /// How messages are serialized, see [`Codec`].
+    ///
+    /// Changes are applied only to new connections.
+    ///
+    /// `"msgpack"` by default.
+    #[serde(default)]
+    pub codec: Codec,
     /// How often nodes should ping each other.
     ///
     /// Pings are used to measure RTT and detect dead connections.
     /// For the latest purpose, see `idle_timeout`.
     ///
     /// `5s` by default.
-    #[serde(with = "humantime_serde", default = "default_ping_interval")]
+    #[serde(default = "default_ping_interval")]
     pub ping_interval: Duration,
     /// The maximum inactivity time of every connection.
     ///
@@ -54,8 +61,173 @@ pub struct Config {
     /// lies in the range of `idle_timeout` to `idle_timeout + ping_interval`.
     ///
     /// `30s` by default.
-    #[serde(with = "humantime_serde", default = "default_idle_timeout")]
+    #[serde(default = "default_idle_timeout")]
     pub idle_timeout: Duration,
+    /// If set, data connections are established on demand and closed
+    /// after `idle_close` time without user traffic. Pings and other
+    /// internal messages don't prevent closing. Idleness is checked every
+    /// `ping_interval`. Control connections, which are used for discovery,
+    /// are never closed for idleness.
+    ///
+    /// Messages sent while the connection is being established are queued,
+    /// the queue is bounded by flow control as usual.
+    ///
+    /// Connections are reopened on demand only by the side that knows the
+    /// peer's address, so it's recommended to list all nodes in
+    /// `discovery.predefined` on every node.
+    ///
+    /// Disabled by default.
+    #[serde(default)]
+    pub idle_close: Option<Duration>,
+    /// Envelopes encoded into more than `chunk_threshold` bytes are sent
+    /// by chunks of `chunk_size` bytes, interleaved with other messages.
+    /// It prevents blocking the connection by large messages.
+    ///
+    /// Changes are applied only to new connections.
+    ///
+    /// `"1MiB"` by default.
+    #[serde(default = "default_chunk_threshold")]
+    pub chunk_threshold: ByteSize,
+    /// The size of chunks, see `chunk_threshold`.
+    ///
+    /// `"64KiB"` by default.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: ByteSize,
+    /// The maximum total size of envelopes received by chunks at once on
+    /// a connection. Envelopes exceeding it are discarded without buffering.
+    ///
+    /// Changes are applied only to new connections.
+    ///
+    /// `"512MiB"` by default.
+    #[serde(default = "default_max_transfer_size")]
+    pub max_transfer_size: ByteSize,
+    /// Authentication of peers.
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Protection against replayed requests.
+    #[serde(default)]
+    pub replay_protection: ReplayProtectionConfig,
+}
+
+/// Authentication of peers by tokens.
+///
+/// The connecting side presents its token, the listening side validates it
+/// against `accept` entries and restricts the connection to allowed groups.
+/// Messages to other local groups are rejected, requests are responded with
+/// `ErrorKind::Forbidden`. If there are no `accept` entries, all peers
+/// are accepted without restrictions.
+///
+/// Updated entries are applied to existing connections, but connections
+/// authenticated by removed tokens are kept unless `revoke` is set.
+///
+/// # Examples
+/// ```toml
+/// [system.network.auth]
+/// token_path = "/etc/elfo/token"
+///
+/// [[system.network.auth.accept]]
+/// # `echo -n "<token>" | sha256sum`
+/// token_hash = "930bbdc51b6aed5c2a5678fd6e28dee7a05e8a4b643cfc0b4427c3efb86c0d94"
+/// allowed_groups = ["telemetry", "orders"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    /// A token presented to listening peers.
+    pub token: Option<String>,
+    /// A path to the file containing the token, an alternative to `token`.
+    /// Leading and trailing whitespaces are trimmed.
+    pub token_path: Option<PathBuf>,
+    /// Tokens accepted from connecting peers.
+    #[serde(default)]
+    pub accept: Vec<AuthAcceptor>,
+    /// Whether to close connections authenticated by tokens, which are no
+    /// longer accepted after updating the config.
+    ///
+    /// `false` by default.
+    #[serde(default)]
+    pub revoke: bool,
+}
+
+/// Protection of local actors against replayed requests.
+///
+/// Connecting peers stamp requests with increasing per-connection nonces.
+/// Listeners track a sliding window of recently seen nonces per connection
+/// and discard duplicates and nonces older than the window, so a captured
+/// request frame cannot be executed twice. Discarded requests aren't
+/// responded, because the response would resolve the original request.
+/// They're logged and counted by `elfo_network_replayed_requests_total`.
+///
+/// Reconnection resets the window, so the common case is cheap.
+///
+/// Listeners aren't updated, so changes are applied only after restart.
+///
+/// # Examples
+/// ```toml
+/// [system.network.replay_protection]
+/// enabled = true
+///
+/// [[system.network.replay_protection.listeners]]
+/// listen = "tcp://0.0.0.0:8150"
+/// window = 4096
+/// strict = true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplayProtectionConfig {
+    /// Whether requests received by listeners are checked.
+    ///
+    /// `false` by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The number of recently seen nonces, up to 65536.
+    /// Requests can be reordered only within the window.
+    ///
+    /// `1024` by default.
+    #[serde(default = "default_replay_window")]
+    pub window: u32,
+    /// Whether to refuse connections from peers, which don't stamp requests,
+    /// e.g. nodes of older versions. Otherwise, such connections aren't
+    /// protected.
+    ///
+    /// `false` by default.
+    #[serde(default)]
+    pub strict: bool,
+    /// Overrides for specific listeners.
+    #[serde(default)]
+    pub listeners: Vec<ListenerReplayProtection>,
+}
+
+impl Default for ReplayProtectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: default_replay_window(),
+            strict: false,
+            listeners: Vec::new(),
+        }
+    }
+}
+
+/// Overrides of [`ReplayProtectionConfig`] for the listener.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerReplayProtection {
+    /// One of addresses from `listen`.
+    pub listen: Transport,
+    /// Overrides `enabled`.
+    pub enabled: Option<bool>,
+    /// Overrides `window`.
+    pub window: Option<u32>,
+    /// Overrides `strict`.
+    pub strict: Option<bool>,
+}
+
+/// A token accepted from connecting peers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthAcceptor {
+    /// The hex encoded SHA-256 hash of the token.
+    pub token_hash: String,
+    /// Local groups the peer is allowed to send messages to.
+    /// All groups if not specified.
+    pub allowed_groups: Option<Vec<String>>,
 }
 
 /// Compression settings.
@@ -76,6 +248,44 @@ pub enum CompressionAlgorithm {
     None,
 }
 
+/// Serialization formats of messages.
+///
+/// A non-default codec is used only if both nodes are configured to use it,
+/// otherwise msgpack is used. It allows changing the codec by rolling
+/// upgrades.
+#[derive(
+    Debug,
+    Display,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Self-describing msgpack with named fields, the message is identified
+    /// by its protocol and name.
+    ///
+    /// Unknown fields are ignored and missing ones are defaulted if possible,
+    /// so it tolerates different versions of messages on nodes.
+    #[default]
+    #[display("msgpack")]
+    Msgpack,
+    /// Compact non-self-describing postcard, the message is identified by a
+    /// hash of its protocol and name.
+    ///
+    /// Messages must have the same shape on both nodes. It's checked by
+    /// comparing hashes of fields (but not nested types), messages with
+    /// different hashes are rejected. Also, it doesn't support
+    /// `#[serde(flatten)]`, `#[serde(untagged)]` and other features that
+    /// require a self-describing format.
+    #[display("postcard")]
+    Postcard,
+}
+
 fn default_ping_interval() -> Duration {
     Duration::from_secs(5)
 }
@@ -84,13 +294,29 @@ fn default_idle_timeout() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_chunk_threshold() -> ByteSize {
+    ByteSize::new(1024 * 1024)
+}
+
+fn default_chunk_size() -> ByteSize {
+    ByteSize::new(64 * 1024)
+}
+
+pub(crate) fn default_max_transfer_size() -> ByteSize {
+    ByteSize::new(512 * 1024 * 1024)
+}
+
+fn default_replay_window() -> u32 {
+    1024
+}
+
 /// How to discover other nodes.
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct DiscoveryConfig {
     /// Predefined list of transports to connect to.
     pub predefined: Vec<Transport>,
     /// How often to attempt to connect to other nodes.
-    #[serde(with = "humantime_serde", default = "default_attempt_interval")]
+    #[serde(default = "default_attempt_interval")]
     pub attempt_interval: Duration,
 }
 
@@ -116,6 +342,14 @@ pub enum Transport {
     #[cfg(feature = "turmoil06")]
     #[display("turmoil06://{_0}")]
     Turmoil06(String),
+    /// In-process transport ("inproc://name").
+    ///
+    /// Connections are pairs of in-memory pipes, so both nodes must run in
+    /// the same process. Unlike local sends, messages pass the whole
+    /// serialization and framing pipeline, so it's useful for testing and
+    /// benchmarking without binding ports.
+    #[display("inproc://{_0}")]
+    Inproc(String),
 }
 
 impl FromStr for Transport {
@@ -123,9 +357,9 @@ impl FromStr for Transport {
 
     fn from_str(s: &str) -> Result<Self> {
         #[cfg(unix)]
-        const PROTOCOLS: &str = "tcp or uds";
+        const PROTOCOLS: &str = "tcp, uds or inproc";
         #[cfg(not(unix))]
-        const PROTOCOLS: &str = "tcp";
+        const PROTOCOLS: &str = "tcp or inproc";
 
         let (protocol, addr) = s.split_once("://").unwrap_or_default();
 
@@ -142,6 +376,13 @@ impl FromStr for Transport {
             }
             #[cfg(feature = "turmoil06")]
             "turmoil06" => Ok(Transport::Turmoil06(addr.into())),
+            "inproc" => {
+                eyre::ensure!(
+                    !addr.is_empty(),
+                    "name of inproc transport must be specified"
+                );
+                Ok(Transport::Inproc(addr.into()))
+            }
             proto => bail!("unknown protocol: {proto}"),
         }
     }
@@ -220,5 +461,15 @@ mod tests {
             Transport::from_str("turmoil06://alice").unwrap(),
             Transport::Turmoil06("alice".into())
         );
+
+        // Inproc
+        assert_eq!(
+            Transport::from_str("inproc://alice").unwrap(),
+            Transport::Inproc("alice".into())
+        );
+        assert_eq!(
+            Transport::from_str("inproc://").unwrap_err().to_string(),
+            "name of inproc transport must be specified"
+        );
     }
 }
@@ -2,20 +2,29 @@ use std::{future::Future, mem, sync::Arc, time::Duration};
 
 use eyre::{bail, eyre, Result, WrapErr};
 use futures::StreamExt;
+use fxhash::FxHashMap;
+use tokio::sync::watch;
 use tracing::{debug, error, info, warn};
 
 use elfo_core::{
-    message, msg, scope, tracing::TraceId, AnyMessage, Envelope, Message, MoveOwnership,
-    RestartPolicy, _priv::MessageKind, addr::GroupNo, messages::ConfigUpdated, stream::Stream,
-    RestartParams, Topology,
+    _priv::MessageKind,
+    addr::{GroupNo, NodeLaunchId, NodeNo},
+    message,
+    messages::ConfigUpdated,
+    msg, scope,
+    stream::{Emitter, Stream},
+    time::Interval,
+    tracing::TraceId,
+    AnyMessage, Envelope, Message, MoveOwnership, RestartParams, RestartPolicy, Topology,
 };
 
 use crate::{
     codec::format::{NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload},
-    config::{self, CompressionAlgorithm, Transport},
+    config::{self, Codec, CompressionAlgorithm, Transport},
     node_map::{NodeInfo, NodeMap},
-    protocol::{internode, DataConnectionFailed, GroupInfo, HandleConnection},
-    socket::{self, ReadError, Socket},
+    protocol::{internode, DataConnectionFailed, GroupInfo, HandleConnection, OpenDataConnection},
+    socket::{self, Authenticator, ReadError, ReplayPolicy, Socket},
+    stats::{GetConnectionStats, StatsRegistry},
     NetworkContext,
 };
 
@@ -27,6 +36,9 @@ mod diff;
 /// TODO: should be different for groups and actors.
 const INITIAL_WINDOW_SIZE: i32 = 100_000;
 
+/// How often the local topology is checked for new groups and interests.
+const TOPOLOGY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 #[message]
 struct ConnectionEstablished {
     role: ConnectionRole,
@@ -72,10 +84,29 @@ struct ControlConnectionFailed {
     transport: Option<Transport>,
 }
 
+#[message]
+struct RemoteGroupsUpdated {
+    node_no: NodeNo,
+    groups: Vec<internode::GroupInfo>,
+    // `Some` only on the client side.
+    transport: Option<Transport>,
+}
+
+#[message]
+struct TopologyTick;
+
 pub(super) struct Discovery {
     cfg: config::Config,
     ctx: NetworkContext,
-    node_map: Arc<NodeMap>,
+    topology: Topology,
+    topology_generation: u64,
+    node_map: NodeMap,
+    /// Local groups announced to peers over control connections.
+    groups_tx: watch::Sender<Vec<internode::GroupInfo>>,
+    /// Transports of nodes this node is a client of.
+    transports: FxHashMap<NodeNo, Transport>,
+    authenticator: Arc<Authenticator>,
+    stats: Arc<StatsRegistry>,
 }
 
 // TODO: move control connections to dedicated actors.
@@ -86,12 +117,27 @@ pub(super) struct Discovery {
 // TODO: graceful termination.
 
 impl Discovery {
-    pub(super) fn new(ctx: NetworkContext, topology: Topology) -> Self {
+    pub(super) fn new(
+        ctx: NetworkContext,
+        topology: Topology,
+        authenticator: Arc<Authenticator>,
+        stats: Arc<StatsRegistry>,
+    ) -> Self {
         let cfg = ctx.config().clone();
+        let topology_generation = topology.generation();
+        let node_map = NodeMap::new(&topology);
+        let (groups_tx, _) = watch::channel(node_map.this.groups.clone());
+
         Self {
             cfg,
             ctx,
-            node_map: Arc::new(NodeMap::new(&topology)),
+            topology,
+            topology_generation,
+            node_map,
+            groups_tx,
+            transports: Default::default(),
+            authenticator,
+            stats,
         }
     }
 
@@ -103,9 +149,16 @@ impl Discovery {
                 Duration::from_secs(30),
             )));
 
+        self.authenticator
+            .configure(&self.cfg.auth)
+            .wrap_err("invalid `auth` config")?;
         self.listen().await?;
         self.discover_all();
 
+        self.ctx
+            .attach(Interval::new(TopologyTick))
+            .start(TOPOLOGY_CHECK_INTERVAL);
+
         while let Some(envelope) = self.ctx.recv().await {
             msg!(match envelope {
                 ConfigUpdated => {
@@ -123,12 +176,25 @@ impl Discovery {
                     });
                     self.open_connection(&msg.transport, role);
                 }
+                msg @ OpenDataConnection => {
+                    let role = ConnectionRole::Data(internode::SwitchToData {
+                        my_group_no: msg.local,
+                        your_group_no: msg.remote.1,
+                        initial_window: INITIAL_WINDOW_SIZE,
+                    });
+                    self.open_connection(&msg.transport, role);
+                }
                 msg @ ControlConnectionFailed => {
                     if let Some(transport) = msg.transport {
                         tokio::time::sleep(std::time::Duration::from_secs(5)).await;
                         self.discover(transport);
                     }
                 }
+                msg @ RemoteGroupsUpdated => self.on_remote_groups_updated(msg),
+                TopologyTick => self.on_topology_tick(),
+                (GetConnectionStats, token) => {
+                    self.ctx.respond(token, self.stats.snapshot());
+                }
             });
         }
 
@@ -136,10 +202,20 @@ impl Discovery {
     }
 
     fn get_capabilities(&self) -> socket::Capabilities {
-        let mut capabilities = socket::Capabilities::empty();
+        let mut capabilities = socket::Capabilities::CHUNKING
+            | socket::Capabilities::REQUEST_LIMITS
+            | socket::Capabilities::ACKS
+            | socket::Capabilities::IDEMPOTENCY_KEYS
+            | socket::Capabilities::EXTENDED_RESPONSES;
         if self.cfg.compression.algorithm == CompressionAlgorithm::Lz4 {
             capabilities |= socket::Capabilities::LZ4;
         }
+        if self.cfg.codec == Codec::Postcard {
+            capabilities |= socket::Capabilities::POSTCARD;
+        }
+        if TraceId::BITS == 128 {
+            capabilities |= socket::Capabilities::TRACE_ID_128;
+        }
         capabilities
     }
 
@@ -148,6 +224,13 @@ impl Discovery {
         let cfg = self.ctx.config().clone();
         let old = mem::replace(&mut self.cfg, cfg);
 
+        if let Err(err) = self.authenticator.configure(&self.cfg.auth) {
+            error!(
+                message = "invalid `auth` config, the previous one is used",
+                error = format!("{:#}", err),
+            );
+        }
+
         self.update_discovery(old.discovery);
     }
 
@@ -182,26 +265,26 @@ impl Discovery {
         let capabilities = self.get_capabilities();
 
         for transport in &self.cfg.listen {
-            let stream = socket::listen(transport, node_no, launch_id, capabilities)
-                .await
-                .wrap_err_with(|| eyre!("cannot listen {}", transport))?
-                .filter_map(move |socket| async move {
-                    if socket.peer.node_no != node_no {
-                        Some(socket)
-                    } else {
-                        info!(
-                            message = "connection to self ignored",
-                            socket = %socket.info,
-                            peer = %socket.peer,
-                        );
-                        None
-                    }
-                })
-                .map(|socket| ConnectionEstablished {
-                    role: ConnectionRole::Unknown,
-                    socket: socket.into(),
-                    transport: None,
-                });
+            let authenticator = self.authenticator.clone();
+            let replay = ReplayPolicy::for_listener(&self.cfg.replay_protection, transport);
+            let stream = socket::listen(
+                transport,
+                node_no,
+                launch_id,
+                capabilities,
+                authenticator,
+                replay,
+            )
+            .await
+            .wrap_err_with(|| eyre!("cannot listen {}", transport))?
+            .filter_map(move |socket| async move {
+                check_peer(&socket, node_no, launch_id).then_some(socket)
+            })
+            .map(|socket| ConnectionEstablished {
+                role: ConnectionRole::Unknown,
+                socket: socket.into(),
+                transport: None,
+            });
 
             info!(
                 message = "listening for connections",
@@ -232,11 +315,12 @@ impl Discovery {
         transport: &Transport,
         role: ConnectionRole,
     ) -> Stream<ConnectionEstablished> {
-        let interval = self.cfg.discovery.attempt_interval;
+        let interval = *self.cfg.discovery.attempt_interval;
         let transport = transport.clone();
         let node_no = self.node_map.this.node_no;
         let launch_id = self.node_map.this.launch_id;
         let capabilities = self.get_capabilities();
+        let authenticator = self.authenticator.clone();
 
         let shift =
             std::time::Duration::from_millis(self.node_map.this.launch_id.into_bits() % 5000);
@@ -245,20 +329,24 @@ impl Discovery {
             loop {
                 debug!(message = "connecting to peer", addr = %transport, role = ?role);
 
-                match socket::connect(&transport, node_no, launch_id, capabilities).await {
+                // Read on every attempt to use the rotated token.
+                let token = authenticator.token();
+                let connecting = socket::connect(
+                    &transport,
+                    node_no,
+                    launch_id,
+                    capabilities,
+                    token.as_deref(),
+                );
+
+                match connecting.await {
                     Ok(socket) => {
-                        if socket.peer.node_no != node_no {
+                        if check_peer(&socket, node_no, launch_id) {
                             break ConnectionEstablished {
                                 role,
                                 socket: socket.into(),
                                 transport: Some(transport),
                             };
-                        } else {
-                            info!(
-                                message = "connection to self ignored",
-                                socket = %socket.info,
-                                peer = %socket.peer,
-                            );
                         }
                     }
                     Err(err) => {
@@ -291,14 +379,19 @@ impl Discovery {
             role = msg.role.as_str(),
         );
 
-        let node_map = self.node_map.clone();
-        let idle_timeout = self.cfg.idle_timeout;
+        if let ConnectionRole::Data(data) = &msg.role {
+            let remote = (socket.peer.node_no, data.your_group_no);
+            self.stats.on_handshaking(data.my_group_no, remote);
+        }
+
+        let this_node = self.node_map.this.clone();
+        let idle_timeout = *self.cfg.idle_timeout;
         self.ctx.attach(Stream::once(async move {
             let info = socket.info.clone();
             let peer = socket.peer.clone();
 
             let result =
-                accept_connection(socket, msg.role, transport, &node_map.this, idle_timeout).await;
+                accept_connection(socket, msg.role, transport, &this_node, idle_timeout).await;
             match result {
                 Ok(accepted) => Ok(accepted),
                 Err(err) => {
@@ -342,6 +435,7 @@ impl Discovery {
                     // TODO: check launch_id.
                 }
 
+                let peer_node_no = socket.peer.node_no;
                 self.control_maintenance(socket, msg.transport.clone());
 
                 // Only initiator (client) can start new connections,
@@ -350,26 +444,11 @@ impl Discovery {
                     return;
                 };
 
-                let this_node = &self.node_map.clone().this;
+                self.transports.insert(peer_node_no, transport.clone());
 
                 // Open connections for all interesting pairs of groups.
-                infer_connections(&remote.groups, &this_node.groups)
-                    .map(|(remote_group_no, local_group_no)| (local_group_no, remote_group_no))
-                    .chain(infer_connections(&this_node.groups, &remote.groups))
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .for_each(|(local_group_no, remote_group_no)| {
-                        // TODO: save stream to cancel later.
-                        // TODO: connect without DNS resolving here.
-                        self.open_connection(
-                            &transport,
-                            ConnectionRole::Data(internode::SwitchToData {
-                                my_group_no: local_group_no,
-                                your_group_no: remote_group_no,
-                                initial_window: INITIAL_WINDOW_SIZE,
-                            }),
-                        );
-                    });
+                let pairs = infer_pairs(&self.node_map.this.groups, &remote.groups);
+                self.connect_groups(peer_node_no, &remote.groups, pairs, &transport);
             }
             ConnectionRole::Data(remote) => {
                 let local_group_name = self
@@ -413,7 +492,7 @@ impl Discovery {
                             group_name: remote_group_name,
                         },
                         transport: msg.transport.clone(),
-                        socket: socket.into(),
+                        socket: Some(socket.into()),
                         initial_window: remote.initial_window,
                     },
                 );
@@ -426,23 +505,181 @@ impl Discovery {
         }
     }
 
+    /// Opens data connections (or starts lazy workers) for the provided pairs
+    /// of `(local_group_no, remote_group_no)`.
+    fn connect_groups(
+        &mut self,
+        peer_node_no: NodeNo,
+        remote_groups: &[internode::GroupInfo],
+        pairs: Vec<(GroupNo, GroupNo)>,
+        transport: &Transport,
+    ) {
+        let is_lazy = self.cfg.idle_close.is_some();
+
+        for (local_group_no, remote_group_no) in pairs {
+            if is_lazy {
+                // The connection will be opened by the worker on demand.
+                self.start_worker(
+                    (peer_node_no, remote_groups),
+                    local_group_no,
+                    remote_group_no,
+                    transport.clone(),
+                );
+                continue;
+            }
+
+            // TODO: save stream to cancel later.
+            // TODO: connect without DNS resolving here.
+            self.open_connection(
+                transport,
+                ConnectionRole::Data(internode::SwitchToData {
+                    my_group_no: local_group_no,
+                    your_group_no: remote_group_no,
+                    initial_window: INITIAL_WINDOW_SIZE,
+                }),
+            );
+        }
+    }
+
+    /// Announces local groups added after connections are established, and
+    /// connects them to already known nodes.
+    fn on_topology_tick(&mut self) {
+        let generation = self.topology.generation();
+        if generation == self.topology_generation {
+            return;
+        }
+
+        self.topology_generation = generation;
+
+        let groups = NodeMap::local_groups(&self.topology);
+        if groups == self.node_map.this.groups {
+            return;
+        }
+
+        info!(
+            message = "local groups changed, announcing them",
+            groups = groups.len(),
+        );
+
+        let old_groups = mem::replace(&mut self.node_map.this.groups, groups);
+        self.groups_tx
+            .send_replace(self.node_map.this.groups.clone());
+
+        // Only a client can open new connections.
+        let peers = {
+            let nodes = self.node_map.nodes.lock();
+            self.transports
+                .iter()
+                .filter_map(|(node_no, transport)| {
+                    let node = nodes.get(node_no)?;
+                    let old_pairs = infer_pairs(&old_groups, &node.groups);
+                    let pairs = infer_pairs(&self.node_map.this.groups, &node.groups)
+                        .into_iter()
+                        .filter(|pair| !old_pairs.contains(pair))
+                        .collect::<Vec<_>>();
+                    Some((*node_no, node.groups.clone(), pairs, transport.clone()))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (node_no, remote_groups, pairs, transport) in peers {
+            self.connect_groups(node_no, &remote_groups, pairs, &transport);
+        }
+    }
+
+    fn on_remote_groups_updated(&mut self, msg: RemoteGroupsUpdated) {
+        info!(
+            message = "remote groups updated",
+            node_no = %msg.node_no,
+            groups = msg.groups.len(),
+        );
+
+        let old_groups = {
+            let mut nodes = self.node_map.nodes.lock();
+            let Some(node) = nodes.get_mut(&msg.node_no) else {
+                return;
+            };
+            mem::replace(&mut node.groups, msg.groups.clone())
+        };
+
+        // Only a client can open new connections.
+        let Some(transport) = msg.transport else {
+            return;
+        };
+
+        let old_pairs = infer_pairs(&self.node_map.this.groups, &old_groups);
+        let pairs = infer_pairs(&self.node_map.this.groups, &msg.groups)
+            .into_iter()
+            .filter(|pair| !old_pairs.contains(pair))
+            .collect();
+
+        self.connect_groups(msg.node_no, &msg.groups, pairs, &transport);
+    }
+
+    /// Starts a worker without a connection.
+    fn start_worker(
+        &self,
+        (remote_node_no, remote_groups): (NodeNo, &[internode::GroupInfo]),
+        local_group_no: GroupNo,
+        remote_group_no: GroupNo,
+        transport: Transport,
+    ) {
+        let this_node = &self.node_map.this;
+        let group_name = |groups: &[internode::GroupInfo], group_no| {
+            groups
+                .iter()
+                .find(|g| g.group_no == group_no)
+                .map(|g| g.name.clone())
+                .expect("invalid group no")
+        };
+
+        let res = self.ctx.try_send_to(
+            self.ctx.group(),
+            HandleConnection {
+                local: GroupInfo {
+                    node_no: this_node.node_no,
+                    group_no: local_group_no,
+                    group_name: group_name(&this_node.groups, local_group_no),
+                },
+                remote: GroupInfo {
+                    node_no: remote_node_no,
+                    group_no: remote_group_no,
+                    group_name: group_name(remote_groups, remote_group_no),
+                },
+                transport: Some(transport),
+                socket: None,
+                initial_window: INITIAL_WINDOW_SIZE,
+            },
+        );
+
+        if let Err(err) = res {
+            error!(message = "cannot start connection handler", error = %err);
+        }
+    }
+
     fn on_connection_rejected(&mut self, _msg: ConnectionRejected) {
         // TODO: something else? Retries?
     }
 
     fn control_maintenance(&mut self, mut socket: Socket, transport: Option<Transport>) {
-        self.ctx.attach(Stream::once(async move {
-            let err = control_maintenance(&mut socket).await.unwrap_err();
+        // The peer has already got current groups in `SwitchToControl`.
+        let mut groups = self.groups_tx.subscribe();
 
-            info!(
-                message = "control connection closed",
-                socket = %socket.info,
-                peer = %socket.peer,
-                reason = format!("{:#}", err), // TODO: use `AsRef<dyn Error>`
-            );
+        self.ctx
+            .attach(Stream::generate(move |mut emitter| async move {
+                let err = control_maintenance(&mut socket, &mut groups, &mut emitter, &transport)
+                    .await
+                    .unwrap_err();
+
+                info!(
+                    message = "control connection closed",
+                    socket = %socket.info,
+                    peer = %socket.peer,
+                    reason = format!("{:#}", err), // TODO: use `AsRef<dyn Error>`
+                );
 
-            ControlConnectionFailed { transport }
-        }));
+                emitter.emit(ControlConnectionFailed { transport }).await;
+            }));
     }
 }
 
@@ -498,7 +735,12 @@ async fn accept_connection(
     })
 }
 
-async fn control_maintenance(socket: &mut Socket) -> Result<()> {
+async fn control_maintenance(
+    socket: &mut Socket,
+    groups: &mut watch::Receiver<Vec<internode::GroupInfo>>,
+    emitter: &mut Emitter,
+    transport: &Option<Transport>,
+) -> Result<()> {
     // TODO: we should use these values from the config.
     // However, prior to it, this code should be rewritten to split logic of sending
     // pings and responding to pings. So, for now, we use hardcoded large
@@ -510,15 +752,103 @@ async fn control_maintenance(socket: &mut Socket) -> Result<()> {
     let mut interval = tokio::time::interval(ping_interval);
 
     loop {
-        interval.tick().await;
+        // Announce changes of local groups without waiting for the next tick.
+        let is_changed = tokio::select! {
+            _ = interval.tick() => false,
+            res = groups.changed() => {
+                res.wrap_err("discovery is stopped")?;
+                true
+            }
+        };
+
+        if socket.grant.is_revoked() {
+            bail!("access of the peer is revoked");
+        }
+
         scope::set_trace_id(TraceId::generate());
+
+        // `UpdateGroups` is sent only before `Ping`, so the peer receives it
+        // while waiting for `Ping` or `Pong`, see `recv_control()`.
+        if is_changed {
+            let groups = groups.borrow_and_update().clone();
+            send_regular(socket, idle_timeout, internode::UpdateGroups { groups }).await?;
+        }
+
         send_regular(socket, idle_timeout, internode::Ping { payload: 0 }).await?;
-        recv_regular::<internode::Ping>(socket, idle_timeout).await?;
+        recv_control::<internode::Ping>(socket, idle_timeout, emitter, transport).await?;
         send_regular(socket, idle_timeout, internode::Pong { payload: 0 }).await?;
-        recv_regular::<internode::Pong>(socket, idle_timeout).await?;
+        recv_control::<internode::Pong>(socket, idle_timeout, emitter, transport).await?;
     }
 }
 
+/// Like `recv_regular()`, but also handles `UpdateGroups` sent by the peer.
+async fn recv_control<M: Message>(
+    socket: &mut Socket,
+    idle_timeout: Duration,
+    emitter: &mut Emitter,
+    transport: &Option<Transport>,
+) -> Result<M> {
+    loop {
+        msg!(match recv(socket, idle_timeout).await? {
+            msg @ internode::UpdateGroups => {
+                let msg = RemoteGroupsUpdated {
+                    node_no: socket.peer.node_no,
+                    groups: msg.groups,
+                    transport: transport.clone(),
+                };
+                emitter.emit(msg).await;
+            }
+            msg @ M => return Ok(msg),
+            envelope => {
+                return Err(unexpected_message_error(
+                    envelope,
+                    &[
+                        &elfo_core::dumping::extract_name_by_type::<M>().to_string(),
+                        "UpdateGroups",
+                    ],
+                ));
+            }
+        })
+    }
+}
+
+/// Returns `false` if the connection must be ignored.
+fn check_peer(socket: &Socket, node_no: NodeNo, launch_id: NodeLaunchId) -> bool {
+    if socket.peer.node_no != node_no {
+        return true;
+    }
+
+    if socket.peer.launch_id == launch_id {
+        info!(
+            message = "connection to self ignored",
+            socket = %socket.info,
+            peer = %socket.peer,
+        );
+    } else {
+        // Another node is configured with the same `node_no`. It breaks routing
+        // and uniqueness of trace ids, so it's a misconfiguration that must be fixed.
+        error!(
+            message = "node_no conflict, connection ignored",
+            socket = %socket.info,
+            peer = %socket.peer,
+            node_no = %node_no,
+        );
+    }
+
+    false
+}
+
+/// Returns pairs of `(local_group_no, remote_group_no)` to be connected.
+fn infer_pairs(
+    local: &[internode::GroupInfo],
+    remote: &[internode::GroupInfo],
+) -> Vec<(GroupNo, GroupNo)> {
+    infer_connections(remote, local)
+        .map(|(remote_group_no, local_group_no)| (local_group_no, remote_group_no))
+        .chain(infer_connections(local, remote))
+        .collect()
+}
+
 fn infer_connections<'a>(
     one: &'a [internode::GroupInfo],
     two: &'a [internode::GroupInfo],
@@ -540,6 +870,7 @@ async fn send_regular<M: Message>(
         sender: NetworkAddr::NULL,    // doesn't matter
         recipient: NetworkAddr::NULL, // doesn't matter
         trace_id: scope::trace_id(),
+        is_force_sampled: false,
         payload: NetworkEnvelopePayload::Regular {
             message: AnyMessage::new(message),
         },
@@ -555,6 +886,7 @@ async fn recv(socket: &mut Socket, idle_timeout: Duration) -> Result<Envelope> {
     let receiving = async {
         socket.read.recv().await.map_err(|err| match err {
             ReadError::EnvelopeSkipped(..) => eyre!("failed to decode message"),
+            ReadError::Replayed(error, _) => eyre!("replayed request: {error}"),
             ReadError::Fatal(report) => report,
         })
     };
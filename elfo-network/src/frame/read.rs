@@ -4,8 +4,9 @@ use crate::{
     codec::{
         self,
         decode::{DecodeState, DecodeStats, EnvelopeDetails},
-        format::NetworkEnvelope,
+        format::{NetworkEnvelope, TraceIdWidth},
     },
+    config::Codec,
     frame::{
         buffers::{ReadBuffer, COMPRESSED_DATA_BUFFER_CAPACITY, DECOMPRESSED_DATA_BUFFER_CAPACITY},
         lz4::{DecompressState, DecompressStats, LZ4Buffer},
@@ -18,12 +19,12 @@ pub(crate) enum FramedRead {
 }
 
 impl FramedRead {
-    pub(crate) fn lz4() -> Self {
-        FramedRead::Lz4(LZ4FramedRead::new())
+    pub(crate) fn lz4(codec: Codec, trace_id_width: TraceIdWidth) -> Self {
+        FramedRead::Lz4(LZ4FramedRead::new(codec, trace_id_width))
     }
 
-    pub(crate) fn none() -> Self {
-        FramedRead::None(NoneFramedRead::new())
+    pub(crate) fn none(codec: Codec, trace_id_width: TraceIdWidth) -> Self {
+        FramedRead::None(NoneFramedRead::new(codec, trace_id_width))
     }
 }
 
@@ -83,15 +84,19 @@ pub(crate) struct LZ4FramedRead {
     decompressed_buffer: LZ4Buffer,
     stats: FramedReadStats,
     position: usize,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
 }
 
 impl LZ4FramedRead {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(codec: Codec, trace_id_width: TraceIdWidth) -> Self {
         Self {
             compressed_buffer: ReadBuffer::with_capacity(COMPRESSED_DATA_BUFFER_CAPACITY),
             decompressed_buffer: LZ4Buffer::with_capacity(DECOMPRESSED_DATA_BUFFER_CAPACITY),
             stats: Default::default(),
             position: 0,
+            codec,
+            trace_id_width,
         }
     }
 }
@@ -136,8 +141,12 @@ impl FramedReadStrategy for LZ4FramedRead {
             // will be skipped and we will try to decompress the next frame.
             'decoding: loop {
                 let envelope_buffer = &self.decompressed_buffer.filled_slice()[self.position..];
-                let codec_state =
-                    codec::decode::decode(envelope_buffer, &mut self.stats.decode_stats)?;
+                let codec_state = codec::decode::decode(
+                    envelope_buffer,
+                    self.codec,
+                    self.trace_id_width,
+                    &mut self.stats.decode_stats,
+                )?;
                 match codec_state {
                     DecodeState::NeedMoreData { .. } => {
                         if self.position == self.decompressed_buffer.len() {
@@ -186,13 +195,17 @@ impl FramedReadStrategy for LZ4FramedRead {
 pub(crate) struct NoneFramedRead {
     buffer: ReadBuffer,
     stats: FramedReadStats,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
 }
 
 impl NoneFramedRead {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(codec: Codec, trace_id_width: TraceIdWidth) -> Self {
         Self {
             buffer: ReadBuffer::with_capacity(DECOMPRESSED_DATA_BUFFER_CAPACITY),
             stats: Default::default(),
+            codec,
+            trace_id_width,
         }
     }
 }
@@ -200,8 +213,12 @@ impl NoneFramedRead {
 impl FramedReadStrategy for NoneFramedRead {
     fn read(&mut self) -> Result<FramedReadState<'_>> {
         loop {
-            let codec_state =
-                codec::decode::decode(self.buffer.filled_slice(), &mut self.stats.decode_stats)?;
+            let codec_state = codec::decode::decode(
+                self.buffer.filled_slice(),
+                self.codec,
+                self.trace_id_width,
+                &mut self.stats.decode_stats,
+            )?;
             match codec_state {
                 DecodeState::NeedMoreData {
                     total_length_estimate,
@@ -5,8 +5,9 @@ use crate::{
     codec::{
         self,
         encode::{EncodeError, EncodeStats},
-        format::NetworkEnvelope,
+        format::{NetworkEnvelope, TraceIdWidth},
     },
+    config::Codec,
     frame::lz4::{CompressStats, LZ4Buffer},
 };
 
@@ -22,12 +23,28 @@ pub(crate) enum FramedWrite {
 }
 
 impl FramedWrite {
-    pub(crate) fn lz4(envelope_size_limit: Option<usize>) -> Self {
-        FramedWrite::Lz4(LZ4FramedWrite::new(envelope_size_limit))
+    pub(crate) fn lz4(
+        codec: Codec,
+        trace_id_width: TraceIdWidth,
+        envelope_size_limit: Option<usize>,
+    ) -> Self {
+        FramedWrite::Lz4(LZ4FramedWrite::new(
+            codec,
+            trace_id_width,
+            envelope_size_limit,
+        ))
     }
 
-    pub(crate) fn none(envelope_size_limit: Option<usize>) -> Self {
-        FramedWrite::None(NoneFramedWrite::new(envelope_size_limit))
+    pub(crate) fn none(
+        codec: Codec,
+        trace_id_width: TraceIdWidth,
+        envelope_size_limit: Option<usize>,
+    ) -> Self {
+        FramedWrite::None(NoneFramedWrite::new(
+            codec,
+            trace_id_width,
+            envelope_size_limit,
+        ))
     }
 }
 
@@ -42,6 +59,17 @@ pub(crate) struct FramedWriteStats {
 pub(crate) trait FramedWriteStrategy {
     fn write(&mut self, envelope: &NetworkEnvelope) -> Result<FrameState, EncodeError>;
 
+    /// Removes the last written envelope from the frame if it's encoded into
+    /// more than `threshold` bytes, returns the encoded envelope.
+    fn take_oversized(&mut self, threshold: usize) -> Option<Vec<u8>>;
+
+    /// Writes the already encoded envelope, previously removed from the frame
+    /// by `take_oversized()`.
+    fn write_encoded(&mut self, encoded: &[u8]) -> FrameState;
+
+    /// Returns the size of the last written envelope before compression.
+    fn last_size(&self) -> usize;
+
     fn finalize(&mut self) -> Result<&[u8]>;
 
     fn take_stats(&mut self) -> FramedWriteStats;
@@ -57,6 +85,27 @@ impl FramedWriteStrategy for FramedWrite {
         }
     }
 
+    fn take_oversized(&mut self, threshold: usize) -> Option<Vec<u8>> {
+        match self {
+            FramedWrite::Lz4(lz4) => lz4.take_oversized(threshold),
+            FramedWrite::None(none) => none.take_oversized(threshold),
+        }
+    }
+
+    fn write_encoded(&mut self, encoded: &[u8]) -> FrameState {
+        match self {
+            FramedWrite::Lz4(lz4) => lz4.write_encoded(encoded),
+            FramedWrite::None(none) => none.write_encoded(encoded),
+        }
+    }
+
+    fn last_size(&self) -> usize {
+        match self {
+            FramedWrite::Lz4(lz4) => lz4.last_size(),
+            FramedWrite::None(none) => none.last_size(),
+        }
+    }
+
     fn finalize(&mut self) -> Result<&[u8]> {
         match self {
             FramedWrite::Lz4(lz4) => lz4.finalize(),
@@ -74,18 +123,28 @@ impl FramedWriteStrategy for FramedWrite {
 
 pub(crate) struct LZ4FramedWrite {
     decompressed_buffer: Vec<u8>,
+    last_start: usize,
     compressed_buffer: LZ4Buffer,
     stats: FramedWriteStats,
     envelope_size_limit: Option<usize>,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
 }
 
 impl LZ4FramedWrite {
-    pub(crate) fn new(envelope_size_limit: Option<usize>) -> Self {
+    pub(crate) fn new(
+        codec: Codec,
+        trace_id_width: TraceIdWidth,
+        envelope_size_limit: Option<usize>,
+    ) -> Self {
         Self {
             decompressed_buffer: Vec::with_capacity(DECOMPRESSED_DATA_BUFFER_CAPACITY),
+            last_start: 0,
             compressed_buffer: LZ4Buffer::with_capacity(COMPRESSED_DATA_BUFFER_CAPACITY),
             stats: Default::default(),
             envelope_size_limit,
+            codec,
+            trace_id_width,
         }
     }
 }
@@ -93,25 +152,46 @@ impl LZ4FramedWrite {
 /// How many bytes we aim at writing into the socket.
 const OUTPUT_FLUSH_THRESHOLD: usize = 64 * 1024;
 
+impl LZ4FramedWrite {
+    fn state(&self) -> FrameState {
+        // We conservatively estimate that LZ4 will provide us with x2 compression rate
+        // on msgpack data.
+        // TODO: improve estimate on actual compression rates.
+        if self.decompressed_buffer.len() / 2 > OUTPUT_FLUSH_THRESHOLD {
+            FrameState::FlushAdvised
+        } else {
+            FrameState::Accumulating
+        }
+    }
+}
+
 impl FramedWriteStrategy for LZ4FramedWrite {
     fn write(&mut self, envelope: &NetworkEnvelope) -> Result<FrameState, EncodeError> {
+        self.last_start = self.decompressed_buffer.len();
         codec::encode::encode(
             envelope,
+            self.codec,
+            self.trace_id_width,
             &mut self.decompressed_buffer,
             &mut self.stats.encode_stats,
             self.envelope_size_limit,
         )?;
 
-        // We conservatively estimate that LZ4 will provide us with x2 compression rate
-        // on msgpack data.
-        // TODO: improve estimate on actual compression rates.
-        Ok(
-            if self.decompressed_buffer.len() / 2 > OUTPUT_FLUSH_THRESHOLD {
-                FrameState::FlushAdvised
-            } else {
-                FrameState::Accumulating
-            },
-        )
+        Ok(self.state())
+    }
+
+    fn take_oversized(&mut self, threshold: usize) -> Option<Vec<u8>> {
+        take_oversized(&mut self.decompressed_buffer, self.last_start, threshold)
+    }
+
+    fn write_encoded(&mut self, encoded: &[u8]) -> FrameState {
+        self.last_start = self.decompressed_buffer.len();
+        self.decompressed_buffer.extend_from_slice(encoded);
+        self.state()
+    }
+
+    fn last_size(&self) -> usize {
+        self.decompressed_buffer.len() - self.last_start
     }
 
     fn finalize(&mut self) -> Result<&[u8]> {
@@ -130,18 +210,34 @@ impl FramedWriteStrategy for LZ4FramedWrite {
 
 pub(crate) struct NoneFramedWrite {
     buffer: Vec<u8>,
+    last_start: usize,
     stats: FramedWriteStats,
     after_finalize: bool,
     envelope_size_limit: Option<usize>,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
 }
 
 impl NoneFramedWrite {
-    fn new(envelope_size_limit: Option<usize>) -> Self {
+    fn new(codec: Codec, trace_id_width: TraceIdWidth, envelope_size_limit: Option<usize>) -> Self {
         Self {
             buffer: Vec::with_capacity(DECOMPRESSED_DATA_BUFFER_CAPACITY),
+            last_start: 0,
             stats: Default::default(),
             after_finalize: false,
             envelope_size_limit,
+            codec,
+            trace_id_width,
+        }
+    }
+}
+
+impl NoneFramedWrite {
+    fn state(&self) -> FrameState {
+        if self.buffer.len() > OUTPUT_FLUSH_THRESHOLD {
+            FrameState::FlushAdvised
+        } else {
+            FrameState::Accumulating
         }
     }
 }
@@ -153,18 +249,36 @@ impl FramedWriteStrategy for NoneFramedWrite {
             self.after_finalize = false;
         }
 
+        self.last_start = self.buffer.len();
         codec::encode::encode(
             envelope,
+            self.codec,
+            self.trace_id_width,
             &mut self.buffer,
             &mut self.stats.encode_stats,
             self.envelope_size_limit,
         )?;
 
-        Ok(if self.buffer.len() > OUTPUT_FLUSH_THRESHOLD {
-            FrameState::FlushAdvised
-        } else {
-            FrameState::Accumulating
-        })
+        Ok(self.state())
+    }
+
+    fn take_oversized(&mut self, threshold: usize) -> Option<Vec<u8>> {
+        take_oversized(&mut self.buffer, self.last_start, threshold)
+    }
+
+    fn write_encoded(&mut self, encoded: &[u8]) -> FrameState {
+        if self.after_finalize {
+            self.buffer.clear();
+            self.after_finalize = false;
+        }
+
+        self.last_start = self.buffer.len();
+        self.buffer.extend_from_slice(encoded);
+        self.state()
+    }
+
+    fn last_size(&self) -> usize {
+        self.buffer.len() - self.last_start
     }
 
     fn finalize(&mut self) -> Result<&[u8]> {
@@ -177,3 +291,14 @@ impl FramedWriteStrategy for NoneFramedWrite {
         std::mem::take(&mut self.stats)
     }
 }
+
+fn take_oversized(buffer: &mut Vec<u8>, last_start: usize, threshold: usize) -> Option<Vec<u8>> {
+    if buffer.len() - last_start <= threshold {
+        return None;
+    }
+
+    let encoded = buffer[last_start..].to_vec();
+    buffer.truncate(last_start);
+    buffer.shrink_to(DECOMPRESSED_DATA_BUFFER_CAPACITY);
+    Some(encoded)
+}
@@ -11,6 +11,7 @@ extern crate elfo_utils;
 use std::{
     fmt::{self, Display},
     hash::Hash,
+    sync::Arc,
 };
 
 use elfo_core::{
@@ -22,9 +23,11 @@ use elfo_core::{
 
 use crate::{
     config::Config,
-    protocol::{DataConnectionFailed, GroupInfo, HandleConnection},
+    protocol::{DataConnectionFailed, GroupInfo, HandleConnection, OpenDataConnection},
 };
 
+pub use crate::stats::{ConnectionState, ConnectionStats, GetConnectionStats};
+
 pub mod config;
 
 mod codec;
@@ -34,6 +37,7 @@ mod node_map;
 mod protocol;
 mod rtt;
 mod socket;
+mod stats;
 mod worker;
 
 #[derive(PartialEq, Eq, Hash, Clone)]
@@ -62,6 +66,11 @@ type NetworkContext = Context<Config, ActorKey>;
 /// TODO
 pub fn new(topology: &Topology) -> Blueprint {
     let topology = topology.clone();
+    let requests = Arc::new(worker::OutgoingRequestsRegistry::default());
+    // Outlives restarts of workers to keep counters over reconnects.
+    let stats = Arc::new(stats::StatsRegistry::default());
+    // Outlives restarts of the discovery to keep revoking existing connections.
+    let authenticator = Arc::new(socket::Authenticator::default());
 
     ActorGroup::new()
         .config::<Config>()
@@ -74,17 +83,26 @@ pub fn new(topology: &Topology) -> Blueprint {
                     local: msg.local.clone(),
                     remote: msg.remote.clone(),
                 }),
-                DataConnectionFailed => Outcome::Unicast(ActorKey::Discovery),
+                DataConnectionFailed | OpenDataConnection | GetConnectionStats => {
+                    Outcome::Unicast(ActorKey::Discovery)
+                }
                 _ => Outcome::Default,
             })
         }))
         .exec(move |ctx: Context<Config, ActorKey>| {
             let topology = topology.clone();
+            let requests = requests.clone();
+            let authenticator = authenticator.clone();
+            let stats = stats.clone();
             async move {
                 match ctx.key().clone() {
-                    ActorKey::Discovery => discovery::Discovery::new(ctx, topology).main().await,
+                    ActorKey::Discovery => {
+                        discovery::Discovery::new(ctx, topology, authenticator, stats)
+                            .main()
+                            .await
+                    }
                     ActorKey::Worker { local, remote } => {
-                        worker::Worker::new(ctx, local, remote, topology)
+                        worker::Worker::new(ctx, local, remote, topology, requests, stats)
                             .main()
                             .await
                     }
@@ -20,25 +20,7 @@ impl NodeMap {
         let this = NodeInfo {
             node_no: topology.node_no(),
             launch_id: topology.launch_id(),
-            groups: topology
-                .locals()
-                .map(|group| {
-                    let interests = topology
-                        .connections()
-                        .filter_map(|conn| {
-                            (conn.from == group.addr)
-                                .then(|| conn.to.into_remote())
-                                .flatten()
-                        })
-                        .collect();
-
-                    GroupInfo {
-                        group_no: group.addr.group_no().expect("invalid group no"),
-                        name: group.name,
-                        interests,
-                    }
-                })
-                .collect(),
+            groups: Self::local_groups(topology),
         };
 
         Self {
@@ -46,6 +28,29 @@ impl NodeMap {
             this,
         }
     }
+
+    /// Collects local groups with their interests from the topology.
+    pub(crate) fn local_groups(topology: &Topology) -> Vec<GroupInfo> {
+        topology
+            .locals()
+            .map(|group| {
+                let interests = topology
+                    .connections()
+                    .filter_map(|conn| {
+                        (conn.from == group.addr)
+                            .then(|| conn.to.into_remote())
+                            .flatten()
+                    })
+                    .collect();
+
+                GroupInfo {
+                    group_no: group.addr.group_no().expect("invalid group no"),
+                    name: group.name,
+                    interests,
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone)]
@@ -1,5 +1,6 @@
 use elfo_core::{
     addr::{GroupNo, NodeNo},
+    errors::AckError,
     message, MoveOwnership,
 };
 
@@ -11,7 +12,8 @@ use crate::{codec::format::NetworkAddr, config::Transport, socket::Socket};
 pub(crate) struct HandleConnection {
     pub(crate) local: GroupInfo,
     pub(crate) remote: GroupInfo,
-    pub(crate) socket: MoveOwnership<Socket>,
+    /// `None` if the connection should be established on demand.
+    pub(crate) socket: Option<MoveOwnership<Socket>>,
     /// Initial window size of every flow.
     pub(crate) initial_window: i32,
     // TODO: different windows for rx/tx and routed flows.
@@ -25,6 +27,21 @@ pub(crate) struct DataConnectionFailed {
     pub(crate) remote: (NodeNo, GroupNo),
 }
 
+/// Sent back as a lightweight `Ack` frame, see `KIND_REGULAR_ACKED`.
+#[message]
+pub(crate) struct Acknowledge {
+    pub(crate) seq: u64,
+    pub(crate) result: Result<(), AckError>,
+}
+
+/// Sent by a worker to establish a connection on demand.
+#[message]
+pub(crate) struct OpenDataConnection {
+    pub(crate) transport: Transport,
+    pub(crate) local: GroupNo,
+    pub(crate) remote: (NodeNo, GroupNo),
+}
+
 #[message(part)]
 #[derive(PartialEq, Eq, Hash)]
 pub(crate) struct GroupInfo {
@@ -43,6 +60,9 @@ pub(crate) mod internode {
     //      SwitchToControl -->
     //                <-- SwitchToControl
     //                  ...
+    //      UpdateGroups -->
+    //                  ...
+    //                   <-- UpdateGroups
     //
     //            data connection
     //      ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -69,7 +89,15 @@ pub(crate) mod internode {
         pub(crate) groups: Vec<GroupInfo>,
     }
 
+    /// Sent over the control connection once local groups are changed,
+    /// before the next `Ping`. Contains all groups, not only changed ones.
+    #[message]
+    pub(crate) struct UpdateGroups {
+        pub(crate) groups: Vec<GroupInfo>,
+    }
+
     #[message(part)]
+    #[derive(PartialEq, Eq)]
     pub(crate) struct GroupInfo {
         pub(crate) group_no: GroupNo, // TODO: just `no`?
         pub(crate) name: String,
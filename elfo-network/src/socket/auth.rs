@@ -0,0 +1,306 @@
+//! Authentication of peers by tokens.
+//!
+//! If both sides support the `AUTH` capability, the connecting side presents
+//! its token right after the handshake:
+//! ```text
+//!     (client)                   (server)
+//!     u16 length, token -->
+//!                           <-- u8 status
+//! ```
+//! The server never presents a token, it's only authenticated by transport.
+
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+};
+
+use eyre::{bail, ensure, eyre, Result, WrapErr};
+use parking_lot::{Mutex, RwLock};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::raw;
+use crate::config::{AuthAcceptor, AuthConfig};
+
+const MAX_TOKEN_LENGTH: usize = 4096;
+
+const STATUS_ACCEPTED: u8 = 0;
+const STATUS_REJECTED: u8 = 1;
+
+/// Holds the token of this node and validates tokens of connecting peers.
+/// Shared by all listeners and outgoing connections to apply config updates.
+#[derive(Default)]
+pub(crate) struct Authenticator {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    token: Option<String>,
+    accept: Vec<AuthAcceptor>,
+    // Grants of established connections to update them on reconfiguration.
+    grants: Vec<Weak<Grant>>,
+}
+
+impl Authenticator {
+    /// Applies the config to new and existing connections.
+    pub(crate) fn configure(&self, config: &AuthConfig) -> Result<()> {
+        let token = match (&config.token, &config.token_path) {
+            (Some(_), Some(_)) => bail!("only one of `token` and `token_path` can be specified"),
+            (Some(token), None) => Some(token.clone()),
+            (None, Some(path)) => Some(
+                fs::read_to_string(path)
+                    .wrap_err_with(|| eyre!("cannot read {}", path.display()))?
+                    .trim()
+                    .to_string(),
+            ),
+            (None, None) => None,
+        };
+
+        if let Some(token) = &token {
+            ensure!(!token.is_empty(), "the token is empty");
+            ensure!(token.len() <= MAX_TOKEN_LENGTH, "the token is too long");
+        }
+
+        let accept = config
+            .accept
+            .iter()
+            .map(|acceptor| AuthAcceptor {
+                token_hash: acceptor.token_hash.to_ascii_lowercase(),
+                allowed_groups: acceptor.allowed_groups.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut inner = self.inner.lock();
+        inner.token = token;
+
+        inner.grants.retain(|grant| {
+            let Some(grant) = grant.upgrade() else {
+                return false;
+            };
+
+            match lookup(&accept, grant.token_hash.as_deref()) {
+                Some(allowed_groups) => *grant.allowed_groups.write() = allowed_groups,
+                None if config.revoke => {
+                    grant.is_revoked.store(true, Ordering::Relaxed);
+                    return false;
+                }
+                None => {}
+            }
+
+            true
+        });
+
+        inner.accept = accept;
+        Ok(())
+    }
+
+    /// Returns the token presented to listening peers.
+    pub(crate) fn token(&self) -> Option<String> {
+        self.inner.lock().token.clone()
+    }
+
+    /// Returns `None` if the token isn't accepted.
+    fn accept(&self, token: Option<&[u8]>) -> Option<Arc<Grant>> {
+        let token_hash = token.map(hash);
+
+        let mut inner = self.inner.lock();
+        let allowed_groups = lookup(&inner.accept, token_hash.as_deref())?;
+
+        let grant = Arc::new(Grant {
+            token_hash,
+            allowed_groups: RwLock::new(allowed_groups),
+            is_revoked: AtomicBool::new(false),
+        });
+
+        inner.grants.retain(|grant| grant.strong_count() > 0);
+        inner.grants.push(Arc::downgrade(&grant));
+        Some(grant)
+    }
+}
+
+/// Returns `None` if the token isn't accepted, otherwise allowed groups.
+fn lookup(accept: &[AuthAcceptor], token_hash: Option<&str>) -> Option<Option<Vec<String>>> {
+    // Authentication is disabled.
+    if accept.is_empty() {
+        return Some(None);
+    }
+
+    let token_hash = token_hash?;
+    accept
+        .iter()
+        .find(|acceptor| acceptor.token_hash == token_hash)
+        .map(|acceptor| acceptor.allowed_groups.clone())
+}
+
+fn hash(token: &[u8]) -> String {
+    Sha256::digest(token)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Access rights of the connecting peer.
+pub(crate) struct Grant {
+    token_hash: Option<String>,
+    // `None` means all groups.
+    allowed_groups: RwLock<Option<Vec<String>>>,
+    is_revoked: AtomicBool,
+}
+
+impl Grant {
+    /// Used for outgoing connections, because listening peers aren't
+    /// authenticated.
+    pub(crate) fn unrestricted() -> Arc<Self> {
+        Arc::new(Self {
+            token_hash: None,
+            allowed_groups: RwLock::new(None),
+            is_revoked: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns `true` if the peer can send messages to the local group.
+    pub(crate) fn is_allowed(&self, group_name: &str) -> bool {
+        !self.is_revoked()
+            && self
+                .allowed_groups
+                .read()
+                .as_ref()
+                .map_or(true, |groups| groups.iter().any(|g| g == group_name))
+    }
+
+    /// Returns `true` if the connection must be closed.
+    pub(crate) fn is_revoked(&self) -> bool {
+        self.is_revoked.load(Ordering::Relaxed)
+    }
+}
+
+/// Presents the token to the listening peer.
+pub(super) async fn present(raw_socket: &mut raw::Socket, token: &str) -> Result<()> {
+    let mut buffer = Vec::with_capacity(2 + token.len());
+    buffer.extend_from_slice(&(token.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(token.as_bytes());
+    raw_socket.write.write_all(&buffer).await?;
+
+    match raw_socket.read.read_u8().await? {
+        STATUS_ACCEPTED => Ok(()),
+        _ => bail!("the token is rejected by the peer"),
+    }
+}
+
+/// Validates the token of the connecting peer, if presented.
+pub(super) async fn validate(
+    raw_socket: &mut raw::Socket,
+    authenticator: &Authenticator,
+    is_presented: bool,
+) -> Result<Arc<Grant>> {
+    if !is_presented {
+        return authenticator
+            .accept(None)
+            .ok_or_else(|| eyre!("no token is presented"));
+    }
+
+    let length = raw_socket.read.read_u16_le().await? as usize;
+    ensure!(length <= MAX_TOKEN_LENGTH, "the token is too long");
+    let mut token = vec![0; length];
+    raw_socket.read.read_exact(&mut token).await?;
+
+    let grant = authenticator.accept(Some(&token));
+    let status = if grant.is_some() {
+        STATUS_ACCEPTED
+    } else {
+        STATUS_REJECTED
+    };
+    raw_socket.write.write_all(&[status]).await?;
+
+    grant.ok_or_else(|| eyre!("unknown token"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN_HASH: &str = "930bbdc51b6aed5c2a5678fd6e28dee7a05e8a4b643cfc0b4427c3efb86c0d94";
+
+    fn config(accept: &[(&str, Option<&[&str]>)], revoke: bool) -> AuthConfig {
+        AuthConfig {
+            token: None,
+            token_path: None,
+            accept: accept
+                .iter()
+                .map(|(token_hash, groups)| AuthAcceptor {
+                    token_hash: token_hash.to_string(),
+                    allowed_groups: groups.map(|g| g.iter().map(|g| g.to_string()).collect()),
+                })
+                .collect(),
+            revoke,
+        }
+    }
+
+    #[test]
+    fn hashing() {
+        assert_eq!(hash(b"secret-token"), TOKEN_HASH);
+    }
+
+    #[test]
+    fn disabled() {
+        let auth = Authenticator::default();
+        let grant = auth.accept(None).unwrap();
+        assert!(grant.is_allowed("orders"));
+        assert!(auth.accept(Some(b"any")).is_some());
+    }
+
+    #[test]
+    fn restricted() {
+        let auth = Authenticator::default();
+        let upper = TOKEN_HASH.to_ascii_uppercase();
+        auth.configure(&config(&[(&upper, Some(&["orders"]))], false))
+            .unwrap();
+
+        assert!(auth.accept(None).is_none());
+        assert!(auth.accept(Some(b"wrong-token")).is_none());
+
+        let grant = auth.accept(Some(b"secret-token")).unwrap();
+        assert!(grant.is_allowed("orders"));
+        assert!(!grant.is_allowed("secrets"));
+    }
+
+    #[test]
+    fn rotation() {
+        let auth = Authenticator::default();
+        auth.configure(&config(&[(TOKEN_HASH, Some(&["orders"]))], false))
+            .unwrap();
+        let grant = auth.accept(Some(b"secret-token")).unwrap();
+
+        // Existing connections are updated.
+        auth.configure(&config(&[(TOKEN_HASH, None)], false))
+            .unwrap();
+        assert!(grant.is_allowed("secrets"));
+
+        // Removed tokens are kept without `revoke`.
+        auth.configure(&config(&[("other", None)], false)).unwrap();
+        assert!(!grant.is_revoked());
+        assert!(auth.accept(Some(b"secret-token")).is_none());
+
+        // ... and revoked with it.
+        auth.configure(&config(&[("other", None)], true)).unwrap();
+        assert!(grant.is_revoked());
+        assert!(!grant.is_allowed("secrets"));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let auth = Authenticator::default();
+        let mut config = config(&[], false);
+        config.token = Some("token".into());
+        config.token_path = Some("token".into());
+        assert!(auth.configure(&config).is_err());
+
+        config.token = None;
+        config.token_path = Some("/nonexistent/elfo/token".into());
+        assert!(auth.configure(&config).is_err());
+    }
+}
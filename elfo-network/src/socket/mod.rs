@@ -1,42 +1,88 @@
-use std::{future::Future, time::Duration};
+use std::{future::Future, sync::Arc, time::Duration};
 
 use derive_more::{Constructor, Display};
-use eyre::{eyre, Result, WrapErr};
+use eyre::{bail, eyre, Result, WrapErr};
 use futures::{stream::BoxStream, StreamExt};
 use metrics::counter;
 use tokio::io;
 use tracing::{trace, warn};
 
-use elfo_core::addr::{NodeLaunchId, NodeNo};
-use elfo_utils::likely;
+use elfo_core::{
+    addr::{NodeLaunchId, NodeNo},
+    tracing::TraceId,
+};
+use elfo_utils::{likely, ward};
+
+pub(crate) use self::{
+    auth::{Authenticator, Grant},
+    idleness::IdleTracker,
+    replay::{ReplayError, ReplayPolicy},
+};
 
-use self::idleness::{IdleTrack, IdleTracker};
+use self::{
+    idleness::IdleTrack,
+    replay::ReplayWindow,
+    transfers::{IncomingTransfers, Outgoing, OutgoingTransfers},
+};
 use crate::{
-    codec::{decode::EnvelopeDetails, encode::EncodeError, format::NetworkEnvelope},
-    config::Transport,
+    codec::{
+        decode::EnvelopeDetails,
+        encode::EncodeError,
+        format::{NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, TraceIdWidth},
+    },
+    config::{self, Codec, Transport},
     frame::{
         read::{FramedRead, FramedReadState, FramedReadStrategy},
         write::{FrameState, FramedWrite, FramedWriteStrategy},
     },
+    stats::Traffic,
 };
 
+mod auth;
 mod handshake;
 mod idleness;
 mod raw;
+mod replay;
+mod transfers;
 
 bitflags::bitflags! {
     #[derive(Clone, Copy)]
     pub(crate) struct Capabilities: u32 {
         const LZ4 = 1 << 8;
+        const CHUNKING = 1 << 9;
+        const AUTH = 1 << 10;
+        /// Advertised only if the postcard codec is configured, so
+        /// it's used only if both nodes want it.
+        const POSTCARD = 1 << 11;
+        /// Requests can carry `RequestLimits`.
+        const REQUEST_LIMITS = 1 << 12;
+        /// Regular messages can be acknowledged, see `KIND_REGULAR_ACKED`.
+        const ACKS = 1 << 13;
+        /// Advertised only if built with `trace-id-128`, see `TraceIdWidth`.
+        const TRACE_ID_128 = 1 << 14;
+        /// Requests are stamped with nonces, see `socket::replay`.
+        /// Advertised by listeners only if they check nonces.
+        const NONCES = 1 << 15;
+        /// Requests can carry `IdempotencyKey`.
+        const IDEMPOTENCY_KEYS = 1 << 16;
+        /// Responses can carry specific errors, see `KIND_RESPONSE_FORBIDDEN`
+        /// and following kinds. Otherwise, they're sent as failed ones.
+        const EXTENDED_RESPONSES = 1 << 17;
     }
 }
 
 pub(crate) struct Socket {
     pub(crate) info: raw::SocketInfo,
     pub(crate) peer: Peer,
+    /// The protocol version of the peer.
+    pub(crate) version: u8,
+    /// The codec negotiated by the handshake.
+    pub(crate) codec: Codec,
     pub(crate) read: ReadHalf,
     pub(crate) write: WriteHalf,
     pub(crate) idle: IdleTracker,
+    /// Access rights of the peer, always unrestricted for outgoing connections.
+    pub(crate) grant: Arc<Grant>,
 }
 
 #[derive(Display, Clone, Constructor)]
@@ -47,13 +93,40 @@ pub(crate) struct Peer {
 }
 
 impl Socket {
-    fn new(raw: raw::Socket, handshake: handshake::Handshake) -> Self {
-        // TODO: maybe do something with the version.
+    fn new(
+        raw: raw::Socket,
+        handshake: handshake::Handshake,
+        grant: Arc<Grant>,
+        replay: Option<ReplayWindow>,
+    ) -> Self {
+        let codec = if handshake.capabilities.contains(Capabilities::POSTCARD) {
+            Codec::Postcard
+        } else {
+            Codec::Msgpack
+        };
+
+        let trace_id_width = if handshake.capabilities.contains(Capabilities::TRACE_ID_128) {
+            TraceIdWidth::Bits128
+        } else {
+            if TraceId::BITS == 128 {
+                warn!(
+                    message = "peer supports only 64-bit trace ids, they're truncated on sending",
+                    peer = %Peer::new(handshake.node_no, handshake.launch_id),
+                );
+            }
+            TraceIdWidth::Bits64
+        };
 
         let (framed_read, framed_write) = if handshake.capabilities.contains(Capabilities::LZ4) {
-            (FramedRead::lz4(), FramedWrite::lz4(None))
+            (
+                FramedRead::lz4(codec, trace_id_width),
+                FramedWrite::lz4(codec, trace_id_width, None),
+            )
         } else {
-            (FramedRead::none(), FramedWrite::none(None))
+            (
+                FramedRead::none(codec, trace_id_width),
+                FramedWrite::none(codec, trace_id_width, None),
+            )
         };
 
         let (idle_tracker, idle_track) = IdleTracker::new();
@@ -61,9 +134,19 @@ impl Socket {
         Self {
             info: raw.info,
             peer: Peer::new(handshake.node_no, handshake.launch_id),
-            read: ReadHalf::new(framed_read, raw.read, idle_track),
-            write: WriteHalf::new(framed_write, raw.write),
+            version: handshake.version,
+            codec,
+            read: ReadHalf::new(
+                framed_read,
+                raw.read,
+                idle_track,
+                codec,
+                trace_id_width,
+                replay,
+            ),
+            write: WriteHalf::new(framed_write, raw.write, handshake.capabilities),
             idle: idle_tracker,
+            grant,
         }
     }
 }
@@ -72,11 +155,17 @@ pub(crate) struct ReadHalf {
     framing: FramedRead,
     read: raw::OwnedReadHalf,
     idle: IdleTrack,
+    transfers: IncomingTransfers,
+    traffic: Arc<Traffic>,
+    /// `None` if requests aren't checked.
+    replay: Option<ReplayWindow>,
 }
 
 #[derive(Debug)]
 pub(crate) enum ReadError {
     EnvelopeSkipped(EnvelopeDetails),
+    /// The request is discarded by replay protection.
+    Replayed(ReplayError, NetworkEnvelope),
     Fatal(eyre::Report),
 }
 
@@ -90,21 +179,47 @@ where
 }
 
 impl ReadHalf {
-    fn new(framing: FramedRead, read: raw::OwnedReadHalf, idle: IdleTrack) -> Self {
+    fn new(
+        framing: FramedRead,
+        read: raw::OwnedReadHalf,
+        idle: IdleTrack,
+        codec: Codec,
+        trace_id_width: TraceIdWidth,
+        replay: Option<ReplayWindow>,
+    ) -> Self {
         Self {
             framing,
             read,
             idle,
+            transfers: IncomingTransfers::new(
+                config::default_max_transfer_size().as_usize(),
+                codec,
+                trace_id_width,
+            ),
+            traffic: Default::default(),
+            replay,
         }
     }
 
+    /// Sets counters of received bytes and envelopes.
+    pub(crate) fn set_traffic(&mut self, traffic: Arc<Traffic>) {
+        self.traffic = traffic;
+    }
+
+    /// Sets the maximum total size of envelopes received by chunks at once.
+    pub(crate) fn set_max_transfer_size(&mut self, max_size: usize) {
+        self.transfers.max_size = max_size;
+    }
+
     fn report_framing_metrics(&mut self) {
         let stats = self.framing.take_stats();
+        let total_messages_received = stats.decode_stats.total_messages_decoded
+            + stats.decode_stats.total_messages_decoding_skipped;
         counter!(
             "elfo_network_received_messages_total",
-            stats.decode_stats.total_messages_decoded
-                + stats.decode_stats.total_messages_decoding_skipped
+            total_messages_received
         );
+        self.traffic.add_messages(total_messages_received);
         counter!(
             "elfo_network_received_uncompressed_bytes_total",
             stats.decompress_stats.total_uncompressed_bytes
@@ -122,6 +237,40 @@ impl ReadHalf {
                     self.idle.update();
                     return Err(ReadError::EnvelopeSkipped(details));
                 }
+                FramedReadState::Done {
+                    decoded:
+                        NetworkEnvelope {
+                            payload:
+                                NetworkEnvelopePayload::Chunk {
+                                    transfer_id,
+                                    is_first,
+                                    is_last,
+                                    is_cancelled,
+                                    data,
+                                },
+                            ..
+                        },
+                } => {
+                    self.idle.update();
+                    trace!(message = "received chunk", transfer_id, is_last);
+
+                    match self
+                        .transfers
+                        .handle(transfer_id, is_first, is_last, is_cancelled, data)
+                    {
+                        Some(Ok(decoded)) => {
+                            counter!("elfo_network_received_messages_total", 1);
+                            self.traffic.add_messages(1);
+                            break decoded;
+                        }
+                        Some(Err(details)) => {
+                            counter!("elfo_network_received_messages_total", 1);
+                            self.traffic.add_messages(1);
+                            return Err(ReadError::EnvelopeSkipped(details));
+                        }
+                        None => continue,
+                    }
+                }
                 FramedReadState::Done { decoded } => {
                     self.idle.update();
                     let (protocol, name) = decoded.payload.protocol_and_name();
@@ -147,6 +296,7 @@ impl ReadHalf {
                 return Ok(None);
             }
             counter!("elfo_network_received_bytes_total", bytes_read as u64);
+            self.traffic.add_bytes(bytes_read as u64);
             self.report_framing_metrics();
 
             self.framing.mark_filled(bytes_read);
@@ -155,6 +305,12 @@ impl ReadHalf {
 
         self.report_framing_metrics();
 
+        if let Some(replay) = &mut self.replay {
+            if let Err(error) = replay.check(&envelope.payload) {
+                return Err(ReadError::Replayed(error, envelope));
+            }
+        }
+
         Ok(Some(envelope))
     }
 }
@@ -162,15 +318,114 @@ impl ReadHalf {
 pub(crate) struct WriteHalf {
     framing: FramedWrite,
     write: raw::OwnedWriteHalf,
+    // `None` if the peer doesn't support chunking.
+    transfers: Option<OutgoingTransfers>,
+    traffic: Arc<Traffic>,
+    has_request_limits: bool,
+    has_acks: bool,
+    has_idempotency_keys: bool,
+    has_extended_responses: bool,
+    /// `None` if the peer doesn't check nonces.
+    next_nonce: Option<u64>,
 }
 
 impl WriteHalf {
-    fn new(framing: FramedWrite, write: raw::OwnedWriteHalf) -> Self {
-        Self { framing, write }
+    fn new(framing: FramedWrite, write: raw::OwnedWriteHalf, capabilities: Capabilities) -> Self {
+        let is_chunking = capabilities.contains(Capabilities::CHUNKING);
+        let has_nonces = capabilities.contains(Capabilities::NONCES);
+
+        Self {
+            framing,
+            write,
+            transfers: is_chunking.then(|| OutgoingTransfers::new(usize::MAX, usize::MAX)),
+            traffic: Default::default(),
+            has_request_limits: capabilities.contains(Capabilities::REQUEST_LIMITS),
+            has_acks: capabilities.contains(Capabilities::ACKS),
+            has_idempotency_keys: capabilities.contains(Capabilities::IDEMPOTENCY_KEYS),
+            has_extended_responses: capabilities.contains(Capabilities::EXTENDED_RESPONSES),
+            next_nonce: has_nonces.then_some(1),
+        }
+    }
+
+    /// Returns `true` if the peer supports `RequestLimits`.
+    pub(crate) fn has_request_limits(&self) -> bool {
+        self.has_request_limits
+    }
+
+    /// Returns `true` if the peer supports `IdempotencyKey`.
+    pub(crate) fn has_idempotency_keys(&self) -> bool {
+        self.has_idempotency_keys
+    }
+
+    /// Returns `true` if the peer decodes specific errors of responses.
+    pub(crate) fn has_extended_responses(&self) -> bool {
+        self.has_extended_responses
+    }
+
+    /// Returns `true` if the peer acknowledges messages.
+    pub(crate) fn has_acks(&self) -> bool {
+        self.has_acks
+    }
+
+    /// Sets counters of sent bytes and envelopes.
+    pub(crate) fn set_traffic(&mut self, traffic: Arc<Traffic>) {
+        self.traffic = traffic;
+    }
+
+    /// Stamps the request with the next nonce if the peer checks them.
+    pub(crate) fn stamp(&mut self, envelope: &mut NetworkEnvelope) {
+        let next_nonce = ward!(&mut self.next_nonce);
+
+        if let NetworkEnvelopePayload::RequestAny { nonce, .. }
+        | NetworkEnvelopePayload::RequestAll { nonce, .. } = &mut envelope.payload
+        {
+            *nonce = *next_nonce;
+            *next_nonce += 1;
+        }
+    }
+
+    /// Enables sending envelopes encoded into more than `threshold` bytes
+    /// by chunks of `chunk_size` bytes. Does nothing if the peer doesn't
+    /// support chunking.
+    pub(crate) fn set_chunking(&mut self, threshold: usize, chunk_size: usize) {
+        if let Some(transfers) = &mut self.transfers {
+            transfers.threshold = threshold;
+            transfers.chunk_size = chunk_size.max(1);
+        }
+    }
+
+    /// Returns `true` if there are chunks, which should be sent by
+    /// `feed_chunks()`.
+    pub(crate) fn has_pending_chunks(&self) -> bool {
+        self.transfers.as_ref().is_some_and(|t| !t.is_empty())
+    }
+
+    /// Encodes the next chunk of every in-flight transfer into the internal
+    /// buffer, followed by envelopes held behind completed transfers.
+    /// Transfers of senders, which are not alive, are cancelled.
+    pub(crate) fn feed_chunks(&mut self, is_alive: impl Fn(NetworkAddr) -> bool) -> Result<()> {
+        let transfers = ward!(&mut self.transfers, return Ok(()));
+
+        for outgoing in transfers.next_chunks(&is_alive) {
+            match outgoing {
+                Outgoing::Chunk(chunk) => match self.framing.write(&chunk) {
+                    Ok(_) | Err(EncodeError::Skipped) => {}
+                    Err(EncodeError::Fatal(err)) => return Err(err.into()),
+                },
+                Outgoing::Encoded(data) => {
+                    self.framing.write_encoded(&data);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Encodes the message into the internal buffer.
     ///
+    /// If a transfer between the same sender and recipient is in flight,
+    /// the message is held and written by `feed_chunks()` after it.
+    ///
     /// Returns
     /// * `Ok(Some(FrameState))` if the message is added successfully.
     /// * `Ok(None)` if the message is skipped because of encoding errors.
@@ -180,12 +435,41 @@ impl WriteHalf {
         // TODO: we should also emit metrics here, not only in `flush()`.
         let write_result = self.framing.write(envelope);
         match write_result {
-            Ok(state) => Ok(Some(state)),
+            Ok(state) => {
+                let transfers = ward!(&mut self.transfers, return Ok(Some(state)));
+
+                if transfers.is_in_flight(envelope) {
+                    let data = self.framing.take_oversized(0).expect("empty envelope");
+                    transfers.hold(envelope, data);
+                    return Ok(Some(state));
+                }
+
+                let data = ward!(
+                    self.framing.take_oversized(transfers.threshold),
+                    return Ok(Some(state))
+                );
+
+                // Only the first chunk is written now, others are written
+                // between next batches of messages by `feed_chunks()`.
+                let chunk = transfers.start(envelope, data);
+                match self.framing.write(&chunk) {
+                    Ok(state) => Ok(Some(state)),
+                    Err(EncodeError::Skipped) => unreachable!("chunks are always encodable"),
+                    Err(EncodeError::Fatal(err)) => Err(err.into()),
+                }
+            }
             Err(EncodeError::Skipped) => Ok(None),
             Err(EncodeError::Fatal(err)) => Err(err.into()),
         }
     }
 
+    /// Returns the number of bytes added by the last successful `feed()`.
+    /// Only the first chunk is counted for envelopes sent by chunks,
+    /// held envelopes aren't counted.
+    pub(crate) fn last_fed_size(&self) -> usize {
+        self.framing.last_size()
+    }
+
     /// Flushed the internal buffer unconditionally.
     pub(crate) async fn flush(&mut self) -> Result<()> {
         let finalized = self.framing.finalize()?;
@@ -205,6 +489,7 @@ impl WriteHalf {
             trace!(message = "wrote bytes to socket", count = finalized_len);
 
             counter!("elfo_network_sent_bytes_total", finalized_len as u64);
+            self.traffic.add_bytes(finalized_len as u64);
             counter!(
                 "elfo_network_sent_uncompressed_bytes_total",
                 stats.compress_stats.total_uncompressed_bytes
@@ -214,6 +499,7 @@ impl WriteHalf {
         }
 
         counter!("elfo_network_sent_messages_total", total_messages_sent);
+        self.traffic.add_messages(total_messages_sent);
 
         result
     }
@@ -243,14 +529,39 @@ pub(crate) async fn connect(
     addr: &Transport,
     node_no: NodeNo,
     launch_id: NodeLaunchId,
-    capabilities: Capabilities,
+    mut capabilities: Capabilities,
+    token: Option<&str>,
 ) -> Result<Socket> {
+    if token.is_some() {
+        capabilities |= Capabilities::AUTH;
+    }
+
+    // Requests are stamped only if the listener checks them.
+    capabilities |= Capabilities::NONCES;
+
     let mut raw_socket = timeout(CONNECT_TIMEOUT, raw::connect(addr)).await?;
-    let handshaking = handshake::handshake(&mut raw_socket, node_no, launch_id, capabilities);
+    let handshaking = async {
+        let handshake =
+            handshake::handshake(&mut raw_socket, node_no, launch_id, capabilities).await?;
+
+        // Old peers don't support authentication.
+        if let Some(token) = token.filter(|_| handshake.capabilities.contains(Capabilities::AUTH)) {
+            auth::present(&mut raw_socket, token)
+                .await
+                .wrap_err("authentication")?;
+        }
+
+        Ok(handshake)
+    };
     let handshake = timeout(HANDSHAKE_TIMEOUT, handshaking)
         .await
         .wrap_err("handshake")?;
-    Ok(Socket::new(raw_socket, handshake))
+    Ok(Socket::new(
+        raw_socket,
+        handshake,
+        Grant::unrestricted(),
+        None,
+    ))
 }
 
 pub(crate) async fn listen(
@@ -258,22 +569,59 @@ pub(crate) async fn listen(
     node_no: NodeNo,
     launch_id: NodeLaunchId,
     capabilities: Capabilities,
+    authenticator: Arc<Authenticator>,
+    replay: Option<ReplayPolicy>,
 ) -> Result<BoxStream<'static, Socket>> {
+    // Tokens are validated only if the connecting peer presents them.
+    let mut capabilities = capabilities | Capabilities::AUTH;
+    if replay.is_some() {
+        capabilities |= Capabilities::NONCES;
+    }
+
     let stream = timeout(LISTEN_TIMEOUT, raw::listen(addr)).await?;
     let stream = stream
-        .map(move |mut raw_socket| async move {
-            let handshaking =
-                handshake::handshake(&mut raw_socket, node_no, launch_id, capabilities);
-
-            match timeout(HANDSHAKE_TIMEOUT, handshaking).await {
-                Ok(handshake) => Some(Socket::new(raw_socket, handshake)),
-                Err(err) => {
-                    warn!(
-                        message = "cannot handshake accepted connection",
-                        error = %err,
-                        socket = %raw_socket.info,
-                    );
-                    None
+        .map(move |mut raw_socket| {
+            let authenticator = authenticator.clone();
+            async move {
+                let handshaking = async {
+                    let handshake =
+                        handshake::handshake(&mut raw_socket, node_no, launch_id, capabilities)
+                            .await?;
+
+                    let is_presented = handshake.capabilities.contains(Capabilities::AUTH);
+                    let grant = auth::validate(&mut raw_socket, &authenticator, is_presented)
+                        .await
+                        .wrap_err_with(|| {
+                            eyre!("authentication of node_no={} failed", handshake.node_no)
+                        })?;
+
+                    let has_nonces = handshake.capabilities.contains(Capabilities::NONCES);
+                    if replay.is_some_and(|policy| policy.is_strict && !has_nonces) {
+                        bail!(
+                            "node_no={} doesn't support replay protection",
+                            handshake.node_no
+                        );
+                    }
+
+                    let window = replay
+                        .filter(|_| has_nonces)
+                        .map(|policy| ReplayWindow::new(policy.window));
+
+                    Ok((handshake, grant, window))
+                };
+
+                match timeout(HANDSHAKE_TIMEOUT, handshaking).await {
+                    Ok((handshake, grant, window)) => {
+                        Some(Socket::new(raw_socket, handshake, grant, window))
+                    }
+                    Err(err) => {
+                        warn!(
+                            message = "cannot handshake accepted connection",
+                            error = format!("{:#}", err),
+                            socket = %raw_socket.info,
+                        );
+                        None
+                    }
                 }
             }
         })
@@ -295,9 +643,12 @@ mod tests {
     use futures::{future, stream::StreamExt};
     use tracing::debug;
 
-    use elfo_core::{_priv::AnyMessage, message, tracing::TraceId};
+    use elfo_core::{_priv::AnyMessage, message, tracing::TraceId, RequestId};
 
-    use crate::codec::format::{NetworkAddr, NetworkEnvelopePayload};
+    use crate::{
+        codec::format::{NetworkAddr, NetworkEnvelopePayload},
+        config::{AuthAcceptor, AuthConfig},
+    };
 
     use super::*;
 
@@ -360,14 +711,22 @@ mod tests {
         let node_no = NodeNo::from_bits(2).unwrap();
         let launch_id = NodeLaunchId::from_bits(1);
 
-        let mut listen_stream = listen(&transport, node_no, launch_id, capabilities)
-            .await
-            .expect("failed to bind server to a port");
+        let authenticator = Arc::new(Authenticator::default());
+        let mut listen_stream = listen(
+            &transport,
+            node_no,
+            launch_id,
+            capabilities,
+            authenticator,
+            None,
+        )
+        .await
+        .expect("failed to bind server to a port");
         let server_socket_fut = listen_stream.next();
 
         let node_no = NodeNo::from_bits(1).unwrap();
         let launch_id = NodeLaunchId::from_bits(2);
-        let client_socket_fut = connect(&transport, node_no, launch_id, capabilities);
+        let client_socket_fut = connect(&transport, node_no, launch_id, capabilities, None);
 
         let (server_socket, client_socket) =
             future::join(server_socket_fut, client_socket_fut).await;
@@ -379,6 +738,7 @@ mod tests {
                 sender: NetworkAddr::NULL,
                 recipient: NetworkAddr::NULL,
                 trace_id: TraceId::try_from(1).unwrap(),
+                is_force_sampled: false,
                 payload: NetworkEnvelopePayload::Regular {
                     message: AnyMessage::new(TestSocketMessage("a".repeat(i * 10))),
                 },
@@ -413,4 +773,416 @@ mod tests {
         )
         .await;
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn inproc_read_write_lz4() {
+        ensure_read_write("inproc://read_write_lz4", Capabilities::LZ4).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn inproc_read_write_postcard() {
+        ensure_read_write(
+            "inproc://read_write_postcard",
+            Capabilities::LZ4 | Capabilities::POSTCARD,
+        )
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_auth() {
+        let transport: Transport = "tcp://127.0.0.1:9210".parse().unwrap();
+
+        let authenticator = Arc::new(Authenticator::default());
+        authenticator
+            .configure(&AuthConfig {
+                token: None,
+                token_path: None,
+                accept: vec![AuthAcceptor {
+                    // `secret-token`
+                    token_hash: "930bbdc51b6aed5c2a5678fd6e28dee7a05e8a4b643cfc0b4427c3efb86c0d94"
+                        .into(),
+                    allowed_groups: Some(vec!["orders".into()]),
+                }],
+                revoke: false,
+            })
+            .unwrap();
+
+        let mut listen_stream = listen(
+            &transport,
+            NodeNo::from_bits(2).unwrap(),
+            NodeLaunchId::from_bits(1),
+            Capabilities::empty(),
+            authenticator,
+            None,
+        )
+        .await
+        .expect("failed to bind server to a port");
+
+        // Only authenticated connections are yielded.
+        let server = tokio::spawn(async move {
+            let socket = listen_stream.next().await.unwrap();
+            (
+                socket.grant.is_allowed("orders"),
+                socket.grant.is_allowed("secrets"),
+            )
+        });
+
+        let connect = |token| {
+            connect(
+                &transport,
+                NodeNo::from_bits(1).unwrap(),
+                NodeLaunchId::from_bits(2),
+                Capabilities::empty(),
+                token,
+            )
+        };
+
+        // Without a token, the connection is closed by the server.
+        let mut client_socket = connect(None).await.unwrap();
+        assert!(!matches!(client_socket.read.recv().await, Ok(Some(_))));
+
+        assert!(connect(Some("wrong-token")).await.is_err());
+
+        let _client_socket = connect(Some("secret-token")).await.unwrap();
+        assert_eq!(server.await.unwrap(), (true, false));
+    }
+
+    async fn make_pair(transport: &str, capabilities: Capabilities) -> (Socket, Socket) {
+        make_pair_with_replay(transport, capabilities, None).await
+    }
+
+    async fn make_pair_with_replay(
+        transport: &str,
+        capabilities: Capabilities,
+        replay: Option<ReplayPolicy>,
+    ) -> (Socket, Socket) {
+        let transport = transport.parse().unwrap();
+
+        let mut listen_stream = listen(
+            &transport,
+            NodeNo::from_bits(2).unwrap(),
+            NodeLaunchId::from_bits(1),
+            capabilities,
+            Arc::new(Authenticator::default()),
+            replay,
+        )
+        .await
+        .expect("failed to bind server to a port");
+
+        let client_socket_fut = connect(
+            &transport,
+            NodeNo::from_bits(1).unwrap(),
+            NodeLaunchId::from_bits(2),
+            capabilities,
+            None,
+        );
+
+        let (server_socket, client_socket) =
+            future::join(listen_stream.next(), client_socket_fut).await;
+        let server_socket = server_socket.expect("server failed");
+        let client_socket = client_socket.expect("failed to connect to the server");
+        (server_socket, client_socket)
+    }
+
+    fn make_envelope(text: String) -> NetworkEnvelope {
+        NetworkEnvelope {
+            sender: NetworkAddr::NULL,
+            recipient: NetworkAddr::NULL,
+            trace_id: TraceId::try_from(1).unwrap(),
+            is_force_sampled: false,
+            payload: NetworkEnvelopePayload::Regular {
+                message: AnyMessage::new(TestSocketMessage(text)),
+            },
+        }
+    }
+
+    fn extract_text(envelope: NetworkEnvelope) -> String {
+        let NetworkEnvelopePayload::Regular { message } = envelope.payload else {
+            panic!("unexpected kind of the received message");
+        };
+        message
+            .downcast_ref::<TestSocketMessage>()
+            .unwrap()
+            .0
+            .clone()
+    }
+
+    fn make_request(text: &str) -> NetworkEnvelope {
+        NetworkEnvelope {
+            payload: NetworkEnvelopePayload::RequestAny {
+                request_id: RequestId::from_ffi(1),
+                nonce: 0,
+                limits: Default::default(),
+                idempotency_key: None,
+                message: AnyMessage::new(TestSocketMessage(text.into())),
+            },
+            ..make_envelope(text.into())
+        }
+    }
+
+    async fn recv_text(socket: &mut Socket) -> Result<String, ReadError> {
+        let envelope = socket.read.recv().await?.expect("closed");
+        let NetworkEnvelopePayload::RequestAny { message, .. } = envelope.payload else {
+            return Ok(extract_text(envelope));
+        };
+        Ok(message
+            .downcast_ref::<TestSocketMessage>()
+            .unwrap()
+            .0
+            .clone())
+    }
+
+    #[tokio::test]
+    async fn replay_protection() {
+        let policy = ReplayPolicy {
+            window: 16,
+            is_strict: true,
+        };
+        let transport = "inproc://replay_protection";
+        let (mut server, mut client) =
+            make_pair_with_replay(transport, Capabilities::empty(), Some(policy)).await;
+
+        // Capture the frame of a stamped request.
+        let mut request = make_request("first");
+        client.write.stamp(&mut request);
+        client.write.feed(&request).unwrap();
+        let frame = client.write.framing.finalize().unwrap().to_vec();
+
+        for _ in 0..2 {
+            io::AsyncWriteExt::write_all(&mut client.write.write, &frame)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(recv_text(&mut server).await.unwrap(), "first");
+        assert!(matches!(
+            recv_text(&mut server).await,
+            Err(ReadError::Replayed(ReplayError::Duplicate(1), _))
+        ));
+
+        // Normal traffic isn't affected.
+        let mut request = make_request("second");
+        client.write.stamp(&mut request);
+        client.write.feed(&request).unwrap();
+        client.write.feed(&make_envelope("regular".into())).unwrap();
+        client.write.flush().await.unwrap();
+
+        assert_eq!(recv_text(&mut server).await.unwrap(), "second");
+        assert_eq!(recv_text(&mut server).await.unwrap(), "regular");
+
+        // Unstamped requests are rejected.
+        client.write.send(&make_request("third")).await.unwrap();
+        assert!(matches!(
+            recv_text(&mut server).await,
+            Err(ReadError::Replayed(ReplayError::Missing, _))
+        ));
+
+        // A new connection starts a new nonce space.
+        drop((server, client));
+        let transport = "inproc://replay_protection_2";
+        let (mut server, mut client) =
+            make_pair_with_replay(transport, Capabilities::empty(), Some(policy)).await;
+
+        let mut request = make_request("again");
+        client.write.stamp(&mut request);
+        client.write.send(&request).await.unwrap();
+        assert_eq!(recv_text(&mut server).await.unwrap(), "again");
+    }
+
+    const LARGE_SIZE: usize = 1024 * 1024;
+    const CHUNK_SIZE: usize = 16 * 1024;
+
+    // Writes a large message, a small one to the same recipient, which must be
+    // held behind the large one, and pings to another recipient between its
+    // chunks. Returns the number of pings.
+    fn spawn_interleaved_writer(
+        mut socket: Socket,
+        is_alive: impl Fn(usize) -> bool + Send + 'static,
+    ) -> tokio::task::JoinHandle<usize> {
+        tokio::spawn(async move {
+            socket.write.set_chunking(64 * 1024, CHUNK_SIZE);
+
+            let large = make_envelope("a".repeat(LARGE_SIZE));
+            socket.write.feed(&large).unwrap().unwrap();
+            socket.write.feed(&make_envelope("held".into())).unwrap();
+
+            let mut count = 0;
+            while socket.write.has_pending_chunks() {
+                let small = NetworkEnvelope {
+                    recipient: NetworkAddr::from_bits(1 << 48 | 1 << 40 | 1).unwrap(),
+                    ..make_envelope(format!("ping #{count}"))
+                };
+                socket.write.feed(&small).unwrap().unwrap();
+                count += 1;
+
+                socket.write.feed_chunks(|_| is_alive(count)).unwrap();
+                socket.write.flush().await.unwrap();
+            }
+
+            let small = make_envelope("last".into());
+            socket.write.send(&small).await.unwrap();
+            count
+        })
+    }
+
+    async fn ensure_chunking(transport: &str, capabilities: Capabilities) {
+        let capabilities = capabilities | Capabilities::CHUNKING;
+        let (mut server_socket, client_socket) = make_pair(transport, capabilities).await;
+
+        let writer = spawn_interleaved_writer(client_socket, |_| true);
+
+        // All pings must be received before the large message is completed.
+        let mut received = Vec::new();
+        loop {
+            let envelope = server_socket.read.recv().await.unwrap().unwrap();
+            let text = extract_text(envelope);
+            if text.len() == LARGE_SIZE {
+                break;
+            }
+            received.push(text);
+        }
+
+        let count = writer.await.unwrap();
+        assert!(count >= LARGE_SIZE / CHUNK_SIZE - 1);
+        assert_eq!(received.len(), count);
+        for (i, text) in received.iter().enumerate() {
+            assert_eq!(text, &format!("ping #{i}"));
+        }
+
+        // The order between the same sender and recipient is preserved.
+        for expected in ["held", "last"] {
+            let envelope = server_socket.read.recv().await.unwrap().unwrap();
+            assert_eq!(extract_text(envelope), expected);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_chunking_no_framing() {
+        ensure_chunking("tcp://127.0.0.1:9202", Capabilities::empty()).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_chunking_lz4() {
+        ensure_chunking("tcp://127.0.0.1:9203", Capabilities::LZ4).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_chunking_cancelled() {
+        let capabilities = Capabilities::CHUNKING;
+        let (mut server_socket, client_socket) =
+            make_pair("tcp://127.0.0.1:9204", capabilities).await;
+
+        // The sender terminates after sending a few chunks.
+        let writer = spawn_interleaved_writer(client_socket, |count| count < 5);
+
+        let mut pings = 0;
+        let mut is_cancelled = false;
+        loop {
+            match server_socket.read.recv().await {
+                Ok(Some(envelope)) => {
+                    let text = extract_text(envelope);
+                    assert_ne!(text.len(), LARGE_SIZE);
+                    match text.as_str() {
+                        "held" => assert!(is_cancelled),
+                        "last" => break,
+                        _ => pings += 1,
+                    }
+                }
+                Err(ReadError::EnvelopeSkipped(details)) => {
+                    assert_eq!(details.trace_id, TraceId::try_from(1).unwrap());
+                    assert_eq!(pings, 5);
+                    is_cancelled = true;
+                }
+                other => panic!("unexpected result: {other:?}"),
+            }
+        }
+
+        assert_eq!(writer.await.unwrap(), 5);
+        assert_eq!(pings, 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_chunking_too_large() {
+        let capabilities = Capabilities::CHUNKING;
+        let (mut server_socket, client_socket) =
+            make_pair("tcp://127.0.0.1:9205", capabilities).await;
+
+        server_socket.read.set_max_transfer_size(LARGE_SIZE / 2);
+        let writer = spawn_interleaved_writer(client_socket, |_| true);
+
+        // The transfer is rejected on the first chunk, other messages are received.
+        match server_socket.read.recv().await {
+            Err(ReadError::EnvelopeSkipped(details)) => {
+                assert_eq!(details.trace_id, TraceId::try_from(1).unwrap());
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let mut received = Vec::new();
+        loop {
+            let envelope = server_socket.read.recv().await.unwrap().unwrap();
+            let text = extract_text(envelope);
+            if text == "last" {
+                break;
+            }
+            received.push(text);
+        }
+
+        // The held message is released after the rejected transfer.
+        assert_eq!(received.pop().as_deref(), Some("held"));
+        assert_eq!(received.len(), writer.await.unwrap());
+        for (i, text) in received.iter().enumerate() {
+            assert_eq!(text, &format!("ping #{i}"));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[tracing_test::traced_test]
+    async fn tcp_chunking_in_flight_limit() {
+        let capabilities = Capabilities::CHUNKING;
+        let (mut server_socket, mut client_socket) =
+            make_pair("tcp://127.0.0.1:9206", capabilities).await;
+
+        // Both transfers fit the limit, but not at the same time.
+        server_socket.read.set_max_transfer_size(LARGE_SIZE * 3 / 2);
+
+        let writer = tokio::spawn(async move {
+            client_socket.write.set_chunking(64 * 1024, CHUNK_SIZE);
+
+            for no in 1..=2 {
+                let large = NetworkEnvelope {
+                    sender: NetworkAddr::from_bits(1 << 48 | 1 << 40 | no).unwrap(),
+                    ..make_envelope("a".repeat(LARGE_SIZE))
+                };
+                client_socket.write.feed(&large).unwrap().unwrap();
+            }
+
+            while client_socket.write.has_pending_chunks() {
+                client_socket.write.feed_chunks(|_| true).unwrap();
+                client_socket.write.flush().await.unwrap();
+            }
+
+            let small = make_envelope("last".into());
+            client_socket.write.send(&small).await.unwrap();
+        });
+
+        assert!(matches!(
+            server_socket.read.recv().await,
+            Err(ReadError::EnvelopeSkipped(_))
+        ));
+
+        let envelope = server_socket.read.recv().await.unwrap().unwrap();
+        assert_eq!(extract_text(envelope).len(), LARGE_SIZE);
+        let envelope = server_socket.read.recv().await.unwrap().unwrap();
+        assert_eq!(extract_text(envelope), "last");
+
+        writer.await.unwrap();
+    }
 }
@@ -0,0 +1,96 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use derive_more::Display;
+use eyre::{bail, eyre, Result};
+use futures::Stream;
+use parking_lot::Mutex;
+use tokio::{
+    io::{self, DuplexStream},
+    sync::mpsc,
+};
+
+pub(super) type OwnedReadHalf = io::ReadHalf<DuplexStream>;
+pub(super) type OwnedWriteHalf = io::WriteHalf<DuplexStream>;
+
+/// The capacity of every direction of the connection.
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// Listeners of the current process by their names.
+static LISTENERS: Mutex<BTreeMap<String, mpsc::UnboundedSender<Socket>>> =
+    Mutex::new(BTreeMap::new());
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Display)]
+#[display("inproc(name={name}, id={id})")] // TODO: use `valuable` after tracing#1570
+pub(crate) struct SocketInfo {
+    name: String,
+    id: u64,
+}
+
+pub(super) struct Socket {
+    pub(super) read: OwnedReadHalf,
+    pub(super) write: OwnedWriteHalf,
+    pub(super) info: SocketInfo,
+}
+
+impl Socket {
+    fn new(stream: DuplexStream, name: &str, id: u64) -> Self {
+        let (read, write) = io::split(stream);
+        let info = SocketInfo {
+            name: name.into(),
+            id,
+        };
+
+        Self { read, write, info }
+    }
+}
+
+pub(super) async fn connect(name: &str) -> Result<Socket> {
+    let listener = LISTENERS
+        .lock()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| eyre!("nobody listens to inproc://{name}"))?;
+
+    let id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+    let (client, server) = io::duplex(BUFFER_SIZE);
+
+    listener
+        .send(Socket::new(server, name, id))
+        .map_err(|_| eyre!("inproc://{name} is closed"))?;
+
+    Ok(Socket::new(client, name, id))
+}
+
+pub(super) fn listen(name: &str) -> Result<impl Stream<Item = Socket> + 'static> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    {
+        let mut listeners = LISTENERS.lock();
+        if listeners.get(name).is_some_and(|tx| !tx.is_closed()) {
+            bail!("inproc://{name} is already listened");
+        }
+        listeners.insert(name.into(), tx);
+    }
+
+    let registration = Registration(name.into());
+    let accept = move |(mut rx, registration): (mpsc::UnboundedReceiver<Socket>, _)| async move {
+        let socket = rx.recv().await?;
+        Some((socket, (rx, registration)))
+    };
+
+    Ok(futures::stream::unfold((rx, registration), accept))
+}
+
+/// Unregisters the listener once the stream is dropped.
+struct Registration(String);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        LISTENERS.lock().remove(&self.0);
+    }
+}
@@ -11,6 +11,7 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 use crate::config::Transport;
 
+mod inproc;
 mod tcp;
 #[cfg(feature = "turmoil06")]
 mod turmoil;
@@ -24,6 +25,7 @@ macro_rules! delegate_call {
             Self::Uds(v) => Pin::new(v).$method($($args),+),
             #[cfg(feature = "turmoil06")]
             Self::Turmoil06(v) => Pin::new(v).$method($($args),+),
+            Self::Inproc(v) => Pin::new(v).$method($($args),+),
         }
     }
 }
@@ -35,6 +37,7 @@ pub(crate) enum SocketInfo {
     Uds(uds::SocketInfo),
     #[cfg(feature = "turmoil06")]
     Turmoil06(turmoil::SocketInfo),
+    Inproc(inproc::SocketInfo),
 }
 
 pub(super) enum OwnedReadHalf {
@@ -43,6 +46,7 @@ pub(super) enum OwnedReadHalf {
     Uds(uds::OwnedReadHalf),
     #[cfg(feature = "turmoil06")]
     Turmoil06(turmoil::OwnedReadHalf),
+    Inproc(inproc::OwnedReadHalf),
 }
 
 impl AsyncRead for OwnedReadHalf {
@@ -61,6 +65,7 @@ pub(super) enum OwnedWriteHalf {
     Uds(uds::OwnedWriteHalf),
     #[cfg(feature = "turmoil06")]
     Turmoil06(turmoil::OwnedWriteHalf),
+    Inproc(inproc::OwnedWriteHalf),
 }
 
 impl AsyncWrite for OwnedWriteHalf {
@@ -91,6 +96,7 @@ impl AsyncWrite for OwnedWriteHalf {
             Self::Uds(v) => v.is_write_vectored(),
             #[cfg(feature = "turmoil06")]
             Self::Turmoil06(v) => v.is_write_vectored(),
+            Self::Inproc(v) => v.is_write_vectored(),
         }
     }
 }
@@ -133,6 +139,16 @@ impl From<turmoil::Socket> for Socket {
     }
 }
 
+impl From<inproc::Socket> for Socket {
+    fn from(socket: inproc::Socket) -> Self {
+        Self {
+            read: OwnedReadHalf::Inproc(socket.read),
+            write: OwnedWriteHalf::Inproc(socket.write),
+            info: SocketInfo::Inproc(socket.info),
+        }
+    }
+}
+
 pub(super) async fn connect(addr: &Transport) -> Result<Socket> {
     match addr {
         Transport::Tcp(addr) => tcp::connect(addr).await.map(Into::into),
@@ -140,6 +156,7 @@ pub(super) async fn connect(addr: &Transport) -> Result<Socket> {
         Transport::Uds(addr) => uds::connect(addr).await.map(Into::into),
         #[cfg(feature = "turmoil06")]
         Transport::Turmoil06(addr) => turmoil::connect(addr).await.map(Into::into),
+        Transport::Inproc(addr) => inproc::connect(addr).await.map(Into::into),
     }
 }
 
@@ -150,5 +167,6 @@ pub(super) async fn listen(addr: &Transport) -> Result<BoxStream<'static, Socket
         Transport::Uds(addr) => Box::pin(uds::listen(addr)?.map(Into::into)),
         #[cfg(feature = "turmoil06")]
         Transport::Turmoil06(addr) => Box::pin(turmoil::listen(addr).await?.map(Into::into)),
+        Transport::Inproc(addr) => Box::pin(inproc::listen(addr)?.map(Into::into)),
     })
 }
@@ -0,0 +1,231 @@
+//! Protection against replaying requests, see `ReplayProtectionConfig`.
+//!
+//! The sending side stamps every request with the next nonce of the
+//! connection, starting from 1. The receiving side tracks a sliding window of
+//! recently seen nonces, like the anti-replay window of IPsec (RFC 4303).
+//! Reconnection starts a new nonce space, so the window is per connection.
+
+use derive_more::Display;
+
+use crate::{
+    codec::format::NetworkEnvelopePayload,
+    config::{ReplayProtectionConfig, Transport},
+};
+
+/// Replay protection of the listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReplayPolicy {
+    pub(crate) window: u32,
+    /// Whether to refuse peers, which don't stamp requests.
+    pub(crate) is_strict: bool,
+}
+
+impl ReplayPolicy {
+    /// Returns `None` if the protection is disabled for the listener.
+    pub(crate) fn for_listener(
+        config: &ReplayProtectionConfig,
+        transport: &Transport,
+    ) -> Option<Self> {
+        let overrides = config.listeners.iter().find(|l| l.listen == *transport);
+        let enabled = overrides.and_then(|l| l.enabled).unwrap_or(config.enabled);
+
+        enabled.then(|| Self {
+            window: overrides.and_then(|l| l.window).unwrap_or(config.window),
+            is_strict: overrides.and_then(|l| l.strict).unwrap_or(config.strict),
+        })
+    }
+}
+
+/// The reason of rejecting the request.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReplayError {
+    /// The request isn't stamped, although the peer supports nonces.
+    #[display("missing nonce")]
+    Missing,
+    /// The nonce has been seen already.
+    #[display("duplicate nonce {_0}")]
+    Duplicate(u64),
+    /// The nonce is too old to know whether it has been seen.
+    #[display("nonce {nonce} is out of window, the highest one is {highest}")]
+    OutOfWindow { nonce: u64, highest: u64 },
+}
+
+impl ReplayError {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Duplicate(_) => "duplicate",
+            Self::OutOfWindow { .. } => "out_of_window",
+        }
+    }
+}
+
+/// Recently seen nonces of one connection.
+pub(crate) struct ReplayWindow {
+    size: u64,
+    highest: u64,
+    /// A ring of bits, the nonce `n` is seen if the bit `n % capacity` is set.
+    bits: Vec<u64>,
+}
+
+impl ReplayWindow {
+    /// The maximum supported size of the window.
+    pub(crate) const MAX_SIZE: u32 = 1 << 16;
+
+    pub(crate) fn new(size: u32) -> Self {
+        let size = size.clamp(1, Self::MAX_SIZE);
+
+        Self {
+            size: u64::from(size),
+            highest: 0,
+            bits: vec![0; (size as usize).div_ceil(64)],
+        }
+    }
+
+    /// Checks nonces of requests, other envelopes are always accepted.
+    pub(crate) fn check(&mut self, payload: &NetworkEnvelopePayload) -> Result<(), ReplayError> {
+        match payload {
+            NetworkEnvelopePayload::RequestAny { nonce, .. }
+            | NetworkEnvelopePayload::RequestAll { nonce, .. } => self.check_nonce(*nonce),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_nonce(&mut self, nonce: u64) -> Result<(), ReplayError> {
+        if nonce == 0 {
+            return Err(ReplayError::Missing);
+        }
+
+        if nonce > self.highest {
+            // Forget nonces, which are out of the window now.
+            let capacity = self.capacity();
+            let forgotten = (nonce - self.highest).min(capacity);
+            for n in (nonce - forgotten + 1)..=nonce {
+                self.set(n, false);
+            }
+
+            self.highest = nonce;
+            self.set(nonce, true);
+            return Ok(());
+        }
+
+        if self.highest - nonce >= self.size {
+            return Err(ReplayError::OutOfWindow {
+                nonce,
+                highest: self.highest,
+            });
+        }
+
+        if self.get(nonce) {
+            return Err(ReplayError::Duplicate(nonce));
+        }
+
+        self.set(nonce, true);
+        Ok(())
+    }
+
+    fn capacity(&self) -> u64 {
+        self.bits.len() as u64 * 64
+    }
+
+    fn get(&self, nonce: u64) -> bool {
+        let bit = nonce % self.capacity();
+        self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+    }
+
+    fn set(&mut self, nonce: u64, is_seen: bool) {
+        let bit = nonce % self.capacity();
+        let word = &mut self.bits[(bit / 64) as usize];
+
+        if is_seen {
+            *word |= 1 << (bit % 64);
+        } else {
+            *word &= !(1 << (bit % 64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ListenerReplayProtection;
+
+    use super::*;
+
+    #[test]
+    fn policy() {
+        let listener = |listen: &str, enabled, window, strict| ListenerReplayProtection {
+            listen: listen.parse().unwrap(),
+            enabled,
+            window,
+            strict,
+        };
+
+        let config = ReplayProtectionConfig {
+            enabled: true,
+            listeners: vec![
+                listener("tcp://0.0.0.0:8150", None, Some(64), Some(true)),
+                listener("tcp://0.0.0.0:8151", Some(false), None, None),
+            ],
+            ..Default::default()
+        };
+
+        let policy =
+            |transport: &str| ReplayPolicy::for_listener(&config, &transport.parse().unwrap());
+
+        assert_eq!(
+            policy("tcp://0.0.0.0:8150"),
+            Some(ReplayPolicy {
+                window: 64,
+                is_strict: true
+            })
+        );
+        assert_eq!(policy("tcp://0.0.0.0:8151"), None);
+        assert_eq!(
+            policy("inproc://other"),
+            Some(ReplayPolicy {
+                window: 1024,
+                is_strict: false
+            })
+        );
+    }
+
+    #[test]
+    fn in_order() {
+        let mut window = ReplayWindow::new(64);
+
+        for nonce in 1..1000 {
+            assert_eq!(window.check_nonce(nonce), Ok(()));
+        }
+
+        assert_eq!(window.check_nonce(999), Err(ReplayError::Duplicate(999)));
+        assert_eq!(window.check_nonce(936), Err(ReplayError::Duplicate(936)));
+        assert_eq!(
+            window.check_nonce(935),
+            Err(ReplayError::OutOfWindow {
+                nonce: 935,
+                highest: 999
+            })
+        );
+        assert_eq!(window.check_nonce(0), Err(ReplayError::Missing));
+    }
+
+    #[test]
+    fn gaps_and_reordering() {
+        let mut window = ReplayWindow::new(100);
+
+        // Skipped nonces can arrive later if they're still in the window.
+        assert_eq!(window.check_nonce(5), Ok(()));
+        assert_eq!(window.check_nonce(3), Ok(()));
+        assert_eq!(window.check_nonce(3), Err(ReplayError::Duplicate(3)));
+        assert_eq!(window.check_nonce(104), Ok(()));
+        assert_eq!(window.check_nonce(5), Err(ReplayError::Duplicate(5)));
+        assert_eq!(window.check_nonce(6), Ok(()));
+        assert!(window.check_nonce(4).is_err());
+
+        // A large jump forgets the whole window.
+        assert_eq!(window.check_nonce(1_000_000), Ok(()));
+        assert_eq!(window.check_nonce(999_950), Ok(()));
+        assert!(window.check_nonce(999_900).is_err());
+        assert_eq!(window.check_nonce(999_901), Ok(()));
+    }
+}
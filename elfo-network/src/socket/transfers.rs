@@ -0,0 +1,419 @@
+//! Chunked transfers of large envelopes.
+//!
+//! An envelope, which is encoded into more than `chunk_threshold` bytes, is
+//! removed from the frame and sent as a sequence of chunks. Chunks of all
+//! in-flight transfers are written in round-robin between batches of other
+//! messages, so small messages aren't stuck behind large ones.
+//!
+//! Envelopes between the same sender and recipient are delivered in order,
+//! so envelopes fed while a transfer between them is in flight are held and
+//! written only after its last chunk.
+//!
+//! The first chunk contains the header of the encoded envelope, so the
+//! receiver knows the total size in advance and can reject the transfer
+//! without buffering it. The last chunk completes the transfer. A chunk with
+//! the cancellation flag is sent instead of the rest if the sender is gone.
+//! The receiver limits the total size of transfers reassembled at once.
+
+use std::collections::VecDeque;
+
+use fxhash::FxHashMap;
+use metrics::{counter, decrement_gauge, increment_gauge};
+use tracing::{debug, error, trace};
+
+use elfo_core::tracing::TraceId;
+
+use crate::{
+    codec::{
+        self,
+        decode::{DecodeState, DecodeStats, EnvelopeDetails},
+        format::{NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, TraceIdWidth},
+    },
+    config::Codec,
+};
+
+// === OutgoingTransfers ===
+
+pub(super) struct OutgoingTransfers {
+    pub(super) threshold: usize,
+    pub(super) chunk_size: usize,
+    next_id: u64,
+    queue: VecDeque<OutgoingTransfer>,
+}
+
+struct OutgoingTransfer {
+    id: u64,
+    sender: NetworkAddr,
+    recipient: NetworkAddr,
+    trace_id: TraceId,
+    data: Vec<u8>,
+    position: usize,
+    /// Encoded envelopes between the same sender and recipient,
+    /// which are fed after this one.
+    held: VecDeque<HeldEnvelope>,
+}
+
+struct HeldEnvelope {
+    trace_id: TraceId,
+    data: Vec<u8>,
+}
+
+/// What should be written into the frame by `WriteHalf::feed_chunks()`.
+pub(super) enum Outgoing {
+    Chunk(NetworkEnvelope),
+    /// An already encoded envelope, which was held behind a transfer.
+    Encoded(Vec<u8>),
+}
+
+impl OutgoingTransfers {
+    pub(super) fn new(threshold: usize, chunk_size: usize) -> Self {
+        Self {
+            threshold,
+            chunk_size: chunk_size.max(1),
+            next_id: 0,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns `true` if there is an in-flight transfer between the sender
+    /// and the recipient, so other envelopes between them must be held.
+    pub(super) fn is_in_flight(&self, envelope: &NetworkEnvelope) -> bool {
+        self.find(envelope.sender, envelope.recipient).is_some()
+    }
+
+    /// Holds the encoded envelope until the in-flight transfer between
+    /// the same sender and recipient is completed.
+    pub(super) fn hold(&mut self, envelope: &NetworkEnvelope, data: Vec<u8>) {
+        let index = self
+            .find(envelope.sender, envelope.recipient)
+            .expect("no in-flight transfer");
+
+        self.queue[index].held.push_back(HeldEnvelope {
+            trace_id: envelope.trace_id,
+            data,
+        });
+    }
+
+    fn find(&self, sender: NetworkAddr, recipient: NetworkAddr) -> Option<usize> {
+        self.queue
+            .iter()
+            .position(|t| t.sender == sender && t.recipient == recipient)
+    }
+
+    /// Starts a new transfer of the encoded envelope, returns the first chunk.
+    pub(super) fn start(&mut self, envelope: &NetworkEnvelope, data: Vec<u8>) -> NetworkEnvelope {
+        self.start_raw(envelope.sender, envelope.recipient, envelope.trace_id, data)
+    }
+
+    fn start_raw(
+        &mut self,
+        sender: NetworkAddr,
+        recipient: NetworkAddr,
+        trace_id: TraceId,
+        data: Vec<u8>,
+    ) -> NetworkEnvelope {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        debug!(
+            message = "starting chunked transfer",
+            transfer_id = id,
+            size = data.len(),
+        );
+
+        increment_gauge!("elfo_network_in_flight_transfers", 1., "direction" => "outgoing");
+        counter!("elfo_network_chunked_messages_total", 1, "direction" => "outgoing");
+
+        let mut transfer = OutgoingTransfer {
+            id,
+            sender,
+            recipient,
+            trace_id,
+            data,
+            position: 0,
+            held: VecDeque::new(),
+        };
+
+        let chunk = transfer.next_chunk(self.chunk_size);
+        self.queue.push_back(transfer);
+        chunk
+    }
+
+    /// Returns the next chunk of every in-flight transfer, followed by
+    /// envelopes held behind completed transfers.
+    /// Transfers of gone senders are cancelled.
+    pub(super) fn next_chunks(&mut self, is_alive: &impl Fn(NetworkAddr) -> bool) -> Vec<Outgoing> {
+        let mut outgoing = Vec::with_capacity(self.queue.len());
+        let mut completed = Vec::new();
+
+        self.queue.retain_mut(|transfer| {
+            let chunk = if is_alive(transfer.sender) {
+                transfer.next_chunk(self.chunk_size)
+            } else {
+                debug!(
+                    message = "sender has gone, cancelling chunked transfer",
+                    transfer_id = transfer.id,
+                    sent = transfer.position,
+                    size = transfer.data.len(),
+                );
+                transfer.cancel()
+            };
+
+            let is_done =
+                matches!(chunk.payload, NetworkEnvelopePayload::Chunk { is_last, .. } if is_last);
+            outgoing.push(Outgoing::Chunk(chunk));
+
+            if is_done {
+                decrement_gauge!("elfo_network_in_flight_transfers", 1., "direction" => "outgoing");
+
+                if !transfer.held.is_empty() {
+                    let held = std::mem::take(&mut transfer.held);
+                    completed.push((transfer.sender, transfer.recipient, held));
+                }
+            }
+            !is_done
+        });
+
+        // Release held envelopes in order. A large one starts a new transfer,
+        // so the rest is held behind it again.
+        for (sender, recipient, held) in completed {
+            let mut held = held.into_iter();
+
+            for envelope in held.by_ref() {
+                if envelope.data.len() > self.threshold {
+                    let chunk = self.start_raw(sender, recipient, envelope.trace_id, envelope.data);
+                    outgoing.push(Outgoing::Chunk(chunk));
+                    break;
+                }
+
+                outgoing.push(Outgoing::Encoded(envelope.data));
+            }
+
+            if let Some(transfer) = self.queue.back_mut() {
+                transfer.held.extend(held);
+            }
+        }
+
+        outgoing
+    }
+}
+
+impl Drop for OutgoingTransfers {
+    fn drop(&mut self) {
+        let count = self.queue.len() as f64;
+        decrement_gauge!("elfo_network_in_flight_transfers", count, "direction" => "outgoing");
+    }
+}
+
+impl OutgoingTransfer {
+    fn next_chunk(&mut self, chunk_size: usize) -> NetworkEnvelope {
+        let is_first = self.position == 0;
+        let end = (self.position + chunk_size).min(self.data.len());
+        let data = self.data[self.position..end].to_vec();
+        self.position = end;
+
+        counter!(
+            "elfo_network_transferred_chunk_bytes_total", data.len() as u64,
+            "direction" => "outgoing"
+        );
+
+        self.make_chunk(is_first, self.position == self.data.len(), false, data)
+    }
+
+    fn cancel(&mut self) -> NetworkEnvelope {
+        self.make_chunk(false, true, true, Vec::new())
+    }
+
+    fn make_chunk(
+        &self,
+        is_first: bool,
+        is_last: bool,
+        is_cancelled: bool,
+        data: Vec<u8>,
+    ) -> NetworkEnvelope {
+        NetworkEnvelope {
+            sender: self.sender,
+            recipient: self.recipient,
+            trace_id: self.trace_id,
+            is_force_sampled: false,
+            payload: NetworkEnvelopePayload::Chunk {
+                transfer_id: self.id,
+                is_first,
+                is_last,
+                is_cancelled,
+                data,
+            },
+        }
+    }
+}
+
+// === IncomingTransfers ===
+
+pub(super) struct IncomingTransfers {
+    /// The maximum total size of transfers reassembled at once.
+    pub(super) max_size: usize,
+    /// The total declared size of in-flight transfers.
+    reserved: usize,
+    codec: Codec,
+    trace_id_width: TraceIdWidth,
+    map: FxHashMap<u64, IncomingTransfer>,
+}
+
+struct IncomingTransfer {
+    details: EnvelopeDetails,
+    size: usize,
+    data: Vec<u8>,
+}
+
+impl IncomingTransfers {
+    pub(super) fn new(max_size: usize, codec: Codec, trace_id_width: TraceIdWidth) -> Self {
+        Self {
+            max_size,
+            reserved: 0,
+            codec,
+            trace_id_width,
+            map: FxHashMap::default(),
+        }
+    }
+
+    /// Handles a received chunk.
+    ///
+    /// Returns
+    /// * `None` if the transfer isn't completed yet.
+    /// * `Some(Ok(envelope))` if the transfer is completed.
+    /// * `Some(Err(details))` if the transfer is cancelled or rejected.
+    pub(super) fn handle(
+        &mut self,
+        transfer_id: u64,
+        is_first: bool,
+        is_last: bool,
+        is_cancelled: bool,
+        data: Vec<u8>,
+    ) -> Option<Result<NetworkEnvelope, EnvelopeDetails>> {
+        counter!(
+            "elfo_network_transferred_chunk_bytes_total", data.len() as u64,
+            "direction" => "incoming"
+        );
+
+        if is_first {
+            let Some(details) = codec::decode::decode_details(&data, self.trace_id_width) else {
+                error!(
+                    message = "invalid first chunk, transfer is skipped",
+                    transfer_id,
+                );
+                return None;
+            };
+
+            // The encoded envelope starts with its size.
+            let size = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+            if data.len() > size {
+                // Impossible if the sender is correct.
+                error!(
+                    message = "chunked transfer exceeds the declared size, skipped",
+                    transfer_id,
+                );
+                return Some(Err(details));
+            }
+
+            if self.reserved.saturating_add(size) > self.max_size {
+                error!(
+                    message = "too large chunked transfer, skipped",
+                    transfer_id,
+                    size,
+                    in_flight = self.reserved,
+                    limit = self.max_size,
+                );
+                return Some(Err(details));
+            }
+
+            debug!(message = "receiving chunked transfer", transfer_id, size,);
+
+            increment_gauge!("elfo_network_in_flight_transfers", 1., "direction" => "incoming");
+            counter!("elfo_network_chunked_messages_total", 1, "direction" => "incoming");
+
+            // Don't allocate the whole buffer at once, the transfer can be cancelled.
+            let mut buffer = Vec::with_capacity(data.len().max(size.min(data.len() * 16)));
+            buffer.extend_from_slice(&data);
+            let transfer = IncomingTransfer {
+                details,
+                size,
+                data: buffer,
+            };
+
+            self.reserved += size;
+            if let Some(prev) = self.map.insert(transfer_id, transfer) {
+                error!(message = "duplicate chunked transfer", transfer_id);
+                self.reserved -= prev.size;
+                decrement_gauge!("elfo_network_in_flight_transfers", 1., "direction" => "incoming");
+            }
+        } else {
+            // Transfers rejected on the first chunk are unknown.
+            let Some(transfer) = self.map.get_mut(&transfer_id) else {
+                trace!(message = "chunk of unknown transfer, skipped", transfer_id);
+                return None;
+            };
+
+            if transfer.data.len() + data.len() > transfer.size {
+                // Impossible if the sender is correct.
+                error!(
+                    message = "chunked transfer exceeds the declared size, skipped",
+                    transfer_id,
+                );
+                return self.remove(transfer_id).map(|t| Err(t.details));
+            }
+
+            transfer.data.extend_from_slice(&data);
+        }
+
+        if !is_last {
+            return None;
+        }
+
+        let transfer = self.remove(transfer_id)?;
+
+        if is_cancelled {
+            debug!(
+                message = "chunked transfer is cancelled by the sender",
+                transfer_id,
+                received = transfer.data.len(),
+            );
+            return Some(Err(transfer.details));
+        }
+
+        let mut stats = DecodeStats::default();
+        match codec::decode::decode(&transfer.data, self.codec, self.trace_id_width, &mut stats) {
+            Ok(DecodeState::Done { decoded, .. }) => Some(Ok(decoded)),
+            Ok(DecodeState::Skipped { .. }) => Some(Err(transfer.details)),
+            Ok(DecodeState::NeedMoreData { .. }) => {
+                error!(message = "truncated chunked transfer, skipped", transfer_id);
+                Some(Err(transfer.details))
+            }
+            Err(err) => {
+                error!(
+                    message = "cannot decode chunked transfer, skipped",
+                    error = %err,
+                    transfer_id,
+                );
+                Some(Err(transfer.details))
+            }
+        }
+    }
+
+    fn remove(&mut self, transfer_id: u64) -> Option<IncomingTransfer> {
+        let transfer = self.map.remove(&transfer_id)?;
+        self.reserved -= transfer.size;
+        decrement_gauge!("elfo_network_in_flight_transfers", 1., "direction" => "incoming");
+        Some(transfer)
+    }
+}
+
+impl Drop for IncomingTransfers {
+    fn drop(&mut self) {
+        let count = self.map.len() as f64;
+        decrement_gauge!("elfo_network_in_flight_transfers", count, "direction" => "incoming");
+    }
+}
@@ -0,0 +1,352 @@
+//! Statistics of data connections, see [`GetConnectionStats`].
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use fxhash::FxHashMap;
+use metrics::{counter, gauge};
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use elfo_core::{
+    addr::{GroupNo, NodeNo},
+    message,
+};
+
+use crate::{config::Codec, protocol::GroupInfo};
+
+/// Returns statistics of all data connections of the network group.
+///
+/// Connections are listed even if they're reconnecting or closed for
+/// idleness right now, so counters survive reconnects.
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// # async fn exec(ctx: elfo::Context, network_addr: elfo::Addr) {
+/// use elfo_network::GetConnectionStats;
+///
+/// let stats = ctx
+///     .request_to(network_addr, GetConnectionStats::default())
+///     .resolve()
+///     .await
+///     .unwrap();
+///
+/// for conn in stats {
+///     println!("{}:{} is {:?}", conn.node_no, conn.remote_group, conn.state);
+/// }
+/// # }
+/// ```
+#[message(ret = Vec<ConnectionStats>)]
+#[derive(Default)]
+#[non_exhaustive]
+pub struct GetConnectionStats {}
+
+/// Statistics of the data connection between a local and a remote group.
+///
+/// Counters are accumulated over all connections between these groups.
+#[message(part)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    /// The local group's name.
+    pub local_group: String,
+    /// The remote group's name.
+    pub remote_group: String,
+    /// The remote node's number.
+    pub node_no: NodeNo,
+    /// The resolved address of the current socket, if any.
+    pub addr: Option<String>,
+    /// The current state of the connection.
+    pub state: ConnectionState,
+    /// The protocol version negotiated by the last handshake, if any.
+    pub protocol_version: Option<u8>,
+    /// The codec negotiated by the last handshake, if any.
+    pub codec: Option<Codec>,
+    /// The number of bytes received from the socket.
+    pub bytes_in: u64,
+    /// The number of bytes written to the socket.
+    pub bytes_out: u64,
+    /// The number of envelopes received, including skipped ones.
+    pub messages_in: u64,
+    /// The number of envelopes sent, including skipped ones.
+    pub messages_out: u64,
+    /// The number of envelopes waiting to be written to the socket.
+    pub tx_queue_depth: usize,
+    /// The round-trip time measured by the last ping, if any.
+    pub rtt: Option<Duration>,
+    /// How many times the connection has been established again.
+    pub reconnects: u64,
+    /// How long the current connection is established, if any.
+    pub uptime: Option<Duration>,
+}
+
+/// A state of the data connection, see [`ConnectionStats::state`].
+#[message(part)]
+#[derive(Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionState {
+    /// The connection is being opened.
+    Connecting,
+    /// The socket is open, groups are being negotiated.
+    Handshaking,
+    /// The connection is established.
+    Active,
+    /// The connection is closing for idleness, queued messages are flushed.
+    Draining,
+    /// The connection is closed for idleness and reopened on demand.
+    Idle,
+}
+
+impl ConnectionState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Connecting,
+            1 => Self::Handshaking,
+            2 => Self::Active,
+            3 => Self::Draining,
+            _ => Self::Idle,
+        }
+    }
+
+    fn into_u8(self) -> u8 {
+        match self {
+            Self::Connecting => 0,
+            Self::Handshaking => 1,
+            Self::Active => 2,
+            Self::Draining => 3,
+            Self::Idle => 4,
+        }
+    }
+}
+
+// === StatsRegistry ===
+
+type LinkKey = (GroupNo, NodeNo, GroupNo);
+
+/// Stats are shared by workers and the discovery.
+///
+/// An entry outlives its worker if the connection is going to be reopened
+/// by the discovery, so counters survive reconnects.
+#[derive(Default)]
+pub(crate) struct StatsRegistry {
+    links: Mutex<FxHashMap<LinkKey, Arc<LinkStats>>>,
+}
+
+impl StatsRegistry {
+    /// Returns stats of the specified pair of groups, creating them if needed.
+    pub(crate) fn get(&self, local: &GroupInfo, remote: &GroupInfo) -> Arc<LinkStats> {
+        let key = (local.group_no, remote.node_no, remote.group_no);
+
+        self.links
+            .lock()
+            .entry(key)
+            .or_insert_with(|| Arc::new(LinkStats::new(local, remote)))
+            .clone()
+    }
+
+    pub(crate) fn remove(&self, local: &GroupInfo, remote: &GroupInfo) {
+        let key = (local.group_no, remote.node_no, remote.group_no);
+        self.links.lock().remove(&key);
+    }
+
+    /// Marks the connection as handshaking if it's known.
+    pub(crate) fn on_handshaking(&self, local: GroupNo, remote: (NodeNo, GroupNo)) {
+        let key = (local, remote.0, remote.1);
+
+        if let Some(link) = self.links.lock().get(&key) {
+            // Don't touch established connections, e.g. on duplicates.
+            let _ = link.state.compare_exchange(
+                ConnectionState::Connecting.into_u8(),
+                ConnectionState::Handshaking.into_u8(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<ConnectionStats> {
+        let links = self.links.lock().values().cloned().collect::<Vec<_>>();
+        links.iter().map(|link| link.snapshot()).collect()
+    }
+}
+
+// === LinkStats ===
+
+/// Stats of all connections between a local and a remote group.
+pub(crate) struct LinkStats {
+    local_group: String,
+    remote_group: String,
+    node_no: NodeNo,
+    state: AtomicU8,
+    pub(crate) rx: Arc<Traffic>,
+    pub(crate) tx: Arc<Traffic>,
+    /// In nanoseconds, zero if unknown.
+    rtt: AtomicU64,
+    connections: AtomicU64,
+    socket: Mutex<SocketStats>,
+}
+
+/// Rarely changed stats, updated once a connection is opened or closed.
+#[derive(Default)]
+struct SocketStats {
+    addr: Option<String>,
+    protocol_version: Option<u8>,
+    codec: Option<Codec>,
+    connected_at: Option<Instant>,
+    tx_queue: Option<Box<dyn Fn() -> usize + Send>>,
+}
+
+impl LinkStats {
+    fn new(local: &GroupInfo, remote: &GroupInfo) -> Self {
+        Self {
+            local_group: local.group_name.clone(),
+            remote_group: remote.group_name.clone(),
+            node_no: remote.node_no,
+            state: AtomicU8::new(ConnectionState::Connecting.into_u8()),
+            rx: Default::default(),
+            tx: Default::default(),
+            rtt: AtomicU64::new(0),
+            connections: AtomicU64::new(0),
+            socket: Default::default(),
+        }
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        self.state.store(state.into_u8(), Ordering::Relaxed);
+    }
+
+    /// Sets the probe of the queue of outgoing envelopes,
+    /// `None` once the worker is stopped.
+    pub(crate) fn set_tx_queue(&self, probe: Option<Box<dyn Fn() -> usize + Send>>) {
+        self.socket.lock().tx_queue = probe;
+    }
+
+    pub(crate) fn on_connected(&self, addr: String, protocol_version: u8, codec: Codec) {
+        let mut socket = self.socket.lock();
+        socket.addr = Some(addr);
+        socket.protocol_version = Some(protocol_version);
+        socket.codec = Some(codec);
+        socket.connected_at = Some(Instant::now());
+        drop(socket);
+
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.set_state(ConnectionState::Active);
+    }
+
+    /// Keeps the negotiated parameters for diagnostics.
+    pub(crate) fn on_disconnected(&self, state: ConnectionState) {
+        let mut socket = self.socket.lock();
+        socket.addr = None;
+        socket.connected_at = None;
+        drop(socket);
+
+        self.set_state(state);
+    }
+
+    pub(crate) fn set_rtt(&self, rtt: Duration) {
+        let nanos = u64::try_from(rtt.as_nanos()).unwrap_or(u64::MAX).max(1);
+        self.rtt.store(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStats {
+        let socket = self.socket.lock();
+        let rtt = self.rtt.load(Ordering::Relaxed);
+
+        ConnectionStats {
+            local_group: self.local_group.clone(),
+            remote_group: self.remote_group.clone(),
+            node_no: self.node_no,
+            addr: socket.addr.clone(),
+            state: ConnectionState::from_u8(self.state.load(Ordering::Relaxed)),
+            protocol_version: socket.protocol_version,
+            codec: socket.codec,
+            bytes_in: self.rx.bytes.load(Ordering::Relaxed),
+            bytes_out: self.tx.bytes.load(Ordering::Relaxed),
+            messages_in: self.rx.messages.load(Ordering::Relaxed),
+            messages_out: self.tx.messages.load(Ordering::Relaxed),
+            tx_queue_depth: socket.tx_queue.as_ref().map_or(0, |probe| probe()),
+            rtt: (rtt > 0).then(|| Duration::from_nanos(rtt)),
+            reconnects: self.connections.load(Ordering::Relaxed).saturating_sub(1),
+            uptime: socket.connected_at.map(|at| at.elapsed()),
+        }
+    }
+}
+
+// === Traffic ===
+
+/// Counters of one direction, updated by a socket half.
+#[derive(Default)]
+pub(crate) struct Traffic {
+    bytes: AtomicU64,
+    messages: AtomicU64,
+}
+
+impl Traffic {
+    pub(crate) fn add_bytes(&self, count: u64) {
+        self.bytes.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_messages(&self, count: u64) {
+        self.messages.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+// === StatsReporter ===
+
+/// Emits stats as metrics labeled by the peer.
+/// Counters are emitted as increments since the previous report.
+pub(crate) struct StatsReporter {
+    labels: [(&'static str, String); 2],
+    prev: Option<ConnectionStats>,
+}
+
+impl StatsReporter {
+    pub(crate) fn new(remote: &GroupInfo) -> Self {
+        Self {
+            labels: [
+                ("peer", remote.node_no.to_string()),
+                ("remote_group", remote.group_name.clone()),
+            ],
+            prev: None,
+        }
+    }
+
+    pub(crate) fn report(&mut self, stats: ConnectionStats) {
+        let labels = &self.labels;
+        let prev = self.prev.as_ref();
+        let delta = |f: fn(&ConnectionStats) -> u64| f(&stats).saturating_sub(prev.map_or(0, f));
+
+        let bytes_in = delta(|s| s.bytes_in);
+        let bytes_out = delta(|s| s.bytes_out);
+        let messages_in = delta(|s| s.messages_in);
+        let messages_out = delta(|s| s.messages_out);
+        let reconnects = delta(|s| s.reconnects);
+        let tx_queue_depth = stats.tx_queue_depth as f64;
+        let rtt = stats.rtt.map_or(f64::NAN, |rtt| rtt.as_secs_f64());
+        let uptime = stats.uptime.map_or(0., |uptime| uptime.as_secs_f64());
+
+        counter!("elfo_network_peer_received_bytes_total", bytes_in, labels);
+        counter!("elfo_network_peer_sent_bytes_total", bytes_out, labels);
+        counter!(
+            "elfo_network_peer_received_messages_total",
+            messages_in,
+            labels
+        );
+        counter!(
+            "elfo_network_peer_sent_messages_total",
+            messages_out,
+            labels
+        );
+        counter!("elfo_network_peer_reconnects_total", reconnects, labels);
+        gauge!("elfo_network_peer_tx_queue_depth", tx_queue_depth, labels);
+        gauge!("elfo_network_peer_rtt_seconds", rtt, labels);
+        gauge!("elfo_network_peer_uptime_seconds", uptime, labels);
+
+        self.prev = Some(stats);
+    }
+}
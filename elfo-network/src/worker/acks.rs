@@ -0,0 +1,41 @@
+use fxhash::FxHashMap;
+use tracing::warn;
+
+use elfo_core::{errors::AckError, remote::AckToken};
+
+/// Acknowledgements of regular messages written to the connection, but not
+/// acknowledged by the peer yet, see `KIND_REGULAR_ACKED`.
+///
+/// Sequence numbers are unique for the whole link, so late acks sent for
+/// messages of an already closed connection are ignored.
+#[derive(Default)]
+pub(super) struct OutgoingAcks {
+    next_seq: u64,
+    map: FxHashMap<u64, AckToken>,
+}
+
+impl OutgoingAcks {
+    pub(super) fn next_seq(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+
+    pub(super) fn add(&mut self, seq: u64, token: AckToken) {
+        self.map.insert(seq, token);
+    }
+
+    pub(super) fn resolve(&mut self, seq: u64, result: Result<(), AckError>) {
+        let Some(token) = self.map.remove(&seq) else {
+            warn!(message = "received ack of unknown message", seq = seq);
+            return;
+        };
+
+        token.resolve(result);
+    }
+
+    /// Fails all pending acks with `AckError::ConnectionLost`.
+    pub(super) fn clear(&mut self) {
+        // Dropped tokens report `AckError::ConnectionLost`.
+        self.map.clear();
+    }
+}
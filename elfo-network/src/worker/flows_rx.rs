@@ -163,6 +163,24 @@ impl RxFlows {
 
         (close, update)
     }
+
+    /// Like [`RxFlows::close()`], but notifies the sender even if there is no
+    /// flow, e.g. if the actor has terminated before getting any direct
+    /// messages. Used when a message is sent to the gone actor, so the
+    /// sender stops sending to it instead of losing messages silently.
+    pub(super) fn close_direct(
+        &mut self,
+        addr: Addr,
+    ) -> (Option<internode::CloseFlow>, Option<internode::UpdateFlow>) {
+        let (close, update) = self.close(addr);
+        let close = close.or_else(|| {
+            Some(internode::CloseFlow {
+                addr: NetworkAddr::from_local(addr, self.node_no),
+            })
+        });
+
+        (close, update)
+    }
 }
 
 #[must_use]
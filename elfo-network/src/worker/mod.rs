@@ -1,49 +1,72 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use eyre::Result;
-use metrics::{decrement_gauge, increment_gauge};
+use metrics::{counter, decrement_gauge, histogram, increment_gauge};
 use parking_lot::Mutex;
 use tracing::{debug, error, info, trace, warn};
 
 use elfo_core::{
-    addr::{Addr, NodeNo},
-    message, Local, Message,
-    _priv::{AnyMessage, EbrGuard, GroupVisitor, MessageKind, Object, OwnedObject},
-    errors::{RequestError, SendError, TrySendError},
-    messages::{ConfigUpdated, Impossible},
-    msg, remote, scope,
+    _priv::{AddressBook, AnyMessage, EbrGuard, GroupVisitor, MessageKind, Object, OwnedObject},
+    addr::{Addr, GroupNo, NodeNo},
+    errors::{AckError, ErrorKind, SendError, TrySendError},
+    message,
+    messages::ConfigUpdated,
+    msg,
+    remote::{self, AckToken, SendNotify},
+    scope,
     stream::Stream,
     time::Interval,
-    Context, Envelope, ResponseToken, Topology,
+    tracing::TraceId,
+    Context, Envelope, Local, Message, RequestLimits, ResponseToken, SourceHandle, Topology,
 };
 use elfo_utils::{likely, time::Instant, unlikely};
 
 use self::{
+    acks::OutgoingAcks,
     flows_rx::RxFlows,
     flows_tx::{Acquire, TryAcquire, TxFlows},
     requests::OutgoingRequests,
+    tx_queues::TxQueues,
 };
 
+pub(crate) use self::requests::OutgoingRequestsRegistry;
+
 use crate::{
     codec::{
         decode::EnvelopeDetails,
         format::{
-            NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload, KIND_REQUEST_ALL,
-            KIND_REQUEST_ANY, KIND_RESPONSE_FAILED, KIND_RESPONSE_IGNORED, KIND_RESPONSE_OK,
+            compat_response_error, NetworkAddr, NetworkEnvelope, NetworkEnvelopePayload,
+            KIND_REGULAR, KIND_REGULAR_ACKED, KIND_REQUEST_ALL, KIND_REQUEST_ANY,
+            KIND_RESPONSE_DECODE_ERROR, KIND_RESPONSE_FAILED, KIND_RESPONSE_FORBIDDEN,
+            KIND_RESPONSE_IGNORED, KIND_RESPONSE_LIMIT_EXCEEDED, KIND_RESPONSE_NO_ROUTE,
+            KIND_RESPONSE_OK, KIND_RESPONSE_TIMEOUT, KIND_RESPONSE_UNSUPPORTED,
         },
     },
     config::Transport,
     frame::write::FrameState,
-    protocol::{internode, DataConnectionFailed, GroupInfo, HandleConnection},
+    protocol::{
+        internode, Acknowledge, DataConnectionFailed, GroupInfo, HandleConnection,
+        OpenDataConnection,
+    },
     rtt::Rtt,
-    socket::{ReadError, ReadHalf, WriteHalf},
+    socket::{Grant, IdleTracker, ReadError, ReadHalf, ReplayError, Socket, WriteHalf},
+    stats::{ConnectionState, LinkStats, StatsRegistry, StatsReporter},
     NetworkContext,
 };
 
+mod acks;
 mod flow_control;
 mod flows_rx;
 mod flows_tx;
 mod requests;
+mod tx_queues;
 
 // TODO: send `CloseFlow` once an actor is closed, not only on incoming message.
 // TODO: don't send control messages if the peer knows nothing about the flow.
@@ -58,19 +81,38 @@ struct PusherStopped;
 struct PingTick;
 
 #[message]
-struct ConnectionClosed;
+struct ConnectionClosed {
+    generation: u32,
+}
+
+#[message]
+struct WriterStopped {
+    generation: u32,
+}
+
+#[message]
+struct WakeUp;
 
 pub(crate) struct Worker {
     ctx: NetworkContext,
     topology: Topology,
     local: GroupInfo,
     remote: GroupInfo,
+    requests: Arc<OutgoingRequestsRegistry>,
+    stats: Arc<StatsRegistry>,
+    link_stats: Arc<LinkStats>,
     transport: Option<Transport>,
+    generation: u32,
 }
 
 impl Drop for Worker {
     fn drop(&mut self) {
+        self.link_stats.set_tx_queue(None);
+
         if let Some(transport) = self.transport.take() {
+            // Keep stats, the connection is reopened by the discovery.
+            self.link_stats.on_disconnected(ConnectionState::Connecting);
+
             let _ = self.ctx.try_send_to(
                 self.ctx.group(),
                 DataConnectionFailed {
@@ -80,24 +122,85 @@ impl Drop for Worker {
                 },
             );
         } else {
+            self.stats.remove(&self.local, &self.remote);
             info!("transport to reopen connection is unknown");
         }
     }
 }
 
+/// Parts shared by all connections of the worker.
+/// Connections can be closed for idleness and reopened on demand,
+/// but flows, requests and the queue of outgoing messages survive.
+struct Link {
+    time_origin: Instant,
+    tx_flows: Arc<TxFlows>,
+    rx_flows: Arc<Mutex<RxFlows>>,
+    requests: Arc<Mutex<OutgoingRequests>>,
+    acks: Arc<Mutex<OutgoingAcks>>,
+    activity: Arc<Activity>,
+    local_tx: kanal::AsyncSender<KanalItem>,
+    local_rx: kanal::AsyncReceiver<KanalItem>,
+    /// Envelopes taken from `local_rx`, but not written yet.
+    tx_queues: Arc<Mutex<TxQueues>>,
+    handle_addr: Addr,
+}
+
+enum State {
+    Connected(Connection),
+    /// Waiting for outgoing messages to reopen the connection.
+    Dormant(Stream<WakeUp>),
+    /// The connection is requested, but isn't established yet.
+    Dialing(tokio::time::Instant),
+}
+
+struct Connection {
+    generation: u32,
+    idle: IdleTracker,
+    writer: Stream<WriterStopped>,
+    reader: Stream<ConnectionClosed>,
+    stop: Arc<AtomicBool>,
+    grant: Arc<Grant>,
+    last_traffic: tokio::time::Instant,
+    is_closing: bool,
+    _gauge: ConnectionGauge,
+}
+
+struct ConnectionGauge(&'static str);
+
+impl ConnectionGauge {
+    fn new(reason: &'static str) -> Self {
+        increment_gauge!("elfo_network_data_connections", 1., "reason" => reason);
+        Self(reason)
+    }
+}
+
+impl Drop for ConnectionGauge {
+    fn drop(&mut self) {
+        decrement_gauge!("elfo_network_data_connections", 1., "reason" => self.0);
+    }
+}
+
 impl Worker {
     pub(super) fn new(
         ctx: NetworkContext,
         local: GroupInfo,
         remote: GroupInfo,
         topology: Topology,
+        requests: Arc<OutgoingRequestsRegistry>,
+        stats: Arc<StatsRegistry>,
     ) -> Self {
+        let link_stats = stats.get(&local, &remote);
+
         Self {
             ctx,
             topology,
+            requests,
+            stats,
+            link_stats,
             local,
             remote,
             transport: None,
+            generation: 0,
         }
     }
 
@@ -108,24 +211,26 @@ impl Worker {
             _ => unreachable!("unexpected initial message"),
         });
 
-        self.transport = first_message.transport;
+        self.transport = first_message.transport.clone();
 
-        let time_origin = Instant::now();
         let tx_flows = Arc::new(TxFlows::new(first_message.initial_window));
         let rx_flows = Arc::new(Mutex::new(RxFlows::new(
             self.local.node_no,
             first_message.initial_window,
         )));
-        let requests = Arc::new(Mutex::new(OutgoingRequests::default()));
-        let socket = first_message.socket.take().unwrap();
+        let activity = Arc::new(Activity::default());
 
         // Register `RemoteHandle`. Now we can receive messages from local groups.
         let (local_tx, local_rx) = kanal::unbounded_async();
         let remote_handle = RemoteHandle {
             tx: local_tx.clone(),
             tx_flows: tx_flows.clone(),
+            activity: activity.clone(),
+            node_no: self.remote.node_no,
+            group_name: self.remote.group_name.clone(),
         };
-        let remote_group_guard = self.topology.register_remote(
+        let topology = self.topology.clone();
+        let remote_group_guard = topology.register_remote(
             self.ctx.addr(),
             self.local.group_no,
             (self.remote.node_no, self.remote.group_no),
@@ -133,92 +238,336 @@ impl Worker {
             remote_handle,
         );
 
-        // Start handling local incoming messages.
-        let sw = SocketWriter {
-            node_no: self.local.node_no,
-            rx: local_rx,
-            tx: socket.write,
-            requests: requests.clone(),
-        };
-        self.ctx.attach(Stream::once(sw.exec()));
-
-        // Start handling network incoming messages.
-        let sr = SocketReader {
-            ctx: self.ctx.pruned(),
-            group_addr: self
-                .topology
-                .locals()
-                .map(|g| g.addr)
-                .find(|a| a.group_no() == Some(self.local.group_no))
-                .expect("invalid local group"),
+        let link = Link {
+            time_origin: Instant::now(),
+            tx_flows,
+            rx_flows,
+            requests: self.requests.get(self.remote.node_no),
+            acks: Default::default(),
+            activity,
+            local_tx,
+            local_rx,
+            tx_queues: Default::default(),
             handle_addr: remote_group_guard.handle_addr(),
-            time_origin,
-            // TODO: the number of samples should be calculated based on telemetry scrape
-            //       interval, but it's not povideded for now by the elfo core.
-            rtt: Rtt::new(5),
-            rx: socket.read,
-            tx: local_tx.clone(),
-            tx_flows: tx_flows.clone(),
-            rx_flows: rx_flows.clone(),
-            requests,
         };
-        self.ctx.attach(Stream::once(sr.exec()));
 
-        let mut idle = socket.idle;
+        let local_rx = link.local_rx.clone();
+        let tx_queues = link.tx_queues.clone();
+        self.link_stats.set_tx_queue(Some(Box::new(move || {
+            local_rx.len() + tx_queues.lock().len()
+        })));
+        let mut stats_reporter = StatsReporter::new(&self.remote);
+
+        let mut state = match first_message.socket.as_ref().and_then(|s| s.take()) {
+            Some(socket) => {
+                let reason = self.reason();
+                State::Connected(self.connect(&link, socket, reason))
+            }
+            None => self.sleep(&link),
+        };
 
         // Start ping ticks.
         let ping_interval = self.ctx.attach(Interval::new(PingTick));
-        ping_interval.start_after(Duration::ZERO, self.ctx.config().ping_interval);
+        ping_interval.start_after(Duration::ZERO, *self.ctx.config().ping_interval);
 
         while let Some(envelope) = self.ctx.recv().await {
             // TODO: graceful termination
 
             msg!(match envelope {
                 ConfigUpdated => {
-                    ping_interval.set_period(self.ctx.config().ping_interval);
+                    ping_interval.set_period(*self.ctx.config().ping_interval);
                 }
                 PingTick => {
-                    let idle_time = idle.check();
+                    stats_reporter.report(self.link_stats.snapshot());
 
-                    if idle_time >= self.ctx.config().idle_timeout {
+                    let State::Connected(conn) = &mut state else {
+                        continue;
+                    };
+
+                    if unlikely(conn.grant.is_revoked()) {
+                        warn!("access of the peer is revoked, closing");
+                        state = ward!(self.on_connection_lost(&link, state), break);
+                        continue;
+                    }
+
+                    let idle_time = conn.idle.check();
+
+                    if idle_time >= *self.ctx.config().idle_timeout {
                         error!(
                             message = "no data is received for a long time, closing",
                             idle_time = ?idle_time,
                             timeout = ?self.ctx.config().idle_timeout,
                         );
-                        break;
+                        state = ward!(self.on_connection_lost(&link, state), break);
+                        continue;
                     }
 
+                    if self.transport.is_some() {
+                        if let Some(idle_close) = self.ctx.config().idle_close {
+                            self.check_idleness(&link, conn, *idle_close);
+                        }
+                    }
+
+                    // Also wakes the writer up if the connection is closing.
                     let envelope = make_system_envelope(internode::Ping {
-                        payload: Instant::now().nanos_since(time_origin),
+                        payload: Instant::now().nanos_since(link.time_origin),
                     });
-                    let _ = local_tx.try_send(KanalItem::simple(NetworkAddr::NULL, envelope));
+                    let _ = link.local_tx.try_send(KanalItem::system(envelope));
                 }
                 msg @ HandleConnection => {
-                    info!("duplicate connection, skipping"); // TODO: replace?
                     if self.transport.is_none() {
-                        self.transport = msg.transport;
+                        self.transport = msg.transport.clone();
                     }
+
+                    let Some(socket) = msg.socket.as_ref().and_then(|s| s.take()) else {
+                        continue;
+                    };
+
+                    let reason = match &state {
+                        State::Connected(_) => {
+                            info!("duplicate connection, skipping"); // TODO: replace?
+                            continue;
+                        }
+                        State::Dialing(since) => {
+                            histogram!(
+                                "elfo_network_dial_duration_seconds",
+                                since.elapsed().as_secs_f64()
+                            );
+                            "on_demand"
+                        }
+                        State::Dormant(_) => self.reason(),
+                    };
+
+                    if let State::Dormant(notifier) = state {
+                        notifier.terminate();
+                    }
+
+                    state = State::Connected(self.connect(&link, socket, reason));
                 }
                 StartPusher(addr) => {
                     let pusher = Pusher {
                         ctx: self.ctx.pruned(),
                         actor_addr: *addr,
-                        tx: local_tx.clone(),
-                        rx_flows: rx_flows.clone(),
+                        tx: link.local_tx.clone(),
+                        rx_flows: link.rx_flows.clone(),
                     };
 
                     self.ctx.attach(Stream::once(pusher.exec()));
                 }
-                ConnectionClosed => {
+                msg @ WriterStopped => {
+                    if !matches!(&state, State::Connected(conn) if conn.generation == msg.generation)
+                    {
+                        continue;
+                    }
+
+                    info!("connection closed for idleness");
+                    counter!("elfo_network_idle_closed_connections_total", 1);
+                    state = self.close(&link, state);
+                }
+                msg @ ConnectionClosed => {
+                    if !matches!(&state, State::Connected(conn) if conn.generation == msg.generation)
+                    {
+                        continue;
+                    }
+
                     info!("connection closed by peer");
-                    break;
+                    state = ward!(self.on_connection_lost(&link, state), break);
+                }
+                WakeUp => {
+                    if matches!(state, State::Dormant(_)) {
+                        state = self.sleep(&link);
+                    }
                 }
             });
         }
 
         Ok(())
     }
+
+    fn reason(&self) -> &'static str {
+        if self.transport.is_some() {
+            "eager"
+        } else {
+            "incoming"
+        }
+    }
+
+    fn connect(&mut self, link: &Link, mut socket: Socket, reason: &'static str) -> Connection {
+        self.generation = self.generation.wrapping_add(1);
+        let generation = self.generation;
+
+        let config = self.ctx.config();
+        socket.write.set_chunking(
+            config.chunk_threshold.as_usize(),
+            config.chunk_size.as_usize(),
+        );
+        socket
+            .read
+            .set_max_transfer_size(config.max_transfer_size.as_usize());
+        socket.read.set_traffic(self.link_stats.rx.clone());
+        socket.write.set_traffic(self.link_stats.tx.clone());
+        self.link_stats
+            .on_connected(socket.info.to_string(), socket.version, socket.codec);
+
+        link.activity.is_dormant.store(false, Ordering::SeqCst);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Start handling local incoming messages.
+        let sw = SocketWriter {
+            generation,
+            node_no: self.local.node_no,
+            rx: link.local_rx.clone(),
+            queues: link.tx_queues.clone(),
+            tx: socket.write,
+            requests: link.requests.clone(),
+            acks: link.acks.clone(),
+            book: self.ctx.book().clone(),
+            stop: stop.clone(),
+        };
+        let writer = self.ctx.attach(Stream::once(sw.exec()));
+
+        // Start handling network incoming messages.
+        let sr = SocketReader {
+            generation,
+            ctx: self.ctx.pruned(),
+            route: LocalRoute::new(self.topology.clone(), self.local.group_no),
+            group_name: self.local.group_name.clone(),
+            grant: socket.grant.clone(),
+            handle_addr: link.handle_addr,
+            time_origin: link.time_origin,
+            // TODO: the number of samples should be calculated based on telemetry scrape
+            //       interval, but it's not povideded for now by the elfo core.
+            rtt: Rtt::new(5),
+            rx: socket.read,
+            tx: link.local_tx.clone(),
+            tx_flows: link.tx_flows.clone(),
+            rx_flows: link.rx_flows.clone(),
+            requests: link.requests.clone(),
+            acks: link.acks.clone(),
+            activity: link.activity.clone(),
+            stats: self.link_stats.clone(),
+        };
+        let reader = self.ctx.attach(Stream::once(sr.exec()));
+
+        Connection {
+            generation,
+            idle: socket.idle,
+            writer,
+            reader,
+            stop,
+            grant: socket.grant,
+            last_traffic: tokio::time::Instant::now(),
+            is_closing: false,
+            _gauge: ConnectionGauge::new(reason),
+        }
+    }
+
+    /// Starts closing the connection if there is no user traffic for a long
+    /// time. The writer flushes queued messages and stops, then the
+    /// connection is closed.
+    fn check_idleness(&self, link: &Link, conn: &mut Connection, idle_close: Duration) {
+        let now = tokio::time::Instant::now();
+
+        if link.activity.has_traffic.swap(false, Ordering::Relaxed) {
+            conn.last_traffic = now;
+            return;
+        }
+
+        if !conn.is_closing && now.duration_since(conn.last_traffic) >= idle_close {
+            debug!(message = "no traffic for a long time, closing", idle_close = ?idle_close);
+            conn.is_closing = true;
+            conn.stop.store(true, Ordering::Relaxed);
+            self.link_stats.set_state(ConnectionState::Draining);
+        }
+    }
+
+    /// Returns `None` if the connection cannot be reopened by this worker.
+    fn on_connection_lost(&mut self, link: &Link, state: State) -> Option<State> {
+        if self.transport.is_none() || self.ctx.config().idle_close.is_none() {
+            return None;
+        }
+
+        Some(self.close(link, state))
+    }
+
+    fn close(&mut self, link: &Link, state: State) -> State {
+        if let State::Connected(conn) = state {
+            conn.writer.terminate();
+            conn.reader.terminate();
+            self.link_stats.on_disconnected(ConnectionState::Idle);
+
+            // Written messages are lost or delivered, it's unknown anymore.
+            link.acks.lock().clear();
+        }
+
+        self.sleep(link)
+    }
+
+    /// Waits for outgoing messages or reopens the connection immediately
+    /// if something has been sent while closing.
+    fn sleep(&mut self, link: &Link) -> State {
+        let activity = &link.activity;
+
+        // Subscribe before checking the queue to avoid missing notifications.
+        activity.is_dormant.store(true, Ordering::SeqCst);
+        let notified = activity.wake.notified();
+
+        if activity.has_traffic.swap(false, Ordering::SeqCst)
+            && (!link.local_rx.is_empty() || !link.tx_queues.lock().is_empty())
+        {
+            return self.dial();
+        }
+
+        self.link_stats.set_state(ConnectionState::Idle);
+        State::Dormant(self.ctx.attach(Stream::once(async move {
+            notified.await;
+            WakeUp
+        })))
+    }
+
+    fn dial(&mut self) -> State {
+        let transport = self.transport.clone().expect("transport must be known");
+        debug!(message = "opening connection on demand", addr = %transport);
+
+        let res = self.ctx.try_send_to(
+            self.ctx.group(),
+            OpenDataConnection {
+                transport,
+                local: self.local.group_no,
+                remote: (self.remote.node_no, self.remote.group_no),
+            },
+        );
+
+        if let Err(err) = res {
+            error!(message = "cannot request a new connection", error = %err);
+        }
+
+        self.link_stats.set_state(ConnectionState::Connecting);
+
+        State::Dialing(tokio::time::Instant::now())
+    }
+}
+
+// === Activity ===
+
+/// Tracks user traffic to close idle connections and reopen them on demand.
+#[derive(Default)]
+struct Activity {
+    has_traffic: AtomicBool,
+    is_dormant: AtomicBool,
+    wake: SendNotify,
+}
+
+impl Activity {
+    fn touch(&self) {
+        if !self.has_traffic.load(Ordering::Relaxed) {
+            self.has_traffic.store(true, Ordering::SeqCst);
+        }
+
+        if unlikely(self.is_dormant.load(Ordering::SeqCst)) {
+            self.wake.notify();
+        }
+    }
 }
 
 // === SocketWriter ===
@@ -226,14 +575,19 @@ impl Worker {
 /// A subtask that handles incoming messages from local actors and writes them
 /// to the socket.
 struct SocketWriter {
+    generation: u32,
     node_no: NodeNo,
     rx: kanal::AsyncReceiver<KanalItem>,
+    queues: Arc<Mutex<TxQueues>>,
     tx: WriteHalf,
     requests: Arc<Mutex<OutgoingRequests>>,
+    acks: Arc<Mutex<OutgoingAcks>>,
+    book: AddressBook,
+    stop: Arc<AtomicBool>,
 }
 
 impl SocketWriter {
-    async fn exec(mut self) -> Impossible {
+    async fn exec(mut self) -> WriterStopped {
         // We should write messages as many as possible at once to have better
         // compression rate and reduce the number of system calls.
         // On the other hand, we should minimize the time which every message is unsent.
@@ -246,54 +600,146 @@ impl SocketWriter {
         // the execution back to the runtime even in case of a full incoming queue.
         // We should use `tokio::task::unconstrained()` here and preempt the (sub)task
         // after sending each batch of messages.
+        //
+        // Large messages are sent by chunks, one chunk of every such message after
+        // each batch of other messages. We don't wait for new messages while there
+        // are unsent chunks.
+        //
+        // Once the connection is closing for idleness, the writer stops after
+        // flushing all available messages and chunks.
+        //
+        // Available messages are moved to per-destination queues before writing,
+        // so a burst to one destination doesn't delay others, see `TxQueues`.
         loop {
             // TODO: error handling, metrics.
-            let mut item = self.rx.recv().await.unwrap();
-            loop {
-                let (network_envelope, response_token) = make_network_envelope(item, self.node_no);
-                scope::set_trace_id(network_envelope.trace_id);
-
-                // NOTE: We use `unwrap()` for results from all `self.tx` methods because these
-                // errors are unrecoverable.
-                if let Some(frame_state) = self.tx.feed(&network_envelope).unwrap() {
-                    // Envelope was encoded successfylly, so we can store the response token.
-                    // Otherwise, it will be dropped with the `Failed` reason.
-                    if let Some(token) = response_token {
-                        self.requests.lock().add_token(token);
-                    }
+            if !self.tx.has_pending_chunks() && self.rx.is_empty() && self.queues.lock().is_empty()
+            {
+                let item = self.rx.recv().await.unwrap();
+                self.queues.lock().push(item);
+            }
 
-                    if frame_state == FrameState::FlushAdvised {
-                        break;
+            {
+                let mut queues = self.queues.lock();
+                while let Some(item) = self.rx.try_recv().unwrap() {
+                    queues.push(item);
+                }
+
+                while let Some((destination, mut item)) = queues.pop() {
+                    let ack = self.take_ack(&mut item);
+                    let has_limits = self.tx.has_request_limits();
+                    let has_idempotency_keys = self.tx.has_idempotency_keys();
+                    let has_extended_responses = self.tx.has_extended_responses();
+                    let (mut network_envelope, response_token) = make_network_envelope(
+                        item,
+                        self.node_no,
+                        has_limits,
+                        has_idempotency_keys,
+                        has_extended_responses,
+                        ack.as_ref().map(|(seq, _)| *seq),
+                    );
+                    self.tx.stamp(&mut network_envelope);
+                    scope::set_trace_id(network_envelope.trace_id);
+
+                    // NOTE: We use `unwrap()` for results from all `self.tx` methods because these
+                    // errors are unrecoverable.
+                    if let Some(frame_state) = self.tx.feed(&network_envelope).unwrap() {
+                        queues.charge(destination, self.tx.last_fed_size());
+
+                        // Envelope was encoded successfylly, so we can store the response token.
+                        // Otherwise, it will be dropped with the `Failed` reason.
+                        if let Some(token) = response_token {
+                            self.requests.lock().add_token(token);
+                        }
+
+                        if let Some((seq, ack)) = ack {
+                            self.acks.lock().add(seq, ack);
+                        }
+
+                        if frame_state == FrameState::FlushAdvised {
+                            break;
+                        }
+                    } else if let Some((_, ack)) = ack {
+                        ack.resolve(Err(AckError::DecodeFailed));
                     }
                 }
+            }
 
-                item = ward!(self.rx.try_recv().unwrap(), break);
+            if self.tx.has_pending_chunks() {
+                let book = &self.book;
+                let is_alive = |addr: NetworkAddr| {
+                    addr == NetworkAddr::NULL
+                        || book.get(addr.into_local(), &EbrGuard::new()).is_some()
+                };
+                self.tx.feed_chunks(is_alive).unwrap();
             }
 
             // We have either received a recommendation for a flush or there are no more
             // messages for the time being. Since we don't know how long we'll
             // wait for the next message, we flush in both cases.
             self.tx.flush().await.unwrap();
+            self.queues.lock().report();
+
+            if unlikely(self.stop.load(Ordering::Relaxed)) && !self.tx.has_pending_chunks() {
+                return WriterStopped {
+                    generation: self.generation,
+                };
+            }
+        }
+    }
+
+    /// Takes the ack token of the envelope and assigns a sequence number.
+    /// If the peer doesn't support acks, the message is sent as a regular one.
+    fn take_ack(&self, item: &mut KanalItem) -> Option<(u64, AckToken)> {
+        let ack = item.envelope.as_mut().ok()?.take_ack()?;
+
+        if unlikely(!self.tx.has_acks()) {
+            ack.resolve(Err(AckError::Unsupported));
+            return None;
         }
+
+        Some((self.acks.lock().next_seq(), ack))
     }
 }
 
+/// Limits and idempotency keys are dropped if the peer doesn't support them.
 fn make_network_envelope(
     item: KanalItem,
     node_no: NodeNo,
+    has_limits: bool,
+    has_idempotency_keys: bool,
+    has_extended_responses: bool,
+    ack_seq: Option<u64>,
 ) -> (NetworkEnvelope, Option<ResponseToken>) {
+    let is_force_sampled = item.envelope.as_ref().is_ok_and(|e| e.is_force_sampled());
     let (sender, trace_id, payload, token) = match (item.envelope, item.token) {
-        // Regular, RequestAny, RequestAll
+        // Ack
+        (Ok(envelope), None) if envelope.is::<Acknowledge>() => {
+            let trace_id = envelope.trace_id();
+            let (ack, _) = envelope.unpack::<Acknowledge>().expect("impossible");
+            let payload = NetworkEnvelopePayload::Ack {
+                seq: ack.seq,
+                result: ack.result,
+            };
+
+            (Addr::NULL, trace_id, payload, None)
+        }
+        // Regular, RegularAcked, RequestAny, RequestAll
         (Ok(envelope), None) => {
             let sender = envelope.sender();
             let trace_id = envelope.trace_id();
             let (message, kind) = envelope.unpack::<AnyMessage>().expect("impossible");
 
             let (payload, token) = match kind {
-                MessageKind::Regular { .. } => (NetworkEnvelopePayload::Regular { message }, None),
+                MessageKind::Regular { .. } => match ack_seq {
+                    Some(seq) => (NetworkEnvelopePayload::RegularAcked { seq, message }, None),
+                    None => (NetworkEnvelopePayload::Regular { message }, None),
+                },
                 MessageKind::RequestAny(token) => (
                     NetworkEnvelopePayload::RequestAny {
                         request_id: token.request_id(),
+                        nonce: 0,
+                        limits: request_limits(&token, has_limits),
+                        idempotency_key: token.idempotency_key().filter(|_| has_idempotency_keys),
                         message,
                     },
                     Some(token),
@@ -301,6 +747,9 @@ fn make_network_envelope(
                 MessageKind::RequestAll(token) => (
                     NetworkEnvelopePayload::RequestAll {
                         request_id: token.request_id(),
+                        nonce: 0,
+                        limits: request_limits(&token, has_limits),
+                        idempotency_key: token.idempotency_key().filter(|_| has_idempotency_keys),
                         message,
                     },
                     Some(token),
@@ -340,7 +789,7 @@ fn make_network_envelope(
 
             let payload = NetworkEnvelopePayload::Response {
                 request_id: token.request_id(),
-                message: Err(err),
+                message: Err(compat_response_error(err, has_extended_responses)),
                 is_last: token.is_last(),
             };
 
@@ -356,19 +805,31 @@ fn make_network_envelope(
         sender: NetworkAddr::from_local(sender, node_no),
         recipient: item.recipient,
         trace_id,
+        is_force_sampled,
         payload,
     };
 
     (envelope, token)
 }
 
+fn request_limits(token: &ResponseToken, has_limits: bool) -> RequestLimits {
+    if has_limits {
+        token.limits()
+    } else {
+        RequestLimits::default()
+    }
+}
+
 // === SocketReader ===
 
 /// A subtask that reads messages from the socket and routes them to local
 /// groups. If some messages cannot be sent right now, the pusher is spawned.
 struct SocketReader {
+    generation: u32,
     ctx: Context,
-    group_addr: Addr,
+    route: LocalRoute,
+    group_name: String,
+    grant: Arc<Grant>,
     handle_addr: Addr,
     time_origin: Instant,
     rtt: Rtt,
@@ -377,6 +838,9 @@ struct SocketReader {
     tx_flows: Arc<TxFlows>,
     rx_flows: Arc<Mutex<RxFlows>>,
     requests: Arc<Mutex<OutgoingRequests>>,
+    acks: Arc<Mutex<OutgoingAcks>>,
+    activity: Arc<Activity>,
+    stats: Arc<LinkStats>,
 }
 
 impl SocketReader {
@@ -387,9 +851,15 @@ impl SocketReader {
                 Ok(None) => break,
                 Err(ReadError::EnvelopeSkipped(details)) => {
                     scope::set_trace_id(details.trace_id);
+                    self.activity.touch();
                     self.handle_skipped_message(details);
                     continue;
                 }
+                Err(ReadError::Replayed(error, envelope)) => {
+                    scope::set_trace_id(envelope.trace_id);
+                    self.handle_replayed_request(error, envelope);
+                    continue;
+                }
                 Err(ReadError::Fatal(e)) => {
                     // TODO: error handling.
                     panic!("fatal error while reading from socket: {:#}", e);
@@ -398,6 +868,35 @@ impl SocketReader {
 
             scope::set_trace_id(network_envelope.trace_id);
 
+            if unlikely(!self.grant.is_allowed(&self.group_name))
+                && is_restricted(&network_envelope.payload)
+            {
+                self.activity.touch();
+                self.handle_forbidden_message(network_envelope);
+                continue;
+            }
+
+            // The local group can be added, disabled or remounted at any time.
+            if network_envelope.recipient == NetworkAddr::NULL
+                && is_restricted(&network_envelope.payload)
+            {
+                if let Err(error) = self.route.resolve(self.ctx.book()) {
+                    self.activity.touch();
+                    self.handle_unroutable_message(network_envelope, error);
+                    continue;
+                }
+            }
+
+            // Only regular messages can be system ones.
+            let is_regular = matches!(
+                network_envelope.payload,
+                NetworkEnvelopePayload::Regular { .. }
+                    | NetworkEnvelopePayload::RegularAcked { .. }
+            );
+            if !is_regular {
+                self.activity.touch();
+            }
+
             let (sender, recipient) = (network_envelope.sender, network_envelope.recipient);
             let envelope = ward!(self.make_envelope(network_envelope), continue);
 
@@ -406,6 +905,10 @@ impl SocketReader {
                 continue;
             }
 
+            if is_regular {
+                self.activity.touch();
+            }
+
             // Recipients can respond to the sender, so we should add a flow.
             self.tx_flows.add_flow_if_needed(sender);
 
@@ -417,14 +920,79 @@ impl SocketReader {
             }
         }
 
-        ConnectionClosed
+        ConnectionClosed {
+            generation: self.generation,
+        }
     }
 
     /// Ensures that messages that were skipped due to errors during decoding
     /// are properly accounted for in flow control. Also notifies the remote
     /// actor if the message was a request in order to avoid indefinite
-    /// waiting from the remote actor's side.
+    /// waiting from the remote actor's side: with `ErrorKind::Unsupported`
+    /// if the message is unknown and `ErrorKind::RemoteDecodeError`
+    /// otherwise.
     fn handle_skipped_message(&self, details: EnvelopeDetails) {
+        let error = if details.is_unknown_message {
+            ErrorKind::Unsupported
+        } else {
+            ErrorKind::RemoteDecodeError
+        };
+
+        self.discard_message(details, error);
+    }
+
+    /// Rejects messages to the local group, which isn't allowed to access by
+    /// the peer. Requests are responded with `ErrorKind::Forbidden`.
+    fn handle_forbidden_message(&self, envelope: NetworkEnvelope) {
+        let (protocol, name) = envelope.payload.protocol_and_name();
+        warn!(
+            message = "message to the forbidden group is rejected",
+            group = %self.group_name,
+            protocol,
+            name,
+            sender = %envelope.sender,
+        );
+        counter!("elfo_network_forbidden_messages_total", 1);
+
+        self.discard_message(incoming_details(&envelope), ErrorKind::Forbidden);
+    }
+
+    /// Discards the request rejected by replay protection. Unlike other
+    /// rejected messages, it isn't responded, because the response would
+    /// resolve the original request, and isn't accounted in flow control,
+    /// because the peer hasn't sent it.
+    fn handle_replayed_request(&self, error: ReplayError, envelope: NetworkEnvelope) {
+        let (protocol, name) = envelope.payload.protocol_and_name();
+        warn!(
+            message = "possibly replayed request is discarded",
+            group = %self.group_name,
+            reason = %error,
+            protocol,
+            name,
+            sender = %envelope.sender,
+        );
+        counter!("elfo_network_replayed_requests_total", 1, "reason" => error.as_str());
+    }
+
+    /// Rejects messages to the local group, which isn't mounted yet or is
+    /// disabled by its mount condition. Requests are responded with
+    /// `ErrorKind::NoRoute`, so the peer doesn't wait for the timeout.
+    fn handle_unroutable_message(&self, envelope: NetworkEnvelope, error: NoRoute) {
+        let (protocol, name) = envelope.payload.protocol_and_name();
+        warn!(
+            message = "no route to the local group, message is rejected",
+            group = %self.group_name,
+            reason = %error,
+            protocol,
+            name,
+            sender = %envelope.sender,
+        );
+        counter!("elfo_network_unroutable_messages_total", 1);
+
+        self.discard_message(incoming_details(&envelope), ErrorKind::NoRoute);
+    }
+
+    fn discard_message(&self, details: EnvelopeDetails, error: ErrorKind) {
         let update = {
             let mut rx_flows = self.rx_flows.lock();
             if details.recipient == NetworkAddr::NULL {
@@ -440,7 +1008,15 @@ impl SocketReader {
 
         self.send_back(update);
 
-        if details.kind == KIND_REQUEST_ALL || details.kind == KIND_REQUEST_ANY {
+        if let Some(seq) = details.ack_seq {
+            let result = Err(match error {
+                ErrorKind::Forbidden => AckError::Forbidden,
+                ErrorKind::NoRoute => AckError::NoRoute,
+                _ => AckError::DecodeFailed,
+            });
+
+            self.send_back(Some(Acknowledge { seq, result }));
+        } else if details.kind == KIND_REQUEST_ALL || details.kind == KIND_REQUEST_ANY {
             let guard = EbrGuard::new();
             let sender = self
                 .ctx
@@ -459,10 +1035,16 @@ impl SocketReader {
             // so we need to introduce the flow which will be used in `sender.respond()`
             // below.
             self.tx_flows.add_flow_if_needed(details.sender);
-            sender.respond(token, Err(RequestError::Failed));
+            sender.respond(token, Err(error));
         } else if details.kind == KIND_RESPONSE_OK
             || details.kind == KIND_RESPONSE_FAILED
             || details.kind == KIND_RESPONSE_IGNORED
+            || details.kind == KIND_RESPONSE_FORBIDDEN
+            || details.kind == KIND_RESPONSE_NO_ROUTE
+            || details.kind == KIND_RESPONSE_TIMEOUT
+            || details.kind == KIND_RESPONSE_DECODE_ERROR
+            || details.kind == KIND_RESPONSE_UNSUPPORTED
+            || details.kind == KIND_RESPONSE_LIMIT_EXCEEDED
         {
             let Some(token) = self.requests.lock().get_token(
                 details.recipient.into_remote(),
@@ -485,28 +1067,49 @@ impl SocketReader {
     }
 
     fn make_envelope(&self, network_envelope: NetworkEnvelope) -> Option<Envelope> {
-        let sender = network_envelope.sender.into_remote();
+        let network_sender = network_envelope.sender;
+        let sender = network_sender.into_remote();
         let recipient = network_envelope.recipient.into_local();
         let trace_id = network_envelope.trace_id;
+        let is_force_sampled = network_envelope.is_force_sampled;
 
+        let mut ack = None;
         let (message, message_kind) = match network_envelope.payload {
             NetworkEnvelopePayload::Regular { message } => {
                 (message, MessageKind::Regular { sender })
             }
+            NetworkEnvelopePayload::RegularAcked { seq, message } => {
+                ack = Some(self.make_ack_token(seq, trace_id));
+                (message, MessageKind::Regular { sender })
+            }
+            NetworkEnvelopePayload::Ack { seq, result } => {
+                self.acks.lock().resolve(seq, result);
+                return None;
+            }
             NetworkEnvelopePayload::RequestAny {
                 request_id,
+                limits,
+                idempotency_key,
                 message,
+                ..
             } => {
                 let token =
-                    ResponseToken::new(sender, request_id, trace_id, self.ctx.book().clone());
+                    ResponseToken::new(sender, request_id, trace_id, self.ctx.book().clone())
+                        .with_limits(limits)
+                        .with_idempotency_key(idempotency_key);
                 (message, MessageKind::RequestAny(token))
             }
             NetworkEnvelopePayload::RequestAll {
                 request_id,
+                limits,
+                idempotency_key,
                 message,
+                ..
             } => {
                 let token =
-                    ResponseToken::new(sender, request_id, trace_id, self.ctx.book().clone());
+                    ResponseToken::new(sender, request_id, trace_id, self.ctx.book().clone())
+                        .with_limits(limits)
+                        .with_idempotency_key(idempotency_key);
                 (message, MessageKind::RequestAll(token))
             }
             NetworkEnvelopePayload::Response {
@@ -550,22 +1153,49 @@ impl SocketReader {
                 };
 
                 let envelope = message.map(|message| {
-                    Envelope::with_trace_id(
+                    let mut envelope = Envelope::with_trace_id(
                         message,
                         MessageKind::Response { sender, request_id },
                         trace_id,
-                    )
+                    );
+                    if is_force_sampled {
+                        envelope.set_force_sampled();
+                    }
+                    envelope
                 });
 
                 // Since this is a response to a request which originated from this node,
-                // all the neccessary flows have been already added.
+                // all the neccessary flows have been already added, except the one to the
+                // responder. It's added to allow direct sends to the responder.
+                self.tx_flows.add_flow_if_needed(network_sender);
                 object.respond(token, envelope);
 
                 return None;
             }
+            NetworkEnvelopePayload::Chunk { .. } => {
+                unreachable!("chunks are reassembled by the socket")
+            }
         };
 
-        Some(Envelope::with_trace_id(message, message_kind, trace_id))
+        let mut envelope = Envelope::with_trace_id(message, message_kind, trace_id);
+        if is_force_sampled {
+            envelope.set_force_sampled();
+        }
+        envelope.set_ack(ack);
+        Some(envelope)
+    }
+
+    /// Makes a token sending `Ack` back once resolved. If the envelope is
+    /// dropped before, e.g. the recipient has gone, `AckError::Closed` is sent.
+    fn make_ack_token(&self, seq: u64, trace_id: TraceId) -> AckToken {
+        let tx = self.tx.clone();
+
+        AckToken::new(AckError::Closed, move |result| {
+            let message = Acknowledge { seq, result };
+            let kind = MessageKind::regular(Addr::NULL);
+            let envelope = Envelope::with_trace_id(message, kind, trace_id);
+            let _ = tx.try_send(KanalItem::system(envelope));
+        })
     }
 
     fn handle_system_message(&mut self, envelope: &Envelope) -> bool {
@@ -584,6 +1214,7 @@ impl SocketReader {
             msg @ internode::Pong => {
                 let time_ns = Instant::now().nanos_since(self.time_origin) - msg.payload;
                 self.rtt.push(Duration::from_nanos(time_ns));
+                self.stats.set_rtt(Duration::from_nanos(time_ns));
             }
             _ => return false,
         });
@@ -597,7 +1228,7 @@ impl SocketReader {
 
         let guard = EbrGuard::new();
         let Some(object) = book.get(recipient, &guard) else {
-            let (close, update) = flows.close(recipient);
+            let (close, update) = flows.close_direct(recipient);
             self.send_back(close);
             self.send_back(update);
             return;
@@ -639,13 +1270,15 @@ impl SocketReader {
             flows: &mut flows,
         };
 
+        // The group is resolved before, but it can be gone since then.
         let guard = EbrGuard::new();
-        let group = self
-            .ctx
-            .book()
-            .get(self.group_addr, &guard)
-            .expect("invalid local group addr");
-        group.visit_group(envelope, &mut visitor);
+        if let Some(group) = self
+            .route
+            .addr()
+            .and_then(|addr| self.ctx.book().get(addr, &guard))
+        {
+            group.visit_group(envelope, &mut visitor);
+        }
 
         self.send_back(flows.release_routed());
     }
@@ -667,18 +1300,40 @@ impl SocketReader {
         // If the recipient is unstable (i.e. already has pending messages), enqueue and
         // return. The envelope will be handled by the corresponding pusher.
         // Unexisted flows (new ones or already closed) are considered stable.
-        if flow.as_ref().map_or(false, |f| !f.is_stable()) {
+        if flow.as_ref().is_some_and(|f| !f.is_stable()) {
             let mut flow = flow.unwrap();
             flow.acquire_direct(!routed);
             flow.enqueue(envelope, routed);
             return;
         }
 
+        // The envelope is acknowledged once enqueued, maybe by the pusher.
+        let mut envelope = envelope;
+        let ack = envelope.take_ack();
+
         // TODO: use `unbounded_send` if the envelope has been sent unboundedly.
         let result = object.try_send(Addr::NULL, envelope);
 
+        let result = match (result, ack) {
+            (Err(TrySendError::Full(mut envelope)), ack) => {
+                envelope.set_ack(ack);
+                Err(TrySendError::Full(envelope))
+            }
+            (result, Some(ack)) => {
+                ack.resolve(match &result {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Closed(e)) if e.rejection().is_some() => {
+                        Err(AckError::Rejected)
+                    }
+                    Err(_) => Err(AckError::Closed),
+                });
+                result
+            }
+            (result, None) => result,
+        };
+
         // If the recipient has gone, close the flow and return.
-        if matches!(result, Err(TrySendError::Closed(_))) {
+        if matches!(&result, Err(TrySendError::Closed(e)) if e.rejection().is_none()) {
             let (close, update) = flows.close(object.addr());
             self.send_back(close);
             self.send_back(update);
@@ -694,7 +1349,9 @@ impl SocketReader {
         flow.acquire_direct(!routed);
 
         match result {
-            Ok(()) => {
+            // Rejected envelopes are dropped, just like handled ones,
+            // other `Closed` ones are handled above.
+            Ok(()) | Err(TrySendError::Closed(_)) => {
                 self.send_back(flow.release_direct());
 
                 if routed {
@@ -710,16 +1367,127 @@ impl SocketReader {
                     error!(error = %err, "failed to start a pusher");
                 }
             }
-            Err(TrySendError::Closed(_)) => unreachable!(),
         }
     }
 
     fn send_back(&self, message: Option<impl Message>) {
         if let Some(envelope) = message.map(make_system_envelope) {
-            self.tx
-                .try_send(KanalItem::simple(NetworkAddr::NULL, envelope))
-                .unwrap();
+            self.tx.try_send(KanalItem::system(envelope)).unwrap();
+        }
+    }
+}
+
+// === LocalRoute ===
+
+/// The reason why a routed message cannot be delivered to the local group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NoRoute {
+    NotMounted,
+    Disabled,
+}
+
+impl fmt::Display for NoRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotMounted => "group isn't mounted",
+            Self::Disabled => "group is disabled",
+        })
+    }
+}
+
+/// Resolves the local group by its number.
+///
+/// The resolved address is cached and tagged with the topology generation,
+/// so it's resolved again only once the local topology is changed.
+struct LocalRoute {
+    topology: Topology,
+    group_no: GroupNo,
+    generation: u64,
+    addr: Option<Addr>,
+}
+
+impl LocalRoute {
+    fn new(topology: Topology, group_no: GroupNo) -> Self {
+        let mut this = Self {
+            topology,
+            group_no,
+            generation: 0,
+            addr: None,
+        };
+        this.refresh();
+        this
+    }
+
+    fn addr(&self) -> Option<Addr> {
+        self.addr
+    }
+
+    fn resolve(&mut self, book: &AddressBook) -> Result<Addr, NoRoute> {
+        if book.topology_generation() != self.generation {
+            self.refresh();
+        }
+
+        let addr = self.addr.ok_or(NoRoute::NotMounted)?;
+        let guard = EbrGuard::new();
+        let group = book.get(addr, &guard).ok_or(NoRoute::NotMounted)?;
+
+        if group.is_disabled_group() {
+            return Err(NoRoute::Disabled);
+        }
+
+        Ok(addr)
+    }
+
+    fn refresh(&mut self) {
+        // Read the generation first to avoid missing concurrent changes.
+        self.generation = self.topology.generation();
+        self.addr = self
+            .topology
+            .locals()
+            .map(|group| group.addr)
+            .find(|addr| addr.group_no() == Some(self.group_no));
+    }
+}
+
+fn incoming_details(envelope: &NetworkEnvelope) -> EnvelopeDetails {
+    let (kind, request_id, ack_seq) = match envelope.payload {
+        NetworkEnvelopePayload::RequestAny { request_id, .. } => {
+            (KIND_REQUEST_ANY, Some(request_id), None)
+        }
+        NetworkEnvelopePayload::RequestAll { request_id, .. } => {
+            (KIND_REQUEST_ALL, Some(request_id), None)
         }
+        NetworkEnvelopePayload::RegularAcked { seq, .. } => (KIND_REGULAR_ACKED, None, Some(seq)),
+        _ => (KIND_REGULAR, None, None),
+    };
+
+    EnvelopeDetails {
+        kind,
+        sender: envelope.sender,
+        recipient: envelope.recipient,
+        request_id,
+        ack_seq,
+        trace_id: envelope.trace_id,
+        is_unknown_message: false,
+    }
+}
+
+/// Returns `true` if the envelope is subject to access restrictions.
+/// Responses and system messages are always allowed.
+fn is_restricted(payload: &NetworkEnvelopePayload) -> bool {
+    match payload {
+        NetworkEnvelopePayload::Regular { message } => {
+            !(message.is::<internode::UpdateFlow>()
+                || message.is::<internode::CloseFlow>()
+                || message.is::<internode::Ping>()
+                || message.is::<internode::Pong>())
+        }
+        NetworkEnvelopePayload::RegularAcked { .. }
+        | NetworkEnvelopePayload::RequestAny { .. }
+        | NetworkEnvelopePayload::RequestAll { .. } => true,
+        NetworkEnvelopePayload::Response { .. }
+        | NetworkEnvelopePayload::Ack { .. }
+        | NetworkEnvelopePayload::Chunk { .. } => false,
     }
 }
 
@@ -764,16 +1532,24 @@ impl Pusher {
         PusherStopped
     }
 
-    async fn push(&self, envelope: Envelope, routed: bool) -> bool {
+    async fn push(&self, mut envelope: Envelope, routed: bool) -> bool {
+        let ack = envelope.take_ack();
         let fut = {
             let guard = EbrGuard::new();
+            // If the recipient has gone, the dropped ack sends `AckError::Closed`.
             let object = ward!(self.ctx.book().get(self.actor_addr, &guard), return false);
 
             // TODO: use `unbounded_send` if the envelope has been sent unboundedly.
             Object::send(object, Addr::NULL, envelope)
         };
 
-        if fut.await.is_ok() {
+        let result = fut.await;
+
+        if let Some(ack) = ack {
+            ack.resolve(result.as_ref().map(drop).map_err(|_| AckError::Closed));
+        }
+
+        if result.is_ok() {
             let mut flows = self.rx_flows.lock();
 
             let Some(mut flow) = flows.get_flow(self.actor_addr) else {
@@ -793,9 +1569,7 @@ impl Pusher {
 
     fn send_back(&self, message: Option<impl Message>) {
         if let Some(envelope) = message.map(make_system_envelope) {
-            self.tx
-                .try_send(KanalItem::simple(NetworkAddr::NULL, envelope))
-                .unwrap();
+            self.tx.try_send(KanalItem::system(envelope)).unwrap();
         }
     }
 }
@@ -810,8 +1584,10 @@ impl Drop for Pusher {
 
 struct KanalItem {
     recipient: NetworkAddr,
-    envelope: Result<Envelope, RequestError>,
+    envelope: Result<Envelope, ErrorKind>,
     token: Option<ResponseToken>,
+    /// Internal messages are sent before others, see [`TxQueues`].
+    is_system: bool,
 }
 
 impl KanalItem {
@@ -820,6 +1596,14 @@ impl KanalItem {
             recipient,
             envelope: Ok(envelope),
             token: None,
+            is_system: false,
+        }
+    }
+
+    fn system(envelope: Envelope) -> Self {
+        Self {
+            is_system: true,
+            ..Self::simple(NetworkAddr::NULL, envelope)
         }
     }
 }
@@ -827,6 +1611,9 @@ impl KanalItem {
 struct RemoteHandle {
     tx: kanal::AsyncSender<KanalItem>,
     tx_flows: Arc<TxFlows>,
+    activity: Arc<Activity>,
+    node_no: NodeNo,
+    group_name: String,
 }
 
 impl remote::RemoteHandle for RemoteHandle {
@@ -837,7 +1624,10 @@ impl remote::RemoteHandle for RemoteHandle {
             Acquire::Done => {
                 let mut item = Some(KanalItem::simple(recipient, envelope));
                 match self.tx.try_send_option(&mut item) {
-                    Ok(true) => remote::SendResult::Ok,
+                    Ok(true) => {
+                        self.activity.touch();
+                        remote::SendResult::Ok
+                    }
                     Ok(false) => unreachable!(),
                     Err(_) => {
                         remote::SendResult::Err(SendError(item.take().unwrap().envelope.unwrap()))
@@ -856,7 +1646,10 @@ impl remote::RemoteHandle for RemoteHandle {
             TryAcquire::Done => {
                 let mut item = Some(KanalItem::simple(recipient, envelope));
                 match self.tx.try_send_option(&mut item) {
-                    Ok(true) => Ok(()),
+                    Ok(true) => {
+                        self.activity.touch();
+                        Ok(())
+                    }
                     Ok(false) => unreachable!(),
                     Err(_) => Err(TrySendError::Closed(item.take().unwrap().envelope.unwrap())),
                 }
@@ -876,7 +1669,10 @@ impl remote::RemoteHandle for RemoteHandle {
         if likely(self.tx_flows.do_acquire(recipient)) {
             let mut item = Some(KanalItem::simple(recipient, envelope));
             match self.tx.try_send_option(&mut item) {
-                Ok(true) => Ok(()),
+                Ok(true) => {
+                    self.activity.touch();
+                    Ok(())
+                }
                 Ok(false) => unreachable!(),
                 Err(_) => Err(SendError(item.take().unwrap().envelope.unwrap())),
             }
@@ -885,25 +1681,38 @@ impl remote::RemoteHandle for RemoteHandle {
         }
     }
 
-    fn respond(&self, token: ResponseToken, envelope: Result<Envelope, RequestError>) {
+    fn respond(&self, token: ResponseToken, envelope: Result<Envelope, ErrorKind>) {
         debug_assert!(!token.is_forgotten());
         debug_assert!(token.sender().is_remote());
 
         let recipient = NetworkAddr::from_remote(token.sender());
 
-        if likely(self.tx_flows.do_acquire(recipient)) {
-            let item = KanalItem {
-                recipient,
-                envelope,
-                token: Some(token),
-            };
-            match self.tx.try_send(item) {
-                Ok(true) => return,
-                Ok(false) => unreachable!(),
-                Err(_) => {}
+        // Responses aren't limited by flows, so they're sent even if the flow is
+        // missing, e.g. if the request has been forwarded by another connection.
+        self.tx_flows.do_acquire(recipient);
+
+        let mut item = Some(KanalItem {
+            recipient,
+            envelope,
+            token: Some(token),
+            is_system: false,
+        });
+
+        match self.tx.try_send_option(&mut item) {
+            Ok(true) => self.activity.touch(),
+            Ok(false) => unreachable!(),
+            Err(_) => {
+                // Otherwise, the dropped token responds again to this handle.
+                if let Some(token) = item.and_then(|item| item.token) {
+                    token.forget();
+                }
+
+                trace!(addr = %recipient, "connection is closed, response is lost");
             }
         }
+    }
 
-        trace!(addr = %recipient, "flow is closed, response is lost");
+    fn remote_group(&self) -> (NodeNo, &str) {
+        (self.node_no, &self.group_name)
     }
 }
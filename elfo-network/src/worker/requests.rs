@@ -1,8 +1,40 @@
+use std::sync::{Arc, Weak};
+
 use fxhash::FxHashMap;
 use metrics::{decrement_gauge, increment_gauge};
+use parking_lot::Mutex;
 use tracing::error;
 
-use elfo_core::{Addr, RequestId, ResponseToken};
+use elfo_core::{addr::NodeNo, errors::ErrorKind, Addr, RequestId, ResponseToken};
+
+/// Outgoing requests are shared by all workers connected to the same node.
+///
+/// A forwarded request is sent by the connection of the forwarder's group,
+/// but the response comes back by the connection of the requester's group.
+///
+/// Once all workers of the node are stopped, unresolved requests are failed
+/// with `ErrorKind::NetworkDisconnected`.
+#[derive(Default)]
+pub(crate) struct OutgoingRequestsRegistry {
+    nodes: Mutex<FxHashMap<NodeNo, Weak<Mutex<OutgoingRequests>>>>,
+}
+
+impl OutgoingRequestsRegistry {
+    /// Returns requests of the specified node, creating them if needed.
+    /// Requests are dropped once all workers of the node are stopped.
+    pub(super) fn get(&self, node_no: NodeNo) -> Arc<Mutex<OutgoingRequests>> {
+        let mut nodes = self.nodes.lock();
+        nodes.retain(|_, requests| requests.strong_count() > 0);
+
+        if let Some(requests) = nodes.get(&node_no).and_then(Weak::upgrade) {
+            return requests;
+        }
+
+        let requests = Arc::new(Mutex::new(OutgoingRequests::default()));
+        nodes.insert(node_no, Arc::downgrade(&requests));
+        requests
+    }
+}
 
 #[derive(Default)]
 pub(super) struct OutgoingRequests {
@@ -61,5 +93,9 @@ impl Drop for OutgoingRequests {
         if count > 0 {
             decrement_gauge!("elfo_network_outgoing_requests", count as f64);
         }
+
+        for (_, token) in self.map.drain() {
+            token.fail(ErrorKind::NetworkDisconnected);
+        }
     }
 }
@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+
+use fxhash::FxHashMap;
+use metrics::{counter, gauge, histogram};
+
+use elfo_core::Addr;
+use elfo_utils::time::Instant;
+
+use super::KanalItem;
+use crate::codec::format::NetworkAddr;
+
+/// How many bytes a destination can send per round before others.
+const QUANTUM: i64 = 16 * 1024;
+
+/// Outgoing envelopes of the link split by destinations.
+///
+/// Every destination (see [`Destination`]) has its own FIFO sub-queue, so
+/// the order of envelopes between the same sender and recipient is preserved.
+/// Sub-queues are drained by
+/// deficit round-robin by encoded bytes, thus a burst to one destination
+/// doesn't delay envelopes to other ones for longer than one round.
+///
+/// Internal messages (flow updates, acks, pings) bypass sub-queues and are
+/// sent first. The depth of sub-queues of regular messages is bounded by
+/// windows of corresponding flows, so senders to a stalled destination
+/// are blocked without affecting other destinations.
+#[derive(Default)]
+pub(super) struct TxQueues {
+    system: VecDeque<KanalItem>,
+    queues: FxHashMap<Destination, SubQueue>,
+    /// Non-empty sub-queues, the front one is being drained.
+    active: VecDeque<Destination>,
+    stats: [ClassStats; 3],
+}
+
+/// A key of the sub-queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Destination {
+    /// Direct messages and responses to the remote actor.
+    Actor(NetworkAddr),
+    /// Routed messages sent by the local actor to the remote group.
+    ///
+    /// The recipient is chosen by the router on the remote node, so routed
+    /// messages are split by senders. It's the finest split preserving the
+    /// order, and a flood of one sender doesn't delay others.
+    Routed(Addr),
+}
+
+impl Destination {
+    fn of(item: &KanalItem) -> Self {
+        if item.recipient != NetworkAddr::NULL {
+            return Self::Actor(item.recipient);
+        }
+
+        let sender = item.envelope.as_ref().map_or(Addr::NULL, |e| e.sender());
+        Self::Routed(sender)
+    }
+}
+
+struct SubQueue {
+    items: VecDeque<KanalItem>,
+    /// Can be negative, because envelopes are charged after encoding.
+    deficit: i64,
+    /// Since when the sub-queue is non-empty.
+    waiting_since: Instant,
+}
+
+#[derive(Default)]
+struct ClassStats {
+    depth: usize,
+    sent_bytes: u64,
+}
+
+/// Sub-queues are reported by classes to keep cardinality of metrics low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    System,
+    Group,
+    Actor,
+}
+
+impl Class {
+    fn of(destination: Option<Destination>) -> Self {
+        match destination {
+            None => Self::System,
+            Some(Destination::Routed(_)) => Self::Group,
+            Some(Destination::Actor(_)) => Self::Actor,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Group => "group",
+            Self::Actor => "actor",
+        }
+    }
+}
+
+impl TxQueues {
+    pub(super) fn len(&self) -> usize {
+        self.stats.iter().map(|stats| stats.depth).sum()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(super) fn push(&mut self, item: KanalItem) {
+        if item.is_system {
+            self.stats[Class::System as usize].depth += 1;
+            self.system.push_back(item);
+            return;
+        }
+
+        let destination = Destination::of(&item);
+        self.stats[Class::of(Some(destination)) as usize].depth += 1;
+
+        let queue = self.queues.entry(destination).or_insert_with(|| SubQueue {
+            items: VecDeque::new(),
+            deficit: QUANTUM,
+            waiting_since: Instant::now(),
+        });
+
+        if queue.items.is_empty() {
+            self.active.push_back(destination);
+        }
+
+        queue.items.push_back(item);
+    }
+
+    /// Returns the next envelope to send and its destination,
+    /// `None` for internal messages.
+    pub(super) fn pop(&mut self) -> Option<(Option<Destination>, KanalItem)> {
+        if let Some(item) = self.system.pop_front() {
+            self.stats[Class::System as usize].depth -= 1;
+            return Some((None, item));
+        }
+
+        loop {
+            let destination = *self.active.front()?;
+            let queue = self
+                .queues
+                .get_mut(&destination)
+                .expect("active queue must exist");
+
+            // The round of the destination is over.
+            if queue.deficit <= 0 {
+                queue.deficit += QUANTUM;
+                self.active.rotate_left(1);
+                continue;
+            }
+
+            let item = queue
+                .items
+                .pop_front()
+                .expect("active queue must be non-empty");
+            let class = Class::of(Some(destination));
+            self.stats[class as usize].depth -= 1;
+
+            // Empty sub-queues are removed, so they don't accumulate deficits.
+            if queue.items.is_empty() {
+                let blocked_time = Instant::now().secs_f64_since(queue.waiting_since);
+                histogram!("elfo_network_tx_queue_blocked_seconds", blocked_time, "destination" => class.as_str());
+                self.queues.remove(&destination);
+                self.active.pop_front();
+            }
+
+            return Some((Some(destination), item));
+        }
+    }
+
+    /// Charges the destination for the encoded envelope.
+    pub(super) fn charge(&mut self, destination: Option<Destination>, size: usize) {
+        self.stats[Class::of(destination) as usize].sent_bytes += size as u64;
+
+        if let Some(queue) = destination.and_then(|d| self.queues.get_mut(&d)) {
+            queue.deficit -= size as i64;
+        }
+    }
+
+    /// Emits metrics accumulated since the previous call.
+    pub(super) fn report(&mut self) {
+        for class in [Class::System, Class::Group, Class::Actor] {
+            let stats = &mut self.stats[class as usize];
+            let destination = class.as_str();
+
+            gauge!("elfo_network_tx_queue_depth", stats.depth as f64, "destination" => destination);
+            if stats.sent_bytes > 0 {
+                let sent_bytes = std::mem::take(&mut stats.sent_bytes);
+                counter!("elfo_network_tx_queue_sent_bytes_total", sent_bytes, "destination" => destination);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elfo_core::{_priv::MessageKind, tracing::TraceId, Addr, Envelope};
+
+    use super::*;
+    use crate::protocol::internode::Ping;
+
+    fn item(recipient: NetworkAddr, no: u64) -> KanalItem {
+        routed_item(Addr::NULL, no, recipient)
+    }
+
+    fn routed_item(sender: Addr, no: u64, recipient: NetworkAddr) -> KanalItem {
+        let kind = MessageKind::regular(sender);
+        let trace_id = TraceId::try_from(1).unwrap();
+        let envelope = Envelope::with_trace_id(Ping { payload: no }, kind, trace_id);
+        KanalItem::simple(recipient, envelope)
+    }
+
+    fn no(item: &KanalItem) -> u64 {
+        let envelope = item.envelope.as_ref().unwrap();
+        envelope.message().downcast_ref::<Ping>().unwrap().payload
+    }
+
+    fn addr(no: u64) -> NetworkAddr {
+        // node_no = 1, group_no = 1.
+        NetworkAddr::from_bits(1 << 48 | 1 << 40 | no).unwrap()
+    }
+
+    /// Drains queues charging every envelope by `size(no)`.
+    fn drain(queues: &mut TxQueues, size: impl Fn(u64) -> usize) -> Vec<u64> {
+        let mut order = Vec::new();
+        while let Some((addr, item)) = queues.pop() {
+            queues.charge(addr, size(no(&item)));
+            order.push(no(&item));
+        }
+        assert!(queues.is_empty());
+        order
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut queues = TxQueues::default();
+        let (a, b) = (addr(1), addr(2));
+
+        for no in [10, 11, 12] {
+            queues.push(item(a, no));
+        }
+        for no in [20, 21] {
+            queues.push(item(NetworkAddr::NULL, no));
+        }
+        queues.push(item(b, 30));
+        assert_eq!(queues.len(), 6);
+
+        // FIFO within every destination.
+        let order = drain(&mut queues, |_| QUANTUM as usize);
+        assert_eq!(order, [10, 20, 30, 11, 21, 12]);
+    }
+
+    #[test]
+    fn deficit_by_bytes() {
+        let mut queues = TxQueues::default();
+        let (small, large) = (addr(1), addr(2));
+
+        for no in 0..8 {
+            queues.push(item(small, no));
+        }
+        for no in 100..102 {
+            queues.push(item(large, no));
+        }
+
+        // Four small envelopes are sent per one large envelope.
+        let size = |no| {
+            if no < 100 {
+                QUANTUM as usize / 4
+            } else {
+                QUANTUM as usize
+            }
+        };
+        let order = drain(&mut queues, size);
+        assert_eq!(order, [0, 1, 2, 3, 100, 4, 5, 6, 7, 101]);
+    }
+
+    #[test]
+    fn routed_by_senders() {
+        let mut queues = TxQueues::default();
+        // Local actors: node_no = 0, group_no = 1.
+        let sender = |no: u64| Addr::from_bits(1 << 40 | no).unwrap();
+        let (flooder, other) = (sender(1), sender(2));
+
+        for no in 0..4 {
+            queues.push(routed_item(flooder, no, NetworkAddr::NULL));
+        }
+        queues.push(routed_item(other, 10, NetworkAddr::NULL));
+        queues.push(routed_item(other, 11, NetworkAddr::NULL));
+
+        // The other sender isn't delayed by the flood, FIFO within senders.
+        let order = drain(&mut queues, |_| QUANTUM as usize);
+        assert_eq!(order, [0, 10, 1, 11, 2, 3]);
+    }
+
+    #[test]
+    fn system_first() {
+        let mut queues = TxQueues::default();
+
+        queues.push(item(addr(1), 1));
+        queues.push(item(NetworkAddr::NULL, 2));
+        let mut system = item(NetworkAddr::NULL, 3);
+        system.is_system = true;
+        queues.push(system);
+
+        assert_eq!(drain(&mut queues, |_| 1), [3, 1, 2]);
+    }
+}
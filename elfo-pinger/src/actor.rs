@@ -29,7 +29,7 @@ pub(crate) async fn exec(mut ctx: Context<Config>, topology: Topology) {
     let mut timed_out = 0;
     let mut pinging = None;
 
-    interval.start(ctx.config().ping_interval / group_count);
+    interval.start(*ctx.config().ping_interval / group_count);
 
     // Accept envelopes from the mailbox concurrently with pinging
     // in order to avoid getting stuck with the configurer.
@@ -38,7 +38,7 @@ pub(crate) async fn exec(mut ctx: Context<Config>, topology: Topology) {
         select! {
             envelope = ctx.recv() => {
                 let envelope = ward!(envelope, break);
-                interval.set_period(ctx.config().ping_interval / group_count);
+                interval.set_period(*ctx.config().ping_interval / group_count);
 
                 if !envelope.is::<PingTick>() || pinging.is_some() {
                     continue;
@@ -55,7 +55,7 @@ pub(crate) async fn exec(mut ctx: Context<Config>, topology: Topology) {
                 }
 
                 let group = groups.pop().unwrap();
-                let warn_threshold = ctx.config().warn_threshold;
+                let warn_threshold = *ctx.config().warn_threshold;
 
                 // Expose a current scope to preserve an original trace id.
                 let fut = scope::expose().within(ping_group(ctx.pruned(), group, warn_threshold));
@@ -4,10 +4,10 @@
 //! and are not subject to stable guarantees. However, the config
 //! structure (usually encoded in TOML) follows stable guarantees.
 
-use std::time::Duration;
-
 use serde::Deserialize;
 
+use elfo_core::config::Duration;
+
 /// The pinger's config.
 ///
 /// # Example
@@ -20,12 +20,12 @@ pub struct Config {
     /// How often pingers should ping all other actors.
     ///
     /// `10s` by default.
-    #[serde(with = "humantime_serde", default = "default_ping_interval")]
+    #[serde(default = "default_ping_interval")]
     pub ping_interval: Duration,
     /// How long to wait for a response before logging a warning.
     ///
     /// `5s` by default.
-    #[serde(with = "humantime_serde", default = "default_warn_threshold")]
+    #[serde(default = "default_warn_threshold")]
     pub warn_threshold: Duration,
 }
 
@@ -1,27 +1,66 @@
-use std::{sync::Arc, time::Duration};
+use std::{fmt, sync::Arc, time::Duration};
 
+use tokio::time::Instant;
 use tracing::{error, info};
 
 use elfo_core::{
-    message, messages::ConfigUpdated, msg, stream::Stream, time::Interval, ActorGroup, Blueprint,
-    Context, RestartParams, RestartPolicy, SourceHandle,
+    message,
+    messages::{ConfigUpdated, Ping, Terminate, UpdateConfig, ValidateConfig},
+    msg,
+    routers::{MapRouter, Outcome},
+    stream::Stream,
+    time::Interval,
+    ActorGroup, Blueprint, Context, RestartParams, RestartPolicy, SourceHandle,
 };
 
 use crate::{
     config::{Config, Retention, Sink},
     hyper,
-    protocol::{GetSnapshot, Render, Rendered, ServerFailed, Snapshot},
+    protocol::{
+        GetSnapshot, GetThroughputHistory, GetTopTraces, Render, Rendered, ServerFailed, Snapshot,
+        TopTraces,
+    },
     render::Renderer,
     storage::Storage,
+    throughput::Throughput,
 };
 
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ActorKey {
+    Main,
+    #[cfg(feature = "tokio-metrics")]
+    Runtime,
+}
+
+impl ActorKey {
+    fn all() -> Vec<Self> {
+        vec![
+            Self::Main,
+            #[cfg(feature = "tokio-metrics")]
+            Self::Runtime,
+        ]
+    }
+}
+
+impl fmt::Display for ActorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // The same as for singletons, it's the only actor in most cases.
+            Self::Main => f.write_str("_"),
+            #[cfg(feature = "tokio-metrics")]
+            Self::Runtime => f.write_str("runtime"),
+        }
+    }
+}
+
 struct Telemeter {
-    ctx: Context<Config>,
+    ctx: Context<Config, ActorKey>,
     interval: Interval<CompactionTick>,
     server: Option<Stream<ServerFailed>>,
     storage: Arc<Storage>,
     snapshot: Arc<Snapshot>,
     renderer: Renderer,
+    throughput: Throughput,
 }
 
 #[message]
@@ -35,11 +74,28 @@ pub(crate) fn new(storage: Arc<Storage>) -> Blueprint {
             Duration::from_secs(30),
         )))
         .stop_order(100)
-        .exec(move |ctx| Telemeter::new(ctx, storage.clone()).main())
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                // The runtime sampler is started and configured along with the main actor.
+                UpdateConfig => Outcome::Multicast(ActorKey::all()),
+                Terminate | Ping | ValidateConfig => Outcome::Default,
+                _ => Outcome::Unicast(ActorKey::Main),
+            })
+        }))
+        .exec(move |ctx: Context<Config, ActorKey>| {
+            let storage = storage.clone();
+            async move {
+                match ctx.key() {
+                    ActorKey::Main => Telemeter::new(ctx, storage).main().await,
+                    #[cfg(feature = "tokio-metrics")]
+                    ActorKey::Runtime => crate::runtime::RuntimeSampler::new(ctx).main().await,
+                }
+            }
+        })
 }
 
 impl Telemeter {
-    pub(crate) fn new(mut ctx: Context<Config>, storage: Arc<Storage>) -> Self {
+    pub(crate) fn new(mut ctx: Context<Config, ActorKey>, storage: Arc<Storage>) -> Self {
         let mut renderer = Renderer::default();
         renderer.configure(ctx.config());
 
@@ -49,6 +105,7 @@ impl Telemeter {
             storage,
             snapshot: Default::default(),
             renderer,
+            throughput: Throughput::new(&ctx.config().throughput),
             ctx,
         }
     }
@@ -57,10 +114,15 @@ impl Telemeter {
         // Now only OpenMetrics is supported.
         assert_eq!(self.ctx.config().sink, Sink::OpenMetrics);
 
+        #[cfg(not(feature = "tokio-metrics"))]
+        if self.ctx.config().runtime.enabled {
+            tracing::warn!("runtime metrics require the `tokio-metrics` feature");
+        }
+
         let mut listen = self.ctx.config().listen;
         self.start_server();
 
-        self.interval.start(self.ctx.config().compaction_interval);
+        self.interval.start(*self.ctx.config().compaction_interval);
 
         while let Some(envelope) = self.ctx.recv().await {
             msg!(match envelope {
@@ -68,6 +130,7 @@ impl Telemeter {
                     let config = self.ctx.config();
 
                     self.renderer.configure(config);
+                    self.throughput.configure(&config.throughput);
 
                     if config.listen != listen {
                         info!(
@@ -81,14 +144,14 @@ impl Telemeter {
                 }
                 (GetSnapshot, token) => {
                     // Rendering includes compaction, skip extra compaction tick.
-                    self.interval.start(self.ctx.config().compaction_interval);
+                    self.interval.start(*self.ctx.config().compaction_interval);
 
                     self.update_snapshot(/* only_compact = */ false).await;
                     self.ctx.respond(token, self.snapshot.clone().into());
                 }
                 (Render, token) => {
                     // Rendering includes compaction, skip extra compaction tick.
-                    self.interval.start(self.ctx.config().compaction_interval);
+                    self.interval.start(*self.ctx.config().compaction_interval);
 
                     self.update_snapshot(/* only_compact = */ false).await;
                     let descriptions = self.storage.descriptions();
@@ -101,8 +164,23 @@ impl Telemeter {
                         self.reset_distributions();
                     }
                 }
+                (GetThroughputHistory { group, window }, token) => {
+                    // Include samples since the last compaction.
+                    self.update_snapshot(/* only_compact = */ true).await;
+
+                    let history = self.throughput.history(&group, window, Instant::now());
+                    self.ctx.respond(token, history);
+                }
+                (GetTopTraces { limit }, token) => {
+                    let traces = elfo_core::tracing::top_traces(limit);
+                    self.ctx.respond(token, TopTraces { traces });
+                }
                 CompactionTick => {
                     self.update_snapshot(/* only_compact = */ true).await;
+
+                    if self.ctx.config().throughput.dump {
+                        self.throughput.dump(Instant::now());
+                    }
                 }
                 ServerFailed(err) => {
                     error!(error = %err, "server failed");
@@ -119,6 +197,10 @@ impl Telemeter {
         // Run the preemtive merge process.
         self.storage.merge(snapshot, only_compact).await;
 
+        if self.ctx.config().throughput.enabled {
+            self.throughput.observe(snapshot, Instant::now());
+        }
+
         if !only_compact {
             snapshot.emit_stats();
         }
@@ -4,10 +4,12 @@
 //! and are not subject to stable guarantees. However, the config
 //! structure (usually encoded in TOML) follows stable guarantees.
 
-use std::{net::SocketAddr, ops::Deref, time::Duration};
+use std::{net::SocketAddr, ops::Deref};
 
 use serde::Deserialize;
 
+use elfo_core::config::Duration;
+
 /// Telemeter configuration.
 ///
 /// # Example
@@ -37,8 +39,101 @@ pub struct Config {
     /// The maximum time between compaction ticks.
     ///
     /// `1.1s` by default.
-    #[serde(with = "humantime_serde", default = "default_compaction_interval")]
+    #[serde(default = "default_compaction_interval")]
     pub compaction_interval: Duration,
+    /// Sampling of tokio runtime metrics.
+    #[serde(default)]
+    pub runtime: RuntimeMetrics,
+    /// Recording of per-message throughput history.
+    #[serde(default)]
+    pub throughput: ThroughputConfig,
+}
+
+/// Sampling of tokio runtime metrics (worker busy ratios, queue depths and so
+/// on) and elfo aggregates (the number of actors, the total mailbox
+/// occupancy, the number of scheduled timers).
+///
+/// Requires the `tokio-metrics` feature, otherwise, the section is ignored.
+///
+/// # Example
+/// ```toml
+/// [system.telemeters]
+/// runtime.enabled = true
+/// runtime.sampling_interval = "5s"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RuntimeMetrics {
+    /// Whether to sample metrics of the runtime.
+    ///
+    /// `false` by default.
+    pub enabled: bool,
+    /// How often metrics are sampled.
+    ///
+    /// `1s` by default.
+    pub sampling_interval: Duration,
+}
+
+impl Default for RuntimeMetrics {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sampling_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Recording of the number of handled messages per group and message type in
+/// fixed-width buckets, available by [`GetThroughputHistory`] requests.
+///
+/// Counts are taken from the `elfo_message_handling_time_seconds` metric,
+/// so per-group telemetry must be enabled (`system.telemetry.per_actor_group`).
+///
+/// # Example
+/// ```toml
+/// [system.telemeters]
+/// throughput.enabled = true
+/// throughput.bucket = "1m"
+/// throughput.buckets = 60
+/// ```
+///
+/// [`GetThroughputHistory`]: crate::protocol::GetThroughputHistory
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ThroughputConfig {
+    /// Whether to record the history.
+    ///
+    /// `false` by default.
+    pub enabled: bool,
+    /// The width of a bucket.
+    ///
+    /// `1m` by default.
+    pub bucket: Duration,
+    /// The number of the latest buckets to keep.
+    ///
+    /// `60` by default.
+    pub buckets: usize,
+    /// The maximum number of tracked (group, message) pairs. The least
+    /// recently updated ones are evicted.
+    ///
+    /// `1024` by default.
+    pub max_series: usize,
+    /// Whether to dump counts of completed buckets to the `system` class.
+    ///
+    /// `false` by default.
+    pub dump: bool,
+}
+
+impl Default for ThroughputConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bucket: Duration::from_secs(60),
+            buckets: 60,
+            max_series: 1024,
+            dump: false,
+        }
+    }
 }
 
 /// Sink for the telemeter output.
@@ -50,21 +145,16 @@ pub enum Sink {
 }
 
 /// Histogram/summary retention policy.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Default, PartialEq, Deserialize)]
 pub enum Retention {
     /// Keep all samples forever.
     Forever,
     /// Reset all samples on each scrape.
+    #[default]
     ResetOnScrape,
     // TODO: `SlidingWindow`
 }
 
-impl Default for Retention {
-    fn default() -> Self {
-        Self::ResetOnScrape
-    }
-}
-
 /// A quantile to use for aggregating distribution metrics into a summary
 /// with the `quantile` label. Must be in the range [0.0, 1.0].
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -10,6 +10,12 @@
 //! label is added, but it's possible to provide `actor_key` on a group basis.
 //! It's useful, if a group has few actors inside.
 //!
+//! With the `tokio-metrics` feature, the telemeter can also sample metrics of
+//! the tokio runtime, see [`RuntimeMetrics`](config::RuntimeMetrics).
+//!
+//! The telemeter can also keep a short history of per-message throughput for
+//! capacity planning, see [`ThroughputConfig`](config::ThroughputConfig).
+//!
 //! [Configuration]: config::Config
 
 use std::sync::Arc;
@@ -30,9 +36,12 @@ mod recorder;
 mod render;
 mod stats;
 mod storage;
+mod throughput;
 
 #[cfg(feature = "unstable")]
 mod allocator;
+#[cfg(feature = "tokio-metrics")]
+mod runtime;
 
 #[cfg(feature = "unstable")]
 pub use allocator::AllocatorStats;
@@ -1,13 +1,14 @@
 //! Contains the protocol to interact with the telemeter.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use fxhash::FxHashMap;
 use metrics::{Key, Unit};
+use serde::{Deserialize, Serialize};
 use sketches_ddsketch::{Config as DDSketchConfig, DDSketch};
 use tracing::warn;
 
-use elfo_core::{message, ActorMeta, Local};
+use elfo_core::{message, tracing::TraceFanOut, ActorMeta, Local};
 
 use crate::stats::SnapshotStats;
 
@@ -26,6 +27,92 @@ pub(crate) struct ServerFailed(pub(crate) String);
 #[non_exhaustive]
 pub(crate) struct GetSnapshot;
 
+/// A request for the number of messages handled by the group per bucket,
+/// see [`ThroughputConfig`] for details. Returns no series if the recording is
+/// disabled.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// use elfo_telemeter::protocol::GetThroughputHistory;
+///
+/// let request = GetThroughputHistory::new("producers", Duration::from_secs(3600));
+/// ```
+///
+/// [`ThroughputConfig`]: crate::config::ThroughputConfig
+#[message(ret = ThroughputHistory)]
+#[non_exhaustive]
+pub struct GetThroughputHistory {
+    /// The group to get the history of.
+    pub group: String,
+    /// How far back to look, limited by the number of stored buckets.
+    pub window: Duration,
+}
+
+impl GetThroughputHistory {
+    /// Creates a request for the group's history within the window.
+    pub fn new(group: impl Into<String>, window: Duration) -> Self {
+        Self {
+            group: group.into(),
+            window,
+        }
+    }
+}
+
+/// The response to [`GetThroughputHistory`].
+#[message]
+#[non_exhaustive]
+pub struct ThroughputHistory {
+    /// The width of a bucket.
+    pub bucket: Duration,
+    /// Series sorted by the protocol and the message name.
+    pub series: Vec<ThroughputSeries>,
+}
+
+/// Counts of handled messages of the same type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ThroughputSeries {
+    /// The protocol of the message.
+    pub protocol: String,
+    /// The name of the message.
+    pub message: String,
+    /// Counts per bucket, oldest first. The last bucket is in progress.
+    pub counts: Vec<u64>,
+}
+
+/// A request for traces with the most messages sent within them on the node,
+/// useful for live debugging of amplification storms. Only traces sent by
+/// groups with configured `system.tracing.fan_out` limits are counted.
+///
+/// # Example
+/// ```
+/// use elfo_telemeter::protocol::GetTopTraces;
+///
+/// let request = GetTopTraces::new(10);
+/// ```
+#[message(ret = TopTraces)]
+#[non_exhaustive]
+pub struct GetTopTraces {
+    /// The maximum number of returned traces.
+    pub limit: usize,
+}
+
+impl GetTopTraces {
+    /// Creates a request for at most `limit` traces.
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+/// The response to [`GetTopTraces`].
+#[message]
+#[non_exhaustive]
+pub struct TopTraces {
+    /// Traces sorted by the number of messages, the largest first.
+    pub traces: Vec<TraceFanOut>,
+}
+
 pub(crate) type GaugeEpoch = u64;
 
 pub(crate) struct Description {
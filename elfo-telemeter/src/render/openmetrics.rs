@@ -10,6 +10,8 @@ use cow_utils::CowUtils;
 use fxhash::FxHashSet;
 use metrics::{Key, Label};
 
+use elfo_core::KeyEncoding;
+
 use super::RenderOptions;
 use crate::protocol::{Description, Distribution, Metrics, Snapshot};
 
@@ -59,7 +61,7 @@ fn render(
                 .map(|g| Label::new("actor_group", g.to_string()));
             let actor_key_label = meta
                 .actor_key
-                .map(|k| Label::new("actor_key", k.to_string()));
+                .map(|k| Label::new("actor_key", KeyEncoding::Label.encode(k).into_owned()));
 
             let labels = options
                 .global_labels
@@ -273,7 +275,7 @@ fn sanitize_label_key(key: &str) -> Cow<'_, str> {
 }
 
 fn sanitize_label_value(value: &str) -> Cow<'_, str> {
-    if value.contains(['\\', '"', '\n']) {
+    if !value.contains(['\\', '"', '\n']) {
         value.into()
     } else {
         value
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use metrics::{counter, gauge, register_counter, register_gauge, Unit};
+#[cfg(tokio_unstable)]
+use metrics::{histogram, register_histogram};
+use tokio::runtime::{Handle, RuntimeMetrics};
+
+use elfo_core::{message, messages::ConfigUpdated, msg, time::Interval, Context};
+
+use crate::{actor::ActorKey, config::Config};
+
+#[message]
+struct SampleTick;
+
+/// Periodically samples metrics of the tokio runtime the telemeter runs on.
+pub(crate) struct RuntimeSampler {
+    ctx: Context<Config, ActorKey>,
+    interval: Interval<SampleTick>,
+    prev: Option<Sample>,
+}
+
+/// Cumulative values of the previous sample, used to calculate deltas.
+struct Sample {
+    time: Instant,
+    busy: Vec<Duration>,
+    parks: Vec<u64>,
+}
+
+impl RuntimeSampler {
+    pub(crate) fn new(mut ctx: Context<Config, ActorKey>) -> Self {
+        Self {
+            interval: ctx.attach(Interval::new(SampleTick)),
+            prev: None,
+            ctx,
+        }
+    }
+
+    pub(crate) async fn main(mut self) {
+        register();
+        self.configure();
+
+        while let Some(envelope) = self.ctx.recv().await {
+            msg!(match envelope {
+                ConfigUpdated => self.configure(),
+                SampleTick => self.sample(),
+            });
+        }
+    }
+
+    fn configure(&mut self) {
+        let config = &self.ctx.config().runtime;
+
+        if config.enabled {
+            self.interval.start(*config.sampling_interval);
+        } else {
+            // Nothing is sampled until the sampling is enabled again.
+            self.interval.stop();
+            self.prev = None;
+        }
+    }
+
+    fn sample(&mut self) {
+        let metrics = Handle::current().metrics();
+        let book = self.ctx.book().stats();
+
+        gauge!("elfo_tokio_workers", metrics.num_workers() as f64);
+        gauge!("elfo_tokio_alive_tasks", metrics.num_alive_tasks() as f64);
+        gauge!(
+            "elfo_tokio_global_queue_depth",
+            metrics.global_queue_depth() as f64
+        );
+        gauge!("elfo_actors", book.actors as f64);
+        gauge!("elfo_mailbox_occupancy", book.mailbox_len as f64);
+        gauge!(
+            "elfo_scheduled_timers",
+            elfo_core::time::scheduled_timers() as f64
+        );
+
+        let sample = Sample::new(&metrics);
+
+        // The number of workers is fixed, but check it anyway.
+        if let Some(prev) = self
+            .prev
+            .take()
+            .filter(|p| p.busy.len() == sample.busy.len())
+        {
+            let elapsed = sample.time.duration_since(prev.time).as_secs_f64();
+
+            for worker in 0..sample.busy.len() {
+                let label = worker.to_string();
+                let busy = sample.busy[worker].saturating_sub(prev.busy[worker]);
+                let parks = sample.parks[worker].saturating_sub(prev.parks[worker]);
+
+                if elapsed > 0. {
+                    let ratio = (busy.as_secs_f64() / elapsed).min(1.);
+                    gauge!("elfo_tokio_worker_busy_ratio", ratio, "worker" => label.clone());
+                }
+
+                counter!("elfo_tokio_worker_parks_total", parks, "worker" => label.clone());
+
+                #[cfg(tokio_unstable)]
+                {
+                    let depth = metrics.worker_local_queue_depth(worker) as f64;
+                    gauge!("elfo_tokio_worker_local_queue_depth", depth, "worker" => label.clone());
+                    let poll_time = metrics.worker_mean_poll_time(worker).as_secs_f64();
+                    histogram!("elfo_tokio_worker_mean_poll_time_seconds", poll_time, "worker" => label);
+                }
+            }
+        }
+
+        self.prev = Some(sample);
+    }
+}
+
+impl Sample {
+    fn new(metrics: &RuntimeMetrics) -> Self {
+        let workers = 0..metrics.num_workers();
+
+        Self {
+            time: Instant::now(),
+            busy: workers
+                .clone()
+                .map(|w| metrics.worker_total_busy_duration(w))
+                .collect(),
+            parks: workers.map(|w| metrics.worker_park_count(w)).collect(),
+        }
+    }
+}
+
+fn register() {
+    register_gauge!(
+        "elfo_tokio_workers",
+        Unit::Count,
+        "The number of worker threads of the runtime"
+    );
+    register_gauge!(
+        "elfo_tokio_alive_tasks",
+        Unit::Count,
+        "The number of alive tasks in the runtime"
+    );
+    register_gauge!(
+        "elfo_tokio_global_queue_depth",
+        Unit::Count,
+        "The number of tasks in the global queue"
+    );
+    register_gauge!(
+        "elfo_tokio_worker_busy_ratio",
+        "The ratio of time the worker was busy since the previous sample"
+    );
+    register_counter!(
+        "elfo_tokio_worker_parks_total",
+        Unit::Count,
+        "The number of times the worker has parked"
+    );
+    #[cfg(tokio_unstable)]
+    register_gauge!(
+        "elfo_tokio_worker_local_queue_depth",
+        Unit::Count,
+        "The number of tasks in the local queue of the worker"
+    );
+    #[cfg(tokio_unstable)]
+    register_histogram!(
+        "elfo_tokio_worker_mean_poll_time_seconds",
+        Unit::Seconds,
+        "The mean duration of task polls"
+    );
+    register_gauge!("elfo_actors", Unit::Count, "The number of alive actors");
+    register_gauge!(
+        "elfo_mailbox_occupancy",
+        Unit::Count,
+        "The approximate number of messages in all mailboxes"
+    );
+    register_gauge!(
+        "elfo_scheduled_timers",
+        Unit::Count,
+        "The number of pending delays and started intervals"
+    );
+}
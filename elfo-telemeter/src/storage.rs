@@ -1,6 +1,13 @@
 #![allow(private_interfaces)]
 
-use std::{hash::Hash, mem, sync::Arc};
+use std::{
+    hash::Hash,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use fxhash::FxHashMap;
 use metrics::{Key, Unit};
@@ -27,7 +34,7 @@ pub(crate) trait ScopeKind: Sized {
 
     fn get_meta(scope: &Self::Scope) -> &Self::Meta;
     fn make_key(scope: &Self::Scope, key: &Key) -> Self::Key;
-    fn registries(shard: &Shard) -> &Registries<Self>;
+    fn registries(buffer: &Buffer) -> &Registries<Self>;
     fn gauge_shared(storage: &Storage) -> &Mutex<GaugeOrigins<Self>>;
     fn snapshot<'s>(snapshot: &'s mut Snapshot, meta: &Self::Meta) -> &'s mut Metrics;
 }
@@ -47,8 +54,8 @@ impl ScopeKind for GlobalScope {
         key.get_hash()
     }
 
-    fn registries(shard: &Shard) -> &Registries<Self> {
-        &shard.global
+    fn registries(buffer: &Buffer) -> &Registries<Self> {
+        &buffer.global
     }
 
     fn gauge_shared(storage: &Storage) -> &Mutex<GaugeOrigins<Self>> {
@@ -77,8 +84,8 @@ impl ScopeKind for GroupScope {
         (scope.group(), key.get_hash())
     }
 
-    fn registries(shard: &Shard) -> &Registries<Self> {
-        &shard.groupwise
+    fn registries(buffer: &Buffer) -> &Registries<Self> {
+        &buffer.groupwise
     }
 
     fn gauge_shared(storage: &Storage) -> &Mutex<GaugeOrigins<Self>> {
@@ -113,8 +120,8 @@ impl ScopeKind for ActorScope {
         (scope.group(), key_hash)
     }
 
-    fn registries(shard: &Shard) -> &Registries<Self> {
-        &shard.actorwise
+    fn registries(buffer: &Buffer) -> &Registries<Self> {
+        &buffer.actorwise
     }
 
     fn gauge_shared(storage: &Storage) -> &Mutex<GaugeOrigins<Self>> {
@@ -134,8 +141,16 @@ impl ScopeKind for ActorScope {
 /// them periodically by the telemeter actor. It *dramatically* reduces
 /// contention, especially if multiple actors use the same telemetry key,
 /// what's common for per-group telemetry or per-actor grouped telemetry.
+///
+/// Every shard has two buffers, writers use the one of the current epoch.
+/// A full merge switches the epoch and drains previous buffers, so the
+/// snapshot is consistent: if an update is included, all updates happened
+/// before it (in any thread) are included too. Otherwise, shards and kinds
+/// of metrics are drained at different moments, and, for instance, a counter
+/// of received messages can be ahead of the counter of sent ones.
 pub(crate) struct Storage {
     shards: ThreadLocal<Shard>,
+    epoch: AtomicUsize,
     // Shared gauge origins between shards. See `Gauge` for more details.
     gauge_shared: GaugeShared,
     descriptions: Mutex<FxHashMap<String, Description>>,
@@ -143,6 +158,17 @@ pub(crate) struct Storage {
 
 #[derive(Default)]
 struct Shard {
+    buffers: [Buffer; 2],
+}
+
+impl Shard {
+    fn buffer(&self, epoch: usize) -> &Buffer {
+        &self.buffers[epoch % 2]
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
     global: Registries<GlobalScope>,
     groupwise: Registries<GroupScope>,
     actorwise: Registries<ActorScope>,
@@ -199,6 +225,7 @@ impl Default for Storage {
     fn default() -> Self {
         Self {
             shards: ThreadLocal::new(),
+            epoch: AtomicUsize::new(0),
             gauge_shared: Default::default(),
             descriptions: Default::default(),
         }
@@ -225,9 +252,19 @@ impl Storage {
         M: Storable,
     {
         let shard = self.shards.get_or_default();
-        let registries = S::registries(shard);
         let reg_key = S::make_key(scope, key);
-        let mut registry = M::registry(registries).lock();
+
+        let mut registry = loop {
+            let epoch = self.epoch.load(Ordering::Relaxed);
+            let registry = M::registry(S::registries(shard.buffer(epoch))).lock();
+
+            // The epoch can be switched before locking and the buffer can be
+            // already drained, so recheck it. The lock is released by merging
+            // after switching, so the new epoch is visible here in this case.
+            if self.epoch.load(Ordering::Relaxed) == epoch {
+                break registry;
+            }
+        };
 
         let entry = registry.entry(reg_key).or_insert_with(|| {
             let shared = M::shared::<S>(self, reg_key);
@@ -237,21 +274,30 @@ impl Storage {
         entry.data.update(value);
     }
 
+    /// Merges updates into the snapshot.
+    ///
+    /// If `only_compact` is set, only histograms of the current epoch are
+    /// merged to limit memory usage. Otherwise, the epoch is switched and
+    /// all updates of the previous one are merged.
     pub(crate) async fn merge(&self, snapshot: &mut Snapshot, only_compact: bool) {
         let mut storage_stats = StorageStats::new::<Self>();
 
-        if !only_compact {
+        let epoch = if only_compact {
+            self.epoch.load(Ordering::Relaxed)
+        } else {
             storage_stats.add_descriptions(&*self.descriptions.lock());
-        }
+            self.epoch.fetch_add(1, Ordering::Relaxed)
+        };
 
         for shard in self.shards.iter() {
             let mut stats = ShardStats::new::<Shard>();
+            let buffer = shard.buffer(epoch);
 
-            self.merge_registries::<GlobalScope>(shard, snapshot, only_compact, &mut stats)
+            self.merge_registries::<GlobalScope>(buffer, snapshot, only_compact, &mut stats)
                 .await;
-            self.merge_registries::<GroupScope>(shard, snapshot, only_compact, &mut stats)
+            self.merge_registries::<GroupScope>(buffer, snapshot, only_compact, &mut stats)
                 .await;
-            self.merge_registries::<ActorScope>(shard, snapshot, only_compact, &mut stats)
+            self.merge_registries::<ActorScope>(buffer, snapshot, only_compact, &mut stats)
                 .await;
 
             storage_stats.add_shard(&stats);
@@ -264,12 +310,12 @@ impl Storage {
 
     async fn merge_registries<S: ScopeKind>(
         &self,
-        shard: &Shard,
+        buffer: &Buffer,
         snapshot: &mut Snapshot,
         only_compact: bool,
         stats: &mut ShardStats,
     ) {
-        let registries = S::registries(shard);
+        let registries = S::registries(buffer);
 
         if !only_compact {
             self.merge_registry::<S, Counter>(registries, snapshot, stats)
@@ -362,3 +408,84 @@ impl Storable for Histogram {
         metrics.histograms.entry(key.clone()).or_default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicBool, mpsc},
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn snapshots_are_consistent() {
+        let storage = Arc::new(Storage::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel::<()>();
+
+        let counter = |name: &'static str| Key::from_parts(name, Vec::new());
+        let (sent, received, requests) =
+            (counter("sent"), counter("received"), counter("requests"));
+        let latency = counter("latency");
+
+        // Increments `sent`, then `received` in another thread.
+        // Also `requests` is incremented before recording `latency`.
+        let sender = thread::spawn({
+            let (storage, stop) = (storage.clone(), stop.clone());
+            let (sent, requests, latency) = (sent.clone(), requests.clone(), latency.clone());
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    storage.upsert::<GlobalScope, Counter>(&(), &sent, 1);
+                    tx.send(()).unwrap();
+                    storage.upsert::<GlobalScope, Counter>(&(), &requests, 1);
+                    storage.upsert::<GlobalScope, Histogram>(&(), &latency, 1.);
+                }
+            }
+        });
+
+        let receiver = thread::spawn({
+            let (storage, received) = (storage.clone(), received.clone());
+            move || {
+                for () in rx {
+                    storage.upsert::<GlobalScope, Counter>(&(), &received, 1);
+                }
+            }
+        });
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let mut snapshot = Snapshot::default();
+        for i in 0..500 {
+            // Compaction merges histograms ahead of counters, but only updates
+            // happened before the next full merge, so it's still consistent.
+            rt.block_on(storage.merge(&mut snapshot, /* only_compact = */ true));
+            thread::yield_now();
+            rt.block_on(storage.merge(&mut snapshot, /* only_compact = */ false));
+            thread::yield_now();
+
+            let metrics = &snapshot.global;
+            let get = |key: &Key| metrics.counters.get(key).copied().unwrap_or_default();
+            assert!(get(&received) <= get(&sent), "iteration {i}");
+
+            let count = metrics
+                .histograms
+                .get(&latency)
+                .map_or(0, |d| d.cumulative_count() as u64);
+            assert!(count <= get(&requests), "iteration {i}");
+        }
+
+        stop.store(true, Ordering::Relaxed);
+        sender.join().unwrap();
+        receiver.join().unwrap();
+
+        // Everything is merged eventually.
+        rt.block_on(storage.merge(&mut snapshot, false));
+        let metrics = &snapshot.global;
+        assert!(metrics.counters[&sent] > 0);
+        assert_eq!(metrics.counters[&received], metrics.counters[&sent]);
+        assert_eq!(metrics.counters[&requests], metrics.counters[&sent]);
+    }
+}
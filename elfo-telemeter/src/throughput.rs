@@ -0,0 +1,362 @@
+//! Per-message throughput history, see `GetThroughputHistory`.
+//!
+//! Counts aren't collected on the hot path. Instead, they are calculated as
+//! deltas of cumulative counts of the `elfo_message_handling_time_seconds`
+//! histogram after each merge of the storage (on compaction and scraping).
+//! Thus, a sample can be attributed to a bucket with a delay up to the
+//! compaction interval.
+
+use std::time::Duration;
+
+use fxhash::FxHashMap;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use elfo_core::{
+    config,
+    dumping::{Dump, Dumper},
+};
+
+use crate::{
+    config::ThroughputConfig,
+    protocol::{Snapshot, ThroughputHistory, ThroughputSeries},
+};
+
+const METRIC_NAME: &str = "elfo_message_handling_time_seconds";
+
+pub(crate) struct Throughput {
+    bucket: Duration,
+    capacity: usize,
+    max_series: usize,
+    origin: Instant,
+    /// Cumulative counts at the latest observation, used to calculate deltas.
+    /// Not limited by `max_series`, because the snapshot stores the same keys.
+    totals: FxHashMap<u64, usize>,
+    series: FxHashMap<u64, Series>,
+    /// Used as a clock for LRU eviction.
+    touches: u64,
+    /// The first bucket that hasn't been dumped yet.
+    undumped: u64,
+    dumper: Dumper,
+}
+
+struct Series {
+    group: String,
+    protocol: String,
+    message: String,
+    /// A ring buffer indexed by `bucket_no % capacity`.
+    counts: Box<[u64]>,
+    /// The latest bucket having a count.
+    last: u64,
+    touched: u64,
+}
+
+impl Series {
+    fn add(&mut self, bucket_no: u64, count: u64) {
+        let capacity = self.counts.len() as u64;
+
+        // Clear buckets that are passed since the latest update.
+        for no in (self.last + 1..=bucket_no).take(self.counts.len()) {
+            self.counts[(no % capacity) as usize] = 0;
+        }
+
+        self.counts[(bucket_no % capacity) as usize] += count;
+        self.last = self.last.max(bucket_no);
+    }
+
+    fn get(&self, bucket_no: u64) -> u64 {
+        let capacity = self.counts.len() as u64;
+
+        if bucket_no > self.last || bucket_no + capacity <= self.last {
+            0
+        } else {
+            self.counts[(bucket_no % capacity) as usize]
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ThroughputBucket {
+    group: String,
+    protocol: String,
+    message: String,
+    bucket: config::Duration,
+    count: u64,
+}
+
+impl Throughput {
+    pub(crate) fn new(config: &ThroughputConfig) -> Self {
+        Self {
+            bucket: *config.bucket,
+            capacity: config.buckets,
+            max_series: config.max_series,
+            origin: Instant::now(),
+            totals: FxHashMap::default(),
+            series: FxHashMap::default(),
+            touches: 0,
+            undumped: 0,
+            dumper: Dumper::new("system"),
+        }
+    }
+
+    /// Applies a new config. The history is lost if the bucket's width or the
+    /// number of buckets is changed.
+    pub(crate) fn configure(&mut self, config: &ThroughputConfig) {
+        if *config.bucket != self.bucket || config.buckets != self.capacity {
+            *self = Self {
+                // Keep totals to avoid counting all samples again.
+                totals: std::mem::take(&mut self.totals),
+                ..Self::new(config)
+            };
+        }
+
+        self.max_series = config.max_series;
+        while self.series.len() > self.max_series {
+            self.evict();
+        }
+    }
+
+    fn bucket_no(&self, now: Instant) -> u64 {
+        let elapsed = now.duration_since(self.origin).as_nanos();
+        (elapsed / self.bucket.as_nanos().max(1)) as u64
+    }
+
+    /// Updates the history by the merged snapshot.
+    pub(crate) fn observe(&mut self, snapshot: &Snapshot, now: Instant) {
+        let bucket_no = self.bucket_no(now);
+
+        for (group, metrics) in &snapshot.groupwise {
+            for (key, distribution) in &metrics.histograms {
+                if key.name() != METRIC_NAME {
+                    continue;
+                }
+
+                let id = fxhash::hash64(&(group, key.get_hash()));
+                let total = distribution.cumulative_count();
+                let prev = self.totals.insert(id, total).unwrap_or_default();
+                let delta = total.saturating_sub(prev) as u64;
+
+                if delta == 0 {
+                    continue;
+                }
+
+                self.touches += 1;
+
+                if let Some(series) = self.series.get_mut(&id) {
+                    series.touched = self.touches;
+                    series.add(bucket_no, delta);
+                    continue;
+                }
+
+                let label = |name| {
+                    key.labels()
+                        .find(|label| label.key() == name)
+                        .map_or("", |label| label.value())
+                };
+
+                // Skip pseudo-messages like `<Startup>` or `<EmptyMailbox>`.
+                let message = label("message");
+                if message.starts_with('<') || self.max_series == 0 {
+                    continue;
+                }
+
+                if self.series.len() >= self.max_series {
+                    self.evict();
+                }
+
+                let mut series = Series {
+                    group: group.clone(),
+                    protocol: label("protocol").into(),
+                    message: message.into(),
+                    counts: vec![0; self.capacity.max(1)].into(),
+                    last: bucket_no,
+                    touched: self.touches,
+                };
+                series.add(bucket_no, delta);
+                self.series.insert(id, series);
+            }
+        }
+    }
+
+    /// Removes the least recently updated series.
+    fn evict(&mut self) {
+        let lru = self
+            .series
+            .iter()
+            .min_by_key(|(_, series)| series.touched)
+            .map(|(id, _)| *id);
+
+        if let Some(id) = lru {
+            self.series.remove(&id);
+        }
+    }
+
+    pub(crate) fn history(&self, group: &str, window: Duration, now: Instant) -> ThroughputHistory {
+        let current = self.bucket_no(now);
+        let window = window.as_nanos().div_ceil(self.bucket.as_nanos().max(1)) as u64;
+        let window = window.clamp(1, self.capacity.max(1) as u64);
+        let first = (current + 1).saturating_sub(window);
+
+        let mut series = self
+            .series
+            .values()
+            .filter(|series| series.group == group)
+            .map(|series| ThroughputSeries {
+                protocol: series.protocol.clone(),
+                message: series.message.clone(),
+                counts: (first..=current).map(|no| series.get(no)).collect(),
+            })
+            .collect::<Vec<_>>();
+
+        series.sort_unstable_by(|a, b| (&a.protocol, &a.message).cmp(&(&b.protocol, &b.message)));
+
+        ThroughputHistory {
+            bucket: self.bucket,
+            series,
+        }
+    }
+
+    /// Dumps counts of completed buckets to the `system` class.
+    pub(crate) fn dump(&mut self, now: Instant) {
+        let current = self.bucket_no(now);
+        let first = self
+            .undumped
+            .max(current.saturating_sub(self.capacity as u64));
+
+        for no in first..current {
+            for series in self.series.values() {
+                let count = series.get(no);
+                if count == 0 {
+                    continue;
+                }
+
+                let Some(permit) = self.dumper.acquire() else {
+                    break;
+                };
+
+                permit.record(Dump::builder().message_name("ThroughputBucket").finish(
+                    ThroughputBucket {
+                        group: series.group.clone(),
+                        protocol: series.protocol.clone(),
+                        message: series.message.clone(),
+                        bucket: self.bucket.into(),
+                        count,
+                    },
+                ));
+            }
+        }
+
+        self.undumped = self.undumped.max(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{Key, Label};
+
+    use super::*;
+
+    const BUCKET: Duration = Duration::from_secs(60);
+
+    fn config(buckets: usize, max_series: usize) -> ThroughputConfig {
+        ThroughputConfig {
+            enabled: true,
+            bucket: BUCKET.into(),
+            buckets,
+            max_series,
+            dump: false,
+        }
+    }
+
+    fn handle(snapshot: &mut Snapshot, group: &str, message: &'static str, count: usize) {
+        let labels = vec![
+            Label::new("message", message),
+            Label::new("protocol", "test"),
+        ];
+        let key = Key::from_parts(METRIC_NAME, labels);
+        let metrics = snapshot.groupwise.entry(group.into()).or_default();
+        let distribution = metrics.histograms.entry(key).or_default();
+        distribution.add(&vec![0.1; count]);
+    }
+
+    fn counts(history: &ThroughputHistory) -> Vec<(&str, &[u64])> {
+        history
+            .series
+            .iter()
+            .map(|s| (s.message.as_str(), s.counts.as_slice()))
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn series_shape() {
+        let mut throughput = Throughput::new(&config(4, 10));
+        let mut snapshot = Snapshot::default();
+        let history = |t: &Throughput, window| t.history("a", window, Instant::now());
+
+        handle(&mut snapshot, "a", "A", 3);
+        handle(&mut snapshot, "a", "<Startup>", 1);
+        handle(&mut snapshot, "b", "A", 7);
+        throughput.observe(&snapshot, Instant::now());
+
+        tokio::time::advance(BUCKET).await;
+        handle(&mut snapshot, "a", "A", 5);
+        handle(&mut snapshot, "a", "B", 1);
+        throughput.observe(&snapshot, Instant::now());
+
+        tokio::time::advance(2 * BUCKET).await;
+        handle(&mut snapshot, "a", "A", 2);
+        throughput.observe(&snapshot, Instant::now());
+
+        let h = history(&throughput, Duration::from_secs(3600));
+        assert_eq!(h.bucket, BUCKET);
+        assert_eq!(counts(&h), [("A", &[3, 5, 0, 2][..]), ("B", &[0, 1, 0, 0])]);
+
+        let h = history(&throughput, Duration::from_secs(90));
+        assert_eq!(counts(&h), [("A", &[0, 2][..]), ("B", &[0, 0])]);
+
+        // Old buckets are overwritten.
+        tokio::time::advance(2 * BUCKET).await;
+        handle(&mut snapshot, "a", "A", 1);
+        throughput.observe(&snapshot, Instant::now());
+        let h = history(&throughput, Duration::from_secs(3600));
+        assert_eq!(counts(&h), [("A", &[0, 2, 0, 1][..]), ("B", &[0, 0, 0, 0])]);
+
+        // Reconfiguration without changes keeps the history.
+        throughput.configure(&config(4, 10));
+        let h = history(&throughput, Duration::from_secs(3600));
+        assert_eq!(counts(&h), [("A", &[0, 2, 0, 1][..]), ("B", &[0, 0, 0, 0])]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lru() {
+        let mut throughput = Throughput::new(&config(2, 2));
+        let mut snapshot = Snapshot::default();
+        let history = |t: &Throughput| t.history("a", BUCKET, Instant::now());
+
+        handle(&mut snapshot, "a", "A", 1);
+        throughput.observe(&snapshot, Instant::now());
+        handle(&mut snapshot, "a", "B", 1);
+        throughput.observe(&snapshot, Instant::now());
+        handle(&mut snapshot, "a", "A", 1);
+        throughput.observe(&snapshot, Instant::now());
+
+        // `B` is the least recently updated one.
+        handle(&mut snapshot, "a", "C", 1);
+        throughput.observe(&snapshot, Instant::now());
+        assert_eq!(
+            counts(&history(&throughput)),
+            [("A", &[2][..]), ("C", &[1])]
+        );
+
+        // Evicted series don't count previous samples again.
+        handle(&mut snapshot, "a", "B", 4);
+        throughput.observe(&snapshot, Instant::now());
+        assert_eq!(
+            counts(&history(&throughput)),
+            [("B", &[4][..]), ("C", &[1])]
+        );
+
+        throughput.configure(&config(2, 1));
+        assert_eq!(counts(&history(&throughput)), [("B", &[4][..])]);
+    }
+}
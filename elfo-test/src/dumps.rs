@@ -0,0 +1,294 @@
+use std::{fmt, sync::Arc};
+
+use elfo_core::{
+    dumping::{capture::CapturedDump, SequenceNo},
+    tracing::TraceId,
+    Addr, Message,
+};
+
+/// A direction of a dumped message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The message has been received by an actor.
+    In,
+    /// The message has been sent by an actor.
+    Out,
+}
+
+/// A message dumped by an actor of the tested topology.
+#[derive(Clone)]
+pub struct Dump(CapturedDump);
+
+impl Dump {
+    /// Returns the direction of the message.
+    pub fn direction(&self) -> Direction {
+        if self.0.is_incoming {
+            Direction::In
+        } else {
+            Direction::Out
+        }
+    }
+
+    /// Returns the group of the actor which has dumped the message.
+    pub fn group(&self) -> &str {
+        &self.0.meta.group
+    }
+
+    /// Returns the key of the actor which has dumped the message.
+    pub fn key(&self) -> &str {
+        &self.0.meta.key
+    }
+
+    /// Returns the dump class, `"internal"` for regular messages.
+    pub fn class(&self) -> &'static str {
+        self.0.class
+    }
+
+    /// Returns the trace id of the message.
+    pub fn trace_id(&self) -> TraceId {
+        self.0.trace_id
+    }
+
+    /// Returns the sequence number of the dump, see
+    /// [`elfo_core::scope::sequence_no()`] for incoming messages.
+    pub fn sequence_no(&self) -> SequenceNo {
+        self.0.sequence_no
+    }
+
+    /// Returns the address the message is sent to, `None` if it's routed or
+    /// incoming. Remote addresses are kept, so it's the concrete destination.
+    pub fn recipient(&self) -> Option<Addr> {
+        Some(self.0.recipient).filter(|addr| !addr.is_null())
+    }
+
+    /// Returns the name of the message, `Enum::Variant` for enum variants.
+    pub fn message_name(&self) -> &str {
+        &self.0.message_name
+    }
+
+    /// Returns the protocol of the message.
+    pub fn message_protocol(&self) -> &'static str {
+        self.0.message_protocol
+    }
+
+    /// Returns the message as it's serialized for dumping.
+    pub fn message_value(&self) -> Option<&serde_value::Value> {
+        self.0.message.as_ref().ok()
+    }
+
+    /// Returns the message if it has the specified type.
+    ///
+    /// Returns `None` if the type differs or the message cannot be
+    /// deserialized back, e.g. if some fields are hidden.
+    pub fn message<M: Message>(&self) -> Option<M> {
+        self.0.message()
+    }
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {}.{} {}::{} (class={}, trace_id={}): ",
+            self.direction(),
+            self.group(),
+            self.key(),
+            self.message_protocol(),
+            self.message_name(),
+            self.class(),
+            self.trace_id(),
+        )?;
+
+        match &self.0.message {
+            Ok(value) => match serde_json::to_string(value) {
+                Ok(json) => f.write_str(&json),
+                Err(err) => write!(f, "<{err}>"),
+            },
+            Err(err) => write!(f, "<{err}>"),
+        }
+    }
+}
+
+impl fmt::Debug for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Dumps captured in the tested topology, see [`Proxy::dumps()`].
+///
+/// The number of captured dumps is bounded, the oldest ones are discarded.
+///
+/// [`Proxy::dumps()`]: crate::Proxy::dumps
+#[derive(Clone, Debug, Default)]
+pub struct Dumps(Vec<Dump>);
+
+impl Dumps {
+    pub(crate) fn new(dumps: Vec<CapturedDump>) -> Self {
+        Self(dumps.into_iter().map(Dump).collect())
+    }
+
+    /// Returns the number of dumps.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no dumps.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over dumps in the order of recording.
+    pub fn iter(&self) -> impl Iterator<Item = &Dump> {
+        self.0.iter()
+    }
+
+    /// Keeps only dumps with the specified direction.
+    pub fn direction(self, direction: Direction) -> Self {
+        self.filter(|d| d.direction() == direction)
+    }
+
+    /// Keeps only dumps of the specified class.
+    pub fn class(self, class: &str) -> Self {
+        self.filter(|d| d.class() == class)
+    }
+
+    /// Keeps only dumps made by actors of the specified group.
+    pub fn group(self, group: &str) -> Self {
+        self.filter(|d| d.group() == group)
+    }
+
+    /// Keeps only dumps with the specified trace id.
+    pub fn trace_id(self, trace_id: TraceId) -> Self {
+        self.filter(|d| d.trace_id() == trace_id)
+    }
+
+    /// Keeps only dumps matching the predicate.
+    pub fn filter(mut self, f: impl Fn(&Dump) -> bool) -> Self {
+        self.0.retain(f);
+        self
+    }
+
+    /// Keeps only dumps of the specified message type.
+    pub fn messages<M: Message>(&self) -> Vec<M> {
+        self.iter().filter_map(Dump::message).collect()
+    }
+}
+
+impl IntoIterator for Dumps {
+    type IntoIter = std::vec::IntoIter<Dump>;
+    type Item = Dump;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+pub(crate) type Capture = Arc<elfo_core::dumping::capture::DumpCapture>;
+
+#[doc(hidden)]
+#[track_caller]
+pub fn check_dumped(
+    dumps: &Dumps,
+    matched: usize,
+    expected: Option<usize>,
+    pattern: &str,
+    filter: &str,
+) {
+    let is_ok = match expected {
+        Some(expected) => matched == expected,
+        None => matched > 0,
+    };
+
+    if is_ok {
+        return;
+    }
+
+    let expected = match expected {
+        Some(expected) => format!("{expected}"),
+        None => "at least one".into(),
+    };
+
+    let mut candidates = String::new();
+    for dump in dumps.iter() {
+        candidates.push_str("\n  ");
+        candidates.push_str(&dump.to_string());
+    }
+    if candidates.is_empty() {
+        candidates.push_str(" <none>");
+    }
+
+    panic!(
+        "\nunexpected number of dumps matching a pattern\n\
+         pattern: {pattern}\n\
+         filter: {filter}\n\
+         expected: {expected}, matched: {matched}\n\
+         candidates:{candidates}\n",
+    );
+}
+
+/// Asserts that the tested topology has dumped a message matching a pattern.
+///
+/// The first argument is a [`Proxy`], then `key = value` filters follow:
+/// * `direction = In | Out`
+/// * `class = "..."`
+/// * `group = "..."`
+/// * `trace_id = <TraceId>`
+/// * `count = <usize>` to require the exact number of matched dumps instead
+///   of at least one.
+///
+/// The last argument is `message = <pattern>`, the type of the message is
+/// inferred from the pattern. Messages are matched after deserialization
+/// of the dumped form, so messages with hidden fields never match.
+///
+/// # Example
+/// ```ignore
+/// assert_dumped!(proxy, direction = Out, count = 1, message = OrderPlaced { qty: 10, .. });
+/// ```
+///
+/// [`Proxy`]: crate::Proxy
+#[macro_export]
+macro_rules! assert_dumped {
+    ($proxy:expr, $($args:tt)+) => {
+        $crate::__assert_dumped!(@munch [$proxy.dumps()] [::std::option::Option::None] [$($args)+] $($args)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_dumped {
+    (@munch [$dumps:expr] [$count:expr] [$($all:tt)+] direction = $direction:ident, $($rest:tt)+) => {
+        $crate::__assert_dumped!(
+            @munch [$dumps.direction($crate::Direction::$direction)] [$count] [$($all)+] $($rest)+
+        )
+    };
+    (@munch [$dumps:expr] [$count:expr] [$($all:tt)+] count = $n:expr, $($rest:tt)+) => {
+        $crate::__assert_dumped!(
+            @munch [$dumps] [::std::option::Option::Some($n)] [$($all)+] $($rest)+
+        )
+    };
+    (@munch [$dumps:expr] [$count:expr] [$($all:tt)+] message = $pat:pat $(,)?) => {{
+        let dumps: $crate::Dumps = $dumps;
+        let matched = dumps
+            .iter()
+            .filter(|dump| {
+                #[allow(unreachable_patterns)]
+                match dump.message() {
+                    ::std::option::Option::Some($pat) => true,
+                    _ => false,
+                }
+            })
+            .count();
+
+        $crate::__check_dumped(
+            &dumps,
+            matched,
+            $count,
+            ::std::stringify!($pat),
+            ::std::stringify!($($all)+),
+        );
+    }};
+    (@munch [$dumps:expr] [$count:expr] [$($all:tt)+] $key:ident = $value:expr, $($rest:tt)+) => {
+        $crate::__assert_dumped!(@munch [$dumps.$key($value)] [$count] [$($all)+] $($rest)+)
+    };
+}
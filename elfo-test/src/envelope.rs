@@ -0,0 +1,187 @@
+use std::marker::PhantomData;
+
+use elfo_core::{
+    _priv::MessageKind, errors::RequestError, scope, tracing::TraceId, Addr, Context, Envelope,
+    Message, Request, RequestId,
+};
+
+use crate::proxy::Proxy;
+
+/// Starts building an envelope with the provided message.
+///
+/// Useful to test routers, middlewares and other code working with raw
+/// envelopes. Built envelopes are the same as produced by actors and can be
+/// sent by [`Proxy::send_raw()`].
+///
+/// # Example
+/// ```ignore
+/// let trace_id = TraceId::try_from(42).unwrap();
+/// let envelope = elfo::test::envelope(SomeMessage).with_trace_id(trace_id).build();
+/// assert_eq!(envelope.trace_id(), trace_id);
+///
+/// let (envelope, response) = elfo::test::envelope(SomeRequest).request(&proxy);
+/// proxy.send_raw(envelope).await;
+/// let response = response.resolve().await.unwrap();
+/// ```
+pub fn envelope<M: Message>(message: M) -> EnvelopeBuilder<M> {
+    EnvelopeBuilder {
+        message,
+        trace_id: None,
+        sender: Addr::NULL,
+    }
+}
+
+/// A builder of envelopes, see [`envelope()`].
+#[must_use]
+pub struct EnvelopeBuilder<M> {
+    message: M,
+    trace_id: Option<TraceId>,
+    sender: Addr,
+}
+
+impl<M: Message> EnvelopeBuilder<M> {
+    /// Sets the trace id of the envelope.
+    ///
+    /// By default, the current one is used inside the actor system,
+    /// otherwise a new one is generated.
+    pub fn with_trace_id(mut self, trace_id: TraceId) -> Self {
+        self.trace_id = Some(trace_id);
+        self
+    }
+
+    /// Sets the sender of the envelope, `Addr::NULL` by default.
+    ///
+    /// Ignored for requests, because only the requester can get responses.
+    pub fn with_sender(mut self, sender: Addr) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    /// Builds a regular message.
+    pub fn build(self) -> Envelope {
+        let trace_id = self.trace_id();
+        Envelope::with_trace_id(self.message, MessageKind::regular(self.sender), trace_id)
+    }
+
+    fn trace_id(&self) -> TraceId {
+        self.trace_id
+            .or_else(scope::try_trace_id)
+            .unwrap_or_else(TraceId::generate)
+    }
+}
+
+impl<R: Request> EnvelopeBuilder<R> {
+    /// Builds a request sent by the proxy, which is the only one able to get
+    /// the response, so the sender is the proxy.
+    ///
+    /// Returns the envelope along with the pending response.
+    pub fn request(self, proxy: &Proxy) -> (Envelope, PendingResponse<R>) {
+        let context = proxy.context().pruned();
+        let trace_id = self.trace_id();
+        let token = context.__new_request(self.message.name(), trace_id);
+
+        let pending = PendingResponse {
+            request_id: token.request_id(),
+            context,
+            is_resolved: false,
+            marker: PhantomData,
+        };
+
+        let kind = MessageKind::RequestAny(token);
+        let envelope = Envelope::with_trace_id(self.message, kind, trace_id);
+        (envelope, pending)
+    }
+}
+
+/// A response to the request built by [`EnvelopeBuilder::request()`].
+///
+/// Dropping it without resolving cancels the request.
+#[must_use]
+pub struct PendingResponse<R> {
+    context: Context,
+    request_id: RequestId,
+    is_resolved: bool,
+    marker: PhantomData<R>,
+}
+
+impl<R: Request> PendingResponse<R> {
+    /// Returns the id of the request.
+    pub fn request_id(&self) -> RequestId {
+        self.request_id
+    }
+
+    /// Waits for the response.
+    ///
+    /// If the envelope is dropped without responding,
+    /// `RequestError::Failed` is returned.
+    pub async fn resolve(mut self) -> Result<R::Response, RequestError> {
+        let response = self.context.__wait_response::<R>(self.request_id).await;
+        self.is_resolved = true;
+        response
+    }
+}
+
+impl<R> Drop for PendingResponse<R> {
+    fn drop(&mut self) {
+        if !self.is_resolved {
+            self.context.__cancel_request(self.request_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elfo_core::{message, scope::Scope, ActorMeta};
+
+    use super::*;
+
+    #[message]
+    struct TestMessage(u32);
+
+    fn create_scope() -> Scope {
+        Scope::test(
+            Addr::NULL,
+            ActorMeta {
+                group: "group".into(),
+                key: "key".into(),
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn explicit_meta() {
+        let trace_id = TraceId::try_from(42).unwrap();
+        let sender = Addr::NULL;
+        let envelope = envelope(TestMessage(5))
+            .with_trace_id(trace_id)
+            .with_sender(sender)
+            .build();
+
+        assert_eq!(envelope.trace_id(), trace_id);
+        assert_eq!(envelope.sender(), sender);
+        assert_eq!(envelope.message().name(), "TestMessage");
+        assert_eq!(envelope.message().protocol(), "elfo-test");
+        assert!(matches!(
+            envelope.message_kind(),
+            MessageKind::Regular { .. }
+        ));
+        assert_eq!(envelope.request_id(), None);
+        assert_eq!(crate::extract_message::<TestMessage>(envelope).0, 5);
+    }
+
+    #[test]
+    fn trace_id_from_scope() {
+        let scope = create_scope();
+        let trace_id = scope.trace_id();
+        let envelope = scope.sync_within(|| envelope(TestMessage(5)).build());
+        assert_eq!(envelope.trace_id(), trace_id);
+    }
+
+    #[test]
+    fn trace_id_is_generated() {
+        let a = envelope(TestMessage(1)).build();
+        let b = envelope(TestMessage(2)).build();
+        assert_ne!(a.trace_id(), b.trace_id());
+    }
+}
@@ -0,0 +1,458 @@
+//! Golden files to catch accidental changes of the wire format of messages,
+//! e.g. renamed fields or reordered enum variants, which break consumers of
+//! dumps and nodes running older versions.
+//!
+//! Samples of a message are serialized the same way as dumps (JSON) and, with
+//! the `network` feature, as the network codecs (msgpack and postcard) do.
+//! The result is compared with a checked-in golden file. Also, golden files
+//! are deserialized back into the message and serialized again, that must
+//! produce the same output, otherwise the serialization is lossy or
+//! non-deterministic (e.g. because of `HashMap`).
+//!
+//! Set `ELFO_UPDATE_GOLDEN=1` to (re)generate golden files intentionally.
+//!
+//! # Example
+//! ```ignore
+//! use elfo::test::golden::Samples;
+//!
+//! impl Samples for SomeEvent {
+//!     fn samples() -> Vec<Self> {
+//!         vec![SomeEvent { id: 1, payload: None }, SomeEvent { id: 2, payload: Some(42) }]
+//!     }
+//! }
+//!
+//! // Checks `tests/golden/<protocol>/<name>.golden` for every message.
+//! elfo::test::golden_messages!(SomeEvent, OtherEvent);
+//! ```
+
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::Path,
+};
+
+use elfo_core::{dumping::extract_name_by_type, Message};
+
+/// If set to a non-empty value other than `0`, golden files are written
+/// instead of being checked.
+pub const UPDATE_ENV: &str = "ELFO_UPDATE_GOLDEN";
+
+/// Provides samples of the message, see [`golden_messages!`].
+///
+/// Samples should cover all variants and optional fields.
+///
+/// [`golden_messages!`]: crate::golden_messages
+pub trait Samples: Message {
+    /// Returns samples in the stable order.
+    fn samples() -> Vec<Self>;
+}
+
+/// Checks that samples are serialized as stored in the golden file
+/// `<dir>/<protocol>/<name>.golden`, see the [module-level docs](self).
+///
+/// # Panics
+/// If the check fails, with a readable report.
+#[track_caller]
+pub fn check<M: Message>(samples: impl IntoIterator<Item = M>, dir: impl AsRef<Path>) {
+    if let Err(report) = try_check(samples, dir) {
+        panic!("{report}");
+    }
+}
+
+/// Like [`check()`], but returns the report instead of panicking.
+pub fn try_check<M: Message>(
+    samples: impl IntoIterator<Item = M>,
+    dir: impl AsRef<Path>,
+) -> Result<(), String> {
+    let update = std::env::var(UPDATE_ENV).is_ok_and(|v| !v.is_empty() && v != "0");
+    run(samples.into_iter().collect(), dir.as_ref(), update)
+}
+
+#[doc(hidden)]
+pub fn __check_all(results: Vec<Result<(), String>>) {
+    let reports = results
+        .into_iter()
+        .filter_map(Result::err)
+        .collect::<Vec<_>>();
+    if !reports.is_empty() {
+        panic!("{}", reports.join("\n\n"));
+    }
+}
+
+/// Generates a test checking golden files of the listed messages in
+/// `tests/golden` of the current crate, see [`golden`](crate::golden).
+///
+/// All messages must implement [`Samples`].
+#[macro_export]
+macro_rules! golden_messages {
+    ($($message:ty),+ $(,)?) => {
+        #[test]
+        fn golden_messages() {
+            let dir = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+            $crate::golden::__check_all(::std::vec![$(
+                $crate::golden::try_check(
+                    <$message as $crate::golden::Samples>::samples(),
+                    &dir,
+                ),
+            )+]);
+        }
+    };
+}
+
+fn run<M: Message>(samples: Vec<M>, dir: &Path, update: bool) -> Result<(), String> {
+    let name = extract_name_by_type::<M>().to_string();
+    let first = samples
+        .first()
+        .ok_or_else(|| format!("{name}: no samples provided"))?;
+    let path = dir.join(first.protocol()).join(format!("{name}.golden"));
+    let title = format!("{name} ({})", path.display());
+
+    let mut actual = Vec::with_capacity(samples.len());
+    for (no, sample) in samples.iter().enumerate() {
+        let encoded = encode(sample).map_err(|err| format!("{title}: sample #{no}: {err}"))?;
+
+        // Maps with unstable ordering produce different outputs here.
+        let again = encode(&sample.clone()).map_err(|err| format!("{title}: {err}"))?;
+        if again != encoded {
+            return Err(format!(
+                "{title}: sample #{no} is serialized non-deterministically"
+            ));
+        }
+
+        actual.push(encoded);
+    }
+
+    if update {
+        let write = || {
+            fs::create_dir_all(path.parent().expect("has a parent"))?;
+            fs::write(&path, render(&name, &actual))
+        };
+        return write().map_err(|err| format!("{title}: cannot write: {err}"));
+    }
+
+    let golden = match fs::read_to_string(&path) {
+        Ok(content) => parse(&content).map_err(|err| format!("{title}: {err}"))?,
+        Err(err) => {
+            return Err(format!(
+                "{title}: cannot read: {err}, run with `{UPDATE_ENV}=1` to create it"
+            ))
+        }
+    };
+
+    let mut report = Report::default();
+    compare(&golden, &actual, &mut report);
+    roundtrip::<M>(first, &golden, &mut report);
+
+    if report.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{title} doesn't match the golden file, \
+             run with `{UPDATE_ENV}=1` if changes are intended:\n{report}"
+        ))
+    }
+}
+
+// === Formats ===
+
+/// A sample serialized by all supported formats, in order.
+type Encoded = Vec<(&'static str, String)>;
+
+fn encode<M: Message>(message: &M) -> Result<Encoded, String> {
+    let mut encoded = Vec::new();
+
+    // The same way as dumps are serialized.
+    let json = serde_json::to_string(&*message._erase()).map_err(|err| format!("json: {err}"))?;
+    encoded.push(("json", json));
+
+    #[cfg(feature = "network")]
+    {
+        use elfo_core::AnyMessage;
+
+        let message = AnyMessage::new(message.clone());
+
+        let mut buffer = Vec::new();
+        message
+            .write_msgpack(&mut buffer, usize::MAX)
+            .map_err(|err| format!("msgpack: {err}"))?;
+        encoded.push(("msgpack", to_hex(&buffer)));
+
+        buffer.clear();
+        message
+            .write_postcard(&mut buffer, usize::MAX)
+            .map_err(|err| format!("postcard: {err}"))?;
+        encoded.push(("postcard", to_hex(&buffer)));
+    }
+
+    Ok(encoded)
+}
+
+#[cfg_attr(not(feature = "network"), allow(unused_variables))]
+fn decode<M: Message>(sample: &M, format: &str, value: &str) -> Result<M, String> {
+    match format {
+        "json" => serde_json::from_str(value).map_err(|err| err.to_string()),
+        #[cfg(feature = "network")]
+        "msgpack" => {
+            let bytes = from_hex(value)?;
+            elfo_core::AnyMessage::read_msgpack(&bytes, sample.protocol(), sample.name())
+                .map_err(|err| err.to_string())?
+                .ok_or("the message isn't registered")?
+                .downcast()
+                .map_err(|_| "unexpected message type".into())
+        }
+        #[cfg(feature = "network")]
+        "postcard" => {
+            let bytes = from_hex(value)?;
+            let id = elfo_core::AnyMessage::new(sample.clone()).network_id();
+            elfo_core::AnyMessage::read_postcard(&bytes, id)
+                .map_err(|err| err.to_string())?
+                .ok_or("the message isn't registered")?
+                .downcast()
+                .map_err(|_| "unexpected message type".into())
+        }
+        _ => Err(format!("unsupported format `{format}`")),
+    }
+}
+
+#[cfg(feature = "network")]
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, byte| {
+        let _ = write!(s, "{byte:02x}");
+        s
+    })
+}
+
+#[cfg(feature = "network")]
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err("odd length of hex".into());
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|err| err.to_string()))
+        .collect()
+}
+
+// === Golden files ===
+
+/// Samples of the golden file, formats unsupported by the build are kept.
+type Golden = Vec<Vec<(String, String)>>;
+
+fn render(name: &str, samples: &[Encoded]) -> String {
+    let mut out = format!("# Samples of `{name}`, set `{UPDATE_ENV}=1` to regenerate.\n");
+
+    for (no, sample) in samples.iter().enumerate() {
+        let _ = writeln!(out, "\n#{no}");
+        for (format, value) in sample {
+            let _ = writeln!(out, "{format}: {value}");
+        }
+    }
+
+    out
+}
+
+fn parse(content: &str) -> Result<Golden, String> {
+    let mut golden = Golden::new();
+
+    for (line_no, line) in content.lines().enumerate() {
+        if line.is_empty() || line.starts_with("# ") {
+            continue;
+        }
+
+        if line
+            .strip_prefix('#')
+            .is_some_and(|no| no.parse::<usize>().is_ok())
+        {
+            golden.push(Vec::new());
+        } else if let (Some((format, value)), Some(sample)) =
+            (line.split_once(": "), golden.last_mut())
+        {
+            sample.push((format.into(), value.into()));
+        } else {
+            return Err(format!("invalid line {}: {line}", line_no + 1));
+        }
+    }
+
+    Ok(golden)
+}
+
+fn compare(golden: &Golden, actual: &[Encoded], report: &mut Report) {
+    if golden.len() != actual.len() {
+        report.add(format!(
+            "the number of samples differs: {} in the golden file, {} provided",
+            golden.len(),
+            actual.len()
+        ));
+    }
+
+    for (no, (golden, actual)) in golden.iter().zip(actual).enumerate() {
+        for (format, actual) in actual {
+            match golden.iter().find(|(f, _)| f == format) {
+                Some((_, expected)) if expected == actual => {}
+                Some((_, expected)) => {
+                    report.add(format!("#{no} {format}:\n  - {expected}\n  + {actual}"));
+                }
+                None => report.add(format!("#{no} {format}: missing in the golden file")),
+            }
+        }
+    }
+}
+
+/// Checks that the golden file can be read and produces the same output.
+fn roundtrip<M: Message>(sample: &M, golden: &Golden, report: &mut Report) {
+    let supported = encode(sample).unwrap_or_default();
+
+    for (no, golden) in golden.iter().enumerate() {
+        for (format, value) in golden {
+            let Some(index) = supported.iter().position(|(f, _)| f == format) else {
+                continue;
+            };
+
+            let decoded = match decode(sample, format, value) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    report.add(format!("#{no} {format}: cannot be deserialized: {err}"));
+                    continue;
+                }
+            };
+
+            let Ok(encoded) = encode(&decoded) else {
+                continue;
+            };
+
+            let (_, reencoded) = &encoded[index];
+            if reencoded != value {
+                report.add(format!(
+                    "#{no} {format}: the round-trip changes the output, the serialization \
+                     is lossy or non-deterministic:\n  - {value}\n  + {reencoded}"
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report(Vec<String>);
+
+impl Report {
+    fn add(&mut self, line: String) {
+        self.0.push(line);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.0 {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use elfo_core::message;
+
+    use super::*;
+
+    #[message]
+    struct Event {
+        id: u32,
+        payload: Option<String>,
+    }
+
+    #[message]
+    struct Unordered {
+        map: HashMap<u32, u32>,
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("elfo-golden-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn samples() -> Vec<Event> {
+        vec![
+            Event {
+                id: 1,
+                payload: None,
+            },
+            Event {
+                id: 2,
+                payload: Some("x".into()),
+            },
+        ]
+    }
+
+    #[test]
+    fn update_and_check() {
+        let dir = temp_dir("update");
+
+        let err = run(samples(), &dir, false).unwrap_err();
+        assert!(err.contains("cannot read"), "{err}");
+        assert!(err.contains(UPDATE_ENV), "{err}");
+
+        run(samples(), &dir, true).unwrap();
+        let content = fs::read_to_string(dir.join("elfo-test/Event.golden")).unwrap();
+        assert!(
+            content.contains(r#"json: {"id":1,"payload":null}"#),
+            "{content}"
+        );
+        assert_eq!(content.contains("msgpack: "), cfg!(feature = "network"));
+
+        run(samples(), &dir, false).unwrap();
+
+        // Changed samples are reported with a diff.
+        let mut changed = samples();
+        changed[1].id = 3;
+        let err = run(changed, &dir, false).unwrap_err();
+        assert!(
+            err.contains(
+                "#1 json:\n  - {\"id\":2,\"payload\":\"x\"}\n  + {\"id\":3,\"payload\":\"x\"}"
+            ),
+            "{err}"
+        );
+        assert!(!err.contains("#0"), "{err}");
+
+        let err = run(samples()[..1].to_vec(), &dir, false).unwrap_err();
+        assert!(err.contains("the number of samples differs"), "{err}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn incompatible_golden() {
+        let dir = temp_dir("incompatible");
+        let path = dir.join("elfo-test/Event.golden");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        // E.g. the field is renamed.
+        fs::write(&path, "#0\njson: {\"ident\":1,\"payload\":null}\n").unwrap();
+
+        let err = run(samples()[..1].to_vec(), &dir, false).unwrap_err();
+        assert!(err.contains("#0 json: cannot be deserialized"), "{err}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unstable_order() {
+        let dir = temp_dir("unstable");
+        let map = (0..32).map(|i| (i, i)).collect::<HashMap<_, _>>();
+        let samples = || vec![Unordered { map: map.clone() }];
+
+        run(samples(), &dir, true).unwrap();
+        let err = run(samples(), &dir, false).unwrap_err();
+        assert!(err.contains("lossy or non-deterministic"), "{err}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,23 @@
 //! Utils for unit testing actors.
 
+pub use dumps::{Direction, Dump, Dumps};
+pub use envelope::{envelope, EnvelopeBuilder, PendingResponse};
+pub use load::{load, Clock, Latency, Load, LoadReport, TrafficSpec};
 pub use proxy::{proxy, Proxy};
+pub use simulation::simulate;
 pub use utils::{extract_message, extract_request};
 
 #[cfg(feature = "unstable")]
 pub use proxy::proxy_with_route;
 
+#[doc(hidden)]
+pub use dumps::check_dumped as __check_dumped;
+
+pub mod golden;
+
+mod dumps;
+mod envelope;
+mod load;
 mod proxy;
+mod simulation;
 mod utils;
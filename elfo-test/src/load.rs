@@ -0,0 +1,499 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::future;
+use serde::{de::Deserializer, Deserialize};
+use serde_value::Value;
+use tokio::time::{self, Instant};
+
+use elfo_core::{
+    config::Rate,
+    probe::{self, Probe},
+    topology::Local,
+    tracing::TraceId,
+    Addr, Context, Message, Request, Topology,
+};
+
+use crate::proxy::{proxy_with_topology, Proxy};
+
+/// Creates a load generator for the topology built by `topology_builder`.
+///
+/// The builder gets the group of injecting proxies to route it to tested
+/// groups, like in [`simulate()`]. Groups are started with empty configs,
+/// use [`Load::config()`] to override it.
+///
+/// The traffic is described by [`TrafficSpec`] and injected either
+/// * in the open-loop mode ([`Load::traffic()`]): messages are sent at the
+///   specified rate regardless of how fast they're handled, latency is
+///   measured from sending until the recipient finishes handling the message.
+///   Messages that cannot be sent without waiting (e.g. mailboxes are full)
+///   are dropped.
+/// * in the closed-loop mode ([`Load::requests()`]): every proxy sends the
+///   next request once the previous one is responded, latency is measured
+///   until the response is received.
+///
+/// [`Load::measure()`] runs the topology and returns a [`LoadReport`].
+/// It must not be called inside a runtime.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use elfo_core as elfo;
+/// # use elfo_test as test;
+/// # use elfo::{message, msg, ActorGroup};
+/// use test::{Clock, TrafficSpec};
+///
+/// #[message]
+/// struct Order(u64);
+///
+/// let report = test::load(|topology, injectors| {
+///     let orders = topology.local("orders");
+///     injectors.route_all_to(&orders);
+///     orders.mount(ActorGroup::new().exec(|mut ctx| async move {
+///         while let Some(envelope) = ctx.recv().await {
+///             msg!(match envelope {
+///                 Order(_) => {}
+///             });
+///         }
+///     }));
+/// })
+/// .clock(Clock::Virtual)
+/// .traffic(TrafficSpec {
+///     message_factory: Order,
+///     rate: Some("50k/s".parse().unwrap()),
+///     concurrency: 2,
+///     duration: Duration::from_millis(100),
+/// })
+/// .measure();
+///
+/// assert_eq!(report.offered, 5000);
+/// assert_eq!(report.dropped, 0);
+/// assert!(report.latency.p99 < Duration::from_millis(1));
+/// ```
+///
+/// [`simulate()`]: crate::simulate()
+pub fn load(topology_builder: impl Fn(&Topology, &Local<'_>) + 'static) -> Load {
+    Load {
+        topology_builder: Box::new(topology_builder),
+        config: Value::Map(BTreeMap::new()),
+        clock: Clock::Real,
+        drain_timeout: Duration::from_secs(1),
+        traffic: None,
+    }
+}
+
+/// A load generator, see [`load()`].
+#[must_use = "the load isn't generated until `measure()` is called"]
+pub struct Load {
+    topology_builder: TopologyBuilder,
+    config: Value,
+    clock: Clock,
+    drain_timeout: Duration,
+    traffic: Option<Traffic>,
+}
+
+type TopologyBuilder = Box<dyn Fn(&Topology, &Local<'_>)>;
+
+/// Which time is used by [`Load::measure()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// A multi-threaded runtime and the real time, for actual numbers.
+    Real,
+    /// A single-threaded runtime with paused time, which is advanced
+    /// automatically once all actors are idle. Handling takes no time,
+    /// so numbers are deterministic and show only the queueing.
+    Virtual,
+}
+
+/// A description of the traffic injected by [`Load`].
+pub struct TrafficSpec<F> {
+    /// Produces the message by its number, starting from zero.
+    pub message_factory: F,
+    /// The offered rate, e.g. `"50k/s"`, it's required in the open-loop mode.
+    /// In the closed-loop mode, it limits the rate of requests, `None` means
+    /// sending the next request right after the response.
+    pub rate: Option<Rate>,
+    /// The number of injecting proxies. In the closed-loop mode, it's also
+    /// the number of requests in flight.
+    pub concurrency: usize,
+    /// For how long messages are injected.
+    pub duration: Duration,
+}
+
+impl Load {
+    /// Sets the config of the topology, see [`proxy()`](crate::proxy()).
+    pub fn config(mut self, config: impl for<'de> Deserializer<'de>) -> Self {
+        self.config = Value::deserialize(config).expect("invalid config");
+        self
+    }
+
+    /// Sets which time is used, [`Clock::Real`] by default.
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets how long to wait for messages in flight once the injection is
+    /// over. Unhandled ones are counted as dropped. `1s` by default.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Injects messages in the open-loop mode, see [`load()`].
+    ///
+    /// # Panics
+    ///
+    /// If the rate isn't specified or the concurrency is zero.
+    pub fn traffic<F, M>(mut self, spec: TrafficSpec<F>) -> Self
+    where
+        F: Fn(u64) -> M + Send + Sync + 'static,
+        M: Message,
+    {
+        assert!(spec.rate.is_some(), "the open-loop mode requires a rate");
+
+        let factory = spec.message_factory;
+        let inject = Inject::Open(Box::new(move |ctx, no| ctx.try_send(factory(no)).is_ok()));
+        self.traffic = Some(Traffic::new(
+            inject,
+            spec.rate,
+            spec.concurrency,
+            spec.duration,
+        ));
+        self
+    }
+
+    /// Injects requests in the closed-loop mode, see [`load()`].
+    /// Failed requests are counted as dropped.
+    ///
+    /// # Panics
+    ///
+    /// If the concurrency is zero.
+    pub fn requests<F, R>(mut self, spec: TrafficSpec<F>) -> Self
+    where
+        F: Fn(u64) -> R + Send + Sync + 'static,
+        R: Request,
+    {
+        let factory = Arc::new(spec.message_factory);
+        let inject = Inject::Closed(Box::new(move |ctx, no| {
+            let request = factory(no);
+            Box::pin(async move { ctx.request(request).resolve().await.is_ok() })
+        }));
+        self.traffic = Some(Traffic::new(
+            inject,
+            spec.rate,
+            spec.concurrency,
+            spec.duration,
+        ));
+        self
+    }
+
+    /// Builds the topology, injects the traffic and returns the report.
+    ///
+    /// # Panics
+    ///
+    /// If the traffic isn't specified or it's called inside a runtime.
+    pub fn measure(mut self) -> LoadReport {
+        let traffic = self
+            .traffic
+            .take()
+            .expect("no traffic, use `traffic()` or `requests()`");
+
+        let rt = match self.clock {
+            Clock::Real => tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build(),
+            Clock::Virtual => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .start_paused(true)
+                .build(),
+        }
+        .expect("cannot build a runtime");
+
+        rt.block_on(self.run(traffic))
+    }
+
+    async fn run(self, traffic: Traffic) -> LoadReport {
+        let topology_builder = &self.topology_builder;
+        let proxy = proxy_with_topology(self.config.clone(), |topology, injectors| {
+            topology_builder(topology, injectors);
+            // There is no single tested group.
+            Addr::NULL
+        })
+        .await;
+
+        let mut proxies = Vec::with_capacity(traffic.concurrency);
+        for _ in 1..traffic.concurrency {
+            proxies.push(proxy.subproxy().await);
+        }
+        proxies.push(proxy);
+
+        let tracker = Arc::new(Tracker {
+            injectors: proxies.iter().map(Proxy::addr).collect(),
+            in_flight: Mutex::default(),
+            latencies: Mutex::default(),
+        });
+        let _guard = probe::register(tracker.clone());
+
+        let started_at = Instant::now();
+        let injections = proxies
+            .iter()
+            .enumerate()
+            .map(|(index, proxy)| traffic.inject(index, proxy, &tracker, started_at));
+        let offered = future::join_all(injections).await.into_iter().sum::<u64>();
+        time::sleep_until(started_at + traffic.duration).await;
+        let injected_for = started_at.elapsed();
+
+        // Wait for messages in flight.
+        let drain_deadline = Instant::now() + self.drain_timeout;
+        while !tracker.in_flight.lock().unwrap().is_empty() && Instant::now() < drain_deadline {
+            time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let latencies = std::mem::take(&mut *tracker.latencies.lock().unwrap());
+        LoadReport::new(offered, injected_for, latencies)
+    }
+}
+
+// === Traffic ===
+
+type OpenInject = Box<dyn Fn(&Context<(), usize>, u64) -> bool + Send + Sync>;
+type ClosedInject = Box<
+    dyn for<'a> Fn(&'a Context<(), usize>, u64) -> Pin<Box<dyn Future<Output = bool> + 'a>>
+        + Send
+        + Sync,
+>;
+
+enum Inject {
+    Open(OpenInject),
+    Closed(ClosedInject),
+}
+
+struct Traffic {
+    inject: Inject,
+    /// Between messages of the same proxy.
+    interval: Option<Duration>,
+    concurrency: usize,
+    duration: Duration,
+}
+
+impl Traffic {
+    fn new(inject: Inject, rate: Option<Rate>, concurrency: usize, duration: Duration) -> Self {
+        assert!(concurrency > 0, "the concurrency must be positive");
+
+        Self {
+            inject,
+            interval: rate
+                .map(|rate| Duration::from_secs_f64(concurrency as f64 / rate.per_second())),
+            concurrency,
+            duration,
+        }
+    }
+
+    /// Returns the number of injected messages.
+    async fn inject(
+        &self,
+        index: usize,
+        proxy: &Proxy,
+        tracker: &Tracker,
+        started_at: Instant,
+    ) -> u64 {
+        let finished_at = started_at + self.duration;
+        let mut no = index as u64;
+        let mut seq_no = 0;
+
+        loop {
+            // Scheduled times are calculated from the start to avoid drift,
+            // late messages are sent in a burst to catch up.
+            if let Some(interval) = self.interval {
+                let offset = interval / self.concurrency as u32 * index as u32;
+                let scheduled_at = started_at + offset + interval * seq_no;
+                if scheduled_at >= finished_at {
+                    break;
+                }
+                time::sleep_until(scheduled_at).await;
+            } else if Instant::now() >= finished_at {
+                break;
+            }
+
+            let trace_id = TraceId::generate();
+            proxy.scope().set_trace_id(trace_id);
+
+            match &self.inject {
+                Inject::Open(inject) => {
+                    // Tracked before sending, because it can be handled immediately.
+                    tracker.on_sent(trace_id);
+                    if !proxy
+                        .scope()
+                        .clone()
+                        .sync_within(|| inject(proxy.context(), no))
+                    {
+                        tracker.on_dropped(trace_id);
+                    }
+                }
+                Inject::Closed(inject) => {
+                    let sent_at = Instant::now();
+                    let fut = inject(proxy.context(), no);
+                    if proxy.scope().clone().within(fut).await {
+                        tracker.on_responded(sent_at.elapsed());
+                    }
+                }
+            }
+
+            no += self.concurrency as u64;
+            seq_no += 1;
+        }
+
+        seq_no as u64
+    }
+}
+
+// === Tracker ===
+
+struct Tracker {
+    injectors: HashSet<Addr>,
+    in_flight: Mutex<HashMap<TraceId, Instant>>,
+    latencies: Mutex<Vec<Duration>>,
+}
+
+impl Tracker {
+    fn on_sent(&self, trace_id: TraceId) {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(trace_id, Instant::now());
+    }
+
+    fn on_dropped(&self, trace_id: TraceId) {
+        self.in_flight.lock().unwrap().remove(&trace_id);
+    }
+
+    fn on_responded(&self, latency: Duration) {
+        self.latencies.lock().unwrap().push(latency);
+    }
+}
+
+impl Probe for Tracker {
+    fn on_handled(&self, sender: Addr, trace_id: TraceId) {
+        if !self.injectors.contains(&sender) {
+            return;
+        }
+
+        // Other actors can handle messages of the same trace, only the first
+        // handling of the injected message is taken into account.
+        let sent_at = self.in_flight.lock().unwrap().remove(&trace_id);
+        if let Some(sent_at) = sent_at {
+            self.on_responded(sent_at.elapsed());
+        }
+    }
+}
+
+// === LoadReport ===
+
+/// The result of [`Load::measure()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LoadReport {
+    /// How many messages have been injected.
+    pub offered: u64,
+    /// How many messages have been handled (or requests responded).
+    pub handled: u64,
+    /// How many messages have failed to be sent (e.g. mailboxes are full),
+    /// haven't been handled until the drain timeout or failed requests.
+    pub dropped: u64,
+    /// Injected messages per second.
+    pub offered_rate: f64,
+    /// Handled messages per second, calculated over the injection time.
+    pub achieved_rate: f64,
+    /// Latencies of handled messages.
+    pub latency: Latency,
+}
+
+/// End-to-end latency percentiles of handled messages.
+/// All values are zero if no message has been handled.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Latency {
+    /// The minimum.
+    pub min: Duration,
+    /// The median.
+    pub p50: Duration,
+    /// The 90th percentile.
+    pub p90: Duration,
+    /// The 99th percentile.
+    pub p99: Duration,
+    /// The 99.9th percentile.
+    pub p999: Duration,
+    /// The maximum.
+    pub max: Duration,
+    /// The arithmetic mean.
+    pub mean: Duration,
+}
+
+impl LoadReport {
+    fn new(offered: u64, injected_for: Duration, mut latencies: Vec<Duration>) -> Self {
+        latencies.sort_unstable();
+
+        let handled = latencies.len() as u64;
+        let secs = injected_for.as_secs_f64().max(f64::EPSILON);
+
+        Self {
+            offered,
+            handled,
+            dropped: offered - handled,
+            offered_rate: offered as f64 / secs,
+            achieved_rate: handled as f64 / secs,
+            latency: Latency::new(&latencies),
+        }
+    }
+}
+
+impl Latency {
+    fn new(sorted: &[Duration]) -> Self {
+        let Some(&max) = sorted.last() else {
+            return Self::default();
+        };
+
+        let percentile = |p: f64| {
+            let index = (p * sorted.len() as f64).ceil() as usize;
+            sorted[index.clamp(1, sorted.len()) - 1]
+        };
+
+        Self {
+            min: sorted[0],
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+            max,
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+        }
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let latency = &self.latency;
+        write!(
+            f,
+            "offered: {} ({:.0}/s), handled: {} ({:.0}/s), dropped: {}, \
+             latency: p50={:?} p90={:?} p99={:?} p999={:?} max={:?}",
+            self.offered,
+            self.offered_rate,
+            self.handled,
+            self.achieved_rate,
+            self.dropped,
+            latency.p50,
+            latency.p90,
+            latency.p99,
+            latency.p999,
+            latency.max,
+        )
+    }
+}
@@ -20,16 +20,18 @@ use serde_value::Value;
 use tokio::task;
 
 use elfo_core::{
-    ActorGroup, ActorMeta, Addr, Blueprint, Context, Envelope, Local, Message, Request,
-    ResponseToken,
     _priv::do_start,
     errors::TrySendError,
     message, msg,
     routers::{MapRouter, Outcome},
     scope::Scope,
-    topology::Topology,
+    topology::{self, Topology},
+    ActorGroup, ActorMeta, Addr, Blueprint, Context, Envelope, Local, Message, Request,
+    ResponseToken,
 };
 
+use crate::dumps::{Capture, Dumps};
+
 const SYNC_YIELD_COUNT: usize = 32;
 
 /// A proxy for testing actors.
@@ -38,6 +40,7 @@ pub struct Proxy {
     scope: Scope,
     subject_addr: Addr,
     recv_timeout: Duration,
+    dumps: Capture,
 }
 
 type ProxyContext = Context<(), usize>;
@@ -96,6 +99,19 @@ impl Proxy {
             .sync_within(|| self.context.try_send_to(recipient, message))
     }
 
+    /// Sends the envelope built by [`envelope()`](crate::envelope()),
+    /// routing it as [`Context::send()`] does.
+    #[track_caller]
+    pub fn send_raw(&self, envelope: Envelope) -> impl Future<Output = ()> + '_ {
+        let location = Location::caller();
+        self.scope.clone().within(async move {
+            let name = envelope.message().name();
+            if let Err(err) = self.context.__send_envelope(envelope).await {
+                panic!("cannot send {} ({}) at {}", name, err, location);
+            }
+        })
+    }
+
     /// See [`Context::request()`] for details.
     #[track_caller]
     pub fn request<R: Request>(&self, request: R) -> impl Future<Output = R::Response> {
@@ -216,9 +232,25 @@ impl Proxy {
             context,
             subject_addr: self.subject_addr,
             recv_timeout: self.recv_timeout,
+            dumps: self.dumps.clone(),
         }
     }
 
+    /// Returns dumps captured in the tested topology so far.
+    ///
+    /// Messages sent by the proxy itself aren't dumped, but they're dumped
+    /// as incoming ones by the recipient. See [`assert_dumped!`] as well.
+    ///
+    /// [`assert_dumped!`]: crate::assert_dumped
+    pub fn dumps(&self) -> Dumps {
+        Dumps::new(self.dumps.snapshot())
+    }
+
+    /// Forgets all captured dumps.
+    pub fn clear_dumps(&self) {
+        self.dumps.clear();
+    }
+
     /// Waits until the testable actor finishes.
     pub async fn finished(&self) {
         let fut = self.context.finished(self.subject_addr);
@@ -229,6 +261,14 @@ impl Proxy {
     pub fn close(&self) {
         self.scope.clone().sync_within(|| self.context.close());
     }
+
+    pub(crate) fn context(&self) -> &ProxyContext {
+        &self.context
+    }
+
+    pub(crate) fn scope(&self) -> &Scope {
+        &self.scope
+    }
 }
 
 #[message(ret = Local<ProxyContext>)]
@@ -268,6 +308,33 @@ fn testers(tx: shared::OneshotSender<ProxyContext>) -> Blueprint {
         })
 }
 
+/// Tests usually define messages with the same name in several test
+/// functions of the same binary, so duplicates are allowed unless the config
+/// sets `system.allow_duplicate_messages` explicitly.
+fn allow_duplicate_messages(config: Value) -> Value {
+    let Value::Map(mut map) = config else {
+        return config;
+    };
+
+    let common = map
+        .entry(Value::String("common".into()))
+        .or_insert_with(|| Value::Map(BTreeMap::new()));
+    let Value::Map(common) = common else {
+        return Value::Map(map);
+    };
+
+    let system = common
+        .entry(Value::String("system".into()))
+        .or_insert_with(|| Value::Map(BTreeMap::new()));
+    if let Value::Map(system) = system {
+        system
+            .entry(Value::String("allow_duplicate_messages".into()))
+            .or_insert(Value::Bool(true));
+    }
+
+    Value::Map(map)
+}
+
 #[doc(hidden)]
 #[stability::unstable]
 pub async fn proxy_with_route<F>(
@@ -278,31 +345,48 @@ pub async fn proxy_with_route<F>(
 where
     F: Fn(&Envelope) -> bool + Send + Sync + 'static,
 {
+    let config = Value::deserialize(config).expect("invalid config");
+    let mut map = BTreeMap::new();
+    map.insert(Value::String("subject".into()), config);
+
+    proxy_with_topology(Value::Map(map), |topology, testers| {
+        let subject = topology.local("subject");
+        let subject_addr = subject.addr();
+
+        testers.route_all_to(&subject);
+        subject.route_to(testers, route_filter);
+        subject.mount(blueprint);
+
+        subject_addr
+    })
+    .await
+}
+
+/// Starts the topology built by `build`, which returns the address of the
+/// tested group and can route the proxy's group (`system.testers`) anywhere.
+pub(crate) async fn proxy_with_topology(
+    config: Value,
+    build: impl FnOnce(&Topology, &topology::Local<'_>) -> Addr,
+) -> Proxy {
     let _ = tracing_subscriber::fmt()
         .with_target(false)
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .with_test_writer()
         .try_init();
 
-    let config = Value::deserialize(config).expect("invalid config");
-    let mut map = BTreeMap::new();
-    map.insert(Value::String("subject".into()), config);
-    let config = Value::Map(map);
-
     let topology = Topology::empty();
-    let subject = topology.local("subject");
     let testers = topology.local("system.testers");
     let configurers = topology.local("system.configurers").entrypoint();
 
-    let subject_addr = subject.addr();
+    let subject_addr = build(&topology, &testers);
 
-    testers.route_all_to(&subject);
-    subject.route_to(&testers, route_filter);
+    let config = allow_duplicate_messages(config);
 
     // TODO: capture log messages.
     // TODO: capture metrics.
     configurers.mount(elfo_configurer::fixture(&topology, config));
-    subject.mount(blueprint);
+
+    let dumps = topology.dump_capture().clone();
 
     let (tx, rx) = shared::oneshot_channel();
     testers.mount(self::testers(tx));
@@ -321,6 +405,7 @@ where
         context,
         subject_addr,
         recv_timeout: Duration::from_millis(150),
+        dumps,
     }
 }
 
@@ -0,0 +1,110 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+};
+
+use serde_value::Value;
+
+use elfo_core::{simulation, topology::Local, Addr, Topology};
+
+use crate::proxy::{proxy_with_topology, Proxy};
+
+/// Runs the scenario against the topology once per seed in the deterministic
+/// simulation mode, panics with the first failing seed.
+///
+/// Every run uses a fresh topology built by `topology_builder` on a new
+/// single-threaded runtime with paused time. The seed controls the order in
+/// which ready actors take messages and the order of multicast deliveries,
+/// so a failing interleaving is replayed exactly by running the same seed
+/// again, e.g. `simulate([42], ..)`.
+///
+/// The builder gets the proxy's group to route it to tested groups.
+/// Groups are started with empty configs.
+///
+/// Interleavings are reproducible as long as actors don't depend on real time,
+/// other threads or randomness. It must not be called inside a runtime.
+///
+/// # Example
+/// ```
+/// # use elfo_core as elfo;
+/// # use elfo_test as test;
+/// # use elfo::{message, msg, ActorGroup};
+/// #[message(ret = u32)]
+/// struct Ping;
+///
+/// test::simulate(
+///     0..10,
+///     |topology, proxy| {
+///         let pinger = topology.local("pinger");
+///         proxy.route_all_to(&pinger);
+///         pinger.mount(ActorGroup::new().exec(|mut ctx| async move {
+///             while let Some(envelope) = ctx.recv().await {
+///                 msg!(match envelope {
+///                     (Ping, token) => ctx.respond(token, 42),
+///                 });
+///             }
+///         }));
+///     },
+///     |proxy| async move {
+///         assert_eq!(proxy.request(Ping).await, 42);
+///     },
+/// );
+/// ```
+pub fn simulate<F, Fut>(
+    seeds: impl IntoIterator<Item = u64>,
+    topology_builder: impl Fn(&Topology, &Local<'_>),
+    scenario: F,
+) where
+    F: Fn(Proxy) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    for seed in seeds {
+        let attempt = || run(seed, &topology_builder, &scenario);
+
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(attempt)) {
+            panic!(
+                "simulation failed with seed {seed}: {}",
+                panic_message(&*panic)
+            );
+        }
+    }
+}
+
+fn run<F, Fut>(seed: u64, topology_builder: &impl Fn(&Topology, &Local<'_>), scenario: &F)
+where
+    F: Fn(Proxy) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    // Must outlive the runtime, because actors are dropped with it.
+    let _guard = simulation::enter(seed);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("cannot build a runtime");
+
+    rt.block_on(async {
+        let config = Value::Map(BTreeMap::new());
+        let proxy = proxy_with_topology(config, |topology, testers| {
+            topology_builder(topology, testers);
+            // There is no single tested group.
+            Addr::NULL
+        })
+        .await;
+
+        scenario(proxy).await;
+    });
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "<non-string panic>"
+    }
+}
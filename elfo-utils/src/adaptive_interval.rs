@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+/// Chooses an interval between flushes of buffered data based on the backlog.
+///
+/// The interval starts at `min` and doubles on every flush of an empty
+/// backlog up to `max`, so idle writers wake up rarely. Once something is
+/// flushed, the interval is reset to `min`. A backlog reaching `high_water`
+/// should be flushed immediately, see [`AdaptiveInterval::is_high_water()`].
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    min: Duration,
+    max: Duration,
+    high_water: usize,
+    current: Duration,
+}
+
+impl AdaptiveInterval {
+    /// Creates a new instance starting at `min`.
+    /// If `max` is less than `min`, `min` is used instead.
+    pub fn new(min: Duration, max: Duration, high_water: usize) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            high_water,
+            current: min,
+        }
+    }
+
+    /// Reconfigures bounds, the current interval is clamped to them.
+    pub fn configure(&mut self, min: Duration, max: Duration, high_water: usize) {
+        self.min = min;
+        self.max = max.max(min);
+        self.high_water = high_water;
+        self.current = self.current.clamp(self.min, self.max);
+    }
+
+    /// Returns the current interval.
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Returns `true` if the backlog must be flushed without waiting.
+    #[inline]
+    pub fn is_high_water(&self, backlog: usize) -> bool {
+        backlog >= self.high_water
+    }
+
+    /// Updates the interval after a flush of `flushed` items (bytes, messages
+    /// and so on) and returns the interval until the next one.
+    pub fn on_flush(&mut self, flushed: usize) -> Duration {
+        self.current = if flushed == 0 {
+            (self.current * 2).min(self.max)
+        } else {
+            self.min
+        };
+
+        self.current
+    }
+}
+
+/// A reason of flushing, used as a label of metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushReason {
+    /// The interval is over.
+    Timer,
+    /// The backlog has reached the high-water mark.
+    HighWater,
+    /// Requested explicitly.
+    Explicit,
+    /// The writer is terminating.
+    Shutdown,
+}
+
+impl FlushReason {
+    /// Returns a snake_case name of the reason.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Timer => "timer",
+            Self::HighWater => "high_water",
+            Self::Explicit => "explicit",
+            Self::Shutdown => "shutdown",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn stretch_and_reset() {
+        let mut interval = AdaptiveInterval::new(ms(10), ms(50), 100);
+        assert_eq!(interval.current(), ms(10));
+
+        // Stretched when idle.
+        assert_eq!(interval.on_flush(0), ms(20));
+        assert_eq!(interval.on_flush(0), ms(40));
+        assert_eq!(interval.on_flush(0), ms(50));
+        assert_eq!(interval.on_flush(0), ms(50));
+
+        // Reset once something is written.
+        assert_eq!(interval.on_flush(1), ms(10));
+        assert_eq!(interval.on_flush(0), ms(20));
+
+        assert!(!interval.is_high_water(99));
+        assert!(interval.is_high_water(100));
+    }
+
+    #[test]
+    fn configure() {
+        let mut interval = AdaptiveInterval::new(ms(10), ms(5), 100);
+        assert_eq!(interval.on_flush(0), ms(10));
+
+        interval.configure(ms(10), ms(100), 100);
+        assert_eq!(interval.on_flush(0), ms(20));
+
+        interval.configure(ms(30), ms(100), 100);
+        assert_eq!(interval.current(), ms(30));
+        interval.configure(ms(1), ms(5), 100);
+        assert_eq!(interval.current(), ms(5));
+    }
+}
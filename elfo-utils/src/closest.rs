@@ -0,0 +1,68 @@
+/// Returns the candidate closest to the key by the edit distance, if it's
+/// close enough to be a typo. Used to suggest keys in error messages.
+///
+/// # Example
+/// ```
+/// use elfo_utils::closest;
+///
+/// let known = ["logging", "dumping", "telemetry"];
+/// assert_eq!(closest("loging", known), Some("logging"));
+/// assert_eq!(closest("metrics", known), None);
+/// ```
+pub fn closest<'a>(key: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Allow one typo per three chars, but at least one.
+    let threshold = (key.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("logging", "logging"), 0);
+        assert_eq!(distance("loging", "logging"), 1);
+        assert_eq!(distance("lgoging", "logging"), 2);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggestions() {
+        let known = ["mailbox", "logging", "dumping", "telemetry", "tracing"];
+        assert_eq!(closest("loging", known), Some("logging"));
+        assert_eq!(closest("dumpnig", known), Some("dumping"));
+        assert_eq!(closest("telemtry", known), Some("telemetry"));
+        assert_eq!(closest("network", known), None);
+        assert_eq!(closest("a", ["b", "ab"]), Some("b"));
+        assert_eq!(closest("a", []), None);
+    }
+}
@@ -1,10 +1,14 @@
 //! A collection of utilities to share among elfo-* crates.
 
 pub use self::{
+    adaptive_interval::{AdaptiveInterval, FlushReason},
+    closest::closest,
     likely::*,
     rate_limiter::{RateLimit, RateLimiter},
 };
 
+mod adaptive_interval;
+mod closest;
 mod likely;
 mod rate_limiter;
 pub mod time;
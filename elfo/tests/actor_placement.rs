@@ -0,0 +1,176 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use tokio::runtime::{Builder, Runtime};
+use toml::{toml, Value};
+
+use elfo::{
+    config::AnyConfig,
+    messages::{ActorStatusReport, SubscribeToActorStatuses, UpdateConfig},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    test::Proxy,
+    RuntimeHandle,
+};
+
+#[message]
+struct Job {
+    symbol: u32,
+    seq: u32,
+}
+
+#[message]
+struct Done {
+    seq: u32,
+    thread: String,
+}
+
+#[message(ret = (String, bool, String))]
+struct WhereAreYou {
+    symbol: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    hot: Vec<u32>,
+}
+
+fn runtime(name: &str) -> Runtime {
+    Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name(name)
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn thread_name() -> String {
+    std::thread::current().name().unwrap_or("?").into()
+}
+
+async fn run_group(hot_rt: &Runtime, cold_rt: &Runtime, config: Value) -> Proxy {
+    let hot = RuntimeHandle::new("hot", hot_rt.handle().clone());
+    let cold = RuntimeHandle::new("cold", cold_rt.handle().clone());
+
+    let blueprint = ActorGroup::new()
+        .config::<Config>()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                Job { symbol, .. } | WhereAreYou { symbol } => Outcome::Unicast(*symbol),
+                _ => Outcome::Default,
+            })
+        }))
+        .placement(move |symbol, config| {
+            if config.hot.contains(symbol) {
+                hot.clone()
+            } else {
+                cold.clone()
+            }
+        })
+        .exec(|mut ctx| async move {
+            let is_migrated = ctx.start_info().cause.is_migrated();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Job { seq, .. } => {
+                        let thread = thread_name();
+                        ctx.send(Done { seq, thread }).await.unwrap();
+                    }
+                    (WhereAreYou { .. }, token) => {
+                        ctx.respond(token, (thread_name(), is_migrated, ctx.addr().to_string()));
+                    }
+                });
+            }
+        });
+
+    elfo::test::proxy(blueprint, config).await
+}
+
+#[tokio::test]
+async fn actors_are_placed_by_key() {
+    let hot_rt = runtime("rt-hot");
+    let cold_rt = runtime("rt-cold");
+
+    let mut proxy = run_group(&hot_rt, &cold_rt, toml! { hot = [1] }.into()).await;
+
+    let (thread, is_migrated, _) = proxy.request(WhereAreYou { symbol: 1 }).await;
+    assert_eq!(thread, "rt-hot");
+    assert!(!is_migrated);
+
+    let (thread, _, _) = proxy.request(WhereAreYou { symbol: 2 }).await;
+    assert_eq!(thread, "rt-cold");
+
+    // The placement is exposed in status reports.
+    proxy.send(SubscribeToActorStatuses::default()).await;
+    proxy.sync().await;
+
+    let mut placements = Vec::new();
+    while let Some(envelope) = proxy.try_recv().await {
+        msg!(match envelope {
+            ActorStatusReport { meta, runtime, .. } => placements.push((meta.key.clone(), runtime)),
+            _ => unreachable!(),
+        })
+    }
+    placements.sort();
+    assert_eq!(
+        placements,
+        [
+            ("1".into(), Some("hot".into())),
+            ("2".into(), Some("cold".into()))
+        ]
+    );
+
+    hot_rt.shutdown_background();
+    cold_rt.shutdown_background();
+}
+
+#[tokio::test]
+async fn actors_are_migrated_on_config_update() {
+    const JOBS: u32 = 60;
+
+    let hot_rt = runtime("rt-hot");
+    let cold_rt = runtime("rt-cold");
+
+    let mut proxy = run_group(&hot_rt, &cold_rt, toml! { hot = [1] }.into()).await;
+
+    for seq in 0..JOBS / 2 {
+        proxy.send(Job { symbol: 1, seq }).await;
+    }
+
+    // The symbol is not hot anymore.
+    let config = AnyConfig::deserialize(toml! { hot = [] }).unwrap();
+    proxy.send(UpdateConfig::new(config)).await;
+
+    for seq in JOBS / 2..JOBS {
+        proxy.send(Job { symbol: 1, seq }).await;
+    }
+
+    // All jobs are done in order, firstly on the old runtime.
+    let mut threads = Vec::new();
+    for expected in 0..JOBS {
+        msg!(match proxy.recv().await {
+            Done { seq, thread } => {
+                assert_eq!(seq, expected);
+                threads.push(thread);
+            }
+        });
+    }
+    threads.dedup();
+    assert_eq!(threads, ["rt-hot", "rt-cold"]);
+
+    let (thread, is_migrated, addr) = proxy.request(WhereAreYou { symbol: 1 }).await;
+    assert_eq!(thread, "rt-cold");
+    assert!(is_migrated);
+
+    // Unchanged placement doesn't lead to migration.
+    let config = AnyConfig::deserialize(toml! { hot = [2] }).unwrap();
+    proxy.send(UpdateConfig::new(config)).await;
+
+    let (thread, _, same_addr) = proxy.request(WhereAreYou { symbol: 1 }).await;
+    assert_eq!(thread, "rt-cold");
+    assert_eq!(same_addr, addr);
+
+    hot_rt.shutdown_background();
+    cold_rt.shutdown_background();
+}
@@ -0,0 +1,107 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{fs, path::Path, time::Duration};
+
+use serde::Deserialize;
+use toml::toml;
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::logger::FlushLogs,
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = ())]
+struct Log(String);
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log(text), token) => {
+                    info!("{text}");
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+fn is_written(path: &Path, text: &str) -> bool {
+    fs::read_to_string(path).unwrap_or_default().contains(text)
+}
+
+#[tokio::test(start_paused = true)]
+async fn logger() {
+    let path = std::env::temp_dir().join(format!("elfo-adaptive-flush-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path_str = path.to_str().unwrap();
+
+    let config = AnyConfig::deserialize(toml! {
+        [system.loggers]
+        sink = "File"
+        path = path_str
+        flush = { min_interval = "1s", max_interval = "1h", high_water = "1KiB" }
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let loggers_addr = loggers.addr();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    let idle = Duration::from_secs(10 * 3600);
+    let path_1 = path.clone();
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let path = path_1;
+        let log = |text: String| ctx.request_to(subject_addr, Log(text)).resolve();
+
+        // The interval is stretched to `max_interval` when idle.
+        tokio::time::sleep(idle).await;
+        log("lonely".into()).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(!is_written(&path, "lonely"));
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        assert!(is_written(&path, "lonely"));
+
+        // Bursts are written without waiting for the timer.
+        tokio::time::sleep(idle).await;
+        for i in 0..20 {
+            log(format!("burst-{i} {}", "x".repeat(100))).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(is_written(&path, "burst-0 "));
+
+        // Explicit flushes.
+        tokio::time::sleep(idle).await;
+        log("explicit".into()).await.unwrap();
+        ctx.request_to(loggers_addr, FlushLogs::default())
+            .resolve()
+            .await
+            .unwrap();
+        assert!(is_written(&path, "explicit"));
+
+        log("final".into()).await.unwrap();
+        terminate(ctx, topology).await;
+    })
+    .await
+    .expect("cannot start");
+
+    let logs = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(logs.contains("final"), "{logs}");
+    assert!(logs.contains("burst-19 "), "{logs}");
+}
@@ -0,0 +1,215 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::{toml, Value};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    admission::Admission,
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::{StartEntrypoint, UpdateConfig},
+    prelude::*,
+    Addr, Topology,
+};
+
+mod common;
+
+const OVERLOADED: &str = "too many pending orders";
+const CLOSED: &str = "orders are closed";
+
+#[message(ret = u32)]
+struct PlaceOrder(u32);
+
+#[message]
+struct CancelOrder(u32);
+
+#[message(ret = ())]
+struct Freeze;
+
+#[message(ret = Vec<(String, bool)>)]
+struct GetHandled;
+
+fn subject() -> Blueprint {
+    ActorGroup::new()
+        // New orders are degraded under load and rejected under overload,
+        // while cancels are always admitted, because they reduce the load.
+        .admission("orders", |envelope, stats| {
+            if !envelope.message().is::<PlaceOrder>() {
+                Admission::Admit
+            } else if stats.len >= 4 {
+                Admission::Reject(OVERLOADED)
+            } else if stats.len >= 2 {
+                Admission::Degrade
+            } else {
+                Admission::Admit
+            }
+        })
+        .admission("closed", |envelope, _| {
+            if envelope.message().is::<PlaceOrder>() {
+                Admission::Reject(CLOSED)
+            } else {
+                Admission::Admit
+            }
+        })
+        .exec(|mut ctx| async move {
+            let mut handled = Vec::new();
+
+            while let Some(envelope) = ctx.recv().await {
+                let is_degraded = envelope.is_degraded();
+
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Freeze, token) => {
+                        ctx.respond(token, ());
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+                    (PlaceOrder(id), token) => {
+                        handled.push((format!("place {id}"), is_degraded));
+                        ctx.respond(token, id);
+                    }
+                    CancelOrder(id) => handled.push((format!("cancel {id}"), is_degraded)),
+                    (GetHandled, token) => ctx.respond(token, std::mem::take(&mut handled)),
+                });
+            }
+        })
+}
+
+fn policy_config(policy: &str) -> AnyConfig {
+    let policy = Value::from(policy);
+    AnyConfig::deserialize(toml! {
+        system.mailbox.admission = policy
+    })
+    .unwrap()
+}
+
+fn topology() -> (Topology, Addr) {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    subject.mount(self::subject());
+
+    (topology, subject_addr)
+}
+
+#[tokio::test(start_paused = true)]
+async fn reject_and_degrade() {
+    common::setup_logger();
+
+    let (topology, subject) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        ctx.request_to(subject, Freeze).resolve().await.unwrap();
+
+        for id in 1..=4 {
+            ctx.try_send_to(subject, PlaceOrder(id)).unwrap();
+        }
+
+        // `try_send()`
+        let err = ctx
+            .detailed()
+            .try_send_to(subject, PlaceOrder(5))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Rejected);
+        assert!(err.is_closed());
+        assert_eq!(err.context().rejection, Some(OVERLOADED));
+        assert_eq!(err.context().group.as_deref(), Some("subject"));
+        assert!(
+            err.to_string()
+                .ends_with("rejected (too many pending orders)"),
+            "{err}"
+        );
+        assert_eq!(err.into_inner().0, 5);
+
+        // `send()`
+        let err = ctx
+            .detailed()
+            .send_to(subject, PlaceOrder(6))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Rejected);
+        assert_eq!(err.context().rejection, Some(OVERLOADED));
+        assert_eq!(err.into_inner().0, 6);
+
+        // `request()`
+        let err = ctx
+            .request_to(subject, PlaceOrder(7))
+            .resolve_detailed()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Rejected);
+        assert!(err.is_failed());
+        assert_eq!(err.context().rejection, Some(OVERLOADED));
+
+        // Cancels get through the overloaded mailbox.
+        ctx.try_send_to(subject, CancelOrder(1)).unwrap();
+        ctx.send_to(subject, CancelOrder(2)).await.unwrap();
+
+        // Degraded envelopes are marked for the receiver.
+        let handled = ctx.request_to(subject, GetHandled).resolve().await.unwrap();
+        let expected = [
+            ("place 1", false),
+            ("place 2", false),
+            ("place 3", true),
+            ("place 4", true),
+            ("cancel 1", false),
+            ("cancel 2", false),
+        ];
+        assert_eq!(
+            handled,
+            expected.map(|(name, is_degraded)| (name.to_string(), is_degraded))
+        );
+
+        // The mailbox is empty, so orders are admitted again.
+        assert_eq!(
+            ctx.request_to(subject, PlaceOrder(8))
+                .resolve()
+                .await
+                .unwrap(),
+            8
+        );
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn reconfiguration() {
+    let proxy = elfo::test::proxy(subject(), AnyConfig::default()).await;
+
+    // The first registered policy is used by default.
+    assert!(proxy.try_send(PlaceOrder(1)).is_ok());
+
+    proxy.send(UpdateConfig::new(policy_config("closed"))).await;
+    // Rejections are reported as `Closed` by the plain API.
+    assert!(proxy.try_send(PlaceOrder(2)).unwrap_err().is_closed());
+    assert!(proxy.try_send(CancelOrder(2)).is_ok());
+
+    proxy.send(UpdateConfig::new(policy_config("none"))).await;
+    assert!(proxy.try_send(PlaceOrder(3)).is_ok());
+
+    // Unknown policies are rejected, the current one is kept.
+    let res = proxy
+        .request(UpdateConfig::new(policy_config("unknown")))
+        .await;
+    assert!(res.unwrap_err().reason.contains("unknown"));
+    assert!(proxy.try_send(PlaceOrder(4)).is_ok());
+
+    let handled = proxy.request(GetHandled).await;
+    let handled = handled.iter().map(|(name, _)| name.as_str());
+    assert_eq!(
+        handled.collect::<Vec<_>>(),
+        ["place 1", "cancel 2", "place 3", "place 4"]
+    );
+}
@@ -0,0 +1,152 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde_json::Value;
+use toml::toml;
+
+use elfo::{audit::AuditConfig, config::AnyConfig, prelude::*, tracing::TraceId};
+
+#[message(ret = u64, dumping = "disabled")]
+struct PlaceOrder {
+    qty: u32,
+}
+
+#[message]
+struct Note(u32);
+
+#[message]
+struct Crash;
+
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("elfo-audit-{}", std::process::id()));
+    let path = dir.join(name).join("risk.audit");
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn records(path: &Path) -> Vec<Value> {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn sample(path: &Path, include_response: bool) -> Blueprint {
+    ActorGroup::new()
+        .audit(AuditConfig {
+            path: path.into(),
+            sync: true,
+            include_response,
+        })
+        .exec(|mut ctx| async move {
+            let mut next_id = 1;
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (PlaceOrder { .. }, token) => {
+                        ctx.respond(token, next_id);
+                        next_id += 1;
+                    }
+                    Note(_) => {}
+                    Crash => panic!("boom"),
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn record_is_written_before_response() {
+    let path = temp_path("before_response");
+    let proxy = elfo::test::proxy(sample(&path, true), AnyConfig::default()).await;
+
+    for expected_id in 1..=3 {
+        let id = proxy.request(PlaceOrder { qty: 10 }).await;
+        assert_eq!(id, expected_id);
+
+        // The response is received, so the record must be already written.
+        let records = records(&path);
+        assert_eq!(records.len() as u64, expected_id);
+
+        let record = records.last().unwrap();
+        assert_eq!(record["mn"], "PlaceOrder");
+        assert_eq!(record["m"]["qty"], 10);
+        assert_eq!(record["from"]["group"], "system.testers");
+        assert_eq!(record["outcome"]["response"]["m"], expected_id);
+        // A number or a hex string, depending on the `trace-id-128` feature.
+        assert!(serde_json::from_value::<TraceId>(record["t"].clone()).is_ok());
+        assert!(record["ts"].is_number());
+    }
+}
+
+#[tokio::test]
+async fn responses_are_optional() {
+    let path = temp_path("without_response");
+    let proxy = elfo::test::proxy(sample(&path, false), AnyConfig::default()).await;
+
+    proxy.request(PlaceOrder { qty: 1 }).await;
+
+    let records = records(&path);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["outcome"], "responded");
+}
+
+#[tokio::test]
+async fn dumping_settings_are_ignored() {
+    let path = temp_path("dumping");
+    let config = toml! {
+        [system.dumping]
+        disabled = true
+        max_rate = 1
+    };
+    let proxy = elfo::test::proxy(sample(&path, true), config).await;
+
+    for i in 0..10 {
+        proxy.send(Note(i)).await;
+    }
+    proxy.request(PlaceOrder { qty: 1 }).await;
+
+    assert!(proxy.dumps().group("subject").is_empty());
+
+    let records = records(&path);
+    let notes = records
+        .iter()
+        .filter(|r| r["mn"] == "Note")
+        .map(|r| {
+            assert_eq!(r["outcome"], "ok");
+            r["m"].as_u64().unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(notes, (0..10).collect::<Vec<_>>());
+    assert_eq!(records.last().unwrap()["mn"], "PlaceOrder");
+}
+
+#[tokio::test]
+async fn panic_is_recorded() {
+    let path = temp_path("panic");
+    let proxy = elfo::test::proxy(sample(&path, true), AnyConfig::default()).await;
+
+    proxy.send(Note(1)).await;
+    proxy.send(Crash).await;
+    proxy.finished().await;
+
+    // The record is written right after the actor is finished.
+    let mut records = records(&path);
+    for _ in 0..100 {
+        if records.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        records = self::records(&path);
+    }
+
+    assert_eq!(records[0]["outcome"], "ok");
+    assert_eq!(records[1]["mn"], "Crash");
+    assert_eq!(records[1]["outcome"]["panic"], "boom");
+}
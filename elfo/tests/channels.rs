@@ -0,0 +1,187 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::sync::Notify;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer,
+    channel::{Channel, ChannelClosed, ChannelOpened, CloseReason},
+    config::AnyConfig,
+    errors::TrySendError,
+    prelude::*,
+    Addr, Context, Envelope, Message, Topology,
+};
+
+#[message]
+#[derive(PartialEq)]
+struct Order(u32);
+
+#[message]
+#[derive(PartialEq)]
+struct Fill(u32);
+
+#[message(ret = Option<CloseReason>)]
+struct GetCloseReason;
+
+/// Orders:
+/// * `0` stops reading until `resume` is notified.
+/// * `u32::MAX` terminates the actor.
+///
+/// Other ones are filled with `no * 10`.
+fn venue(resume: Arc<Notify>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let resume = resume.clone();
+        async move {
+            let mut fills = None::<Channel<Fill, Order>>;
+            let mut close_reason = None;
+
+            while let Some(envelope) = ctx.recv().await {
+                let channel_id = envelope.channel_id();
+
+                msg!(match envelope {
+                    (opened @ ChannelOpened { .. }, token) => {
+                        fills = opened.take();
+                        ctx.respond(token, ());
+                    }
+                    Order(no) => {
+                        let fills = fills.as_ref().unwrap();
+                        assert_eq!(channel_id, Some(fills.id()));
+
+                        match no {
+                            0 => resume.notified().await,
+                            u32::MAX => break,
+                            _ => {}
+                        }
+
+                        fills.send(Fill(no * 10)).await.unwrap();
+                    }
+                    ChannelClosed { id, reason } => {
+                        let fills = fills.as_ref().unwrap();
+                        assert_eq!(id, fills.id());
+                        assert!(fills.is_closed());
+                        assert!(fills.send(Fill(0)).await.is_err());
+                        close_reason = Some(reason);
+                    }
+                    (GetCloseReason, token) => ctx.respond(token, close_reason.clone()),
+                });
+            }
+        }
+    })
+}
+
+async fn recv<M: Message>(ctx: &mut Context) -> (M, Envelope) {
+    let envelope = ctx.recv().await.unwrap();
+    let message = envelope.message().downcast_ref::<M>().cloned();
+    let message = message.unwrap_or_else(|| panic!("unexpected {envelope:?}"));
+    (message, envelope)
+}
+
+async fn run<F>(f: impl FnOnce(Context, Addr, Arc<Notify>) -> F)
+where
+    F: Future<Output = Context>,
+{
+    let resume = Arc::new(Notify::new());
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let venue = topology.local("venue");
+    let venue_addr = venue.addr();
+
+    configurers.mount(configurer::fixture(&topology, AnyConfig::default()));
+    venue.mount(self::venue(resume.clone()));
+
+    do_start(topology, false, |ctx, topology| async move {
+        let ctx = f(ctx, venue_addr, resume).await;
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn open_and_exchange() {
+    run(|mut ctx, venue, _| async move {
+        let orders = ctx.open_channel::<Order, Fill>(venue).await.unwrap();
+        assert!(!orders.is_closed());
+
+        for no in 1..=3 {
+            orders.send(Order(no)).await.unwrap();
+        }
+
+        for no in 1..=3 {
+            let (fill, envelope) = recv::<Fill>(&mut ctx).await;
+            assert_eq!(fill, Fill(no * 10));
+            assert_eq!(envelope.channel_id(), Some(orders.id()));
+            assert_eq!(envelope.sender(), orders.peer());
+        }
+
+        orders.close("session is over");
+
+        let reason = ctx.request_to(venue, GetCloseReason).resolve().await;
+        assert_eq!(
+            reason.unwrap(),
+            Some(CloseReason::Closed("session is over".into()))
+        );
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn backpressure() {
+    run(|mut ctx, venue, resume| async move {
+        let orders = ctx
+            .open_channel::<Order, Fill>(venue)
+            .window(2)
+            .await
+            .unwrap();
+
+        // The venue stops reading after the first order.
+        for no in 0..=2 {
+            orders.send(Order(no)).await.unwrap();
+        }
+
+        // The window is exhausted.
+        assert!(matches!(
+            orders.try_send(Order(3)),
+            Err(TrySendError::Full(Order(3)))
+        ));
+        let send = tokio::time::timeout(Duration::from_secs(5), orders.send(Order(3)));
+        assert!(send.await.is_err());
+
+        // The credit is returned once the venue handles an order.
+        resume.notify_one();
+        orders.send(Order(3)).await.unwrap();
+
+        // Orders are filled in the sending order.
+        for no in 0..=3 {
+            assert_eq!(recv::<Fill>(&mut ctx).await.0, Fill(no * 10));
+        }
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn close_on_termination() {
+    run(|mut ctx, venue, _| async move {
+        let orders = ctx.open_channel::<Order, Fill>(venue).await.unwrap();
+        orders.send(Order(u32::MAX)).await.unwrap();
+
+        let (closed, _) = recv::<ChannelClosed>(&mut ctx).await;
+        assert_eq!(closed.id, orders.id());
+        assert_eq!(closed.reason, CloseReason::Dropped);
+
+        assert!(orders.is_closed());
+        assert!(orders.send(Order(1)).await.is_err());
+        assert!(matches!(
+            orders.try_send(Order(1)),
+            Err(TrySendError::Closed(Order(1)))
+        ));
+        ctx
+    })
+    .await;
+}
@@ -0,0 +1,158 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::{CircuitEdge, CircuitState, SetCircuit},
+    prelude::*,
+    test::Proxy,
+};
+
+#[message]
+struct Call;
+
+#[message(ret = ())]
+struct Probe;
+
+#[message]
+struct Notify;
+
+#[message]
+#[derive(PartialEq)]
+struct Outcome(String);
+
+async fn run_group(config: AnyConfig) -> Proxy {
+    let blueprint = ActorGroup::new().exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Call => {
+                    let outcome = match ctx.request(Probe).resolve_detailed().await {
+                        Ok(()) => "ok",
+                        Err(err) => match err.kind() {
+                            ErrorKind::Ignored => "ignored",
+                            ErrorKind::CircuitOpen => "circuit open",
+                            _ => panic!("unexpected error: {err}"),
+                        },
+                    };
+                    ctx.send(Outcome(outcome.into())).await.unwrap();
+                }
+                Notify => ctx.send(Notify).await.unwrap(),
+            });
+        }
+    });
+
+    elfo::test::proxy(blueprint, config).await
+}
+
+fn config() -> AnyConfig {
+    AnyConfig::deserialize(toml! {
+        [system.circuit_breaker.destinations."system.testers"]
+        failure_ratio = 0.6
+        min_requests = 2
+        open_duration = "10s"
+    })
+    .unwrap()
+}
+
+// Calls the `Probe` request, the proxy plays a role of the responder.
+async fn call(proxy: &mut Proxy, is_success: bool) -> String {
+    proxy.send(Call).await;
+
+    let envelope = proxy.recv().await;
+    let envelope = msg!(match envelope {
+        (Probe, token) => {
+            if is_success {
+                proxy.respond(token, ());
+            } else {
+                drop(token);
+            }
+            proxy.recv().await
+        }
+        envelope => envelope,
+    });
+
+    msg!(match envelope {
+        Outcome(outcome) => outcome,
+        envelope => panic!("unexpected message: {:?}", envelope.message()),
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn open_half_open_closed() {
+    let mut proxy = run_group(config()).await;
+
+    assert_eq!(call(&mut proxy, true).await, "ok");
+    assert_eq!(call(&mut proxy, false).await, "ignored");
+    assert_eq!(call(&mut proxy, false).await, "ignored");
+
+    // The circuit is open, requests aren't delivered.
+    assert_eq!(call(&mut proxy, true).await, "circuit open");
+    assert_eq!(call(&mut proxy, true).await, "circuit open");
+
+    // Regular messages aren't affected.
+    proxy.send(Notify).await;
+    assert_msg!(proxy.recv().await, Notify);
+
+    // A failed probe opens the circuit again.
+    tokio::time::sleep(Duration::from_secs(11)).await;
+    assert_eq!(call(&mut proxy, false).await, "ignored");
+    assert_eq!(call(&mut proxy, true).await, "circuit open");
+
+    // A successful probe closes the circuit.
+    tokio::time::sleep(Duration::from_secs(11)).await;
+    assert_eq!(call(&mut proxy, true).await, "ok");
+    assert_eq!(call(&mut proxy, true).await, "ok");
+
+    // Old failures are forgotten.
+    assert_eq!(call(&mut proxy, false).await, "ignored");
+    assert_eq!(call(&mut proxy, true).await, "ok");
+}
+
+#[tokio::test(start_paused = true)]
+async fn unconfigured() {
+    let mut proxy = run_group(AnyConfig::default()).await;
+
+    for _ in 0..10 {
+        assert_eq!(call(&mut proxy, false).await, "ignored");
+    }
+    assert_eq!(call(&mut proxy, true).await, "ok");
+}
+
+#[tokio::test(start_paused = true)]
+async fn forced() {
+    let mut proxy = run_group(AnyConfig::default()).await;
+    let edge = || CircuitEdge::new("subject".into(), "system.testers".into());
+
+    proxy
+        .send(SetCircuit::new(edge(), Some(CircuitState::Open)))
+        .await;
+    assert_eq!(call(&mut proxy, true).await, "circuit open");
+
+    // Edges of other groups are ignored.
+    let another = CircuitEdge::new("another".into(), "system.testers".into());
+    proxy.send(SetCircuit::new(another, None)).await;
+    assert_eq!(call(&mut proxy, true).await, "circuit open");
+
+    proxy.send(SetCircuit::new(edge(), None)).await;
+    assert_eq!(call(&mut proxy, true).await, "ok");
+}
+
+#[tokio::test(start_paused = true)]
+async fn forced_closed() {
+    let mut proxy = run_group(config()).await;
+    let edge = CircuitEdge::new("subject".into(), "system.testers".into());
+
+    proxy
+        .send(SetCircuit::new(edge, Some(CircuitState::Closed)))
+        .await;
+
+    for _ in 0..5 {
+        assert_eq!(call(&mut proxy, false).await, "ignored");
+    }
+}
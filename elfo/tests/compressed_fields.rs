@@ -0,0 +1,286 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "network", feature = "compression", feature = "test-util"))]
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use metrics::{GaugeValue, Key, Recorder, Unit};
+use tokio::sync::mpsc;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    topology, Compressed, Topology,
+};
+
+mod common;
+
+#[message]
+#[derive(PartialEq)]
+struct Snapshot {
+    version: u64,
+    data: Compressed<Vec<u8>>,
+}
+
+#[message(ret = Received)]
+struct Upload(Snapshot);
+
+#[message]
+struct Received {
+    /// `Debug` of the snapshot before the first access.
+    debug: String,
+    snapshot: Snapshot,
+}
+
+#[message]
+struct Dumped(Snapshot);
+
+#[message(dumping = "disabled")]
+struct Passed(Snapshot);
+
+fn snapshot(version: u64) -> Snapshot {
+    let data = (0..100_000u32)
+        .map(|i| (i / 1000) as u8)
+        .collect::<Vec<_>>();
+    let data = Compressed::new(data);
+    Snapshot { version, data }
+}
+
+// === Metrics ===
+
+/// Counts compressed bytes by names of metrics and messages.
+#[derive(Default)]
+struct ByteCounter(Mutex<HashMap<(String, String), u64>>);
+
+impl Recorder for ByteCounter {
+    fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+    fn record_histogram(&self, _: &Key, _: f64) {}
+
+    fn increment_counter(&self, key: &Key, value: u64) {
+        if !key.name().starts_with("elfo_compression_") {
+            return;
+        }
+
+        let message = key.labels().find(|l| l.key() == "message").unwrap();
+        *self
+            .0
+            .lock()
+            .unwrap()
+            .entry((key.name().into(), message.value().into()))
+            .or_default() += value;
+    }
+}
+
+fn counter() -> &'static ByteCounter {
+    static COUNTER: OnceLock<&'static ByteCounter> = OnceLock::new();
+
+    COUNTER.get_or_init(|| {
+        let counter = Box::leak(Box::<ByteCounter>::default());
+        metrics::set_recorder(counter).unwrap();
+        counter
+    })
+}
+
+/// Returns compressed (input, output) bytes of the message.
+fn compressed(message: &str) -> (u64, u64) {
+    let bytes = counter().0.lock().unwrap();
+    let get = |name: &str| {
+        let key = (name.to_string(), message.to_string());
+        bytes.get(&key).copied().unwrap_or(0)
+    };
+    (
+        get("elfo_compression_input_bytes_total"),
+        get("elfo_compression_output_bytes_total"),
+    )
+}
+
+// === Tests ===
+
+fn uploads() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Upload(snapshot), token) => {
+                    let debug = format!("{:?}", snapshot.data);
+                    snapshot.data.get();
+                    ctx.respond(token, Received { debug, snapshot });
+                }
+            });
+        }
+    })
+}
+
+fn uploaders(tx: mpsc::UnboundedSender<Received>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let tx = tx.clone();
+        async move {
+            let Some(envelope) = ctx.recv().await else {
+                return;
+            };
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                _ => unreachable!(),
+            });
+
+            // Wait for the connection.
+            let received = loop {
+                if let Ok(received) = ctx.request(Upload(snapshot(1))).resolve().await {
+                    break received;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+
+            let _ = tx.send(received);
+        }
+    })
+}
+
+#[tokio::test]
+async fn network_roundtrip() {
+    common::setup_logger();
+    counter();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let uploads = server.local("uploads").entrypoint();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://compressed_fields"]
+        },
+    ));
+    uploads.mount(self::uploads());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let uploaders = client.local("uploaders").entrypoint();
+    let uploads = client.remote("uploads");
+
+    uploaders.route_to(&uploads, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [common]
+            system.compression.algorithm = "Deflate"
+
+            [system.network]
+            discovery.predefined = ["inproc://compressed_fields"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    uploaders.mount(self::uploaders(tx));
+
+    let received = do_start(server, false, |ctx, server| async move {
+        let res = do_start(client, false, |ctx, client| async move {
+            let res = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await;
+            terminate(ctx, client).await;
+            res
+        })
+        .await;
+        terminate(ctx, server).await;
+        res
+    })
+    .await
+    .expect("cannot start server")
+    .expect("cannot start client")
+    .expect("timeout")
+    .unwrap();
+
+    // Decompressed lazily by the config of the sender.
+    assert!(
+        received.debug.starts_with("<compressed by Deflate"),
+        "{}",
+        received.debug
+    );
+    assert_eq!(received.snapshot, snapshot(1));
+
+    let (input, output) = compressed("Upload");
+    assert!(input >= 100_000, "{input}");
+    assert!(output * 10 < input, "{output}");
+
+    // Forwarded as received.
+    assert_eq!(compressed("Received"), (0, 0));
+}
+
+#[cfg(feature = "dumping")]
+fn dumper() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Dumped(snapshot) => ctx.send(Dumped(snapshot)).await.unwrap(),
+            });
+        }
+    })
+}
+
+#[cfg(feature = "dumping")]
+#[tokio::test]
+async fn dump_roundtrip() {
+    use elfo::{compression::Algorithm, test::Direction};
+
+    counter();
+
+    let mut proxy = elfo::test::proxy(dumper(), AnyConfig::default()).await;
+    let mut sent = snapshot(2);
+    sent.data = sent.data.with_algorithm(Algorithm::Deflate, 9);
+    proxy.send(Dumped(sent)).await;
+    assert_msg!(proxy.recv().await, Dumped(_));
+
+    let dumps = proxy.dumps().direction(Direction::Out).group("subject");
+    let dumped = dumps.messages::<Dumped>();
+    assert_eq!(dumped.len(), 1);
+
+    // Decompressed lazily by the algorithm of the field.
+    let debug = format!("{:?}", dumped[0].0.data);
+    assert!(debug.starts_with("<compressed by Deflate"), "{debug}");
+    assert_eq!(dumped[0].0, snapshot(2));
+
+    let (input, output) = compressed("Dumped");
+    assert!(input >= 100_000, "{input}");
+    assert!(output * 10 < input, "{output}");
+}
+
+#[tokio::test]
+async fn local_pass_through() {
+    counter();
+
+    let blueprint = ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Passed(snapshot) => ctx.send(Passed(snapshot)).await.unwrap(),
+            });
+        }
+    });
+
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+    let sent = snapshot(3);
+    proxy.send(Passed(sent.clone())).await;
+    let received = msg!(match proxy.recv().await {
+        Passed(snapshot) => snapshot,
+        _ => unreachable!(),
+    });
+
+    // The same value, neither compressed nor copied.
+    assert_eq!(received.data.get().as_ptr(), sent.data.get().as_ptr());
+    assert_eq!(compressed("Passed"), (0, 0));
+}
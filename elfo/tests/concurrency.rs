@@ -0,0 +1,187 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use elfo::{
+    config::AnyConfig,
+    messages::{ConfigUpdated, UpdateConfig},
+    prelude::*,
+    Concurrency,
+};
+
+#[message]
+struct Work {
+    id: u32,
+    delay: Duration,
+    panic: bool,
+}
+
+impl Work {
+    fn new(id: u32, delay: Duration) -> Self {
+        Self {
+            id,
+            delay,
+            panic: false,
+        }
+    }
+}
+
+#[message]
+struct Done {
+    id: u32,
+}
+
+#[derive(Default)]
+struct Stats {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    finished: AtomicUsize,
+    finished_before_barrier: AtomicUsize,
+}
+
+fn testee(concurrency: impl Into<Concurrency>, stats: Arc<Stats>) -> Blueprint {
+    ActorGroup::new()
+        .concurrency(concurrency)
+        .exec(move |mut ctx| {
+            let stats = stats.clone();
+
+            async move {
+                let pruned = ctx.pruned();
+
+                while let Some(envelope) = ctx
+                    .recv_concurrent(|envelope| {
+                        let ctx = pruned.clone();
+                        let stats = stats.clone();
+
+                        async move {
+                            let sender = envelope.sender();
+                            let work = msg!(match envelope {
+                                work @ Work => work,
+                                _ => unreachable!(),
+                            });
+
+                            let in_flight = stats.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            stats.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                            tokio::time::sleep(work.delay).await;
+                            stats.in_flight.fetch_sub(1, Ordering::SeqCst);
+                            stats.finished.fetch_add(1, Ordering::SeqCst);
+
+                            assert!(!work.panic, "oops");
+                            let _ = ctx.send_to(sender, Done { id: work.id }).await;
+                        }
+                    })
+                    .await
+                {
+                    msg!(match envelope {
+                        ConfigUpdated => {
+                            let finished = stats.finished.load(Ordering::SeqCst);
+                            stats
+                                .finished_before_barrier
+                                .store(finished, Ordering::SeqCst);
+                        }
+                    });
+                }
+            }
+        })
+}
+
+#[tokio::test(start_paused = true)]
+async fn limit() {
+    let stats = Arc::new(Stats::default());
+    let mut proxy = elfo::test::proxy(testee(3, stats.clone()), AnyConfig::default()).await;
+
+    for id in 0..6 {
+        let delay = Duration::from_millis(10 * (6 - u64::from(id)));
+        proxy.send(Work::new(id, delay)).await;
+    }
+
+    let mut completed = Vec::new();
+    for _ in 0..6 {
+        completed.push(msg!(match proxy.recv().await {
+            Done { id } => id,
+            _ => unreachable!(),
+        }));
+    }
+
+    assert_eq!(stats.max_in_flight.load(Ordering::SeqCst), 3);
+
+    // Completions are out of order.
+    assert_ne!(completed, [0, 1, 2, 3, 4, 5]);
+    completed.sort();
+    assert_eq!(completed, [0, 1, 2, 3, 4, 5]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn system_messages_are_barriers() {
+    let stats = Arc::new(Stats::default());
+    let mut proxy = elfo::test::proxy(testee(16, stats.clone()), AnyConfig::default()).await;
+
+    for id in 0..3 {
+        proxy.send(Work::new(id, Duration::from_secs(1))).await;
+    }
+    proxy.send(UpdateConfig::new(AnyConfig::default())).await;
+    proxy.send(Work::new(3, Duration::from_millis(10))).await;
+
+    let mut completed = Vec::new();
+    for _ in 0..4 {
+        completed.push(msg!(match proxy.recv().await {
+            Done { id } => id,
+            _ => unreachable!(),
+        }));
+    }
+
+    // `ConfigUpdated` is handled only once all in-flight handlers are finished,
+    // and the next message isn't handled before it despite a shorter delay.
+    assert_eq!(stats.finished_before_barrier.load(Ordering::SeqCst), 3);
+    assert_eq!(completed.pop(), Some(3));
+}
+
+#[tokio::test(start_paused = true)]
+async fn panics_are_isolated() {
+    let stats = Arc::new(Stats::default());
+    let mut proxy = elfo::test::proxy(testee(4, stats.clone()), AnyConfig::default()).await;
+    // The backtrace of the panic is resolved in a blocking thread, which stops
+    // the paused clock from advancing, and it can take long under load.
+    proxy.set_recv_timeout(Duration::from_secs(30));
+
+    let mut work = Work::new(0, Duration::from_millis(10));
+    work.panic = true;
+    proxy.send(work).await;
+    proxy.send(Work::new(1, Duration::from_millis(20))).await;
+
+    msg!(match proxy.recv().await {
+        Done { id } => assert_eq!(id, 1),
+    });
+
+    // The actor is still alive.
+    proxy.send(Work::new(2, Duration::from_millis(10))).await;
+    msg!(match proxy.recv().await {
+        Done { id } => assert_eq!(id, 2),
+    });
+    assert_eq!(stats.finished.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn panics_are_propagated() {
+    let stats = Arc::new(Stats::default());
+    let concurrency = Concurrency::new(4).propagate_panics();
+    let proxy = elfo::test::proxy(testee(concurrency, stats.clone()), AnyConfig::default()).await;
+
+    let mut work = Work::new(0, Duration::from_millis(10));
+    work.panic = true;
+    proxy.send(work).await;
+    proxy.send(Work::new(1, Duration::from_secs(10))).await;
+
+    // Siblings are dropped along with the actor.
+    tokio::time::timeout(Duration::from_secs(1), proxy.finished())
+        .await
+        .expect("the actor must fail");
+    assert_eq!(stats.finished.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,129 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::{AnyConfig, Secret},
+    prelude::*,
+    Topology,
+};
+
+mod common;
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Config {
+    password: Secret<String>,
+    shards: Vec<Shard>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Shard {
+    host: String,
+    port: u16,
+}
+
+fn topology(config: AnyConfig) -> Topology {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let producers = topology.local("producers");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    producers.mount(
+        ActorGroup::new()
+            .config::<Config>()
+            .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} }),
+    );
+
+    topology
+}
+
+async fn start(config: toml::Table) -> String {
+    let config = AnyConfig::deserialize(config).unwrap();
+    let error = do_start(topology(config), false, terminate)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.errors.len(), 1);
+    let error = error.errors.into_iter().next().unwrap();
+    assert_eq!(error.group, "producers");
+    error.reason
+}
+
+fn shards(count: u16) -> toml::Value {
+    let shards = (0..count)
+        .map(|i| toml! { host = "localhost" port = i }.into())
+        .collect::<Vec<toml::Value>>();
+    shards.into()
+}
+
+#[tokio::test]
+async fn bounded_and_redacted() {
+    common::setup_logger();
+
+    let mut config = toml! {
+        [producers]
+        password = "hunter2"
+        api_token = "abcdef"
+    };
+    let mut shards = shards(1000);
+    if let toml::Value::Array(shards) = &mut shards {
+        shards[500] = toml! { host = "localhost" }.into();
+    }
+    config["producers"]
+        .as_table_mut()
+        .unwrap()
+        .insert("shards".into(), shards);
+
+    let reason = start(config).await;
+
+    // The path and the serde message are preserved.
+    assert!(
+        reason.starts_with("missing field `port` at `shards.500`, got "),
+        "{reason}"
+    );
+    assert!(reason.ends_with(r#"got {"host": "localhost"}"#), "{reason}");
+    assert!(!reason.contains("hunter2"), "{reason}");
+    assert!(!reason.contains("abcdef"), "{reason}");
+}
+
+#[tokio::test]
+async fn limits_are_configurable() {
+    common::setup_logger();
+
+    let mut config = toml! {
+        [producers]
+        password = 42
+        system.config_rejection.max_size = "64B"
+        system.config_rejection.max_depth = 2
+        system.config_rejection.redacted_keys = []
+    };
+    let producers = config["producers"].as_table_mut().unwrap();
+    producers.insert("shards".into(), shards(1000));
+
+    // Secret fields are redacted regardless of `redacted_keys`.
+    let reason = start(config.clone()).await;
+    assert_eq!(
+        reason,
+        "invalid type: integer `42`, expected a string at `password`, got \"<secret>\""
+    );
+
+    // The whole config is embedded if the offending value is the root.
+    let producers = config["producers"].as_table_mut().unwrap();
+    producers.remove("password");
+    producers.insert("api_token".into(), "abcdef".into());
+
+    let reason = start(config).await;
+    assert!(
+        reason.starts_with(
+            r#"missing field `password`, got {"api_token": "abcdef", "shards": ["…", "…", "#
+        ),
+        "{reason}"
+    );
+    assert!(reason.ends_with(r#", "…"]}"#), "{reason}");
+    assert!(reason.len() < 200, "{reason}");
+}
@@ -0,0 +1,184 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer::{self, ReloadConfigs},
+    messages::{ConfigUpdated, StartEntrypoint},
+    prelude::*,
+    Topology,
+};
+
+mod common;
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    version: u32,
+}
+
+#[message(ret = u32)]
+struct GetVersion;
+
+#[message]
+struct Stall(Duration);
+
+/// What consumers have seen on `ConfigUpdated`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Seen {
+    own: u32,
+    storage: u32,
+    at: Instant,
+}
+
+type Log = Arc<Mutex<Vec<Seen>>>;
+
+fn storage() -> Blueprint {
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (GetVersion, token) => ctx.respond(token, ctx.config().version),
+                    Stall(duration) => tokio::time::sleep(duration).await,
+                    _ => {}
+                });
+            }
+        })
+}
+
+fn consumers(log: Log) -> Blueprint {
+    ActorGroup::new().config::<Config>().exec(move |mut ctx| {
+        let log = log.clone();
+
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    ConfigUpdated => {
+                        let at = Instant::now();
+                        let storage = ctx.request(GetVersion).resolve().await.unwrap();
+                        let own = ctx.config().version;
+                        log.lock().unwrap().push(Seen { own, storage, at });
+                    }
+                    _ => {}
+                });
+            }
+        }
+    })
+}
+
+fn starter() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                _ => {}
+            });
+        }
+    })
+}
+
+fn write_config(path: &Path, version: u32) {
+    let config = format!(
+        r#"
+        [system.configurers]
+        sync_updates = true
+        update_timeout = "5s"
+
+        [common]
+        version = {version}
+        "#
+    );
+
+    std::fs::write(path, config).unwrap();
+}
+
+fn config_path() -> PathBuf {
+    let pid = std::process::id();
+    std::env::temp_dir().join(format!("elfo-config-update-order-{pid}.toml"))
+}
+
+#[tokio::test(start_paused = true)]
+async fn dependencies_apply_first() {
+    common::setup_logger();
+
+    let path = config_path();
+    write_config(&path, 1);
+
+    let log = Log::default();
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let starter = topology.local("starter").entrypoint();
+    // Declared first, so it would get the config first without ordering.
+    let consumers = topology.local("consumers");
+    let storage = topology.local("storage");
+
+    consumers.route_to(&storage, |envelope| {
+        msg!(match envelope {
+            GetVersion => true,
+            _ => false,
+        })
+    });
+
+    let configurers_addr = configurers.addr();
+    let storage_addr = storage.addr();
+
+    configurers.mount(configurer::from_path(&topology, &path));
+    starter.mount(self::starter());
+    consumers.mount(self::consumers(log.clone()));
+    storage.mount(self::storage());
+
+    let path = &path;
+    do_start(topology, false, |ctx, topology| async move {
+        let reload = || {
+            let res = ctx
+                .request_to(configurers_addr, ReloadConfigs::default())
+                .resolve();
+            async move { res.await.unwrap().unwrap() }
+        };
+
+        // The storage is busy, so the consumers wait for it.
+        ctx.send_to(storage_addr, Stall(Duration::from_secs(1)))
+            .await
+            .unwrap();
+        write_config(path, 2);
+        let started_at = Instant::now();
+        reload().await;
+
+        // Let the consumers handle `ConfigUpdated`.
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let seen = log.lock().unwrap().pop().expect("not updated");
+        assert_eq!((seen.own, seen.storage), (2, 2));
+        assert!(seen.at - started_at >= Duration::from_secs(1));
+
+        // The storage is stuck, the consumers are updated after the timeout.
+        ctx.send_to(storage_addr, Stall(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        write_config(path, 3);
+        let started_at = Instant::now();
+        reload().await;
+
+        // The storage responds once the stall is over.
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let seen = log.lock().unwrap().pop().expect("not updated");
+        assert_eq!(seen.own, 3);
+        let elapsed = seen.at - started_at;
+        assert!(elapsed >= Duration::from_secs(5), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(60), "{elapsed:?}");
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = std::fs::remove_file(path);
+}
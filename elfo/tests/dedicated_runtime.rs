@@ -0,0 +1,69 @@
+#![allow(missing_docs)]
+#![cfg(all(
+    feature = "dedicated-runtime",
+    feature = "test-util",
+    target_os = "linux"
+))]
+
+use std::mem;
+
+use elfo::{
+    config::AnyConfig,
+    messages::{ActorStatusReport, SubscribeToActorStatuses},
+    prelude::*,
+    RuntimeOptions,
+};
+
+#[message(ret = (String, Vec<usize>))]
+struct WhereAreYou;
+
+fn affinity() -> Vec<usize> {
+    // SAFETY: `cpu_set_t` is a plain bitmask, zeroed is an empty set.
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    // SAFETY: the set is initialized, `0` means the current thread.
+    let rc = unsafe { libc::sched_getaffinity(0, mem::size_of_val(&set), &mut set) };
+    assert_eq!(rc, 0);
+
+    (0..libc::CPU_SETSIZE as usize)
+        // SAFETY: the core is in the set's range.
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect()
+}
+
+#[tokio::test]
+async fn threads_are_pinned() {
+    // The last available core differs from the default mask if there are many.
+    let core = *affinity().last().unwrap();
+
+    let blueprint = ActorGroup::new()
+        .dedicated_runtime(RuntimeOptions {
+            worker_threads: 2,
+            thread_name_prefix: "md".into(),
+            pin_to_cores: vec![core],
+            thread_priority: None,
+        })
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (WhereAreYou, token) => {
+                        let thread = std::thread::current().name().unwrap_or("?").to_owned();
+                        ctx.respond(token, (thread, affinity()));
+                    }
+                });
+            }
+        });
+
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+
+    let (thread, cores) = proxy.request(WhereAreYou).await;
+    assert!(thread.starts_with("md-"), "unexpected thread: {thread}");
+    assert_eq!(cores, [core]);
+
+    // The runtime is exposed in status reports.
+    proxy.send(SubscribeToActorStatuses::default()).await;
+    proxy.sync().await;
+
+    msg!(match proxy.recv().await {
+        ActorStatusReport { runtime, .. } => assert_eq!(runtime.as_deref(), Some("md")),
+    });
+}
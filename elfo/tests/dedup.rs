@@ -0,0 +1,85 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{config::AnyConfig, prelude::*, DedupWindow};
+
+#[message]
+#[derive(PartialEq)]
+struct Event {
+    id: u32,
+}
+
+#[message(ret = Vec<u32>)]
+struct Collect;
+
+fn testee(window: DedupWindow) -> Blueprint {
+    ActorGroup::new()
+        .dedup_by::<Event, _>(|event| event.id, window)
+        .exec(|mut ctx| async move {
+            let mut handled = Vec::new();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Event { id } => handled.push(id),
+                    (Collect, token) => ctx.respond(token, handled.clone()),
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn count_window() {
+    let proxy = elfo::test::proxy(testee(DedupWindow::count(2)), AnyConfig::default()).await;
+
+    // The window is [2, 4] last keys.
+    for id in [1, 2, 1, 3, 1, 2, 4, 5, 1] {
+        proxy.send(Event { id }).await;
+    }
+
+    assert_eq!(proxy.request(Collect).await, vec![1, 2, 3, 4, 5, 1]);
+
+    // Duplicates are dumped with the `dup` class only.
+    if elfo::dumping::ENABLED {
+        let dumps = proxy.dumps().class("dup");
+        assert_eq!(dumps.messages::<Event>(), [1, 1, 2].map(|id| Event { id }));
+        let dumps = proxy
+            .dumps()
+            .class("internal")
+            .filter(|d| d.group() == "subject");
+        assert_eq!(dumps.messages::<Event>().len(), 6);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn time_window() {
+    let period = Duration::from_secs(10);
+    let proxy = elfo::test::proxy(testee(DedupWindow::time(period)), AnyConfig::default()).await;
+
+    proxy.send(Event { id: 1 }).await;
+    tokio::time::sleep(period / 2).await;
+    proxy.send(Event { id: 1 }).await;
+    proxy.send(Event { id: 2 }).await;
+
+    // Straddles the boundary, thus still remembered.
+    tokio::time::sleep(period / 2).await;
+    proxy.send(Event { id: 1 }).await;
+    proxy.send(Event { id: 2 }).await;
+
+    // Both windows are over.
+    tokio::time::sleep(period).await;
+    proxy.send(Event { id: 1 }).await;
+    proxy.send(Event { id: 2 }).await;
+
+    assert_eq!(proxy.request(Collect).await, vec![1, 2, 1, 2]);
+}
+
+#[tokio::test]
+async fn other_types_pass() {
+    let proxy = elfo::test::proxy(testee(DedupWindow::count(1)), AnyConfig::default()).await;
+
+    proxy.send(Event { id: 1 }).await;
+    assert_eq!(proxy.request(Collect).await, vec![1]);
+    assert_eq!(proxy.request(Collect).await, vec![1]);
+}
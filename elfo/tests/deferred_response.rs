@@ -0,0 +1,145 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    Addr, DeferredToken, Topology,
+};
+
+#[message(ret = u32)]
+struct GetPrice;
+
+#[message(ret = u32)]
+struct GetPriceWithin(Duration);
+
+#[message]
+struct SetPrice(u32);
+
+#[message(ret = usize)]
+struct CountDeferred;
+
+#[message(ret = ())]
+struct Stop;
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut waiting: Vec<DeferredToken<GetPrice>> = Vec::new();
+        let mut waiting_within: Vec<DeferredToken<GetPriceWithin>> = Vec::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (GetPrice, token) => waiting.push(token.defer("price")),
+                (GetPriceWithin(timeout), token) => {
+                    let deferred = token.defer("price_within").with_deadline(timeout);
+                    waiting_within.push(deferred);
+                }
+                SetPrice(price) => {
+                    for deferred in waiting.drain(..) {
+                        ctx.respond_deferred(deferred, price);
+                    }
+                    for deferred in waiting_within.drain(..) {
+                        ctx.respond_deferred(deferred, price);
+                    }
+                }
+                (CountDeferred, token) => {
+                    let stats = ctx.deferred_stats();
+                    assert_eq!(stats.oldest_age.is_some(), stats.count > 0);
+                    ctx.respond(token, stats.count);
+                }
+                (Stop, token) => {
+                    ctx.respond(token, ());
+                    break;
+                }
+            });
+        }
+    })
+}
+
+fn topology() -> (Topology, Addr) {
+    let config = AnyConfig::default();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    subject.mount(self::subject());
+
+    (topology, subject_addr)
+}
+
+#[tokio::test(start_paused = true)]
+async fn answered_later() {
+    let (topology, subject) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let (price, ()) = tokio::join!(ctx.request_to(subject, GetPrice).resolve(), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let count = ctx.request_to(subject, CountDeferred).resolve().await;
+            assert_eq!(count.unwrap(), 1);
+            ctx.send_to(subject, SetPrice(42)).await.unwrap();
+        });
+
+        assert_eq!(price.unwrap(), 42);
+        let count = ctx.request_to(subject, CountDeferred).resolve().await;
+        assert_eq!(count.unwrap(), 0);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn expired() {
+    let (topology, subject) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let timeout = Duration::from_secs(5);
+        let started = tokio::time::Instant::now();
+        let res = ctx
+            .request_to(subject, GetPriceWithin(timeout))
+            .resolve_detailed()
+            .await;
+
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Timeout);
+        assert!(started.elapsed() >= timeout);
+        let count = ctx.request_to(subject, CountDeferred).resolve().await;
+        assert_eq!(count.unwrap(), 0);
+
+        // Late responses are discarded.
+        ctx.send_to(subject, SetPrice(42)).await.unwrap();
+        let count = ctx.request_to(subject, CountDeferred).resolve().await;
+        assert_eq!(count.unwrap(), 0);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropped_on_termination() {
+    let (topology, subject) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let (res, ()) = tokio::join!(ctx.request_to(subject, GetPrice).resolve(), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            ctx.request_to(subject, Stop).resolve().await.unwrap();
+        });
+
+        assert!(res.unwrap_err().is_ignored());
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
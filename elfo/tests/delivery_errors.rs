@@ -0,0 +1,121 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    Addr, Local, Topology,
+};
+
+#[message(ret = Local<Addr>)]
+struct Freeze(u32);
+
+#[message]
+struct Data;
+
+#[message]
+struct Unrouted;
+
+fn subject() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|e| {
+            msg!(match e {
+                StartEntrypoint { .. } => Outcome::Unicast(0),
+                Freeze(key) => Outcome::Unicast(*key),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Freeze(_), token) => {
+                        ctx.respond(token, ctx.addr().into());
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+                    Data => {}
+                });
+            }
+        })
+}
+
+fn topology() -> (Topology, Addr) {
+    let config = AnyConfig::deserialize(toml! {
+        [subject.system.mailbox]
+        capacity = 1
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    subject.mount(self::subject());
+
+    (topology, subject_addr)
+}
+
+#[tokio::test(start_paused = true)]
+async fn full() {
+    let (topology, subject) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let addr = *ctx.request_to(subject, Freeze(42)).resolve().await.unwrap();
+
+        ctx.try_send_to(addr, Data).unwrap();
+        let err = ctx.detailed().try_send_to(addr, Data).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Full);
+        assert!(err.is_full());
+
+        let context = err.context();
+        assert_eq!(context.message, "Data");
+        assert_eq!(context.group.as_deref(), Some("subject"));
+        assert_eq!(context.key.as_deref(), Some("42"));
+        assert!(!context.is_remote());
+        assert!(
+            err.to_string()
+                .ends_with("to `subject` (key `42`): mailbox full"),
+            "{err}"
+        );
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn no_route() {
+    let (topology, _) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        // The initial context has no routes at all.
+        let err = ctx.detailed().send(Unrouted).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NoRoute);
+        assert_eq!(err.context().message, "Unrouted");
+        assert_eq!(err.context().group, None);
+
+        let err = ctx.detailed().try_send(Unrouted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NoRoute);
+
+        let err = ctx.request(Freeze(1)).resolve_detailed().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NoRoute);
+        assert_eq!(err.context().message, "Freeze");
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
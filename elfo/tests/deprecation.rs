@@ -0,0 +1,286 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use metrics::{GaugeValue, Key, Recorder, Unit};
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{config::AnyConfig, errors::ErrorKind, prelude::*, scope};
+
+#[message(deprecated = "use OrderAcceptedV2")]
+struct OrderAccepted(u32);
+
+#[message(deprecated = "use OrderRejectedV2")]
+struct OrderRejected(u32);
+
+#[message(ret = u32, deprecated = "use GetOrderV2")]
+struct GetOrder;
+
+/// The testee sends `count` messages, `OrderAccepted` or `OrderRejected`.
+#[message]
+struct Produce {
+    count: u32,
+    accepted: bool,
+}
+
+/// Whether every message is sent or rejected as deprecated.
+#[message]
+#[derive(PartialEq)]
+struct Produced(Vec<bool>);
+
+#[message]
+struct TryRequest;
+
+#[message]
+struct Requested {
+    is_deprecated: bool,
+}
+
+fn testee() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Produce { count, accepted } => {
+                    let produced = (0..count)
+                        .map(|i| {
+                            let res = if accepted {
+                                let res = ctx.detailed().try_send(OrderAccepted(i));
+                                res.map_err(|err| (err.kind(), err.is_closed()))
+                            } else {
+                                let res = ctx.detailed().try_send(OrderRejected(i));
+                                res.map_err(|err| (err.kind(), err.is_closed()))
+                            };
+
+                            match res {
+                                Ok(()) => true,
+                                Err((kind, is_closed)) => {
+                                    assert_eq!(kind, ErrorKind::Deprecated);
+                                    assert!(is_closed);
+                                    false
+                                }
+                            }
+                        })
+                        .collect();
+
+                    ctx.send(Produced(produced)).await.unwrap();
+                }
+                TryRequest => {
+                    let err = ctx.request(GetOrder).resolve_detailed().await.unwrap_err();
+                    assert!(err.is_failed());
+                    let is_deprecated = err.kind() == ErrorKind::Deprecated;
+                    ctx.send(Requested { is_deprecated }).await.unwrap();
+                }
+                OrderAccepted | OrderRejected => {}
+            });
+        }
+    })
+}
+
+// === Metrics ===
+
+/// Counts deprecated messages by metric names, groups and messages.
+#[derive(Default)]
+struct DeprecationCounter(Mutex<HashMap<(String, String, String), u64>>);
+
+impl Recorder for DeprecationCounter {
+    fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+    fn record_histogram(&self, _: &Key, _: f64) {}
+
+    fn increment_counter(&self, key: &Key, value: u64) {
+        if !key.name().starts_with("elfo_deprecated_") {
+            return;
+        }
+
+        // The group label is added by the telemeter from the current scope.
+        let group = scope::try_meta().map(|meta| meta.group.clone()).unwrap();
+        let message = key.labels().find(|l| l.key() == "message").unwrap();
+        assert!(key.labels().any(|l| l.key() == "protocol"));
+        let message = message.value().into();
+
+        *self
+            .0
+            .lock()
+            .unwrap()
+            .entry((key.name().into(), group, message))
+            .or_default() += value;
+    }
+}
+
+fn counter() -> &'static DeprecationCounter {
+    static COUNTER: OnceLock<&'static DeprecationCounter> = OnceLock::new();
+
+    COUNTER.get_or_init(|| {
+        let counter = Box::leak(Box::<DeprecationCounter>::default());
+        metrics::set_recorder(counter).unwrap();
+        counter
+    })
+}
+
+fn uses(name: &str, group: &str, message: &str) -> u64 {
+    let key = (name.into(), group.into(), message.into());
+    counter().0.lock().unwrap().get(&key).copied().unwrap_or(0)
+}
+
+// === Logs ===
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Capture {
+    fn warnings(&self, pattern: &str) -> Vec<String> {
+        let logs = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        logs.lines()
+            .filter(|line| line.contains(pattern))
+            .inspect(|line| assert!(line.contains("WARN"), "{line}"))
+            .map(Into::into)
+            .collect()
+    }
+}
+
+fn capture_logs() -> (Capture, tracing::subscriber::DefaultGuard) {
+    let capture = Capture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    (capture, tracing::subscriber::set_default(subscriber))
+}
+
+// === Tests ===
+
+#[tokio::test]
+async fn counted_and_warned_once() {
+    counter();
+    let (capture, _guard) = capture_logs();
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+
+    // Handled by the testee.
+    proxy.send(OrderAccepted(0)).await;
+    proxy.send(OrderAccepted(1)).await;
+
+    // Sent by the testee.
+    proxy
+        .send(Produce {
+            count: 3,
+            accepted: true,
+        })
+        .await;
+
+    for _ in 0..3 {
+        assert_msg!(proxy.recv().await, OrderAccepted(_));
+    }
+    assert_msg_eq!(proxy.recv().await, Produced(vec![true; 3]));
+
+    let sent = "elfo_deprecated_sent_messages_total";
+    let handled = "elfo_deprecated_handled_messages_total";
+    assert_eq!(uses(sent, "subject", "OrderAccepted"), 3);
+    assert_eq!(uses(handled, "subject", "OrderAccepted"), 2);
+    // The proxy sends and handles them too, but in its own group.
+    assert_eq!(uses(sent, "proxy", "OrderAccepted"), 2);
+    assert_eq!(uses(handled, "proxy", "OrderAccepted"), 3);
+
+    // Every use is counted, but warned only once per group and direction.
+    let warnings = capture.warnings("deprecated message is sent");
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    assert!(
+        warnings[0].contains("name=\"OrderAccepted\""),
+        "{warnings:?}"
+    );
+    assert!(
+        warnings[0].contains("note=\"use OrderAcceptedV2\""),
+        "{warnings:?}"
+    );
+    assert_eq!(capture.warnings("deprecated message is handled").len(), 2);
+}
+
+#[tokio::test]
+async fn warn_interval_is_configurable() {
+    let (capture, _guard) = capture_logs();
+    let config = AnyConfig::deserialize(toml! {
+        system.deprecation.warn_interval = "0s"
+    })
+    .unwrap();
+    let mut proxy = elfo::test::proxy(testee(), config).await;
+
+    proxy
+        .send(Produce {
+            count: 3,
+            accepted: false,
+        })
+        .await;
+
+    for _ in 0..3 {
+        assert_msg!(proxy.recv().await, OrderRejected(_));
+    }
+    assert_msg_eq!(proxy.recv().await, Produced(vec![true; 3]));
+
+    // The proxy isn't configured, so it warns only once about handling.
+    let warnings = capture.warnings("deprecated message is sent");
+    assert_eq!(warnings.len(), 3, "{warnings:?}");
+    assert_eq!(capture.warnings("deprecated message is handled").len(), 1);
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_sending() {
+    let (capture, _guard) = capture_logs();
+    let config = AnyConfig::deserialize(toml! {
+        system.deprecation.strict = true
+    })
+    .unwrap();
+    let mut proxy = elfo::test::proxy(testee(), config).await;
+
+    proxy
+        .send(Produce {
+            count: 2,
+            accepted: false,
+        })
+        .await;
+    assert_msg_eq!(proxy.recv().await, Produced(vec![false; 2]));
+
+    proxy.send(TryRequest).await;
+    assert_msg!(
+        proxy.recv().await,
+        Requested {
+            is_deprecated: true
+        }
+    );
+
+    let warnings = capture.warnings("deprecated message is rejected");
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    assert!(warnings
+        .iter()
+        .any(|w| w.contains("note=\"use GetOrderV2\"")));
+    assert!(capture.warnings("deprecated message is sent").is_empty());
+
+    // Handling of deprecated messages isn't affected.
+    proxy.send(OrderRejected(0)).await;
+    proxy
+        .send(Produce {
+            count: 0,
+            accepted: false,
+        })
+        .await;
+    assert_msg_eq!(proxy.recv().await, Produced(vec![]));
+    assert_eq!(capture.warnings("deprecated message is handled").len(), 1);
+}
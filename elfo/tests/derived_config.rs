@@ -0,0 +1,112 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{config::AnyConfig, messages::UpdateConfig, prelude::*};
+
+#[derive(Debug, Clone, Deserialize)]
+struct Config {
+    limit: u32,
+}
+
+struct Derived(u32);
+
+impl Derived {
+    fn build(config: &Config, builds: &AtomicUsize) -> Self {
+        builds.fetch_add(1, Ordering::SeqCst);
+        assert_ne!(config.limit, 0, "zero limit");
+        Self(config.limit * 2)
+    }
+}
+
+#[message(ret = Option<u32>)]
+struct GetDerived;
+
+#[message(ret = u64)]
+struct GetGeneration;
+
+fn update(limit: u32) -> UpdateConfig {
+    UpdateConfig::new(AnyConfig::deserialize(toml! { limit = limit }).unwrap())
+}
+
+#[tokio::test]
+async fn it_works() {
+    let builds = Arc::new(AtomicUsize::new(0));
+    let builds1 = builds.clone();
+
+    let blueprint = ActorGroup::new().config::<Config>().exec(move |mut ctx| {
+        let builds = builds1.clone();
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (GetDerived, token) => {
+                        // Two call sites share the same value.
+                        let first = ctx.derived_config(|c| Derived::build(c, &builds));
+                        let second = ctx.derived_config(|c| Derived::build(c, &builds));
+
+                        let value = match (first, second) {
+                            (Ok(first), Ok(second)) => {
+                                assert!(Arc::ptr_eq(&first, &second));
+                                Some(first.0)
+                            }
+                            (Err(first), Err(second)) => {
+                                assert!(first.reason.contains("zero limit"));
+                                assert_eq!(first.reason, second.reason);
+                                None
+                            }
+                            _ => unreachable!(),
+                        };
+                        ctx.respond(token, value);
+                    }
+                    (GetGeneration, token) => {
+                        ctx.respond(token, ctx.config_generation());
+                    }
+                    _ => {}
+                });
+            }
+        }
+    });
+
+    let proxy = elfo::test::proxy(blueprint, toml! { limit = 1 }).await;
+    let builds = || builds.load(Ordering::SeqCst);
+
+    // Lazily built.
+    assert_eq!(proxy.request(GetGeneration).await, 0);
+    assert_eq!(builds(), 0);
+
+    assert_eq!(proxy.request(GetDerived).await, Some(2));
+    assert_eq!(proxy.request(GetDerived).await, Some(2));
+    assert_eq!(builds(), 1);
+
+    // Rebuilt once after an update.
+    assert!(proxy.request(update(5)).await.is_ok());
+    assert_eq!(proxy.request(GetGeneration).await, 1);
+    assert_eq!(builds(), 1);
+    assert_eq!(proxy.request(GetDerived).await, Some(10));
+    assert_eq!(proxy.request(GetDerived).await, Some(10));
+    assert_eq!(builds(), 2);
+
+    // Several updates without accessing to the value.
+    assert!(proxy.request(update(6)).await.is_ok());
+    assert!(proxy.request(update(7)).await.is_ok());
+    assert_eq!(proxy.request(GetGeneration).await, 3);
+    assert_eq!(proxy.request(GetDerived).await, Some(14));
+    assert_eq!(builds(), 3);
+
+    // Panics are cached until the next update.
+    assert!(proxy.request(update(0)).await.is_ok());
+    assert_eq!(proxy.request(GetDerived).await, None);
+    assert_eq!(proxy.request(GetDerived).await, None);
+    assert_eq!(builds(), 4);
+
+    assert!(proxy.request(update(3)).await.is_ok());
+    assert_eq!(proxy.request(GetDerived).await, Some(6));
+    assert_eq!(builds(), 5);
+}
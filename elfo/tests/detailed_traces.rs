@@ -0,0 +1,130 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use tokio::time::Instant;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    prelude::*,
+    time::Interval,
+    tracing::TraceId,
+    Topology,
+};
+
+mod common;
+
+const JOBS_PER_TICK: usize = 3;
+
+#[message]
+struct Tick;
+
+#[message]
+struct Job;
+
+type Log = Arc<Mutex<Vec<(Instant, TraceId)>>>;
+
+// Every tick starts a new trace, which is continued by jobs.
+fn ticker(log: Log) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let log = log.clone();
+        async move {
+            ctx.attach(Interval::new(Tick))
+                .start(Duration::from_secs(1));
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Tick => {
+                        let (is_detailed, trace_id) =
+                            elfo::scope::with(|scope| (scope.is_force_sampled(), scope.trace_id()));
+
+                        if is_detailed {
+                            log.lock().push((Instant::now(), trace_id));
+                        }
+
+                        for _ in 0..JOBS_PER_TICK {
+                            ctx.send(Job).await.unwrap();
+                        }
+                    }
+                });
+            }
+        }
+    })
+}
+
+fn worker() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Job => {}
+            });
+        }
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn budget() {
+    common::setup_logger();
+
+    // Nothing is dumped in ordinary traces, at most one dump per second is
+    // allowed in the worker.
+    let config = AnyConfig::deserialize(toml! {
+        [ticker.system.tracing]
+        detailed_budget = "5/m"
+
+        [ticker.system.dumping]
+        trace_sample_rate = 0.0
+
+        [worker.system.dumping]
+        trace_sample_rate = 0.0
+        max_rate = 1
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let ticker = topology.local("ticker");
+    let worker = topology.local("worker");
+
+    ticker.route_to(&worker, |e| e.is::<Job>());
+
+    let log = Log::default();
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    ticker.mount(self::ticker(log.clone()));
+    worker.mount(self::worker());
+
+    let capture = topology.dump_capture().clone();
+
+    do_start(topology, false, |ctx, topology| async move {
+        // Ticks at 1s, 2s, ..., 60s.
+        tokio::time::sleep(Duration::from_millis(60_500)).await;
+        terminate(ctx, topology).await;
+    })
+    .await
+    .expect("cannot start");
+
+    // The budget is honored and spread over the period.
+    let log = log.lock().clone();
+    assert_eq!(log.len(), 5);
+    for pair in log.windows(2) {
+        assert_eq!(pair[1].0 - pair[0].0, Duration::from_secs(12));
+    }
+
+    // Detailed traces are dumped in all groups bypassing sampling and rate
+    // limits, other traces are unaffected.
+    let detailed = log.iter().map(|(_, id)| *id).collect::<HashSet<_>>();
+    let jobs = capture
+        .snapshot()
+        .into_iter()
+        .filter(|dump| dump.message_name == "Job" && dump.meta.group == "worker")
+        .map(|dump| dump.trace_id)
+        .collect::<Vec<_>>();
+
+    assert_eq!(jobs.len(), detailed.len() * JOBS_PER_TICK);
+    assert!(jobs.iter().all(|trace_id| detailed.contains(trace_id)));
+}
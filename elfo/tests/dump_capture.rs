@@ -0,0 +1,252 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use elfo::{
+    config::AnyConfig,
+    prelude::*,
+    test::{assert_dumped, Direction},
+    tracing::TraceId,
+};
+
+#[message]
+struct PlaceOrder {
+    qty: u32,
+}
+
+#[message]
+#[derive(PartialEq)]
+struct OrderPlaced {
+    id: u64,
+    qty: u32,
+}
+
+#[message]
+struct Unrelated;
+
+fn sample() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut next_id = 1;
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                PlaceOrder { qty } => {
+                    let id = next_id;
+                    next_id += 1;
+                    ctx.send(OrderPlaced { id, qty }).await.unwrap();
+                }
+                Unrelated => {
+                    // Starts a new trace.
+                    elfo::scope::set_trace_id(TraceId::generate());
+                    ctx.send(Unrelated).await.unwrap();
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn it_works() {
+    let mut proxy = elfo::test::proxy(sample(), AnyConfig::default()).await;
+
+    proxy.send(PlaceOrder { qty: 10 }).await;
+    assert_msg!(proxy.recv().await, OrderPlaced { qty: 10, .. });
+
+    assert_dumped!(proxy, direction = In, message = PlaceOrder { qty: 10 });
+    assert_dumped!(
+        proxy,
+        direction = Out,
+        count = 1,
+        message = OrderPlaced { id: 1, qty: 10 }
+    );
+    assert_dumped!(proxy, count = 0, message = OrderPlaced { qty: 20, .. });
+    assert_dumped!(
+        proxy,
+        group = "proxy",
+        count = 0,
+        message = PlaceOrder { .. }
+    );
+
+    proxy.send(PlaceOrder { qty: 20 }).await;
+    assert_msg!(proxy.recv().await, OrderPlaced { qty: 20, .. });
+    assert_dumped!(
+        proxy,
+        direction = Out,
+        count = 2,
+        message = OrderPlaced { .. }
+    );
+
+    let dumps = proxy.dumps().direction(Direction::Out).group("subject");
+    assert_eq!(
+        dumps.messages::<OrderPlaced>(),
+        vec![
+            OrderPlaced { id: 1, qty: 10 },
+            OrderPlaced { id: 2, qty: 20 }
+        ]
+    );
+    assert!(dumps.iter().all(|d| d.message_name() == "OrderPlaced"));
+
+    proxy.clear_dumps();
+    assert!(proxy.dumps().is_empty());
+}
+
+#[tokio::test]
+async fn trace_id() {
+    let mut proxy = elfo::test::proxy(sample(), AnyConfig::default()).await;
+
+    proxy.send(PlaceOrder { qty: 1 }).await;
+    assert_msg!(proxy.recv().await, OrderPlaced { .. });
+    proxy.send(Unrelated).await;
+    assert_msg!(proxy.recv().await, Unrelated);
+
+    let trace_id = proxy
+        .dumps()
+        .direction(Direction::Out)
+        .iter()
+        .find(|d| d.message::<OrderPlaced>().is_some())
+        .map(|d| d.trace_id())
+        .unwrap();
+
+    // Messages sent by the proxy share its trace id.
+    assert_eq!(proxy.dumps().trace_id(trace_id).len(), 3);
+    assert_dumped!(
+        proxy,
+        trace_id = trace_id,
+        count = 1,
+        message = PlaceOrder { .. }
+    );
+    assert_dumped!(
+        proxy,
+        trace_id = trace_id,
+        direction = In,
+        message = Unrelated
+    );
+    assert_dumped!(
+        proxy,
+        trace_id = trace_id,
+        direction = Out,
+        count = 0,
+        message = Unrelated
+    );
+    assert_dumped!(proxy, direction = Out, count = 1, message = Unrelated);
+
+    let unknown = TraceId::try_from(42).unwrap();
+    assert!(proxy.dumps().trace_id(unknown).is_empty());
+}
+
+#[tokio::test]
+#[should_panic(expected = "expected: 1, matched: 0")]
+async fn failure() {
+    let mut proxy = elfo::test::proxy(sample(), AnyConfig::default()).await;
+
+    proxy.send(PlaceOrder { qty: 10 }).await;
+    assert_msg!(proxy.recv().await, OrderPlaced { .. });
+
+    assert_dumped!(
+        proxy,
+        direction = Out,
+        count = 1,
+        message = OrderPlaced { qty: 11, .. }
+    );
+}
+
+#[message(part)]
+#[derive(Copy, PartialEq)]
+enum Venue {
+    Binance,
+    Okx,
+}
+
+#[message]
+struct Quote {
+    venue: Venue,
+}
+
+#[message]
+#[derive(PartialEq)]
+struct QuoteHandled {
+    venue: Venue,
+}
+
+#[message]
+#[derive(PartialEq)]
+struct Report(u32);
+
+fn classified() -> Blueprint {
+    ActorGroup::new()
+        .dump_class_by(|envelope| {
+            let quote = envelope.message().downcast_ref::<Quote>()?;
+            Some(match quote.venue {
+                Venue::Binance => "venue_binance",
+                Venue::Okx => "venue_okx",
+            })
+        })
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Quote { venue } => {
+                        ctx.send(QuoteHandled { venue }).await.unwrap();
+                    }
+                    Report(_) => {
+                        ctx.send(Report(1)).await.unwrap();
+
+                        elfo::scope::within_dump_class("outer", async {
+                            ctx.send(Report(2)).await.unwrap();
+                            elfo::scope::with_dump_class("inner", || {
+                                assert_eq!(elfo::scope::dump_class(), Some("inner"));
+                                ctx.try_send(Report(3)).unwrap();
+                            });
+                            ctx.send(Report(4)).await.unwrap();
+                        })
+                        .await;
+
+                        assert_eq!(elfo::scope::dump_class(), None);
+                        ctx.send(Report(5)).await.unwrap();
+                    }
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn overridden_class() {
+    let mut proxy = elfo::test::proxy(classified(), AnyConfig::default()).await;
+
+    // Classified by the group.
+    proxy
+        .send(Quote {
+            venue: Venue::Binance,
+        })
+        .await;
+    proxy.send(Quote { venue: Venue::Okx }).await;
+    assert_msg!(proxy.recv().await, QuoteHandled { .. });
+    assert_msg!(proxy.recv().await, QuoteHandled { .. });
+
+    for (venue, class) in [(Venue::Binance, "venue_binance"), (Venue::Okx, "venue_okx")] {
+        let dumps = proxy.dumps().group("subject").class(class);
+        assert_eq!(dumps.len(), 2);
+        assert_eq!(
+            dumps.messages::<QuoteHandled>(),
+            vec![QuoteHandled { venue }]
+        );
+    }
+
+    // Overridden in the handler, the innermost class wins.
+    proxy.send(Report(0)).await;
+    for _ in 1..=5 {
+        assert_msg!(proxy.recv().await, Report(_));
+    }
+
+    let dumps = proxy.dumps().group("subject").direction(Direction::Out);
+    let classes = dumps
+        .iter()
+        .filter(|d| d.message::<Report>().is_some())
+        .map(|d| d.class())
+        .collect::<Vec<_>>();
+    assert_eq!(classes, ["internal", "outer", "inner", "outer", "internal"]);
+    assert_dumped!(
+        proxy,
+        class = "internal",
+        direction = In,
+        group = "subject",
+        message = Report(0)
+    );
+}
@@ -0,0 +1,152 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::path::{Path, PathBuf};
+
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::dumper::FlushDumps,
+    prelude::*,
+    Topology,
+};
+
+#[message(part)]
+#[derive(Copy)]
+enum Venue {
+    Binance,
+    Okx,
+}
+
+impl Venue {
+    fn class(self) -> &'static str {
+        match self {
+            Venue::Binance => "venue_binance",
+            Venue::Okx => "venue_okx",
+        }
+    }
+}
+
+#[message]
+struct Quote {
+    venue: Venue,
+}
+
+#[message]
+struct QuoteHandled;
+
+#[message]
+struct Report;
+
+#[message(ret = ())]
+struct Produce;
+
+#[message(ret = ())]
+struct Sync;
+
+fn producer() -> Blueprint {
+    ActorGroup::new()
+        .dump_class_by(|envelope| {
+            let quote = envelope.message().downcast_ref::<Quote>()?;
+            Some(quote.venue.class())
+        })
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Produce, token) => {
+                        for venue in [Venue::Binance, Venue::Okx] {
+                            ctx.send_to(ctx.addr(), Quote { venue }).await.unwrap();
+                        }
+                        elfo::scope::with_dump_class("reports", || {
+                            // Nobody is interested, but it's dumped anyway.
+                            let _ = ctx.try_send(Report);
+                        });
+                        ctx.respond(token, ());
+                    }
+                    Quote { .. } => {
+                        let _ = ctx.send(QuoteHandled).await;
+                    }
+                    (Sync, token) => ctx.respond(token, ()),
+                });
+            }
+        })
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("elfo-dump-classes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn count(dir: &Path, class: &str, message_name: &str) -> usize {
+    let content = std::fs::read_to_string(dir.join(format!("{class}.dump"))).unwrap_or_default();
+    let pattern = format!(r#""mn":"{message_name}""#);
+    content
+        .lines()
+        .filter(|line| line.contains(&pattern))
+        .count()
+}
+
+#[tokio::test]
+async fn overridden_classes_are_written_to_own_files() {
+    let dir = temp_dir();
+    let path = dir.join("{class}.dump");
+    let path = path.to_str().unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let dumpers = topology.local("system.dumpers");
+    let dumpers_addr = dumpers.addr();
+    let producers = topology.local("producers");
+    let producers_addr = producers.addr();
+
+    dumpers.mount(elfo::batteries::dumper::new());
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        toml! {
+            [system.dumpers]
+            path = path
+        },
+    ));
+    producers.mount(producer());
+
+    do_start(topology, false, |ctx, topology| async move {
+        ctx.request_to(producers_addr, Produce)
+            .resolve()
+            .await
+            .unwrap();
+        // Wait for quotes to be handled.
+        ctx.request_to(producers_addr, Sync)
+            .resolve()
+            .await
+            .unwrap();
+
+        // Dumpers of new classes are started on demand.
+        ctx.request_to(dumpers_addr, FlushDumps::default())
+            .all()
+            .resolve()
+            .await;
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .expect("cannot start");
+
+    // Sent in the default class.
+    assert_eq!(count(&dir, "internal", "Quote"), 2);
+    assert_eq!(count(&dir, "internal", "QuoteHandled"), 0);
+    assert_eq!(count(&dir, "internal", "Report"), 0);
+
+    // Classified on receiving, including sent messages.
+    for venue in [Venue::Binance, Venue::Okx] {
+        assert_eq!(count(&dir, venue.class(), "Quote"), 1);
+        assert_eq!(count(&dir, venue.class(), "QuoteHandled"), 1);
+    }
+
+    // Overridden in the handler.
+    assert_eq!(count(&dir, "reports", "Report"), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
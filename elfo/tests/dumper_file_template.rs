@@ -0,0 +1,105 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::runtime::Builder;
+use toml::{toml, Value};
+
+use elfo::{batteries::dumper::FlushDumps, prelude::*};
+use elfo_utils::time::with_system_time_mock;
+
+// 2024-02-29 13:59:58 UTC
+const START: Duration = Duration::from_secs(1_709_215_198);
+
+#[message]
+struct Item(u32);
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("elfo-dumper-template-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn items(path: &Path) -> Vec<u64> {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    content
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|dump| dump["mn"] == "Item")
+        .map(|dump| dump["m"].as_u64().unwrap())
+        .collect()
+}
+
+async fn wait_link(link: &Path, expected: &str) {
+    while std::fs::read_link(link).ok().as_deref() != Some(Path::new(expected)) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[test]
+fn files_are_switched_on_hour_boundary() {
+    let dir = temp_dir();
+    let path = dir.join("{class}/{date}/{hour}-{seq}.dump");
+    let path = path.to_str().unwrap();
+    let config: Value = toml! {
+        file_template = path
+        // Nothing is written by timer during the test.
+        write_interval = "1h"
+        max_write_interval = "1h"
+    }
+    .into();
+
+    let class_dir = dir.join("internal");
+    let link = class_dir.join("current.dump");
+
+    with_system_time_mock(|time| {
+        time.advance(START);
+
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let proxy = elfo::test::proxy(elfo::batteries::dumper::new(), config).await;
+
+            // Dumps are recorded on sending, the destination doesn't matter.
+            let _ = proxy.try_send(Item(1));
+            proxy.request(FlushDumps::default()).await;
+
+            assert_eq!(
+                std::fs::read_link(&link).unwrap(),
+                Path::new("2024-02-29/13-0.dump")
+            );
+
+            // The next hour starts, nothing is written meanwhile.
+            time.advance(Duration::from_secs(3));
+            wait_link(&link, "2024-02-29/14-0.dump").await;
+
+            let _ = proxy.try_send(Item(2));
+            proxy.request(FlushDumps::default()).await;
+
+            // `SIGHUP` starts a new file in the same slice.
+            #[cfg(unix)]
+            {
+                // SAFETY: the handler is installed by the dumper on start.
+                unsafe { libc::raise(libc::SIGHUP) };
+                wait_link(&link, "2024-02-29/14-1.dump").await;
+
+                let _ = proxy.try_send(Item(3));
+                proxy.request(FlushDumps::default()).await;
+                assert_eq!(items(&class_dir.join("2024-02-29/14-1.dump")), [3]);
+            }
+        });
+    });
+
+    assert_eq!(items(&class_dir.join("2024-02-29/13-0.dump")), [1]);
+    assert_eq!(items(&class_dir.join("2024-02-29/14-0.dump")), [2]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
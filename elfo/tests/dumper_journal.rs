@@ -0,0 +1,110 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use toml::{toml, Value};
+
+use elfo::{batteries::dumper::FlushDumps, messages::Terminate, prelude::*};
+
+const BURST: u32 = 5000;
+
+#[message]
+struct Item(u32);
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("elfo-dumper-journal-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn config(dir: &Path) -> Value {
+    let path = dir.join("{class}.dump");
+    let path = path.to_str().unwrap();
+
+    toml! {
+        path = path
+        // Nothing is written by timer during the test.
+        write_interval = "1h"
+        max_write_interval = "1h"
+        journal = true
+    }
+    .into()
+}
+
+fn count_items(path: &Path) -> usize {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    content
+        .lines()
+        .filter(|line| line.contains(r#""mn":"Item""#))
+        .count()
+}
+
+fn count_segments(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| {
+            let path = entry.as_ref().unwrap().path();
+            path.extension().is_some_and(|ext| ext == "journal")
+        })
+        .count()
+}
+
+/// If set, the test produces dumps and crashes, see below.
+const CRASH_DIR_ENV: &str = "ELFO_DUMPER_JOURNAL_CRASH_DIR";
+
+/// Produces dumps and aborts the process, so pending dumps are neither written
+/// nor the journal is cleaned up.
+async fn produce_and_crash(dir: &Path) -> ! {
+    let proxy = elfo::test::proxy(elfo::batteries::dumper::new(), config(dir)).await;
+
+    // Wait until the dumper is started, so the journal is opened.
+    proxy.request(FlushDumps::default()).await;
+
+    // Dumps are recorded on sending, the destination doesn't matter.
+    for i in 0..BURST {
+        let _ = proxy.try_send(Item(i));
+    }
+
+    std::process::abort();
+}
+
+#[tokio::test]
+async fn dumps_are_recovered_after_crash() {
+    if let Some(dir) = std::env::var_os(CRASH_DIR_ENV) {
+        produce_and_crash(Path::new(&dir)).await;
+    }
+
+    let dir = temp_dir();
+    let dump_path = dir.join("internal.dump");
+
+    // The dump recorder is global, so the crash happens in another process.
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["dumps_are_recovered_after_crash", "--exact"])
+        .env(CRASH_DIR_ENV, &dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    assert!(!status.success());
+    assert_eq!(count_items(&dump_path), 0);
+    assert!(count_segments(&dir) > 0);
+
+    // Restart the dumper pointed at the same directory.
+    let proxy = elfo::test::proxy(elfo::batteries::dumper::new(), config(&dir)).await;
+    proxy.send(Terminate::default()).await;
+    proxy.finished().await;
+
+    // All journaled dumps are recovered and written.
+    assert_eq!(count_items(&dump_path), BURST as usize);
+
+    // The journal is removed on clean termination.
+    assert_eq!(count_segments(&dir), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
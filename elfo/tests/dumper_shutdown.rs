@@ -0,0 +1,71 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::path::PathBuf;
+
+use toml::toml;
+
+use elfo::{messages::Terminate, prelude::*};
+
+const BURST: u32 = 5000;
+
+#[message]
+struct Item(u32);
+
+fn dump_path() -> PathBuf {
+    std::env::temp_dir().join(format!("elfo-dumper-shutdown-{}.dump", std::process::id()))
+}
+
+// Extracts `"<key>":<number>` from the trailer line.
+fn number(line: &str, key: &str) -> usize {
+    let pattern = format!(r#""{key}":"#);
+    let start = line.find(&pattern).unwrap() + pattern.len();
+    let len = line[start..].find(|c: char| !c.is_ascii_digit()).unwrap();
+    line[start..start + len].parse().unwrap()
+}
+
+#[tokio::test]
+async fn everything_is_written_on_terminate() {
+    let path = dump_path();
+    let _ = std::fs::remove_file(&path);
+
+    let path_str = path.to_str().unwrap();
+    let config = toml! {
+        path = path_str
+        // Nothing is written by timer during the test.
+        write_interval = "1h"
+        max_write_interval = "1h"
+    };
+
+    let proxy = elfo::test::proxy(elfo::batteries::dumper::new(), config).await;
+
+    // Dumps are recorded on sending, the destination doesn't matter.
+    for i in 0..BURST {
+        let _ = proxy.try_send(Item(i));
+    }
+
+    proxy.send(Terminate::default()).await;
+    proxy.finished().await;
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let lines = content.lines().collect::<Vec<_>>();
+
+    // All dumps made before termination are written.
+    let items = lines
+        .iter()
+        .filter(|line| line.contains(r#""mn":"Item""#))
+        .count();
+    assert_eq!(items, BURST as usize);
+
+    // The trailer is the last line.
+    let (trailer, dumps) = lines.split_last().unwrap();
+    assert!(
+        trailer.contains(r#""cl":"internal","$trailer":{"#),
+        "{trailer}"
+    );
+    assert!(trailer.ends_with(r#""clean":true}}"#), "{trailer}");
+    assert_eq!(number(trailer, "written"), dumps.len());
+    assert!(number(trailer, "from") <= number(trailer, "to"));
+    assert!(dumps.iter().all(|line| !line.contains("$trailer")));
+}
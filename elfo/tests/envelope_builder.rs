@@ -0,0 +1,91 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use elfo::{
+    config::AnyConfig,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    tracing::TraceId,
+    Envelope,
+};
+
+#[message]
+struct Event(u32);
+
+#[message(ret = (u32, TraceId))]
+struct Whoami;
+
+// An example of a router, which is tested directly with built envelopes.
+// Events are sharded, requests with the same trace id hit the same actor.
+fn route(envelope: &Envelope) -> Outcome<u32> {
+    msg!(match envelope {
+        Event(n) => Outcome::Unicast(n % 2),
+        Whoami => Outcome::Unicast((u128::from(envelope.trace_id()) % 2) as u32),
+        _ => Outcome::Discard,
+    })
+}
+
+fn trace_id(id: u64) -> TraceId {
+    TraceId::try_from(id).unwrap()
+}
+
+#[test]
+fn router() {
+    let event = elfo::test::envelope(Event(3)).build();
+    assert!(matches!(route(&event), Outcome::Unicast(1)));
+
+    let whoami = elfo::test::envelope(Whoami)
+        .with_trace_id(trace_id(42))
+        .build();
+    assert!(matches!(route(&whoami), Outcome::Unicast(0)));
+}
+
+fn subject() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(route))
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                let trace_id = envelope.trace_id();
+                msg!(match envelope {
+                    (Whoami, token) => ctx.respond(token, (*ctx.key(), trace_id)),
+                    Event(_) => {}
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn request() {
+    let proxy = elfo::test::proxy(subject(), AnyConfig::default()).await;
+
+    let (envelope, response) = elfo::test::envelope(Whoami)
+        .with_trace_id(trace_id(43))
+        .request(&proxy);
+
+    assert_eq!(envelope.trace_id(), trace_id(43));
+    assert_eq!(envelope.sender(), proxy.addr());
+    assert_eq!(envelope.request_id(), Some(response.request_id()));
+    assert!(matches!(route(&envelope), Outcome::Unicast(1)));
+
+    proxy.send_raw(envelope).await;
+    assert_eq!(response.resolve().await.unwrap(), (1, trace_id(43)));
+}
+
+#[tokio::test]
+async fn dropped_request() {
+    let proxy = elfo::test::proxy(subject(), AnyConfig::default()).await;
+
+    // The envelope is dropped without responding.
+    let (envelope, response) = elfo::test::envelope(Whoami).request(&proxy);
+    drop(envelope);
+    assert!(response.resolve().await.unwrap_err().is_failed());
+
+    // The pending response is dropped, the response is discarded.
+    let (envelope, response) = elfo::test::envelope(Whoami).request(&proxy);
+    drop(response);
+    proxy.send_raw(envelope).await;
+
+    // The proxy is still usable for regular requests.
+    let (key, _) = proxy.request(Whoami).await;
+    assert!(key < 2);
+}
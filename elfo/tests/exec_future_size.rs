@@ -0,0 +1,139 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    hint::black_box,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    Topology,
+};
+
+const SIZE: usize = 80 * 1024;
+
+async fn huge(mut ctx: Context<AnyConfig, u32>) {
+    // Held across `.await`, so it's stored in the future.
+    let buffer = [0u8; SIZE];
+    while ctx.recv().await.is_some() {}
+    black_box(&buffer);
+}
+
+fn topology(config: AnyConfig, boxed: bool) -> Topology {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subjects = topology.local("subjects");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    // Actors aren't spawned, spawning such futures can overflow the stack.
+    let group = ActorGroup::new()
+        .config::<AnyConfig>()
+        .router(MapRouter::new(|_| Outcome::<u32>::Discard));
+    subjects.mount(if boxed {
+        group.boxed_exec(huge)
+    } else {
+        group.exec(huge)
+    });
+
+    topology
+}
+
+async fn start(topology: Topology) -> Vec<(String, String)> {
+    match do_start(topology, false, terminate).await {
+        Ok(()) => Vec::new(),
+        Err(error) => error
+            .errors
+            .into_iter()
+            .map(|error| (error.group, error.reason))
+            .collect(),
+    }
+}
+
+fn strict() -> AnyConfig {
+    AnyConfig::deserialize(toml! {
+        [common]
+        system.strict_exec_future_size = true
+    })
+    .unwrap()
+}
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn warned_above_limit() {
+    let capture = Capture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let topology = topology(AnyConfig::default(), false);
+    let graph = topology.graph();
+    let subjects = graph.groups.iter().find(|g| g.name == "subjects").unwrap();
+    assert!(subjects.exec_future_size.unwrap() >= SIZE);
+
+    // Only warned by default.
+    assert_eq!(start(topology).await, []);
+
+    let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    let warning = logs
+        .lines()
+        .find(|line| line.contains("the exec future is too large"))
+        .expect("no warning");
+    assert!(warning.contains("WARN"), "{warning}");
+    assert!(warning.contains("limit=64KiB"), "{warning}");
+}
+
+#[tokio::test]
+async fn strict_mode_fails_startup() {
+    let errors = start(topology(strict(), false)).await;
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert_eq!(errors[0].0, "subjects");
+    assert!(
+        errors[0]
+            .1
+            .contains("more than `system.max_exec_future_size` (64KiB)"),
+        "{errors:?}"
+    );
+
+    // The limit is configurable.
+    let config = AnyConfig::deserialize(toml! {
+        [common]
+        system.strict_exec_future_size = true
+        system.max_exec_future_size = "1MiB"
+    })
+    .unwrap();
+    assert_eq!(start(topology(config, false)).await, []);
+}
+
+#[tokio::test]
+async fn boxed_mounted_cleanly() {
+    let topology = topology(strict(), true);
+    let graph = topology.graph();
+    let subjects = graph.groups.iter().find(|g| g.name == "subjects").unwrap();
+    assert!(subjects.exec_future_size.unwrap() <= 16);
+
+    assert_eq!(start(topology).await, []);
+}
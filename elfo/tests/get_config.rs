@@ -0,0 +1,252 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::{AnyConfig, Secret},
+    messages::{
+        AppliedConfig, CircuitEdge, CircuitState, ConfigOrigin, GetConfig, SetCircuit, UpdateConfig,
+    },
+    prelude::*,
+    Topology,
+};
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[allow(dead_code)]
+    limit: u32,
+    #[allow(dead_code)]
+    password: Secret<String>,
+}
+
+// What is returned by `GetConfig`.
+#[derive(Debug, PartialEq, Deserialize)]
+struct RawConfig {
+    limit: u32,
+    password: String,
+}
+
+fn subject() -> Blueprint {
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} })
+}
+
+fn raw(applied: &AppliedConfig) -> RawConfig {
+    RawConfig::deserialize(applied.config.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn latest_is_returned() {
+    let config = toml! {
+        limit = 1
+        password = "hunter2"
+    };
+    let proxy = elfo::test::proxy(subject(), config).await;
+
+    let first = proxy.request(GetConfig::new("subject".into())).await;
+    assert_eq!(first.generation, 1);
+    assert_eq!(
+        raw(&first),
+        RawConfig {
+            limit: 1,
+            password: "<secret>".into(),
+        }
+    );
+
+    for limit in [2, 3] {
+        let config = AnyConfig::deserialize(toml! {
+            limit = limit
+            password = "hunter2"
+        })
+        .unwrap();
+        proxy.send(UpdateConfig::new(config)).await;
+    }
+
+    let last = proxy.request(GetConfig::new("subject".into())).await;
+    assert_eq!(last.generation, 3);
+    assert!(last.applied_at >= first.applied_at);
+    assert_eq!(raw(&last).limit, 3);
+    assert_eq!(raw(&last).password, "<secret>");
+    assert!(last.overrides.is_empty());
+
+    // Runtime overrides are reported.
+    let edge = CircuitEdge::new("subject".into(), "another".into());
+    proxy
+        .send(SetCircuit::new(edge, Some(CircuitState::Open)))
+        .await;
+    let overridden = proxy.request(GetConfig::new("subject".into())).await;
+    assert_eq!(overridden.generation, 3);
+    assert_eq!(overridden.overrides, ["circuit to another is forced open"]);
+}
+
+#[tokio::test]
+async fn forwarded_by_configurer() {
+    let config = AnyConfig::deserialize(toml! {
+        [subject]
+        limit = 42
+        password = "hunter2"
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subject = topology.local("subject");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    subject.mount(self::subject());
+
+    let configurers = topology
+        .locals()
+        .find(|g| g.name == "system.configurers")
+        .unwrap()
+        .addr;
+
+    let (applied, unknown) = do_start(topology, false, move |ctx, topology| async move {
+        let applied = ctx
+            .request_to(configurers, GetConfig::new("subject".into()))
+            .resolve()
+            .await;
+        let unknown = ctx
+            .request_to(configurers, GetConfig::new("unknown".into()))
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        (applied, unknown)
+    })
+    .await
+    .expect("cannot start");
+
+    let applied = applied.unwrap();
+    assert_eq!(applied.generation, 1);
+    assert_eq!(
+        raw(&applied),
+        RawConfig {
+            limit: 42,
+            password: "<secret>".into(),
+        }
+    );
+
+    assert!(unknown.unwrap_err().is_ignored());
+}
+
+#[tokio::test]
+async fn common_section_is_merged() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Effective {
+        enabled: bool,
+        limit: u32,
+        password: String,
+        nested: Nested,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Nested {
+        a: u32,
+        b: Leaves,
+        list: Vec<u32>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Leaves {
+        c: u32,
+        d: u32,
+    }
+
+    let config = AnyConfig::deserialize(toml! {
+        [common]
+        enabled = true
+        limit = 1
+        password = "common"
+
+        [common.nested]
+        a = 1
+        b = { c = 2, d = 3 }
+        list = [1, 2]
+
+        [subject]
+        password = "hunter2"
+
+        [subject.nested]
+        b = { c = 20 }
+        list = [3]
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let subject = topology.local("subject");
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    // The condition sees the effective config.
+    subject.mount_if(self::subject(), |config| config.get_bool("enabled"));
+
+    let configurers = topology
+        .locals()
+        .find(|g| g.name == "system.configurers")
+        .unwrap()
+        .addr;
+
+    let (direct, forwarded) = do_start(topology, false, move |ctx, topology| async move {
+        let direct = ctx
+            .request_to(
+                subject_addr,
+                GetConfig::new("subject".into()).with_provenance(),
+            )
+            .resolve()
+            .await;
+        let forwarded = ctx
+            .request_to(
+                configurers,
+                GetConfig::new("subject".into()).with_provenance(),
+            )
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        (direct, forwarded)
+    })
+    .await
+    .expect("cannot start");
+
+    let expected = Effective {
+        enabled: true,
+        limit: 1,
+        password: "<secret>".into(),
+        nested: Nested {
+            a: 1,
+            b: Leaves { c: 20, d: 3 },
+            // Arrays are replaced, not concatenated.
+            list: vec![3],
+        },
+    };
+
+    // Supervisors return the effective config, but don't know provenance.
+    let direct = direct.unwrap();
+    assert_eq!(Effective::deserialize(direct.config).unwrap(), expected);
+    assert!(direct.provenance.is_empty());
+
+    let forwarded = forwarded.unwrap();
+    assert_eq!(Effective::deserialize(forwarded.config).unwrap(), expected);
+
+    let provenance = forwarded
+        .provenance
+        .into_iter()
+        .map(|p| (p.path, p.origin))
+        .collect::<Vec<_>>();
+
+    let expected = [
+        ("enabled", ConfigOrigin::Common),
+        ("limit", ConfigOrigin::Common),
+        ("nested.a", ConfigOrigin::Common),
+        ("nested.b.c", ConfigOrigin::Group),
+        ("nested.b.d", ConfigOrigin::Common),
+        ("nested.list", ConfigOrigin::Group),
+        ("password", ConfigOrigin::Group),
+    ]
+    .map(|(path, origin)| (path.to_owned(), origin));
+    assert_eq!(provenance, expected);
+}
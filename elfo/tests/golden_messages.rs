@@ -0,0 +1,61 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::collections::BTreeMap;
+
+use elfo::{prelude::*, test::golden::Samples};
+
+#[message]
+struct Quote {
+    symbol: String,
+    price: f64,
+    venue: Option<String>,
+}
+
+#[message]
+enum Side {
+    Buy,
+    Sell { reason: String },
+}
+
+#[message]
+struct Book {
+    levels: BTreeMap<u32, u64>,
+}
+
+impl Samples for Quote {
+    fn samples() -> Vec<Self> {
+        vec![
+            Quote {
+                symbol: "BTC".into(),
+                price: 1.5,
+                venue: None,
+            },
+            Quote {
+                symbol: "ETH".into(),
+                price: -0.25,
+                venue: Some("binance".into()),
+            },
+        ]
+    }
+}
+
+impl Samples for Side {
+    fn samples() -> Vec<Self> {
+        vec![
+            Side::Buy,
+            Side::Sell {
+                reason: "limit".into(),
+            },
+        ]
+    }
+}
+
+impl Samples for Book {
+    fn samples() -> Vec<Self> {
+        let levels = (0..5).map(|i| (i, u64::from(i) * 10)).collect();
+        vec![Book { levels }]
+    }
+}
+
+elfo::test::golden_messages!(Quote, Side, Book);
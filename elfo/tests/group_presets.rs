@@ -0,0 +1,192 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    config::{AnyConfig, Extended},
+    messages::Ping,
+    prelude::*,
+    routers::{Outcome, Router},
+    Envelope, Preset,
+};
+
+#[message]
+struct Dummy;
+
+#[message]
+struct Hidden;
+
+#[message(ret = ())]
+struct Freeze;
+
+#[message]
+struct Seen(u32);
+
+fn exec<R: Router<()>>(group: ActorGroup<R, ()>) -> Blueprint {
+    group.exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Dummy => ctx.send(Seen(1)).await.unwrap(),
+                Hidden => ctx.send(Seen(2)).await.unwrap(),
+                (Freeze, token) => {
+                    ctx.respond(token, ());
+                    tokio::time::sleep(Duration::from_secs(60)).await
+                }
+            });
+        }
+    })
+}
+
+fn assert_capacity(proxy: &elfo::test::Proxy, capacity: usize) {
+    for i in 1..=capacity {
+        assert!(proxy.try_send(Dummy).is_ok(), "shoud pass [{i}/{capacity}]");
+    }
+    assert!(proxy.try_send(Dummy).is_err(), "should reject [{capacity}]");
+}
+
+// A router wrapper discarding `Hidden` messages.
+struct Filtered<R>(R);
+
+impl<C, R: Router<C>> Router<C> for Filtered<R> {
+    type Key = R::Key;
+
+    fn update(&self, config: &C) {
+        self.0.update(config);
+    }
+
+    fn route(&self, envelope: &Envelope) -> Outcome<Self::Key> {
+        if envelope.is::<Hidden>() {
+            Outcome::Discard
+        } else {
+            self.0.route(envelope)
+        }
+    }
+}
+
+// A preset implemented as a struct.
+struct Common {
+    mounted: Arc<Mutex<Vec<String>>>,
+}
+
+impl<R: Router<C>, C> Preset<R, C> for Common {
+    type Router = Filtered<R>;
+    type Config = C;
+
+    fn apply(self, group: ActorGroup<R, C>) -> ActorGroup<Filtered<R>, C> {
+        let mounted = self.mounted;
+        group
+            .mailbox_capacity(5)
+            .map_router(Filtered)
+            .on_mount(move |name| mounted.lock().unwrap().push(name.into()))
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn preset_is_observable() {
+    let mounted = Arc::new(Mutex::new(Vec::new()));
+    let group = ActorGroup::new().apply(Common {
+        mounted: mounted.clone(),
+    });
+
+    let mut proxy = elfo::test::proxy(exec(group), AnyConfig::default()).await;
+    assert_eq!(*mounted.lock().unwrap(), vec!["subject".to_string()]);
+
+    // The router wrapper.
+    proxy.send(Dummy).await;
+    assert_msg!(proxy.recv().await, Seen(1));
+    assert!(proxy.try_send(Hidden).is_err());
+
+    // The mailbox capacity.
+    proxy.request(Freeze).await;
+    assert_capacity(&proxy, 5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn presets_are_applied_in_order() {
+    let group = ActorGroup::new()
+        .apply(elfo::presets::production())
+        .apply(|group: ActorGroup<_, _>| group.mailbox_capacity(3));
+
+    let mut proxy = elfo::test::proxy(exec(group), AnyConfig::default()).await;
+
+    proxy.request(Freeze).await;
+    assert_capacity(&proxy, 3);
+
+    // Consume the `Seen` messages and ensure all sent messages are handled.
+    while proxy.try_recv().await.is_some() {}
+    proxy.request(Ping::default()).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn config_overrides_preset() {
+    let config = AnyConfig::deserialize(toml! {
+        system.mailbox.capacity = 2
+    })
+    .unwrap();
+
+    let group = ActorGroup::new().apply(elfo::presets::production());
+    let proxy = elfo::test::proxy(exec(group), config).await;
+
+    proxy.request(Freeze).await;
+    assert_capacity(&proxy, 2);
+}
+
+#[derive(Debug, Deserialize)]
+struct Own {
+    value: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Extra {
+    bonus: u32,
+}
+
+// A preset adding a middleware and its own config fields.
+fn extending<R>(group: ActorGroup<R, Own>) -> ActorGroup<R, Extended<Own, Extra>> {
+    group
+        .intercept(|envelope| (!envelope.is::<Hidden>()).then_some(envelope))
+        .config()
+}
+
+#[tokio::test(start_paused = true)]
+async fn preset_intercepts_and_extends_config() {
+    let group =
+        ActorGroup::new()
+            .config::<Own>()
+            .apply(extending)
+            .exec(move |mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        Dummy => {
+                            let config = ctx.config();
+                            let value = config.value + config.extra().bonus;
+                            ctx.send(Seen(value)).await.unwrap();
+                        }
+                        Hidden => ctx.send(Seen(0)).await.unwrap(),
+                    });
+                }
+            });
+
+    let config = AnyConfig::deserialize(toml! {
+        value = 10
+        bonus = 5
+    })
+    .unwrap();
+
+    let mut proxy = elfo::test::proxy(group, config).await;
+
+    proxy.send(Hidden).await;
+    proxy.send(Dummy).await;
+    assert_msg!(proxy.recv().await, Seen(15));
+
+    // System messages bypass interceptors.
+    proxy.request(Ping::default()).await;
+    assert!(proxy.try_recv().await.is_none());
+}
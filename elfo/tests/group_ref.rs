@@ -0,0 +1,160 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::{StartEntrypoint, UpdateConfig},
+    prelude::*,
+    Addr, GroupRef, Topology,
+};
+
+mod common;
+
+#[message]
+struct Ping(u32);
+
+#[message]
+struct Pong(u32);
+
+#[derive(Debug, Deserialize)]
+struct ExporterConfig {
+    #[allow(dead_code)]
+    enabled: bool,
+}
+
+fn exporter() -> Blueprint {
+    ActorGroup::new()
+        .config::<ExporterConfig>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                let sender = envelope.sender();
+                msg!(match envelope {
+                    Ping(n) => ctx.send_to(sender, Pong(n)).await.unwrap(),
+                });
+            }
+        })
+}
+
+#[derive(Debug, Deserialize)]
+struct DriverConfig {
+    target: GroupRef,
+}
+
+#[message(ret = ())]
+struct Run;
+
+fn driver(exporter: Addr) -> Blueprint {
+    ActorGroup::new()
+        .config::<DriverConfig>()
+        .exec(move |mut ctx| async move {
+            async fn pong(ctx: &mut Context<DriverConfig>) -> u32 {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        Pong(n) => return n,
+                        _ => {}
+                    });
+                }
+                unreachable!()
+            }
+
+            // Fail fast on unknown groups.
+            let group = ctx.locate_group("exporter").unwrap();
+            assert_eq!(group.name(), "exporter");
+            assert!(!group.is_remote());
+            let err = ctx.locate_group("exporterr").unwrap_err();
+            assert_eq!(err.name, "exporterr");
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Run, token) => {
+                        ctx.send_to_group(&group, Ping(1)).await.unwrap();
+                        assert_eq!(pong(&mut ctx).await, 1);
+
+                        // Refs from the config are resolved on sending.
+                        let from_config = ctx.config().target.clone();
+                        ctx.send_to_group(&from_config, Ping(2)).await.unwrap();
+                        assert_eq!(pong(&mut ctx).await, 2);
+
+                        // Disabled by a config update.
+                        let update = UpdateConfig::new(
+                            AnyConfig::deserialize(toml! { enabled = false }).unwrap(),
+                        );
+                        ctx.request_to(exporter, update)
+                            .resolve()
+                            .await
+                            .unwrap()
+                            .unwrap();
+
+                        let err = ctx.detailed().send_to_group(&group, Ping(3)).await;
+                        let err = err.unwrap_err();
+                        assert_eq!(err.kind(), ErrorKind::GroupDisabled);
+                        assert_eq!(err.context().group.as_deref(), Some("exporter"));
+                        let err = ctx.detailed().send_to_group(&from_config, Ping(4)).await;
+                        assert_eq!(err.unwrap_err().kind(), ErrorKind::GroupDisabled);
+
+                        ctx.respond(token, ());
+                    }
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn locate_send_and_disable() {
+    common::setup_logger();
+
+    let config = AnyConfig::deserialize(toml! {
+        [exporter]
+        enabled = true
+
+        [driver]
+        target = "exporter"
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let exporter = topology.local("exporter");
+    let driver = topology.local("driver");
+    let driver_addr = driver.addr();
+
+    assert_eq!(exporter.group_ref().name(), "exporter");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    driver.mount(self::driver(exporter.addr()));
+    exporter.mount_if(self::exporter(), |config| config.get_bool("enabled"));
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let res = ctx.request_to(driver_addr, Run).resolve().await;
+        terminate(ctx, topology).await;
+        res
+    })
+    .await
+    .expect("cannot start")
+    .expect("driver failed");
+}
+
+#[tokio::test]
+async fn unknown_group_from_config() {
+    common::setup_logger();
+
+    let config: DriverConfig = toml::from_str(r#"target = "missing""#).unwrap();
+    let group = config.target;
+
+    do_start(Topology::empty(), false, move |ctx, topology| async move {
+        let err = ctx.detailed().send_to_group(&group, Ping(1)).await;
+        let err = err.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NoRoute);
+        assert_eq!(err.context().group.as_deref(), Some("missing"));
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,195 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    Addr, Context, IdempotencyKey, RequestLimits, Topology,
+};
+
+mod common;
+
+// Charges the amount after the delay and returns the balance.
+#[message(ret = u64)]
+struct Charge(u64);
+
+#[message(ret = u32)]
+struct GetExecutions;
+
+fn charger(delay: Duration) -> Blueprint {
+    ActorGroup::new()
+        .idempotency_cache(16, Duration::from_secs(60))
+        .exec(move |mut ctx| async move {
+            let mut balance = 0;
+            let mut executions = 0;
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Charge(amount), token) => {
+                        // Ignores the deadline, so the response is dropped.
+                        tokio::time::sleep(delay).await;
+                        executions += 1;
+                        balance += amount;
+                        ctx.respond(token, balance);
+                    }
+                    (GetExecutions, token) => ctx.respond(token, executions),
+                });
+            }
+        })
+}
+
+/// Returns results of the first attempt, its retry and a new request,
+/// and the number of executions. The recipient is routed if `None`.
+async fn charge_with_retry(
+    ctx: &Context,
+    to: Option<Addr>,
+    limit: Duration,
+) -> (Vec<Result<u64, ErrorKind>>, u32) {
+    let charge = |amount, key, limits| {
+        let request = match to {
+            Some(addr) => ctx.request_to(addr, Charge(amount)),
+            None => ctx.request(Charge(amount)),
+        };
+        let request = request.idempotency_key(key).limits(limits);
+        async move { request.resolve_detailed().await.map_err(|err| err.kind()) }
+    };
+
+    let key = IdempotencyKey::generate();
+    let limited = RequestLimits::default().max_handling_time(limit);
+
+    let results = vec![
+        charge(10, key, limited).await,
+        charge(10, key, RequestLimits::default()).await,
+        charge(5, IdempotencyKey::generate(), RequestLimits::default()).await,
+    ];
+
+    let executions = match to {
+        Some(addr) => ctx.request_to(addr, GetExecutions).resolve().await,
+        None => ctx.request(GetExecutions).resolve().await,
+    };
+
+    (results, executions.unwrap())
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_replays_response() {
+    common::setup_logger();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let chargers = topology.local("chargers").entrypoint();
+    let chargers_addr = chargers.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    chargers.mount(charger(Duration::from_secs(10)));
+
+    do_start(topology, false, |ctx, topology| async move {
+        let limit = Duration::from_secs(5);
+        let (results, executions) = charge_with_retry(&ctx, Some(chargers_addr), limit).await;
+
+        // The retry gets the response to the first attempt.
+        assert_eq!(results, [Err(ErrorKind::LimitExceeded), Ok(10), Ok(15)]);
+        assert_eq!(executions, 2);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn retry_replays_remote_response() {
+    use tokio::sync::mpsc;
+    use toml::toml;
+
+    use elfo::topology;
+
+    common::setup_logger();
+
+    fn requester(tx: mpsc::UnboundedSender<(Vec<Result<u64, ErrorKind>>, u32)>) -> Blueprint {
+        ActorGroup::new().exec(move |mut ctx| {
+            let tx = tx.clone();
+            async move {
+                let envelope = ctx.recv().await.unwrap();
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                });
+
+                // Wait for the connection.
+                while ctx.request(GetExecutions).resolve().await.is_err() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                let limit = Duration::from_millis(100);
+                let (results, executions) = charge_with_retry(&ctx, None, limit).await;
+                let _ = tx.send((results, executions));
+            }
+        })
+    }
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let chargers = server.local("chargers").entrypoint();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://retry_replays_remote_response"]
+        },
+    ));
+    chargers.mount(charger(Duration::from_millis(300)));
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let requesters = client.local("requesters").entrypoint();
+    let chargers = client.remote("chargers");
+
+    requesters.route_to(&chargers, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://retry_replays_remote_response"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    requesters.mount(requester(tx));
+
+    let (results, executions) = do_start(server, false, |ctx, server| async move {
+        let res = do_start(client, false, |ctx, client| async move {
+            let res = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await;
+            terminate(ctx, client).await;
+            res
+        })
+        .await;
+        terminate(ctx, server).await;
+        res
+    })
+    .await
+    .expect("cannot start server")
+    .expect("cannot start client")
+    .expect("timeout")
+    .unwrap();
+
+    assert_eq!(results, [Err(ErrorKind::LimitExceeded), Ok(10), Ok(15)]);
+    assert_eq!(executions, 2);
+}
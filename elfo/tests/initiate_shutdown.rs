@@ -0,0 +1,95 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+
+use elfo::{init::ShutdownReason, messages::StartEntrypoint, prelude::*, Topology};
+
+mod common;
+
+type Log = Arc<Mutex<Vec<&'static str>>>;
+
+// Initiates shutdown with every reason once started.
+fn sample(name: &'static str, reasons: &'static [&'static str], log: Log) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let log = log.clone();
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => {
+                        ctx.respond(token, Ok(()));
+                        for reason in reasons {
+                            ctx.initiate_shutdown(*reason);
+                        }
+                    }
+                    _ => {}
+                });
+            }
+
+            log.lock().push(name);
+        }
+    })
+}
+
+fn topology(trigger_reasons: &'static [&'static str], log: &Log) -> Topology {
+    let topology = Topology::empty();
+    let trigger = topology.local("trigger").entrypoint();
+    let late = topology.local("late").entrypoint();
+
+    topology.shutdown_order([&trigger, &late]);
+
+    trigger.mount(sample("trigger", trigger_reasons, log.clone()));
+    late.mount(sample("late", &[], log.clone()));
+    topology
+}
+
+#[tokio::test]
+async fn actor_initiates_shutdown() {
+    common::setup_logger();
+
+    let log = Log::default();
+    let topology = topology(&["fatal", "ignored"], &log);
+
+    let reason = elfo::init::try_run(topology.clone()).await.unwrap();
+
+    // Duplicate requests are coalesced to the first reason.
+    assert_eq!(reason, ShutdownReason::Requested("fatal".into()));
+    assert!(!topology.initiate_shutdown("too late"));
+
+    // The shutdown order is respected.
+    assert_eq!(*log.lock(), ["trigger", "late"]);
+
+    #[cfg(feature = "dumping")]
+    {
+        let dumps = topology.dump_capture().snapshot();
+        let dump = dumps
+            .iter()
+            .find(|dump| dump.message_name == "ShutdownInitiated")
+            .expect("the reason isn't dumped");
+        assert_eq!(dump.meta.group, "system.init");
+        assert!(format!("{:?}", dump.message).contains("fatal"));
+    }
+}
+
+#[tokio::test]
+async fn shutdown_is_initiated_outside_actors() {
+    common::setup_logger();
+
+    let log = Log::default();
+    let topology = topology(&[], &log);
+
+    tokio::spawn({
+        let topology = topology.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(topology.initiate_shutdown("external"));
+        }
+    });
+
+    let reason = elfo::init::try_run(topology).await.unwrap();
+    assert_eq!(reason, ShutdownReason::Requested("external".into()));
+    assert_eq!(reason.to_string(), "requested: external");
+    assert_eq!(*log.lock(), ["trigger", "late"]);
+}
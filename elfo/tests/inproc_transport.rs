@@ -0,0 +1,1354 @@
+#![allow(missing_docs)]
+#![cfg(feature = "network")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::{mpsc, watch};
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::{AckError, ErrorKind},
+    messages::{StartEntrypoint, UpdateConfig},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    topology, Addr, Context, RequestLimits, RestartParams, RestartPolicy, Topology,
+};
+
+mod common;
+
+#[message(part)]
+#[derive(PartialEq)]
+struct Exact {
+    value: u32,
+}
+
+#[message(part)]
+#[derive(PartialEq)]
+struct Lossy {
+    value: u32,
+    // Forgotten on serialization, so it breaks only remote sends.
+    #[serde(skip)]
+    cached: u32,
+}
+
+#[message(ret = Exact)]
+struct EchoExact(Exact);
+
+#[message(ret = Lossy)]
+struct EchoLossy(Lossy);
+
+fn echo() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (EchoExact(exact), token) => ctx.respond(token, exact),
+                (EchoLossy(lossy), token) => ctx.respond(token, lossy),
+            });
+        }
+    })
+}
+
+fn requester(tx: mpsc::UnboundedSender<(Exact, Lossy)>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let tx = tx.clone();
+        async move {
+            let mut is_started = false;
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => {
+                        ctx.respond(token, Ok(()));
+                        is_started = true;
+                    }
+                });
+
+                if !is_started {
+                    continue;
+                }
+
+                // Wait for the connection.
+                let exact = loop {
+                    if let Ok(exact) = ctx.request(EchoExact(Exact { value: 42 })).resolve().await {
+                        break exact;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                };
+
+                let lossy = Lossy {
+                    value: 42,
+                    cached: 42,
+                };
+                let lossy = ctx.request(EchoLossy(lossy)).resolve().await.unwrap();
+
+                let _ = tx.send((exact, lossy));
+                break;
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn lossy_serde_is_caught() {
+    common::setup_logger();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let echoes = server.local("echoes").entrypoint();
+    let requesters = server.remote("requesters");
+
+    echoes.route_to(&requesters, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://lossy_serde_is_caught"]
+        },
+    ));
+    echoes.mount(echo());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let requesters = client.local("requesters").entrypoint();
+    let echoes = client.remote("echoes");
+
+    requesters.route_to(&echoes, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://lossy_serde_is_caught"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    requesters.mount(requester(tx));
+
+    let (exact, lossy) = do_start(server, false, |ctx, server| async move {
+        let res = do_start(client, false, |ctx, client| async move {
+            let res = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await;
+            terminate(ctx, client).await;
+            res
+        })
+        .await;
+        terminate(ctx, server).await;
+        res
+    })
+    .await
+    .expect("cannot start server")
+    .expect("cannot start client")
+    .expect("timeout")
+    .unwrap();
+
+    assert_eq!(exact, Exact { value: 42 });
+
+    // Local sends would keep `cached`.
+    assert_ne!(
+        lossy,
+        Lossy {
+            value: 42,
+            cached: 42
+        }
+    );
+    assert_eq!(lossy.cached, 0);
+}
+
+// Requests are stamped by the connecting node and checked by the listener.
+#[tokio::test]
+async fn replay_protection() {
+    common::setup_logger();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let echoes = server.local("echoes").entrypoint();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://replay_protection"]
+            replay_protection.enabled = true
+            replay_protection.window = 4
+            replay_protection.strict = true
+        },
+    ));
+    echoes.mount(echo());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let requesters = client.local("requesters").entrypoint();
+    let echoes = client.remote("echoes");
+
+    requesters.route_to(&echoes, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://replay_protection"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    requesters.mount(requester(tx));
+
+    let (exact, lossy) = do_start(server, false, |ctx, server| async move {
+        let res = do_start(client, false, |ctx, client| async move {
+            let res = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await;
+            terminate(ctx, client).await;
+            res
+        })
+        .await;
+        terminate(ctx, server).await;
+        res
+    })
+    .await
+    .expect("cannot start server")
+    .expect("cannot start client")
+    .expect("timeout")
+    .unwrap();
+
+    assert_eq!(exact, Exact { value: 42 });
+    assert_eq!(lossy.value, 42);
+}
+
+#[message(ret = u32)]
+struct Increment(u32);
+
+#[message(ret = Result<u32, String>)]
+struct Probe(u32);
+
+#[derive(Debug, Deserialize)]
+struct IncrementerConfig {
+    #[allow(dead_code)]
+    enabled: bool,
+}
+
+fn incrementer() -> Blueprint {
+    ActorGroup::new()
+        .config::<IncrementerConfig>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Increment(n), token) => ctx.respond(token, n + 1),
+                });
+            }
+        })
+}
+
+// Forwards probes to the remote group.
+fn prober() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Probe(n), token) => {
+                    let res = ctx.request(Increment(n)).resolve_detailed().await;
+                    ctx.respond(token, res.map_err(|err| err.kind().to_string()));
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn remounted_group_is_reachable() {
+    common::setup_logger();
+
+    fn config(enabled: bool) -> AnyConfig {
+        AnyConfig::deserialize(toml! { enabled = enabled }).unwrap()
+    }
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let incrementers = server.local("incrementers");
+    let incrementers_addr = incrementers.addr();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://remounted_group_is_reachable"]
+
+            [incrementers]
+            enabled = true
+        },
+    ));
+    incrementers.mount_if(incrementer(), |config| config.get_bool("enabled"));
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let probers = client.local("probers").entrypoint();
+    let probers_addr = probers.addr();
+    let incrementers = client.remote("incrementers");
+
+    probers.route_to(&incrementers, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://remounted_group_is_reachable"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    probers.mount(prober());
+
+    let probe = |ctx: Context, n| async move {
+        ctx.request_to(probers_addr, Probe(n))
+            .resolve()
+            .await
+            .unwrap()
+    };
+
+    let update = |ctx: Context, enabled| async move {
+        ctx.request_to(incrementers_addr, UpdateConfig::new(config(enabled)))
+            .resolve()
+            .await
+            .unwrap()
+            .unwrap()
+    };
+
+    let wait_delivery = |ctx: Context, n| async move {
+        loop {
+            if probe(ctx.pruned(), n).await == Ok(n + 1) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    do_start(server, false, |server_ctx, server| async move {
+        let server_ctx = &server_ctx;
+        do_start(client, false, |client_ctx, client| async move {
+            let scenario = async {
+                // Wait for the connection.
+                wait_delivery(client_ctx.pruned(), 1).await;
+
+                // Disabled groups are reported to the remote sender.
+                update(server_ctx.pruned(), false).await;
+                let res = probe(client_ctx.pruned(), 2).await;
+                assert_eq!(res, Err(ErrorKind::NoRoute.to_string()));
+
+                // Remounted groups are reachable without reconnecting.
+                update(server_ctx.pruned(), true).await;
+                wait_delivery(client_ctx.pruned(), 3).await;
+            };
+
+            let res = tokio::time::timeout(Duration::from_secs(10), scenario).await;
+            terminate(client_ctx, client).await;
+            res
+        })
+        .await
+        .expect("cannot start client")
+        .expect("timeout");
+
+        terminate(server_ctx.pruned(), server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
+
+#[message(ret = u32)]
+struct Count;
+
+#[message]
+struct Touch;
+
+#[message(ret = ())]
+struct Crash;
+
+#[message(ret = Result<u32, String>)]
+struct Pin;
+
+#[message(ret = Result<u32, String>)]
+struct CountPinned;
+
+#[message(ret = Result<(), String>)]
+struct TouchPinned;
+
+// Counts requests and touches, the counter is reset on restart.
+fn counter() -> Blueprint {
+    ActorGroup::new()
+        .restart_policy(RestartPolicy::on_failure(RestartParams::new(
+            Duration::ZERO,
+            Duration::ZERO,
+        )))
+        .exec(|mut ctx| async move {
+            let mut count = 0;
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Count, token) => {
+                        count += 1;
+                        ctx.respond(token, count);
+                    }
+                    Touch => count += 1,
+                    (Crash, token) => {
+                        drop(token);
+                        panic!("boom!");
+                    }
+                });
+            }
+        })
+}
+
+// Pins the responder of the routed request and talks to it directly.
+fn pinner() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut pinned = Addr::NULL;
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Pin, token) => {
+                    let res = match ctx.request(Count).resolve_with_responder().await {
+                        Ok((_, responder)) if !responder.is_remote() => {
+                            Err(format!("{responder} isn't remote"))
+                        }
+                        Ok((_, responder)) if responder == pinned => {
+                            Err(format!("{responder} is reused"))
+                        }
+                        Ok((count, responder)) => {
+                            pinned = responder;
+                            Ok(count)
+                        }
+                        Err(err) => Err(err.to_string()),
+                    };
+                    ctx.respond(token, res);
+                }
+                (CountPinned, token) => {
+                    let res = ctx.request_to(pinned, Count).resolve().await;
+                    ctx.respond(token, res.map_err(|err| err.to_string()));
+                }
+                (TouchPinned, token) => {
+                    let res = ctx.send_to(pinned, Touch).await;
+                    ctx.respond(token, res.map_err(|err| err.to_string()));
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn pinned_responder() {
+    common::setup_logger();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let counters = server.local("counters");
+    let counters_addr = counters.addr();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://pinned_responder"]
+        },
+    ));
+    counters.mount(counter());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let pinners = client.local("pinners").entrypoint();
+    let pinners_addr = pinners.addr();
+    let counters = client.remote("counters");
+
+    pinners.route_to(&counters, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://pinned_responder"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    pinners.mount(pinner());
+
+    async fn request<R: elfo::Request>(ctx: &Context, addr: Addr, request: R) -> R::Response {
+        ctx.request_to(addr, request).resolve().await.unwrap()
+    }
+
+    do_start(server, false, |server_ctx, server| async move {
+        let server_ctx = &server_ctx;
+        do_start(client, false, |client_ctx, client| async move {
+            let scenario = async {
+                // Wait for the connection.
+                while request(&client_ctx, pinners_addr, Pin).await.is_err() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                // Direct sends reach the same actor.
+                assert_eq!(request(&client_ctx, pinners_addr, CountPinned).await, Ok(2));
+                assert_eq!(
+                    request(&client_ctx, pinners_addr, TouchPinned).await,
+                    Ok(())
+                );
+                assert_eq!(request(&client_ctx, pinners_addr, CountPinned).await, Ok(4));
+
+                // The failed actor is restarted immediately with a new address.
+                let res = server_ctx.request_to(counters_addr, Crash).resolve().await;
+                assert!(res.is_err());
+
+                // The old address isn't re-routed.
+                assert!(request(&client_ctx, pinners_addr, CountPinned)
+                    .await
+                    .is_err());
+
+                // Sends fail after the remote node reports the actor as gone.
+                while request(&client_ctx, pinners_addr, TouchPinned)
+                    .await
+                    .is_ok()
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                assert!(request(&client_ctx, pinners_addr, CountPinned)
+                    .await
+                    .is_err());
+
+                // The restarted actor can be pinned again.
+                assert_eq!(request(&client_ctx, pinners_addr, Pin).await, Ok(1));
+                assert_eq!(request(&client_ctx, pinners_addr, CountPinned).await, Ok(2));
+
+                // Dumps record the concrete destination.
+                #[cfg(all(feature = "test-util", feature = "dumping"))]
+                {
+                    let dumps = client.dump_capture().snapshot();
+                    let touch = dumps
+                        .iter()
+                        .find(|dump| dump.message_name == "Touch" && !dump.is_incoming)
+                        .expect("missing dump");
+                    assert!(touch.recipient.is_remote());
+                }
+            };
+
+            let res = tokio::time::timeout(Duration::from_secs(10), scenario).await;
+            terminate(client_ctx, client).await;
+            res
+        })
+        .await
+        .expect("cannot start client")
+        .expect("timeout");
+
+        terminate(server_ctx.pruned(), server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
+
+#[tokio::test]
+async fn connection_stats() {
+    use elfo::batteries::network::{ConnectionState, ConnectionStats, GetConnectionStats};
+
+    common::setup_logger();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let server_network_addr = network.addr();
+    let incrementers = server.local("incrementers");
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://connection_stats"]
+
+            [incrementers]
+            enabled = true
+        },
+    ));
+    incrementers.mount(incrementer());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let client_network_addr = network.addr();
+    let probers = client.local("probers").entrypoint();
+    let probers_addr = probers.addr();
+    let incrementers = client.remote("incrementers");
+
+    probers.route_to(&incrementers, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://connection_stats"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    probers.mount(prober());
+
+    async fn get_stats(ctx: &Context, addr: Addr, remote_group: &str) -> ConnectionStats {
+        let stats = ctx
+            .request_to(addr, GetConnectionStats::default())
+            .resolve()
+            .await
+            .unwrap();
+
+        stats
+            .into_iter()
+            .find(|conn| conn.remote_group == remote_group)
+            .expect("missing connection")
+    }
+
+    do_start(server, false, |server_ctx, server| async move {
+        let server_ctx = &server_ctx;
+        do_start(client, false, |client_ctx, client| async move {
+            let scenario = async {
+                // Wait for the connection.
+                loop {
+                    let res = client_ctx
+                        .request_to(probers_addr, Probe(1))
+                        .resolve()
+                        .await;
+                    if res.unwrap() == Ok(2) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                let before = get_stats(&client_ctx, client_network_addr, "incrementers").await;
+                assert_eq!(before.local_group, "probers");
+                assert_eq!(before.state, ConnectionState::Active);
+                assert!(before.addr.is_some());
+                assert!(before.codec.is_some());
+                assert!(before.protocol_version.is_some());
+                assert!(before.uptime.is_some());
+                assert!(before.bytes_out > 0);
+                assert!(before.bytes_in > 0);
+                assert!(before.messages_out > 0);
+                assert!(before.messages_in > 0);
+                assert_eq!(before.reconnects, 0);
+
+                for n in 0..10 {
+                    let res = client_ctx
+                        .request_to(probers_addr, Probe(n))
+                        .resolve()
+                        .await;
+                    assert_eq!(res.unwrap(), Ok(n + 1));
+                }
+
+                // Counters move after traffic.
+                let after = get_stats(&client_ctx, client_network_addr, "incrementers").await;
+                assert!(after.bytes_out > before.bytes_out);
+                assert!(after.bytes_in > before.bytes_in);
+                assert!(after.messages_out >= before.messages_out + 10);
+                assert!(after.messages_in >= before.messages_in + 10);
+
+                // The peer sees the same connection from its side.
+                let server_side = get_stats(server_ctx, server_network_addr, "probers").await;
+                assert_eq!(server_side.local_group, "incrementers");
+                assert_eq!(server_side.state, ConnectionState::Active);
+                assert!(server_side.messages_in >= 10);
+            };
+
+            let res = tokio::time::timeout(Duration::from_secs(10), scenario).await;
+            terminate(client_ctx, client).await;
+            res
+        })
+        .await
+        .expect("cannot start client")
+        .expect("timeout");
+
+        terminate(server_ctx.pruned(), server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
+
+#[message(ret = u64)]
+struct Compute(u64);
+
+#[message(ret = Vec<u8>)]
+struct Select(usize);
+
+#[message(ret = Vec<String>)]
+struct GetHandled;
+
+#[message(ret = Result<usize, String>)]
+struct LimitedRequest {
+    compute: u64,
+    select: usize,
+    max_handling_time: Option<Duration>,
+    max_response_size: Option<usize>,
+}
+
+// Handles `Compute` for the specified number of seconds.
+fn computer() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut handled = Vec::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Compute(secs), token) => {
+                    let computing = tokio::time::sleep(Duration::from_secs(secs));
+                    if ctx.within_deadline(computing).await.is_some() {
+                        handled.push(format!("computed {secs}"));
+                        ctx.respond(token, secs);
+                    } else {
+                        handled.push(format!("cancelled {secs}"));
+                    }
+                }
+                (Select(size), token) => ctx.respond(token, vec![0; size]),
+                (GetHandled, token) => ctx.respond(token, std::mem::take(&mut handled)),
+            });
+        }
+    })
+}
+
+// Sends limited requests to the remote group.
+fn limited_requester() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (request @ LimitedRequest { .. }, token) => {
+                    let mut limits = RequestLimits::default();
+                    limits.max_handling_time = request.max_handling_time;
+                    limits.max_response_size = request.max_response_size;
+
+                    let res = ctx
+                        .request(Compute(request.compute))
+                        .limits(limits)
+                        .resolve_detailed()
+                        .await;
+                    let res = match res {
+                        Ok(_) => {
+                            let select = Select(request.select);
+                            let request = ctx.request(select).limits(limits);
+                            request.resolve_detailed().await
+                        }
+                        Err(err) => Err(err),
+                    };
+                    ctx.respond(
+                        token,
+                        res.map(|rows| rows.len())
+                            .map_err(|err| err.kind().to_string()),
+                    );
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn request_limits() {
+    common::setup_logger();
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let computers = server.local("computers");
+    let computers_addr = computers.addr();
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://request_limits"]
+        },
+    ));
+    computers.mount(computer());
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let requesters = client.local("requesters").entrypoint();
+    let requesters_addr = requesters.addr();
+    let computers = client.remote("computers");
+
+    requesters.route_to(&computers, |_, _| topology::Outcome::Broadcast);
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://request_limits"]
+            discovery.attempt_interval = "10ms"
+        },
+    ));
+    requesters.mount(limited_requester());
+
+    let request = |ctx: Context, compute, select, limits: RequestLimits| async move {
+        let request = LimitedRequest {
+            compute,
+            select,
+            max_handling_time: limits.max_handling_time,
+            max_response_size: limits.max_response_size,
+        };
+        ctx.request_to(requesters_addr, request)
+            .resolve()
+            .await
+            .unwrap()
+    };
+
+    do_start(server, false, |server_ctx, server| async move {
+        let server_ctx = &server_ctx;
+        do_start(client, false, |client_ctx, client| async move {
+            let time_limit = RequestLimits::default().max_handling_time(Duration::from_secs(5));
+            let size_limit = RequestLimits::default().max_response_size(32);
+
+            // Wait for the connection.
+            loop {
+                let res = request(client_ctx.pruned(), 0, 0, RequestLimits::default()).await;
+                if res == Ok(0) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            // The deadline is exceeded on both nodes.
+            let started_at = tokio::time::Instant::now();
+            let res = request(client_ctx.pruned(), 10, 0, time_limit).await;
+            assert_eq!(res, Err(ErrorKind::LimitExceeded.to_string()));
+            assert!(started_at.elapsed() < Duration::from_secs(6));
+
+            assert_eq!(request(client_ctx.pruned(), 2, 0, time_limit).await, Ok(0));
+
+            // Oversized responses are rejected by the remote responder.
+            assert_eq!(
+                request(client_ctx.pruned(), 0, 16, size_limit).await,
+                Ok(16)
+            );
+            let res = request(client_ctx.pruned(), 0, 64, size_limit).await;
+            assert_eq!(res, Err(ErrorKind::LimitExceeded.to_string()));
+
+            // Wait for the remote handling to be cancelled.
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let handled = server_ctx
+                .request_to(computers_addr, GetHandled)
+                .resolve()
+                .await
+                .unwrap();
+            assert!(handled.contains(&"cancelled 10".to_string()));
+            assert!(handled.contains(&"computed 2".to_string()));
+
+            terminate(client_ctx, client).await;
+        })
+        .await
+        .expect("cannot start client");
+
+        terminate(server_ctx.pruned(), server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
+
+#[message]
+struct Notify;
+
+#[message]
+struct Unroutable;
+
+// Sent successfully, but cannot be decoded by the receiving node.
+#[message]
+struct Poisoned(#[serde(deserialize_with = "fail_decoding")] u32);
+
+fn fail_decoding<'de, D: serde::Deserializer<'de>>(_: D) -> Result<u32, D::Error> {
+    Err(serde::de::Error::custom("poisoned"))
+}
+
+#[message]
+struct ToClosed;
+
+#[message]
+struct ToStuck;
+
+#[message]
+struct ToLocal;
+
+#[message]
+#[derive(Copy)]
+enum Target {
+    Receiver,
+    Disabled,
+    Poisoned,
+    Closed,
+    Stuck,
+    Local,
+    Nowhere,
+}
+
+#[message(ret = Result<(), AckError>)]
+struct SendAcked(Target);
+
+// Sends messages to the specified target and waits for their acks.
+fn ack_sender() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (SendAcked(target), token) => {
+                    let res = match target {
+                        Target::Receiver => ctx.send_acknowledged(Notify).await,
+                        Target::Disabled => ctx.send_acknowledged(Unroutable).await,
+                        Target::Poisoned => ctx.send_acknowledged(Poisoned(42)).await,
+                        Target::Closed => ctx.send_acknowledged(ToClosed).await,
+                        Target::Stuck => ctx.send_acknowledged(ToStuck).await,
+                        Target::Local => ctx.send_acknowledged(ToLocal).await,
+                        Target::Nowhere => ctx.send_acknowledged(target).await,
+                    };
+                    ctx.respond(token, res);
+                }
+            });
+        }
+    })
+}
+
+fn ack_receiver() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move { while ctx.recv().await.is_some() {} })
+}
+
+// Closes the mailbox on the first message and stays alive until released.
+fn ack_closer(release: watch::Receiver<bool>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let mut release = release.clone();
+        async move {
+            ctx.recv().await;
+            ctx.close();
+            let _ = release.wait_for(|released| *released).await;
+        }
+    })
+}
+
+// Doesn't receive messages until released.
+fn ack_staller(release: watch::Receiver<bool>) -> Blueprint {
+    ActorGroup::new().mailbox_capacity(1).exec(move |mut ctx| {
+        let mut release = release.clone();
+        async move {
+            let _ = release.wait_for(|released| *released).await;
+            while ctx.recv().await.is_some() {}
+        }
+    })
+}
+
+#[tokio::test]
+async fn acknowledged_sends() {
+    common::setup_logger();
+
+    let (release_tx, release_rx) = watch::channel(false);
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+    let receivers = server.local("receivers");
+    let disabled = server.local("disabled");
+    let closers = server.local("closers");
+    let stallers = server.local("stallers");
+
+    network.mount(elfo::batteries::network::new(&server));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        toml! {
+            [system.network]
+            listen = ["inproc://acknowledged_sends"]
+        },
+    ));
+    receivers.mount(ack_receiver());
+    disabled.mount_if(ack_receiver(), |_| false);
+    closers.mount(ack_closer(release_rx.clone()));
+    stallers.mount(ack_staller(release_rx));
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let senders = client.local("senders").entrypoint();
+    let senders_addr = senders.addr();
+    let locals = client.local("locals");
+    let receivers = client.remote("receivers");
+    let disabled = client.remote("disabled");
+    let closers = client.remote("closers");
+    let stallers = client.remote("stallers");
+
+    senders.route_to(&receivers, |e, _| {
+        msg!(match e {
+            Notify | Poisoned => topology::Outcome::Broadcast,
+            _ => topology::Outcome::Discard,
+        })
+    });
+    senders.route_to(&disabled, |e, _| {
+        msg!(match e {
+            Unroutable => topology::Outcome::Broadcast,
+            _ => topology::Outcome::Discard,
+        })
+    });
+    senders.route_to(&closers, |e, _| {
+        msg!(match e {
+            ToClosed => topology::Outcome::Broadcast,
+            _ => topology::Outcome::Discard,
+        })
+    });
+    senders.route_to(&stallers, |e, _| {
+        msg!(match e {
+            ToStuck => topology::Outcome::Broadcast,
+            _ => topology::Outcome::Discard,
+        })
+    });
+    senders.route_to(&locals, |e| e.is::<ToLocal>());
+
+    network.mount(elfo::batteries::network::new(&client));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        toml! {
+            [system.network]
+            discovery.predefined = ["inproc://acknowledged_sends"]
+            discovery.attempt_interval = "10ms"
+            // Used to lose the connection with pending acks.
+            ping_interval = "10ms"
+            idle_close = "200ms"
+        },
+    ));
+    senders.mount(ack_sender());
+    locals.mount(ack_receiver());
+
+    let send = |ctx: Context, target| async move {
+        ctx.request_to(senders_addr, SendAcked(target))
+            .resolve()
+            .await
+            .unwrap()
+    };
+
+    do_start(server, false, |server_ctx, server| async move {
+        do_start(client, false, |client_ctx, client| async move {
+            let scenario = async {
+                // Local sends are acknowledged once enqueued.
+                assert_eq!(send(client_ctx.pruned(), Target::Local).await, Ok(()));
+                assert_eq!(
+                    send(client_ctx.pruned(), Target::Nowhere).await,
+                    Err(AckError::NoRoute)
+                );
+
+                // Wait for the connection.
+                while send(client_ctx.pruned(), Target::Receiver).await.is_err() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                assert_eq!(
+                    send(client_ctx.pruned(), Target::Disabled).await,
+                    Err(AckError::NoRoute)
+                );
+                assert_eq!(
+                    send(client_ctx.pruned(), Target::Poisoned).await,
+                    Err(AckError::DecodeFailed)
+                );
+
+                // The first message is enqueued before the mailbox is closed.
+                loop {
+                    match send(client_ctx.pruned(), Target::Closed).await {
+                        Ok(()) => tokio::time::sleep(Duration::from_millis(10)).await,
+                        Err(err) => break assert_eq!(err, AckError::Closed),
+                    }
+                }
+
+                // The second message waits for capacity until the connection
+                // is closed for idleness.
+                assert_eq!(send(client_ctx.pruned(), Target::Stuck).await, Ok(()));
+                assert_eq!(
+                    send(client_ctx.pruned(), Target::Stuck).await,
+                    Err(AckError::ConnectionLost)
+                );
+            };
+
+            let res = tokio::time::timeout(Duration::from_secs(10), scenario).await;
+            terminate(client_ctx, client).await;
+            res
+        })
+        .await
+        .expect("cannot start client")
+        .expect("timeout");
+
+        release_tx.send_replace(true);
+        terminate(server_ctx, server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
+
+#[message]
+struct Seq(u32);
+
+#[message(ret = Vec<u32>)]
+struct GetSeqs;
+
+#[message(ret = u32)]
+struct Flood(u32);
+
+#[message(ret = Option<Vec<u32>>)]
+struct CollectSeqs;
+
+// Records sequence numbers of received `Seq`.
+// If `release` is set, stops receiving on the first `Seq` until released.
+fn seq_recorder(release: Option<watch::Receiver<bool>>) -> Blueprint {
+    ActorGroup::new()
+        .mailbox_capacity(1)
+        .exec(move |ctx| record_seqs(ctx, release.clone()))
+}
+
+// The same, but both destinations are actors of one group, so they share
+// the link: `Seq` is routed to the `stalled` actor, `Compute` to `flowing`.
+fn keyed_seq_recorder(release: watch::Receiver<bool>) -> Blueprint {
+    ActorGroup::new()
+        .mailbox_capacity(1)
+        .router(MapRouter::new(|e| {
+            msg!(match e {
+                Seq | GetSeqs => Outcome::Unicast("stalled"),
+                Compute => Outcome::Unicast("flowing"),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(move |ctx| {
+            let release = (*ctx.key() == "stalled").then(|| release.clone());
+            record_seqs(ctx, release)
+        })
+}
+
+async fn record_seqs<K>(mut ctx: Context<(), K>, mut release: Option<watch::Receiver<bool>>) {
+    let mut seqs = Vec::new();
+    while let Some(envelope) = ctx.recv().await {
+        msg!(match envelope {
+            Seq(no) => {
+                if let Some(mut release) = release.take() {
+                    let _ = release.wait_for(|released| *released).await;
+                }
+                seqs.push(no);
+            }
+            (GetSeqs, token) => ctx.respond(token, seqs.clone()),
+            (Compute(no), token) => ctx.respond(token, no),
+        });
+    }
+}
+
+// Floods the stalled destination, forwards `Compute` to the flowing one.
+// Both are done by different actors, so requests don't wait for the flood.
+fn flooder() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|e| {
+            msg!(match e {
+                Flood => Outcome::Unicast("flood"),
+                StartEntrypoint | Compute | CollectSeqs => Outcome::Unicast("compute"),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Flood(count), token) => {
+                        for no in 0..count {
+                            ctx.send(Seq(no)).await.unwrap();
+                        }
+                        ctx.respond(token, count);
+                    }
+                    (Compute(no), token) => {
+                        let res = ctx.request(Compute(no)).resolve().await;
+                        ctx.respond(token, res.unwrap_or(u64::MAX));
+                    }
+                    (CollectSeqs, token) => {
+                        let res = ctx.request(GetSeqs).all().resolve().await;
+                        let seqs = res.into_iter().map(Result::ok).collect::<Option<Vec<_>>>();
+                        ctx.respond(token, seqs.and_then(|mut seqs| seqs.pop()));
+                    }
+                });
+            }
+        })
+}
+
+// A stalled destination only backs up its own traffic.
+#[tokio::test]
+async fn stalled_destination() {
+    check_stalled_destination("stalled_destination", false).await;
+}
+
+// The same, but routed traffic to both destinations goes through one link.
+#[tokio::test]
+async fn stalled_destination_on_shared_link() {
+    check_stalled_destination("stalled_destination_on_shared_link", true).await;
+}
+
+async fn check_stalled_destination(name: &str, shared_link: bool) {
+    common::setup_logger();
+
+    const COUNT: u32 = 20_000;
+
+    let (release_tx, release_rx) = watch::channel(false);
+    let address = format!("inproc://{name}");
+
+    // The first node.
+    let server = Topology::empty();
+    let configurers = server.local("system.configurers").entrypoint();
+    let network = server.local("system.network");
+
+    network.mount(elfo::batteries::network::new(&server));
+    let config = format!(r#"system.network.listen = ["{address}"]"#);
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &server,
+        config.parse::<toml::Table>().unwrap(),
+    ));
+
+    if shared_link {
+        let sinks = server.local("sinks");
+        sinks.mount(keyed_seq_recorder(release_rx));
+    } else {
+        let stalled = server.local("stalled");
+        let flowing = server.local("flowing");
+        stalled.mount(seq_recorder(Some(release_rx)));
+        flowing.mount(seq_recorder(None));
+    }
+
+    // The second node.
+    let client = Topology::empty();
+    let configurers = client.local("system.configurers").entrypoint();
+    let network = client.local("system.network");
+    let flooders = client.local("flooders").entrypoint();
+    let flooders_addr = flooders.addr();
+
+    if shared_link {
+        let sinks = client.remote("sinks");
+        flooders.route_to(&sinks, |e, _| {
+            msg!(match e {
+                Seq | GetSeqs | Compute => topology::Outcome::Broadcast,
+                _ => topology::Outcome::Discard,
+            })
+        });
+    } else {
+        let stalled = client.remote("stalled");
+        let flowing = client.remote("flowing");
+        flooders.route_to(&stalled, |e, _| {
+            msg!(match e {
+                Seq | GetSeqs => topology::Outcome::Broadcast,
+                _ => topology::Outcome::Discard,
+            })
+        });
+        flooders.route_to(&flowing, |e, _| {
+            msg!(match e {
+                Compute => topology::Outcome::Broadcast,
+                _ => topology::Outcome::Discard,
+            })
+        });
+    }
+
+    network.mount(elfo::batteries::network::new(&client));
+    let config = format!(
+        r#"
+        [system.network]
+        discovery.predefined = ["{address}"]
+        discovery.attempt_interval = "10ms"
+        "#
+    );
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &client,
+        config.parse::<toml::Table>().unwrap(),
+    ));
+    flooders.mount(flooder());
+
+    do_start(server, false, |server_ctx, server| async move {
+        do_start(client, false, |client_ctx, client| async move {
+            let compute = |no| {
+                let ctx = client_ctx.pruned();
+                async move {
+                    let start = tokio::time::Instant::now();
+                    let res = ctx.request_to(flooders_addr, Compute(no)).resolve().await;
+                    (res.unwrap(), start.elapsed())
+                }
+            };
+
+            let scenario = async {
+                // Wait for connections.
+                while compute(0).await.0 != 0 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                while client_ctx
+                    .request_to(flooders_addr, CollectSeqs)
+                    .resolve()
+                    .await
+                    .unwrap()
+                    .is_none()
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+
+                let flood = client_ctx.request_to(flooders_addr, Flood(COUNT)).resolve();
+
+                // Requests to the flowing destination aren't delayed by the stalled one.
+                // The bound is generous, because the first request competes for CPU
+                // with encoding of the burst, which is slow in debug builds.
+                let flow = async {
+                    for no in 1..=50 {
+                        let (res, elapsed) = compute(no).await;
+                        assert_eq!(res, no);
+                        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+                    }
+                };
+
+                let (flooded, ()) = tokio::join!(flood, flow);
+                assert_eq!(flooded.unwrap(), COUNT);
+                release_tx.send_replace(true);
+
+                // The order is preserved within the destination.
+                let seqs = loop {
+                    let res = client_ctx.request_to(flooders_addr, CollectSeqs).resolve();
+                    let seqs = res.await.unwrap().unwrap();
+                    if seqs.len() == COUNT as usize {
+                        break seqs;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                };
+                assert!(seqs.iter().copied().eq(0..COUNT));
+            };
+
+            let res = tokio::time::timeout(Duration::from_secs(20), scenario).await;
+            terminate(client_ctx, client).await;
+            res
+        })
+        .await
+        .expect("cannot start client")
+        .expect("timeout");
+
+        terminate(server_ctx, server).await;
+    })
+    .await
+    .expect("cannot start server");
+}
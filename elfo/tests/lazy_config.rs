@@ -0,0 +1,160 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Deserialize, Deserializer};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer::{self, ReloadConfigs},
+    messages::{GetConfig, StartEntrypoint, UpdateConfig},
+    prelude::*,
+    Addr, Topology,
+};
+
+mod common;
+
+static DESERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+// Counts invocations of `Deserialize`, e.g. expensive regex compilation.
+#[derive(Debug)]
+struct Config;
+
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Raw {
+            limit: u32,
+            shared: u32,
+        }
+
+        DESERIALIZED.fetch_add(1, Ordering::SeqCst);
+        Raw::deserialize(deserializer).map(|_| Config)
+    }
+}
+
+fn deserialized() -> usize {
+    DESERIALIZED.swap(0, Ordering::SeqCst)
+}
+
+fn sample() -> Blueprint {
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} })
+}
+
+fn starter() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                _ => {}
+            });
+        }
+    })
+}
+
+fn write_config(path: &Path, limits: [u32; 3], shared: u32) {
+    let config = format!(
+        r#"
+        [common]
+        shared = {shared}
+
+        [a]
+        limit = {}
+
+        [b]
+        limit = {}
+
+        [c]
+        limit = {}
+        "#,
+        limits[0], limits[1], limits[2]
+    );
+
+    std::fs::write(path, config).unwrap();
+}
+
+fn topology(path: &Path) -> (Topology, Addr, Addr) {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let starter = topology.local("starter").entrypoint();
+
+    let groups = ["a", "b", "c"].map(|name| topology.local(name));
+
+    let configurers_addr = configurers.addr();
+    let a_addr = groups[0].addr();
+
+    configurers.mount(configurer::from_path(&topology, path));
+    starter.mount(self::starter());
+    for group in groups {
+        group.mount(sample());
+    }
+
+    (topology, configurers_addr, a_addr)
+}
+
+fn config_path() -> PathBuf {
+    std::env::temp_dir().join(format!("elfo-lazy-config-{}.toml", std::process::id()))
+}
+
+#[tokio::test]
+async fn unchanged_groups_are_skipped() {
+    common::setup_logger();
+
+    let path = config_path();
+    write_config(&path, [1, 1, 1], 0);
+    let (topology, configurers, a) = topology(&path);
+
+    let path = &path;
+    do_start(topology, false, |ctx, topology| async move {
+        let reload = |request: ReloadConfigs| {
+            let res = ctx.request_to(configurers, request).resolve();
+            async move { res.await.unwrap().unwrap() }
+        };
+
+        // Validation and updating of every group.
+        let per_group = deserialized() / 3;
+        assert!(per_group > 0);
+
+        // Nothing is changed.
+        reload(ReloadConfigs::default()).await;
+        assert_eq!(deserialized(), 0);
+
+        // Only one group is changed.
+        write_config(path, [2, 1, 1], 0);
+        reload(ReloadConfigs::default()).await;
+        assert_eq!(deserialized(), per_group);
+
+        // The common section affects all groups.
+        write_config(path, [2, 1, 1], 1);
+        reload(ReloadConfigs::default()).await;
+        assert_eq!(deserialized(), 3 * per_group);
+
+        // Forcing bypasses the check.
+        reload(ReloadConfigs::forcing()).await;
+        assert_eq!(deserialized(), 3 * per_group);
+
+        // Groups skip the applied config sent directly.
+        let applied = ctx.request_to(a, GetConfig::new("a".into()));
+        let config = applied.resolve().await.unwrap().config;
+        let update = ctx.request_to(a, UpdateConfig::new(config.clone()));
+        update.resolve().await.unwrap().unwrap();
+        assert_eq!(deserialized(), 0);
+
+        let update = ctx.request_to(a, UpdateConfig::new(config).forcing());
+        update.resolve().await.unwrap().unwrap();
+        assert_eq!(deserialized(), 1);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,138 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    config::AnyConfig,
+    messages::{
+        ActorRestarted, ActorSpawned, ActorTerminated, GroupTerminated, SubscribeToLifecycleEvents,
+        Terminate,
+    },
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    test::Proxy,
+    ActorStatusKind, RestartParams, RestartPolicy,
+};
+
+#[message]
+struct Start(u32);
+
+#[message]
+struct Fail(u32);
+
+async fn run_group() -> Proxy {
+    let blueprint = ActorGroup::new()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                Start(n) | Fail(n) => Outcome::Unicast(*n),
+                _ => Outcome::Default,
+            })
+        }))
+        .restart_policy(RestartPolicy::on_failure(RestartParams::new(
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+        )))
+        .exec(move |mut ctx| async move {
+            // Emulate an actor crashing during initialization.
+            if *ctx.key() == 0 {
+                panic!("cannot initialize");
+            }
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Start => {}
+                    Fail => panic!("oops"),
+                    _ => {}
+                });
+            }
+        });
+
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+    proxy.send(SubscribeToLifecycleEvents::default()).await;
+    proxy.sync().await;
+    proxy
+}
+
+async fn collect(proxy: &mut Proxy) -> Vec<String> {
+    proxy.sync().await;
+
+    let mut events = Vec::new();
+    while let Some(envelope) = proxy.try_recv().await {
+        msg!(match envelope {
+            ActorSpawned { meta, .. } => events.push(format!("{} spawned", meta.key)),
+            ActorTerminated { meta, reason, .. } => {
+                let reason = match reason.kind() {
+                    ActorStatusKind::Failed => "failed",
+                    _ => "terminated",
+                };
+                events.push(format!("{} {reason}", meta.key));
+            }
+            ActorRestarted {
+                meta,
+                attempt,
+                backoff,
+                ..
+            } => events.push(format!(
+                "{} restarted #{attempt} after {}s",
+                meta.key,
+                backoff.as_secs()
+            )),
+            GroupTerminated { group, .. } => events.push(format!("{group} terminated")),
+            envelope => panic!("unexpected message: {:?}", envelope.message()),
+        });
+    }
+    events
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarts() {
+    let mut proxy = run_group().await;
+
+    proxy.send(Start(1)).await;
+    assert_eq!(collect(&mut proxy).await, ["1 spawned"]);
+
+    proxy.send(Fail(1)).await;
+    assert_eq!(
+        collect(&mut proxy).await,
+        ["1 failed", "1 restarted #1 after 5s"]
+    );
+
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    assert_eq!(collect(&mut proxy).await, ["1 spawned"]);
+
+    proxy.send(Fail(1)).await;
+    assert_eq!(
+        collect(&mut proxy).await,
+        ["1 failed", "1 restarted #2 after 10s"]
+    );
+
+    tokio::time::sleep(Duration::from_secs(11)).await;
+    assert_eq!(collect(&mut proxy).await, ["1 spawned"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn crash_on_start() {
+    let mut proxy = run_group().await;
+
+    proxy.send(Start(0)).await;
+    assert_eq!(
+        collect(&mut proxy).await,
+        ["0 spawned", "0 failed", "0 restarted #1 after 5s"]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn group_terminated() {
+    let mut proxy = run_group().await;
+
+    proxy.send(Start(1)).await;
+    proxy.send(Start(2)).await;
+    assert_eq!(collect(&mut proxy).await, ["1 spawned", "2 spawned"]);
+
+    proxy.send(Terminate::default()).await;
+    let mut events = collect(&mut proxy).await;
+    assert_eq!(events.pop().unwrap(), "subject terminated");
+    events.sort();
+    assert_eq!(events, ["1 terminated", "2 terminated"]);
+}
@@ -0,0 +1,117 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    prelude::*,
+    test::{self, Clock, TrafficSpec},
+    topology::Local,
+    Topology,
+};
+
+#[message]
+struct Order(u64);
+
+#[message(ret = u64)]
+struct Quote(u64);
+
+/// Spends `cost` on every message.
+fn venue(cost: Duration) -> impl Fn(&Topology, &Local<'_>) {
+    move |topology, injectors| {
+        let venue = topology.local("venue");
+        injectors.route_all_to(&venue);
+        venue.mount(ActorGroup::new().exec(move |mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                if !cost.is_zero() {
+                    tokio::time::sleep(cost).await;
+                }
+
+                msg!(match envelope {
+                    Order(_) => {}
+                    (Quote(no), token) => ctx.respond(token, no),
+                });
+            }
+        }));
+    }
+}
+
+fn orders(rate: &str, concurrency: usize) -> TrafficSpec<fn(u64) -> Order> {
+    TrafficSpec {
+        message_factory: Order,
+        rate: Some(rate.parse().unwrap()),
+        concurrency,
+        duration: Duration::from_secs(1),
+    }
+}
+
+#[test]
+fn open_loop_under_capacity() {
+    let report = test::load(venue(Duration::from_millis(1)))
+        .clock(Clock::Virtual)
+        .traffic(orders("500/s", 2))
+        .measure();
+
+    assert_eq!(report.offered, 500);
+    assert_eq!(report.handled, 500);
+    assert_eq!(report.dropped, 0);
+    assert_eq!(report.offered_rate.round(), 500.);
+    assert_eq!(report.achieved_rate.round(), 500.);
+
+    // No queueing, only the handling itself.
+    assert_eq!(report.latency.min, Duration::from_millis(1));
+    assert_eq!(report.latency.p99, Duration::from_millis(1));
+    assert_eq!(report.latency.max, Duration::from_millis(1));
+}
+
+#[test]
+fn open_loop_over_capacity() {
+    let report = test::load(venue(Duration::from_millis(1)))
+        .clock(Clock::Virtual)
+        .traffic(orders("2k/s", 4))
+        .measure();
+
+    // The venue handles 1k/s, so the mailbox (100 messages) becomes full.
+    assert_eq!(report.offered, 2000);
+    assert_eq!(report.handled + report.dropped, 2000);
+    assert!((1000..1200).contains(&report.handled), "{report}");
+    assert!(report.achieved_rate < report.offered_rate);
+    assert!(report.latency.p50 >= Duration::from_millis(99), "{report}");
+    assert!(report.latency.max <= Duration::from_millis(101), "{report}");
+}
+
+#[test]
+fn closed_loop() {
+    let report = test::load(venue(Duration::from_millis(1)))
+        .clock(Clock::Virtual)
+        .requests(TrafficSpec {
+            message_factory: Quote,
+            rate: None,
+            concurrency: 4,
+            duration: Duration::from_millis(100),
+        })
+        .measure();
+
+    // Requests in flight wait for each other.
+    assert_eq!(report.dropped, 0);
+    assert!((100..=104).contains(&report.handled), "{report}");
+    assert_eq!(report.latency.p50, Duration::from_millis(4));
+}
+
+#[test]
+fn real_clock() {
+    let report = test::load(venue(Duration::ZERO))
+        .clock(Clock::Real)
+        .traffic(TrafficSpec {
+            message_factory: Order,
+            rate: Some("10k/s".parse().unwrap()),
+            concurrency: 2,
+            duration: Duration::from_millis(100),
+        })
+        .measure();
+
+    assert_eq!(report.offered, 1000);
+    assert_eq!(report.dropped, 0);
+    // A generous threshold to be stable on CI.
+    assert!(report.latency.p99 < Duration::from_millis(100), "{report}");
+}
@@ -0,0 +1,91 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::fs;
+
+use serde::Deserialize;
+use toml::toml;
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = u64)]
+struct Handle;
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Handle, token) => {
+                    info!("handling");
+                    let sequence_no = elfo::scope::sequence_no().unwrap();
+                    ctx.respond(token, sequence_no.into());
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn seq_matches_dump() {
+    let path = std::env::temp_dir().join(format!("elfo-log-dump-join-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path_str = path.to_str().unwrap();
+
+    let config = AnyConfig::deserialize(toml! {
+        [system.loggers]
+        sink = "File"
+        path = path_str
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    let capture = topology.dump_capture().clone();
+
+    let handled_sequence_no = do_start(topology, false, move |ctx, topology| async move {
+        let sequence_no = ctx.request_to(subject_addr, Handle).resolve().await;
+        terminate(ctx, topology).await;
+        sequence_no
+    })
+    .await
+    .expect("cannot start")
+    .expect("subject failed");
+
+    // The number is the same as in the incoming dump.
+    let dump_sequence_no = capture
+        .snapshot()
+        .into_iter()
+        .find(|d| d.is_incoming && d.message_name == "Handle")
+        .expect("no dump")
+        .sequence_no;
+    assert_eq!(u64::from(dump_sequence_no), handled_sequence_no);
+
+    // The number is the same as in the log line.
+    let logs = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    let line = logs
+        .lines()
+        .find(|line| line.contains("handling"))
+        .expect("no log line");
+    assert!(
+        line.split('\t')
+            .any(|field| field == format!("seq={handled_sequence_no}")),
+        "unexpected log line: {line}"
+    );
+}
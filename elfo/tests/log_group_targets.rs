@@ -0,0 +1,120 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{fs, path::Path};
+
+use tracing::{debug, info};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::{
+        configurer::{self, ReloadConfigs},
+        logger::FlushLogs,
+    },
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = ())]
+struct Log(String);
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log(text), token) => {
+                    debug!("debug: {text}");
+                    info!("info: {text}");
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+fn write_config(path: &Path, log_path: &Path, targets: &str) {
+    let config = format!(
+        r#"
+        [system.loggers]
+        sink = "File"
+        path = {log_path:?}
+        {targets}
+        "#
+    );
+
+    fs::write(path, config).unwrap();
+}
+
+#[tokio::test]
+async fn group_levels_are_updated() {
+    let pid = std::process::id();
+    let log_path = std::env::temp_dir().join(format!("elfo-log-group-targets-{pid}.log"));
+    let config_path = std::env::temp_dir().join(format!("elfo-log-group-targets-{pid}.toml"));
+    let _ = fs::remove_file(&log_path);
+    write_config(&config_path, &log_path, r#"targets.subject = "Debug""#);
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let configurers_addr = configurers.addr();
+    let loggers_addr = loggers.addr();
+    let subject_addr = subject.addr();
+
+    configurers.mount(configurer::from_path(&topology, &config_path));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    let paths = (&config_path, &log_path);
+    do_start(topology, false, |ctx, topology| async move {
+        let log = |text: &str| ctx.request_to(subject_addr, Log(text.into())).resolve();
+        let reload = |targets: &str| {
+            write_config(paths.0, paths.1, targets);
+            async {
+                ctx.request_to(configurers_addr, ReloadConfigs::default())
+                    .resolve()
+                    .await
+                    .unwrap()
+                    .unwrap();
+
+                // Loggers apply targets on `ConfigUpdated`, which is handled after
+                // the reload is responded, so wait for it.
+                ctx.request_to(loggers_addr, FlushLogs::default())
+                    .resolve()
+                    .await
+                    .unwrap();
+            }
+        };
+
+        // Replaces `system.logging.max_level`, which is `Info` by default.
+        log("first").await.unwrap();
+
+        reload(r#"targets.subject = { max_level = "Warn" }"#).await;
+        log("second").await.unwrap();
+
+        reload("").await;
+        log("third").await.unwrap();
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let logs = fs::read_to_string(&log_path).unwrap();
+    let _ = fs::remove_file(&log_path);
+    let _ = fs::remove_file(&config_path);
+    let lines = logs
+        .lines()
+        .filter_map(|line| line.split(" - ").nth(1))
+        .filter(|line| line.starts_with("debug: ") || line.starts_with("info: "))
+        .map(|line| line.split('\t').next().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        lines,
+        ["debug: first", "info: first", "info: third"],
+        "{logs}"
+    );
+}
@@ -0,0 +1,116 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::fs;
+
+use serde_json::{json, Value};
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = ())]
+struct Log;
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log, token) => {
+                    elfo::scope::set_baggage("request_id", "5b0e1f9c-gw");
+                    info!(
+                        attempt = 2,
+                        cached = false,
+                        user = "\"alice\"",
+                        "line\n\tnext"
+                    );
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn lines_are_json_objects() {
+    let pid = std::process::id();
+    let log_path = std::env::temp_dir().join(format!("elfo-log-json-{pid}.log"));
+    let config_path = std::env::temp_dir().join(format!("elfo-log-json-{pid}.toml"));
+    let _ = fs::remove_file(&log_path);
+
+    let config = format!(
+        r#"
+        [system.loggers]
+        sink = "File"
+        path = {log_path:?}
+        format = "json"
+
+        [subject]
+        system.logging.fields = {{ team = "payments" }}
+        "#
+    );
+    fs::write(&config_path, config).unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(configurer::from_path(&topology, &config_path));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    elfo::batteries::logger::set_meta_enricher(|scope, meta| {
+        if let Some(request_id) = scope.baggage("request_id") {
+            meta.add("request_id", request_id)?;
+        }
+        Ok(())
+    });
+
+    do_start(topology, false, |ctx, topology| async move {
+        ctx.request_to(subject_addr, Log).resolve().await.unwrap();
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let logs = fs::read_to_string(&log_path).unwrap();
+    let _ = fs::remove_file(&log_path);
+    let _ = fs::remove_file(&config_path);
+
+    let lines = logs
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).expect(line))
+        .collect::<Vec<_>>();
+
+    let line = lines
+        .iter()
+        .find(|line| line["message"] == "line\n\tnext")
+        .expect("no log line");
+
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["group"], "subject");
+    assert!(line["timestamp"].is_string());
+    assert!(line["ts_ns"].is_u64());
+    assert!(line["trace_id"].is_string());
+    assert_eq!(line["request_id"], "5b0e1f9c-gw");
+    assert_eq!(line["key"], "_");
+    assert_eq!(
+        line["fields"],
+        json!({
+            "attempt": 2,
+            "cached": false,
+            "user": "\"alice\"",
+            "seq": line["fields"]["seq"],
+            "team": "payments",
+        })
+    );
+    assert!(line["fields"]["seq"].is_u64());
+}
@@ -0,0 +1,103 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::fs;
+
+use serde::Deserialize;
+use toml::toml;
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = String)]
+struct Handle {
+    request_id: String,
+}
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Handle { request_id }, token) => {
+                    info!("received");
+                    elfo::scope::set_baggage("request_id", request_id);
+                    info!("handling");
+                    ctx.respond(token, elfo::scope::trace_id().to_string());
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn extra_meta_is_written() {
+    let path = std::env::temp_dir().join(format!("elfo-log-enricher-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path_str = path.to_str().unwrap();
+
+    let config = AnyConfig::deserialize(toml! {
+        [system.loggers]
+        sink = "File"
+        path = path_str
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    elfo::batteries::logger::set_meta_enricher(|scope, meta| {
+        if let Some(request_id) = scope.baggage("request_id") {
+            meta.add("request_id", request_id)?;
+        }
+        Ok(())
+    });
+
+    let trace_id = do_start(topology, false, move |ctx, topology| async move {
+        let request_id = "5b0e1f9c-gw".into();
+        let trace_id = ctx
+            .request_to(subject_addr, Handle { request_id })
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        trace_id
+    })
+    .await
+    .expect("cannot start")
+    .expect("subject failed");
+
+    let logs = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    let find = |message: &str| {
+        logs.lines()
+            .find(|line| line.contains(message))
+            .expect("no log line")
+    };
+
+    // Both ids are in a stable position right after the standard meta.
+    let line = find("handling");
+    assert!(
+        line.contains(&format!("[{trace_id}] request_id=5b0e1f9c-gw subject/_ - ")),
+        "unexpected log line: {line}"
+    );
+
+    // Nothing is added without baggage.
+    let line = find("received");
+    assert!(
+        line.contains(&format!("[{trace_id}] subject/_ - ")),
+        "unexpected log line: {line}"
+    );
+}
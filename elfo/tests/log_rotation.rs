@@ -0,0 +1,116 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{fs, path::Path};
+
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+const LINES: usize = 200;
+
+#[message(ret = ())]
+struct Log(usize);
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log(no), token) => {
+                    info!("rotated line #{no:04}");
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+fn numbered(path: &Path, no: usize) -> String {
+    format!("{}.{no}", path.display())
+}
+
+#[tokio::test]
+async fn rotation_keeps_all_lines() {
+    let pid = std::process::id();
+    let log_path = std::env::temp_dir().join(format!("elfo-log-rotation-{pid}.log"));
+    let config_path = std::env::temp_dir().join(format!("elfo-log-rotation-{pid}.toml"));
+    for no in 0..=5 {
+        let _ = fs::remove_file(numbered(&log_path, no));
+    }
+    let _ = fs::remove_file(&log_path);
+
+    // Left by a larger limit.
+    fs::write(numbered(&log_path, 3), "stale").unwrap();
+    fs::write(numbered(&log_path, 4), "stale").unwrap();
+
+    // Every line is flushed separately, so there are many rotations.
+    let config = format!(
+        r#"
+        [system.loggers]
+        sink = "File"
+        path = {log_path:?}
+        flush.high_water = 1
+        rotation = {{ max_size = "2KiB", max_files = 2 }}
+        "#
+    );
+    fs::write(&config_path, config).unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let subject_addr = subject.addr();
+
+    configurers.mount(configurer::from_path(&topology, &config_path));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    do_start(topology, false, |ctx, topology| async move {
+        for no in 0..LINES {
+            ctx.request_to(subject_addr, Log(no))
+                .resolve()
+                .await
+                .unwrap();
+        }
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = fs::remove_file(&config_path);
+    let files = [numbered(&log_path, 2), numbered(&log_path, 1)]
+        .into_iter()
+        .chain([log_path.display().to_string()])
+        .map(|path| {
+            let content = fs::read_to_string(&path).unwrap();
+            let _ = fs::remove_file(&path);
+            content
+        })
+        .collect::<Vec<_>>();
+
+    assert!(!Path::new(&numbered(&log_path, 3)).exists());
+    assert!(!Path::new(&numbered(&log_path, 4)).exists());
+
+    for content in &files[..2] {
+        assert!(content.len() <= 2048, "{content}");
+    }
+
+    // Kept lines are whole and go without gaps up to the last one.
+    let numbers = files
+        .iter()
+        .flat_map(|content| content.lines())
+        .filter_map(|line| line.split_once("rotated line #"))
+        .map(|(_, rest)| rest[..4].parse::<usize>().expect(rest))
+        .collect::<Vec<_>>();
+
+    assert!(numbers.len() > 10, "{numbers:?}");
+    assert_eq!(*numbers.last().unwrap(), LINES - 1);
+    assert!(numbers.windows(2).all(|w| w[1] == w[0] + 1), "{numbers:?}");
+}
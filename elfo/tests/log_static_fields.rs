@@ -0,0 +1,124 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{fs, path::Path};
+
+use tracing::info;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer::{self, ReloadConfigs},
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = ())]
+struct Log(String);
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log(message), token) => {
+                    match message.as_str() {
+                        "overridden" => info!(team = "risk", "{message}"),
+                        _ => info!("{message}"),
+                    }
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+fn write_config(path: &Path, log_path: &Path, team: &str) {
+    let config = format!(
+        r#"
+        [system.loggers]
+        sink = "File"
+        path = {log_path:?}
+
+        [subject]
+        system.logging.fields = {{ subsystem = "billing", team = "{team}" }}
+        "#
+    );
+
+    fs::write(path, config).unwrap();
+}
+
+#[tokio::test]
+async fn static_fields_are_appended() {
+    let pid = std::process::id();
+    let log_path = std::env::temp_dir().join(format!("elfo-log-static-fields-{pid}.log"));
+    let config_path = std::env::temp_dir().join(format!("elfo-log-static-fields-{pid}.toml"));
+    let _ = fs::remove_file(&log_path);
+    write_config(&config_path, &log_path, "payments");
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let configurers_addr = configurers.addr();
+    let subject_addr = subject.addr();
+
+    configurers.mount(configurer::from_path(&topology, &config_path));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    let paths = (&config_path, &log_path);
+    do_start(topology, false, |ctx, topology| async move {
+        let log = |message: &str| ctx.request_to(subject_addr, Log(message.into())).resolve();
+
+        log("first").await.unwrap();
+        log("overridden").await.unwrap();
+
+        // Applied to subsequent lines.
+        write_config(paths.0, paths.1, "treasury");
+        ctx.request_to(configurers_addr, ReloadConfigs::default())
+            .resolve()
+            .await
+            .unwrap()
+            .unwrap();
+        log("updated").await.unwrap();
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let logs = fs::read_to_string(&log_path).unwrap();
+    let _ = fs::remove_file(&log_path);
+    let _ = fs::remove_file(&config_path);
+    let find = |message: &str| {
+        logs.lines()
+            .find(|line| line.contains(&format!(" - {message}")))
+            .expect("no log line")
+    };
+
+    let line = find("first");
+    assert!(
+        line.ends_with("\tsubsystem=\"billing\"\tteam=\"payments\""),
+        "unexpected log line: {line}"
+    );
+
+    // The dynamic value wins.
+    let line = find("overridden");
+    assert!(
+        line.contains("\tteam=\"risk\"\t") && line.ends_with("\tsubsystem=\"billing\""),
+        "unexpected log line: {line}"
+    );
+
+    let line = find("updated");
+    assert!(
+        line.ends_with("\tsubsystem=\"billing\"\tteam=\"treasury\""),
+        "unexpected log line: {line}"
+    );
+
+    // Other groups aren't affected.
+    assert!(logs
+        .lines()
+        .filter(|line| !line.contains("] subject"))
+        .all(|line| !line.contains("subsystem=")));
+}
@@ -0,0 +1,192 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    messages::{DeadLetter, DeadLetterReason, SubscribeToLifecycleEvents},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    test::Proxy,
+};
+
+#[message]
+struct Retire {
+    shard: u32,
+    successor: Option<u32>,
+}
+
+#[message]
+struct Job {
+    shard: u32,
+    seq: u32,
+}
+
+#[message(ret = u32)]
+struct Ask {
+    shard: u32,
+}
+
+#[message]
+#[derive(PartialEq)]
+struct Done {
+    owner: u32,
+    seq: u32,
+}
+
+// Shards are owned by actors with the same key until reassigned.
+async fn run_group(config: AnyConfig) -> (Proxy, Arc<AtomicU32>) {
+    // The new owner of the first shard after retirement.
+    let reassigned = Arc::new(AtomicU32::new(0));
+    let reassigned1 = reassigned.clone();
+
+    let blueprint = ActorGroup::new()
+        .router(MapRouter::new(move |envelope| {
+            let owner = |shard: u32| match reassigned1.load(Ordering::SeqCst) {
+                owner if shard == 1 && owner != 0 => owner,
+                _ => shard,
+            };
+
+            msg!(match envelope {
+                Retire { shard, .. } | Job { shard, .. } | Ask { shard } => {
+                    Outcome::Unicast(owner(*shard))
+                }
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(move |mut ctx| async move {
+            let owner = *ctx.key();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Retire { successor, .. } => {
+                        match successor {
+                            Some(successor) => ctx.drain_to(successor),
+                            None => ctx.drain(),
+                        }
+                        return;
+                    }
+                    Job { seq, .. } => ctx.send(Done { owner, seq }).await.unwrap(),
+                    (Ask { .. }, token) => ctx.respond(token, owner),
+                });
+            }
+        });
+
+    let proxy = elfo::test::proxy(blueprint, config).await;
+    (proxy, reassigned)
+}
+
+async fn assert_done(proxy: &mut Proxy, expected: &[(u32, u32)]) {
+    for &(owner, seq) in expected {
+        assert_msg_eq!(proxy.recv().await, Done { owner, seq });
+    }
+    assert!(proxy.try_recv().await.is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn drain_to_key() {
+    let (mut proxy, _) = run_group(AnyConfig::default()).await;
+
+    proxy
+        .send(Retire {
+            shard: 1,
+            successor: Some(2),
+        })
+        .await;
+
+    for seq in 1..=5 {
+        proxy.send(Job { shard: 1, seq }).await;
+    }
+
+    assert_done(&mut proxy, &[(2, 1), (2, 2), (2, 3), (2, 4), (2, 5)]).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn drain_requests() {
+    let (proxy, _) = run_group(AnyConfig::default()).await;
+
+    proxy
+        .send(Retire {
+            shard: 1,
+            successor: Some(2),
+        })
+        .await;
+
+    // The request is queued behind `Retire` and must be answered by the new owner.
+    assert_eq!(proxy.request(Ask { shard: 1 }).await, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn drain_through_router() {
+    let (mut proxy, reassigned) = run_group(AnyConfig::default()).await;
+
+    proxy
+        .send(Retire {
+            shard: 1,
+            successor: None,
+        })
+        .await;
+
+    for seq in 1..=3 {
+        proxy.send(Job { shard: 1, seq }).await;
+    }
+
+    // Reassign the shard before the retiring actor finishes.
+    reassigned.store(3, Ordering::SeqCst);
+
+    assert_done(&mut proxy, &[(3, 1), (3, 2), (3, 3)]).await;
+
+    // New messages are routed to the new owner directly.
+    proxy.send(Job { shard: 1, seq: 4 }).await;
+    assert_done(&mut proxy, &[(3, 4)]).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn drain_limit() {
+    let config = AnyConfig::deserialize(toml! {
+        system.mailbox.drain_limit = 2
+    })
+    .unwrap();
+
+    let (mut proxy, _) = run_group(config).await;
+    proxy.send(SubscribeToLifecycleEvents::default()).await;
+    proxy.sync().await;
+
+    proxy
+        .send(Retire {
+            shard: 1,
+            successor: Some(2),
+        })
+        .await;
+
+    for seq in 1..=5 {
+        proxy.send(Job { shard: 1, seq }).await;
+    }
+
+    // The excess is dead-lettered in order, the rest is handed off.
+    let mut done = Vec::new();
+    let mut dead = Vec::new();
+    while done.len() < 2 || dead.len() < 3 {
+        msg!(match proxy.recv().await {
+            Done { owner, seq } => done.push((owner, seq)),
+            DeadLetter {
+                message, reason, ..
+            } => {
+                assert!(matches!(reason, DeadLetterReason::DrainLimitExceeded));
+                let job = message.downcast::<Job>().expect("unexpected dead letter");
+                dead.push(job.seq);
+            }
+            _ => {} // other lifecycle events
+        });
+    }
+
+    assert_eq!(done, [(2, 1), (2, 2)]);
+    assert_eq!(dead, [3, 4, 5]);
+}
@@ -0,0 +1,160 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{config::AnyConfig, messages::Terminate, prelude::*, Addr, Message, TerminationPolicy};
+
+#[message]
+struct Start;
+
+#[message]
+struct WriteRow(u32);
+
+#[message]
+struct Flush;
+
+#[message]
+struct Step;
+
+/// `(peeked, has_queued::<WriteRow>, mailbox_len, received)` for every `recv`.
+#[message]
+struct Peeked(Vec<(Option<String>, bool, usize, String)>);
+
+/// The number of received messages, which differ from ones peeked before.
+#[message]
+#[derive(PartialEq)]
+struct Mismatches(u32);
+
+// Fills the mailbox with `Terminate`, which overtakes others,
+// `WriteRow(0), Flush, WriteRow(1), WriteRow(2)` and the self queue with
+// `Step`.
+fn testee() -> Blueprint {
+    ActorGroup::new()
+        .termination_policy(TerminationPolicy::manually())
+        .exec(|mut ctx| async move {
+            let mut reporter = Addr::NULL;
+            let mut peeked = Vec::new();
+            let mut is_recording = false;
+
+            loop {
+                let next = ctx.peek_next_message_name().map(String::from);
+                let has_row = ctx.has_queued::<WriteRow>();
+                let len = ctx.mailbox_len();
+
+                let envelope = ctx.recv().await.unwrap();
+                if is_recording {
+                    let name = envelope.message().name().into();
+                    peeked.push((next, has_row, len, name));
+                }
+
+                let sender = envelope.sender();
+                msg!(match envelope {
+                    Start => {
+                        reporter = sender;
+                        is_recording = true;
+
+                        let addr = ctx.addr();
+                        ctx.try_send_to(addr, WriteRow(0)).unwrap();
+                        ctx.try_send_to(addr, Flush).unwrap();
+                        ctx.try_send_to(addr, WriteRow(1)).unwrap();
+                        ctx.try_send_to(addr, WriteRow(2)).unwrap();
+                        ctx.send_to_self(Step).unwrap();
+                        ctx.try_send_to(addr, Terminate::default()).unwrap();
+                    }
+                    // Nothing is queued anymore.
+                    WriteRow(no) if no == 2 => {
+                        assert_eq!(ctx.peek_next_message_name(), None);
+                        assert!(!ctx.has_queued::<WriteRow>());
+                        assert_eq!(ctx.mailbox_len(), 0);
+
+                        is_recording = false;
+                        let report = Peeked(std::mem::take(&mut peeked));
+                        ctx.send_to(reporter, report).await.unwrap();
+                    }
+                    WriteRow => {}
+                    _ => {}
+                });
+            }
+        })
+}
+
+fn step(
+    peeked: Option<&str>,
+    has_row: bool,
+    len: usize,
+    received: &str,
+) -> (Option<String>, bool, usize, String) {
+    (peeked.map(Into::into), has_row, len, received.into())
+}
+
+#[tokio::test]
+async fn matches_recv() {
+    let config = AnyConfig::deserialize(toml! {
+        system.mailbox.on_terminate = "prioritize"
+    })
+    .unwrap();
+    let mut proxy = elfo::test::proxy(testee(), config).await;
+
+    proxy.send(Start).await;
+    let peeked = msg!(match proxy.recv().await {
+        Peeked(peeked) => peeked,
+        _ => unreachable!(),
+    });
+
+    assert_eq!(
+        peeked,
+        [
+            // `Terminate` overtakes both the mailbox and the self queue.
+            step(Some("Terminate"), false, 5, "Terminate"),
+            // The self queue goes before the mailbox.
+            step(Some("Step"), false, 4, "Step"),
+            // `WriteRow(0)` has been taken from the mailbox to receive `Step`.
+            step(Some("WriteRow"), true, 3, "WriteRow"),
+            step(Some("Flush"), false, 3, "Flush"),
+            // Peeked messages are still counted.
+            step(Some("WriteRow"), true, 2, "WriteRow"),
+            step(Some("WriteRow"), true, 1, "WriteRow"),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn concurrent_sends() {
+    const COUNT: u32 = 1000;
+
+    let blueprint = ActorGroup::new().exec(|mut ctx| async move {
+        let mut mismatches = 0;
+
+        loop {
+            let next = ctx.peek_next_message_name();
+            let envelope = ctx.recv().await.unwrap();
+
+            // Messages can be sent after peeking an empty mailbox.
+            if next.is_some_and(|next| next != envelope.message().name()) {
+                mismatches += 1;
+            }
+
+            let sender = envelope.sender();
+            msg!(match envelope {
+                WriteRow(no) if no == COUNT => {
+                    ctx.send_to(sender, Mismatches(mismatches)).await.unwrap();
+                }
+                WriteRow => {}
+                _ => {}
+            });
+        }
+    });
+
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+
+    for no in 1..=COUNT {
+        if no % 3 == 0 {
+            proxy.send(Flush).await;
+        }
+        proxy.send(WriteRow(no)).await;
+    }
+
+    assert_msg_eq!(proxy.recv().await, Mismatches(0));
+}
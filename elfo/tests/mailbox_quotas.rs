@@ -0,0 +1,117 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::{toml, Value};
+
+use elfo::{
+    config::AnyConfig,
+    errors::TrySendError,
+    messages::{Ping, UpdateConfig},
+    prelude::*,
+};
+
+#[message]
+struct DataUpdate;
+
+#[message]
+struct Command;
+
+#[message(ret = ())]
+struct Freeze;
+
+fn testee() -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                DataUpdate | Command => {}
+                (Freeze, token) => {
+                    ctx.respond(token, ());
+                    tokio::time::sleep(Duration::from_secs(60)).await
+                }
+            });
+        }
+    })
+}
+
+fn testee_config(quota: impl Into<Value>) -> AnyConfig {
+    let quota = quota.into();
+    AnyConfig::deserialize(toml! {
+        [system.mailbox]
+        capacity = 10
+        quotas.DataUpdate = quota
+    })
+    .unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn other_types_get_through() {
+    let proxy = elfo::test::proxy(testee(), testee_config("80%")).await;
+
+    for _ in 0..3 {
+        proxy.request(Freeze).await;
+
+        for i in 1..=8 {
+            assert!(proxy.try_send(DataUpdate).is_ok(), "should pass [{i}/8]");
+        }
+        assert!(matches!(
+            proxy.try_send(DataUpdate),
+            Err(TrySendError::Full(_))
+        ));
+
+        // Another type still gets through, but the total capacity is respected.
+        assert!(proxy.try_send(Command).is_ok());
+        assert!(proxy.try_send(Command).is_ok());
+        assert!(proxy.try_send(Command).is_err());
+
+        // `send()` waits for the quota.
+        let fut = proxy.send(DataUpdate);
+        assert!(tokio::time::timeout(Duration::from_secs(1), fut)
+            .await
+            .is_err());
+
+        // Ensure that all sent messages are handled and permits are returned.
+        proxy.request(Ping::default()).await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconfiguration() {
+    let proxy = elfo::test::proxy(testee(), testee_config("50%")).await;
+
+    for quota in [3, 7, 1, 10] {
+        proxy.send(UpdateConfig::new(testee_config(quota))).await;
+        proxy.request(Freeze).await;
+
+        for i in 1..=quota {
+            assert!(
+                proxy.try_send(DataUpdate).is_ok(),
+                "should pass [{i}/{quota}]"
+            );
+        }
+        assert!(
+            proxy.try_send(DataUpdate).is_err(),
+            "should reject [{quota}]"
+        );
+
+        proxy.request(Ping::default()).await;
+    }
+}
+
+#[tokio::test]
+async fn invalid_quotas() {
+    let proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+
+    for quota in [Value::from("0%"), Value::from("146%"), Value::from("oops")] {
+        let config = testee_config(quota);
+        assert!(proxy.request(UpdateConfig::new(config)).await.is_err());
+    }
+
+    let config = AnyConfig::deserialize(toml! {
+        system.mailbox.quotas.UnknownMessage = "10%"
+    })
+    .unwrap();
+    assert!(proxy.request(UpdateConfig::new(config)).await.is_err());
+}
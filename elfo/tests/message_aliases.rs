@@ -0,0 +1,159 @@
+#![allow(missing_docs)]
+#![cfg(feature = "network")]
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use metrics::{GaugeValue, Key, Recorder, Unit};
+
+use elfo::{_priv::AnyMessage, messages::MessageCatalog, prelude::*, Message};
+
+#[message(protocol = "orders", alias = "OrderPlaced", alias = "legacy/NewOrder")]
+#[derive(PartialEq, Eq)]
+struct OrderAccepted {
+    id: u64,
+}
+
+#[message(protocol = "orders", ret = OrderAccepted, alias = "GetPlacedOrder")]
+struct GetAcceptedOrder;
+
+// === Metrics ===
+
+/// Counts uses of aliases by their names.
+#[derive(Default)]
+struct AliasCounter(Mutex<HashMap<String, u64>>);
+
+impl Recorder for AliasCounter {
+    fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+    fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+    fn record_histogram(&self, _: &Key, _: f64) {}
+
+    fn increment_counter(&self, key: &Key, value: u64) {
+        if key.name() != "elfo_message_aliases_used_total" {
+            return;
+        }
+
+        let alias = key.labels().find(|l| l.key() == "alias").unwrap();
+        *self
+            .0
+            .lock()
+            .unwrap()
+            .entry(alias.value().into())
+            .or_default() += value;
+    }
+}
+
+fn counter() -> &'static AliasCounter {
+    static COUNTER: OnceLock<&'static AliasCounter> = OnceLock::new();
+
+    COUNTER.get_or_init(|| {
+        let counter = Box::leak(Box::<AliasCounter>::default());
+        metrics::set_recorder(counter).unwrap();
+        counter
+    })
+}
+
+fn alias_uses(alias: &str) -> u64 {
+    counter().0.lock().unwrap().get(alias).copied().unwrap_or(0)
+}
+
+/// FNV-1a of `protocol/name`, sent instead of names by the postcard codec.
+fn network_id(protocol: &str, name: &str) -> u64 {
+    format!("{protocol}/{name}")
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+        })
+}
+
+// === Tests ===
+
+#[test]
+fn msgpack() {
+    counter();
+
+    // `{"id": 42}`, encoded by the old version under the old name.
+    let frame = [0x81, 0xa2, b'i', b'd', 0x2a];
+
+    let message = AnyMessage::read_msgpack(&frame, "orders", "OrderPlaced")
+        .unwrap()
+        .expect("unknown alias");
+    assert_eq!(message.name(), "OrderAccepted");
+    assert_eq!(
+        message.downcast::<OrderAccepted>().unwrap(),
+        OrderAccepted { id: 42 }
+    );
+    assert_eq!(alias_uses("OrderPlaced"), 1);
+
+    // An alias with another protocol.
+    let message = AnyMessage::read_msgpack(&frame, "legacy", "NewOrder")
+        .unwrap()
+        .expect("unknown alias");
+    assert!(message.is::<OrderAccepted>());
+    assert_eq!(alias_uses("legacy/NewOrder"), 1);
+
+    // Unknown protocols of aliases aren't resolved.
+    assert!(AnyMessage::read_msgpack(&frame, "orders", "NewOrder")
+        .unwrap()
+        .is_none());
+
+    // The actual name isn't counted.
+    AnyMessage::read_msgpack(&frame, "orders", "OrderAccepted")
+        .unwrap()
+        .unwrap();
+    assert_eq!(alias_uses("OrderAccepted"), 0);
+}
+
+#[test]
+fn postcard() {
+    counter();
+
+    let old_id = network_id("orders", "GetPlacedOrder::Response");
+    let (protocol, name, _) = AnyMessage::lookup_network_id(old_id).expect("unknown alias");
+    assert_eq!((protocol, name), ("orders", "GetAcceptedOrder::Response"));
+
+    // `OrderAccepted { id: 42 }` as the response of the old request.
+    let message = AnyMessage::read_postcard(&[0x2a], old_id)
+        .unwrap()
+        .expect("unknown alias");
+    assert_eq!(message.name(), "GetAcceptedOrder::Response");
+    assert!(alias_uses("GetPlacedOrder::Response") >= 1);
+}
+
+#[test]
+fn encoded_under_actual_name() {
+    let message = AnyMessage::new(OrderAccepted { id: 42 });
+    assert_eq!(message.name(), "OrderAccepted");
+    assert_eq!(message.network_id(), network_id("orders", "OrderAccepted"));
+
+    let json = serde_json::to_string(&message).unwrap();
+    assert_eq!(json, r#"["orders","OrderAccepted",{"id":42}]"#);
+
+    // Dumps made by the old version.
+    let old = r#"["orders","OrderPlaced",{"id":42}]"#;
+    let message = serde_json::from_str::<AnyMessage>(old).unwrap();
+    assert_eq!(
+        message.downcast::<OrderAccepted>().unwrap(),
+        OrderAccepted { id: 42 }
+    );
+}
+
+#[test]
+fn listed_in_catalog() {
+    let catalog = MessageCatalog::collect("orders");
+
+    let names = catalog.messages.iter().map(|m| &m.name[..]);
+    assert_eq!(
+        names.collect::<Vec<_>>(),
+        ["GetAcceptedOrder", "OrderAccepted"]
+    );
+    assert_eq!(catalog.messages[0].aliases, ["GetPlacedOrder"]);
+    assert_eq!(
+        catalog.messages[1].aliases,
+        ["OrderPlaced", "legacy/NewOrder"]
+    );
+}
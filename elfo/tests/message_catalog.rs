@@ -0,0 +1,112 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::any::type_name;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::{CatalogMessage, GetMessageCatalog, MessageCatalog},
+    prelude::*,
+    Topology,
+};
+
+#[message(part)]
+struct AdminStatus {
+    is_ready: bool,
+}
+
+#[message(protocol = "admin.test", ret = AdminStatus)]
+struct GetAdminStatus;
+
+#[message(protocol = "admin.test")]
+struct AdminEvent;
+
+#[message(protocol = "admin.test", deprecated = "use AdminEvent")]
+struct LegacyAdminEvent;
+
+fn admin() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (GetAdminStatus, token) => ctx.respond(token, AdminStatus { is_ready: true }),
+            });
+        }
+    })
+}
+
+async fn catalog(protocol_prefix: &str) -> MessageCatalog {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let admin = topology.local("admin");
+    let addr = configurers.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    admin.mount(self::admin());
+
+    let request = GetMessageCatalog::with_protocol_prefix(protocol_prefix);
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let catalog = ctx.request_to(addr, request).resolve().await;
+        terminate(ctx, topology).await;
+        catalog
+    })
+    .await
+    .expect("cannot start")
+    .expect("no catalog")
+}
+
+#[tokio::test]
+async fn custom_request() {
+    let catalog = catalog("admin").await;
+
+    // Response wrappers are described by requests.
+    let names = catalog.messages.iter().map(|m| &m.name[..]);
+    assert_eq!(
+        names.collect::<Vec<_>>(),
+        ["AdminEvent", "GetAdminStatus", "LegacyAdminEvent"]
+    );
+
+    let event = &catalog.messages[0];
+    assert_eq!(event.protocol, "admin.test");
+    assert!(!event.is_request());
+    assert_eq!(event.response, None);
+    assert!(!event.is_deprecated());
+
+    let request = &catalog.messages[1];
+    assert_eq!(request.protocol, "admin.test");
+    assert_eq!(request.path, "message_catalog::GetAdminStatus");
+    assert!(request.is_request());
+    assert_eq!(
+        request.response.as_deref(),
+        Some(type_name::<AdminStatus>())
+    );
+    assert_eq!(request.schema_hash.is_some(), cfg!(feature = "network"));
+
+    let legacy = &catalog.messages[2];
+    assert!(legacy.is_deprecated());
+    assert_eq!(legacy.deprecated.as_deref(), Some("use AdminEvent"));
+}
+
+#[tokio::test]
+async fn all_protocols() {
+    let catalog = catalog("").await;
+
+    let request = catalog
+        .messages
+        .iter()
+        .find(|m| m.name == "GetMessageCatalog")
+        .expect("no builtin request");
+    assert_eq!(
+        request.response.as_deref(),
+        Some(type_name::<MessageCatalog>())
+    );
+
+    assert!(catalog.messages.iter().any(|m| m.name == "GetAdminStatus"));
+    let is_sorted =
+        |w: &[CatalogMessage]| (&w[0].protocol, &w[0].name) <= (&w[1].protocol, &w[1].name);
+    assert!(catalog.messages.windows(2).all(is_sorted));
+}
@@ -0,0 +1,97 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    prelude::*,
+    Topology,
+};
+use serde::Deserialize;
+use toml::toml;
+
+mod common;
+
+// Both messages are registered as `elfo/Charge`.
+mod billing {
+    use elfo::message;
+
+    #[message]
+    pub(super) struct Charge;
+}
+
+mod legacy {
+    use elfo::message;
+
+    #[message]
+    pub(super) struct Charge;
+}
+
+fn topology(config: AnyConfig) -> Topology {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let billing = topology.local("billing");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    billing.mount(ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                billing::Charge => {}
+                legacy::Charge => {}
+            });
+        }
+    }));
+
+    topology
+}
+
+#[tokio::test]
+async fn startup_fails_on_collisions() {
+    common::setup_logger();
+
+    #[cfg(feature = "unstable")]
+    {
+        let collisions = elfo::init::message_collisions();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].protocol, "elfo");
+        assert_eq!(collisions[0].name, "Charge");
+        assert_eq!(
+            collisions[0].paths,
+            [
+                "message_collisions::billing::Charge",
+                "message_collisions::legacy::Charge",
+            ]
+        );
+    }
+
+    let error = do_start(topology(AnyConfig::default()), false, terminate)
+        .await
+        .unwrap_err();
+
+    let reasons = error
+        .errors
+        .iter()
+        .map(|error| error.reason.as_str())
+        .collect::<Vec<_>>();
+    assert!(!reasons.is_empty());
+
+    for reason in reasons {
+        assert!(reason.contains("message `elfo/Charge` is defined several times"));
+        assert!(reason.contains("`message_collisions::billing::Charge`"));
+        assert!(reason.contains("`message_collisions::legacy::Charge`"));
+        assert!(reason.contains("system.allow_duplicate_messages"));
+    }
+}
+
+#[tokio::test]
+async fn collisions_can_be_allowed() {
+    common::setup_logger();
+
+    let config = AnyConfig::deserialize(toml! {
+        [common]
+        system.allow_duplicate_messages = true
+    })
+    .unwrap();
+
+    do_start(topology(config), false, terminate).await.unwrap();
+}
@@ -0,0 +1,132 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::{DeliveryError, ErrorKind},
+    messages::{GroupTerminated, StartEntrypoint, SubscribeToLifecycleEvents, UpdateConfig},
+    prelude::*,
+    Addr, Topology,
+};
+
+mod common;
+
+#[message(ret = u32)]
+struct Ping(u32);
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[allow(dead_code)]
+    enabled: bool,
+}
+
+fn exporter() -> Blueprint {
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Ping(n), token) => ctx.respond(token, n + 1),
+                });
+            }
+        })
+}
+
+fn config(enabled: bool) -> AnyConfig {
+    AnyConfig::deserialize(toml! { enabled = enabled }).unwrap()
+}
+
+#[message(ret = ())]
+struct Run;
+
+fn disabled<T, E>(res: Result<T, DeliveryError<E>>) -> bool {
+    res.is_err_and(|err| err.kind() == ErrorKind::GroupDisabled)
+}
+
+fn driver(exporter: Addr) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| async move {
+        let update = |enabled| UpdateConfig::new(self::config(enabled));
+
+        ctx.send_to(exporter, SubscribeToLifecycleEvents::default())
+            .await
+            .unwrap();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Run, token) => {
+                    // Disabled initially.
+                    let res = ctx.request_to(exporter, Ping(1)).resolve_detailed().await;
+                    assert!(disabled(res));
+                    assert!(disabled(ctx.detailed().try_send_to(exporter, Ping(1))));
+
+                    // Enabled by a config update.
+                    let res = ctx.request_to(exporter, update(true)).resolve().await;
+                    res.unwrap().unwrap();
+                    assert_eq!(
+                        ctx.request_to(exporter, Ping(1)).resolve().await.unwrap(),
+                        2
+                    );
+
+                    // Disabled again.
+                    let res = ctx.request_to(exporter, update(false)).resolve().await;
+                    res.unwrap().unwrap();
+                    let res = ctx.request_to(exporter, Ping(2)).resolve_detailed().await;
+                    assert!(disabled(res));
+                    assert!(disabled(ctx.detailed().try_send_to(exporter, Ping(2))));
+
+                    // Wait for the old actor to terminate.
+                    while let Some(envelope) = ctx.recv().await {
+                        if envelope.is::<GroupTerminated>() {
+                            break;
+                        }
+                    }
+
+                    // And enabled again with a fresh actor.
+                    let res = ctx.request_to(exporter, update(true)).resolve().await;
+                    res.unwrap().unwrap();
+                    assert_eq!(
+                        ctx.request_to(exporter, Ping(2)).resolve().await.unwrap(),
+                        3
+                    );
+
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn enable_and_disable() {
+    common::setup_logger();
+
+    let config = AnyConfig::deserialize(toml! {
+        [exporter]
+        enabled = false
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let exporter = topology.local("exporter");
+    let driver = topology.local("driver").entrypoint();
+    let driver_addr = driver.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    driver.mount(self::driver(exporter.addr()));
+    exporter.mount_if(self::exporter(), |config| config.get_bool("enabled"));
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let res = ctx.request_to(driver_addr, Run).resolve().await;
+        terminate(ctx, topology).await;
+        res
+    })
+    .await
+    .expect("cannot start")
+    .expect("driver failed");
+}
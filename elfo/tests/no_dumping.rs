@@ -0,0 +1,54 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", not(feature = "dumping")))]
+
+use elfo::{config::AnyConfig, prelude::*};
+
+#[message]
+struct Ping;
+
+#[message]
+struct Pong;
+
+fn sample() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Ping => ctx.send(Pong).await.unwrap(),
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn nothing_is_dumped() {
+    assert!(!elfo::dumping::ENABLED);
+
+    let mut proxy = elfo::test::proxy(sample(), AnyConfig::default()).await;
+
+    proxy.send(Ping).await;
+    assert_msg!(proxy.recv().await, Pong);
+
+    assert!(proxy.dumps().is_empty());
+}
+
+// Types are stubbed, not removed, so code using them still compiles.
+#[cfg(feature = "unstable")]
+#[test]
+fn types_are_present() {
+    use elfo::dumping::{Direction, Dump, Dumper};
+
+    let dumper = Dumper::new("custom");
+    if let Some(permit) = dumper.acquire() {
+        permit.record(Dump::builder().direction(Direction::Out).finish(Pong));
+    }
+    assert!(dumper.acquire().is_none());
+}
+
+#[cfg(feature = "full")]
+#[test]
+#[should_panic(expected = "dumping is disabled (the `dumping` feature of `elfo-core` is off)")]
+fn dumper_refuses_to_mount() {
+    let topology = elfo::Topology::empty();
+    let dumpers = topology.local("system.dumpers");
+    dumpers.mount(elfo::batteries::dumper::new());
+}
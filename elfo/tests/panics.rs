@@ -0,0 +1,104 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Serialize;
+use serde_json::json;
+
+use elfo::{
+    config::AnyConfig,
+    messages::{ActorStatusReport, SubscribeToActorStatuses},
+    prelude::*,
+    ActorStatusKind, RestartPolicy,
+};
+
+#[message]
+struct Reject(u64);
+
+#[message]
+struct Unsupported;
+
+#[derive(Serialize)]
+struct OrderRejected {
+    order_id: u64,
+    reason: &'static str,
+}
+
+struct Opaque;
+
+fn testee() -> Blueprint {
+    ActorGroup::new()
+        .restart_policy(RestartPolicy::never())
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Reject(order_id) => std::panic::panic_any(OrderRejected {
+                        order_id,
+                        reason: "no liquidity",
+                    }),
+                    Unsupported => std::panic::panic_any(Opaque),
+                    _ => {}
+                });
+            }
+        })
+}
+
+async fn failed_status(proxy: &mut elfo::test::Proxy) -> elfo::ActorStatus {
+    loop {
+        msg!(match proxy.recv().await {
+            ActorStatusReport { status, .. } if status.kind() == ActorStatusKind::Failed => {
+                return status;
+            }
+            ActorStatusReport => {}
+            _ => unreachable!(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn registered_payload() {
+    // Enables backtraces for this test binary only.
+    std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    elfo::panics::register_extractor::<OrderRejected>(|p| serde_json::to_value(p));
+
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+    proxy.send(SubscribeToActorStatuses::default()).await;
+    proxy.send(Reject(42)).await;
+
+    let expected = json!({ "order_id": 42, "reason": "no liquidity" });
+
+    // The status.
+    let status = failed_status(&mut proxy).await;
+    assert_eq!(
+        status.details(),
+        Some(r#"panic: OrderRejected {"order_id":42,"reason":"no liquidity"}"#)
+    );
+    let panic = status.panic().unwrap();
+    assert_eq!(panic.message, "OrderRejected");
+    assert_eq!(panic.payload.as_ref(), Some(&expected));
+    assert!(panic
+        .backtrace
+        .as_ref()
+        .is_some_and(|b| !b.as_str().is_empty()));
+
+    // The dump.
+    let dumps = proxy.dumps().class("panic");
+    assert_eq!(dumps.len(), 1);
+    let dump = dumps.iter().next().unwrap();
+    assert_eq!(dump.message_name(), "Panic");
+    let value = dump.message_value().unwrap().clone();
+    let value = value.deserialize_into::<serde_json::Value>().unwrap();
+    assert_eq!(value["message"], "OrderRejected");
+    assert_eq!(value["payload"], expected);
+    assert!(value["backtrace"].is_string());
+}
+
+#[tokio::test]
+async fn unregistered_payload() {
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+    proxy.send(SubscribeToActorStatuses::default()).await;
+    proxy.send(Unsupported).await;
+
+    let status = failed_status(&mut proxy).await;
+    assert_eq!(status.details(), Some("panic: <unsupported payload>"));
+    assert_eq!(status.panic().unwrap().payload, None);
+}
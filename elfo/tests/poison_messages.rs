@@ -0,0 +1,114 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use toml::toml;
+
+use elfo::{
+    messages::{DeadLetter, DeadLetterReason, SubscribeToLifecycleEvents},
+    prelude::*,
+    test::Proxy,
+    RestartParams, RestartPolicy,
+};
+
+#[message]
+struct Poison;
+
+#[message]
+struct Flaky;
+
+#[message]
+struct Job(u32);
+
+#[message]
+struct Done(u32);
+
+static FLAKY_PANICS: AtomicU32 = AtomicU32::new(0);
+
+async fn run_group() -> Proxy {
+    let blueprint = ActorGroup::new()
+        .restart_policy(RestartPolicy::on_failure(RestartParams::new(
+            Duration::ZERO,
+            Duration::ZERO,
+        )))
+        .exec(move |mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Poison => panic!("poisoned"),
+                    Flaky => {
+                        if FLAKY_PANICS.fetch_add(1, Ordering::SeqCst) == 0 {
+                            panic!("flaky");
+                        }
+                        ctx.send(Done(0)).await.unwrap();
+                    }
+                    Job(n) => ctx.send(Done(n)).await.unwrap(),
+                });
+            }
+        });
+
+    let config = toml! {
+        [system.mailbox]
+        poison_threshold = 3
+    };
+
+    let mut proxy = elfo::test::proxy(blueprint, config).await;
+    proxy.send(SubscribeToLifecycleEvents::default()).await;
+    proxy.sync().await;
+    proxy
+}
+
+async fn collect(proxy: &mut Proxy, count: usize) -> (Vec<u32>, Vec<DeadLetter>) {
+    let mut done = Vec::new();
+    let mut dead_letters = Vec::new();
+
+    while done.len() < count {
+        msg!(match proxy.recv().await {
+            Done(n) => done.push(n),
+            dead_letter @ DeadLetter => dead_letters.push(dead_letter),
+            _ => {} // other lifecycle events
+        });
+    }
+
+    (done, dead_letters)
+}
+
+#[tokio::test]
+async fn poison_is_quarantined() {
+    let mut proxy = run_group().await;
+
+    proxy.send(Poison).await;
+    for n in 1..=3 {
+        proxy.send(Job(n)).await;
+    }
+
+    // Good messages are handled in order after the poisoned one.
+    let (done, dead_letters) = collect(&mut proxy, 3).await;
+    assert_eq!(done, [1, 2, 3]);
+
+    let [dead_letter] = &dead_letters[..] else {
+        panic!("unexpected dead letters: {dead_letters:?}");
+    };
+    assert!(dead_letter.message.is::<Poison>());
+    assert_eq!(dead_letter.meta.group, "subject");
+    assert!(matches!(
+        &dead_letter.reason,
+        DeadLetterReason::Poisoned { panic } if panic == "poisoned"
+    ));
+}
+
+#[tokio::test]
+async fn message_is_handled_again_below_threshold() {
+    let mut proxy = run_group().await;
+
+    proxy.send(Flaky).await;
+    proxy.send(Job(1)).await;
+
+    let (done, dead_letters) = collect(&mut proxy, 2).await;
+    assert_eq!(done, [0, 1]);
+    assert!(dead_letters.is_empty());
+    assert_eq!(FLAKY_PANICS.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,293 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tokio::sync::Notify;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    messages::Terminate,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+};
+
+#[message]
+struct Block;
+
+#[message]
+struct Blocked(usize);
+
+#[message(ret = usize)]
+struct WhoAmI;
+
+#[message]
+struct Exited(usize);
+
+fn testee(workers: usize, release: Arc<Notify>) -> Blueprint {
+    ActorGroup::new().pool(workers).exec(move |mut ctx| {
+        let release = release.clone();
+
+        async move {
+            let worker = *ctx.key();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Block => {
+                        ctx.send(Blocked(worker)).await.unwrap();
+                        release.notified().await;
+                    }
+                    (WhoAmI, token) => ctx.respond(token, worker),
+                });
+            }
+
+            ctx.send(Exited(worker)).await.unwrap();
+        }
+    })
+}
+
+#[tokio::test]
+async fn slow_message_does_not_delay_others() {
+    let release = Arc::new(Notify::new());
+    let blueprint = testee(2, release.clone());
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+
+    proxy.send(Block).await;
+    let blocked = msg!(match proxy.recv().await {
+        Blocked(worker) => worker,
+        _ => unreachable!(),
+    });
+
+    // All requests are handled by the idle worker meanwhile.
+    for _ in 0..10 {
+        let request = proxy.request(WhoAmI);
+        let worker = tokio::time::timeout(Duration::from_secs(5), request)
+            .await
+            .expect("delayed by the slow message");
+        assert_ne!(worker, blocked);
+    }
+
+    // The released worker takes messages again.
+    release.notify_one();
+    proxy.send(Block).await;
+    proxy.send(Block).await;
+    let mut workers = Vec::new();
+    for _ in 0..2 {
+        msg!(match proxy.recv().await {
+            Blocked(worker) => workers.push(worker),
+            _ => unreachable!(),
+        });
+    }
+    workers.sort_unstable();
+    assert_eq!(workers, [0, 1]);
+
+    release.notify_waiters();
+    proxy.sync().await;
+}
+
+#[tokio::test]
+async fn terminate_reaches_all_workers() {
+    let release = Arc::new(Notify::new());
+    let mut proxy = elfo::test::proxy(testee(3, release), AnyConfig::default()).await;
+
+    proxy.send(Terminate::default()).await;
+    proxy.finished().await;
+
+    let mut workers = Vec::new();
+    for _ in 0..3 {
+        msg!(match proxy.recv().await {
+            Exited(worker) => workers.push(worker),
+            _ => unreachable!(),
+        });
+    }
+    workers.sort_unstable();
+    assert_eq!(workers, [0, 1, 2]);
+}
+
+#[tokio::test]
+#[should_panic(expected = "must be routed by `PoolRouter`")]
+async fn other_routers_are_rejected() {
+    let blueprint = ActorGroup::new()
+        .pool(2)
+        .router(MapRouter::new(|_| Outcome::<usize>::Broadcast))
+        .exec(|_ctx| async {});
+
+    elfo::test::proxy(blueprint, AnyConfig::default()).await;
+}
+
+#[message]
+struct Job {
+    key: u32,
+    no: u32,
+    block: bool,
+}
+
+impl Job {
+    fn new(key: u32, no: u32) -> Self {
+        Self {
+            key,
+            no,
+            block: false,
+        }
+    }
+
+    fn blocking(key: u32, no: u32) -> Self {
+        Self {
+            key,
+            no,
+            block: true,
+        }
+    }
+}
+
+#[message]
+struct Started {
+    key: u32,
+    no: u32,
+    worker: usize,
+    /// Another job of the same key is being handled by another worker.
+    overlapped: bool,
+}
+
+fn sticky_testee(workers: usize, release: Arc<Notify>) -> Blueprint {
+    let active = Arc::new(Mutex::new(HashSet::new()));
+
+    ActorGroup::new()
+        .pool(workers)
+        .sticky_by(|envelope| {
+            msg!(match envelope {
+                Job { key, .. } => Some(*key),
+                _ => None,
+            })
+        })
+        .exec(move |mut ctx| {
+            let release = release.clone();
+            let active = active.clone();
+
+            async move {
+                let worker = *ctx.key();
+
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        Job { key, no, block } => {
+                            let overlapped = !active.lock().unwrap().insert(key);
+                            let started = Started {
+                                key,
+                                no,
+                                worker,
+                                overlapped,
+                            };
+                            ctx.send(started).await.unwrap();
+
+                            if block {
+                                release.notified().await;
+                            } else {
+                                tokio::time::sleep(Duration::from_millis(5)).await;
+                            }
+
+                            active.lock().unwrap().remove(&key);
+                        }
+                    });
+                }
+            }
+        })
+}
+
+async fn started(proxy: &mut elfo::test::Proxy) -> Started {
+    msg!(match proxy.recv().await {
+        started @ Started => started,
+        _ => unreachable!(),
+    })
+}
+
+#[tokio::test]
+async fn sticky_same_key_is_serialized() {
+    let release = Arc::new(Notify::new());
+    let mut proxy = elfo::test::proxy(sticky_testee(4, release), AnyConfig::default()).await;
+
+    for no in 0..10 {
+        proxy.send(Job::new(1, no)).await;
+        proxy.send(Job::new(2, no)).await;
+    }
+
+    let mut handled = [Vec::new(), Vec::new()];
+    for _ in 0..20 {
+        let started = started(&mut proxy).await;
+        assert!(!started.overlapped);
+        handled[started.key as usize - 1].push((started.no, started.worker));
+    }
+
+    // FIFO on the same worker.
+    for handled in handled {
+        let worker = handled[0].1;
+        assert_eq!(handled, (0..10).map(|no| (no, worker)).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test]
+async fn sticky_different_keys_are_parallel() {
+    let release = Arc::new(Notify::new());
+    let blueprint = sticky_testee(2, release.clone());
+    let mut proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
+
+    proxy.send(Job::blocking(1, 0)).await;
+    let blocked = started(&mut proxy).await.worker;
+
+    // The next job of the blocked key waits for it, other keys don't.
+    proxy.send(Job::new(1, 1)).await;
+    proxy.send(Job::new(2, 0)).await;
+    let started_2 = started(&mut proxy).await;
+    assert_eq!((started_2.key, started_2.no), (2, 0));
+    assert_ne!(started_2.worker, blocked);
+
+    release.notify_one();
+    let started_1 = started(&mut proxy).await;
+    assert_eq!((started_1.key, started_1.no), (1, 1));
+    assert_eq!(started_1.worker, blocked);
+    assert!(!started_1.overlapped);
+}
+
+#[tokio::test]
+async fn sticky_falls_back_to_any_worker_if_full() {
+    let release = Arc::new(Notify::new());
+    let config = AnyConfig::deserialize(toml! {
+        system.mailbox.sticky_capacity = 1
+    })
+    .unwrap();
+    let mut proxy = elfo::test::proxy(sticky_testee(3, release.clone()), config).await;
+
+    // The only binding is occupied by the first key.
+    proxy.send(Job::blocking(1, 0)).await;
+    let first = started(&mut proxy).await.worker;
+
+    // Jobs of other keys aren't bound, so they're handled by any idle worker.
+    proxy.send(Job::blocking(2, 0)).await;
+    let second = started(&mut proxy).await.worker;
+    assert_ne!(second, first);
+
+    proxy.send(Job::blocking(2, 1)).await;
+    let third = started(&mut proxy).await;
+    assert_eq!((third.key, third.no), (2, 1));
+    assert_ne!(third.worker, first);
+    assert_ne!(third.worker, second);
+    assert!(third.overlapped);
+
+    // The bound key is still sticky.
+    proxy.send(Job::new(1, 1)).await;
+    release.notify_waiters();
+    let mut workers = Vec::new();
+    loop {
+        let started = started(&mut proxy).await;
+        if started.key == 1 {
+            workers.push(started.worker);
+            break;
+        }
+    }
+    assert_eq!(workers, [first]);
+}
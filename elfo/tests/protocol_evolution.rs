@@ -76,6 +76,58 @@ fn struct_new_default_field() {
     ensure_parsable(S0 { a: 42 }, S3 { a: 42, b: 0 });
 }
 
+#[test]
+fn struct_strict() {
+    #[message(strict)]
+    #[derive(PartialEq, Eq)]
+    struct SS0 {
+        a: u32,
+    }
+
+    #[message]
+    #[derive(PartialEq, Eq)]
+    struct SS1 {
+        a: u32,
+        b: u32,
+    }
+
+    ensure_parsable(S0 { a: 42 }, SS0 { a: 42 });
+    ensure_unparsable(SS1 { a: 42, b: 42 }, SS0 { a: 42 });
+}
+
+#[test]
+fn struct_ignored_fields() {
+    #[message]
+    #[derive(PartialEq, Eq)]
+    struct SI1 {
+        a: u32,
+        b: u32,
+        c: String,
+    }
+
+    let s0 = S0 { a: 42 };
+    let read = |buf: &[u8]| AnyMessage::read_msgpack_lenient(buf, s0.protocol(), s0.name());
+
+    let mut buf = Vec::new();
+    AnyMessage::new(s0.clone())
+        .write_msgpack(&mut buf, 512)
+        .unwrap();
+    assert_eq!(read(&buf).unwrap().unwrap().1, 0);
+
+    buf.clear();
+    let si1 = SI1 {
+        a: 42,
+        b: 42,
+        c: "42".into(),
+    };
+    AnyMessage::new(si1).write_msgpack(&mut buf, 512).unwrap();
+    assert_eq!(read(&buf).unwrap().unwrap().1, 2);
+
+    // Corrupted messages are reported instead of being silently re-decoded.
+    buf.truncate(buf.len() - 1);
+    assert!(read(&buf).is_err());
+}
+
 #[test]
 fn struct_aliased_field() {
     #[message]
@@ -0,0 +1,147 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    prelude::*,
+    Addr, RestartParams, RestartPolicy, Topic, Topology,
+};
+
+mod common;
+
+const SESSIONS: Topic = Topic::new("sessions");
+
+#[message]
+struct SessionEvent(u32);
+
+#[message]
+struct Unrelated;
+
+#[message(ret = bool)]
+struct Subscribe;
+
+#[message(ret = bool)]
+struct Unsubscribe;
+
+#[message(ret = ())]
+struct Crash;
+
+#[message(ret = Vec<u32>)]
+struct GetEvents;
+
+fn subscriber() -> Blueprint {
+    ActorGroup::new()
+        .restart_policy(RestartPolicy::on_failure(RestartParams::new(
+            Duration::ZERO,
+            Duration::ZERO,
+        )))
+        .exec(|mut ctx| async move {
+            let mut events = Vec::new();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Subscribe, token) => {
+                        let is_new = ctx.subscribe::<SessionEvent>(SESSIONS);
+                        ctx.respond(token, is_new);
+                    }
+                    (Unsubscribe, token) => {
+                        let is_removed = ctx.unsubscribe::<SessionEvent>(SESSIONS);
+                        ctx.respond(token, is_removed);
+                    }
+                    (Crash, token) => {
+                        drop(token);
+                        panic!("boom!");
+                    }
+                    SessionEvent(id) => events.push(id),
+                    (GetEvents, token) => ctx.respond(token, std::mem::take(&mut events)),
+                });
+            }
+        })
+}
+
+fn topology() -> (Topology, Addr, Addr) {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let audit = topology.local("audit");
+    let billing = topology.local("billing");
+    let addrs = (audit.addr(), billing.addr());
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    audit.mount(subscriber());
+    billing.mount(subscriber());
+
+    (topology, addrs.0, addrs.1)
+}
+
+async fn request<R: elfo::Request>(ctx: &Context, addr: Addr, request: R) -> R::Response {
+    ctx.request_to(addr, request).resolve().await.unwrap()
+}
+
+#[tokio::test]
+async fn publish_subscribe() {
+    common::setup_logger();
+
+    let (topology, audit, billing) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        // No subscribers.
+        assert_eq!(ctx.publish(SESSIONS, SessionEvent(1)).await, 0);
+
+        assert!(request(&ctx, audit, Subscribe).await);
+        assert!(request(&ctx, billing, Subscribe).await);
+        assert!(!request(&ctx, billing, Subscribe).await);
+
+        assert_eq!(ctx.publish(SESSIONS, SessionEvent(2)).await, 2);
+        // Dynamic topics are the same as static ones with the same name.
+        let topic = Topic::from(String::from("sessions"));
+        assert_eq!(ctx.publish(topic, SessionEvent(3)).await, 2);
+
+        // Other topics and message types aren't delivered.
+        assert_eq!(ctx.publish("orders", SessionEvent(4)).await, 0);
+        assert_eq!(ctx.publish(SESSIONS, Unrelated).await, 0);
+
+        assert_eq!(request(&ctx, audit, GetEvents).await, [2, 3]);
+        assert_eq!(request(&ctx, billing, GetEvents).await, [2, 3]);
+
+        // Explicit unsubscription.
+        assert!(request(&ctx, audit, Unsubscribe).await);
+        assert!(!request(&ctx, audit, Unsubscribe).await);
+        assert_eq!(ctx.publish(SESSIONS, SessionEvent(5)).await, 1);
+        assert!(request(&ctx, audit, GetEvents).await.is_empty());
+        assert_eq!(request(&ctx, billing, GetEvents).await, [5]);
+
+        // Restarted actors lose subscriptions.
+        assert!(request(&ctx, audit, Subscribe).await);
+        // The failed actor is restarted immediately.
+        ctx.request_to(billing, Crash).resolve().await.unwrap_err();
+        assert_eq!(ctx.publish(SESSIONS, SessionEvent(6)).await, 1);
+        assert!(request(&ctx, billing, GetEvents).await.is_empty());
+
+        assert!(request(&ctx, billing, Subscribe).await);
+        assert_eq!(ctx.publish(SESSIONS, SessionEvent(7)).await, 2);
+        assert_eq!(request(&ctx, audit, GetEvents).await, [6, 7]);
+        assert_eq!(request(&ctx, billing, GetEvents).await, [7]);
+
+        // Outgoing dumps are recorded in the topic's class.
+        #[cfg(feature = "dumping")]
+        {
+            let dumps = topology.dump_capture().snapshot();
+            let published = dumps
+                .iter()
+                .filter(|dump| dump.class == "topic:sessions" && !dump.is_incoming)
+                .count();
+            // All but ones published without subscribers.
+            assert_eq!(published, 5);
+        }
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
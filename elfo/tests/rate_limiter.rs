@@ -0,0 +1,175 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::time::Instant;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    errors::{ErrorKind, TrySendError},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    test::Proxy,
+};
+
+/// Every worker sends `count` ticks, waiting for the budget.
+#[message]
+struct Produce {
+    workers: u32,
+    count: u32,
+}
+
+/// The worker tries to send `count` ticks without waiting.
+#[message]
+struct TryProduce(u32);
+
+#[message]
+struct Tick(u32);
+
+#[message]
+#[derive(PartialEq)]
+struct Tried(Vec<bool>);
+
+fn testee() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                Produce { workers, .. } => Outcome::Multicast((0..*workers).collect()),
+                _ => Outcome::Unicast(0),
+            })
+        }))
+        .exec(|mut ctx| async move {
+            let worker = *ctx.key();
+            let testers = ctx.locate_group("system.testers").unwrap();
+            let rate = "50/s".parse().unwrap();
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Produce { count, .. } => {
+                        // Call sites of the same actor share the budget.
+                        let a = ctx.rate_limited(&testers, rate);
+                        let b = ctx.rate_limited(&testers, rate);
+
+                        for i in 0..count {
+                            let limited = if i % 2 == 0 { &a } else { &b };
+                            limited.send(Tick(worker)).await.unwrap();
+                        }
+                    }
+                    TryProduce(count) => {
+                        let limited = ctx.rate_limited(&testers, rate);
+
+                        let tried = (0..count)
+                            .map(|_| match limited.try_send_detailed(Tick(worker)) {
+                                Ok(()) => true,
+                                Err(err) => {
+                                    assert_eq!(err.kind(), ErrorKind::RateLimited);
+                                    assert!(matches!(*err, TrySendError::Full(Tick(_))));
+                                    false
+                                }
+                            })
+                            .collect();
+
+                        ctx.send(Tried(tried)).await.unwrap();
+                    }
+                });
+            }
+        })
+}
+
+/// Receives ticks, returns their offsets since the call and senders.
+async fn recv_ticks(proxy: &mut Proxy, count: usize) -> Vec<(Duration, u32)> {
+    let start = Instant::now();
+    let mut ticks = Vec::new();
+
+    for _ in 0..count {
+        msg!(match proxy.recv().await {
+            Tick(worker) => ticks.push((start.elapsed(), worker)),
+            _ => unreachable!(),
+        });
+    }
+
+    ticks
+}
+
+fn offsets(ticks: &[(Duration, u32)]) -> Vec<u128> {
+    ticks.iter().map(|(offset, _)| offset.as_millis()).collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn paces_sends() {
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+
+    proxy
+        .send(Produce {
+            workers: 1,
+            count: 10,
+        })
+        .await;
+
+    let ticks = recv_ticks(&mut proxy, 10).await;
+    let expected = (0..10).map(|i| i * 20).collect::<Vec<_>>();
+    assert_eq!(offsets(&ticks), expected);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rate_from_config() {
+    let config = AnyConfig::deserialize(toml! {
+        [system.rate_limiter.destinations]
+        "system.testers" = "10/s"
+    })
+    .unwrap();
+    let mut proxy = elfo::test::proxy(testee(), config).await;
+
+    proxy
+        .send(Produce {
+            workers: 1,
+            count: 5,
+        })
+        .await;
+
+    let ticks = recv_ticks(&mut proxy, 5).await;
+    assert_eq!(offsets(&ticks), [0, 100, 200, 300, 400]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn try_send_fails_fast() {
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+
+    proxy.send(TryProduce(3)).await;
+    assert_msg!(proxy.recv().await, Tick(0));
+    assert_msg_eq!(proxy.recv().await, Tried(vec![true, false, false]));
+
+    // The budget is restored in the next slot.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    proxy.send(TryProduce(2)).await;
+    assert_msg!(proxy.recv().await, Tick(0));
+    assert_msg_eq!(proxy.recv().await, Tried(vec![true, false]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn shared_by_actors_fairly() {
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+
+    proxy
+        .send(Produce {
+            workers: 3,
+            count: 10,
+        })
+        .await;
+
+    // Actors of the group share the budget.
+    let ticks = recv_ticks(&mut proxy, 30).await;
+    let expected = (0..30).map(|i| i * 20).collect::<Vec<_>>();
+    assert_eq!(offsets(&ticks), expected);
+
+    // Every actor gets its share all the time, except of startup.
+    for window in ticks.chunks(6) {
+        for worker in 0..3 {
+            let sent = window.iter().filter(|(_, w)| *w == worker).count();
+            assert!((1..=3).contains(&sent), "{ticks:?}");
+        }
+    }
+}
@@ -0,0 +1,127 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    messages::{ActorStatusReport, GetRecentDumps, RecentDump, SubscribeToActorStatuses},
+    prelude::*,
+    ActorStatusKind, RestartPolicy,
+};
+
+#[message]
+struct Number(u32);
+
+#[message]
+struct Crash;
+
+fn testee() -> Blueprint {
+    ActorGroup::new()
+        .restart_policy(RestartPolicy::never())
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Crash => panic!("crashed"),
+                    _ => {}
+                });
+            }
+        })
+}
+
+fn config() -> AnyConfig {
+    AnyConfig::deserialize(toml! {
+        [system.dumping.recent]
+        count = 3
+        payloads = true
+    })
+    .unwrap()
+}
+
+fn summary(dumps: &[RecentDump]) -> Vec<(&str, Option<&str>)> {
+    dumps
+        .iter()
+        .map(|d| (&d.name[..], d.payload.as_deref()))
+        .collect()
+}
+
+#[tokio::test]
+async fn attached_to_panic() {
+    let mut proxy = elfo::test::proxy(testee(), config()).await;
+    proxy.send(SubscribeToActorStatuses::default()).await;
+
+    for number in 1..=5 {
+        proxy.send(Number(number)).await;
+    }
+    proxy.sync().await;
+
+    // Inspected live.
+    let dumps = proxy
+        .request(GetRecentDumps::new("subject".into(), "_".into()))
+        .await;
+    assert_eq!(
+        summary(&dumps),
+        [
+            ("Number", Some("3")),
+            ("Number", Some("4")),
+            ("Number", Some("5"))
+        ]
+    );
+    assert!(dumps.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+    // Attached to the panic.
+    proxy.send(Crash).await;
+
+    let status = loop {
+        msg!(match proxy.recv().await {
+            ActorStatusReport { status, .. } if status.kind() == ActorStatusKind::Failed => {
+                break status;
+            }
+            ActorStatusReport => {}
+            _ => unreachable!(),
+        })
+    };
+
+    let panic = status.panic().unwrap();
+    assert_eq!(panic.message, "crashed");
+    assert_eq!(
+        summary(&panic.recent_dumps),
+        [
+            ("Number", Some("4")),
+            ("Number", Some("5")),
+            ("Crash", Some("null"))
+        ]
+    );
+    assert!(panic.recent_dumps.iter().all(|d| d.protocol == "elfo"));
+
+    // Shipped with the dump of the panic.
+    let dumps = proxy.dumps().class("panic");
+    let value = dumps.iter().next().unwrap().message_value().unwrap();
+
+    #[derive(Deserialize)]
+    struct Dumped {
+        recent_dumps: Vec<DumpedMessage>,
+    }
+
+    #[derive(Deserialize)]
+    struct DumpedMessage {
+        name: String,
+    }
+
+    let dumped = value.clone().deserialize_into::<Dumped>().unwrap();
+    let names = dumped.recent_dumps.iter().map(|d| &d.name[..]);
+    assert_eq!(names.collect::<Vec<_>>(), ["Number", "Number", "Crash"]);
+}
+
+#[tokio::test]
+async fn disabled_by_default() {
+    let mut proxy = elfo::test::proxy(testee(), AnyConfig::default()).await;
+    proxy.send(Number(1)).await;
+    proxy.sync().await;
+
+    let dumps = proxy
+        .request(GetRecentDumps::new("subject".into(), "_".into()))
+        .await;
+    assert!(dumps.is_empty());
+}
@@ -0,0 +1,203 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::{toml, Value};
+
+use elfo::{config::AnyConfig, messages::Terminate, prelude::*, Addr, TerminationPolicy};
+
+#[message]
+struct Start;
+
+#[message]
+struct Step(u32);
+
+#[message]
+struct Observed(Vec<String>);
+
+// Sends `Terminate` to the actor itself, so it's placed right after
+// already queued messages.
+#[message]
+struct TerminateSelf;
+
+fn testee(policy: TerminationPolicy, limit: usize) -> Blueprint {
+    ActorGroup::new()
+        .termination_policy(policy)
+        .exec(move |mut ctx| async move {
+            let mut reporter = Addr::NULL;
+
+            while let Some(batch) = ctx.recv_many(limit).await {
+                let mut observed = Vec::new();
+
+                for envelope in batch.items {
+                    let sender = envelope.sender();
+                    msg!(match envelope {
+                        Start => {
+                            reporter = sender;
+                            observed.push("start".into());
+                        }
+                        Step(no) => observed.push(no.to_string()),
+                        TerminateSelf => {
+                            observed.push("terminate self".into());
+                            let addr = ctx.addr();
+                            ctx.try_send_to(addr, Terminate::default()).unwrap();
+                        }
+                        _ => unreachable!(),
+                    });
+                }
+
+                if let Some(envelope) = batch.interrupted_by {
+                    msg!(match envelope {
+                        Terminate => {
+                            observed.push("terminate".into());
+                            ctx.close();
+                        }
+                        _ => observed.push("system".into()),
+                    });
+                }
+
+                ctx.send_to(reporter, Observed(observed)).await.unwrap();
+            }
+
+            let _ = ctx.send_to(reporter, Observed(vec!["none".into()])).await;
+        })
+}
+
+fn config(on_terminate: &str) -> AnyConfig {
+    let on_terminate = Value::from(on_terminate);
+    AnyConfig::deserialize(toml! {
+        system.mailbox.on_terminate = on_terminate
+    })
+    .unwrap()
+}
+
+async fn observed(proxy: &mut elfo::test::Proxy) -> Vec<String> {
+    msg!(match proxy.recv().await {
+        Observed(observed) => observed,
+        _ => unreachable!(),
+    })
+}
+
+async fn start(policy: TerminationPolicy, limit: usize, on_terminate: &str) -> elfo::test::Proxy {
+    let mut proxy = elfo::test::proxy(testee(policy, limit), config(on_terminate)).await;
+    proxy.send(Start).await;
+    assert_eq!(observed(&mut proxy).await, ["start"]);
+    proxy
+}
+
+fn send_steps(proxy: &elfo::test::Proxy, steps: std::ops::Range<u32>) {
+    for no in steps {
+        proxy.try_send(Step(no)).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn batches_are_limited() {
+    let mut proxy = start(TerminationPolicy::closing(), 2, "process").await;
+
+    send_steps(&proxy, 0..5);
+    assert_eq!(observed(&mut proxy).await, ["0", "1"]);
+    assert_eq!(observed(&mut proxy).await, ["2", "3"]);
+    assert_eq!(observed(&mut proxy).await, ["4"]);
+}
+
+#[tokio::test]
+async fn process_left_messages() {
+    let mut proxy = start(TerminationPolicy::closing(), 10, "process").await;
+
+    // Before the batch is received.
+    send_steps(&proxy, 0..3);
+    proxy.try_send(Terminate::default()).unwrap();
+    assert_eq!(observed(&mut proxy).await, ["0", "1", "2"]);
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+
+    // Between batches.
+    let mut proxy = start(TerminationPolicy::closing(), 2, "process").await;
+
+    send_steps(&proxy, 0..1);
+    proxy.try_send(TerminateSelf).unwrap();
+    send_steps(&proxy, 2..4);
+    assert_eq!(observed(&mut proxy).await, ["0", "terminate self"]);
+    assert_eq!(observed(&mut proxy).await, ["2", "3"]);
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+}
+
+#[tokio::test]
+async fn stop_now() {
+    let mut proxy = start(TerminationPolicy::closing(), 10, "stop").await;
+
+    // Before the batch is received.
+    send_steps(&proxy, 0..3);
+    proxy.try_send(Terminate::default()).unwrap();
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+
+    // Between batches.
+    let mut proxy = start(TerminationPolicy::closing(), 2, "stop").await;
+
+    send_steps(&proxy, 0..1);
+    proxy.try_send(TerminateSelf).unwrap();
+    send_steps(&proxy, 2..4);
+    assert_eq!(observed(&mut proxy).await, ["0", "terminate self"]);
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+}
+
+#[tokio::test]
+async fn manual_terminate_in_order() {
+    let mut proxy = start(TerminationPolicy::manually(), 10, "process").await;
+
+    // `Terminate` is received in order and interrupts the batch.
+    send_steps(&proxy, 0..3);
+    proxy.try_send(Terminate::default()).unwrap();
+    send_steps(&proxy, 3..4);
+    assert_eq!(observed(&mut proxy).await, ["0", "1", "2", "terminate"]);
+    assert_eq!(observed(&mut proxy).await, ["3"]);
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+}
+
+#[tokio::test]
+async fn manual_terminate_is_not_hidden() {
+    let mut proxy = start(TerminationPolicy::manually(), 10, "prioritize").await;
+
+    // `Terminate` overtakes queued messages and interrupts the batch.
+    send_steps(&proxy, 0..3);
+    proxy.try_send(Terminate::default()).unwrap();
+    send_steps(&proxy, 3..4);
+    assert_eq!(observed(&mut proxy).await, ["terminate"]);
+
+    // The actor has closed the mailbox, so left messages are received.
+    assert_eq!(observed(&mut proxy).await, ["0", "1", "2", "3"]);
+    assert_eq!(observed(&mut proxy).await, ["none"]);
+}
+
+#[tokio::test]
+async fn try_recv_returns_terminate_first() {
+    let mut proxy = elfo::test::proxy(
+        ActorGroup::new()
+            .termination_policy(TerminationPolicy::manually())
+            .exec(|mut ctx| async move {
+                let envelope = ctx.recv().await.unwrap();
+                let reporter = envelope.sender();
+
+                let addr = ctx.addr();
+                ctx.try_send_to(addr, Step(0)).unwrap();
+                ctx.try_send_to(addr, Step(1)).unwrap();
+                ctx.try_send_to(addr, Terminate::default()).unwrap();
+
+                let mut observed = Vec::new();
+                while let Ok(envelope) = ctx.try_recv().await {
+                    msg!(match envelope {
+                        Terminate => observed.push("terminate".into()),
+                        Step(no) => observed.push(no.to_string()),
+                        _ => unreachable!(),
+                    });
+                }
+
+                ctx.send_to(reporter, Observed(observed)).await.unwrap();
+            }),
+        config("prioritize"),
+    )
+    .await;
+
+    proxy.send(Start).await;
+    assert_eq!(observed(&mut proxy).await, ["terminate", "0", "1"]);
+}
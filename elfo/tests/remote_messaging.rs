@@ -8,7 +8,12 @@ use tokio::sync::Notify;
 use toml::toml;
 use tracing::info;
 
-use elfo::{prelude::*, time::Interval, topology, Topology};
+use elfo::{
+    errors::ErrorKind,
+    prelude::*,
+    time::{Delay, Interval},
+    topology, Topology,
+};
 
 mod common;
 
@@ -132,3 +137,420 @@ fn simple() {
 
     sim.run().unwrap();
 }
+
+#[test]
+fn idle_close() {
+    common::setup_logger();
+
+    #[message(ret = bool)]
+    struct Ask;
+
+    #[message]
+    struct Notice;
+
+    #[message]
+    struct Probe;
+
+    // Replies whether the requester's group has been unreachable after the first request.
+    fn responder() -> Blueprint {
+        ActorGroup::new().exec(move |mut ctx| async move {
+            let mut is_probing = false;
+            let mut was_closed = false;
+
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Ask, token) => {
+                        if !is_probing {
+                            ctx.attach(Delay::new(Duration::from_secs(6), Probe));
+                            is_probing = true;
+                        }
+                        ctx.respond(token, was_closed);
+                    }
+                    // Fails without any traffic if the connection is closed.
+                    Probe => was_closed = ctx.try_send(Notice).is_err(),
+                })
+            }
+        })
+    }
+
+    fn requester(notify: Arc<Notify>) -> Blueprint {
+        ActorGroup::new().exec(move |ctx| {
+            let notify = notify.clone();
+            async move {
+                // Wait for the connection.
+                loop {
+                    if ctx.request(Ask).resolve().await.is_ok() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+
+                // The connection is closed for idleness, but reopened on demand.
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                assert!(ctx.request(Ask).resolve().await.unwrap());
+
+                notify.notify_one();
+            }
+        })
+    }
+
+    let mut sim = turmoil::Builder::new()
+        .enable_tokio_io()
+        .tick_duration(Duration::from_millis(100))
+        .simulation_duration(Duration::from_secs(60))
+        .build();
+
+    sim.host("server", || async {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let responders = topology.local("responders");
+        let requesters = topology.remote("requesters");
+
+        responders.route_to(&requesters, |_, _| topology::Outcome::Broadcast);
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(
+            &topology,
+            toml! {
+                [system.network]
+                listen = ["turmoil06://0.0.0.0"]
+                ping_interval = "1s"
+                idle_close = "2s"
+            },
+        ));
+        responders.mount(responder());
+
+        Ok(elfo::init::try_start(topology).await?)
+    });
+
+    sim.client("client", async {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let requesters = topology.local("requesters");
+        let responders = topology.remote("responders");
+
+        requesters.route_to(&responders, |_, _| topology::Outcome::Broadcast);
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(
+            &topology,
+            toml! {
+                [system.network]
+                discovery.predefined = ["turmoil06://server"]
+                ping_interval = "1s"
+                idle_close = "2s"
+            },
+        ));
+
+        let notify = Arc::new(Notify::new());
+        requesters.mount(requester(notify.clone()));
+
+        Ok(elfo::_priv::do_start(topology, false, |_, _| async move {
+            notify.notified().await;
+        })
+        .await?)
+    });
+
+    sim.run().unwrap();
+}
+
+#[test]
+fn forwarding() {
+    common::setup_logger();
+
+    #[message(ret = u32)]
+    struct Get(u32);
+
+    fn backend() -> Blueprint {
+        ActorGroup::new().exec(move |mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Get(value), token) => ctx.respond(token, value * 10),
+                })
+            }
+        })
+    }
+
+    // Delegates requests to the remote backend.
+    fn gateway() -> Blueprint {
+        ActorGroup::new().exec(move |mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Get(value), token) => {
+                        // Undelivered requests fail, the requester retries them.
+                        let _ = ctx.forward_request(token, Get(value + 1)).await;
+                    }
+                })
+            }
+        })
+    }
+
+    fn requester(notify: Arc<Notify>) -> Blueprint {
+        ActorGroup::new().exec(move |ctx| {
+            let notify = notify.clone();
+            async move {
+                // Wait for the connection.
+                let value = loop {
+                    if let Ok(value) = ctx.request(Get(4)).resolve().await {
+                        break value;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                };
+
+                assert_eq!(value, 50);
+                notify.notify_one();
+            }
+        })
+    }
+
+    let mut sim = turmoil::Builder::new()
+        .enable_tokio_io()
+        .tick_duration(Duration::from_millis(100))
+        .build();
+
+    sim.host("server", || async {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let backends = topology.local("backends");
+        let requesters = topology.remote("requesters");
+
+        // Responses go directly to requesters.
+        backends.route_to(&requesters, |_, _| topology::Outcome::Broadcast);
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(
+            &topology,
+            toml! {
+                [system.network]
+                listen = ["turmoil06://0.0.0.0"]
+            },
+        ));
+        backends.mount(backend());
+
+        Ok(elfo::init::try_start(topology).await?)
+    });
+
+    sim.client("client", async {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let requesters = topology.local("requesters");
+        let gateways = topology.local("gateways");
+        let backends = topology.remote("backends");
+
+        requesters.route_to(&gateways, |_| true);
+        gateways.route_to(&backends, |_, _| topology::Outcome::Broadcast);
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(
+            &topology,
+            toml! {
+                [system.network]
+                discovery.predefined = ["turmoil06://server"]
+            },
+        ));
+
+        let notify = Arc::new(Notify::new());
+        requesters.mount(requester(notify.clone()));
+        gateways.mount(gateway());
+
+        Ok(elfo::_priv::do_start(topology, false, |_, _| async move {
+            notify.notified().await;
+        })
+        .await?)
+    });
+
+    sim.run().unwrap();
+}
+
+// `echo -n "secret-token" | sha256sum`
+const TOKEN_HASH: &str = "930bbdc51b6aed5c2a5678fd6e28dee7a05e8a4b643cfc0b4427c3efb86c0d94";
+
+#[message(ret = u32)]
+struct GetOrder(u32);
+
+#[message(ret = u32)]
+struct GetSecret(u32);
+
+fn orders() -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (GetOrder(value), token) => ctx.respond(token, value * 10),
+            })
+        }
+    })
+}
+
+fn secrets() -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (GetSecret(_), token) => {
+                    ctx.respond(token, 0);
+                    panic!("the secret is leaked");
+                }
+            })
+        }
+    })
+}
+
+fn auth_server(sim: &mut turmoil::Sim<'_>) {
+    sim.host("server", || async {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let orders = topology.local("orders");
+        let secrets = topology.local("secrets");
+        let requesters = topology.remote("requesters");
+
+        // Responses go directly to requesters.
+        orders.route_to(&requesters, |_, _| topology::Outcome::Broadcast);
+        secrets.route_to(&requesters, |_, _| topology::Outcome::Broadcast);
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(
+            &topology,
+            toml! {
+                [system.network]
+                listen = ["turmoil06://0.0.0.0"]
+
+                [[system.network.auth.accept]]
+                token_hash = TOKEN_HASH
+                allowed_groups = ["orders"]
+            },
+        ));
+        orders.mount(self::orders());
+        secrets.mount(self::secrets());
+
+        Ok(elfo::init::try_start(topology).await?)
+    });
+}
+
+fn auth_client(
+    sim: &mut turmoil::Sim<'_>,
+    name: &str,
+    token: Option<&'static str>,
+    requester: impl FnOnce(Arc<Notify>) -> Blueprint + 'static,
+) {
+    sim.client(name, async move {
+        let topology = Topology::empty();
+        let configurers = topology.local("system.configurers").entrypoint();
+        let network = topology.local("system.network");
+        let requesters = topology.local("requesters");
+        let orders = topology.remote("orders");
+        let secrets = topology.remote("secrets");
+
+        requesters.route_to(&orders, |e, _| {
+            msg!(match e {
+                GetOrder => topology::Outcome::Broadcast,
+                _ => topology::Outcome::Discard,
+            })
+        });
+        requesters.route_to(&secrets, |e, _| {
+            msg!(match e {
+                GetSecret => topology::Outcome::Broadcast,
+                _ => topology::Outcome::Discard,
+            })
+        });
+
+        let config = match token {
+            Some(token) => toml! {
+                [system.network]
+                discovery.predefined = ["turmoil06://server"]
+                auth.token = token
+            },
+            None => toml! {
+                [system.network]
+                discovery.predefined = ["turmoil06://server"]
+            },
+        };
+
+        network.mount(elfo::batteries::network::new(&topology));
+        configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+
+        let notify = Arc::new(Notify::new());
+        requesters.mount(requester(notify.clone()));
+
+        Ok(elfo::_priv::do_start(topology, false, |_, _| async move {
+            notify.notified().await;
+        })
+        .await?)
+    });
+}
+
+#[test]
+fn auth_restricted() {
+    common::setup_logger();
+
+    fn requester(notify: Arc<Notify>) -> Blueprint {
+        ActorGroup::new().exec(move |ctx| {
+            let notify = notify.clone();
+            async move {
+                // Wait for the connection.
+                let value = loop {
+                    if let Ok(value) = ctx.request(GetOrder(4)).resolve().await {
+                        break value;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                };
+                assert_eq!(value, 40);
+
+                // Failed until the connection to the group is established.
+                let err = loop {
+                    match ctx.request(GetSecret(4)).resolve_detailed().await {
+                        Err(err) if err.kind() == ErrorKind::Failed => {}
+                        res => break res.unwrap_err(),
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                };
+                assert_eq!(err.kind(), ErrorKind::Forbidden, "{err}");
+
+                notify.notify_one();
+            }
+        })
+    }
+
+    let mut sim = turmoil::Builder::new()
+        .enable_tokio_io()
+        .tick_duration(Duration::from_millis(100))
+        .build();
+
+    auth_server(&mut sim);
+    auth_client(&mut sim, "client", Some("secret-token"), requester);
+    sim.run().unwrap();
+}
+
+#[test]
+fn auth_rejected() {
+    common::setup_logger();
+
+    fn requester(notify: Arc<Notify>) -> Blueprint {
+        ActorGroup::new().exec(move |ctx| {
+            let notify = notify.clone();
+            async move {
+                // The connection is never established.
+                for _ in 0..50 {
+                    let err = ctx.request(GetOrder(4)).resolve().await.unwrap_err();
+                    assert!(err.is_failed(), "{err:?}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+
+                notify.notify_one();
+            }
+        })
+    }
+
+    let mut sim = turmoil::Builder::new()
+        .enable_tokio_io()
+        .tick_duration(Duration::from_millis(100))
+        .build();
+
+    auth_server(&mut sim);
+    auth_client(&mut sim, "wrong", Some("wrong-token"), requester);
+    auth_client(&mut sim, "missing", None, requester);
+    sim.run().unwrap();
+}
@@ -0,0 +1,128 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    Addr, Topology,
+};
+
+#[message(ret = u32)]
+struct Slow(u32);
+
+#[message(ret = Vec<u32>)]
+struct GetHandled;
+
+#[message(ret = ())]
+struct Run;
+
+#[derive(Default)]
+struct Shared {
+    started: Notify,
+    release: Notify,
+    handled: Mutex<Vec<u32>>,
+}
+
+fn responder(shared: Arc<Shared>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let shared = shared.clone();
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Slow(n), token) => {
+                        shared.handled.lock().push(n);
+                        shared.started.notify_one();
+                        shared.release.notified().await;
+                        // A late response, which is dropped.
+                        ctx.respond(token, n);
+                    }
+                    (GetHandled, token) => {
+                        ctx.respond(token, shared.handled.lock().clone());
+                    }
+                });
+            }
+        }
+    })
+}
+
+fn requester(responder: Addr, shared: Arc<Shared>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let shared = shared.clone();
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                    (Run, token) => {
+                        let (first, second, ()) = futures::join!(
+                            ctx.request_to(responder, Slow(1)).resolve_detailed(),
+                            ctx.request_to(responder, Slow(2)).resolve_detailed(),
+                            async {
+                                // The first one is picked up, the second one is in the mailbox.
+                                shared.started.notified().await;
+
+                                let pending = ctx.pending_requests();
+                                assert_eq!(pending.len(), 2);
+                                for request in &pending {
+                                    assert_eq!(request.message_name(), "Slow");
+                                    assert_eq!(request.recipient(), Some(responder));
+                                    assert!(request.trace_id().is_some());
+                                }
+
+                                assert_eq!(ctx.cancel_requests_matching(|_| true), 2);
+                                // Already cancelled.
+                                assert!(!pending[0].cancel());
+                                shared.release.notify_one();
+                            }
+                        );
+
+                        assert_eq!(first.unwrap_err().kind(), ErrorKind::Cancelled);
+                        assert_eq!(second.unwrap_err().kind(), ErrorKind::Cancelled);
+
+                        // No leaked entries.
+                        assert!(ctx.pending_requests().is_empty());
+
+                        // Other requests work as usual.
+                        let handled = ctx.request_to(responder, GetHandled).resolve().await;
+                        assert_eq!(handled.unwrap(), vec![1]);
+                        ctx.respond(token, ());
+                    }
+                });
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn cancel_pending() {
+    let shared = Arc::new(Shared::default());
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let responder = topology.local("responder");
+    let requester = topology.local("requester").entrypoint();
+    let requester_addr = requester.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    requester.mount(self::requester(responder.addr(), shared.clone()));
+    responder.mount(self::responder(shared));
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let res = ctx.request_to(requester_addr, Run).resolve().await;
+        terminate(ctx, topology).await;
+        res
+    })
+    .await
+    .expect("cannot start")
+    .expect("requester failed");
+}
@@ -0,0 +1,180 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::RequestError,
+    messages::StartEntrypoint,
+    prelude::*,
+    scope,
+    tracing::TraceId,
+    Addr, Topology,
+};
+
+#[message(ret = (u32, TraceId))]
+struct Get {
+    value: u32,
+    ignore: bool,
+}
+
+fn gateway(handled: Arc<AtomicUsize>) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| {
+        let handled = handled.clone();
+        async move {
+            while let Some(envelope) = ctx.recv().await {
+                handled.fetch_add(1, Ordering::SeqCst);
+
+                msg!(match envelope {
+                    (Get { value, ignore }, token) => {
+                        let request = Get {
+                            value: value + 1,
+                            ignore,
+                        };
+                        ctx.forward_request(token, request).await.unwrap();
+                    }
+                });
+            }
+        }
+    })
+}
+
+fn backend() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Get { value, ignore }, token) => {
+                    if !ignore {
+                        ctx.respond(token, (value * 10, scope::trace_id()));
+                    }
+                }
+            });
+        }
+    })
+}
+
+fn requester(gateway: Addr) -> Blueprint {
+    ActorGroup::new().exec(move |mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Get { value, ignore }, token) => {
+                    let request = Get { value, ignore };
+                    let trace_id = scope::trace_id();
+                    let res = ctx.request_to(gateway, request).resolve().await.unwrap();
+                    assert_eq!(res.1, trace_id);
+                    ctx.respond(token, res);
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test]
+async fn chain() {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let requester = topology.local("requester").entrypoint();
+    let gateway = topology.local("gateway");
+    let backend = topology.local("backend");
+    let requester_addr = requester.addr();
+
+    gateway.route_to(&backend, |e| {
+        msg!(match e {
+            Get => true,
+            _ => false,
+        })
+    });
+
+    let handled = Arc::new(AtomicUsize::new(0));
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    requester.mount(self::requester(gateway.addr()));
+    gateway.mount(self::gateway(handled.clone()));
+    backend.mount(self::backend());
+
+    let capture = topology.dump_capture().clone();
+
+    let res = do_start(topology, false, move |ctx, topology| async move {
+        let res = ctx
+            .request_to(
+                requester_addr,
+                Get {
+                    value: 4,
+                    ignore: false,
+                },
+            )
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        res
+    })
+    .await
+    .expect("cannot start");
+
+    assert_eq!(res.unwrap().0, 50);
+
+    // The gateway handles only the request, the response bypasses it.
+    assert_eq!(handled.load(Ordering::SeqCst), 1);
+
+    // The delegation is dumped by both sides.
+    let forwarded = capture
+        .snapshot()
+        .into_iter()
+        .filter(|d| format!("{:?}", d.message_kind).starts_with("Forward"))
+        .map(|d| (d.meta.group.clone(), d.is_incoming))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        forwarded,
+        [("gateway".into(), false), ("backend".into(), true)]
+    );
+}
+
+#[tokio::test]
+async fn ignored() {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let gateway = topology.local("gateway");
+    let backend = topology.local("backend");
+    let gateway_addr = gateway.addr();
+
+    gateway.route_to(&backend, |e| {
+        msg!(match e {
+            Get => true,
+            _ => false,
+        })
+    });
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    gateway.mount(self::gateway(Default::default()));
+    backend.mount(self::backend());
+
+    let res = do_start(topology, false, move |ctx, topology| async move {
+        let res = ctx
+            .request_to(
+                gateway_addr,
+                Get {
+                    value: 0,
+                    ignore: true,
+                },
+            )
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        res
+    })
+    .await
+    .expect("cannot start");
+
+    assert!(matches!(res, Err(RequestError::Ignored)));
+}
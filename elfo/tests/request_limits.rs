@@ -0,0 +1,203 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    Addr, RequestLimits, Topology,
+};
+
+mod common;
+
+// Handles the request for the specified number of seconds.
+#[message(ret = u64)]
+struct Compute(u64);
+
+// Handles the request until it's cancelled.
+#[message(ret = ())]
+struct Poll;
+
+#[message(ret = Vec<u8>)]
+struct Select(usize);
+
+#[message(ret = Vec<String>)]
+struct GetHandled;
+
+fn responder() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut handled = Vec::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Compute(secs), token) => {
+                    let computing = tokio::time::sleep(Duration::from_secs(secs));
+                    if ctx.within_deadline(computing).await.is_some() {
+                        handled.push(format!("computed {secs}"));
+                        ctx.respond(token, secs);
+                    } else {
+                        handled.push(format!("cancelled {secs}"));
+                    }
+                }
+                (Poll, token) => {
+                    while !token.is_cancelled() {
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                    handled.push("stopped polling".into());
+                }
+                (Select(size), token) => ctx.respond(token, vec![0; size]),
+                (GetHandled, token) => ctx.respond(token, std::mem::take(&mut handled)),
+            });
+        }
+    })
+}
+
+fn topology() -> (Topology, Addr) {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let responders = topology.local("responders").entrypoint();
+    let responders_addr = responders.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(
+        &topology,
+        AnyConfig::default(),
+    ));
+    responders.mount(responder());
+
+    (topology, responders_addr)
+}
+
+fn time_limit(secs: u64) -> RequestLimits {
+    RequestLimits::default().max_handling_time(Duration::from_secs(secs))
+}
+
+#[tokio::test(start_paused = true)]
+async fn deadline() {
+    common::setup_logger();
+
+    let (topology, responders) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let compute = |secs, limit| {
+            let request = ctx.request_to(responders, Compute(secs));
+            let request = match limit {
+                Some(limit) => request.limits(time_limit(limit)),
+                None => request,
+            };
+            request.resolve_detailed()
+        };
+
+        // The handling is cancelled once the deadline is exceeded.
+        let started_at = Instant::now();
+        let err = compute(10, Some(5)).await.unwrap_err();
+        assert_eq!(started_at.elapsed(), Duration::from_secs(5));
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        assert!(err.is_failed());
+
+        assert_eq!(compute(2, Some(5)).await.unwrap(), 2);
+        assert_eq!(compute(10, None).await.unwrap(), 10);
+
+        let handled = ctx.request_to(responders, GetHandled).resolve().await;
+        assert_eq!(
+            handled.unwrap(),
+            ["cancelled 10", "computed 2", "computed 10"]
+        );
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn expired_requests_are_dropped() {
+    common::setup_logger();
+
+    let (topology, responders) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        // Blocks the responder for 10 seconds.
+        let busy = ctx.request_to(responders, Compute(10)).resolve();
+
+        // Expires while waiting in the mailbox.
+        let limited = async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            let request = ctx.request_to(responders, Compute(1));
+            request.limits(time_limit(5)).resolve_detailed().await
+        };
+
+        let (busy, limited) = tokio::join!(busy, limited);
+        assert_eq!(busy.unwrap(), 10);
+        assert_eq!(limited.unwrap_err().kind(), ErrorKind::LimitExceeded);
+
+        let handled = ctx.request_to(responders, GetHandled).resolve().await;
+        assert_eq!(handled.unwrap(), ["computed 10"]);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn cancellation_is_advisory() {
+    common::setup_logger();
+
+    let (topology, responders) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        // The deadline is exceeded.
+        let request = ctx.request_to(responders, Poll).limits(time_limit(5));
+        let err = request.resolve_detailed().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+
+        // The request is cancelled by the requester.
+        let polling = ctx.request_to(responders, Poll).resolve_detailed();
+        let cancelling = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            for request in ctx.pending_requests() {
+                assert!(request.cancel());
+            }
+        };
+        let (res, _) = tokio::join!(polling, cancelling);
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Cancelled);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let handled = ctx.request_to(responders, GetHandled).resolve().await;
+        assert_eq!(handled.unwrap(), ["stopped polling", "stopped polling"]);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn oversized_responses_are_rejected() {
+    common::setup_logger();
+
+    let (topology, responders) = topology();
+
+    do_start(topology, false, |ctx, topology| async move {
+        let limits = RequestLimits::default().max_response_size(32);
+
+        let request = ctx.request_to(responders, Select(16)).limits(limits);
+        assert_eq!(request.resolve().await.unwrap(), vec![0; 16]);
+
+        let request = ctx.request_to(responders, Select(64)).limits(limits);
+        let err = request.resolve_detailed().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
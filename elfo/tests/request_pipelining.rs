@@ -0,0 +1,225 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use tokio::time;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer,
+    config::AnyConfig,
+    errors::ErrorKind,
+    messages::StartEntrypoint,
+    prelude::*,
+    Addr, RequestLimits, ResponseToken, Topology,
+};
+
+#[message(ret = u32)]
+struct Work(u32);
+
+// Responds to held requests with the specified numbers.
+#[message(ret = ())]
+struct Release(Vec<u32>);
+
+#[message(ret = usize)]
+struct Held;
+
+// Holds requests until they're released, drops `Work(0)` without responding.
+fn backend() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut held = Vec::<(u32, ResponseToken<Work>)>::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Work(n), token) => {
+                    if n != 0 {
+                        held.push((n, token));
+                    }
+                }
+                (Release(numbers), token) => {
+                    for n in numbers {
+                        let index = held.iter().position(|(m, _)| *m == n).unwrap();
+                        let (n, token) = held.remove(index);
+                        ctx.respond(token, n * 10);
+                    }
+                    ctx.respond(token, ());
+                }
+                (Held, token) => ctx.respond(token, held.len()),
+            });
+        }
+    })
+}
+
+fn starter() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                _ => {}
+            });
+        }
+    })
+}
+
+async fn run<F>(f: impl FnOnce(Context, Addr) -> F)
+where
+    F: std::future::Future<Output = Context> + Send,
+{
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let starter = topology.local("starter").entrypoint();
+    let backend = topology.local("backend");
+    let backend_addr = backend.addr();
+
+    configurers.mount(configurer::fixture(&topology, AnyConfig::default()));
+    starter.mount(self::starter());
+    backend.mount(self::backend());
+
+    do_start(topology, false, |ctx, topology| async move {
+        let ctx = f(ctx, backend_addr).await;
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+async fn is_blocked(f: impl std::future::Future) -> bool {
+    time::timeout(Duration::from_millis(50), f).await.is_err()
+}
+
+async fn release(ctx: &Context, backend: Addr, numbers: &[u32]) {
+    let request = ctx.request_to(backend, Release(numbers.to_vec()));
+    request.resolve().await.unwrap();
+}
+
+#[tokio::test]
+async fn window_limit_and_unordered() {
+    run(|ctx, backend| async move {
+        let mut pipeline = ctx.pipeline(backend).max_in_flight(3);
+
+        for n in 1..=3 {
+            pipeline.push(Work(n)).await;
+        }
+        assert_eq!(pipeline.in_flight(), 3);
+
+        // The window is full.
+        assert!(is_blocked(pipeline.push(Work(4))).await);
+        let held = ctx.request_to(backend, Held).resolve().await.unwrap();
+        assert_eq!(held, 3);
+
+        // Responses are yielded as they arrive.
+        release(&ctx, backend, &[3]).await;
+        assert_eq!(pipeline.next_response().await.unwrap().unwrap(), 30);
+        assert_eq!(pipeline.in_flight(), 2);
+
+        pipeline.push(Work(4)).await;
+        assert_eq!(pipeline.in_flight(), 3);
+
+        release(&ctx, backend, &[4, 1, 2]).await;
+        assert_eq!(pipeline.next_response().await.unwrap().unwrap(), 10);
+        assert_eq!(pipeline.next_response().await.unwrap().unwrap(), 20);
+        assert_eq!(pipeline.next_response().await.unwrap().unwrap(), 40);
+        assert!(pipeline.next_response().await.is_none());
+        assert!(pipeline.is_empty());
+
+        drop(pipeline);
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn ordered() {
+    run(|ctx, backend| async move {
+        let mut pipeline = ctx.pipeline(backend).max_in_flight(4).ordered();
+
+        for n in 1..=3 {
+            pipeline.push(Work(n)).await;
+        }
+
+        // The response to the first request is awaited.
+        release(&ctx, backend, &[3, 2]).await;
+        assert!(is_blocked(pipeline.next_response()).await);
+        assert_eq!(pipeline.in_flight(), 1);
+
+        release(&ctx, backend, &[1]).await;
+        for expected in [10, 20, 30] {
+            assert_eq!(pipeline.next_response().await.unwrap().unwrap(), expected);
+        }
+        assert!(pipeline.next_response().await.is_none());
+        drop(pipeline);
+
+        // The reorder buffer is limited.
+        let mut pipeline = ctx.pipeline(backend).max_in_flight(4).reorder_capacity(1);
+        pipeline.push(Work(5)).await;
+        pipeline.push(Work(6)).await;
+        release(&ctx, backend, &[6]).await;
+        assert!(is_blocked(pipeline.push(Work(7))).await);
+
+        release(&ctx, backend, &[5]).await;
+        pipeline.push(Work(7)).await;
+        release(&ctx, backend, &[7]).await;
+        for expected in [50, 60, 70] {
+            assert_eq!(pipeline.next_response().await.unwrap().unwrap(), expected);
+        }
+
+        drop(pipeline);
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn errors() {
+    run(|ctx, backend| async move {
+        let limits = RequestLimits::default().max_handling_time(Duration::from_millis(100));
+        let mut pipeline = ctx.pipeline(backend).limits(limits).ordered();
+
+        pipeline.push(Work(1)).await; // timed out
+        pipeline.push(Work(0)).await; // ignored
+        pipeline.push(Work(2)).await;
+        release(&ctx, backend, &[2]).await;
+
+        let err = pipeline
+            .next_response_detailed()
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        let err = pipeline.next_response().await.unwrap().unwrap_err();
+        assert!(err.is_ignored());
+        assert_eq!(pipeline.next_response().await.unwrap().unwrap(), 20);
+        drop(pipeline);
+
+        // Unavailable recipients.
+        let mut pipeline = ctx.pipeline(Addr::NULL);
+        pipeline.push(Work(3)).await;
+        assert_eq!(pipeline.in_flight(), 0);
+        assert!(pipeline.next_response().await.unwrap().is_err());
+
+        drop(pipeline);
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn cancelled_on_drop() {
+    run(|ctx, backend| async move {
+        let mut pipeline = ctx.pipeline(backend);
+        pipeline.push(Work(1)).await;
+        pipeline.push(Work(2)).await;
+        assert_eq!(ctx.pending_requests().len(), 2);
+        let held = ctx.request_to(backend, Held).resolve().await.unwrap();
+        assert_eq!(held, 2);
+
+        drop(pipeline);
+        assert!(ctx.pending_requests().is_empty());
+
+        // Late responses are discarded.
+        release(&ctx, backend, &[1, 2]).await;
+        ctx
+    })
+    .await;
+}
@@ -0,0 +1,111 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "tokio-metrics"))]
+
+use std::{net::TcpListener, time::Duration};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+            });
+        }
+    })
+}
+
+async fn scrape(listen: &str) -> String {
+    let mut stream = TcpStream::connect(listen).await.unwrap();
+    let request = format!("GET /metrics HTTP/1.1\r\nHost: {listen}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+// Sums values of all series of the metric.
+fn sum(output: &str, name: &str) -> Option<f64> {
+    output
+        .lines()
+        .filter(|line| line.starts_with(&format!("{name}{{")))
+        .map(|line| line.rsplit(' ').next().unwrap().parse::<f64>().unwrap())
+        .reduce(|a, b| a + b)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sampled() {
+    let listen = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+
+    let listen_str = listen.as_str();
+    let config = AnyConfig::deserialize(toml! {
+        [system.telemeters]
+        sink = "OpenMetrics"
+        listen = listen_str
+        runtime = { enabled = true, sampling_interval = "50ms" }
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let telemeters = topology.local("system.telemeters");
+    let subject = topology.local("subject").entrypoint();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    telemeters.mount(elfo::batteries::telemeter::init());
+    subject.mount(self::subject());
+
+    let (first, second) = do_start(topology, false, move |ctx, topology| async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let first = scrape(&listen).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let second = scrape(&listen).await;
+        terminate(ctx, topology).await;
+        (first, second)
+    })
+    .await
+    .expect("cannot start");
+
+    for name in [
+        "elfo_tokio_workers",
+        "elfo_tokio_alive_tasks",
+        "elfo_tokio_global_queue_depth",
+        "elfo_tokio_worker_busy_ratio",
+        "elfo_tokio_worker_parks_total",
+        "elfo_actors",
+        "elfo_mailbox_occupancy",
+        "elfo_scheduled_timers",
+    ] {
+        assert!(sum(&first, name).is_some(), "{name} is missing:\n{first}");
+    }
+
+    assert_eq!(sum(&second, "elfo_tokio_workers"), Some(2.));
+    assert!(second
+        .contains(r#"elfo_tokio_worker_parks_total{actor_group="system.telemeters",worker="1"}"#));
+
+    // Configurers, telemeters (and the sampler) and the subject at least.
+    assert!(sum(&second, "elfo_actors").unwrap() >= 4.);
+    // Compaction, sampling and so on.
+    assert!(sum(&second, "elfo_scheduled_timers").unwrap() >= 2.);
+
+    // Updated across samples.
+    let parks = |output| sum(output, "elfo_tokio_worker_parks_total").unwrap();
+    assert!(parks(&second) > parks(&first));
+}
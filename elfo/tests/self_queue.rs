@@ -0,0 +1,143 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use elfo::{
+    config::AnyConfig, messages::Terminate, prelude::*, Addr, SelfQueue, SelfQueuePriority,
+    TerminationPolicy,
+};
+
+#[message]
+struct Start;
+
+#[message]
+struct Step(u32);
+
+#[message]
+struct Received(Vec<String>);
+
+#[message]
+struct Close;
+
+// Fills the mailbox with `Terminate, Step(0), Step(3)` and the queue with
+// `Step(1), Step(2)`, because `Step(3)` doesn't fit into the queue.
+fn testee(priority: SelfQueuePriority) -> Blueprint {
+    ActorGroup::new()
+        .termination_policy(TerminationPolicy::manually())
+        .self_queue(SelfQueue::new(2).priority(priority))
+        .exec(|mut ctx| async move {
+            let mut reporter = Addr::NULL;
+            let mut received = Vec::new();
+
+            while let Some(envelope) = ctx.recv().await {
+                let sender = envelope.sender();
+
+                msg!(match envelope {
+                    Start => {
+                        reporter = sender;
+                        let addr = ctx.addr();
+                        ctx.try_send_to(addr, Terminate::default()).unwrap();
+                        ctx.try_send_to(addr, Step(0)).unwrap();
+                        ctx.send_to_self(Step(1)).unwrap();
+                        ctx.send_to_self(Step(2)).unwrap();
+                        ctx.send_to_self(Step(3)).unwrap();
+                    }
+                    Terminate => received.push("terminate".into()),
+                    Step(no) => received.push(no.to_string()),
+                    Close => {
+                        ctx.send_to_self(Step(4)).unwrap();
+                        ctx.close();
+                        // Rejected once the mailbox is closed.
+                        ctx.recv().await.unwrap();
+                        assert!(ctx.send_to_self(Step(5)).is_err());
+                    }
+                });
+
+                if received.len() == 5 {
+                    let report = Received(std::mem::take(&mut received));
+                    ctx.send_to(reporter, report).await.unwrap();
+                }
+            }
+        })
+}
+
+async fn received(proxy: &mut elfo::test::Proxy) -> Vec<String> {
+    msg!(match proxy.recv().await {
+        Received(received) => received,
+        _ => unreachable!(),
+    })
+}
+
+#[tokio::test]
+async fn before_mailbox() {
+    let mut proxy = elfo::test::proxy(
+        testee(SelfQueuePriority::BeforeMailbox),
+        AnyConfig::default(),
+    )
+    .await;
+
+    proxy.send(Start).await;
+    assert_eq!(
+        received(&mut proxy).await,
+        ["terminate", "1", "2", "0", "3"]
+    );
+}
+
+#[tokio::test]
+async fn after_mailbox() {
+    let mut proxy = elfo::test::proxy(
+        testee(SelfQueuePriority::AfterMailbox),
+        AnyConfig::default(),
+    )
+    .await;
+
+    proxy.send(Start).await;
+    assert_eq!(
+        received(&mut proxy).await,
+        ["terminate", "0", "3", "1", "2"]
+    );
+}
+
+#[cfg(feature = "dumping")]
+#[tokio::test]
+async fn closing() {
+    let mut proxy = elfo::test::proxy(
+        testee(SelfQueuePriority::BeforeMailbox),
+        AnyConfig::default(),
+    )
+    .await;
+
+    proxy.send(Start).await;
+    received(&mut proxy).await;
+
+    // Messages queued before closing are still received.
+    proxy.send(Close).await;
+    proxy.finished().await;
+    elfo::test::assert_dumped!(proxy, direction = In, count = 1, message = Step(4));
+    elfo::test::assert_dumped!(proxy, direction = In, count = 0, message = Step(5));
+}
+
+#[cfg(feature = "dumping")]
+#[tokio::test]
+async fn dumping() {
+    let mut proxy = elfo::test::proxy(
+        testee(SelfQueuePriority::BeforeMailbox),
+        AnyConfig::default(),
+    )
+    .await;
+
+    proxy.send(Start).await;
+    received(&mut proxy).await;
+
+    // Both directions are dumped with the same trace id.
+    let dumps = proxy
+        .dumps()
+        .filter(|dump| dump.message::<Step>().is_some_and(|s| s.0 == 1));
+    let directions = dumps.iter().map(|d| d.direction()).collect::<Vec<_>>();
+    assert_eq!(
+        directions,
+        [elfo::test::Direction::Out, elfo::test::Direction::In]
+    );
+    assert!(dumps.iter().next().unwrap().recipient().is_some());
+    let trace_ids = dumps.iter().map(|d| d.trace_id()).collect::<Vec<_>>();
+    assert_eq!(trace_ids[0], trace_ids[1]);
+}
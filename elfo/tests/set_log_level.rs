@@ -0,0 +1,129 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{fs, time::Duration};
+
+use serde::Deserialize;
+use toml::toml;
+use tracing::{debug, info};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::logger::{LogLevel, SetLogLevel},
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message(ret = ())]
+struct Log(String);
+
+fn subject() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Log(text), token) => {
+                    debug!("debug: {text}");
+                    info!("info: {text}");
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn override_and_revert() {
+    let path = std::env::temp_dir().join(format!("elfo-set-log-level-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path_str = path.to_str().unwrap();
+
+    let config = AnyConfig::deserialize(toml! {
+        [system.loggers]
+        sink = "File"
+        path = path_str
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let loggers = topology.local("system.loggers");
+    let subject = topology.local("subject").entrypoint();
+    let loggers_addr = loggers.addr();
+    let subject_addr = subject.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    loggers.mount(elfo::batteries::logger::init());
+    subject.mount(self::subject());
+
+    let period = Duration::from_secs(600);
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let log = |text: &str| ctx.request_to(subject_addr, Log(text.into())).resolve();
+        log("before").await.unwrap();
+
+        // Debug logs are enabled for the group.
+        let request = SetLogLevel::target("subject", LogLevel::Debug).for_duration(period);
+        let prev = ctx.request_to(loggers_addr, request).resolve().await;
+        assert!(prev.unwrap().is_none());
+        log("during").await.unwrap();
+
+        // Info logs are disabled globally, but the group override has precedence.
+        let prev = ctx
+            .request_to(loggers_addr, SetLogLevel::global(LogLevel::Warn))
+            .resolve()
+            .await;
+        assert!(prev.unwrap().is_none());
+        log("still").await.unwrap();
+
+        // The group override is reverted, the global one is not.
+        tokio::time::sleep(period + Duration::from_secs(1)).await;
+        log("after").await.unwrap();
+
+        // Replaced, the previous one is returned.
+        let prev = ctx
+            .request_to(loggers_addr, SetLogLevel::global(LogLevel::Info))
+            .resolve()
+            .await;
+        assert_eq!(prev.unwrap().unwrap().level, LogLevel::Warn);
+        log("finally").await.unwrap();
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .expect("cannot start");
+
+    let logs = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    let lines = logs
+        .lines()
+        .filter_map(|line| line.split(" - ").nth(1))
+        .filter(|line| line.starts_with("debug: ") || line.starts_with("info: "))
+        .map(|line| line.split('\t').next().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        lines,
+        [
+            "info: before",
+            "debug: during",
+            "info: during",
+            "debug: still",
+            "info: still",
+            "info: finally",
+        ],
+        "{logs}"
+    );
+    assert!(
+        logs.contains("log level is overridden\tnew=subject=Debug for 10m"),
+        "{logs}"
+    );
+    // Logged according to the replaced settings.
+    assert!(
+        logs.contains("log level is overridden\tnew=*=Warn"),
+        "{logs}"
+    );
+    assert!(!logs.contains("new=*=Info"), "{logs}");
+}
@@ -0,0 +1,153 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use elfo::{
+    _priv,
+    messages::{StartEntrypoint, Terminate},
+    prelude::*,
+    TerminationPolicy, Topology,
+};
+
+mod common;
+
+type Log = Arc<Mutex<Vec<(&'static str, Duration)>>>;
+
+// Terminates after `delay` on `Terminate`, or never if `delay` is `None`,
+// but always on closing `Terminate`.
+fn sample(name: &'static str, delay: Option<Duration>, started_at: Instant, log: Log) -> Blueprint {
+    ActorGroup::new()
+        .termination_policy(TerminationPolicy::manually())
+        .exec(move |mut ctx| {
+            let log = log.clone();
+            async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                        Terminate => {
+                            if let Some(delay) = delay {
+                                tokio::time::sleep(delay).await;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    });
+                }
+
+                log.lock().push((name, started_at.elapsed()));
+            }
+        })
+}
+
+async fn run(
+    groups: &[(&'static str, Option<Duration>)],
+    order: &[&'static str],
+    wave_timeout: Option<Duration>,
+) -> Vec<(&'static str, Duration)> {
+    common::setup_logger();
+
+    let log = Log::default();
+    let started_at = Instant::now();
+    let topology = Topology::empty();
+
+    if let Some(timeout) = wave_timeout {
+        topology.set_shutdown_wave_timeout(timeout);
+    }
+
+    let locals = groups
+        .iter()
+        .map(|(name, _)| topology.local(*name).entrypoint())
+        .collect::<Vec<_>>();
+
+    topology.shutdown_order(
+        order
+            .iter()
+            .map(|name| &locals[groups.iter().position(|(n, _)| n == name).unwrap()]),
+    );
+
+    for (local, (name, delay)) in locals.into_iter().zip(groups) {
+        local.mount(sample(name, *delay, started_at, log.clone()));
+    }
+
+    _priv::do_start(topology, false, _priv::terminate)
+        .await
+        .expect("cannot start");
+
+    let log = log.lock().clone();
+    log
+}
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[tokio::test(start_paused = true)]
+async fn ordered() {
+    let log = run(
+        &[
+            ("other", Some(ms(50))),
+            ("store", Some(ms(100))),
+            ("enrich", Some(ms(200))),
+            ("ingest", Some(ms(300))),
+        ],
+        &["ingest", "enrich", "store"],
+        None,
+    )
+    .await;
+
+    let names = log.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    assert_eq!(names, ["ingest", "enrich", "store", "other"]);
+
+    // Every wave waits for the previous one.
+    let elapsed = log.iter().map(|(_, elapsed)| *elapsed).collect::<Vec<_>>();
+    assert!(elapsed[0] >= ms(300));
+    assert!(elapsed[1] >= ms(500));
+    assert!(elapsed[2] >= ms(600));
+    assert!(elapsed[3] >= ms(650));
+}
+
+#[tokio::test(start_paused = true)]
+async fn unordered() {
+    let log = run(
+        &[
+            ("a", Some(ms(300))),
+            ("b", Some(ms(100))),
+            ("c", Some(ms(200))),
+        ],
+        &[],
+        None,
+    )
+    .await;
+
+    // All groups are terminated in one wave.
+    let names = log.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    assert_eq!(names, ["b", "c", "a"]);
+    assert!(log[2].1 < ms(350));
+}
+
+#[tokio::test(start_paused = true)]
+async fn wave_timeout() {
+    let log = run(
+        &[
+            ("ingest", Some(ms(100))),
+            ("enrich", None),
+            ("store", Some(ms(100))),
+        ],
+        &["ingest", "enrich", "store"],
+        Some(Duration::from_secs(1)),
+    )
+    .await;
+
+    let names = log.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    assert_eq!(names, ["ingest", "enrich", "store"]);
+
+    // `enrich` is forcibly closed after the timeout, then `store` is terminated.
+    let elapsed = log.iter().map(|(_, elapsed)| *elapsed).collect::<Vec<_>>();
+    assert!(elapsed[1] >= ms(1100));
+    assert!(elapsed[1] < ms(1200));
+    assert!(elapsed[2] >= ms(1200));
+}
@@ -0,0 +1,107 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::panic;
+
+use elfo::{prelude::*, test::Proxy, topology::Local, Topology};
+
+const SEEDS: std::ops::Range<u64> = 0..64;
+
+#[message(ret = ())]
+struct Deposit(u32);
+
+#[message]
+struct BalanceChanged(u32);
+
+#[message(ret = u32)]
+struct GetNotified;
+
+// The ledger notifies the notifier about every change of the balance.
+fn topology(topology: &Topology, proxy: &Local<'_>) {
+    let ledger = topology.local("ledger");
+    let notifier = topology.local("notifier");
+
+    proxy.route_to(&ledger, |envelope| {
+        msg!(match envelope {
+            Deposit => true,
+            _ => false,
+        })
+    });
+    proxy.route_to(&notifier, |envelope| {
+        msg!(match envelope {
+            GetNotified => true,
+            _ => false,
+        })
+    });
+    ledger.route_to(&notifier, |envelope| {
+        msg!(match envelope {
+            BalanceChanged => true,
+            _ => false,
+        })
+    });
+
+    ledger.mount(ActorGroup::new().exec(|mut ctx| async move {
+        let mut balance = 0;
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Deposit(amount), token) => {
+                    balance += amount;
+                    ctx.send(BalanceChanged(balance)).await.unwrap();
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    }));
+
+    notifier.mount(ActorGroup::new().exec(|mut ctx| async move {
+        let mut notified = 0;
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                BalanceChanged(balance) => notified = balance,
+                (GetNotified, token) => ctx.respond(token, notified),
+            });
+        }
+    }));
+}
+
+// The bug: it's assumed that one yield is enough to propagate the deposit.
+// It's true with the default scheduling, but not for all interleavings.
+async fn racy(proxy: Proxy) {
+    proxy.send(Deposit(10)).await;
+    tokio::task::yield_now().await;
+    assert_eq!(proxy.request(GetNotified).await, 10);
+}
+
+// The fix: the ledger responds after notifying.
+async fn fixed(proxy: Proxy) {
+    proxy.request(Deposit(10)).await;
+    assert_eq!(proxy.request(GetNotified).await, 10);
+}
+
+fn passes(seed: u64) -> bool {
+    panic::catch_unwind(|| elfo::test::simulate([seed], topology, racy)).is_ok()
+}
+
+#[test]
+fn explores_interleavings() {
+    let (failing, passing): (Vec<_>, Vec<_>) = SEEDS.partition(|seed| !passes(*seed));
+    assert!(!failing.is_empty(), "the race isn't found");
+    assert!(!passing.is_empty(), "the race is always observed");
+
+    // Found interleavings are replayed exactly by seeds.
+    for _ in 0..3 {
+        assert!(!passes(failing[0]));
+        assert!(passes(passing[0]));
+    }
+
+    // The first failing seed is reported.
+    let panic = panic::catch_unwind(|| elfo::test::simulate(SEEDS, topology, racy)).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    let expected = format!("simulation failed with seed {}: ", failing[0]);
+    assert!(message.starts_with(&expected), "{message}");
+
+    // The fixed protocol survives all interleavings.
+    elfo::test::simulate(SEEDS, topology, fixed);
+}
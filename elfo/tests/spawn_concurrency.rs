@@ -0,0 +1,125 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    config::AnyConfig,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+};
+
+#[message]
+struct Spawn(u32);
+
+#[message]
+struct Spawned(u32);
+
+#[message(ret = u32)]
+struct Ping(u32);
+
+#[derive(Default)]
+struct Stats {
+    initializing: AtomicUsize,
+    max_initializing: AtomicUsize,
+    order: Mutex<Vec<u32>>,
+}
+
+fn testee(stats: Arc<Stats>) -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                Spawn(key) | Ping(key) => Outcome::Unicast(*key),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(move |mut ctx| {
+            let stats = stats.clone();
+
+            async move {
+                let key = *ctx.key();
+
+                // Expensive initialization, e.g. reading from a database.
+                let initializing = stats.initializing.fetch_add(1, Ordering::SeqCst) + 1;
+                stats
+                    .max_initializing
+                    .fetch_max(initializing, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                stats.order.lock().push(key);
+                stats.initializing.fetch_sub(1, Ordering::SeqCst);
+
+                while let Some(envelope) = ctx.recv().await {
+                    let sender = envelope.sender();
+                    msg!(match envelope {
+                        Spawn(key) => ctx.send_to(sender, Spawned(key)).await.unwrap(),
+                        (Ping(key), token) => ctx.respond(token, key),
+                    });
+                }
+            }
+        })
+}
+
+fn config(limit: usize, requests_first: bool) -> AnyConfig {
+    let limit = limit as i64;
+    AnyConfig::deserialize(toml! {
+        system.spawn_concurrency = limit
+        system.spawn_requests_first = requests_first
+    })
+    .unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn bounded_concurrency() {
+    let stats = Arc::new(Stats::default());
+    let mut proxy = elfo::test::proxy(testee(stats.clone()), config(3, false)).await;
+    proxy.set_recv_timeout(Duration::from_secs(10));
+
+    for key in 0..20 {
+        proxy.send(Spawn(key)).await;
+    }
+
+    let mut spawned = Vec::new();
+    for _ in 0..20 {
+        msg!(match proxy.recv().await {
+            Spawned(key) => spawned.push(key),
+        });
+    }
+
+    spawned.sort_unstable();
+    assert_eq!(spawned, (0..20).collect::<Vec<_>>());
+    assert_eq!(stats.max_initializing.load(Ordering::SeqCst), 3);
+    // Queued actors are started in order.
+    assert_eq!(*stats.order.lock(), (0..20).collect::<Vec<_>>());
+}
+
+#[tokio::test(start_paused = true)]
+async fn requests_first() {
+    for requests_first in [false, true] {
+        let stats = Arc::new(Stats::default());
+        let proxy = elfo::test::proxy(testee(stats.clone()), config(1, requests_first)).await;
+
+        for key in 0..3 {
+            proxy.send(Spawn(key)).await;
+        }
+        assert_eq!(proxy.request(Ping(10)).await, 10);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let expected = if requests_first {
+            [0, 10, 1, 2]
+        } else {
+            [0, 1, 2, 10]
+        };
+        assert_eq!(*stats.order.lock(), expected);
+        assert_eq!(stats.max_initializing.load(Ordering::SeqCst), 1);
+    }
+}
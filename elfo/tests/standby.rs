@@ -0,0 +1,210 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer,
+    config::AnyConfig,
+    messages::{
+        ActorTerminated, SetStandby, StandbyRole, StandbySwitched, SubscribeToLifecycleEvents,
+    },
+    prelude::*,
+    scope,
+    topology::{HealthPolicy, MessageFilter, StandbyConfig},
+    Addr, AnyMessage, Context, Message, Topology,
+};
+
+#[message]
+struct Put(u32);
+
+#[message]
+struct Crash;
+
+#[message(ret = (String, Vec<u32>))]
+struct Get;
+
+#[message(ret = Vec<(u32, bool)>)]
+struct GetPuts;
+
+// Stores `Put`s along with their passive flags.
+fn cache() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut puts = Vec::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            let is_passive = envelope.is_passive();
+
+            msg!(match envelope {
+                Put(no) => puts.push((no, is_passive)),
+                Crash => panic!("crashed"),
+                (Get, token) => {
+                    let values = puts.iter().map(|(no, _)| *no).collect();
+                    ctx.respond(token, (scope::meta().group.clone(), values));
+                }
+                (GetPuts, token) => ctx.respond(token, puts.clone()),
+            });
+        }
+    })
+}
+
+// Routes messages received from the test to the cache.
+fn client() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Get, token) => {
+                    let res = ctx.request(Get).resolve().await.unwrap();
+                    ctx.respond(token, res);
+                }
+                envelope => {
+                    let message = envelope.unpack::<AnyMessage>().unwrap().0;
+                    ctx.send(message).await.unwrap();
+                }
+            });
+        }
+    })
+}
+
+struct Groups {
+    client: Addr,
+    primary: Addr,
+    standby: Addr,
+}
+
+async fn run<F>(policy: HealthPolicy, f: impl FnOnce(Context, Groups) -> F)
+where
+    F: std::future::Future<Output = Context>,
+{
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let client = topology.local("client");
+    let primary = topology.local("cache");
+    let standby = topology.local("cache.standby");
+
+    client.route_all_to(&primary);
+    topology.standby(
+        &primary,
+        &standby,
+        StandbyConfig {
+            mirror: MessageFilter::new(|envelope| envelope.is::<Put>()),
+            failover_on: policy,
+        },
+    );
+
+    let groups = Groups {
+        client: client.addr(),
+        primary: primary.addr(),
+        standby: standby.addr(),
+    };
+
+    configurers.mount(configurer::fixture(&topology, AnyConfig::default()));
+    client.mount(self::client());
+    primary.mount(cache());
+    standby.mount(cache());
+
+    do_start(topology, false, |ctx, topology| async move {
+        let ctx = f(ctx, groups).await;
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
+
+async fn get(ctx: &Context, groups: &Groups) -> (String, Vec<u32>) {
+    ctx.request_to(groups.client, Get).resolve().await.unwrap()
+}
+
+async fn puts(ctx: &Context, group: Addr) -> Vec<(u32, bool)> {
+    ctx.request_to(group, GetPuts).resolve().await.unwrap()
+}
+
+// Skips other lifecycle events.
+async fn recv<M: Message>(ctx: &mut Context) -> M {
+    loop {
+        let envelope = tokio::time::timeout(Duration::from_secs(5), ctx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        if let Some(message) = envelope.message().downcast_ref::<M>() {
+            return message.clone();
+        }
+    }
+}
+
+#[tokio::test]
+async fn failover_on_failures() {
+    run(HealthPolicy::failures(1), |mut ctx, groups| async move {
+        ctx.send_to(groups.primary, SubscribeToLifecycleEvents::default())
+            .await
+            .unwrap();
+
+        // Mirrored to the standby, requests are handled by the primary only.
+        ctx.send_to(groups.client, Put(1)).await.unwrap();
+        ctx.send_to(groups.client, Put(2)).await.unwrap();
+        assert_eq!(get(&ctx, &groups).await, ("cache".into(), vec![1, 2]));
+        assert_eq!(puts(&ctx, groups.standby).await, [(1, true), (2, true)]);
+
+        // Not mirrored.
+        ctx.send_to(groups.client, Crash).await.unwrap();
+
+        let switched = recv::<StandbySwitched>(&mut ctx).await;
+        assert_eq!(switched.primary, "cache");
+        assert_eq!(switched.standby, "cache.standby");
+        assert_eq!(switched.active, StandbyRole::Standby);
+        assert!(!switched.forced);
+
+        // The standby is warm and active now.
+        ctx.send_to(groups.client, Put(3)).await.unwrap();
+        assert_eq!(
+            get(&ctx, &groups).await,
+            ("cache.standby".into(), vec![1, 2, 3])
+        );
+        assert_eq!(
+            puts(&ctx, groups.standby).await,
+            [(1, true), (2, true), (3, false)]
+        );
+
+        // A new actor of the primary doesn't receive anything.
+        assert_eq!(puts(&ctx, groups.primary).await, []);
+        ctx
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn forced() {
+    run(HealthPolicy::manual(), |mut ctx, groups| async move {
+        ctx.send_to(groups.primary, SubscribeToLifecycleEvents::default())
+            .await
+            .unwrap();
+
+        // Failures are ignored by the manual policy.
+        ctx.send_to(groups.client, Crash).await.unwrap();
+        recv::<ActorTerminated>(&mut ctx).await;
+        assert_eq!(get(&ctx, &groups).await.0, "cache");
+
+        let force = SetStandby::new("cache".into(), Some(StandbyRole::Standby));
+        ctx.send_to(groups.primary, force).await.unwrap();
+        let switched = recv::<StandbySwitched>(&mut ctx).await;
+        assert_eq!(switched.active, StandbyRole::Standby);
+        assert!(switched.forced);
+        assert_eq!(get(&ctx, &groups).await.0, "cache.standby");
+
+        // The standby forwards it to the primary.
+        let revert = SetStandby::new("cache".into(), None);
+        ctx.send_to(groups.standby, revert).await.unwrap();
+        let switched = recv::<StandbySwitched>(&mut ctx).await;
+        assert_eq!(switched.active, StandbyRole::Primary);
+        assert_eq!(get(&ctx, &groups).await.0, "cache");
+
+        // Unknown relationships are ignored.
+        let unknown = SetStandby::new("unknown".into(), Some(StandbyRole::Standby));
+        ctx.send_to(groups.primary, unknown).await.unwrap();
+        assert_eq!(get(&ctx, &groups).await.0, "cache");
+        ctx
+    })
+    .await;
+}
@@ -0,0 +1,101 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    prelude::*,
+    Topology,
+};
+
+mod common;
+
+fn topology(config: AnyConfig) -> Topology {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let producers = topology.local("producers");
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    producers.mount(
+        ActorGroup::new().exec(|mut ctx| async move { while ctx.recv().await.is_some() {} }),
+    );
+
+    topology
+}
+
+async fn start(config: AnyConfig) -> Vec<(String, String)> {
+    match do_start(topology(config), false, terminate).await {
+        Ok(()) => Vec::new(),
+        Err(error) => error
+            .errors
+            .into_iter()
+            .map(|error| (error.group, error.reason))
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn misspelled_section() {
+    common::setup_logger();
+
+    let config = toml! {
+        [common]
+        system.strict_config = true
+
+        [producrs]
+        rate = 10
+    };
+
+    let errors = start(AnyConfig::deserialize(config.clone()).unwrap()).await;
+    assert_eq!(
+        errors,
+        [(
+            "system.configurers".into(),
+            "unknown section `[producrs]`, did you mean `[producers]`?".into()
+        )]
+    );
+
+    // Ignored by default.
+    let mut config = config;
+    config.remove("common");
+    assert_eq!(start(AnyConfig::deserialize(config).unwrap()).await, []);
+}
+
+#[tokio::test]
+async fn misspelled_system_key() {
+    common::setup_logger();
+
+    let config = AnyConfig::deserialize(toml! {
+        [common]
+        system.strict_config = true
+
+        [producers]
+        rate = 10
+        system.loging.max_level = "Warn"
+        system.mailbox.capacit = 100
+        unknown_user_key = 1
+    })
+    .unwrap();
+
+    let errors = start(config).await;
+    assert_eq!(errors.len(), 1);
+
+    // User keys are governed by the group's config, so ignored here.
+    let (group, reason) = &errors[0];
+    assert_eq!(group, "producers");
+    assert!(reason.starts_with("system section: "), "{reason}");
+    assert!(
+        reason.contains("unknown key `system.loging`, did you mean `system.logging`?"),
+        "{reason}"
+    );
+    assert!(
+        reason.contains(
+            "unknown key `system.mailbox.capacit`, did you mean `system.mailbox.capacity`?"
+        ),
+        "{reason}"
+    );
+    assert!(!reason.contains("unknown_user_key"), "{reason}");
+}
@@ -0,0 +1,100 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "elfo-telemeter"))]
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::telemeter::protocol::GetThroughputHistory,
+    config::AnyConfig,
+    messages::StartEntrypoint,
+    prelude::*,
+    Topology,
+};
+
+#[message]
+struct Work;
+
+fn worker() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                Work => {}
+            });
+        }
+    })
+}
+
+// The recorder is global, so it's the only test with the telemeter here.
+#[tokio::test(start_paused = true)]
+async fn series_shape() {
+    let config = AnyConfig::deserialize(toml! {
+        [system.telemeters]
+        sink = "OpenMetrics"
+        listen = "127.0.0.1:0"
+        throughput = { enabled = true, bucket = "1m", buckets = 4 }
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let telemeters = topology.local("system.telemeters");
+    let workers = topology.local("workers").entrypoint();
+    let telemeters_addr = telemeters.addr();
+    let workers_addr = workers.addr();
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    telemeters.mount(elfo::batteries::telemeter::init());
+    workers.mount(worker());
+
+    do_start(topology, false, move |ctx, topology| async move {
+        let burst = |count| {
+            let ctx = &ctx;
+            async move {
+                for _ in 0..count {
+                    ctx.send_to(workers_addr, Work).await.unwrap();
+                }
+            }
+        };
+
+        let history = |window| {
+            let request = GetThroughputHistory::new("workers", window);
+            let ctx = &ctx;
+            async move {
+                let history = ctx.request_to(telemeters_addr, request).resolve().await;
+                let history = history.unwrap();
+                assert_eq!(history.bucket, Duration::from_secs(60));
+                history
+                    .series
+                    .into_iter()
+                    .find(|s| s.message == "Work")
+                    .map(|s| s.counts)
+            }
+        };
+
+        // Bursts cross boundaries of buckets, crossing compaction ticks too.
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        burst(3).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        burst(5).await;
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        burst(2).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let hour = Duration::from_secs(3600);
+        assert_eq!(history(hour).await.unwrap(), [3, 5, 0, 2]);
+        assert_eq!(history(Duration::from_secs(90)).await.unwrap(), [0, 2]);
+
+        // Old buckets are rotated out.
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert_eq!(history(hour).await.unwrap(), [0, 2, 0, 0]);
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,125 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    config::AnyConfig,
+    messages::{GetTopologyGraph, StartEntrypoint},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    Topology,
+};
+
+#[message]
+struct Event;
+
+#[message]
+struct Other;
+
+#[derive(Debug, Deserialize)]
+struct Config {}
+
+fn producer() -> Blueprint {
+    ActorGroup::new()
+        .config::<Config>()
+        .mailbox_capacity(100)
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (StartEntrypoint { .. }, token) => {
+                        ctx.send(Event).await.unwrap();
+                        ctx.send(Event).await.unwrap();
+                        ctx.send(Other).await.unwrap();
+                        ctx.respond(token, Ok(()));
+                    }
+                });
+            }
+        })
+}
+
+fn consumer() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|_| Outcome::Unicast(0)))
+        .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} })
+}
+
+fn topology() -> Topology {
+    let config = AnyConfig::deserialize(toml! {
+        [producer]
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let producer = topology.local("producer").entrypoint();
+    let consumer = topology.local("consumer");
+    let logger = topology.local("logger");
+
+    producer.route_to(&consumer, |e| {
+        msg!(match e {
+            Event => true,
+            _ => false,
+        })
+    });
+    // Not declared, but observed.
+    producer.route_all_to(&logger);
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    producer.mount(self::producer());
+    consumer.mount(self::consumer());
+    logger.mount(ActorGroup::new().exec(|_| async {}));
+    topology
+}
+
+const GROUPS: &str = r#"digraph topology {
+    "consumer" [label="consumer\nrouter: MapRouter\nmailbox: config", shape=box];
+    "logger" [label="logger\nrouter: Singleton\nmailbox: config", shape=box];
+    "producer" [label="producer\nconfig: topology_graph::Config\nrouter: Singleton\nmailbox: 100", shape=box, peripheries=2];
+    "system.configurers" [label="system.configurers\nconfig: elfo_configurer::config::Config\nrouter: Singleton\nmailbox: config", shape=box, peripheries=2];
+"#;
+
+#[test]
+fn declared() {
+    let topology = topology();
+
+    let expected = format!("{GROUPS}    \"producer\" -> \"consumer\";\n}}\n");
+    assert_eq!(topology.to_dot(), expected);
+
+    let json = topology.to_json();
+    assert!(json.contains(r#""config": "topology_graph::Config""#));
+    assert!(json.contains(r#""mailbox_capacity": 100"#));
+    assert!(json.contains(r#""is_declared": true"#));
+}
+
+#[tokio::test]
+async fn observed() {
+    let topology = topology();
+    topology.set_record_edges(true);
+    let configurers = topology
+        .locals()
+        .find(|g| g.name == "system.configurers")
+        .unwrap()
+        .addr;
+
+    let graph = do_start(topology, false, move |ctx, topology| async move {
+        let graph = ctx
+            .request_to(configurers, GetTopologyGraph::default())
+            .resolve()
+            .await;
+        terminate(ctx, topology).await;
+        graph
+    })
+    .await
+    .expect("cannot start")
+    .expect("no graph");
+
+    let expected = format!(
+        "{GROUPS}{}{}}}\n",
+        "    \"producer\" -> \"consumer\" [label=\"Event (2)\"];\n",
+        "    \"producer\" -> \"logger\" [label=\"Event (2)\\nOther (1)\", style=dotted];\n",
+    );
+    assert_eq!(graph.to_dot(), expected);
+}
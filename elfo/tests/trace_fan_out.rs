@@ -0,0 +1,100 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use toml::toml;
+
+use elfo::{
+    errors::ErrorKind,
+    prelude::*,
+    routers::{MapRouter, Outcome},
+    scope,
+    tracing::TraceId,
+};
+
+#[message]
+struct Ping(u32);
+
+#[message]
+struct Pong(u32);
+
+#[message]
+struct Stopped {
+    hops: u32,
+    is_budget_exceeded: bool,
+}
+
+// Two actors bouncing messages to each other forever, a recursive fan-out bug.
+fn ping_pong() -> Blueprint {
+    ActorGroup::new()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                Ping => Outcome::Unicast(0),
+                Pong => Outcome::Unicast(1),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(|mut ctx: Context<(), u32>| async move {
+            while let Some(envelope) = ctx.recv().await {
+                let (hops, res) = msg!(match envelope {
+                    Ping(n) => (
+                        n,
+                        ctx.detailed()
+                            .send_to(ctx.group(), Pong(n + 1))
+                            .await
+                            .map_err(|e| e.kind())
+                    ),
+                    Pong(n) => (
+                        n,
+                        ctx.detailed()
+                            .send_to(ctx.group(), Ping(n + 1))
+                            .await
+                            .map_err(|e| e.kind())
+                    ),
+                    _ => continue,
+                });
+
+                let Err(kind) = res else { continue };
+
+                // Report in a new trace, the stopped one has no budget left.
+                scope::set_trace_id(TraceId::generate());
+                let is_budget_exceeded = kind == ErrorKind::TraceBudgetExceeded;
+                ctx.send(Stopped {
+                    hops,
+                    is_budget_exceeded,
+                })
+                .await
+                .unwrap();
+            }
+        })
+}
+
+#[tokio::test]
+async fn hard_limit_stops_storm() {
+    let config = toml! {
+        [system.tracing.fan_out]
+        soft_limit = 50
+        hard_limit = 100
+    };
+
+    let mut proxy = elfo::test::proxy(ping_pong(), config).await;
+
+    let trace_id = TraceId::try_from(0x5eed).unwrap();
+    let ping = elfo::test::envelope(Ping(0))
+        .with_trace_id(trace_id)
+        .build();
+    proxy.send_raw(ping).await;
+
+    let stopped: Stopped = elfo::test::extract_message(proxy.recv().await);
+    assert!(stopped.is_budget_exceeded);
+    assert_eq!(stopped.hops, 100);
+
+    let top = elfo::tracing::top_traces(1);
+    assert_eq!(top[0].trace_id, trace_id);
+    assert_eq!(top[0].messages, 101);
+    assert_eq!(top[0].groups, [("subject".into(), 101)]);
+
+    // Other traces aren't affected.
+    proxy.send(Ping(0)).await;
+    let stopped: Stopped = elfo::test::extract_message(proxy.recv().await);
+    assert_eq!(stopped.hops, 100);
+}
@@ -0,0 +1,150 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{_priv::do_start, config::AnyConfig, prelude::*, tracing::TraceId, Topology};
+
+mod common;
+
+const TRACES: u32 = 1000;
+
+#[message(ret = ())]
+struct Run {
+    forced: Option<u32>,
+}
+
+#[message]
+struct Job(u32);
+
+#[message]
+struct Done(u32);
+
+fn front() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (Run { forced }, token) => {
+                    for i in 0..TRACES {
+                        // Every job is processed in its own trace.
+                        elfo::scope::set_trace_id(TraceId::generate());
+                        if forced == Some(i) {
+                            ctx.force_sampling();
+                        }
+                        ctx.send(Job(i)).await.unwrap();
+
+                        let envelope = ctx.recv().await.unwrap();
+                        assert!(envelope.is::<Done>());
+                    }
+
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+fn back() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                Job(i) => ctx.send(Done(i)).await.unwrap(),
+            });
+        }
+    })
+}
+
+/// Runs all traces and returns names of captured messages grouped by traces.
+async fn run(rate: f64, forced: Option<u32>) -> HashMap<TraceId, Vec<(String, String)>> {
+    common::setup_logger();
+
+    let config = AnyConfig::deserialize(toml! {
+        [front.system.dumping]
+        trace_sample_rate = rate
+
+        [back.system.dumping]
+        trace_sample_rate = rate
+    })
+    .unwrap();
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let front = topology.local("front");
+    let front_addr = front.addr();
+    let back = topology.local("back");
+
+    front.route_to(&back, |e| e.is::<Job>());
+    back.route_to(&front, |e| e.is::<Done>());
+
+    configurers.mount(elfo::batteries::configurer::fixture(&topology, config));
+    front.mount(self::front());
+    back.mount(self::back());
+
+    let capture = topology.dump_capture().clone();
+
+    do_start(topology, false, move |ctx, _| async move {
+        ctx.request_to(front_addr, Run { forced }).resolve().await
+    })
+    .await
+    .expect("cannot start")
+    .expect("front actor failed");
+
+    let mut traces = HashMap::<_, Vec<_>>::new();
+    for dump in capture.snapshot() {
+        if dump.message_name == "Job" || dump.message_name == "Done" {
+            let entry = (dump.meta.group.clone(), dump.message_name.clone());
+            traces.entry(dump.trace_id).or_default().push(entry);
+        }
+    }
+    traces
+}
+
+fn assert_complete(trace: &[(String, String)]) {
+    let mut trace = trace.to_vec();
+    trace.sort();
+
+    let expected = [
+        ("back", "Done"),
+        ("back", "Job"),
+        ("front", "Done"),
+        ("front", "Job"),
+    ];
+    let expected = expected.map(|(g, m)| (g.to_string(), m.to_string()));
+    assert_eq!(trace, expected);
+}
+
+#[tokio::test]
+async fn coherent_partial_capture() {
+    let traces = run(0.2, None).await;
+
+    // Every captured trace is captured in both groups in both directions.
+    for trace in traces.values() {
+        assert_complete(trace);
+    }
+
+    // The rate is approximately honored.
+    let sampled = traces.len() as u32;
+    assert!(
+        (TRACES / 10..TRACES * 3 / 10).contains(&sampled),
+        "sampled {sampled} of {TRACES}"
+    );
+}
+
+#[tokio::test]
+async fn all_and_none() {
+    let traces = run(1., None).await;
+    assert_eq!(traces.len() as u32, TRACES);
+    traces.values().for_each(|trace| assert_complete(trace));
+
+    assert!(run(0., None).await.is_empty());
+}
+
+#[tokio::test]
+async fn force_sampling() {
+    let traces = run(0., Some(42)).await;
+    assert_eq!(traces.len(), 1);
+    assert_complete(traces.values().next().unwrap());
+}
@@ -0,0 +1,240 @@
+#![allow(missing_docs)]
+#![cfg(feature = "test-util")]
+
+use std::{path::Path, time::Duration};
+
+use serde::Deserialize;
+use tokio::time::Instant;
+use toml::toml;
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer::{self, ReloadConfigs},
+    config::system::ttl::TtlConfig,
+    errors::ErrorKind,
+    messages::{GetConfig, StartEntrypoint},
+    prelude::*,
+    RequestLimits, Topology,
+};
+
+mod common;
+
+#[message(ret = ())]
+struct GetQuote;
+
+#[message(ret = ())]
+struct GetOther;
+
+#[message]
+struct PriceUpdate;
+
+#[message]
+struct Notice;
+
+/// Blocks the responder, so sent messages wait in its mailbox.
+#[message]
+struct Block(Duration);
+
+#[message(ret = Vec<String>)]
+struct GetReceived;
+
+/// Sends `PriceUpdate` and `Notice` to the responder.
+#[message(ret = ())]
+struct Emit;
+
+/// Sends the request and returns how long it took and whether it expired.
+#[message(ret = (Duration, bool))]
+struct Measure {
+    quote: bool,
+    explicit: Option<Duration>,
+}
+
+fn requester() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Measure { quote, explicit }, token) => {
+                    let mut limits = RequestLimits::default();
+                    if let Some(time) = explicit {
+                        limits = limits.max_handling_time(time);
+                    }
+
+                    let started_at = Instant::now();
+                    let res = if quote {
+                        ctx.request(GetQuote)
+                            .limits(limits)
+                            .resolve_detailed()
+                            .await
+                    } else {
+                        ctx.request(GetOther)
+                            .limits(limits)
+                            .resolve_detailed()
+                            .await
+                    };
+
+                    let is_expired = res.is_err_and(|err| err.kind() == ErrorKind::LimitExceeded);
+                    ctx.respond(token, (started_at.elapsed(), is_expired));
+                }
+                (Emit, token) => {
+                    ctx.send(PriceUpdate).await.unwrap();
+                    ctx.send(Notice).await.unwrap();
+                    ctx.respond(token, ());
+                }
+            });
+        }
+    })
+}
+
+/// Never responds, requests are ignored after a minute.
+fn responder() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        let mut received = Vec::new();
+
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (GetQuote, token) => drop_later(token),
+                (GetOther, token) => drop_later(token),
+                Block(time) => tokio::time::sleep(time).await,
+                PriceUpdate => received.push("PriceUpdate".to_string()),
+                Notice => received.push("Notice".to_string()),
+                (GetReceived, token) => ctx.respond(token, std::mem::take(&mut received)),
+            });
+        }
+    })
+}
+
+fn drop_later<T: Send + 'static>(token: T) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        drop(token);
+    });
+}
+
+fn write_config(path: &Path, config: &str) {
+    std::fs::write(path, format!("[requester]\n{config}")).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn defaults_and_precedence() {
+    common::setup_logger();
+
+    let pid = std::process::id();
+    let path = std::env::temp_dir().join(format!("elfo-ttl-{pid}.toml"));
+    write_config(
+        &path,
+        r#"
+        system.ttl.GetQuote = "50ms"
+        system.ttl.PriceUpdate = "50ms"
+        system.ttl."*" = "5s"
+        "#,
+    );
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let requester = topology.local("requester").entrypoint();
+    let responder = topology.local("responder");
+
+    requester.route_all_to(&responder);
+
+    let configurers_addr = configurers.addr();
+    let requester_addr = requester.addr();
+    let responder_addr = responder.addr();
+
+    configurers.mount(configurer::from_path(&topology, &path));
+    requester.mount(self::requester());
+    responder.mount(self::responder());
+
+    let path = &path;
+    do_start(topology, false, |ctx, topology| async move {
+        let measure = |quote, explicit| {
+            let res = ctx
+                .request_to(requester_addr, Measure { quote, explicit })
+                .resolve();
+            async move { res.await.unwrap() }
+        };
+
+        let ms = Duration::from_millis;
+
+        // Regular messages waiting in the mailbox for 100ms.
+        let emit = || async {
+            let block = Block(ms(100));
+            ctx.send_to(responder_addr, block).await.unwrap();
+            ctx.request_to(requester_addr, Emit)
+                .resolve()
+                .await
+                .unwrap();
+            let received = ctx.request_to(responder_addr, GetReceived).resolve();
+            received.await.unwrap()
+        };
+
+        // Per type, then the wildcard.
+        assert_eq!(measure(true, None).await, (ms(50), true));
+        assert_eq!(measure(false, None).await, (ms(5000), true));
+        assert_eq!(emit().await, ["Notice"]);
+
+        // Explicit limits always win.
+        assert_eq!(measure(true, Some(ms(1000))).await, (ms(1000), true));
+        assert_eq!(measure(false, Some(ms(10))).await, (ms(10), true));
+
+        // Reloaded without the wildcard.
+        write_config(
+            path,
+            r#"
+            system.ttl.GetQuote = "200ms"
+            system.ttl.PriceUpdate = "200ms"
+            "#,
+        );
+        ctx.request_to(configurers_addr, ReloadConfigs::default())
+            .resolve()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(measure(true, None).await, (ms(200), true));
+        assert_eq!(measure(false, None).await, (ms(60_000), false));
+        assert_eq!(emit().await, ["PriceUpdate", "Notice"]);
+
+        // Visible in the applied config.
+        let applied = ctx
+            .request_to(configurers_addr, GetConfig::new("requester".into()))
+            .resolve()
+            .await
+            .unwrap();
+
+        #[derive(Deserialize)]
+        struct Applied {
+            system: toml::Value,
+        }
+
+        let applied = Applied::deserialize(applied.config).unwrap();
+        assert_eq!(applied.system["ttl"]["GetQuote"].as_str(), Some("200ms"));
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn unknown_messages_are_rejected() {
+    let config = toml! {
+        GetQuote = "50ms"
+        UnknownMessage = "1s"
+    };
+    let err = TtlConfig::deserialize(config).unwrap_err();
+    assert!(err.to_string().contains("UnknownMessage"), "{err}");
+
+    // Allowed for configs shared by several binaries.
+    let config = toml! {
+        allow_unknown = true
+        UnknownMessage = "1s"
+        "*" = "5s"
+    };
+    let config = TtlConfig::deserialize(config).unwrap();
+    assert!(config.allow_unknown);
+    assert_eq!(config.messages.len(), 2);
+    assert_eq!(config.messages["*"], Duration::from_secs(5));
+}
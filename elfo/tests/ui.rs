@@ -4,4 +4,11 @@
 fn ui() {
     let t = trybuild::TestCases::new();
     t.compile_fail("tests/ui/*.rs");
+
+    // Listed implementations of traits depend on enabled crates.
+    if cfg!(feature = "network") {
+        t.compile_fail("tests/ui/network/*.rs");
+    } else {
+        t.compile_fail("tests/ui/local/*.rs");
+    }
 }
@@ -98,3 +98,28 @@ async fn panic_in_deserialize() {
 
     let _proxy = elfo::test::proxy(blueprint, AnyConfig::default()).await;
 }
+
+#[tokio::test]
+async fn rejected_human_readable_units() {
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        #[allow(dead_code)]
+        timeout: elfo::config::Duration,
+    }
+
+    let blueprint = ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} });
+
+    let proxy = elfo::test::proxy(blueprint, toml! { timeout = "5s" }).await;
+
+    let config = AnyConfig::deserialize(toml! { timeout = "5 parsecs" }).unwrap();
+    let reason = match proxy.request(UpdateConfig::new(config)).await {
+        Err(ConfigRejected { reason, .. }) => reason,
+        res => panic!("unexpected result: {res:?}"),
+    };
+
+    // Contains the offending string and examples.
+    assert!(reason.contains(r#""5 parsecs""#), "{reason}");
+    assert!(reason.contains(r#"e.g. "100ms" or "2h 30m""#), "{reason}");
+}
@@ -0,0 +1,192 @@
+#![allow(missing_docs)]
+#![cfg(all(feature = "test-util", feature = "dumping"))]
+
+use std::path::{Path, PathBuf};
+
+use elfo::{
+    _priv::{do_start, terminate},
+    batteries::configurer::{self, ReloadConfigs},
+    messages::StartEntrypoint,
+    prelude::*,
+    scope,
+    tracing::TraceId,
+    Addr, Topology,
+};
+
+mod common;
+
+const TRACES: usize = 1000;
+
+#[message(ret = String)]
+struct WhoAreYou;
+
+#[message(ret = String)]
+struct Ask;
+
+fn pricing() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (WhoAreYou, token) => ctx.respond(token, scope::meta().group.clone()),
+            });
+        }
+    })
+}
+
+fn client() -> Blueprint {
+    ActorGroup::new().exec(|mut ctx| async move {
+        while let Some(envelope) = ctx.recv().await {
+            msg!(match envelope {
+                (StartEntrypoint { .. }, token) => ctx.respond(token, Ok(())),
+                (Ask, token) => {
+                    // The response is routed back regardless of the target.
+                    let name = ctx.request(WhoAreYou).resolve().await.unwrap();
+                    ctx.respond(token, name);
+                }
+            });
+        }
+    })
+}
+
+fn write_config(path: &Path, weights: (u32, u32), extra: &str) {
+    let config = format!(
+        r#"
+        [routes.pricing]
+        targets = [
+            {{ group = "pricing-v1", weight = {} }},
+            {{ group = "pricing-v2", weight = {} }},
+            {extra}
+        ]
+        "#,
+        weights.0, weights.1
+    );
+
+    std::fs::write(path, config).unwrap();
+}
+
+fn topology(path: &Path) -> (Topology, Addr, Addr) {
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let client = topology.local("client").entrypoint();
+    let pricing_v1 = topology.local("pricing-v1");
+    let pricing_v2 = topology.local("pricing-v2");
+
+    let configurers_addr = configurers.addr();
+    let client_addr = client.addr();
+
+    client.route_to(&topology.weighted("pricing"), |envelope| {
+        msg!(match envelope {
+            WhoAreYou => true,
+            _ => false,
+        })
+    });
+
+    configurers.mount(configurer::from_path(&topology, path));
+    client.mount(self::client());
+    pricing_v1.mount(self::pricing());
+    pricing_v2.mount(self::pricing());
+
+    (topology, configurers_addr, client_addr)
+}
+
+fn config_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("elfo-weighted-{name}-{}.toml", std::process::id()))
+}
+
+async fn ask_all(ctx: &Context, client: Addr, trace_ids: &[TraceId]) -> Vec<String> {
+    let mut names = Vec::with_capacity(trace_ids.len());
+    for trace_id in trace_ids {
+        scope::set_trace_id(*trace_id);
+        names.push(ctx.request_to(client, Ask).resolve().await.unwrap());
+    }
+    names
+}
+
+fn count(names: &[String], group: &str) -> usize {
+    names.iter().filter(|name| *name == group).count()
+}
+
+#[tokio::test]
+async fn split_and_stickiness() {
+    common::setup_logger();
+
+    let path = config_path("split");
+    write_config(&path, (90, 10), "");
+    let (topology, _, client) = topology(&path);
+
+    do_start(topology, false, |ctx, topology| async move {
+        let trace_ids = (0..TRACES).map(|_| TraceId::generate()).collect::<Vec<_>>();
+        let names = ask_all(&ctx, client, &trace_ids).await;
+
+        // Expected 100, the standard deviation is ~9.5.
+        let v2 = count(&names, "pricing-v2");
+        assert_eq!(count(&names, "pricing-v1") + v2, TRACES);
+        assert!((60..=140).contains(&v2), "{v2}");
+
+        // The same traces are routed to the same groups.
+        assert_eq!(ask_all(&ctx, client, &trace_ids).await, names);
+
+        // The chosen group is recorded by incoming dumps.
+        let dumps = topology.dump_capture().snapshot();
+        for (trace_id, name) in trace_ids.iter().zip(&names).take(10) {
+            assert!(dumps.iter().any(|dump| dump.is_incoming
+                && dump.trace_id == *trace_id
+                && dump.message_name == "WhoAreYou"
+                && dump.meta.group == *name));
+        }
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn reloading() {
+    common::setup_logger();
+
+    let path = config_path("reloading");
+    write_config(&path, (100, 0), "");
+    let (topology, configurers, client) = topology(&path);
+
+    let path = &path;
+    do_start(topology, false, |ctx, topology| async move {
+        let trace_ids = (0..100).map(|_| TraceId::generate()).collect::<Vec<_>>();
+        let names = ask_all(&ctx, client, &trace_ids).await;
+        assert_eq!(count(&names, "pricing-v1"), trace_ids.len());
+
+        // Gradual rollout.
+        write_config(path, (50, 50), "");
+        let res = ctx.request_to(configurers, ReloadConfigs::default());
+        res.resolve().await.unwrap().unwrap();
+
+        let names = ask_all(&ctx, client, &trace_ids).await;
+        assert!(count(&names, "pricing-v1") > 0);
+        assert!(count(&names, "pricing-v2") > 0);
+
+        // Unknown groups are rejected, the previous weights are kept.
+        write_config(path, (0, 100), r#"{ group = "pricing-v3", weight = 1 }"#);
+        let res = ctx.request_to(configurers, ReloadConfigs::default());
+        let rejected = res.resolve().await.unwrap().unwrap_err();
+        assert_eq!(rejected.errors.len(), 1);
+        assert_eq!(rejected.errors[0].group, "routes");
+        assert!(rejected.errors[0].reason.contains("pricing-v3"));
+        assert_eq!(ask_all(&ctx, client, &trace_ids).await, names);
+
+        // Done.
+        write_config(path, (0, 100), "");
+        let res = ctx.request_to(configurers, ReloadConfigs::default());
+        res.resolve().await.unwrap().unwrap();
+
+        let names = ask_all(&ctx, client, &trace_ids).await;
+        assert_eq!(count(&names, "pricing-v2"), trace_ids.len());
+
+        terminate(ctx, topology).await;
+    })
+    .await
+    .unwrap();
+
+    let _ = std::fs::remove_file(path);
+}